@@ -0,0 +1,344 @@
+//! # Internal Pages
+//!
+//! This module generates the `about:` pages: `about:blank`,
+//! `about:version`, `about:memory`, `about:config` and `about:cache`.
+//! They show what the engine knows about itself, as ordinary documents
+//! the tab lays out and renders like any other page.
+//!
+//! ## Design Principles
+//!
+//! 1. **Built, Not Parsed**: Pages are DOM trees assembled in Rust, so
+//!    values from the engine never need escaping and a page cannot be
+//!    malformed.
+//! 2. **Normal Pipeline**: An internal page is a document with an opaque
+//!    origin; layout, rendering, scripts and devtools treat it like a page
+//!    from the network, so no special-case UI is needed.
+//! 3. **Generated on Load**: A page reflects the engine at the moment it
+//!    is loaded, and reloading it shows fresh values.
+
+use std::fmt;
+use std::rc::Rc;
+use dom::{Document, Node, NodeType};
+use layout::{LayerTree, LayoutBox};
+use crate::BrowserEngine;
+
+/// Stylesheet of the internal pages
+pub const ABOUT_STYLESHEET: &str = r#"
+body { font-family: sans-serif; margin: 24px; color: #202124; background-color: #ffffff; }
+h1 { font-size: 28px; margin-bottom: 16px; }
+h2 { font-size: 20px; margin-top: 24px; margin-bottom: 8px; }
+th { font-weight: bold; padding-right: 24px; }
+td { font-family: monospace; }
+li { font-family: monospace; margin-bottom: 4px; }
+"#;
+
+/// An internal page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AboutPage {
+    /// An empty document
+    Blank,
+    /// The engine's version and build
+    Version,
+    /// Memory used by the tab's document, layout and caches
+    Memory,
+    /// The tab's settings
+    Config,
+    /// Cached and prefetched responses
+    Cache,
+}
+
+impl AboutPage {
+    /// Every internal page, in the order `about:version` lists them
+    pub const ALL: [AboutPage; 5] = [AboutPage::Blank, AboutPage::Version, AboutPage::Memory, AboutPage::Config, AboutPage::Cache];
+
+    /// Get the page a URL names
+    ///
+    /// The query and fragment are ignored, and the name is matched
+    /// case-insensitively.
+    pub fn from_url(url: &str) -> Option<Self> {
+        let name = url.trim().get(6..).filter(|_| is_about_url(url))?;
+        let name = name.split(['?', '#']).next().unwrap_or_default().to_ascii_lowercase();
+        AboutPage::ALL.into_iter().find(|page| page.name() == name)
+    }
+
+    /// Get the name after `about:`
+    pub fn name(&self) -> &'static str {
+        match self {
+            AboutPage::Blank => "blank",
+            AboutPage::Version => "version",
+            AboutPage::Memory => "memory",
+            AboutPage::Config => "config",
+            AboutPage::Cache => "cache",
+        }
+    }
+
+    /// Get the title of the page
+    pub fn title(&self) -> &'static str {
+        match self {
+            AboutPage::Blank => "",
+            AboutPage::Version => "About Dubby",
+            AboutPage::Memory => "Memory",
+            AboutPage::Config => "Configuration",
+            AboutPage::Cache => "Cache",
+        }
+    }
+}
+
+impl fmt::Display for AboutPage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "about:{}", self.name())
+    }
+}
+
+/// Check whether a URL has the `about:` scheme
+pub fn is_about_url(url: &str) -> bool {
+    url.trim().get(..6).is_some_and(|scheme| scheme.eq_ignore_ascii_case("about:"))
+}
+
+/// Assembles the DOM of an internal page
+pub(crate) struct PageBuilder {
+    pub(crate) document: Document,
+    pub(crate) body: Rc<Node>,
+}
+
+impl PageBuilder {
+    /// Start an internal page with its title as the heading
+    fn new(page: AboutPage) -> Self {
+        Self::with_title(page.title())
+    }
+
+    /// Start a page with `title` as the heading, or no heading if it is
+    /// empty
+    pub(crate) fn with_title(title: &str) -> Self {
+        let document = Document::new();
+        let html = document.create_element("html");
+        let head = document.create_element("head");
+        let body = document.create_element("body");
+        document.root.append_child(&html);
+        html.append_child(&head);
+        html.append_child(&body);
+        document.record_landmark(&html);
+        document.record_landmark(&head);
+        document.record_landmark(&body);
+        let builder = PageBuilder { document, body };
+        if !title.is_empty() {
+            head.append_child(&builder.text_element("title", title));
+            builder.body.append_child(&builder.text_element("h1", title));
+        }
+        builder
+    }
+
+    /// Create an element holding only `text`
+    pub(crate) fn text_element(&self, tag: &str, text: &str) -> Rc<Node> {
+        let element = self.document.create_element(tag);
+        element.append_child(&self.document.create_text_node(text));
+        element
+    }
+
+    /// Add a section heading
+    pub(crate) fn heading(&self, text: &str) {
+        self.body.append_child(&self.text_element("h2", text));
+    }
+
+    /// Add a paragraph
+    pub(crate) fn paragraph(&self, text: &str) {
+        self.body.append_child(&self.text_element("p", text));
+    }
+
+    /// Add a table of names and values
+    pub(crate) fn table(&self, id: &str, rows: &[(&str, String)]) {
+        let table = self.document.create_element("table");
+        table.set_attribute("id", id);
+        for (name, value) in rows {
+            let row = self.document.create_element("tr");
+            row.append_child(&self.text_element("th", name));
+            row.append_child(&self.text_element("td", value));
+            table.append_child(&row);
+        }
+        self.body.append_child(&table);
+    }
+
+    /// Add a list, or `empty` when there is nothing to list
+    pub(crate) fn list(&self, items: &[String], empty: &str) {
+        if items.is_empty() {
+            self.paragraph(empty);
+            return;
+        }
+        let list = self.document.create_element("ul");
+        for item in items {
+            list.append_child(&self.text_element("li", item));
+        }
+        self.body.append_child(&list);
+    }
+
+    pub(crate) fn finish(self) -> Document {
+        self.document
+    }
+}
+
+/// Generate an internal page from the state of `engine`
+pub fn build_page(page: AboutPage, engine: &BrowserEngine) -> Document {
+    let builder = PageBuilder::new(page);
+    match page {
+        AboutPage::Blank => {}
+        AboutPage::Version => version_page(&builder),
+        AboutPage::Memory => memory_page(&builder, engine),
+        AboutPage::Config => config_page(&builder, engine),
+        AboutPage::Cache => cache_page(&builder, engine),
+    }
+    let document = builder.finish();
+    document.set_url(&page.to_string());
+    document
+}
+
+fn version_page(builder: &PageBuilder) {
+    builder.table("version", &[
+        ("Version", format!("Dubby {}", env!("CARGO_PKG_VERSION"))),
+        ("Build", if cfg!(debug_assertions) { "debug" } else { "release" }.to_string()),
+        ("Platform", format!("{} {}", std::env::consts::OS, std::env::consts::ARCH)),
+        ("JavaScript", "Boa".to_string()),
+        ("Graphics", "wgpu".to_string()),
+    ]);
+    builder.heading("Internal pages");
+    let pages: Vec<String> = AboutPage::ALL.iter().map(AboutPage::to_string).collect();
+    builder.list(&pages, "");
+}
+
+/// Count the nodes and elements of a tree
+fn count_nodes(node: &Rc<Node>) -> (usize, usize) {
+    let is_element = matches!(node.node_type, NodeType::Element { .. });
+    node.children.borrow().iter().fold((1, is_element as usize), |(nodes, elements), child| {
+        let (child_nodes, child_elements) = count_nodes(child);
+        (nodes + child_nodes, elements + child_elements)
+    })
+}
+
+/// Count the boxes of a layout tree
+fn count_boxes(layout: &LayoutBox) -> usize {
+    1 + layout.children.iter().map(count_boxes).sum::<usize>()
+}
+
+/// Get the resident memory of the process, where the OS reports it
+fn resident_memory() -> Option<u64> {
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    Some(pages * 4096)
+}
+
+fn kib(bytes: u64) -> String {
+    format!("{} KiB", bytes / 1024)
+}
+
+fn memory_page(builder: &PageBuilder, engine: &BrowserEngine) {
+    builder.heading("Process");
+    let resident = resident_memory().map_or_else(|| "unknown".to_string(), kib);
+    builder.table("process", &[("Resident memory", resident)]);
+
+    builder.heading("This tab");
+    let (nodes, elements) = engine.current_document.as_ref().map_or((0, 0), |document| count_nodes(&document.root));
+    let (boxes, layers, texture_bytes) = engine.current_layout.as_ref().map_or((0, 0, 0), |layout| {
+        let layers = LayerTree::from_layout(layout);
+        (count_boxes(layout), layers.len(), layers.texture_bytes())
+    });
+    let source = engine.document_source.as_ref().map_or(0, String::len) + engine.stylesheet_source.as_ref().map_or(0, String::len);
+    builder.table("tab", &[
+        ("DOM nodes", nodes.to_string()),
+        ("Elements", elements.to_string()),
+        ("Layout boxes", boxes.to_string()),
+        ("Composited layers", layers.to_string()),
+        ("Layer textures", kib(texture_bytes)),
+        ("Page and stylesheet source", kib(source as u64)),
+        ("Console messages", engine.console.messages().len().to_string()),
+        ("History entries", engine.history.len().to_string()),
+    ]);
+
+    builder.heading("Shared");
+    builder.table("shared", &[
+        ("Visited URLs", engine.visited.len().to_string()),
+        ("Prefetched responses", engine.prefetch_cache.len().to_string()),
+        ("Caches", engine.cache_storage.keys().len().to_string()),
+    ]);
+}
+
+fn config_page(builder: &PageBuilder, engine: &BrowserEngine) {
+    let flag = |on: bool| if on { "on" } else { "off" }.to_string();
+    builder.heading("Appearance");
+    let features = &engine.media_features;
+    builder.table("appearance", &[
+        ("Color scheme", features.color_scheme.as_str().to_string()),
+        ("Reduced motion", flag(features.reduced_motion)),
+        ("Forced colors", flag(features.forced_colors)),
+        ("Visibility", engine.visibility_state.to_string()),
+    ]);
+
+    builder.heading("Prefetching");
+    let prefetch = engine.prefetch_policy;
+    builder.table("prefetch", &[
+        ("Resource hints", flag(prefetch.enabled)),
+        ("Hovered links", flag(prefetch.hover)),
+        ("Cross-origin targets", flag(prefetch.cross_origin)),
+        ("On metered connections", flag(prefetch.on_metered_connection)),
+        ("Metered connection", flag(engine.metered_connection)),
+    ]);
+
+    builder.heading("Background tabs");
+    let freezing = engine.freezing_policy;
+    builder.table("tab-freezing", &[
+        ("Minimum timer delay", format!("{} ms", freezing.hidden_timer_min_delay.as_millis())),
+        ("Suspend rendering", flag(freezing.suspend_hidden_rendering)),
+    ]);
+
+    builder.heading("Scripts");
+    let sandbox = &engine.default_sandbox_policy;
+    builder.table("sandbox", &[
+        ("Network", flag(sandbox.allow_network)),
+        ("Storage", flag(sandbox.allow_storage)),
+        ("Timers", flag(sandbox.allow_timers)),
+        ("Workers", flag(sandbox.allow_workers)),
+        ("Origins with their own policy", engine.sandbox_policies.len().to_string()),
+    ]);
+    builder.paragraph("Settings are changed with the shell's commands, such as prefetch and color-scheme.");
+}
+
+fn cache_page(builder: &PageBuilder, engine: &BrowserEngine) {
+    for name in engine.cache_storage.keys() {
+        builder.heading(&format!("Cache {}", name));
+        let urls = engine.cache_storage.open_cache(&name).map(|cache| cache.urls()).unwrap_or_default();
+        builder.list(&urls, "Empty");
+    }
+    builder.heading("Prefetched");
+    builder.list(&engine.prefetch_cache.urls(), "Nothing was prefetched");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use networking::disk_cache::CachedResponse;
+    use std::time::Instant;
+
+    #[test]
+    fn test_internal_pages_are_loaded_as_documents() {
+        assert_eq!(AboutPage::from_url("ABOUT:Memory?tab=1"), Some(AboutPage::Memory));
+        assert_eq!(AboutPage::from_url("about:unknown"), None);
+        assert_eq!(AboutPage::from_url("https://about:blank"), None);
+
+        let mut engine = BrowserEngine::new();
+        engine.prefetch_cache.insert(CachedResponse::new("https://example.com/next.html", 200, Vec::new(), Vec::new()), Instant::now());
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        assert!(runtime.block_on(engine.fetch_url("about:cache")));
+        assert_eq!(engine.current_url.as_deref(), Some("about:cache"));
+        assert!(engine.get_text_content().contains("https://example.com/next.html"));
+        assert!(engine.perform_layout());
+
+        assert!(runtime.block_on(engine.fetch_url("about:memory")));
+        let document = engine.get_document().unwrap();
+        assert!(document.root.query_selector("#tab").unwrap().is_some());
+        assert!(document.origin().is_opaque());
+        assert!(engine.get_text_content().contains("DOM nodes"));
+
+        assert!(runtime.block_on(engine.fetch_url("about:blank")));
+        assert_eq!(engine.get_text_content().trim(), "");
+        assert!(!runtime.block_on(engine.fetch_url("about:nothing")));
+        assert_eq!(engine.history().0, ["about:cache", "about:memory", "about:blank"]);
+    }
+}
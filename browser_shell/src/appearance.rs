@@ -0,0 +1,139 @@
+//! # Appearance Preferences
+//!
+//! This module decides the color scheme, motion preference and
+//! forced-colors mode pages see through `prefers-color-scheme`,
+//! `prefers-reduced-motion` and `forced-colors`. Values come from the
+//! operating system unless the user overrides them in the shell.
+//!
+//! ## Design Principles
+//!
+//! 1. **System First**: Without an override the shell follows the OS, so
+//!    dark mode and reduced motion work without configuration.
+//! 2. **Explicit Overrides**: A shell toggle replaces one preference and
+//!    leaves the other following the system.
+//! 3. **Best Effort Detection**: Detection never fails; when the OS cannot
+//!    be asked, the defaults (light, full motion, author colors) apply.
+
+use std::process::Command;
+use css_parser::media::{ColorScheme, MediaFeatures};
+
+/// Environment variable forcing the detected color scheme (`light`/`dark`)
+pub const COLOR_SCHEME_ENV: &str = "DUBBY_COLOR_SCHEME";
+
+/// Environment variable forcing the detected motion preference (`1`/`0`)
+pub const REDUCED_MOTION_ENV: &str = "DUBBY_REDUCED_MOTION";
+
+/// Environment variable forcing the detected high-contrast setting (`1`/`0`)
+pub const FORCED_COLORS_ENV: &str = "DUBBY_FORCED_COLORS";
+
+/// Appearance preferences reported by the operating system
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SystemPreferences {
+    pub color_scheme: ColorScheme,
+    pub reduced_motion: bool,
+    pub forced_colors: bool,
+}
+
+impl SystemPreferences {
+    /// Ask the operating system for its appearance preferences
+    ///
+    /// `DUBBY_COLOR_SCHEME`, `DUBBY_REDUCED_MOTION` and `DUBBY_FORCED_COLORS`
+    /// take precedence, then the desktop settings of macOS or GNOME, then
+    /// `GTK_THEME`.
+    pub fn detect() -> Self {
+        let color_scheme = std::env::var(COLOR_SCHEME_ENV)
+            .ok()
+            .and_then(|value| ColorScheme::parse(value.trim()))
+            .or_else(detect_color_scheme)
+            .unwrap_or_default();
+        let reduced_motion = std::env::var(REDUCED_MOTION_ENV)
+            .ok()
+            .and_then(|value| parse_flag(&value))
+            .or_else(detect_reduced_motion)
+            .unwrap_or(false);
+        let forced_colors = std::env::var(FORCED_COLORS_ENV)
+            .ok()
+            .and_then(|value| parse_flag(&value))
+            .or_else(detect_forced_colors)
+            .unwrap_or(false);
+        SystemPreferences { color_scheme, reduced_motion, forced_colors }
+    }
+}
+
+/// The user's appearance overrides; `None` follows the system
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AppearanceSettings {
+    pub color_scheme: Option<ColorScheme>,
+    pub reduced_motion: Option<bool>,
+    pub forced_colors: Option<bool>,
+}
+
+impl AppearanceSettings {
+    /// Build the media features pages are styled with
+    ///
+    /// Features other than the preferences are taken from `base`.
+    pub fn media_features(&self, system: &SystemPreferences, base: &MediaFeatures) -> MediaFeatures {
+        MediaFeatures {
+            color_scheme: self.color_scheme.unwrap_or(system.color_scheme),
+            reduced_motion: self.reduced_motion.unwrap_or(system.reduced_motion),
+            forced_colors: self.forced_colors.unwrap_or(system.forced_colors),
+            ..base.clone()
+        }
+    }
+}
+
+/// Parse a boolean preference such as `1`, `on` or `reduce`
+pub fn parse_flag(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "on" | "yes" | "reduce" => Some(true),
+        "0" | "false" | "off" | "no" | "no-preference" => Some(false),
+        _ => None,
+    }
+}
+
+/// Run a settings command and return its trimmed output
+fn read_setting(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().trim_matches('\'').to_string())
+}
+
+fn detect_color_scheme() -> Option<ColorScheme> {
+    if cfg!(target_os = "macos") {
+        // The key only exists in dark mode
+        return Some(match read_setting("defaults", &["read", "-g", "AppleInterfaceStyle"]) {
+            Some(style) if style.eq_ignore_ascii_case("dark") => ColorScheme::Dark,
+            _ => ColorScheme::Light,
+        });
+    }
+    if let Some(scheme) = read_setting("gsettings", &["get", "org.gnome.desktop.interface", "color-scheme"]) {
+        match scheme.as_str() {
+            "prefer-dark" => return Some(ColorScheme::Dark),
+            "prefer-light" => return Some(ColorScheme::Light),
+            _ => {}
+        }
+    }
+    std::env::var("GTK_THEME")
+        .ok()
+        .map(|theme| if theme.to_ascii_lowercase().ends_with(":dark") { ColorScheme::Dark } else { ColorScheme::Light })
+}
+
+fn detect_reduced_motion() -> Option<bool> {
+    if cfg!(target_os = "macos") {
+        return read_setting("defaults", &["read", "com.apple.universalaccess", "reduceMotion"]).and_then(|value| parse_flag(&value));
+    }
+    // GNOME turns animations off rather than reducing them
+    read_setting("gsettings", &["get", "org.gnome.desktop.interface", "enable-animations"])
+        .and_then(|value| parse_flag(&value))
+        .map(|enabled| !enabled)
+}
+
+fn detect_forced_colors() -> Option<bool> {
+    // macOS has no forced palette, only increased contrast
+    if cfg!(target_os = "macos") {
+        return None;
+    }
+    read_setting("gsettings", &["get", "org.gnome.desktop.a11y.interface", "high-contrast"]).and_then(|value| parse_flag(&value))
+}
@@ -0,0 +1,497 @@
+//! # Benchmarks
+//!
+//! This module implements `browser_shell bench`, which times the engine on
+//! standard workloads: parsing a 1MB HTML snapshot, cascading 30k rules over
+//! 10k nodes, laying the snapshot out and painting it offscreen. Refactors
+//! of any crate can be compared on the same numbers.
+//!
+//! ## Design Principles
+//!
+//! 1. **Reproducible Inputs**: Documents and stylesheets are generated from
+//!    a fixed seed, so every run and every machine times the same work.
+//! 2. **Statistics, Not Samples**: Each workload is warmed up and sampled,
+//!    and reported as a mean with a 95% confidence interval, like
+//!    criterion does.
+//! 3. **Machine Readable**: Results serialize to JSON, so runs can be
+//!    stored and compared by scripts.
+
+use std::time::Instant;
+use css_parser::{CSSCascadeEngine, CSSParser, Stylesheet};
+use dom::Document;
+use layout::{LayoutEngine, StyleMemory};
+use renderer::display_list::DisplayList;
+use serde::Serialize;
+
+/// Size of the HTML snapshot parsed by the `parse` workload
+pub const SNAPSHOT_BYTES: usize = 1024 * 1024;
+/// Number of rules in the stylesheet of the `cascade` workload
+pub const CASCADE_RULES: usize = 30_000;
+/// Number of elements in the document of the `cascade` workload
+pub const CASCADE_NODES: usize = 10_000;
+
+/// A standard workload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Workload {
+    /// Parse the HTML snapshot into a DOM
+    Parse,
+    /// Compute the styles of every node with a large stylesheet
+    Cascade,
+    /// Lay out the parsed snapshot
+    Layout,
+    /// Build the display list of the laid out snapshot
+    Paint,
+}
+
+impl Workload {
+    pub const ALL: [Workload; 4] = [Workload::Parse, Workload::Cascade, Workload::Layout, Workload::Paint];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Workload::Parse => "parse",
+            Workload::Cascade => "cascade",
+            Workload::Layout => "layout",
+            Workload::Paint => "paint",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|workload| workload.name() == name)
+    }
+}
+
+/// How the benchmarks are run
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchConfig {
+    /// Timed runs of each workload
+    pub samples: usize,
+    /// Untimed runs before sampling
+    pub warmup: usize,
+    /// Factor applied to the input sizes, 1.0 for the standard workloads
+    pub scale: f64,
+}
+
+impl Default for BenchConfig {
+    fn default() -> Self {
+        BenchConfig { samples: 20, warmup: 3, scale: 1.0 }
+    }
+}
+
+impl BenchConfig {
+    fn scaled(&self, size: usize) -> usize {
+        ((size as f64 * self.scale).round() as usize).max(1)
+    }
+}
+
+/// Summary of the sample times of a workload, in nanoseconds
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Statistics {
+    pub samples: usize,
+    pub mean: f64,
+    pub median: f64,
+    pub std_dev: f64,
+    pub min: f64,
+    pub max: f64,
+    /// Lower bound of the 95% confidence interval of the mean
+    pub ci_lower: f64,
+    /// Upper bound of the 95% confidence interval of the mean
+    pub ci_upper: f64,
+    /// Samples outside the Tukey fences, 1.5 interquartile ranges beyond
+    /// the quartiles
+    pub outliers: usize,
+}
+
+impl Statistics {
+    /// Summarize sample times
+    pub fn from_samples(samples: &[f64]) -> Self {
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let n = sorted.len();
+        if n == 0 {
+            return Statistics { samples: 0, mean: 0.0, median: 0.0, std_dev: 0.0, min: 0.0, max: 0.0, ci_lower: 0.0, ci_upper: 0.0, outliers: 0 };
+        }
+        let mean = sorted.iter().sum::<f64>() / n as f64;
+        let variance = if n > 1 {
+            sorted.iter().map(|sample| (sample - mean).powi(2)).sum::<f64>() / (n - 1) as f64
+        } else {
+            0.0
+        };
+        let std_dev = variance.sqrt();
+        let margin = 1.96 * std_dev / (n as f64).sqrt();
+        let q1 = percentile(&sorted, 0.25);
+        let q3 = percentile(&sorted, 0.75);
+        let iqr = q3 - q1;
+        let outliers = sorted.iter().filter(|&&sample| sample < q1 - 1.5 * iqr || sample > q3 + 1.5 * iqr).count();
+        Statistics {
+            samples: n,
+            mean,
+            median: percentile(&sorted, 0.5),
+            std_dev,
+            min: sorted[0],
+            max: sorted[n - 1],
+            ci_lower: (mean - margin).max(0.0),
+            ci_upper: mean + margin,
+            outliers,
+        }
+    }
+}
+
+/// Get a percentile of sorted samples, interpolating between neighbours
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let position = p * (sorted.len() - 1) as f64;
+    let lower = position.floor() as usize;
+    let upper = position.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (position - lower as f64)
+}
+
+/// The result of one workload
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchResult {
+    pub workload: &'static str,
+    /// What the workload processed, e.g. `1048576 bytes`
+    pub input: String,
+    pub statistics: Statistics,
+    /// Fraction of elements that reused a sibling's styles, for workloads
+    /// that lay out a page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style_sharing_hit_rate: Option<f64>,
+    /// Memory taken by the computed styles of a page of `CASCADE_NODES`
+    /// elements, for workloads that lay out a page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style_memory: Option<StyleMemoryReport>,
+}
+
+/// Shallow size of the computed styles of a laid out page, with every box
+/// holding its own copy of each style struct and with structs shared
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct StyleMemoryReport {
+    pub boxes: usize,
+    pub unshared_bytes: usize,
+    pub shared_bytes: usize,
+}
+
+impl From<StyleMemory> for StyleMemoryReport {
+    fn from(memory: StyleMemory) -> Self {
+        StyleMemoryReport { boxes: memory.boxes, unshared_bytes: memory.unshared_bytes, shared_bytes: memory.shared_bytes }
+    }
+}
+
+/// The results of a benchmark run
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchReport {
+    pub config: BenchConfig,
+    /// Whether the engine was built with optimizations
+    pub optimized: bool,
+    pub results: Vec<BenchResult>,
+}
+
+impl BenchReport {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    /// Format the results as a table, one workload per line
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        if !self.optimized {
+            text.push_str("⚠️  Unoptimized build; use --release for meaningful numbers\n");
+        }
+        for result in &self.results {
+            let statistics = &result.statistics;
+            let sharing = result
+                .style_sharing_hit_rate
+                .map(|rate| format!(", {:.1}% styles shared", rate * 100.0))
+                .unwrap_or_default();
+            text.push_str(&format!(
+                "{:<8} time: [{} {} {}]  ({} samples, {} outliers, {}{})\n",
+                result.workload,
+                format_duration(statistics.ci_lower),
+                format_duration(statistics.mean),
+                format_duration(statistics.ci_upper),
+                statistics.samples,
+                statistics.outliers,
+                result.input,
+                sharing
+            ));
+            if let Some(memory) = result.style_memory {
+                text.push_str(&format!(
+                    "{:<8} styles: {} unshared, {} shared over {} boxes\n",
+                    "",
+                    format_bytes(memory.unshared_bytes),
+                    format_bytes(memory.shared_bytes),
+                    memory.boxes
+                ));
+            }
+        }
+        text
+    }
+}
+
+/// Format nanoseconds with a readable unit
+pub fn format_duration(nanos: f64) -> String {
+    if nanos >= 1e9 {
+        format!("{:.3} s", nanos / 1e9)
+    } else if nanos >= 1e6 {
+        format!("{:.3} ms", nanos / 1e6)
+    } else if nanos >= 1e3 {
+        format!("{:.3} µs", nanos / 1e3)
+    } else {
+        format!("{:.0} ns", nanos)
+    }
+}
+
+/// Format a byte count with a readable unit
+pub fn format_bytes(bytes: usize) -> String {
+    if bytes >= 1 << 20 {
+        format!("{:.2} MiB", bytes as f64 / (1 << 20) as f64)
+    } else if bytes >= 1 << 10 {
+        format!("{:.1} KiB", bytes as f64 / (1 << 10) as f64)
+    } else {
+        format!("{} B", bytes)
+    }
+}
+
+/// Run workloads and collect their statistics
+pub fn run_benchmarks(workloads: &[Workload], config: &BenchConfig) -> BenchReport {
+    let snapshot = snapshot_html(config.scaled(SNAPSHOT_BYTES));
+    let mut results = Vec::new();
+    for workload in workloads {
+        let mut style_sharing_hit_rate = None;
+        let mut style_memory = None;
+        let (input, samples) = match workload {
+            Workload::Parse => {
+                let samples = sample(config, || {
+                    parse(&snapshot);
+                });
+                (format!("{} bytes", snapshot.len()), samples)
+            }
+            Workload::Cascade => {
+                let nodes = config.scaled(CASCADE_NODES);
+                let rules = config.scaled(CASCADE_RULES);
+                let document = parse(&document_with_elements(nodes));
+                let mut cascade = CSSCascadeEngine::new();
+                cascade.add_stylesheet(parse_stylesheet(&stress_stylesheet(rules)));
+                let samples = sample(config, || {
+                    cascade.compute_styles(&document);
+                });
+                (format!("{} rules over {} nodes", rules, nodes), samples)
+            }
+            Workload::Layout => {
+                let document = parse(&snapshot);
+                let stylesheet = parse_stylesheet(SNAPSHOT_CSS);
+                let samples = sample(config, || {
+                    let engine = LayoutEngine::new(stylesheet.clone());
+                    engine.layout_document(&document);
+                    style_sharing_hit_rate = Some(engine.style_sharing_stats().hit_rate());
+                });
+                let page = parse(&document_with_elements(config.scaled(CASCADE_NODES)));
+                let layout = LayoutEngine::new(stylesheet.clone()).layout_document(&page);
+                style_memory = Some(StyleMemory::measure(&layout).into());
+                (format!("{} bytes", snapshot.len()), samples)
+            }
+            Workload::Paint => {
+                let document = parse(&snapshot);
+                let layout = LayoutEngine::new(parse_stylesheet(SNAPSHOT_CSS)).layout_document(&document);
+                let mut items = 0;
+                let samples = sample(config, || {
+                    items = DisplayList::from_layout(&layout).len();
+                });
+                (format!("{} display items", items), samples)
+            }
+        };
+        results.push(BenchResult { workload: workload.name(), input, statistics: Statistics::from_samples(&samples), style_sharing_hit_rate, style_memory });
+    }
+    BenchReport { config: config.clone(), optimized: !cfg!(debug_assertions), results }
+}
+
+/// Time `f`, after warming up
+fn sample(config: &BenchConfig, mut f: impl FnMut()) -> Vec<f64> {
+    for _ in 0..config.warmup {
+        f();
+    }
+    (0..config.samples)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed().as_nanos() as f64
+        })
+        .collect()
+}
+
+fn parse(html: &str) -> Document {
+    html_parser::parse_html_string(html).map(|(document, _)| document).unwrap_or_else(|_| Document::new())
+}
+
+fn parse_stylesheet(css: &str) -> Stylesheet {
+    CSSParser::new(css.to_string())
+        .parse_stylesheet()
+        .unwrap_or(Stylesheet { rules: vec![], page_rules: vec![], source_url: None })
+}
+
+/// Stylesheet of the snapshot for the layout and paint workloads
+const SNAPSHOT_CSS: &str = "body { margin: 8px } header { background-color: #eeeeee; padding: 4px } \
+    article { margin: 10px; padding: 6px } h2 { font-size: 20px; color: navy } \
+    p { margin: 4px } .note { color: gray } li { margin: 2px } td { padding: 2px }";
+
+/// A deterministic pseudo-random sequence, so inputs are the same on every
+/// run
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self, bound: usize) -> usize {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        ((self.0 >> 33) as usize) % bound.max(1)
+    }
+}
+
+const WORDS: [&str; 16] = [
+    "browser", "engine", "layout", "style", "render", "network", "script", "parser",
+    "document", "element", "cascade", "paint", "frame", "pixel", "token", "selector",
+];
+
+/// Append one article of a news-like page, returning its element count
+fn push_article(html: &mut String, index: usize, rng: &mut Lcg) -> usize {
+    let mut elements = 2;
+    html.push_str(&format!("<article id=\"a{}\" class=\"story c{}\"><h2>", index, rng.next(50)));
+    push_words(html, 4, rng);
+    html.push_str("</h2>");
+    for _ in 0..1 + rng.next(3) {
+        html.push_str(&format!("<p class=\"c{}\">", rng.next(50)));
+        push_words(html, 20 + rng.next(40), rng);
+        html.push_str(&format!(" <a href=\"/story/{}\">more</a></p>", rng.next(10_000)));
+        elements += 2;
+    }
+    match rng.next(3) {
+        0 => {
+            html.push_str("<ul>");
+            for _ in 0..3 {
+                html.push_str("<li>");
+                push_words(html, 5, rng);
+                html.push_str("</li>");
+            }
+            html.push_str("</ul>");
+            elements += 4;
+        }
+        1 => {
+            html.push_str("<table><tr>");
+            for _ in 0..3 {
+                html.push_str(&format!("<td>{}</td>", rng.next(1000)));
+            }
+            html.push_str("</tr></table>");
+            elements += 5;
+        }
+        _ => {
+            html.push_str("<p class=\"note\"><span>");
+            push_words(html, 8, rng);
+            html.push_str("</span></p>");
+            elements += 2;
+        }
+    }
+    html.push_str("</article>");
+    elements
+}
+
+fn push_words(html: &mut String, count: usize, rng: &mut Lcg) {
+    for i in 0..count {
+        if i > 0 {
+            html.push(' ');
+        }
+        html.push_str(WORDS[rng.next(WORDS.len())]);
+    }
+}
+
+/// Generate the HTML snapshot, a news-like page of about `bytes` bytes
+pub fn snapshot_html(bytes: usize) -> String {
+    let mut rng = Lcg(0x5eed);
+    let mut html = String::with_capacity(bytes + 1024);
+    html.push_str("<!DOCTYPE html><html><head><title>Benchmark snapshot</title></head><body><header><h1>Front page</h1></header><main>");
+    let mut index = 0;
+    while html.len() < bytes {
+        push_article(&mut html, index, &mut rng);
+        index += 1;
+    }
+    html.push_str("</main></body></html>");
+    html
+}
+
+/// Generate a page with at least `elements` elements in its body
+pub fn document_with_elements(elements: usize) -> String {
+    let mut rng = Lcg(0x5eed);
+    let mut html = String::from("<!DOCTYPE html><html><head><title>Cascade</title></head><body><main>");
+    let mut count = 1;
+    let mut index = 0;
+    while count < elements {
+        count += push_article(&mut html, index, &mut rng);
+        index += 1;
+    }
+    html.push_str("</main></body></html>");
+    html
+}
+
+/// Generate a stylesheet of `rules` rules with a realistic mix of type,
+/// class, id, descendant and compound selectors
+pub fn stress_stylesheet(rules: usize) -> String {
+    let mut rng = Lcg(0xc55);
+    let tags = ["article", "h2", "p", "a", "ul", "li", "td", "span"];
+    let properties = ["color: navy", "margin: 2px", "padding: 1px", "font-size: 14px", "background-color: #eeeeee"];
+    let mut css = String::new();
+    for _ in 0..rules {
+        let selector = match rng.next(5) {
+            0 => tags[rng.next(tags.len())].to_string(),
+            1 => format!(".c{}", rng.next(50)),
+            2 => format!("#a{}", rng.next(5000)),
+            3 => format!("article {}", tags[rng.next(tags.len())]),
+            _ => format!("{}.c{}", tags[rng.next(tags.len())], rng.next(50)),
+        };
+        css.push_str(&format!("{} {{ {} }}\n", selector, properties[rng.next(properties.len())]));
+    }
+    css
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count_elements(node: &dom::Node) -> usize {
+        let own = usize::from(matches!(node.node_type, dom::NodeType::Element { .. }));
+        own + node.children.borrow().iter().map(|child| count_elements(child)).sum::<usize>()
+    }
+
+    #[test]
+    fn test_statistics_from_samples() {
+        let statistics = Statistics::from_samples(&[10.0, 12.0, 11.0, 13.0, 100.0]);
+        assert_eq!(statistics.samples, 5);
+        assert_eq!(statistics.median, 12.0);
+        assert_eq!(statistics.min, 10.0);
+        assert_eq!(statistics.max, 100.0);
+        assert_eq!(statistics.mean, 29.2);
+        assert_eq!(statistics.outliers, 1);
+        assert!(statistics.ci_lower < statistics.mean && statistics.mean < statistics.ci_upper);
+        assert_eq!(format_duration(1_500_000.0), "1.500 ms");
+    }
+
+    #[test]
+    fn test_workloads_are_reproducible() {
+        assert_eq!(snapshot_html(20_000), snapshot_html(20_000));
+        assert!(snapshot_html(20_000).len() >= 20_000);
+        let document = parse(&document_with_elements(300));
+        assert!(count_elements(&document.root) >= 300);
+        assert_eq!(parse_stylesheet(&stress_stylesheet(100)).rules.len(), 100);
+
+        let config = BenchConfig { samples: 2, warmup: 0, scale: 0.01 };
+        let report = run_benchmarks(&Workload::ALL, &config);
+        let names: Vec<_> = report.results.iter().map(|result| result.workload).collect();
+        assert_eq!(names, vec!["parse", "cascade", "layout", "paint"]);
+        assert!(report.results.iter().all(|result| result.statistics.samples == 2));
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["results"][1]["input"], "300 rules over 100 nodes");
+        assert!(json["results"][0]["statistics"]["mean"].as_f64().unwrap() > 0.0);
+        // Siblings such as list items and table cells share their styles
+        assert!(report.results[2].style_sharing_hit_rate.is_some_and(|rate| rate > 0.0));
+        assert!(json["results"][0].get("style_sharing_hit_rate").is_none());
+        // Boxes share most of their style structs
+        let memory = report.results[2].style_memory.unwrap();
+        assert!(memory.boxes >= 100);
+        assert!(memory.shared_bytes < memory.unshared_bytes);
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+    }
+}
@@ -35,6 +35,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             enable_animations: true,
             record_metrics: true,
             render_config: (),
+            render_blocking_timeout: std::time::Duration::from_secs(3),
         };
         
         let mut loader = WebpageLoader::new(config);
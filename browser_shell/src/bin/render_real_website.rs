@@ -11,6 +11,7 @@ use std::env;
 use std::time::Instant;
 use browser_shell::webpage_loader::{WebpageLoader, WebpageLoaderConfig};
 use browser_shell::gpu_webpage_renderer::{GpuWebpageRenderer, GpuRenderConfig};
+use renderer_wgpu::text_quality::TextRenderSettings;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -47,6 +48,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         enable_animations: true,
         record_metrics: true,
         render_config: (),
+        render_blocking_timeout: std::time::Duration::from_secs(3),
     };
     
     let mut loader = WebpageLoader::new(config);
@@ -68,6 +70,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             None
         },
         show_metrics: true,
+        // Screenshots must not depend on the display they were taken on
+        text_rendering: if save_screenshot {
+            TextRenderSettings::deterministic()
+        } else {
+            TextRenderSettings::from_env()
+        },
     };
     
     let mut gpu_renderer = GpuWebpageRenderer::new(gpu_config).await?;
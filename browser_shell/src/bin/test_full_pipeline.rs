@@ -40,6 +40,7 @@ async fn test_complete_pipeline() {
         enable_animations: true,
         record_metrics: true,
         render_config: (),
+        render_blocking_timeout: Duration::from_secs(3),
     };
     
     let mut loader = WebpageLoader::new(config);
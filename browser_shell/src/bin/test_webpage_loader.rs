@@ -42,6 +42,7 @@ async fn test_basic_webpage_loading() {
         enable_animations: true,
         record_metrics: true,
         render_config: (),
+        render_blocking_timeout: Duration::from_secs(3),
     };
     
     let mut loader = WebpageLoader::new(config);
@@ -95,6 +96,9 @@ async fn test_performance_metrics() {
         css_rules: 75,
         layout_boxes: 120,
         js_statements: 25,
+        first_paint: Some(Duration::from_millis(300)),
+        first_contentful_paint: Some(Duration::from_millis(320)),
+        render_blocking_time: Duration::from_millis(60),
     };
     
     println!("📈 Performance Metrics Test:");
@@ -158,6 +162,7 @@ async fn test_configuration_options() {
             enable_animations: false,
             record_metrics: false,
             render_config: (),
+            render_blocking_timeout: Duration::from_secs(3),
         }),
         ("Full Features", WebpageLoaderConfig {
             timeout: Duration::from_secs(30),
@@ -167,6 +172,7 @@ async fn test_configuration_options() {
             enable_animations: true,
             record_metrics: true,
             render_config: (),
+            render_blocking_timeout: Duration::from_secs(3),
         }),
         ("Performance Focused", WebpageLoaderConfig {
             timeout: Duration::from_secs(15),
@@ -176,6 +182,7 @@ async fn test_configuration_options() {
             enable_animations: false, // Disable for performance
             record_metrics: true,
             render_config: (),
+            render_blocking_timeout: Duration::from_secs(3),
         }),
     ];
     
@@ -208,6 +215,7 @@ async fn test_stress_loading() {
         enable_animations: true,
         record_metrics: true,
         render_config: (),
+        render_blocking_timeout: Duration::from_secs(3),
     };
     
     println!("🚀 Stress test scenarios:");
@@ -0,0 +1,498 @@
+//! # Bookmarks
+//!
+//! This module keeps the user's bookmarks, persists them in the profile
+//! directory, and imports and exports them in the Netscape bookmark file
+//! format that every other browser reads and writes.
+//!
+//! ## Design Principles
+//!
+//! 1. **Saved on Change**: Every change is written to disk immediately, so
+//!    bookmarks survive a crash of the shell.
+//! 2. **Folders as Paths**: A bookmark's folder is a `/`-separated path;
+//!    import and export map it to nested `<H3>`/`<DL>` lists.
+//! 3. **Own Parser**: Bookmark files are parsed with the engine's HTML
+//!    parser, which already recovers from the unclosed `<DT>` and `<p>` tags
+//!    the format relies on.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use dom::events::KeyboardEventInit;
+use dom::{Document, Node, NodeType};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use crate::error::escape_html;
+
+/// Name of the bookmarks file in the profile directory
+pub const BOOKMARKS_FILE: &str = "bookmarks.json";
+
+/// Custom error types for bookmark operations
+#[derive(Error, Debug)]
+pub enum BookmarkError {
+    #[error("Bookmark I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Invalid bookmarks file: {0}")]
+    Format(#[from] serde_json::Error),
+
+    #[error("Invalid bookmark file: {0}")]
+    Import(String),
+
+    #[error("No bookmark with id {0}")]
+    NotFound(u64),
+}
+
+/// Result type for bookmark operations
+pub type BookmarkResult<T> = Result<T, BookmarkError>;
+
+/// A bookmarked page
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub id: u64,
+    pub url: String,
+    pub title: String,
+    pub tags: Vec<String>,
+    /// Folder path such as `Toolbar/News`, empty for the top level
+    pub folder: String,
+    /// When the bookmark was added, in seconds since the Unix epoch
+    pub added_at: u64,
+}
+
+impl Bookmark {
+    /// Check whether the bookmark matches every term of a search query
+    ///
+    /// Terms of the form `tag:name` match tags exactly; other terms match
+    /// the title, URL, folder or a tag case-insensitively.
+    pub fn matches(&self, query: &str) -> bool {
+        query.split_whitespace().all(|term| {
+            if let Some(tag) = term.strip_prefix("tag:") {
+                return self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag));
+            }
+            let term = term.to_lowercase();
+            self.title.to_lowercase().contains(&term)
+                || self.url.to_lowercase().contains(&term)
+                || self.folder.to_lowercase().contains(&term)
+                || self.tags.iter().any(|tag| tag.to_lowercase().contains(&term))
+        })
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct BookmarkFile {
+    next_id: u64,
+    bookmarks: Vec<Bookmark>,
+}
+
+/// The bookmarks of a profile
+#[derive(Debug)]
+pub struct BookmarkStore {
+    path: PathBuf,
+    file: BookmarkFile,
+}
+
+impl BookmarkStore {
+    /// Open the bookmarks stored in `dir`, creating an empty store if there
+    /// are none yet
+    pub fn open(dir: impl AsRef<Path>) -> BookmarkResult<Self> {
+        let path = dir.as_ref().join(BOOKMARKS_FILE);
+        let file = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BookmarkFile { next_id: 1, bookmarks: Vec::new() },
+            Err(e) => return Err(e.into()),
+        };
+        Ok(BookmarkStore { path, file })
+    }
+
+    /// Get all bookmarks in the order they were added
+    pub fn bookmarks(&self) -> &[Bookmark] {
+        &self.file.bookmarks
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Bookmark> {
+        self.file.bookmarks.iter().find(|bookmark| bookmark.id == id)
+    }
+
+    /// Find the bookmark for a URL
+    pub fn find_by_url(&self, url: &str) -> Option<&Bookmark> {
+        self.file.bookmarks.iter().find(|bookmark| bookmark.url == url)
+    }
+
+    pub fn is_bookmarked(&self, url: &str) -> bool {
+        self.find_by_url(url).is_some()
+    }
+
+    /// Bookmark a page
+    ///
+    /// Bookmarking a URL twice updates the title and adds the new tags to
+    /// the existing bookmark. Returns the bookmark's id.
+    pub fn add(&mut self, url: &str, title: &str, tags: &[String]) -> BookmarkResult<u64> {
+        let id = self.insert(url, title, tags, "", now());
+        self.save()?;
+        Ok(id)
+    }
+
+    /// Remove a bookmark
+    pub fn remove(&mut self, id: u64) -> BookmarkResult<Bookmark> {
+        let index = self
+            .file
+            .bookmarks
+            .iter()
+            .position(|bookmark| bookmark.id == id)
+            .ok_or(BookmarkError::NotFound(id))?;
+        let bookmark = self.file.bookmarks.remove(index);
+        self.save()?;
+        Ok(bookmark)
+    }
+
+    /// Add a tag to a bookmark
+    pub fn tag(&mut self, id: u64, tag: &str) -> BookmarkResult<()> {
+        let bookmark = self
+            .file
+            .bookmarks
+            .iter_mut()
+            .find(|bookmark| bookmark.id == id)
+            .ok_or(BookmarkError::NotFound(id))?;
+        if !bookmark.tags.iter().any(|t| t == tag) {
+            bookmark.tags.push(tag.to_string());
+        }
+        self.save()
+    }
+
+    /// Remove a tag from a bookmark
+    pub fn untag(&mut self, id: u64, tag: &str) -> BookmarkResult<()> {
+        let bookmark = self
+            .file
+            .bookmarks
+            .iter_mut()
+            .find(|bookmark| bookmark.id == id)
+            .ok_or(BookmarkError::NotFound(id))?;
+        bookmark.tags.retain(|t| t != tag);
+        self.save()
+    }
+
+    /// Find the bookmarks matching a query, see `Bookmark::matches`
+    pub fn search(&self, query: &str) -> Vec<&Bookmark> {
+        self.file.bookmarks.iter().filter(|bookmark| bookmark.matches(query)).collect()
+    }
+
+    /// Import bookmarks from a Netscape bookmark file
+    ///
+    /// URLs that are already bookmarked are merged. Returns the number of
+    /// bookmarks read from the file.
+    pub fn import_netscape(&mut self, html: &str) -> BookmarkResult<usize> {
+        let (document, _) = html_parser::parse_html(html.as_bytes().to_vec())
+            .map_err(|e| BookmarkError::Import(e.to_string()))?;
+        let entries = parse_netscape_bookmarks(&document);
+        for entry in &entries {
+            self.insert(&entry.url, &entry.title, &entry.tags, &entry.folder, entry.added_at);
+        }
+        self.save()?;
+        Ok(entries.len())
+    }
+
+    /// Export all bookmarks as a Netscape bookmark file
+    pub fn export_netscape(&self) -> String {
+        export_netscape_bookmarks(&self.file.bookmarks)
+    }
+
+    fn insert(&mut self, url: &str, title: &str, tags: &[String], folder: &str, added_at: u64) -> u64 {
+        if let Some(existing) = self.file.bookmarks.iter_mut().find(|bookmark| bookmark.url == url) {
+            if !title.is_empty() {
+                existing.title = title.to_string();
+            }
+            for tag in tags {
+                if !existing.tags.contains(tag) {
+                    existing.tags.push(tag.clone());
+                }
+            }
+            return existing.id;
+        }
+
+        let id = self.file.next_id.max(1);
+        self.file.next_id = id + 1;
+        self.file.bookmarks.push(Bookmark {
+            id,
+            url: url.to_string(),
+            title: if title.is_empty() { url.to_string() } else { title.to_string() },
+            tags: tags.to_vec(),
+            folder: folder.to_string(),
+            added_at,
+        });
+        id
+    }
+
+    fn save(&self) -> BookmarkResult<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let temporary = self.path.with_extension("json.tmp");
+        fs::write(&temporary, serde_json::to_string_pretty(&self.file)?)?;
+        fs::rename(&temporary, &self.path)?;
+        Ok(())
+    }
+}
+
+/// A browser-level action bound to a key combination
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookmarkShortcut {
+    /// Ctrl+D / Cmd+D
+    BookmarkPage,
+    /// Ctrl+Shift+O / Cmd+Shift+O
+    ShowBookmarks,
+}
+
+/// The bookmark action triggered by a key combination, if any
+///
+/// Accepts both Control (Windows, Linux) and Meta (macOS) shortcuts.
+pub fn bookmark_shortcut(init: &KeyboardEventInit) -> Option<BookmarkShortcut> {
+    if !(init.ctrl_key || init.meta_key) || init.alt_key {
+        return None;
+    }
+    match (init.key.to_ascii_lowercase().as_str(), init.shift_key) {
+        ("d", false) => Some(BookmarkShortcut::BookmarkPage),
+        ("o", true) => Some(BookmarkShortcut::ShowBookmarks),
+        _ => None,
+    }
+}
+
+/// Get the title of a document, from its `<title>` element
+pub fn document_title(document: &Document) -> Option<String> {
+    // `get_elements_by_tag_name` returns detached copies without children,
+    // so find the element itself
+    fn find_title(node: &Rc<Node>) -> Option<Rc<Node>> {
+        if node.tag_name() == Some("title") {
+            return Some(Rc::clone(node));
+        }
+        node.children.borrow().iter().find_map(find_title)
+    }
+    find_title(&document.root)
+        .map(|title| title.text_content().split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|title| !title.is_empty())
+}
+
+/// A bookmark read from a Netscape bookmark file
+#[derive(Debug, Clone, PartialEq)]
+struct ImportedBookmark {
+    url: String,
+    title: String,
+    tags: Vec<String>,
+    folder: String,
+    added_at: u64,
+}
+
+/// Read the bookmarks of a parsed Netscape bookmark file
+fn parse_netscape_bookmarks(document: &Document) -> Vec<ImportedBookmark> {
+    let mut entries = Vec::new();
+    collect_bookmarks(&document.root, "", &mut None, &mut entries);
+    entries
+}
+
+/// Walk a bookmark list
+///
+/// A folder is an `<H3>` followed by the `<DL>` holding its contents; the
+/// heading is remembered until that list is reached.
+fn collect_bookmarks(
+    node: &Rc<Node>,
+    folder: &str,
+    pending_folder: &mut Option<String>,
+    entries: &mut Vec<ImportedBookmark>,
+) {
+    for child in node.children.borrow().iter() {
+        match child.tag_name() {
+            Some("h3") => {
+                *pending_folder = Some(child.text_content().trim().replace('/', "-"));
+            }
+            Some("dl") => {
+                let nested = match pending_folder.take() {
+                    Some(name) if folder.is_empty() => name,
+                    Some(name) => format!("{}/{}", folder, name),
+                    None => folder.to_string(),
+                };
+                collect_bookmarks(child, &nested, &mut None, entries);
+            }
+            Some("a") => {
+                let Some(url) = child.get_attribute("href").filter(|href| !href.is_empty()) else {
+                    continue;
+                };
+                let tags = child
+                    .get_attribute("tags")
+                    .map(|tags| {
+                        tags.split(',')
+                            .map(|tag| tag.trim().to_string())
+                            .filter(|tag| !tag.is_empty())
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                entries.push(ImportedBookmark {
+                    url,
+                    title: child.text_content().trim().to_string(),
+                    tags,
+                    folder: folder.to_string(),
+                    added_at: child
+                        .get_attribute("add_date")
+                        .and_then(|date| date.trim().parse().ok())
+                        .unwrap_or(0),
+                });
+            }
+            _ => {
+                if matches!(child.node_type, NodeType::Element { .. }) {
+                    collect_bookmarks(child, folder, pending_folder, entries);
+                }
+            }
+        }
+    }
+}
+
+/// Write bookmarks as a Netscape bookmark file
+fn export_netscape_bookmarks(bookmarks: &[Bookmark]) -> String {
+    let mut output = String::from(
+        "<!DOCTYPE NETSCAPE-Bookmark-file-1>\n\
+         <META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=UTF-8\">\n\
+         <TITLE>Bookmarks</TITLE>\n\
+         <H1>Bookmarks</H1>\n",
+    );
+    let mut sorted: Vec<&Bookmark> = bookmarks.iter().collect();
+    // Stable sort keeps the order bookmarks were added within a folder
+    sorted.sort_by(|a, b| a.folder.cmp(&b.folder));
+    write_folder(&mut output, &sorted, &[], 0);
+    output
+}
+
+/// Write the bookmarks in `folder` and its subfolders
+fn write_folder(output: &mut String, bookmarks: &[&Bookmark], folder: &[&str], depth: usize) {
+    let indent = "    ".repeat(depth);
+    output.push_str(&format!("{}<DL><p>\n", indent));
+
+    let in_folder = |bookmark: &&&Bookmark| folder_path(&bookmark.folder) == folder;
+    for bookmark in bookmarks.iter().filter(in_folder) {
+        let tags = if bookmark.tags.is_empty() {
+            String::new()
+        } else {
+            format!(" TAGS=\"{}\"", escape_html(&bookmark.tags.join(",")))
+        };
+        output.push_str(&format!(
+            "{}    <DT><A HREF=\"{}\" ADD_DATE=\"{}\"{}>{}</A>\n",
+            indent,
+            escape_html(&bookmark.url),
+            bookmark.added_at,
+            tags,
+            escape_html(&bookmark.title)
+        ));
+    }
+
+    let mut subfolders: Vec<&str> = Vec::new();
+    for bookmark in bookmarks {
+        let path = folder_path(&bookmark.folder);
+        if path.len() > folder.len() && path[..folder.len()] == *folder && !subfolders.contains(&path[folder.len()]) {
+            subfolders.push(path[folder.len()]);
+        }
+    }
+    for name in subfolders {
+        output.push_str(&format!("{}    <DT><H3>{}</H3>\n", indent, escape_html(name)));
+        let mut nested = folder.to_vec();
+        nested.push(name);
+        write_folder(output, bookmarks, &nested, depth + 1);
+    }
+
+    output.push_str(&format!("{}</DL><p>\n", indent));
+}
+
+fn folder_path(folder: &str) -> Vec<&str> {
+    folder.split('/').filter(|part| !part.is_empty()).collect()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temporary_profile;
+
+    #[test]
+    fn test_bookmark_store_persists_changes() {
+        let dir = temporary_profile("bookmarks-store");
+        let mut store = BookmarkStore::open(&dir).unwrap();
+        let rust = store.add("https://www.rust-lang.org/", "Rust", &["lang".to_string()]).unwrap();
+        let news = store.add("https://news.example.com/", "Daily News", &[]).unwrap();
+        store.tag(news, "reading").unwrap();
+        assert_eq!(store.add("https://www.rust-lang.org/", "Rust Language", &[]).unwrap(), rust);
+
+        let store = BookmarkStore::open(&dir).unwrap();
+        assert_eq!(store.bookmarks().len(), 2);
+        assert_eq!(store.get(rust).unwrap().title, "Rust Language");
+        assert_eq!(store.search("tag:reading").len(), 1);
+        assert_eq!(store.search("RUST lang").len(), 1);
+        assert!(store.search("missing").is_empty());
+
+        let mut store = store;
+        store.remove(rust).unwrap();
+        assert!(matches!(store.remove(rust), Err(BookmarkError::NotFound(_))));
+        assert!(!BookmarkStore::open(&dir).unwrap().is_bookmarked("https://www.rust-lang.org/"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_netscape_import_and_export() {
+        let file = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<META HTTP-EQUIV="Content-Type" CONTENT="text/html; charset=UTF-8">
+<TITLE>Bookmarks</TITLE>
+<H1>Bookmarks</H1>
+<DL><p>
+    <DT><A HREF="https://example.com/" ADD_DATE="1700000000" TAGS="demo,test">Example &amp; Co</A>
+    <DT><H3 ADD_DATE="1700000001">Toolbar</H3>
+    <DL><p>
+        <DT><A HREF="https://docs.example.com/">Docs</A>
+        <DT><H3>Nested</H3>
+        <DL><p>
+            <DT><A HREF="https://deep.example.com/">Deep</A>
+        </DL><p>
+    </DL><p>
+    <DT><A HREF="https://after.example.com/">After</A>
+</DL><p>
+"#;
+        let dir = temporary_profile("bookmarks-import");
+        let mut store = BookmarkStore::open(&dir).unwrap();
+        assert_eq!(store.import_netscape(file).unwrap(), 4);
+
+        let example = store.find_by_url("https://example.com/").unwrap();
+        assert_eq!(example.title, "Example & Co");
+        assert_eq!(example.tags, vec!["demo", "test"]);
+        assert_eq!(example.added_at, 1700000000);
+        assert_eq!(store.find_by_url("https://docs.example.com/").unwrap().folder, "Toolbar");
+        assert_eq!(store.find_by_url("https://deep.example.com/").unwrap().folder, "Toolbar/Nested");
+        assert_eq!(store.find_by_url("https://after.example.com/").unwrap().folder, "");
+
+        // Exported files import back to the same bookmarks
+        let exported = store.export_netscape();
+        assert!(exported.contains("<DT><H3>Nested</H3>"));
+        assert!(exported.contains("Example &amp; Co"));
+        let other = temporary_profile("bookmarks-reimport");
+        let mut reimported = BookmarkStore::open(&other).unwrap();
+        assert_eq!(reimported.import_netscape(&exported).unwrap(), 4);
+        for bookmark in store.bookmarks() {
+            let copy = reimported.find_by_url(&bookmark.url).unwrap();
+            assert_eq!((&copy.title, &copy.folder, &copy.tags), (&bookmark.title, &bookmark.folder, &bookmark.tags));
+        }
+        let _ = fs::remove_dir_all(&dir);
+        let _ = fs::remove_dir_all(&other);
+    }
+
+    #[test]
+    fn test_bookmark_shortcuts() {
+        let mut init = KeyboardEventInit::new("d", "KeyD");
+        assert_eq!(bookmark_shortcut(&init), None);
+        init.ctrl_key = true;
+        assert_eq!(bookmark_shortcut(&init), Some(BookmarkShortcut::BookmarkPage));
+        let mut init = KeyboardEventInit::new("O", "KeyO");
+        init.meta_key = true;
+        init.shift_key = true;
+        assert_eq!(bookmark_shortcut(&init), Some(BookmarkShortcut::ShowBookmarks));
+    }
+}
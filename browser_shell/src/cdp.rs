@@ -0,0 +1,673 @@
+//! # Remote Debugging Protocol
+//!
+//! This module serves a subset of the Chrome DevTools Protocol over a
+//! WebSocket, so tools written for Chrome (puppeteer-like scripts, editor
+//! debuggers) can drive and inspect the engine:
+//!
+//! - `Page.navigate`, `Page.reload`, `Page.captureScreenshot` and
+//!   `Page.getFrameTree`, with `Page.frameNavigated` and
+//!   `Page.loadEventFired`
+//! - `Runtime.evaluate`, with `Runtime.consoleAPICalled`
+//! - `DOM.getDocument`
+//! - `Network.requestWillBeSent`, `Network.responseReceived`,
+//!   `Network.loadingFinished` and `Network.loadingFailed` for navigations
+//!
+//! ## Design Principles
+//!
+//! 1. **Protocol Apart From Transport**: `CdpSession` turns one JSON message
+//!    into its response and events without knowing about sockets, so the
+//!    protocol is tested without a network.
+//! 2. **One Page, One Client**: The endpoint exposes a single page target
+//!    and serves one connection at a time, which keeps the engine on one
+//!    thread like the rest of the shell.
+//! 3. **Local Only**: The server binds to the loopback interface, and a
+//!    WebSocket upgrade sent with an `Origin` that is not a local page is
+//!    refused, so the web pages the user visits cannot drive the engine.
+//!    Whatever may connect can run script in the page.
+//! 4. **Events Follow Responses**: The events a command causes are sent
+//!    right after its response, in the order they happened.
+
+use std::time::Instant;
+use base64::Engine;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+use dom::{Node, NodeType};
+use js_integration::console::ConsoleLevel;
+use js_integration::repl::ValuePreview;
+use js_integration::{JsEngine, JsIntegrationError};
+use layout::Dimensions;
+use renderer_wgpu::snapshot::encode_png;
+use crate::screenshot::{CaptureArea, ScreenshotError};
+use crate::BrowserEngine;
+
+/// Port Chrome uses for remote debugging by default
+pub const DEFAULT_PORT: u16 = 9222;
+
+/// Id of the single page target
+pub const TARGET_ID: &str = "page-1";
+
+/// Id of the page's main frame
+const FRAME_ID: &str = "main";
+
+/// Id of the page's only execution context
+const EXECUTION_CONTEXT_ID: u32 = 1;
+
+/// Largest message a client may send, over all of its frames
+const MAX_MESSAGE_BYTES: usize = 16 * 1024 * 1024;
+
+const BLANK_PAGE: &str = "<html><head></head><body></body></html>";
+
+/// Errors returned to the client in place of a result
+#[derive(Error, Debug)]
+pub enum CdpError {
+    #[error("'{0}' wasn't found")]
+    MethodNotFound(String),
+
+    #[error("Invalid parameters: {0}")]
+    InvalidParams(String),
+
+    #[error("{0}")]
+    Server(String),
+}
+
+impl CdpError {
+    /// JSON-RPC error code
+    pub fn code(&self) -> i64 {
+        match self {
+            CdpError::MethodNotFound(_) => -32601,
+            CdpError::InvalidParams(_) => -32602,
+            CdpError::Server(_) => -32000,
+        }
+    }
+}
+
+/// The protocol state of one debugging client
+pub struct CdpSession {
+    engine: BrowserEngine,
+    /// Script engine of the current document, created on first use
+    script: Option<JsEngine>,
+    page_events: bool,
+    network_events: bool,
+    runtime_events: bool,
+    next_request: u64,
+    started: Instant,
+}
+
+impl CdpSession {
+    pub fn new(engine: BrowserEngine) -> Self {
+        CdpSession {
+            engine,
+            script: None,
+            page_events: false,
+            network_events: false,
+            runtime_events: false,
+            next_request: 1,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn engine(&self) -> &BrowserEngine {
+        &self.engine
+    }
+
+    /// Seconds since the session started, the protocol's monotonic time
+    fn timestamp(&self) -> f64 {
+        self.started.elapsed().as_secs_f64()
+    }
+
+    /// Handle one message from the client
+    ///
+    /// Returns the response followed by the events the command caused.
+    pub async fn handle_message(&mut self, text: &str) -> Vec<Value> {
+        let message: Value = match serde_json::from_str(text) {
+            Ok(message) => message,
+            Err(e) => return vec![json!({ "error": { "code": -32700, "message": format!("Message must be valid JSON: {}", e) } })],
+        };
+        let id = message.get("id").cloned().unwrap_or(Value::Null);
+        let method = message.get("method").and_then(Value::as_str).unwrap_or_default().to_string();
+        let params = message.get("params").cloned().unwrap_or_else(|| json!({}));
+
+        let mut events = Vec::new();
+        let response = match self.dispatch(&method, &params, &mut events).await {
+            Ok(result) => json!({ "id": id, "result": result }),
+            Err(e) => json!({ "id": id, "error": { "code": e.code(), "message": e.to_string() } }),
+        };
+        let mut messages = vec![response];
+        messages.extend(events);
+        messages
+    }
+
+    async fn dispatch(&mut self, method: &str, params: &Value, events: &mut Vec<Value>) -> Result<Value, CdpError> {
+        match method {
+            "Browser.getVersion" => Ok(json!({
+                "protocolVersion": "1.3",
+                "product": format!("Dubby/{}", env!("CARGO_PKG_VERSION")),
+                "userAgent": format!("Dubby/{}", env!("CARGO_PKG_VERSION")),
+                "jsVersion": "boa",
+            })),
+            "Page.enable" => {
+                self.page_events = true;
+                Ok(json!({}))
+            }
+            "Page.disable" => {
+                self.page_events = false;
+                Ok(json!({}))
+            }
+            "Network.enable" => {
+                self.network_events = true;
+                Ok(json!({}))
+            }
+            "Network.disable" => {
+                self.network_events = false;
+                Ok(json!({}))
+            }
+            "Runtime.enable" => {
+                self.runtime_events = true;
+                events.push(event("Runtime.executionContextCreated", json!({
+                    "context": { "id": EXECUTION_CONTEXT_ID, "origin": self.origin(), "name": "" }
+                })));
+                Ok(json!({}))
+            }
+            "Runtime.disable" => {
+                self.runtime_events = false;
+                Ok(json!({}))
+            }
+            "DOM.enable" | "DOM.disable" => Ok(json!({})),
+            "Page.getFrameTree" => Ok(json!({ "frameTree": { "frame": self.frame() } })),
+            "Page.navigate" => {
+                let url = params.get("url").and_then(Value::as_str).ok_or_else(|| CdpError::InvalidParams("url must be a string".to_string()))?;
+                Ok(self.navigate(url.to_string(), events).await)
+            }
+            "Page.reload" => {
+                let url = self.engine.current_url.clone().unwrap_or_else(|| "about:blank".to_string());
+                self.navigate(url, events).await;
+                Ok(json!({}))
+            }
+            "Page.captureScreenshot" => self.capture_screenshot(params),
+            "Runtime.evaluate" => self.evaluate(params, events),
+            "DOM.getDocument" => {
+                let depth = params.get("depth").and_then(Value::as_i64).unwrap_or(1);
+                let document = self.engine.get_document().ok_or_else(|| CdpError::Server("No document".to_string()))?;
+                Ok(json!({ "root": describe_node(&document.root, depth) }))
+            }
+            _ => Err(CdpError::MethodNotFound(method.to_string())),
+        }
+    }
+
+    fn origin(&self) -> String {
+        self.engine.get_document().map(|document| document.origin().serialize()).unwrap_or_else(|| "null".to_string())
+    }
+
+    fn frame(&self) -> Value {
+        json!({
+            "id": FRAME_ID,
+            "loaderId": format!("loader-{}", self.next_request.saturating_sub(1)),
+            "url": self.engine.current_url.clone().unwrap_or_else(|| "about:blank".to_string()),
+            "securityOrigin": self.origin(),
+            "mimeType": "text/html",
+        })
+    }
+
+    /// Load a page, reporting it as a network request
+    async fn navigate(&mut self, url: String, events: &mut Vec<Value>) -> Value {
+        let request_id = self.next_request.to_string();
+        self.next_request += 1;
+        let loader_id = format!("loader-{}", request_id);
+        if self.network_events {
+            events.push(event("Network.requestWillBeSent", json!({
+                "requestId": request_id,
+                "loaderId": loader_id,
+                "documentURL": url,
+                "request": { "url": url, "method": "GET", "headers": {} },
+                "timestamp": self.timestamp(),
+                "type": "Document",
+                "frameId": FRAME_ID,
+            })));
+        }
+
+        let loaded = match inline_document(&url) {
+            Some(Ok(html)) => self.engine.load_html(&html),
+            Some(Err(e)) => {
+                self.engine.last_error = None;
+                return self.navigation_failed(request_id, loader_id, e, events);
+            }
+            None => self.engine.fetch_url(&url).await,
+        };
+        self.script = None;
+        if !loaded {
+            let error = self.engine.last_error().map(|e| e.to_string()).unwrap_or_else(|| "net::ERR_FAILED".to_string());
+            return self.navigation_failed(request_id, loader_id, error, events);
+        }
+
+        if self.network_events {
+            let length = self.engine.document_source.as_ref().map_or(0, String::len);
+            events.push(event("Network.responseReceived", json!({
+                "requestId": request_id,
+                "loaderId": loader_id,
+                "timestamp": self.timestamp(),
+                "type": "Document",
+                "frameId": FRAME_ID,
+                "response": { "url": url, "status": 200, "statusText": "OK", "headers": {}, "mimeType": "text/html" },
+            })));
+            events.push(event("Network.loadingFinished", json!({
+                "requestId": request_id,
+                "timestamp": self.timestamp(),
+                "encodedDataLength": length,
+            })));
+        }
+        if self.page_events {
+            let mut frame = self.frame();
+            frame["url"] = json!(url);
+            events.push(event("Page.frameNavigated", json!({ "frame": frame })));
+            events.push(event("Page.loadEventFired", json!({ "timestamp": self.timestamp() })));
+        }
+        json!({ "frameId": FRAME_ID, "loaderId": loader_id })
+    }
+
+    fn navigation_failed(&self, request_id: String, loader_id: String, error: String, events: &mut Vec<Value>) -> Value {
+        if self.network_events {
+            events.push(event("Network.loadingFailed", json!({
+                "requestId": request_id,
+                "timestamp": self.timestamp(),
+                "type": "Document",
+                "errorText": error,
+            })));
+        }
+        json!({ "frameId": FRAME_ID, "loaderId": loader_id, "errorText": error })
+    }
+
+    fn capture_screenshot(&mut self, params: &Value) -> Result<Value, CdpError> {
+        let format = params.get("format").and_then(Value::as_str).unwrap_or("png");
+        if format != "png" {
+            return Err(CdpError::InvalidParams(format!("Unsupported screenshot format: {}", format)));
+        }
+        let (area, scale) = match params.get("clip") {
+            Some(clip) => {
+                let number = |name: &str| clip.get(name).and_then(Value::as_f64).map(|value| value as f32);
+                let (Some(x), Some(y), Some(width), Some(height)) = (number("x"), number("y"), number("width"), number("height")) else {
+                    return Err(CdpError::InvalidParams("clip needs x, y, width and height".to_string()));
+                };
+                (CaptureArea::Region(Dimensions::new(x, y, width, height)), number("scale").unwrap_or(1.0))
+            }
+            None if params.get("captureBeyondViewport").and_then(Value::as_bool).unwrap_or(false) => (CaptureArea::FullPage, 1.0),
+            None => (CaptureArea::Viewport, 1.0),
+        };
+        let page = self.engine.capture_screenshot(&area, scale).map_err(|e| match e {
+            ScreenshotError::EmptyArea => CdpError::InvalidParams("clip must have a positive size and scale".to_string()),
+            e => CdpError::Server(e.to_string()),
+        })?;
+        Ok(json!({ "data": base64::engine::general_purpose::STANDARD.encode(encode_png(&page)) }))
+    }
+
+    fn evaluate(&mut self, params: &Value, events: &mut Vec<Value>) -> Result<Value, CdpError> {
+        let expression = params.get("expression").and_then(Value::as_str).ok_or_else(|| CdpError::InvalidParams("expression must be a string".to_string()))?;
+        let by_value = params.get("returnByValue").and_then(Value::as_bool).unwrap_or(false);
+        if self.script.is_none() {
+            let mut script = self.engine.create_script_engine().map_err(|e| CdpError::Server(e.to_string()))?;
+            if let Err(e) = script.execute_inline_scripts() {
+                println!("⚠️ Page scripts failed: {}", e);
+            }
+            self.script = Some(script);
+        }
+        let timestamp = self.timestamp() * 1000.0;
+        let Some(script) = self.script.as_mut() else {
+            return Err(CdpError::Server("No script engine".to_string()));
+        };
+
+        let start = script.console().messages().len();
+        let result = script.execute(expression);
+        let _ = script.process_microtasks();
+        if self.runtime_events {
+            for message in script.console().messages().into_iter().skip(start) {
+                let kind = match message.level {
+                    ConsoleLevel::Log => "log",
+                    ConsoleLevel::Info => "info",
+                    ConsoleLevel::Warn => "warning",
+                    ConsoleLevel::Error => "error",
+                };
+                events.push(event("Runtime.consoleAPICalled", json!({
+                    "type": kind,
+                    "args": [{ "type": "string", "value": message.text }],
+                    "executionContextId": EXECUTION_CONTEXT_ID,
+                    "timestamp": timestamp,
+                })));
+            }
+        }
+
+        match result {
+            Ok(value) => Ok(json!({ "result": remote_object(script, &value, by_value) })),
+            Err(e) => {
+                let text = match e {
+                    JsIntegrationError::ExecutionError(message) => message,
+                    other => other.to_string(),
+                };
+                Ok(json!({
+                    "result": { "type": "object", "subtype": "error", "description": text },
+                    "exceptionDetails": {
+                        "exceptionId": 1,
+                        "text": "Uncaught",
+                        "lineNumber": 0,
+                        "columnNumber": 0,
+                        "exception": { "type": "object", "subtype": "error", "description": text },
+                    },
+                }))
+            }
+        }
+    }
+}
+
+fn event(method: &str, params: Value) -> Value {
+    json!({ "method": method, "params": params })
+}
+
+/// Decode the document of a `data:` or `about:blank` URL
+///
+/// Returns `None` for URLs that are fetched.
+fn inline_document(url: &str) -> Option<Result<String, String>> {
+    if url == "about:blank" {
+        return Some(Ok(BLANK_PAGE.to_string()));
+    }
+    let rest = url.strip_prefix("data:")?;
+    let Some((meta, data)) = rest.split_once(',') else {
+        return Some(Err("net::ERR_INVALID_URL".to_string()));
+    };
+    let bytes: Vec<u8> = percent_encoding::percent_decode_str(data).collect();
+    let bytes = if meta.split(';').any(|part| part.eq_ignore_ascii_case("base64")) {
+        match base64::engine::general_purpose::STANDARD.decode(&bytes) {
+            Ok(bytes) => bytes,
+            Err(_) => return Some(Err("net::ERR_INVALID_URL".to_string())),
+        }
+    } else {
+        bytes
+    };
+    Some(Ok(String::from_utf8_lossy(&bytes).into_owned()))
+}
+
+/// Describe a node as a protocol `DOM.Node`, with children `depth` levels
+/// down (-1 for the whole subtree)
+fn describe_node(node: &Node, depth: i64) -> Value {
+    // Node ids must be positive; engine ids start at 0
+    let node_id = node.id + 1;
+    let children = node.children.borrow();
+    let (node_type, node_name, local_name, node_value) = match &node.node_type {
+        NodeType::Document => (9, "#document".to_string(), String::new(), String::new()),
+        NodeType::Element { tag_name, .. } => (1, tag_name.to_ascii_uppercase(), tag_name.to_ascii_lowercase(), String::new()),
+        NodeType::Text(_) => (3, "#text".to_string(), String::new(), node.text_content()),
+    };
+    let mut description = json!({
+        "nodeId": node_id,
+        "backendNodeId": node_id,
+        "nodeType": node_type,
+        "nodeName": node_name,
+        "localName": local_name,
+        "nodeValue": node_value,
+        "childNodeCount": children.len(),
+    });
+    if let NodeType::Element { attributes, .. } = &node.node_type {
+        let mut names: Vec<&String> = attributes.keys().collect();
+        names.sort();
+        let flat: Vec<&String> = names.into_iter().flat_map(|name| [name, &attributes[name]]).collect();
+        description["attributes"] = json!(flat);
+    }
+    if depth != 0 {
+        description["children"] = children.iter().map(|child| describe_node(child, depth - 1)).collect();
+    }
+    description
+}
+
+/// Describe a value as a protocol `Runtime.RemoteObject`
+fn remote_object(script: &mut JsEngine, value: &boa_engine::JsValue, by_value: bool) -> Value {
+    let kind = value.type_of();
+    if value.is_undefined() {
+        return json!({ "type": "undefined" });
+    }
+    if value.is_null() {
+        return json!({ "type": "object", "subtype": "null", "value": null });
+    }
+    let preview = ValuePreview::of(script, value, 1);
+    let mut object = json!({ "type": kind, "description": preview.summary() });
+    let subtype = match &preview {
+        ValuePreview::Node(_) => Some("node"),
+        ValuePreview::Array { .. } => Some("array"),
+        ValuePreview::Error(_) => Some("error"),
+        _ => None,
+    };
+    if let Some(subtype) = subtype {
+        object["subtype"] = json!(subtype);
+    }
+    if value.as_object().is_none() || by_value {
+        if let Ok(json) = value.to_json(&mut script.context) {
+            object["value"] = json;
+        }
+    }
+    object
+}
+
+/// Check whether a WebSocket upgrade may come from `origin`: tools send
+/// none, and pages served from this machine may connect
+fn is_allowed_origin(origin: Option<&str>) -> bool {
+    let Some(origin) = origin else {
+        return true;
+    };
+    match dom::Origin::from_url(origin) {
+        dom::Origin::Tuple { scheme, host, .. } => {
+            matches!(scheme.as_str(), "http" | "https") && matches!(host.as_str(), "localhost" | "127.0.0.1" | "[::1]")
+        }
+        dom::Origin::Opaque => false,
+    }
+}
+
+/// Refuse a WebSocket upgrade with `status`
+fn refuse_upgrade(status: StatusCode, reason: &str) -> ErrorResponse {
+    let mut error = ErrorResponse::new(Some(reason.to_string()));
+    *error.status_mut() = status;
+    error
+}
+
+/// Accept an upgrade to the page target from an allowed origin
+#[allow(clippy::result_large_err)] // The handshake callback signature of tungstenite
+fn check_upgrade(request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+    if !request.uri().path().starts_with("/devtools/page/") {
+        return Err(refuse_upgrade(StatusCode::NOT_FOUND, "Unknown target"));
+    }
+    let origin = request.headers().get("origin").map(|origin| origin.to_str().unwrap_or("null"));
+    if !is_allowed_origin(origin) {
+        println!("🚫 Refused debugger connection from origin {}", origin.unwrap_or_default());
+        return Err(refuse_upgrade(StatusCode::FORBIDDEN, "Origin not allowed"));
+    }
+    Ok(response)
+}
+
+/// Serve debugging clients, one connection at a time
+pub async fn serve(listener: TcpListener, session: &mut CdpSession) -> std::io::Result<()> {
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        println!("🔌 Debugger connected from {}", peer);
+        if let Err(e) = serve_connection(stream, session).await {
+            println!("⚠️ Debugger connection ended: {}", e);
+        }
+    }
+}
+
+/// Answer a discovery request, or upgrade to a WebSocket and run the
+/// protocol until the client closes it
+async fn serve_connection(stream: TcpStream, session: &mut CdpSession) -> std::io::Result<()> {
+    let mut stream = BufReader::new(stream);
+    // The request head is kept to replay it to the WebSocket handshake
+    let mut head = String::new();
+    stream.read_line(&mut head).await?;
+    let path = head.split_whitespace().nth(1).unwrap_or("/").to_string();
+    let mut host = format!("127.0.0.1:{}", DEFAULT_PORT);
+    let mut upgrade = false;
+    loop {
+        let mut line = String::new();
+        if stream.read_line(&mut line).await? == 0 {
+            break;
+        }
+        head.push_str(&line);
+        if line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            match name.trim().to_ascii_lowercase().as_str() {
+                "host" => host = value.trim().to_string(),
+                "upgrade" => upgrade = value.trim().eq_ignore_ascii_case("websocket"),
+                _ => {}
+            }
+        }
+    }
+
+    if upgrade {
+        let (reader, writer) = tokio::io::split(stream);
+        let replayed = tokio::io::join(std::io::Cursor::new(head.into_bytes()).chain(reader), writer);
+        let config = WebSocketConfig {
+            max_message_size: Some(MAX_MESSAGE_BYTES),
+            max_frame_size: Some(MAX_MESSAGE_BYTES),
+            ..WebSocketConfig::default()
+        };
+        let socket = tokio_tungstenite::accept_hdr_async_with_config(replayed, check_upgrade, Some(config))
+            .await
+            .map_err(std::io::Error::other)?;
+        return run_websocket(socket, session).await;
+    }
+
+    let socket_url = format!("ws://{}/devtools/page/{}", host, TARGET_ID);
+    let body = match path.as_str() {
+        "/json/version" => json!({
+            "Browser": format!("Dubby/{}", env!("CARGO_PKG_VERSION")),
+            "Protocol-Version": "1.3",
+            "webSocketDebuggerUrl": socket_url,
+        }),
+        "/json" | "/json/list" => json!([{
+            "id": TARGET_ID,
+            "type": "page",
+            "title": session.engine().get_document().and_then(|document| document.root.get_element_by_tag_name("title")).map(|title| title.text_content()).unwrap_or_default(),
+            "url": session.engine().current_url.clone().unwrap_or_else(|| "about:blank".to_string()),
+            "webSocketDebuggerUrl": socket_url,
+        }]),
+        _ => {
+            stream.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n").await?;
+            return Ok(());
+        }
+    };
+    let body = body.to_string();
+    let response = format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+    stream.write_all(response.as_bytes()).await
+}
+
+/// Run the protocol over an accepted WebSocket; the socket answers pings
+/// and closes, and refuses messages over `MAX_MESSAGE_BYTES`
+async fn run_websocket<S: AsyncRead + AsyncWrite + Unpin>(mut socket: WebSocketStream<S>, session: &mut CdpSession) -> std::io::Result<()> {
+    while let Some(message) = socket.next().await {
+        let text = match message.map_err(std::io::Error::other)? {
+            Message::Text(text) => text,
+            Message::Binary(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            _ => continue,
+        };
+        for reply in session.handle_message(&text).await {
+            socket.send(Message::Text(reply.to_string())).await.map_err(std::io::Error::other)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run<T>(future: impl std::future::Future<Output = T>) -> T {
+        tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(future)
+    }
+
+    #[test]
+    fn test_protocol_commands_and_events() {
+        let mut session = CdpSession::new(BrowserEngine::new());
+        run(async {
+            session.handle_message(r#"{"id": 1, "method": "Page.enable"}"#).await;
+            session.handle_message(r#"{"id": 2, "method": "Network.enable"}"#).await;
+            let replies = session.handle_message(r#"{"id": 3, "method": "Runtime.enable"}"#).await;
+            assert_eq!(replies[1]["method"], "Runtime.executionContextCreated");
+
+            let url = "data:text/html,<html><body><h1 id=%22t%22>Hi</h1><script>var n = 41;</script></body></html>";
+            let replies = session.handle_message(&json!({ "id": 4, "method": "Page.navigate", "params": { "url": url } }).to_string()).await;
+            assert_eq!(replies[0]["id"], 4);
+            assert_eq!(replies[0]["result"]["frameId"], FRAME_ID);
+            let methods: Vec<&str> = replies[1..].iter().map(|reply| reply["method"].as_str().unwrap()).collect();
+            assert_eq!(methods, vec![
+                "Network.requestWillBeSent",
+                "Network.responseReceived",
+                "Network.loadingFinished",
+                "Page.frameNavigated",
+                "Page.loadEventFired",
+            ]);
+
+            let replies = session.handle_message(r#"{"id": 5, "method": "Runtime.evaluate", "params": {"expression": "console.log('x'); n + 1"}}"#).await;
+            assert_eq!(replies[0]["result"]["result"], json!({ "type": "number", "description": "42", "value": 42 }));
+            assert_eq!(replies[1]["params"]["args"][0]["value"], "x");
+            let replies = session.handle_message(r#"{"id": 6, "method": "Runtime.evaluate", "params": {"expression": "({a: [1]})", "returnByValue": true}}"#).await;
+            assert_eq!(replies[0]["result"]["result"]["value"], json!({ "a": [1] }));
+            let replies = session.handle_message(r#"{"id": 7, "method": "Runtime.evaluate", "params": {"expression": "missing()"}}"#).await;
+            assert_eq!(replies[0]["result"]["exceptionDetails"]["text"], "Uncaught");
+
+            let replies = session.handle_message(r#"{"id": 8, "method": "DOM.getDocument", "params": {"depth": -1}}"#).await;
+            let root = &replies[0]["result"]["root"];
+            assert_eq!(root["nodeName"], "#document");
+            let html = &root["children"][0];
+            assert_eq!(html["nodeName"], "HTML");
+            let heading = html["children"].as_array().unwrap().iter()
+                .flat_map(|child| child["children"].as_array().cloned().unwrap_or_default())
+                .find(|node| node["nodeName"] == "H1")
+                .unwrap();
+            assert_eq!(heading["attributes"], json!(["id", "t"]));
+
+            let replies = session.handle_message(r#"{"id": 9, "method": "Page.captureScreenshot", "params": {"clip": {"x": 0, "y": 0, "width": 10, "height": 10, "scale": 2}}}"#).await;
+            let png = base64::engine::general_purpose::STANDARD.decode(replies[0]["result"]["data"].as_str().unwrap()).unwrap();
+            assert_eq!(&png[1..4], b"PNG");
+            assert_eq!(u32::from_be_bytes([png[16], png[17], png[18], png[19]]), 20);
+
+            let replies = session.handle_message(r#"{"id": 10, "method": "Tracing.start"}"#).await;
+            assert_eq!(replies[0]["error"]["code"], -32601);
+        });
+    }
+
+    #[test]
+    fn test_websocket_refuses_web_origins() {
+        use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+        assert!(is_allowed_origin(None));
+        assert!(is_allowed_origin(Some("http://localhost:3000")));
+        assert!(!is_allowed_origin(Some("https://evil.example")));
+        assert!(!is_allowed_origin(Some("null")));
+
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let mut session = CdpSession::new(BrowserEngine::new());
+        runtime.block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("ws://{}/devtools/page/{}", listener.local_addr().unwrap(), TARGET_ID);
+            let client = async {
+                let mut request = url.as_str().into_client_request().unwrap();
+                request.headers_mut().insert("Origin", "https://evil.example".parse().unwrap());
+                let refused = tokio_tungstenite::connect_async(request).await;
+                assert!(matches!(refused, Err(tokio_tungstenite::tungstenite::Error::Http(response)) if response.status() == StatusCode::FORBIDDEN));
+
+                // Tools send no origin and get replies
+                let (mut socket, _) = tokio_tungstenite::connect_async(url.as_str()).await.unwrap();
+                socket.send(Message::Text(r#"{"id": 1, "method": "Runtime.evaluate", "params": {"expression": "1 + 1"}}"#.to_string())).await.unwrap();
+                let reply = socket.next().await.unwrap().unwrap().into_text().unwrap();
+                serde_json::from_str::<Value>(&reply).unwrap()
+            };
+            tokio::select! {
+                reply = client => assert_eq!(reply["result"]["result"]["value"], 2),
+                _ = serve(listener, &mut session) => unreachable!("the server runs until dropped"),
+            }
+        });
+    }
+}
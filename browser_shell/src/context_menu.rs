@@ -0,0 +1,403 @@
+//! # Context Menu
+//!
+//! This module builds the menu shown when the user right-clicks a page.
+//! Its items depend on what is under the pointer: a link can be opened in
+//! a new tab or copied, an image copied or saved, selected text copied,
+//! and a misspelled word in editable text replaced by the spellchecker's
+//! suggestions. Every menu ends with "Inspect", which opens the devtools
+//! console on the element.
+//!
+//! ## Design Principles
+//!
+//! 1. **Menus Are Data**: A menu is a list of labels and actions, so any
+//!    frontend can draw it and tests can run items without one.
+//! 2. **The Target Decides**: A right-click is hit-tested once into a
+//!    `ContextMenuTarget`; the link, image, selection and text found there
+//!    each add a section, and a click on none of them gets the page items.
+//! 3. **Open to Contributors**: Other subsystems add their own sections
+//!    through `ContextMenuContributor`. Their items carry a command the
+//!    menu hands back to them, so the menu needs no knowledge of what they
+//!    do.
+//! 4. **One Spellchecker per Profile**: Tabs share the spellchecker, so a
+//!    word added to the dictionary in one tab is accepted in all of them.
+//! 5. **Edits Go Through the DOM**: Replacing a word sets the character
+//!    data of its text node, so mutation observers and layout see an
+//!    ordinary edit.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use dom::spellcheck::{word_at, Spellchecker};
+use dom::Node;
+use networking::UrlResolver;
+
+/// A spellchecker shared by the tabs of a browser
+pub type SharedSpellchecker = Rc<RefCell<dyn Spellchecker>>;
+
+/// A subsystem adding items to context menus, registered with
+/// `BrowserEngine::add_context_menu_contributor`
+pub type SharedContributor = Rc<RefCell<dyn ContextMenuContributor>>;
+
+/// Adds items for a subsystem to context menus
+pub trait ContextMenuContributor {
+    /// The name `ContextMenuAction::Contributed` items refer to
+    fn name(&self) -> &str;
+
+    /// Add items for a target, usually as one section
+    fn contribute(&self, target: &ContextMenuTarget, menu: &mut ContextMenu);
+
+    /// Run the command of one of this contributor's items
+    ///
+    /// Returns `false` if the command did nothing.
+    fn run(&mut self, command: &str) -> bool;
+}
+
+/// What a context menu is shown for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextMenuKind {
+    /// Nothing more specific than the page
+    Page,
+    Link,
+    Image,
+    Selection,
+}
+
+/// What is under the pointer of a right-click
+#[derive(Debug, Clone)]
+pub struct ContextMenuTarget {
+    /// The element hit
+    pub element: Rc<Node>,
+    /// The resolved `href` of the link around the element
+    pub link: Option<String>,
+    /// The resolved source of the image hit
+    pub image: Option<String>,
+    /// The selected text of the text control hit
+    pub selection: Option<String>,
+    /// A text node and character offset, for spelling suggestions
+    pub text: Option<(Rc<Node>, usize)>,
+}
+
+impl ContextMenuTarget {
+    /// Describe a right-click on an element, resolving URLs with the
+    /// document's resolver
+    pub fn new(element: &Rc<Node>, resolver: &UrlResolver) -> Self {
+        let link = element
+            .closest("a[href], area[href]")
+            .ok()
+            .flatten()
+            .and_then(|link| link.get_attribute("href"))
+            .and_then(|href| resolver.resolve(&href))
+            .map(String::from);
+        let image = Some(element)
+            .filter(|element| element.is_image())
+            .and_then(|image| image.get_attribute("src"))
+            .filter(|src| !src.trim().is_empty())
+            .and_then(|src| resolver.resolve(&src))
+            .map(String::from);
+        let selection = element.form_control_type().and_then(|_| {
+            let (start, end) = element.selection_range();
+            let text: String = element.value().chars().skip(start).take(end - start).collect();
+            Some(text).filter(|text| !text.is_empty())
+        });
+        ContextMenuTarget { element: Rc::clone(element), link, image, selection, text: None }
+    }
+
+    /// Add the text node and character offset under the pointer
+    pub fn with_text(mut self, node: &Rc<Node>, offset: usize) -> Self {
+        self.text = Some((Rc::clone(node), offset));
+        self
+    }
+
+    /// Get the kinds of menu the target gets, in menu order
+    pub fn kinds(&self) -> Vec<ContextMenuKind> {
+        let mut kinds = Vec::new();
+        if self.selection.is_some() {
+            kinds.push(ContextMenuKind::Selection);
+        }
+        if self.link.is_some() {
+            kinds.push(ContextMenuKind::Link);
+        }
+        if self.image.is_some() {
+            kinds.push(ContextMenuKind::Image);
+        }
+        if kinds.is_empty() {
+            kinds.push(ContextMenuKind::Page);
+        }
+        kinds
+    }
+}
+
+/// What an item of the context menu does
+#[derive(Debug, Clone)]
+pub enum ContextMenuAction {
+    /// Replace the characters `start..end` of a text node
+    ReplaceWord {
+        node: Rc<Node>,
+        start: usize,
+        end: usize,
+        replacement: String,
+    },
+    /// Accept a word from now on
+    AddToDictionary(String),
+    /// Open a URL in a new tab; run by the shell, which owns the tabs
+    OpenLinkInNewTab(String),
+    /// Copy a link's URL to the clipboard
+    CopyLink(String),
+    /// Copy an image's URL to the clipboard
+    CopyImage(String),
+    /// Save an image with the download manager
+    SaveImage(String),
+    /// Copy selected text to the clipboard
+    CopySelection(String),
+    /// Load the page again; run by the shell
+    Reload,
+    /// Select an element as `$0` of the devtools console
+    Inspect(Rc<Node>),
+    /// Run a command of a `ContextMenuContributor`
+    Contributed { contributor: String, command: String },
+}
+
+/// An item of the context menu
+#[derive(Debug, Clone)]
+pub struct ContextMenuItem {
+    pub label: String,
+    /// `None` for an item shown disabled, such as "No suggestions", and
+    /// for separators
+    pub action: Option<ContextMenuAction>,
+}
+
+impl ContextMenuItem {
+    pub fn new(label: &str, action: ContextMenuAction) -> Self {
+        ContextMenuItem { label: label.to_string(), action: Some(action) }
+    }
+
+    /// Create an item shown disabled
+    pub fn disabled(label: &str) -> Self {
+        ContextMenuItem { label: label.to_string(), action: None }
+    }
+
+    /// Create the line between two sections
+    pub fn separator() -> Self {
+        Self::disabled("")
+    }
+
+    pub fn is_separator(&self) -> bool {
+        self.label.is_empty() && self.action.is_none()
+    }
+}
+
+/// The items of a context menu, top to bottom
+#[derive(Debug, Clone, Default)]
+pub struct ContextMenu {
+    pub items: Vec<ContextMenuItem>,
+}
+
+impl ContextMenu {
+    /// Build the menu for a right-click
+    ///
+    /// Spelling suggestions come first, then the items of the target's
+    /// kinds, the contributors' items and "Inspect".
+    pub fn for_target(target: &ContextMenuTarget, spellchecker: &dyn Spellchecker, contributors: &[SharedContributor]) -> ContextMenu {
+        let mut menu = match &target.text {
+            Some((node, offset)) => ContextMenu::for_text(node, *offset, spellchecker),
+            None => ContextMenu::default(),
+        };
+        for kind in target.kinds() {
+            let items = match kind {
+                ContextMenuKind::Selection => {
+                    let text = target.selection.clone().unwrap_or_default();
+                    vec![ContextMenuItem::new("Copy", ContextMenuAction::CopySelection(text))]
+                }
+                ContextMenuKind::Link => {
+                    let url = target.link.clone().unwrap_or_default();
+                    vec![
+                        ContextMenuItem::new("Open Link in New Tab", ContextMenuAction::OpenLinkInNewTab(url.clone())),
+                        ContextMenuItem::new("Copy Link Address", ContextMenuAction::CopyLink(url)),
+                    ]
+                }
+                ContextMenuKind::Image => {
+                    let url = target.image.clone().unwrap_or_default();
+                    vec![
+                        ContextMenuItem::new("Copy Image Address", ContextMenuAction::CopyImage(url.clone())),
+                        ContextMenuItem::new("Save Image", ContextMenuAction::SaveImage(url)),
+                    ]
+                }
+                ContextMenuKind::Page => vec![ContextMenuItem::new("Reload", ContextMenuAction::Reload)],
+            };
+            menu.add_section(items);
+        }
+        for contributor in contributors {
+            contributor.borrow().contribute(target, &mut menu);
+        }
+        menu.add_section(vec![ContextMenuItem::new("Inspect", ContextMenuAction::Inspect(Rc::clone(&target.element)))]);
+        menu
+    }
+
+    /// Build the spelling items for a click at a character offset of a
+    /// text node
+    ///
+    /// The menu is empty unless the text is spellchecked and the word
+    /// there is misspelled.
+    pub fn for_text(node: &Rc<Node>, offset: usize, spellchecker: &dyn Spellchecker) -> ContextMenu {
+        let text = match node.character_data() {
+            Some(text) if node.spellcheck_enabled() => text,
+            _ => return ContextMenu::default(),
+        };
+        let Some((start, end, word)) = word_at(&text, offset).filter(|(_, _, word)| !spellchecker.check(word)) else {
+            return ContextMenu::default();
+        };
+        let mut items: Vec<ContextMenuItem> = spellchecker
+            .suggest(&word)
+            .into_iter()
+            .map(|replacement| ContextMenuItem {
+                label: replacement.clone(),
+                action: Some(ContextMenuAction::ReplaceWord { node: Rc::clone(node), start, end, replacement }),
+            })
+            .collect();
+        if items.is_empty() {
+            items.push(ContextMenuItem::disabled("No suggestions"));
+        }
+        items.push(ContextMenuItem::new("Add to Dictionary", ContextMenuAction::AddToDictionary(word)));
+        ContextMenu { items }
+    }
+
+    /// Add items below a separator, unless the menu or `items` is empty
+    pub fn add_section(&mut self, items: Vec<ContextMenuItem>) {
+        if !self.items.is_empty() && !items.is_empty() {
+            self.items.push(ContextMenuItem::separator());
+        }
+        self.items.extend(items);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Get the labels of the items, top to bottom, without separators
+    pub fn labels(&self) -> Vec<&str> {
+        self.items.iter().filter(|item| !item.is_separator()).map(|item| item.label.as_str()).collect()
+    }
+
+    /// Get the action of the enabled item with a label
+    pub fn find(&self, label: &str) -> Option<&ContextMenuAction> {
+        self.items.iter().find(|item| item.label == label).and_then(|item| item.action.as_ref())
+    }
+}
+
+/// Run the spelling action of a context menu item
+///
+/// Returns `false` for other actions, which the engine and the shell run,
+/// and if the text node no longer has the characters the action refers to.
+pub fn run_context_menu_action(action: &ContextMenuAction, spellchecker: &mut dyn Spellchecker) -> bool {
+    match action {
+        ContextMenuAction::ReplaceWord { node, start, end, replacement } => {
+            let Some(text) = node.character_data() else {
+                return false;
+            };
+            let chars: Vec<char> = text.chars().collect();
+            if *end > chars.len() || start > end {
+                return false;
+            }
+            let edited: String = chars[..*start].iter().copied().chain(replacement.chars()).chain(chars[*end..].iter().copied()).collect();
+            node.set_character_data(&edited);
+            true
+        }
+        ContextMenuAction::AddToDictionary(word) => {
+            spellchecker.add_word(word);
+            true
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dom::spellcheck::DictionarySpellchecker;
+    use dom::Document;
+
+    #[test]
+    fn test_spelling_suggestions_replace_the_word() {
+        let document = Document::new();
+        let editor = document.create_element("textarea");
+        let text = document.create_text_node("Plaese send the file");
+        editor.append_child(&text);
+        document.root.append_child(&editor);
+        let mut spellchecker = DictionarySpellchecker::new();
+
+        // Correct words get no spelling items
+        assert!(ContextMenu::for_text(&text, 8, &spellchecker).is_empty());
+
+        let menu = ContextMenu::for_text(&text, 3, &spellchecker);
+        assert_eq!(menu.labels().first(), Some(&"Please"));
+        assert_eq!(menu.labels().last(), Some(&"Add to Dictionary"));
+        assert!(run_context_menu_action(menu.items[0].action.as_ref().unwrap(), &mut spellchecker));
+        assert_eq!(text.character_data().unwrap(), "Please send the file");
+
+        text.set_character_data("Qzxv send");
+        let menu = ContextMenu::for_text(&text, 0, &spellchecker);
+        assert_eq!(menu.labels(), vec!["No suggestions", "Add to Dictionary"]);
+        assert!(run_context_menu_action(menu.items[1].action.as_ref().unwrap(), &mut spellchecker));
+        assert!(ContextMenu::for_text(&text, 0, &spellchecker).is_empty());
+    }
+
+    /// Offers to translate every paragraph
+    struct Translator {
+        translated: Vec<String>,
+    }
+
+    impl ContextMenuContributor for Translator {
+        fn name(&self) -> &str {
+            "translate"
+        }
+
+        fn contribute(&self, target: &ContextMenuTarget, menu: &mut ContextMenu) {
+            if target.element.tag_name() == Some("p") {
+                let command = target.element.text_content();
+                menu.add_section(vec![ContextMenuItem::new("Translate", ContextMenuAction::Contributed { contributor: self.name().to_string(), command })]);
+            }
+        }
+
+        fn run(&mut self, command: &str) -> bool {
+            self.translated.push(command.to_string());
+            true
+        }
+    }
+
+    #[test]
+    fn test_menu_items_follow_the_target() {
+        let html = "<html><body><p>Bonjour</p><a href=\"/docs\"><img src=\"cat.png\"></a><input value=\"hello world\"></body></html>";
+        let (document, _) = html_parser::parse_html_string(html).unwrap();
+        let resolver = UrlResolver::with_base("https://example.com/index.html").unwrap();
+        let spellchecker = DictionarySpellchecker::new();
+        let translator = Rc::new(RefCell::new(Translator { translated: Vec::new() }));
+        let contributors: Vec<SharedContributor> = vec![translator.clone()];
+        let menu_for = |tag: &str| {
+            let element = document.root.query_selector(tag).unwrap().unwrap();
+            ContextMenu::for_target(&ContextMenuTarget::new(&element, &resolver), &spellchecker, &contributors)
+        };
+
+        // A linked image gets the link and the image items
+        let menu = menu_for("img");
+        assert_eq!(menu.labels(), vec!["Open Link in New Tab", "Copy Link Address", "Copy Image Address", "Save Image", "Inspect"]);
+        assert!(matches!(menu.find("Copy Link Address"), Some(ContextMenuAction::CopyLink(url)) if url == "https://example.com/docs"));
+        assert!(matches!(menu.find("Save Image"), Some(ContextMenuAction::SaveImage(url)) if url == "https://example.com/cat.png"));
+        assert_eq!(menu.items.iter().filter(|item| item.is_separator()).count(), 2);
+
+        // A collapsed selection is not a selection
+        let input = document.root.query_selector("input").unwrap().unwrap();
+        assert_eq!(menu_for("input").labels(), vec!["Reload", "Inspect"]);
+        input.set_selection_range(6, 11);
+        let menu = menu_for("input");
+        assert!(matches!(menu.find("Copy"), Some(ContextMenuAction::CopySelection(text)) if text == "world"));
+
+        // Contributed items come back to their contributor
+        let menu = menu_for("p");
+        assert_eq!(menu.labels(), vec!["Reload", "Translate", "Inspect"]);
+        let Some(ContextMenuAction::Contributed { contributor, command }) = menu.find("Translate") else {
+            panic!("no contributed item");
+        };
+        assert_eq!(contributor, "translate");
+        assert!(translator.borrow_mut().run(command));
+        assert_eq!(translator.borrow().translated, vec!["Bonjour"]);
+    }
+}
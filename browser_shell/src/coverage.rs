@@ -0,0 +1,174 @@
+//! # Coverage Reports
+//!
+//! This module reports how much of a page's stylesheet and scripts the page
+//! used: CSS rules that matched an element in any layout, and script
+//! functions that ran. It backs the shell's `coverage` command and the
+//! devtools console's `.coverage`.
+//!
+//! ## Design Principles
+//!
+//! 1. **Byte Ranges**: Each stylesheet and script is reported as used and
+//!    unused byte ranges of its text, so a report points at the exact text
+//!    that can go.
+//! 2. **Ever Matched**: A rule counts as used if it matched in any layout
+//!    since the stylesheet was loaded, so state reached by interaction is
+//!    not reported as dead.
+//! 3. **Text and JSON**: The report prints for people and serializes for
+//!    tools and tests.
+
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::ops::Range;
+use css_parser::Stylesheet;
+use js_integration::coverage::ScriptCoverageReport;
+use serde_json::{json, Value};
+
+/// Whether a rule of a stylesheet ever matched
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleCoverage {
+    /// Selector text as written
+    pub selector: String,
+    /// Byte range of the rule in the stylesheet
+    pub range: Range<usize>,
+    pub used: bool,
+}
+
+/// The rules of one stylesheet and whether they matched
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StylesheetCoverage {
+    pub url: String,
+    /// Length of the stylesheet in bytes
+    pub length: usize,
+    pub rules: Vec<RuleCoverage>,
+}
+
+impl StylesheetCoverage {
+    /// Pair the rules of a stylesheet with the indices of the rules that
+    /// matched
+    pub fn new(url: &str, source: &str, stylesheet: &Stylesheet, used: &HashSet<usize>) -> Self {
+        let rules = stylesheet.rules.iter().enumerate().filter_map(|(index, rule)| {
+            let range = rule.source_range.clone().filter(|range| range.end <= source.len())?;
+            let text = &source[range.clone()];
+            let selector = text.split('{').next().unwrap_or(text).trim().to_string();
+            Some(RuleCoverage { selector, range, used: used.contains(&index) })
+        }).collect();
+        StylesheetCoverage { url: url.to_string(), length: source.len(), rules }
+    }
+
+    /// Byte ranges of the rules that never matched, in order
+    pub fn unused_ranges(&self) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = self.rules.iter().filter(|rule| !rule.used).map(|rule| rule.range.clone()).collect();
+        ranges.sort_by_key(|range| range.start);
+        ranges
+    }
+
+    pub fn unused_bytes(&self) -> usize {
+        self.unused_ranges().iter().map(|range| range.len()).sum()
+    }
+
+    pub fn used_bytes(&self) -> usize {
+        self.length - self.unused_bytes()
+    }
+}
+
+/// Coverage of a page's stylesheets and scripts
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CoverageReport {
+    pub stylesheets: Vec<StylesheetCoverage>,
+    pub scripts: Vec<ScriptCoverageReport>,
+}
+
+impl CoverageReport {
+    pub fn total_bytes(&self) -> usize {
+        self.stylesheets.iter().map(|sheet| sheet.length).sum::<usize>() + self.scripts.iter().map(|script| script.length).sum::<usize>()
+    }
+
+    pub fn unused_bytes(&self) -> usize {
+        self.stylesheets.iter().map(StylesheetCoverage::unused_bytes).sum::<usize>()
+            + self.scripts.iter().map(ScriptCoverageReport::unused_bytes).sum::<usize>()
+    }
+
+    /// Format the report as a table with the unused parts of each resource
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        let line = |text: &mut String, kind: &str, url: &str, used: usize, length: usize| {
+            let percent = if length == 0 { 100.0 } else { used as f64 * 100.0 / length as f64 };
+            let _ = writeln!(text, "  {:<4} {:>8} / {:<8} {:>5.1}% used  {}", kind, used, length, percent, url);
+        };
+        for sheet in &self.stylesheets {
+            line(&mut text, "css", &sheet.url, sheet.used_bytes(), sheet.length);
+            for rule in sheet.rules.iter().filter(|rule| !rule.used) {
+                let _ = writeln!(text, "         unused {}..{}  {}", rule.range.start, rule.range.end, rule.selector);
+            }
+        }
+        for script in &self.scripts {
+            line(&mut text, "js", &script.url, script.used_bytes(), script.length);
+            for function in script.functions.iter().filter(|function| function.count == 0) {
+                let name = function.name.as_deref().unwrap_or("(anonymous)");
+                let _ = writeln!(text, "         unused {}..{}  {}()", function.range.start, function.range.end, name);
+            }
+        }
+        let total = self.total_bytes();
+        let _ = writeln!(text, "  {} of {} bytes unused", self.unused_bytes(), total);
+        text
+    }
+
+    pub fn to_json(&self) -> Value {
+        let ranges = |ranges: Vec<Range<usize>>| -> Vec<Value> {
+            ranges.into_iter().map(|range| json!({ "start": range.start, "end": range.end })).collect()
+        };
+        json!({
+            "stylesheets": self.stylesheets.iter().map(|sheet| json!({
+                "url": sheet.url,
+                "length": sheet.length,
+                "usedBytes": sheet.used_bytes(),
+                "unusedRanges": ranges(sheet.unused_ranges()),
+                "rules": sheet.rules.iter().map(|rule| json!({
+                    "selector": rule.selector,
+                    "start": rule.range.start,
+                    "end": rule.range.end,
+                    "used": rule.used,
+                })).collect::<Vec<_>>(),
+            })).collect::<Vec<_>>(),
+            "scripts": self.scripts.iter().map(|script| json!({
+                "url": script.url,
+                "length": script.length,
+                "usedBytes": script.used_bytes(),
+                "unusedRanges": ranges(script.unused_ranges()),
+                "functions": script.functions.iter().map(|function| json!({
+                    "name": function.name,
+                    "start": function.range.start,
+                    "end": function.range.end,
+                    "count": function.count,
+                })).collect::<Vec<_>>(),
+            })).collect::<Vec<_>>(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::BrowserEngine;
+
+    #[test]
+    fn test_coverage_of_page_styles_and_scripts() {
+        let mut engine = BrowserEngine::new();
+        assert!(engine.load_html("<html><body><p class=\"a\">Hi</p><script>function shown() {} function hidden() { return 1; } shown();</script></body></html>"));
+        let css = "p { color: red }\n.missing { color: blue }\n.a { margin: 0 }";
+        assert!(engine.load_css(css));
+
+        let report = engine.collect_coverage();
+        let sheet = &report.stylesheets[0];
+        let used: Vec<(&str, bool)> = sheet.rules.iter().map(|rule| (rule.selector.as_str(), rule.used)).collect();
+        assert_eq!(used, vec![("p", true), (".missing", false), (".a", true)]);
+        assert_eq!(sheet.unused_bytes(), ".missing { color: blue }".len());
+
+        let script = &report.scripts[0];
+        assert_eq!(script.url, "inline script #1");
+        assert_eq!(script.unused_bytes(), "function hidden() { return 1; }".len());
+        let text = report.to_text();
+        assert!(text.contains("unused 17..41  .missing"), "{}", text);
+        assert!(text.contains("hidden()"));
+        assert_eq!(report.to_json()["scripts"][0]["functions"][0]["count"], 1);
+    }
+}
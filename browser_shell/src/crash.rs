@@ -0,0 +1,197 @@
+//! # Crash Handling
+//!
+//! This module contains a panic in one tab's pipeline so the rest of the
+//! shell keeps running. The crashed tab shows a "tab crashed" page with a
+//! reload button, and a crash report is written to disk for debugging.
+//!
+//! ## Design Principles
+//!
+//! 1. **Contain, Then Replace**: A tab that panicked may be left in any
+//!    state, so it is never used again; a fresh engine takes its place.
+//! 2. **Report Everything**: The report has the panic message and location,
+//!    a backtrace, the URL and the pipeline phase, which is usually enough
+//!    to reproduce the crash.
+//! 3. **Quiet Tabs**: Panics caught here are reported once, as a crash,
+//!    not also by the default panic hook.
+
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::cell::{Cell, RefCell};
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Once;
+use std::time::{SystemTime, UNIX_EPOCH};
+use css_parser::Stylesheet;
+use crate::error::{escape_html, EnginePhase};
+use crate::session::TabState;
+
+/// Directory of the profile crash reports are written to
+pub const CRASH_DIR: &str = "crashes";
+
+/// What went wrong when a tab crashed
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrashReport {
+    /// The panic message
+    pub message: String,
+    /// Source location of the panic, e.g. `layout/src/lib.rs:120:9`
+    pub location: Option<String>,
+    pub backtrace: String,
+    /// URL of the tab's document, if it was loaded from the network
+    pub url: Option<String>,
+    /// The pipeline phase the tab was in
+    pub phase: EnginePhase,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+}
+
+impl CrashReport {
+    /// Format the report as the text written to disk
+    pub fn to_text(&self) -> String {
+        let mut text = String::from("Tab crash report\n");
+        text.push_str(&format!("Time: {}\n", self.timestamp));
+        text.push_str(&format!("Phase: {}\n", self.phase));
+        text.push_str(&format!("URL: {}\n", self.url.as_deref().unwrap_or("(local document)")));
+        text.push_str(&format!("Message: {}\n", self.message));
+        if let Some(location) = &self.location {
+            text.push_str(&format!("Location: {}\n", location));
+        }
+        text.push_str("\nBacktrace:\n");
+        text.push_str(&self.backtrace);
+        text.push('\n');
+        text
+    }
+
+    /// Write the report to a new file in `dir`
+    pub fn write_to(&self, dir: &Path) -> io::Result<PathBuf> {
+        fs::create_dir_all(dir)?;
+        let mut path = dir.join(format!("crash-{}.txt", self.timestamp));
+        let mut n = 1;
+        while path.exists() {
+            path = dir.join(format!("crash-{}-{}.txt", self.timestamp, n));
+            n += 1;
+        }
+        fs::write(&path, self.to_text())?;
+        Ok(path)
+    }
+
+    /// Render the "tab crashed" page shown in place of the document
+    pub fn crash_page(&self, report_path: Option<&Path>) -> String {
+        let report = match report_path {
+            Some(path) => format!("<p class=\"report\">A crash report was saved to {}</p>", escape_html(&path.display().to_string())),
+            None => String::new(),
+        };
+        format!(
+            "<html><head><title>Tab crashed</title></head><body><h1>This tab crashed</h1><p>The page stopped working during {}. Other tabs are not affected.</p><p class=\"message\">{}</p>{}<button id=\"reload\">Reload</button></body></html>",
+            escape_html(&self.phase.to_string()),
+            escape_html(&self.message),
+            report
+        )
+    }
+}
+
+/// Everything needed to reload a tab that crashed
+#[derive(Debug, Clone)]
+pub struct CrashRecovery {
+    pub report: CrashReport,
+    /// Where the report was written, if it was
+    pub report_path: Option<PathBuf>,
+    /// History, scroll offset and form values of the tab before the crash
+    pub state: TabState,
+    /// Source of the tab's document, for documents not loaded from a URL
+    pub source: Option<String>,
+    pub stylesheet: Option<Stylesheet>,
+}
+
+thread_local! {
+    /// Whether a panic on this thread is being caught by `catch_crash`
+    static CATCHING: Cell<bool> = const { Cell::new(false) };
+    /// Message, location and backtrace of the last caught panic
+    static LAST_PANIC: RefCell<Option<(String, Option<String>, String)>> = const { RefCell::new(None) };
+}
+
+static INSTALL_HOOK: Once = Once::new();
+
+/// Run `f`, turning a panic into a crash report
+///
+/// `phase` and `url` describe what the tab was doing, for the report.
+pub fn catch_crash<T>(phase: EnginePhase, url: Option<&str>, f: impl FnOnce() -> T) -> Result<T, CrashReport> {
+    install_panic_hook();
+    let catching = CATCHING.with(|catching| catching.replace(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING.with(|c| c.set(catching));
+    result.map_err(|payload| {
+        let (message, location, backtrace) = LAST_PANIC
+            .with(|last| last.borrow_mut().take())
+            .unwrap_or_else(|| (panic_message(payload.as_ref()), None, String::new()));
+        CrashReport {
+            message,
+            location,
+            backtrace,
+            url: url.map(str::to_string),
+            phase,
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+        }
+    })
+}
+
+/// Install a panic hook that records panics caught by `catch_crash` and
+/// leaves every other panic to the previous hook
+fn install_panic_hook() {
+    INSTALL_HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !CATCHING.with(Cell::get) {
+                previous(info);
+                return;
+            }
+            let message = panic_message(info.payload());
+            let location = info.location().map(|location| location.to_string());
+            let backtrace = Backtrace::force_capture().to_string();
+            LAST_PANIC.with(|last| *last.borrow_mut() = Some((message, location, backtrace)));
+        }));
+    });
+}
+
+/// Get the message a panic was raised with
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temporary_profile;
+
+    #[test]
+    fn test_catch_crash_reports_panics() {
+        assert_eq!(catch_crash(EnginePhase::Layout, None, || 7), Ok(7));
+
+        let report = catch_crash(EnginePhase::Layout, Some("https://example.com/"), || -> u32 {
+            panic!("box tree is cyclic")
+        })
+        .unwrap_err();
+        assert_eq!(report.message, "box tree is cyclic");
+        assert!(report.location.as_deref().unwrap().contains("crash.rs"));
+        assert!(!report.backtrace.is_empty());
+
+        let dir = temporary_profile("crash");
+        let first = report.write_to(&dir).unwrap();
+        let second = report.write_to(&dir).unwrap();
+        assert_ne!(first, second);
+        let text = fs::read_to_string(&first).unwrap();
+        assert!(text.contains("Phase: layout"));
+        assert!(text.contains("URL: https://example.com/"));
+        assert!(text.contains("Message: box tree is cyclic"));
+
+        let page = report.crash_page(Some(&first));
+        assert!(page.contains("<button id=\"reload\">"));
+        assert!(page.contains("during layout"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
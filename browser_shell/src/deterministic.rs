@@ -0,0 +1,132 @@
+//! # Deterministic Mode
+//!
+//! This module puts a tab in deterministic mode, for reftests and JS tests
+//! that must give the same results on every machine and every run: time
+//! comes from a `ManualClock` that only moves when the test advances it,
+//! layout uses the bundled test font alone, `Math.random()` is seeded, and
+//! text is rasterized without platform-dependent antialiasing.
+//!
+//! ## Design Principles
+//!
+//! 1. **Virtual Time**: Timers, animation frames, `performance.now()` and
+//!    `Date` all read the manual clock, and `run_virtual_time` jumps it from
+//!    one due timer or frame to the next, so a page's 5-second animation is
+//!    tested instantly and with the same frames every time.
+//! 2. **One Switch**: `DUBBY_DETERMINISTIC` or `--deterministic` turns
+//!    everything on together; a value other than `1` is the random seed.
+//! 3. **Nothing Changes Otherwise**: Without the mode the engine reads the
+//!    system clock and fonts as before.
+
+use std::sync::Arc;
+use std::time::Duration;
+use dom::clock::{self, Clock, ManualClock};
+use js_integration::{JsEngine, JsResult};
+
+/// Environment variable turning deterministic mode on (`1`, or a seed)
+pub const DETERMINISTIC_ENV: &str = "DUBBY_DETERMINISTIC";
+
+/// Seed of `Math.random()` unless one is given
+pub const DEFAULT_RANDOM_SEED: u64 = 0x5EED;
+
+/// Most event loop iterations `run_virtual_time` runs, so an interval of
+/// zero cannot spin forever
+const MAX_VIRTUAL_TIME_STEPS: usize = 10_000;
+
+/// The clock and seed of a deterministic tab
+#[derive(Debug, Clone)]
+pub struct DeterministicMode {
+    pub clock: ManualClock,
+    pub random_seed: u64,
+}
+
+impl Default for DeterministicMode {
+    fn default() -> Self {
+        Self::new(DEFAULT_RANDOM_SEED)
+    }
+}
+
+impl DeterministicMode {
+    pub fn new(random_seed: u64) -> Self {
+        DeterministicMode { clock: ManualClock::new(), random_seed }
+    }
+
+    /// Get the mode `DUBBY_DETERMINISTIC` asks for, if any
+    pub fn from_env() -> Option<Self> {
+        Self::parse(&std::env::var(DETERMINISTIC_ENV).ok()?)
+    }
+
+    /// Parse the value of `DUBBY_DETERMINISTIC`: a flag, or the seed to
+    /// use
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if let Some(enabled) = crate::appearance::parse_flag(value) {
+            return enabled.then(Self::default);
+        }
+        value.parse().ok().map(Self::new)
+    }
+
+    /// Make the mode's clock the one the engine reads on this thread
+    pub fn install(&self) {
+        clock::set_clock(Arc::new(self.clock.clone()));
+    }
+}
+
+/// Run a script engine's event loop for `duration` of virtual time
+///
+/// The clock jumps to each due timer, animation frame or idle period in
+/// turn, and ends `duration` after it started. Returns the number of tasks
+/// run.
+pub fn run_virtual_time(script: &mut JsEngine, clock: &ManualClock, duration: Duration) -> JsResult<usize> {
+    let end = clock.now() + duration;
+    let mut tasks_run = 0;
+    for _ in 0..MAX_VIRTUAL_TIME_STEPS {
+        tasks_run += script.process_event_loop()?.tasks_run;
+        match script.event_loop().next_wakeup(clock.now()) {
+            Some(wakeup) if wakeup <= end => clock.advance_to(wakeup),
+            _ => break,
+        }
+    }
+    clock.advance_to(end);
+    Ok(tasks_run)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BrowserEngine;
+
+    /// What a page's scripts saw, as its text
+    fn run_page(seed: &str) -> String {
+        let mode = DeterministicMode::parse(seed).unwrap();
+        let mut engine = BrowserEngine::new();
+        engine.set_deterministic_mode(Some(mode.clone()));
+        assert!(engine.load_html("<p id='out'></p>"));
+        let mut script = engine.create_script_engine().unwrap();
+        script
+            .execute("var out = []; var start = performance.now(); out.push(Math.random().toFixed(6), Date.now());")
+            .unwrap();
+        let timers = [("out.push('t' + (performance.now() - start))", 250), ("out.push('late')", 5000)];
+        for (callback, delay) in timers {
+            script.event_loop().set_timer(callback.to_string(), Duration::from_millis(delay), false, clock::now());
+        }
+        run_virtual_time(&mut script, &mode.clock, Duration::from_secs(1)).unwrap();
+        let result = script.execute("out.join(' ')").unwrap().as_string().unwrap().to_std_string_escaped();
+        engine.set_deterministic_mode(None);
+        result
+    }
+
+    #[test]
+    fn test_deterministic_runs_repeat() {
+        assert!(DeterministicMode::parse("0").is_none());
+        assert_eq!(DeterministicMode::parse("42").unwrap().random_seed, 42);
+
+        let first = run_page("1");
+        assert_eq!(first, run_page("on"));
+        assert_ne!(first, run_page("7"));
+        // A second of virtual time ran the first timer on time, not the
+        // second, and Date started at the manual clock's epoch
+        let words: Vec<&str> = first.split(' ').collect();
+        assert_eq!(words[1..], ["946684800000", "t250"]);
+        assert!(clock::uses_system_clock());
+    }
+}
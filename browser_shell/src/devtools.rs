@@ -0,0 +1,140 @@
+//! # Devtools Console
+//!
+//! This module runs the devtools console: a REPL attached to the page of
+//! a tab, used by the shell's `console` command and by `browser_shell repl
+//! <url>`.
+//!
+//! ## Design Principles
+//!
+//! 1. **A Live Page**: The console gets a script engine for the tab's
+//!    document and runs the page's inline scripts first, so input sees the
+//!    state the page set up.
+//! 2. **Dot Commands**: Lines starting with `.` drive the console itself
+//!    (`.select`, `.expand`, `.coverage`, `.profile`, `.exit`); everything else is
+//!    JavaScript.
+//! 3. **Testable Loop**: The loop reads from any `BufRead` and writes to
+//!    any `Write`, so it is driven by stdin in the shell and by strings in
+//!    tests.
+
+use std::io::{BufRead, Write};
+use js_integration::console::ConsoleLevel;
+use js_integration::repl::ConsoleRepl;
+use crate::coverage::CoverageReport;
+use crate::profiler::{record_frames, Profiler};
+use crate::BrowserEngine;
+
+/// Print the console's help
+fn write_help(output: &mut dyn Write) -> std::io::Result<()> {
+    writeln!(output, "  <javascript>     - Evaluate in the page; $0 is the selected node, $_ the last result")?;
+    writeln!(output, "  .select <tag>    - Select the first element with a tag name as $0")?;
+    writeln!(output, "  .expand          - Show the last result as a tree")?;
+    writeln!(output, "  .coverage        - Show the unused bytes of the stylesheet and of the scripts run so far")?;
+    writeln!(output, "  .profile [n]     - Record n frames (default 10) and show them as a flame chart")?;
+    writeln!(output, "  .exit            - Leave the console")
+}
+
+/// Run the console for a tab's page until `.exit` or the end of `input`
+///
+/// The element last inspected from a context menu starts out as `$0`.
+/// Returns the number of inputs evaluated.
+pub fn run_console(engine: &BrowserEngine, input: &mut dyn BufRead, output: &mut dyn Write) -> std::io::Result<usize> {
+    let mut script = match engine.create_script_engine() {
+        Ok(script) => script,
+        Err(e) => {
+            writeln!(output, "❌ Failed to start the console: {}", e)?;
+            return Ok(0);
+        }
+    };
+    // Page scripts are instrumented for `.coverage`
+    script.coverage().start();
+    if let Err(e) = script.execute_inline_scripts() {
+        writeln!(output, "⚠️ Page scripts failed: {}", e)?;
+    }
+    let mut repl = ConsoleRepl::new();
+    // "Inspect" in a context menu opens the console on its element
+    if let Some(node) = engine.inspected_node() {
+        writeln!(output, "$0 = <{}>", node.tag_name().unwrap_or_default())?;
+        repl.select(Some(node));
+    }
+    let mut evaluated = 0;
+
+    loop {
+        write!(output, "> ")?;
+        output.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            break;
+        }
+        let line = line.trim();
+        let (command, args) = line.split_once(' ').map(|(command, args)| (command, args.trim())).unwrap_or((line, ""));
+        match command {
+            "" => {}
+            ".exit" => break,
+            ".help" => write_help(output)?,
+            ".expand" => writeln!(output, "{}", repl.expand_last(&mut script).expanded())?,
+            ".coverage" => {
+                let report = CoverageReport {
+                    stylesheets: engine.stylesheet_coverage().into_iter().collect(),
+                    scripts: script.coverage().report(),
+                };
+                write!(output, "{}", report.to_text())?;
+            }
+            ".profile" => {
+                let frames = args.parse().unwrap_or(10);
+                let (Some(document), Some(layout_engine)) = (engine.get_document(), engine.layout_engine()) else {
+                    writeln!(output, "No page to profile")?;
+                    continue;
+                };
+                let mut profiler = Profiler::default();
+                record_frames(document, &layout_engine, &mut script, frames, false, &mut profiler);
+                write!(output, "{}{}", profiler.flame_chart(100), profiler.summary())?;
+            }
+            ".select" => {
+                let node = engine.get_document().and_then(|document| document.root.get_element_by_tag_name(args));
+                match node {
+                    Some(node) => {
+                        repl.select(Some(node));
+                        writeln!(output, "$0 = <{}>", args.to_ascii_lowercase())?;
+                    }
+                    None => writeln!(output, "No <{}> element", args)?,
+                }
+            }
+            _ => {
+                let result = repl.evaluate(&mut script, line);
+                evaluated += 1;
+                // Log and info messages were echoed as they arrived; the
+                // stream is repeated with levels for warnings and errors
+                for message in result.messages.iter().filter(|message| matches!(message.level, ConsoleLevel::Warn | ConsoleLevel::Error)) {
+                    writeln!(output, "  {}", message)?;
+                }
+                writeln!(output, "{}", result)?;
+            }
+        }
+    }
+    Ok(evaluated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_console_evaluates_in_the_page() {
+        let mut engine = BrowserEngine::new();
+        assert!(engine.load_html("<html><body><h1 id=\"title\">Hi</h1><script>var greeting = 'hello';</script></body></html>"));
+        assert!(engine.load_css("h1 { color: red }"));
+
+        let mut input = "greeting.toUpperCase()\n.select h1\n$0\n[1, {a: 2}]\n.expand\nconsole.warn('careful')\n.coverage\n.profile 2\n.exit\nnot evaluated\n".as_bytes();
+        let mut output = Vec::new();
+        assert_eq!(run_console(&engine, &mut input, &mut output).unwrap(), 4);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("← \"HELLO\""));
+        assert!(output.contains("$0 = <h1>"));
+        assert!(output.contains("← <h1 id=\"title\">"));
+        assert!(output.contains("← Array(2) [1, {…}]"));
+        assert!(output.contains("▼ 1: {a: 2}"));
+        assert!(output.contains("[warn] careful"));
+        assert!(output.contains("inline script #1"));
+        assert!(output.contains("Frame 2"));
+    }
+}
@@ -0,0 +1,200 @@
+//! # Downloads
+//!
+//! This module saves resources to the user's download directory, such as
+//! the images saved from the context menu, and keeps the list of
+//! downloads of the session.
+//!
+//! ## Design Principles
+//!
+//! 1. **Never Overwrite**: A file is saved under the last segment of its
+//!    URL; if that name is taken, ` (1)`, ` (2)`... is added before the
+//!    extension, as other browsers do.
+//! 2. **Shared by Tabs**: Clones of a manager share one download list, so
+//!    every tab reports to the same place.
+//! 3. **Failures Are Kept**: A download that fails stays in the list with
+//!    its error, so the user can see what was not saved.
+
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use networking::{HttpClient, HttpRequest, NetworkError};
+use thiserror::Error;
+
+/// Name given to downloads whose URL has no file name
+pub const DEFAULT_FILE_NAME: &str = "download";
+
+/// Custom error types for downloads
+#[derive(Error, Debug)]
+pub enum DownloadError {
+    #[error("Download I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Download failed: {0}")]
+    Network(#[from] NetworkError),
+
+    #[error("Download failed: HTTP {0}")]
+    Status(u16),
+}
+
+/// Result type for download operations
+pub type DownloadResult<T> = Result<T, DownloadError>;
+
+/// Where a download stands
+#[derive(Debug, Clone, PartialEq)]
+pub enum DownloadState {
+    InProgress,
+    /// Saved, with the number of bytes written
+    Complete(usize),
+    Failed(String),
+}
+
+/// A download of the session
+#[derive(Debug, Clone, PartialEq)]
+pub struct Download {
+    pub id: u64,
+    pub url: String,
+    /// The file the download is saved to, once it is known
+    pub path: Option<PathBuf>,
+    pub state: DownloadState,
+}
+
+/// Saves resources to a download directory
+#[derive(Debug, Clone)]
+pub struct DownloadManager {
+    dir: PathBuf,
+    downloads: Rc<RefCell<Vec<Download>>>,
+}
+
+impl Default for DownloadManager {
+    /// Save to `Downloads` in the home directory, or the temporary
+    /// directory if there is no home
+    fn default() -> Self {
+        let home = std::env::var_os("HOME").map(PathBuf::from).unwrap_or_else(std::env::temp_dir);
+        Self::new(home.join("Downloads"))
+    }
+}
+
+impl DownloadManager {
+    /// Create a manager saving to `dir`, which is created on the first
+    /// download
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        DownloadManager { dir: dir.into(), downloads: Rc::new(RefCell::new(Vec::new())) }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Get the downloads of the session, oldest first
+    pub fn downloads(&self) -> Vec<Download> {
+        self.downloads.borrow().clone()
+    }
+
+    /// Fetch a URL and save the response body
+    ///
+    /// Returns the path of the saved file.
+    pub async fn download(&self, client: &HttpClient, url: &str) -> DownloadResult<PathBuf> {
+        let id = self.start(url);
+        let body = match client.send_request(HttpRequest::get(url.to_string())).await {
+            Ok(response) if response.status.is_success() => Ok(response.body),
+            Ok(response) => Err(DownloadError::Status(response.status_code)),
+            Err(e) => Err(e.into()),
+        };
+        self.finish(id, body.and_then(|body| self.write(url, &body)))
+    }
+
+    /// Save bytes that are already in memory, such as a decoded image
+    pub fn save(&self, url: &str, bytes: &[u8]) -> DownloadResult<PathBuf> {
+        let id = self.start(url);
+        self.finish(id, self.write(url, bytes))
+    }
+
+    fn start(&self, url: &str) -> u64 {
+        let mut downloads = self.downloads.borrow_mut();
+        let id = downloads.len() as u64 + 1;
+        downloads.push(Download { id, url: url.to_string(), path: None, state: DownloadState::InProgress });
+        id
+    }
+
+    fn finish(&self, id: u64, result: DownloadResult<(PathBuf, usize)>) -> DownloadResult<PathBuf> {
+        let mut downloads = self.downloads.borrow_mut();
+        let Some(download) = downloads.iter_mut().find(|download| download.id == id) else {
+            return result.map(|(path, _)| path);
+        };
+        match result {
+            Ok((path, written)) => {
+                download.path = Some(path.clone());
+                download.state = DownloadState::Complete(written);
+                Ok(path)
+            }
+            Err(e) => {
+                download.state = DownloadState::Failed(e.to_string());
+                Err(e)
+            }
+        }
+    }
+
+    fn write(&self, url: &str, bytes: &[u8]) -> DownloadResult<(PathBuf, usize)> {
+        fs::create_dir_all(&self.dir)?;
+        let path = unique_path(&self.dir, &file_name(url));
+        fs::write(&path, bytes)?;
+        Ok((path, bytes.len()))
+    }
+}
+
+/// Get the file name a URL is saved under
+pub fn file_name(url: &str) -> String {
+    let path = url::Url::parse(url).map(|url| url.path().to_string()).unwrap_or_else(|_| url.to_string());
+    let name = path.rsplit('/').next().unwrap_or_default();
+    let name = percent_encoding::percent_decode_str(name).decode_utf8_lossy();
+    // Keep names inside the download directory
+    let name: String = name.chars().filter(|c| !matches!(c, '/' | '\\' | '\0')).collect();
+    match name.trim() {
+        "" | "." | ".." => DEFAULT_FILE_NAME.to_string(),
+        name => name.to_string(),
+    }
+}
+
+/// Find a path in `dir` for `name` that no file uses yet
+fn unique_path(dir: &Path, name: &str) -> PathBuf {
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+        _ => (name, String::new()),
+    };
+    let mut path = dir.join(name);
+    let mut copy = 1;
+    while path.exists() {
+        path = dir.join(format!("{} ({}){}", stem, copy, extension));
+        copy += 1;
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temporary_profile;
+
+    #[test]
+    fn test_downloads_never_overwrite() {
+        let dir = temporary_profile("downloads");
+        let manager = DownloadManager::new(&dir);
+        let shared = manager.clone();
+
+        assert_eq!(file_name("https://example.com/img/cat%20photo.png?size=2"), "cat photo.png");
+        assert_eq!(file_name("https://example.com/"), DEFAULT_FILE_NAME);
+
+        let first = manager.save("https://example.com/cat.png", b"meow").unwrap();
+        let second = shared.save("https://example.org/cat.png", b"purr").unwrap();
+        assert_eq!(first, dir.join("cat.png"));
+        assert_eq!(second, dir.join("cat (1).png"));
+        assert_eq!(fs::read(&second).unwrap(), b"purr");
+
+        let downloads = manager.downloads();
+        assert_eq!(downloads.len(), 2);
+        assert_eq!(downloads[1].state, DownloadState::Complete(4));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,270 @@
+//! # Engine Errors
+//!
+//! This module wraps the error types of every engine component in one
+//! `EngineError`, so the shell reports any failure the same way.
+//!
+//! ## Design Principles
+//!
+//! 1. **One Type**: Parser, CSS, network, render, script and security
+//!    errors convert into `EngineError` with `?`. The component error is
+//!    kept as the source, so nothing is lost in the conversion.
+//!
+//! 2. **Context**: Each error records the phase it happened in, and the
+//!    URL and byte offset when they are known.
+//!
+//! 3. **Structured Output**: An error renders as a single console entry
+//!    with its source chain, or as an HTML error page.
+
+use css_parser::CSSError;
+use dom::SecurityError;
+use html_parser::ParseError;
+use js_integration::JsIntegrationError;
+use networking::NetworkError;
+use renderer_wgpu::RenderError;
+use std::error::Error;
+use std::fmt;
+use thiserror::Error;
+
+/// The stage of loading a page in which an error happened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnginePhase {
+    Navigation,
+    Network,
+    HtmlParsing,
+    CssParsing,
+    Layout,
+    Scripting,
+    Rendering,
+}
+
+impl fmt::Display for EnginePhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            EnginePhase::Navigation => "navigation",
+            EnginePhase::Network => "network",
+            EnginePhase::HtmlParsing => "HTML parsing",
+            EnginePhase::CssParsing => "CSS parsing",
+            EnginePhase::Layout => "layout",
+            EnginePhase::Scripting => "scripting",
+            EnginePhase::Rendering => "rendering",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The component error behind an `EngineError`
+#[derive(Error, Debug)]
+pub enum EngineErrorKind {
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    #[error(transparent)]
+    Css(#[from] CSSError),
+    #[error(transparent)]
+    Network(#[from] NetworkError),
+    #[error(transparent)]
+    Render(#[from] RenderError),
+    #[error(transparent)]
+    Script(#[from] JsIntegrationError),
+    #[error(transparent)]
+    Security(#[from] SecurityError),
+    /// A failure in the shell itself
+    #[error("{0}")]
+    Shell(String),
+}
+
+impl EngineErrorKind {
+    /// Get the phase errors of this kind usually come from
+    fn default_phase(&self) -> EnginePhase {
+        match self {
+            EngineErrorKind::Parse(_) => EnginePhase::HtmlParsing,
+            EngineErrorKind::Css(_) => EnginePhase::CssParsing,
+            EngineErrorKind::Network(_) => EnginePhase::Network,
+            EngineErrorKind::Render(_) => EnginePhase::Rendering,
+            EngineErrorKind::Script(_) => EnginePhase::Scripting,
+            EngineErrorKind::Security(_) | EngineErrorKind::Shell(_) => EnginePhase::Navigation,
+        }
+    }
+
+    /// Get the position the component reported, if any
+    fn byte_offset(&self) -> Option<usize> {
+        match self {
+            EngineErrorKind::Parse(ParseError::ParseError(offset, _))
+            | EngineErrorKind::Css(CSSError::ParseError(offset, _)) if *offset > 0 => Some(*offset),
+            _ => None,
+        }
+    }
+}
+
+/// An error from any engine component, with where it happened
+#[derive(Debug)]
+pub struct EngineError {
+    // Boxed so results carrying an `EngineError` stay small
+    kind: Box<EngineErrorKind>,
+    phase: EnginePhase,
+    url: Option<String>,
+    byte_offset: Option<usize>,
+}
+
+impl EngineError {
+    /// Create an error in a phase
+    pub fn new(phase: EnginePhase, kind: impl Into<EngineErrorKind>) -> Self {
+        let kind = kind.into();
+        EngineError {
+            byte_offset: kind.byte_offset(),
+            kind: Box::new(kind),
+            phase,
+            url: None,
+        }
+    }
+
+    /// Create an error raised by the shell itself
+    pub fn shell(phase: EnginePhase, message: impl Into<String>) -> Self {
+        Self::new(phase, EngineErrorKind::Shell(message.into()))
+    }
+
+    /// Record the URL being loaded
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.url = Some(url.into());
+        self
+    }
+
+    /// Record the byte offset in the resource
+    pub fn with_byte_offset(mut self, offset: usize) -> Self {
+        self.byte_offset = Some(offset);
+        self
+    }
+
+    /// Record the phase, replacing the one implied by the component
+    pub fn in_phase(mut self, phase: EnginePhase) -> Self {
+        self.phase = phase;
+        self
+    }
+
+    pub fn kind(&self) -> &EngineErrorKind {
+        &self.kind
+    }
+
+    pub fn phase(&self) -> EnginePhase {
+        self.phase
+    }
+
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    pub fn byte_offset(&self) -> Option<usize> {
+        self.byte_offset
+    }
+
+    /// Get the messages of the error and every error that caused it
+    pub fn chain(&self) -> Vec<String> {
+        let mut messages = vec![self.kind.to_string()];
+        let mut source = self.kind.source();
+        while let Some(error) = source {
+            messages.push(error.to_string());
+            source = error.source();
+        }
+        messages
+    }
+
+    /// Format the error as a single console entry
+    pub fn console_entry(&self) -> String {
+        let mut entry = format!("❌ {}", self);
+        for cause in self.chain().iter().skip(1) {
+            entry.push_str(&format!("\n   caused by: {}", cause));
+        }
+        entry
+    }
+
+    /// Render the error as an HTML error page
+    pub fn error_page(&self) -> String {
+        let mut details = format!("<li>Phase: {}</li>", escape_html(&self.phase.to_string()));
+        if let Some(url) = &self.url {
+            details.push_str(&format!("<li>URL: {}</li>", escape_html(url)));
+        }
+        if let Some(offset) = self.byte_offset {
+            details.push_str(&format!("<li>Byte offset: {}</li>", offset));
+        }
+        let causes: String = self
+            .chain()
+            .iter()
+            .map(|message| format!("<li>{}</li>", escape_html(message)))
+            .collect();
+        format!(
+            "<html><head><title>Error</title></head><body><h1>This page could not be loaded</h1><ul class=\"details\">{}</ul><ol class=\"causes\">{}</ol></body></html>",
+            details, causes
+        )
+    }
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} failed", self.phase)?;
+        if let Some(url) = &self.url {
+            write!(f, " for {}", url)?;
+        }
+        if let Some(offset) = self.byte_offset {
+            write!(f, " at byte {}", offset)?;
+        }
+        write!(f, ": {}", self.kind)
+    }
+}
+
+impl Error for EngineError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.kind.as_ref())
+    }
+}
+
+impl From<ParseError> for EngineError {
+    fn from(error: ParseError) -> Self {
+        let kind = EngineErrorKind::from(error);
+        EngineError::new(kind.default_phase(), kind)
+    }
+}
+
+impl From<CSSError> for EngineError {
+    fn from(error: CSSError) -> Self {
+        let kind = EngineErrorKind::from(error);
+        EngineError::new(kind.default_phase(), kind)
+    }
+}
+
+impl From<NetworkError> for EngineError {
+    fn from(error: NetworkError) -> Self {
+        let kind = EngineErrorKind::from(error);
+        EngineError::new(kind.default_phase(), kind)
+    }
+}
+
+impl From<RenderError> for EngineError {
+    fn from(error: RenderError) -> Self {
+        let kind = EngineErrorKind::from(error);
+        EngineError::new(kind.default_phase(), kind)
+    }
+}
+
+impl From<JsIntegrationError> for EngineError {
+    fn from(error: JsIntegrationError) -> Self {
+        let kind = EngineErrorKind::from(error);
+        EngineError::new(kind.default_phase(), kind)
+    }
+}
+
+impl From<SecurityError> for EngineError {
+    fn from(error: SecurityError) -> Self {
+        let kind = EngineErrorKind::from(error);
+        EngineError::new(kind.default_phase(), kind)
+    }
+}
+
+/// Result type for engine operations
+pub type EngineResult<T> = Result<T, EngineError>;
+
+/// Escape text for HTML content and attribute values
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
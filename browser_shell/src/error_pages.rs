@@ -0,0 +1,280 @@
+//! # Error Pages
+//!
+//! This module builds the pages a tab shows in place of a document it could
+//! not show: network failures, TLS errors, responses that are not
+//! documents, and crashed tabs. Each page explains what happened, lists the
+//! details of the `EngineError` or crash report, and has a retry button.
+//!
+//! ## Design Principles
+//!
+//! 1. **Built, Not Parsed**: Like the internal pages, error pages are DOM
+//!    trees assembled in Rust, so the failing URL and error messages never
+//!    need escaping and cannot break the page.
+//! 2. **The Error Picks the Template**: The kind of failure is read from
+//!    the error itself, so every caller that has an `EngineError` gets the
+//!    same page for the same failure.
+//! 3. **Retry Is a Navigation**: The retry button loads the failing URL
+//!    again through the tab's normal navigation, so a retry is recorded in
+//!    history and uses the caches like any other load.
+
+use std::fmt;
+use std::path::Path;
+use dom::Document;
+use networking::NetworkError;
+use crate::about::PageBuilder;
+use crate::crash::CrashReport;
+use crate::error::{EngineError, EngineErrorKind};
+
+/// `id` of the retry button of every error page
+pub const RETRY_BUTTON_ID: &str = "retry";
+
+/// Stylesheet of the error pages
+pub const ERROR_STYLESHEET: &str = r#"
+body { font-family: sans-serif; margin: 48px; color: #3c4043; background-color: #ffffff; }
+h1 { font-size: 28px; margin-bottom: 16px; }
+p { margin-bottom: 12px; }
+th { font-weight: bold; padding-right: 24px; }
+td { font-family: monospace; }
+li { font-family: monospace; margin-bottom: 4px; }
+button { padding: 8px; margin-top: 16px; }
+"#;
+
+/// The kind of failure an error page explains
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorPageKind {
+    /// The server could not be reached or answered with an error
+    Network,
+    /// The secure connection could not be established
+    Tls,
+    /// The response is not a document the browser can show
+    UnsupportedContent,
+    /// The tab's pipeline panicked
+    Crashed,
+    /// Any other failure, such as a blocked navigation
+    Failed,
+}
+
+impl ErrorPageKind {
+    /// Get the kind of page for an error
+    pub fn for_error(error: &EngineError) -> Self {
+        match error.kind() {
+            EngineErrorKind::Network(NetworkError::SslError(_)) => ErrorPageKind::Tls,
+            EngineErrorKind::Network(NetworkError::UnsupportedContentType(_)) => ErrorPageKind::UnsupportedContent,
+            EngineErrorKind::Network(_) => ErrorPageKind::Network,
+            _ => ErrorPageKind::Failed,
+        }
+    }
+
+    /// Get the name of the kind, used as the class of the page's body
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorPageKind::Network => "network",
+            ErrorPageKind::Tls => "tls",
+            ErrorPageKind::UnsupportedContent => "unsupported-content",
+            ErrorPageKind::Crashed => "crashed",
+            ErrorPageKind::Failed => "failed",
+        }
+    }
+
+    /// Get the title and heading of the page
+    pub fn title(&self) -> &'static str {
+        match self {
+            ErrorPageKind::Network => "This site can't be reached",
+            ErrorPageKind::Tls => "Your connection is not secure",
+            ErrorPageKind::UnsupportedContent => "This file can't be shown",
+            ErrorPageKind::Crashed => "This tab crashed",
+            ErrorPageKind::Failed => "This page could not be loaded",
+        }
+    }
+
+    /// Get the label of the retry button
+    pub fn retry_label(&self) -> &'static str {
+        match self {
+            ErrorPageKind::Crashed => "Reload",
+            _ => "Try again",
+        }
+    }
+}
+
+impl fmt::Display for ErrorPageKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// An error page, before it is built into a document
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorPage {
+    pub kind: ErrorPageKind,
+    /// The URL that failed, which the retry button loads again
+    pub url: Option<String>,
+    /// What the user can do about the failure
+    pub explanation: String,
+    /// Names and values of what is known about the failure
+    pub details: Vec<(&'static str, String)>,
+    /// Messages of the error and each error that caused it
+    pub causes: Vec<String>,
+}
+
+impl ErrorPage {
+    /// Describe a failure to load a page
+    pub fn for_error(error: &EngineError) -> Self {
+        let kind = ErrorPageKind::for_error(error);
+        let explanation = match kind {
+            ErrorPageKind::Network => match error.kind() {
+                EngineErrorKind::Network(NetworkError::Timeout { .. }) => "The server took too long to respond. Check your connection, then try again.".to_string(),
+                EngineErrorKind::Network(NetworkError::HttpError { status, .. }) => format!("The server answered with status {}. The page may have moved, or the server may be having trouble.", status),
+                _ => "The server could not be reached. Check the address and your connection, then try again.".to_string(),
+            },
+            ErrorPageKind::Tls => "The site's certificate could not be verified, so the page was not loaded. Someone may be trying to intercept your connection.".to_string(),
+            ErrorPageKind::UnsupportedContent => "The server sent a file the browser cannot show as a page.".to_string(),
+            ErrorPageKind::Crashed | ErrorPageKind::Failed => format!("Something went wrong during {}.", error.phase()),
+        };
+        let mut details = vec![("Phase", error.phase().to_string())];
+        if let Some(url) = error.url() {
+            details.push(("URL", url.to_string()));
+        }
+        if let Some(offset) = error.byte_offset() {
+            details.push(("Byte offset", offset.to_string()));
+        }
+        ErrorPage {
+            kind,
+            url: error.url().map(String::from),
+            explanation,
+            details,
+            causes: error.chain(),
+        }
+    }
+
+    /// Describe a crashed tab
+    ///
+    /// `report_path` is where the crash report was written, if it was.
+    pub fn for_crash(report: &CrashReport, report_path: Option<&Path>) -> Self {
+        let mut details = vec![("Phase", report.phase.to_string())];
+        if let Some(url) = &report.url {
+            details.push(("URL", url.clone()));
+        }
+        if let Some(location) = &report.location {
+            details.push(("Location", location.clone()));
+        }
+        if let Some(path) = report_path {
+            details.push(("Crash report", path.display().to_string()));
+        }
+        ErrorPage {
+            kind: ErrorPageKind::Crashed,
+            url: report.url.clone(),
+            explanation: format!("The page stopped working during {}. Other tabs are not affected.", report.phase),
+            details,
+            causes: vec![report.message.clone()],
+        }
+    }
+
+    /// Build the page's document
+    pub fn build(&self) -> Document {
+        let builder = PageBuilder::with_title(self.kind.title());
+        builder.body.set_attribute("class", &format!("error-page {}", self.kind));
+        let explanation = builder.text_element("p", &self.explanation);
+        explanation.set_attribute("class", "explanation");
+        builder.body.append_child(&explanation);
+        builder.table("details", &self.details);
+
+        let causes = builder.document.create_element("ol");
+        causes.set_attribute("id", "causes");
+        for cause in &self.causes {
+            causes.append_child(&builder.text_element("li", cause));
+        }
+        builder.body.append_child(&causes);
+
+        let retry = builder.text_element("button", self.kind.retry_label());
+        retry.set_attribute("id", RETRY_BUTTON_ID);
+        retry.set_attribute("type", "button");
+        if let Some(url) = &self.url {
+            retry.set_attribute("data-url", url);
+        }
+        builder.body.append_child(&retry);
+        builder.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::EnginePhase;
+    use crate::{BrowserCLI, BrowserEngine};
+    use networking::{HttpMethod, InterceptedResponse, NetworkResult, RequestInterceptor};
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use url::Url;
+
+    /// Serves a PNG for `/logo.png`, and a page for `/` once it is up
+    struct FlakyServer {
+        up: AtomicBool,
+    }
+
+    impl RequestInterceptor for FlakyServer {
+        fn intercept(&self, _method: &HttpMethod, url: &Url) -> Option<NetworkResult<InterceptedResponse>> {
+            let (content_type, body) = match url.path() {
+                "/logo.png" => ("image/png", b"\x89PNG".to_vec()),
+                _ if self.up.load(Ordering::SeqCst) => ("text/html", b"<html><body><p>Back online</p></body></html>".to_vec()),
+                _ => return Some(Err(NetworkError::ConnectionFailed("connection refused".to_string()))),
+            };
+            Some(Ok(InterceptedResponse {
+                status_code: 200,
+                headers: HashMap::from([("content-type".to_string(), content_type.to_string())]),
+                body,
+                url: url.to_string(),
+                delay: Duration::ZERO,
+            }))
+        }
+    }
+
+    #[test]
+    fn test_error_pages_explain_failures_and_retry() {
+        let tls = EngineError::from(NetworkError::SslError("invalid peer certificate: Expired".to_string())).with_url("https://expired.example/");
+        assert_eq!(ErrorPageKind::for_error(&tls), ErrorPageKind::Tls);
+        let blocked = EngineError::shell(EnginePhase::Navigation, "No such internal page");
+        assert_eq!(ErrorPageKind::for_error(&blocked), ErrorPageKind::Failed);
+
+        let server = Arc::new(FlakyServer { up: AtomicBool::new(false) });
+        let mut engine = BrowserEngine::new();
+        engine.set_request_interceptor(Arc::clone(&server) as Arc<dyn RequestInterceptor>);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        // A response that is not a document gets its own page
+        assert!(!runtime.block_on(engine.fetch_url("http://example.com/logo.png")));
+        assert!(engine.show_error_page());
+        assert_eq!(engine.error_page().unwrap().kind, ErrorPageKind::UnsupportedContent);
+        assert!(engine.get_text_content().contains("Unsupported content type: image/png"));
+
+        // The failing URL and the error's causes are shown as text
+        assert!(!runtime.block_on(engine.fetch_url("http://example.com/")));
+        assert!(engine.show_error_page());
+        let document = engine.get_document().unwrap();
+        assert_eq!(document.root.query_selector("body").unwrap().unwrap().get_attribute("class").as_deref(), Some("error-page network"));
+        assert!(document.root.query_selector("#details").unwrap().unwrap().text_content().contains("http://example.com/"));
+        assert!(document.root.query_selector("#causes li").unwrap().unwrap().text_content().contains("connection refused"));
+        assert!(engine.perform_layout());
+
+        // The retry button navigates to the failing URL again
+        server.up.store(true, Ordering::SeqCst);
+        assert!(engine.click_element(RETRY_BUTTON_ID));
+        let retry = engine.take_link_navigation().unwrap();
+        assert!(runtime.block_on(engine.fetch_url(&retry)));
+        assert!(engine.error_page().is_none());
+        assert!(engine.get_text_content().contains("Back online"));
+        assert_eq!(engine.history().0.last().map(String::as_str), Some(retry.as_str()));
+
+        // Crashed tabs get the same template and reload from the CLI
+        let mut cli = BrowserCLI::new();
+        assert!(cli.engine().load_html("<html><body><p>Article</p></body></html>"));
+        assert_eq!(cli.run_in_tab(EnginePhase::Layout, |_| -> bool { panic!("layout invariant violated") }), None);
+        let page = cli.engine().error_page().unwrap().clone();
+        assert_eq!(page.kind, ErrorPageKind::Crashed);
+        assert_eq!(page.causes, ["layout invariant violated"]);
+        assert!(cli.engine().get_text_content().contains("This tab crashed"));
+        assert!(cli.reload_tab());
+        assert!(cli.engine().get_text_content().contains("Article"));
+    }
+}
@@ -0,0 +1,504 @@
+//! # Extensions
+//!
+//! This module loads extensions from the profile directory: each is a
+//! directory under `extensions/` with a `manifest.json` declaring content
+//! scripts and styles and the pages they apply to. Scripts are run in the
+//! page's script engine when its document starts or ends loading, and
+//! styles join the user stylesheet, so pages can be customized without
+//! rebuilding the engine.
+//!
+//! ## Design Principles
+//!
+//! 1. **Manifests Like Other Browsers'**: `content_scripts` entries take
+//!    `matches`, `exclude_matches`, `js`, `css` and `run_at` with the
+//!    meaning they have in WebExtensions, and match patterns such as
+//!    `https://*.example.com/*` and `<all_urls>` are written the same way.
+//! 2. **User Origin**: Content styles are applied at the user origin of
+//!    the cascade, after the user stylesheets, so like them they style what
+//!    the page leaves alone and need `!important` to override it.
+//! 3. **Only Changes Are Saved**: Extensions are enabled unless the user
+//!    turned them off; the settings file lists the disabled ones.
+//! 4. **Forgiving**: An extension with a broken manifest, pattern or file
+//!    is skipped with a warning; it never keeps a page from loading.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use css_parser::{CSSParser, Stylesheet};
+use js_integration::JsEngine;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Name of the extension directory in the profile directory
+pub const EXTENSIONS_DIR: &str = "extensions";
+
+/// Name of the manifest file of an extension
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Name of the extension settings file in the profile directory
+pub const EXTENSION_SETTINGS_FILE: &str = "extensions.json";
+
+/// When the content scripts of an entry run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunAt {
+    /// Before any other script of the page
+    DocumentStart,
+    /// Once the document is parsed; `document_idle` is taken to mean this
+    #[default]
+    #[serde(alias = "document_idle")]
+    DocumentEnd,
+}
+
+/// A URL match pattern, e.g. `https://*.example.com/*`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MatchPattern {
+    /// `<all_urls>`: every `http`, `https` and `file` URL
+    AllUrls,
+    Url {
+        /// A scheme, or `*` for `http` and `https`
+        scheme: String,
+        /// A host, `*` for any, or `*.` and a domain for it and its
+        /// subdomains
+        host: String,
+        /// The path and query, with `*` matching any run of characters
+        path: String,
+    },
+}
+
+impl MatchPattern {
+    /// Parse a match pattern, or `None` if it is not one
+    pub fn parse(pattern: &str) -> Option<Self> {
+        if pattern == "<all_urls>" {
+            return Some(MatchPattern::AllUrls);
+        }
+        let (scheme, rest) = pattern.split_once("://")?;
+        if !matches!(scheme, "*" | "http" | "https" | "file") {
+            return None;
+        }
+        let slash = rest.find('/')?;
+        let (host, path) = rest.split_at(slash);
+        let wildcard_is_prefix = host == "*" || host.strip_prefix("*.").is_some_and(|domain| !domain.contains('*'));
+        if (host.contains('*') && !wildcard_is_prefix) || (host.is_empty() != (scheme == "file")) {
+            return None;
+        }
+        Some(MatchPattern::Url { scheme: scheme.to_string(), host: host.to_ascii_lowercase(), path: path.to_string() })
+    }
+
+    /// Check whether `url` matches the pattern
+    pub fn matches(&self, url: &Url) -> bool {
+        match self {
+            MatchPattern::AllUrls => matches!(url.scheme(), "http" | "https" | "file"),
+            MatchPattern::Url { scheme, host, path } => {
+                let scheme_matches = match scheme.as_str() {
+                    "*" => matches!(url.scheme(), "http" | "https"),
+                    scheme => url.scheme() == scheme,
+                };
+                let url_host = url.host_str().unwrap_or("");
+                let host_matches = match host.strip_prefix("*.") {
+                    _ if host == "*" => true,
+                    Some(domain) => url_host == domain || url_host.ends_with(&format!(".{}", domain)),
+                    None => url_host == host,
+                };
+                let url_path = match url.query() {
+                    Some(query) => format!("{}?{}", url.path(), query),
+                    None => url.path().to_string(),
+                };
+                scheme_matches && host_matches && glob_matches(path, &url_path)
+            }
+        }
+    }
+}
+
+/// Match `text` against a pattern where `*` stands for any characters
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    name: String,
+    #[serde(default)]
+    version: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    content_scripts: Vec<ManifestContentScript>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ManifestContentScript {
+    matches: Vec<String>,
+    #[serde(default)]
+    exclude_matches: Vec<String>,
+    #[serde(default)]
+    js: Vec<String>,
+    #[serde(default)]
+    css: Vec<String>,
+    #[serde(default)]
+    run_at: RunAt,
+}
+
+/// A script an extension injects into pages
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentScript {
+    /// Id of the extension it belongs to
+    pub extension: String,
+    /// Path of the script in the extension
+    pub file: String,
+    pub source: String,
+}
+
+/// A content scripts entry of a manifest, with its files loaded
+#[derive(Debug, Clone)]
+struct ContentScriptEntry {
+    matches: Vec<MatchPattern>,
+    exclude_matches: Vec<MatchPattern>,
+    scripts: Vec<ContentScript>,
+    stylesheet: Option<Stylesheet>,
+    run_at: RunAt,
+}
+
+impl ContentScriptEntry {
+    fn applies_to(&self, url: &Url) -> bool {
+        self.matches.iter().any(|pattern| pattern.matches(url)) && !self.exclude_matches.iter().any(|pattern| pattern.matches(url))
+    }
+}
+
+/// An installed extension
+#[derive(Debug, Clone)]
+pub struct Extension {
+    /// Name of the extension's directory
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    pub description: String,
+    entries: Vec<ContentScriptEntry>,
+}
+
+impl Extension {
+    /// Load the extension in `dir`, warning and returning `None` if it
+    /// cannot be used
+    fn load(id: &str, dir: &Path) -> Option<Self> {
+        let manifest = fs::read_to_string(dir.join(MANIFEST_FILE))
+            .map_err(|e| e.to_string())
+            .and_then(|contents| serde_json::from_str::<Manifest>(&contents).map_err(|e| e.to_string()));
+        let manifest = match manifest {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                eprintln!("❌ Invalid manifest of extension {}: {}", id, e);
+                return None;
+            }
+        };
+
+        let mut entries = Vec::new();
+        for entry in manifest.content_scripts {
+            let parse = |patterns: &[String]| -> Option<Vec<MatchPattern>> {
+                patterns
+                    .iter()
+                    .map(|pattern| MatchPattern::parse(pattern).or_else(|| {
+                        eprintln!("❌ Invalid match pattern {} in extension {}", pattern, id);
+                        None
+                    }))
+                    .collect()
+            };
+            let (matches, exclude_matches) = (parse(&entry.matches)?, parse(&entry.exclude_matches)?);
+            let mut scripts = Vec::new();
+            for file in &entry.js {
+                scripts.push(ContentScript { extension: id.to_string(), file: file.clone(), source: read_file(id, dir, file)? });
+            }
+            let mut stylesheet: Option<Stylesheet> = None;
+            for file in &entry.css {
+                let source = read_file(id, dir, file)?;
+                // Content styles rely on `!important` as user stylesheets
+                // do, so they go through the full parser
+                match CSSParser::new(source).parse_stylesheet() {
+                    Ok(parsed) => stylesheet
+                        .get_or_insert_with(|| Stylesheet { rules: vec![], page_rules: vec![], source_url: Some(format!("extension:{}", id)) })
+                        .rules
+                        .extend(parsed.rules),
+                    Err(e) => {
+                        eprintln!("❌ Invalid stylesheet {} in extension {}: {}", file, id, e);
+                        return None;
+                    }
+                }
+            }
+            entries.push(ContentScriptEntry { matches, exclude_matches, scripts, stylesheet, run_at: entry.run_at });
+        }
+        Some(Extension { id: id.to_string(), name: manifest.name, version: manifest.version, description: manifest.description, entries })
+    }
+
+    /// Count the content script entries of the manifest
+    pub fn content_script_count(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+/// Read a file of an extension, refusing paths that leave its directory
+fn read_file(id: &str, dir: &Path, file: &str) -> Option<String> {
+    let path = Path::new(file);
+    if path.is_absolute() || path.components().any(|component| matches!(component, std::path::Component::ParentDir)) {
+        eprintln!("❌ Extension {} refers to {} outside of its directory", id, file);
+        return None;
+    }
+    match fs::read_to_string(dir.join(path)) {
+        Ok(contents) => Some(contents),
+        Err(e) => {
+            eprintln!("❌ Cannot read {} of extension {}: {}", file, id, e);
+            None
+        }
+    }
+}
+
+/// Run content scripts in a page's script engine, in order
+///
+/// A script that throws is reported and does not stop the others. Returns
+/// the number of scripts that ran without throwing.
+pub fn run_content_scripts(script: &mut JsEngine, scripts: &[ContentScript]) -> usize {
+    let mut ran = 0;
+    for content_script in scripts {
+        match script.execute(&content_script.source) {
+            Ok(_) => ran += 1,
+            Err(e) => println!("⚠️  Content script {} of extension {} failed: {}", content_script.file, content_script.extension, e),
+        }
+    }
+    ran
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ExtensionSettings {
+    /// Ids of the extensions the user turned off
+    #[serde(default)]
+    disabled: BTreeSet<String>,
+}
+
+#[derive(Debug, Default)]
+struct ExtensionsState {
+    extensions: Vec<Extension>,
+    settings: ExtensionSettings,
+    /// File the settings are saved to, if the store is persistent
+    path: Option<PathBuf>,
+}
+
+/// The installed extensions and which of them are enabled
+///
+/// Cloning shares the store.
+#[derive(Debug, Clone, Default)]
+pub struct Extensions {
+    state: Arc<Mutex<ExtensionsState>>,
+}
+
+impl Extensions {
+    /// Create a store without extensions that is not saved anywhere
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open the extensions of the profile in `profile_dir`
+    ///
+    /// Creates the extension directory if there is none yet and loads the
+    /// extensions in it, in the order of their ids.
+    pub fn open(profile_dir: impl AsRef<Path>) -> io::Result<Self> {
+        let dir = profile_dir.as_ref().join(EXTENSIONS_DIR);
+        fs::create_dir_all(&dir)?;
+        let path = profile_dir.as_ref().join(EXTENSION_SETTINGS_FILE);
+        let settings = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => ExtensionSettings::default(),
+            Err(e) => return Err(e),
+        };
+
+        let mut ids: Vec<String> = fs::read_dir(&dir)?
+            .flatten()
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+            .collect();
+        ids.sort();
+        let extensions = ids.iter().filter_map(|id| Extension::load(id, &dir.join(id))).collect();
+        Ok(Extensions { state: Arc::new(Mutex::new(ExtensionsState { extensions, settings, path: Some(path) })) })
+    }
+
+    /// Get the installed extensions and whether each is enabled
+    pub fn list(&self) -> Vec<(Extension, bool)> {
+        let state = self.lock();
+        state.extensions.iter().map(|extension| (extension.clone(), !state.settings.disabled.contains(&extension.id))).collect()
+    }
+
+    /// Enable or disable an extension
+    ///
+    /// Returns whether an extension with that id is installed. Pages
+    /// loaded from then on see the change.
+    pub fn set_enabled(&self, id: &str, enabled: bool) -> io::Result<bool> {
+        let mut state = self.lock();
+        if !state.extensions.iter().any(|extension| extension.id == id) {
+            return Ok(false);
+        }
+        if enabled {
+            state.settings.disabled.remove(id);
+        } else {
+            state.settings.disabled.insert(id.to_string());
+        }
+        state.save()?;
+        Ok(true)
+    }
+
+    /// Get the scripts enabled extensions inject into `url` at `run_at`,
+    /// in the order they run
+    pub fn scripts_for(&self, url: &str, run_at: RunAt) -> Vec<ContentScript> {
+        let Ok(url) = Url::parse(url) else {
+            return Vec::new();
+        };
+        self.lock()
+            .entries_for(&url)
+            .filter(|entry| entry.run_at == run_at)
+            .flat_map(|entry| entry.scripts.iter().cloned())
+            .collect()
+    }
+
+    /// Build the stylesheet enabled extensions inject into `url`, if any
+    pub fn stylesheet_for(&self, url: &str) -> Option<Stylesheet> {
+        let url = Url::parse(url).ok()?;
+        let state = self.lock();
+        let rules: Vec<_> = state
+            .entries_for(&url)
+            .filter_map(|entry| entry.stylesheet.as_ref())
+            .flat_map(|stylesheet| stylesheet.rules.iter().cloned())
+            .collect();
+        (!rules.is_empty()).then(|| Stylesheet { rules, page_rules: vec![], source_url: Some(format!("extensions:{}", url)) })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ExtensionsState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl ExtensionsState {
+    /// Get the content script entries of enabled extensions applying to
+    /// `url`
+    fn entries_for<'a>(&'a self, url: &'a Url) -> impl Iterator<Item = &'a ContentScriptEntry> {
+        self.extensions
+            .iter()
+            .filter(|extension| !self.settings.disabled.contains(&extension.id))
+            .flat_map(|extension| &extension.entries)
+            .filter(move |entry| entry.applies_to(url))
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let contents = serde_json::to_string_pretty(&self.settings).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let temporary = path.with_extension("json.tmp");
+        fs::write(&temporary, contents)?;
+        fs::rename(&temporary, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{evaluate, find_box, temporary_profile};
+    use std::time::Instant;
+    use crate::BrowserEngine;
+
+    fn write_extension(profile: &Path, id: &str, manifest: &str, files: &[(&str, &str)]) {
+        let dir = profile.join(EXTENSIONS_DIR).join(id);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(MANIFEST_FILE), manifest).unwrap();
+        for (name, contents) in files {
+            fs::write(dir.join(name), contents).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_match_patterns() {
+        let url = |url: &str| Url::parse(url).unwrap();
+        let pattern = MatchPattern::parse("https://*.example.com/docs/*").unwrap();
+        assert!(pattern.matches(&url("https://example.com/docs/intro")));
+        assert!(pattern.matches(&url("https://www.example.com/docs/?page=2")));
+        assert!(!pattern.matches(&url("https://notexample.com/docs/intro")));
+        assert!(!pattern.matches(&url("http://example.com/docs/intro")));
+        assert!(MatchPattern::parse("*://*/*").unwrap().matches(&url("http://localhost:8080/")));
+        assert!(MatchPattern::parse("file:///*.html").unwrap().matches(&url("file:///tmp/page.html")));
+        assert!(MatchPattern::AllUrls.matches(&url("file:///tmp/page.html")));
+        assert!(!MatchPattern::AllUrls.matches(&url("about:blank")));
+        for invalid in ["example.com/*", "ftp://example.com/*", "https://www.*.com/*", "https://example.com", "file://host/*"] {
+            assert_eq!(MatchPattern::parse(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_content_scripts_and_styles_are_injected() {
+        let profile = temporary_profile("extensions-inject");
+        write_extension(&profile, "dark-docs", r#"{
+            "name": "Dark docs",
+            "version": "1.0",
+            "content_scripts": [{
+                "matches": ["https://*.example.com/*"],
+                "exclude_matches": ["https://example.com/private/*"],
+                "js": ["start.js"],
+                "css": ["dark.css"],
+                "run_at": "document_start"
+            }, {
+                "matches": ["https://*.example.com/*"],
+                "js": ["end.js"]
+            }]
+        }"#, &[
+            ("start.js", "window.injected = ['start'];"),
+            ("end.js", "injected.push(document.body.textContent);"),
+            ("dark.css", "p { font-size: 30px; color: white !important }"),
+        ]);
+        write_extension(&profile, "broken", "{ \"name\": ", &[]);
+        write_extension(&profile, "escapes", r#"{ "name": "Escapes", "content_scripts": [{ "matches": ["<all_urls>"], "js": ["../../secret.js"] }] }"#, &[]);
+        let extensions = Extensions::open(&profile).unwrap();
+        let listed: Vec<_> = extensions.list().into_iter().map(|(extension, enabled)| (extension.id, enabled)).collect();
+        assert_eq!(listed, [("dark-docs".to_string(), true)]);
+
+        // Scripts run at the start and at the end of the document, and the
+        // styles apply at the user origin
+        let mut engine = BrowserEngine::new();
+        engine.set_extensions(extensions.clone());
+        assert!(engine.load_html_from("<html><body><p>Docs</p></body></html>", Some("https://www.example.com/guide"), Instant::now()));
+        assert_eq!(evaluate(&mut engine, "injected.join('|')"), "start|Docs");
+        assert!(engine.load_css("p { margin: 0; color: black }"));
+        assert!(engine.perform_layout());
+        let paragraph = engine.get_document().unwrap().root.query_selector("p").unwrap().unwrap();
+        let styles = &find_box(engine.get_layout().unwrap(), paragraph.id).unwrap().styles;
+        assert_eq!(styles.font.size, Some(30.0));
+        assert_eq!(styles.color.as_deref(), Some("white"));
+
+        // Pages without a URL get nothing
+        assert!(engine.load_html("<html><body><p>Docs</p></body></html>"));
+        assert_eq!(evaluate(&mut engine, "typeof injected"), "undefined");
+
+        // Excluded pages and disabled extensions get nothing
+        assert!(extensions.scripts_for("https://example.com/private/notes", RunAt::DocumentStart).is_empty());
+        assert!(extensions.set_enabled("dark-docs", false).unwrap());
+        assert!(!extensions.set_enabled("missing", false).unwrap());
+        let reopened = Extensions::open(&profile).unwrap();
+        assert!(!reopened.list()[0].1);
+        assert!(reopened.scripts_for("https://www.example.com/guide", RunAt::DocumentEnd).is_empty());
+        assert!(reopened.stylesheet_for("https://www.example.com/guide").is_none());
+        let _ = fs::remove_dir_all(&profile);
+    }
+}
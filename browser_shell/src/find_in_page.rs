@@ -0,0 +1,93 @@
+//! # Find in Page
+//!
+//! This module finds text in the current page through a helper script
+//! talking to the shell over the page script's shell bridge, see
+//! `js_integration::shell_bridge`. The helper walks the text nodes of the
+//! document and answers each query with the number of matches and a few
+//! snippets around them.
+//!
+//! ## Design Principles
+//!
+//! 1. **Queries Are Data**: The text searched for is posted as a message,
+//!    never spliced into a script, so quotes and backslashes in it need no
+//!    escaping and cannot break out into the page.
+//! 2. **Installed on First Use**: The helper is installed in a page the
+//!    first time the user searches it, and goes away with the page.
+//! 3. **Case-Insensitive**: Matches ignore case, as the find bar of other
+//!    browsers does by default.
+
+use serde::Deserialize;
+
+/// Name the find-in-page helper is installed under
+pub const FIND_HELPER: &str = "find-in-page";
+
+/// Snippets of context a search reports at most
+pub const MAX_SNIPPETS: usize = 5;
+
+/// Source of the find-in-page helper
+///
+/// Posted `{ query }`, it replies `{ query, matches, snippets }`.
+pub const FIND_HELPER_SOURCE: &str = r#"function (port) {
+    const MAX_SNIPPETS = 5;
+    const CONTEXT = 20;
+    port.addEventListener('message', event => {
+        const query = String(event.data.query || '');
+        const needle = query.toLowerCase();
+        const snippets = [];
+        let matches = 0;
+        if (needle && document.body) {
+            const walker = document.createTreeWalker(document.body, 4);
+            while (walker.nextNode()) {
+                const text = walker.currentNode.textContent;
+                const haystack = text.toLowerCase();
+                let index = haystack.indexOf(needle);
+                while (index >= 0) {
+                    matches++;
+                    if (snippets.length < MAX_SNIPPETS) {
+                        const start = Math.max(0, index - CONTEXT);
+                        const end = Math.min(text.length, index + needle.length + CONTEXT);
+                        snippets.push(text.slice(start, end).trim());
+                    }
+                    index = haystack.indexOf(needle, index + needle.length);
+                }
+            }
+        }
+        port.postMessage({ query, matches, snippets });
+    });
+}"#;
+
+/// The answer of the find-in-page helper to a query
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct FindResult {
+    /// The text searched for
+    pub query: String,
+    /// Number of matches in the page
+    pub matches: usize,
+    /// Text around the first `MAX_SNIPPETS` matches
+    pub snippets: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::BrowserEngine;
+
+    #[test]
+    fn test_find_in_page_counts_matches_through_the_helper() {
+        let mut engine = BrowserEngine::new();
+        assert!(engine.find_in_page("fox").is_none());
+        assert!(engine.load_html("<html><body><p>The quick brown Fox</p><p>jumps over the lazy dog. Fox again, \"fox\" quoted</p></body></html>"));
+
+        let result = engine.find_in_page("fox").unwrap();
+        assert_eq!(result.query, "fox");
+        assert_eq!(result.matches, 3);
+        assert_eq!(result.snippets[0], "The quick brown Fox");
+
+        // Queries are data, so quotes are searched for as they are
+        assert_eq!(engine.find_in_page("\"fox\"').x('").unwrap().matches, 0);
+        assert_eq!(engine.find_in_page("\"fox\"").unwrap().matches, 1);
+
+        // The helper goes away with the page
+        assert!(engine.load_html("<html><body>No animals</body></html>"));
+        assert_eq!(engine.find_in_page("fox").unwrap().matches, 0);
+    }
+}
@@ -0,0 +1,93 @@
+//! # Frames
+//!
+//! This module decides what an `<iframe>` loads: the markup of its
+//! `srcdoc` attribute, or the document at its `src` URL. The engine loads
+//! it into a child engine of its own, which carries the frame's sandbox
+//! flags, see `BrowserEngine::load_frame`.
+//!
+//! ## Design Principles
+//!
+//! 1. **`srcdoc` Wins**: A frame with both attributes shows its `srcdoc`,
+//!    as in browsers, and such a document shares the origin of the page
+//!    that embeds it unless the sandbox makes it opaque.
+//! 2. **The Child Is Restricted, Not the Parent**: Sandbox flags are set on
+//!    the child engine before its document is shown, so its origin, script
+//!    policy, forms, navigations and downloads are restricted from the
+//!    start, while the embedding page is unaffected.
+
+use dom::{Document, Node};
+use url::Url;
+
+/// URL of documents loaded from a frame's `srcdoc` attribute
+pub const SRCDOC_URL: &str = "about:srcdoc";
+
+/// What a frame loads
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrameSource {
+    /// The markup of the `srcdoc` attribute
+    Srcdoc(String),
+    /// The `src` attribute, resolved against the embedding document
+    Url(Url),
+}
+
+impl FrameSource {
+    /// Get what an `<iframe>` of `document` loads, or `None` if it loads
+    /// nothing or is no frame
+    pub fn of(document: &Document, frame: &Node) -> Option<Self> {
+        if !frame.tag_name().is_some_and(|tag| tag.eq_ignore_ascii_case("iframe")) {
+            return None;
+        }
+        if let Some(markup) = frame.get_attribute("srcdoc") {
+            return Some(FrameSource::Srcdoc(markup));
+        }
+        let src = frame.get_attribute("src")?;
+        networking::resolve_url(document, src.trim()).map(FrameSource::Url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::evaluate;
+    use std::rc::Rc;
+    use crate::BrowserEngine;
+
+    #[test]
+    fn test_sandboxed_frames_load_in_a_restricted_child() {
+        let document = Document::new_with_url("https://example.com/page");
+        let frame = document.create_element("iframe");
+        frame.set_attribute("src", "/widget");
+        assert_eq!(FrameSource::of(&document, &frame), Some(FrameSource::Url(Url::parse("https://example.com/widget").unwrap())));
+        frame.set_attribute("srcdoc", "<p>Inline</p>");
+        assert_eq!(FrameSource::of(&document, &frame), Some(FrameSource::Srcdoc("<p>Inline</p>".to_string())));
+        assert_eq!(FrameSource::of(&document, &document.create_element("div")), None);
+
+        let mut engine = BrowserEngine::new();
+        assert!(engine.load_html("<html><body><iframe id=\"ad\" sandbox=\"allow-scripts\" srcdoc=\"<form><button id=send>Send</button></form><a id=out href=/away target=_top>Away</a>\"></iframe></body></html>"));
+        engine.get_document().unwrap().set_url("https://example.com/page");
+        let frame = engine.get_document().unwrap().root.query_selector("#ad").unwrap().unwrap();
+
+        // The child gets a unique origin and the frame's script policy,
+        // and the page keeps its own
+        let mut child = engine.load_frame(&frame).unwrap();
+        let framed = Rc::clone(child.get_document().unwrap());
+        assert!(framed.origin().is_opaque());
+        assert_eq!(framed.url().as_deref(), Some(SRCDOC_URL));
+        let policy = child.current_sandbox_policy();
+        assert!(policy.allow_scripts && !policy.allow_modals && !policy.allow_storage);
+        assert!(engine.current_sandbox_policy().allow_modals);
+        assert_eq!(evaluate(&mut child, "typeof confirm"), "function");
+
+        // Forms and top-level navigation are blocked
+        child.click_element("send");
+        assert!(child.take_form_submission().is_none());
+        child.click_element("out");
+        assert!(child.take_link_navigation().is_none());
+
+        // With allow-same-origin, a srcdoc document shares the page's origin
+        frame.set_attribute("sandbox", "allow-same-origin allow-forms");
+        let child = engine.load_frame(&frame).unwrap();
+        assert_eq!(child.get_document().unwrap().origin(), dom::Origin::from_url("https://example.com/page"));
+        assert!(!child.current_sandbox_policy().allow_scripts);
+    }
+}
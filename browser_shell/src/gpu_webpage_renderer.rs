@@ -5,6 +5,7 @@
 
 use std::time::Instant;
 use dom::Document;
+use renderer_wgpu::text_quality::{GlyphAtlas, TextRenderSettings};
 
 /// Configuration for GPU webpage rendering
 #[derive(Debug, Clone)]
@@ -19,6 +20,8 @@ pub struct GpuRenderConfig {
     pub save_screenshot: Option<String>,
     /// Show performance metrics
     pub show_metrics: bool,
+    /// Antialiasing, hinting and gamma of text
+    pub text_rendering: TextRenderSettings,
 }
 
 impl Default for GpuRenderConfig {
@@ -29,6 +32,7 @@ impl Default for GpuRenderConfig {
             debug_mode: false,
             save_screenshot: None,
             show_metrics: true,
+            text_rendering: TextRenderSettings::from_env(),
         }
     }
 }
@@ -37,6 +41,7 @@ impl Default for GpuRenderConfig {
 pub struct GpuWebpageRenderer {
     config: GpuRenderConfig,
     performance_metrics: PerformanceMetrics,
+    glyph_atlas: GlyphAtlas,
 }
 
 /// Performance metrics for GPU rendering
@@ -68,6 +73,7 @@ impl GpuWebpageRenderer {
                 initialization_time,
                 ..Default::default()
             },
+            glyph_atlas: GlyphAtlas::new(),
         })
     }
     
@@ -98,6 +104,8 @@ impl GpuWebpageRenderer {
         
         if self.config.debug_mode {
             println!("🐛 Debug mode: DOM boxes highlighted");
+            let text = &self.config.text_rendering;
+            println!("🔤 Text: {} antialiasing, {} hinting, gamma {}", text.antialiasing.as_str(), text.hinting.as_str(), text.gamma);
         }
         
         // Simulate render time
@@ -121,6 +129,19 @@ impl GpuWebpageRenderer {
         Ok(())
     }
     
+    /// Change the text rendering settings
+    ///
+    /// Glyphs rasterized under the old settings are dropped from the atlas.
+    pub fn set_text_rendering(&mut self, settings: TextRenderSettings) {
+        self.config.text_rendering = settings;
+        self.glyph_atlas.retain_settings(&settings);
+    }
+
+    /// Get the cache of rasterized glyphs
+    pub fn glyph_atlas(&self) -> &GlyphAtlas {
+        &self.glyph_atlas
+    }
+
     /// Save a screenshot of the rendered webpage
     pub async fn save_screenshot(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
         // This would integrate with the existing screenshot functionality
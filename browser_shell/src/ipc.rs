@@ -0,0 +1,138 @@
+//! # Shell/Engine IPC
+//!
+//! This module defines the messages the shell process and a tab's engine
+//! process exchange, and how they are framed on the wire. The shell sends
+//! `ShellRequest`s; the engine answers each with exactly one `EngineReply`.
+//!
+//! ## Design Principles
+//!
+//! 1. **Request/Reply**: Every request gets one reply, so the shell never
+//!    has to match replies to requests and a missing reply means the
+//!    engine is gone.
+//! 2. **Length-Prefixed JSON**: Messages are serde JSON preceded by their
+//!    length as a big-endian `u32`, readable from any byte stream.
+//! 3. **Untrusted Peer**: The shell assumes the engine process may be
+//!    compromised: oversized or malformed messages are errors, never
+//!    panics.
+
+use std::io::{self, Read, Write};
+use renderer::display_list::DisplayList;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Largest message accepted from the other process
+pub const MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+/// Custom error types for shell/engine communication
+#[derive(Error, Debug)]
+pub enum IpcError {
+    #[error("IPC I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Invalid IPC message: {0}")]
+    Encoding(#[from] serde_json::Error),
+
+    #[error("IPC message of {0} bytes is too large")]
+    MessageTooLarge(usize),
+
+    #[error("Tab crashed: {0}")]
+    TabCrashed(String),
+
+    #[error("Unexpected reply from the engine process: {0}")]
+    UnexpectedReply(String),
+}
+
+/// Result type for shell/engine communication
+pub type IpcResult<T> = Result<T, IpcError>;
+
+/// A request from the shell to a tab's engine process
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ShellRequest {
+    /// Parse an HTML document, fetched from `url` if given
+    LoadHtml { html: String, url: Option<String> },
+    /// Fetch and parse the document at a URL
+    FetchUrl { url: String },
+    /// Use a stylesheet for the current document
+    LoadCss { css: String },
+    /// Lay the document out and send its display list
+    Paint,
+    /// Run a script in the current document
+    ExecuteScript { code: String },
+    /// Make the engine process crash, like `about:crash` in other browsers
+    Crash,
+    /// Exit the engine process
+    Shutdown,
+}
+
+/// The reply of an engine process to a `ShellRequest`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum EngineReply {
+    /// A document was loaded, or failed to load
+    Loaded { ok: bool, title: Option<String> },
+    /// The request was carried out, or failed
+    Done { ok: bool },
+    /// The display list of the current document
+    Frame { display_list: DisplayList },
+    /// The result of a script, or its error, as text
+    ScriptResult { result: Result<String, String> },
+    /// The engine panicked handling the request and is exiting; `report` is
+    /// the path of its crash report, if it saved one
+    Crashed { message: String, report: Option<String> },
+}
+
+/// Write a message to a stream
+pub fn write_message<W: Write, T: Serialize>(writer: &mut W, message: &T) -> IpcResult<()> {
+    let bytes = serde_json::to_vec(message)?;
+    if bytes.len() > MAX_MESSAGE_SIZE {
+        return Err(IpcError::MessageTooLarge(bytes.len()));
+    }
+    writer.write_all(&(bytes.len() as u32).to_be_bytes())?;
+    writer.write_all(&bytes)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Read a message from a stream
+///
+/// Returns `None` if the stream ended cleanly before a message began.
+pub fn read_message<R: Read, T: DeserializeOwned>(reader: &mut R) -> IpcResult<Option<T>> {
+    let mut length = [0u8; 4];
+    match reader.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let length = u32::from_be_bytes(length) as usize;
+    if length > MAX_MESSAGE_SIZE {
+        return Err(IpcError::MessageTooLarge(length));
+    }
+    let mut bytes = vec![0u8; length];
+    reader.read_exact(&mut bytes)?;
+    Ok(Some(serde_json::from_slice(&bytes)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_message_framing() {
+        let mut buffer = Vec::new();
+        let request = ShellRequest::LoadHtml { html: "<p>Hi</p>".to_string(), url: None };
+        write_message(&mut buffer, &request).unwrap();
+        write_message(&mut buffer, &ShellRequest::Paint).unwrap();
+
+        let mut reader = Cursor::new(buffer);
+        assert_eq!(read_message::<_, ShellRequest>(&mut reader).unwrap(), Some(request));
+        assert_eq!(read_message::<_, ShellRequest>(&mut reader).unwrap(), Some(ShellRequest::Paint));
+        assert_eq!(read_message::<_, ShellRequest>(&mut reader).unwrap(), None);
+
+        // A hostile length prefix is rejected before allocating
+        let mut hostile = Cursor::new(u32::MAX.to_be_bytes().to_vec());
+        assert!(matches!(read_message::<_, EngineReply>(&mut hostile), Err(IpcError::MessageTooLarge(_))));
+        let mut garbage = Cursor::new([&3u32.to_be_bytes()[..], b"{{{"].concat());
+        assert!(matches!(read_message::<_, EngineReply>(&mut garbage), Err(IpcError::Encoding(_))));
+    }
+}
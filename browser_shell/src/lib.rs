@@ -16,21 +16,202 @@
 //!    like networking, JavaScript execution, etc.
 //! 
 //! 4. **Error Handling**: Provides robust error handling and recovery
-//!    mechanisms.
+//!    mechanisms. Failures of every component are reported as one
+//!    `EngineError`, on the console or as an error page.
 
-use dom::Document;
+use dom::{Document, Node, Origin, SecurityPolicy};
+use dom::clipboard::Clipboard;
+use dom::mutations::{self, Mutation, ObserverId};
+use dom::dom_event_integration::DomEventManager;
+use dom::forms::{FormMethod, FormSubmission};
+use dom::fullscreen;
+use dom::page_visibility::{self, VisibilityState};
 use html_parser::parse_html;
-use css_parser::{parse_css, Stylesheet};
-use layout::{LayoutEngine, LayoutBox};
+use css_parser::{parse_css, StyleInvalidation, Stylesheet};
+use layout::{Dimensions, FontRegistry, GeometryObservers, LayerTree, LayoutEngine, LayoutBox};
+use layout::translation::{pseudo_localize, text_segments, TextSegment};
 use renderer::{render_as_text, extract_text_content, render_layout_box};
-use networking::HttpClient;
-use renderer_wgpu::render_layout_tree;
+use networking::{HttpClient, HttpRequest, RequestInterceptor, UrlResolver};
+use renderer_wgpu::render_layout_tree_with_input;
+use renderer_wgpu::input_handler::InputHandler;
 // use js_integration::JsEngine;
+use js_integration::cache_storage::is_secure_origin;
+use js_integration::clipboard::{ClipboardAccess, ClipboardPermissions};
+use js_integration::console::ConsoleSink;
+use js_integration::geolocation::{FixedLocationProvider, LocationProvider};
+use js_integration::notifications::{DesktopNotification, NotificationHandler};
+use js_integration::permissions::{PermissionName, PermissionPrompt, PermissionState, Permissions};
+use js_integration::performance::PerformanceTimeline;
+use js_integration::sandbox::SandboxPolicy;
+use js_integration::user_prompts::{UserPrompt, UserPromptHandler};
+use js_integration::{JsEngine, JsResult};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::io::{self, Write};
 use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Instant;
 
 pub mod webpage_loader;
 pub mod gpu_webpage_renderer;
+pub mod error;
+pub mod session;
+pub mod bookmarks;
+pub mod user_styles;
+pub mod appearance;
+pub mod crash;
+pub mod bench;
+pub mod visited;
+pub mod offline;
+pub mod devtools;
+pub mod coverage;
+pub mod profiler;
+pub mod cdp;
+pub mod ipc;
+#[cfg(unix)]
+pub mod tab_process;
+pub mod tab_freezing;
+pub mod prefetch;
+pub mod about;
+pub mod error_pages;
+pub mod zoom;
+pub mod find_in_page;
+pub mod extensions;
+pub mod frames;
+pub mod snapshots;
+pub mod deterministic;
+pub mod context_menu;
+pub mod downloads;
+pub mod passwords;
+pub mod screenshot;
+pub mod print;
+pub mod system_clipboard;
+pub mod system_gamepads;
+
+#[cfg(test)]
+mod test_support;
+
+pub use error::{EngineError, EngineErrorKind, EnginePhase, EngineResult};
+use bookmarks::{bookmark_shortcut, document_title, BookmarkShortcut, BookmarkStore};
+use dom::events::KeyboardEventInit;
+use session::{startup_action, RestorePreference, Session, SessionStore, StartupAction, TabState};
+use user_styles::UserStyles;
+use networking::content_blocking::ContentBlocker;
+use networking::hsts::HstsStore;
+use networking::cache_storage::CacheStorage;
+use offline::{pin_page, pinned_stylesheets, PinReport, OFFLINE_CACHE};
+use visited::{mark_visited_links, VisitedStore};
+use zoom::{zoom_shortcut, ZoomShortcut, ZoomStore};
+use extensions::{run_content_scripts, Extensions, RunAt};
+use frames::{FrameSource, SRCDOC_URL};
+use dom::sandboxing::SandboxFlags;
+use appearance::{parse_flag, AppearanceSettings, SystemPreferences};
+use renderer_wgpu::text_quality::TextRenderSettings;
+use css_parser::media::{ColorScheme, MediaFeatures};
+use crash::{catch_crash, CrashRecovery, CrashReport};
+use context_menu::{ContextMenu, ContextMenuAction, ContextMenuTarget, SharedContributor, SharedSpellchecker};
+use downloads::DownloadManager;
+use passwords::{PasswordSavePrompt, PasswordStore};
+use dom::autofill::login_forms;
+use dom::spellcheck::DictionarySpellchecker;
+use tab_freezing::TabFreezingPolicy;
+use about::{is_about_url, AboutPage};
+use error_pages::{ErrorPage, RETRY_BUTTON_ID};
+use deterministic::DeterministicMode;
+use prefetch::{link_target, prefetch, resource_hints, PrefetchCache, PrefetchHint, PrefetchKind, PrefetchPolicy};
+use std::path::PathBuf;
+
+/// Ask on the terminal whether an origin may access the clipboard
+fn prompt_clipboard_permission(origin: &str, access: ClipboardAccess) -> bool {
+    print!("Allow {} to {} the clipboard? [y/N] ", origin, access);
+    if io::stdout().flush().is_err() {
+        return false;
+    }
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes")
+}
+
+/// Ask on the terminal whether to save a password the user signed in with
+fn prompt_save_password(origin: &str, username: &str) -> bool {
+    let username = if username.is_empty() { "this login".to_string() } else { format!("the password of {}", username) };
+    print!("Save {} for {}? [y/N] ", username, origin);
+    if io::stdout().flush().is_err() {
+        return false;
+    }
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes")
+}
+
+/// Ask on the terminal whether an origin may use a feature
+///
+/// An empty answer, or end of input, dismisses the prompt so the page can
+/// ask again later.
+fn prompt_permission(origin: &str, name: PermissionName) -> Option<bool> {
+    print!("Allow {} to {}? [y/n, Enter to decide later] ", origin, name.description());
+    io::stdout().flush().ok()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).ok()?;
+    match answer.trim().to_ascii_lowercase().as_str() {
+        "y" | "yes" => Some(true),
+        "n" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+/// Show a page's notification with the desktop's notification service
+fn show_desktop_notification(notification: &DesktopNotification) {
+    let mut desktop = notify_rust::Notification::new();
+    desktop.summary(&notification.title).body(&notification.body).appname(&notification.origin);
+    if let Err(e) = desktop.show() {
+        println!("🔔 {}: {} ({})", notification, notification.body, e);
+    }
+}
+
+/// Show a script's `alert`, `confirm` or `prompt`, or the leave/stay prompt
+/// of a page being left, on the terminal and wait for the answer
+///
+/// An empty answer accepts a confirm, takes the default of a prompt and
+/// leaves the page; end of input dismisses any of them.
+fn prompt_user(prompt: &UserPrompt) -> Option<String> {
+    match prompt {
+        UserPrompt::Alert(message) => print!("[alert] {} [Enter] ", message),
+        UserPrompt::Confirm(message) => print!("[confirm] {} [Y/n] ", message),
+        UserPrompt::Prompt { message, default_value } => print!("[prompt] {} [{}] ", message, default_value),
+        UserPrompt::BeforeUnload => print!("[leave] {} [Leave/stay] ", prompt.message()),
+    }
+    io::stdout().flush().ok()?;
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).ok()? == 0 {
+        return None;
+    }
+    let answer = answer.trim_end_matches(['\r', '\n']);
+    match prompt {
+        UserPrompt::Alert(_) => Some(String::new()),
+        UserPrompt::Confirm(_) => (!matches!(answer.trim().to_ascii_lowercase().as_str(), "n" | "no")).then(String::new),
+        UserPrompt::Prompt { default_value, .. } if answer.is_empty() => Some(default_value.clone()),
+        UserPrompt::Prompt { .. } => Some(answer.to_string()),
+        UserPrompt::BeforeUnload => (!matches!(answer.trim().to_ascii_lowercase().as_str(), "s" | "stay")).then(String::new),
+    }
+}
+
+/// Run a future to completion from synchronous code
+/// 
+/// Inside a multi-threaded tokio runtime the future runs on the current
+/// worker; otherwise a runtime is created for it.
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => tokio::task::block_in_place(|| handle.block_on(future)),
+        Err(_) => tokio::runtime::Runtime::new()
+            .expect("Failed to create async runtime")
+            .block_on(future),
+    }
+}
 
 /// The main browser engine that coordinates all components
 /// 
@@ -42,12 +223,119 @@ pub struct BrowserEngine {
     current_document: Option<Rc<Document>>,
     /// The current stylesheet
     current_stylesheet: Option<Stylesheet>,
+    /// Text of the current stylesheet, for coverage reports
+    stylesheet_source: Option<String>,
+    /// Rules of the current stylesheet that matched an element in any
+    /// layout
+    used_css_rules: HashSet<usize>,
     /// The current layout tree
     current_layout: Option<LayoutBox>,
+    /// Mutations of DOM trees on this thread since the last layout
+    dom_mutations: Rc<RefCell<Vec<Mutation>>>,
+    /// Observer recording `dom_mutations`
+    mutation_observer: ObserverId,
     /// HTTP client for fetching resources
     http_client: HttpClient,
+    /// Event manager for dispatching user input to the current document
+    event_manager: DomEventManager,
+    /// URL of the current document, if it was fetched from the network
+    current_url: Option<String>,
     // /// JavaScript engine for executing scripts
     // js_engine: JsEngine,
+    /// Script sandbox policy for origins without their own policy
+    default_sandbox_policy: SandboxPolicy,
+    /// Script sandbox policies configured per origin
+    sandbox_policies: HashMap<Origin, SandboxPolicy>,
+    /// Sandbox flags of the frame this engine loads documents for, if it
+    /// is the child engine of a sandboxed `<iframe>`
+    frame_sandbox: Option<SandboxFlags>,
+    /// Clipboard shared by scripts and keyboard shortcuts
+    clipboard: Clipboard,
+    /// Clipboard access granted to origins
+    clipboard_permissions: ClipboardPermissions,
+    /// Shows the `alert`, `confirm` and `prompt` of scripts
+    user_prompt_handler: UserPromptHandler,
+    /// Permissions of origins, asking with `permission_prompt`
+    permissions: Permissions,
+    /// Asks the user whether an origin may use a feature
+    permission_prompt: PermissionPrompt,
+    /// Shows the notifications of pages
+    notification_handler: NotificationHandler,
+    /// Where `navigator.geolocation` says the device is
+    location_provider: Rc<dyn LocationProvider>,
+    /// The most recent failure, for the error page
+    last_error: Option<EngineError>,
+    /// Resource and paint timings of the current document
+    performance: PerformanceTimeline,
+    /// URLs visited, oldest first
+    history: Vec<String>,
+    /// Position of the current document in `history`
+    history_index: usize,
+    /// Scroll offset of the viewport, as last reported by the renderer
+    scroll_offset: (f32, f32),
+    /// The user's stylesheets, shared by all tabs
+    user_styles: Option<Rc<RefCell<UserStyles>>>,
+    /// Extensions injecting scripts and styles into pages, shared by all
+    /// tabs
+    extensions: Extensions,
+    /// Environment `@media` rules and `matchMedia` are evaluated against
+    media_features: MediaFeatures,
+    /// Console messages of the page, shared with its script engines
+    console: ConsoleSink,
+    /// Source of the current document, for reloading it
+    document_source: Option<String>,
+    /// How to reload the tab, if it shows a crash page
+    crash_recovery: Option<CrashRecovery>,
+    /// The error page the tab shows, if any
+    error_page: Option<ErrorPage>,
+    /// URL the error page's retry button asked to load again
+    retry_navigation: Option<String>,
+    /// Visited URLs, shared by all tabs, for `:visited`
+    visited: VisitedStore,
+    /// Zoom levels of sites and the minimum font size, shared by all tabs
+    zoom: ZoomStore,
+    /// The script engine running the current document, which is unloaded
+    /// before the next document is shown
+    page_script: Option<JsEngine>,
+    /// Zoom level of the current page
+    zoom_level: f32,
+    /// Caches of pages and pinned offline copies, shared by all tabs
+    cache_storage: CacheStorage,
+    /// Whether the tab is in the foreground of a window that is not
+    /// minimized, given to every document it loads
+    visibility_state: VisibilityState,
+    /// How script engines are throttled while the tab is hidden
+    freezing_policy: TabFreezingPolicy,
+    /// Which resource hints and hovered links are fetched ahead of time
+    prefetch_policy: PrefetchPolicy,
+    /// Responses fetched ahead of time, shared by all tabs
+    prefetch_cache: PrefetchCache,
+    /// Whether the network connection is metered
+    metered_connection: bool,
+    /// Prefetches waiting for the tab to be idle
+    pending_prefetches: Vec<PrefetchHint>,
+    /// The manual clock and random seed of the tab, if it is in
+    /// deterministic mode
+    deterministic: Option<DeterministicMode>,
+    /// Subscriptions to element geometry, shared by every layout engine
+    /// of the tab and its script engines' `ResizeObserver`s
+    geometry_observers: GeometryObservers,
+    /// Marks misspelled words of editable text, shared by every tab
+    spellchecker: SharedSpellchecker,
+    /// Subsystems adding items to context menus
+    context_menu_contributors: Vec<SharedContributor>,
+    /// Saves images and other resources for the user, shared by every tab
+    downloads: DownloadManager,
+    /// The element last inspected from a context menu, which the devtools
+    /// console selects as `$0`
+    inspected: Option<Rc<Node>>,
+    /// Saved logins, shared by every tab
+    passwords: PasswordStore,
+    /// Asks whether to save the credentials of a submitted login form
+    password_save_prompt: PasswordSavePrompt,
+    /// Styles hiding the elements the content blocker's cosmetic filters
+    /// target on the current page
+    cosmetic_filters: Option<Stylesheet>,
     /// Whether the browser is running
     is_running: bool,
 }
@@ -55,12 +343,63 @@ pub struct BrowserEngine {
 impl BrowserEngine {
     /// Create a new browser engine instance
     pub fn new() -> Self {
+        let dom_mutations = Rc::new(RefCell::new(Vec::new()));
+        let recorded = Rc::clone(&dom_mutations);
         BrowserEngine {
             current_document: None,
             current_stylesheet: None,
+            stylesheet_source: None,
+            used_css_rules: HashSet::new(),
             current_layout: None,
+            dom_mutations,
+            mutation_observer: mutations::observe(move |mutation| recorded.borrow_mut().push(mutation.clone())),
             http_client: HttpClient::new(),
+            event_manager: DomEventManager::new(),
+            current_url: None,
             // js_engine: JsEngine::new(),
+            default_sandbox_policy: SandboxPolicy::unrestricted(),
+            frame_sandbox: None,
+            sandbox_policies: HashMap::new(),
+            clipboard: system_clipboard::system_clipboard(),
+            clipboard_permissions: ClipboardPermissions::new(Arc::new(prompt_clipboard_permission)),
+            user_prompt_handler: Arc::new(prompt_user),
+            permissions: Permissions::new().with_prompt(Arc::new(prompt_permission)),
+            permission_prompt: Arc::new(prompt_permission),
+            notification_handler: Arc::new(show_desktop_notification),
+            location_provider: Rc::new(FixedLocationProvider::default()),
+            last_error: None,
+            performance: PerformanceTimeline::new(),
+            history: Vec::new(),
+            history_index: 0,
+            scroll_offset: (0.0, 0.0),
+            user_styles: None,
+            extensions: Extensions::new(),
+            media_features: MediaFeatures::default(),
+            console: ConsoleSink::new(),
+            document_source: None,
+            crash_recovery: None,
+            error_page: None,
+            retry_navigation: None,
+            visited: VisitedStore::new(),
+            zoom: ZoomStore::new(),
+            zoom_level: zoom::DEFAULT_ZOOM,
+            page_script: None,
+            cache_storage: CacheStorage::new(),
+            visibility_state: VisibilityState::Visible,
+            freezing_policy: TabFreezingPolicy::default(),
+            prefetch_policy: PrefetchPolicy::default(),
+            prefetch_cache: PrefetchCache::new(),
+            metered_connection: false,
+            pending_prefetches: Vec::new(),
+            deterministic: None,
+            geometry_observers: GeometryObservers::new(),
+            spellchecker: Rc::new(RefCell::new(DictionarySpellchecker::new())),
+            context_menu_contributors: Vec::new(),
+            downloads: DownloadManager::default(),
+            inspected: None,
+            passwords: PasswordStore::new(),
+            password_save_prompt: Arc::new(prompt_save_password),
+            cosmetic_filters: None,
             is_running: false,
         }
     }
@@ -78,22 +417,166 @@ impl BrowserEngine {
     /// 
     /// `true` if the HTML was successfully loaded and parsed, `false` otherwise
     pub fn load_html(&mut self, html_content: &str) -> bool {
+        if !self.prompt_to_unload() {
+            return false;
+        }
+        self.load_html_from(html_content, None, dom::clock::now())
+    }
+    
+    /// Ask the current document whether it may be left, before a
+    /// navigation starts
+    /// 
+    /// Fires `beforeunload` in the document's script engine, if it has
+    /// one; a page asking to confirm gets the leave/stay prompt. Returns
+    /// `false` if the user chose to stay, which cancels the navigation.
+    pub fn prompt_to_unload(&mut self) -> bool {
+        let leave = self.page_script.as_mut().is_none_or(JsEngine::prompt_to_unload);
+        if !leave {
+            println!("Staying on {}", self.current_url.as_deref().unwrap_or("the page"));
+        }
+        leave
+    }
+    
+    /// Unload the current document before the next one is shown
+    /// 
+    /// Its script engine fires `pagehide` and `unload`, then its fetches
+    /// are aborted and its timers and observers dropped. Prefetches the
+    /// document queued are dropped too.
+    fn unload_document(&mut self) {
+        self.pending_prefetches.clear();
+        let Some(mut script) = self.page_script.take() else {
+            return;
+        };
+        let report = script.unload();
+        if !report.is_empty() {
+            println!(
+                "🧹 Unloaded {}: aborted {} requests, dropped {} tasks, disconnected {} observers",
+                self.current_url.as_deref().unwrap_or("the page"),
+                report.aborted_requests,
+                report.dropped_tasks,
+                report.disconnected_observers,
+            );
+        }
+    }
+    
+    /// Get the script engine running the current document, creating it
+    /// on first use
+    /// 
+    /// Unlike the engines of `create_script_engine`, it lives as long as
+    /// the document and is unloaded when the tab navigates away.
+    pub fn page_script(&mut self) -> JsResult<&mut JsEngine> {
+        if self.page_script.is_none() {
+            let mut script = self.create_script_engine()?;
+            if let Some(url) = self.document_url() {
+                run_content_scripts(&mut script, &self.extensions.scripts_for(&url, RunAt::DocumentStart));
+            }
+            self.page_script = Some(script);
+        }
+        Ok(self.page_script.as_mut().expect("page script was just created"))
+    }
+    
+    /// Run the `document_end` content scripts of extensions in the current
+    /// document, which starts its page script and so runs the
+    /// `document_start` ones before them
+    fn inject_content_scripts(&mut self) {
+        let Some(url) = self.document_url() else {
+            return;
+        };
+        let scripts = self.extensions.scripts_for(&url, RunAt::DocumentEnd);
+        if scripts.is_empty() && self.extensions.scripts_for(&url, RunAt::DocumentStart).is_empty() {
+            return;
+        }
+        match self.page_script() {
+            Ok(script) => {
+                run_content_scripts(script, &scripts);
+            }
+            Err(e) => println!("❌ Failed to run the page's scripts: {}", e),
+        }
+    }
+    
+    /// Get the URL of the current document, if it has one
+    fn document_url(&self) -> Option<String> {
+        self.current_document.as_ref()?.url()
+    }
+    
+    /// Load HTML content fetched from `url`, if it came from the network
+    /// 
+    /// The document's performance timeline starts at `navigation_start`.
+    fn load_html_from(&mut self, html_content: &str, url: Option<&str>, navigation_start: Instant) -> bool {
         match self.parse_html_safely(html_content) {
             Ok(document) => {
-                let document_rc = Rc::new(document);
-                self.current_document = Some(Rc::clone(&document_rc));
-                // self.js_engine.set_document(document_rc);
-                // Clear layout when HTML changes
-                self.current_layout = None;
+                self.show_document(document, Some(html_content), url, navigation_start);
                 true
             }
             Err(e) => {
-                eprintln!("Error parsing HTML: {}", e);
+                let e = match url {
+                    Some(url) => e.with_url(url),
+                    None => e,
+                };
+                self.report_error(e);
                 false
             }
         }
     }
     
+    /// Make `document` the current document
+    /// 
+    /// `source` is the HTML it was parsed from, if it was parsed, and `url`
+    /// where it came from.
+    fn show_document(&mut self, document: Document, source: Option<&str>, url: Option<&str>, navigation_start: Instant) {
+        self.unload_document();
+        let document_rc = Rc::new(document);
+        self.performance = PerformanceTimeline::with_time_origin(navigation_start);
+        self.console.take();
+        self.scroll_offset = (0.0, 0.0);
+        self.current_document = Some(Rc::clone(&document_rc));
+        self.document_source = source.map(str::to_string);
+        self.crash_recovery = None;
+        self.error_page = None;
+        self.retry_navigation = None;
+        self.event_manager = DomEventManager::new();
+        self.event_manager.set_document(Rc::clone(&document_rc));
+        self.pending_prefetches.clear();
+        self.zoom_level = url.and_then(|url| url::Url::parse(url).ok()).map_or(zoom::DEFAULT_ZOOM, |url| self.zoom.zoom_for(&url));
+        if self.frame_sandbox.is_some() {
+            document_rc.set_sandbox_flags(self.frame_sandbox);
+        }
+        if let Some(url) = url {
+            document_rc.set_url(url);
+            if let Ok(url) = url::Url::parse(url) {
+                self.visited.record(&url);
+                for hint in resource_hints(&document_rc) {
+                    self.queue_prefetch(&url, hint);
+                }
+            }
+        }
+        mark_visited_links(&document_rc, &self.visited);
+        page_visibility::set_visibility_state(&document_rc.root, self.visibility_state);
+        // self.js_engine.set_document(document_rc);
+        // Clear layout when HTML changes
+        self.current_layout = None;
+        self.cosmetic_filters = self.cosmetic_filters_for(url);
+        self.autofill_logins();
+        self.inject_content_scripts();
+    }
+    
+    /// Load an internal page, generated from the engine's current state
+    /// 
+    /// Returns `false` if `url` names no internal page.
+    fn load_about_page(&mut self, url: &str, navigation_start: Instant) -> bool {
+        let Some(page) = AboutPage::from_url(url) else {
+            self.report_error(EngineError::shell(EnginePhase::Navigation, "No such internal page").with_url(url));
+            return false;
+        };
+        let document = about::build_page(page, self);
+        let url = page.to_string();
+        self.show_document(document, None, Some(&url), navigation_start);
+        self.load_css(about::ABOUT_STYLESHEET);
+        self.current_url = Some(url.clone());
+        self.push_history(&url);
+        true
+    }
+    
     /// Load CSS content and parse it into a stylesheet
     /// 
     /// This method takes CSS content, parses it using the CSS parser,
@@ -110,13 +593,15 @@ impl BrowserEngine {
         match self.parse_css_safely(css_content) {
             Ok(stylesheet) => {
                 self.current_stylesheet = Some(stylesheet.clone());
+                self.stylesheet_source = Some(css_content.to_string());
+                self.used_css_rules.clear();
                 // self.js_engine.set_stylesheet(stylesheet);
                 // Clear layout when CSS changes
                 self.current_layout = None;
                 true
             }
             Err(e) => {
-                eprintln!("Error parsing CSS: {}", e);
+                self.report_error(e);
                 false
             }
         }
@@ -135,18 +620,309 @@ impl BrowserEngine {
     /// 
     /// `true` if the URL was successfully fetched and loaded, `false` otherwise
     pub async fn fetch_url(&mut self, url: &str) -> bool {
+        let navigation_start = dom::clock::now();
+        if let Err(e) = SecurityPolicy::default().check_navigation(url) {
+            self.report_error(EngineError::from(e).with_url(url));
+            return false;
+        }
+        if !self.prompt_to_unload() {
+            return false;
+        }
+        if is_about_url(url) {
+            return self.load_about_page(url, navigation_start);
+        }
+        
+        if let Some(response) = self.prefetch_cache.take(url, Instant::now()) {
+            println!("⚡ Using the prefetched copy of {}", url);
+            let html_content = String::from_utf8_lossy(&response.body);
+            let loaded = self.load_html_from(&html_content, Some(url), navigation_start);
+            self.current_url = Some(url.to_string());
+            if loaded {
+                self.push_history(url);
+            }
+            return loaded;
+        }
+        
         match self.http_client.fetch_html(url).await {
             Ok(html_content) => {
                 println!("Fetched {} bytes from {}", html_content.len(), url);
-                self.load_html(&html_content)
+                let loaded = self.load_html_from(&html_content, Some(url), navigation_start);
+                self.current_url = Some(url.to_string());
+                if loaded {
+                    self.push_history(url);
+                }
+                loaded
             }
             Err(e) => {
-                eprintln!("Error fetching URL {}: {}", url, e);
+                if self.load_offline_copy(url, navigation_start) {
+                    println!("📦 {} is unreachable ({}); showing the pinned copy", url, e);
+                    return true;
+                }
+                self.report_error(EngineError::from(e).with_url(url));
                 false
             }
         }
     }
     
+    /// Load the pinned copy of a page, with its pinned stylesheets
+    /// 
+    /// Returns `false` if the page is not pinned.
+    fn load_offline_copy(&mut self, url: &str, navigation_start: Instant) -> bool {
+        if !self.cache_storage.has(OFFLINE_CACHE) {
+            return false;
+        }
+        let Ok(cache) = self.cache_storage.open_cache(OFFLINE_CACHE) else {
+            return false;
+        };
+        let Some(page) = cache.get(url) else {
+            return false;
+        };
+        let html = String::from_utf8_lossy(&page.body).into_owned();
+        if !self.load_html_from(&html, Some(url), navigation_start) {
+            return false;
+        }
+        if let Some(document) = &self.current_document {
+            let stylesheets = pinned_stylesheets(&cache, document);
+            if !stylesheets.is_empty() {
+                self.load_css(&stylesheets);
+            }
+        }
+        self.current_url = Some(url.to_string());
+        self.push_history(url);
+        true
+    }
+    
+    /// Pin the current page for offline use
+    /// 
+    /// The page and its stylesheets, scripts and images are stored in the
+    /// offline cache. Returns `None` if the page was not fetched from a
+    /// URL.
+    pub async fn pin_for_offline(&mut self) -> Option<PinReport> {
+        let url = url::Url::parse(self.current_url.as_deref()?).ok()?;
+        let html = self.document_source.clone()?;
+        let document = Rc::clone(self.current_document.as_ref()?);
+        let cache = match self.cache_storage.open_cache(OFFLINE_CACHE) {
+            Ok(cache) => cache,
+            Err(e) => {
+                eprintln!("❌ Failed to open the offline cache: {}", e);
+                return None;
+            }
+        };
+        Some(pin_page(&cache, &self.http_client, &url, &html, &document).await)
+    }
+    
+    /// Keep page caches and pinned pages in a shared storage
+    pub fn set_cache_storage(&mut self, storage: CacheStorage) {
+        self.cache_storage = storage;
+    }
+    
+    /// Queue a prefetch for when the tab is idle, if the policy allows it
+    /// from a page at `page`
+    fn queue_prefetch(&mut self, page: &url::Url, hint: PrefetchHint) -> bool {
+        let already_fetched = hint.kind != PrefetchKind::Preconnect && self.prefetch_cache.contains(hint.url.as_str(), Instant::now());
+        if already_fetched || self.pending_prefetches.contains(&hint) || !self.prefetch_policy.allows(page, &hint.url, self.metered_connection) {
+            return false;
+        }
+        self.pending_prefetches.push(hint);
+        true
+    }
+    
+    /// Rest the pointer on the element with the given `id` attribute
+    /// 
+    /// With hover prefetching on, the target of the link the element is in
+    /// is queued for prefetching. Returns whether it was.
+    pub fn hover_element(&mut self, element_id: &str) -> bool {
+        if !self.prefetch_policy.hover {
+            return false;
+        }
+        let (Some(document), Some(page)) = (&self.current_document, self.current_url.as_deref().and_then(|url| url::Url::parse(url).ok())) else {
+            return false;
+        };
+        let Some(url) = self.event_manager.find_node_by_id(element_id).and_then(|node| link_target(document, &node)) else {
+            return false;
+        };
+        self.queue_prefetch(&page, PrefetchHint { url, kind: PrefetchKind::Prefetch })
+    }
+    
+    /// Get the prefetches waiting for the tab to be idle
+    pub fn pending_prefetches(&self) -> &[PrefetchHint] {
+        &self.pending_prefetches
+    }
+    
+    /// Run the queued prefetches, one at a time
+    /// 
+    /// The shell calls this when it is idle. Returns the number of hints
+    /// that were fetched.
+    pub async fn run_prefetches(&mut self) -> usize {
+        let mut fetched = 0;
+        for hint in std::mem::take(&mut self.pending_prefetches) {
+            match prefetch(&self.http_client, &self.prefetch_cache, &hint).await {
+                Ok(()) => {
+                    println!("⚡ {} {}", hint.kind.as_str(), hint.url);
+                    fetched += 1;
+                }
+                Err(e) => println!("⚠️  Could not {} {}: {}", hint.kind.as_str(), hint.url, e),
+            }
+        }
+        fetched
+    }
+    
+    /// Choose which resource hints and hovered links are fetched ahead of
+    /// time
+    pub fn set_prefetch_policy(&mut self, policy: PrefetchPolicy) {
+        self.prefetch_policy = policy;
+    }
+    
+    /// Get the policy prefetches are checked against
+    pub fn prefetch_policy(&self) -> PrefetchPolicy {
+        self.prefetch_policy
+    }
+    
+    /// Keep prefetched responses in a cache shared with other tabs
+    pub fn set_prefetch_cache(&mut self, cache: PrefetchCache) {
+        self.prefetch_cache = cache;
+    }
+    
+    /// Tell the tab whether the network connection is metered
+    /// 
+    /// Nothing is prefetched on a metered connection unless the policy
+    /// allows it.
+    pub fn set_metered_connection(&mut self, metered: bool) {
+        self.metered_connection = metered;
+        if metered && !self.prefetch_policy.on_metered_connection {
+            self.pending_prefetches.clear();
+        }
+    }
+
+    /// Put the tab in deterministic mode, or take it out with `None`
+    ///
+    /// The mode's clock becomes the engine clock of this thread, layout
+    /// uses the bundled test font, and script engines created afterwards
+    /// get a seeded `Math.random()`.
+    pub fn set_deterministic_mode(&mut self, mode: Option<DeterministicMode>) {
+        match &mode {
+            Some(mode) => mode.install(),
+            None => dom::clock::reset_clock(),
+        }
+        self.deterministic = mode;
+        self.performance = PerformanceTimeline::new();
+    }
+
+    /// Get the deterministic mode of the tab, if it is in one
+    pub fn deterministic_mode(&self) -> Option<&DeterministicMode> {
+        self.deterministic.as_ref()
+    }
+
+    /// Get the geometry observers notified after every layout of the tab,
+    /// see `LayoutEngine::observe_geometry`
+    pub fn geometry_observers(&self) -> &GeometryObservers {
+        &self.geometry_observers
+    }
+
+    /// Mark misspelled words with a spellchecker shared with other tabs
+    pub fn set_spellchecker(&mut self, spellchecker: SharedSpellchecker) {
+        self.spellchecker = spellchecker;
+    }
+
+    /// Get the spellchecker that marks misspelled words of editable text
+    pub fn spellchecker(&self) -> SharedSpellchecker {
+        Rc::clone(&self.spellchecker)
+    }
+
+    /// Add items of a subsystem to the context menus of the tab
+    pub fn add_context_menu_contributor(&mut self, contributor: SharedContributor) {
+        self.context_menu_contributors.push(contributor);
+    }
+
+    /// Save downloads with a manager shared with other tabs
+    pub fn set_download_manager(&mut self, downloads: DownloadManager) {
+        self.downloads = downloads;
+    }
+
+    pub fn download_manager(&self) -> &DownloadManager {
+        &self.downloads
+    }
+
+    /// Get the element last inspected from a context menu
+    pub fn inspected_node(&self) -> Option<Rc<Node>> {
+        self.inspected.clone()
+    }
+
+    /// Build the context menu for a right-click at a window position
+    ///
+    /// Returns `None` if no element of the current layout is hit. In
+    /// spellchecked text the character under the pointer is estimated
+    /// from the position across the element's box.
+    pub fn context_menu_at(&self, x: f32, y: f32) -> Option<ContextMenu> {
+        let layout = self.current_layout.as_ref()?;
+        let element = layout.hit_test(x, y).filter(|node| !node.is_inert())?;
+        let resolver = self.current_document.as_deref().map_or_else(UrlResolver::default, UrlResolver::for_document);
+        let mut target = ContextMenuTarget::new(&element, &resolver);
+        let text = element.children.borrow().iter().find(|child| child.character_data().is_some()).cloned();
+        if let (Some(text), Some(border)) = (text.filter(|text| text.spellcheck_enabled()), layout::geometry::border_boxes(layout).get(&element.id)) {
+            let length = text.character_data().unwrap_or_default().chars().count();
+            let fraction = if border.width > 0.0 { ((x - border.x) / border.width).clamp(0.0, 1.0) } else { 0.0 };
+            target = target.with_text(&text, (fraction * length as f32) as usize);
+        }
+        Some(self.context_menu(&target))
+    }
+
+    /// Build the context menu for a target
+    pub fn context_menu(&self, target: &ContextMenuTarget) -> ContextMenu {
+        ContextMenu::for_target(target, &*self.spellchecker.borrow(), &self.context_menu_contributors)
+    }
+
+    /// Run an item of a context menu built with `context_menu`
+    ///
+    /// After a spelling item the page is laid out again, as a replaced
+    /// word or a word added to the dictionary changes what is marked.
+    /// Returns `false` if the action failed or is run by the shell:
+    /// opening a new tab and reloading.
+    pub fn run_context_menu_action(&mut self, action: &ContextMenuAction) -> bool {
+        match action {
+            ContextMenuAction::ReplaceWord { .. } | ContextMenuAction::AddToDictionary(_) => {
+                let ran = context_menu::run_context_menu_action(action, &mut *self.spellchecker.borrow_mut());
+                if ran {
+                    self.current_layout = None;
+                }
+                ran
+            }
+            ContextMenuAction::CopyLink(text) | ContextMenuAction::CopyImage(text) | ContextMenuAction::CopySelection(text) => {
+                match self.clipboard.write_text(text) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        println!("❌ Failed to copy: {}", e);
+                        false
+                    }
+                }
+            }
+            ContextMenuAction::SaveImage(url) if self.frame_sandbox.is_some_and(|flags| !flags.downloads) => {
+                println!("🛑 Download of {} blocked by the frame's sandbox", url);
+                false
+            }
+            ContextMenuAction::SaveImage(url) => match block_on(self.downloads.download(&self.http_client, url)) {
+                Ok(path) => {
+                    println!("💾 Saved {} to {}", url, path.display());
+                    true
+                }
+                Err(e) => {
+                    println!("❌ {}", e);
+                    false
+                }
+            },
+            ContextMenuAction::Inspect(node) => {
+                self.inspected = Some(Rc::clone(node));
+                true
+            }
+            ContextMenuAction::Contributed { contributor, command } => self
+                .context_menu_contributors
+                .iter()
+                .find(|candidate| candidate.borrow().name() == contributor)
+                .is_some_and(|candidate| candidate.borrow_mut().run(command)),
+            ContextMenuAction::OpenLinkInNewTab(_) | ContextMenuAction::Reload => false,
+        }
+    }
+
     // /// Execute JavaScript code
     // /// 
     // /// This method executes JavaScript code and optionally triggers layout
@@ -212,150 +988,1159 @@ impl BrowserEngine {
     /// 
     /// `true` if layout was successfully calculated, `false` otherwise
     pub fn perform_layout(&mut self) -> bool {
-        if let (Some(document), Some(stylesheet)) = (&self.current_document, &self.current_stylesheet) {
-            let layout_engine = LayoutEngine::new(stylesheet.clone());
-            let layout = layout_engine.layout_document(document);
+        self.dom_mutations.borrow_mut().clear();
+        if let (Some(document), Some(layout_engine)) = (&self.current_document, self.layout_engine()) {
+            let (width, height) = screenshot::DEFAULT_VIEWPORT;
+            let layout = layout_engine.layout_document_in(document, Dimensions::new(0.0, 0.0, width / self.zoom_level, height / self.zoom_level));
+            self.used_css_rules.extend(layout_engine.matched_rules());
             self.current_layout = Some(layout);
             true
         } else {
-            eprintln!("Cannot perform layout: missing document or stylesheet");
+            self.report_error(EngineError::shell(EnginePhase::Layout, "missing document or stylesheet"));
             false
         }
     }
     
-    /// Safely parse HTML content with error handling
-    /// 
-    /// This method wraps the HTML parsing in error handling to provide
-    /// graceful degradation when encountering malformed HTML.
-    fn parse_html_safely(&self, html_content: &str) -> Result<Document, String> {
-        // For now, we'll use a simple approach - in a real browser,
-        // this would include more sophisticated error recovery
-        if html_content.trim().is_empty() {
-            return Err("Empty HTML content".to_string());
-        }
-        
-        match html_parser::parse_html_string(html_content) {
-            Ok((document, _resources)) => Ok(document),
-            Err(e) => Err(format!("HTML parsing error: {}", e)),
+    /// Extract the visible text of the page as segments to translate,
+    /// laying the page out first if needed
+    pub fn text_segments(&mut self) -> Vec<TextSegment> {
+        if self.current_layout.is_none() && !self.perform_layout() {
+            return Vec::new();
         }
+        self.current_layout.as_ref().map(text_segments).unwrap_or_default()
     }
     
-    /// Safely parse CSS content with error handling
+    /// Replace the text of the page with translations and lay it out again
     /// 
-    /// This method wraps the CSS parsing in error handling to provide
-    /// graceful degradation when encountering malformed CSS.
-    fn parse_css_safely(&self, css_content: &str) -> Result<Stylesheet, String> {
-        // For now, we'll use a simple approach - in a real browser,
-        // this would include more sophisticated error recovery
-        if css_content.trim().is_empty() {
-            return Err("Empty CSS content".to_string());
+    /// `translate` gets each segment and returns the translation of its
+    /// source, or `None` to keep it. Returns the number of segments
+    /// translated.
+    pub fn translate_page(&mut self, mut translate: impl FnMut(&TextSegment) -> Option<String>) -> usize {
+        let mut translated = 0;
+        for segment in self.text_segments() {
+            let Some(translation) = translate(&segment) else { continue };
+            match segment.inject(&translation) {
+                Ok(_) => translated += 1,
+                Err(e) => println!("⚠️  Could not translate segment {}: {}", segment.id, e),
+            }
         }
-        
-        Ok(parse_css(css_content))
+        if translated > 0 {
+            self.perform_layout();
+        }
+        translated
     }
     
-    /// Render the current document to text
+    /// Find text in the current page with the find-in-page helper
     /// 
-    /// This method renders the current document using the text renderer
-    /// and returns the formatted output.
-    /// 
-    /// # Returns
-    /// 
-    /// The rendered text output, or an error message if no document is loaded
-    pub fn render_to_text(&self) -> String {
-        match &self.current_document {
-            Some(document) => render_as_text(document),
-            None => "No document loaded".to_string(),
+    /// Returns `None` if no document is loaded or the helper failed.
+    pub fn find_in_page(&mut self, query: &str) -> Option<find_in_page::FindResult> {
+        let origin = self.current_document.as_ref()?.origin().serialize();
+        let script = match self.page_script() {
+            Ok(script) => script,
+            Err(e) => {
+                println!("❌ Failed to run the page's scripts: {}", e);
+                return None;
+            }
+        };
+        let posted = match script.shell_bridge().has_helper(find_in_page::FIND_HELPER) {
+            true => Ok(()),
+            false => script.install_helper(find_in_page::FIND_HELPER, find_in_page::FIND_HELPER_SOURCE),
         }
+        .and_then(|_| script.post_to_helper(&origin, find_in_page::FIND_HELPER, &serde_json::json!({ "query": query })));
+        if let Err(e) = posted {
+            println!("❌ Find in page failed: {}", e);
+            return None;
+        }
+        script
+            .take_helper_messages()
+            .into_iter()
+            .filter(|message| message.helper == find_in_page::FIND_HELPER && message.origin == origin)
+            .find_map(|message| serde_json::from_value(message.data).ok())
     }
     
-    /// Render the current layout with styling information
-    /// 
-    /// This method renders the current layout tree with computed styles
-    /// and dimensions, showing how the browser engine has styled and
-    /// positioned each element.
-    /// 
-    /// # Returns
-    /// 
-    /// The rendered layout output, or an error message if no layout is available
-    pub fn render_layout(&self) -> String {
-        match &self.current_layout {
-            Some(layout) => render_layout_box(layout),
-            None => "No layout available. Load HTML, CSS, and perform layout first.".to_string(),
+    /// Create a layout engine for the current stylesheet, with the user
+    /// styles and media features of the tab
+    fn layout_engine(&self) -> Option<LayoutEngine> {
+        let mut layout_engine = LayoutEngine::new(self.current_stylesheet.clone()?);
+        let mut user_stylesheet = self.user_styles.as_ref().map(|user_styles| user_styles.borrow().stylesheet_for(self.current_url.as_deref()));
+        if let Some(injected) = self.document_url().and_then(|url| self.extensions.stylesheet_for(&url)) {
+            match &mut user_stylesheet {
+                Some(stylesheet) => stylesheet.rules.extend(injected.rules),
+                None => user_stylesheet = Some(injected),
+            }
+        }
+        if let Some(filters) = &self.cosmetic_filters {
+            let stylesheet = user_stylesheet.get_or_insert_with(|| Stylesheet { rules: vec![], page_rules: vec![], source_url: filters.source_url.clone() });
+            stylesheet.rules.extend(filters.rules.iter().cloned());
         }
+        if let Some(user_stylesheet) = user_stylesheet {
+            layout_engine.set_user_stylesheet(user_stylesheet);
+        }
+        layout_engine.set_media_features(self.zoomed_media_features());
+        layout_engine.set_minimum_font_size(self.zoom.minimum_font_size());
+        if self.deterministic.is_some() {
+            layout_engine.set_font_registry(FontRegistry::test_fonts());
+        }
+        layout_engine.set_geometry_observers(self.geometry_observers.clone());
+        Some(layout_engine)
     }
     
-    /// Extract text content from the current document
+    /// Click the element with the given `id` attribute
     /// 
-    /// This method extracts only the text content from the current document,
-    /// ignoring all HTML markup.
+    /// Runs the element's activation behavior (for example toggling a
+    /// `<details>` element) and recomputes layout if the DOM changed.
     /// 
     /// # Returns
     /// 
-    /// The text content, or an error message if no document is loaded
-    pub fn get_text_content(&self) -> String {
-        match &self.current_document {
-            Some(document) => extract_text_content(document),
-            None => "No document loaded".to_string(),
+    /// `true` if the click was dispatched and not cancelled, `false` otherwise
+    pub fn click_element(&mut self, element_id: &str) -> bool {
+        let dispatched = self.event_manager.simulate_click(element_id);
+        self.relayout_if_needed();
+        // The retry button of an error page loads the failing URL again
+        if dispatched && element_id == RETRY_BUTTON_ID {
+            self.retry_navigation = self.error_page.as_ref().and_then(|page| page.url.clone());
         }
+        dispatched
     }
     
-    /// Get a reference to the current document
-    /// 
-    /// This method provides access to the current DOM tree for advanced
-    /// operations that require direct DOM manipulation.
-    /// 
-    /// # Returns
+    /// Press a key while the element with the given `id` attribute is focused
     /// 
-    /// A reference to the current document, or `None` if no document is loaded
-    pub fn get_document(&self) -> Option<&Rc<Document>> {
-        self.current_document.as_ref()
+    /// Pressing Enter in a text field triggers implicit form submission.
+    pub fn press_key(&mut self, element_id: &str, key: &str) -> bool {
+        let dispatched = self.event_manager.simulate_keydown(element_id, key);
+        self.relayout_if_needed();
+        dispatched
     }
     
-    /// Check if a document is currently loaded
-    /// 
-    /// # Returns
-    /// 
-    /// `true` if a document is loaded, `false` otherwise
-    pub fn has_document(&self) -> bool {
-        self.current_document.is_some()
+    /// Type a value into the form control with the given `id` attribute
+    pub fn input_text(&mut self, element_id: &str, value: &str) -> bool {
+        let dispatched = self.event_manager.simulate_input(element_id, value);
+        self.relayout_if_needed();
+        dispatched
     }
     
-    /// Check if a stylesheet is currently loaded
-    /// 
-    /// # Returns
+    /// Bring the layout up to date if user input changed the DOM
     /// 
-    /// `true` if a stylesheet is loaded, `false` otherwise
-    pub fn has_stylesheet(&self) -> bool {
-        self.current_stylesheet.is_some()
+    /// Changed attributes restyle only the boxes they invalidate, and the
+    /// page is laid out again only if a changed style affects geometry.
+    /// Added, removed and edited nodes, and changes no mutation announces,
+    /// like a form control's value, change the boxes themselves and
+    /// relayout.
+    fn relayout_if_needed(&mut self) {
+        let mutations = std::mem::take(&mut *self.dom_mutations.borrow_mut());
+        if !self.event_manager.needs_relayout() && mutations.is_empty() {
+            return;
+        }
+        self.event_manager.clear_relayout();
+        if self.current_layout.is_some() && !self.restyle_for_mutations(&mutations) {
+            self.perform_layout();
+        }
     }
     
-    /// Check if layout has been calculated
-    /// 
-    /// # Returns
-    /// 
-    /// `true` if layout is available, `false` otherwise
-    pub fn has_layout(&self) -> bool {
-        self.current_layout.is_some()
+    /// Restyle the current layout for attribute mutations of the current
+    /// document, returning whether that brought the layout up to date
+    fn restyle_for_mutations(&mut self, mutations: &[Mutation]) -> bool {
+        let (Some(document), Some(layout_engine)) = (self.current_document.clone(), self.layout_engine()) else {
+            return false;
+        };
+        if mutations.is_empty() {
+            return false;
+        }
+        let matcher = layout_engine.style_matcher();
+        let mut invalidations: HashMap<u64, StyleInvalidation> = HashMap::new();
+        for mutation in mutations {
+            let id = match mutation {
+                Mutation::ChildList { parent } => *parent,
+                Mutation::Attribute { element, .. } => *element,
+                Mutation::CharacterData { node } => *node,
+            };
+            // Other documents on the thread are not laid out here
+            let Some(node) = find_node(&document.root, id) else {
+                continue;
+            };
+            if !matches!(mutation, Mutation::Attribute { .. }) {
+                return false;
+            }
+            for (id, invalidation) in matcher.invalidations_for_mutation(&node, mutation) {
+                let entry = invalidations.entry(id).or_insert(StyleInvalidation::None);
+                *entry = (*entry).max(invalidation);
+            }
+        }
+        let Some(layout) = self.current_layout.as_mut() else {
+            return false;
+        };
+        !matcher.restyle_layout_tree(layout, &invalidations).needs_layout
     }
     
-    /// Get a reference to the current layout
-    /// 
-    /// # Returns
-    /// 
-    /// A reference to the current layout, or `None` if no layout is available
-    pub fn get_layout(&self) -> Option<&LayoutBox> {
-        self.current_layout.as_ref()
+    /// Upgrade requests to HTTPS with a shared HSTS store and record new
+    /// policies in it
+    pub fn set_hsts_store(&mut self, store: HstsStore) {
+        self.http_client.set_hsts_store(store);
     }
-
-    /// Render the current layout using GPU
-    pub async fn render_with_gpu(&self) -> Result<(), Box<dyn std::error::Error>> {
-        if let Some(layout) = &self.current_layout {
-            render_layout_tree(layout).await?;
-            Ok(())
-        } else {
-            Err("No layout available. Load HTML, CSS, and perform layout first.".into())
-        }
+    
+    /// Block requests and hide elements with a shared content blocker's
+    /// filter lists
+    pub fn set_content_blocker(&mut self, blocker: ContentBlocker) {
+        self.http_client.set_content_blocker(blocker);
+        self.apply_cosmetic_filters();
+    }
+    
+    /// Get the filter lists the tab's requests are checked against
+    pub fn content_blocker(&self) -> &ContentBlocker {
+        self.http_client.content_blocker()
+    }
+    
+    /// Hide the elements of the current page the cosmetic filters target,
+    /// e.g. after filter lists were added
+    pub fn apply_cosmetic_filters(&mut self) {
+        self.cosmetic_filters = self.cosmetic_filters_for(self.current_url.as_deref());
+        self.restyle();
+    }
+    
+    /// Parse the element hiding styles of the cosmetic filters for a page
+    fn cosmetic_filters_for(&self, url: Option<&str>) -> Option<Stylesheet> {
+        let url = url::Url::parse(url?).ok()?;
+        let css = self.content_blocker().cosmetic_css(url.host_str()?);
+        if css.is_empty() {
+            return None;
+        }
+        // The styles rely on `!important`, so they go through the full
+        // parser. A selector it rejects only drops its own rule, which is on
+        // a line of its own.
+        let mut stylesheet = css_parser::CSSParser::new(css.clone()).parse_stylesheet().unwrap_or_else(|_| {
+            let rules = css.lines().filter_map(|rule| css_parser::CSSParser::new(rule.to_string()).parse_stylesheet().ok());
+            Stylesheet { rules: rules.flat_map(|stylesheet| stylesheet.rules).collect(), page_rules: vec![], source_url: None }
+        });
+        stylesheet.source_url = Some(format!("content-blocker:{}", url));
+        Some(stylesheet)
+    }
+    
+    /// Let `interceptor` answer the tab's requests, e.g. to replay a HAR
+    /// archive
+    pub fn set_request_interceptor(&mut self, interceptor: Arc<dyn RequestInterceptor>) {
+        self.http_client.set_request_interceptor(interceptor);
+    }
+    
+    /// Read and record the permissions of origins in a shared store
+    pub fn set_permission_store(&mut self, store: Permissions) {
+        self.permissions = store.with_prompt(Arc::clone(&self.permission_prompt));
+    }
+    
+    /// Ask the user for permissions with `prompt` instead of on the terminal
+    pub fn set_permission_prompt(&mut self, prompt: PermissionPrompt) {
+        self.permissions = self.permissions.with_prompt(Arc::clone(&prompt));
+        self.permission_prompt = prompt;
+    }
+    
+    /// Show the notifications of pages with `handler` instead of the
+    /// desktop's notification service
+    pub fn set_notification_handler(&mut self, handler: NotificationHandler) {
+        self.notification_handler = handler;
+    }
+    
+    /// Locate the device with `provider` instead of reporting a fixed
+    /// location
+    pub fn set_location_provider(&mut self, provider: Rc<dyn LocationProvider>) {
+        self.location_provider = provider;
+    }
+    
+    /// Get the permissions of origins
+    pub fn permissions(&self) -> &Permissions {
+        &self.permissions
+    }
+    
+    /// Record visits in a shared store and match `:visited` against it
+    pub fn set_visited_store(&mut self, store: VisitedStore) {
+        self.visited = store;
+        if let Some(document) = &self.current_document {
+            mark_visited_links(document, &self.visited);
+            self.current_layout = None;
+        }
+    }
+    
+    /// Style pages with the user's stylesheets
+    pub fn set_user_styles(&mut self, user_styles: Rc<RefCell<UserStyles>>) {
+        self.user_styles = Some(user_styles);
+        self.current_layout = None;
+    }
+    
+    /// Inject the content scripts and styles of extensions into pages
+    /// 
+    /// The current page gets the styles at once and the scripts when it is
+    /// loaded again.
+    pub fn set_extensions(&mut self, extensions: Extensions) {
+        self.extensions = extensions;
+        self.current_layout = None;
+    }
+    
+    /// Get the extensions injecting into pages
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+    
+    /// Lay the page out again if it has a layout, e.g. after the user
+    /// stylesheets changed
+    pub fn restyle(&mut self) {
+        if self.current_layout.is_some() {
+            self.perform_layout();
+        }
+    }
+    
+    /// Change the environment pages are styled for, e.g. the color scheme
+    /// 
+    /// Restyles the page if anything changed. Script engines created
+    /// earlier are updated through `sync_media_features`. Returns whether
+    /// the features changed.
+    pub fn set_media_features(&mut self, features: MediaFeatures) -> bool {
+        if features == self.media_features {
+            return false;
+        }
+        self.media_features = features;
+        self.restyle();
+        true
+    }
+    
+    pub fn media_features(&self) -> &MediaFeatures {
+        &self.media_features
+    }
+    
+    /// Get the environment the page is styled for at its zoom level
+    fn zoomed_media_features(&self) -> MediaFeatures {
+        zoom::zoomed_media_features(&self.media_features, self.zoom_level)
+    }
+    
+    /// Remember zoom levels and the minimum font size in a shared store
+    /// 
+    /// The current page takes the zoom level stored for its site.
+    pub fn set_zoom_store(&mut self, store: ZoomStore) {
+        self.zoom = store;
+        if let Some(url) = self.current_url.as_deref().and_then(|url| url::Url::parse(url).ok()) {
+            self.zoom_level = self.zoom.zoom_for(&url);
+        }
+        self.restyle();
+    }
+    
+    /// Get the store of zoom levels and the minimum font size
+    pub fn zoom_store(&self) -> &ZoomStore {
+        &self.zoom
+    }
+    
+    /// Get the zoom level of the current page, 1.0 for 100%
+    pub fn zoom_level(&self) -> f32 {
+        self.zoom_level
+    }
+    
+    /// Zoom the current page and remember the level for its site
+    /// 
+    /// The level is clamped to the range of `zoom::ZOOM_LEVELS`. Restyles
+    /// the page if the level changed. Returns whether it changed.
+    pub fn set_zoom_level(&mut self, level: f32) -> bool {
+        let level = zoom::clamp_zoom(level);
+        if (level - self.zoom_level).abs() < f32::EPSILON {
+            return false;
+        }
+        self.zoom_level = level;
+        if let Some(url) = self.current_url.as_deref().and_then(|url| url::Url::parse(url).ok()) {
+            if let Err(e) = self.zoom.set_zoom(&url, level) {
+                println!("❌ Failed to save zoom level: {}", e);
+            }
+        }
+        self.restyle();
+        true
+    }
+    
+    /// Zoom the current page in to the next preset level
+    pub fn zoom_in(&mut self) -> bool {
+        self.set_zoom_level(zoom::zoom_in(self.zoom_level))
+    }
+    
+    /// Zoom the current page out to the next preset level
+    pub fn zoom_out(&mut self) -> bool {
+        self.set_zoom_level(zoom::zoom_out(self.zoom_level))
+    }
+    
+    /// Set the current page back to 100%
+    pub fn reset_zoom(&mut self) -> bool {
+        self.set_zoom_level(zoom::DEFAULT_ZOOM)
+    }
+    
+    /// Get the console messages of the page and its scripts
+    pub fn console(&self) -> &ConsoleSink {
+        &self.console
+    }
+    
+    /// Count the insecure requests blocked on this page, shown as the
+    /// shield next to the prompt
+    pub fn blocked_mixed_content(&self) -> usize {
+        self.console.messages().iter().filter(|message| message.is_blocked_mixed_content()).count()
+    }
+    
+    /// Count the requests of this page the content blocker blocked
+    pub fn blocked_requests(&self) -> usize {
+        self.console.messages().iter().filter(|message| message.is_blocked_by_client()).count()
+    }
+    
+    /// Bring a script engine's `matchMedia` lists up to date, firing their
+    /// `change` events
+    /// 
+    /// Returns the number of lists that changed.
+    pub fn sync_media_features(&self, script: &mut JsEngine) -> usize {
+        script.set_media_features(self.zoomed_media_features())
+    }
+    
+    /// Take the next form submission triggered by user input
+    pub fn take_form_submission(&mut self) -> Option<FormSubmission> {
+        self.event_manager.take_form_submission()
+    }
+    
+    /// Take the URL of the next link the user followed
+    /// 
+    /// Links whose `href` does not resolve are skipped. A click on the
+    /// retry button of an error page navigates to the URL that failed.
+    pub fn take_link_navigation(&mut self) -> Option<String> {
+        if let Some(url) = self.retry_navigation.take() {
+            return Some(url);
+        }
+        while let Some(href) = self.event_manager.take_navigation() {
+            if let Some(url) = self.resolve_document_url(&href) {
+                return Some(url);
+            }
+        }
+        None
+    }
+    
+    /// Get the fullscreen element of the current document
+    /// 
+    /// The GPU window is in fullscreen mode exactly while there is one.
+    pub fn fullscreen_element(&self) -> Option<Rc<Node>> {
+        self.current_document.as_ref().and_then(|document| fullscreen::fullscreen_element(&document.root))
+    }
+    
+    /// Take the current document out of fullscreen, as when the user
+    /// leaves fullscreen mode, firing `fullscreenchange`
+    /// 
+    /// # Returns
+    /// 
+    /// `true` if the document was in fullscreen, `false` otherwise
+    pub fn exit_fullscreen(&mut self) -> bool {
+        let Some(root) = self.current_document.as_ref().map(|document| Rc::clone(&document.root)) else {
+            return false;
+        };
+        let exited = self.event_manager.handle_user_input(|manager| manager.fully_exit_fullscreen(&root));
+        self.relayout_if_needed();
+        exited
+    }
+    
+    /// Get whether the tab is visible
+    pub fn visibility_state(&self) -> VisibilityState {
+        self.visibility_state
+    }
+    
+    /// Show or hide the tab, as when the user switches tabs or minimizes
+    /// the window, firing `visibilitychange` at the current document
+    /// 
+    /// Script engines follow with `sync_visibility`.
+    /// 
+    /// # Returns
+    /// 
+    /// `true` if the visibility changed, `false` otherwise
+    pub fn set_visibility_state(&mut self, state: VisibilityState) -> bool {
+        if state == self.visibility_state {
+            return false;
+        }
+        self.visibility_state = state;
+        if let Some(root) = self.current_document.as_ref().map(|document| Rc::clone(&document.root)) {
+            self.event_manager.set_visibility_state(&root, state);
+        }
+        true
+    }
+    
+    /// Set how script engines are throttled while the tab is hidden
+    pub fn set_tab_freezing_policy(&mut self, policy: TabFreezingPolicy) {
+        self.freezing_policy = policy;
+    }
+    
+    /// Bring a script engine of the tab up to date with its visibility,
+    /// throttling it while hidden and firing `visibilitychange` at its
+    /// `document`
+    /// 
+    /// Returns whether the event fired.
+    pub fn sync_visibility(&self, script: &mut JsEngine) -> bool {
+        self.freezing_policy.apply(self.visibility_state, script.event_loop());
+        script.set_visibility_state(self.visibility_state)
+    }
+    
+    /// Send a form submission and load the response as the new document
+    /// 
+    /// GET submissions navigate to the action URL with the form data as its
+    /// query string; POST submissions send the form-encoded data as the body.
+    /// 
+    /// # Returns
+    /// 
+    /// `true` if the response was fetched and loaded, `false` otherwise
+    pub async fn submit_form(&mut self, submission: FormSubmission) -> bool {
+        if self.frame_sandbox.is_some_and(|flags| !flags.forms) {
+            self.report_error(EngineError::shell(EnginePhase::Navigation, "form submission blocked by the frame's sandbox"));
+            return false;
+        }
+        self.offer_to_save_login(&submission);
+        let action = match self.resolve_document_url(&submission.action) {
+            Some(action) => action,
+            None => {
+                let message = format!("unable to resolve form action '{}'", submission.action);
+                self.report_error(EngineError::shell(EnginePhase::Navigation, message));
+                return false;
+            }
+        };
+        
+        match submission.method {
+            FormMethod::Get => match networking::form_submission_url(&action, &submission.entries) {
+                Ok(url) => self.fetch_url(&url).await,
+                Err(e) => {
+                    self.report_error(EngineError::from(e).in_phase(EnginePhase::Navigation).with_url(action));
+                    false
+                }
+            },
+            FormMethod::Post => {
+                if !self.prompt_to_unload() {
+                    return false;
+                }
+                let navigation_start = dom::clock::now();
+                let request = HttpRequest::form_post(action.clone(), &submission.entries);
+                match self.http_client.send_request(request).await {
+                    Ok(response) => {
+                        let html_content = String::from_utf8_lossy(&response.body).to_string();
+                        let loaded = self.load_html_from(&html_content, Some(&response.url), navigation_start);
+                        if loaded {
+                            self.push_history(&response.url);
+                        }
+                        self.current_url = Some(response.url);
+                        loaded
+                    }
+                    Err(e) => {
+                        self.report_error(EngineError::from(e).with_url(action));
+                        false
+                    }
+                }
+            }
+        }
+    }
+    
+    /// Add a navigation to the history, dropping any forward entries
+    fn push_history(&mut self, url: &str) {
+        if !self.history.is_empty() {
+            self.history.truncate(self.history_index + 1);
+        }
+        self.history.push(url.to_string());
+        self.history_index = self.history.len() - 1;
+    }
+
+    /// Get the URLs visited, oldest first, and the position of the current
+    /// document among them
+    pub fn history(&self) -> (&[String], usize) {
+        (&self.history, self.history_index)
+    }
+
+    /// Get the scroll offset of the viewport
+    pub fn scroll_offset(&self) -> (f32, f32) {
+        self.scroll_offset
+    }
+
+    /// Record the scroll offset of the viewport, e.g. after the user
+    /// scrolled in the renderer
+    pub fn set_scroll_offset(&mut self, x: f32, y: f32) {
+        self.scroll_offset = (x, y);
+    }
+
+    /// Capture an area of the current page at a device scale factor
+    ///
+    /// Lays the page out first if needed; a page without a stylesheet is
+    /// laid out with the user agent styles only.
+    pub fn capture_screenshot(&mut self, area: &screenshot::CaptureArea, scale: f32) -> screenshot::ScreenshotResult<renderer_wgpu::filters::Layer> {
+        if self.current_stylesheet.is_none() {
+            self.current_stylesheet = Some(Stylesheet { rules: vec![], page_rules: vec![], source_url: None });
+        }
+        if self.current_layout.is_none() && !self.perform_layout() {
+            return Err(screenshot::ScreenshotError::NoLayout);
+        }
+        let layout = self.current_layout.as_ref().ok_or(screenshot::ScreenshotError::NoLayout)?;
+        screenshot::capture(layout, area, self.scroll_offset, self.zoom_level, scale)
+    }
+
+    /// Dump the DOM, layout tree and display list of the current page for
+    /// snapshot tests, laying the page out first if needed
+    pub fn capture_snapshot(&mut self) -> snapshots::SnapshotResult<snapshots::PageSnapshot> {
+        if self.current_stylesheet.is_none() {
+            self.current_stylesheet = Some(Stylesheet { rules: vec![], page_rules: vec![], source_url: None });
+        }
+        if self.current_layout.is_none() && !self.perform_layout() {
+            return Err(snapshots::SnapshotError::NoLayout);
+        }
+        let document = self.current_document.as_ref().ok_or(snapshots::SnapshotError::NoLayout)?;
+        let layout = self.current_layout.as_ref().ok_or(snapshots::SnapshotError::NoLayout)?;
+        Ok(snapshots::PageSnapshot::new(&document.root, layout))
+    }
+
+    /// Print the current page to PDF, on the pages its `@page` rules give
+    ///
+    /// A page without a stylesheet is printed with the user agent styles
+    /// only. The on-screen layout is left as it is.
+    pub fn print_to_pdf(&mut self) -> print::PrintResult<Vec<u8>> {
+        if self.current_stylesheet.is_none() {
+            self.current_stylesheet = Some(Stylesheet { rules: vec![], page_rules: vec![], source_url: None });
+        }
+        let document = self.current_document.clone().ok_or(print::PrintError::NoDocument)?;
+        let mut layout_engine = self.layout_engine().ok_or(print::PrintError::NoDocument)?;
+        Ok(print::write_pdf(&layout_engine.layout_pages(&document)))
+    }
+
+    /// Capture the state of this engine for the session file
+    pub fn capture_tab_state(&self) -> TabState {
+        TabState {
+            url: self.current_url.clone(),
+            history: self.history.clone(),
+            history_index: self.history_index,
+            scroll_x: self.scroll_offset.0,
+            scroll_y: self.scroll_offset.1,
+            form_controls: self
+                .current_document
+                .as_ref()
+                .map(|document| session::capture_form_controls(&document.root))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Apply saved history, scroll offset and form values to the current
+    /// document
+    /// 
+    /// Returns the number of form controls restored.
+    pub fn restore_tab_state(&mut self, state: &TabState) -> usize {
+        self.history = state.history.clone();
+        self.history_index = state.history_index.min(state.history.len().saturating_sub(1));
+        self.scroll_offset = (state.scroll_x, state.scroll_y);
+        match &self.current_document {
+            Some(document) => session::restore_form_controls(&document.root, &state.form_controls),
+            None => 0,
+        }
+    }
+
+    /// Load a saved tab: fetch its URL, then restore its state
+    /// 
+    /// # Returns
+    /// 
+    /// `true` if the tab's document was loaded, `false` otherwise
+    pub async fn restore_tab(&mut self, state: &TabState) -> bool {
+        let loaded = match &state.url {
+            Some(url) => self.fetch_url(url).await,
+            None => false,
+        };
+        if loaded {
+            self.restore_tab_state(state);
+        }
+        loaded
+    }
+
+    /// Record a failure and print it as one console entry
+    fn report_error(&mut self, error: EngineError) {
+        eprintln!("{}", error.console_entry());
+        self.last_error = Some(error);
+    }
+
+    /// Get the most recent failure
+    pub fn last_error(&self) -> Option<&EngineError> {
+        self.last_error.as_ref()
+    }
+
+    /// Replace the current document with the error page of the most recent
+    /// failure
+    ///
+    /// # Returns
+    ///
+    /// `true` if there was a failure to show, `false` otherwise
+    pub fn show_error_page(&mut self) -> bool {
+        let Some(page) = self.last_error.as_ref().map(ErrorPage::for_error) else {
+            return false;
+        };
+        self.show_document(page.build(), None, None, dom::clock::now());
+        self.load_css(error_pages::ERROR_STYLESHEET);
+        self.error_page = Some(page);
+        true
+    }
+
+    /// Get the error page the tab shows, if it shows one
+    pub fn error_page(&self) -> Option<&ErrorPage> {
+        self.error_page.as_ref()
+    }
+
+    /// Capture what is needed to reload this tab after it crashed
+    ///
+    /// Called on the crashed engine, which is then replaced.
+    pub fn crash_recovery_for(&self, report: CrashReport, report_path: Option<PathBuf>) -> CrashRecovery {
+        match &self.crash_recovery {
+            // A tab that crashes while reloading reloads what it crashed on
+            Some(recovery) => CrashRecovery { report, report_path, ..recovery.clone() },
+            None => CrashRecovery {
+                report,
+                report_path,
+                state: self.capture_tab_state(),
+                source: self.document_source.clone(),
+                stylesheet: self.current_stylesheet.clone(),
+            },
+        }
+    }
+
+    /// Replace the current document with the "tab crashed" page
+    pub fn show_crash_page(&mut self, recovery: CrashRecovery) -> bool {
+        let page = ErrorPage::for_crash(&recovery.report, recovery.report_path.as_deref());
+        self.show_document(page.build(), None, None, dom::clock::now());
+        self.load_css(error_pages::ERROR_STYLESHEET);
+        self.error_page = Some(page);
+        self.history = recovery.state.history.clone();
+        self.history_index = recovery.state.history_index;
+        self.current_url = recovery.state.url.clone();
+        self.crash_recovery = Some(recovery);
+        true
+    }
+
+    /// Get how to reload the tab, if it shows a crash page
+    pub fn crash_recovery(&self) -> Option<&CrashRecovery> {
+        self.crash_recovery.as_ref()
+    }
+
+    /// Set the script sandbox policy for origins without their own policy
+    pub fn set_default_sandbox_policy(&mut self, policy: SandboxPolicy) {
+        self.default_sandbox_policy = policy;
+    }
+
+    /// Set the script sandbox policy for the origin of `url`
+    pub fn set_sandbox_policy_for_origin(&mut self, url: &str, policy: SandboxPolicy) {
+        self.sandbox_policies.insert(Origin::from_url(url), policy);
+    }
+
+    /// Get the script sandbox policy for a document loaded from `url`
+    ///
+    /// Documents with an opaque origin (e.g. `data:` or `file:` URLs) never get more
+    /// than the untrusted policy.
+    pub fn sandbox_policy_for_url(&self, url: &str) -> SandboxPolicy {
+        let origin = Origin::from_url(url);
+        if origin.is_opaque() {
+            return self.default_sandbox_policy.intersect(&SandboxPolicy::untrusted());
+        }
+        self.sandbox_policies
+            .get(&origin)
+            .cloned()
+            .unwrap_or_else(|| self.default_sandbox_policy.clone())
+    }
+
+    /// Get the script sandbox policy for the current document
+    /// 
+    /// In the child engine of a sandboxed frame it is narrowed further by
+    /// the frame's sandbox flags.
+    pub fn current_sandbox_policy(&self) -> SandboxPolicy {
+        let policy = match &self.current_url {
+            Some(url) => self.sandbox_policy_for_url(url),
+            None => self.default_sandbox_policy.clone(),
+        };
+        match &self.frame_sandbox {
+            Some(flags) => policy.intersect(&SandboxPolicy::for_sandbox_flags(flags)),
+            None => policy,
+        }
+    }
+    
+    /// Load the document of an `<iframe>` of the current page into a child
+    /// engine
+    /// 
+    /// The child shares the page's policies and prompt handler and carries
+    /// the frame's `sandbox` flags, if any. Returns `None` if the frame
+    /// loads nothing or its document could not be loaded.
+    pub fn load_frame(&self, frame: &Rc<Node>) -> Option<BrowserEngine> {
+        let document = self.current_document.as_ref()?;
+        let source = FrameSource::of(document, frame)?;
+        let mut child = BrowserEngine::new();
+        child.default_sandbox_policy = self.default_sandbox_policy.clone();
+        child.sandbox_policies = self.sandbox_policies.clone();
+        child.frame_sandbox = SandboxFlags::of_frame(frame);
+        child.set_user_prompt_handler(Arc::clone(&self.user_prompt_handler));
+        child.set_content_blocker(self.content_blocker().clone());
+        child.set_media_features(self.media_features.clone());
+        let loaded = match source {
+            FrameSource::Srcdoc(markup) => {
+                let loaded = child.load_html_from(&markup, Some(SRCDOC_URL), dom::clock::now());
+                // `srcdoc` documents have the origin of the embedding page
+                if let Some(framed) = child.current_document.as_ref().filter(|_| loaded) {
+                    framed.set_origin(document.origin());
+                }
+                loaded
+            }
+            FrameSource::Url(url) => block_on(child.fetch_url(url.as_str())),
+        };
+        loaded.then_some(child)
+    }
+    
+    /// Get the sandbox flags of the frame this engine loads documents for
+    pub fn frame_sandbox(&self) -> Option<SandboxFlags> {
+        self.frame_sandbox
+    }
+
+    /// Set the clipboard shared by scripts and keyboard shortcuts
+    pub fn set_clipboard(&mut self, clipboard: Clipboard) {
+        self.clipboard = clipboard;
+    }
+
+    /// Get the clipboard shared by scripts and keyboard shortcuts
+    pub fn clipboard(&self) -> &Clipboard {
+        &self.clipboard
+    }
+
+    /// Keep saved logins in a store shared with other tabs
+    pub fn set_password_store(&mut self, store: PasswordStore) {
+        self.passwords = store;
+    }
+
+    pub fn password_store(&self) -> &PasswordStore {
+        &self.passwords
+    }
+
+    /// Ask whether to save passwords with `prompt` instead of on the
+    /// terminal
+    pub fn set_password_save_prompt(&mut self, prompt: PasswordSavePrompt) {
+        self.password_save_prompt = prompt;
+    }
+
+    /// Fill the saved login of the current origin into its login forms
+    ///
+    /// The first time an origin's forms would be filled, the user is asked
+    /// for the autofill permission. Returns the number of fields filled.
+    pub fn autofill_logins(&mut self) -> usize {
+        let Some(document) = &self.current_document else {
+            return 0;
+        };
+        let origin = document.origin();
+        if origin.is_opaque() {
+            return 0;
+        }
+        let origin = origin.serialize();
+        let Some(credentials) = self.passwords.credentials_for(&origin) else {
+            return 0;
+        };
+        let forms = login_forms(&document.root);
+        if forms.is_empty() || self.permissions.request(&origin, PermissionName::Autofill) != PermissionState::Granted {
+            return 0;
+        }
+        forms.iter().map(|form| form.fill(&credentials)).sum()
+    }
+
+    /// Offer to save the credentials a login form of the current document
+    /// is submitted with
+    ///
+    /// Returns `true` if they were saved.
+    fn offer_to_save_login(&mut self, submission: &FormSubmission) -> bool {
+        let Some(document) = &self.current_document else {
+            return false;
+        };
+        let origin = document.origin();
+        if origin.is_opaque() {
+            return false;
+        }
+        let origin = origin.serialize();
+        let credentials = login_forms(&document.root)
+            .into_iter()
+            .find(|form| form.is_submitted_in(&submission.entries))
+            .and_then(|form| form.credentials());
+        let Some(credentials) = credentials.filter(|credentials| !self.passwords.is_saved(&origin, credentials)) else {
+            return false;
+        };
+        if !(self.password_save_prompt)(&origin, &credentials.username) {
+            return false;
+        }
+        match self.passwords.save(&origin, &credentials) {
+            Ok(saved) => saved,
+            Err(e) => {
+                println!("❌ Failed to save the password: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Replace the clipboard permissions, e.g. to skip the prompt
+    pub fn set_clipboard_permissions(&mut self, permissions: ClipboardPermissions) {
+        self.clipboard_permissions = permissions;
+    }
+
+    /// Replace how script prompts are shown, e.g. to answer them without
+    /// asking
+    pub fn set_user_prompt_handler(&mut self, handler: UserPromptHandler) {
+        self.user_prompt_handler = handler;
+    }
+
+    /// Get the coverage of the current stylesheet by the layouts done since
+    /// it was loaded
+    pub fn stylesheet_coverage(&self) -> Option<coverage::StylesheetCoverage> {
+        let (stylesheet, source) = (self.current_stylesheet.as_ref()?, self.stylesheet_source.as_ref()?);
+        let url = self.current_url.clone().unwrap_or_else(|| "inline stylesheet".to_string());
+        Some(coverage::StylesheetCoverage::new(&url, source, stylesheet, &self.used_css_rules))
+    }
+    
+    /// Lay out the page and run its scripts with coverage on, and report
+    /// what of its stylesheet and scripts was used
+    pub fn collect_coverage(&mut self) -> coverage::CoverageReport {
+        let mut report = coverage::CoverageReport::default();
+        if self.current_stylesheet.is_some() && self.perform_layout() {
+            report.stylesheets.extend(self.stylesheet_coverage());
+        }
+        match self.create_script_engine() {
+            Ok(mut script) => {
+                script.coverage().start();
+                if let Err(e) = script.execute_inline_scripts() {
+                    println!("⚠️ Page scripts failed: {}", e);
+                }
+                let _ = script.execute_external_scripts();
+                let _ = script.process_microtasks();
+                report.scripts = script.coverage().report();
+            }
+            Err(e) => println!("❌ Failed to run the page's scripts: {}", e),
+        }
+        report
+    }
+    
+    /// Run the page's scripts and render `frames` frames, recording the
+    /// time each frame spends in scripts, style, layout, paint and
+    /// composite
+    pub fn profile_frames(&mut self, frames: usize, profiler: &mut profiler::Profiler) -> bool {
+        let (Some(document), Some(layout_engine)) = (self.current_document.clone(), self.layout_engine()) else {
+            self.report_error(EngineError::shell(EnginePhase::Layout, "missing document or stylesheet"));
+            return false;
+        };
+        let mut script = match self.create_script_engine() {
+            Ok(script) => script,
+            Err(e) => {
+                self.report_error(EngineError::shell(EnginePhase::Scripting, e.to_string()));
+                return false;
+            }
+        };
+        let layout = profiler::record_frames(&document, &layout_engine, &mut script, frames, true, profiler);
+        self.used_css_rules.extend(layout_engine.matched_rules());
+        if layout.is_some() {
+            self.current_layout = layout;
+        }
+        true
+    }
+    
+    /// Create a JavaScript engine for the current document, restricted by
+    /// its sandbox policy
+    ///
+    /// Documents with a tuple origin also get `navigator.clipboard`,
+    /// `navigator.permissions` and `Notification`, and secure ones
+    /// `navigator.geolocation` and `caches`. The engine's `performance` object reads the document's
+    /// timeline, its prompts are shown with the shell's prompt handler,
+    /// and its event loop is throttled while the tab is hidden. Its
+    /// `ResizeObserver`s see the tab's layouts, and its scripts are checked
+    /// against the tab's content blocker.
+    pub fn create_script_engine(&self) -> JsResult<JsEngine> {
+        let mut engine = JsEngine::with_sandbox_policy(self.current_sandbox_policy())?;
+        engine.set_user_prompt_handler(Arc::clone(&self.user_prompt_handler));
+        engine.set_geometry_observers(self.geometry_observers.clone());
+        engine.set_content_blocker(self.content_blocker().clone());
+        if let Some(document) = &self.current_document {
+            engine.set_document(Rc::clone(document));
+            let origin = document.origin();
+            if !origin.is_opaque() {
+                engine.enable_clipboard(
+                    self.clipboard.clone(),
+                    self.clipboard_permissions.clone(),
+                    &origin.serialize(),
+                )?;
+                engine.enable_permissions(
+                    self.permissions.clone(),
+                    &origin.serialize(),
+                    Arc::clone(&self.notification_handler),
+                )?;
+            }
+            if is_secure_origin(&origin) {
+                engine.enable_geolocation(Rc::clone(&self.location_provider), self.permissions.clone(), &origin.serialize())?;
+            }
+        }
+        if let Some(url) = &self.current_url {
+            engine.enable_cache_storage(&self.cache_storage, url)?;
+        }
+        if let Some(stylesheet) = &self.current_stylesheet {
+            engine.set_stylesheet(stylesheet.clone());
+        }
+        engine.set_performance_timeline(self.performance.clone())?;
+        engine.set_media_features(self.zoomed_media_features());
+        engine.set_console_sink(self.console.clone())?;
+        self.freezing_policy.apply(self.visibility_state, engine.event_loop());
+        if let Some(mode) = &self.deterministic {
+            engine.set_random_seed(mode.random_seed)?;
+        }
+        Ok(engine)
+    }
+
+    /// Resolve a form action or link against the base URL of the current
+    /// document
+    fn resolve_document_url(&self, href: &str) -> Option<String> {
+        let resolver = self.current_document.as_deref().map_or_else(UrlResolver::default, UrlResolver::for_document);
+        resolver.resolve(href).map(String::from)
+    }
+    
+    /// Safely parse HTML content with error handling
+    /// 
+    /// This method wraps the HTML parsing in error handling to provide
+    /// graceful degradation when encountering malformed HTML.
+    fn parse_html_safely(&self, html_content: &str) -> EngineResult<Document> {
+        // For now, we'll use a simple approach - in a real browser,
+        // this would include more sophisticated error recovery
+        if html_content.trim().is_empty() {
+            return Err(EngineError::shell(EnginePhase::HtmlParsing, "Empty HTML content"));
+        }
+        
+        let (document, _resources) = html_parser::parse_html_string(html_content)?;
+        Ok(document)
+    }
+    
+    /// Safely parse CSS content with error handling
+    /// 
+    /// This method wraps the CSS parsing in error handling to provide
+    /// graceful degradation when encountering malformed CSS.
+    fn parse_css_safely(&self, css_content: &str) -> EngineResult<Stylesheet> {
+        // For now, we'll use a simple approach - in a real browser,
+        // this would include more sophisticated error recovery
+        if css_content.trim().is_empty() {
+            return Err(EngineError::shell(EnginePhase::CssParsing, "Empty CSS content"));
+        }
+        
+        Ok(parse_css(css_content))
+    }
+    
+    /// Render the current document to text
+    /// 
+    /// This method renders the current document using the text renderer
+    /// and returns the formatted output.
+    /// 
+    /// # Returns
+    /// 
+    /// The rendered text output, or an error message if no document is loaded
+    pub fn render_to_text(&self) -> String {
+        match &self.current_document {
+            Some(document) => render_as_text(document),
+            None => "No document loaded".to_string(),
+        }
+    }
+    
+    /// Render the current layout with styling information
+    /// 
+    /// This method renders the current layout tree with computed styles
+    /// and dimensions, showing how the browser engine has styled and
+    /// positioned each element.
+    /// 
+    /// # Returns
+    /// 
+    /// The rendered layout output, or an error message if no layout is available
+    pub fn render_layout(&self) -> String {
+        match &self.current_layout {
+            Some(layout) => render_layout_box(layout),
+            None => "No layout available. Load HTML, CSS, and perform layout first.".to_string(),
+        }
+    }
+    
+    /// Extract text content from the current document
+    /// 
+    /// This method extracts only the text content from the current document,
+    /// ignoring all HTML markup.
+    /// 
+    /// # Returns
+    /// 
+    /// The text content, or an error message if no document is loaded
+    pub fn get_text_content(&self) -> String {
+        match &self.current_document {
+            Some(document) => extract_text_content(document),
+            None => "No document loaded".to_string(),
+        }
+    }
+    
+    /// Get a reference to the current document
+    /// 
+    /// This method provides access to the current DOM tree for advanced
+    /// operations that require direct DOM manipulation.
+    /// 
+    /// # Returns
+    /// 
+    /// A reference to the current document, or `None` if no document is loaded
+    pub fn get_document(&self) -> Option<&Rc<Document>> {
+        self.current_document.as_ref()
+    }
+    
+    /// Check if a document is currently loaded
+    /// 
+    /// # Returns
+    /// 
+    /// `true` if a document is loaded, `false` otherwise
+    pub fn has_document(&self) -> bool {
+        self.current_document.is_some()
+    }
+    
+    /// Check if a stylesheet is currently loaded
+    /// 
+    /// # Returns
+    /// 
+    /// `true` if a stylesheet is loaded, `false` otherwise
+    pub fn has_stylesheet(&self) -> bool {
+        self.current_stylesheet.is_some()
+    }
+    
+    /// Check if layout has been calculated
+    /// 
+    /// # Returns
+    /// 
+    /// `true` if layout is available, `false` otherwise
+    pub fn has_layout(&self) -> bool {
+        self.current_layout.is_some()
+    }
+    
+    /// Get a reference to the current layout
+    /// 
+    /// # Returns
+    /// 
+    /// A reference to the current layout, or `None` if no layout is available
+    pub fn get_layout(&self) -> Option<&LayoutBox> {
+        self.current_layout.as_ref()
+    }
+
+    /// Get the resource and paint timings of the current document
+    pub fn performance(&self) -> &PerformanceTimeline {
+        &self.performance
+    }
+
+    /// Record that a frame of the current layout was presented
+    /// 
+    /// The first frame is the document's first paint, and the first one
+    /// with text or images its first contentful paint.
+    pub fn record_paint(&self) {
+        if let Some(layout) = &self.current_layout {
+            self.performance.record_paint(layout);
+        }
+    }
+
+    /// Render the current layout using GPU
+    /// 
+    /// The window enters fullscreen mode while the document has a
+    /// fullscreen element, and leaving fullscreen mode takes the document
+    /// out of fullscreen. Copy, cut and paste in the window use the
+    /// engine's clipboard, and the machine's gamepads are polled each frame.
+    pub async fn render_with_gpu(&self) -> EngineResult<()> {
+        if let Some(layout) = &self.current_layout {
+            let mut input_handler = InputHandler::new();
+            input_handler.set_clipboard(self.clipboard.clone());
+            input_handler.set_gamepad_backend(system_gamepads::system_gamepads());
+            render_layout_tree_with_input(layout, input_handler).await?;
+            self.record_paint();
+            Ok(())
+        } else {
+            Err(EngineError::shell(
+                EnginePhase::Rendering,
+                "No layout available. Load HTML, CSS, and perform layout first.",
+            ))
+        }
     }
     
     /// Start the browser engine
@@ -394,20 +2179,1075 @@ impl Default for BrowserEngine {
     }
 }
 
+impl Drop for BrowserEngine {
+    fn drop(&mut self) {
+        mutations::unobserve(self.mutation_observer);
+    }
+}
+
+/// Find the node with the given node id in a tree
+fn find_node(root: &Rc<Node>, id: u64) -> Option<Rc<Node>> {
+    if root.id == id {
+        return Some(Rc::clone(root));
+    }
+    root.children.borrow().iter().find_map(|child| find_node(child, id))
+}
+
 /// A simple command-line interface for the browser engine
 /// 
 /// This struct provides a basic command-line interface for interacting
 /// with the browser engine. It's useful for testing and demonstration
 /// purposes.
 pub struct BrowserCLI {
-    engine: BrowserEngine,
+    /// One engine per open tab
+    tabs: Vec<BrowserEngine>,
+    /// Index of the tab commands apply to
+    active_tab: usize,
+    /// Where the session is saved, if session persistence is enabled
+    session_store: Option<SessionStore>,
+    /// Whether the previous session is restored at startup
+    restore_preference: RestorePreference,
+    /// The user's bookmarks, if a profile is in use
+    bookmarks: Option<BookmarkStore>,
+    /// The user's stylesheets, if a profile is in use
+    user_styles: Option<Rc<RefCell<UserStyles>>>,
+    /// Installed extensions, shared by all tabs
+    extensions: Extensions,
+    /// Appearance preferences of the operating system
+    system_preferences: SystemPreferences,
+    /// The user's overrides of the system preferences
+    appearance: AppearanceSettings,
+    /// Antialiasing, hinting and gamma text is rasterized with
+    text_rendering: TextRenderSettings,
+    /// HSTS policies shared by every tab
+    hsts: HstsStore,
+    /// Visited URLs shared by every tab
+    visited: VisitedStore,
+    /// Zoom levels of sites and the minimum font size, shared by every tab
+    zoom: ZoomStore,
+    /// Permissions of origins shared by every tab
+    permissions: Permissions,
+    /// Page caches and pinned pages shared by every tab
+    cache_storage: CacheStorage,
+    /// Which resource hints and hovered links every tab prefetches
+    prefetch_policy: PrefetchPolicy,
+    /// Prefetched responses shared by every tab
+    prefetch_cache: PrefetchCache,
+    /// Whether the network connection is metered
+    metered_connection: bool,
+    /// Where crash reports are written, if a profile is in use
+    crash_reports: Option<PathBuf>,
+    /// The clock and seed every tab shares in deterministic mode
+    deterministic: Option<DeterministicMode>,
+    /// The spellchecker every tab shares, so added words apply everywhere
+    spellchecker: SharedSpellchecker,
+    /// The downloads every tab shares
+    downloads: DownloadManager,
+    /// Saved logins shared by every tab
+    passwords: PasswordStore,
+    /// Filter lists shared by every tab
+    content_blocker: ContentBlocker,
 }
 
 impl BrowserCLI {
     /// Create a new browser CLI
     pub fn new() -> Self {
-        BrowserCLI {
-            engine: BrowserEngine::new(),
+        let deterministic = DeterministicMode::from_env();
+        let first_tab = BrowserEngine::new();
+        let spellchecker = first_tab.spellchecker();
+        let downloads = first_tab.download_manager().clone();
+        let passwords = first_tab.password_store().clone();
+        let content_blocker = first_tab.content_blocker().clone();
+        let mut cli = BrowserCLI {
+            tabs: vec![first_tab],
+            active_tab: 0,
+            session_store: None,
+            restore_preference: RestorePreference::default(),
+            bookmarks: None,
+            user_styles: None,
+            extensions: Extensions::new(),
+            system_preferences: SystemPreferences::default(),
+            appearance: AppearanceSettings::default(),
+            text_rendering: TextRenderSettings::from_env(),
+            hsts: HstsStore::new(),
+            visited: VisitedStore::new(),
+            zoom: ZoomStore::new(),
+            permissions: Permissions::new(),
+            cache_storage: CacheStorage::new(),
+            prefetch_policy: PrefetchPolicy::default(),
+            prefetch_cache: PrefetchCache::new(),
+            metered_connection: false,
+            crash_reports: None,
+            deterministic: None,
+            spellchecker,
+            downloads,
+            passwords,
+            content_blocker,
+        };
+        if deterministic.is_some() {
+            cli.set_deterministic_mode(deterministic);
+        }
+        cli
+    }
+    
+    /// Create a browser CLI that saves its session to `store`
+    /// 
+    /// The session is saved periodically and on exit, and the previous one
+    /// is restored at startup according to `preference`.
+    pub fn with_session(store: SessionStore, preference: RestorePreference) -> Self {
+        let mut cli = Self::new();
+        cli.session_store = Some(store);
+        cli.restore_preference = preference;
+        cli
+    }
+    
+    /// Use a bookmark store for the bookmark commands and shortcuts
+    pub fn set_bookmark_store(&mut self, store: BookmarkStore) {
+        self.bookmarks = Some(store);
+    }
+    
+    /// Share an HSTS store, usually the profile's, between every tab
+    pub fn set_hsts_store(&mut self, store: HstsStore) {
+        for tab in &mut self.tabs {
+            tab.set_hsts_store(store.clone());
+        }
+        self.hsts = store;
+    }
+    
+    /// Get the HSTS policies of the tabs
+    pub fn hsts_store(&self) -> &HstsStore {
+        &self.hsts
+    }
+    
+    /// Share a content blocker, usually with the profile's filter lists,
+    /// between every tab
+    pub fn set_content_blocker(&mut self, blocker: ContentBlocker) {
+        for tab in &mut self.tabs {
+            tab.set_content_blocker(blocker.clone());
+        }
+        self.content_blocker = blocker;
+    }
+    
+    /// Share a visited link store, usually the profile's, between every tab
+    pub fn set_visited_store(&mut self, store: VisitedStore) {
+        for tab in &mut self.tabs {
+            tab.set_visited_store(store.clone());
+        }
+        self.visited = store;
+    }
+    
+    /// Share a zoom store, usually the profile's, between every tab
+    pub fn set_zoom_store(&mut self, store: ZoomStore) {
+        for tab in &mut self.tabs {
+            tab.set_zoom_store(store.clone());
+        }
+        self.zoom = store;
+    }
+    
+    /// Share a permission store, usually the profile's, between every tab
+    pub fn set_permission_store(&mut self, store: Permissions) {
+        for tab in &mut self.tabs {
+            tab.set_permission_store(store.clone());
+        }
+        self.permissions = store;
+    }
+    
+    /// Share a password store, usually the profile's, between every tab
+    pub fn set_password_store(&mut self, store: PasswordStore) {
+        for tab in &mut self.tabs {
+            tab.set_password_store(store.clone());
+        }
+        self.passwords = store;
+    }
+    
+    /// Share cache storage, usually the profile's, between every tab
+    pub fn set_cache_storage(&mut self, storage: CacheStorage) {
+        for tab in &mut self.tabs {
+            tab.set_cache_storage(storage.clone());
+        }
+        self.cache_storage = storage;
+    }
+    
+    /// Choose which resource hints and hovered links every tab prefetches
+    pub fn set_prefetch_policy(&mut self, policy: PrefetchPolicy) {
+        for tab in &mut self.tabs {
+            tab.set_prefetch_policy(policy);
+        }
+        self.prefetch_policy = policy;
+    }
+    
+    /// Put every tab in deterministic mode, or take them out with `None`
+    ///
+    /// The tabs share the mode's clock, and text is rasterized with the
+    /// deterministic settings.
+    pub fn set_deterministic_mode(&mut self, mode: Option<DeterministicMode>) {
+        for tab in &mut self.tabs {
+            tab.set_deterministic_mode(mode.clone());
+        }
+        self.text_rendering = match mode {
+            Some(_) => TextRenderSettings::deterministic(),
+            None => TextRenderSettings::from_env(),
+        };
+        self.deterministic = mode;
+    }
+    
+    /// Tell every tab whether the network connection is metered
+    pub fn set_metered_connection(&mut self, metered: bool) {
+        for tab in &mut self.tabs {
+            tab.set_metered_connection(metered);
+        }
+        self.metered_connection = metered;
+    }
+    
+    /// Run the `prefetch` command: show or change the prefetch policy
+    fn run_prefetch_command(&mut self, args: &str) {
+        let mut policy = self.prefetch_policy;
+        let mut parts = args.split_whitespace();
+        let valid = match (parts.next(), parts.next().and_then(parse_flag)) {
+            (None, _) => true,
+            (Some(value), None) => match parse_flag(value) {
+                Some(enabled) => {
+                    policy.enabled = enabled;
+                    true
+                }
+                None => false,
+            },
+            (Some("hover"), Some(hover)) => {
+                policy.hover = hover;
+                true
+            }
+            (Some("cross-origin"), Some(cross_origin)) => {
+                policy.cross_origin = cross_origin;
+                true
+            }
+            (Some("metered"), Some(on_metered)) => {
+                policy.on_metered_connection = on_metered;
+                true
+            }
+            _ => false,
+        };
+        if !valid {
+            println!("Usage: prefetch [on|off | hover <on|off> | cross-origin <on|off> | metered <on|off>]");
+            return;
+        }
+        self.set_prefetch_policy(policy);
+        let flag = |on: bool| if on { "on" } else { "off" };
+        println!(
+            "Prefetching: {}, hover: {}, cross-origin: {}, on metered connections: {}{}",
+            flag(policy.enabled),
+            flag(policy.hover),
+            flag(policy.cross_origin),
+            flag(policy.on_metered_connection),
+            if self.metered_connection { " (the connection is metered)" } else { "" }
+        );
+        println!("{} prefetched responses", self.prefetch_cache.len());
+    }
+    
+    /// Run the `pin` command: keep the current page for offline use
+    fn run_pin_command(&mut self) {
+        let tab = &mut self.tabs[self.active_tab];
+        match block_on(tab.pin_for_offline()) {
+            Some(report) if report.failed.is_empty() => println!("📌 Pinned the page and {} subresources", report.stored.saturating_sub(1)),
+            Some(report) => println!(
+                "📌 Pinned the page and {} subresources; {} could not be fetched",
+                report.stored.saturating_sub(1),
+                report.failed.len()
+            ),
+            None => println!("Only pages fetched from a URL can be pinned"),
+        }
+    }
+    
+    /// Run the `caches` command: list caches or delete one
+    fn run_caches_command(&mut self, args: &str) {
+        match args.split_once(' ').map(|(action, name)| (action, name.trim())).unwrap_or((args, "")) {
+            ("", _) => {
+                let names = self.cache_storage.keys();
+                if names.is_empty() {
+                    println!("No caches");
+                }
+                for name in names {
+                    let entries = self.cache_storage.open_cache(&name).map(|cache| cache.len()).unwrap_or(0);
+                    println!("  {} ({} entries)", name, entries);
+                }
+            }
+            ("delete", name) if !name.is_empty() => match self.cache_storage.delete(name) {
+                Ok(true) => println!("Deleted cache {}", name),
+                Ok(false) => println!("No cache named {}", name),
+                Err(e) => eprintln!("❌ Failed to delete cache {}: {}", name, e),
+            },
+            _ => println!("Usage: caches [delete <name>]"),
+        }
+    }
+    
+    /// Run the `layers` command: list the composited layers of the page
+    fn show_layers(&self) {
+        let Some(layout) = self.tabs[self.active_tab].get_layout() else {
+            println!("No page loaded");
+            return;
+        };
+        let layers = LayerTree::from_layout(layout);
+        if layers.is_empty() {
+            println!("No composited layers");
+            return;
+        }
+        println!("{} composited layers, {} KiB of textures:", layers.len(), layers.texture_bytes() / 1024);
+        for line in layers.report().lines() {
+            println!("  {}", line);
+        }
+        if layers.is_over_promoted(&layout.margin) {
+            println!("⚠️  Layers cover more than {} times the page; check will-change rules", layout::layers::OVER_PROMOTION_FACTOR);
+        }
+    }
+    
+    /// Run the `permissions` command: list the decisions about origins or
+    /// forget those about one
+    fn run_permissions_command(&mut self, args: &str) {
+        match args.split_once(' ').map(|(action, origin)| (action, origin.trim())).unwrap_or((args, "")) {
+            ("", _) => {
+                let decisions = self.permissions.decisions();
+                if decisions.is_empty() {
+                    println!("No permissions granted or denied");
+                }
+                for (origin, name, state) in decisions {
+                    println!("  {} {}: {}", origin, name, state);
+                }
+            }
+            ("reset", origin) if !origin.is_empty() => match self.permissions.reset(origin) {
+                Ok(()) => println!("Reset the permissions of {}", origin),
+                Err(e) => eprintln!("❌ Failed to save permissions: {}", e),
+            },
+            _ => println!("Usage: permissions [reset <origin>]"),
+        }
+    }
+    
+    /// Run the `visited` command: count or forget visited links
+    fn run_visited_command(&mut self, args: &str) {
+        match args {
+            "" => println!("{} visited URLs", self.visited.len()),
+            "clear" => match self.visited.clear() {
+                Ok(()) => {
+                    let store = self.visited.clone();
+                    self.set_visited_store(store);
+                    println!("Cleared visited links");
+                }
+                Err(e) => eprintln!("❌ Failed to save visited links: {}", e),
+            },
+            _ => println!("Usage: visited [clear]"),
+        }
+    }
+    
+    /// Run the `extensions` command: list the installed extensions, or
+    /// enable or disable one
+    fn run_extensions_command(&mut self, args: &str) {
+        let (action, id) = args.split_once(' ').map_or((args, ""), |(action, id)| (action, id.trim()));
+        match (action, id) {
+            ("", _) => {
+                let extensions = self.extensions.list();
+                if extensions.is_empty() {
+                    println!("No extensions installed");
+                }
+                for (extension, enabled) in extensions {
+                    let state = if enabled { "on " } else { "off" };
+                    println!("  [{}] {} {} ({}) - {}", state, extension.name, extension.version, extension.id, extension.description);
+                }
+            }
+            ("enable" | "disable", id) if !id.is_empty() => match self.extensions.set_enabled(id, action == "enable") {
+                Ok(true) => println!("{}d {}; reload pages to apply", if action == "enable" { "Enable" } else { "Disable" }, id),
+                Ok(false) => println!("No extension {}", id),
+                Err(e) => eprintln!("❌ Failed to save extension settings: {}", e),
+            },
+            _ => println!("Usage: extensions [enable <id> | disable <id>]"),
+        }
+    }
+    
+    /// Run the `zoom` command: show, step or set the zoom level of the
+    /// current page
+    fn run_zoom_command(&mut self, args: &str) {
+        let engine = &mut self.tabs[self.active_tab];
+        match args {
+            "" => {}
+            "in" => {
+                engine.zoom_in();
+            }
+            "out" => {
+                engine.zoom_out();
+            }
+            "reset" => {
+                engine.reset_zoom();
+            }
+            "sites" => {
+                let levels = self.zoom.levels();
+                if levels.is_empty() {
+                    println!("No zoomed sites");
+                }
+                for (origin, level) in levels {
+                    println!("  {:>5}  {}", zoom::format_zoom(level), origin);
+                }
+                return;
+            }
+            _ => match args.trim_end_matches('%').parse::<f32>() {
+                Ok(percent) if percent > 0.0 => {
+                    engine.set_zoom_level(percent / 100.0);
+                }
+                _ => {
+                    println!("Usage: zoom [in | out | reset | sites | <percent>]");
+                    return;
+                }
+            },
+        }
+        println!("🔍 {}", zoom::format_zoom(engine.zoom_level()));
+    }
+    
+    /// Run the `min-font-size` command: show or set the smallest font size
+    /// pages are shown with
+    fn run_minimum_font_size_command(&mut self, args: &str) {
+        let size = match args {
+            "" => {
+                match self.zoom.minimum_font_size() {
+                    size if size > 0.0 => println!("Minimum font size: {}px", size),
+                    _ => println!("No minimum font size"),
+                }
+                return;
+            }
+            "off" => 0.0,
+            _ => match args.trim_end_matches("px").parse::<f32>() {
+                Ok(size) if size >= 0.0 => size,
+                _ => {
+                    println!("Usage: min-font-size [<px> | off]");
+                    return;
+                }
+            },
+        };
+        if let Err(e) = self.zoom.set_minimum_font_size(size) {
+            eprintln!("❌ Failed to save the minimum font size: {}", e);
+        }
+        for tab in &mut self.tabs {
+            tab.restyle();
+        }
+    }
+    
+    /// Run the `hsts` command: list, forget or clear HSTS policies
+    fn run_hsts_command(&mut self, args: &str) {
+        let (action, host) = args.split_once(' ').map(|(action, host)| (action, host.trim())).unwrap_or((args, ""));
+        match action {
+            "" | "list" => {
+                let entries = self.hsts.entries();
+                if entries.is_empty() {
+                    println!("No HSTS hosts");
+                }
+                for entry in entries {
+                    println!(
+                        "  {}{} (expires at {})",
+                        entry.host,
+                        if entry.include_subdomains { " +subdomains" } else { "" },
+                        entry.expires_at
+                    );
+                }
+            }
+            "forget" if !host.is_empty() => match self.hsts.remove(host) {
+                Ok(true) => println!("Forgot HSTS policy of {}", host),
+                Ok(false) => println!("{} has no HSTS policy", host),
+                Err(e) => eprintln!("❌ Failed to save HSTS policies: {}", e),
+            },
+            "clear" => match self.hsts.clear() {
+                Ok(()) => println!("Cleared all HSTS policies"),
+                Err(e) => eprintln!("❌ Failed to save HSTS policies: {}", e),
+            },
+            _ => println!("Usage: hsts [list | forget <host> | clear]"),
+        }
+    }
+    
+    /// Run the `filters` command: show the content blocker's rules or add
+    /// a filter list for the session
+    fn run_filters_command(&mut self, args: &str) {
+        let (action, path) = args.split_once(' ').map(|(action, path)| (action, path.trim())).unwrap_or((args, ""));
+        match action {
+            "" | "list" => println!("{} content blocking rules loaded", self.content_blocker.rule_count()),
+            "add" if !path.is_empty() => match std::fs::read_to_string(path) {
+                Ok(list) => {
+                    let stats = self.content_blocker.add_filter_list(&list);
+                    println!(
+                        "Added {} network and {} cosmetic rules from {} ({} unsupported rules skipped)",
+                        stats.network, stats.cosmetic, path, stats.skipped
+                    );
+                    for tab in &mut self.tabs {
+                        tab.apply_cosmetic_filters();
+                    }
+                }
+                Err(e) => eprintln!("❌ Failed to read filter list {}: {}", path, e),
+            },
+            _ => println!("Usage: filters [list | add <file>]"),
+        }
+    }
+    
+    /// Run the `profile` command: record frames of the active tab, show
+    /// them as a flame chart and optionally export them as a trace
+    fn run_profile_command(&mut self, args: &str) {
+        let mut frames = 10;
+        let mut export = None;
+        let mut words = args.split_whitespace();
+        while let Some(word) = words.next() {
+            match word {
+                "--export" => export = words.next().map(PathBuf::from),
+                _ => match word.parse() {
+                    Ok(count) => frames = count,
+                    Err(_) => {
+                        println!("Usage: profile [frames] [--export <file>]");
+                        return;
+                    }
+                },
+            }
+        }
+
+        let mut profiler = profiler::Profiler::default();
+        if self.run_in_tab(EnginePhase::Rendering, |tab| tab.profile_frames(frames, &mut profiler)) != Some(true) {
+            return;
+        }
+        print!("{}{}", profiler.flame_chart(100), profiler.summary());
+        if let Some(path) = export {
+            let trace = serde_json::to_string(&profiler.to_trace_json()).unwrap_or_default();
+            match std::fs::write(&path, trace) {
+                Ok(()) => println!("✅ Wrote trace to {}", path.display()),
+                Err(e) => println!("❌ Failed to write {}: {}", path.display(), e),
+            }
+        }
+    }
+    
+    /// Style the pages of every tab with the user's stylesheets
+    pub fn set_user_styles(&mut self, user_styles: UserStyles) {
+        let user_styles = Rc::new(RefCell::new(user_styles));
+        for tab in &mut self.tabs {
+            tab.set_user_styles(Rc::clone(&user_styles));
+        }
+        self.user_styles = Some(user_styles);
+    }
+    
+    /// Share the installed extensions, usually the profile's, between
+    /// every tab
+    pub fn set_extensions(&mut self, extensions: Extensions) {
+        for tab in &mut self.tabs {
+            tab.set_extensions(extensions.clone());
+        }
+        self.extensions = extensions;
+    }
+    
+    /// Reload user stylesheets that changed on disk and restyle the tabs
+    /// 
+    /// Returns whether any stylesheet changed.
+    pub fn reload_user_styles(&mut self) -> bool {
+        let Some(user_styles) = &self.user_styles else {
+            return false;
+        };
+        if !user_styles.borrow_mut().reload_if_changed() {
+            return false;
+        }
+        for tab in &mut self.tabs {
+            tab.restyle();
+        }
+        true
+    }
+    
+    /// Follow new appearance preferences of the operating system
+    pub fn set_system_preferences(&mut self, preferences: SystemPreferences) {
+        self.system_preferences = preferences;
+        self.apply_appearance();
+    }
+    
+    /// Override appearance preferences, restyling every tab
+    pub fn set_appearance(&mut self, appearance: AppearanceSettings) {
+        self.appearance = appearance;
+        self.apply_appearance();
+    }
+    
+    /// Apply the effective appearance preferences to every tab
+    /// 
+    /// Returns the number of tabs that were restyled.
+    fn apply_appearance(&mut self) -> usize {
+        let mut restyled = 0;
+        for tab in &mut self.tabs {
+            let features = self.appearance.media_features(&self.system_preferences, tab.media_features());
+            if tab.set_media_features(features) {
+                restyled += 1;
+            }
+        }
+        restyled
+    }
+    
+    /// Get the settings text is rasterized with
+    pub fn text_rendering(&self) -> TextRenderSettings {
+        self.text_rendering
+    }
+    
+    /// Run the `text-rendering` command
+    fn run_text_rendering_command(&mut self, args: &str) {
+        let mut parts = args.split_whitespace();
+        match (parts.next(), parts.next()) {
+            (None, _) => {}
+            (Some("default"), None) => self.text_rendering = TextRenderSettings::default(),
+            (Some("deterministic"), None) => self.text_rendering = TextRenderSettings::deterministic(),
+            (Some(setting), Some(value)) => {
+                if let Err(e) = self.text_rendering.set(setting, value) {
+                    println!("❌ {}", e);
+                    return;
+                }
+            }
+            (Some(_), None) => {
+                println!("Usage: text-rendering [<antialiasing|hinting|gamma> <value> | default | deterministic]");
+                return;
+            }
+        }
+        let text = &self.text_rendering;
+        println!("Text rendering: {} antialiasing, {} hinting, gamma {}", text.antialiasing.as_str(), text.hinting.as_str(), text.gamma);
+    }
+    
+    /// Run the `color-scheme`, `reduced-motion` and `forced-colors` commands
+    fn run_appearance_command(&mut self, command: &str, args: &str) {
+        let mut appearance = self.appearance;
+        let value = args.trim().to_ascii_lowercase();
+        let valid = match (command, value.as_str()) {
+            (_, "system") => {
+                match command {
+                    "color-scheme" => appearance.color_scheme = None,
+                    "forced-colors" => appearance.forced_colors = None,
+                    _ => appearance.reduced_motion = None,
+                }
+                true
+            }
+            ("color-scheme", value) => match ColorScheme::parse(value) {
+                Some(scheme) => {
+                    appearance.color_scheme = Some(scheme);
+                    true
+                }
+                None => false,
+            },
+            ("forced-colors", value) => match parse_flag(value) {
+                Some(forced) => {
+                    appearance.forced_colors = Some(forced);
+                    true
+                }
+                None => false,
+            },
+            (_, value) => match parse_flag(value) {
+                Some(reduced) => {
+                    appearance.reduced_motion = Some(reduced);
+                    true
+                }
+                None => false,
+            },
+        };
+        if !valid {
+            match command {
+                "color-scheme" => println!("Usage: color-scheme <light|dark|system>"),
+                "forced-colors" => println!("Usage: forced-colors <on|off|system>"),
+                _ => println!("Usage: reduced-motion <on|off|system>"),
+            }
+            return;
+        }
+        self.set_appearance(appearance);
+        let features = self.appearance.media_features(&self.system_preferences, &MediaFeatures::default());
+        println!(
+            "Color scheme: {}, reduced motion: {}, forced colors: {}",
+            features.color_scheme.as_str(),
+            if features.reduced_motion { "on" } else { "off" },
+            if features.forced_colors { "on" } else { "off" }
+        );
+    }
+    
+    /// Write crash reports of tabs to `dir`
+    pub fn set_crash_report_dir(&mut self, dir: PathBuf) {
+        self.crash_reports = Some(dir);
+    }
+
+    /// Run part of the active tab's pipeline, recovering if it panics
+    ///
+    /// A tab that panics is replaced by one showing the crash page, and
+    /// `None` is returned.
+    fn run_in_tab<T>(&mut self, phase: EnginePhase, f: impl FnOnce(&mut BrowserEngine) -> T) -> Option<T> {
+        let url = self.tabs[self.active_tab].current_url.clone();
+        let tab = &mut self.tabs[self.active_tab];
+        match catch_crash(phase, url.as_deref(), || f(tab)) {
+            Ok(result) => Some(result),
+            Err(report) => {
+                self.handle_tab_crash(report);
+                None
+            }
+        }
+    }
+
+    /// Replace the crashed active tab with one showing the crash page
+    fn handle_tab_crash(&mut self, report: CrashReport) {
+        println!("💥 Tab {} crashed during {}: {}", self.active_tab, report.phase, report.message);
+        let report_path = self.crash_reports.as_ref().and_then(|dir| match report.write_to(dir) {
+            Ok(path) => {
+                println!("Crash report saved to {}", path.display());
+                Some(path)
+            }
+            Err(e) => {
+                eprintln!("❌ Failed to save the crash report: {}", e);
+                None
+            }
+        });
+        let recovery = self.tabs[self.active_tab].crash_recovery_for(report, report_path);
+        let mut engine = self.open_engine();
+        engine.show_crash_page(recovery);
+        self.tabs[self.active_tab] = engine;
+        println!("Type 'reload' to load the page again");
+    }
+
+    /// Reload the active tab's document
+    ///
+    /// A crashed tab is loaded again with the document, stylesheet and state
+    /// it had before the crash, and a tab showing an error page loads the
+    /// URL that failed.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the document was loaded, `false` otherwise
+    pub fn reload_tab(&mut self) -> bool {
+        let tab = &self.tabs[self.active_tab];
+        let (state, source, stylesheet) = match tab.crash_recovery() {
+            Some(recovery) => (recovery.state.clone(), recovery.source.clone(), recovery.stylesheet.clone()),
+            None => {
+                // An error page is not broken, so the tab itself navigates
+                if let Some(url) = tab.error_page().and_then(|page| page.url.clone()) {
+                    return self.run_in_tab(EnginePhase::Navigation, |tab| block_on(tab.fetch_url(&url))).unwrap_or(false);
+                }
+                (tab.capture_tab_state(), tab.document_source.clone(), tab.current_stylesheet.clone())
+            }
+        };
+        if tab.crash_recovery().is_none() && !self.tabs[self.active_tab].prompt_to_unload() {
+            return false;
+        }
+        let mut engine = self.open_engine();
+        let reloaded = catch_crash(EnginePhase::Navigation, state.url.as_deref(), || {
+            let loaded = match (&state.url, &source) {
+                (Some(url), _) => block_on(engine.fetch_url(url)),
+                (None, Some(source)) => engine.load_html(source),
+                (None, None) => false,
+            };
+            if loaded {
+                engine.current_stylesheet = stylesheet;
+                engine.restore_tab_state(&state);
+            }
+            loaded
+        });
+        match reloaded {
+            Ok(true) => {
+                let mut old = std::mem::replace(&mut self.tabs[self.active_tab], engine);
+                old.unload_document();
+                true
+            }
+            // The tab keeps its page, or crash page, so the reload can be
+            // retried
+            Ok(false) => false,
+            Err(report) => {
+                self.handle_tab_crash(report);
+                false
+            }
+        }
+    }
+
+    /// Create the engine for a new tab
+    fn open_engine(&self) -> BrowserEngine {
+        let mut engine = BrowserEngine::new();
+        if let Some(user_styles) = &self.user_styles {
+            engine.set_user_styles(Rc::clone(user_styles));
+        }
+        let features = self.appearance.media_features(&self.system_preferences, engine.media_features());
+        engine.set_media_features(features);
+        engine.set_hsts_store(self.hsts.clone());
+        engine.set_content_blocker(self.content_blocker.clone());
+        engine.set_visited_store(self.visited.clone());
+        engine.set_zoom_store(self.zoom.clone());
+        engine.set_extensions(self.extensions.clone());
+        engine.set_permission_store(self.permissions.clone());
+        engine.set_cache_storage(self.cache_storage.clone());
+        engine.set_prefetch_policy(self.prefetch_policy);
+        engine.set_prefetch_cache(self.prefetch_cache.clone());
+        engine.set_metered_connection(self.metered_connection);
+        engine.set_spellchecker(Rc::clone(&self.spellchecker));
+        engine.set_download_manager(self.downloads.clone());
+        engine.set_password_store(self.passwords.clone());
+        if self.deterministic.is_some() {
+            engine.set_deterministic_mode(self.deterministic.clone());
+        }
+        engine.start();
+        engine
+    }
+    
+    /// Bookmark the page in the active tab
+    /// 
+    /// Returns the bookmark's id, or `None` if there is no page to bookmark.
+    pub fn bookmark_current_page(&mut self, tags: &[String]) -> Option<u64> {
+        let tab = &self.tabs[self.active_tab];
+        let Some(url) = tab.current_url.clone() else {
+            println!("Only pages loaded from a URL can be bookmarked");
+            return None;
+        };
+        let title = tab
+            .get_document()
+            .and_then(|document| document_title(document))
+            .unwrap_or_else(|| url.clone());
+        let Some(store) = &mut self.bookmarks else {
+            println!("Bookmarks are not available without a profile");
+            return None;
+        };
+        match store.add(&url, &title, tags) {
+            Ok(id) => {
+                println!("★ Bookmarked {} as #{}", title, id);
+                Some(id)
+            }
+            Err(e) => {
+                eprintln!("❌ {}", e);
+                None
+            }
+        }
+    }
+    
+    /// Print the bookmarks matching a query, or all of them
+    fn show_bookmarks(&self, query: &str) {
+        let Some(store) = &self.bookmarks else {
+            println!("Bookmarks are not available without a profile");
+            return;
+        };
+        let matches = store.search(query);
+        if matches.is_empty() {
+            println!("No bookmarks found");
+        }
+        for bookmark in matches {
+            let folder = if bookmark.folder.is_empty() { String::new() } else { format!(" [{}]", bookmark.folder) };
+            let tags = if bookmark.tags.is_empty() { String::new() } else { format!(" #{}", bookmark.tags.join(" #")) };
+            println!("  {}: {} <{}>{}{}", bookmark.id, bookmark.title, bookmark.url, folder, tags);
+        }
+    }
+    
+    /// Run the browser-level action bound to a key combination
+    /// 
+    /// Returns `false` if the keys are not a browser shortcut, in which case
+    /// the page should receive them.
+    pub fn handle_shortcut(&mut self, init: &KeyboardEventInit) -> bool {
+        if let Some(shortcut) = zoom_shortcut(init) {
+            let engine = &mut self.tabs[self.active_tab];
+            match shortcut {
+                ZoomShortcut::ZoomIn => engine.zoom_in(),
+                ZoomShortcut::ZoomOut => engine.zoom_out(),
+                ZoomShortcut::Reset => engine.reset_zoom(),
+            };
+            println!("🔍 {}", zoom::format_zoom(engine.zoom_level()));
+            return true;
+        }
+        match bookmark_shortcut(init) {
+            Some(BookmarkShortcut::BookmarkPage) => {
+                self.bookmark_current_page(&[]);
+                true
+            }
+            Some(BookmarkShortcut::ShowBookmarks) => {
+                self.show_bookmarks("");
+                true
+            }
+            None => false,
+        }
+    }
+    
+    /// Run a bookmark command of the CLI
+    fn run_bookmark_command(&mut self, command: &str, args: &str) {
+        let result = match (command, &mut self.bookmarks) {
+            (_, None) => {
+                println!("Bookmarks are not available without a profile");
+                return;
+            }
+            ("unbookmark", Some(store)) => match args.trim().parse::<u64>() {
+                Ok(id) => store.remove(id).map(|bookmark| println!("Removed bookmark {}", bookmark.title)),
+                Err(_) => {
+                    println!("Usage: unbookmark <id>");
+                    return;
+                }
+            },
+            ("tag-bookmark", Some(store)) => match args.split_once(' ').map(|(id, tag)| (id.parse::<u64>(), tag.trim())) {
+                Some((Ok(id), tag)) if !tag.is_empty() => store.tag(id, tag).map(|_| println!("Tagged bookmark {} with {}", id, tag)),
+                _ => {
+                    println!("Usage: tag-bookmark <id> <tag>");
+                    return;
+                }
+            },
+            ("import-bookmarks", Some(store)) => match std::fs::read_to_string(args.trim()) {
+                Ok(html) => store.import_netscape(&html).map(|count| println!("Imported {} bookmarks", count)),
+                Err(e) => Err(e.into()),
+            },
+            ("export-bookmarks", Some(store)) => std::fs::write(args.trim(), store.export_netscape())
+                .map(|_| println!("Exported {} bookmarks to {}", store.bookmarks().len(), args.trim()))
+                .map_err(Into::into),
+            _ => return,
+        };
+        if let Err(e) = result {
+            eprintln!("❌ {}", e);
+        }
+    }
+    
+    /// Make another tab the active one, hiding the previous tab and showing
+    /// the new one so background tabs are throttled
+    fn switch_tab(&mut self, index: usize) {
+        if index != self.active_tab {
+            self.tabs[self.active_tab].set_visibility_state(VisibilityState::Hidden);
+        }
+        self.active_tab = index;
+        self.tabs[index].set_visibility_state(VisibilityState::Visible);
+    }
+    
+    /// Run an item of the active tab's context menu
+    ///
+    /// Opening a link in a new tab and reloading are done here, as they
+    /// change the tabs; everything else is run by the tab.
+    pub fn run_context_menu_action(&mut self, action: &ContextMenuAction) -> bool {
+        match action {
+            ContextMenuAction::OpenLinkInNewTab(url) => {
+                let mut engine = self.open_engine();
+                let loaded = block_on(engine.fetch_url(url));
+                // The new tab opens in the background, as in other browsers
+                engine.set_visibility_state(VisibilityState::Hidden);
+                self.tabs.push(engine);
+                loaded
+            }
+            ContextMenuAction::Reload => self.reload_tab(),
+            _ => self.run_in_tab(EnginePhase::Scripting, |tab| tab.run_context_menu_action(action)).unwrap_or(false),
+        }
+    }
+
+    /// Show the context menu for a window position, or run its item
+    /// `menu <x> <y> <n>`
+    fn run_menu_command(&mut self, args: &str) {
+        let numbers: Vec<f32> = args.split_whitespace().filter_map(|arg| arg.parse().ok()).collect();
+        let (x, y) = match numbers[..] {
+            [x, y] | [x, y, _] => (x, y),
+            _ => {
+                println!("Usage: menu <x> <y> [item]");
+                return;
+            }
+        };
+        let Some(menu) = self.tabs[self.active_tab].context_menu_at(x, y) else {
+            println!("Nothing at ({}, {}); perform layout first", x, y);
+            return;
+        };
+        let Some(index) = numbers.get(2).map(|index| *index as usize) else {
+            for (index, item) in menu.items.iter().enumerate() {
+                if item.is_separator() {
+                    println!("     ────");
+                } else if item.action.is_some() {
+                    println!("  {:>2} {}", index, item.label);
+                } else {
+                    println!("     {} (disabled)", item.label);
+                }
+            }
+            return;
+        };
+        let Some(action) = menu.items.get(index).and_then(|item| item.action.clone()) else {
+            println!("No item {} in the menu", index);
+            return;
+        };
+        if !self.run_context_menu_action(&action) {
+            println!("The item could not be run");
+        } else if let ContextMenuAction::Inspect(_) = action {
+            let stdin = io::stdin();
+            println!("Devtools console for tab {}; type .help for commands, .exit to leave", self.active_tab + 1);
+            if let Err(e) = devtools::run_console(&self.tabs[self.active_tab], &mut stdin.lock(), &mut io::stdout()) {
+                println!("❌ Console failed: {}", e);
+            }
+        } else if let ContextMenuAction::OpenLinkInNewTab(url) = action {
+            println!("Opened {} in tab {}", url, self.tabs.len() - 1);
+        }
+    }
+
+    /// Hide or show the active tab as the window is minimized or restored
+    pub fn set_window_minimized(&mut self, minimized: bool) {
+        self.engine().set_visibility_state(VisibilityState::from_hidden(minimized));
+    }
+    
+    /// Get the engine of the active tab
+    fn engine(&mut self) -> &mut BrowserEngine {
+        &mut self.tabs[self.active_tab]
+    }
+    
+    /// Capture the state of every tab
+    pub fn capture_session(&self) -> Session {
+        Session {
+            tabs: self.tabs.iter().map(BrowserEngine::capture_tab_state).collect(),
+            active_tab: self.active_tab,
+            saved_at: 0,
+        }
+    }
+    
+    /// Replace the open tabs with the ones of a saved session
+    /// 
+    /// Tabs whose document cannot be loaded keep their history so the user
+    /// can retry.
+    pub fn restore_session(&mut self, session: &Session) {
+        let mut tabs = Vec::new();
+        for state in &session.tabs {
+            let mut engine = self.open_engine();
+            if !block_on(engine.restore_tab(state)) {
+                engine.restore_tab_state(state);
+            }
+            tabs.push(engine);
+        }
+        if tabs.is_empty() {
+            return;
+        }
+        self.active_tab = session.active_tab.min(tabs.len() - 1);
+        for (index, tab) in tabs.iter_mut().enumerate() {
+            tab.set_visibility_state(VisibilityState::from_hidden(index != self.active_tab));
+        }
+        self.tabs = tabs;
+        println!("Restored {} tabs", self.tabs.len());
+    }
+    
+    /// Restore the previous session if the preference or a crash calls
+    /// for it
+    fn restore_at_startup(&mut self) {
+        let Some(store) = &self.session_store else {
+            return;
+        };
+        let session = match store.load() {
+            Ok(session) => session,
+            Err(e) => {
+                eprintln!("❌ {}", e);
+                None
+            }
+        };
+        let action = startup_action(self.restore_preference, store.previous_run_crashed(), session.is_some());
+        let restore = match action {
+            StartupAction::StartFresh => false,
+            StartupAction::Restore => true,
+            StartupAction::OfferRestore => {
+                print!("The browser did not shut down correctly. Restore the previous session? [y/N] ");
+                io::stdout().flush().is_ok() && {
+                    let mut answer = String::new();
+                    io::stdin().read_line(&mut answer).is_ok()
+                        && matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes")
+                }
+            }
+        };
+        if let (true, Some(session)) = (restore, session) {
+            self.restore_session(&session);
+        }
+    }
+    
+    /// Save the session if the save interval has passed
+    fn save_session_if_due(&mut self) {
+        let session = self.capture_session();
+        if let Some(store) = &mut self.session_store {
+            if let Err(e) = store.save_if_due(&session, std::time::Instant::now()) {
+                eprintln!("❌ {}", e);
+            }
+        }
+    }
+    
+    /// Save the session and mark a clean exit
+    fn close_session(&mut self) {
+        let session = self.capture_session();
+        if let Some(mut store) = self.session_store.take() {
+            if let Err(e) = store.save(&session).and_then(|_| store.close()) {
+                eprintln!("❌ {}", e);
+            }
         }
     }
     
@@ -416,7 +3256,8 @@ impl BrowserCLI {
     /// This method starts an interactive loop where users can enter
     /// commands to control the browser engine.
     pub fn run(&mut self) {
-        self.engine.start();
+        self.engine().start();
+        self.restore_at_startup();
         
         println!("Browser Engine CLI");
         println!("Commands:");
@@ -429,11 +3270,29 @@ impl BrowserCLI {
         println!("  gpu-render      - Render with GPU (opens window)");
         // println!("  js <code>       - Execute JavaScript code");
         println!("  text            - Extract text content");
+        println!("  reload          - Reload the page, e.g. after the tab crashed");
+        println!("  tabs            - List open tabs");
+        println!("  new-tab         - Open a new tab");
+        println!("  tab <n>         - Switch to tab n");
+        println!("  bookmark [tags] - Bookmark the current page (Ctrl+D)");
+        println!("  bookmarks [q]   - List or search bookmarks (Ctrl+Shift+O)");
         println!("  help            - Show this help");
         println!("  quit            - Exit the browser");
         println!();
         
         loop {
+            let blocked = self.tabs[self.active_tab].blocked_mixed_content();
+            if blocked > 0 {
+                print!("🛡️ {} blocked | ", blocked);
+            }
+            let filtered = self.tabs[self.active_tab].blocked_requests();
+            if filtered > 0 {
+                print!("🚫 {} filtered | ", filtered);
+            }
+            let zoom_level = self.tabs[self.active_tab].zoom_level();
+            if zoom_level != zoom::DEFAULT_ZOOM {
+                print!("🔍 {} | ", zoom::format_zoom(zoom_level));
+            }
             print!("browser> ");
             io::stdout().flush().unwrap();
             
@@ -456,10 +3315,10 @@ impl BrowserCLI {
                     if args.is_empty() {
                         println!("Usage: load <html>");
                     } else {
-                        if self.engine.load_html(args) {
-                            println!("HTML loaded successfully");
-                        } else {
-                            println!("Failed to load HTML");
+                        match self.run_in_tab(EnginePhase::HtmlParsing, |tab| tab.load_html(args)) {
+                            Some(true) => println!("HTML loaded successfully"),
+                            Some(false) => println!("Failed to load HTML"),
+                            None => {}
                         }
                     }
                 }
@@ -467,40 +3326,49 @@ impl BrowserCLI {
                     if args.is_empty() {
                         println!("Usage: css <css>");
                     } else {
-                        if self.engine.load_css(args) {
-                            println!("CSS loaded successfully");
-                        } else {
-                            println!("Failed to load CSS");
+                        match self.run_in_tab(EnginePhase::CssParsing, |tab| tab.load_css(args)) {
+                            Some(true) => println!("CSS loaded successfully"),
+                            Some(false) => println!("Failed to load CSS"),
+                            None => {}
                         }
                     }
                 }
                 "fetch" => {
                     if args.is_empty() {
                         println!("Usage: fetch <url>");
+                    } else if is_about_url(args) {
+                        // Internal pages are generated without the network
+                        match self.run_in_tab(EnginePhase::Navigation, |tab| block_on(tab.fetch_url(args))) {
+                            Some(true) => println!("Opened {}", args),
+                            Some(false) => println!("{} is not an internal page; try about:version", args),
+                            None => {}
+                        }
                     } else {
                         println!("Fetching URL: {}", args);
                         println!("Note: Fetch command requires async runtime. Use 'cargo run -- fetch <url>' instead.");
                     }
                 }
-                "layout" => {
-                    if self.engine.perform_layout() {
-                        println!("Layout calculated successfully");
-                    } else {
-                        println!("Failed to calculate layout");
-                    }
-                }
+                "layout" => match self.run_in_tab(EnginePhase::Layout, BrowserEngine::perform_layout) {
+                    Some(true) => println!("Layout calculated successfully"),
+                    Some(false) => println!("Failed to calculate layout"),
+                    None => {}
+                },
                 "render" => {
-                    println!("{}", self.engine.render_to_text());
+                    if let Some(text) = self.run_in_tab(EnginePhase::Rendering, |tab| tab.render_to_text()) {
+                        println!("{}", text);
+                    }
                 }
                 "layout-render" => {
-                    println!("{}", self.engine.render_layout());
+                    if let Some(text) = self.run_in_tab(EnginePhase::Layout, |tab| tab.render_layout()) {
+                        println!("{}", text);
+                    }
                 }
                 "gpu-render" => {
-                    if let Some(_layout) = self.engine.get_layout() {
+                    if let Some(_layout) = self.engine().get_layout() {
                         println!("Opening GPU renderer window...");
                         let rt = tokio::runtime::Runtime::new().unwrap();
-                        if let Err(e) = rt.block_on(self.engine.render_with_gpu()) {
-                            eprintln!("GPU renderer error: {}", e);
+                        if let Err(e) = rt.block_on(self.engine().render_with_gpu()) {
+                            eprintln!("{}", e.console_entry());
                         }
                     } else {
                         println!("No layout available. Load HTML, CSS, and perform layout first.");
@@ -510,7 +3378,7 @@ impl BrowserCLI {
                 //     if args.is_empty() {
                 //         println!("Usage: js <javascript_code>");
                 //     } else {
-                //         if self.engine.execute_javascript(args) {
+                //         if self.engine().execute_javascript(args) {
                 //             println!("JavaScript executed successfully");
                 //         } else {
                 //             println!("Failed to execute JavaScript");
@@ -518,8 +3386,175 @@ impl BrowserCLI {
                 //     }
                 // }
                 "text" => {
-                    println!("{}", self.engine.get_text_content());
+                    if let Some(text) = self.run_in_tab(EnginePhase::Rendering, |tab| tab.get_text_content()) {
+                        println!("{}", text);
+                    }
+                }
+                "find" => {
+                    if args.is_empty() {
+                        println!("Usage: find <text>");
+                    } else if let Some(result) = self.tabs[self.active_tab].find_in_page(args) {
+                        println!("🔎 {} matches for \"{}\"", result.matches, result.query);
+                        for snippet in &result.snippets {
+                            println!("  …{}…", snippet);
+                        }
+                    }
+                }
+                "pseudo-localize" => {
+                    let translated = self.tabs[self.active_tab].translate_page(|segment| Some(pseudo_localize(&segment.source)));
+                    println!("Pseudo-localized {} text segments", translated);
+                }
+                "reload" => {
+                    if self.reload_tab() {
+                        println!("Reloaded tab {}", self.active_tab);
+                    } else if self.tabs[self.active_tab].crash_recovery().is_some() {
+                        println!("The page could not be loaded; type 'reload' to try again");
+                    } else {
+                        println!("Failed to reload the page");
+                    }
+                }
+                "click" => {
+                    if args.is_empty() {
+                        println!("Usage: click <element id>");
+                    } else if (args == "reload" && self.tabs[self.active_tab].crash_recovery().is_some())
+                        || (args == RETRY_BUTTON_ID && self.tabs[self.active_tab].error_page().is_some()) {
+                        // The retry button of the crash page or an error page
+                        if self.reload_tab() {
+                            println!("Reloaded tab {}", self.active_tab);
+                        } else {
+                            println!("The page could not be loaded; type 'click {}' to try again", RETRY_BUTTON_ID);
+                        }
+                    } else {
+                        match self.run_in_tab(EnginePhase::Scripting, |tab| tab.click_element(args)) {
+                            Some(true) => println!("Clicked #{}", args),
+                            Some(false) => println!("The click on #{} was not dispatched or was cancelled", args),
+                            None => {}
+                        }
+                    }
+                }
+                "hover" => {
+                    if args.is_empty() {
+                        println!("Usage: hover <element id>");
+                    } else if self.tabs[self.active_tab].hover_element(args) {
+                        println!("Queued the link under #{} for prefetching", args);
+                    }
+                }
+                "prefetch" => {
+                    self.run_prefetch_command(args);
+                }
+                "tabs" => {
+                    for (index, tab) in self.tabs.iter().enumerate() {
+                        let marker = if index == self.active_tab { "*" } else { " " };
+                        let title = tab.current_url.as_deref().unwrap_or(if tab.has_document() { "(local document)" } else { "(empty)" });
+                        let title = match tab.crash_recovery() {
+                            Some(_) => format!("💥 crashed: {}", title),
+                            None => title.to_string(),
+                        };
+                        let bookmarked = match (&self.bookmarks, &tab.current_url) {
+                            (Some(store), Some(url)) if store.is_bookmarked(url) => " ★",
+                            _ => "",
+                        };
+                        println!("{} {}: {}{}", marker, index, title, bookmarked);
+                    }
+                }
+                "new-tab" => {
+                    let engine = self.open_engine();
+                    self.tabs.push(engine);
+                    self.switch_tab(self.tabs.len() - 1);
+                    println!("Opened tab {}", self.active_tab);
+                }
+                "tab" => match args.parse::<usize>() {
+                    Ok(index) if index < self.tabs.len() => {
+                        self.switch_tab(index);
+                        println!("Switched to tab {}", index);
+                    }
+                    _ => println!("Usage: tab <0-{}>", self.tabs.len() - 1),
+                },
+                "bookmark" => {
+                    let tags: Vec<String> = args.split_whitespace().map(str::to_string).collect();
+                    self.bookmark_current_page(&tags);
+                }
+                "bookmarks" => {
+                    self.show_bookmarks(args);
+                }
+                "unbookmark" | "tag-bookmark" | "import-bookmarks" | "export-bookmarks" => {
+                    self.run_bookmark_command(command, args);
+                }
+                "color-scheme" | "reduced-motion" | "forced-colors" => {
+                    self.run_appearance_command(command, args);
+                }
+                "menu" => {
+                    self.run_menu_command(args);
+                }
+                "console" => {
+                    let stdin = io::stdin();
+                    println!("Devtools console for tab {}; type .help for commands, .exit to leave", self.active_tab + 1);
+                    if let Err(e) = devtools::run_console(&self.tabs[self.active_tab], &mut stdin.lock(), &mut io::stdout()) {
+                        println!("❌ Console failed: {}", e);
+                    }
+                }
+                "text-rendering" => {
+                    self.run_text_rendering_command(args);
+                }
+                "coverage" => {
+                    if let Some(report) = self.run_in_tab(EnginePhase::Scripting, |tab| tab.collect_coverage()) {
+                        match args.trim() {
+                            "--json" => println!("{}", serde_json::to_string_pretty(&report.to_json()).unwrap_or_default()),
+                            _ => print!("{}", report.to_text()),
+                        }
+                    }
+                }
+                "profile" => {
+                    self.run_profile_command(args);
+                }
+                "hsts" => {
+                    self.run_hsts_command(args);
+                }
+                "filters" => {
+                    self.run_filters_command(args);
+                }
+                "visited" => {
+                    self.run_visited_command(args);
+                }
+                "extensions" => {
+                    self.run_extensions_command(args);
+                }
+                "zoom" => {
+                    self.run_zoom_command(args);
                 }
+                "min-font-size" => {
+                    self.run_minimum_font_size_command(args);
+                }
+                "permissions" => {
+                    self.run_permissions_command(args);
+                }
+                "pin" => {
+                    self.run_pin_command();
+                }
+                "caches" => {
+                    self.run_caches_command(args);
+                }
+                "layers" => {
+                    self.show_layers();
+                }
+                "user-styles" => match &self.user_styles {
+                    Some(user_styles) => {
+                        let user_styles = user_styles.borrow();
+                        println!("User stylesheets in {}:", user_styles.dir().display());
+                        for file in user_styles.files() {
+                            println!("  {}", file);
+                        }
+                    }
+                    None => println!("User stylesheets are not available without a profile"),
+                },
+                "key" => match parse_key_combination(args) {
+                    Some(init) => {
+                        if !self.handle_shortcut(&init) {
+                            println!("{} is not a browser shortcut", args);
+                        }
+                    }
+                    None => println!("Usage: key <combination>, e.g. key Ctrl+D"),
+                },
                 "help" => {
                     self.show_help();
                 }
@@ -530,9 +3565,22 @@ impl BrowserCLI {
                     println!("Unknown command: {}. Type 'help' for available commands.", command);
                 }
             }
+            
+            if self.reload_user_styles() {
+                println!("🔸 User stylesheets changed; pages were restyled");
+            }
+            // Prefetches wait until the command's own work is done
+            let tab = &mut self.tabs[self.active_tab];
+            if !tab.pending_prefetches().is_empty() {
+                block_on(tab.run_prefetches());
+            }
+            self.save_session_if_due();
         }
         
-        self.engine.stop();
+        self.close_session();
+        for tab in &mut self.tabs {
+            tab.stop();
+        }
     }
     
     /// Show help information
@@ -540,18 +3588,71 @@ impl BrowserCLI {
         println!("Available commands:");
         println!("  load <html>      - Load HTML content and parse it into DOM");
         println!("  css <css>        - Load CSS content and parse it into stylesheet");
-        println!("  fetch <url>      - Fetch HTML content from a URL");
+        println!("  fetch <url>      - Fetch HTML content from a URL, or open an internal page such as about:memory");
         println!("  layout           - Perform layout calculation on current document");
         println!("  render           - Render the current document as formatted text");
         println!("  layout-render    - Render with layout and styling information");
         println!("  gpu-render       - Render with GPU acceleration (opens window)");
         // println!("  js <code>        - Execute JavaScript code");
         println!("  text             - Extract only the text content from the document");
+        println!("  find <text>      - Find text in the page and show the matches in context");
+        println!("  pseudo-localize  - Replace the page's text with accented, padded text to find untranslatable strings");
+        println!("  reload           - Reload the page; crashed tabs load their page again");
+        println!("  click <id>       - Click the element with the given id");
+        println!("  hover <id>       - Rest the pointer on the element with the given id, prefetching its link");
+        println!("  tabs             - List the open tabs; the active one is marked with *");
+        println!("  new-tab          - Open an empty tab and switch to it");
+        println!("  tab <n>          - Switch to the tab with index n");
+        println!("  bookmark [tags]  - Bookmark the current page with optional tags (Ctrl+D)");
+        println!("  bookmarks [q]    - List bookmarks, or search them; tag:name matches a tag (Ctrl+Shift+O)");
+        println!("  unbookmark <id>  - Remove a bookmark");
+        println!("  tag-bookmark <id> <tag> - Add a tag to a bookmark");
+        println!("  import-bookmarks <file> - Import a bookmarks HTML file from another browser");
+        println!("  export-bookmarks <file> - Export bookmarks as a bookmarks HTML file");
+        println!("  key <keys>       - Press a key combination such as Ctrl+D");
+        println!("  user-styles      - List the user stylesheets and where they are kept");
+        println!("  extensions [enable <id> | disable <id>] - List the extensions injecting scripts and styles, or turn one on or off");
+        println!("  color-scheme <light|dark|system>   - Set the color scheme pages see");
+        println!("  reduced-motion <on|off|system>     - Ask pages to reduce motion");
+        println!("  forced-colors <on|off|system>      - Show pages in the high-contrast palette");
+        println!("  console          - Open the devtools console on the current page");
+        println!("  menu <x> <y> [n] - Show the context menu at a position, or run its item n");
+        println!("  coverage [--json] - Show the bytes of the page's stylesheet and scripts that were never used");
+        println!("  profile [frames] [--export <file>] - Record frames as a flame chart, optionally as a trace JSON file");
+        println!("  text-rendering [<setting> <value>] - Set antialiasing (grayscale|subpixel|bgr), hinting (none|slight|medium|full) or gamma");
+        println!("  hsts [list | forget <host> | clear] - Inspect or clear HTTPS-only hosts");
+        println!("  filters [list | add <file>] - Show or add content blocking filter lists");
+        println!("  visited [clear]  - Count or forget the visited links");
+        println!("  zoom [in | out | reset | sites | <percent>] - Zoom the page, remembered for its site (Ctrl+= / Ctrl+- / Ctrl+0)");
+        println!("  min-font-size [<px> | off] - Show text no smaller than a size on every page");
+        println!("  permissions [reset <origin>] - List the permissions of sites, or forget those of one");
+        println!("  pin              - Keep the current page and its subresources for offline use");
+        println!("  caches [delete <name>] - List the caches of pages and pinned pages, or delete one");
+        println!("  layers           - Show the composited layers of the page and why each was promoted");
+        println!("  prefetch [on|off | hover|cross-origin|metered <on|off>] - Show or change what is fetched ahead of time");
         println!("  help             - Show this help message");
         println!("  quit/exit        - Exit the browser");
     }
 }
 
+/// Parse a key combination such as `Ctrl+Shift+O`
+fn parse_key_combination(text: &str) -> Option<KeyboardEventInit> {
+    let mut parts: Vec<&str> = text.trim().split('+').map(str::trim).collect();
+    let key = parts.pop().filter(|key| !key.is_empty())?;
+    let code = if key.len() == 1 { format!("Key{}", key.to_ascii_uppercase()) } else { key.to_string() };
+    let mut init = KeyboardEventInit::new(&key.to_ascii_lowercase(), &code);
+    for modifier in parts {
+        match modifier.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => init.ctrl_key = true,
+            "shift" => init.shift_key = true,
+            "alt" => init.alt_key = true,
+            "cmd" | "meta" | "super" => init.meta_key = true,
+            _ => return None,
+        }
+    }
+    Some(init)
+}
+
 impl Default for BrowserCLI {
     fn default() -> Self {
         Self::new()
@@ -561,6 +3662,8 @@ impl Default for BrowserCLI {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::temporary_profile;
+    use js_integration::permissions::PermissionState;
 
     #[test]
     fn test_browser_engine_creation() {
@@ -601,6 +3704,110 @@ mod tests {
         assert_eq!(text.trim(), "Hello World");
     }
 
+    #[test]
+    fn test_details_toggle_relayout() {
+        let mut engine = BrowserEngine::new();
+        let html = "<html><body><details><summary id=\"toggle\">More</summary><p>Hidden text</p></details></body></html>";
+        assert!(engine.load_html(html));
+        assert!(engine.load_css("p { color: black; }"));
+        assert!(engine.perform_layout());
+        assert!(!engine.render_layout().contains("<p>"));
+        
+        assert!(engine.click_element("toggle"));
+        assert!(engine.render_layout().contains("<p>"));
+    }
+
+    #[test]
+    fn test_attribute_mutation_restyles_in_place() {
+        fn find_box(layout_box: &LayoutBox, id: u64) -> Option<&LayoutBox> {
+            if layout_box.node.id == id {
+                return Some(layout_box);
+            }
+            layout_box.children.iter().find_map(|child| find_box(child, id))
+        }
+
+        let mut engine = BrowserEngine::new();
+        assert!(engine.load_html("<html><body><div id=\"panel\"><span id=\"label\">Saved</span></div></body></html>"));
+        assert!(engine.load_css("div:has(> span[data-saved]) {\n  color: #008000;\n}"));
+        assert!(engine.perform_layout());
+        let panel = engine.event_manager.find_node_by_id("panel").unwrap();
+        let label = engine.event_manager.find_node_by_id("label").unwrap();
+
+        // A data attribute only :has() reads restyles the panel and its
+        // label in the existing tree, which a new layout would not have
+        // marked, to the styles a new cascade gives them
+        engine.current_layout.as_mut().unwrap().content.x = -1.0;
+        label.set_attribute("data-saved", "");
+        engine.relayout_if_needed();
+        let layout = engine.current_layout.as_ref().unwrap();
+        assert_eq!(layout.content.x, -1.0);
+        assert_eq!(find_box(layout, panel.id).unwrap().styles.color.as_deref(), Some("#008000"));
+        let matcher = engine.layout_engine().unwrap();
+        assert_eq!(find_box(layout, label.id).unwrap().styles, matcher.style_matcher().compute_styles(&label));
+
+        // Hiding the label changes the boxes, so the page is laid out again
+        label.set_attribute("hidden", "");
+        engine.relayout_if_needed();
+        assert_ne!(engine.current_layout.as_ref().unwrap().content.x, -1.0);
+    }
+
+    #[test]
+    fn test_form_submission_from_shell() {
+        let mut engine = BrowserEngine::new();
+        let html = "<html><body><form action=\"https://example.com/search\"><input id=\"q\" name=\"q\"><input type=\"submit\" id=\"go\"></form></body></html>";
+        assert!(engine.load_html(html));
+        
+        assert!(engine.input_text("q", "rust"));
+        assert!(engine.press_key("q", "Enter"));
+        
+        let submission = engine.take_form_submission().unwrap();
+        assert_eq!(submission.method, FormMethod::Get);
+        assert_eq!(submission.entries, vec![("q".to_string(), "rust".to_string())]);
+        assert_eq!(
+            engine.resolve_document_url(&submission.action).as_deref(),
+            Some("https://example.com/search")
+        );
+    }
+
+    #[test]
+    fn test_link_navigation_from_shell() {
+        let mut engine = BrowserEngine::new();
+        let html = "<html><body><a href=\"https://example.com/docs\"><span id=\"docs\">Docs</span></a></body></html>";
+        assert!(engine.load_html(html));
+        
+        assert!(engine.click_element("docs"));
+        assert_eq!(engine.take_link_navigation().as_deref(), Some("https://example.com/docs"));
+        assert!(engine.take_link_navigation().is_none());
+    }
+
+    #[test]
+    fn test_fullscreen_from_script_and_user() {
+        let mut engine = BrowserEngine::new();
+        let html = "<html><body><video id=\"movie\"></video></body></html>";
+        assert!(engine.load_html(html));
+        assert!(engine.load_css("video { width: 320px; }"));
+        assert!(engine.perform_layout());
+        
+        let mut script_engine = engine.create_script_engine().unwrap();
+        script_engine.execute("document.querySelector('video').requestFullscreen()").unwrap();
+        assert!(engine.fullscreen_element().is_some_and(|element| element.get_attribute("id").as_deref() == Some("movie")));
+        assert!(engine.exit_fullscreen());
+        assert!(engine.fullscreen_element().is_none() && !engine.exit_fullscreen());
+        
+        // Escape exits fullscreen too
+        script_engine.execute("document.querySelector('video').requestFullscreen()").unwrap();
+        engine.press_key("movie", "Escape");
+        assert!(engine.fullscreen_element().is_none());
+    }
+    
+    #[test]
+    fn test_javascript_url_blocked() {
+        let mut engine = BrowserEngine::new();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        assert!(!runtime.block_on(engine.fetch_url("javascript:alert(1)")));
+        assert!(!engine.has_document());
+    }
+
     #[test]
     fn test_engine_lifecycle() {
         let mut engine = BrowserEngine::new();
@@ -614,4 +3821,544 @@ mod tests {
         assert!(!engine.is_running());
         assert!(!engine.has_document());
     }
+
+    #[test]
+    fn test_sandbox_policy_per_origin() {
+        let mut engine = BrowserEngine::new();
+        engine.set_sandbox_policy_for_origin("https://untrusted.example/", SandboxPolicy::untrusted());
+
+        let policy = engine.sandbox_policy_for_url("https://untrusted.example/page.html");
+        assert!(!policy.allow_network);
+        assert!(engine.sandbox_policy_for_url("https://trusted.example/").allow_network);
+        assert!(!engine.sandbox_policy_for_url("data:text/html,<p>hi</p>").allow_network);
+
+        engine.current_url = Some("https://untrusted.example/".to_string());
+        let script_engine = engine.create_script_engine().unwrap();
+        assert!(!script_engine.sandbox_policy().allow_network);
+    }
+
+    #[test]
+    fn test_scripts_share_the_clipboard() {
+        let mut engine = BrowserEngine::new();
+        engine.set_clipboard(Clipboard::in_memory());
+        engine.set_clipboard_permissions(ClipboardPermissions::allow_all());
+        assert!(engine.load_html("<html><body><button>Copy</button></body></html>"));
+        engine.current_document.as_ref().unwrap().set_origin(Origin::from_url("https://example.com/"));
+
+        let mut script_engine = engine.create_script_engine().unwrap();
+        script_engine.execute("navigator.clipboard.writeText('https://example.com/invite')").unwrap();
+        assert_eq!(engine.clipboard().read_text().unwrap(), "https://example.com/invite");
+
+        // Opaque documents do not get the clipboard
+        engine.current_document.as_ref().unwrap().set_origin(Origin::Opaque);
+        let mut script_engine = engine.create_script_engine().unwrap();
+        let result = script_engine.execute("typeof navigator === 'undefined' || navigator.clipboard === undefined").unwrap();
+        assert_eq!(result.as_boolean(), Some(true));
+    }
+
+    #[test]
+    fn test_notifications_ask_for_permission_once() {
+        let mut engine = BrowserEngine::new();
+        let shown = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = Arc::clone(&shown);
+        engine.set_notification_handler(Arc::new(move |notification: &DesktopNotification| log.lock().unwrap().push(notification.title.clone())));
+        engine.set_permission_prompt(Arc::new(|origin, name| Some(origin == "https://chat.example" && name == PermissionName::Notifications)));
+        let mut cli = BrowserCLI { tabs: vec![engine], ..BrowserCLI::new() };
+        cli.set_permission_store(Permissions::new());
+        assert!(cli.engine().load_html("<html><body></body></html>"));
+        cli.engine().current_document.as_ref().unwrap().set_origin(Origin::from_url("https://chat.example/"));
+
+        let mut script_engine = cli.engine().create_script_engine().unwrap();
+        script_engine.execute("Notification.requestPermission().then(() => new Notification('Ping'))").unwrap();
+        script_engine.context.run_jobs();
+        assert_eq!(*shown.lock().unwrap(), vec!["Ping".to_string()]);
+        // The decision is shared with the other tabs
+        assert_eq!(cli.permissions.state("https://chat.example", PermissionName::Notifications), PermissionState::Granted);
+        cli.run_permissions_command("reset https://chat.example");
+        assert!(cli.engine().permissions().decisions().is_empty());
+    }
+
+    #[test]
+    fn test_geolocation_in_secure_documents() {
+        let mut engine = BrowserEngine::new();
+        engine.set_permission_prompt(Arc::new(|_, name| Some(name == PermissionName::Geolocation)));
+        engine.set_location_provider(Rc::new(FixedLocationProvider::new(48.8584, 2.2945, 10.0)));
+        assert!(engine.load_html("<html><body></body></html>"));
+        engine.current_document.as_ref().unwrap().set_origin(Origin::from_url("https://maps.example/"));
+
+        let mut script_engine = engine.create_script_engine().unwrap();
+        script_engine
+            .execute("var found = []; navigator.geolocation.watchPosition((position) => found.push(position.coords.longitude))")
+            .unwrap();
+        script_engine.context.run_jobs();
+        assert_eq!(script_engine.update_geolocation(), 0);
+        let found = script_engine.execute("found.join()").unwrap();
+        assert_eq!(found.to_string(&mut script_engine.context).unwrap().to_std_string_escaped(), "2.2945");
+
+        // Plain HTTP pages are not secure contexts
+        engine.current_document.as_ref().unwrap().set_origin(Origin::from_url("http://maps.example/"));
+        let mut script_engine = engine.create_script_engine().unwrap();
+        let result = script_engine.execute("navigator.geolocation === undefined").unwrap();
+        assert_eq!(result.as_boolean(), Some(true));
+    }
+
+    #[test]
+    fn test_prefetched_navigations() {
+        let mut engine = BrowserEngine::new();
+        let html = r#"<html><head><link rel="prefetch" href="/next.html"><link rel="prefetch" href="https://ads.example/"></head>
+            <body><a href="/more.html"><span id="more">More</span></a></body></html>"#;
+        engine.set_prefetch_policy(PrefetchPolicy { hover: true, ..PrefetchPolicy::default() });
+        assert!(engine.load_html_from(html, Some("https://example.com/"), Instant::now()));
+        engine.current_url = Some("https://example.com/".to_string());
+        let queued: Vec<&str> = engine.pending_prefetches().iter().map(|hint| hint.url.as_str()).collect();
+        assert_eq!(queued, vec!["https://example.com/next.html"]);
+        assert!(engine.hover_element("more"));
+        assert!(!engine.hover_element("more"));
+        assert_eq!(engine.pending_prefetches().len(), 2);
+
+        // A metered connection drops what is queued
+        engine.set_metered_connection(true);
+        assert!(engine.pending_prefetches().is_empty());
+        assert!(!engine.hover_element("more"));
+
+        // Navigations are served from the prefetch cache once
+        let cache = PrefetchCache::new();
+        engine.set_prefetch_cache(cache.clone());
+        let page = networking::disk_cache::CachedResponse::new("https://example.com/next.html", 200, Vec::new(), b"<html><body><p>Next</p></body></html>".to_vec());
+        cache.insert(page, Instant::now());
+        assert!(block_on(engine.fetch_url("https://example.com/next.html")));
+        assert!(engine.get_text_content().contains("Next"));
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_engine_error_context() {
+        let error = EngineError::from(html_parser::ParseError::ParseError(12, "bad tag".to_string()))
+            .with_url("https://example.com/");
+        assert_eq!(error.phase(), EnginePhase::HtmlParsing);
+        assert_eq!(error.byte_offset(), Some(12));
+        assert_eq!(
+            error.to_string(),
+            "HTML parsing failed for https://example.com/ at byte 12: Parse error at position 12: bad tag"
+        );
+        assert!(error.error_page().contains("<li>URL: https://example.com/</li>"));
+    }
+
+    #[test]
+    fn test_failures_are_reported_as_engine_errors() {
+        let mut engine = BrowserEngine::new();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        assert!(!runtime.block_on(engine.fetch_url("javascript:alert(1)")));
+
+        let error = engine.last_error().unwrap();
+        assert_eq!(error.phase(), EnginePhase::Navigation);
+        assert_eq!(error.url(), Some("javascript:alert(1)"));
+        assert!(matches!(error.kind(), EngineErrorKind::Security(_)));
+        assert!(error.console_entry().starts_with("❌ navigation failed for javascript:alert(1)"));
+
+        // No document was loaded
+        assert!(!engine.perform_layout());
+        assert_eq!(engine.last_error().unwrap().phase(), EnginePhase::Layout);
+
+        // The error page becomes the document, with its own stylesheet
+        assert!(engine.show_error_page());
+        assert!(engine.get_text_content().contains("This page could not be loaded"));
+        assert!(engine.perform_layout());
+    }
+
+    #[test]
+    fn test_paint_timing_observed_by_scripts() {
+        let mut engine = BrowserEngine::new();
+        assert!(engine.load_html("<html><body><p>Hello</p></body></html>"));
+        assert!(engine.load_css("p { color: black; }"));
+        assert!(engine.perform_layout());
+
+        let mut script_engine = engine.create_script_engine().unwrap();
+        script_engine
+            .execute("globalThis.paints = []; new PerformanceObserver((list) => list.getEntries().forEach((e) => paints.push(e.name))).observe({ type: 'paint' });")
+            .unwrap();
+
+        engine.record_paint();
+        engine.record_paint();
+        assert!(engine.performance().first_contentful_paint().is_some());
+
+        script_engine.process_event_loop().unwrap();
+        let observed = script_engine.execute("paints.join(',')").unwrap();
+        assert_eq!(
+            observed.to_string(&mut script_engine.context).unwrap().to_std_string_escaped(),
+            "first-paint,first-contentful-paint"
+        );
+        let count = script_engine.execute("performance.getEntriesByType('paint').length").unwrap();
+        assert_eq!(count.as_number(), Some(2.0));
+    }
+
+    #[test]
+    fn test_tab_state_round_trip() {
+        let html = "<html><body><form><input name=\"q\"></form></body></html>";
+        let mut engine = BrowserEngine::new();
+        assert!(engine.load_html(html));
+        dom::forms::form_controls(&engine.get_document().unwrap().root)[0].set_value("unsent");
+        engine.set_scroll_offset(0.0, 480.0);
+        engine.push_history("https://example.com/a");
+        engine.push_history("https://example.com/b");
+
+        let cli = BrowserCLI { tabs: vec![engine], ..BrowserCLI::new() };
+        let session = cli.capture_session();
+        assert_eq!(session.tabs.len(), 1);
+        let state = &session.tabs[0];
+        assert_eq!(state.history_index, 1);
+        assert_eq!(state.form_controls.len(), 1);
+
+        let mut restored = BrowserEngine::new();
+        assert!(restored.load_html(html));
+        assert_eq!(restored.restore_tab_state(state), 1);
+        assert_eq!(restored.scroll_offset(), (0.0, 480.0));
+        assert_eq!(restored.history().0.len(), 2);
+        let control = &dom::forms::form_controls(&restored.get_document().unwrap().root)[0];
+        assert_eq!(control.value(), "unsent");
+    }
+
+    #[test]
+    fn test_bookmark_shortcut_in_cli() {
+        let dir = temporary_profile("cli-bookmarks");
+        let mut cli = BrowserCLI::new();
+        cli.set_bookmark_store(BookmarkStore::open(&dir).unwrap());
+        assert!(cli.engine().load_html("<html><head><title>Example  Page</title></head><body></body></html>"));
+        cli.engine().current_url = Some("https://example.com/".to_string());
+
+        assert!(cli.handle_shortcut(&parse_key_combination("Ctrl+D").unwrap()));
+        assert!(!cli.handle_shortcut(&parse_key_combination("Ctrl+Q").unwrap()));
+        assert!(parse_key_combination("Hyper+D").is_none());
+
+        let store = BookmarkStore::open(&dir).unwrap();
+        let bookmark = store.find_by_url("https://example.com/").unwrap();
+        assert_eq!(bookmark.title, "Example Page");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_user_styles_reload_live() {
+        fn count_boxes(layout: &LayoutBox) -> usize {
+            1 + layout.children.iter().map(count_boxes).sum::<usize>()
+        }
+        
+        let dir = temporary_profile("cli-user-styles");
+        let mut cli = BrowserCLI::new();
+        cli.set_user_styles(UserStyles::open(&dir).unwrap());
+        assert!(cli.engine().load_html("<html><body><p>Article</p><div class=\"ad\" style=\"display: block\">Buy now</div></body></html>"));
+        cli.engine().current_url = Some("https://news.example.com/".to_string());
+        assert!(cli.engine().load_css("p { color: black; }"));
+        assert!(cli.engine().perform_layout());
+        let before = count_boxes(cli.engine().get_layout().unwrap());
+        assert!(!cli.reload_user_styles());
+        
+        std::fs::write(dir.join(user_styles::USER_STYLES_DIR).join("example.com.css"), ".ad { display: none !important }").unwrap();
+        assert!(cli.reload_user_styles());
+        assert!(count_boxes(cli.engine().get_layout().unwrap()) < before);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_color_scheme_toggle_restyles_and_notifies_scripts() {
+        fn colors(layout: &LayoutBox, out: &mut Vec<String>) {
+            out.extend(layout.styles.color.clone());
+            layout.children.iter().for_each(|child| colors(child, out));
+        }
+        
+        let mut cli = BrowserCLI::new();
+        assert!(cli.engine().load_html("<html><body><p>Text</p></body></html>"));
+        assert!(cli.engine().load_css("p { color: black; }\n@media (prefers-color-scheme: dark) {\n  p { color: white; }\n}"));
+        assert!(cli.engine().perform_layout());
+        let mut before = Vec::new();
+        colors(cli.engine().get_layout().unwrap(), &mut before);
+        assert!(before.contains(&"black".to_string()) && !before.contains(&"white".to_string()));
+        
+        let mut script_engine = cli.engine().create_script_engine().unwrap();
+        script_engine
+            .execute("globalThis.log = []; matchMedia('(prefers-color-scheme: dark)').addEventListener('change', (e) => log.push(e.matches));")
+            .unwrap();
+        
+        cli.run_appearance_command("color-scheme", "dark");
+        let mut after = Vec::new();
+        colors(cli.engine().get_layout().unwrap(), &mut after);
+        assert!(after.contains(&"white".to_string()));
+        assert_eq!(cli.engine().sync_media_features(&mut script_engine), 1);
+        let log = script_engine.execute("log.join(',')").unwrap();
+        assert_eq!(log.to_string(&mut script_engine.context).unwrap().to_std_string_escaped(), "true");
+        
+        // Following the system again restores the light scheme
+        cli.run_appearance_command("color-scheme", "system");
+        assert_eq!(cli.engine().media_features().color_scheme, ColorScheme::Light);
+        assert!(!cli.engine().set_media_features(MediaFeatures::default()));
+    }
+
+    #[test]
+    fn test_forced_colors_toggle_overrides_page_colors() {
+        fn find<'a>(layout: &'a LayoutBox, tag: &str) -> Option<&'a LayoutBox> {
+            if layout.node.tag_name() == Some(tag) {
+                return Some(layout);
+            }
+            layout.children.iter().find_map(|child| find(child, tag))
+        }
+        
+        let mut cli = BrowserCLI::new();
+        assert!(cli.engine().load_html("<html><body><p>Text</p><a href=\"/next\">Next</a></body></html>"));
+        assert!(cli.engine().load_css("p { color: red; background-color: #ffee00; }"));
+        assert!(cli.engine().perform_layout());
+        let mut script_engine = cli.engine().create_script_engine().unwrap();
+        script_engine.execute("globalThis.forced = matchMedia('(forced-colors: active)');").unwrap();
+        
+        cli.run_appearance_command("forced-colors", "on");
+        assert!(cli.engine().media_features().forced_colors);
+        let palette = layout::SystemPalette::for_scheme(ColorScheme::Light);
+        let layout = cli.engine().get_layout().unwrap();
+        let paragraph = &find(layout, "p").unwrap().styles;
+        assert_eq!(paragraph.color.as_deref(), Some(palette.canvas_text));
+        assert_eq!(paragraph.background_color.as_deref(), Some(palette.canvas));
+        assert_eq!(find(layout, "a").unwrap().styles.color.as_deref(), Some(palette.link_text));
+        assert_eq!(cli.engine().sync_media_features(&mut script_engine), 1);
+        let matches = script_engine.execute("forced.matches").unwrap();
+        assert!(matches.to_boolean());
+        
+        cli.run_appearance_command("forced-colors", "system");
+        assert!(!cli.engine().media_features().forced_colors);
+    }
+
+    #[test]
+    fn test_hsts_store_shared_by_tabs() {
+        let mut cli = BrowserCLI::new();
+        cli.set_hsts_store(HstsStore::new());
+        let tab = cli.open_engine();
+        cli.tabs.push(tab);
+        
+        // A policy recorded by one tab upgrades requests in every tab
+        let secure = url::Url::parse("https://secure.example/").unwrap();
+        assert!(cli.tabs[0].http_client.hsts_store().record(&secure, "max-age=3600; includeSubDomains"));
+        let plain = url::Url::parse("http://www.secure.example/login").unwrap();
+        let upgraded = cli.tabs[1].http_client.hsts_store().upgrade(&plain).unwrap();
+        assert_eq!(upgraded.as_str(), "https://www.secure.example/login");
+        assert_eq!(cli.hsts_store().entries().len(), 1);
+        
+        cli.run_hsts_command("forget secure.example");
+        assert!(cli.tabs[1].http_client.hsts_store().upgrade(&plain).is_none());
+    }
+
+    #[test]
+    fn test_mixed_content_scripts_blocked_and_counted() {
+        let mut engine = BrowserEngine::new();
+        assert!(engine.load_html("<html><body><p>Checkout</p></body></html>"));
+        engine.current_document.as_ref().unwrap().set_origin(Origin::from_url("https://shop.example/"));
+        
+        let mut script_engine = engine.create_script_engine().unwrap();
+        assert!(script_engine.fetch_and_execute_script("http://cdn.example/tracker.js").is_err());
+        script_engine.execute("console.log('page message')").unwrap();
+        assert_eq!(engine.blocked_mixed_content(), 1);
+        assert_eq!(engine.console().messages().len(), 2);
+        
+        // A new page starts without blocked requests
+        assert!(engine.load_html("<html><body></body></html>"));
+        assert_eq!(engine.blocked_mixed_content(), 0);
+    }
+
+    #[test]
+    fn test_filter_lists_block_scripts_and_hide_elements() {
+        let mut cli = BrowserCLI::new();
+        let blocker = ContentBlocker::new();
+        blocker.add_filter_list("||ads.example^$third-party\n##.sponsored");
+        cli.set_content_blocker(blocker);
+        cli.tabs.push(cli.open_engine());
+
+        let engine = &mut cli.tabs[1];
+        let html = "<html><body><p class=\"sponsored\">Buy now</p><p>News</p></body></html>";
+        assert!(engine.load_html_from(html, Some("https://news.example/"), dom::clock::now()));
+        assert!(engine.load_css("p { display: block }"));
+        let mut script_engine = engine.create_script_engine().unwrap();
+        assert!(script_engine.fetch_and_execute_script("https://ads.example/banner.js").is_err());
+        assert_eq!(engine.blocked_requests(), 1);
+        assert_eq!(engine.blocked_mixed_content(), 0);
+
+        // The cosmetic filter hides the sponsored paragraph
+        let hidden = engine.cosmetic_filters.as_ref().unwrap();
+        assert_eq!(hidden.rules.len(), 1);
+        let layout = engine.layout_engine().unwrap();
+        let sponsored = engine.current_document.as_ref().unwrap().root.query_selector(".sponsored").unwrap().unwrap();
+        assert_eq!(layout.style_matcher().compute_styles(&sponsored).display, layout::DisplayType::None);
+
+        // A new page starts without blocked requests
+        assert!(engine.load_html("<html><body></body></html>"));
+        assert_eq!(engine.blocked_requests(), 0);
+    }
+
+    #[test]
+    fn test_translate_page_keeps_links() {
+        let mut engine = BrowserEngine::new();
+        assert!(engine.load_html("<html><body><p>See <a href=\"/faq\">the FAQ</a>.</p></body></html>"));
+        assert!(engine.load_css("p { display: block }"));
+        let segments = engine.text_segments();
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].source, "See <g1>the FAQ</g1>.");
+
+        let translated = engine.translate_page(|segment| Some(segment.source.replace("See", "Lire").replace("the FAQ", "la FAQ")));
+        assert_eq!(translated, 1);
+        let link = engine.current_document.as_ref().unwrap().root.query_selector("a").unwrap().unwrap();
+        assert_eq!(link.text_content(), "la FAQ");
+        assert_eq!(engine.text_segments()[0].source, "Lire <g1>la FAQ</g1>.");
+    }
+
+    #[test]
+    fn test_crashed_tab_shows_crash_page_and_reloads() {
+        let dir = temporary_profile("cli-crashes");
+        let mut cli = BrowserCLI::new();
+        cli.set_crash_report_dir(dir.clone());
+        cli.engine().load_html("<html><body><p>Other tab</p></body></html>");
+        cli.tabs.push(cli.open_engine());
+        cli.active_tab = 1;
+        assert_eq!(cli.run_in_tab(EnginePhase::HtmlParsing, |tab| tab.load_html("<html><body><p>Article</p></body></html>")), Some(true));
+        assert!(cli.engine().load_css("p { color: navy }"));
+
+        let crashed = cli.run_in_tab(EnginePhase::Layout, |_| -> bool { panic!("layout invariant violated") });
+        assert_eq!(crashed, None);
+        let recovery = cli.engine().crash_recovery().unwrap();
+        assert_eq!(recovery.report.phase, EnginePhase::Layout);
+        let report = std::fs::read_to_string(recovery.report_path.as_ref().unwrap()).unwrap();
+        assert!(report.contains("Message: layout invariant violated"));
+        assert!(report.contains("Backtrace:"));
+        assert!(cli.engine().get_text_content().contains("This tab crashed"));
+        // The other tab is untouched
+        assert!(cli.tabs[0].get_text_content().contains("Other tab"));
+
+        assert!(cli.reload_tab());
+        assert!(cli.engine().crash_recovery().is_none());
+        assert!(cli.engine().get_text_content().contains("Article"));
+        assert!(cli.engine().perform_layout());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_visited_links_shared_by_tabs() {
+        let mut cli = BrowserCLI::new();
+        cli.set_visited_store(VisitedStore::new());
+        assert!(cli.tabs[0].load_html_from("<p>Read me</p>", Some("https://example.com/read"), Instant::now()));
+        
+        let mut tab = cli.open_engine();
+        assert!(tab.load_html("<a id=\"read\" href=\"https://example.com/read#top\">Read</a><a id=\"new\" href=\"https://example.com/new\">New</a>"));
+        let read = tab.event_manager.find_node_by_id("read").unwrap();
+        let new = tab.event_manager.find_node_by_id("new").unwrap();
+        assert!(read.element_state().contains(dom::ElementState::VISITED));
+        assert!(!new.element_state().contains(dom::ElementState::VISITED));
+        
+        cli.tabs.push(tab);
+        cli.run_visited_command("clear");
+        assert!(!read.element_state().contains(dom::ElementState::VISITED));
+    }
+
+    #[test]
+    fn test_pinned_page_served_when_offline() {
+        use networking::disk_cache::CachedResponse;
+        
+        // Nothing listens on the discard port, so the fetch fails
+        let url = "http://127.0.0.1:9/article";
+        let storage = CacheStorage::new();
+        let pinned = storage.open_cache(OFFLINE_CACHE).unwrap();
+        let html = "<html><head><link rel=\"stylesheet\" href=\"/style.css\"></head><body><p>Saved for later</p></body></html>";
+        pinned.put(CachedResponse::new(url, 200, Vec::new(), html.as_bytes().to_vec())).unwrap();
+        pinned.put(CachedResponse::new("http://127.0.0.1:9/style.css", 200, Vec::new(), b"p {\n  color: green;\n}".to_vec())).unwrap();
+        
+        let mut cli = BrowserCLI::new();
+        cli.set_cache_storage(storage);
+        let mut tab = cli.open_engine();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        assert!(runtime.block_on(tab.fetch_url(url)));
+        assert!(tab.get_text_content().contains("Saved for later"));
+        assert!(tab.has_stylesheet());
+        assert_eq!(tab.history().0, [url.to_string()]);
+        
+        // Pages without a pinned copy still fail
+        assert!(!runtime.block_on(tab.fetch_url("http://127.0.0.1:9/other")));
+    }
+
+    #[test]
+    fn test_context_menu_at_a_link() {
+        let mut cli = BrowserCLI::new();
+        cli.engine().set_clipboard(Clipboard::in_memory());
+        let html = "<html><body><a id=\"docs\" href=\"https://example.com/docs\" style=\"display: block; width: 200px; height: 40px\">Docs</a></body></html>";
+        assert!(cli.engine().load_html(html));
+        assert!(cli.engine().load_css("a { color: blue }"));
+        assert!(cli.engine().perform_layout());
+
+        let menu = cli.engine().context_menu_at(10.0, 10.0).expect("the link is hit");
+        assert_eq!(menu.labels(), vec!["Open Link in New Tab", "Copy Link Address", "Inspect"]);
+        assert!(cli.run_context_menu_action(menu.find("Copy Link Address").unwrap()));
+        assert_eq!(cli.engine().clipboard().read_text().unwrap(), "https://example.com/docs");
+
+        // Inspecting selects the link in the devtools console
+        assert!(cli.run_context_menu_action(menu.find("Inspect").unwrap()));
+        let mut output = Vec::new();
+        devtools::run_console(&cli.tabs[cli.active_tab], &mut "$0.matches('#docs')\n".as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("$0 = <a>"));
+        assert!(output.contains("← true"));
+    }
+
+    #[test]
+    fn test_login_saved_and_filled_on_revisit() {
+        let mut engine = BrowserEngine::new();
+        let asked = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = Arc::clone(&asked);
+        engine.set_password_save_prompt(Arc::new(move |origin, username| {
+            log.lock().unwrap().push(format!("{} {}", origin, username));
+            true
+        }));
+        engine.set_permission_prompt(Arc::new(|origin, name| Some(origin == "http://127.0.0.1:9" && name == PermissionName::Autofill)));
+        // Nothing listens on the discard port, so submitting fails after
+        // the password is saved
+        let url = "http://127.0.0.1:9/login";
+        let html = "<html><body><form action=\"/session\" method=\"post\"><input id=\"user\" name=\"user\"><input id=\"pass\" name=\"pass\" type=\"password\"></form></body></html>";
+        assert!(engine.load_html_from(html, Some(url), dom::clock::now()));
+        assert!(engine.input_text("user", "ada"));
+        assert!(engine.input_text("pass", "hunter2"));
+        assert!(engine.press_key("pass", "Enter"));
+        let submission = engine.take_form_submission().unwrap();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        assert!(!runtime.block_on(engine.submit_form(submission.clone())));
+        assert_eq!(*asked.lock().unwrap(), vec!["http://127.0.0.1:9 ada"]);
+        assert_eq!(engine.password_store().len(), 1);
+
+        // The next visit is filled, and the same login is not offered again
+        assert!(engine.load_html_from(html, Some(url), dom::clock::now()));
+        let password = engine.get_document().unwrap().root.query_selector("#pass").unwrap().unwrap();
+        assert_eq!(password.value(), "hunter2");
+        assert_eq!(engine.permissions().state("http://127.0.0.1:9", PermissionName::Autofill), PermissionState::Granted);
+        assert!(!engine.offer_to_save_login(&submission));
+        assert_eq!(asked.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_navigation_unloads_the_page_or_is_canceled() {
+        let mut engine = BrowserEngine::new();
+        let answers = Arc::new(std::sync::Mutex::new(vec![Some(String::new()), None]));
+        let prompts = Arc::clone(&answers);
+        engine.set_user_prompt_handler(Arc::new(move |prompt: &UserPrompt| {
+            assert_eq!(*prompt, UserPrompt::BeforeUnload);
+            prompts.lock().unwrap().pop().expect("prompted too often")
+        }));
+        assert!(engine.load_html("<html><body>Draft</body></html>"));
+        let draft = Rc::clone(engine.get_document().unwrap());
+        let script = engine.page_script().unwrap();
+        script.execute("window.addEventListener('beforeunload', e => e.preventDefault()); window.addEventListener('unload', () => { document.body.textContent = 'Unloaded'; });").unwrap();
+        let now = script.now();
+        script.event_loop().set_timer("autosave()".to_string(), std::time::Duration::from_secs(5), true, now);
+
+        // Staying cancels the navigation and keeps the page running
+        assert!(!engine.load_html("<html><body>Next</body></html>"));
+        assert!(Rc::ptr_eq(engine.get_document().unwrap(), &draft));
+        assert_eq!(engine.page_script().unwrap().event_loop().timer_count(), 1);
+
+        // Leaving unloads the page before the next one is shown
+        assert!(engine.load_html("<html><body>Next</body></html>"));
+        assert!(answers.lock().unwrap().is_empty());
+        assert_eq!(draft.root.text_content(), "Unloaded");
+        assert!(engine.get_text_content().contains("Next"));
+        assert_eq!(engine.page_script().unwrap().event_loop().timer_count(), 0);
+    }
 }
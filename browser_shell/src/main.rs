@@ -6,8 +6,28 @@
 
 use browser_shell::{BrowserEngine, BrowserCLI};
 use browser_shell::webpage_loader::{WebpageLoader, WebpageLoaderConfig};
+use browser_shell::session::{RestorePreference, SessionStore};
+use browser_shell::bookmarks::BookmarkStore;
+use browser_shell::user_styles::UserStyles;
+use browser_shell::extensions::Extensions;
+use browser_shell::appearance::{AppearanceSettings, SystemPreferences};
+use browser_shell::bench::{run_benchmarks, BenchConfig, Workload};
+use browser_shell::visited::VisitedStore;
+use browser_shell::zoom::ZoomStore;
+use browser_shell::passwords::PasswordStore;
+use browser_shell::screenshot::CaptureArea;
+use browser_shell::snapshots::{snapshot_url, DEFAULT_SNAPSHOT_DIR};
+use js_integration::permissions::Permissions;
+use networking::cache_storage::CacheStorage;
+use networking::content_blocking::ContentBlocker;
+use css_parser::media::MediaFeatures;
+use networking::hsts::HstsStore;
+use networking::har::HarReplay;
+use networking::RequestInterceptor;
+use renderer_wgpu::snapshot::encode_png;
 use std::env;
 use std::path::Path;
+use std::sync::Arc;
 
 /// Example HTML document for demonstration
 const EXAMPLE_HTML: &str = r#"
@@ -86,6 +106,25 @@ li {
 /// Main function that demonstrates the browser engine
 #[tokio::main]
 async fn main() {
+    // Engine processes talk to the shell over a socket, not the terminal
+    #[cfg(unix)]
+    if let Some(socket) = env::args().skip_while(|arg| arg != browser_shell::tab_process::ENGINE_PROCESS_FLAG).nth(1) {
+        run_engine_process(socket);
+        return;
+    }
+    // Benchmark output may be JSON for scripts, so it is printed alone
+    if env::args().nth(1).as_deref() == Some("bench") {
+        run_bench_mode(&env::args().skip(2).collect::<Vec<_>>());
+        return;
+    }
+    // Snapshot runs fail with an exit code, so scripts and CI can check them
+    if env::args().nth(1).as_deref() == Some("snapshot") {
+        if !run_snapshot_mode(&env::args().skip(2).collect::<Vec<_>>()).await {
+            std::process::exit(1);
+        }
+        return;
+    }
+
     println!("🚀 Experimental Browser Engine - Phase 3");
     println!("==========================================");
     println!();
@@ -98,6 +137,11 @@ async fn main() {
     let mut fetch_timeout = 30000; // 30 seconds default
     let mut enable_js_tracing = false;
     let mut performance_metrics = false;
+    let mut restore_preference = RestorePreference::Never;
+    let mut replay_har = None;
+    let mut replay_timings = false;
+    let mut capture_area = CaptureArea::Viewport;
+    let mut screenshot_output = None;
     
     // Parse flags
     for i in 1..args.len() {
@@ -122,6 +166,44 @@ async fn main() {
                 performance_metrics = true;
                 println!("🔸 Performance metrics enabled");
             }
+            "--restore-session" => {
+                restore_preference = RestorePreference::Always;
+                println!("🔸 Previous session will be restored");
+            }
+            "--replay-har" => {
+                replay_har = args.get(i + 1).cloned();
+            }
+            "--replay-timings" => {
+                replay_timings = true;
+            }
+            "--capture-selector" => {
+                if let Some(selector) = args.get(i + 1) {
+                    capture_area = CaptureArea::Selector(selector.clone());
+                }
+            }
+            "--full-page" => {
+                capture_area = CaptureArea::FullPage;
+            }
+            "--output" => {
+                screenshot_output = args.get(i + 1).cloned();
+            }
+            "--paint-flashing" => {
+                // Read by every GPU window the process opens
+                env::set_var(renderer_wgpu::paint_flashing::PAINT_FLASHING_ENV, "1");
+                println!("🔸 Paint flashing enabled (F9 toggles it in the window)");
+            }
+            "--spatial-navigation" => {
+                // Read by every GPU window the process opens
+                env::set_var(renderer_wgpu::input_handler::SPATIAL_NAVIGATION_ENV, "1");
+                println!("🔸 Spatial navigation enabled: arrow keys move focus (F7 toggles it in the window)");
+            }
+            "--deterministic" => {
+                // Read by the CLI when it opens its tabs
+                if env::var(browser_shell::deterministic::DETERMINISTIC_ENV).is_err() {
+                    env::set_var(browser_shell::deterministic::DETERMINISTIC_ENV, "1");
+                }
+                println!("🔸 Deterministic mode: manual clock, test fonts and seeded Math.random()");
+            }
             "--help" => {
                 print_help();
                 return;
@@ -130,15 +212,36 @@ async fn main() {
         }
     }
     
+    let replay: Option<Arc<dyn RequestInterceptor>> = match &replay_har {
+        Some(path) => match HarReplay::open(path) {
+            Ok(replay) => {
+                println!("🔸 Replaying {} responses from {}{}", replay.len(), path, if replay_timings { " with their timings" } else { "" });
+                Some(Arc::new(replay.honor_timings(replay_timings)))
+            }
+            Err(e) => {
+                eprintln!("❌ Failed to read {}: {}", path, e);
+                return;
+            }
+        },
+        None => None,
+    };
+    
     if args.len() > 1 && args[1] == "--interactive" {
         // Run in interactive mode
-        run_interactive_mode();
+        run_interactive_mode(restore_preference);
+    } else if args.len() > 1 && args[1] == "--multi-process" {
+        run_multi_process_mode();
+    } else if args.len() > 2 && args[1] == "repl" {
+        run_repl_mode(&args[2]).await;
+    } else if args.len() > 1 && args[1] == "--remote-debugging-port" {
+        let port = args.get(2).cloned().unwrap_or_else(|| browser_shell::cdp::DEFAULT_PORT.to_string());
+        run_remote_debugging_mode(&port).await;
     } else if args.len() > 2 && args[1] == "fetch" {
         // Run fetch mode
-        run_fetch_mode(&args[2]).await;
+        run_fetch_mode(&args[2], replay).await;
     } else if args.len() > 2 && args[1] == "--load-url" {
         // Run full webpage loading mode
-        run_webpage_loader(&args[2], trace_microtasks, fetch_timeout, enable_js_tracing, performance_metrics).await;
+        run_webpage_loader(&args[2], trace_microtasks, fetch_timeout, enable_js_tracing, performance_metrics, replay).await;
     } else if args.len() > 1 && args[1] == "--demo" {
         // Run demo webpage
         run_demo_webpage().await;
@@ -147,7 +250,9 @@ async fn main() {
         run_with_screenshot(&args[2]).await;
     } else if args.len() > 2 && args[1] == "--screenshot" {
         // Run with screenshot saving
-        run_with_screenshot_save(&args[2]).await;
+        run_with_screenshot_save(&args[2], capture_area, screenshot_output).await;
+    } else if args.len() > 2 && args[1] == "--print-to-pdf" {
+        run_with_print_to_pdf(&args[2], screenshot_output).await;
     } else if args.len() > 2 && args[1] == "--debug" {
         // Run with debug visualization
         run_with_debug(&args[2]).await;
@@ -252,17 +357,201 @@ async fn run_demonstration() {
     println!("  --record-screenshot <url> Load webpage and record screenshot");
 }
 
+/// Serve a tab for the shell listening on `socket`
+///
+/// The engine runs its own runtime to fetch documents, so it runs on a
+/// thread outside the async context of `main`.
+#[cfg(unix)]
+fn run_engine_process(socket: String) {
+    let engine = std::thread::spawn(move || browser_shell::tab_process::run_engine_process(std::path::Path::new(&socket)));
+    match engine.join() {
+        Ok(Ok(())) => {}
+        // The shell was told about the crash and shows it
+        Ok(Err(browser_shell::ipc::IpcError::TabCrashed(_))) => std::process::exit(101),
+        Ok(Err(e)) => {
+            eprintln!("❌ Engine process failed: {}", e);
+            std::process::exit(1);
+        }
+        Err(_) => std::process::exit(101),
+    }
+}
+
+/// Run the interactive shell with each tab in its own engine process
+fn run_multi_process_mode() {
+    #[cfg(unix)]
+    {
+        println!("🛡️ Multi-process mode: each tab runs in its own engine process");
+        println!("Type 'help' for available commands, 'quit' to exit");
+        browser_shell::tab_process::MultiProcessShell::new().run();
+    }
+    #[cfg(not(unix))]
+    println!("❌ Multi-process mode is only available on Unix");
+}
+
+/// Time the standard workloads and print their statistics
+fn run_bench_mode(args: &[String]) {
+    let mut config = BenchConfig::default();
+    let mut json = false;
+    let mut workloads = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--samples" | "--warmup" | "--scale" => {
+                let Some(value) = args.next() else {
+                    println!("❌ {} needs a value", arg);
+                    return;
+                };
+                let parsed = match arg.as_str() {
+                    "--samples" => value.parse().map(|n: usize| config.samples = n.max(1)).is_ok(),
+                    "--warmup" => value.parse().map(|n| config.warmup = n).is_ok(),
+                    _ => value.parse::<f64>().ok().filter(|scale| *scale > 0.0).map(|scale| config.scale = scale).is_some(),
+                };
+                if !parsed {
+                    println!("❌ Invalid value for {}: {}", arg, value);
+                    return;
+                }
+            }
+            name => match Workload::from_name(name) {
+                Some(workload) => workloads.push(workload),
+                None => {
+                    println!("❌ Unknown workload: {}. Workloads: parse, cascade, layout, paint", name);
+                    return;
+                }
+            },
+        }
+    }
+    if workloads.is_empty() {
+        workloads = Workload::ALL.to_vec();
+    }
+    if !json {
+        println!("🔸 Running {} workloads, {} samples each", workloads.len(), config.samples);
+    }
+    let report = run_benchmarks(&workloads, &config);
+    if json {
+        println!("{}", report.to_json());
+    } else {
+        print!("{}", report.to_text());
+    }
+}
+
+/// Compare pages against their DOM, layout and display list snapshots
+///
+/// Returns `false` if a page failed to load or differs from its snapshots.
+async fn run_snapshot_mode(args: &[String]) -> bool {
+    let mut update = false;
+    let mut directory = DEFAULT_SNAPSHOT_DIR.to_string();
+    let mut replay_har = None;
+    let mut urls = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--update" => update = true,
+            "--dir" | "--replay-har" => {
+                let Some(value) = args.next() else {
+                    println!("❌ {} needs a value", arg);
+                    return false;
+                };
+                if arg == "--dir" {
+                    directory = value.clone();
+                } else {
+                    replay_har = Some(value.clone());
+                }
+            }
+            url => urls.push(url.to_string()),
+        }
+    }
+    let replay: Option<Arc<dyn RequestInterceptor>> = match &replay_har {
+        Some(path) => match HarReplay::open(path) {
+            Ok(replay) => {
+                // Without a URL, snapshot the page the archive was recorded from
+                if urls.is_empty() {
+                    urls.extend(replay.first_url().map(|url| url.to_string()));
+                }
+                Some(Arc::new(replay))
+            }
+            Err(e) => {
+                println!("❌ Failed to read {}: {}", path, e);
+                return false;
+            }
+        },
+        None => None,
+    };
+    if urls.is_empty() {
+        println!("❌ Usage: snapshot [--update] [--dir <dir>] [--replay-har <file>] <url>...");
+        return false;
+    }
+    let mut passed = true;
+    for url in &urls {
+        match snapshot_url(url, replay.clone(), Path::new(&directory), update).await {
+            Ok(report) => {
+                print!("{}", report.to_text());
+                passed &= report.passed();
+            }
+            Err(e) => {
+                println!("❌ {}", e);
+                passed = false;
+            }
+        }
+    }
+    if !passed {
+        println!("Snapshots differ; rerun with --update to accept the changes");
+    }
+    passed
+}
+
+/// Load a page and open the devtools console on it
+async fn run_repl_mode(url: &str) {
+    let mut engine = BrowserEngine::new();
+    engine.start();
+    if !engine.fetch_url(url).await {
+        println!("❌ Failed to load {}", url);
+        return;
+    }
+    println!("Console for {}; type .help for commands, .exit to leave", url);
+    let stdin = std::io::stdin();
+    if let Err(e) = browser_shell::devtools::run_console(&engine, &mut stdin.lock(), &mut std::io::stdout()) {
+        println!("❌ Console failed: {}", e);
+    }
+    engine.stop();
+}
+
+/// Serve the remote debugging protocol on a loopback port
+async fn run_remote_debugging_mode(port: &str) {
+    let Ok(port) = port.parse::<u16>() else {
+        println!("❌ Invalid port: {}", port);
+        return;
+    };
+    let listener = match tokio::net::TcpListener::bind(("127.0.0.1", port)).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            println!("❌ Failed to listen on port {}: {}", port, e);
+            return;
+        }
+    };
+    let mut engine = BrowserEngine::new();
+    engine.start();
+    println!("DevTools listening on ws://127.0.0.1:{}/devtools/page/{}", port, browser_shell::cdp::TARGET_ID);
+    let mut session = browser_shell::cdp::CdpSession::new(engine);
+    if let Err(e) = browser_shell::cdp::serve(listener, &mut session).await {
+        println!("❌ Remote debugging stopped: {}", e);
+    }
+}
+
 /// Run the browser engine in fetch mode
 /// 
 /// This function fetches HTML from a URL and demonstrates the new networking
 /// capabilities of the browser engine.
-async fn run_fetch_mode(url: &str) {
+async fn run_fetch_mode(url: &str, replay: Option<Arc<dyn RequestInterceptor>>) {
     println!("Running in fetch mode...");
     println!("Fetching: {}", url);
     println!();
     
     // Create a new browser engine
     let mut engine = BrowserEngine::new();
+    if let Some(replay) = replay {
+        engine.set_request_interceptor(replay);
+    }
     engine.start();
     
     // Fetch the URL
@@ -314,15 +603,89 @@ async fn run_fetch_mode(url: &str) {
 /// 
 /// This function starts an interactive command-line interface where
 /// users can enter HTML content and see it rendered.
-fn run_interactive_mode() {
+fn run_interactive_mode(restore_preference: RestorePreference) {
     println!("Running in interactive mode...");
     println!("Type 'help' for available commands.");
     println!();
     
-    let mut cli = BrowserCLI::new();
+    let mut cli = create_cli(restore_preference);
     cli.run();
 }
 
+/// Apply the user's environment to a loader: the system appearance
+/// preferences and the profile's HSTS policies and filter lists
+fn configure_loader(loader: &mut WebpageLoader) {
+    loader.set_media_features(AppearanceSettings::default().media_features(&SystemPreferences::detect(), &MediaFeatures::default()));
+    match HstsStore::open(SessionStore::default_dir()) {
+        Ok(store) => loader.set_hsts_store(store),
+        Err(e) => eprintln!("❌ {}; HSTS policies are not available", e),
+    }
+    match ContentBlocker::open(SessionStore::default_dir()) {
+        Ok(blocker) => loader.set_content_blocker(blocker),
+        Err(e) => eprintln!("❌ {}; filter lists are not available", e),
+    }
+}
+
+/// Create the interactive CLI, keeping its session and bookmarks in the
+/// profile directory
+/// 
+/// Falls back to a CLI without session persistence or bookmarks if the
+/// profile directory cannot be used.
+fn create_cli(restore_preference: RestorePreference) -> BrowserCLI {
+    let profile = SessionStore::default_dir();
+    let mut cli = match SessionStore::open(&profile) {
+        Ok(store) => BrowserCLI::with_session(store, restore_preference),
+        Err(e) => {
+            eprintln!("❌ {}; the session will not be saved", e);
+            BrowserCLI::new()
+        }
+    };
+    match BookmarkStore::open(&profile) {
+        Ok(store) => cli.set_bookmark_store(store),
+        Err(e) => eprintln!("❌ {}; bookmarks are not available", e),
+    }
+    match UserStyles::open(&profile) {
+        Ok(user_styles) => cli.set_user_styles(user_styles),
+        Err(e) => eprintln!("❌ {}; user stylesheets are not available", e),
+    }
+    match Extensions::open(&profile) {
+        Ok(extensions) => cli.set_extensions(extensions),
+        Err(e) => eprintln!("❌ {}; extensions are not available", e),
+    }
+    match HstsStore::open(&profile) {
+        Ok(store) => cli.set_hsts_store(store),
+        Err(e) => eprintln!("❌ {}; HSTS policies will not be saved", e),
+    }
+    match ContentBlocker::open(&profile) {
+        Ok(blocker) => cli.set_content_blocker(blocker),
+        Err(e) => eprintln!("❌ {}; filter lists are not available", e),
+    }
+    match VisitedStore::open(&profile) {
+        Ok(store) => cli.set_visited_store(store),
+        Err(e) => eprintln!("❌ {}; visited links will not be saved", e),
+    }
+    match ZoomStore::open(&profile) {
+        Ok(store) => cli.set_zoom_store(store),
+        Err(e) => eprintln!("❌ {}; zoom levels will not be saved", e),
+    }
+    match Permissions::open(&profile) {
+        Ok(store) => cli.set_permission_store(store),
+        Err(e) => eprintln!("❌ {}; site permissions will not be saved", e),
+    }
+    match PasswordStore::open(&profile) {
+        Ok(store) => cli.set_password_store(store),
+        Err(e) => eprintln!("❌ {}; passwords will not be saved", e),
+    }
+    match CacheStorage::open(&profile) {
+        Ok(storage) => cli.set_cache_storage(storage),
+        Err(e) => eprintln!("❌ {}; caches and pinned pages will not be saved", e),
+    }
+    cli.set_system_preferences(SystemPreferences::detect());
+    cli.set_metered_connection(browser_shell::prefetch::detect_metered_connection());
+    cli.set_crash_report_dir(profile.join(browser_shell::crash::CRASH_DIR));
+    cli
+}
+
 /// Print help information
 fn print_help() {
     println!("🚀 Rust Browser Engine - Help");
@@ -332,14 +695,20 @@ fn print_help() {
     println!();
     println!("Commands:");
     println!("  --interactive              Run in interactive mode");
+    println!("  --multi-process           Run in interactive mode with each tab in its own process");
     println!("  fetch <url>               Fetch and display a URL");
+    println!("  repl <url>                Load a page and evaluate JavaScript in its console");
+    println!("  --remote-debugging-port [port] Serve the DevTools protocol on localhost (default: 9222)");
+    println!("  bench [workloads]         Time the standard workloads: parse, cascade, layout, paint");
+    println!("  snapshot <url>...         Compare pages against their DOM, layout and display list snapshots");
     println!("  --load-url <url>          Load complete webpage with full pipeline");
     println!("  --demo                    Run demo webpage with advanced features");
     println!("  --promise-demo            Run Promise and microtask demo");
     println!("  --fetch-demo              Run fetch API demo");
     println!("  --comprehensive-demo      Run comprehensive demo with all features");
     println!("  --record-screenshot <url> Load webpage and record screenshot");
-    println!("  --screenshot <url>        Load webpage and save screenshot");
+    println!("  --screenshot <url>        Load webpage and save a PNG screenshot of the viewport");
+    println!("  --print-to-pdf <url>      Load webpage and print it to a PDF file");
     println!("  --debug <url>             Load webpage with debug visualization");
     println!("  --help                    Show this help message");
     println!();
@@ -348,12 +717,33 @@ fn print_help() {
     println!("  --fetch-timeout <ms>      Set fetch timeout in milliseconds (default: 30000)");
     println!("  --js-trace                Enable JavaScript execution tracing");
     println!("  --performance             Enable performance metrics collection");
+    println!("  --restore-session         Restore the tabs of the previous session");
+    println!("  --replay-har <file>       Serve fetch and --load-url requests from a HAR archive instead of the network");
+    println!("  --replay-timings          Delay replayed responses by their recorded timings");
+    println!("  --paint-flashing          Flash repainted regions and outline relayout boundaries in GPU windows (F9 toggles)");
+    println!("  --spatial-navigation      Move focus with the arrow keys to the nearest focusable element in GPU windows (F7 toggles)");
+    println!("  --capture-selector <sel>  With --screenshot, capture the border box of the first element matching <sel>");
+    println!("  --full-page               With --screenshot, capture the whole page down to its full scroll height");
+    println!("  --output <file>           With --screenshot or --print-to-pdf, the file to write");
+    println!("  --deterministic           Freeze the clock, use the test font and seed Math.random() (DUBBY_DETERMINISTIC sets the seed)");
+    println!();
+    println!("Bench options:");
+    println!("  --json                    Print the results as JSON");
+    println!("  --samples <n>             Timed runs per workload (default: 20)");
+    println!("  --warmup <n>              Untimed runs before sampling (default: 3)");
+    println!("  --scale <factor>          Scale the input sizes, e.g. 0.1 for a quick run");
+    println!();
+    println!("Snapshot options:");
+    println!("  --update                  Overwrite the snapshots that changed");
+    println!("  --dir <dir>               Directory of the snapshots (default: snapshots)");
+    println!("  --replay-har <file>       Serve the pages from a HAR archive; without a URL, snapshot its first page");
     println!();
     println!("Examples:");
     println!("  browser_shell --load-url https://example.com --trace-microtasks --performance");
     println!("  browser_shell --promise-demo --trace-microtasks");
     println!("  browser_shell --fetch-demo --fetch-timeout 5000 --performance");
     println!("  browser_shell --comprehensive-demo --trace-microtasks --js-trace --performance");
+    println!("  browser_shell --screenshot https://example.com --capture-selector \"#main\" --output main.png");
     println!();
 }
 
@@ -361,7 +751,14 @@ fn print_help() {
 /// 
 /// This function demonstrates the complete end-to-end pipeline:
 /// fetch → parse → style → layout → JS → render
-async fn run_webpage_loader(url: &str, trace_microtasks: bool, fetch_timeout: u64, enable_js_tracing: bool, performance_metrics: bool) {
+async fn run_webpage_loader(
+    url: &str,
+    trace_microtasks: bool,
+    fetch_timeout: u64,
+    enable_js_tracing: bool,
+    performance_metrics: bool,
+    replay: Option<Arc<dyn RequestInterceptor>>,
+) {
     println!("🌐 Full Webpage Loading Pipeline");
     println!("=================================");
     println!("Loading: {}", url);
@@ -376,10 +773,17 @@ async fn run_webpage_loader(url: &str, trace_microtasks: bool, fetch_timeout: u6
         enable_animations: true,
         record_metrics: true,
         render_config: (),
+        render_blocking_timeout: std::time::Duration::from_secs(3),
     };
     
     // Create and initialize webpage loader
     let mut loader = WebpageLoader::new(config);
+    configure_loader(&mut loader);
+    if let Some(replay) = replay {
+        // HSTS policies learned since the recording would change its URLs
+        loader.set_hsts_store(HstsStore::new());
+        loader.set_request_interceptor(replay);
+    }
     
     match loader.initialize().await {
         Ok(_) => {
@@ -390,6 +794,14 @@ async fn run_webpage_loader(url: &str, trace_microtasks: bool, fetch_timeout: u6
                 Ok(_) => {
                     println!("\n🎉 Webpage loaded successfully!");
                     println!("URL: {}", url);
+                    let blocked = loader.console().messages().iter().filter(|message| message.is_blocked_mixed_content()).count();
+                    if blocked > 0 {
+                        println!("🛡️ Blocked {} insecure resources", blocked);
+                    }
+                    let filtered = loader.console().messages().iter().filter(|message| message.is_blocked_by_client()).count();
+                    if filtered > 0 {
+                        println!("🚫 Blocked {} requests matching filter lists", filtered);
+                    }
                     
                     // Display performance metrics if available
                     if performance_metrics {
@@ -408,7 +820,7 @@ async fn run_webpage_loader(url: &str, trace_microtasks: bool, fetch_timeout: u6
                     println!("🔸 Fetch timeout: {}ms", fetch_timeout);
                 }
                 Err(e) => {
-                    println!("❌ Failed to load webpage: {}", e);
+                    println!("{}", e.console_entry());
                 }
             }
         }
@@ -449,10 +861,12 @@ async fn run_demo_webpage() {
                 enable_animations: true,
                 record_metrics: true,
                 render_config: (),
+                render_blocking_timeout: std::time::Duration::from_secs(3),
             };
             
             // Create and initialize webpage loader
             let mut loader = WebpageLoader::new(config);
+            configure_loader(&mut loader);
             
             match loader.initialize().await {
                 Ok(_) => {
@@ -514,6 +928,7 @@ async fn run_with_screenshot(url: &str) {
     
     // Create and initialize webpage loader
     let mut loader = WebpageLoader::new(config);
+    configure_loader(&mut loader);
     
     match loader.initialize().await {
         Ok(_) => {
@@ -550,7 +965,7 @@ async fn run_with_screenshot(url: &str) {
                     println!("  Efficiency score: 85/100 (simulated)");
                 }
                 Err(e) => {
-                    println!("❌ Failed to load webpage: {}", e);
+                    println!("{}", e.console_entry());
                 }
             }
         }
@@ -598,39 +1013,55 @@ fn calculate_efficiency_score(metrics: &browser_shell::webpage_loader::Performan
     score.max(0).min(100)
 }
 
-/// Run webpage loading with screenshot saving
-async fn run_with_screenshot_save(url: &str) {
+/// Load a webpage and save a screenshot of it as PNG
+async fn run_with_screenshot_save(url: &str, area: CaptureArea, output: Option<String>) {
     println!("📸 Webpage Loading with Screenshot Saving");
     println!("=============================================");
     println!("Loading: {}", url);
     println!();
-    
-    let config = WebpageLoaderConfig {
-        timeout: std::time::Duration::from_secs(30),
-        max_redirects: 5,
-        user_agent: "RustBrowser/1.0 (Screenshot Mode)".to_string(),
-        enable_js: true,
-        enable_animations: true,
-        record_metrics: true,
-        render_config: (),
-    };
-    
-    let mut loader = WebpageLoader::new(config);
-    if let Err(e) = loader.initialize().await {
-        eprintln!("❌ Failed to initialize loader: {}", e);
+
+    let mut engine = BrowserEngine::new();
+    if !engine.fetch_url(url).await {
+        println!("❌ Failed to load {}", url);
         return;
     }
-    
-    match loader.load_webpage(url).await {
-        Ok(result) => {
-            println!("✅ Webpage loaded successfully!");
-            println!("📸 Screenshot saved to: {}", url.replace("https://", "").replace("/", "_") + ".ppm");
-            println!("📊 Performance Metrics:");
-            // Performance metrics are printed automatically by the loader
+    let page = match engine.capture_screenshot(&area, 1.0) {
+        Ok(page) => page,
+        Err(e) => {
+            println!("❌ Screenshot failed: {}", e);
+            return;
         }
+    };
+    let path = output.unwrap_or_else(|| url.replace("https://", "").replace("http://", "").replace('/', "_") + ".png");
+    match std::fs::write(&path, encode_png(&page)) {
+        Ok(()) => println!("📸 Screenshot saved to: {} ({}x{})", path, page.width, page.height),
+        Err(e) => println!("❌ Failed to save {}: {}", path, e),
+    }
+}
+
+/// Load a webpage and print it to PDF
+async fn run_with_print_to_pdf(url: &str, output: Option<String>) {
+    println!("🖨️ Webpage Printing to PDF");
+    println!("=============================================");
+    println!("Loading: {}", url);
+    println!();
+
+    let mut engine = BrowserEngine::new();
+    if !engine.fetch_url(url).await {
+        println!("❌ Failed to load {}", url);
+        return;
+    }
+    let pdf = match engine.print_to_pdf() {
+        Ok(pdf) => pdf,
         Err(e) => {
-            eprintln!("❌ Failed to load webpage: {}", e);
+            println!("❌ Printing failed: {}", e);
+            return;
         }
+    };
+    let path = output.unwrap_or_else(|| url.replace("https://", "").replace("http://", "").replace('/', "_") + ".pdf");
+    match std::fs::write(&path, &pdf) {
+        Ok(()) => println!("🖨️ PDF saved to: {} ({} bytes)", path, pdf.len()),
+        Err(e) => println!("❌ Failed to save {}: {}", path, e),
     }
 }
 
@@ -649,9 +1080,11 @@ async fn run_with_debug(url: &str) {
         enable_animations: true,
         record_metrics: true,
         render_config: (),
+        render_blocking_timeout: std::time::Duration::from_secs(3),
     };
     
     let mut loader = WebpageLoader::new(config);
+    configure_loader(&mut loader);
     if let Err(e) = loader.initialize().await {
         eprintln!("❌ Failed to initialize loader: {}", e);
         return;
@@ -665,7 +1098,7 @@ async fn run_with_debug(url: &str) {
             // Performance metrics are printed automatically by the loader
         }
         Err(e) => {
-            eprintln!("❌ Failed to load webpage: {}", e);
+            eprintln!("{}", e.console_entry());
         }
     }
 }
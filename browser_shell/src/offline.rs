@@ -0,0 +1,152 @@
+//! # Offline Pages
+//!
+//! This module pins pages for offline use: the page and the stylesheets,
+//! scripts and images it links to are stored in a named cache of the
+//! profile's cache storage, and served from there when the network is
+//! unreachable.
+//!
+//! ## Design Principles
+//!
+//! 1. **No Service Worker**: Pinning is a shell action, not something a
+//!    page asks for. The pinned copy lives in `OFFLINE_CACHE`, next to the
+//!    caches pages create themselves but outside every origin's view.
+//! 2. **Best Effort**: A subresource that cannot be fetched is skipped and
+//!    reported; the page is pinned with what could be stored.
+//! 3. **Network First**: The pinned copy is only used when fetching the
+//!    page fails, so it never hides a newer version of the page.
+
+use std::rc::Rc;
+use dom::{Document, Node};
+use networking::disk_cache::{CachedResponse, DiskCache};
+use networking::{resolve_url, HttpClient, HttpRequest};
+use url::Url;
+
+/// Name of the cache holding pinned pages
+pub const OFFLINE_CACHE: &str = "offline-pages";
+
+/// What pinning a page stored
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PinReport {
+    /// Responses stored, the page included
+    pub stored: usize,
+    /// Subresources that could not be fetched
+    pub failed: Vec<String>,
+}
+
+/// A subresource a document links to
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subresource {
+    pub url: String,
+    pub is_stylesheet: bool,
+}
+
+/// Find the stylesheets, scripts and images of a document, in document
+/// order and without duplicates
+///
+/// Only http(s) subresources are returned.
+pub fn subresources(document: &Document) -> Vec<Subresource> {
+    fn collect(document: &Document, node: &Rc<Node>, found: &mut Vec<Subresource>) {
+        let reference = match node.tag_name() {
+            Some("link") => {
+                let rel = node.get_attribute("rel").unwrap_or_default().to_ascii_lowercase();
+                rel.split_whitespace()
+                    .any(|token| token == "stylesheet")
+                    .then(|| node.get_attribute("href"))
+                    .flatten()
+                    .map(|href| (href, true))
+            }
+            Some("script") | Some("img") => node.get_attribute("src").map(|src| (src, false)),
+            _ => None,
+        };
+        if let Some((reference, is_stylesheet)) = reference {
+            if let Some(mut url) = resolve_url(document, &reference) {
+                url.set_fragment(None);
+                let is_http = matches!(url.scheme(), "http" | "https");
+                let url = url.to_string();
+                if is_http && !found.iter().any(|found| found.url == url) {
+                    found.push(Subresource { url, is_stylesheet });
+                }
+            }
+        }
+        for child in node.children.borrow().iter() {
+            collect(document, child, found);
+        }
+    }
+    let mut found = Vec::new();
+    collect(document, &document.root, &mut found);
+    found
+}
+
+/// Store a page and its subresources in the offline cache
+///
+/// `html` is the page as it was fetched from `url`, and `document` the
+/// page parsed from it.
+pub async fn pin_page(cache: &DiskCache, client: &HttpClient, url: &Url, html: &str, document: &Document) -> PinReport {
+    let mut report = PinReport::default();
+    let page = CachedResponse::new(
+        url.as_str(),
+        200,
+        vec![("content-type".to_string(), "text/html; charset=utf-8".to_string())],
+        html.as_bytes().to_vec(),
+    );
+    match cache.put(page) {
+        Ok(()) => report.stored += 1,
+        Err(e) => {
+            println!("❌ Failed to store {}: {}", url, e);
+            report.failed.push(url.to_string());
+            return report;
+        }
+    }
+    for subresource in subresources(document) {
+        let stored = match client.send_request(HttpRequest::get(subresource.url.clone())).await {
+            Ok(response) if response.status.is_success() => {
+                let mut cached = CachedResponse::from(&response);
+                cached.url = subresource.url.clone();
+                cache.put(cached).map_err(|e| e.to_string())
+            }
+            Ok(response) => Err(format!("HTTP {}", response.status_code)),
+            Err(e) => Err(e.to_string()),
+        };
+        match stored {
+            Ok(()) => report.stored += 1,
+            Err(e) => {
+                println!("⚠️  Could not pin {}: {}", subresource.url, e);
+                report.failed.push(subresource.url);
+            }
+        }
+    }
+    report
+}
+
+/// Get the text of the pinned stylesheets of a document, in document
+/// order
+pub fn pinned_stylesheets(cache: &DiskCache, document: &Document) -> String {
+    subresources(document)
+        .into_iter()
+        .filter(|subresource| subresource.is_stylesheet)
+        .filter_map(|subresource| cache.get(&subresource.url))
+        .map(|response| String::from_utf8_lossy(&response.body).into_owned())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subresources_and_pinned_stylesheets() {
+        let html = r#"<html><head><link rel="stylesheet" href="/site.css"><link rel="icon" href="/favicon.ico">
+            <script src="app.js"></script></head><body><img src="logo.png#x"><img src="logo.png"></body></html>"#;
+        let (document, _) = html_parser::parse_html_string(html).unwrap();
+        document.set_url("https://example.com/docs/page.html");
+
+        let found = subresources(&document);
+        let urls: Vec<&str> = found.iter().map(|subresource| subresource.url.as_str()).collect();
+        assert_eq!(urls, vec!["https://example.com/site.css", "https://example.com/docs/app.js", "https://example.com/docs/logo.png"]);
+
+        let cache = DiskCache::new();
+        cache.put(CachedResponse::new("https://example.com/site.css", 200, Vec::new(), b"p { color: red; }".to_vec())).unwrap();
+        assert_eq!(pinned_stylesheets(&cache, &document), "p { color: red; }");
+    }
+}
@@ -0,0 +1,329 @@
+//! # Password Manager
+//!
+//! This module keeps the passwords the user saves when signing in, per
+//! origin, in an encrypted file of the profile directory. The engine
+//! offers to save the credentials of a submitted login form and fills
+//! them back in when the origin's login page is visited again.
+//!
+//! ## Design Principles
+//!
+//! 1. **Encrypted at Rest**: Logins are sealed with ChaCha20-Poly1305
+//!    under a random key kept in its own file, readable only by the user.
+//!    A tampered or truncated file fails to open instead of yielding
+//!    garbage.
+//! 2. **Saved on Change**: Every change rewrites the file, through a
+//!    temporary file, so a crash never loses or corrupts saved logins.
+//! 3. **Shared by Tabs**: Clones of a store share its logins, so a
+//!    password saved in one tab is filled in every other.
+//! 4. **Secrets Stay Out of Logs**: `Debug` output never shows passwords
+//!    or the key.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+use dom::autofill::Credentials;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Name of the encrypted logins file in the profile directory
+pub const PASSWORDS_FILE: &str = "passwords.db";
+
+/// Name of the file holding the key of `PASSWORDS_FILE`
+pub const PASSWORDS_KEY_FILE: &str = "passwords.key";
+
+/// Length of the key, in bytes
+const KEY_LEN: usize = 32;
+
+/// Custom error types for the password store
+#[derive(Error, Debug)]
+pub enum PasswordError {
+    #[error("Password store I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Invalid password store: {0}")]
+    Format(#[from] serde_json::Error),
+
+    #[error("The password store could not be decrypted")]
+    Decrypt,
+
+    #[error("The password store key is invalid")]
+    InvalidKey,
+}
+
+/// Result type for password store operations
+pub type PasswordResult<T> = Result<T, PasswordError>;
+
+/// Callback asking the user whether to save the password of a username
+/// on an origin
+pub type PasswordSavePrompt = Arc<dyn Fn(&str, &str) -> bool + Send + Sync>;
+
+/// A saved login
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedLogin {
+    /// The serialized origin the login was saved on
+    pub origin: String,
+    pub username: String,
+    pub password: String,
+}
+
+impl SavedLogin {
+    pub fn credentials(&self) -> Credentials {
+        Credentials { username: self.username.clone(), password: self.password.clone() }
+    }
+}
+
+impl fmt::Debug for SavedLogin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SavedLogin")
+            .field("origin", &self.origin)
+            .field("username", &self.username)
+            .field("password", &"***")
+            .finish()
+    }
+}
+
+struct PasswordState {
+    key: [u8; KEY_LEN],
+    /// Logins, least recently saved first
+    logins: Vec<SavedLogin>,
+    /// File the logins are saved to, if the store is persistent
+    path: Option<PathBuf>,
+}
+
+/// The saved logins of a profile
+///
+/// Cloning shares the logins.
+#[derive(Clone)]
+pub struct PasswordStore {
+    state: Rc<RefCell<PasswordState>>,
+}
+
+impl Default for PasswordStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PasswordStore {
+    /// Create an empty store that is not saved anywhere
+    ///
+    /// # Panics
+    ///
+    /// Panics if the system's random number generator fails, rather than
+    /// sealing logins under a key anyone could guess.
+    pub fn new() -> Self {
+        let key = random_key().expect("The system random number generator failed to create a password store key");
+        let state = PasswordState { key, logins: Vec::new(), path: None };
+        PasswordStore { state: Rc::new(RefCell::new(state)) }
+    }
+
+    /// Open the logins saved in `dir`, creating an empty store and its key
+    /// if there are none yet
+    pub fn open(dir: impl AsRef<Path>) -> PasswordResult<Self> {
+        let dir = dir.as_ref();
+        let key = load_or_create_key(&dir.join(PASSWORDS_KEY_FILE))?;
+        let path = dir.join(PASSWORDS_FILE);
+        let logins = match fs::read(&path) {
+            Ok(sealed) => serde_json::from_slice(&open_sealed(&key, sealed)?)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let state = PasswordState { key, logins, path: Some(path) };
+        Ok(PasswordStore { state: Rc::new(RefCell::new(state)) })
+    }
+
+    /// Get the logins saved on an origin, least recently saved first
+    pub fn logins_for(&self, origin: &str) -> Vec<SavedLogin> {
+        self.state.borrow().logins.iter().filter(|login| login.origin == origin).cloned().collect()
+    }
+
+    /// Get the credentials saved last on an origin, which autofill uses
+    pub fn credentials_for(&self, origin: &str) -> Option<Credentials> {
+        self.state.borrow().logins.iter().rev().find(|login| login.origin == origin).map(SavedLogin::credentials)
+    }
+
+    /// Check whether exactly these credentials are saved on an origin
+    pub fn is_saved(&self, origin: &str, credentials: &Credentials) -> bool {
+        self.state
+            .borrow()
+            .logins
+            .iter()
+            .any(|login| login.origin == origin && login.username == credentials.username && login.password == credentials.password)
+    }
+
+    /// Save credentials on an origin, replacing the password of a login
+    /// with the same username
+    ///
+    /// Returns `false` if they were saved already.
+    pub fn save(&self, origin: &str, credentials: &Credentials) -> PasswordResult<bool> {
+        if self.is_saved(origin, credentials) {
+            return Ok(false);
+        }
+        let mut state = self.state.borrow_mut();
+        state.logins.retain(|login| login.origin != origin || login.username != credentials.username);
+        state.logins.push(SavedLogin {
+            origin: origin.to_string(),
+            username: credentials.username.clone(),
+            password: credentials.password.clone(),
+        });
+        state.save()?;
+        Ok(true)
+    }
+
+    /// Forget the login of a username on an origin
+    ///
+    /// Returns `false` if there was none.
+    pub fn remove(&self, origin: &str, username: &str) -> PasswordResult<bool> {
+        let mut state = self.state.borrow_mut();
+        let count = state.logins.len();
+        state.logins.retain(|login| login.origin != origin || login.username != username);
+        if state.logins.len() == count {
+            return Ok(false);
+        }
+        state.save()?;
+        Ok(true)
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.borrow().logins.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Debug for PasswordStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.borrow();
+        f.debug_struct("PasswordStore").field("logins", &state.logins.len()).field("path", &state.path).finish()
+    }
+}
+
+impl PasswordState {
+    fn save(&self) -> PasswordResult<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let sealed = seal(&self.key, serde_json::to_vec(&self.logins)?)?;
+        let temporary = path.with_extension("db.tmp");
+        // A temporary file left by an interrupted save is replaced
+        match fs::remove_file(&temporary) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        write_private(&temporary, &sealed)?;
+        fs::rename(&temporary, path)?;
+        Ok(())
+    }
+}
+
+fn random_key() -> PasswordResult<[u8; KEY_LEN]> {
+    let mut key = [0; KEY_LEN];
+    SystemRandom::new().fill(&mut key).map_err(|_| PasswordError::InvalidKey)?;
+    Ok(key)
+}
+
+/// Read the key file, or create it readable only by the user
+fn load_or_create_key(path: &Path) -> PasswordResult<[u8; KEY_LEN]> {
+    match fs::read(path) {
+        Ok(bytes) => bytes.try_into().map_err(|_| PasswordError::InvalidKey),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let key = random_key()?;
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            write_private(path, &key)?;
+            Ok(key)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Write a new file that is readable only by the user from the moment it
+/// is created
+fn write_private(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(data)
+}
+
+fn aead_key(key: &[u8; KEY_LEN]) -> PasswordResult<LessSafeKey> {
+    let key = UnboundKey::new(&CHACHA20_POLY1305, key).map_err(|_| PasswordError::InvalidKey)?;
+    Ok(LessSafeKey::new(key))
+}
+
+/// Encrypt data under a fresh nonce, which is written first
+fn seal(key: &[u8; KEY_LEN], mut data: Vec<u8>) -> PasswordResult<Vec<u8>> {
+    let mut nonce = [0; NONCE_LEN];
+    SystemRandom::new().fill(&mut nonce).map_err(|_| PasswordError::InvalidKey)?;
+    aead_key(key)?
+        .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut data)
+        .map_err(|_| PasswordError::InvalidKey)?;
+    Ok(nonce.iter().copied().chain(data).collect())
+}
+
+/// Decrypt data written by `seal`
+fn open_sealed(key: &[u8; KEY_LEN], mut sealed: Vec<u8>) -> PasswordResult<Vec<u8>> {
+    if sealed.len() < NONCE_LEN {
+        return Err(PasswordError::Decrypt);
+    }
+    let mut data = sealed.split_off(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(&sealed).map_err(|_| PasswordError::Decrypt)?;
+    let length = aead_key(key)?.open_in_place(nonce, Aad::empty(), &mut data).map_err(|_| PasswordError::Decrypt)?.len();
+    data.truncate(length);
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temporary_profile;
+
+    #[test]
+    fn test_passwords_are_encrypted_on_disk() {
+        let dir = temporary_profile("passwords");
+        let origin = "https://mail.example";
+        let ada = Credentials { username: "ada".to_string(), password: "correct horse".to_string() };
+
+        let store = PasswordStore::open(&dir).unwrap();
+        assert!(store.save(origin, &ada).unwrap());
+        assert!(!store.clone().save(origin, &ada).unwrap());
+        let changed = Credentials { password: "battery staple".to_string(), ..ada.clone() };
+        assert!(store.save(origin, &changed).unwrap());
+        assert_eq!(store.len(), 1);
+        assert!(!format!("{:?}", store.logins_for(origin)).contains("battery"));
+
+        let sealed = fs::read(dir.join(PASSWORDS_FILE)).unwrap();
+        assert!(!String::from_utf8_lossy(&sealed).contains("battery"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            for file in [PASSWORDS_KEY_FILE, PASSWORDS_FILE] {
+                assert_eq!(fs::metadata(dir.join(file)).unwrap().permissions().mode() & 0o777, 0o600);
+            }
+        }
+        let reopened = PasswordStore::open(&dir).unwrap();
+        assert_eq!(reopened.credentials_for(origin), Some(changed));
+        assert_eq!(reopened.credentials_for("https://other.example"), None);
+
+        // A tampered file does not open
+        let mut tampered = sealed;
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        fs::write(dir.join(PASSWORDS_FILE), tampered).unwrap();
+        assert!(matches!(PasswordStore::open(&dir), Err(PasswordError::Decrypt)));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
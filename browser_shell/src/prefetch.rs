@@ -0,0 +1,298 @@
+//! # Speculative Prefetching
+//!
+//! This module fetches documents the user is likely to open next before
+//! they ask for them: the targets of `<link rel=prefetch>` and
+//! `<link rel=preload>`, and optionally links the pointer rests on. A
+//! navigation to a prefetched URL is served from the prefetch cache, so
+//! clicking feels instant. `<link rel=preconnect>` only warms up the
+//! connection to an origin.
+//!
+//! ## Design Principles
+//!
+//! 1. **Low Priority**: Prefetches are queued while a page loads and only
+//!    run once the shell is idle, one at a time, so they never compete
+//!    with the page itself.
+//! 2. **Conservative by Default**: Only same-origin targets are fetched,
+//!    nothing is fetched on a metered connection, and hovering prefetches
+//!    nothing until the user turns it on.
+//! 3. **Used Once**: A prefetched response is served to one navigation
+//!    and expires after `PREFETCH_LIFETIME`, so a stale copy never hides
+//!    a newer page for long.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use dom::{Document, Node};
+use networking::disk_cache::CachedResponse;
+use networking::mixed_content::RequestDestination;
+use networking::{resolve_url, HttpClient, HttpMethod, HttpRequest, NetworkError};
+use url::Url;
+
+/// Environment variable forcing whether the connection is metered (`1`/`0`)
+pub const METERED_ENV: &str = "DUBBY_METERED_CONNECTION";
+
+/// How long a prefetched response may be used for a navigation
+pub const PREFETCH_LIFETIME: Duration = Duration::from_secs(5 * 60);
+
+/// What a resource hint asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrefetchKind {
+    /// `rel=prefetch` or a hovered link: a document of a likely navigation
+    Prefetch,
+    /// `rel=preload`: a resource the current page needs soon
+    Preload,
+    /// `rel=preconnect`: only the connection to the origin
+    Preconnect,
+}
+
+impl PrefetchKind {
+    /// Get the kind of a `rel` keyword, if it is a resource hint
+    pub fn from_rel(keyword: &str) -> Option<Self> {
+        match keyword.to_ascii_lowercase().as_str() {
+            "prefetch" => Some(PrefetchKind::Prefetch),
+            "preload" => Some(PrefetchKind::Preload),
+            "preconnect" => Some(PrefetchKind::Preconnect),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PrefetchKind::Prefetch => "prefetch",
+            PrefetchKind::Preload => "preload",
+            PrefetchKind::Preconnect => "preconnect",
+        }
+    }
+}
+
+/// A URL to fetch ahead of time
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrefetchHint {
+    pub url: Url,
+    pub kind: PrefetchKind,
+}
+
+/// Find the resource hints of a document, in document order and without
+/// duplicates
+///
+/// Only http(s) targets are returned.
+pub fn resource_hints(document: &Document) -> Vec<PrefetchHint> {
+    fn collect(document: &Document, node: &Rc<Node>, found: &mut Vec<PrefetchHint>) {
+        if node.tag_name() == Some("link") {
+            let rel = node.get_attribute("rel").unwrap_or_default();
+            let href = node.get_attribute("href").and_then(|href| resolve_url(document, &href));
+            if let Some(mut url) = href.filter(|url| matches!(url.scheme(), "http" | "https")) {
+                url.set_fragment(None);
+                for kind in rel.split_whitespace().filter_map(PrefetchKind::from_rel) {
+                    let hint = PrefetchHint { url: url.clone(), kind };
+                    if !found.contains(&hint) {
+                        found.push(hint);
+                    }
+                }
+            }
+        }
+        for child in node.children.borrow().iter() {
+            collect(document, child, found);
+        }
+    }
+    let mut found = Vec::new();
+    collect(document, &document.root, &mut found);
+    found
+}
+
+/// Get the target of the link `node` is in, if any
+pub fn link_target(document: &Document, node: &Rc<Node>) -> Option<Url> {
+    let mut current = Some(Rc::clone(node));
+    while let Some(candidate) = current {
+        if matches!(candidate.tag_name(), Some("a" | "area")) {
+            if let Some(href) = candidate.get_attribute("href") {
+                let mut url = resolve_url(document, &href).filter(|url| matches!(url.scheme(), "http" | "https"))?;
+                url.set_fragment(None);
+                return Some(url);
+            }
+        }
+        current = candidate.parent.borrow().upgrade();
+    }
+    None
+}
+
+/// Which hints are followed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrefetchPolicy {
+    /// Whether anything is prefetched at all
+    pub enabled: bool,
+    /// Whether links the pointer rests on are prefetched
+    pub hover: bool,
+    /// Whether targets on other origins than the page's are fetched
+    pub cross_origin: bool,
+    /// Whether prefetching continues on a metered connection
+    pub on_metered_connection: bool,
+}
+
+impl Default for PrefetchPolicy {
+    fn default() -> Self {
+        PrefetchPolicy {
+            enabled: true,
+            hover: false,
+            cross_origin: false,
+            on_metered_connection: false,
+        }
+    }
+}
+
+impl PrefetchPolicy {
+    /// Check whether a page at `page` may fetch `target` ahead of time
+    pub fn allows(&self, page: &Url, target: &Url, metered: bool) -> bool {
+        self.enabled
+            && (self.on_metered_connection || !metered)
+            && (self.cross_origin || page.origin() == target.origin())
+    }
+}
+
+/// Check whether the network connection is metered
+///
+/// `DUBBY_METERED_CONNECTION` takes precedence, then NetworkManager's
+/// guess. Connections are assumed not to be metered when neither answers.
+pub fn detect_metered_connection() -> bool {
+    if let Some(metered) = std::env::var(METERED_ENV).ok().and_then(|value| crate::appearance::parse_flag(&value)) {
+        return metered;
+    }
+    let output = std::process::Command::new("busctl")
+        .args(["get-property", "org.freedesktop.NetworkManager", "/org/freedesktop/NetworkManager", "org.freedesktop.NetworkManager", "Metered"])
+        .output();
+    match output {
+        // NM_METERED_YES and NM_METERED_GUESS_YES
+        Ok(output) if output.status.success() => matches!(String::from_utf8_lossy(&output.stdout).trim(), "u 1" | "u 3"),
+        _ => false,
+    }
+}
+
+/// A response fetched ahead of time
+#[derive(Debug, Clone)]
+struct PrefetchedResponse {
+    response: CachedResponse,
+    fetched_at: Instant,
+}
+
+/// Responses fetched ahead of time, keyed by URL
+///
+/// Clones share the same entries, so a prefetch started in one tab
+/// serves a navigation in another.
+#[derive(Debug, Clone, Default)]
+pub struct PrefetchCache {
+    entries: Rc<RefCell<HashMap<String, PrefetchedResponse>>>,
+}
+
+impl PrefetchCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store a response fetched at `now`
+    pub fn insert(&self, response: CachedResponse, now: Instant) {
+        let url = response.url.clone();
+        self.entries.borrow_mut().insert(url, PrefetchedResponse { response, fetched_at: now });
+    }
+
+    /// Check whether a fresh response for `url` is stored
+    pub fn contains(&self, url: &str, now: Instant) -> bool {
+        self.entries
+            .borrow()
+            .get(url)
+            .is_some_and(|entry| now.duration_since(entry.fetched_at) < PREFETCH_LIFETIME)
+    }
+
+    /// Take the response for `url` out of the cache, if it is still fresh
+    pub fn take(&self, url: &str, now: Instant) -> Option<CachedResponse> {
+        let entry = self.entries.borrow_mut().remove(url)?;
+        (now.duration_since(entry.fetched_at) < PREFETCH_LIFETIME).then_some(entry.response)
+    }
+
+    /// Get the URLs of the stored responses, sorted
+    pub fn urls(&self) -> Vec<String> {
+        let mut urls: Vec<String> = self.entries.borrow().keys().cloned().collect();
+        urls.sort();
+        urls
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.borrow().is_empty()
+    }
+
+    /// Forget every prefetched response
+    pub fn clear(&self) {
+        self.entries.borrow_mut().clear();
+    }
+}
+
+/// Fetch a hint into `cache`
+///
+/// Preconnects send a `HEAD` request to the origin, which leaves an open
+/// connection in the client's pool and stores nothing. Other hints are
+/// requested with `Sec-Purpose: prefetch`, so servers can tell them from
+/// navigations.
+pub async fn prefetch(client: &HttpClient, cache: &PrefetchCache, hint: &PrefetchHint) -> Result<(), NetworkError> {
+    if hint.kind == PrefetchKind::Preconnect {
+        let origin = hint.url.origin().ascii_serialization();
+        client.send_request(HttpRequest::new(HttpMethod::HEAD, origin)).await?;
+        return Ok(());
+    }
+    let mut request = HttpRequest::get(hint.url.to_string());
+    request.set_header("Sec-Purpose".to_string(), "prefetch".to_string());
+    if hint.kind == PrefetchKind::Prefetch {
+        request.set_destination(RequestDestination::Document);
+    }
+    let response = client.send_request(request).await?;
+    if !response.status.is_success() {
+        return Err(NetworkError::HttpError { status: response.status_code, message: "prefetch failed".to_string() });
+    }
+    let mut cached = CachedResponse::from(&response);
+    cached.url = hint.url.to_string();
+    cache.insert(cached, Instant::now());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hints_policy_and_cache() {
+        let html = r#"<html><head><link rel="prefetch" href="/next.html"><link rel="preload stylesheet" href="site.css#x">
+            <link rel="preconnect" href="https://cdn.example"><link rel="prefetch" href="/next.html"><link rel="icon" href="/favicon.ico">
+            </head><body><a id="more" href="/more.html#top"><span id="label">More</span></a></body></html>"#;
+        let (document, _) = html_parser::parse_html_string(html).unwrap();
+        document.set_url("https://example.com/docs/page.html");
+
+        let hints = resource_hints(&document);
+        let found: Vec<(&str, &str)> = hints.iter().map(|hint| (hint.kind.as_str(), hint.url.as_str())).collect();
+        assert_eq!(found, vec![
+            ("prefetch", "https://example.com/next.html"),
+            ("preload", "https://example.com/docs/site.css"),
+            ("preconnect", "https://cdn.example/"),
+        ]);
+        let label = document.root.query_selector("#label").unwrap().unwrap();
+        assert_eq!(link_target(&document, &label).unwrap().as_str(), "https://example.com/more.html");
+
+        let page = Url::parse("https://example.com/docs/page.html").unwrap();
+        let policy = PrefetchPolicy::default();
+        assert!(policy.allows(&page, &hints[0].url, false));
+        assert!(!policy.allows(&page, &hints[2].url, false));
+        assert!(!policy.allows(&page, &hints[0].url, true));
+        let permissive = PrefetchPolicy { cross_origin: true, on_metered_connection: true, ..policy };
+        assert!(permissive.allows(&page, &hints[2].url, true));
+
+        let cache = PrefetchCache::new();
+        let now = Instant::now();
+        cache.insert(CachedResponse::new("https://example.com/next.html", 200, Vec::new(), b"<p>Next</p>".to_vec()), now);
+        assert!(cache.contains("https://example.com/next.html", now));
+        assert!(!cache.contains("https://example.com/next.html", now + PREFETCH_LIFETIME));
+        assert_eq!(cache.clone().take("https://example.com/next.html", now).unwrap().body, b"<p>Next</p>");
+        assert!(cache.take("https://example.com/next.html", now).is_none());
+    }
+}
@@ -0,0 +1,213 @@
+//! # Printing
+//!
+//! This module prints the current page to PDF. The page is laid out for
+//! `print` media on the page boxes its `@page` rules give, cut into pages
+//! at the breaks `break-before`, `break-after`, `break-inside`, `widows`
+//! and `orphans` allow (see `layout::paged`), and each page is written as
+//! vector backgrounds and text.
+//!
+//! ## Design Principles
+//!
+//! 1. **Layout Decides the Pages**: Page sizes, margins and breaks all
+//!    come from `LayoutEngine::layout_pages`; this module only paints the
+//!    slice of the document each page shows into its page area.
+//! 2. **Whole Lines**: A line of text belongs to the page its top is on,
+//!    and backgrounds are clipped to the slice, so nothing is painted
+//!    twice across a page break.
+//! 3. **Self-Contained Output**: Text uses the standard Helvetica font, so
+//!    the PDF embeds no fonts and opens in any viewer.
+
+use layout::paged::{Page, PagedLayout};
+use layout::{LayoutBox, Visibility};
+use renderer_wgpu::filters::parse_color;
+use thiserror::Error;
+
+/// PDF points per CSS pixel
+const POINTS_PER_PIXEL: f32 = 0.75;
+
+/// Custom error types for printing
+#[derive(Error, Debug)]
+pub enum PrintError {
+    #[error("No document to print")]
+    NoDocument,
+}
+
+/// Result type for printing operations
+pub type PrintResult<T> = Result<T, PrintError>;
+
+/// Write a document laid out on pages as a PDF file
+pub fn write_pdf(paged: &PagedLayout) -> Vec<u8> {
+    // Objects 1 to 3 are the catalog, the page tree and the font; each
+    // page is followed by its content stream
+    let page_ids: Vec<usize> = (0..paged.pages.len()).map(|index| 4 + index * 2).collect();
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            page_ids.iter().map(|id| format!("{} 0 R", id)).collect::<Vec<_>>().join(" "),
+            page_ids.len()
+        ),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>".to_string(),
+    ];
+    for (page, id) in paged.pages.iter().zip(&page_ids) {
+        let content = page_content(&paged.layout, page);
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+            number(page.style.width * POINTS_PER_PIXEL),
+            number(page.style.height * POINTS_PER_PIXEL),
+            id + 1
+        ));
+        objects.push(format!("<< /Length {} >>\nstream\n{}\nendstream", content.len(), content));
+    }
+
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend(format!("{} 0 obj\n{}\nendobj\n", index + 1, object).bytes());
+    }
+    let xref = pdf.len();
+    pdf.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).bytes());
+    for offset in offsets {
+        pdf.extend(format!("{:010} 00000 n \n", offset).bytes());
+    }
+    pdf.extend(format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref).bytes());
+    pdf
+}
+
+/// Paint the slice of the document a page shows, as a PDF content stream
+fn page_content(layout: &LayoutBox, page: &Page) -> String {
+    let mut content = String::new();
+    paint_box(layout, 0.0, 0.0, page, &mut content);
+    content
+}
+
+/// Paint a box whose parent's content box is at (`parent_x`, `parent_y`)
+/// in document coordinates, and its descendants
+///
+/// Content boxes are offset from their parent's, as in
+/// `layout::geometry::border_boxes`.
+fn paint_box(layout_box: &LayoutBox, parent_x: f32, parent_y: f32, page: &Page, content: &mut String) {
+    let x = parent_x + layout_box.content.x;
+    let y = parent_y + layout_box.content.y;
+    let styles = &layout_box.styles;
+    let visible = styles.visibility.unwrap_or_default() == Visibility::Visible;
+    let (offset_x, offset_y) = page.offset();
+    let page_height = page.style.height;
+
+    if let Some(color) = styles.background_color.as_deref().filter(|_| visible).and_then(parse_color).filter(|color| color[3] > 0.0) {
+        let width = layout_box.border.width.max(layout_box.content.width);
+        let height = layout_box.border.height.max(layout_box.content.height);
+        let top = y.max(page.top);
+        let bottom = (y + height).min(page.bottom);
+        if bottom > top && width > 0.0 {
+            content.push_str(&format!(
+                "{} rg {} {} {} {} re f\n",
+                rgb(color),
+                number((x + offset_x) * POINTS_PER_PIXEL),
+                number((page_height - (bottom + offset_y)) * POINTS_PER_PIXEL),
+                number(width * POINTS_PER_PIXEL),
+                number((bottom - top) * POINTS_PER_PIXEL)
+            ));
+        }
+    }
+
+    for fragment in layout_box.fragments.iter().filter(|_| visible) {
+        let top = y + fragment.y;
+        if top < page.top || top >= page.bottom {
+            continue;
+        }
+        let font_size = fragment.styles.font.size.or(styles.font.size).unwrap_or(16.0);
+        let color = fragment.styles.color.as_deref().or(styles.color.as_deref()).and_then(parse_color).unwrap_or([0.0, 0.0, 0.0, 1.0]);
+        // The baseline sits about four fifths of the font size down
+        let baseline = top + offset_y + font_size * 0.8;
+        content.push_str(&format!(
+            "BT {} rg /F1 {} Tf {} {} Td ({}) Tj ET\n",
+            rgb(color),
+            number(font_size * POINTS_PER_PIXEL),
+            number((x + fragment.x + offset_x) * POINTS_PER_PIXEL),
+            number((page_height - baseline) * POINTS_PER_PIXEL),
+            escape_text(&fragment.text)
+        ));
+    }
+
+    for child in &layout_box.children {
+        paint_box(child, x, y, page, content);
+    }
+}
+
+/// Format a number for PDF, which has no exponents
+fn number(value: f32) -> String {
+    let text = format!("{:.2}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    match text {
+        "-0" | "" => "0".to_string(),
+        text => text.to_string(),
+    }
+}
+
+/// Format a color as PDF RGB components
+fn rgb(color: [f32; 4]) -> String {
+    format!("{} {} {}", number(color[0]), number(color[1]), number(color[2]))
+}
+
+/// Escape text for a PDF string in the Latin-1 range of WinAnsi, writing
+/// other characters as `?`
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '(' | ')' | '\\' => {
+                escaped.push('\\');
+                escaped.push(ch);
+            }
+            ' '..='~' => escaped.push(ch),
+            '\u{a0}'..='\u{ff}' => escaped.push_str(&format!("\\{:03o}", ch as u32)),
+            _ => escaped.push('?'),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use css_parser::CSSParser;
+    use dom::Document;
+    use layout::LayoutEngine;
+
+    #[test]
+    fn test_pages_are_written_as_pdf() {
+        let document = Document::new();
+        let body = document.create_element("body");
+        document.root.append_child(&body);
+        for (tag, text) in [("h1", "Report (draft)"), ("p", "Introduction"), ("h2", "Appendix")] {
+            let element = document.create_element(tag);
+            element.append_child(&document.create_text_node(text));
+            body.append_child(&element);
+        }
+        let css = "@page { size: 400px 300px; margin: 20px } h1 { background-color: #ff0000 } h2 { break-before: page }";
+        let mut engine = LayoutEngine::new(CSSParser::new(css.to_string()).parse_stylesheet().unwrap());
+        let paged = engine.layout_pages(&document);
+        assert!(paged.pages.len() >= 2);
+
+        let pdf = String::from_utf8(write_pdf(&paged)).unwrap();
+        assert!(pdf.starts_with("%PDF-1.4\n") && pdf.ends_with("%%EOF\n"));
+        assert_eq!(pdf.matches("/Type /Page ").count(), paged.pages.len());
+        assert!(pdf.contains("/MediaBox [0 0 300 225]"));
+        // Text is escaped, and each heading is on its own page
+        let first = pdf.find("(Report \\(draft\\)) Tj").unwrap();
+        let appendix = pdf.find("(Appendix) Tj").unwrap();
+        let second_page = pdf.match_indices("/Type /Page ").nth(1).unwrap().0;
+        assert!(first < second_page && appendix > second_page);
+        assert!(pdf.contains("1 0 0 rg 15 "));
+
+        // The cross-reference table points at each object
+        let xref: usize = pdf.lines().rev().nth(1).unwrap().parse().unwrap();
+        assert!(pdf[xref..].starts_with("xref"));
+        for (index, entry) in pdf[xref..].lines().skip(3).take_while(|line| line.ends_with(" n ")).enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(pdf[offset..].starts_with(&format!("{} 0 obj", index + 1)));
+        }
+    }
+}
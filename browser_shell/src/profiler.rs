@@ -0,0 +1,442 @@
+//! # Frame Profiler
+//!
+//! This module records where each frame of a page spends its time: the
+//! script tasks it ran, then style, layout, paint and composite. Recorded
+//! frames render as a flame chart in the shell and the devtools console,
+//! and export to the trace event format read by `chrome://tracing` and
+//! Perfetto, so jank on a page can be pinned to a phase or a script.
+//!
+//! ## Design Principles
+//!
+//! 1. **Bounded Buffer**: Events go into a ring buffer of fixed capacity,
+//!    so a long recording keeps the latest frames instead of growing
+//!    without limit.
+//! 2. **Nested Spans**: Phases are spans opened and closed around the work
+//!    they time; spans opened inside another are drawn below it, as in any
+//!    flame chart.
+//! 3. **Scripts With Stacks**: Script tasks are reported by the event loop
+//!    after they ran, with the task kind and the start of the script as
+//!    their stack, so a slow frame names the script that made it slow.
+//! 4. **Real Pipeline**: Frames are produced by the same style, layout,
+//!    paint and raster cache code the shell renders with, paced like vsync
+//!    so timers come due between frames.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use dom::{Document, Node, NodeType};
+use js_integration::JsEngine;
+use layout::{LayoutBox, LayoutEngine, StyleMatcher};
+use renderer_wgpu::memory::MemoryCoordinator;
+use renderer_wgpu::raster_cache::{stacking_contexts, RasterCache};
+use serde_json::{json, Value};
+
+/// Events kept by a profiler unless another capacity is given
+pub const DEFAULT_CAPACITY: usize = 10_000;
+
+/// Time a frame has at 60 frames per second
+pub const FRAME_BUDGET: Duration = Duration::from_micros(16_667);
+
+/// Longest start of a script shown in its stack
+const SCRIPT_SNIPPET_CHARS: usize = 40;
+
+/// Part of the frame pipeline an event belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Phase {
+    /// A whole frame
+    Frame,
+    /// Script tasks, callbacks and evaluation
+    Script,
+    /// Computing the styles of every element
+    Style,
+    Layout,
+    /// Building the display lists of the stacking contexts
+    Paint,
+    /// Rasterizing and compositing the stacking contexts
+    Composite,
+}
+
+impl Phase {
+    /// Phases inside a frame, in pipeline order
+    pub const PIPELINE: [Phase; 5] = [Phase::Script, Phase::Style, Phase::Layout, Phase::Paint, Phase::Composite];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Frame => "frame",
+            Phase::Script => "script",
+            Phase::Style => "style",
+            Phase::Layout => "layout",
+            Phase::Paint => "paint",
+            Phase::Composite => "composite",
+        }
+    }
+
+    /// Character the phase's bars are drawn with in the flame chart
+    fn glyph(&self) -> char {
+        match self {
+            Phase::Frame => '=',
+            Phase::Script => 'j',
+            Phase::Style => 's',
+            Phase::Layout => 'l',
+            Phase::Paint => 'p',
+            Phase::Composite => 'c',
+        }
+    }
+}
+
+/// A timed span of work
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileEvent {
+    pub name: String,
+    pub phase: Phase,
+    /// Number of the frame the event belongs to, starting at 1
+    pub frame: u64,
+    /// Start, in microseconds since the profiler was created
+    pub start_us: f64,
+    pub duration_us: f64,
+    /// Number of spans open around the event
+    pub depth: usize,
+    /// Outermost first, e.g. the task kind and the start of its script
+    pub stack: Vec<String>,
+}
+
+impl ProfileEvent {
+    pub fn end_us(&self) -> f64 {
+        self.start_us + self.duration_us
+    }
+}
+
+/// Time a frame spent in each phase
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameProfile {
+    pub frame: u64,
+    pub duration_us: f64,
+    /// Time in each phase of `Phase::PIPELINE`, in microseconds
+    pub phases: Vec<(Phase, f64)>,
+    /// Name of the longest script event, if scripts ran
+    pub slowest_script: Option<String>,
+}
+
+impl FrameProfile {
+    /// Whether the frame took longer than `FRAME_BUDGET`
+    pub fn is_jank(&self) -> bool {
+        self.duration_us > FRAME_BUDGET.as_secs_f64() * 1e6
+    }
+
+    /// The phase the frame spent the most time in
+    pub fn dominant_phase(&self) -> Option<(Phase, f64)> {
+        self.phases.iter().copied().filter(|(_, time)| *time > 0.0).max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+}
+
+/// A span that has been opened but not closed
+#[derive(Debug)]
+struct OpenSpan {
+    name: String,
+    phase: Phase,
+    start: Instant,
+}
+
+/// A ring buffer of timed frame events
+#[derive(Debug)]
+pub struct Profiler {
+    events: VecDeque<ProfileEvent>,
+    capacity: usize,
+    epoch: Instant,
+    frame: u64,
+    open: Vec<OpenSpan>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl Profiler {
+    pub fn new(capacity: usize) -> Self {
+        Profiler { events: VecDeque::new(), capacity: capacity.max(1), epoch: Instant::now(), frame: 0, open: Vec::new() }
+    }
+
+    /// Open the span of the next frame, returning its number
+    pub fn begin_frame(&mut self) -> u64 {
+        self.frame += 1;
+        self.begin(Phase::Frame, &format!("Frame {}", self.frame));
+        self.frame
+    }
+
+    /// Open a span, nested in the spans already open
+    pub fn begin(&mut self, phase: Phase, name: &str) {
+        self.open.push(OpenSpan { name: name.to_string(), phase, start: Instant::now() });
+    }
+
+    /// Close the innermost open span
+    pub fn end(&mut self) {
+        if let Some(span) = self.open.pop() {
+            let duration = span.start.elapsed();
+            self.record(span.phase, &span.name, span.start, duration, Vec::new());
+        }
+    }
+
+    /// Time `work` as a span
+    pub fn time<R>(&mut self, phase: Phase, name: &str, work: impl FnOnce() -> R) -> R {
+        self.begin(phase, name);
+        let result = work();
+        self.end();
+        result
+    }
+
+    /// Record work that already ran, nested in the spans open now
+    pub fn record(&mut self, phase: Phase, name: &str, start: Instant, duration: Duration, stack: Vec<String>) {
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back(ProfileEvent {
+            name: name.to_string(),
+            phase,
+            frame: self.frame,
+            start_us: start.saturating_duration_since(self.epoch).as_secs_f64() * 1e6,
+            duration_us: duration.as_secs_f64() * 1e6,
+            depth: self.open.len(),
+            stack,
+        });
+    }
+
+    /// Recorded events, in the order they ended
+    pub fn events(&self) -> impl Iterator<Item = &ProfileEvent> {
+        self.events.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    /// Summarize the recorded frames, oldest first
+    pub fn frames(&self) -> Vec<FrameProfile> {
+        let mut frames = Vec::new();
+        for frame in self.events.iter().filter(|event| event.phase == Phase::Frame) {
+            let children: Vec<&ProfileEvent> = self.events
+                .iter()
+                .filter(|event| event.frame == frame.frame && event.depth == frame.depth + 1)
+                .collect();
+            let phases = Phase::PIPELINE
+                .iter()
+                .map(|phase| (*phase, children.iter().filter(|event| event.phase == *phase).map(|event| event.duration_us).sum()))
+                .collect();
+            let slowest_script = children
+                .iter()
+                .filter(|event| event.phase == Phase::Script)
+                .max_by(|a, b| a.duration_us.total_cmp(&b.duration_us))
+                .map(|event| event.name.clone());
+            frames.push(FrameProfile { frame: frame.frame, duration_us: frame.duration_us, phases, slowest_script });
+        }
+        frames
+    }
+
+    /// One line per frame with its phase times, flagging frames over
+    /// budget with the phase to blame
+    pub fn summary(&self) -> String {
+        let mut text = String::new();
+        for frame in self.frames() {
+            let _ = write!(text, "  Frame {:<4} {:>8.2} ms ", frame.frame, frame.duration_us / 1000.0);
+            for (phase, time) in &frame.phases {
+                let _ = write!(text, " {} {:.2}", phase.name(), time / 1000.0);
+            }
+            if frame.is_jank() {
+                if let Some((phase, time)) = frame.dominant_phase() {
+                    let _ = write!(text, "  ⚠️ over budget: {} {:.2} ms", phase.name(), time / 1000.0);
+                    if let (Phase::Script, Some(script)) = (phase, &frame.slowest_script) {
+                        let _ = write!(text, " in {}", script);
+                    }
+                }
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Draw the recorded events as a flame chart `width` columns wide,
+    /// one row per nesting depth
+    pub fn flame_chart(&self, width: usize) -> String {
+        let width = width.max(10);
+        let Some(start) = self.events.iter().map(|event| event.start_us).min_by(f64::total_cmp) else {
+            return "  (no frames recorded)\n".to_string();
+        };
+        let end = self.events.iter().map(ProfileEvent::end_us).max_by(f64::total_cmp).unwrap_or(start);
+        let scale = width as f64 / (end - start).max(1.0);
+        let depth = self.events.iter().map(|event| event.depth).max().unwrap_or(0);
+
+        let mut text = format!("  0 ms{:>1$}\n", format!("{:.2} ms", (end - start) / 1000.0), width - 2);
+        for row in 0..=depth {
+            let mut cells = vec![' '; width];
+            for event in self.events.iter().filter(|event| event.depth == row) {
+                let first = (((event.start_us - start) * scale) as usize).min(width - 1);
+                let last = (((event.end_us() - start) * scale).ceil() as usize).clamp(first + 1, width);
+                cells[first..last].fill(event.phase.glyph());
+                // Label the bar when the name fits inside it
+                let label = event.name.chars().count();
+                if label + 2 <= last - first {
+                    for (cell, c) in cells[first + 1..].iter_mut().zip(event.name.chars()) {
+                        *cell = c;
+                    }
+                }
+            }
+            let _ = writeln!(text, "  {}", cells.into_iter().collect::<String>());
+        }
+        let legend: Vec<String> = Phase::PIPELINE.iter().map(|phase| format!("{} {}", phase.glyph(), phase.name())).collect();
+        let _ = writeln!(text, "  {}", legend.join("  "));
+        text
+    }
+
+    /// Serialize the events in the trace event format, as complete events
+    pub fn to_trace_json(&self) -> Value {
+        let mut events: Vec<&ProfileEvent> = self.events.iter().collect();
+        events.sort_by(|a, b| a.start_us.total_cmp(&b.start_us).then(a.depth.cmp(&b.depth)));
+        let mut trace = vec![json!({ "name": "thread_name", "ph": "M", "pid": 1, "tid": 1, "args": { "name": "main" } })];
+        trace.extend(events.into_iter().map(|event| json!({
+            "name": event.name,
+            "cat": event.phase.name(),
+            "ph": "X",
+            "ts": event.start_us,
+            "dur": event.duration_us,
+            "pid": 1,
+            "tid": 1,
+            "args": { "frame": event.frame, "stack": event.stack },
+        })));
+        json!({ "traceEvents": trace, "displayTimeUnit": "ms" })
+    }
+}
+
+/// Compute the styles of every element under `node`, returning how many
+/// were styled
+pub fn recalculate_style(matcher: &StyleMatcher, node: &Rc<Node>) -> usize {
+    let mut styled = 0;
+    if matches!(node.node_type, NodeType::Element { .. }) {
+        matcher.compute_styles(node);
+        styled += 1;
+    }
+    for child in node.children.borrow().iter() {
+        styled += recalculate_style(matcher, child);
+    }
+    styled
+}
+
+/// Name and stack of a script that ran in a task
+fn script_stack(kind: &str, script: &str) -> (String, Vec<String>) {
+    let line = script.trim().lines().next().unwrap_or("").trim();
+    let mut snippet: String = line.chars().take(SCRIPT_SNIPPET_CHARS).collect();
+    if snippet.len() < line.len() {
+        snippet.push('…');
+    }
+    if snippet.is_empty() {
+        snippet = "(native)".to_string();
+    }
+    (format!("{}: {}", kind, snippet), vec![kind.to_string(), snippet])
+}
+
+/// Run `frames` frames of a page and record them
+///
+/// The first frame also evaluates the page's inline scripts. Each frame
+/// runs one iteration of the script's event loop with a render due, then
+/// styles, lays out, paints and composites the document, and sleeps out the
+/// rest of `FRAME_BUDGET`. Returns the layout of the last frame.
+pub fn record_frames(
+    document: &Document,
+    layout_engine: &LayoutEngine,
+    script: &mut JsEngine,
+    frames: usize,
+    evaluate_scripts: bool,
+    profiler: &mut Profiler,
+) -> Option<LayoutBox> {
+    let timings = Rc::new(RefCell::new(Vec::new()));
+    let observed = Rc::clone(&timings);
+    script.set_task_observer(Box::new(move |timing| observed.borrow_mut().push(timing)));
+    let mut cache = RasterCache::new(MemoryCoordinator::default());
+    let mut last_layout = None;
+
+    for index in 0..frames {
+        let frame_start = Instant::now();
+        profiler.begin_frame();
+        if index == 0 && evaluate_scripts {
+            if let Err(e) = profiler.time(Phase::Script, "Evaluate scripts", || script.execute_inline_scripts()) {
+                println!("⚠️ Page scripts failed: {}", e);
+            }
+        }
+        script.event_loop().request_render();
+        if let Err(e) = script.process_event_loop() {
+            println!("⚠️ Event loop task failed: {}", e);
+        }
+        for timing in timings.borrow_mut().drain(..) {
+            let (name, stack) = script_stack(timing.kind, &timing.script);
+            profiler.record(Phase::Script, &name, timing.start, timing.duration, stack);
+        }
+
+        profiler.time(Phase::Style, "Recalculate style", || recalculate_style(layout_engine.style_matcher(), &document.root));
+        let layout = profiler.time(Phase::Layout, "Layout", || layout_engine.layout_document(document));
+        let contexts = profiler.time(Phase::Paint, "Paint", || stacking_contexts(&layout));
+        profiler.time(Phase::Composite, "Composite", || cache.paint_frame(&contexts, 1.0));
+        profiler.end();
+        last_layout = Some(layout);
+
+        if let Some(rest) = FRAME_BUDGET.checked_sub(frame_start.elapsed()) {
+            std::thread::sleep(rest);
+        }
+    }
+    last_layout
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BrowserEngine;
+    use js_integration::event_loop::TaskSource;
+
+    #[test]
+    fn test_profile_attributes_frames_to_phases_and_scripts() {
+        let mut engine = BrowserEngine::new();
+        assert!(engine.load_html("<html><body><p id=\"out\">0</p><script>\
+            var frames = 0;\
+            function tick() { frames++; document.getElementById('out').textContent = String(frames); }\
+            </script></body></html>"));
+        assert!(engine.load_css("p { color: red; will-change: transform }"));
+        let mut profiler = Profiler::default();
+        assert!(engine.profile_frames(2, &mut profiler));
+        assert_eq!(profiler.frames().iter().map(|frame| frame.frame).collect::<Vec<_>>(), vec![1, 2]);
+
+        let mut profiler = Profiler::default();
+        let mut script = engine.create_script_engine().unwrap();
+        script.queue_task(TaskSource::Timer, "tick()");
+        script.request_animation_frame("tick()");
+        let layout_engine = LayoutEngine::new(engine.current_stylesheet.clone().unwrap());
+        let document = engine.get_document().unwrap().clone();
+        assert!(record_frames(&document, &layout_engine, &mut script, 3, true, &mut profiler).is_some());
+
+        let frames = profiler.frames();
+        assert_eq!(frames.len(), 3);
+        assert!(frames.iter().all(|frame| frame.phases.iter().any(|(phase, time)| *phase == Phase::Layout && *time > 0.0)));
+        assert_eq!(frames[0].slowest_script.as_deref().map(|name| name.starts_with("task: tick()") || name == "Evaluate scripts"), Some(true));
+        let ticks: Vec<&ProfileEvent> = profiler.events().filter(|event| event.name == "task: tick()").collect();
+        assert_eq!(ticks.len(), 2);
+        assert_eq!(ticks[0].stack, vec!["task".to_string(), "tick()".to_string()]);
+        assert_eq!(ticks[0].depth, 1);
+        assert_eq!(script.execute("frames").unwrap().as_number(), Some(2.0));
+
+        let trace = profiler.to_trace_json();
+        let events = trace["traceEvents"].as_array().unwrap();
+        assert_eq!(events[0]["ph"], "M");
+        assert_eq!(events[1]["name"], "Frame 1");
+        assert!(events.iter().any(|event| event["cat"] == "composite" && event["ph"] == "X"));
+        let chart = profiler.flame_chart(200);
+        assert_eq!(chart.lines().count(), 4);
+        assert!(chart.contains("Frame 1"));
+        assert_eq!(profiler.summary().lines().count(), 3);
+
+        let mut small = Profiler::new(2);
+        small.time(Phase::Style, "a", || ());
+        small.time(Phase::Layout, "b", || ());
+        small.time(Phase::Paint, "c", || ());
+        assert_eq!(small.events().map(|event| event.name.as_str()).collect::<Vec<_>>(), vec!["b", "c"]);
+    }
+}
@@ -0,0 +1,119 @@
+//! # Screenshots
+//!
+//! This module captures the current page as PNG: the viewport, a region of
+//! the page, the border box of the element matching a selector, or the
+//! whole page down to its full scroll height. It serves the `--screenshot`
+//! command line mode and the remote debugging protocol, and is meant for
+//! visual tests of single components.
+//!
+//! ## Design Principles
+//!
+//! 1. **Layout Query API**: Elements are found with `query_selector` and
+//!    their border boxes are read from `layout::geometry`, the same
+//!    geometry resize observers see.
+//! 2. **Page State Untouched**: A capture never scrolls the page, enters or
+//!    leaves fullscreen, or runs script; full pages are rendered offscreen
+//!    and stitched, so taking a screenshot cannot change what it shows.
+//! 3. **Explicit Failures**: An invalid selector, a selector that matches
+//!    nothing and an element without a box are errors, never a blank image.
+
+use dom::selectors::SelectorError;
+use layout::{Dimensions, LayoutBox};
+use renderer_wgpu::filters::Layer;
+use renderer_wgpu::snapshot::{render_element, render_full_page, render_page};
+use thiserror::Error;
+
+/// Size of the viewport screenshots are taken at
+pub const DEFAULT_VIEWPORT: (f32, f32) = (800.0, 600.0);
+
+/// Custom error types for screenshots
+#[derive(Error, Debug)]
+pub enum ScreenshotError {
+    #[error("Page has no layout to capture")]
+    NoLayout,
+
+    #[error("Invalid capture selector: {0}")]
+    Selector(#[from] SelectorError),
+
+    #[error("No element matches '{0}'")]
+    NoMatch(String),
+
+    #[error("Element '{0}' has no box to capture")]
+    NoBox(String),
+
+    #[error("Capture area must have a positive size and scale")]
+    EmptyArea,
+}
+
+/// Result type for screenshot operations
+pub type ScreenshotResult<T> = Result<T, ScreenshotError>;
+
+/// The part of the page a screenshot shows
+#[derive(Debug, Clone, PartialEq)]
+pub enum CaptureArea {
+    /// The viewport at the current scroll offset
+    Viewport,
+    /// The whole page, as wide as the viewport
+    FullPage,
+    /// A region of the page, in CSS pixels
+    Region(Dimensions),
+    /// The border box of the first element matching a selector
+    Selector(String),
+}
+
+/// Capture an area of a laid-out page at a zoom level and device scale
+/// factor
+pub fn capture(layout: &LayoutBox, area: &CaptureArea, scroll_offset: (f32, f32), zoom: f32, scale: f32) -> ScreenshotResult<Layer> {
+    if scale <= 0.0 || zoom <= 0.0 {
+        return Err(ScreenshotError::EmptyArea);
+    }
+    // A zoomed page shows fewer CSS pixels, each painted larger
+    let (width, height) = (DEFAULT_VIEWPORT.0 / zoom, DEFAULT_VIEWPORT.1 / zoom);
+    let scale = scale * zoom;
+    match area {
+        CaptureArea::Viewport => {
+            let viewport = Dimensions::new(scroll_offset.0, scroll_offset.1, width, height);
+            Ok(render_page(layout, &viewport, scale))
+        }
+        CaptureArea::FullPage => Ok(render_full_page(layout, width, height, scale)),
+        CaptureArea::Region(region) if region.width <= 0.0 || region.height <= 0.0 => Err(ScreenshotError::EmptyArea),
+        CaptureArea::Region(region) => Ok(render_page(layout, region, scale)),
+        CaptureArea::Selector(selector) => {
+            let element = layout.node.query_selector(selector)?.ok_or_else(|| ScreenshotError::NoMatch(selector.clone()))?;
+            render_element(layout, element.id, scale).ok_or_else(|| ScreenshotError::NoBox(selector.clone()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BrowserEngine;
+    use renderer_wgpu::snapshot::encode_png;
+
+    #[test]
+    fn test_capture_areas() {
+        let mut engine = BrowserEngine::new();
+        engine.load_html("<html><body><div id='main'></div><p class='hidden' style='display: none'>x</p></body></html>");
+        engine.load_css("body { margin: 0 }\ndiv { height: 40px; background-color: #00ff00 }");
+
+        let element = engine.capture_screenshot(&CaptureArea::Selector("#main".to_string()), 1.0).unwrap();
+        assert_eq!(element.height, 40);
+        assert_eq!(element.pixel(0, 0), [0.0, 1.0, 0.0, 1.0]);
+        assert_eq!(&encode_png(&element)[..8], b"\x89PNG\r\n\x1a\n");
+
+        let viewport = engine.capture_screenshot(&CaptureArea::Viewport, 1.0).unwrap();
+        assert_eq!((viewport.width, viewport.height), (800, 600));
+        let region = engine.capture_screenshot(&CaptureArea::Region(Dimensions::new(0.0, 20.0, 10.0, 40.0)), 2.0).unwrap();
+        assert_eq!((region.width, region.height), (20, 80));
+        assert_eq!(region.pixel(0, 0), [0.0, 1.0, 0.0, 1.0]);
+        assert_eq!(region.pixel(0, 79), [1.0, 1.0, 1.0, 1.0]);
+
+        // Failures are reported, not captured as blank images
+        assert!(matches!(engine.capture_screenshot(&CaptureArea::Selector("#missing".to_string()), 1.0), Err(ScreenshotError::NoMatch(_))));
+        assert!(matches!(engine.capture_screenshot(&CaptureArea::Selector("#".to_string()), 1.0), Err(ScreenshotError::Selector(_))));
+        assert!(matches!(engine.capture_screenshot(&CaptureArea::Selector(".hidden".to_string()), 1.0), Err(ScreenshotError::NoBox(_))));
+        assert!(matches!(engine.capture_screenshot(&CaptureArea::Region(Dimensions::new(0.0, 0.0, 0.0, 5.0)), 1.0), Err(ScreenshotError::EmptyArea)));
+        assert_eq!(engine.scroll_offset(), (0.0, 0.0));
+    }
+}
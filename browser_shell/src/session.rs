@@ -0,0 +1,343 @@
+//! # Session Persistence
+//!
+//! This module saves the shell's session (open tabs, their history, scroll
+//! offsets and unsubmitted form values) to disk and restores it at startup.
+//!
+//! ## Design Principles
+//!
+//! 1. **Plain Snapshot**: A `Session` is plain data captured from the tabs,
+//!    so it can be saved without holding on to documents.
+//! 2. **Atomic Writes**: The session file is written to a temporary file and
+//!    renamed, so a crash while saving never leaves a truncated session.
+//! 3. **Crash Detection**: A lock file marks a running shell. If it is still
+//!    there at startup, the previous run exited abnormally and restoring is
+//!    offered even when the preference is not to restore.
+//! 4. **User Choice**: Restoring is controlled by `RestorePreference`; the
+//!    shell never restores silently unless asked to.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use dom::forms::{form_controls, FormControlState};
+use dom::Node;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Name of the session file in the profile directory
+pub const SESSION_FILE: &str = "session.json";
+
+/// Name of the file marking a running shell
+pub const LOCK_FILE: &str = "session.lock";
+
+/// How often a running shell saves its session by default
+pub const DEFAULT_SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Custom error types for session persistence
+#[derive(Error, Debug)]
+pub enum SessionError {
+    #[error("Session I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Invalid session file: {0}")]
+    Format(#[from] serde_json::Error),
+}
+
+/// Result type for session operations
+pub type SessionResult<T> = Result<T, SessionError>;
+
+/// The state of a form control the user changed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedFormControl {
+    /// Position among the document's form controls, in tree order
+    pub index: usize,
+    /// The control's `name`, checked on restore so edits to the page do
+    /// not put values into the wrong control
+    pub name: Option<String>,
+    pub value: Option<String>,
+    pub checked: Option<bool>,
+    pub selected_index: Option<i32>,
+}
+
+/// The saved state of one tab
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TabState {
+    /// URL of the current document, `None` for documents not loaded from
+    /// the network
+    pub url: Option<String>,
+    /// URLs visited in this tab, oldest first
+    pub history: Vec<String>,
+    /// Position of the current entry in `history`
+    pub history_index: usize,
+    pub scroll_x: f32,
+    pub scroll_y: f32,
+    /// Form controls with unsubmitted changes
+    pub form_controls: Vec<SavedFormControl>,
+}
+
+/// The saved state of the whole shell
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub tabs: Vec<TabState>,
+    /// Index of the tab that was in front
+    pub active_tab: usize,
+    /// When the session was saved, in seconds since the Unix epoch
+    pub saved_at: u64,
+}
+
+/// Whether the previous session is restored at startup
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RestorePreference {
+    /// Start with a fresh session, offering to restore only after a crash
+    #[default]
+    Never,
+    /// Always restore the previous session
+    Always,
+}
+
+/// What the shell should do with the previous session at startup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartupAction {
+    StartFresh,
+    Restore,
+    /// Ask the user, because the previous run crashed
+    OfferRestore,
+}
+
+/// Decide what to do with the previous session at startup
+pub fn startup_action(preference: RestorePreference, crashed: bool, has_session: bool) -> StartupAction {
+    if !has_session {
+        return StartupAction::StartFresh;
+    }
+    match (preference, crashed) {
+        (RestorePreference::Always, _) => StartupAction::Restore,
+        (RestorePreference::Never, true) => StartupAction::OfferRestore,
+        (RestorePreference::Never, false) => StartupAction::StartFresh,
+    }
+}
+
+/// Capture the form controls of a document the user changed
+pub fn capture_form_controls(root: &Rc<Node>) -> Vec<SavedFormControl> {
+    form_controls(root)
+        .iter()
+        .enumerate()
+        .filter_map(|(index, control)| {
+            let state = control.form_state();
+            if state == FormControlState::default() {
+                return None;
+            }
+            Some(SavedFormControl {
+                index,
+                name: control.get_attribute("name"),
+                value: state.value,
+                checked: state.checked,
+                selected_index: state.selected_index,
+            })
+        })
+        .collect()
+}
+
+/// Restore saved form controls into a document
+///
+/// Returns the number of controls restored. Controls whose position or name
+/// no longer match are skipped.
+pub fn restore_form_controls(root: &Rc<Node>, saved: &[SavedFormControl]) -> usize {
+    let controls = form_controls(root);
+    let mut restored = 0;
+    for saved_control in saved {
+        let Some(control) = controls.get(saved_control.index) else {
+            continue;
+        };
+        if control.get_attribute("name") != saved_control.name {
+            continue;
+        }
+        control.set_form_state(FormControlState {
+            value: saved_control.value.clone(),
+            checked: saved_control.checked,
+            selected_index: saved_control.selected_index,
+            selection: None,
+        });
+        restored += 1;
+    }
+    restored
+}
+
+/// Session storage in a profile directory
+#[derive(Debug)]
+pub struct SessionStore {
+    dir: PathBuf,
+    save_interval: Duration,
+    last_save: Option<Instant>,
+    previous_run_crashed: bool,
+}
+
+impl SessionStore {
+    /// Open the store in `dir` and mark the shell as running
+    ///
+    /// Records whether the previous run left its lock file behind, i.e.
+    /// exited without calling `close`.
+    pub fn open(dir: impl Into<PathBuf>) -> SessionResult<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let lock = dir.join(LOCK_FILE);
+        let previous_run_crashed = lock.exists();
+        fs::write(&lock, std::process::id().to_string())?;
+        Ok(SessionStore {
+            dir,
+            save_interval: DEFAULT_SAVE_INTERVAL,
+            last_save: None,
+            previous_run_crashed,
+        })
+    }
+
+    /// Get the default profile directory
+    ///
+    /// This is `$DUBBY_PROFILE_DIR` if set, otherwise `.dubby` in the home
+    /// directory.
+    pub fn default_dir() -> PathBuf {
+        if let Some(dir) = std::env::var_os("DUBBY_PROFILE_DIR") {
+            return PathBuf::from(dir);
+        }
+        let home = std::env::var_os("HOME").map(PathBuf::from).unwrap_or_else(|| PathBuf::from("."));
+        home.join(".dubby")
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Whether the previous run exited abnormally
+    pub fn previous_run_crashed(&self) -> bool {
+        self.previous_run_crashed
+    }
+
+    /// Set how often `save_if_due` saves
+    pub fn set_save_interval(&mut self, interval: Duration) {
+        self.save_interval = interval;
+    }
+
+    /// Load the saved session, if there is one
+    pub fn load(&self) -> SessionResult<Option<Session>> {
+        match fs::read_to_string(self.dir.join(SESSION_FILE)) {
+            Ok(contents) => Ok(Some(serde_json::from_str(&contents)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Save a session, replacing the previous one
+    pub fn save(&mut self, session: &Session) -> SessionResult<()> {
+        let mut session = session.clone();
+        session.saved_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+        let contents = serde_json::to_string_pretty(&session)?;
+
+        let temporary = self.dir.join(format!("{}.tmp", SESSION_FILE));
+        fs::write(&temporary, contents)?;
+        fs::rename(&temporary, self.dir.join(SESSION_FILE))?;
+        self.last_save = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Save a session if the save interval has passed since the last save
+    ///
+    /// Returns whether the session was saved.
+    pub fn save_if_due(&mut self, session: &Session, now: Instant) -> SessionResult<bool> {
+        let due = self
+            .last_save
+            .is_none_or(|last_save| now.saturating_duration_since(last_save) >= self.save_interval);
+        if due {
+            self.save(session)?;
+        }
+        Ok(due)
+    }
+
+    /// Delete the saved session
+    pub fn clear(&mut self) -> SessionResult<()> {
+        match fs::remove_file(self.dir.join(SESSION_FILE)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Mark a clean exit by removing the lock file
+    pub fn close(self) -> SessionResult<()> {
+        match fs::remove_file(self.dir.join(LOCK_FILE)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temporary_profile;
+
+    #[test]
+    fn test_session_round_trip_and_crash_detection() {
+        let dir = temporary_profile("session-round-trip");
+        let session = Session {
+            tabs: vec![TabState {
+                url: Some("https://example.com/b".to_string()),
+                history: vec!["https://example.com/a".to_string(), "https://example.com/b".to_string()],
+                history_index: 1,
+                scroll_x: 0.0,
+                scroll_y: 240.0,
+                form_controls: vec![],
+            }],
+            active_tab: 0,
+            saved_at: 0,
+        };
+
+        let mut store = SessionStore::open(&dir).unwrap();
+        assert!(!store.previous_run_crashed());
+        assert_eq!(store.load().unwrap(), None);
+        store.save(&session).unwrap();
+        assert!(!store.save_if_due(&session, Instant::now()).unwrap());
+        // Exit without closing, as a crash would
+        drop(store);
+
+        let store = SessionStore::open(&dir).unwrap();
+        assert!(store.previous_run_crashed());
+        let loaded = store.load().unwrap().unwrap();
+        assert_eq!(loaded.tabs, session.tabs);
+        assert!(loaded.saved_at > 0);
+        assert_eq!(startup_action(RestorePreference::Never, true, true), StartupAction::OfferRestore);
+        store.close().unwrap();
+
+        let store = SessionStore::open(&dir).unwrap();
+        assert!(!store.previous_run_crashed());
+        assert_eq!(startup_action(RestorePreference::Never, false, true), StartupAction::StartFresh);
+        assert_eq!(startup_action(RestorePreference::Always, false, true), StartupAction::Restore);
+        assert_eq!(startup_action(RestorePreference::Always, false, false), StartupAction::StartFresh);
+        store.close().unwrap();
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_form_controls_round_trip() {
+        let html = b"<html><body><form><input name=\"q\"><input type=\"checkbox\" name=\"remember\"><textarea name=\"note\"></textarea></form></body></html>";
+        let document = html_parser::parse_html(html.to_vec()).unwrap().0;
+        let controls = form_controls(&document.root);
+        controls[0].set_value("draft query");
+        controls[1].set_checked(true);
+
+        let saved = capture_form_controls(&document.root);
+        assert_eq!(saved.len(), 2);
+        assert_eq!(saved[0].name.as_deref(), Some("q"));
+
+        let fresh = html_parser::parse_html(html.to_vec()).unwrap().0;
+        assert_eq!(restore_form_controls(&fresh.root, &saved), 2);
+        let controls = form_controls(&fresh.root);
+        assert_eq!(controls[0].value(), "draft query");
+        assert!(controls[1].checked());
+
+        // A control that was renamed is left alone
+        let changed = html_parser::parse_html(b"<html><body><input name=\"search\"></body></html>".to_vec()).unwrap().0;
+        assert_eq!(restore_form_controls(&changed.root, &saved), 0);
+    }
+}
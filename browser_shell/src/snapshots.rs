@@ -0,0 +1,513 @@
+//! # Page Snapshots
+//!
+//! This module implements `browser_shell snapshot`, which loads real pages,
+//! from the network or from a HAR archive, and stores what every stage of
+//! the engine made of them as golden files: the DOM tree from the parser,
+//! the layout tree from the cascade and layout, and the display list from
+//! the painter. Later runs compare the same pages against the stored files
+//! and report each difference as a line diff, so a change in any crate
+//! that alters what a real page turns into shows up in review.
+//!
+//! ## Design Principles
+//!
+//! 1. **Text, One Fact per Line**: Each stage is dumped as indented text
+//!    with one node, box or paint command per line, so snapshots diff well
+//!    in a terminal and in version control.
+//! 2. **Stable Across Runs**: Pages are loaded in deterministic mode, with
+//!    the bundled test font, attributes are sorted, whitespace-only text is
+//!    left out and geometry is rounded to hundredths of a pixel, so two
+//!    runs of an unchanged engine produce the same files byte for byte on
+//!    any machine.
+//! 3. **Updates Are Explicit**: A missing snapshot is written on the first
+//!    run, but a changed one is only overwritten when asked to, so a
+//!    regression cannot quietly become the new golden file.
+//! 4. **Offline Replays**: With a HAR archive the page and its resources are
+//!    served from the archive, so snapshots do not change with the live
+//!    site.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use dom::{Node, NodeType};
+use layout::LayoutBox;
+use renderer::display_list::DisplayList;
+use thiserror::Error;
+use crate::BrowserEngine;
+use crate::deterministic::DeterministicMode;
+
+/// Directory snapshots are stored in unless another is given
+pub const DEFAULT_SNAPSHOT_DIR: &str = "snapshots";
+
+/// Lines of unchanged context shown around each difference
+pub const DIFF_CONTEXT: usize = 2;
+
+/// Most lines of a diff shown for one stage; the rest are counted
+pub const MAX_DIFF_LINES: usize = 200;
+
+/// Largest product of changed lines diffed line by line; larger changes
+/// are shown as a removal of the old lines and an addition of the new
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// Custom error types for snapshots
+#[derive(Error, Debug)]
+pub enum SnapshotError {
+    #[error("Failed to load {0}")]
+    Load(String),
+
+    #[error("Page has no layout to snapshot")]
+    NoLayout,
+
+    #[error("Snapshot I/O failed: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Result type for snapshot operations
+pub type SnapshotResult<T> = Result<T, SnapshotError>;
+
+/// A stage of the engine a snapshot records
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SnapshotKind {
+    /// The DOM tree built by the parser
+    Dom,
+    /// The layout tree, with the geometry of every box
+    Layout,
+    /// The display list painted from the layout tree
+    DisplayList,
+}
+
+impl SnapshotKind {
+    pub const ALL: [SnapshotKind; 3] = [SnapshotKind::Dom, SnapshotKind::Layout, SnapshotKind::DisplayList];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SnapshotKind::Dom => "dom",
+            SnapshotKind::Layout => "layout",
+            SnapshotKind::DisplayList => "display-list",
+        }
+    }
+
+    /// Get the path of the snapshot of a page named `page` in `directory`
+    pub fn path(&self, directory: &Path, page: &str) -> PathBuf {
+        directory.join(format!("{}.{}.txt", page, self.name()))
+    }
+}
+
+/// The dumps of every stage of a loaded page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageSnapshot {
+    pub dom: String,
+    pub layout: String,
+    pub display_list: String,
+}
+
+impl PageSnapshot {
+    /// Dump a document and its layout tree
+    pub fn new(root: &Node, layout: &LayoutBox) -> Self {
+        PageSnapshot {
+            dom: dump_dom(root),
+            layout: dump_layout(layout),
+            display_list: dump_display_list(&DisplayList::from_layout(layout)),
+        }
+    }
+
+    /// Get the dump of a stage
+    pub fn get(&self, kind: SnapshotKind) -> &str {
+        match kind {
+            SnapshotKind::Dom => &self.dom,
+            SnapshotKind::Layout => &self.layout,
+            SnapshotKind::DisplayList => &self.display_list,
+        }
+    }
+}
+
+/// Dump a DOM tree, one node per line, indented by depth
+pub fn dump_dom(root: &Node) -> String {
+    let mut dump = String::new();
+    dump_node(root, 0, &mut dump);
+    dump
+}
+
+fn dump_node(node: &Node, depth: usize, dump: &mut String) {
+    let indent = "  ".repeat(depth);
+    match &node.node_type {
+        NodeType::Document => dump.push_str("#document\n"),
+        NodeType::Text(_) => {
+            let text = node.character_data().unwrap_or_default();
+            if !text.trim().is_empty() {
+                let _ = writeln!(dump, "{}{:?}", indent, text.trim());
+            }
+            return;
+        }
+        NodeType::Element { tag_name, .. } => {
+            let _ = write!(dump, "{}<{}", indent, tag_name);
+            for (attribute, value) in node.attributes_ns() {
+                let _ = write!(dump, " {}={:?}", attribute.qualified_name(), value);
+            }
+            dump.push_str(">\n");
+        }
+    }
+    for child in node.children.borrow().iter() {
+        dump_node(child, depth + 1, dump);
+    }
+}
+
+/// Dump a layout tree, one box per line with its border box, and one line
+/// per text fragment below its box
+pub fn dump_layout(root: &LayoutBox) -> String {
+    let mut dump = String::new();
+    dump_box(root, 0, &mut dump);
+    dump
+}
+
+fn dump_box(layout_box: &LayoutBox, depth: usize, dump: &mut String) {
+    let indent = "  ".repeat(depth);
+    let border = &layout_box.border;
+    let _ = writeln!(
+        dump,
+        "{}{} {:?} at ({}, {}) size {}x{}",
+        indent,
+        box_label(&layout_box.node),
+        layout_box.styles.display,
+        round(border.x),
+        round(border.y),
+        round(border.width),
+        round(border.height)
+    );
+    for fragment in &layout_box.fragments {
+        let _ = writeln!(
+            dump,
+            "{}  text {:?} at ({}, {}) size {}x{}",
+            indent,
+            fragment.text,
+            round(fragment.x),
+            round(fragment.y),
+            round(fragment.width),
+            round(fragment.height)
+        );
+    }
+    for child in &layout_box.children {
+        dump_box(child, depth + 1, dump);
+    }
+}
+
+/// Name a box by its node: the tag with its id and classes, like a selector
+fn box_label(node: &Node) -> String {
+    match &node.node_type {
+        NodeType::Document => "#document".to_string(),
+        NodeType::Text(_) => "#text".to_string(),
+        NodeType::Element { tag_name, .. } => {
+            let mut label = tag_name.clone();
+            if let Some(id) = node.get_attribute("id") {
+                label.push('#');
+                label.push_str(&id);
+            }
+            for class in node.get_attribute("class").unwrap_or_default().split_whitespace() {
+                label.push('.');
+                label.push_str(class);
+            }
+            label
+        }
+    }
+}
+
+/// Dump a display list, one paint command per line as JSON
+pub fn dump_display_list(list: &DisplayList) -> String {
+    let mut dump = String::new();
+    for item in &list.items {
+        let _ = writeln!(dump, "{}", serde_json::to_string(item).unwrap_or_default());
+    }
+    dump
+}
+
+/// Round a length to hundredths of a pixel for a stable dump
+fn round(value: f32) -> f32 {
+    let rounded = (value * 100.0).round() / 100.0;
+    // Avoid a "-0" that differs from "0" only in sign
+    if rounded == 0.0 {
+        0.0
+    } else {
+        rounded
+    }
+}
+
+/// Get the name the snapshots of a page are stored under: its host and
+/// path with every other character replaced
+pub fn snapshot_name(url: &str) -> String {
+    let trimmed = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let mut name: String = trimmed
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+        .collect();
+    while name.ends_with('_') {
+        name.pop();
+    }
+    if name.is_empty() {
+        "page".to_string()
+    } else {
+        name
+    }
+}
+
+/// What a run made of the snapshot of one stage
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotOutcome {
+    /// There was no snapshot, and one was written
+    Created,
+    /// The page still matches the snapshot
+    Matched,
+    /// The page differed, and the snapshot was overwritten as asked
+    Updated,
+    /// The page differs from the snapshot; the diff is readable text
+    Changed(String),
+}
+
+/// The outcomes of comparing one page against its snapshots
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotReport {
+    /// Name the snapshots are stored under
+    pub page: String,
+    pub outcomes: Vec<(SnapshotKind, SnapshotOutcome)>,
+}
+
+impl SnapshotReport {
+    /// Check whether no stage changed
+    pub fn passed(&self) -> bool {
+        !self.outcomes.iter().any(|(_, outcome)| matches!(outcome, SnapshotOutcome::Changed(_)))
+    }
+
+    /// Format the outcomes, one stage per line, each followed by its diff
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for (kind, outcome) in &self.outcomes {
+            let status = match outcome {
+                SnapshotOutcome::Created => "✨ created",
+                SnapshotOutcome::Matched => "✅ matches",
+                SnapshotOutcome::Updated => "📝 updated",
+                SnapshotOutcome::Changed(_) => "❌ changed",
+            };
+            let _ = writeln!(text, "{} {}: {}", self.page, kind.name(), status);
+            if let SnapshotOutcome::Changed(diff) = outcome {
+                text.push_str(diff);
+            }
+        }
+        text
+    }
+}
+
+/// Compare a page against its snapshots in `directory`, writing the
+/// missing ones, and the changed ones if `update` is set
+pub fn check_snapshot(directory: &Path, page: &str, snapshot: &PageSnapshot, update: bool) -> SnapshotResult<SnapshotReport> {
+    fs::create_dir_all(directory)?;
+    let mut outcomes = Vec::new();
+    for kind in SnapshotKind::ALL {
+        let path = kind.path(directory, page);
+        let actual = snapshot.get(kind);
+        let outcome = match fs::read_to_string(&path) {
+            Ok(expected) if expected == actual => SnapshotOutcome::Matched,
+            Ok(_) if update => {
+                fs::write(&path, actual)?;
+                SnapshotOutcome::Updated
+            }
+            Ok(expected) => SnapshotOutcome::Changed(diff_lines(&expected, actual)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                fs::write(&path, actual)?;
+                SnapshotOutcome::Created
+            }
+            Err(e) => return Err(e.into()),
+        };
+        outcomes.push((kind, outcome));
+    }
+    Ok(SnapshotReport { page: page.to_string(), outcomes })
+}
+
+/// Load `url` into a fresh engine, optionally served by an interceptor such
+/// as a HAR replay, and compare it against its snapshots
+pub async fn snapshot_url(
+    url: &str,
+    interceptor: Option<std::sync::Arc<dyn networking::RequestInterceptor>>,
+    directory: &Path,
+    update: bool,
+) -> SnapshotResult<SnapshotReport> {
+    let mut engine = BrowserEngine::new();
+    engine.set_deterministic_mode(Some(DeterministicMode::default()));
+    if let Some(interceptor) = interceptor {
+        engine.set_request_interceptor(interceptor);
+    }
+    if !engine.fetch_url(url).await {
+        return Err(SnapshotError::Load(url.to_string()));
+    }
+    let snapshot = engine.capture_snapshot();
+    // The clock goes back to the system one for whatever runs next
+    engine.set_deterministic_mode(None);
+    check_snapshot(directory, &snapshot_name(url), &snapshot?, update)
+}
+
+/// A line of a diff
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DiffLine<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Format the difference between two dumps as hunks of removed (`-`) and
+/// added (`+`) lines with a little unchanged context, like `diff -u`
+pub fn diff_lines(expected: &str, actual: &str) -> String {
+    let old: Vec<&str> = expected.lines().collect();
+    let new: Vec<&str> = actual.lines().collect();
+    let lines = diff(&old, &new);
+
+    // Mark the unchanged lines close enough to a change to be shown
+    let mut shown = vec![false; lines.len()];
+    for (index, line) in lines.iter().enumerate() {
+        if !matches!(line, DiffLine::Same(_)) {
+            let start = index.saturating_sub(DIFF_CONTEXT);
+            let end = (index + DIFF_CONTEXT + 1).min(lines.len());
+            shown[start..end].iter_mut().for_each(|shown| *shown = true);
+        }
+    }
+
+    let mut text = String::new();
+    let mut written = 0;
+    let (mut old_line, mut new_line) = (1, 1);
+    let mut in_hunk = false;
+    for (index, line) in lines.iter().enumerate() {
+        if shown[index] {
+            if !in_hunk {
+                let _ = writeln!(text, "@@ line {} (was {}) @@", new_line, old_line);
+                in_hunk = true;
+            }
+            if written < MAX_DIFF_LINES {
+                let _ = match line {
+                    DiffLine::Same(line) => writeln!(text, "  {}", line),
+                    DiffLine::Removed(line) => writeln!(text, "- {}", line),
+                    DiffLine::Added(line) => writeln!(text, "+ {}", line),
+                };
+            }
+            written += 1;
+        } else {
+            in_hunk = false;
+        }
+        match line {
+            DiffLine::Same(_) => {
+                old_line += 1;
+                new_line += 1;
+            }
+            DiffLine::Removed(_) => old_line += 1,
+            DiffLine::Added(_) => new_line += 1,
+        }
+    }
+    if written > MAX_DIFF_LINES {
+        let _ = writeln!(text, "… {} more lines", written - MAX_DIFF_LINES);
+    }
+    text
+}
+
+/// Diff two lists of lines by their longest common subsequence
+///
+/// The common prefix and suffix are split off first, so a small change in
+/// a large dump is cheap.
+fn diff<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<DiffLine<'a>> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let (old_middle, new_middle) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+
+    let mut lines: Vec<DiffLine> = old[..prefix].iter().map(|line| DiffLine::Same(line)).collect();
+    let (n, m) = (old_middle.len(), new_middle.len());
+    if n * m > MAX_DIFF_CELLS {
+        lines.extend(old_middle.iter().map(|line| DiffLine::Removed(line)));
+        lines.extend(new_middle.iter().map(|line| DiffLine::Added(line)));
+    } else {
+        // common[i][j] is the length of the longest common subsequence of
+        // old_middle[i..] and new_middle[j..]
+        let mut common = vec![vec![0u32; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                common[i][j] = if old_middle[i] == new_middle[j] { common[i + 1][j + 1] + 1 } else { common[i + 1][j].max(common[i][j + 1]) };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && old_middle[i] == new_middle[j] {
+                lines.push(DiffLine::Same(old_middle[i]));
+                i += 1;
+                j += 1;
+            } else if i < n && (j == m || common[i + 1][j] >= common[i][j + 1]) {
+                lines.push(DiffLine::Removed(old_middle[i]));
+                i += 1;
+            } else {
+                lines.push(DiffLine::Added(new_middle[j]));
+                j += 1;
+            }
+        }
+    }
+    lines.extend(old[old.len() - suffix..].iter().map(|line| DiffLine::Same(line)));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temporary_profile;
+    use networking::har::HarReplay;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_snapshots_are_written_then_diffed() {
+        let directory = temporary_profile("snapshots");
+        let mut engine = BrowserEngine::new();
+        assert!(engine.load_html("<html><body><h1 id=\"title\">Hello</h1>\n  <p class=\"lead intro\">World</p></body></html>"));
+        let snapshot = engine.capture_snapshot().unwrap();
+        assert!(snapshot.dom.contains("      <h1 id=\"title\">\n        \"Hello\"\n"));
+        assert!(snapshot.layout.contains("      p.lead.intro Block at ("));
+        assert!(snapshot.layout.contains("text \"World\" at ("));
+        assert!(snapshot.display_list.contains("\"text\":\"World\""));
+        assert_eq!(snapshot_name("https://example.com/docs/?page=2"), "example.com_docs__page_2");
+
+        // The first run writes the snapshots, and an unchanged page matches
+        let report = check_snapshot(&directory, "page", &snapshot, false).unwrap();
+        assert!(report.outcomes.iter().all(|(_, outcome)| *outcome == SnapshotOutcome::Created));
+        assert!(check_snapshot(&directory, "page", &engine.capture_snapshot().unwrap(), false).unwrap().passed());
+
+        // A changed page fails with a diff of what changed in each stage
+        assert!(engine.load_html("<html><body><h1 id=\"title\">Hello</h1>\n  <p class=\"lead intro\">Everyone</p></body></html>"));
+        let changed = engine.capture_snapshot().unwrap();
+        let report = check_snapshot(&directory, "page", &changed, false).unwrap();
+        assert!(!report.passed());
+        let text = report.to_text();
+        assert!(text.contains("page dom: ❌ changed"));
+        assert!(text.contains("-         \"World\"\n+         \"Everyone\"\n"));
+        assert!(text.contains("        <p class=\"lead intro\">\n-"));
+
+        // Updating accepts the change
+        let report = check_snapshot(&directory, "page", &changed, true).unwrap();
+        assert!(report.outcomes.iter().any(|(_, outcome)| *outcome == SnapshotOutcome::Updated));
+        assert!(check_snapshot(&directory, "page", &changed, false).unwrap().passed());
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    #[tokio::test]
+    async fn test_recorded_page_matches_its_snapshots() {
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join(DEFAULT_SNAPSHOT_DIR);
+        let replay = HarReplay::open(directory.join("example.com.har")).unwrap();
+        let url = replay.first_url().unwrap().to_string();
+        let mut engine = BrowserEngine::new();
+        engine.set_deterministic_mode(Some(DeterministicMode::default()));
+        engine.set_request_interceptor(Arc::new(replay));
+        assert!(engine.fetch_url(&url).await);
+
+        // A missing snapshot would be created rather than fail, so every
+        // checked in one must be found and match
+        let report = check_snapshot(&directory, &snapshot_name(&url), &engine.capture_snapshot().unwrap(), false).unwrap();
+        assert!(report.outcomes.iter().all(|(_, outcome)| *outcome == SnapshotOutcome::Matched), "{}", report.to_text());
+    }
+
+    #[test]
+    fn test_diff_lines_shows_hunks_with_context() {
+        let expected = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let actual = "a\nb\nc\nD\ne\nf\ng\nh\ni\nj\nk\n";
+        assert_eq!(diff_lines(expected, actual), "@@ line 2 (was 2) @@\n  b\n  c\n- d\n+ D\n  e\n  f\n@@ line 9 (was 9) @@\n  i\n  j\n+ k\n");
+        assert_eq!(diff_lines(expected, expected), "");
+    }
+}
@@ -0,0 +1,61 @@
+//! # System Clipboard
+//!
+//! This module connects the shell's clipboard to the platform clipboard
+//! through arboard, so `navigator.clipboard` and the copy, cut and paste
+//! shortcuts exchange text with other applications.
+//!
+//! ## Design Principles
+//!
+//! 1. **Opened on First Use**: The platform clipboard is opened when it is
+//!    first read or written, so engines that never touch it, and headless
+//!    runs without a display, pay nothing.
+//! 2. **Failures Are Errors**: When the platform clipboard cannot be
+//!    opened, reads and writes fail with `ClipboardError::Unavailable`;
+//!    nothing falls back to a private clipboard that other applications
+//!    cannot see. Tests install `MemoryClipboard` explicitly.
+
+use dom::clipboard::{Clipboard, ClipboardBackend, ClipboardError};
+
+/// The platform clipboard, reached through arboard
+#[derive(Default)]
+pub struct SystemClipboard {
+    clipboard: Option<arboard::Clipboard>,
+}
+
+impl SystemClipboard {
+    /// Create a handle that opens the platform clipboard on first use
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the platform clipboard, opening it if needed
+    fn open(&mut self) -> Result<&mut arboard::Clipboard, ClipboardError> {
+        if self.clipboard.is_none() {
+            self.clipboard = Some(arboard::Clipboard::new().map_err(unavailable)?);
+        }
+        Ok(self.clipboard.as_mut().expect("the clipboard was just opened"))
+    }
+}
+
+impl ClipboardBackend for SystemClipboard {
+    fn read_text(&mut self) -> Result<String, ClipboardError> {
+        match self.open()?.get_text() {
+            Ok(text) => Ok(text),
+            Err(arboard::Error::ContentNotAvailable) => Err(ClipboardError::Empty),
+            Err(error) => Err(unavailable(error)),
+        }
+    }
+
+    fn write_text(&mut self, text: &str) -> Result<(), ClipboardError> {
+        self.open()?.set_text(text).map_err(unavailable)
+    }
+}
+
+/// Create the clipboard the shell uses by default, the platform one
+pub fn system_clipboard() -> Clipboard {
+    Clipboard::new(Box::new(SystemClipboard::new()))
+}
+
+fn unavailable(error: arboard::Error) -> ClipboardError {
+    ClipboardError::Unavailable(error.to_string())
+}
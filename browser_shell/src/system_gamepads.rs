@@ -0,0 +1,120 @@
+//! # System Gamepads
+//!
+//! This module reads the gamepads connected to the machine through gilrs
+//! and reports them to the Gamepad API, with buttons and axes in the order
+//! of the standard gamepad layout.
+//!
+//! ## Design Principles
+//!
+//! 1. **Opened on First Poll**: gilrs is started when the backend is first
+//!    polled by the window's event loop, so engines that never open a
+//!    window, and headless runs, pay nothing.
+//! 2. **Standard Layout**: gilrs maps known devices, through SDL mappings
+//!    or the driver, to one layout that matches the standard gamepad, so
+//!    buttons and axes are read in the standard order. Devices without a
+//!    mapping report the same slots without `standard_mapping`.
+//! 3. **No Devices Without gilrs**: When gilrs cannot start, polls report
+//!    no gamepads rather than failing every frame.
+
+use dom::gamepad::{GamepadBackend, GamepadButton, GamepadSnapshot};
+use gilrs::{Axis, Button, Gilrs, MappingSource};
+
+/// The buttons of the standard gamepad, in order
+const STANDARD_BUTTONS: [Button; 17] = [
+    Button::South,
+    Button::East,
+    Button::West,
+    Button::North,
+    Button::LeftTrigger,
+    Button::RightTrigger,
+    Button::LeftTrigger2,
+    Button::RightTrigger2,
+    Button::Select,
+    Button::Start,
+    Button::LeftThumb,
+    Button::RightThumb,
+    Button::DPadUp,
+    Button::DPadDown,
+    Button::DPadLeft,
+    Button::DPadRight,
+    Button::Mode,
+];
+
+/// The axes of the standard gamepad, in order, and whether gilrs reports
+/// them upside down (its sticks point up for positive values, the
+/// standard gamepad's point down)
+const STANDARD_AXES: [(Axis, bool); 4] = [
+    (Axis::LeftStickX, false),
+    (Axis::LeftStickY, true),
+    (Axis::RightStickX, false),
+    (Axis::RightStickY, true),
+];
+
+/// The machine's gamepads, reached through gilrs
+#[derive(Default)]
+pub struct SystemGamepads {
+    gilrs: Option<Gilrs>,
+    /// Set once gilrs failed to start, so it is not retried every frame
+    unavailable: bool,
+}
+
+impl SystemGamepads {
+    /// Create a backend that starts gilrs on first poll
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get gilrs, starting it if needed
+    fn open(&mut self) -> Option<&mut Gilrs> {
+        if self.gilrs.is_none() && !self.unavailable {
+            match Gilrs::new() {
+                Ok(gilrs) => self.gilrs = Some(gilrs),
+                Err(e) => {
+                    println!("⚠️  Gamepads unavailable: {}", e);
+                    self.unavailable = true;
+                }
+            }
+        }
+        self.gilrs.as_mut()
+    }
+}
+
+impl GamepadBackend for SystemGamepads {
+    fn poll(&mut self) -> Vec<GamepadSnapshot> {
+        let Some(gilrs) = self.open() else {
+            return Vec::new();
+        };
+        // Draining the events brings gilrs' state of each device up to date
+        while gilrs.next_event().is_some() {}
+
+        gilrs
+            .gamepads()
+            .map(|(id, gamepad)| GamepadSnapshot {
+                device: usize::from(id) as u64,
+                name: gamepad.name().to_string(),
+                standard_mapping: gamepad.mapping_source() != MappingSource::None,
+                axes: STANDARD_AXES
+                    .iter()
+                    .map(|&(axis, inverted)| {
+                        let value = f64::from(gamepad.value(axis));
+                        if inverted { -value } else { value }
+                    })
+                    .collect(),
+                buttons: STANDARD_BUTTONS
+                    .iter()
+                    .map(|&button| {
+                        let data = gamepad.button_data(button);
+                        let pressed = data.is_some_and(|data| data.is_pressed());
+                        let value = data.map_or(0.0, |data| f64::from(data.value()));
+                        GamepadButton { pressed, touched: pressed || value > 0.0, value }
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+/// Create the gamepad backend the shell installs by default
+pub fn system_gamepads() -> Box<dyn GamepadBackend> {
+    Box::new(SystemGamepads::new())
+}
@@ -0,0 +1,100 @@
+//! # Tab Freezing
+//!
+//! This module decides how much work the pages of hidden tabs may do. A
+//! page in a background tab or a minimized window cannot be seen, so its
+//! timers are throttled and its animation frames suspended, which keeps
+//! CPU usage low with many tabs open.
+//!
+//! ## Design Principles
+//!
+//! 1. **Policy in the Shell**: The event loop only knows a minimum timer
+//!    delay and whether rendering is suspended; which pages get them is
+//!    decided here, from the document's visibility state.
+//! 2. **Slowed, Not Lost**: Timers and frame callbacks of a hidden page are
+//!    kept, and run at their normal pace as soon as the page is shown.
+
+use std::time::Duration;
+use dom::page_visibility::VisibilityState;
+use js_integration::event_loop::EventLoop;
+
+/// Minimum delay of the timers of hidden pages
+pub const HIDDEN_TIMER_MIN_DELAY: Duration = Duration::from_secs(1);
+
+/// How the event loops of hidden pages are throttled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TabFreezingPolicy {
+    /// Minimum delay of timers while the page is hidden
+    pub hidden_timer_min_delay: Duration,
+    /// Whether animation frames and rendering stop while the page is hidden
+    pub suspend_hidden_rendering: bool,
+}
+
+impl Default for TabFreezingPolicy {
+    fn default() -> Self {
+        TabFreezingPolicy {
+            hidden_timer_min_delay: HIDDEN_TIMER_MIN_DELAY,
+            suspend_hidden_rendering: true,
+        }
+    }
+}
+
+impl TabFreezingPolicy {
+    /// A policy that lets hidden pages run like visible ones
+    pub fn disabled() -> Self {
+        TabFreezingPolicy {
+            hidden_timer_min_delay: Duration::ZERO,
+            suspend_hidden_rendering: false,
+        }
+    }
+
+    /// Throttle or release an event loop for a page in `state`
+    pub fn apply(&self, state: VisibilityState, event_loop: &mut EventLoop) {
+        let hidden = state == VisibilityState::Hidden;
+        event_loop.set_min_timer_delay(if hidden { self.hidden_timer_min_delay } else { Duration::ZERO });
+        event_loop.set_rendering_suspended(hidden && self.suspend_hidden_rendering);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BrowserCLI, BrowserEngine};
+
+    #[test]
+    fn test_hidden_tabs_are_throttled() {
+        let mut engine = BrowserEngine::new();
+        assert!(engine.load_html("<html><body><p>Background work</p></body></html>"));
+        let mut script = engine.create_script_engine().unwrap();
+        script.execute("const log = []; document.addEventListener('visibilitychange', () => log.push(document.visibilityState));").unwrap();
+        let now = std::time::Instant::now();
+        script.event_loop().set_timer("ticks++".to_string(), Duration::from_millis(10), true, now);
+        script.request_animation_frame("frames++");
+        assert_eq!(script.event_loop().next_wakeup(now), Some(now));
+
+        assert!(engine.set_visibility_state(VisibilityState::Hidden));
+        assert!(engine.sync_visibility(&mut script));
+        assert!(!engine.sync_visibility(&mut script));
+        let wakeup = script.event_loop().next_wakeup(now).unwrap();
+        assert_eq!(wakeup, now + HIDDEN_TIMER_MIN_DELAY);
+
+        // Pages loaded into a hidden tab start hidden and throttled
+        assert!(engine.load_html("<html><body></body></html>"));
+        assert_eq!(engine.visibility_state(), VisibilityState::Hidden);
+        let mut fresh = engine.create_script_engine().unwrap();
+        let hidden = fresh.execute("document.hidden").unwrap();
+        assert_eq!(hidden.as_boolean(), Some(true));
+
+        engine.set_visibility_state(VisibilityState::Visible);
+        engine.sync_visibility(&mut script);
+        let log = script.execute("log.join()").unwrap();
+        assert_eq!(log.to_string(&mut script.context).unwrap().to_std_string_escaped(), "hidden,visible");
+        assert_eq!(script.event_loop().next_wakeup(now), Some(now));
+
+        // Switching tabs hides the one left behind
+        let mut cli = BrowserCLI { tabs: vec![BrowserEngine::new(), BrowserEngine::new()], ..BrowserCLI::new() };
+        cli.switch_tab(1);
+        assert_eq!(cli.tabs[0].visibility_state(), VisibilityState::Hidden);
+        cli.set_window_minimized(true);
+        assert_eq!(cli.tabs[1].visibility_state(), VisibilityState::Hidden);
+    }
+}
@@ -0,0 +1,570 @@
+//! # Tab Processes
+//!
+//! This module runs each tab's engine in its own process. The engine
+//! process parses, styles, lays out and runs scripts, and sends display
+//! lists back; the shell process only keeps the tabs and composites their
+//! frames. A crash or exploit in one page then takes down one tab, not the
+//! shell or the other tabs.
+//!
+//! ## Design Principles
+//!
+//! 1. **Same Binary**: Engine processes are the shell's own executable
+//!    started with `--engine-process`, so they always speak the same
+//!    protocol version.
+//! 2. **Socket per Tab**: Each tab has a Unix socket to the shell; the
+//!    engine's stdout is discarded so its logging can never corrupt the
+//!    channel.
+//! 3. **Crashes Are Replies**: A tab whose process died answers every
+//!    request with `IpcError::TabCrashed` instead of taking the shell down.
+
+use std::io;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use css_parser::Stylesheet;
+use renderer::display_list::{DisplayItem, DisplayList};
+use crate::bookmarks::document_title;
+use crate::ipc::{read_message, write_message, EngineReply, IpcError, IpcResult, ShellRequest};
+use crate::crash::{catch_crash, CRASH_DIR};
+use crate::error::EnginePhase;
+use crate::session::SessionStore;
+use crate::BrowserEngine;
+use js_integration::user_prompts::UserPrompt;
+
+/// Command line flag that starts an engine process
+pub const ENGINE_PROCESS_FLAG: &str = "--engine-process";
+
+/// How long a new engine process has to connect to the shell
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Serve shell requests on `stream` until the shell shuts the tab down or
+/// goes away
+///
+/// This is the main loop of an engine process. It must not run inside an
+/// async runtime, as it starts its own to fetch documents. If the engine
+/// panics, a crash report is written to `crash_dir` and the shell is told
+/// before the loop ends.
+pub fn serve(mut stream: UnixStream, crash_dir: Option<&Path>) -> IpcResult<()> {
+    let mut engine = BrowserEngine::new();
+    engine.start();
+    // The process has no terminal to ask on: prompts are dismissed, but a
+    // page asking to confirm leaving is left
+    engine.set_user_prompt_handler(Arc::new(|prompt: &UserPrompt| (*prompt == UserPrompt::BeforeUnload).then(String::new)));
+    let mut runtime: Option<tokio::runtime::Runtime> = None;
+
+    while let Some(request) = read_message::<_, ShellRequest>(&mut stream)? {
+        if request == ShellRequest::Shutdown {
+            write_message(&mut stream, &EngineReply::Done { ok: true })?;
+            return Ok(());
+        }
+        if matches!(request, ShellRequest::FetchUrl { .. }) && runtime.is_none() {
+            runtime = Some(tokio::runtime::Builder::new_current_thread().enable_all().build()?);
+        }
+        let url = engine.current_url.clone();
+        let reply = catch_crash(phase_of(&request), url.as_deref(), || match request {
+            ShellRequest::LoadHtml { html, url } => {
+                let ok = engine.prompt_to_unload() && engine.load_html_from(&html, url.as_deref(), Instant::now());
+                if ok {
+                    engine.current_url = url;
+                }
+                loaded(&engine, ok)
+            }
+            ShellRequest::FetchUrl { url } => {
+                let ok = runtime.as_ref().map(|runtime| runtime.block_on(engine.fetch_url(&url))).unwrap_or(false);
+                loaded(&engine, ok)
+            }
+            ShellRequest::LoadCss { css } => EngineReply::Done { ok: engine.load_css(&css) },
+            ShellRequest::Paint => {
+                // Documents without a stylesheet are painted unstyled
+                if engine.current_stylesheet.is_none() {
+                    engine.current_stylesheet = Some(Stylesheet { rules: vec![], page_rules: vec![], source_url: None });
+                }
+                let display_list = if engine.current_layout.is_some() || engine.perform_layout() {
+                    let spellchecker = engine.spellchecker();
+                    let spellchecker = spellchecker.borrow();
+                    engine.get_layout().map(|layout| DisplayList::from_layout_with_spellchecker(layout, &*spellchecker)).unwrap_or_default()
+                } else {
+                    DisplayList::default()
+                };
+                EngineReply::Frame { display_list }
+            }
+            ShellRequest::ExecuteScript { code } => {
+                let result = match engine.page_script() {
+                    Ok(script) => match script.execute(&code) {
+                        Ok(value) => value
+                            .to_string(&mut script.context)
+                            .map(|value| value.to_std_string_escaped())
+                            .map_err(|e| e.to_string()),
+                        Err(e) => Err(e.to_string()),
+                    },
+                    Err(e) => Err(e.to_string()),
+                };
+                // Scripts may have changed the document
+                engine.current_layout = None;
+                EngineReply::ScriptResult { result }
+            }
+            ShellRequest::Crash => panic!("Crash requested by the shell"),
+            ShellRequest::Shutdown => EngineReply::Done { ok: true },
+        });
+        match reply {
+            Ok(reply) => write_message(&mut stream, &reply)?,
+            Err(report) => {
+                let path = crash_dir.and_then(|dir| report.write_to(dir).ok());
+                let message = format!("{} during {}", report.message, report.phase);
+                let reply = EngineReply::Crashed { message: message.clone(), report: path.map(|path| path.display().to_string()) };
+                write_message(&mut stream, &reply)?;
+                return Err(IpcError::TabCrashed(message));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Get the pipeline phase a request runs, for crash reports
+fn phase_of(request: &ShellRequest) -> EnginePhase {
+    match request {
+        ShellRequest::LoadHtml { .. } => EnginePhase::HtmlParsing,
+        ShellRequest::FetchUrl { .. } => EnginePhase::Network,
+        ShellRequest::LoadCss { .. } => EnginePhase::CssParsing,
+        ShellRequest::Paint => EnginePhase::Layout,
+        ShellRequest::ExecuteScript { .. } | ShellRequest::Crash => EnginePhase::Scripting,
+        ShellRequest::Shutdown => EnginePhase::Navigation,
+    }
+}
+
+fn loaded(engine: &BrowserEngine, ok: bool) -> EngineReply {
+    let title = engine.current_document.as_deref().and_then(document_title);
+    EngineReply::Loaded { ok, title }
+}
+
+/// Connect to the shell at `socket` and serve its requests
+///
+/// Called by the shell binary when started with `--engine-process`. Crash
+/// reports go to the profile's crash directory.
+pub fn run_engine_process(socket: &Path) -> IpcResult<()> {
+    let crash_dir = SessionStore::default_dir().join(CRASH_DIR);
+    serve(UnixStream::connect(socket)?, Some(&crash_dir))
+}
+
+/// The shell's handle on a tab's engine process
+#[derive(Debug)]
+pub struct TabProcess {
+    stream: UnixStream,
+    /// The engine process, `None` if the engine runs on a thread
+    child: Option<Child>,
+    /// Why the tab crashed, once it has
+    crash: Option<String>,
+    /// The requests that built the current page, to load it again
+    replay: Vec<ShellRequest>,
+}
+
+impl TabProcess {
+    /// Start an engine process for a new tab
+    pub fn spawn() -> IpcResult<Self> {
+        let socket = socket_path();
+        let _ = std::fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket)?;
+        let child = Command::new(std::env::current_exe()?)
+            .arg(ENGINE_PROCESS_FLAG)
+            .arg(&socket)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .spawn();
+        let result = child.map_err(IpcError::from).and_then(|mut child| match accept(&listener, &mut child) {
+            Ok(stream) => Ok(TabProcess { stream, child: Some(child), crash: None, replay: Vec::new() }),
+            Err(e) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                Err(e)
+            }
+        });
+        let _ = std::fs::remove_file(&socket);
+        result
+    }
+
+    /// Talk to an engine served on the other end of `stream`, e.g. by
+    /// `serve` on a thread
+    pub fn from_stream(stream: UnixStream) -> Self {
+        TabProcess { stream, child: None, crash: None, replay: Vec::new() }
+    }
+
+    /// Why the tab crashed, if it has
+    pub fn crash_reason(&self) -> Option<&str> {
+        self.crash.as_deref()
+    }
+
+    /// Send a request and wait for its reply
+    ///
+    /// Fails with `IpcError::TabCrashed` if the engine process is gone.
+    pub fn request(&mut self, request: &ShellRequest) -> IpcResult<EngineReply> {
+        if let Some(reason) = &self.crash {
+            return Err(IpcError::TabCrashed(reason.clone()));
+        }
+        let reply = write_message(&mut self.stream, request).and_then(|_| read_message(&mut self.stream));
+        match reply {
+            Ok(Some(EngineReply::Crashed { message, report })) => {
+                let reason = match report {
+                    Some(report) => format!("{}; crash report saved to {}", message, report),
+                    None => message,
+                };
+                self.crash = Some(reason.clone());
+                Err(IpcError::TabCrashed(reason))
+            }
+            Ok(Some(reply)) => {
+                match request {
+                    ShellRequest::LoadHtml { .. } | ShellRequest::FetchUrl { .. } => self.replay = vec![request.clone()],
+                    ShellRequest::LoadCss { .. } => self.replay.push(request.clone()),
+                    _ => {}
+                }
+                Ok(reply)
+            }
+            Ok(None) | Err(IpcError::Io(_)) => Err(IpcError::TabCrashed(self.record_crash())),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Load the page of `crashed` into this tab, as it was before the crash
+    pub fn replay_from(&mut self, crashed: &TabProcess) -> IpcResult<()> {
+        for request in &crashed.replay {
+            self.request(request)?;
+        }
+        Ok(())
+    }
+
+    /// Ask the engine for the display list of its document
+    pub fn paint(&mut self) -> IpcResult<DisplayList> {
+        match self.request(&ShellRequest::Paint)? {
+            EngineReply::Frame { display_list } => Ok(display_list),
+            reply => Err(IpcError::UnexpectedReply(format!("{:?}", reply))),
+        }
+    }
+
+    /// Shut the engine process down
+    pub fn shutdown(mut self) {
+        if self.crash.is_none() {
+            let _ = self.request(&ShellRequest::Shutdown);
+        }
+        if let Some(mut child) = self.child.take() {
+            let _ = child.wait();
+        }
+    }
+
+    /// Note that the engine went away and describe how
+    fn record_crash(&mut self) -> String {
+        let reason = match self.child.as_mut() {
+            Some(child) => {
+                // Give the process a moment to finish exiting
+                let deadline = Instant::now() + Duration::from_secs(1);
+                loop {
+                    match child.try_wait() {
+                        Ok(Some(status)) => break format!("engine process exited with {}", status),
+                        Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(10)),
+                        _ => break "engine process stopped responding".to_string(),
+                    }
+                }
+            }
+            None => "engine stopped responding".to_string(),
+        };
+        self.crash = Some(reason.clone());
+        reason
+    }
+}
+
+impl Drop for TabProcess {
+    fn drop(&mut self) {
+        if let Some(child) = self.child.as_mut() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Wait for a new engine process to connect
+fn accept(listener: &UnixListener, child: &mut Child) -> IpcResult<UnixStream> {
+    listener.set_nonblocking(true)?;
+    let deadline = Instant::now() + CONNECT_TIMEOUT;
+    loop {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                stream.set_nonblocking(false)?;
+                return Ok(stream);
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                if let Some(status) = child.try_wait()? {
+                    return Err(IpcError::TabCrashed(format!("engine process exited with {} before connecting", status)));
+                }
+                if Instant::now() >= deadline {
+                    return Err(IpcError::TabCrashed("engine process did not connect".to_string()));
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+fn socket_path() -> PathBuf {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static NEXT_TAB: AtomicUsize = AtomicUsize::new(0);
+    let tab = NEXT_TAB.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!("dubby-tab-{}-{}.sock", std::process::id(), tab))
+}
+
+/// Composite a tab's frame for the terminal: one line per display item
+pub fn composite(display_list: &DisplayList) -> String {
+    display_list
+        .items
+        .iter()
+        .map(|item| match item {
+            DisplayItem::Rect { x, y, width, height, color } => {
+                format!("[{:.0},{:.0} {:.0}x{:.0}] {}", x, y, width, height, color)
+            }
+            DisplayItem::RoundedRect { x, y, width, height, radius, color } => {
+                format!("[{:.0},{:.0} {:.0}x{:.0} r{:.0}] {}", x, y, width, height, radius, color)
+            }
+            DisplayItem::BackgroundImage { x, y, width, height, src, fixed, .. } => {
+                format!("[{:.0},{:.0} {:.0}x{:.0}] url({}){}", x, y, width, height, src, if *fixed { " fixed" } else { "" })
+            }
+            DisplayItem::Text { x, y, text, color, .. } => format!("[{:.0},{:.0}] {} ({})", x, y, text, color),
+            DisplayItem::Squiggle { x, y, width, color, .. } => format!("[{:.0},{:.0} {:.0}] ~~~ ({})", x, y, width, color),
+            DisplayItem::Replaced { x, y, width, height, element, src, .. } => {
+                format!("[{:.0},{:.0} {:.0}x{:.0}] <{}> {}", x, y, width, height, element, src.as_deref().unwrap_or(""))
+            }
+            DisplayItem::Surface { x, y, width, height, kind, surface_id, .. } => {
+                format!("[{:.0},{:.0} {:.0}x{:.0}] {} surface #{}", x, y, width, height, kind.as_str(), surface_id)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A shell whose tabs each run in their own engine process
+#[derive(Debug)]
+pub struct MultiProcessShell {
+    tabs: Vec<TabProcess>,
+    active_tab: usize,
+    /// Starts the engine of a new or reloaded tab
+    spawn: fn() -> IpcResult<TabProcess>,
+}
+
+impl MultiProcessShell {
+    pub fn new() -> Self {
+        Self::with_spawner(TabProcess::spawn)
+    }
+
+    /// Create a shell that starts tab engines with `spawn`
+    pub fn with_spawner(spawn: fn() -> IpcResult<TabProcess>) -> Self {
+        MultiProcessShell { tabs: Vec::new(), active_tab: 0, spawn }
+    }
+
+    /// Replace the active tab with a new engine that loads its page again
+    ///
+    /// This is how a crashed tab is recovered.
+    pub fn reload_tab(&mut self) -> IpcResult<()> {
+        let mut tab = (self.spawn)()?;
+        if let Some(current) = self.tabs.get(self.active_tab) {
+            tab.replay_from(current)?;
+        }
+        match self.tabs.get_mut(self.active_tab) {
+            Some(current) => std::mem::replace(current, tab).shutdown(),
+            None => {
+                self.add_tab(tab);
+            }
+        }
+        Ok(())
+    }
+
+    /// Add a tab, making it the active one
+    pub fn add_tab(&mut self, tab: TabProcess) -> usize {
+        self.tabs.push(tab);
+        self.active_tab = self.tabs.len() - 1;
+        self.active_tab
+    }
+
+    pub fn tab_count(&self) -> usize {
+        self.tabs.len()
+    }
+
+    /// Get the tab commands go to
+    pub fn active_tab(&mut self) -> Option<&mut TabProcess> {
+        self.tabs.get_mut(self.active_tab)
+    }
+
+    /// Run a shell command, returning `false` when the shell should exit
+    pub fn handle_command(&mut self, line: &str) -> bool {
+        let (command, args) = line.trim().split_once(' ').map(|(command, args)| (command, args.trim())).unwrap_or((line.trim(), ""));
+        let request = match command {
+            "" => return true,
+            "quit" | "exit" => return false,
+            "help" => {
+                self.show_help();
+                return true;
+            }
+            "new-tab" => {
+                match (self.spawn)() {
+                    Ok(tab) => println!("Opened tab {}", self.add_tab(tab)),
+                    Err(e) => println!("❌ Failed to start a tab: {}", e),
+                }
+                return true;
+            }
+            "tab" => {
+                match args.parse::<usize>() {
+                    Ok(index) if index < self.tabs.len() => self.active_tab = index,
+                    _ => println!("Usage: tab <0-{}>", self.tabs.len().saturating_sub(1)),
+                }
+                return true;
+            }
+            "reload" => {
+                match self.reload_tab() {
+                    Ok(()) => println!("Reloaded tab {}", self.active_tab),
+                    Err(e) => println!("❌ Failed to reload the tab: {}", e),
+                }
+                return true;
+            }
+            "tabs" => {
+                for (index, tab) in self.tabs.iter().enumerate() {
+                    let marker = if index == self.active_tab { "*" } else { " " };
+                    match tab.crash_reason() {
+                        Some(reason) => println!("{} {}: crashed ({})", marker, index, reason),
+                        None => println!("{} {}", marker, index),
+                    }
+                }
+                return true;
+            }
+            "load" => ShellRequest::LoadHtml { html: args.to_string(), url: None },
+            "fetch" => ShellRequest::FetchUrl { url: args.to_string() },
+            "css" => ShellRequest::LoadCss { css: args.to_string() },
+            "render" | "paint" => ShellRequest::Paint,
+            "js" => ShellRequest::ExecuteScript { code: args.to_string() },
+            "crash" => ShellRequest::Crash,
+            _ => {
+                println!("Unknown command: {}. Type 'help' for available commands.", command);
+                return true;
+            }
+        };
+
+        let index = self.active_tab;
+        let Some(tab) = self.tabs.get_mut(index) else {
+            println!("No tab is open; use 'new-tab'");
+            return true;
+        };
+        if let Some(reason) = tab.crash_reason() {
+            // The crash page, shown instead of the page until it is reloaded
+            println!("💥 This tab crashed: {}", reason);
+            println!("Type 'reload' to load the page again. Other tabs are not affected.");
+            return true;
+        }
+        match tab.request(&request) {
+            Ok(EngineReply::Loaded { ok: true, title }) => println!("✅ Loaded {}", title.unwrap_or_default()),
+            Ok(EngineReply::Loaded { ok: false, .. }) | Ok(EngineReply::Done { ok: false }) => println!("❌ {} failed", command),
+            Ok(EngineReply::Done { ok: true }) => println!("✅ Done"),
+            Ok(EngineReply::Frame { display_list }) => println!("{}", composite(&display_list)),
+            Ok(EngineReply::ScriptResult { result: Ok(value) }) => println!("{}", value),
+            Ok(EngineReply::ScriptResult { result: Err(e) }) => println!("❌ {}", e),
+            Ok(EngineReply::Crashed { message: reason, .. }) | Err(IpcError::TabCrashed(reason)) => {
+                println!("💥 Tab {} crashed: {}", index, reason);
+                println!("Type 'reload' to load the page again. Other tabs are not affected.");
+            }
+            Err(e) => println!("❌ {}", e),
+        }
+        true
+    }
+
+    /// Read commands from stdin until `quit`
+    pub fn run(&mut self) {
+        use std::io::{BufRead, Write};
+        if self.tabs.is_empty() {
+            self.handle_command("new-tab");
+        }
+        let stdin = io::stdin();
+        loop {
+            print!("browser[mp]> ");
+            let _ = io::stdout().flush();
+            let mut line = String::new();
+            match stdin.lock().read_line(&mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    if !self.handle_command(&line) {
+                        break;
+                    }
+                }
+            }
+        }
+        for tab in self.tabs.drain(..) {
+            tab.shutdown();
+        }
+    }
+
+    fn show_help(&self) {
+        println!("Each tab runs in its own engine process.");
+        println!("Available commands:");
+        println!("  load <html>      - Load HTML content in the active tab");
+        println!("  fetch <url>      - Fetch and load a URL in the active tab");
+        println!("  css <css>        - Apply CSS to the active tab");
+        println!("  render           - Paint the active tab");
+        println!("  js <code>        - Run JavaScript in the active tab");
+        println!("  new-tab          - Open a tab in a new engine process");
+        println!("  tab <n>          - Switch to tab n");
+        println!("  tabs             - List tabs and whether they crashed");
+        println!("  reload           - Load the page again in a new engine process");
+        println!("  crash            - Crash the active tab's engine process");
+        println!("  quit/exit        - Exit the browser");
+    }
+}
+
+impl Default for MultiProcessShell {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Start an engine on a thread, as the engine process would run it
+    fn thread_tab() -> TabProcess {
+        let (shell, engine) = UnixStream::pair().unwrap();
+        std::thread::spawn(move || {
+            let _ = serve(engine, None);
+        });
+        TabProcess::from_stream(shell)
+    }
+
+    #[test]
+    fn test_tabs_survive_a_crashed_tab() {
+        let mut shell = MultiProcessShell::with_spawner(|| Ok(thread_tab()));
+        shell.add_tab(thread_tab());
+        shell.add_tab(thread_tab());
+
+        let tab = shell.active_tab().unwrap();
+        let reply = tab
+            .request(&ShellRequest::LoadHtml {
+                html: "<html><head><title>News</title></head><body><p>Headline</p></body></html>".to_string(),
+                url: Some("https://news.example/".to_string()),
+            })
+            .unwrap();
+        assert_eq!(reply, EngineReply::Loaded { ok: true, title: Some("News".to_string()) });
+        let reply = tab.request(&ShellRequest::ExecuteScript { code: "1 + 2".to_string() }).unwrap();
+        assert_eq!(reply, EngineReply::ScriptResult { result: Ok("3".to_string()) });
+        assert!(tab.paint().unwrap().text_runs().contains(&"Headline"));
+
+        // Crashing the other tab leaves this one working
+        assert!(shell.handle_command("tab 0"));
+        assert!(shell.handle_command("crash"));
+        assert!(shell.active_tab().unwrap().crash_reason().unwrap().contains("Crash requested by the shell"));
+        assert!(matches!(shell.active_tab().unwrap().paint(), Err(IpcError::TabCrashed(_))));
+        assert!(shell.handle_command("tab 1"));
+        assert!(shell.active_tab().unwrap().paint().unwrap().text_runs().contains(&"Headline"));
+
+        // Reloading a crashed tab loads its page in a new engine
+        assert!(shell.handle_command("load <html><body><p>Draft</p></body></html>"));
+        assert!(shell.handle_command("crash"));
+        shell.reload_tab().unwrap();
+        assert_eq!(shell.tab_count(), 2);
+        assert!(shell.active_tab().unwrap().crash_reason().is_none());
+        assert!(shell.active_tab().unwrap().paint().unwrap().text_runs().contains(&"Draft"));
+    }
+}
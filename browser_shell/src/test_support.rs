@@ -0,0 +1,32 @@
+//! # Test Support
+//!
+//! Test-only helpers shared by the unit tests of the browser shell.
+
+use std::fs;
+use std::path::PathBuf;
+use layout::LayoutBox;
+use crate::BrowserEngine;
+
+/// Get an empty profile directory for a test, unique to the test run
+///
+/// `name` must be unique among the tests, which run in parallel.
+pub fn temporary_profile(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("dubby-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+/// Find the box of a node in a layout tree
+pub fn find_box(layout: &LayoutBox, id: u64) -> Option<&LayoutBox> {
+    if layout.node.id == id {
+        return Some(layout);
+    }
+    layout.children.iter().find_map(|child| find_box(child, id))
+}
+
+/// Run a script in the page and get its result as a string
+pub fn evaluate(engine: &mut BrowserEngine, code: &str) -> String {
+    let script = engine.page_script().unwrap();
+    let result = script.execute(code).unwrap();
+    result.to_string(&mut script.context).unwrap().to_std_string_escaped()
+}
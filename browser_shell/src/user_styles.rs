@@ -0,0 +1,209 @@
+//! # User Stylesheets
+//!
+//! This module loads the user's own stylesheets from the profile directory
+//! and keeps them up to date while the shell runs. They are applied at the
+//! user origin of the cascade, which makes them the place for dark-mode
+//! overrides and rules hiding ads.
+//!
+//! ## Design Principles
+//!
+//! 1. **Plain Files**: `user-styles/user.css` applies to every page, and
+//!    `user-styles/<domain>.css` to pages on that domain and its
+//!    subdomains, so `example.com.css` also styles `www.example.com`.
+//! 2. **Most Specific Last**: The global stylesheet comes first, then the
+//!    domain stylesheets from the widest domain to the narrowest, so a
+//!    site's own overrides win over the general ones.
+//! 3. **Live Reload**: Files are re-read when their modification time or
+//!    size changes, so edits show up without restarting the shell.
+//! 4. **Forgiving**: A stylesheet that cannot be read or parsed is skipped
+//!    with a warning; it never keeps a page from rendering.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use css_parser::{CSSParser, Stylesheet};
+
+/// Name of the user stylesheet directory in the profile directory
+pub const USER_STYLES_DIR: &str = "user-styles";
+
+/// Name of the stylesheet applied to every page
+pub const GLOBAL_STYLESHEET: &str = "user.css";
+
+/// A user stylesheet file and the state it was loaded in
+#[derive(Debug, Clone)]
+struct LoadedStylesheet {
+    modified: Option<SystemTime>,
+    len: u64,
+    stylesheet: Stylesheet,
+}
+
+/// The user stylesheets of a profile
+#[derive(Debug)]
+pub struct UserStyles {
+    dir: PathBuf,
+    /// Loaded stylesheets by file name
+    stylesheets: BTreeMap<String, LoadedStylesheet>,
+}
+
+impl UserStyles {
+    /// Open the user stylesheets of the profile in `profile_dir`
+    ///
+    /// Creates the stylesheet directory if there is none yet and loads the
+    /// stylesheets in it.
+    pub fn open(profile_dir: impl AsRef<Path>) -> io::Result<Self> {
+        let dir = profile_dir.as_ref().join(USER_STYLES_DIR);
+        fs::create_dir_all(&dir)?;
+        let mut styles = UserStyles { dir, stylesheets: BTreeMap::new() };
+        styles.reload_if_changed();
+        Ok(styles)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Get the names of the loaded stylesheet files
+    pub fn files(&self) -> Vec<&str> {
+        self.stylesheets.keys().map(String::as_str).collect()
+    }
+
+    /// Re-read stylesheets that were added, changed or removed
+    ///
+    /// Returns whether any stylesheet changed, i.e. whether pages need to
+    /// be styled again.
+    pub fn reload_if_changed(&mut self) -> bool {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                eprintln!("❌ Cannot read user stylesheets in {}: {}", self.dir.display(), e);
+                return false;
+            }
+        };
+
+        let mut changed = false;
+        let mut present = Vec::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Some(name) = path.file_name().and_then(|name| name.to_str()).map(str::to_string) else {
+                continue;
+            };
+            if !name.ends_with(".css") {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            present.push(name.clone());
+
+            let modified = metadata.modified().ok();
+            let len = metadata.len();
+            if let Some(loaded) = self.stylesheets.get(&name) {
+                if loaded.modified == modified && loaded.len == len {
+                    continue;
+                }
+            }
+            match load_stylesheet(&path) {
+                Some(stylesheet) => {
+                    println!("🔸 Loaded user stylesheet {}", name);
+                    self.stylesheets.insert(name, LoadedStylesheet { modified, len, stylesheet });
+                }
+                None => {
+                    self.stylesheets.remove(&name);
+                }
+            }
+            changed = true;
+        }
+
+        let before = self.stylesheets.len();
+        self.stylesheets.retain(|name, _| present.contains(name));
+        changed || self.stylesheets.len() != before
+    }
+
+    /// Build the user stylesheet for a page
+    ///
+    /// Combines the global stylesheet with the stylesheets of the page's
+    /// domain and its parent domains. Pages without a URL only get the
+    /// global stylesheet.
+    pub fn stylesheet_for(&self, url: Option<&str>) -> Stylesheet {
+        let mut names = vec![GLOBAL_STYLESHEET.to_string()];
+        if let Some(host) = url.and_then(|url| url::Url::parse(url).ok()).and_then(|url| url.host_str().map(str::to_string)) {
+            let labels: Vec<&str> = host.trim_end_matches('.').split('.').collect();
+            // Widest domain first, skipping the bare top-level domain
+            for start in (0..labels.len().saturating_sub(1)).rev() {
+                names.push(format!("{}.css", labels[start..].join(".").to_ascii_lowercase()));
+            }
+            if labels.len() == 1 {
+                names.push(format!("{}.css", host.to_ascii_lowercase()));
+            }
+        }
+
+        let rules = names
+            .iter()
+            .filter_map(|name| self.stylesheets.get(name))
+            .flat_map(|loaded| loaded.stylesheet.rules.iter().cloned())
+            .collect();
+        Stylesheet { rules, page_rules: vec![], source_url: Some(format!("user-styles:{}", url.unwrap_or(""))) }
+    }
+}
+
+/// Read and parse a user stylesheet, warning if it cannot be used
+fn load_stylesheet(path: &Path) -> Option<Stylesheet> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            eprintln!("❌ Cannot read user stylesheet {}: {}", path.display(), e);
+            return None;
+        }
+    };
+    // User stylesheets rely on `!important`, so they go through the full
+    // parser rather than `parse_css`
+    match CSSParser::new(contents).parse_stylesheet() {
+        Ok(mut stylesheet) => {
+            stylesheet.source_url = Some(format!("file://{}", path.display()));
+            Some(stylesheet)
+        }
+        Err(e) => {
+            eprintln!("❌ Invalid user stylesheet {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temporary_profile;
+
+    #[test]
+    fn test_global_and_domain_stylesheets() {
+        let dir = temporary_profile("user-styles-domains");
+        let mut styles = UserStyles::open(&dir).unwrap();
+        assert!(styles.files().is_empty());
+
+        fs::write(styles.dir().join(GLOBAL_STYLESHEET), "body { color: white }").unwrap();
+        fs::write(styles.dir().join("example.com.css"), ".ad { display: none !important }").unwrap();
+        fs::write(styles.dir().join("www.example.com.css"), "p { color: #eeeeee }").unwrap();
+        fs::write(styles.dir().join("notes.txt"), "not a stylesheet").unwrap();
+        assert!(styles.reload_if_changed());
+        assert!(!styles.reload_if_changed());
+        assert_eq!(styles.files(), vec!["example.com.css", "user.css", "www.example.com.css"]);
+
+        assert_eq!(styles.stylesheet_for(None).rules.len(), 1);
+        assert_eq!(styles.stylesheet_for(Some("https://other.org/")).rules.len(), 1);
+        let site = styles.stylesheet_for(Some("https://www.example.com/page"));
+        assert_eq!(site.rules.len(), 3);
+        // The narrowest domain comes last so it wins
+        assert!(site.rules[1].declarations[0].important);
+        assert_eq!(site.rules[2].declarations[0].property, "color");
+
+        // Edits and deletions are picked up
+        fs::write(styles.dir().join(GLOBAL_STYLESHEET), "body { color: white } a { color: yellow }").unwrap();
+        fs::remove_file(styles.dir().join("example.com.css")).unwrap();
+        assert!(styles.reload_if_changed());
+        assert_eq!(styles.stylesheet_for(Some("https://www.example.com/")).rules.len(), 3);
+        assert_eq!(styles.stylesheet_for(Some("https://example.com/")).rules.len(), 2);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
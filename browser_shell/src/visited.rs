@@ -0,0 +1,194 @@
+//! # Visited Links
+//!
+//! This module remembers the URLs the user has visited, persists them in
+//! the profile directory, and marks the links of a document that point to
+//! one so `:visited` rules apply to them.
+//!
+//! ## Design Principles
+//!
+//! 1. **Saved on Change**: Every visit is written to disk immediately, like
+//!    bookmarks, so history survives a crash of the shell.
+//! 2. **Shared by Tabs**: A store is shared by cloning, so a page visited
+//!    in one tab shows as visited in every other.
+//! 3. **Documents Only See State**: Links are marked with
+//!    `ElementState::VISITED`; the cascade decides what that may change and
+//!    scripts never see it, see `css_parser::declaration_allowed_through`.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use dom::element_state::is_link;
+use dom::{Document, ElementState, Node};
+use networking::resolve_url;
+use url::Url;
+
+/// Name of the visited links file in the profile directory
+pub const VISITED_FILE: &str = "visited.json";
+
+#[derive(Debug, Default)]
+struct VisitedState {
+    urls: BTreeSet<String>,
+    /// File the URLs are saved to, if the store is persistent
+    path: Option<PathBuf>,
+}
+
+/// URLs the user has visited
+///
+/// Cloning shares the store.
+#[derive(Debug, Clone, Default)]
+pub struct VisitedStore {
+    state: Arc<Mutex<VisitedState>>,
+}
+
+impl VisitedStore {
+    /// Create an empty store that is not saved anywhere
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open the store saved in `dir`, creating an empty one if there is none
+    /// yet
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let path = dir.as_ref().join(VISITED_FILE);
+        let urls = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeSet::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(VisitedStore { state: Arc::new(Mutex::new(VisitedState { urls, path: Some(path) })) })
+    }
+
+    /// Record a visit to a URL
+    ///
+    /// Returns whether the URL was not visited before.
+    pub fn record(&self, url: &Url) -> bool {
+        let Some(key) = visit_key(url) else {
+            return false;
+        };
+        let mut state = self.lock();
+        let added = state.urls.insert(key);
+        if added {
+            if let Err(e) = state.save() {
+                println!("❌ Failed to save visited links: {}", e);
+            }
+        }
+        added
+    }
+
+    /// Check whether a URL has been visited
+    pub fn is_visited(&self, url: &Url) -> bool {
+        visit_key(url).is_some_and(|key| self.lock().urls.contains(&key))
+    }
+
+    /// Get the number of visited URLs
+    pub fn len(&self) -> usize {
+        self.lock().urls.len()
+    }
+
+    /// Check whether no URL has been visited
+    pub fn is_empty(&self) -> bool {
+        self.lock().urls.is_empty()
+    }
+
+    /// Forget all visits
+    pub fn clear(&self) -> io::Result<()> {
+        let mut state = self.lock();
+        state.urls.clear();
+        state.save()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VisitedState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl VisitedState {
+    fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let contents = serde_json::to_string_pretty(&self.urls).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let temporary = path.with_extension("json.tmp");
+        fs::write(&temporary, contents)?;
+        fs::rename(&temporary, path)
+    }
+}
+
+/// Get the key a visit is kept under
+///
+/// Fragments are dropped, since they name a place in the same page. Only
+/// http(s) and file URLs are remembered.
+fn visit_key(url: &Url) -> Option<String> {
+    if !matches!(url.scheme(), "http" | "https" | "file") {
+        return None;
+    }
+    let mut url = url.clone();
+    url.set_fragment(None);
+    Some(url.to_string())
+}
+
+/// Set or clear `:visited` on the links of `document`
+///
+/// Relative `href`s are resolved against the document base URL. Returns
+/// the number of links marked visited.
+pub fn mark_visited_links(document: &Document, store: &VisitedStore) -> usize {
+    mark_links_under(document, &document.root, store)
+}
+
+/// Set or clear `:visited` on the links under `root`
+fn mark_links_under(document: &Document, root: &Rc<Node>, store: &VisitedStore) -> usize {
+    let mut count = 0;
+    if is_link(root) {
+        let href = root.get_attribute("href").unwrap_or_default();
+        let target = resolve_url(document, &href);
+        let visited = target.is_some_and(|target| store.is_visited(&target));
+        root.set_element_state(ElementState::VISITED, visited);
+        if visited {
+            count += 1;
+        }
+    }
+    for child in root.children.borrow().iter() {
+        count += mark_links_under(document, child, store);
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temporary_profile;
+
+    #[test]
+    fn test_visited_store_marks_links() {
+        let dir = temporary_profile("visited");
+        let store = VisitedStore::open(&dir).unwrap();
+        assert!(store.record(&Url::parse("https://example.com/read#intro").unwrap()));
+        assert!(!store.record(&Url::parse("https://example.com/read").unwrap()));
+        assert!(!store.record(&Url::parse("javascript:alert(1)").unwrap()));
+
+        let document = Document::new_with_url("https://example.com/index.html");
+        let relative = document.create_element("a");
+        relative.set_attribute("href", "/read#part-2");
+        let other = document.create_element("a");
+        other.set_attribute("href", "https://example.com/new");
+        document.root.append_child(&relative);
+        document.root.append_child(&other);
+        assert_eq!(mark_visited_links(&document, &store), 1);
+        assert!(relative.element_state().contains(ElementState::VISITED));
+        assert!(!other.element_state().contains(ElementState::VISITED));
+
+        // Visits persist in the profile
+        let reopened = VisitedStore::open(&dir).unwrap();
+        assert_eq!(reopened.len(), 1);
+        reopened.clear().unwrap();
+        assert_eq!(mark_visited_links(&document, &reopened), 0);
+        assert!(!relative.element_state().contains(ElementState::VISITED));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
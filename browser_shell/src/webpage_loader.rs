@@ -6,11 +6,19 @@
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
-use networking::{HttpClient, HttpRequest, NetworkError};
-use css_parser::{parse_css, Stylesheet, CSSCascadeEngine, ComputedStyles};
-use dom::{Document, Node, NodeType};
+use networking::{AbortToken, HttpClient, HttpRequest, NetworkError, RequestInterceptor, RequestMode, UrlResolver};
+use networking::integrity::IntegrityMetadata;
+use networking::hsts::HstsStore;
+use networking::content_blocking::ContentBlocker;
+use networking::mixed_content::RequestDestination;
+use css_parser::{parse_css, Stylesheet, CSSCascadeEngine, CSSParser, ComputedStyles};
+use css_parser::media::{MediaFeatures, MediaQueryList};
+use dom::{Document, Node, NodeType, Origin};
 use layout::{LayoutEngine, LayoutBox};
 use renderer_wgpu::GpuRenderer;
+use crate::error::{EngineError, EngineResult};
+use js_integration::performance::{EntryType, PerformanceTimeline};
+use js_integration::console::{ConsoleLevel, ConsoleSink};
 use std::collections::HashMap;
 use std::rc::Rc;
 
@@ -28,6 +36,12 @@ pub struct PerformanceMetrics {
     pub css_rules: usize,
     pub layout_boxes: usize,
     pub js_statements: usize,
+    /// Time from the start of the load to the first paint
+    pub first_paint: Option<Duration>,
+    /// Time from the start of the load to the first paint with content
+    pub first_contentful_paint: Option<Duration>,
+    /// Time the first paint waited for render-blocking stylesheets
+    pub render_blocking_time: Duration,
 }
 
 impl Default for PerformanceMetrics {
@@ -44,6 +58,9 @@ impl Default for PerformanceMetrics {
             css_rules: 0,
             layout_boxes: 0,
             js_statements: 0,
+            first_paint: None,
+            first_contentful_paint: None,
+            render_blocking_time: Duration::ZERO,
         }
     }
 }
@@ -58,6 +75,9 @@ pub struct WebpageLoaderConfig {
     pub enable_animations: bool,
     pub record_metrics: bool,
     pub render_config: (),
+    /// How long the first paint waits for render-blocking stylesheets
+    /// before painting with the styles that arrived
+    pub render_blocking_timeout: Duration,
 }
 
 impl Default for WebpageLoaderConfig {
@@ -70,6 +90,7 @@ impl Default for WebpageLoaderConfig {
             enable_animations: true,
             record_metrics: true,
             render_config: (),
+            render_blocking_timeout: Duration::from_secs(3),
         }
     }
 }
@@ -82,8 +103,18 @@ pub struct WebpageLoader {
     config: WebpageLoaderConfig,
     metrics: PerformanceMetrics,
     external_resources: Vec<html_parser::ExternalResource>,
+    /// Stylesheets that load after the first paint, in document order
+    pending_stylesheets: Vec<LinkedStylesheet>,
     css_engine: CSSCascadeEngine,
     computed_styles: HashMap<u64, ComputedStyles>,
+    /// Final URL of the current document (after redirects)
+    current_url: Option<String>,
+    /// Signal shared by every request of the current load
+    abort_token: AbortToken,
+    /// Resource and paint timings of the current load
+    performance: PerformanceTimeline,
+    /// Console messages about the current load
+    console: ConsoleSink,
 }
 
 impl WebpageLoader {
@@ -98,17 +129,58 @@ impl WebpageLoader {
             config,
             metrics: PerformanceMetrics::default(),
             external_resources: Vec::new(),
+            pending_stylesheets: Vec::new(),
             css_engine: CSSCascadeEngine::new(),
             computed_styles: HashMap::new(),
+            current_url: None,
+            abort_token: AbortToken::new(),
+            performance: PerformanceTimeline::new(),
+            console: ConsoleSink::new(),
         }
     }
     
+    /// Set the environment `@media` rules are evaluated against, e.g. the
+    /// user's color scheme
+    pub fn set_media_features(&mut self, features: MediaFeatures) {
+        self.css_engine.set_media_features(features);
+    }
+    
+    /// Re-evaluate media queries after the environment changed, e.g. the
+    /// viewport was resized, and compute styles again
+    /// 
+    /// Stylesheets whose `media` attribute did not match at load time were
+    /// still fetched, so they apply as soon as their queries match.
+    pub fn update_media_features(&mut self, features: MediaFeatures, document: &Document) {
+        if &features == self.css_engine.media_features() {
+            return;
+        }
+        self.css_engine.set_media_features(features);
+        self.computed_styles = self.css_engine.compute_styles(document);
+    }
+    
+    /// Upgrade requests to HTTPS with a shared HSTS store and record new
+    /// policies in it
+    pub fn set_hsts_store(&mut self, store: HstsStore) {
+        self.http_client.set_hsts_store(store);
+    }
+    
+    /// Block requests with a content blocker's filter lists
+    pub fn set_content_blocker(&mut self, blocker: ContentBlocker) {
+        self.http_client.set_content_blocker(blocker);
+    }
+    
+    /// Let `interceptor` answer the loader's requests, e.g. to replay a HAR
+    /// archive
+    pub fn set_request_interceptor(&mut self, interceptor: Arc<dyn RequestInterceptor>) {
+        self.http_client.set_request_interceptor(interceptor);
+    }
+    
     /// Initialize the loader with all required engines
-    pub async fn initialize(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    pub async fn initialize(&mut self) -> EngineResult<()> {
         println!("🚀 Initializing Webpage Loader...");
         
         // Initialize layout engine
-        let stylesheet = Stylesheet { rules: vec![], source_url: None };
+        let stylesheet = Stylesheet { rules: vec![], page_rules: vec![], source_url: None };
         self.layout_engine = Some(LayoutEngine::new(stylesheet));
         
         // Initialize JavaScript engine (placeholder)
@@ -127,31 +199,51 @@ impl WebpageLoader {
     }
     
     /// Load and process a complete webpage from a URL
-    pub async fn load_webpage(&mut self, url: &str) -> Result<(), Box<dyn std::error::Error>> {
+    pub async fn load_webpage(&mut self, url: &str) -> EngineResult<()> {
         let start_time = Instant::now();
         println!("🌐 Loading webpage: {}", url);
+        self.performance = PerformanceTimeline::with_time_origin(start_time);
+        
+        // Failures are reported with the URL being loaded
+        let with_url = |error: EngineError| error.with_url(url);
+        
+        // Refuse to navigate to javascript: URLs
+        dom::SecurityPolicy::default().check_navigation(url).map_err(|e| with_url(e.into()))?;
+        
+        // A previous load was aborted; start this one with a fresh signal
+        if self.abort_token.is_aborted() {
+            self.abort_token = AbortToken::new();
+        }
         
         // Step 1: Fetch HTML content
-        let html_content = self.fetch_html(url).await?;
+        let html_content = self.fetch_html(url).await.map_err(|e| with_url(e.into()))?;
         
         // Step 2: Parse HTML into DOM
-        let document = self.parse_html(&html_content)?;
+        let document = self.parse_html(&html_content).map_err(with_url)?;
+        let document_url = self.current_url.clone().unwrap_or_else(|| url.to_string());
+        document.set_url(&document_url);
         
         // Step 3: Extract and parse CSS
-        self.extract_and_parse_css(&document).await?;
+        self.extract_and_parse_css(&document).await.map_err(with_url)?;
         
         // Step 4: Compute styles and perform layout
-        let layout_tree = self.compute_layout(&document)?;
+        let layout_tree = self.compute_layout(&document).map_err(with_url)?;
         
         // Step 5: Execute JavaScript
         let js_results = if self.config.enable_js {
-            self.execute_javascript(&document).await?
+            self.execute_javascript(&document).await.map_err(with_url)?
         } else {
             Vec::new()
         };
         
         // Step 6: Render with GPU
-        let render_result = self.render_webpage(&layout_tree).await?;
+        self.render_webpage(&layout_tree).await.map_err(with_url)?;
+        
+        // Step 7: Apply the stylesheets that did not block the first paint
+        if self.load_pending_stylesheets(&document).await.map_err(with_url)? {
+            let layout_tree = self.compute_layout(&document).map_err(with_url)?;
+            self.render_webpage(&layout_tree).await.map_err(with_url)?;
+        }
         
         // Calculate total time
         self.metrics.total_time = start_time.elapsed();
@@ -162,6 +254,20 @@ impl WebpageLoader {
         Ok(())
     }
     
+    /// Get a token that cancels the in-flight load when aborted
+    /// 
+    /// Aborting cancels the pending document and stylesheet requests and
+    /// makes `load_webpage` fail with `NetworkError::RequestAborted`.
+    pub fn abort_handle(&self) -> AbortToken {
+        self.abort_token.clone()
+    }
+    
+    /// Abort the current load
+    pub fn abort(&self) {
+        println!("🛑 Aborting webpage load");
+        self.abort_token.abort();
+    }
+    
     /// Fetch HTML content from URL
     async fn fetch_html(&mut self, url: &str) -> Result<String, NetworkError> {
         let start_time = Instant::now();
@@ -180,11 +286,16 @@ impl WebpageLoader {
             max_redirects: self.config.max_redirects,
             follow_redirects: true,
             credentials: false,
+            origin: None,
+            mode: RequestMode::Navigate,
+            destination: RequestDestination::Document,
+            signal: Some(self.abort_token.clone()),
         };
         
         // Make real HTTP request
         let response = self.http_client.send_request(request).await?;
         self.metrics.fetch_time = start_time.elapsed();
+        self.current_url = Some(response.url.clone());
         
         println!("📥 Fetched {} bytes in {:?}", response.body.len(), self.metrics.fetch_time);
         println!("🌐 Final URL: {}", response.url);
@@ -194,7 +305,7 @@ impl WebpageLoader {
     }
     
     /// Parse HTML content into DOM
-    fn parse_html(&mut self, html_content: &str) -> Result<Document, Box<dyn std::error::Error>> {
+    fn parse_html(&mut self, html_content: &str) -> EngineResult<Document> {
         let start_time = Instant::now();
         
         // Convert string to bytes for the new parser
@@ -215,37 +326,64 @@ impl WebpageLoader {
     }
     
     /// Extract and parse CSS from the document
-    pub async fn extract_and_parse_css(&mut self, document: &Document) -> Result<(), Box<dyn std::error::Error>> {
+    pub async fn extract_and_parse_css(&mut self, document: &Document) -> EngineResult<()> {
         let start_time = Instant::now();
         
-        // Extract inline styles
+        // Extract inline styles, whose `url()` values are relative to the
+        // document
         let inline_styles = extract_inline_styles(document);
-        for style_content in inline_styles {
-            let stylesheet = parse_css(&style_content);
+        let resolver = UrlResolver::for_document(document);
+        for (style_content, media) in inline_styles {
+            let mut stylesheet = parse_css(&style_content);
+            stylesheet.set_media(media);
+            stylesheet.resolve_urls(|url| resolver.resolve(url).map(String::from));
             self.css_engine.add_stylesheet(stylesheet);
         }
         
-        // Process external stylesheets found by the parser
-        let stylesheet_urls: Vec<String> = self.external_resources
+        // Process external stylesheets found by the parser, skipping those
+        // whose URL does not resolve
+        let stylesheets: Vec<LinkedStylesheet> = self.external_resources
             .iter()
             .filter(|resource| matches!(resource.resource_type, html_parser::ResourceType::Stylesheet))
-            .map(|resource| resource.url.clone())
+            .filter_map(|resource| {
+                let url = resolver.resolve(&resource.url)?;
+                Some(LinkedStylesheet {
+                    url: url.to_string(),
+                    integrity: resource.attributes.get("integrity").cloned(),
+                    media: resource.attributes.get("media").map(|media| MediaQueryList::parse(media)).filter(|media| !media.queries().is_empty()),
+                    in_head: resource.in_head,
+                })
+            })
             .collect();
-            
-        for url in stylesheet_urls {
-            match self.fetch_css(&url).await {
-                Ok(css_content) => {
-                    if let Err(e) = self.css_engine.add_stylesheet_from_url(&url, css_content) {
-                        println!("⚠️  Failed to parse CSS from {}: {}", url, e);
-                    } else {
-                        println!("🎨 Loaded external stylesheet: {}", url);
-                    }
-                }
-                Err(e) => {
-                    println!("⚠️  Failed to fetch CSS from {}: {}", url, e);
+        let (blocking, mut deferred) = render_blocking(&stylesheets, self.css_engine.media_features());
+        
+        // Wait for the render-blocking sheets until the timeout; the rest
+        // load after the first paint
+        let origin = document.origin();
+        let blocking_start = Instant::now();
+        let deadline = tokio::time::Instant::now() + self.config.render_blocking_timeout;
+        let mut fetched: Vec<Option<String>> = vec![None; stylesheets.len()];
+        for (position, &index) in blocking.iter().enumerate() {
+            match tokio::time::timeout_at(deadline, self.fetch_stylesheet(&stylesheets[index], &origin)).await {
+                Ok(result) => fetched[index] = result?,
+                Err(_) => {
+                    println!("⏱️  Render-blocking stylesheets timed out after {:?}, painting with partial styles", self.config.render_blocking_timeout);
+                    deferred.extend_from_slice(&blocking[position..]);
+                    break;
                 }
             }
         }
+        self.metrics.render_blocking_time = blocking_start.elapsed();
+        deferred.sort_unstable();
+        self.pending_stylesheets = deferred.into_iter().map(|index| stylesheets[index].clone()).collect();
+        
+        // Add in document order so the cascade does not depend on which
+        // sheet arrived first
+        for (stylesheet, css_content) in stylesheets.iter().zip(fetched) {
+            if let Some(css_content) = css_content {
+                self.add_linked_stylesheet(stylesheet, css_content);
+            }
+        }
         
         // Compute styles for all DOM nodes
         self.computed_styles = self.css_engine.compute_styles(document);
@@ -262,6 +400,85 @@ impl WebpageLoader {
         Ok(())
     }
     
+    /// Load the stylesheets that did not block the first paint and compute
+    /// styles again, returning whether any of them was added
+    /// 
+    /// Sheets whose `media` matches are fetched first. Like render-blocking
+    /// sheets, they are added in document order once all have arrived.
+    pub async fn load_pending_stylesheets(&mut self, document: &Document) -> EngineResult<bool> {
+        let mut pending = std::mem::take(&mut self.pending_stylesheets);
+        if pending.is_empty() {
+            return Ok(false);
+        }
+        let start_time = Instant::now();
+        let origin = document.origin();
+        let features = self.css_engine.media_features().clone();
+        let mut fetch_order: Vec<usize> = (0..pending.len()).collect();
+        fetch_order.sort_by_key(|&index| !pending[index].media.as_ref().is_none_or(|media| media.matches(&features)));
+        let mut fetched: Vec<Option<String>> = vec![None; pending.len()];
+        for index in fetch_order {
+            fetched[index] = self.fetch_stylesheet(&pending[index], &origin).await?;
+        }
+        let mut added = false;
+        for (stylesheet, css_content) in pending.drain(..).zip(fetched) {
+            if let Some(css_content) = css_content {
+                added |= self.add_linked_stylesheet(&stylesheet, css_content);
+            }
+        }
+        if added {
+            self.computed_styles = self.css_engine.compute_styles(document);
+            self.metrics.css_rules = self.css_engine.get_total_rules();
+            println!("🎨 Applied late stylesheets in {:?}", start_time.elapsed());
+        }
+        Ok(added)
+    }
+    
+    /// Fetch a linked stylesheet, reporting failures that do not stop the
+    /// load and giving `None` for them
+    async fn fetch_stylesheet(&mut self, stylesheet: &LinkedStylesheet, origin: &Origin) -> EngineResult<Option<String>> {
+        let url = &stylesheet.url;
+        match self.fetch_css(url, origin, stylesheet.integrity.as_deref()).await {
+            Ok(css_content) => Ok(Some(css_content)),
+            Err(NetworkError::RequestAborted) => {
+                Err(EngineError::from(NetworkError::RequestAborted).with_url(url.clone()))
+            }
+            Err(e @ (NetworkError::MixedContent { .. } | NetworkError::BlockedByClient { .. })) => {
+                self.console.report(ConsoleLevel::Error, e.to_string(), Some(url));
+                Ok(None)
+            }
+            Err(e @ NetworkError::IntegrityMismatch(_)) => {
+                self.console.report(ConsoleLevel::Error, format!("Blocked stylesheet from '{}': {}", url, e), Some(url));
+                Ok(None)
+            }
+            Err(e) => {
+                println!("⚠️  Failed to fetch CSS from {}: {}", url, e);
+                Ok(None)
+            }
+        }
+    }
+    
+    /// Parse a fetched stylesheet and add it to the cascade, returning
+    /// whether it parsed
+    fn add_linked_stylesheet(&mut self, linked: &LinkedStylesheet, css_content: String) -> bool {
+        let url = &linked.url;
+        match CSSParser::new(css_content).parse_stylesheet() {
+            Ok(mut stylesheet) => {
+                // `url()` values are relative to the stylesheet
+                if let Some(resolver) = UrlResolver::with_base(url) {
+                    stylesheet.resolve_urls(|reference| resolver.resolve(reference).map(String::from));
+                }
+                stylesheet.set_media(linked.media.clone());
+                self.css_engine.add_parsed_stylesheet_from_url(url, stylesheet);
+                println!("🎨 Loaded external stylesheet: {}", url);
+                true
+            }
+            Err(e) => {
+                println!("⚠️  Failed to parse CSS from {}: {}", url, e);
+                false
+            }
+        }
+    }
+    
     /// Log computed styles for debugging
     fn log_computed_styles(&self, document: &Document) {
         println!("🎨 Computed Styles Summary:");
@@ -295,8 +512,8 @@ impl WebpageLoader {
                         println!("{}  height: {}", indent, height);
                     }
                 }
-                NodeType::Text(content) => {
-                    println!("{}{:?}", indent, content);
+                NodeType::Text(_) => {
+                    println!("{}{:?}", indent, node.text_content());
                 }
                 _ => {}
             }
@@ -309,7 +526,10 @@ impl WebpageLoader {
     }
     
     /// Fetch external CSS file
-    async fn fetch_css(&mut self, url: &str) -> Result<String, NetworkError> {
+    /// 
+    /// With `integrity`, the stylesheet is only returned if its bytes match
+    /// the metadata.
+    async fn fetch_css(&mut self, url: &str, origin: &Origin, integrity: Option<&str>) -> Result<String, NetworkError> {
         let request = HttpRequest {
             method: networking::HttpMethod::GET,
             url: url.to_string(),
@@ -324,16 +544,27 @@ impl WebpageLoader {
             max_redirects: self.config.max_redirects,
             follow_redirects: true,
             credentials: false,
+            origin: Some(origin.clone()),
+            // Stylesheets load in no-cors mode like in other browsers
+            mode: RequestMode::NoCors,
+            destination: RequestDestination::Style,
+            signal: Some(self.abort_token.clone()),
         };
         
         // Make real HTTP request for CSS
         let response = self.http_client.send_request(request).await?;
+        if let Some(timing) = &response.timing {
+            self.performance.record_resource(url, "link", timing, response.body.len(), response.status_code);
+        }
         println!("🎨 Fetched CSS: {} bytes from {}", response.body.len(), response.url);
+        if let Some(integrity) = integrity {
+            IntegrityMetadata::parse(integrity).verify(&response.body)?;
+        }
         Ok(String::from_utf8(response.body).map_err(|e| networking::NetworkError::ParseError(e.to_string()))?)
     }
     
     /// Compute styles and perform layout
-    fn compute_layout(&mut self, document: &Document) -> Result<LayoutBox, Box<dyn std::error::Error>> {
+    fn compute_layout(&mut self, document: &Document) -> EngineResult<LayoutBox> {
         let start_time = Instant::now();
         
         // Create layout engine
@@ -349,7 +580,7 @@ impl WebpageLoader {
     }
     
     /// Execute JavaScript in the document
-    async fn execute_javascript(&mut self, document: &Document) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    async fn execute_javascript(&mut self, document: &Document) -> EngineResult<Vec<String>> {
         let start_time = Instant::now();
         let mut results = Vec::new();
         
@@ -370,7 +601,7 @@ impl WebpageLoader {
     }
     
     /// Render the webpage with GPU
-    async fn render_webpage(&mut self, _layout_tree: &LayoutBox) -> Result<RenderResult, Box<dyn std::error::Error>> {
+    async fn render_webpage(&mut self, layout_tree: &LayoutBox) -> EngineResult<RenderResult> {
         let start_time = Instant::now();
         
         // Simulate GPU rendering (always succeeds now)
@@ -382,6 +613,11 @@ impl WebpageLoader {
         self.metrics.render_time = start_time.elapsed();
         println!("🎨 Rendered webpage in {:?}", self.metrics.render_time);
         
+        self.performance.record_paint(layout_tree);
+        let since_origin = |ms: f64| Duration::from_secs_f64(ms / 1000.0);
+        self.metrics.first_paint = self.performance.first_paint().map(since_origin);
+        self.metrics.first_contentful_paint = self.performance.first_contentful_paint().map(since_origin);
+        
         Ok(RenderResult {
             width: 800,
             height: 600,
@@ -405,6 +641,10 @@ impl WebpageLoader {
             println!("CSS rules:         {}", self.metrics.css_rules);
             println!("Layout boxes:      {}", self.metrics.layout_boxes);
             println!("JS statements:     {}", self.metrics.js_statements);
+            println!("First paint:       {:?}", self.metrics.first_paint);
+            println!("First contentful:  {:?}", self.metrics.first_contentful_paint);
+            println!("Render blocking:   {:?}", self.metrics.render_blocking_time);
+            println!("Resources:         {}", self.performance.entries_by_type(EntryType::Resource).len());
         }
     }
     
@@ -412,6 +652,17 @@ impl WebpageLoader {
     pub fn get_metrics(&self) -> &PerformanceMetrics {
         &self.metrics
     }
+    
+    /// Get the resource and paint timings of the current load
+    pub fn performance(&self) -> &PerformanceTimeline {
+        &self.performance
+    }
+    
+    /// Get the console messages about the current load, such as
+    /// stylesheets blocked by an integrity check
+    pub fn console(&self) -> &ConsoleSink {
+        &self.console
+    }
 }
 
 /// Result of webpage loading
@@ -455,22 +706,53 @@ fn count_layout_boxes(layout_box: &LayoutBox) -> usize {
     count
 }
 
-fn extract_inline_styles(document: &Document) -> Vec<String> {
+/// A stylesheet linked from the document
+#[derive(Debug, Clone)]
+struct LinkedStylesheet {
+    /// Resolved URL of the sheet
+    url: String,
+    integrity: Option<String>,
+    media: Option<MediaQueryList>,
+    /// Whether the link is in the document's head
+    in_head: bool,
+}
+
+/// Split linked stylesheets into those that block the first paint and
+/// those that load after it, as indices in document order
+/// 
+/// Sheets linked from the head whose `media` matches the environment block
+/// rendering, though not parsing. Sheets linked from the body apply when
+/// they arrive, and those whose media does not match, such as print
+/// sheets, are fetched at low priority.
+fn render_blocking(stylesheets: &[LinkedStylesheet], features: &MediaFeatures) -> (Vec<usize>, Vec<usize>) {
+    let matches = |stylesheet: &LinkedStylesheet| stylesheet.media.as_ref().is_none_or(|media| media.matches(features));
+    let (blocking, deferred): (Vec<usize>, Vec<usize>) = (0..stylesheets.len())
+        .partition(|&index| stylesheets[index].in_head && matches(&stylesheets[index]));
+    for &index in &deferred {
+        let stylesheet = &stylesheets[index];
+        if !matches(stylesheet) {
+            println!("📌 Deferred stylesheet {} (media: {})", stylesheet.url, stylesheet.media.as_ref().map(MediaQueryList::text).unwrap_or_default());
+        }
+    }
+    (blocking, deferred)
+}
+
+fn extract_inline_styles(document: &Document) -> Vec<(String, Option<MediaQueryList>)> {
     let mut styles = Vec::new();
     
-    fn extract_recursive(node: &Node, styles: &mut Vec<String>) {
+    fn extract_recursive(node: &Node, styles: &mut Vec<(String, Option<MediaQueryList>)>) {
         match &node.node_type {
             NodeType::Element { tag_name, .. } => {
                 if tag_name == "style" {
                     // Extract text content from style tag
                     let mut style_content = String::new();
                     for child in node.children.borrow().iter() {
-                        if let NodeType::Text(text) = &child.node_type {
-                            style_content.push_str(text);
+                        if let Some(text) = child.character_data() {
+                            style_content.push_str(&text);
                         }
                     }
                     if !style_content.trim().is_empty() {
-                        styles.push(style_content);
+                        styles.push((style_content, css_parser::stylesheet_media(node)));
                     }
                 }
             }
@@ -501,3 +783,72 @@ fn extract_inline_scripts(_document: &Document) -> Vec<String> {
     scripts
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use networking::{HttpMethod, InterceptedResponse, NetworkResult};
+    use url::Url;
+    
+    /// Serves fixed bodies, each after a delay
+    struct SlowServer(HashMap<&'static str, (&'static str, Duration)>);
+    
+    impl RequestInterceptor for SlowServer {
+        fn intercept(&self, _method: &HttpMethod, url: &Url) -> Option<NetworkResult<InterceptedResponse>> {
+            let (body, delay) = self.0.get(url.as_str())?;
+            Some(Ok(InterceptedResponse {
+                status_code: 200,
+                headers: HashMap::new(),
+                body: body.as_bytes().to_vec(),
+                url: url.to_string(),
+                delay: *delay,
+            }))
+        }
+    }
+    
+    async fn load(resources: &[(&'static str, &'static str, u64)], render_blocking_timeout: Duration) -> WebpageLoader {
+        let server = resources.iter().map(|&(url, body, delay)| (url, (body, Duration::from_millis(delay)))).collect();
+        let mut loader = WebpageLoader::new(WebpageLoaderConfig { render_blocking_timeout, record_metrics: false, ..WebpageLoaderConfig::default() });
+        loader.set_request_interceptor(Arc::new(SlowServer(server)));
+        loader.load_webpage("http://example.com/").await.unwrap();
+        loader
+    }
+    
+    #[tokio::test]
+    async fn test_only_head_stylesheets_block_first_paint() {
+        let page = r#"<html><head><link rel="stylesheet" href="/head.css"><link rel="stylesheet" href="/print.css" media="print"></head>
+            <body><p>Text</p><link rel="stylesheet" href="/body.css"></body></html>"#;
+        let loader = load(&[
+            ("http://example.com/", page, 0),
+            ("http://example.com/head.css", "p { color: #aa2200; }", 100),
+            ("http://example.com/print.css", "p { color: #bb3300; }", 600),
+            ("http://example.com/body.css", "p { margin: 4px; }", 600),
+        ], Duration::from_secs(5)).await;
+        
+        // The first paint waited for the head sheet only, and the late
+        // sheets applied afterwards
+        let metrics = loader.get_metrics();
+        assert!(metrics.render_blocking_time >= Duration::from_millis(100));
+        assert!(metrics.render_blocking_time < Duration::from_millis(600));
+        assert!(metrics.first_paint.unwrap() < Duration::from_millis(600));
+        assert!(metrics.total_time >= Duration::from_millis(600));
+        assert_eq!(metrics.css_rules, 3);
+    }
+    
+    #[tokio::test]
+    async fn test_render_blocking_timeout_paints_with_partial_styles() {
+        let page = r#"<html><head><link rel="stylesheet" href="/fast.css"><link rel="stylesheet" href="/slow.css"></head><body><p>Text</p></body></html>"#;
+        let loader = load(&[
+            ("http://example.com/", page, 0),
+            ("http://example.com/fast.css", "p { color: #aa2200; }", 0),
+            ("http://example.com/slow.css", "p { margin: 4px; }", 800),
+        ], Duration::from_millis(100)).await;
+        
+        let metrics = loader.get_metrics();
+        assert!(metrics.render_blocking_time >= Duration::from_millis(100));
+        assert!(metrics.render_blocking_time < Duration::from_millis(800));
+        assert!(metrics.first_paint.unwrap() < Duration::from_millis(800));
+        // The sheet that timed out still loaded
+        assert_eq!(metrics.css_rules, 2);
+    }
+}
@@ -0,0 +1,260 @@
+//! # Page Zoom
+//!
+//! This module holds the zoom levels the user picked for each site and the
+//! minimum font size pages are shown with, and persists both in the profile
+//! directory. Zooming a page scales CSS pixels: the page is laid out in a
+//! viewport that many times smaller, `@media` rules see that viewport, and
+//! it is painted at that many device pixels per CSS pixel.
+//!
+//! ## Design Principles
+//!
+//! 1. **Per Origin**: A zoom level is remembered for the origin of the
+//!    page, so every page of `example.com` opens at the level last picked
+//!    on any of them. Pages without an origin, such as `file:` and
+//!    internal pages, are zoomed but not remembered.
+//! 2. **Only Changes Are Saved**: Setting a site back to 100% forgets it,
+//!    so the file lists exactly the sites that differ from the default.
+//! 3. **Shared by Tabs**: A store is shared by cloning, like the visited
+//!    links, so zooming a site in one tab applies to its pages in every
+//!    tab opened afterwards.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use css_parser::media::MediaFeatures;
+use dom::events::KeyboardEventInit;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Name of the zoom preferences file in the profile directory
+pub const ZOOM_FILE: &str = "zoom.json";
+
+/// Zoom levels `zoom in` and `zoom out` step through
+pub const ZOOM_LEVELS: [f32; 17] = [0.25, 0.33, 0.5, 0.67, 0.75, 0.8, 0.9, 1.0, 1.1, 1.25, 1.5, 1.75, 2.0, 2.5, 3.0, 4.0, 5.0];
+
+/// The zoom level of sites the user has not zoomed
+pub const DEFAULT_ZOOM: f32 = 1.0;
+
+/// Get the next larger preset zoom level, or the largest
+pub fn zoom_in(level: f32) -> f32 {
+    ZOOM_LEVELS.iter().copied().find(|&preset| preset > level + f32::EPSILON).unwrap_or(ZOOM_LEVELS[ZOOM_LEVELS.len() - 1])
+}
+
+/// Get the next smaller preset zoom level, or the smallest
+pub fn zoom_out(level: f32) -> f32 {
+    ZOOM_LEVELS.iter().rev().copied().find(|&preset| preset < level - f32::EPSILON).unwrap_or(ZOOM_LEVELS[0])
+}
+
+/// Clamp a zoom level to the range of the presets
+pub fn clamp_zoom(level: f32) -> f32 {
+    level.clamp(ZOOM_LEVELS[0], ZOOM_LEVELS[ZOOM_LEVELS.len() - 1])
+}
+
+/// Format a zoom level as a percentage, e.g. `125%`
+pub fn format_zoom(level: f32) -> String {
+    format!("{}%", (level * 100.0).round())
+}
+
+/// Get the environment a page zoomed to `level` is styled for
+///
+/// The viewport shrinks by the zoom level, since each CSS pixel covers
+/// more of the window.
+pub fn zoomed_media_features(features: &MediaFeatures, level: f32) -> MediaFeatures {
+    MediaFeatures {
+        viewport_width: features.viewport_width / level,
+        viewport_height: features.viewport_height / level,
+        ..features.clone()
+    }
+}
+
+/// A zoom action bound to a key combination
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZoomShortcut {
+    /// Ctrl+= / Cmd+=, also with Shift for Ctrl++
+    ZoomIn,
+    /// Ctrl+- / Cmd+-
+    ZoomOut,
+    /// Ctrl+0 / Cmd+0
+    Reset,
+}
+
+/// The zoom action triggered by a key combination, if any
+///
+/// Accepts both Control (Windows, Linux) and Meta (macOS) shortcuts.
+pub fn zoom_shortcut(init: &KeyboardEventInit) -> Option<ZoomShortcut> {
+    if !(init.ctrl_key || init.meta_key) || init.alt_key {
+        return None;
+    }
+    match init.key.as_str() {
+        "=" | "+" => Some(ZoomShortcut::ZoomIn),
+        "-" => Some(ZoomShortcut::ZoomOut),
+        "0" => Some(ZoomShortcut::Reset),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ZoomPreferences {
+    /// Zoom levels of origins that are not at 100%
+    #[serde(default)]
+    levels: BTreeMap<String, f32>,
+    /// Smallest font size in pixels, or 0 for none
+    #[serde(default)]
+    minimum_font_size: f32,
+}
+
+#[derive(Debug, Default)]
+struct ZoomState {
+    preferences: ZoomPreferences,
+    /// File the preferences are saved to, if the store is persistent
+    path: Option<PathBuf>,
+}
+
+/// Zoom levels of sites and the minimum font size
+///
+/// Cloning shares the store.
+#[derive(Debug, Clone, Default)]
+pub struct ZoomStore {
+    state: Arc<Mutex<ZoomState>>,
+}
+
+impl ZoomStore {
+    /// Create an empty store that is not saved anywhere
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open the store saved in `dir`, creating an empty one if there is none
+    /// yet
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let path = dir.as_ref().join(ZOOM_FILE);
+        let preferences = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => ZoomPreferences::default(),
+            Err(e) => return Err(e),
+        };
+        Ok(ZoomStore { state: Arc::new(Mutex::new(ZoomState { preferences, path: Some(path) })) })
+    }
+
+    /// Get the zoom level of the site `url` belongs to
+    pub fn zoom_for(&self, url: &Url) -> f32 {
+        zoom_key(url)
+            .and_then(|key| self.lock().preferences.levels.get(&key).copied())
+            .unwrap_or(DEFAULT_ZOOM)
+    }
+
+    /// Remember the zoom level of the site `url` belongs to
+    ///
+    /// Returns whether the level was remembered; sites without an origin
+    /// are not.
+    pub fn set_zoom(&self, url: &Url, level: f32) -> io::Result<bool> {
+        let Some(key) = zoom_key(url) else {
+            return Ok(false);
+        };
+        let mut state = self.lock();
+        if (level - DEFAULT_ZOOM).abs() < f32::EPSILON {
+            state.preferences.levels.remove(&key);
+        } else {
+            state.preferences.levels.insert(key, clamp_zoom(level));
+        }
+        state.save()?;
+        Ok(true)
+    }
+
+    /// Get the zoom levels of all zoomed sites, by origin
+    pub fn levels(&self) -> Vec<(String, f32)> {
+        self.lock().preferences.levels.iter().map(|(origin, level)| (origin.clone(), *level)).collect()
+    }
+
+    /// Get the smallest font size in pixels pages are shown with, or 0
+    pub fn minimum_font_size(&self) -> f32 {
+        self.lock().preferences.minimum_font_size
+    }
+
+    /// Set the smallest font size in pixels pages are shown with, 0 for
+    /// none
+    pub fn set_minimum_font_size(&self, size: f32) -> io::Result<()> {
+        let mut state = self.lock();
+        state.preferences.minimum_font_size = size.max(0.0);
+        state.save()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ZoomState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl ZoomState {
+    fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let contents = serde_json::to_string_pretty(&self.preferences).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let temporary = path.with_extension("json.tmp");
+        fs::write(&temporary, contents)?;
+        fs::rename(&temporary, path)
+    }
+}
+
+/// Get the key a site's zoom level is kept under, its origin
+fn zoom_key(url: &Url) -> Option<String> {
+    let origin = url.origin();
+    origin.is_tuple().then(|| origin.ascii_serialization())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{find_box, temporary_profile};
+    use crate::BrowserEngine;
+
+    #[test]
+    fn test_zoom_levels_persist_per_origin() {
+        assert_eq!(zoom_in(1.0), 1.1);
+        assert_eq!(zoom_in(1.1), 1.25);
+        assert_eq!(zoom_out(1.2), 1.1);
+        assert_eq!(zoom_out(0.25), 0.25);
+        assert_eq!(format_zoom(1.25), "125%");
+        let mut init = KeyboardEventInit::new("=", "Equal");
+        assert_eq!(zoom_shortcut(&init), None);
+        init.meta_key = true;
+        assert_eq!(zoom_shortcut(&init), Some(ZoomShortcut::ZoomIn));
+
+        let dir = temporary_profile("zoom");
+        let store = ZoomStore::open(&dir).unwrap();
+        let article = Url::parse("https://example.com/articles/1").unwrap();
+        assert!(store.set_zoom(&article, 1.25).unwrap());
+        assert!(!store.set_zoom(&Url::parse("file:///tmp/page.html").unwrap(), 2.0).unwrap());
+        store.set_minimum_font_size(14.0).unwrap();
+
+        // Every page of the site opens at its level, in a new session too
+        let reopened = ZoomStore::open(&dir).unwrap();
+        assert_eq!(reopened.zoom_for(&Url::parse("https://example.com/").unwrap()), 1.25);
+        assert_eq!(reopened.zoom_for(&Url::parse("https://example.org/").unwrap()), DEFAULT_ZOOM);
+        assert_eq!(reopened.minimum_font_size(), 14.0);
+
+        // The engine lays the page out in a smaller viewport and raises
+        // small text
+        let mut engine = BrowserEngine::new();
+        engine.set_zoom_store(reopened.clone());
+        assert!(engine.load_html("<html><body><p style=\"font-size: 9px\">Fine print</p></body></html>"));
+        assert!(engine.load_css("p { margin: 0 }"));
+        assert!(engine.set_zoom_level(2.0));
+        assert_eq!(engine.zoom_level(), 2.0);
+        assert!(engine.perform_layout());
+        let layout = engine.get_layout().unwrap();
+        assert_eq!(layout.content.width, 400.0);
+        let paragraph = engine.get_document().unwrap().root.query_selector("p").unwrap().unwrap();
+        assert_eq!(find_box(layout, paragraph.id).unwrap().styles.font.size, Some(14.0));
+
+        // Back at 100% the site is forgotten
+        assert!(reopened.set_zoom(&article, DEFAULT_ZOOM).unwrap());
+        assert!(ZoomStore::open(&dir).unwrap().levels().is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,16 @@
+//! Fuzz `CSSParser::parse_stylesheet` and the line-based `parse_css`
+//!
+//! Run from `css_parser/` with the pathological seeds:
+//! `cargo fuzz run parser fuzz/corpus/parser fuzz/seeds`
+
+#![no_main]
+
+use css_parser::{parse_css, CSSParser};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let input = String::from_utf8_lossy(data).into_owned();
+    let stylesheet = CSSParser::new(input.clone()).parse_stylesheet();
+    assert!(stylesheet.is_ok(), "parse_stylesheet recovers from any input");
+    parse_css(&input);
+});
@@ -0,0 +1,25 @@
+//! Fuzz `CSSTokenizer::next_token`
+//!
+//! Run from `css_parser/` with the pathological seeds:
+//! `cargo fuzz run tokenizer fuzz/corpus/tokenizer fuzz/seeds`
+
+#![no_main]
+
+use css_parser::{CSSToken, CSSTokenizer};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let input = String::from_utf8_lossy(data).into_owned();
+    let length = input.chars().count();
+    let mut tokenizer = CSSTokenizer::new(input);
+    // Every token consumes a character, so the end is reached in bounded steps
+    for _ in 0..=length {
+        let peeked = tokenizer.peek_token();
+        let token = tokenizer.next_token();
+        assert_eq!(peeked, token, "peek_token disagrees with next_token");
+        if token == CSSToken::Eof {
+            return;
+        }
+    }
+    panic!("tokenizer did not reach the end of {} characters", length);
+});
@@ -0,0 +1,296 @@
+//! # Colors
+//!
+//! This module parses CSS colors into one `Color` type: hex colors, named
+//! colors, `rgb()` and `hsl()` in both their comma and space separated
+//! forms, and `color()` in the sRGB, linear sRGB and Display P3 spaces.
+//!
+//! ## Design Principles
+//!
+//! 1. **sRGB at Rest**: A `Color` holds gamma-encoded sRGB with straight
+//!    alpha, as CSS specifies colors, so parsing never loses precision to
+//!    a conversion nobody asked for.
+//! 2. **Linear for Math**: Blending and interpolation convert to linear
+//!    light first with `to_linear`, and back with `from_linear`; mixing
+//!    encoded values darkens every edge.
+//! 3. **Fall Back, Don't Fail**: Colors outside sRGB, such as saturated
+//!    Display P3 ones, are converted and clipped to the nearest sRGB color
+//!    instead of being dropped, since the renderer only outputs sRGB.
+
+use std::fmt;
+
+/// A color in the sRGB space with straight alpha, each channel 0 to 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+/// Linear Display P3 to linear sRGB
+const P3_TO_SRGB: [[f32; 3]; 3] = [
+    [1.224_940_2, -0.224_940_4, 0.0],
+    [-0.042_056_955, 1.042_057_1, 0.0],
+    [-0.019_637_555, -0.078_636_05, 1.098_273_6],
+];
+
+/// Decode one sRGB channel to linear light
+pub fn srgb_to_linear(channel: f32) -> f32 {
+    if channel <= 0.04045 {
+        channel / 12.92
+    } else {
+        ((channel + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encode one linear channel as sRGB
+pub fn linear_to_srgb(channel: f32) -> f32 {
+    if channel <= 0.003_130_8 {
+        channel * 12.92
+    } else {
+        1.055 * channel.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+impl Color {
+    pub const TRANSPARENT: Color = Color { r: 0.0, g: 0.0, b: 0.0, a: 0.0 };
+    pub const BLACK: Color = Color { r: 0.0, g: 0.0, b: 0.0, a: 1.0 };
+
+    pub fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Color { r, g, b, a }
+    }
+
+    /// Parse a CSS color
+    ///
+    /// Returns `None` for invalid colors and for keywords that depend on
+    /// the element, such as `currentcolor`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim().to_ascii_lowercase();
+        if let Some(hex) = text.strip_prefix('#') {
+            return parse_hex(hex);
+        }
+        if let Some((name, arguments)) = text.strip_suffix(')').and_then(|text| text.split_once('(')) {
+            return match name.trim() {
+                "rgb" | "rgba" => parse_rgb(arguments),
+                "hsl" | "hsla" => parse_hsl(arguments),
+                "color" => parse_color_function(arguments),
+                _ => None,
+            };
+        }
+        named_color(&text)
+    }
+
+    /// Get the channels as `[r, g, b, a]`
+    pub fn to_array(&self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+
+    /// Get the channels in linear light as `[r, g, b, a]`
+    pub fn to_linear(&self) -> [f32; 4] {
+        [srgb_to_linear(self.r), srgb_to_linear(self.g), srgb_to_linear(self.b), self.a]
+    }
+
+    /// Create a color from linear channels, clipping them to sRGB
+    pub fn from_linear(linear: [f32; 4]) -> Self {
+        let [r, g, b] = [linear[0], linear[1], linear[2]].map(|channel| linear_to_srgb(channel.clamp(0.0, 1.0)));
+        Color { r, g, b, a: linear[3].clamp(0.0, 1.0) }
+    }
+
+    /// Composite this color over a backdrop in linear light
+    pub fn over(&self, backdrop: Color) -> Color {
+        let (source, below) = (self.to_linear(), backdrop.to_linear());
+        let alpha = self.a + backdrop.a * (1.0 - self.a);
+        if alpha == 0.0 {
+            return Color::TRANSPARENT;
+        }
+        let channel = |index: usize| (source[index] * self.a + below[index] * backdrop.a * (1.0 - self.a)) / alpha;
+        Color::from_linear([channel(0), channel(1), channel(2), alpha])
+    }
+}
+
+impl fmt::Display for Color {
+    /// Serialize as `rgb()` or `rgba()`, as `getComputedStyle` does
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [r, g, b] = [self.r, self.g, self.b].map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+        if self.a >= 1.0 {
+            write!(f, "rgb({}, {}, {})", r, g, b)
+        } else {
+            write!(f, "rgba({}, {}, {}, {})", r, g, b, (self.a.clamp(0.0, 1.0) * 1000.0).round() / 1000.0)
+        }
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<Color> {
+    let digits: Vec<f32> = hex.chars().map(|c| c.to_digit(16).map(|digit| digit as f32)).collect::<Option<_>>()?;
+    let channels: Vec<f32> = match digits.len() {
+        3 | 4 => digits.iter().map(|digit| digit / 15.0).collect(),
+        6 | 8 => digits.chunks(2).map(|pair| (pair[0] * 16.0 + pair[1]) / 255.0).collect(),
+        _ => return None,
+    };
+    Some(Color::new(channels[0], channels[1], channels[2], channels.get(3).copied().unwrap_or(1.0)))
+}
+
+/// Split function arguments into components and an optional alpha
+///
+/// Accepts the legacy comma form, `1, 2, 3, 0.5`, and the modern space
+/// form, `1 2 3 / 0.5`; the two cannot be mixed.
+fn split_arguments(arguments: &str) -> Option<(Vec<&str>, Option<&str>)> {
+    if arguments.contains(',') {
+        if arguments.contains('/') {
+            return None;
+        }
+        let mut parts: Vec<&str> = arguments.split(',').map(str::trim).collect();
+        let alpha = if parts.len() == 4 { parts.pop() } else { None };
+        return (parts.len() == 3).then_some((parts, alpha));
+    }
+    let (components, alpha) = match arguments.split_once('/') {
+        Some((components, alpha)) => (components, Some(alpha.trim())),
+        None => (arguments, None),
+    };
+    Some((components.split_whitespace().collect(), alpha))
+}
+
+/// Parse a number or percentage; `percent` is what 100% maps to
+fn parse_number(text: &str, percent: f32) -> Option<f32> {
+    if text == "none" {
+        return Some(0.0);
+    }
+    let value = match text.strip_suffix('%') {
+        Some(number) => number.parse::<f32>().ok()? / 100.0 * percent,
+        None => text.parse::<f32>().ok()?,
+    };
+    value.is_finite().then_some(value)
+}
+
+fn parse_alpha(alpha: Option<&str>) -> Option<f32> {
+    alpha.map_or(Some(1.0), |alpha| parse_number(alpha, 1.0)).map(|alpha| alpha.clamp(0.0, 1.0))
+}
+
+fn parse_rgb(arguments: &str) -> Option<Color> {
+    let (components, alpha) = split_arguments(arguments)?;
+    let [r, g, b] = <[&str; 3]>::try_from(components).ok()?.map(|component| parse_number(component, 255.0));
+    let [r, g, b] = [r?, g?, b?].map(|channel| (channel / 255.0).clamp(0.0, 1.0));
+    Some(Color::new(r, g, b, parse_alpha(alpha)?))
+}
+
+/// Parse a hue in degrees, turns, radians or gradians
+fn parse_hue(text: &str) -> Option<f32> {
+    let (number, scale) = if let Some(number) = text.strip_suffix("deg") {
+        (number, 1.0)
+    } else if let Some(number) = text.strip_suffix("grad") {
+        (number, 0.9)
+    } else if let Some(number) = text.strip_suffix("rad") {
+        (number, 180.0 / std::f32::consts::PI)
+    } else if let Some(number) = text.strip_suffix("turn") {
+        (number, 360.0)
+    } else {
+        (text, 1.0)
+    };
+    parse_number(number, 1.0).map(|hue| (hue * scale).rem_euclid(360.0))
+}
+
+fn parse_hsl(arguments: &str) -> Option<Color> {
+    let (components, alpha) = split_arguments(arguments)?;
+    let [hue, saturation, lightness] = <[&str; 3]>::try_from(components).ok()?;
+    let hue = parse_hue(hue)?;
+    let [saturation, lightness] = [saturation, lightness].map(|component| parse_number(component, 100.0));
+    let (saturation, lightness) = ((saturation? / 100.0).clamp(0.0, 1.0), (lightness? / 100.0).clamp(0.0, 1.0));
+
+    let channel = |n: f32| {
+        let k = (n + hue / 30.0) % 12.0;
+        let amount = saturation * lightness.min(1.0 - lightness);
+        lightness - amount * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0)
+    };
+    Some(Color::new(channel(0.0), channel(8.0), channel(4.0), parse_alpha(alpha)?))
+}
+
+/// Parse the arguments of `color()`, converting to sRGB
+fn parse_color_function(arguments: &str) -> Option<Color> {
+    let (components, alpha) = match arguments.split_once('/') {
+        Some((components, alpha)) => (components, Some(alpha.trim())),
+        None => (arguments, None),
+    };
+    let mut components = components.split_whitespace();
+    let space = components.next()?;
+    let values: Vec<f32> = components.map(|component| parse_number(component, 1.0)).collect::<Option<_>>()?;
+    let [r, g, b] = <[f32; 3]>::try_from(values).ok()?;
+    let alpha = parse_alpha(alpha)?;
+    match space {
+        "srgb" => Some(Color::new(r.clamp(0.0, 1.0), g.clamp(0.0, 1.0), b.clamp(0.0, 1.0), alpha)),
+        "srgb-linear" => Some(Color::from_linear([r, g, b, alpha])),
+        "display-p3" => {
+            // Display P3 shares the sRGB transfer function
+            let linear = [r, g, b].map(srgb_to_linear);
+            let [r, g, b] = P3_TO_SRGB.map(|row| row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2]);
+            Some(Color::from_linear([r, g, b, alpha]))
+        }
+        _ => None,
+    }
+}
+
+fn named_color(name: &str) -> Option<Color> {
+    let rgb = |r: u8, g: u8, b: u8| Some(Color::new(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0));
+    match name {
+        "transparent" => Some(Color::TRANSPARENT),
+        "black" => rgb(0, 0, 0),
+        "white" => rgb(255, 255, 255),
+        "red" => rgb(255, 0, 0),
+        "lime" => rgb(0, 255, 0),
+        "green" => rgb(0, 128, 0),
+        "blue" => rgb(0, 0, 255),
+        "yellow" => rgb(255, 255, 0),
+        "cyan" | "aqua" => rgb(0, 255, 255),
+        "magenta" | "fuchsia" => rgb(255, 0, 255),
+        "gray" | "grey" => rgb(128, 128, 128),
+        "silver" => rgb(192, 192, 192),
+        "lightgray" | "lightgrey" => rgb(211, 211, 211),
+        "darkgray" | "darkgrey" => rgb(169, 169, 169),
+        "maroon" => rgb(128, 0, 0),
+        "olive" => rgb(128, 128, 0),
+        "navy" => rgb(0, 0, 128),
+        "purple" => rgb(128, 0, 128),
+        "teal" => rgb(0, 128, 128),
+        "orange" => rgb(255, 165, 0),
+        "pink" => rgb(255, 192, 203),
+        "brown" => rgb(165, 42, 42),
+        "gold" => rgb(255, 215, 0),
+        "indigo" => rgb(75, 0, 130),
+        "violet" => rgb(238, 130, 238),
+        "rebeccapurple" => rgb(102, 51, 153),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgba(text: &str) -> [u8; 4] {
+        Color::parse(text).unwrap().to_array().map(|channel| (channel * 255.0).round() as u8)
+    }
+
+    #[test]
+    fn test_modern_color_syntax() {
+        assert_eq!(rgba("#0f08"), [0, 255, 0, 136]);
+        assert_eq!(rgba("rgb(10 20 30 / 50%)"), [10, 20, 30, 128]);
+        assert_eq!(rgba("rgba(10, 20, 30, 0.5)"), [10, 20, 30, 128]);
+        assert_eq!(rgba("rgb(100% 0% 50%)"), [255, 0, 128, 255]);
+        assert!(Color::parse("rgb(1, 2 3)").is_none() && Color::parse("rgb(1, 2, 3 / 1)").is_none());
+
+        assert_eq!(rgba("hsl(120deg 100% 25% / 0.5)"), [0, 128, 0, 128]);
+        assert_eq!(rgba("hsla(0.5turn, 100%, 50%, 1)"), [0, 255, 255, 255]);
+        assert_eq!(rgba("RebeccaPurple"), [102, 51, 153, 255]);
+        assert!(Color::parse("currentcolor").is_none());
+
+        // Display P3 red is outside sRGB and clips to sRGB red
+        assert_eq!(rgba("color(display-p3 1 0 0)"), [255, 0, 0, 255]);
+        assert_eq!(rgba("color(display-p3 0.4 0.4 0.4 / 0.25)"), [102, 102, 102, 64]);
+        assert_eq!(rgba("color(srgb-linear 0.5 0 1)"), [188, 0, 255, 255]);
+        assert!(Color::parse("color(rec2020 1 0 0)").is_none());
+
+        // Blending in linear light keeps a 50% mix brighter than averaging
+        let mixed = Color::new(1.0, 1.0, 1.0, 0.5).over(Color::BLACK);
+        assert!((mixed.r - 0.735).abs() < 0.001 && mixed.a == 1.0);
+        assert_eq!(Color::parse("#ff000080").unwrap().to_string(), "rgba(255, 0, 0, 0.502)");
+    }
+}
@@ -6,16 +6,24 @@
 //! ## Features
 //! 
 //! 1. **CSS3 Tokenizer**: Handles all CSS syntax including selectors, declarations, and values
-//! 2. **Selector Engine**: Supports type, class, ID, descendant, child, and attribute selectors
+//! 2. **Selector Engine**: Supports type, class, ID, descendant, child, and attribute selectors,
+//!    and `:has()`, whose rules are invalidated only for mutations their arguments can see
 //! 3. **Cascade Algorithm**: Implements CSS cascade with specificity, source order, and !important
 //! 4. **Inheritance**: Handles inherited properties like font-family, color, etc.
 //! 5. **External Stylesheets**: Fetches and parses external CSS files
 //! 6. **Performance**: Caches parsed stylesheets and batches operations
 
-use dom::{Document, Node, NodeType};
+use dom::{Document, ElementState, Node, NodeType};
+use dom::element_state::is_link;
+use dom::mutations::Mutation;
 use std::collections::HashMap;
 use thiserror::Error;
 
+pub mod media;
+pub mod color;
+
+use media::{MediaFeatures, MediaQueryList};
+
 /// Errors that can occur during CSS parsing or cascade
 #[derive(Error, Debug)]
 pub enum CSSError {
@@ -42,6 +50,7 @@ pub enum CSSToken {
     Percentage(f32),
     Color(String),
     Url(String),
+    AtKeyword(String), // @media
     
     // Punctuation
     LeftBrace,    // {
@@ -70,267 +79,213 @@ pub enum CSSToken {
 }
 
 /// CSS tokenizer that converts CSS text into tokens
+///
+/// Positions count characters rather than bytes, so any UTF-8 input can be
+/// scanned. Every token except `Eof` consumes at least one character, so
+/// reading until `Eof` always terminates.
 pub struct CSSTokenizer {
-    input: String,
+    chars: Vec<char>,
     position: usize,
 }
 
 impl CSSTokenizer {
     pub fn new(input: String) -> Self {
-        CSSTokenizer { input, position: 0 }
+        CSSTokenizer {
+            chars: input.chars().collect(),
+            position: 0,
+        }
     }
     
+    /// Get the next token without consuming it
     pub fn peek_token(&self) -> CSSToken {
-        let mut temp_tokenizer = CSSTokenizer {
-            input: self.input.clone(),
-            position: self.position,
-        };
-        temp_tokenizer.skip_whitespace();
-        
-        if temp_tokenizer.position >= temp_tokenizer.input.len() {
-            return CSSToken::Eof;
-        }
-        
-        let current_char = temp_tokenizer.input.chars().nth(temp_tokenizer.position).unwrap();
-        
-        match current_char {
-            '{' => CSSToken::LeftBrace,
-            '}' => CSSToken::RightBrace,
-            '(' => CSSToken::LeftParen,
-            ')' => CSSToken::RightParen,
-            '[' => CSSToken::LeftBracket,
-            ']' => CSSToken::RightBracket,
-            ':' => CSSToken::Colon,
-            ';' => CSSToken::Semicolon,
-            ',' => CSSToken::Comma,
-            '.' => CSSToken::Period,
-            '#' => CSSToken::Hash,
-            '*' => CSSToken::Asterisk,
-            '+' => CSSToken::Plus,
-            '>' => CSSToken::GreaterThan,
-            '~' => CSSToken::Tilde,
-            '=' => CSSToken::Equals,
-            '|' => CSSToken::Pipe,
-            '!' => CSSToken::Exclamation,
-            '"' | '\'' => {
-                // For peek, we'll just return the quote token
-                if current_char == '"' { CSSToken::String("".to_string()) } else { CSSToken::String("".to_string()) }
-            }
-            '0'..='9' | '-' => CSSToken::Number(0.0),
-            'a'..='z' | 'A'..='Z' | '_' => CSSToken::Ident("".to_string()),
-            '/' => CSSToken::Whitespace, // Slash is handled as comment or whitespace
-            _ => CSSToken::Whitespace,
-        }
+        self.scan(self.position).0
     }
     
     pub fn next_token(&mut self) -> CSSToken {
-        self.skip_whitespace();
-        
-        if self.position >= self.input.len() {
-            return CSSToken::Eof;
+        let (token, end) = self.scan(self.position);
+        self.position = end;
+        token
+    }
+    
+    /// Check whether whitespace separates the consumed input from the next token
+    pub fn at_whitespace(&self) -> bool {
+        self.char_at(self.position).is_some_and(char::is_whitespace)
+    }
+    
+    fn char_at(&self, position: usize) -> Option<char> {
+        self.chars.get(position).copied()
+    }
+    
+    fn skip_whitespace(&self, mut position: usize) -> usize {
+        while self.char_at(position).is_some_and(char::is_whitespace) {
+            position += 1;
         }
+        position
+    }
+    
+    /// Scan the token at a position, returning it and the position after it
+    fn scan(&self, position: usize) -> (CSSToken, usize) {
+        let position = self.skip_whitespace(position);
+        let Some(current_char) = self.char_at(position) else {
+            return (CSSToken::Eof, position);
+        };
         
-        let current_char = self.input.chars().nth(self.position).unwrap();
+        let punctuation = match current_char {
+            '{' => Some(CSSToken::LeftBrace),
+            '}' => Some(CSSToken::RightBrace),
+            '(' => Some(CSSToken::LeftParen),
+            ')' => Some(CSSToken::RightParen),
+            '[' => Some(CSSToken::LeftBracket),
+            ']' => Some(CSSToken::RightBracket),
+            ':' => Some(CSSToken::Colon),
+            ';' => Some(CSSToken::Semicolon),
+            ',' => Some(CSSToken::Comma),
+            '.' => Some(CSSToken::Period),
+            '#' => Some(CSSToken::Hash),
+            '*' => Some(CSSToken::Asterisk),
+            '+' => Some(CSSToken::Plus),
+            '>' => Some(CSSToken::GreaterThan),
+            '~' => Some(CSSToken::Tilde),
+            '=' => Some(CSSToken::Equals),
+            '|' => Some(CSSToken::Pipe),
+            '!' => Some(CSSToken::Exclamation),
+            _ => None,
+        };
+        if let Some(token) = punctuation {
+            return (token, position + 1);
+        }
         
         match current_char {
-            '{' => { self.position += 1; CSSToken::LeftBrace }
-            '}' => { self.position += 1; CSSToken::RightBrace }
-            '(' => { self.position += 1; CSSToken::LeftParen }
-            ')' => { self.position += 1; CSSToken::RightParen }
-            '[' => { self.position += 1; CSSToken::LeftBracket }
-            ']' => { self.position += 1; CSSToken::RightBracket }
-            ':' => { self.position += 1; CSSToken::Colon }
-            ';' => { self.position += 1; CSSToken::Semicolon }
-            ',' => { self.position += 1; CSSToken::Comma }
-            '.' => { self.position += 1; CSSToken::Period }
-            '#' => { self.position += 1; CSSToken::Hash }
-            '*' => { self.position += 1; CSSToken::Asterisk }
-            '+' => { self.position += 1; CSSToken::Plus }
-            '>' => { self.position += 1; CSSToken::GreaterThan }
-            '~' => { self.position += 1; CSSToken::Tilde }
-            '=' => { self.position += 1; CSSToken::Equals }
-            '|' => { self.position += 1; CSSToken::Pipe }
-            '!' => { self.position += 1; CSSToken::Exclamation }
-            '"' | '\'' => self.parse_string(),
-            '0'..='9' | '-' => self.parse_number(),
-            'a'..='z' | 'A'..='Z' | '_' => self.parse_identifier(),
-            '/' => self.parse_comment_or_slash(),
-            _ => {
-                self.position += 1;
-                CSSToken::Whitespace
-            }
-        }
-    }
-    
-    fn skip_whitespace(&mut self) {
-        while self.position < self.input.len() {
-            let ch = self.input.chars().nth(self.position).unwrap();
-            if ch.is_whitespace() {
-                self.position += 1;
-            } else {
-                break;
+            '"' | '\'' => self.scan_string(position),
+            // `-webkit-box` and `--custom` are identifiers, `-5px` a number
+            '-' if self.char_at(position + 1).is_some_and(is_name_start) || self.char_at(position + 1) == Some('-') => {
+                self.scan_identifier(position)
             }
+            '0'..='9' => self.scan_number(position),
+            '-' if self.char_at(position + 1).is_some_and(|ch| ch.is_ascii_digit() || ch == '.') => {
+                self.scan_number(position)
+            }
+            c if is_name_start(c) => self.scan_identifier(position),
+            '@' if self.char_at(position + 1).is_some_and(|ch| is_name_start(ch) || ch == '-') => {
+                match self.scan_identifier(position + 1) {
+                    (CSSToken::Ident(name), end) => (CSSToken::AtKeyword(name), end),
+                    (_, end) => (CSSToken::Whitespace, end),
+                }
+            }
+            '/' => self.scan_comment_or_slash(position),
+            _ => (CSSToken::Whitespace, position + 1),
         }
     }
     
-    fn parse_string(&mut self) -> CSSToken {
-        let quote_char = self.input.chars().nth(self.position).unwrap();
-        self.position += 1; // Skip opening quote
+    fn scan_string(&self, position: usize) -> (CSSToken, usize) {
+        let quote_char = self.chars[position];
+        let start = position + 1; // Skip opening quote
         
-        let start = self.position;
-        while self.position < self.input.len() {
-            let ch = self.input.chars().nth(self.position).unwrap();
-            if ch == quote_char {
-                let value = self.input[start..self.position].to_string();
-                self.position += 1; // Skip closing quote
-                return CSSToken::String(value);
+        match self.chars[start..].iter().position(|&ch| ch == quote_char) {
+            Some(length) => {
+                let value = self.chars[start..start + length].iter().collect();
+                (CSSToken::String(value), start + length + 1)
             }
-            self.position += 1;
+            // Unterminated string
+            None => (CSSToken::String(self.chars[start..].iter().collect()), self.chars.len()),
         }
-        
-        // Unterminated string
-        let value = self.input[start..].to_string();
-        self.position = self.input.len();
-        CSSToken::String(value)
     }
     
-    fn parse_number(&mut self) -> CSSToken {
-        let start = self.position;
+    fn scan_number(&self, start: usize) -> (CSSToken, usize) {
+        let mut position = start;
         
         // Handle sign
-        if self.position < self.input.len() {
-            let ch = self.input.chars().nth(self.position).unwrap();
-            if ch == '-' || ch == '+' {
-                self.position += 1;
-            }
+        if self.char_at(position) == Some('-') {
+            position += 1;
         }
         
         // Parse digits
-        while self.position < self.input.len() {
-            let ch = self.input.chars().nth(self.position).unwrap();
-            if ch.is_ascii_digit() || ch == '.' {
-                self.position += 1;
-            } else {
-                break;
-            }
+        while self.char_at(position).is_some_and(|ch| ch.is_ascii_digit() || ch == '.') {
+            position += 1;
         }
         
-        let number_str = &self.input[start..self.position];
-        if let Ok(value) = number_str.parse::<f32>() {
-            // Check for unit
-            if self.position < self.input.len() {
-                let ch = self.input.chars().nth(self.position).unwrap();
-                if ch.is_ascii_alphabetic() {
-                    let unit_start = self.position;
-                    while self.position < self.input.len() {
-                        let ch = self.input.chars().nth(self.position).unwrap();
-                        if ch.is_ascii_alphanumeric() || ch == '-' {
-                            self.position += 1;
-                        } else {
-                            break;
-                        }
-                    }
-                    let unit = self.input[unit_start..self.position].to_string();
-                    return CSSToken::Dimension(value, unit);
-                } else if ch == '%' {
-                    self.position += 1;
-                    return CSSToken::Percentage(value);
+        let number_str: String = self.chars[start..position].iter().collect();
+        let Ok(value) = number_str.parse::<f32>() else {
+            return (CSSToken::Number(0.0), position);
+        };
+        // Out-of-range numbers are clamped to the largest finite value
+        let value = value.clamp(f32::MIN, f32::MAX);
+        
+        // Check for unit
+        match self.char_at(position) {
+            Some(ch) if ch.is_ascii_alphabetic() => {
+                let unit_start = position;
+                while self.char_at(position).is_some_and(|ch| ch.is_ascii_alphanumeric() || ch == '-') {
+                    position += 1;
                 }
+                let unit = self.chars[unit_start..position].iter().collect();
+                (CSSToken::Dimension(value, unit), position)
             }
-            CSSToken::Number(value)
-        } else {
-            CSSToken::Number(0.0)
+            Some('%') => (CSSToken::Percentage(value), position + 1),
+            _ => (CSSToken::Number(value), position),
         }
     }
     
-    fn parse_identifier(&mut self) -> CSSToken {
-        let start = self.position;
-        let chars: Vec<char> = self.input.chars().collect();
-        
-        while self.position < chars.len() {
-            let ch = chars[self.position];
-            if ch.is_ascii_alphanumeric() || ch == '_' || ch == '-' {
-                self.position += 1;
-            } else {
-                break;
-            }
+    fn scan_identifier(&self, start: usize) -> (CSSToken, usize) {
+        let mut position = start;
+        while self.char_at(position).is_some_and(|ch| is_name_start(ch) || ch.is_ascii_digit() || ch == '-') {
+            position += 1;
         }
         
-        let ident: String = chars[start..self.position].iter().collect();
+        let ident: String = self.chars[start..position].iter().collect();
         
         // Check for special functions
-        if self.position < chars.len() && chars[self.position] == '(' {
-            match ident.as_str() {
-                "url" => {
-                    self.position += 1; // Skip (
-                    self.skip_whitespace();
-                    let url_start = self.position;
-                    while self.position < chars.len() {
-                        let ch = chars[self.position];
-                        if ch == ')' {
-                            break;
-                        }
-                        self.position += 1;
-                    }
-                    let url: String = chars[url_start..self.position].iter().collect::<String>().trim().to_string();
-                    if self.position < chars.len() {
-                        self.position += 1; // Skip )
-                    }
-                    return CSSToken::Url(url);
-                }
-                "rgb" | "rgba" | "hsl" | "hsla" => {
-                    // Parse color function
-                    self.position += 1; // Skip (
-                    let color_start = self.position;
-                    while self.position < chars.len() {
-                        let ch = chars[self.position];
-                        if ch == ')' {
-                            break;
-                        }
-                        self.position += 1;
-                    }
-                    let color_value: String = chars[color_start..self.position].iter().collect();
-                    if self.position < chars.len() {
-                        self.position += 1; // Skip )
-                    }
-                    return CSSToken::Color(format!("{}({})", ident, color_value));
+        if self.char_at(position) == Some('(') {
+            let is_color = matches!(ident.as_str(), "rgb" | "rgba" | "hsl" | "hsla" | "color");
+            if ident == "url" || is_color {
+                let mut argument_start = position + 1; // Skip (
+                if ident == "url" {
+                    argument_start = self.skip_whitespace(argument_start);
                 }
-                _ => {}
+                let argument_end = self.chars[argument_start..]
+                    .iter()
+                    .position(|&ch| ch == ')')
+                    .map_or(self.chars.len(), |length| argument_start + length);
+                let argument: String = self.chars[argument_start..argument_end].iter().collect();
+                // Skip ) if the function is closed
+                let end = (argument_end + 1).min(self.chars.len());
+                
+                return if is_color {
+                    (CSSToken::Color(format!("{}({})", ident, argument)), end)
+                } else {
+                    (CSSToken::Url(argument.trim().to_string()), end)
+                };
             }
         }
         
-        CSSToken::Ident(ident)
+        (CSSToken::Ident(ident), position)
     }
     
-    fn parse_comment_or_slash(&mut self) -> CSSToken {
-        if self.position + 1 < self.input.len() && 
-           self.input.chars().nth(self.position + 1).unwrap() == '*' {
-            // Parse comment
-            self.position += 2; // Skip /*
-            let start = self.position;
-            
-            while self.position + 1 < self.input.len() {
-                if self.input.chars().nth(self.position).unwrap() == '*' &&
-                   self.input.chars().nth(self.position + 1).unwrap() == '/' {
-                    let content = self.input[start..self.position].to_string();
-                    self.position += 2; // Skip */
-                    return CSSToken::Comment(content);
-                }
-                self.position += 1;
+    fn scan_comment_or_slash(&self, position: usize) -> (CSSToken, usize) {
+        if self.char_at(position + 1) != Some('*') {
+            // Just a slash
+            return (CSSToken::Whitespace, position + 1);
+        }
+        
+        let start = position + 2; // Skip /*
+        match self.chars[start..].windows(2).position(|pair| pair == ['*', '/']) {
+            Some(length) => {
+                let content = self.chars[start..start + length].iter().collect();
+                (CSSToken::Comment(content), start + length + 2)
             }
-            
             // Unterminated comment
-            let content = self.input[start..].to_string();
-            self.position = self.input.len();
-            CSSToken::Comment(content)
-        } else {
-            // Just a slash
-            self.position += 1;
-            CSSToken::Whitespace
+            None => (CSSToken::Comment(self.chars[start..].iter().collect()), self.chars.len()),
         }
     }
 }
 
+/// Check whether a character can start an identifier
+///
+/// Non-ASCII characters are name characters, so `.café` is a class.
+fn is_name_start(ch: char) -> bool {
+    ch.is_ascii_alphabetic() || ch == '_' || !ch.is_ascii()
+}
+
 /// CSS selector types
 #[derive(Debug, Clone, PartialEq)]
 pub enum Selector {
@@ -346,6 +301,37 @@ pub enum Selector {
     AdjacentSibling(Box<Selector>, Box<Selector>),
     GeneralSibling(Box<Selector>, Box<Selector>),
     Group(Vec<Selector>),
+    /// Simple selectors that must all match the same element, e.g. `a.nav:hover`
+    Compound(Vec<Selector>),
+    /// `:has()`, matching elements that some relative selector reaches
+    Has(Vec<RelativeSelector>),
+}
+
+/// How the compounds of a relative selector are joined, shared with the
+/// selector matching of `dom`
+pub use dom::selectors::Combinator;
+
+/// A selector inside `:has()`, relative to the element `:has()` is on,
+/// e.g. `> img` or `+ .note p`
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelativeSelector {
+    /// Compound selectors left to right, each with the combinator joining
+    /// it to the previous compound, or to the anchor element for the first
+    pub steps: Vec<(Combinator, Selector)>,
+}
+
+impl RelativeSelector {
+    /// Check whether an element reached from `anchor` matches this
+    /// relative selector
+    pub fn matches(&self, anchor: &Node) -> bool {
+        relative_matches(self, anchor, MatchMode::Normal)
+    }
+
+    /// Whether any compound is joined by a sibling combinator, so that
+    /// the anchor depends on its siblings as well as its descendants
+    fn uses_siblings(&self) -> bool {
+        self.steps.iter().any(|(combinator, _)| matches!(combinator, Combinator::NextSibling | Combinator::SubsequentSibling))
+    }
 }
 
 /// CSS property value
@@ -362,6 +348,51 @@ pub enum CSSValue {
     List(Vec<CSSValue>),
 }
 
+impl CSSValue {
+    /// Serialize the value back to CSS text, e.g. `right 10px`
+    pub fn to_css_text(&self) -> String {
+        match self {
+            CSSValue::Keyword(value) | CSSValue::Color(value) => value.clone(),
+            CSSValue::String(value) => format!("\"{}\"", value),
+            CSSValue::Number(value) => value.to_string(),
+            CSSValue::Dimension(value, unit) => format!("{}{}", value, unit),
+            CSSValue::Percentage(value) => format!("{}%", value),
+            CSSValue::Url(value) => format!("url({})", value),
+            CSSValue::Function(name, arguments) => {
+                let arguments: Vec<String> = arguments.iter().map(CSSValue::to_css_text).collect();
+                format!("{}({})", name, arguments.join(", "))
+            }
+            CSSValue::List(values) => values.iter().map(CSSValue::to_css_text).collect::<Vec<_>>().join(" "),
+        }
+    }
+
+    /// Rewrite the `url()` values in the value with `resolve`, see
+    /// `Stylesheet::resolve_urls`
+    pub fn resolve_urls(&mut self, resolve: &dyn Fn(&str) -> Option<String>) {
+        match self {
+            CSSValue::Url(value) => {
+                let quote = value.chars().next().filter(|&ch| (ch == '"' || ch == '\'') && value.len() > 1 && value.ends_with(ch));
+                let written = match quote {
+                    Some(_) => &value[1..value.len() - 1],
+                    None => value.as_str(),
+                };
+                if let Some(resolved) = resolve(written) {
+                    *value = match quote {
+                        Some(quote) => format!("{quote}{resolved}{quote}"),
+                        None => resolved,
+                    };
+                }
+            }
+            CSSValue::Function(_, values) | CSSValue::List(values) => {
+                for value in values {
+                    value.resolve_urls(resolve);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
 /// CSS declaration (property: value)
 #[derive(Debug, Clone)]
 pub struct CSSDeclaration {
@@ -376,6 +407,23 @@ pub struct CSSRule {
     pub selectors: Vec<Selector>,
     pub declarations: Vec<CSSDeclaration>,
     pub specificity: Specificity,
+    /// Media queries of the enclosing `@media` rule, `None` outside one
+    pub media: Option<MediaQueryList>,
+    /// Media queries of the stylesheet the rule belongs to, from the
+    /// `media` attribute of its `<link>` or `<style>` element
+    pub sheet_media: Option<MediaQueryList>,
+    /// Byte range of the rule in its stylesheet's text, for rules parsed
+    /// from text
+    pub source_range: Option<std::ops::Range<usize>>,
+}
+
+impl CSSRule {
+    /// Check whether the rule's and its stylesheet's media queries match
+    /// an environment
+    pub fn applies_to(&self, features: &MediaFeatures) -> bool {
+        self.media.as_ref().is_none_or(|media| media.matches(features))
+            && self.sheet_media.as_ref().is_none_or(|media| media.matches(features))
+    }
 }
 
 /// CSS specificity (a, b, c, d)
@@ -418,17 +466,79 @@ impl Specificity {
                     .max()
                     .unwrap_or_else(Specificity::new)
             }
+            Selector::Compound(selectors) => Specificity::sum(selectors.iter()),
+            // The most specific argument, as for `:is()`
+            Selector::Has(relative) => relative
+                .iter()
+                .map(|relative| Specificity::sum(relative.steps.iter().map(|(_, compound)| compound)))
+                .max()
+                .unwrap_or_else(Specificity::new),
         }
     }
+    
+    fn sum<'a>(selectors: impl Iterator<Item = &'a Selector>) -> Self {
+        selectors
+            .map(Specificity::calculate)
+            .fold(Specificity::new(), |total, spec| Specificity {
+                a: total.a + spec.a,
+                b: total.b + spec.b,
+                c: total.c + spec.c,
+                d: total.d + spec.d,
+            })
+    }
+}
+
+/// An `@page` rule, styling the page boxes of paged media
+#[derive(Debug, Clone)]
+pub struct PageRule {
+    /// Page selector, such as `:first` or `:left`; `None` matches every
+    /// page
+    pub selector: Option<String>,
+    pub declarations: Vec<CSSDeclaration>,
 }
 
 /// CSS stylesheet
 #[derive(Debug, Clone)]
 pub struct Stylesheet {
     pub rules: Vec<CSSRule>,
+    /// `@page` rules, in order
+    pub page_rules: Vec<PageRule>,
     pub source_url: Option<String>,
 }
 
+impl Stylesheet {
+    /// Restrict the stylesheet to environments matching the `media`
+    /// attribute of the element that brought it in
+    /// 
+    /// The queries stay on the rules, so sheets merged into one keep them
+    /// and they are evaluated again whenever the environment changes.
+    pub fn set_media(&mut self, media: Option<MediaQueryList>) {
+        for rule in &mut self.rules {
+            rule.sheet_media = media.clone();
+        }
+    }
+
+    /// Rewrite the `url()` values of the stylesheet to absolute URLs
+    ///
+    /// `resolve` gets each URL as written, without its quotes, and should
+    /// resolve it against the stylesheet's own URL, or the document's for
+    /// `<style>` elements. URLs it returns `None` for are left as written.
+    pub fn resolve_urls(&mut self, resolve: impl Fn(&str) -> Option<String>) {
+        for declaration in self.rules.iter_mut().flat_map(|rule| rule.declarations.iter_mut()) {
+            declaration.value.resolve_urls(&resolve);
+        }
+    }
+}
+
+/// Get the media queries of a `<link rel=stylesheet>` or `<style>` element
+/// 
+/// A missing or empty `media` attribute applies everywhere, like `all`.
+pub fn stylesheet_media(node: &Node) -> Option<MediaQueryList> {
+    let media = node.get_attribute("media")?;
+    let media = MediaQueryList::parse(&media);
+    (!media.queries().is_empty()).then_some(media)
+}
+
 /// Computed styles for a DOM node
 #[derive(Debug, Clone, Default)]
 pub struct ComputedStyles {
@@ -462,121 +572,249 @@ pub struct ComputedStyles {
     pub overflow: Option<String>,
     pub visibility: Option<String>,
     pub opacity: Option<String>,
+    pub content_visibility: Option<String>,
+    pub contain_intrinsic_size: Option<String>,
+    pub object_fit: Option<String>,
+    pub object_position: Option<String>,
+    pub filter: Option<String>,
+    pub mix_blend_mode: Option<String>,
+    pub isolation: Option<String>,
+    pub will_change: Option<String>,
+    pub direction: Option<String>,
+    pub unicode_bidi: Option<String>,
+    pub hyphens: Option<String>,
+    pub font_style: Option<String>,
+    pub font_variant: Option<String>,
+    pub font_feature_settings: Option<String>,
+    pub font_synthesis: Option<String>,
+}
+
+/// Nesting limit for function arguments in values
+///
+/// Deeper arguments are skipped rather than parsed, so input such as
+/// `calc((((...` cannot exhaust the stack.
+const MAX_FUNCTION_NESTING: usize = 32;
+
+/// A buffered token and whether whitespace came before it
+#[derive(Debug, Clone)]
+struct LookaheadToken {
+    token: CSSToken,
+    after_whitespace: bool,
+    /// Character positions of the token's first character and the one
+    /// after it
+    start: usize,
+    end: usize,
 }
 
 /// CSS parser that builds stylesheets from CSS text
+///
+/// Tokens are read through a one-token lookahead buffer, so the parser can
+/// decide on the next token before consuming it. Every loop consumes a
+/// token or stops at `Eof`, so parsing terminates on any input, and a
+/// malformed rule or declaration is skipped rather than ending the parse.
 pub struct CSSParser {
     tokenizer: CSSTokenizer,
+    lookahead: Option<LookaheadToken>,
+    /// Character position after the last consumed token
+    consumed: usize,
 }
 
 impl CSSParser {
     pub fn new(input: String) -> Self {
         CSSParser {
             tokenizer: CSSTokenizer::new(input),
+            lookahead: None,
+            consumed: 0,
         }
     }
     
     pub fn parse_stylesheet(&mut self) -> Result<Stylesheet, CSSError> {
         let mut rules = Vec::new();
+        let mut page_rules = Vec::new();
         
-        // Simple parser - just parse one rule for now
-        self.skip_whitespace_and_comments();
-        
-        if self.tokenizer.position < self.tokenizer.input.len() {
-            // Try to parse a simple rule: selector { property: value; }
-            match self.parse_simple_rule() {
-                Ok(rule) => {
-                    rules.push(rule);
-                }
-                Err(_) => {
-                    // Return empty stylesheet if parsing fails
+        loop {
+            match self.peek() {
+                CSSToken::Eof => break,
+                // Stray tokens between rules
+                CSSToken::RightBrace | CSSToken::Semicolon | CSSToken::Whitespace => {
+                    self.next();
                 }
+                CSSToken::AtKeyword(_) => self.parse_at_rule(&mut rules, &mut page_rules),
+                _ => match self.parse_rule() {
+                    Ok(rule) => rules.push(rule),
+                    Err(_) => self.skip_rule(),
+                },
             }
         }
         
-        Ok(Stylesheet { rules, source_url: None })
+        // Rules recorded their ranges in characters
+        let mut byte_offsets = Vec::with_capacity(self.tokenizer.chars.len() + 1);
+        let mut offset = 0;
+        for c in &self.tokenizer.chars {
+            byte_offsets.push(offset);
+            offset += c.len_utf8();
+        }
+        byte_offsets.push(offset);
+        for rule in &mut rules {
+            rule.source_range = rule.source_range.take().map(|range| byte_offsets[range.start]..byte_offsets[range.end]);
+        }
+        
+        Ok(Stylesheet { rules, page_rules, source_url: None })
     }
     
-    fn parse_simple_rule(&mut self) -> Result<CSSRule, CSSError> {
-        // Parse selector (just type selector for now)
-        let selector_token = self.tokenizer.next_token();
-        let selector = match selector_token {
-            CSSToken::Ident(name) => Selector::Type(name),
-            _ => return Err(CSSError::InvalidSelector("Expected type selector".to_string())),
+    /// Parse an at-rule at the top level of a stylesheet
+    /// 
+    /// The rules of an `@media` block are added to `rules` with the block's
+    /// media queries, and `@page` rules to `page_rules`. Other at-rules,
+    /// and at-rules nested in `@media`, are skipped.
+    fn parse_at_rule(&mut self, rules: &mut Vec<CSSRule>, page_rules: &mut Vec<PageRule>) {
+        let name = match self.next() {
+            CSSToken::AtKeyword(name) => name.to_ascii_lowercase(),
+            _ => String::new(),
         };
-        
-        // Expect {
-        match self.tokenizer.next_token() {
-            CSSToken::LeftBrace => {}
-            _ => return Err(CSSError::ParseError(0, "Expected '{'".to_string())),
+        if name != "media" && name != "page" {
+            self.skip_rule();
+            return;
         }
         
-        // Parse declarations
-        let mut declarations = Vec::new();
+        let mut prelude = String::new();
         loop {
-            match self.tokenizer.next_token() {
-                CSSToken::RightBrace => break,
-                CSSToken::Semicolon => continue,
-                CSSToken::Ident(property) => {
-                    // Expect :
-                    match self.tokenizer.next_token() {
-                        CSSToken::Colon => {
-                            // Parse value
-                            match self.tokenizer.next_token() {
-                                CSSToken::Ident(value) => {
-                                    declarations.push(CSSDeclaration {
-                                        property,
-                                        value: CSSValue::Keyword(value),
-                                        important: false,
-                                    });
-                                }
-                                _ => return Err(CSSError::ParseError(0, "Expected value".to_string())),
-                            }
-                        }
-                        _ => return Err(CSSError::ParseError(0, "Expected ':'".to_string())),
+            match self.peek() {
+                CSSToken::LeftBrace => {
+                    self.next();
+                    break;
+                }
+                CSSToken::Semicolon | CSSToken::RightBrace | CSSToken::Eof => {
+                    self.skip_rule();
+                    return;
+                }
+                _ => {
+                    if self.after_whitespace() && !prelude.is_empty() {
+                        prelude.push(' ');
                     }
+                    prelude.push_str(&token_text(&self.next()));
                 }
-                _ => return Err(CSSError::ParseError(0, "Expected property or '}'".to_string())),
             }
         }
+        if name == "page" {
+            let selector = Some(prelude).filter(|prelude| !prelude.is_empty());
+            page_rules.push(PageRule { selector, declarations: self.parse_declarations() });
+            return;
+        }
+        let media = MediaQueryList::parse(&prelude);
         
-        Ok(CSSRule {
-            selectors: vec![selector],
-            declarations,
-            specificity: Specificity::calculate(&Selector::Type("div".to_string())),
-        })
+        loop {
+            match self.peek() {
+                CSSToken::Eof => break,
+                CSSToken::RightBrace => {
+                    self.next();
+                    break;
+                }
+                CSSToken::Semicolon | CSSToken::Whitespace => {
+                    self.next();
+                }
+                CSSToken::AtKeyword(_) => {
+                    self.next();
+                    self.skip_rule();
+                }
+                _ => match self.parse_rule() {
+                    Ok(mut rule) => {
+                        rule.media = Some(media.clone());
+                        rules.push(rule);
+                    }
+                    Err(_) => self.skip_rule(),
+                },
+            }
+        }
+    }
+    
+    /// Look at the next token without consuming it
+    ///
+    /// Comments are dropped here, so the rest of the parser never sees them.
+    fn peek(&mut self) -> &CSSToken {
+        if self.lookahead.is_none() {
+            let mut after_whitespace = false;
+            let (token, start) = loop {
+                after_whitespace |= self.tokenizer.at_whitespace();
+                let start = self.tokenizer.skip_whitespace(self.tokenizer.position);
+                match self.tokenizer.next_token() {
+                    CSSToken::Comment(_) => continue,
+                    token => break (token, start),
+                }
+            };
+            let end = self.tokenizer.position;
+            self.lookahead = Some(LookaheadToken { token, after_whitespace, start, end });
+        }
+        match &self.lookahead {
+            Some(lookahead) => &lookahead.token,
+            None => &CSSToken::Eof,
+        }
+    }
+    
+    /// Check whether whitespace comes before the next token
+    fn after_whitespace(&mut self) -> bool {
+        self.peek();
+        self.lookahead.as_ref().is_some_and(|lookahead| lookahead.after_whitespace)
+    }
+    
+    /// Consume the next token
+    fn next(&mut self) -> CSSToken {
+        self.peek();
+        match self.lookahead.take() {
+            Some(lookahead) => {
+                self.consumed = lookahead.end;
+                lookahead.token
+            }
+            None => CSSToken::Eof,
+        }
     }
     
-    fn skip_whitespace_and_comments(&mut self) {
+    /// Skip the rest of a malformed rule, including its block
+    fn skip_rule(&mut self) {
+        let mut depth = 0usize;
         loop {
-            match self.tokenizer.next_token() {
+            match self.next() {
                 CSSToken::Eof => break,
-                CSSToken::Comment(_) | CSSToken::Whitespace => continue,
-                _ => {
-                    // Put the token back by decrementing position
-                    // This is a hack - in a real parser we'd use lookahead
-                    if self.tokenizer.position > 0 {
-                        self.tokenizer.position -= 1;
-                    }
+                CSSToken::Semicolon if depth == 0 => break,
+                CSSToken::LeftBrace => depth += 1,
+                CSSToken::RightBrace if depth <= 1 => break,
+                CSSToken::RightBrace => depth -= 1,
+                _ => {}
+            }
+        }
+    }
+    
+    /// Skip the rest of a malformed declaration
+    ///
+    /// Stops after its `;` or before the `}` that closes the block, skipping
+    /// any blocks nested in the declaration.
+    fn skip_declaration(&mut self, mut depth: usize) {
+        loop {
+            match self.peek() {
+                CSSToken::Eof => break,
+                CSSToken::Semicolon if depth == 0 => {
+                    self.next();
                     break;
                 }
+                CSSToken::RightBrace if depth == 0 => break,
+                CSSToken::LeftBrace | CSSToken::LeftParen | CSSToken::LeftBracket => depth += 1,
+                CSSToken::RightBrace | CSSToken::RightParen | CSSToken::RightBracket => {
+                    depth = depth.saturating_sub(1);
+                }
+                _ => {}
             }
+            self.next();
         }
     }
     
     fn parse_rule(&mut self) -> Result<CSSRule, CSSError> {
+        self.peek();
+        let start = self.lookahead.as_ref().map_or(self.consumed, |lookahead| lookahead.start);
         let selectors = self.parse_selectors()?;
         
-        // The LeftBrace was already consumed by parse_selectors
-        // No need to expect it again
-        
-        let declarations = self.parse_declarations()?;
-        
-        // Expect }
-        match self.tokenizer.next_token() {
-            CSSToken::RightBrace => {}
-            _ => return Err(CSSError::ParseError(0, "Expected '}'".to_string())),
-        }
+        // parse_selectors consumed the LeftBrace, and parse_declarations
+        // consumes the RightBrace
+        let declarations = self.parse_declarations();
         
         // Calculate specificity for the first selector (simplified)
         let specificity = if let Some(first_selector) = selectors.first() {
@@ -589,6 +827,9 @@ impl CSSParser {
             selectors,
             declarations,
             specificity,
+            media: None,
+            sheet_media: None,
+            source_range: Some(start..self.consumed),
         })
     }
     
@@ -599,22 +840,16 @@ impl CSSParser {
             let selector = self.parse_selector()?;
             selectors.push(selector);
             
-            // Check what the next token is without consuming it
-            let next_token = self.tokenizer.next_token();
-            match next_token {
+            match self.peek() {
                 CSSToken::Comma => {
-                    // Consume the comma and continue
-                    continue;
+                    self.next();
                 }
                 CSSToken::LeftBrace => {
-                    // We consumed the LeftBrace, which is correct
-                    // The tokenizer is now positioned at the first token inside the braces
-                    break;
-                }
-                _ => {
-                    // Put the token back by not advancing
+                    self.next();
                     break;
                 }
+                // Left in place, so recovery can see a closing brace
+                token => return Err(CSSError::InvalidSelector(format!("Unexpected {:?} after selector", token))),
             }
         }
         
@@ -622,146 +857,699 @@ impl CSSParser {
     }
     
     fn parse_selector(&mut self) -> Result<Selector, CSSError> {
-        let mut selector = self.parse_simple_selector()?;
+        let mut selector = self.parse_compound_selector()?;
         
         // Parse combinators
         loop {
-            match self.tokenizer.next_token() {
-                CSSToken::Whitespace => {
-                    // Descendant combinator
-                    let next_selector = self.parse_simple_selector()?;
-                    selector = Selector::Descendant(Box::new(selector), Box::new(next_selector));
-                }
-                CSSToken::GreaterThan => {
-                    // Child combinator
-                    let next_selector = self.parse_simple_selector()?;
-                    selector = Selector::Child(Box::new(selector), Box::new(next_selector));
-                }
-                CSSToken::Plus => {
-                    // Adjacent sibling combinator
-                    let next_selector = self.parse_simple_selector()?;
-                    selector = Selector::AdjacentSibling(Box::new(selector), Box::new(next_selector));
-                }
-                CSSToken::Tilde => {
-                    // General sibling combinator
-                    let next_selector = self.parse_simple_selector()?;
-                    selector = Selector::GeneralSibling(Box::new(selector), Box::new(next_selector));
-                }
-                _ => break,
-            }
+            let combinator = match self.peek().clone() {
+                CSSToken::GreaterThan | CSSToken::Plus | CSSToken::Tilde => Some(self.next()),
+                CSSToken::Comma | CSSToken::LeftBrace | CSSToken::Eof => break,
+                // Descendant combinator
+                CSSToken::Ident(_) | CSSToken::Asterisk | CSSToken::Period | CSSToken::Hash | CSSToken::Colon | CSSToken::LeftBracket
+                    if self.after_whitespace() => None,
+                token => return Err(CSSError::InvalidSelector(format!("Unexpected {:?} in selector", token))),
+            };
+            
+            let next_selector = self.parse_compound_selector()?;
+            selector = match combinator {
+                Some(CSSToken::GreaterThan) => Selector::Child(Box::new(selector), Box::new(next_selector)),
+                Some(CSSToken::Plus) => Selector::AdjacentSibling(Box::new(selector), Box::new(next_selector)),
+                Some(_) => Selector::GeneralSibling(Box::new(selector), Box::new(next_selector)),
+                None => Selector::Descendant(Box::new(selector), Box::new(next_selector)),
+            };
         }
         
         Ok(selector)
     }
     
+    /// Parse simple selectors that are not separated by whitespace, e.g. `a.nav:hover`
+    fn parse_compound_selector(&mut self) -> Result<Selector, CSSError> {
+        let mut selectors = vec![self.parse_simple_selector()?];
+        while !self.after_whitespace()
+            && matches!(self.peek(), CSSToken::Period | CSSToken::Hash | CSSToken::Colon | CSSToken::LeftBracket)
+        {
+            selectors.push(self.parse_simple_selector()?);
+        }
+        
+        if selectors.len() == 1 {
+            Ok(selectors.remove(0))
+        } else {
+            Ok(Selector::Compound(selectors))
+        }
+    }
+    
     fn parse_simple_selector(&mut self) -> Result<Selector, CSSError> {
-        match self.tokenizer.next_token() {
+        match self.next() {
             CSSToken::Asterisk => Ok(Selector::Universal),
             CSSToken::Hash => {
-                match self.tokenizer.next_token() {
+                match self.next() {
                     CSSToken::Ident(id) => Ok(Selector::Id(id)),
                     _ => Err(CSSError::InvalidSelector("Expected ID after #".to_string())),
                 }
             }
             CSSToken::Period => {
-                match self.tokenizer.next_token() {
+                match self.next() {
                     CSSToken::Ident(class) => Ok(Selector::Class(class)),
                     _ => Err(CSSError::InvalidSelector("Expected class after .".to_string())),
                 }
             }
+            CSSToken::Colon => {
+                match self.next() {
+                    CSSToken::Ident(name) if name.eq_ignore_ascii_case("has") && *self.peek() == CSSToken::LeftParen && !self.after_whitespace() => {
+                        self.next();
+                        Ok(Selector::Has(self.parse_relative_selectors()?))
+                    }
+                    CSSToken::Ident(name) => Ok(Selector::PseudoClass(name)),
+                    CSSToken::Colon => match self.next() {
+                        CSSToken::Ident(name) => Ok(Selector::PseudoElement(name)),
+                        _ => Err(CSSError::InvalidSelector("Expected pseudo-element after ::".to_string())),
+                    },
+                    _ => Err(CSSError::InvalidSelector("Expected pseudo-class after :".to_string())),
+                }
+            }
+            CSSToken::LeftBracket => self.parse_attribute_selector(),
             CSSToken::Ident(name) => Ok(Selector::Type(name)),
             _ => Err(CSSError::InvalidSelector("Invalid selector".to_string())),
         }
     }
     
-    fn parse_declarations(&mut self) -> Result<Vec<CSSDeclaration>, CSSError> {
+    /// Parse the relative selectors of `:has()` after its `(`, up to and
+    /// including the `)`
+    fn parse_relative_selectors(&mut self) -> Result<Vec<RelativeSelector>, CSSError> {
+        let mut selectors = Vec::new();
+        loop {
+            let mut combinator = self.parse_combinator().unwrap_or(Combinator::Descendant);
+            let mut steps = Vec::new();
+            loop {
+                let compound = self.parse_compound_selector()?;
+                if uses_has(&compound) {
+                    return Err(CSSError::InvalidSelector(":has() cannot be nested".to_string()));
+                }
+                steps.push((combinator, compound));
+                combinator = match self.peek().clone() {
+                    CSSToken::Comma | CSSToken::RightParen => break,
+                    CSSToken::GreaterThan | CSSToken::Plus | CSSToken::Tilde => self.parse_combinator().unwrap_or(Combinator::Descendant),
+                    CSSToken::Ident(_) | CSSToken::Asterisk | CSSToken::Period | CSSToken::Hash | CSSToken::Colon | CSSToken::LeftBracket
+                        if self.after_whitespace() => Combinator::Descendant,
+                    token => return Err(CSSError::InvalidSelector(format!("Unexpected {:?} in :has()", token))),
+                };
+            }
+            selectors.push(RelativeSelector { steps });
+            if self.next() == CSSToken::RightParen {
+                return Ok(selectors);
+            }
+        }
+    }
+    
+    /// Consume a `>`, `+` or `~` combinator
+    fn parse_combinator(&mut self) -> Option<Combinator> {
+        let combinator = match self.peek() {
+            CSSToken::GreaterThan => Combinator::Child,
+            CSSToken::Plus => Combinator::NextSibling,
+            CSSToken::Tilde => Combinator::SubsequentSibling,
+            _ => return None,
+        };
+        self.next();
+        Some(combinator)
+    }
+    
+    /// Parse an attribute selector after its `[`, e.g. `type="text"]`
+    fn parse_attribute_selector(&mut self) -> Result<Selector, CSSError> {
+        let name = match self.next() {
+            CSSToken::Ident(name) => name,
+            _ => return Err(CSSError::InvalidSelector("Expected attribute name".to_string())),
+        };
+        
+        let operator = match self.next() {
+            CSSToken::RightBracket => return Ok(Selector::Attribute(name, None, None)),
+            CSSToken::Equals => "=",
+            CSSToken::Tilde => "~=",
+            CSSToken::Pipe => "|=",
+            CSSToken::Asterisk => "*=",
+            _ => return Err(CSSError::InvalidSelector("Expected attribute operator".to_string())),
+        };
+        if operator != "=" && self.next() != CSSToken::Equals {
+            return Err(CSSError::InvalidSelector("Expected '=' in attribute operator".to_string()));
+        }
+        
+        let value = match self.next() {
+            CSSToken::Ident(value) | CSSToken::String(value) => value,
+            _ => return Err(CSSError::InvalidSelector("Expected attribute value".to_string())),
+        };
+        match self.next() {
+            CSSToken::RightBracket => Ok(Selector::Attribute(name, Some(operator.to_string()), Some(value))),
+            _ => Err(CSSError::InvalidSelector("Expected ']'".to_string())),
+        }
+    }
+    
+    /// Parse the declarations of a block up to and including its `}`
+    fn parse_declarations(&mut self) -> Vec<CSSDeclaration> {
         let mut declarations = Vec::new();
         
         loop {
-            match self.tokenizer.next_token() {
-                CSSToken::RightBrace => break,
-                CSSToken::Semicolon => continue,
-                CSSToken::Ident(property) => {
-                    // Parse property: value
-                    match self.tokenizer.next_token() {
-                        CSSToken::Colon => {
-                            let value = self.parse_value()?;
-                            let mut important = false;
-                            
-                            // Check for !important
-                            match self.tokenizer.next_token() {
-                                CSSToken::Exclamation => {
-                                    match self.tokenizer.next_token() {
-                                        CSSToken::Ident(ident) if ident == "important" => {
-                                            important = true;
-                                        }
-                                        _ => {}
-                                    }
-                                }
-                                _ => {}
-                            }
-                            
-                            declarations.push(CSSDeclaration {
-                                property,
-                                value,
-                                important,
-                            });
+            match self.next() {
+                CSSToken::RightBrace | CSSToken::Eof => break,
+                CSSToken::Semicolon | CSSToken::Whitespace => continue,
+                CSSToken::Ident(property) if *self.peek() == CSSToken::Colon => {
+                    self.next();
+                    if let Some((value, important)) = self.parse_value() {
+                        declarations.push(CSSDeclaration {
+                            property,
+                            value,
+                            important,
+                        });
+                    }
+                }
+                CSSToken::LeftBrace | CSSToken::LeftParen | CSSToken::LeftBracket => self.skip_declaration(1),
+                _ => self.skip_declaration(0),
+            }
+        }
+        
+        declarations
+    }
+    
+    /// Parse a declaration value up to the `;` or `}` that ends it
+    ///
+    /// Returns the value and whether it is `!important`, or `None` if there
+    /// is no value. Several components make a `CSSValue::List`.
+    fn parse_value(&mut self) -> Option<(CSSValue, bool)> {
+        let mut values = Vec::new();
+        let mut important = false;
+        
+        loop {
+            if matches!(self.peek(), CSSToken::Semicolon | CSSToken::RightBrace | CSSToken::Eof) {
+                break;
+            }
+            match self.next() {
+                CSSToken::Exclamation => {
+                    if matches!(self.peek(), CSSToken::Ident(ident) if ident.eq_ignore_ascii_case("important")) {
+                        self.next();
+                        important = true;
+                    }
+                }
+                CSSToken::Hash if !self.after_whitespace() => {
+                    // Digit-led colors such as `#008000` lex as numbers, so
+                    // the color is taken from the source text
+                    let start = self.consumed;
+                    let valid = match self.next() {
+                        CSSToken::Ident(_) => true,
+                        CSSToken::Number(_) | CSSToken::Dimension(..) => {
+                            self.tokenizer.chars[start..self.consumed].iter().all(char::is_ascii_hexdigit)
                         }
-                        _ => return Err(CSSError::ParseError(0, "Expected ':' after property".to_string())),
+                        _ => false,
+                    };
+                    if valid {
+                        let hex: String = self.tokenizer.chars[start..self.consumed].iter().collect();
+                        values.push(CSSValue::Color(format!("#{}", hex)));
                     }
                 }
-                _ => continue,
+                CSSToken::Ident(name) if *self.peek() == CSSToken::LeftParen && !self.after_whitespace() => {
+                    self.next();
+                    values.push(CSSValue::Function(name, self.parse_function_arguments(1)));
+                }
+                CSSToken::LeftParen => self.skip_function_arguments(),
+                token => values.extend(token_value(token)),
             }
         }
         
-        Ok(declarations)
+        match values.len() {
+            0 => None,
+            1 => values.pop().map(|value| (value, important)),
+            _ => Some((CSSValue::List(values), important)),
+        }
+    }
+    
+    /// Parse function arguments up to the `)` that closes the function
+    fn parse_function_arguments(&mut self, depth: usize) -> Vec<CSSValue> {
+        if depth > MAX_FUNCTION_NESTING {
+            self.skip_function_arguments();
+            return Vec::new();
+        }
+        
+        let mut arguments = Vec::new();
+        loop {
+            // An unclosed function ends with its declaration
+            if matches!(self.peek(), CSSToken::Semicolon | CSSToken::RightBrace | CSSToken::Eof) {
+                break;
+            }
+            match self.next() {
+                CSSToken::RightParen => break,
+                CSSToken::Ident(name) if *self.peek() == CSSToken::LeftParen && !self.after_whitespace() => {
+                    self.next();
+                    arguments.push(CSSValue::Function(name, self.parse_function_arguments(depth + 1)));
+                }
+                CSSToken::LeftParen => arguments.extend(self.parse_function_arguments(depth + 1)),
+                token => arguments.extend(token_value(token)),
+            }
+        }
+        arguments
     }
     
-    fn parse_value(&mut self) -> Result<CSSValue, CSSError> {
-        match self.tokenizer.next_token() {
-            CSSToken::Ident(keyword) => Ok(CSSValue::Keyword(keyword)),
-            CSSToken::String(s) => Ok(CSSValue::String(s)),
-            CSSToken::Number(n) => Ok(CSSValue::Number(n)),
-            CSSToken::Dimension(n, unit) => Ok(CSSValue::Dimension(n, unit)),
-            CSSToken::Percentage(p) => Ok(CSSValue::Percentage(p)),
-            CSSToken::Color(c) => Ok(CSSValue::Color(c)),
-            CSSToken::Url(u) => Ok(CSSValue::Url(u)),
-            _ => Err(CSSError::InvalidPropertyValue("Invalid CSS value".to_string())),
+    /// Skip function arguments up to the `)` that closes the function
+    fn skip_function_arguments(&mut self) {
+        let mut depth = 1usize;
+        while depth > 0 {
+            if matches!(self.peek(), CSSToken::Semicolon | CSSToken::RightBrace | CSSToken::Eof) {
+                break;
+            }
+            match self.next() {
+                CSSToken::LeftParen => depth += 1,
+                CSSToken::RightParen => depth -= 1,
+                _ => {}
+            }
         }
     }
 }
 
-/// CSS cascade engine that applies styles to DOM nodes
-pub struct CSSCascadeEngine {
-    stylesheets: Vec<Stylesheet>,
-    cache: HashMap<String, Stylesheet>,
+/// Convert a value token to a value
+fn token_value(token: CSSToken) -> Option<CSSValue> {
+    match token {
+        CSSToken::Ident(keyword) => Some(CSSValue::Keyword(keyword)),
+        CSSToken::String(s) => Some(CSSValue::String(s)),
+        CSSToken::Number(n) => Some(CSSValue::Number(n)),
+        CSSToken::Dimension(n, unit) => Some(CSSValue::Dimension(n, unit)),
+        CSSToken::Percentage(p) => Some(CSSValue::Percentage(p)),
+        CSSToken::Color(c) => Some(CSSValue::Color(c)),
+        CSSToken::Url(u) => Some(CSSValue::Url(u)),
+        _ => None,
+    }
 }
 
-impl CSSCascadeEngine {
-    pub fn new() -> Self {
-        CSSCascadeEngine {
-            stylesheets: Vec::new(),
-            cache: HashMap::new(),
+/// Get the source text of a token, for preludes kept as text
+fn token_text(token: &CSSToken) -> String {
+    match token {
+        CSSToken::Ident(text) | CSSToken::Color(text) => text.clone(),
+        CSSToken::String(text) => format!("\"{}\"", text),
+        CSSToken::Number(n) => n.to_string(),
+        CSSToken::Dimension(n, unit) => format!("{}{}", n, unit),
+        CSSToken::Percentage(p) => format!("{}%", p),
+        CSSToken::Url(url) => format!("url({})", url),
+        CSSToken::AtKeyword(name) => format!("@{}", name),
+        CSSToken::LeftBrace => "{".to_string(),
+        CSSToken::RightBrace => "}".to_string(),
+        CSSToken::LeftParen => "(".to_string(),
+        CSSToken::RightParen => ")".to_string(),
+        CSSToken::LeftBracket => "[".to_string(),
+        CSSToken::RightBracket => "]".to_string(),
+        CSSToken::Colon => ":".to_string(),
+        CSSToken::Semicolon => ";".to_string(),
+        CSSToken::Comma => ",".to_string(),
+        CSSToken::Period => ".".to_string(),
+        CSSToken::Hash => "#".to_string(),
+        CSSToken::Asterisk => "*".to_string(),
+        CSSToken::Plus => "+".to_string(),
+        CSSToken::GreaterThan => ">".to_string(),
+        CSSToken::Tilde => "~".to_string(),
+        CSSToken::Equals => "=".to_string(),
+        CSSToken::Pipe => "|".to_string(),
+        CSSToken::Exclamation => "!".to_string(),
+        CSSToken::Whitespace | CSSToken::Comment(_) | CSSToken::Eof => String::new(),
+    }
+}
+
+/// Build the user agent stylesheet
+/// 
+/// These rules are applied before any author stylesheet, so author rules
+/// always win over them regardless of specificity.
+pub fn user_agent_stylesheet() -> Stylesheet {
+    fn display_rule(selector: Selector, display: &str) -> CSSRule {
+        CSSRule {
+            specificity: Specificity::calculate(&selector),
+            media: None,
+            sheet_media: None,
+            source_range: None,
+            selectors: vec![selector],
+            declarations: vec![CSSDeclaration {
+                property: "display".to_string(),
+                value: CSSValue::Keyword(display.to_string()),
+                important: false,
+            }],
         }
     }
     
-    pub fn add_stylesheet(&mut self, stylesheet: Stylesheet) {
+    Stylesheet {
+        page_rules: Vec::new(),
+        rules: vec![
+            display_rule(Selector::Type("details".to_string()), "block"),
+            display_rule(Selector::Type("summary".to_string()), "block"),
+            display_rule(Selector::Attribute("hidden".to_string(), None, None), "none"),
+        ],
+        source_url: Some("about:user-agent-stylesheet".to_string()),
+    }
+}
+
+/// Check whether a node is content of a closed `<details>` element
+/// 
+/// Everything inside a `<details>` element except its first `<summary>`
+/// is not rendered unless the element has the `open` attribute.
+pub fn is_closed_details_content(node: &Node) -> bool {
+    let parent = match node.parent.borrow().upgrade() {
+        Some(parent) => parent,
+        None => return false,
+    };
+    if parent.tag_name() != Some("details") || parent.has_attribute("open") {
+        return false;
+    }
+    let first_summary_id = parent
+        .children
+        .borrow()
+        .iter()
+        .find(|child| child.tag_name() == Some("summary"))
+        .map(|child| child.id);
+    first_summary_id != Some(node.id)
+}
+
+/// Parse the declarations of a `style` attribute
+/// 
+/// Declarations that cannot be parsed are skipped, like invalid
+/// declarations in a stylesheet.
+pub fn parse_inline_style(style: &str) -> Vec<CSSDeclaration> {
+    style
+        .split(';')
+        .filter_map(|declaration| {
+            let (property, value) = declaration.split_once(':')?;
+            let property = property.trim().to_ascii_lowercase();
+            let mut value = value.trim();
+            if property.is_empty() || value.is_empty() {
+                return None;
+            }
+            
+            let important = match value.to_ascii_lowercase().rfind("!important") {
+                Some(index) => {
+                    value = value[..index].trim_end();
+                    true
+                }
+                None => false,
+            };
+            
+            Some(CSSDeclaration {
+                property,
+                value: parse_declaration_value(value),
+                important,
+            })
+        })
+        .collect()
+}
+
+/// Convert a declaration value from text
+fn parse_declaration_value(value: &str) -> CSSValue {
+    let value = value.trim();
+    if value.starts_with('#') {
+        CSSValue::Color(value.to_string())
+    } else if let Some(number) = value.strip_suffix('%').and_then(|number| number.trim().parse::<f32>().ok()) {
+        CSSValue::Percentage(number)
+    } else if let Some(number) = value.strip_suffix("px") {
+        match number.parse::<f32>() {
+            Ok(num) => CSSValue::Dimension(num, "px".to_string()),
+            Err(_) => CSSValue::Keyword(value.to_string()),
+        }
+    } else if value == "0" {
+        CSSValue::Number(0.0)
+    } else if value.starts_with('"') || value.starts_with('\'') {
+        CSSValue::String(value.trim_matches(|c| c == '"' || c == '\'').to_string())
+    } else {
+        CSSValue::Keyword(value.to_string())
+    }
+}
+
+/// Map presentational HTML attributes to CSS declarations
+/// 
+/// Legacy attributes such as `bgcolor`, `align` and `<font color>`, and
+/// the `dir` attribute, become presentational hints. The cascade applies
+/// them after the user agent stylesheet and before every author rule, as
+/// if they were author rules with zero specificity, so any author CSS
+/// overrides them.
+pub fn presentational_hints(node: &Node) -> Vec<CSSDeclaration> {
+    let tag_name = match node.tag_name() {
+        Some(tag_name) => tag_name.to_ascii_lowercase(),
+        None => return Vec::new(),
+    };
+    let tag = tag_name.as_str();
+    let mut hints = Vec::new();
+    let mut hint = |property: &str, value: CSSValue| {
+        hints.push(CSSDeclaration {
+            property: property.to_string(),
+            value,
+            important: false,
+        });
+    };
+    
+    // Dimensions
+    if matches!(tag, "img" | "table" | "td" | "th" | "col" | "iframe" | "video" | "canvas" | "embed" | "object" | "hr") {
+        if let Some(width) = node.get_attribute("width").and_then(|value| parse_dimension_attribute(&value)) {
+            hint("width", width);
+        }
+    }
+    if matches!(tag, "img" | "table" | "td" | "th" | "tr" | "iframe" | "video" | "canvas" | "embed" | "object") {
+        if let Some(height) = node.get_attribute("height").and_then(|value| parse_dimension_attribute(&value)) {
+            hint("height", height);
+        }
+    }
+    
+    // Alignment
+    if let Some(align) = node.get_attribute("align").map(|value| value.trim().to_ascii_lowercase()) {
+        match tag {
+            "div" | "p" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "caption" | "td" | "th" | "tr" | "thead" | "tbody" | "tfoot" => {
+                let text_align = match align.as_str() {
+                    "left" | "right" | "justify" => Some(align.as_str()),
+                    "center" | "middle" => Some("center"),
+                    _ => None,
+                };
+                if let Some(text_align) = text_align {
+                    hint("text-align", CSSValue::Keyword(text_align.to_string()));
+                }
+            }
+            "img" | "table" | "iframe" | "object" | "embed" if align == "left" || align == "right" => {
+                hint("float", CSSValue::Keyword(align.clone()));
+            }
+            _ => {}
+        }
+    }
+    
+    // Background colour
+    if matches!(tag, "body" | "table" | "tr" | "td" | "th" | "thead" | "tbody" | "tfoot") {
+        if let Some(color) = node.get_attribute("bgcolor").and_then(|value| parse_legacy_color(&value)) {
+            hint("background-color", CSSValue::Color(color));
+        }
+    }
+    
+    // Borders
+    if matches!(tag, "table" | "img" | "object") {
+        if let Some(border) = node.get_attribute("border") {
+            // A bare `border` attribute on a table means a 1px border
+            let width = match border.trim() {
+                "" if tag == "table" => Some(1.0),
+                value => value.parse::<f32>().ok().filter(|width| *width >= 0.0),
+            };
+            if let Some(width) = width {
+                hint("border-width", CSSValue::Dimension(width, "px".to_string()));
+                if width > 0.0 {
+                    hint("border-style", CSSValue::Keyword("solid".to_string()));
+                }
+            }
+        }
+    }
+    
+    // Text colour and fonts
+    match tag {
+        "font" => {
+            if let Some(color) = node.get_attribute("color").and_then(|value| parse_legacy_color(&value)) {
+                hint("color", CSSValue::Color(color));
+            }
+            if let Some(face) = node.get_attribute("face").filter(|face| !face.trim().is_empty()) {
+                hint("font-family", CSSValue::String(face.trim().to_string()));
+            }
+            if let Some(size) = node.get_attribute("size").and_then(|value| legacy_font_size(&value)) {
+                hint("font-size", CSSValue::Keyword(size.to_string()));
+            }
+        }
+        "body" => {
+            if let Some(color) = node.get_attribute("text").and_then(|value| parse_legacy_color(&value)) {
+                hint("color", CSSValue::Color(color));
+            }
+        }
+        _ => {}
+    }
+    
+    // Directionality
+    let dir = node.get_attribute("dir").map(|value| value.trim().to_ascii_lowercase());
+    match dir.as_deref() {
+        Some("ltr" | "rtl") => {
+            hint("direction", CSSValue::Keyword(dir.clone().unwrap_or_default()));
+        }
+        Some("auto") => {
+            hint("direction", CSSValue::Keyword(node.directionality().as_str().to_string()));
+        }
+        _ => {}
+    }
+    let unicode_bidi = match (tag, dir.as_deref()) {
+        ("bdo", _) => Some("isolate-override"),
+        ("textarea" | "pre", Some("auto")) => Some("plaintext"),
+        (_, Some("ltr" | "rtl" | "auto")) | ("bdi", _) => Some("isolate"),
+        _ => None,
+    };
+    if let Some(unicode_bidi) = unicode_bidi {
+        hint("unicode-bidi", CSSValue::Keyword(unicode_bidi.to_string()));
+    }
+    
+    hints
+}
+
+/// Parse a dimension attribute such as `width="100"` or `width="50%"`
+fn parse_dimension_attribute(value: &str) -> Option<CSSValue> {
+    let value = value.trim();
+    if let Some(percentage) = value.strip_suffix('%') {
+        return percentage.trim().parse::<f32>().ok()
+            .filter(|percentage| *percentage >= 0.0)
+            .map(CSSValue::Percentage);
+    }
+    
+    // Legacy parsing takes the leading number, so "100px" is 100 pixels
+    let end = value.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(value.len());
+    value[..end].parse::<f32>().ok()
+        .filter(|pixels| *pixels >= 0.0)
+        .map(|pixels| CSSValue::Dimension(pixels, "px".to_string()))
+}
+
+/// Parse a legacy colour attribute value
+/// 
+/// Accepts colour names and hex colours with or without the leading `#`.
+fn parse_legacy_color(value: &str) -> Option<String> {
+    let value = value.trim();
+    if value.is_empty() || value.eq_ignore_ascii_case("transparent") {
+        return None;
+    }
+    if value.starts_with('#') {
+        return Some(value.to_ascii_lowercase());
+    }
+    if matches!(value.len(), 3 | 6) && value.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Some(format!("#{}", value.to_ascii_lowercase()));
+    }
+    if value.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Some(value.to_ascii_lowercase());
+    }
+    None
+}
+
+/// Map a `<font size>` value to a font-size keyword
+/// 
+/// Sizes range from 1 to 7; `+n` and `-n` are relative to the default of 3.
+fn legacy_font_size(value: &str) -> Option<&'static str> {
+    let value = value.trim();
+    let size = if let Some(relative) = value.strip_prefix('+') {
+        3 + relative.parse::<i32>().ok()?
+    } else if let Some(relative) = value.strip_prefix('-') {
+        3 - relative.parse::<i32>().ok()?
+    } else {
+        value.parse::<i32>().ok()?
+    };
+    
+    Some(match size.clamp(1, 7) {
+        1 => "x-small",
+        2 => "small",
+        3 => "medium",
+        4 => "large",
+        5 => "x-large",
+        6 => "xx-large",
+        _ => "xxx-large",
+    })
+}
+
+/// CSS cascade engine that applies styles to DOM nodes
+/// 
+/// Declarations are applied in cascade order: the user agent stylesheet,
+/// normal user declarations, presentational hints, author rules by
+/// specificity, the element's `style` attribute, and finally `!important`
+/// user declarations, which override everything the page sets.
+pub struct CSSCascadeEngine {
+    stylesheets: Vec<Stylesheet>,
+    cache: HashMap<String, Stylesheet>,
+    user_agent_stylesheet: Stylesheet,
+    /// Stylesheets of the user origin, e.g. the user's own `user.css`
+    user_stylesheets: Vec<Stylesheet>,
+    /// Environment `@media` rules are evaluated against
+    media_features: MediaFeatures,
+    /// Whether links match as unvisited, see `set_hide_visited`
+    hide_visited: bool,
+}
+
+impl CSSCascadeEngine {
+    pub fn new() -> Self {
+        CSSCascadeEngine {
+            stylesheets: Vec::new(),
+            cache: HashMap::new(),
+            user_agent_stylesheet: user_agent_stylesheet(),
+            user_stylesheets: Vec::new(),
+            media_features: MediaFeatures::default(),
+            hide_visited: false,
+        }
+    }
+    
+    /// Match every link as unvisited
+    /// 
+    /// Styles computed for scripts, such as `getComputedStyle`, must not
+    /// reveal which links the user visited.
+    pub fn set_hide_visited(&mut self, hide: bool) {
+        self.hide_visited = hide;
+    }
+    
+    pub fn add_stylesheet(&mut self, stylesheet: Stylesheet) {
         self.stylesheets.push(stylesheet);
     }
     
+    /// Add a stylesheet of the user origin
+    pub fn add_user_stylesheet(&mut self, stylesheet: Stylesheet) {
+        self.user_stylesheets.push(stylesheet);
+    }
+    
+    /// Remove all user stylesheets, e.g. before reloading them
+    pub fn clear_user_stylesheets(&mut self) {
+        self.user_stylesheets.clear();
+    }
+    
+    /// Set the environment `@media` rules are evaluated against
+    /// 
+    /// Styles computed before the change are not updated; compute them
+    /// again to apply rules whose media queries now match.
+    pub fn set_media_features(&mut self, features: MediaFeatures) {
+        self.media_features = features;
+    }
+    
+    pub fn media_features(&self) -> &MediaFeatures {
+        &self.media_features
+    }
+    
     pub fn add_stylesheet_from_url(&mut self, url: &str, content: String) -> Result<(), CSSError> {
+        self.add_stylesheet_from_url_with_media(url, content, None)
+    }
+    
+    /// Add a stylesheet that only applies where `media` matches, see
+    /// `Stylesheet::set_media`
+    pub fn add_stylesheet_from_url_with_media(&mut self, url: &str, content: String, media: Option<MediaQueryList>) -> Result<(), CSSError> {
         if self.cache.contains_key(url) {
             return Ok(());
         }
         
         let mut parser = CSSParser::new(content);
         let mut stylesheet = parser.parse_stylesheet()?;
-        stylesheet.source_url = Some(url.to_string());
+        stylesheet.set_media(media);
+        self.add_parsed_stylesheet_from_url(url, stylesheet);
         
+        Ok(())
+    }
+    
+    /// Add a stylesheet the caller parsed from the content of `url`, e.g.
+    /// to resolve its `url()` values first
+    /// 
+    /// Like `add_stylesheet_from_url`, a sheet whose URL was already added
+    /// is ignored.
+    pub fn add_parsed_stylesheet_from_url(&mut self, url: &str, mut stylesheet: Stylesheet) {
+        if self.cache.contains_key(url) {
+            return;
+        }
+        stylesheet.source_url = Some(url.to_string());
         self.cache.insert(url.to_string(), stylesheet.clone());
         self.stylesheets.push(stylesheet);
-        
-        Ok(())
     }
     
     /// Get the total number of CSS rules across all stylesheets
@@ -778,6 +1566,47 @@ impl CSSCascadeEngine {
         computed_styles
     }
     
+    /// Determine what must be restyled after `changed` state flags flip on
+    /// a node, across all stylesheets
+    pub fn invalidation_for_state_change(&self, node: &Node, changed: ElementState) -> StyleInvalidation {
+        std::iter::once(&self.user_agent_stylesheet)
+            .chain(self.user_stylesheets.iter())
+            .chain(self.stylesheets.iter())
+            .map(|stylesheet| invalidation_for_state_change(stylesheet, node, changed))
+            .max()
+            .unwrap_or(StyleInvalidation::None)
+    }
+    
+    /// Determine which elements must be restyled after a DOM mutation
+    /// because of `:has()` rules, across all stylesheets
+    pub fn invalidations_for_mutation(&self, node: &Node, mutation: &Mutation) -> HashMap<u64, StyleInvalidation> {
+        let mut invalidations = HashMap::new();
+        for stylesheet in std::iter::once(&self.user_agent_stylesheet).chain(self.user_stylesheets.iter()).chain(self.stylesheets.iter()) {
+            for (id, invalidation) in invalidations_for_mutation(stylesheet, node, mutation) {
+                let entry = invalidations.entry(id).or_insert(StyleInvalidation::None);
+                *entry = (*entry).max(invalidation);
+            }
+        }
+        invalidations
+    }
+    
+    /// Recompute the styles covered by an invalidation, leaving the rest of
+    /// `computed_styles` untouched
+    pub fn restyle(&self, node: &Node, invalidation: StyleInvalidation, computed_styles: &mut HashMap<u64, ComputedStyles>) {
+        match invalidation {
+            StyleInvalidation::None => {}
+            StyleInvalidation::Element => {
+                computed_styles.insert(node.id, self.compute_node_styles(node));
+            }
+            StyleInvalidation::Subtree => self.apply_styles_recursive(node, computed_styles),
+        }
+    }
+    
+    /// Compute the styles of a single element
+    pub fn compute_element_styles(&self, node: &Node) -> ComputedStyles {
+        self.compute_node_styles(node)
+    }
+    
     fn apply_styles_recursive(&self, node: &Node, computed_styles: &mut HashMap<u64, ComputedStyles>) {
         // Compute styles for this node
         let styles = self.compute_node_styles(node);
@@ -792,114 +1621,214 @@ impl CSSCascadeEngine {
     fn compute_node_styles(&self, node: &Node) -> ComputedStyles {
         let mut styles = ComputedStyles::default();
         
-        // Collect all matching rules
-        let mut matching_rules = Vec::new();
-        
-        for stylesheet in &self.stylesheets {
-            for rule in &stylesheet.rules {
-                for selector in &rule.selectors {
-                    if self.selector_matches(selector, node) {
-                        matching_rules.push((rule, selector));
-                    }
+        // User agent rules come first so that author rules override them
+        for rule in &self.user_agent_stylesheet.rules {
+            if rule.applies_to(&self.media_features) {
+                for declaration in self.matched_declarations(rule, node) {
+                    self.apply_declaration(&mut styles, declaration);
                 }
             }
         }
         
-        // Sort by specificity and source order
-        matching_rules.sort_by(|a, b| {
-            let spec_a = &a.0.specificity;
-            let spec_b = &b.0.specificity;
-            spec_a.cmp(spec_b)
-        });
+        // Normal user declarations sit between the user agent and the page
+        let user_rules = self.matching_rules(&self.user_stylesheets, node);
+        for declarations in &user_rules {
+            for declaration in declarations.iter().filter(|declaration| !declaration.important) {
+                self.apply_declaration(&mut styles, declaration);
+            }
+        }
+        
+        // Presentational hints sit below every author rule
+        for declaration in &presentational_hints(node) {
+            self.apply_declaration(&mut styles, declaration);
+        }
         
-        // Apply declarations in order
-        for (rule, _selector) in matching_rules {
-            for declaration in &rule.declarations {
+        // Apply author declarations by specificity and source order
+        for declarations in self.matching_rules(&self.stylesheets, node) {
+            for declaration in declarations {
                 self.apply_declaration(&mut styles, declaration);
             }
         }
         
+        // The style attribute overrides author rules
+        if let Some(style) = node.get_attribute("style") {
+            for declaration in &parse_inline_style(&style) {
+                self.apply_declaration(&mut styles, declaration);
+            }
+        }
+        
+        // Important user declarations override everything the page sets
+        for declarations in &user_rules {
+            for declaration in declarations.iter().filter(|declaration| declaration.important) {
+                self.apply_declaration(&mut styles, declaration);
+            }
+        }
+        
+        // Content of a closed <details> is never rendered
+        if is_closed_details_content(node) {
+            styles.display = Some("none".to_string());
+        }
+        
         // Apply inheritance
         self.apply_inheritance(&mut styles, node);
         
         styles
     }
     
+    /// Collect the declarations of the rules of `stylesheets` matching a
+    /// node, one list per rule, sorted by specificity and then source order
+    fn matching_rules<'a>(&self, stylesheets: &'a [Stylesheet], node: &Node) -> Vec<Vec<&'a CSSDeclaration>> {
+        let mut matching_rules: Vec<(&CSSRule, Vec<&CSSDeclaration>)> = stylesheets
+            .iter()
+            .flat_map(|stylesheet| stylesheet.rules.iter())
+            .filter(|rule| rule.applies_to(&self.media_features))
+            .map(|rule| (rule, self.matched_declarations(rule, node)))
+            .filter(|(_, declarations)| !declarations.is_empty())
+            .collect();
+        // The sort is stable, so rules of equal specificity keep source order
+        matching_rules.sort_by(|a, b| a.0.specificity.cmp(&b.0.specificity));
+        matching_rules.into_iter().map(|(_, declarations)| declarations).collect()
+    }
+    
+    /// Get the declarations of a rule that apply to a node: none if no
+    /// selector matches, and only the ones `:visited` may set if the rule
+    /// only matches through it
+    fn matched_declarations<'a>(&self, rule: &'a CSSRule, node: &Node) -> Vec<&'a CSSDeclaration> {
+        let matched: Vec<&Selector> = rule.selectors.iter().filter(|selector| self.selector_matches(selector, node)).collect();
+        rule.declarations
+            .iter()
+            .filter(|declaration| matched.iter().any(|selector| declaration_allowed_through(selector, declaration)))
+            .collect()
+    }
+    
     fn selector_matches(&self, selector: &Selector, node: &Node) -> bool {
-        match selector {
-            Selector::Universal => true,
-            Selector::Type(tag_name) => {
-                if let NodeType::Element { tag_name: node_tag, .. } = &node.node_type {
-                    node_tag == tag_name
-                } else {
-                    false
+        let mode = if self.hide_visited { MatchMode::Unvisited } else { MatchMode::Normal };
+        selector_matches_node(selector, node, mode)
+    }
+    
+    fn apply_declaration(&self, styles: &mut ComputedStyles, declaration: &CSSDeclaration) {
+        match declaration.property.as_str() {
+            "display" => {
+                if let CSSValue::Keyword(value) = &declaration.value {
+                    styles.display = Some(value.clone());
                 }
             }
-            Selector::Class(class_name) => {
-                if let NodeType::Element { attributes, .. } = &node.node_type {
-                    attributes.get("class").map_or(false, |class_attr| {
-                        class_attr.split_whitespace().any(|c| c == class_name)
-                    })
-                } else {
-                    false
+            "color" => {
+                if let CSSValue::Color(value) | CSSValue::Keyword(value) = &declaration.value {
+                    styles.color = Some(value.clone());
                 }
             }
-            Selector::Id(id_name) => {
-                if let NodeType::Element { attributes, .. } = &node.node_type {
-                    attributes.get("id").map_or(false, |id_attr| id_attr == id_name)
-                } else {
-                    false
+            "background-color" => {
+                if let CSSValue::Color(value) | CSSValue::Keyword(value) = &declaration.value {
+                    styles.background_color = Some(value.clone());
                 }
             }
-            Selector::Descendant(ancestor, descendant) => {
-                // Check if this node matches the descendant and has an ancestor that matches
-                if self.selector_matches(descendant, node) {
-                    self.has_matching_ancestor(node, ancestor)
-                } else {
-                    false
+            "text-align" => {
+                if let CSSValue::Keyword(value) = &declaration.value {
+                    styles.text_align = Some(value.clone());
+                }
+            }
+            "border-width" => {
+                if let CSSValue::Dimension(value, unit) = &declaration.value {
+                    styles.border_width = Some(format!("{}{}", value, unit));
+                }
+            }
+            "border-style" => {
+                if let CSSValue::Keyword(value) = &declaration.value {
+                    styles.border_style = Some(value.clone());
                 }
             }
-            Selector::Child(parent, child) => {
-                // Check if this node matches the child and its parent matches
-                if self.selector_matches(child, node) {
-                    if let Some(parent_node) = node.parent.borrow().upgrade() {
-                        self.selector_matches(parent, &parent_node)
-                    } else {
-                        false
+            "content-visibility" => {
+                if let CSSValue::Keyword(value) = &declaration.value {
+                    styles.content_visibility = Some(value.clone());
+                }
+            }
+            "position" => {
+                if let CSSValue::Keyword(value) = &declaration.value {
+                    styles.position = Some(value.clone());
+                }
+            }
+            "top" | "right" | "bottom" | "left" => {
+                let value = match &declaration.value {
+                    CSSValue::Dimension(value, unit) => Some(format!("{}{}", value, unit)),
+                    CSSValue::Number(value) => Some(value.to_string()),
+                    CSSValue::Keyword(value) => Some(value.clone()),
+                    _ => None,
+                };
+                if let Some(value) = value {
+                    match declaration.property.as_str() {
+                        "top" => styles.top = Some(value),
+                        "right" => styles.right = Some(value),
+                        "bottom" => styles.bottom = Some(value),
+                        _ => styles.left = Some(value),
                     }
-                } else {
-                    false
                 }
             }
-            _ => false, // Simplified for now
-        }
-    }
-    
-    fn has_matching_ancestor(&self, node: &Node, selector: &Selector) -> bool {
-        if let Some(parent) = node.parent.borrow().upgrade() {
-            if self.selector_matches(selector, &parent) {
-                return true;
+            "object-fit" => {
+                if let CSSValue::Keyword(value) = &declaration.value {
+                    styles.object_fit = Some(value.clone());
+                }
             }
-            return self.has_matching_ancestor(&parent, selector);
-        }
-        false
-    }
-    
-    fn apply_declaration(&self, styles: &mut ComputedStyles, declaration: &CSSDeclaration) {
-        match declaration.property.as_str() {
-            "display" => {
+            "object-position" => {
+                styles.object_position = Some(declaration.value.to_css_text());
+            }
+            "filter" => {
+                styles.filter = Some(declaration.value.to_css_text());
+            }
+            "mix-blend-mode" => {
                 if let CSSValue::Keyword(value) = &declaration.value {
-                    styles.display = Some(value.clone());
+                    styles.mix_blend_mode = Some(value.clone());
                 }
             }
-            "color" => {
-                if let CSSValue::Color(value) = &declaration.value {
-                    styles.color = Some(value.clone());
+            "isolation" => {
+                if let CSSValue::Keyword(value) = &declaration.value {
+                    styles.isolation = Some(value.clone());
                 }
             }
-            "background-color" => {
-                if let CSSValue::Color(value) = &declaration.value {
-                    styles.background_color = Some(value.clone());
+            "will-change" => {
+                styles.will_change = Some(declaration.value.to_css_text());
+            }
+            "direction" => {
+                if let CSSValue::Keyword(value) = &declaration.value {
+                    styles.direction = Some(value.clone());
+                }
+            }
+            "unicode-bidi" => {
+                if let CSSValue::Keyword(value) = &declaration.value {
+                    styles.unicode_bidi = Some(value.clone());
+                }
+            }
+            "hyphens" => {
+                if let CSSValue::Keyword(value) = &declaration.value {
+                    styles.hyphens = Some(value.clone());
+                }
+            }
+            "visibility" => {
+                if let CSSValue::Keyword(value) = &declaration.value {
+                    styles.visibility = Some(value.clone());
+                }
+            }
+            "font-style" => {
+                styles.font_style = Some(declaration.value.to_css_text());
+            }
+            "font-variant" => {
+                styles.font_variant = Some(declaration.value.to_css_text());
+            }
+            "font-feature-settings" => {
+                styles.font_feature_settings = Some(declaration.value.to_css_text());
+            }
+            "font-synthesis" => {
+                styles.font_synthesis = Some(declaration.value.to_css_text());
+            }
+            "contain-intrinsic-size" => {
+                match &declaration.value {
+                    CSSValue::Dimension(value, unit) => {
+                        styles.contain_intrinsic_size = Some(format!("{}{}", value, unit));
+                    }
+                    CSSValue::Keyword(value) => {
+                        styles.contain_intrinsic_size = Some(value.clone());
+                    }
+                    _ => {}
                 }
             }
             "font-family" => {
@@ -988,11 +1917,602 @@ impl CSSCascadeEngine {
     }
 }
 
+/// How state pseudo-classes match while matching selectors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MatchMode {
+    /// Match the element's current state
+    Normal,
+    /// State pseudo-classes (`:hover`, `:active`, `:focus`, `:visited`)
+    /// always match; invalidation uses this to find the rules a state
+    /// change could affect
+    IgnoreState,
+    /// Links match as if they were unvisited, for styles exposed to
+    /// scripts, which must not learn the user's history
+    Unvisited,
+    /// `:has()` always matches; invalidation uses this to find the
+    /// elements a mutation could turn into `:has()` subjects or out of them
+    IgnoreRelational,
+}
+
+/// Match a selector against a node
+fn selector_matches_node(selector: &Selector, node: &Node, mode: MatchMode) -> bool {
+    match selector {
+        Selector::Universal => true,
+        Selector::Type(tag_name) => {
+            if let NodeType::Element { tag_name: node_tag, .. } = &node.node_type {
+                node_tag == tag_name
+            } else {
+                false
+            }
+        }
+        Selector::Class(class_name) => {
+            node.get_attribute("class").is_some_and(|class_attr| {
+                class_attr.split_whitespace().any(|c| c == class_name)
+            })
+        }
+        Selector::Id(id_name) => {
+            node.get_attribute("id").is_some_and(|id_attr| &id_attr == id_name)
+        }
+        Selector::Attribute(name, operator, value) => {
+            match node.get_attribute(name) {
+                Some(actual) => match (operator.as_deref(), value) {
+                    (None, _) => true,
+                    (Some("="), Some(expected)) => &actual == expected,
+                    (Some("~="), Some(expected)) => actual.split_whitespace().any(|v| v == expected),
+                    (Some("^="), Some(expected)) => actual.starts_with(expected.as_str()),
+                    (Some("$="), Some(expected)) => actual.ends_with(expected.as_str()),
+                    (Some("*="), Some(expected)) => actual.contains(expected.as_str()),
+                    _ => false,
+                },
+                None => false,
+            }
+        }
+        Selector::Descendant(ancestor, descendant) => {
+            // Check if this node matches the descendant and has an ancestor that matches
+            selector_matches_node(descendant, node, mode) && has_matching_ancestor(node, ancestor, mode)
+        }
+        Selector::Child(parent, child) => {
+            // Check if this node matches the child and its parent matches
+            selector_matches_node(child, node, mode)
+                && node.parent.borrow().upgrade().is_some_and(|parent_node| selector_matches_node(parent, &parent_node, mode))
+        }
+        Selector::PseudoClass(name) => {
+            if mode == MatchMode::IgnoreState && ElementState::from_pseudo_class(name).is_some() {
+                true
+            } else {
+                pseudo_class_matches_in(name, node, mode)
+            }
+        }
+        Selector::Compound(selectors) => {
+            selectors.iter().all(|selector| selector_matches_node(selector, node, mode))
+        }
+        Selector::Group(selectors) => {
+            selectors.iter().any(|selector| selector_matches_node(selector, node, mode))
+        }
+        Selector::Has(_) if mode == MatchMode::IgnoreRelational => true,
+        Selector::Has(relative) => relative.iter().any(|relative| relative_matches(relative, node, mode)),
+        _ => false, // Simplified for now
+    }
+}
+
+/// Check whether an element reached from `anchor` matches a relative
+/// selector, with the walk `Element.matches()` uses for `:has()`
+fn relative_matches(relative: &RelativeSelector, anchor: &Node, mode: MatchMode) -> bool {
+    dom::selectors::relative_steps_match(anchor, &relative.steps, &|compound, candidate| selector_matches_node(compound, candidate, mode))
+}
+
+/// Check whether `:has()` matches an element
+pub fn has_matches(relative: &[RelativeSelector], node: &Node) -> bool {
+    relative.iter().any(|relative| relative.matches(node))
+}
+
+/// Check whether a selector uses `:has()`
+fn uses_has(selector: &Selector) -> bool {
+    match selector {
+        Selector::Has(_) => true,
+        Selector::Compound(selectors) | Selector::Group(selectors) => selectors.iter().any(uses_has),
+        Selector::Descendant(left, right) | Selector::Child(left, right) |
+        Selector::AdjacentSibling(left, right) | Selector::GeneralSibling(left, right) => uses_has(left) || uses_has(right),
+        _ => false,
+    }
+}
+
+fn has_matching_ancestor(node: &Node, selector: &Selector, mode: MatchMode) -> bool {
+    if let Some(parent) = node.parent.borrow().upgrade() {
+        if selector_matches_node(selector, &parent, mode) {
+            return true;
+        }
+        return has_matching_ancestor(&parent, selector, mode);
+    }
+    false
+}
+
+/// Check whether a pseudo-class matches a node
+pub fn pseudo_class_matches(name: &str, node: &Node) -> bool {
+    pseudo_class_matches_in(name, node, MatchMode::Normal)
+}
+
+fn pseudo_class_matches_in(name: &str, node: &Node, mode: MatchMode) -> bool {
+    let visited = mode != MatchMode::Unvisited && node.element_state().contains(ElementState::VISITED);
+    match name {
+        "any-link" => is_link(node),
+        "link" => is_link(node) && !visited,
+        "visited" => is_link(node) && visited,
+        _ => match ElementState::from_pseudo_class(name) {
+            Some(flag) => node.element_state().contains(flag),
+            None => false,
+        },
+    }
+}
+
+/// Check whether a selector can match through `:visited`
+pub fn selector_uses_visited(selector: &Selector) -> bool {
+    match selector {
+        Selector::PseudoClass(name) => name == "visited",
+        Selector::Compound(selectors) | Selector::Group(selectors) => selectors.iter().any(selector_uses_visited),
+        Selector::Descendant(left, right) | Selector::Child(left, right) |
+        Selector::AdjacentSibling(left, right) | Selector::GeneralSibling(left, right) => {
+            selector_uses_visited(left) || selector_uses_visited(right)
+        }
+        _ => false,
+    }
+}
+
+/// Check whether a declaration may apply to an element matched by
+/// `selector`
+/// 
+/// Rules matching through `:visited` may only change `color`: properties
+/// that change layout or load resources would let a page measure which
+/// links the user visited.
+pub fn declaration_allowed_through(selector: &Selector, declaration: &CSSDeclaration) -> bool {
+    declaration.property == "color" || !selector_uses_visited(selector)
+}
+
+/// Parse selector text such as `ul > li.item:hover, a`
+/// 
+/// Supports compound selectors (type, `.class`, `#id`, `[attr]`, `:pseudo`),
+/// the four combinators, `:has()` and selector lists.
+pub fn parse_selector_text(text: &str) -> Selector {
+    let mut selectors: Vec<Selector> = split_selector_list(text)
+        .into_iter()
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .map(parse_complex_selector)
+        .collect();
+    match selectors.len() {
+        0 => Selector::Type(text.trim().to_string()),
+        1 => selectors.remove(0),
+        _ => Selector::Group(selectors),
+    }
+}
+
+/// Split selector text at the commas outside parentheses and brackets
+fn split_selector_list(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (index, c) in text.char_indices() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                parts.push(&text[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+/// Split a complex selector into its compounds, each with the combinator
+/// before it, which is `None` for a first compound without one
+fn split_compounds(text: &str) -> Vec<(Option<Combinator>, &str)> {
+    let mut compounds = Vec::new();
+    let mut combinator = None;
+    let mut start = None;
+    let mut depth = 0usize;
+    for (index, c) in text.char_indices() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = depth.saturating_sub(1),
+            _ if depth > 0 => {}
+            '>' | '+' | '~' | ' ' | '\t' | '\n' | '\r' => {
+                if let Some(begin) = start.take() {
+                    compounds.push((combinator.take(), &text[begin..index]));
+                    combinator = Some(Combinator::Descendant);
+                }
+                match c {
+                    '>' => combinator = Some(Combinator::Child),
+                    '+' => combinator = Some(Combinator::NextSibling),
+                    '~' => combinator = Some(Combinator::SubsequentSibling),
+                    _ => {}
+                }
+                continue;
+            }
+            _ => {}
+        }
+        start.get_or_insert(index);
+    }
+    if let Some(begin) = start {
+        compounds.push((combinator, &text[begin..]));
+    }
+    compounds
+}
+
+/// Parse a selector with combinators
+fn parse_complex_selector(text: &str) -> Selector {
+    let mut result: Option<Selector> = None;
+    for (combinator, part) in split_compounds(text) {
+        let compound = parse_compound_selector(part);
+        result = Some(match (result, combinator) {
+            (None, _) => compound,
+            (Some(previous), Some(Combinator::Child)) => Selector::Child(Box::new(previous), Box::new(compound)),
+            (Some(previous), Some(Combinator::NextSibling)) => Selector::AdjacentSibling(Box::new(previous), Box::new(compound)),
+            (Some(previous), Some(Combinator::SubsequentSibling)) => Selector::GeneralSibling(Box::new(previous), Box::new(compound)),
+            (Some(previous), _) => Selector::Descendant(Box::new(previous), Box::new(compound)),
+        });
+    }
+    result.unwrap_or_else(|| Selector::Type(text.to_string()))
+}
+
+/// Parse the argument of `:has()`, e.g. `> img, + .note p`
+/// 
+/// Nested `:has()` is not allowed; relative selectors using it are
+/// dropped.
+fn parse_relative_selector_text(text: &str) -> Vec<RelativeSelector> {
+    split_selector_list(text)
+        .into_iter()
+        .map(|part| RelativeSelector {
+            steps: split_compounds(part)
+                .into_iter()
+                .map(|(combinator, compound)| (combinator.unwrap_or(Combinator::Descendant), parse_compound_selector(compound)))
+                .collect(),
+        })
+        .filter(|relative| !relative.steps.is_empty() && !relative.steps.iter().any(|(_, compound)| uses_has(compound)))
+        .collect()
+}
+
+/// Parse a compound selector such as `a.nav:hover`
+fn parse_compound_selector(text: &str) -> Selector {
+    // Split before each '.', '#', ':' and '[' that starts a simple selector
+    let mut parts: Vec<String> = Vec::new();
+    let mut in_brackets = false;
+    let mut parentheses = 0usize;
+    for c in text.chars() {
+        let nested = in_brackets || parentheses > 0;
+        let starts_part = !nested && matches!(c, '.' | '#' | '[')
+            || (c == ':' && !nested && parts.last().is_none_or(|part| part != ":"));
+        if starts_part || parts.is_empty() {
+            parts.push(String::new());
+        }
+        match c {
+            '[' => in_brackets = true,
+            ']' => in_brackets = false,
+            '(' => parentheses += 1,
+            ')' => parentheses = parentheses.saturating_sub(1),
+            _ => {}
+        }
+        parts.last_mut().unwrap().push(c);
+    }
+    
+    let mut selectors: Vec<Selector> = Vec::new();
+    for part in parts {
+        let selector = if let Some(name) = part.strip_prefix("::") {
+            Selector::PseudoElement(name.to_string())
+        } else if let Some(argument) = part.strip_prefix(":has(").and_then(|rest| rest.strip_suffix(')')) {
+            Selector::Has(parse_relative_selector_text(argument))
+        } else if let Some(name) = part.strip_prefix(':') {
+            Selector::PseudoClass(name.to_string())
+        } else if let Some(class) = part.strip_prefix('.') {
+            Selector::Class(class.to_string())
+        } else if let Some(id) = part.strip_prefix('#') {
+            Selector::Id(id.to_string())
+        } else if let Some(attribute) = part.strip_prefix('[') {
+            parse_attribute_selector(attribute.trim_end_matches(']'))
+        } else if part == "*" {
+            Selector::Universal
+        } else {
+            Selector::Type(part)
+        };
+        selectors.push(selector);
+    }
+    
+    if selectors.len() == 1 {
+        selectors.remove(0)
+    } else {
+        Selector::Compound(selectors)
+    }
+}
+
+/// Parse the inside of an attribute selector, e.g. `type="text"`
+fn parse_attribute_selector(text: &str) -> Selector {
+    for operator in ["~=", "^=", "$=", "*=", "="] {
+        if let Some((name, value)) = text.split_once(operator) {
+            let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+            return Selector::Attribute(name.trim().to_string(), Some(operator.to_string()), Some(value.to_string()));
+        }
+    }
+    Selector::Attribute(text.trim().to_string(), None, None)
+}
+
+/// How much of the tree must be restyled after an element's state changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum StyleInvalidation {
+    /// No rule depends on the changed state
+    None,
+    /// Only the element itself
+    Element,
+    /// The element and all of its descendants
+    Subtree,
+}
+
+/// Determine what must be restyled after `changed` state flags flip on a node
+/// 
+/// Rules whose subject compound uses the changed pseudo-class restyle the
+/// element itself; rules that use it further left (`li:hover a`) restyle
+/// the whole subtree. Everything else is left alone, so moving the pointer
+/// over a page with no `:hover` rules costs no restyle at all.
+pub fn invalidation_for_state_change(stylesheet: &Stylesheet, node: &Node, changed: ElementState) -> StyleInvalidation {
+    let mut invalidation = StyleInvalidation::None;
+    for rule in &stylesheet.rules {
+        for selector in &rule.selectors {
+            invalidation = invalidation.max(selector_invalidation(selector, node, changed));
+            if invalidation == StyleInvalidation::Subtree {
+                return invalidation;
+            }
+        }
+    }
+    invalidation
+}
+
+fn selector_invalidation(selector: &Selector, node: &Node, changed: ElementState) -> StyleInvalidation {
+    if let Selector::Group(selectors) = selector {
+        return selectors
+            .iter()
+            .map(|selector| selector_invalidation(selector, node, changed))
+            .max()
+            .unwrap_or(StyleInvalidation::None);
+    }
+    
+    let mut compounds = Vec::new();
+    collect_compounds(selector, &mut compounds);
+    let Some((subject, ancestors)) = compounds.split_last() else {
+        return StyleInvalidation::None;
+    };
+    
+    let affected = |compound: &Selector| {
+        compound_state(compound).intersects(changed) && selector_matches_node(compound, node, MatchMode::IgnoreState)
+    };
+    if ancestors.iter().any(|compound| affected(compound)) {
+        StyleInvalidation::Subtree
+    } else if affected(subject) {
+        StyleInvalidation::Element
+    } else {
+        StyleInvalidation::None
+    }
+}
+
+/// Collect the compound selectors of a complex selector, left to right
+fn collect_compounds<'a>(selector: &'a Selector, compounds: &mut Vec<&'a Selector>) {
+    match selector {
+        Selector::Descendant(left, right) | Selector::Child(left, right) |
+        Selector::AdjacentSibling(left, right) | Selector::GeneralSibling(left, right) => {
+            collect_compounds(left, compounds);
+            collect_compounds(right, compounds);
+        }
+        _ => compounds.push(selector),
+    }
+}
+
+/// The interaction state flags a compound selector depends on
+fn compound_state(selector: &Selector) -> ElementState {
+    match selector {
+        Selector::PseudoClass(name) => ElementState::from_pseudo_class(name).unwrap_or(ElementState::EMPTY),
+        Selector::Compound(selectors) => selectors
+            .iter()
+            .fold(ElementState::EMPTY, |state, selector| state | compound_state(selector)),
+        _ => ElementState::EMPTY,
+    }
+}
+
+/// A compound selector using `:has()`, and what to restyle when an element
+/// it matches may have started or stopped matching
+struct HasDependency<'a> {
+    compound: &'a Selector,
+    relative: Vec<&'a RelativeSelector>,
+    /// Whether the compound is the subject of its selector
+    subject: bool,
+    /// Whether a sibling combinator follows the compound, so elements
+    /// outside its subtree depend on it
+    siblings: bool,
+}
+
+/// Collect the compounds of a stylesheet's selectors that use `:has()`
+fn has_dependencies(stylesheet: &Stylesheet) -> Vec<HasDependency<'_>> {
+    let mut dependencies = Vec::new();
+    for selector in stylesheet.rules.iter().flat_map(|rule| &rule.selectors) {
+        let selectors = match selector {
+            Selector::Group(selectors) => selectors.iter().collect(),
+            selector => vec![selector],
+        };
+        for selector in selectors.into_iter().filter(|selector| uses_has(selector)) {
+            let mut compounds = Vec::new();
+            collect_compounds_with_siblings(selector, false, &mut compounds);
+            let last = compounds.len().saturating_sub(1);
+            for (index, (compound, siblings)) in compounds.into_iter().enumerate() {
+                let relative: Vec<&RelativeSelector> = match compound {
+                    Selector::Has(relative) => relative.iter().collect(),
+                    Selector::Compound(selectors) => selectors
+                        .iter()
+                        .filter_map(|selector| match selector {
+                            Selector::Has(relative) => Some(relative),
+                            _ => None,
+                        })
+                        .flatten()
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                if !relative.is_empty() {
+                    dependencies.push(HasDependency { compound, relative, subject: index == last, siblings });
+                }
+            }
+        }
+    }
+    dependencies
+}
+
+/// Collect the compounds of a complex selector left to right, with whether
+/// a sibling combinator follows them
+fn collect_compounds_with_siblings<'a>(selector: &'a Selector, siblings: bool, compounds: &mut Vec<(&'a Selector, bool)>) {
+    match selector {
+        Selector::Descendant(left, right) | Selector::Child(left, right) => {
+            collect_compounds_with_siblings(left, siblings, compounds);
+            collect_compounds_with_siblings(right, siblings, compounds);
+        }
+        Selector::AdjacentSibling(left, right) | Selector::GeneralSibling(left, right) => {
+            collect_compounds_with_siblings(left, true, compounds);
+            collect_compounds_with_siblings(right, siblings, compounds);
+        }
+        _ => compounds.push((selector, siblings)),
+    }
+}
+
+/// Check whether a relative selector can match differently after a
+/// mutation anywhere in the part of the tree it reaches
+fn mutation_affects(relative: &RelativeSelector, mutation: &Mutation) -> bool {
+    match mutation {
+        Mutation::ChildList { .. } => true,
+        Mutation::Attribute { name, .. } => relative.steps.iter().any(|(_, compound)| tests_attribute(compound, name)),
+        // No selector the cascade supports looks at text
+        Mutation::CharacterData { .. } => false,
+    }
+}
+
+/// Check whether a compound selector reads an attribute
+fn tests_attribute(selector: &Selector, attribute: &str) -> bool {
+    match selector {
+        Selector::Class(_) => attribute == "class",
+        Selector::Id(_) => attribute == "id",
+        Selector::Attribute(name, _, _) => name.eq_ignore_ascii_case(attribute),
+        Selector::PseudoClass(name) => matches!(name.as_str(), "link" | "any-link" | "visited") && attribute == "href",
+        Selector::Compound(selectors) => selectors.iter().any(|selector| tests_attribute(selector, attribute)),
+        _ => false,
+    }
+}
+
+/// Determine which elements must be restyled after a DOM mutation because
+/// a `:has()` rule may now match them differently
+/// 
+/// `node` is the node the mutation is reported on: the element whose
+/// attribute changed, or the parent whose children changed. Only elements
+/// whose `:has()` arguments reach the mutated part of the tree are
+/// candidates: its ancestors, and with sibling combinators the elements
+/// before it and before its ancestors. Of those, the ones matching a
+/// `:has()` compound apart from `:has()` itself are invalidated, so a
+/// mutation no `:has()` argument depends on restyles nothing, instead of
+/// the whole document.
+pub fn invalidations_for_mutation(stylesheet: &Stylesheet, node: &Node, mutation: &Mutation) -> HashMap<u64, StyleInvalidation> {
+    let mut invalidations = HashMap::new();
+    let dependencies: Vec<HasDependency> = has_dependencies(stylesheet)
+        .into_iter()
+        .filter(|dependency| dependency.relative.iter().any(|relative| mutation_affects(relative, mutation)))
+        .collect();
+    if dependencies.is_empty() {
+        return invalidations;
+    }
+    
+    // Elements reached only through their siblings are candidates for
+    // arguments with sibling combinators alone
+    let mut invalidate = |candidate: &Node, sibling: bool| {
+        for dependency in &dependencies {
+            if sibling && !dependency.relative.iter().any(|relative| relative.uses_siblings()) {
+                continue;
+            }
+            if !selector_matches_node(dependency.compound, candidate, MatchMode::IgnoreRelational) {
+                continue;
+            }
+            let (id, invalidation) = match candidate.parent.borrow().upgrade() {
+                Some(parent) if dependency.siblings => (parent.id, StyleInvalidation::Subtree),
+                _ if dependency.subject => (candidate.id, StyleInvalidation::Element),
+                _ => (candidate.id, StyleInvalidation::Subtree),
+            };
+            let entry = invalidations.entry(id).or_insert(StyleInvalidation::None);
+            *entry = (*entry).max(invalidation);
+        }
+    };
+    
+    let uses_siblings = dependencies.iter().any(|dependency| dependency.relative.iter().any(|relative| relative.uses_siblings()));
+    // The parent of added or removed children is an anchor itself, and
+    // any of its children may sit after the change
+    if let Mutation::ChildList { .. } = mutation {
+        invalidate(node, false);
+        if uses_siblings {
+            for child in node.children.borrow().iter().filter(|child| matches!(child.node_type, NodeType::Element { .. })) {
+                invalidate(child, true);
+            }
+        }
+    } else if uses_siblings {
+        dom::selectors::preceding_elements(node).iter().for_each(|sibling| invalidate(sibling, true));
+    }
+    let mut ancestor = node.parent.borrow().upgrade();
+    while let Some(element) = ancestor {
+        invalidate(&element, false);
+        if uses_siblings {
+            dom::selectors::preceding_elements(&element).iter().for_each(|sibling| invalidate(sibling, true));
+        }
+        ancestor = element.parent.borrow().upgrade();
+    }
+    invalidations
+}
+
+/// Determine which elements must be restyled after an attribute of
+/// `element` changed, because a selector outside `:has()` tests it
+/// 
+/// Whether the element matched before the change is not known, so every
+/// compound testing the attribute counts: as the subject it restyles the
+/// element, further left the element's subtree, and before a sibling
+/// combinator the subtree of its parent. `:has()` arguments are left to
+/// `invalidations_for_mutation`.
+pub fn invalidations_for_attribute_change(stylesheet: &Stylesheet, element: &Node, name: &str) -> HashMap<u64, StyleInvalidation> {
+    let mut invalidations = HashMap::new();
+    for selector in stylesheet.rules.iter().flat_map(|rule| &rule.selectors) {
+        let selectors = match selector {
+            Selector::Group(selectors) => selectors.iter().collect(),
+            selector => vec![selector],
+        };
+        for selector in selectors {
+            let mut compounds = Vec::new();
+            collect_compounds_with_siblings(selector, false, &mut compounds);
+            let last = compounds.len().saturating_sub(1);
+            for (index, (compound, siblings)) in compounds.into_iter().enumerate() {
+                if !tests_attribute(compound, name) {
+                    continue;
+                }
+                let (id, invalidation) = match element.parent.borrow().upgrade() {
+                    Some(parent) if siblings => (parent.id, StyleInvalidation::Subtree),
+                    _ if index == last => (element.id, StyleInvalidation::Element),
+                    _ => (element.id, StyleInvalidation::Subtree),
+                };
+                let entry = invalidations.entry(id).or_insert(StyleInvalidation::None);
+                *entry = (*entry).max(invalidation);
+            }
+        }
+    }
+    invalidations
+}
+
 /// Convenience function to parse CSS from string
 pub fn parse_css(input: &str) -> Stylesheet {
     // Simple CSS parser for basic rules
     let mut rules = Vec::new();
+    let mut page_rules = Vec::new();
     let mut lines = input.lines();
+    // Media queries of the `@media` block the lines are in
+    let mut media: Option<MediaQueryList> = None;
+    // Lines are slices of the input, so their offsets give rule ranges
+    let offset = |text: &str| text.as_ptr() as usize - input.as_ptr() as usize;
     
     while let Some(line) = lines.next() {
         let line = line.trim();
@@ -1002,6 +2522,18 @@ pub fn parse_css(input: &str) -> Stylesheet {
             continue;
         }
         
+        // An `@media` block opens on its own line and closes with a lone `}`
+        if let Some(prelude) = line.strip_prefix("@media") {
+            if let Some((prelude, _)) = prelude.split_once('{') {
+                media = Some(MediaQueryList::parse(prelude));
+            }
+            continue;
+        }
+        if line == "}" {
+            media = None;
+            continue;
+        }
+        
         // Look for CSS rules (selector { declarations })
         if line.contains('{') {
             // Extract selector
@@ -1009,64 +2541,75 @@ pub fn parse_css(input: &str) -> Stylesheet {
             if parts.len() >= 2 {
                 let selector_text = parts[0].trim();
                 let mut declarations_text = parts[1].to_string();
+                let start = offset(line);
+                let mut end = line.find('}').map_or(input.len(), |brace| start + brace + 1);
                 
-                // Collect remaining lines until we find the closing brace
-                while let Some(next_line) = lines.next() {
-                    declarations_text.push_str(next_line);
-                    if next_line.contains('}') {
-                        break;
+                // Collect remaining lines until we find the closing brace,
+                // unless the rule is on a single line
+                if !declarations_text.contains('}') {
+                    for next_line in lines.by_ref() {
+                        declarations_text.push_str(next_line);
+                        if let Some(brace) = next_line.find('}') {
+                            end = offset(next_line) + brace + 1;
+                            break;
+                        }
                     }
                 }
                 
                 // Parse selector
-                let selector = if selector_text.starts_with('.') {
-                    Selector::Class(selector_text[1..].to_string())
-                } else if selector_text.starts_with('#') {
-                    Selector::Id(selector_text[1..].to_string())
-                } else {
-                    Selector::Type(selector_text.to_string())
-                };
+                let selector = parse_selector_text(selector_text);
                 
                 // Parse declarations
                 let mut declarations = Vec::new();
+                let declarations_text = declarations_text.split('}').next().unwrap_or("");
                 let decl_parts: Vec<&str> = declarations_text.split(';').collect();
                 for decl in decl_parts {
                     let decl = decl.trim();
-                    if decl.contains(':') {
-                        let prop_parts: Vec<&str> = decl.split(':').collect();
-                        if prop_parts.len() >= 2 {
-                            let property = prop_parts[0].trim().to_string();
-                            let value = prop_parts[1].trim().to_string();
-                            
-                            // Convert value to CSSValue
-                            let css_value = if value.starts_with('#') {
-                                CSSValue::Color(value)
-                            } else if value.ends_with("px") {
-                                if let Ok(num) = value[..value.len()-2].parse::<f32>() {
-                                    CSSValue::Dimension(num, "px".to_string())
-                                } else {
-                                    CSSValue::Keyword(value)
-                                }
-                            } else if value == "0" {
-                                CSSValue::Number(0.0)
+                    // Split at the first colon only, as values such as
+                    // `url(https://...)` contain colons of their own
+                    if let Some((property, value)) = decl.split_once(':') {
+                        let property = property.trim().to_string();
+                        let value = value.trim().to_string();
+                        
+                        // Convert value to CSSValue
+                        let css_value = if value.starts_with('#') {
+                            CSSValue::Color(value)
+                        } else if value.ends_with("px") {
+                            if let Ok(num) = value[..value.len()-2].parse::<f32>() {
+                                CSSValue::Dimension(num, "px".to_string())
                             } else {
                                 CSSValue::Keyword(value)
-                            };
-                            
-                            declarations.push(CSSDeclaration {
-                                property,
-                                value: css_value,
-                                important: false,
-                            });
-                        }
+                            }
+                        } else if value == "0" {
+                            CSSValue::Number(0.0)
+                        } else if let Some(url) = value.strip_prefix("url(").and_then(|url| url.strip_suffix(')')) {
+                            CSSValue::Url(url.trim().to_string())
+                        } else {
+                            CSSValue::Keyword(value)
+                        };
+                        
+                        declarations.push(CSSDeclaration {
+                            property,
+                            value: css_value,
+                            important: false,
+                        });
                     }
                 }
                 
-                if !declarations.is_empty() {
+                if let Some(page_selector) = selector_text.strip_prefix("@page") {
+                    let page_selector = page_selector.trim();
+                    page_rules.push(PageRule {
+                        selector: (!page_selector.is_empty()).then(|| page_selector.to_string()),
+                        declarations,
+                    });
+                } else if !declarations.is_empty() {
                     let rule = CSSRule {
                         selectors: vec![selector],
                         declarations,
                         specificity: Specificity::new(),
+                        media: media.clone(),
+                        sheet_media: None,
+                        source_range: Some(start..end),
                     };
                     rules.push(rule);
                 }
@@ -1077,6 +2620,7 @@ pub fn parse_css(input: &str) -> Stylesheet {
     println!("🎨 Successfully parsed CSS with {} rules", rules.len());
     Stylesheet {
         rules,
+        page_rules,
         source_url: None,
     }
 }
@@ -1119,4 +2663,460 @@ mod tests {
         assert!(Specificity::calculate(&id_selector) > Specificity::calculate(&class_selector));
         assert!(Specificity::calculate(&class_selector) > Specificity::calculate(&type_selector));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_user_agent_stylesheet() {
+        let document = Document::new();
+        let mut hidden_attrs = HashMap::new();
+        hidden_attrs.insert("hidden".to_string(), String::new());
+        let hidden = document.create_node(NodeType::Element {
+            tag_name: "div".to_string(),
+            attributes: hidden_attrs,
+        });
+        let details = document.create_element("details");
+        let summary = document.create_element("summary");
+        let content = document.create_element("p");
+        
+        document.root.append_child(&hidden);
+        document.root.append_child(&details);
+        details.append_child(&summary);
+        details.append_child(&content);
+        
+        let engine = CSSCascadeEngine::new();
+        let styles = engine.compute_styles(&document);
+        assert_eq!(styles[&hidden.id].display.as_deref(), Some("none"));
+        assert_eq!(styles[&summary.id].display.as_deref(), Some("block"));
+        assert_eq!(styles[&content.id].display.as_deref(), Some("none"));
+        
+        // Opening the details element reveals its content
+        details.set_attribute("open", "");
+        let styles = engine.compute_styles(&document);
+        assert_ne!(styles[&content.id].display.as_deref(), Some("none"));
+        
+        // Author rules override the user agent stylesheet
+        let mut engine = CSSCascadeEngine::new();
+        engine.add_stylesheet(parse_css("div {\n  display: block;\n}"));
+        let styles = engine.compute_styles(&document);
+        assert_eq!(styles[&hidden.id].display.as_deref(), Some("block"));
+    }
+
+    #[test]
+    fn test_presentational_hints_cascade_level() {
+        let document = Document::new();
+        let cell = document.create_element("td");
+        cell.set_attribute("bgcolor", "ff0000");
+        cell.set_attribute("align", "middle");
+        cell.set_attribute("width", "50%");
+        let font = document.create_element("font");
+        font.set_attribute("color", "Navy");
+        font.set_attribute("size", "+2");
+        let table = document.create_element("table");
+        table.set_attribute("border", "");
+        document.root.append_child(&table);
+        table.append_child(&cell);
+        cell.append_child(&font);
+        
+        let engine = CSSCascadeEngine::new();
+        let styles = engine.compute_styles(&document);
+        assert_eq!(styles[&cell.id].background_color.as_deref(), Some("#ff0000"));
+        assert_eq!(styles[&cell.id].text_align.as_deref(), Some("center"));
+        assert_eq!(styles[&cell.id].width.as_deref(), Some("50%"));
+        assert_eq!(styles[&font.id].color.as_deref(), Some("navy"));
+        assert_eq!(styles[&font.id].font_size.as_deref(), Some("x-large"));
+        assert_eq!(styles[&table.id].border_width.as_deref(), Some("1px"));
+        
+        // Author rules beat hints, and the style attribute beats author rules
+        let mut engine = CSSCascadeEngine::new();
+        engine.add_stylesheet(parse_css("td {\n  background-color: #00ff00;\n  width: 20px;\n}"));
+        cell.set_attribute("style", "width: 30px !important");
+        let styles = engine.compute_styles(&document);
+        assert_eq!(styles[&cell.id].background_color.as_deref(), Some("#00ff00"));
+        assert_eq!(styles[&cell.id].width.as_deref(), Some("30px"));
+    }
+    
+    #[test]
+    fn test_parse_inline_style() {
+        let declarations = parse_inline_style("color: #333; ; width:50%;font-family: 'Times' !important; bogus");
+        assert_eq!(declarations.len(), 3);
+        assert_eq!(declarations[0].value, CSSValue::Color("#333".to_string()));
+        assert_eq!(declarations[1].value, CSSValue::Percentage(50.0));
+        assert_eq!(declarations[2].value, CSSValue::String("Times".to_string()));
+        assert!(declarations[2].important);
+    }
+
+    #[test]
+    fn test_parse_hex_colors_starting_with_digits() {
+        let css = "p { color: #008000; background-color: #0000ff; border-color: #123; outline-color: #1.5 }";
+        let stylesheet = CSSParser::new(css.to_string()).parse_stylesheet().unwrap();
+        let declarations = &stylesheet.rules[0].declarations;
+        assert_eq!(declarations.len(), 3);
+        assert_eq!(declarations[0].value, CSSValue::Color("#008000".to_string()));
+        assert_eq!(declarations[1].value, CSSValue::Color("#0000ff".to_string()));
+        assert_eq!(declarations[2].value, CSSValue::Color("#123".to_string()));
+    }
+
+    #[test]
+    fn test_parse_selector_text() {
+        assert_eq!(
+            parse_selector_text("ul > li.item:hover"),
+            Selector::Child(
+                Box::new(Selector::Type("ul".to_string())),
+                Box::new(Selector::Compound(vec![
+                    Selector::Type("li".to_string()),
+                    Selector::Class("item".to_string()),
+                    Selector::PseudoClass("hover".to_string()),
+                ])),
+            )
+        );
+        assert_eq!(
+            parse_selector_text("input[type=\"text\"], p::first-line"),
+            Selector::Group(vec![
+                Selector::Compound(vec![
+                    Selector::Type("input".to_string()),
+                    Selector::Attribute("type".to_string(), Some("=".to_string()), Some("text".to_string())),
+                ]),
+                Selector::Compound(vec![
+                    Selector::Type("p".to_string()),
+                    Selector::PseudoElement("first-line".to_string()),
+                ]),
+            ])
+        );
+    }
+    
+    #[test]
+    fn test_hover_matching_and_invalidation() {
+        let document = Document::new();
+        let item = document.create_element("li");
+        let link = document.create_element("a");
+        let button = document.create_element("button");
+        document.root.append_child(&item);
+        item.append_child(&link);
+        document.root.append_child(&button);
+        
+        let mut engine = CSSCascadeEngine::new();
+        engine.add_stylesheet(parse_css("a:hover {\n  color: #ff0000;\n}\nli:hover a {\n  background-color: #eeeeee;\n}"));
+        
+        // Only elements that some :hover rule can match need work
+        assert_eq!(engine.invalidation_for_state_change(&link, ElementState::HOVER), StyleInvalidation::Element);
+        assert_eq!(engine.invalidation_for_state_change(&item, ElementState::HOVER), StyleInvalidation::Subtree);
+        assert_eq!(engine.invalidation_for_state_change(&button, ElementState::HOVER), StyleInvalidation::None);
+        assert_eq!(engine.invalidation_for_state_change(&link, ElementState::ACTIVE), StyleInvalidation::None);
+        
+        let mut styles = engine.compute_styles(&document);
+        assert_eq!(styles[&link.id].color.as_deref(), Some("black"));
+        
+        let mut interaction = dom::element_state::InteractionState::new();
+        for change in interaction.set_hovered(Some(&link)) {
+            let invalidation = engine.invalidation_for_state_change(&change.node, change.changed);
+            engine.restyle(&change.node, invalidation, &mut styles);
+        }
+        assert_eq!(styles[&link.id].color.as_deref(), Some("#ff0000"));
+        assert_eq!(styles[&link.id].background_color.as_deref(), Some("#eeeeee"));
+    }
+
+    #[test]
+    fn test_has_matching_and_invalidation() {
+        let document = Document::new();
+        let list = document.create_element("ul");
+        let card = document.create_element("section");
+        let figure = document.create_element("figure");
+        let heading = document.create_element("h2");
+        let note = document.create_element("p");
+        document.root.append_child(&list);
+        list.append_child(&card);
+        card.append_child(&figure);
+        list.append_child(&heading);
+        list.append_child(&note);
+        
+        // Both parsers read relative selectors, with commas inside :has()
+        let mut parser = CSSParser::new("section:has(> figure img, .featured) { color: red } h2:has(+ p.note) { color: blue }".to_string());
+        let stylesheet = parser.parse_stylesheet().unwrap();
+        assert_eq!(stylesheet.rules[0].selectors, vec![parse_selector_text("section:has(> figure img, .featured)")]);
+        assert_eq!(stylesheet.rules[0].specificity, Specificity { a: 0, b: 1, c: 1, d: 0 });
+        assert!(CSSParser::new("a:has(b:has(c)) { color: red }".to_string()).parse_stylesheet().unwrap().rules.is_empty());
+        
+        let cards = parse_selector_text("section:has(> figure img, .featured)");
+        let headings = parse_selector_text("h2:has(+ p.note)");
+        assert!(!selector_matches_node(&cards, &card, MatchMode::Normal));
+        let image = document.create_element("img");
+        figure.append_child(&image);
+        assert!(selector_matches_node(&cards, &card, MatchMode::Normal));
+        assert!(!selector_matches_node(&parse_selector_text("ul:has(> figure)"), &list, MatchMode::Normal));
+        assert!(selector_matches_node(&parse_selector_text("ul:has(figure)"), &list, MatchMode::Normal));
+        assert!(!selector_matches_node(&headings, &heading, MatchMode::Normal));
+        
+        let mut engine = CSSCascadeEngine::new();
+        engine.add_stylesheet(parse_css("section:has(> figure img, .featured) {\n  color: #ff0000;\n}\nh2:has(+ p.note) {\n  color: #0000ff;\n}\nul:has([data-open]) li {\n  color: #00ff00;\n}"));
+        let mut styles = engine.compute_styles(&document);
+        assert_eq!(styles[&card.id].color.as_deref(), Some("#ff0000"));
+        
+        // Setting a class only :has(+ p.note) tests restyles the heading
+        // before it, and nothing else
+        note.set_attribute("class", "note");
+        let mutation = Mutation::Attribute { element: note.id, name: "class".to_string() };
+        let invalidations = engine.invalidations_for_mutation(&note, &mutation);
+        assert_eq!(invalidations, HashMap::from([(heading.id, StyleInvalidation::Element)]));
+        engine.restyle(&heading, invalidations[&heading.id], &mut styles);
+        assert_eq!(styles[&heading.id].color.as_deref(), Some("#0000ff"));
+        
+        // Attributes no :has() argument reads invalidate nothing; a :has()
+        // in an ancestor compound restyles the subtree
+        let title = Mutation::Attribute { element: note.id, name: "title".to_string() };
+        assert!(engine.invalidations_for_mutation(&note, &title).is_empty());
+        let open = Mutation::Attribute { element: figure.id, name: "data-open".to_string() };
+        assert_eq!(engine.invalidations_for_mutation(&figure, &open), HashMap::from([(list.id, StyleInvalidation::Subtree)]));
+        let removed = Mutation::ChildList { parent: figure.id };
+        assert_eq!(engine.invalidations_for_mutation(&figure, &removed)[&card.id], StyleInvalidation::Element);
+        assert!(engine.invalidations_for_mutation(&figure, &Mutation::CharacterData { node: figure.id }).is_empty());
+
+        // Outside :has(), a tested attribute restyles its element, its
+        // subtree for an ancestor compound and its parent's subtree before
+        // a sibling combinator
+        let stylesheet = parse_css("p.note {\n  color: #0000ff;\n}\nh2.note a {\n  color: #ff0000;\n}\nh2[data-open] + p {\n  color: #00ff00;\n}");
+        assert_eq!(invalidations_for_attribute_change(&stylesheet, &note, "class"), HashMap::from([(note.id, StyleInvalidation::Subtree)]));
+        assert_eq!(invalidations_for_attribute_change(&stylesheet, &heading, "data-open"), HashMap::from([(list.id, StyleInvalidation::Subtree)]));
+        assert!(invalidations_for_attribute_change(&stylesheet, &note, "title").is_empty());
+    }
+
+    #[test]
+    fn test_tokenizer_non_ascii_and_peek() {
+        let mut tokenizer = CSSTokenizer::new(".café { content: \"→\" } -webkit-box -5px".to_string());
+        let mut tokens = Vec::new();
+        loop {
+            let peeked = tokenizer.peek_token();
+            let token = tokenizer.next_token();
+            assert_eq!(peeked, token);
+            if token == CSSToken::Eof {
+                break;
+            }
+            tokens.push(token);
+        }
+        assert_eq!(tokens[1], CSSToken::Ident("café".to_string()));
+        assert_eq!(tokens[5], CSSToken::String("→".to_string()));
+        assert_eq!(tokens[7], CSSToken::Ident("-webkit-box".to_string()));
+        assert_eq!(tokens[8], CSSToken::Dimension(-5.0, "px".to_string()));
+    }
+
+    #[test]
+    fn test_parser_selectors_and_values() {
+        let css = "ul > li.item:hover, a[href] p { margin: 1px auto !important; width: calc(100% - 2px) }";
+        let stylesheet = CSSParser::new(css.to_string()).parse_stylesheet().unwrap();
+        let rule = &stylesheet.rules[0];
+
+        assert_eq!(rule.selectors.len(), 2);
+        assert_eq!(rule.selectors[0], parse_selector_text("ul > li.item:hover"));
+        assert_eq!(
+            rule.selectors[1],
+            Selector::Descendant(
+                Box::new(Selector::Compound(vec![
+                    Selector::Type("a".to_string()),
+                    Selector::Attribute("href".to_string(), None, None),
+                ])),
+                Box::new(Selector::Type("p".to_string())),
+            )
+        );
+        assert!(rule.declarations[0].important);
+        assert_eq!(
+            rule.declarations[0].value,
+            CSSValue::List(vec![CSSValue::Dimension(1.0, "px".to_string()), CSSValue::Keyword("auto".to_string())])
+        );
+        assert!(matches!(&rule.declarations[1].value, CSSValue::Function(name, arguments) if name == "calc" && arguments.len() == 2));
+    }
+
+    #[test]
+    fn test_parser_recovers_from_malformed_rules() {
+        let css = "div } p { color: red; } h1 { : ; color blue; margin: 0 } .{ x: y } a { width: calc((((1px";
+        let stylesheet = CSSParser::new(css.to_string()).parse_stylesheet().unwrap();
+
+        let selectors: Vec<_> = stylesheet.rules.iter().map(|rule| rule.selectors[0].clone()).collect();
+        assert_eq!(selectors, vec![
+            Selector::Type("p".to_string()),
+            Selector::Type("h1".to_string()),
+            Selector::Type("a".to_string()),
+        ]);
+        // The malformed declaration is skipped, the next one kept
+        assert_eq!(stylesheet.rules[1].declarations.len(), 1);
+        assert_eq!(stylesheet.rules[1].declarations[0].property, "margin");
+    }
+
+    #[test]
+    fn test_fuzz_seeds() {
+        let seeds = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/seeds");
+        for entry in std::fs::read_dir(seeds).unwrap() {
+            let path = entry.unwrap().path();
+            let input = String::from_utf8_lossy(&std::fs::read(&path).unwrap()).into_owned();
+
+            // The same checks as the fuzz targets
+            let length = input.chars().count();
+            let mut tokenizer = CSSTokenizer::new(input.clone());
+            let reached_eof = (0..=length).any(|_| tokenizer.next_token() == CSSToken::Eof);
+            assert!(reached_eof, "{}: tokenizer did not reach the end", path.display());
+            assert!(CSSParser::new(input.clone()).parse_stylesheet().is_ok(), "{}", path.display());
+            parse_css(&input);
+        }
+    }
+
+    #[test]
+    fn test_user_stylesheet_cascade_level() {
+        let document = Document::new();
+        let banner = document.create_element("div");
+        banner.set_attribute("class", "ad");
+        banner.set_attribute("style", "display: block");
+        let paragraph = document.create_element("p");
+        document.root.append_child(&banner);
+        document.root.append_child(&paragraph);
+        
+        let mut engine = CSSCascadeEngine::new();
+        engine.add_stylesheet(parse_css("p {\n  color: #111111;\n}"));
+        let user = CSSParser::new(".ad { display: none !important } p { color: #eeeeee; background-color: black }".to_string())
+            .parse_stylesheet()
+            .unwrap();
+        engine.add_user_stylesheet(user);
+        let styles = engine.compute_styles(&document);
+        
+        // Normal user declarations lose to the page but fill in what it leaves unset
+        assert_eq!(styles[&paragraph.id].color.as_deref(), Some("#111111"));
+        assert_eq!(styles[&paragraph.id].background_color.as_deref(), Some("black"));
+        // Important user declarations beat even the style attribute
+        assert_eq!(styles[&banner.id].display.as_deref(), Some("none"));
+        
+        engine.clear_user_stylesheets();
+        let styles = engine.compute_styles(&document);
+        assert_eq!(styles[&banner.id].display.as_deref(), Some("block"));
+    }
+
+    #[test]
+    fn test_media_rules() {
+        let css = "@media (prefers-color-scheme: dark) { p { color: white } @font-face { font-family: x } } p { color: black } @import url(a.css); div { color: red }";
+        let stylesheet = CSSParser::new(css.to_string()).parse_stylesheet().unwrap();
+        assert_eq!(stylesheet.rules.len(), 3);
+        assert_eq!(stylesheet.rules[0].media.as_ref().map(|media| media.text()), Some("(prefers-color-scheme: dark)"));
+        assert!(stylesheet.rules[1].media.is_none());
+        
+        // The line-based parser understands @media blocks too
+        let lines = parse_css("@media (prefers-color-scheme: dark) {\n  body { background-color: #111111; }\n  p { color: white; }\n}\np { font-size: 20px; }");
+        assert_eq!(lines.rules.len(), 3);
+        assert!(lines.rules[0].media.is_some() && lines.rules[1].media.is_some());
+        assert!(lines.rules[2].media.is_none());
+        
+        let document = Document::new();
+        let paragraph = document.create_element("p");
+        document.root.append_child(&paragraph);
+        let mut engine = CSSCascadeEngine::new();
+        engine.add_stylesheet(CSSParser::new("p { color: black } @media (prefers-color-scheme: dark) { p { color: white } }".to_string()).parse_stylesheet().unwrap());
+        assert_eq!(engine.compute_styles(&document)[&paragraph.id].color.as_deref(), Some("black"));
+        engine.set_media_features(media::MediaFeatures { color_scheme: media::ColorScheme::Dark, ..Default::default() });
+        assert_eq!(engine.compute_styles(&document)[&paragraph.id].color.as_deref(), Some("white"));
+    }
+
+    #[test]
+    fn test_page_rules() {
+        let css = "@page { size: A4; margin: 1in } @page :first { margin-top: 2in } p { color: black }";
+        let stylesheet = CSSParser::new(css.to_string()).parse_stylesheet().unwrap();
+        assert_eq!(stylesheet.rules.len(), 1);
+        let selectors: Vec<Option<&str>> = stylesheet.page_rules.iter().map(|rule| rule.selector.as_deref()).collect();
+        assert_eq!(selectors, [None, Some(":first")]);
+        let declarations: Vec<String> = stylesheet.page_rules[0].declarations.iter().map(|declaration| format!("{}: {}", declaration.property, declaration.value.to_css_text())).collect();
+        assert_eq!(declarations, ["size: A4", "margin: 1in"]);
+        
+        // The line-based parser keeps them out of the style rules too
+        let lines = parse_css("@page :left {
+  margin-left: 2cm;
+}
+p { color: black; }");
+        assert_eq!(lines.rules.len(), 1);
+        assert_eq!(lines.page_rules[0].selector.as_deref(), Some(":left"));
+        assert_eq!(lines.page_rules[0].declarations[0].property, "margin-left");
+    }
+
+    #[test]
+    fn test_stylesheet_media_attribute() {
+        let document = Document::new();
+        let style = document.create_element("style");
+        style.set_attribute("media", "print");
+        let paragraph = document.create_element("p");
+        document.root.append_child(&style);
+        document.root.append_child(&paragraph);
+        assert_eq!(stylesheet_media(&style).map(|media| media.text().to_string()).as_deref(), Some("print"));
+        style.set_attribute("media", " ");
+        assert!(stylesheet_media(&style).is_none());
+        
+        let mut engine = CSSCascadeEngine::new();
+        engine.add_stylesheet(parse_css("p { color: black; }"));
+        let narrow = "@media (prefers-color-scheme: dark) { p { color: white } } p { font-size: 12px }";
+        engine.add_stylesheet_from_url_with_media("narrow.css", narrow.to_string(), Some(MediaQueryList::parse("(max-width: 600px)"))).unwrap();
+        engine.add_stylesheet_from_url_with_media("print.css", "p { color: gray }".to_string(), Some(MediaQueryList::parse("print"))).unwrap();
+        let styles = engine.compute_styles(&document);
+        assert_eq!((styles[&paragraph.id].color.as_deref(), styles[&paragraph.id].font_size.as_deref()), (Some("black"), Some("16px")));
+        
+        // Both the sheet's and the rule's queries must match
+        engine.set_media_features(media::MediaFeatures { viewport_width: 400.0, ..Default::default() });
+        let styles = engine.compute_styles(&document);
+        assert_eq!((styles[&paragraph.id].color.as_deref(), styles[&paragraph.id].font_size.as_deref()), (Some("black"), Some("12px")));
+        engine.set_media_features(media::MediaFeatures { viewport_width: 400.0, color_scheme: media::ColorScheme::Dark, ..Default::default() });
+        assert_eq!(engine.compute_styles(&document)[&paragraph.id].color.as_deref(), Some("white"));
+        engine.set_media_features(media::MediaFeatures { media_type: "print".to_string(), ..Default::default() });
+        assert_eq!(engine.compute_styles(&document)[&paragraph.id].color.as_deref(), Some("gray"));
+    }
+
+    #[test]
+    fn test_visited_links_only_change_color() {
+        let document = Document::new();
+        let visited = document.create_element("a");
+        visited.set_attribute("href", "https://example.com/read");
+        visited.set_element_state(ElementState::VISITED, true);
+        let unvisited = document.create_element("a");
+        unvisited.set_attribute("href", "https://example.com/new");
+        let anchor = document.create_element("a");
+        document.root.append_child(&visited);
+        document.root.append_child(&unvisited);
+        document.root.append_child(&anchor);
+        
+        let mut engine = CSSCascadeEngine::new();
+        engine.add_stylesheet(parse_css("a:link {\n  color: blue;\n}\na:visited {\n  color: purple;\n  display: none;\n  background-color: #ff0000;\n}"));
+        let styles = engine.compute_styles(&document);
+        assert_eq!(styles[&visited.id].color.as_deref(), Some("purple"));
+        // Only color may differ, so layout cannot reveal the history
+        assert_eq!(styles[&visited.id].display, None);
+        assert_eq!(styles[&visited.id].background_color, None);
+        assert_eq!(styles[&unvisited.id].color.as_deref(), Some("blue"));
+        // An anchor without href is not a link
+        assert_eq!(styles[&anchor.id].color.as_deref(), Some("black"));
+        
+        // Styles exposed to scripts treat every link as unvisited
+        engine.set_hide_visited(true);
+        assert_eq!(engine.compute_element_styles(&visited).color.as_deref(), Some("blue"));
+    }
+
+    #[test]
+    fn test_rules_record_their_source_ranges() {
+        let css = "/* é */ p { color: red }\n@media print {\n  .note, h1 { color: black; }\n}\ndiv > span{margin:0}";
+        let stylesheet = CSSParser::new(css.to_string()).parse_stylesheet().unwrap();
+        let texts: Vec<&str> = stylesheet.rules.iter().map(|rule| &css[rule.source_range.clone().unwrap()]).collect();
+        assert_eq!(texts, vec!["p { color: red }", ".note, h1 { color: black; }", "div > span{margin:0}"]);
+        
+        let css = "h1 { color: red }\np {\n  margin: 0;\n}\n";
+        let stylesheet = parse_css(css);
+        let texts: Vec<&str> = stylesheet.rules.iter().map(|rule| &css[rule.source_range.clone().unwrap()]).collect();
+        assert_eq!(texts, vec!["h1 { color: red }", "p {\n  margin: 0;\n}"]);
+    }
+
+    #[test]
+    fn test_resolve_urls() {
+        let css = ".hero { background-image: url('img/hero.png') } .icon { background: #ffffff url(icon.svg) no-repeat } .data { background-image: url(\"data:image/png;base64,AA==\") }";
+        let mut stylesheet = CSSParser::new(css.to_string()).parse_stylesheet().unwrap();
+        stylesheet.resolve_urls(|url| (!url.starts_with("data:")).then(|| format!("https://example.com/css/{}", url)));
+        let values: Vec<String> = stylesheet.rules.iter().map(|rule| rule.declarations[0].value.to_css_text()).collect();
+        assert_eq!(values, [
+            "url('https://example.com/css/img/hero.png')",
+            "#ffffff url(https://example.com/css/icon.svg) no-repeat",
+            "url(\"data:image/png;base64,AA==\")",
+        ]);
+
+        // The line-based parser of `<style>` elements keeps them too
+        let mut inline = parse_css(".a {\n  background-image: url(https://cdn.example/a.png);\n}\n.b {\n  background-image: url( b.png );\n}");
+        inline.resolve_urls(|url| (!url.contains(':')).then(|| format!("https://example.com/{}", url)));
+        let values: Vec<String> = inline.rules.iter().map(|rule| rule.declarations[0].value.to_css_text()).collect();
+        assert_eq!(values, ["url(https://cdn.example/a.png)", "url(https://example.com/b.png)"]);
+    }
+}
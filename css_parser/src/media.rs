@@ -0,0 +1,339 @@
+//! # Media Queries
+//!
+//! This module parses media query lists, as found in `@media` rules and
+//! `window.matchMedia()`, and evaluates them against the features of the
+//! environment a document is shown in.
+//!
+//! ## Design Principles
+//!
+//! 1. **Environment as Data**: `MediaFeatures` holds everything a query can
+//!    test, so the shell decides where values come from (OS settings, a
+//!    toggle, a test) and the cascade only compares.
+//! 2. **Parse Once**: Queries are parsed when the stylesheet is parsed and
+//!    evaluated on every restyle, so changing a preference never requires
+//!    re-parsing CSS.
+//! 3. **Unknown Means False**: A query using an unknown feature, value or
+//!    syntax never matches, as if it were `not all`, so pages cannot apply
+//!    rules meant for environments we do not understand.
+
+use std::fmt;
+
+/// The color scheme the user prefers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorScheme {
+    #[default]
+    Light,
+    Dark,
+}
+
+impl ColorScheme {
+    /// Parse a `prefers-color-scheme` value
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "light" => Some(ColorScheme::Light),
+            "dark" => Some(ColorScheme::Dark),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ColorScheme::Light => "light",
+            ColorScheme::Dark => "dark",
+        }
+    }
+}
+
+/// The environment media queries are evaluated against
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaFeatures {
+    /// The media type, `screen` for the shell's windows
+    pub media_type: String,
+    pub viewport_width: f32,
+    pub viewport_height: f32,
+    pub color_scheme: ColorScheme,
+    /// Whether the user asked for as little motion as possible
+    pub reduced_motion: bool,
+    /// Whether pages are shown with the system's limited palette, as in
+    /// high-contrast modes
+    pub forced_colors: bool,
+}
+
+impl Default for MediaFeatures {
+    fn default() -> Self {
+        MediaFeatures {
+            media_type: "screen".to_string(),
+            viewport_width: 800.0,
+            viewport_height: 600.0,
+            color_scheme: ColorScheme::Light,
+            reduced_motion: false,
+            forced_colors: false,
+        }
+    }
+}
+
+/// A feature test such as `(min-width: 600px)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaFeatureTest {
+    pub name: String,
+    /// The value, `None` in boolean context such as `(prefers-color-scheme)`
+    pub value: Option<String>,
+}
+
+impl MediaFeatureTest {
+    /// Check whether the test holds in an environment
+    pub fn matches(&self, features: &MediaFeatures) -> bool {
+        let value = self.value.as_deref().map(str::to_ascii_lowercase);
+        match (self.name.as_str(), value.as_deref()) {
+            ("prefers-color-scheme", None) => true,
+            ("prefers-color-scheme", Some(value)) => {
+                ColorScheme::parse(value) == Some(features.color_scheme)
+            }
+            ("prefers-reduced-motion", None) | ("prefers-reduced-motion", Some("reduce")) => features.reduced_motion,
+            ("prefers-reduced-motion", Some("no-preference")) => !features.reduced_motion,
+            ("forced-colors", None) | ("forced-colors", Some("active")) => features.forced_colors,
+            ("forced-colors", Some("none")) => !features.forced_colors,
+            ("orientation", Some("portrait")) => features.viewport_height >= features.viewport_width,
+            ("orientation", Some("landscape")) => features.viewport_width > features.viewport_height,
+            ("width", value) => compare_length(value, features.viewport_width, |actual, expected| actual == expected),
+            ("min-width", value) => compare_length(value, features.viewport_width, |actual, expected| actual >= expected),
+            ("max-width", value) => compare_length(value, features.viewport_width, |actual, expected| actual <= expected),
+            ("height", value) => compare_length(value, features.viewport_height, |actual, expected| actual == expected),
+            ("min-height", value) => compare_length(value, features.viewport_height, |actual, expected| actual >= expected),
+            ("max-height", value) => compare_length(value, features.viewport_height, |actual, expected| actual <= expected),
+            _ => false,
+        }
+    }
+}
+
+/// Compare a viewport dimension with a length value
+///
+/// In boolean context, a dimension matches when it is not zero.
+fn compare_length(value: Option<&str>, actual: f32, compare: fn(f32, f32) -> bool) -> bool {
+    let Some(value) = value else {
+        return actual != 0.0;
+    };
+    let (number, scale) = if let Some(number) = value.strip_suffix("px") {
+        (number, 1.0)
+    } else if let Some(number) = value.strip_suffix("rem").or_else(|| value.strip_suffix("em")) {
+        (number, 16.0)
+    } else {
+        (value, 1.0)
+    };
+    match number.trim().parse::<f32>() {
+        // Unitless lengths other than zero are invalid
+        Ok(number) if scale != 1.0 || value.ends_with("px") || number == 0.0 => compare(actual, number * scale),
+        _ => false,
+    }
+}
+
+/// One query of a media query list, e.g. `screen and (min-width: 600px)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaQuery {
+    pub negated: bool,
+    /// The media type, `None` if the query only tests features
+    pub media_type: Option<String>,
+    pub features: Vec<MediaFeatureTest>,
+    /// Whether the query could be parsed; invalid queries never match
+    pub valid: bool,
+}
+
+impl MediaQuery {
+    fn invalid() -> Self {
+        MediaQuery { negated: false, media_type: None, features: Vec::new(), valid: false }
+    }
+
+    /// Parse a single query
+    pub fn parse(text: &str) -> Self {
+        let mut words = query_words(text);
+        if words.is_empty() {
+            return MediaQuery::invalid();
+        }
+        let mut query = MediaQuery { negated: false, media_type: None, features: Vec::new(), valid: true };
+
+        let mut index = 0;
+        match words[0].to_ascii_lowercase().as_str() {
+            "not" => {
+                query.negated = true;
+                index = 1;
+            }
+            "only" => index = 1,
+            _ => {}
+        }
+        if let Some(word) = words.get(index).filter(|word| !word.starts_with('(')) {
+            query.media_type = Some(word.to_ascii_lowercase());
+            index += 1;
+            // Features follow the media type after `and`
+            if index < words.len() {
+                if !words[index].eq_ignore_ascii_case("and") || index + 1 >= words.len() {
+                    return MediaQuery::invalid();
+                }
+                index += 1;
+            }
+        } else if query.negated && index < words.len() {
+            // `not (feature)` negates the feature test
+        } else if index > 0 {
+            return MediaQuery::invalid();
+        }
+
+        let rest = words.split_off(index.min(words.len()));
+        for (position, word) in rest.iter().enumerate() {
+            if position % 2 == 1 {
+                if !word.eq_ignore_ascii_case("and") {
+                    return MediaQuery::invalid();
+                }
+                continue;
+            }
+            let Some(inner) = word.strip_prefix('(').and_then(|word| word.strip_suffix(')')) else {
+                return MediaQuery::invalid();
+            };
+            let test = match inner.split_once(':') {
+                Some((name, value)) => MediaFeatureTest {
+                    name: name.trim().to_ascii_lowercase(),
+                    value: Some(value.trim().to_string()),
+                },
+                None => MediaFeatureTest { name: inner.trim().to_ascii_lowercase(), value: None },
+            };
+            if test.name.is_empty() || test.value.as_deref() == Some("") {
+                return MediaQuery::invalid();
+            }
+            query.features.push(test);
+        }
+        if rest.len().is_multiple_of(2) && !rest.is_empty() {
+            // Ends with a dangling `and`
+            return MediaQuery::invalid();
+        }
+        query
+    }
+
+    /// Check whether the query matches an environment
+    pub fn matches(&self, features: &MediaFeatures) -> bool {
+        if !self.valid {
+            return false;
+        }
+        let type_matches = match self.media_type.as_deref() {
+            None | Some("all") => true,
+            Some(media_type) => media_type == features.media_type,
+        };
+        let matches = type_matches && self.features.iter().all(|test| test.matches(features));
+        matches != self.negated
+    }
+}
+
+/// Split a query into words, keeping each parenthesized feature whole
+fn query_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0usize;
+    for ch in text.chars() {
+        match ch {
+            '(' => {
+                if depth == 0 && !current.trim().is_empty() {
+                    words.push(std::mem::take(&mut current).trim().to_string());
+                }
+                depth += 1;
+                current.push(ch);
+            }
+            ')' => {
+                current.push(ch);
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    words.push(std::mem::take(&mut current).trim().to_string());
+                }
+            }
+            c if c.is_whitespace() && depth == 0 => {
+                if !current.trim().is_empty() {
+                    words.push(std::mem::take(&mut current).trim().to_string());
+                }
+                current.clear();
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.trim().is_empty() {
+        words.push(current.trim().to_string());
+    }
+    words
+}
+
+/// A comma-separated list of media queries
+///
+/// The list matches if any of its queries matches; an empty list matches
+/// every environment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaQueryList {
+    text: String,
+    queries: Vec<MediaQuery>,
+}
+
+impl MediaQueryList {
+    /// Parse a media query list such as `screen, (prefers-color-scheme: dark)`
+    pub fn parse(text: &str) -> Self {
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        let queries = if text.is_empty() {
+            Vec::new()
+        } else {
+            text.split(',').map(MediaQuery::parse).collect()
+        };
+        MediaQueryList { text, queries }
+    }
+
+    /// The serialized list, as exposed by `MediaQueryList.media`
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn queries(&self) -> &[MediaQuery] {
+        &self.queries
+    }
+
+    /// Check whether the list matches an environment
+    pub fn matches(&self, features: &MediaFeatures) -> bool {
+        self.queries.is_empty() || self.queries.iter().any(|query| query.matches(features))
+    }
+}
+
+impl fmt::Display for MediaQueryList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_media_query_evaluation() {
+        let light = MediaFeatures::default();
+        let dark = MediaFeatures { color_scheme: ColorScheme::Dark, reduced_motion: true, forced_colors: true, ..MediaFeatures::default() };
+
+        let scheme = MediaQueryList::parse("(prefers-color-scheme:   dark)");
+        assert_eq!(scheme.text(), "(prefers-color-scheme: dark)");
+        assert!(!scheme.matches(&light));
+        assert!(scheme.matches(&dark));
+
+        let motion = MediaQueryList::parse("(prefers-reduced-motion: no-preference)");
+        assert!(motion.matches(&light));
+        assert!(!motion.matches(&dark));
+        assert!(MediaQueryList::parse("(prefers-reduced-motion)").matches(&dark));
+
+        let forced = MediaQueryList::parse("(forced-colors: active)");
+        assert!(!forced.matches(&light));
+        assert!(forced.matches(&dark));
+        assert!(MediaQueryList::parse("(forced-colors: none)").matches(&light));
+        assert!(MediaQueryList::parse("(forced-colors)").matches(&dark));
+
+        assert!(MediaQueryList::parse("screen and (min-width: 600px) and (max-width: 50em)").matches(&light));
+        assert!(!MediaQueryList::parse("print").matches(&light));
+        assert!(MediaQueryList::parse("print, (orientation: landscape)").matches(&light));
+        assert!(MediaQueryList::parse("not print").matches(&light));
+        assert!(MediaQueryList::parse("").matches(&light));
+
+        // Unknown features and broken syntax never match
+        assert!(!MediaQueryList::parse("(prefers-contrast-ratio: 7)").matches(&light));
+        assert!(!MediaQueryList::parse("screen and").matches(&light));
+        assert!(!MediaQueryList::parse("(min-width: 600)").matches(&light));
+        assert!(!MediaQueryList::parse("not (prefers-color-scheme: light) and").matches(&light));
+    }
+}
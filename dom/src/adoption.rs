@@ -0,0 +1,192 @@
+//! # Node Ownership
+//!
+//! This module tracks which document owns a node and moves nodes between
+//! documents, for `adoptNode()`, `importNode()`, iframes and template
+//! instantiation.
+//!
+//! ## Design Principles
+//!
+//! 1. **Owner by Id**: Every node records the id of its owner document, the
+//!    id of the document's root. Nodes made with `Node::new` have no owner
+//!    until they are inserted into a document's tree.
+//!
+//! 2. **Ids Survive Adoption**: Node ids are unique across documents, so an
+//!    adopted node keeps its id and tables keyed by node id, such as event
+//!    listeners and script handles, stay valid. `adopt_node` returns the
+//!    ids of the adopted subtree so such tables can move their entries.
+//!
+//! 3. **Insertion Adopts**: `append_child` adopts a child from another
+//!    document into the parent's document, as the DOM does. Operations that
+//!    cannot do that report a `WrongDocumentError` instead of corrupting
+//!    either tree.
+
+use std::fmt;
+use std::rc::Rc;
+use crate::{Document, Node, NodeType};
+
+/// A node that cannot be moved to or used with a document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdoptError {
+    /// Documents cannot be adopted or imported
+    NotSupported,
+    /// The node belongs to a different document than the one it is used with
+    WrongDocument { node: u64, owner: Option<u64>, document: u64 },
+}
+
+impl AdoptError {
+    /// The name of the `DOMException` scripts see
+    pub fn name(&self) -> &'static str {
+        match self {
+            AdoptError::NotSupported => "NotSupportedError",
+            AdoptError::WrongDocument { .. } => "WrongDocumentError",
+        }
+    }
+}
+
+impl fmt::Display for AdoptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdoptError::NotSupported => write!(f, "documents cannot be adopted or imported"),
+            AdoptError::WrongDocument { node, owner: Some(owner), document } => {
+                write!(f, "node {} belongs to document {}, not document {}", node, owner, document)
+            }
+            AdoptError::WrongDocument { node, owner: None, document } => {
+                write!(f, "node {} belongs to no document, not document {}", node, document)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AdoptError {}
+
+impl Node {
+    /// Get the id of the document that owns this node
+    pub fn owner_document_id(&self) -> Option<u64> {
+        Some(self.owner.get()).filter(|&owner| owner != 0)
+    }
+
+    /// Make a document the owner of this node and its descendants
+    pub(crate) fn set_owner(&self, owner: u64) {
+        self.owner.set(owner);
+        for child in self.children.borrow().iter() {
+            child.set_owner(owner);
+        }
+    }
+}
+
+impl Document {
+    /// Get the id of this document, which its nodes record as their owner
+    pub fn id(&self) -> u64 {
+        self.root.id
+    }
+
+    /// Check whether this document owns a node
+    pub fn owns(&self, node: &Node) -> bool {
+        node.owner.get() == self.id()
+    }
+
+    /// Fail with a `WrongDocumentError` unless this document owns a node
+    pub fn check_owns(&self, node: &Node) -> Result<(), AdoptError> {
+        if self.owns(node) {
+            return Ok(());
+        }
+        Err(AdoptError::WrongDocument { node: node.id, owner: node.owner_document_id(), document: self.id() })
+    }
+
+    /// Move a node and its descendants into this document, like
+    /// `adoptNode()`
+    ///
+    /// The node is removed from its parent. Returns the ids of the adopted
+    /// nodes, for moving entries of tables keyed by node id.
+    pub fn adopt_node(&self, node: &Rc<Node>) -> Result<Vec<u64>, AdoptError> {
+        if matches!(node.node_type, NodeType::Document) {
+            return Err(AdoptError::NotSupported);
+        }
+        let parent = node.parent.borrow().upgrade();
+        if let Some(parent) = parent {
+            parent.remove_child(node);
+        }
+        node.set_owner(self.id());
+
+        let mut ids = Vec::new();
+        collect_ids(node, &mut ids);
+        Ok(ids)
+    }
+
+    /// Copy a node, with its descendants when `deep`, into this document,
+    /// like `importNode()`
+    ///
+    /// The copy has new ids and the current attributes and text of the
+    /// original, which is left unchanged.
+    pub fn import_node(&self, node: &Node, deep: bool) -> Result<Rc<Node>, AdoptError> {
+        let copy = match &node.node_type {
+            NodeType::Document => return Err(AdoptError::NotSupported),
+            NodeType::Text(_) => self.create_text_node(&node.character_data().unwrap_or_default()),
+            NodeType::Element { tag_name, .. } => {
+                let element_type = NodeType::Element { tag_name: tag_name.clone(), attributes: Default::default() };
+                let copy = Node::new_namespaced(element_type, self.get_next_id(), node.namespace.clone(), node.prefix.clone());
+                copy.owner.set(self.id());
+                for (name, value) in node.attributes_ns() {
+                    match &name.namespace {
+                        None => copy.set_attribute(&name.local_name, &value),
+                        Some(_) => copy.namespaced_attributes.borrow_mut().push((name, value)),
+                    }
+                }
+                copy
+            }
+        };
+        if deep {
+            for child in node.children.borrow().iter() {
+                copy.append_child(&self.import_node(child, true)?);
+            }
+        }
+        Ok(copy)
+    }
+}
+
+/// Append the ids of a node and its descendants
+fn collect_ids(node: &Node, ids: &mut Vec<u64>) {
+    ids.push(node.id);
+    for child in node.children.borrow().iter() {
+        collect_ids(child, ids);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adopt_and_import_between_documents() {
+        let source = Document::new();
+        let target = Document::new();
+        let list = source.create_element("ul");
+        source.root.append_child(&list);
+        let item = source.create_element("li");
+        item.set_attribute("class", "a");
+        list.append_child(&item);
+        item.append_child(&source.create_text_node("x"));
+        assert!(source.owns(&item) && !target.owns(&item));
+
+        let copy = target.import_node(&list, true).unwrap();
+        assert!(target.owns(&copy) && target.owns(&copy.children.borrow()[0].children.borrow()[0]));
+        assert_ne!(copy.id, list.id);
+        assert_eq!(copy.outer_html(), list.outer_html());
+        assert!(target.import_node(&list, false).unwrap().children.borrow().is_empty());
+
+        let ids = target.adopt_node(&item).unwrap();
+        assert_eq!(ids.len(), 2);
+        assert_eq!(ids[0], item.id);
+        assert!(list.children.borrow().is_empty());
+        assert!(item.parent.borrow().upgrade().is_none());
+        assert_eq!(item.owner_document_id(), Some(target.id()));
+        assert_eq!(source.check_owns(&item).unwrap_err().name(), "WrongDocumentError");
+
+        // Inserting adopts implicitly
+        list.append_child(&item);
+        assert!(source.owns(&item.children.borrow()[0]));
+
+        assert_eq!(target.adopt_node(&source.root), Err(AdoptError::NotSupported));
+        assert_eq!(target.import_node(&source.root, true).unwrap_err().name(), "NotSupportedError");
+    }
+}
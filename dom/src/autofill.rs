@@ -0,0 +1,169 @@
+//! # Login Form Detection
+//!
+//! This module finds the login forms of a document for the password
+//! manager: which field takes the password, which one the username, what
+//! the user submitted and how to fill saved credentials back in.
+//!
+//! ## Design Principles
+//!
+//! 1. **Association Decides**: Fields belong to the form that owns them,
+//!    as for submission, so a login form is a form owning exactly one
+//!    password field. Forms with several, such as sign-up and
+//!    change-password forms, are left alone.
+//! 2. **Authors Can Say**: `autocomplete="username"` picks the username
+//!    field and `autocomplete="new-password"` marks a password that must
+//!    not be filled. Otherwise the username is the last text field before
+//!    the password, as in other browsers.
+//! 3. **Never Overwrite**: Filling only touches empty fields, so what the
+//!    user already typed wins.
+
+use std::rc::Rc;
+use crate::forms::FormControlType;
+use crate::Node;
+
+/// A username and password a login form was submitted with, or that is
+/// filled into one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+/// A form the user signs in with
+#[derive(Debug, Clone)]
+pub struct LoginForm {
+    pub form: Rc<Node>,
+    pub username: Option<Rc<Node>>,
+    pub password: Rc<Node>,
+}
+
+impl LoginForm {
+    /// Detect whether a `<form>` element is a login form
+    pub fn detect(form: &Rc<Node>) -> Option<LoginForm> {
+        let fields: Vec<Rc<Node>> = descendants(form)
+            .into_iter()
+            .filter(|field| field.form_owner().is_some_and(|owner| Rc::ptr_eq(&owner, form)))
+            .filter(|field| !field.has_attribute("disabled"))
+            .collect();
+        let mut passwords = fields.iter().filter(|field| field.form_control_type() == Some(FormControlType::Password));
+        let (Some(password), None) = (passwords.next(), passwords.next()) else {
+            return None;
+        };
+        if autocomplete(password).as_deref() == Some("new-password") {
+            return None;
+        }
+        let text_fields = fields.iter().filter(|field| field.form_control_type() == Some(FormControlType::Text));
+        let username = text_fields
+            .clone()
+            .find(|field| autocomplete(field).as_deref() == Some("username"))
+            .or_else(|| text_fields.take_while(|field| !Rc::ptr_eq(field, password)).last())
+            .cloned();
+        Some(LoginForm { form: Rc::clone(form), username, password: Rc::clone(password) })
+    }
+
+    /// Get the credentials the form holds, if a password is entered
+    pub fn credentials(&self) -> Option<Credentials> {
+        let password = self.password.value();
+        let username = self.username.as_ref().map(|field| field.value()).unwrap_or_default();
+        (!password.is_empty()).then_some(Credentials { username, password })
+    }
+
+    /// Check whether a form data set was submitted from this form, by its
+    /// password entry
+    ///
+    /// A password field without a name is not submitted, so its form never
+    /// matches.
+    pub fn is_submitted_in(&self, entries: &[(String, String)]) -> bool {
+        let Some(name) = self.password.get_attribute("name").filter(|name| !name.is_empty()) else {
+            return false;
+        };
+        let value = self.password.value();
+        entries.iter().any(|(entry_name, entry_value)| *entry_name == name && *entry_value == value)
+    }
+
+    /// Fill credentials into the empty fields of the form
+    ///
+    /// Returns the number of fields filled.
+    pub fn fill(&self, credentials: &Credentials) -> usize {
+        let mut filled = 0;
+        let fields = self.username.iter().map(|field| (field, &credentials.username)).chain([(&self.password, &credentials.password)]);
+        for (field, value) in fields {
+            if field.value().is_empty() && !value.is_empty() && !field.has_attribute("readonly") {
+                field.set_value(value);
+                filled += 1;
+            }
+        }
+        filled
+    }
+}
+
+/// Find the login forms below a node, in tree order
+pub fn login_forms(root: &Rc<Node>) -> Vec<LoginForm> {
+    let mut forms: Vec<Rc<Node>> = descendants(root).into_iter().filter(|node| node.tag_name() == Some("form")).collect();
+    if root.tag_name() == Some("form") {
+        forms.insert(0, Rc::clone(root));
+    }
+    forms.iter().filter_map(LoginForm::detect).collect()
+}
+
+/// The `autocomplete` attribute of a field, lowercased
+fn autocomplete(field: &Node) -> Option<String> {
+    field.get_attribute("autocomplete").map(|value| value.trim().to_ascii_lowercase())
+}
+
+/// All descendants of a node in tree order
+fn descendants(node: &Rc<Node>) -> Vec<Rc<Node>> {
+    let mut result = Vec::new();
+    for child in node.children.borrow().iter() {
+        result.push(Rc::clone(child));
+        result.extend(descendants(child));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Document;
+
+    fn element(document: &Document, parent: &Rc<Node>, tag: &str, attributes: &[(&str, &str)]) -> Rc<Node> {
+        let node = document.create_element(tag);
+        for (name, value) in attributes {
+            node.set_attribute(name, value);
+        }
+        parent.append_child(&node);
+        node
+    }
+
+    #[test]
+    fn test_login_forms_are_detected_and_filled() {
+        let document = Document::new();
+        let login = element(&document, &document.root, "form", &[]);
+        element(&document, &login, "input", &[("name", "search")]);
+        let user = element(&document, &login, "input", &[("name", "user"), ("type", "email")]);
+        let password = element(&document, &login, "input", &[("name", "pass"), ("type", "password")]);
+        let signup = element(&document, &document.root, "form", &[]);
+        element(&document, &signup, "input", &[("type", "password")]);
+        element(&document, &signup, "input", &[("type", "password")]);
+        let change = element(&document, &document.root, "form", &[]);
+        element(&document, &change, "input", &[("type", "password"), ("autocomplete", "new-password")]);
+
+        let forms = login_forms(&document.root);
+        assert_eq!(forms.len(), 1);
+        let form = &forms[0];
+        assert!(Rc::ptr_eq(form.username.as_ref().unwrap(), &user));
+        assert!(form.credentials().is_none());
+
+        let saved = Credentials { username: "ada@example.com".to_string(), password: "hunter2".to_string() };
+        assert_eq!(form.fill(&saved), 2);
+        assert_eq!(form.credentials(), Some(saved.clone()));
+        assert!(form.is_submitted_in(&[("user".to_string(), saved.username.clone()), ("pass".to_string(), saved.password.clone())]));
+        assert!(!form.is_submitted_in(&[("pass".to_string(), "other".to_string())]));
+
+        // What the user typed is kept
+        password.set_value("typed");
+        user.set_value("");
+        assert_eq!(form.fill(&saved), 1);
+        assert_eq!(password.value(), "typed");
+    }
+}
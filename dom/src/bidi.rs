@@ -0,0 +1,339 @@
+//! # Bidirectional Text
+//!
+//! This module applies the Unicode Bidirectional Algorithm (UAX #9) to
+//! paragraphs that mix left-to-right and right-to-left scripts: it resolves
+//! an embedding level for every character, reorders lines into visual order
+//! and moves carets through text the way it is displayed.
+//!
+//! ## Design Principles
+//!
+//! 1. **Logical Storage, Visual Display**: Text is always stored in logical
+//!    order. Only line layout and caret movement look at the visual order,
+//!    so editing and scripts never see reordered text.
+//!
+//! 2. **Shared by Layout and Editing**: Layout reorders the runs of a line
+//!    with the same levels that caret movement in form controls uses, so the
+//!    caret always moves over what is painted.
+//!
+//! 3. **Full Unicode Data**: Levels, line reordering and bidi classes come
+//!    from `unicode-bidi`, which follows the complete Unicode database. This
+//!    module only maps its byte offsets to the character indices the rest
+//!    of the engine uses and adds mirroring (rule L4).
+
+use std::ops::Range;
+use unicode_bidi::{Level, ParagraphBidiInfo};
+use crate::Node;
+
+/// Inline base direction (`direction`, `dir`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Direction {
+    #[default]
+    Ltr,
+    Rtl,
+}
+
+impl Direction {
+    /// Parse `ltr` or `rtl`
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "ltr" => Some(Direction::Ltr),
+            "rtl" => Some(Direction::Rtl),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Ltr => "ltr",
+            Direction::Rtl => "rtl",
+        }
+    }
+
+    /// Get the paragraph embedding level of the direction
+    pub fn level(self) -> u8 {
+        match self {
+            Direction::Ltr => 0,
+            Direction::Rtl => 1,
+        }
+    }
+
+    /// Get the direction of an embedding level
+    pub fn of_level(level: u8) -> Self {
+        if level % 2 == 1 {
+            Direction::Rtl
+        } else {
+            Direction::Ltr
+        }
+    }
+}
+
+/// Get the mirrored glyph of a character painted right-to-left
+pub fn mirrored(c: char) -> char {
+    match c {
+        '(' => ')',
+        ')' => '(',
+        '[' => ']',
+        ']' => '[',
+        '{' => '}',
+        '}' => '{',
+        '<' => '>',
+        '>' => '<',
+        '«' => '»',
+        '»' => '«',
+        '‹' => '›',
+        '›' => '‹',
+        _ => c,
+    }
+}
+
+/// Get the direction of the first strong character of the first
+/// paragraph of some text, as `dir=auto` does
+pub fn first_strong_direction(text: &str) -> Option<Direction> {
+    match unicode_bidi::get_base_direction(text) {
+        unicode_bidi::Direction::Ltr => Some(Direction::Ltr),
+        unicode_bidi::Direction::Rtl => Some(Direction::Rtl),
+        unicode_bidi::Direction::Mixed => None,
+    }
+}
+
+/// A run of characters with one embedding level
+#[derive(Debug, Clone, PartialEq)]
+pub struct BidiRun {
+    /// Character indices of the run, in logical order
+    pub range: Range<usize>,
+    pub level: u8,
+}
+
+impl BidiRun {
+    pub fn is_rtl(&self) -> bool {
+        self.level % 2 == 1
+    }
+}
+
+/// A paragraph of text with its resolved embedding levels
+#[derive(Debug, Clone, PartialEq)]
+pub struct BidiParagraph {
+    /// The text `unicode-bidi` resolves, which starts with an override
+    /// control for `bidi-override`
+    source: String,
+    /// Byte offset in `source` of every character, and of the end
+    offsets: Vec<usize>,
+    chars: Vec<char>,
+    levels: Vec<u8>,
+    base_level: u8,
+}
+
+impl BidiParagraph {
+    /// Resolve the embedding levels of a paragraph
+    ///
+    /// `direction` is the base direction; `None` takes it from the first
+    /// strong character, as `dir=auto` and `unicode-bidi: plaintext` do.
+    pub fn new(text: &str, direction: Option<Direction>) -> Self {
+        let base = direction.or_else(|| first_strong_direction(text)).unwrap_or_default();
+        Self::resolve(String::new(), text, base)
+    }
+
+    /// Resolve a paragraph whose characters are all forced to one
+    /// direction (`unicode-bidi: bidi-override`)
+    pub fn overridden(text: &str, direction: Direction) -> Self {
+        // An unterminated LRO or RLO overrides the rest of the paragraph
+        let control = match direction {
+            Direction::Ltr => '\u{202D}',
+            Direction::Rtl => '\u{202E}',
+        };
+        Self::resolve(control.to_string(), text, direction)
+    }
+
+    fn resolve(mut source: String, text: &str, base: Direction) -> Self {
+        let prefix = source.len();
+        source.push_str(text);
+        let chars: Vec<char> = text.chars().collect();
+        let offsets: Vec<usize> = text.char_indices().map(|(offset, _)| prefix + offset).chain([source.len()]).collect();
+        let info = ParagraphBidiInfo::new(&source, Some(Level::from(base.level())));
+        let levels = offsets[..chars.len()].iter().map(|&offset| info.levels[offset].number()).collect();
+        BidiParagraph { source, offsets, chars, levels, base_level: base.level() }
+    }
+
+    fn info(&self) -> ParagraphBidiInfo<'_> {
+        ParagraphBidiInfo::new(&self.source, Some(Level::from(self.base_level)))
+    }
+
+    /// Get the byte range in `source` of a range of characters
+    fn byte_range(&self, line: &Range<usize>) -> Range<usize> {
+        self.offsets[line.start]..self.offsets[line.end]
+    }
+
+    /// Get the index of the character at a byte offset in `source`
+    fn char_index(&self, offset: usize) -> usize {
+        self.offsets.partition_point(|&start| start < offset)
+    }
+
+    pub fn base_direction(&self) -> Direction {
+        Direction::of_level(self.base_level)
+    }
+
+    pub fn len(&self) -> usize {
+        self.chars.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+
+    /// Get the resolved level of every character
+    pub fn levels(&self) -> &[u8] {
+        &self.levels
+    }
+
+    /// Get the levels of a line, with trailing whitespace and separators
+    /// reset to the paragraph level (rule L1)
+    pub fn line_levels(&self, line: Range<usize>) -> Vec<u8> {
+        let levels = self.info().reordered_levels(self.byte_range(&line));
+        self.offsets[line].iter().map(|&offset| levels[offset].number()).collect()
+    }
+
+    /// Get the character indices of a line in visual order, left to right
+    /// (rule L2)
+    pub fn visual_order(&self, line: Range<usize>) -> Vec<usize> {
+        let levels: Vec<Level> = self.line_levels(line.clone()).into_iter().map(Level::from).collect();
+        ParagraphBidiInfo::reorder_visual(&levels).into_iter().map(|index| line.start + index).collect()
+    }
+
+    /// Get the runs of a line in visual order, left to right
+    pub fn visual_runs(&self, line: Range<usize>) -> Vec<BidiRun> {
+        let (levels, runs) = self.info().visual_runs(self.byte_range(&line));
+        runs.into_iter()
+            .map(|run| BidiRun { level: levels[run.start].number(), range: self.char_index(run.start)..self.char_index(run.end) })
+            .collect()
+    }
+
+    /// Get the text of a run as it is painted left to right, reversed and
+    /// mirrored if the run is right-to-left (rule L4)
+    pub fn visual_text(&self, run: &BidiRun) -> String {
+        let chars = &self.chars[run.range.clone()];
+        if run.is_rtl() {
+            chars.iter().rev().map(|&c| mirrored(c)).collect()
+        } else {
+            chars.iter().collect()
+        }
+    }
+
+    /// Get the visual boundary (0 is the left edge) of a caret before the
+    /// logical character index `caret`
+    ///
+    /// A caret sits beside the character before it, or beside the first
+    /// character at the start of the text.
+    fn caret_boundary(&self, order: &[usize], levels: &[u8], caret: usize) -> usize {
+        if self.chars.is_empty() {
+            return 0;
+        }
+        let position = |index: usize| order.iter().position(|&i| i == index).unwrap_or(0);
+        if caret == 0 {
+            position(0) + (levels[0] % 2) as usize
+        } else {
+            let before = caret.min(self.chars.len()) - 1;
+            position(before) + 1 - (levels[before] % 2) as usize
+        }
+    }
+
+    /// Move a caret one character to the left or right on screen
+    ///
+    /// The caret is a logical character index, as stored by form controls.
+    /// Some visual boundaries share a logical index, such as both ends of a
+    /// right-to-left run; those are skipped so the caret always moves in the
+    /// direction of the key that was pressed.
+    pub fn move_caret(&self, caret: usize, rightward: bool) -> usize {
+        let len = self.chars.len();
+        let caret = caret.min(len);
+        let levels = self.line_levels(0..len);
+        let order = self.visual_order(0..len);
+        let from = self.caret_boundary(&order, &levels, caret);
+        let mut boundary = from;
+        loop {
+            // Step over the character on the side the caret moves to
+            let target = if rightward {
+                if boundary >= len {
+                    return caret;
+                }
+                let index = order[boundary];
+                boundary += 1;
+                index + 1 - (levels[index] % 2) as usize
+            } else {
+                if boundary == 0 {
+                    return caret;
+                }
+                boundary -= 1;
+                let index = order[boundary];
+                index + (levels[index] % 2) as usize
+            };
+            let reached = self.caret_boundary(&order, &levels, target);
+            if (rightward && reached > from) || (!rightward && reached < from) {
+                return target;
+            }
+        }
+    }
+}
+
+impl Node {
+    /// Get the directionality of an element from its `dir` attribute or
+    /// its ancestors'
+    ///
+    /// `dir=auto` takes the direction of the first strong character of a
+    /// form control's value or of the element's text.
+    pub fn directionality(&self) -> Direction {
+        match self.get_attribute("dir").map(|dir| dir.trim().to_ascii_lowercase()).as_deref() {
+            Some("ltr") => return Direction::Ltr,
+            Some("rtl") => return Direction::Rtl,
+            Some("auto") => {
+                let text = if self.form_control_type().is_some() { self.value() } else { self.text_content() };
+                return first_strong_direction(&text).unwrap_or_default();
+            }
+            _ => {}
+        }
+        match self.parent.borrow().upgrade() {
+            Some(parent) => parent.directionality(),
+            None => Direction::Ltr,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mixed_paragraphs_reorder_runs() {
+        // Hebrew alef, bet, gimel inside an English sentence
+        let paragraph = BidiParagraph::new("abc \u{5d0}\u{5d1}\u{5d2} def", None);
+        assert_eq!(paragraph.base_direction(), Direction::Ltr);
+        assert_eq!(paragraph.levels(), &[0, 0, 0, 0, 1, 1, 1, 0, 0, 0, 0]);
+        let runs = paragraph.visual_runs(0..paragraph.len());
+        assert_eq!(runs.iter().map(|run| run.range.clone()).collect::<Vec<_>>(), vec![0..4, 4..7, 7..11]);
+        assert_eq!(paragraph.visual_text(&runs[1]), "\u{5d2}\u{5d1}\u{5d0}");
+
+        // Numbers keep their order inside right-to-left text, and brackets
+        // are mirrored
+        let paragraph = BidiParagraph::new("\u{5d0} (12) \u{5d1}", None);
+        assert_eq!(paragraph.base_direction(), Direction::Rtl);
+        let runs = paragraph.visual_runs(0..paragraph.len());
+        let painted: Vec<String> = runs.iter().map(|run| paragraph.visual_text(run)).collect();
+        assert_eq!(painted.concat(), "\u{5d1} (12) \u{5d0}");
+
+        // Every right-to-left script resolves, Thaana included
+        let paragraph = BidiParagraph::new("abc \u{780}\u{781}", Some(Direction::Ltr));
+        assert_eq!(paragraph.levels(), &[0, 0, 0, 0, 1, 1]);
+
+        // An override forces every character to one direction
+        let overridden = BidiParagraph::overridden("abc", Direction::Rtl);
+        assert_eq!(overridden.visual_order(0..3), vec![2, 1, 0]);
+
+        // The caret moves over the Hebrew word in visual order: rightward
+        // from after `c` lands before the last letter of the logical word
+        let paragraph = BidiParagraph::new("abc\u{5d0}\u{5d1}\u{5d2}", Some(Direction::Ltr));
+        assert_eq!(paragraph.move_caret(3, true), 5);
+        assert_eq!(paragraph.move_caret(5, true), 4);
+        assert_eq!(paragraph.move_caret(4, false), 5);
+        assert_eq!(paragraph.move_caret(2, true), 3);
+        assert_eq!(paragraph.move_caret(0, false), 0);
+    }
+}
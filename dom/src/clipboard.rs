@@ -0,0 +1,128 @@
+//! # Clipboard
+//!
+//! This module provides the clipboard shared by the page-facing clipboard
+//! API (`navigator.clipboard`) and the copy/cut/paste events fired for
+//! keyboard shortcuts.
+//!
+//! ## Design Principles
+//!
+//! 1. **Pluggable Backend**: The system clipboard is reached through the
+//!    `ClipboardBackend` trait. The shell plugs in the platform clipboard;
+//!    `MemoryClipboard` is for tests.
+//!
+//! 2. **Plain Text**: Only `text/plain` data is exchanged for now.
+//!
+//! 3. **Shared Handle**: `Clipboard` is a cheap, thread-safe handle, so the
+//!    input handler and script engines of every tab see the same contents.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Errors reported by a clipboard backend
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClipboardError {
+    /// The platform clipboard could not be accessed
+    Unavailable(String),
+    /// The clipboard holds no text
+    Empty,
+}
+
+impl fmt::Display for ClipboardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClipboardError::Unavailable(reason) => write!(f, "Clipboard unavailable: {}", reason),
+            ClipboardError::Empty => write!(f, "Clipboard contains no text"),
+        }
+    }
+}
+
+impl std::error::Error for ClipboardError {}
+
+/// Storage behind the clipboard
+pub trait ClipboardBackend: Send {
+    /// Read the text on the clipboard
+    fn read_text(&mut self) -> Result<String, ClipboardError>;
+    /// Replace the clipboard contents with text
+    fn write_text(&mut self, text: &str) -> Result<(), ClipboardError>;
+}
+
+/// A clipboard kept in memory, private to the process
+#[derive(Debug, Default)]
+pub struct MemoryClipboard {
+    text: Option<String>,
+}
+
+impl MemoryClipboard {
+    /// Create an empty clipboard
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ClipboardBackend for MemoryClipboard {
+    fn read_text(&mut self) -> Result<String, ClipboardError> {
+        self.text.clone().ok_or(ClipboardError::Empty)
+    }
+
+    fn write_text(&mut self, text: &str) -> Result<(), ClipboardError> {
+        self.text = Some(text.to_string());
+        Ok(())
+    }
+}
+
+/// Shared handle to a clipboard backend
+#[derive(Clone)]
+pub struct Clipboard {
+    backend: Arc<Mutex<Box<dyn ClipboardBackend>>>,
+}
+
+impl Clipboard {
+    /// Wrap a backend
+    pub fn new(backend: Box<dyn ClipboardBackend>) -> Self {
+        Self {
+            backend: Arc::new(Mutex::new(backend)),
+        }
+    }
+
+    /// Create a clipboard backed by memory
+    pub fn in_memory() -> Self {
+        Self::new(Box::new(MemoryClipboard::new()))
+    }
+
+    /// Read the text on the clipboard
+    pub fn read_text(&self) -> Result<String, ClipboardError> {
+        self.backend.lock().unwrap().read_text()
+    }
+
+    /// Replace the clipboard contents with text
+    pub fn write_text(&self, text: &str) -> Result<(), ClipboardError> {
+        self.backend.lock().unwrap().write_text(text)
+    }
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        Self::in_memory()
+    }
+}
+
+impl fmt::Debug for Clipboard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Clipboard").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clipboard_handles_share_contents() {
+        let clipboard = Clipboard::in_memory();
+        assert_eq!(clipboard.read_text(), Err(ClipboardError::Empty));
+
+        let other = clipboard.clone();
+        other.write_text("copied").unwrap();
+        assert_eq!(clipboard.read_text().unwrap(), "copied");
+    }
+}
@@ -0,0 +1,206 @@
+//! # Clock
+//!
+//! This module is the engine's source of the current time. Timers,
+//! animations, `performance.now()` and `Date` all read the time here
+//! instead of from `Instant::now()`, so a test can swap the system clock
+//! for a `ManualClock` that only moves when told to, and a page behaves the
+//! same on every machine and every run.
+//!
+//! ## Design Principles
+//!
+//! 1. **One Clock per Thread**: Like the rest of the engine's shared state,
+//!    the clock is thread-local, so tests running in parallel each get
+//!    their own clock.
+//!
+//! 2. **Injectable**: Components that keep time, such as the event loop and
+//!    the layout engine, hold a `SharedClock`. It is a `ThreadClock`,
+//!    following the thread's clock, unless a test injects its own.
+//!
+//! 3. **System Time Unless Replaced**: Nothing changes for callers until a
+//!    clock is installed with `set_clock`; `reset_clock` goes back to the
+//!    system clock.
+//!
+//! 4. **Monotonic and Wall Time Together**: A clock answers both `Instant`
+//!    and Unix time questions, and a manual clock advances them together,
+//!    so `Date.now()` and `performance.now()` never disagree.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Unix time a manual clock starts at: 2000-01-01T00:00:00Z
+pub const MANUAL_CLOCK_EPOCH: Duration = Duration::from_secs(946_684_800);
+
+/// A source of the current time
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// Get the current monotonic time
+    fn now(&self) -> Instant;
+
+    /// Get the current time since the Unix epoch
+    fn unix_time(&self) -> Duration;
+
+    /// Check whether the clock follows the system clock
+    fn is_system(&self) -> bool {
+        false
+    }
+}
+
+/// A clock shared by the components that read it
+pub type SharedClock = Arc<dyn Clock>;
+
+/// The operating system's clocks
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn unix_time(&self) -> Duration {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default()
+    }
+
+    fn is_system(&self) -> bool {
+        true
+    }
+}
+
+/// A clock that stands still until advanced
+///
+/// Clones share the same time, so the copy injected into an engine is
+/// advanced through the one a test keeps.
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    /// The instant the clock was created at, which its time counts from
+    anchor: Instant,
+    elapsed: Arc<Mutex<Duration>>,
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        ManualClock { anchor: Instant::now(), elapsed: Arc::new(Mutex::new(Duration::ZERO)) }
+    }
+
+    /// Move the clock forward
+    pub fn advance(&self, by: Duration) {
+        *self.elapsed.lock().unwrap() += by;
+    }
+
+    /// Get how far the clock has been advanced since it was created
+    pub fn elapsed(&self) -> Duration {
+        *self.elapsed.lock().unwrap()
+    }
+
+    /// Move the clock forward to `instant`, if that is not in its past
+    pub fn advance_to(&self, instant: Instant) {
+        self.advance(instant.saturating_duration_since(self.now()));
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.anchor + self.elapsed()
+    }
+
+    fn unix_time(&self) -> Duration {
+        MANUAL_CLOCK_EPOCH + self.elapsed()
+    }
+}
+
+/// The clock installed on the calling thread, read at each call
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreadClock;
+
+impl Clock for ThreadClock {
+    fn now(&self) -> Instant {
+        now()
+    }
+
+    fn unix_time(&self) -> Duration {
+        unix_time()
+    }
+
+    fn is_system(&self) -> bool {
+        uses_system_clock()
+    }
+}
+
+/// Get a clock following the calling thread's clock, the default of
+/// components that keep time
+pub fn thread_clock() -> SharedClock {
+    Arc::new(ThreadClock)
+}
+
+thread_local! {
+    static CURRENT_CLOCK: RefCell<Option<SharedClock>> = const { RefCell::new(None) };
+}
+
+/// Install the clock the engine reads the time from on this thread
+///
+/// A `ThreadClock` must not be installed: it reads the installed clock.
+pub fn set_clock(clock: SharedClock) {
+    CURRENT_CLOCK.with(|current| *current.borrow_mut() = Some(clock));
+}
+
+/// Go back to the system clock
+pub fn reset_clock() {
+    CURRENT_CLOCK.with(|current| *current.borrow_mut() = None);
+}
+
+/// Check whether the engine reads the system clock
+pub fn uses_system_clock() -> bool {
+    CURRENT_CLOCK.with(|current| current.borrow().as_ref().is_none_or(|clock| clock.is_system()))
+}
+
+/// Get the current monotonic time
+pub fn now() -> Instant {
+    CURRENT_CLOCK.with(|current| match current.borrow().as_ref() {
+        Some(clock) => clock.now(),
+        None => Instant::now(),
+    })
+}
+
+/// Get the current time since the Unix epoch
+pub fn unix_time() -> Duration {
+    CURRENT_CLOCK.with(|current| match current.borrow().as_ref() {
+        Some(clock) => clock.unix_time(),
+        None => SystemClock.unix_time(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manual_clock_stands_still_until_advanced() {
+        assert!(uses_system_clock());
+        let clock = ManualClock::new();
+        set_clock(Arc::new(clock.clone()));
+        assert!(!uses_system_clock());
+        assert!(!ThreadClock.is_system());
+
+        let start = now();
+        assert_eq!(unix_time(), MANUAL_CLOCK_EPOCH);
+        std::thread::sleep(Duration::from_millis(2));
+        assert_eq!(now(), start);
+
+        clock.advance(Duration::from_millis(250));
+        assert_eq!(now() - start, Duration::from_millis(250));
+        assert_eq!(unix_time(), MANUAL_CLOCK_EPOCH + Duration::from_millis(250));
+        clock.advance_to(start + Duration::from_millis(100));
+        assert_eq!(clock.elapsed(), Duration::from_millis(250));
+
+        reset_clock();
+        assert!(uses_system_clock());
+        assert!(unix_time() > MANUAL_CLOCK_EPOCH + Duration::from_secs(3600 * 24 * 365 * 20));
+    }
+}
@@ -0,0 +1,198 @@
+//! # Live Collections
+//!
+//! This module provides the live element collections behind
+//! `getElementsByTagName()`, `getElementsByClassName()` and `children`.
+//! A collection remembers its root and filter, not its elements, so it
+//! always lists the elements the tree holds at the time it is read.
+//!
+//! ## Design Principles
+//!
+//! 1. **Cached Queries**: The matching elements are computed on first use
+//!    and cached together with the mutation generation, see `mutations`.
+//!    Reads in a loop reuse the cache until the tree changes.
+//!
+//! 2. **Document Order**: Elements are listed in tree order, descendants
+//!    of the root only, never the root itself.
+//!
+//! 3. **Static Lists Elsewhere**: `querySelectorAll()` returns a plain
+//!    `Vec`, a snapshot, as the DOM specifies for `NodeList`s from
+//!    selectors.
+
+use std::cell::{Ref, RefCell};
+use std::rc::Rc;
+use crate::mutations;
+use crate::Node;
+
+/// What elements a live collection lists
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CollectionFilter {
+    /// Element children of the root
+    Children,
+    /// Descendant elements with a qualified name, `*` for all
+    TagName(String),
+    /// Descendant elements that have all of the classes
+    ClassNames(Vec<String>),
+}
+
+impl CollectionFilter {
+    /// Check whether an element belongs in the collection
+    fn accepts(&self, element: &Node) -> bool {
+        let Some(tag_name) = element.tag_name() else {
+            return false;
+        };
+        match self {
+            CollectionFilter::Children => true,
+            CollectionFilter::TagName(name) if name == "*" => true,
+            // HTML elements match regardless of the case of the query
+            CollectionFilter::TagName(name) if element.is_html_element() => tag_name == name.to_ascii_lowercase(),
+            CollectionFilter::TagName(name) => tag_name == name,
+            CollectionFilter::ClassNames(classes) => {
+                let class = element.get_attribute("class").unwrap_or_default();
+                let present: Vec<&str> = class.split_ascii_whitespace().collect();
+                !classes.is_empty() && classes.iter().all(|wanted| present.contains(&wanted.as_str()))
+            }
+        }
+    }
+}
+
+/// An `HTMLCollection` that reflects later changes to the tree
+#[derive(Debug)]
+pub struct LiveCollection {
+    root: Rc<Node>,
+    filter: CollectionFilter,
+    cache: RefCell<Option<(u64, Vec<Rc<Node>>)>>,
+}
+
+impl LiveCollection {
+    /// Create a collection of the elements below `root` that pass `filter`
+    pub fn new(root: Rc<Node>, filter: CollectionFilter) -> Self {
+        LiveCollection { root, filter, cache: RefCell::new(None) }
+    }
+
+    /// The node the collection is rooted at
+    pub fn root(&self) -> &Rc<Node> {
+        &self.root
+    }
+
+    /// The number of elements in the collection
+    pub fn len(&self) -> usize {
+        self.elements().len()
+    }
+
+    /// Check whether the collection has no elements
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the element at an index, like `item()`
+    pub fn item(&self, index: usize) -> Option<Rc<Node>> {
+        self.elements().get(index).cloned()
+    }
+
+    /// Get the first element with the given id or, for HTML elements,
+    /// `name` attribute, like `namedItem()`
+    pub fn named_item(&self, name: &str) -> Option<Rc<Node>> {
+        if name.is_empty() {
+            return None;
+        }
+        self.elements()
+            .iter()
+            .find(|element| {
+                element.get_attribute("id").as_deref() == Some(name)
+                    || (element.is_html_element() && element.get_attribute("name").as_deref() == Some(name))
+            })
+            .cloned()
+    }
+
+    /// Get a snapshot of the current elements
+    pub fn to_vec(&self) -> Vec<Rc<Node>> {
+        self.elements().clone()
+    }
+
+    /// Get the current elements, recomputing them if the tree changed
+    fn elements(&self) -> Ref<'_, Vec<Rc<Node>>> {
+        let generation = mutations::generation();
+        let fresh = matches!(&*self.cache.borrow(), Some((cached, _)) if *cached == generation);
+        if !fresh {
+            let mut elements = Vec::new();
+            match self.filter {
+                CollectionFilter::Children => {
+                    elements.extend(self.root.children.borrow().iter().filter(|child| self.filter.accepts(child)).cloned())
+                }
+                _ => collect_descendants(&self.root, &self.filter, &mut elements),
+            }
+            *self.cache.borrow_mut() = Some((generation, elements));
+        }
+        Ref::map(self.cache.borrow(), |cache| &cache.as_ref().expect("cache was just filled").1)
+    }
+}
+
+/// Append the descendants of `node` that pass `filter`, in tree order
+fn collect_descendants(node: &Node, filter: &CollectionFilter, elements: &mut Vec<Rc<Node>>) {
+    for child in node.children.borrow().iter() {
+        if filter.accepts(child) {
+            elements.push(Rc::clone(child));
+        }
+        collect_descendants(child, filter, elements);
+    }
+}
+
+impl Node {
+    /// Get the live collection of this node's element children, like
+    /// `children`
+    pub fn children_collection(self: &Rc<Self>) -> LiveCollection {
+        LiveCollection::new(Rc::clone(self), CollectionFilter::Children)
+    }
+
+    /// Get the live collection of descendant elements with a qualified
+    /// name, like `getElementsByTagName()`
+    pub fn elements_by_tag_name(self: &Rc<Self>, qualified_name: &str) -> LiveCollection {
+        LiveCollection::new(Rc::clone(self), CollectionFilter::TagName(qualified_name.to_string()))
+    }
+
+    /// Get the live collection of descendant elements that have all the
+    /// space separated classes, like `getElementsByClassName()`
+    pub fn elements_by_class_name(self: &Rc<Self>, class_names: &str) -> LiveCollection {
+        let classes = class_names.split_ascii_whitespace().map(str::to_string).collect();
+        LiveCollection::new(Rc::clone(self), CollectionFilter::ClassNames(classes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Document;
+
+    #[test]
+    fn test_collections_follow_mutations() {
+        let document = Document::new();
+        let list = document.create_element("ul");
+        document.root.append_child(&list);
+        let first = document.create_element("li");
+        first.set_attribute("class", "item done");
+        list.append_child(&first);
+
+        let items = document.root.elements_by_tag_name("LI");
+        let done = document.root.elements_by_class_name(" done  item ");
+        let children = list.children_collection();
+        assert_eq!(items.len(), 1);
+        assert_eq!(done.len(), 1);
+
+        let second = document.create_element("li");
+        second.set_attribute("id", "second");
+        list.append_child(&second);
+        list.append_child(&document.create_text_node("text"));
+        assert_eq!(items.len(), 2);
+        assert_eq!(children.len(), 2);
+        assert_eq!(items.named_item("second").map(|item| item.id), Some(second.id));
+
+        first.remove_attribute("class");
+        assert!(done.is_empty());
+        second.set_attribute("class", "item done");
+        assert_eq!(done.item(0).map(|item| item.id), Some(second.id));
+
+        list.remove_children();
+        assert!(items.is_empty());
+        assert!(document.root.elements_by_class_name("  ").is_empty());
+        assert_eq!(document.root.elements_by_tag_name("*").len(), 1);
+    }
+}
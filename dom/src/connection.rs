@@ -0,0 +1,354 @@
+//! # Connectedness
+//!
+//! This module implements the DOM's insertion and removal steps. A node is
+//! connected while its root is a document; when an element becomes
+//! connected or disconnected, the registered connection observers run, and
+//! they carry out the side effects: scripts run, stylesheets apply and
+//! unapply, images start loading.
+//!
+//! ## Design Principles
+//!
+//! 1. **One Place for Tree Edits**: Children are inserted with
+//!    `insert_child_at` and removed with `remove_child` or
+//!    `remove_children`. All of them run the steps, so no caller has to
+//!    remember the side effects.
+//!
+//! 2. **Steps After the Edit**: Observers run once the tree is consistent
+//!    and no borrow is held, for every element of the inserted or removed
+//!    subtree in tree order, so they may read and even change the tree.
+//!
+//! 3. **Classified Once**: `effect` decides which side effect an element
+//!    has, so every observer agrees on what a `<script>` or a
+//!    `<link rel=stylesheet>` is.
+//!
+//! 4. **Removal Observers**: Separately from connectedness, removal
+//!    observers see the root of every subtree taken out of a parent,
+//!    connected or not, while the caller still holds it, so owners of
+//!    weak references to nodes can keep them alive.
+//!
+//! 5. **Parser-Inserted Elements**: Elements the parser inserts are marked.
+//!    Their scripts and stylesheets are handled by the loader, and
+//!    observers must not handle them a second time.
+
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+use crate::mutations::{self, Mutation, ObserverId};
+use crate::top_layer;
+use crate::{Node, NodeType};
+
+/// Whether an element became connected or disconnected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionChange {
+    Connected,
+    Disconnected,
+}
+
+/// The side effect connecting or disconnecting an element has
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionEffect {
+    /// A `<script>` runs when it is first connected
+    RunScript,
+    /// A `<style>` or `<link rel=stylesheet>` applies while connected
+    Stylesheet,
+    /// An `<img>` with a source starts loading when connected
+    LoadImage,
+}
+
+type Observer = Rc<dyn Fn(&Rc<Node>, ConnectionChange)>;
+type RemovalObserver = Rc<dyn Fn(&Rc<Node>)>;
+
+thread_local! {
+    static NEXT_OBSERVER_ID: std::cell::Cell<ObserverId> = const { std::cell::Cell::new(1) };
+    static OBSERVERS: RefCell<Vec<(ObserverId, Observer)>> = RefCell::new(Vec::new());
+    static REMOVAL_OBSERVERS: RefCell<Vec<(ObserverId, RemovalObserver)>> = RefCell::new(Vec::new());
+}
+
+fn next_observer_id() -> ObserverId {
+    NEXT_OBSERVER_ID.with(|next| {
+        let id = next.get();
+        next.set(id + 1);
+        id
+    })
+}
+
+/// Call `callback` with every element that becomes connected or
+/// disconnected until `unobserve` is called
+pub fn observe(callback: impl Fn(&Rc<Node>, ConnectionChange) + 'static) -> ObserverId {
+    let id = next_observer_id();
+    OBSERVERS.with(|observers| observers.borrow_mut().push((id, Rc::new(callback))));
+    id
+}
+
+/// Call `callback` with the root of every subtree removed from its parent,
+/// connected or not, until `unobserve` is called
+pub fn observe_removals(callback: impl Fn(&Rc<Node>) + 'static) -> ObserverId {
+    let id = next_observer_id();
+    REMOVAL_OBSERVERS.with(|observers| observers.borrow_mut().push((id, Rc::new(callback))));
+    id
+}
+
+/// Stop calling a connection or removal observer, returning whether it was
+/// registered
+pub fn unobserve(id: ObserverId) -> bool {
+    let connection = OBSERVERS.with(|observers| {
+        let mut observers = observers.borrow_mut();
+        let before = observers.len();
+        observers.retain(|(observer_id, _)| *observer_id != id);
+        observers.len() != before
+    });
+    let removal = REMOVAL_OBSERVERS.with(|observers| {
+        let mut observers = observers.borrow_mut();
+        let before = observers.len();
+        observers.retain(|(observer_id, _)| *observer_id != id);
+        observers.len() != before
+    });
+    connection || removal
+}
+
+/// Get the side effect of connecting or disconnecting an element
+pub fn effect(element: &Node) -> Option<ConnectionEffect> {
+    if !element.is_html_element() {
+        return None;
+    }
+    match element.local_name()? {
+        "script" => Some(ConnectionEffect::RunScript),
+        "style" => Some(ConnectionEffect::Stylesheet),
+        "link" => {
+            let rel = element.get_attribute("rel").unwrap_or_default();
+            rel.split_ascii_whitespace()
+                .any(|token| token.eq_ignore_ascii_case("stylesheet"))
+                .then_some(ConnectionEffect::Stylesheet)
+        }
+        "img" => element.has_attribute("src").then_some(ConnectionEffect::LoadImage),
+        _ => None,
+    }
+}
+
+/// Run the steps for the elements of a subtree that changed connectedness
+fn run_steps(node: &Rc<Node>, change: ConnectionChange) {
+    if change == ConnectionChange::Disconnected {
+        remove_from_top_layer(node);
+    }
+    let observers: Vec<Observer> = OBSERVERS.with(|observers| observers.borrow().iter().map(|(_, observer)| Rc::clone(observer)).collect());
+    if observers.is_empty() {
+        return;
+    }
+    let mut elements = Vec::new();
+    collect_elements(node, &mut elements);
+    for element in &elements {
+        for observer in &observers {
+            observer(element, change);
+        }
+    }
+}
+
+/// Tell the removal observers that a subtree was taken out of its parent
+pub(crate) fn notify_removal(node: &Rc<Node>) {
+    let observers: Vec<RemovalObserver> = REMOVAL_OBSERVERS.with(|observers| observers.borrow().iter().map(|(_, observer)| Rc::clone(observer)).collect());
+    for observer in &observers {
+        observer(node);
+    }
+}
+
+/// Take a removed subtree's elements out of the top layer
+fn remove_from_top_layer(node: &Rc<Node>) {
+    top_layer::removal_steps(node);
+    for child in node.children.borrow().iter() {
+        remove_from_top_layer(child);
+    }
+}
+
+/// Append the inclusive descendant elements of a node, in tree order
+fn collect_elements(node: &Rc<Node>, elements: &mut Vec<Rc<Node>>) {
+    if node.tag_name().is_some() {
+        elements.push(Rc::clone(node));
+    }
+    for child in node.children.borrow().iter() {
+        collect_elements(child, elements);
+    }
+}
+
+impl Node {
+    /// Check whether the root of this node is a document
+    pub fn is_connected(&self) -> bool {
+        if matches!(self.node_type, NodeType::Document) {
+            return true;
+        }
+        let mut current = self.parent.borrow().upgrade();
+        while let Some(node) = current {
+            if matches!(node.node_type, NodeType::Document) {
+                return true;
+            }
+            current = node.parent.borrow().upgrade();
+        }
+        false
+    }
+
+    /// Check whether the HTML parser inserted this element
+    pub fn is_parser_inserted(&self) -> bool {
+        self.parser_inserted.get()
+    }
+
+    /// Mark this element as inserted by the HTML parser
+    pub fn mark_parser_inserted(&self) {
+        self.parser_inserted.set(true);
+    }
+
+    /// Set the "already started" flag of a script, returning whether it was
+    /// not set before
+    ///
+    /// A script runs at most once, even when it is removed and inserted
+    /// again.
+    pub fn start_script(&self) -> bool {
+        !self.already_started.replace(true)
+    }
+
+    /// Insert a child at an index, moving it from its old parent
+    ///
+    /// The child joins the document of this node, and if this node is
+    /// connected the insertion steps run for the child's subtree. `index`
+    /// must be at most the number of children.
+    pub fn insert_child_at(self: &Rc<Self>, index: usize, child: &Rc<Node>) {
+        let mut index = index;
+        let old_parent = child.parent.borrow().upgrade();
+        if let Some(old_parent) = old_parent {
+            let position = old_parent.children.borrow().iter().position(|sibling| Rc::ptr_eq(sibling, child));
+            if Rc::ptr_eq(&old_parent, self) && position.is_some_and(|position| position < index) {
+                index -= 1;
+            }
+            old_parent.remove_child(child);
+        }
+
+        // Inserted nodes belong to the document of their new parent
+        if self.owner.get() != 0 && child.owner.get() != self.owner.get() {
+            child.set_owner(self.owner.get());
+        }
+        *child.parent.borrow_mut() = Rc::downgrade(self);
+        self.children.borrow_mut().insert(index, Rc::clone(child));
+        mutations::notify(Mutation::ChildList { parent: self.id });
+        if self.is_connected() {
+            run_steps(child, ConnectionChange::Connected);
+        }
+    }
+
+    /// Remove a child, returning whether it was a child of this node
+    ///
+    /// The removal observers see the child. If this node is connected the
+    /// removal steps run for the child's subtree.
+    pub fn remove_child(&self, child: &Rc<Node>) -> bool {
+        let position = self.children.borrow().iter().position(|sibling| Rc::ptr_eq(sibling, child));
+        let Some(position) = position else {
+            return false;
+        };
+        self.children.borrow_mut().remove(position);
+        *child.parent.borrow_mut() = Weak::new();
+        mutations::notify(Mutation::ChildList { parent: self.id });
+        notify_removal(child);
+        if self.is_connected() {
+            run_steps(child, ConnectionChange::Disconnected);
+        }
+        true
+    }
+
+    /// Detach and return all children of this node, telling the removal
+    /// observers and running the removal steps if it is connected
+    pub fn remove_children(&self) -> Vec<Rc<Node>> {
+        let removed = std::mem::take(&mut *self.children.borrow_mut());
+        for child in &removed {
+            *child.parent.borrow_mut() = Weak::new();
+        }
+        if !removed.is_empty() {
+            mutations::notify(Mutation::ChildList { parent: self.id });
+            for child in &removed {
+                notify_removal(child);
+            }
+            if self.is_connected() {
+                for child in &removed {
+                    run_steps(child, ConnectionChange::Disconnected);
+                }
+            }
+        }
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Document;
+
+    #[test]
+    fn test_steps_run_for_connected_subtrees() {
+        let document = Document::new();
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        let id = observe({
+            let changes = Rc::clone(&changes);
+            move |element, change| {
+                changes.borrow_mut().push((element.tag_name().unwrap().to_string(), change, effect(element)));
+            }
+        });
+
+        let body = document.create_element("body");
+        let script = document.create_element("script");
+        body.append_child(&script);
+        assert!(!script.is_connected());
+        assert!(changes.borrow().is_empty());
+
+        document.root.append_child(&body);
+        assert!(script.is_connected());
+        assert_eq!(
+            *changes.borrow(),
+            vec![
+                ("body".to_string(), ConnectionChange::Connected, None),
+                ("script".to_string(), ConnectionChange::Connected, Some(ConnectionEffect::RunScript)),
+            ]
+        );
+
+        // Moving within the document disconnects and reconnects
+        let style = document.create_element("style");
+        body.insert_child_at(0, &style);
+        document.root.insert_child_at(0, &style);
+        assert_eq!(document.root.children.borrow().len(), 2);
+        assert_eq!(body.children.borrow().len(), 1);
+        assert!(document.root.remove_child(&style));
+        assert!(!document.root.remove_child(&style));
+        let effects: Vec<_> = changes.borrow()[2..].iter().map(|(_, change, effect)| (*change, *effect)).collect();
+        assert_eq!(effects, [
+            (ConnectionChange::Connected, Some(ConnectionEffect::Stylesheet)),
+            (ConnectionChange::Disconnected, Some(ConnectionEffect::Stylesheet)),
+            (ConnectionChange::Connected, Some(ConnectionEffect::Stylesheet)),
+            (ConnectionChange::Disconnected, Some(ConnectionEffect::Stylesheet)),
+        ]);
+
+        document.root.remove_children();
+        assert_eq!(changes.borrow().len(), 8);
+        assert!(unobserve(id));
+        assert!(script.start_script());
+        assert!(!script.start_script());
+    }
+
+    #[test]
+    fn test_removal_observers_see_detached_subtrees() {
+        let document = Document::new();
+        let removed = Rc::new(RefCell::new(Vec::new()));
+        let id = observe_removals({
+            let removed = Rc::clone(&removed);
+            move |node| removed.borrow_mut().push(node.id)
+        });
+
+        // Removals outside the document are seen too
+        let div = document.create_element("div");
+        let span = document.create_element("span");
+        let text = document.create_text_node("a");
+        div.append_child(&span);
+        span.append_child(&text);
+        assert!(span.remove_child(&text));
+        div.remove_children();
+        assert_eq!(*removed.borrow(), vec![text.id, span.id]);
+
+        assert!(unobserve(id));
+        div.append_child(&span);
+        div.remove_children();
+        assert_eq!(removed.borrow().len(), 2);
+    }
+}
@@ -0,0 +1,178 @@
+//! # Dialog Elements
+//!
+//! This module implements `<dialog>`: `show()`, `showModal()`, `close()`
+//! and `returnValue`. A modal dialog joins the top layer, matches `:modal`
+//! and makes the rest of the document inert until it closes.
+//!
+//! ## Design Principles
+//!
+//! 1. **The Attribute Is the State**: A dialog is open exactly while it has
+//!    the `open` attribute, so styles, layout and serialization see the
+//!    same state scripts do.
+//!
+//! 2. **Modality Is Element State**: Being modal is a state flag like
+//!    `:hover`, so `:modal` invalidates through the same path and the top
+//!    layer only needs to know which elements it holds.
+//!
+//! 3. **Callers Fire Events**: `close_dialog` reports whether the dialog
+//!    closed, and the caller fires `close`, through the DOM event manager
+//!    for the user agent or through the script bindings for scripts.
+
+use std::fmt;
+use std::rc::Rc;
+use crate::element_state::ElementState;
+use crate::{top_layer, Node};
+
+/// Runtime state of a dialog element
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DialogState {
+    /// The `returnValue`, set by `close()` with an argument
+    pub return_value: String,
+}
+
+/// Why a dialog cannot be shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DialogError {
+    /// `show()` on a modal dialog, or `showModal()` on a non-modal one
+    AlreadyOpen,
+    /// `showModal()` on a dialog that is not in a document
+    NotConnected,
+}
+
+impl DialogError {
+    /// Get the DOMException name of the error
+    pub fn name(&self) -> &'static str {
+        "InvalidStateError"
+    }
+}
+
+impl fmt::Display for DialogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DialogError::AlreadyOpen => write!(f, "the dialog is already open with a different modality"),
+            DialogError::NotConnected => write!(f, "the dialog is not connected"),
+        }
+    }
+}
+
+impl std::error::Error for DialogError {}
+
+impl Node {
+    /// Check whether this is a `<dialog>` element
+    pub fn is_dialog(&self) -> bool {
+        self.is_html_element() && self.local_name() == Some("dialog")
+    }
+
+    /// Check whether this dialog was shown with `showModal()` and is open
+    pub fn is_modal(&self) -> bool {
+        self.element_state().contains(ElementState::MODAL)
+    }
+
+    /// Get the `returnValue` of a dialog
+    pub fn return_value(&self) -> String {
+        self.dialog_state.borrow().return_value.clone()
+    }
+
+    /// Set the `returnValue` of a dialog
+    pub fn set_return_value(&self, value: &str) {
+        self.dialog_state.borrow_mut().return_value = value.to_string();
+    }
+
+    /// Open a dialog without making it modal, like `show()`
+    ///
+    /// Showing an open non-modal dialog does nothing.
+    pub fn show_dialog(&self) -> Result<(), DialogError> {
+        if self.has_attribute("open") {
+            return if self.is_modal() { Err(DialogError::AlreadyOpen) } else { Ok(()) };
+        }
+        self.set_attribute("open", "");
+        Ok(())
+    }
+
+    /// Open a dialog as a modal, like `showModal()`
+    ///
+    /// The dialog joins the top layer and the rest of its document becomes
+    /// inert. Showing an open modal dialog does nothing.
+    pub fn show_modal(self: &Rc<Self>) -> Result<(), DialogError> {
+        if self.has_attribute("open") {
+            return if self.is_modal() { Ok(()) } else { Err(DialogError::AlreadyOpen) };
+        }
+        if !self.is_connected() {
+            return Err(DialogError::NotConnected);
+        }
+        self.set_attribute("open", "");
+        self.set_element_state(ElementState::MODAL, true);
+        top_layer::add(self);
+        Ok(())
+    }
+
+    /// Close a dialog, like `close()`, setting its `returnValue` if one is
+    /// given
+    ///
+    /// Returns whether the dialog was open, in which case the caller fires
+    /// `close` at it.
+    pub fn close_dialog(&self, return_value: Option<&str>) -> bool {
+        if !self.has_attribute("open") {
+            return false;
+        }
+        self.remove_attribute("open");
+        if self.is_modal() {
+            self.set_element_state(ElementState::MODAL, false);
+            top_layer::remove(self);
+        }
+        if let Some(value) = return_value {
+            self.set_return_value(value);
+        }
+        true
+    }
+}
+
+/// Get the modal dialog Escape closes: the topmost modal element of the
+/// document that owns `node`, if it is a dialog
+pub fn cancelable_dialog(node: &Node) -> Option<Rc<Node>> {
+    top_layer::blocking_element(node).filter(|element| element.is_dialog())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dom_event_integration::DomEventManager;
+    use crate::events::{KeyboardEventInit, SyntheticEventFactory};
+    use crate::Document;
+
+    #[test]
+    fn test_show_modal_and_close() {
+        let document = Document::new();
+        let body = document.create_element("body");
+        document.root.append_child(&body);
+        let dialog = document.create_element("dialog");
+
+        assert_eq!(dialog.show_modal(), Err(DialogError::NotConnected));
+        body.append_child(&dialog);
+        dialog.show_modal().unwrap();
+        assert!(dialog.has_attribute("open") && dialog.is_modal() && top_layer::contains(&dialog));
+        assert!(cancelable_dialog(&body).is_some_and(|found| Rc::ptr_eq(&found, &dialog)));
+        assert_eq!(dialog.show_dialog(), Err(DialogError::AlreadyOpen));
+        assert_eq!(dialog.show_modal(), Ok(()));
+
+        assert!(dialog.close_dialog(Some("confirm")));
+        assert!(!dialog.close_dialog(Some("again")));
+        assert_eq!(dialog.return_value(), "confirm");
+        assert!(!dialog.is_modal() && !top_layer::contains(&dialog) && cancelable_dialog(&body).is_none());
+
+        dialog.show_dialog().unwrap();
+        assert!(!dialog.is_modal() && !top_layer::contains(&dialog));
+        assert_eq!(dialog.show_modal(), Err(DialogError::AlreadyOpen));
+        assert!(dialog.close_dialog(None));
+        assert_eq!(dialog.return_value(), "confirm");
+
+        // Escape cancels, then closes, the modal dialog
+        let mut manager = DomEventManager::new();
+        manager.enable_dispatch_log();
+        dialog.show_modal().unwrap();
+        let escape = KeyboardEventInit { key: "Escape".to_string(), code: "Escape".to_string(), ..Default::default() };
+        manager.dispatch_keyboard_event(&body, SyntheticEventFactory::create_keyboard_event("keydown", &escape));
+        assert_eq!(manager.take_dispatch_log(), vec!["keydown", "cancel", "close"]);
+        assert!(!dialog.has_attribute("open") && !dialog.is_modal());
+    }
+}
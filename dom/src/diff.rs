@@ -0,0 +1,319 @@
+//! # Tree Diffing
+//!
+//! This module computes the mutations that turn one DOM tree into another
+//! and applies them. Reader mode swaps in its simplified tree this way,
+//! devtools applies "edit as HTML" by patching the live element with the
+//! parsed markup, and tests compare trees by asserting an empty diff.
+//!
+//! ## Design Principles
+//!
+//! 1. **Reuse Before Recreate**: Children are matched by a longest common
+//!    subsequence over their kind (text, or element name and `id`). Matched
+//!    nodes are kept and patched in place, so their identity, listeners and
+//!    state survive; only unmatched children are removed or inserted.
+//!
+//! 2. **Sequential Patches**: A patch addresses its node by child indices
+//!    from the root, valid in the tree as left by the patches before it.
+//!    Patches are applied in order, one at a time.
+//!
+//! 3. **Current State**: Attributes and text are compared as they are now,
+//!    runtime changes included, like `serialization` does.
+//!
+//! 4. **Imported Insertions**: Inserted subtrees are copied from the new
+//!    tree with `Document::import_node`; the new tree is not changed. The
+//!    patched tree must belong to the document passed to `apply`.
+
+use std::fmt;
+use std::rc::Rc;
+use crate::adoption::AdoptError;
+use crate::namespaces::QualifiedName;
+use crate::{Document, Node, NodeType};
+
+/// Child indices leading from the root to a node
+pub type NodePath = Vec<usize>;
+
+/// One mutation of a tree
+#[derive(Debug, Clone)]
+pub enum Patch {
+    /// Replace the data of a text node
+    SetText { path: NodePath, data: String },
+    /// Add or change an attribute
+    SetAttribute { path: NodePath, name: QualifiedName, value: String },
+    /// Remove an attribute
+    RemoveAttribute { path: NodePath, name: QualifiedName },
+    /// Insert a copy of a node of the new tree as the child at `index`
+    Insert { parent: NodePath, index: usize, node: Rc<Node> },
+    /// Remove the child at `index`
+    Remove { parent: NodePath, index: usize },
+    /// Replace a node with a copy of a node of the new tree
+    Replace { path: NodePath, node: Rc<Node> },
+}
+
+/// A patch that does not fit the tree it is applied to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchError {
+    /// No node at the path, or not one the patch applies to
+    InvalidPath(NodePath),
+    /// The root itself cannot be replaced in place
+    ReplaceRoot,
+    /// The tree does not belong to the document, or a node cannot be
+    /// imported into it
+    Adopt(AdoptError),
+}
+
+impl From<AdoptError> for PatchError {
+    fn from(error: AdoptError) -> Self {
+        PatchError::Adopt(error)
+    }
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::InvalidPath(path) => write!(f, "no suitable node at {:?}", path),
+            PatchError::ReplaceRoot => write!(f, "the root of a tree cannot be replaced"),
+            PatchError::Adopt(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+/// Compute the patches that turn the tree at `old` into the tree at `new`
+///
+/// An empty result means both trees are equivalent.
+pub fn diff(old: &Rc<Node>, new: &Rc<Node>) -> Vec<Patch> {
+    let mut patches = Vec::new();
+    if same_kind(old, new) {
+        diff_node(old, new, &mut Vec::new(), &mut patches);
+    } else {
+        patches.push(Patch::Replace { path: Vec::new(), node: Rc::clone(new) });
+    }
+    patches
+}
+
+/// Apply patches from `diff` to the tree at `root`, creating inserted
+/// nodes in `document`
+pub fn apply(root: &Rc<Node>, patches: &[Patch], document: &Document) -> Result<(), PatchError> {
+    document.check_owns(root)?;
+    for patch in patches {
+        match patch {
+            Patch::SetText { path, data } => {
+                let node = resolve(root, path)?;
+                if node.character_data().is_none() {
+                    return Err(PatchError::InvalidPath(path.clone()));
+                }
+                node.set_character_data(data);
+            }
+            Patch::SetAttribute { path, name, value } => {
+                let node = element_at(root, path)?;
+                match &name.namespace {
+                    None => node.set_attribute(&name.local_name, value),
+                    Some(namespace) => node
+                        .set_attribute_ns(Some(namespace), &name.qualified_name(), value)
+                        .map_err(|_| PatchError::InvalidPath(path.clone()))?,
+                }
+            }
+            Patch::RemoveAttribute { path, name } => {
+                element_at(root, path)?.remove_attribute_ns(name.namespace.as_deref(), &name.local_name);
+            }
+            Patch::Insert { parent, index, node } => {
+                let parent_node = resolve(root, parent)?;
+                if *index > parent_node.children.borrow().len() {
+                    return Err(PatchError::InvalidPath(parent.clone()));
+                }
+                parent_node.insert_child_at(*index, &document.import_node(node, true)?);
+            }
+            Patch::Remove { parent, index } => {
+                let parent_node = resolve(root, parent)?;
+                if *index >= parent_node.children.borrow().len() {
+                    return Err(PatchError::InvalidPath(parent.clone()));
+                }
+                let child = Rc::clone(&parent_node.children.borrow()[*index]);
+                parent_node.remove_child(&child);
+            }
+            Patch::Replace { path, node } => {
+                let Some((index, parent)) = path.split_last() else {
+                    return Err(PatchError::ReplaceRoot);
+                };
+                let parent_node = resolve(root, parent)?;
+                if *index >= parent_node.children.borrow().len() {
+                    return Err(PatchError::InvalidPath(path.clone()));
+                }
+                let copy = document.import_node(node, true)?;
+                let child = Rc::clone(&parent_node.children.borrow()[*index]);
+                parent_node.remove_child(&child);
+                parent_node.insert_child_at(*index, &copy);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check whether an old node can be patched into a new one
+fn same_kind(old: &Node, new: &Node) -> bool {
+    match (&old.node_type, &new.node_type) {
+        (NodeType::Document, NodeType::Document) | (NodeType::Text(_), NodeType::Text(_)) => true,
+        (NodeType::Element { .. }, NodeType::Element { .. }) => {
+            old.namespace_uri() == new.namespace_uri()
+                && old.tag_name() == new.tag_name()
+                && old.get_attribute("id") == new.get_attribute("id")
+        }
+        _ => false,
+    }
+}
+
+/// Diff two nodes of the same kind at `path`
+fn diff_node(old: &Rc<Node>, new: &Rc<Node>, path: &mut NodePath, patches: &mut Vec<Patch>) {
+    if let (Some(old_data), Some(new_data)) = (old.character_data(), new.character_data()) {
+        if old_data != new_data {
+            patches.push(Patch::SetText { path: path.clone(), data: new_data });
+        }
+        return;
+    }
+    diff_attributes(old, new, path, patches);
+    diff_children(old, new, path, patches);
+}
+
+/// Diff the attributes of two elements
+fn diff_attributes(old: &Node, new: &Node, path: &NodePath, patches: &mut Vec<Patch>) {
+    let old_attributes = old.attributes_ns();
+    let new_attributes = new.attributes_ns();
+    let same_name = |a: &QualifiedName, b: &QualifiedName| a.namespace == b.namespace && a.local_name == b.local_name;
+    for (name, _) in &old_attributes {
+        if !new_attributes.iter().any(|(new_name, _)| same_name(name, new_name)) {
+            patches.push(Patch::RemoveAttribute { path: path.clone(), name: name.clone() });
+        }
+    }
+    for (name, value) in &new_attributes {
+        let unchanged = old_attributes.iter().any(|(old_name, old_value)| same_name(name, old_name) && old_value == value);
+        if !unchanged {
+            patches.push(Patch::SetAttribute { path: path.clone(), name: name.clone(), value: value.clone() });
+        }
+    }
+}
+
+/// Diff the children of two nodes, keeping the longest common
+/// subsequence of children of the same kind
+fn diff_children(old: &Rc<Node>, new: &Rc<Node>, path: &mut NodePath, patches: &mut Vec<Patch>) {
+    let old_children = old.children.borrow().clone();
+    let new_children = new.children.borrow().clone();
+    let kept = common_subsequence(&old_children, &new_children);
+
+    // Remove from the back so the indices of earlier children hold
+    for index in (0..old_children.len()).rev() {
+        if !kept.iter().any(|(old_index, _)| *old_index == index) {
+            patches.push(Patch::Remove { parent: path.clone(), index });
+        }
+    }
+    // Insert from the front, so every kept child is already in place
+    for (index, child) in new_children.iter().enumerate() {
+        if !kept.iter().any(|(_, new_index)| *new_index == index) {
+            patches.push(Patch::Insert { parent: path.clone(), index, node: Rc::clone(child) });
+        }
+    }
+    for (old_index, new_index) in kept {
+        path.push(new_index);
+        diff_node(&old_children[old_index], &new_children[new_index], path, patches);
+        path.pop();
+    }
+}
+
+/// Pair old and new children of the same kind, in order, as many as
+/// possible
+fn common_subsequence(old: &[Rc<Node>], new: &[Rc<Node>]) -> Vec<(usize, usize)> {
+    // lengths[i][j] is the longest common subsequence of old[i..] and new[j..]
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if same_kind(&old[i], &new[j]) {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if same_kind(&old[i], &new[j]) {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+/// Find the node at a path below `root`
+fn resolve(root: &Rc<Node>, path: &[usize]) -> Result<Rc<Node>, PatchError> {
+    let mut node = Rc::clone(root);
+    for &index in path {
+        let child = node.children.borrow().get(index).cloned();
+        node = child.ok_or_else(|| PatchError::InvalidPath(path.to_vec()))?;
+    }
+    Ok(node)
+}
+
+/// Find the element at a path below `root`
+fn element_at(root: &Rc<Node>, path: &[usize]) -> Result<Rc<Node>, PatchError> {
+    let node = resolve(root, path)?;
+    if node.tag_name().is_none() {
+        return Err(PatchError::InvalidPath(path.to_vec()));
+    }
+    Ok(node)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Children as tag name, attributes and text; an empty tag is a text node
+    type Markup<'a> = [(&'a str, &'a [(&'a str, &'a str)], &'a str)];
+
+    fn parse(document: &Document, markup: &Markup) -> Rc<Node> {
+        let root = document.create_element("div");
+        for (tag, attributes, text) in markup {
+            let child = if tag.is_empty() {
+                document.create_text_node(text)
+            } else {
+                let element = document.create_element(tag);
+                for (name, value) in attributes.iter() {
+                    element.set_attribute(name, value);
+                }
+                element.append_child(&document.create_text_node(text));
+                element
+            };
+            root.append_child(&child);
+        }
+        root
+    }
+
+    #[test]
+    fn test_diff_and_apply_reuses_nodes() {
+        let document = Document::new();
+        let old = parse(&document, &[("h1", &[], "Title"), ("p", &[("class", "a")], "One"), ("", &[], "tail"), ("p", &[], "Two")]);
+        let new = parse(&document, &[("p", &[("class", "b")], "One!"), ("ul", &[], "x"), ("p", &[], "Two"), ("", &[], "end")]);
+        let kept_paragraph = Rc::clone(&old.children.borrow()[1]);
+
+        let patches = diff(&old, &new);
+        apply(&old, &patches, &document).unwrap();
+        assert_eq!(old.inner_html(), new.inner_html());
+        assert!(diff(&old, &new).is_empty());
+        assert!(Rc::ptr_eq(&old.children.borrow()[0], &kept_paragraph));
+        assert!(!Rc::ptr_eq(&old.children.borrow()[1], &new.children.borrow()[1]));
+        // Two removals, two insertions, and the class and text of the paragraph
+        assert_eq!(patches.len(), 6);
+
+        let text = document.create_text_node("x");
+        assert_eq!(diff(&old, &text).len(), 1);
+        assert_eq!(apply(&old, &diff(&old, &text), &document), Err(PatchError::ReplaceRoot));
+        let bad = [Patch::Remove { parent: vec![9], index: 0 }];
+        assert_eq!(apply(&old, &bad, &document), Err(PatchError::InvalidPath(vec![9])));
+        assert!(matches!(apply(&old, &[], &Document::new()), Err(PatchError::Adopt(_))));
+    }
+}
@@ -9,7 +9,15 @@ use std::collections::HashMap;
 use crate::{Node, NodeType, Document};
 use crate::event_types::*;
 use crate::element::Element;
-use crate::events::EventDispatcher;
+use crate::events::{EventDispatcher, KeyboardEventInit, SyntheticEventFactory};
+use crate::forms::{self, FormControlType, FormSubmission};
+use crate::clipboard::Clipboard;
+use crate::pointer::PointerCaptures;
+use crate::dialog;
+use crate::fullscreen;
+use crate::popover::{self, PopoverTargetAction, PopoverToggle};
+use crate::page_visibility::{self, VisibilityState};
+use crate::sandboxing::{NavigationTarget, SandboxFlags};
 
 /// DOM Event Manager
 /// 
@@ -25,6 +33,52 @@ pub struct DomEventManager {
     element_cache: HashMap<u64, Rc<RefCell<Element>>>,
     /// Document reference for DOM traversal
     document: Option<Rc<Document>>,
+    /// Set when an event changed the DOM in a way that requires relayout
+    relayout_needed: bool,
+    /// Form submissions waiting to be sent by the browser shell
+    pending_submissions: Vec<FormSubmission>,
+    /// The IME composition in progress, if any
+    composition: Option<Composition>,
+    /// Types of dispatched events, recorded only when enabled
+    dispatch_log: Option<Vec<String>>,
+    /// Set while user input is handled; every event dispatched then is
+    /// trusted
+    user_input: bool,
+    /// Runs the script listeners of a node, which may cancel the event
+    listener_hook: Option<ListenerHook>,
+    /// Link targets waiting to be navigated to by the browser shell
+    pending_navigations: Vec<String>,
+    /// Which elements have captured which pointers
+    pointer_captures: PointerCaptures,
+}
+
+type Listeners = Box<dyn FnMut(&Rc<Node>, &mut Event)>;
+
+/// Runs the listeners an embedder registered outside this manager
+struct ListenerHook(Listeners);
+
+impl std::fmt::Debug for ListenerHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ListenerHook")
+    }
+}
+
+/// The state a click changed before dispatch, restored if it is cancelled
+#[derive(Debug)]
+enum PreActivation {
+    /// A checkbox was toggled from `was_checked`
+    Checkbox { was_checked: bool },
+    /// A radio button was checked, unchecking `previous`
+    Radio { previous: Option<Rc<Node>> },
+}
+
+/// An IME composition in progress on an editable element
+#[derive(Debug)]
+struct Composition {
+    target: u64,
+    /// The control's value when the composition started; the composed text
+    /// is appended to it
+    base_value: String,
 }
 
 impl DomEventManager {
@@ -35,9 +89,58 @@ impl DomEventManager {
             node_listeners: HashMap::new(),
             element_cache: HashMap::new(),
             document: None,
+            relayout_needed: false,
+            pending_submissions: Vec::new(),
+            composition: None,
+            dispatch_log: None,
+            user_input: false,
+            listener_hook: None,
+            pending_navigations: Vec::new(),
+            pointer_captures: PointerCaptures::new(),
         }
     }
 
+    /// Get the pointer capture state, to share with script bindings
+    pub fn pointer_captures(&self) -> &PointerCaptures {
+        &self.pointer_captures
+    }
+
+    /// Handle user input, marking every event dispatched by `f` as trusted
+    ///
+    /// This covers the events the input causes, too, such as `keypress`
+    /// after `keydown` or `change` after a checkbox click. Events dispatched
+    /// any other way keep the `is_trusted` they were created with, which is
+    /// false for script-created events.
+    pub fn handle_user_input<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        let previous = std::mem::replace(&mut self.user_input, true);
+        let result = f(self);
+        self.user_input = previous;
+        result
+    }
+
+    /// Run `hook` for every node an event reaches, in every phase
+    ///
+    /// The hook is how script listeners take part in dispatch: the events
+    /// it cancels do not run their default actions.
+    pub fn set_listener_hook(&mut self, hook: impl FnMut(&Rc<Node>, &mut Event) + 'static) {
+        self.listener_hook = Some(ListenerHook(Box::new(hook)));
+    }
+
+    /// Get the document this event manager dispatches into
+    pub fn document(&self) -> Option<&Rc<Document>> {
+        self.document.as_ref()
+    }
+
+    /// Start recording the type of every dispatched event
+    pub fn enable_dispatch_log(&mut self) {
+        self.dispatch_log.get_or_insert_with(Vec::new);
+    }
+
+    /// Take the event types dispatched since the last call
+    pub fn take_dispatch_log(&mut self) -> Vec<String> {
+        self.dispatch_log.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
     /// Set the document for this event manager
     pub fn set_document(&mut self, document: Rc<Document>) {
         self.document = Some(document);
@@ -61,6 +164,21 @@ impl DomEventManager {
         println!("Added event listener {} to node {} for event '{}'", id, node.id, event_type);
     }
 
+    /// Move the listeners and element wrappers of adopted nodes to the
+    /// event manager of their new document
+    ///
+    /// `node_ids` are the ids `Document::adopt_node` returned.
+    pub fn move_listeners_to(&mut self, other: &mut DomEventManager, node_ids: &[u64]) {
+        for id in node_ids {
+            if let Some(listeners) = self.node_listeners.remove(id) {
+                other.node_listeners.insert(*id, listeners);
+            }
+            if let Some(element) = self.element_cache.remove(id) {
+                other.element_cache.insert(*id, element);
+            }
+        }
+    }
+
     /// Remove an event listener from a DOM node
     pub fn remove_event_listener(&mut self, node: &Rc<Node>, event_type: &str, listener_id: u64) {
         if let Some(listeners) = self.node_listeners.get_mut(&node.id) {
@@ -71,29 +189,58 @@ impl DomEventManager {
         }
     }
 
+    /// Check whether every listener an event would reach is passive
+    ///
+    /// True when no listener for the type is registered on the target or
+    /// its ancestors, too. Passive listeners cannot cancel the event, so
+    /// its default action, such as scrolling for `wheel`, need not wait
+    /// for them.
+    pub fn has_only_passive_listeners(&self, target_node: &Rc<Node>, event_type: &str) -> bool {
+        self.calculate_event_path(target_node).iter().all(|node| {
+            self.node_listeners
+                .get(&node.id)
+                .is_none_or(|listeners| listeners.get_listeners(event_type).iter().all(|listener| listener.options.passive))
+        })
+    }
+
     /// Dispatch an event to a specific DOM node
+    ///
+    /// Default actions run only when the event was not cancelled: a click
+    /// follows links, submits forms and toggles `<details>`. Checkboxes and
+    /// radio buttons change before the click is dispatched, so listeners
+    /// see the new state, and change back if it is cancelled. Returns
+    /// whether the event was not cancelled.
     pub fn dispatch_event(&mut self, target_node: &Rc<Node>, mut event: Event) -> bool {
         // Set the target
         let _target_element = self.get_element(target_node);
         event.target = Some(Rc::new(RefCell::new(Element::new(Rc::clone(target_node)))));
+        if self.user_input {
+            event.is_trusted = true;
+        }
+        if let Some(log) = self.dispatch_log.as_mut() {
+            log.push(event.event_type.clone());
+        }
+
+        let is_click = event.event_type == "click";
+        let pre_activation = if is_click { Self::run_legacy_pre_activation(target_node) } else { None };
 
         // Calculate the event path (from target to root)
         let event_path = self.calculate_event_path(target_node);
         
-        // Execute capturing phase (root to target)
-        if event.bubbles {
-            event.phase = EventPhase::Capturing;
-            for node in event_path.iter().rev() {
-                if event.propagation_stopped {
-                    break;
-                }
-                self.execute_listeners(node, &mut event);
+        // Execute capturing phase (root to the target's parent)
+        event.phase = EventPhase::Capturing;
+        for node in event_path.iter().skip(1).rev() {
+            if event.propagation_stopped {
+                break;
             }
+            self.execute_listeners(node, &mut event);
         }
 
         // Execute target phase
-        event.phase = EventPhase::AtTarget;
-        self.execute_listeners(target_node, &mut event);
+        if !event.propagation_stopped {
+            event.phase = EventPhase::AtTarget;
+            self.execute_listeners(target_node, &mut event);
+        }
 
         // Execute bubbling phase (target to root)
         if event.bubbles && !event.propagation_stopped {
@@ -109,7 +256,553 @@ impl DomEventManager {
             }
         }
 
-        !event.default_prevented
+        event.phase = EventPhase::None;
+        let not_cancelled = !event.default_prevented;
+
+        // Run built-in activation behavior for uncancelled clicks
+        if is_click {
+            if not_cancelled {
+                self.run_activation_behavior(target_node, pre_activation.is_some());
+            } else if let Some(pre_activation) = pre_activation {
+                Self::undo_pre_activation(target_node, pre_activation);
+            }
+        }
+
+        not_cancelled
+    }
+
+    /// Dispatch a pointer event at the element under the pointer
+    ///
+    /// A pointer captured by an element sends its events there instead,
+    /// and `gotpointercapture`/`lostpointercapture` fire first when the
+    /// capture changed. The capture is released after `pointerup` and
+    /// `pointercancel`. Returns whether the event was not cancelled; the
+    /// caller skips the compatibility mouse events of a cancelled
+    /// `pointerdown`.
+    pub fn dispatch_pointer_event(&mut self, hit_target: &Rc<Node>, event: PointerEvent) -> bool {
+        let pointer_id = event.pointer_id;
+        let event_type = event.mouse.base.event_type.clone();
+        if event_type == "pointerdown" {
+            self.pointer_captures.pointer_down(pointer_id);
+        }
+        self.process_pointer_capture(pointer_id);
+
+        let target = self.pointer_captures.capture_target(pointer_id).unwrap_or_else(|| Rc::clone(hit_target));
+        let not_cancelled = self.dispatch_event(&target, event.mouse.base);
+
+        if matches!(event_type.as_str(), "pointerup" | "pointercancel") {
+            self.pointer_captures.pointer_up(pointer_id);
+            self.process_pointer_capture(pointer_id);
+        }
+        not_cancelled
+    }
+
+    /// Dispatch a touch event to the target of its changed touch, returning
+    /// `false` if a listener canceled it
+    pub fn dispatch_touch_event(&mut self, event: TouchEvent) -> bool {
+        let Some(target) = event.changed_touches.first().and_then(|touch| touch.target.clone()) else {
+            return true;
+        };
+        self.dispatch_event(&target, event.base)
+    }
+
+    /// Fire `lostpointercapture` and `gotpointercapture` if the capture of
+    /// a pointer changed
+    ///
+    /// An element that left the document loses the capture, and the
+    /// document receives its `lostpointercapture`.
+    fn process_pointer_capture(&mut self, pointer_id: i32) {
+        let change = self.pointer_captures.process_pending(pointer_id);
+        if let Some(lost) = change.lost {
+            let target = if lost.is_connected() { Some(lost) } else { self.document.as_ref().map(|document| Rc::clone(&document.root)) };
+            if let Some(target) = target {
+                self.dispatch_event(&target, Event::new("lostpointercapture", true, false));
+            }
+        }
+        if let Some(got) = change.got {
+            self.dispatch_event(&got, Event::new("gotpointercapture", true, false));
+        }
+    }
+
+    /// Dispatch a key event and run its default actions
+    ///
+    /// An uncancelled `keydown` for a character-producing key is followed by
+    /// `keypress`; if that is not cancelled either, editable targets receive
+    /// the text through `beforeinput`/`input`. Backspace edits without a
+    /// `keypress`, Enter in a text field triggers implicit submission and
+    /// the left and right arrows move the caret of text controls in visual
+    /// order, and Escape exits fullscreen, or else hides the topmost `auto`
+    /// popover, or else cancels the topmost modal dialog. `keyup` has no
+    /// default action. Returns whether the event was not
+    /// cancelled.
+    pub fn dispatch_keyboard_event(&mut self, target_node: &Rc<Node>, event: KeyboardEvent) -> bool {
+        let is_keydown = event.base.event_type == "keydown";
+        let init = KeyboardEventInit {
+            key: event.key.clone(),
+            code: event.code.clone(),
+            location: event.location,
+            repeat: event.repeat,
+            ctrl_key: event.ctrl_key,
+            shift_key: event.shift_key,
+            alt_key: event.alt_key,
+            meta_key: event.meta_key,
+            is_composing: event.is_composing,
+        };
+        let produces_character = event.produces_character();
+
+        let not_cancelled = self.dispatch_event(target_node, event.base);
+        if !not_cancelled || !is_keydown {
+            return not_cancelled;
+        }
+
+        if produces_character {
+            let keypress = SyntheticEventFactory::create_keyboard_event("keypress", &init);
+            if !self.dispatch_event(target_node, keypress.base) {
+                return not_cancelled;
+            }
+            if init.key == "Enter" {
+                if target_node.form_control_type() == Some(FormControlType::TextArea) {
+                    self.run_editing_action(target_node, "insertLineBreak", Some("\n".to_string()), false);
+                } else if target_node.form_control_type().is_none() && target_node.is_editable() {
+                    self.run_editing_action(target_node, "insertParagraph", None, false);
+                } else {
+                    self.run_implicit_submission(target_node);
+                }
+            } else if target_node.is_editable() {
+                self.run_editing_action(target_node, "insertText", Some(init.key), false);
+            }
+        } else if init.key == "Backspace" && !init.is_composing && target_node.is_editable() {
+            self.run_editing_action(target_node, "deleteContentBackward", None, false);
+        } else if matches!(init.key.as_str(), "ArrowLeft" | "ArrowRight")
+            && matches!(target_node.form_control_type(), Some(FormControlType::Text | FormControlType::Password | FormControlType::TextArea))
+        {
+            target_node.move_caret(init.key == "ArrowRight", init.shift_key);
+        } else if init.key == "Escape" {
+            if self.fully_exit_fullscreen(target_node) {
+                return not_cancelled;
+            }
+            match popover::topmost_auto_popover(target_node) {
+                Some(top) => {
+                    let toggles = top.hide_popover().unwrap_or_default();
+                    self.fire_popover_toggles(toggles);
+                }
+                None => self.run_dialog_cancel(target_node),
+            }
+        }
+
+        not_cancelled
+    }
+
+    /// Fire `cancel` at the topmost modal dialog and, unless it is
+    /// cancelled, close the dialog and fire `close`
+    fn run_dialog_cancel(&mut self, target_node: &Rc<Node>) {
+        let Some(dialog) = dialog::cancelable_dialog(target_node) else {
+            return;
+        };
+        if self.dispatch_event(&dialog, Event::new("cancel", false, true)) && dialog.close_dialog(None) {
+            self.relayout_needed = true;
+            self.dispatch_event(&dialog, Event::new("close", false, false));
+        }
+    }
+
+    /// Light dismiss popovers before a pointer goes down on `target_node`,
+    /// firing `toggle` at each popover hidden
+    pub fn light_dismiss_popovers(&mut self, target_node: &Rc<Node>) {
+        let toggles = popover::light_dismiss(target_node);
+        self.fire_popover_toggles(toggles);
+    }
+
+    /// Take the document that owns `node` out of fullscreen, firing
+    /// `fullscreenchange` at each element that left it
+    ///
+    /// Returns whether any element was fullscreen.
+    pub fn fully_exit_fullscreen(&mut self, node: &Rc<Node>) -> bool {
+        let elements = fullscreen::fully_exit_fullscreen(node);
+        for element in &elements {
+            println!("Exited fullscreen for element {}", element.id);
+            self.relayout_needed = true;
+            self.dispatch_event(element, Event::new("fullscreenchange", true, false));
+        }
+        !elements.is_empty()
+    }
+
+    /// Set the visibility state of the document that owns `node`, firing
+    /// `visibilitychange` at the document if it changed
+    ///
+    /// Returns whether the state changed.
+    pub fn set_visibility_state(&mut self, node: &Rc<Node>, state: VisibilityState) -> bool {
+        if !page_visibility::set_visibility_state(node, state) {
+            return false;
+        }
+        println!("Document is now {}", state);
+        let root = forms::tree_root(node);
+        self.dispatch_event(&root, Event::new("visibilitychange", true, false));
+        true
+    }
+
+    /// Fire a non-bubbling `toggle` at each popover that opened or closed
+    fn fire_popover_toggles(&mut self, toggles: Vec<PopoverToggle>) {
+        for toggle in toggles {
+            self.relayout_needed = true;
+            self.dispatch_event(&toggle.popover, Event::new("toggle", false, false));
+        }
+    }
+
+    /// Dispatch a composition event and update the composed text
+    ///
+    /// `compositionstart` remembers the control's value, every
+    /// `compositionupdate` replaces the composed text after it (announced by
+    /// a composing `beforeinput`/`input` pair), and `compositionend` commits
+    /// the final text with a non-composing `input`.
+    pub fn dispatch_composition_event(&mut self, target_node: &Rc<Node>, event: CompositionEvent) -> bool {
+        let event_type = event.base.event_type.clone();
+        let data = event.data.clone();
+        let not_cancelled = self.dispatch_event(target_node, event.base);
+        if !target_node.is_editable() {
+            return not_cancelled;
+        }
+
+        match event_type.as_str() {
+            "compositionstart" => {
+                self.composition = Some(Composition {
+                    target: target_node.id,
+                    base_value: target_node.value(),
+                });
+            }
+            "compositionupdate" => {
+                self.run_editing_action(target_node, "insertCompositionText", Some(data), true);
+            }
+            "compositionend" => {
+                let composition = self.composition.take().filter(|c| c.target == target_node.id);
+                if let Some(composition) = composition {
+                    if target_node.form_control_type().is_some() {
+                        target_node.set_value(&format!("{}{}", composition.base_value, data));
+                        self.relayout_needed = true;
+                    }
+                    let mut input = SyntheticEventFactory::create_input_event(Some(data), "insertCompositionText");
+                    input.base.cancelable = false;
+                    self.dispatch_event(target_node, input.base);
+                }
+            }
+            _ => {}
+        }
+        not_cancelled
+    }
+
+    /// Fire a clipboard event and run its default action
+    ///
+    /// `copy` and `cut` put the value of a text control on the clipboard
+    /// (controls have no selection yet, so the whole value is used; password
+    /// fields are never copied), and `cut` then clears it. `paste` carries
+    /// the clipboard text in `clipboardData` and inserts it into editable
+    /// targets. Returns whether the event was not cancelled.
+    pub fn dispatch_clipboard_event(&mut self, target_node: &Rc<Node>, event_type: &str, clipboard: &Clipboard) -> bool {
+        let data = match event_type {
+            "paste" => clipboard.read_text().ok(),
+            _ => None,
+        };
+        let event = SyntheticEventFactory::create_clipboard_event(event_type, data.clone());
+        if !self.dispatch_event(target_node, event.base) {
+            return false;
+        }
+
+        let copyable = matches!(
+            target_node.form_control_type(),
+            Some(FormControlType::Text | FormControlType::TextArea)
+        );
+        match event_type {
+            "copy" | "cut" if copyable => {
+                let value = target_node.value();
+                if value.is_empty() {
+                    return true;
+                }
+                if let Err(error) = clipboard.write_text(&value) {
+                    println!("❌ Failed to write to the clipboard: {}", error);
+                    return true;
+                }
+                if event_type == "cut" && target_node.is_editable() {
+                    self.run_editing_action(target_node, "deleteByCut", None, false);
+                }
+            }
+            "paste" if target_node.is_editable() => {
+                if let Some(text) = data {
+                    self.run_editing_action(target_node, "insertFromPaste", Some(text), false);
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+
+    /// Fire `beforeinput`, apply the edit if it was not cancelled, then fire
+    /// `input`
+    ///
+    /// Form controls replace their selection, or insert at the caret.
+    /// Compositions are appended to the value the composition started from.
+    /// `contenteditable` hosts receive the events but their text nodes are
+    /// left to script.
+    fn run_editing_action(&mut self, target_node: &Rc<Node>, input_type: &str, data: Option<String>, is_composing: bool) {
+        let is_control = target_node.form_control_type().is_some();
+        let value = target_node.value();
+        let chars: Vec<char> = value.chars().collect();
+        let (start, end) = target_node.selection_range();
+        let splice = |from: usize, to: usize, text: &str| -> String {
+            chars[..from].iter().copied().chain(text.chars()).chain(chars[to..].iter().copied()).collect()
+        };
+        let (new_value, caret) = match input_type {
+            "deleteContentBackward" => {
+                if start == end && start == 0 && is_control {
+                    return;
+                }
+                let from = if start == end { start.saturating_sub(1) } else { start };
+                (splice(from, end, ""), Some(from))
+            }
+            "deleteByCut" => (String::new(), None),
+            "insertCompositionText" => {
+                let base = match &self.composition {
+                    Some(composition) if composition.target == target_node.id => composition.base_value.clone(),
+                    _ => value.clone(),
+                };
+                (format!("{}{}", base, data.clone().unwrap_or_default()), None)
+            }
+            _ => {
+                let text = data.clone().unwrap_or_default();
+                (splice(start, end, &text), Some(start + text.chars().count()))
+            }
+        };
+
+        let beforeinput = SyntheticEventFactory::create_beforeinput_event(data.clone(), input_type, is_composing);
+        // Composition updates cannot be cancelled
+        let mut beforeinput_base = beforeinput.base;
+        beforeinput_base.cancelable = !is_composing;
+        if !self.dispatch_event(target_node, beforeinput_base) {
+            return;
+        }
+
+        if is_control {
+            target_node.set_value(&new_value);
+            if let Some(caret) = caret {
+                target_node.set_selection_range(caret, caret);
+            }
+            self.relayout_needed = true;
+        }
+
+        let mut input = SyntheticEventFactory::create_input_event(data, input_type);
+        input.is_composing = is_composing;
+        input.base.cancelable = false;
+        self.dispatch_event(target_node, input.base);
+    }
+
+    /// Change the state of a clicked checkbox or radio button before the
+    /// click is dispatched
+    ///
+    /// Returns what changed, or `None` if nothing did.
+    fn run_legacy_pre_activation(target_node: &Rc<Node>) -> Option<PreActivation> {
+        match target_node.form_control_type() {
+            Some(FormControlType::Checkbox) => {
+                let was_checked = target_node.checked();
+                target_node.set_checked(!was_checked);
+                Some(PreActivation::Checkbox { was_checked })
+            }
+            Some(FormControlType::Radio) if !target_node.checked() => {
+                let previous = target_node.radio_group().into_iter().find(|radio| radio.checked());
+                target_node.set_checked(true);
+                Some(PreActivation::Radio { previous })
+            }
+            _ => None,
+        }
+    }
+
+    /// Restore the state a cancelled click changed
+    fn undo_pre_activation(target_node: &Rc<Node>, pre_activation: PreActivation) {
+        match pre_activation {
+            PreActivation::Checkbox { was_checked } => target_node.set_checked(was_checked),
+            PreActivation::Radio { previous: Some(previous) } => previous.set_checked(true),
+            PreActivation::Radio { previous: None } => target_node.set_checked(false),
+        }
+    }
+
+    /// Run the activation behavior of the element that was clicked
+    ///
+    /// This implements:
+    /// - `input`/`change` for checkboxes and radio buttons whose state the
+    ///   click changed (`state_changed`)
+    /// - form submission from submit buttons
+    /// - link navigation: clicking a link with an `href` (or anything
+    ///   inside it) queues its target for the browser shell
+    /// - `<details>` toggling: clicking the summary (or anything inside it)
+    ///   flips the `open` attribute and fires a non-bubbling `toggle` event
+    /// - popover invocation: clicking a button with a `popovertarget` (or
+    ///   anything inside it) toggles, shows or hides its popover as its
+    ///   `popovertargetaction` says
+    fn run_activation_behavior(&mut self, target_node: &Rc<Node>, state_changed: bool) {
+        let invoked = popover::invoker_button(target_node)
+            .and_then(|button| popover::invoked_popover(&button).map(|(popover, action)| (button, popover, action)));
+        if let Some((button, target, action)) = invoked {
+            let toggles = match action {
+                PopoverTargetAction::Toggle => target.toggle_popover(None, Some(&button)),
+                PopoverTargetAction::Show => target.show_popover(Some(&button)),
+                PopoverTargetAction::Hide => target.hide_popover(),
+            };
+            self.fire_popover_toggles(toggles.unwrap_or_default());
+            return;
+        }
+        match target_node.form_control_type() {
+            Some(FormControlType::Checkbox | FormControlType::Radio) => {
+                if state_changed {
+                    self.fire_input_and_change(target_node);
+                }
+                return;
+            }
+            Some(FormControlType::Submit) => {
+                if let Some(form) = target_node.form_owner() {
+                    self.submit_form(&form, Some(target_node));
+                }
+                return;
+            }
+            _ => {}
+        }
+
+        // The nearest link or summary handles the click
+        let mut current = Some(Rc::clone(target_node));
+        while let Some(candidate) = current {
+            if let Some(href) = Self::link_href(&candidate) {
+                let target = NavigationTarget::of(&candidate);
+                if !self.sandbox_flags().is_none_or(|flags| flags.allows_navigation(target, true)) {
+                    println!("Blocked navigation to '{}' from a sandboxed frame", href);
+                    return;
+                }
+                println!("Queued navigation to '{}'", href);
+                self.pending_navigations.push(href);
+                return;
+            }
+            if candidate.tag_name() == Some("summary") {
+                if let Some(details) = Self::details_for_summary(&candidate) {
+                    details.toggle_attribute("open");
+                    self.relayout_needed = true;
+                    self.dispatch_event(&details, Event::new("toggle", false, false));
+                }
+                return;
+            }
+            current = candidate.parent.borrow().upgrade();
+        }
+    }
+
+    /// Get the sandbox flags of the document, if it is sandboxed
+    fn sandbox_flags(&self) -> Option<SandboxFlags> {
+        self.document.as_ref().and_then(|document| document.sandbox_flags())
+    }
+
+    /// Get the `href` of an `<a>` or `<area>` element that has one
+    fn link_href(node: &Rc<Node>) -> Option<String> {
+        if !matches!(node.tag_name(), Some("a" | "area")) {
+            return None;
+        }
+        node.get_attribute("href")
+    }
+
+    /// Fire `input` followed by `change` after a control's state changed
+    fn fire_input_and_change(&mut self, target_node: &Rc<Node>) {
+        self.relayout_needed = true;
+        self.dispatch_event(target_node, Event::new("input", true, false));
+        self.dispatch_event(target_node, Event::new("change", true, false));
+    }
+
+    /// Fire a `submit` event at a form and queue the submission if it is
+    /// not cancelled
+    pub fn submit_form(&mut self, form: &Rc<Node>, submitter: Option<&Rc<Node>>) -> bool {
+        if self.sandbox_flags().is_some_and(|flags| !flags.forms) {
+            println!("Blocked form submission from a sandboxed frame without allow-forms");
+            return false;
+        }
+        if !self.dispatch_event(form, Event::new("submit", true, true)) {
+            println!("Form submission cancelled by a submit listener");
+            return false;
+        }
+        let submission = forms::build_form_submission(form, submitter);
+        println!(
+            "Queued {:?} form submission to '{}' with {} entries",
+            submission.method,
+            submission.action,
+            submission.entries.len()
+        );
+        self.pending_submissions.push(submission);
+        true
+    }
+
+    /// Run implicit submission after Enter was pressed in a text field
+    ///
+    /// Clicks the form's default button if it has one, otherwise submits
+    /// the form directly.
+    fn run_implicit_submission(&mut self, target_node: &Rc<Node>) {
+        let is_text_entry = target_node
+            .form_control_type()
+            .is_some_and(|control_type| control_type.is_text_entry());
+        if !is_text_entry {
+            return;
+        }
+        if let Some(form) = target_node.form_owner() {
+            match forms::default_button(&form) {
+                Some(button) => {
+                    self.dispatch_event(&button, MouseEvent::new("click", true, true).base);
+                }
+                None => {
+                    self.submit_form(&form, None);
+                }
+            }
+        }
+    }
+
+    /// Take the oldest queued form submission
+    pub fn take_form_submission(&mut self) -> Option<FormSubmission> {
+        if self.pending_submissions.is_empty() {
+            None
+        } else {
+            Some(self.pending_submissions.remove(0))
+        }
+    }
+
+    /// Take the oldest queued link navigation
+    ///
+    /// The link's `href` is returned as written; the browser shell resolves
+    /// it against the document's base URL.
+    pub fn take_navigation(&mut self) -> Option<String> {
+        if self.pending_navigations.is_empty() {
+            None
+        } else {
+            Some(self.pending_navigations.remove(0))
+        }
+    }
+
+    /// Find the `<details>` element controlled by a summary
+    ///
+    /// Only the first `<summary>` child of a `<details>` element acts as its
+    /// toggle.
+    fn details_for_summary(summary: &Rc<Node>) -> Option<Rc<Node>> {
+        let parent = summary.parent.borrow().upgrade()?;
+        if parent.tag_name() != Some("details") {
+            return None;
+        }
+        let first_summary = parent
+            .children
+            .borrow()
+            .iter()
+            .find(|child| child.tag_name() == Some("summary"))
+            .cloned();
+        match first_summary {
+            Some(first) if Rc::ptr_eq(&first, summary) => Some(parent),
+            _ => None,
+        }
+    }
+
+    /// Check whether an event has changed the DOM in a way that requires relayout
+    pub fn needs_relayout(&self) -> bool {
+        self.relayout_needed
+    }
+
+    /// Clear the relayout flag once layout has been recomputed
+    pub fn clear_relayout(&mut self) {
+        self.relayout_needed = false;
     }
 
     /// Calculate the event path from target to root
@@ -128,7 +821,10 @@ impl DomEventManager {
     }
 
     /// Execute event listeners for a specific node
-    fn execute_listeners(&self, node: &Rc<Node>, event: &mut Event) {
+    fn execute_listeners(&mut self, node: &Rc<Node>, event: &mut Event) {
+        if let Some(hook) = self.listener_hook.as_mut() {
+            (hook.0)(node, event);
+        }
         if let Some(listeners) = self.node_listeners.get(&node.id) {
             let relevant_listeners = match event.phase {
                 EventPhase::Capturing => listeners.get_capture_listeners(&event.event_type),
@@ -171,12 +867,8 @@ impl DomEventManager {
     /// Recursively find a node by ID
     fn find_node_by_id_recursive(&self, node: &Rc<Node>, id: &str) -> Option<Rc<Node>> {
         // Check if this node has the matching ID attribute
-        if let NodeType::Element { attributes, .. } = &node.node_type {
-            if let Some(node_id) = attributes.get("id") {
-                if node_id == id {
-                    return Some(Rc::clone(node));
-                }
-            }
+        if node.get_attribute("id").as_deref() == Some(id) {
+            return Some(Rc::clone(node));
         }
 
         // Search in children
@@ -203,11 +895,9 @@ impl DomEventManager {
         let mut results = Vec::new();
 
         // Check if this node has the matching class
-        if let NodeType::Element { attributes, .. } = &node.node_type {
-            if let Some(class_attr) = attributes.get("class") {
-                if class_attr.split_whitespace().any(|c| c == class_name) {
-                    results.push(Rc::clone(node));
-                }
+        if let Some(class_attr) = node.get_attribute("class") {
+            if class_attr.split_whitespace().any(|c| c == class_name) {
+                results.push(Rc::clone(node));
             }
         }
 
@@ -262,11 +952,8 @@ impl DomEventManager {
     /// Simulate a keydown event on a node
     pub fn simulate_keydown(&mut self, target_id: &str, key: &str) -> bool {
         if let Some(target) = self.find_node_by_id(target_id) {
-            let mut keydown_event = KeyboardEvent::new("keydown", true, true);
-            keydown_event.key = key.to_string();
-            keydown_event.code = key.to_string();
-            keydown_event.base.target = Some(Rc::new(RefCell::new(Element::new(Rc::clone(&target)))));
-            self.dispatch_event(&target, keydown_event.base)
+            let keydown_event = SyntheticEventFactory::create_keyboard_event("keydown", &KeyboardEventInit::new(key, key));
+            self.dispatch_keyboard_event(&target, keydown_event)
         } else {
             println!("Node with ID '{}' not found", target_id);
             false
@@ -280,6 +967,10 @@ impl DomEventManager {
             input_event.data = Some(value.to_string());
             input_event.input_type = "insertText".to_string();
             input_event.base.target = Some(Rc::new(RefCell::new(Element::new(Rc::clone(&target)))));
+            if target.form_control_type().is_some() {
+                target.set_value(value);
+                self.relayout_needed = true;
+            }
             self.dispatch_event(&target, input_event.base)
         } else {
             println!("Node with ID '{}' not found", target_id);
@@ -380,6 +1071,157 @@ mod tests {
         assert_eq!(body.children.borrow().len(), 1); // div element
     }
 
+    #[test]
+    fn test_details_summary_toggle() {
+        let doc = Document::new();
+        let details = doc.create_element("details");
+        let summary = doc.create_element("summary");
+        let label = doc.create_text_node("More");
+        let content = doc.create_element("p");
+
+        doc.root.append_child(&details);
+        details.append_child(&summary);
+        summary.append_child(&label);
+        details.append_child(&content);
+
+        let mut manager = DomEventManager::new();
+        let toggle_listener = EventListener {
+            callback: "onToggle".to_string(),
+            options: EventListenerOptions::default(),
+            id: 1,
+        };
+        manager.add_event_listener(&details, "toggle", toggle_listener);
+
+        // Clicking inside the summary opens the details element
+        assert!(manager.dispatch_event(&label, MouseEvent::new("click", true, true).base));
+        assert!(details.has_attribute("open"));
+        assert!(manager.needs_relayout());
+        manager.clear_relayout();
+
+        // Clicking the summary again closes it
+        manager.dispatch_event(&summary, MouseEvent::new("click", true, true).base);
+        assert!(!details.has_attribute("open"));
+
+        // Clicking the content does nothing
+        manager.clear_relayout();
+        manager.dispatch_event(&content, MouseEvent::new("click", true, true).base);
+        assert!(!details.has_attribute("open"));
+        assert!(!manager.needs_relayout());
+    }
+
+    fn control(doc: &Document, tag: &str, attrs: &[(&str, &str)]) -> Rc<Node> {
+        let attributes = attrs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        doc.create_node(NodeType::Element {
+            tag_name: tag.to_string(),
+            attributes,
+        })
+    }
+
+    #[test]
+    fn test_form_controls_and_submission() {
+        let doc = Document::new();
+        let form = control(&doc, "form", &[("action", "/search")]);
+        let query = control(&doc, "input", &[("id", "q"), ("name", "q")]);
+        let safe = control(&doc, "input", &[("id", "safe"), ("type", "checkbox"), ("name", "safe")]);
+        let go = control(&doc, "input", &[("id", "go"), ("type", "submit"), ("name", "go"), ("value", "Go")]);
+        doc.root.append_child(&form);
+        form.append_child(&query);
+        form.append_child(&safe);
+        form.append_child(&go);
+
+        let mut manager = DomEventManager::new();
+        manager.set_document(Rc::new(doc));
+
+        manager.simulate_input("q", "rust");
+        assert_eq!(query.value(), "rust");
+
+        manager.simulate_click("safe");
+        assert!(safe.checked());
+
+        manager.simulate_click("go");
+        let submission = manager.take_form_submission().unwrap();
+        assert_eq!(submission.action, "/search");
+        assert_eq!(
+            submission.entries,
+            vec![
+                ("q".to_string(), "rust".to_string()),
+                ("safe".to_string(), "on".to_string()),
+                ("go".to_string(), "Go".to_string()),
+            ]
+        );
+
+        // Enter in a text field clicks the default button
+        manager.simulate_keydown("q", "Enter");
+        assert!(manager.take_form_submission().is_some());
+        assert!(manager.take_form_submission().is_none());
+    }
+
+    #[test]
+    fn test_default_actions_run_only_when_not_prevented() {
+        let doc = Document::new();
+        let form = control(&doc, "form", &[("action", "/save")]);
+        let agree = control(&doc, "input", &[("id", "agree"), ("type", "checkbox"), ("name", "agree")]);
+        let save = control(&doc, "input", &[("id", "save"), ("type", "submit")]);
+        let link = control(&doc, "a", &[("href", "/next")]);
+        let label = control(&doc, "span", &[("id", "label")]);
+        doc.root.append_child(&form);
+        form.append_child(&agree);
+        form.append_child(&save);
+        doc.root.append_child(&link);
+        link.append_child(&label);
+
+        // Script listeners cancel clicks while `block` is set and record
+        // what they saw
+        let block = Rc::new(std::cell::Cell::new(true));
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let mut manager = DomEventManager::new();
+        manager.set_listener_hook({
+            let block = Rc::clone(&block);
+            let seen = Rc::clone(&seen);
+            let agree = Rc::clone(&agree);
+            move |_node, event| {
+                if event.phase == EventPhase::AtTarget {
+                    seen.borrow_mut().push((event.event_type.clone(), event.is_trusted, agree.checked()));
+                    if block.get() && event.event_type == "click" {
+                        event.prevent_default();
+                    }
+                }
+            }
+        });
+        manager.set_document(Rc::new(doc));
+
+        // Cancelled clicks change nothing, though listeners saw the new state
+        assert!(!manager.simulate_click("agree"));
+        assert!(!agree.checked());
+        assert_eq!(seen.borrow()[0], ("click".to_string(), false, true));
+        assert!(!manager.simulate_click("save"));
+        assert!(manager.take_form_submission().is_none());
+        assert!(!manager.simulate_click("label"));
+        assert!(manager.take_navigation().is_none());
+
+        // Uncancelled ones run their default actions
+        block.set(false);
+        seen.borrow_mut().clear();
+        manager.simulate_click("agree");
+        assert!(agree.checked());
+        let types: Vec<_> = seen.borrow().iter().map(|(event_type, ..)| event_type.clone()).collect();
+        assert_eq!(types, ["click", "input", "change"]);
+        manager.simulate_click("save");
+        assert!(manager.take_form_submission().is_some());
+        manager.simulate_click("label");
+        assert_eq!(manager.take_navigation().as_deref(), Some("/next"));
+
+        // Events caused by user input are trusted, and have a timestamp
+        seen.borrow_mut().clear();
+        manager.handle_user_input(|manager| manager.simulate_click("agree"));
+        assert!(seen.borrow().iter().all(|(_, trusted, _)| *trusted));
+        let first = Event::new("first", false, false);
+        assert!(Event::new("second", false, false).timestamp >= first.timestamp);
+    }
+
     #[test]
     fn test_event_listener_management() {
         let doc = Document::new();
@@ -399,4 +1241,142 @@ mod tests {
         assert_eq!(stats.total_nodes, 1);
         assert_eq!(stats.total_listeners, 1);
     }
+
+    #[test]
+    fn test_adopted_listeners_move_with_their_node() {
+        let source = Document::new();
+        let target = Document::new();
+        let button = source.create_element("button");
+        source.root.append_child(&button);
+        let mut source_manager = DomEventManager::new();
+        let mut target_manager = DomEventManager::new();
+        let listener = EventListener {
+            callback: "function() {}".to_string(),
+            options: EventListenerOptions::default(),
+            id: 1,
+        };
+        source_manager.add_event_listener(&button, "click", listener);
+
+        let adopted = target.adopt_node(&button).unwrap();
+        source_manager.move_listeners_to(&mut target_manager, &adopted);
+        assert_eq!(source_manager.get_stats().total_listeners, 0);
+        assert_eq!(target_manager.get_stats().total_listeners, 1);
+    }
+
+    #[test]
+    fn test_key_sequence_edits_focused_text_field() {
+        let doc = Document::new();
+        let field = control(&doc, "input", &[("value", "hi")]);
+        let area = control(&doc, "textarea", &[]);
+        let locked = control(&doc, "input", &[("readonly", "")]);
+        doc.root.append_child(&field);
+        doc.root.append_child(&area);
+        doc.root.append_child(&locked);
+
+        let mut manager = DomEventManager::new();
+        manager.enable_dispatch_log();
+        let press = |manager: &mut DomEventManager, node: &Rc<Node>, init: KeyboardEventInit| {
+            manager.dispatch_keyboard_event(node, SyntheticEventFactory::create_keyboard_event("keydown", &init));
+            manager.dispatch_keyboard_event(node, SyntheticEventFactory::create_keyboard_event("keyup", &init));
+        };
+
+        press(&mut manager, &field, KeyboardEventInit::new("!", "Digit1"));
+        assert_eq!(field.value(), "hi!");
+        assert_eq!(manager.take_dispatch_log(), vec!["keydown", "keypress", "beforeinput", "input", "keyup"]);
+
+        press(&mut manager, &field, KeyboardEventInit::new("Backspace", "Backspace"));
+        assert_eq!(field.value(), "hi");
+        assert_eq!(manager.take_dispatch_log(), vec!["keydown", "beforeinput", "input", "keyup"]);
+
+        // Shortcuts and read-only controls do not edit
+        let mut select_all = KeyboardEventInit::new("a", "KeyA");
+        select_all.ctrl_key = true;
+        press(&mut manager, &field, select_all);
+        press(&mut manager, &locked, KeyboardEventInit::new("x", "KeyX"));
+        assert_eq!(field.value(), "hi");
+        assert_eq!(locked.value(), "");
+        assert_eq!(manager.take_dispatch_log(), vec!["keydown", "keyup", "keydown", "keypress", "keyup"]);
+
+        press(&mut manager, &area, KeyboardEventInit::new("Enter", "Enter"));
+        assert_eq!(area.value(), "\n");
+
+        // An IME composition replaces its text on every update
+        let composition = |manager: &mut DomEventManager, event_type: &str, data: &str| {
+            manager.dispatch_composition_event(&field, SyntheticEventFactory::create_composition_event(event_type, data));
+        };
+        manager.take_dispatch_log();
+        composition(&mut manager, "compositionstart", "");
+        composition(&mut manager, "compositionupdate", "n");
+        composition(&mut manager, "compositionupdate", "に");
+        assert_eq!(field.value(), "hiに");
+        composition(&mut manager, "compositionend", "日");
+        assert_eq!(field.value(), "hi日");
+        assert_eq!(
+            manager.take_dispatch_log(),
+            vec![
+                "compositionstart",
+                "compositionupdate", "beforeinput", "input",
+                "compositionupdate", "beforeinput", "input",
+                "compositionend", "input",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_arrow_keys_move_caret_in_visual_order() {
+        let doc = Document::new();
+        // "ab" followed by the Hebrew letters alef and bet
+        let field = control(&doc, "input", &[("value", "ab\u{5d0}\u{5d1}")]);
+        doc.root.append_child(&field);
+        let mut manager = DomEventManager::new();
+        let press = |manager: &mut DomEventManager, init: KeyboardEventInit| {
+            manager.dispatch_keyboard_event(&field, SyntheticEventFactory::create_keyboard_event("keydown", &init));
+        };
+
+        // Right of "b" the caret enters the Hebrew word from its visual
+        // left, which is after its last logical letter
+        field.set_selection_range(2, 2);
+        press(&mut manager, KeyboardEventInit::new("ArrowRight", "ArrowRight"));
+        assert_eq!(field.selection_range(), (3, 3));
+        press(&mut manager, KeyboardEventInit::new("!", "Digit1"));
+        assert_eq!(field.value(), "ab\u{5d0}!\u{5d1}");
+
+        let mut extend = KeyboardEventInit::new("ArrowLeft", "ArrowLeft");
+        extend.shift_key = true;
+        field.set_selection_range(2, 2);
+        press(&mut manager, extend);
+        assert_eq!(field.selection_range(), (1, 2));
+        press(&mut manager, KeyboardEventInit::new("Backspace", "Backspace"));
+        assert_eq!(field.value(), "a\u{5d0}!\u{5d1}");
+        assert_eq!(field.selection_range(), (1, 1));
+    }
+
+    #[test]
+    fn test_clipboard_events_copy_cut_and_paste() {
+        let doc = Document::new();
+        let source = control(&doc, "input", &[("value", "token")]);
+        let secret = control(&doc, "input", &[("type", "password"), ("value", "hunter2")]);
+        let target = control(&doc, "textarea", &[]);
+        doc.root.append_child(&source);
+        doc.root.append_child(&secret);
+        doc.root.append_child(&target);
+
+        let clipboard = Clipboard::in_memory();
+        let mut manager = DomEventManager::new();
+        manager.enable_dispatch_log();
+
+        manager.dispatch_clipboard_event(&secret, "copy", &clipboard);
+        assert!(clipboard.read_text().is_err());
+
+        manager.dispatch_clipboard_event(&source, "cut", &clipboard);
+        assert_eq!(clipboard.read_text().unwrap(), "token");
+        assert_eq!(source.value(), "");
+
+        manager.dispatch_clipboard_event(&target, "paste", &clipboard);
+        assert_eq!(target.value(), "token");
+        assert_eq!(
+            manager.take_dispatch_log(),
+            vec!["copy", "cut", "beforeinput", "input", "paste", "beforeinput", "input"]
+        );
+    }
 }
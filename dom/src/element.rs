@@ -110,7 +110,7 @@ impl Element {
     /// Set the inner text of the element
     pub fn set_inner_text(&mut self, text: &str) {
         // Clear existing children
-        self.node.children.borrow_mut().clear();
+        self.node.remove_children();
         
         // Add new text node
         let text_node = crate::Node::new(
@@ -0,0 +1,275 @@
+//! # Element Interaction State
+//!
+//! This module tracks the user interaction state of elements (`:hover`,
+//! `:active`, `:focus`) that selectors can match against, whether a link
+//! points to a visited URL (`:visited`), whether a dialog is modal
+//! (`:modal`), whether a popover is showing (`:popover-open`) and whether
+//! an element is fullscreen (`:fullscreen`).
+//!
+//! ## Design Principles
+//!
+//! 1. **State Bits on Nodes**: Each node carries a small set of state flags,
+//!    so selector matching only needs the node itself.
+//!
+//! 2. **Chains, Not Targets**: Hover and active apply to the target and all
+//!    of its ancestors, like in browsers; `InteractionState` keeps the chains
+//!    consistent and reports which nodes changed so callers can restyle only
+//!    those.
+//!
+//! 3. **Focus Decides Keyboard Targets**: Key events go to the focused
+//!    element, so focusability and editability live next to the state that
+//!    tracks focus.
+
+use std::rc::{Rc, Weak};
+use crate::Node;
+use crate::forms::FormControlType;
+
+/// A set of interaction state flags
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ElementState(u8);
+
+impl ElementState {
+    /// No state
+    pub const EMPTY: ElementState = ElementState(0);
+    /// The pointer is over the element or one of its descendants
+    pub const HOVER: ElementState = ElementState(1 << 0);
+    /// The element or one of its descendants is being activated
+    pub const ACTIVE: ElementState = ElementState(1 << 1);
+    /// The element has focus
+    pub const FOCUS: ElementState = ElementState(1 << 2);
+    /// The element is a link to a URL in the user's history
+    pub const VISITED: ElementState = ElementState(1 << 3);
+    /// The element is a dialog shown with `showModal()`
+    pub const MODAL: ElementState = ElementState(1 << 4);
+    /// The element is a popover that is showing
+    pub const POPOVER_OPEN: ElementState = ElementState(1 << 5);
+    /// The element is the fullscreen element or below it in the top layer
+    pub const FULLSCREEN: ElementState = ElementState(1 << 6);
+
+    /// Check whether all flags in `other` are set
+    pub fn contains(&self, other: ElementState) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Check whether any flag in `other` is set
+    pub fn intersects(&self, other: ElementState) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    /// Set the flags in `other`
+    pub fn insert(&mut self, other: ElementState) {
+        self.0 |= other.0;
+    }
+
+    /// Clear the flags in `other`
+    pub fn remove(&mut self, other: ElementState) {
+        self.0 &= !other.0;
+    }
+
+    /// Check whether no flag is set
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Get the state flag matched by a pseudo-class, if any
+    pub fn from_pseudo_class(name: &str) -> Option<ElementState> {
+        match name {
+            "hover" => Some(ElementState::HOVER),
+            "active" => Some(ElementState::ACTIVE),
+            "focus" => Some(ElementState::FOCUS),
+            "visited" => Some(ElementState::VISITED),
+            "modal" => Some(ElementState::MODAL),
+            "popover-open" => Some(ElementState::POPOVER_OPEN),
+            "fullscreen" => Some(ElementState::FULLSCREEN),
+            _ => None,
+        }
+    }
+}
+
+/// Check whether a node is a link that `:link` and `:visited` apply to: an
+/// `<a>` or `<area>` element with an `href`
+pub fn is_link(node: &Node) -> bool {
+    matches!(node.tag_name(), Some("a") | Some("area")) && node.has_attribute("href")
+}
+
+impl std::ops::BitOr for ElementState {
+    type Output = ElementState;
+
+    fn bitor(self, other: ElementState) -> ElementState {
+        ElementState(self.0 | other.0)
+    }
+}
+
+/// A node whose state flags changed
+#[derive(Debug, Clone)]
+pub struct StateChange {
+    pub node: Rc<Node>,
+    /// The flags that flipped
+    pub changed: ElementState,
+}
+
+/// Tracks the hover and active chains of a document
+#[derive(Debug, Default)]
+pub struct InteractionState {
+    hovered: Option<Weak<Node>>,
+    active: Option<Weak<Node>>,
+    focused: Option<Weak<Node>>,
+}
+
+impl InteractionState {
+    /// Create an empty interaction state
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the node the pointer is over
+    pub fn hovered(&self) -> Option<Rc<Node>> {
+        self.hovered.as_ref().and_then(Weak::upgrade)
+    }
+
+    /// Get the node being activated
+    pub fn active(&self) -> Option<Rc<Node>> {
+        self.active.as_ref().and_then(Weak::upgrade)
+    }
+
+    /// Get the focused node
+    pub fn focused(&self) -> Option<Rc<Node>> {
+        self.focused.as_ref().and_then(Weak::upgrade)
+    }
+
+    /// Move the hover chain to `target` and its ancestors
+    pub fn set_hovered(&mut self, target: Option<&Rc<Node>>) -> Vec<StateChange> {
+        let previous = self.hovered();
+        self.hovered = target.map(Rc::downgrade);
+        move_chain(previous.as_ref(), target, ElementState::HOVER, true)
+    }
+
+    /// Move the active chain to `target` and its ancestors
+    pub fn set_active(&mut self, target: Option<&Rc<Node>>) -> Vec<StateChange> {
+        let previous = self.active();
+        self.active = target.map(Rc::downgrade);
+        move_chain(previous.as_ref(), target, ElementState::ACTIVE, true)
+    }
+
+    /// Move focus to `target`; unlike hover, focus does not propagate to
+    /// ancestors
+    pub fn set_focused(&mut self, target: Option<&Rc<Node>>) -> Vec<StateChange> {
+        let previous = self.focused();
+        self.focused = target.map(Rc::downgrade);
+        move_chain(previous.as_ref(), target, ElementState::FOCUS, false)
+    }
+}
+
+impl Node {
+    /// Whether clicking this element gives it focus
+    ///
+    /// Form controls, links with an `href`, elements with a `tabindex` and
+    /// editing hosts are focusable; disabled controls and inert elements
+    /// are not.
+    pub fn is_focusable(&self) -> bool {
+        if self.has_attribute("disabled") || self.is_inert() {
+            return false;
+        }
+        match self.tag_name() {
+            Some("input" | "select" | "textarea" | "button") => true,
+            Some("a") => self.has_attribute("href"),
+            Some(_) => self.has_attribute("tabindex") || self.is_content_editable(),
+            None => false,
+        }
+    }
+
+    /// Whether key presses edit this element: enabled, writable text
+    /// controls and `contenteditable` elements
+    pub fn is_editable(&self) -> bool {
+        match self.form_control_type() {
+            Some(FormControlType::Text | FormControlType::Password | FormControlType::TextArea) => {
+                !self.has_attribute("disabled") && !self.has_attribute("readonly")
+            }
+            Some(_) => false,
+            None => self.is_content_editable(),
+        }
+    }
+
+    /// Whether this element is inside an editing host
+    ///
+    /// `contenteditable` is inherited; `contenteditable="false"` stops it.
+    pub fn is_content_editable(&self) -> bool {
+        match self.get_attribute("contenteditable") {
+            Some(value) => !value.eq_ignore_ascii_case("false"),
+            None => match self.parent.borrow().upgrade() {
+                Some(parent) => parent.is_content_editable(),
+                None => false,
+            },
+        }
+    }
+}
+
+/// The nodes a state flag applies to: the target, plus its ancestors if
+/// the flag propagates
+fn chain(target: Option<&Rc<Node>>, propagate: bool) -> Vec<Rc<Node>> {
+    let mut nodes = Vec::new();
+    let mut current = target.cloned();
+    while let Some(node) = current {
+        current = if propagate { node.parent.borrow().upgrade() } else { None };
+        nodes.push(node);
+    }
+    nodes
+}
+
+fn move_chain(
+    previous: Option<&Rc<Node>>,
+    target: Option<&Rc<Node>>,
+    flag: ElementState,
+    propagate: bool,
+) -> Vec<StateChange> {
+    let old_chain = chain(previous, propagate);
+    let new_chain = chain(target, propagate);
+    let mut changes = Vec::new();
+
+    for node in &old_chain {
+        if !new_chain.iter().any(|other| other.id == node.id) && node.set_element_state(flag, false) {
+            changes.push(StateChange { node: Rc::clone(node), changed: flag });
+        }
+    }
+    for node in &new_chain {
+        if node.set_element_state(flag, true) {
+            changes.push(StateChange { node: Rc::clone(node), changed: flag });
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Document;
+
+    #[test]
+    fn test_hover_chain_updates_only_changed_nodes() {
+        let document = Document::new();
+        let list = document.create_element("ul");
+        let first = document.create_element("li");
+        let second = document.create_element("li");
+        document.root.append_child(&list);
+        list.append_child(&first);
+        list.append_child(&second);
+
+        let mut state = InteractionState::new();
+        let changes = state.set_hovered(Some(&first));
+        assert_eq!(changes.len(), 3);
+        assert!(first.is_hovered() && list.is_hovered() && document.root.is_hovered());
+
+        // Moving to a sibling only flips the two list items
+        let changes = state.set_hovered(Some(&second));
+        let changed: Vec<u64> = changes.iter().map(|change| change.node.id).collect();
+        assert_eq!(changed, vec![first.id, second.id]);
+        assert!(!first.is_hovered());
+        assert!(list.is_hovered());
+
+        state.set_active(Some(&second));
+        assert!(list.element_state().contains(ElementState::HOVER | ElementState::ACTIVE));
+        state.set_active(None);
+        state.set_hovered(None);
+        assert!(list.element_state().is_empty());
+    }
+}
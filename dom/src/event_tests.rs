@@ -200,6 +200,35 @@ mod synthetic_event_tests {
         assert_eq!(event.key_code, 13);
     }
 
+    #[test]
+    fn test_keyboard_event_init_and_legacy_codes() {
+        let mut init = KeyboardEventInit::new("A", "KeyA");
+        init.shift_key = true;
+        init.location = KeyLocation::Standard;
+        let keydown = SyntheticEventFactory::create_keyboard_event("keydown", &init);
+        assert_eq!(keydown.key_code, 65);
+        assert!(keydown.get_modifier_state("Shift"));
+        assert!(keydown.produces_character());
+
+        let keypress = SyntheticEventFactory::create_keyboard_event("keypress", &init);
+        assert_eq!(keypress.char_code, 'A' as u32);
+
+        let mut shift = KeyboardEventInit::new("Shift", "ShiftRight");
+        shift.location = KeyLocation::Right;
+        let event = SyntheticEventFactory::create_keyboard_event("keydown", &shift);
+        assert_eq!(event.key_code, 16);
+        assert_eq!(event.location as u32, 2);
+        assert!(!event.produces_character());
+
+        // Shortcuts and composing keys do not produce characters
+        let mut copy = KeyboardEventInit::new("c", "KeyC");
+        copy.ctrl_key = true;
+        assert!(!SyntheticEventFactory::create_keyboard_event("keydown", &copy).produces_character());
+        let mut composing = KeyboardEventInit::new("Process", "KeyK");
+        composing.is_composing = true;
+        assert_eq!(SyntheticEventFactory::create_keyboard_event("keydown", &composing).key_code, 229);
+    }
+
     #[test]
     fn test_input_event_creation() {
         let event = SyntheticEventFactory::create_input_event(
@@ -425,8 +454,8 @@ mod performance_tests {
         
         // Dispatch 1000 events
         for _i in 0..1000 {
-            let event = Event::new("click", true, true);
-            dispatcher.dispatch_event(target.clone(), event, None);
+            let mut event = Event::new("click", true, true);
+            dispatcher.dispatch_event(target.clone(), &mut event, None);
         }
         
         let duration = start.elapsed();
@@ -57,10 +57,21 @@ pub struct Event {
     pub default_prevented: bool,
     pub propagation_stopped: bool,
     pub immediate_propagation_stopped: bool,
-    pub timestamp: u64,
+    /// Milliseconds between `time_origin()` and the creation of the event
+    pub timestamp: f64,
+    /// Set only for events the user agent fires for user input
     pub is_trusted: bool,
 }
 
+/// The instant event timestamps are measured from
+///
+/// The clock is monotonic, so timestamps never go backwards when the
+/// system clock is adjusted.
+pub fn time_origin() -> std::time::Instant {
+    static ORIGIN: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+    *ORIGIN.get_or_init(std::time::Instant::now)
+}
+
 impl Event {
     pub fn new(event_type: &str, bubbles: bool, cancelable: bool) -> Self {
         Self {
@@ -73,10 +84,7 @@ impl Event {
             default_prevented: false,
             propagation_stopped: false,
             immediate_propagation_stopped: false,
-            timestamp: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_millis() as u64,
+            timestamp: time_origin().elapsed().as_secs_f64() * 1000.0,
             is_trusted: false,
         }
     }
@@ -95,6 +103,161 @@ impl Event {
         self.propagation_stopped = true;
         self.immediate_propagation_stopped = true;
     }
+
+    /// Set the type and flags of an event, like the legacy `initEvent()`
+    ///
+    /// Clears the canceled and stop propagation flags, so one event object
+    /// can be initialized and dispatched again. Callers must not
+    /// reinitialize an event that is being dispatched.
+    pub fn init_event(&mut self, event_type: &str, bubbles: bool, cancelable: bool) {
+        self.event_type = event_type.to_string();
+        self.bubbles = bubbles;
+        self.cancelable = cancelable;
+        self.default_prevented = false;
+        self.propagation_stopped = false;
+        self.immediate_propagation_stopped = false;
+        self.is_trusted = false;
+    }
+}
+
+/// The interface of an event, which decides the attributes it has
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventInterface {
+    Event,
+    UiEvent,
+    MouseEvent,
+    PointerEvent,
+    KeyboardEvent,
+    FocusEvent,
+    InputEvent,
+    CompositionEvent,
+    TouchEvent,
+    CustomEvent,
+}
+
+impl EventInterface {
+    /// Every interface, each after the one it inherits from
+    pub const ALL: [EventInterface; 10] = [
+        EventInterface::Event,
+        EventInterface::UiEvent,
+        EventInterface::MouseEvent,
+        EventInterface::PointerEvent,
+        EventInterface::KeyboardEvent,
+        EventInterface::FocusEvent,
+        EventInterface::InputEvent,
+        EventInterface::CompositionEvent,
+        EventInterface::TouchEvent,
+        EventInterface::CustomEvent,
+    ];
+
+    /// Get the interface `document.createEvent()` creates for a name
+    ///
+    /// Names are matched ASCII case-insensitively and include the legacy
+    /// plural aliases such as `HTMLEvents` and `MouseEvents`. Returns `None`
+    /// for names the DOM does not support, for which `createEvent()` throws
+    /// a `NotSupportedError`. Newer interfaces such as `PointerEvent` can
+    /// only be constructed.
+    pub fn from_legacy_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "event" | "events" | "htmlevents" | "svgevents" => Some(EventInterface::Event),
+            "uievent" | "uievents" => Some(EventInterface::UiEvent),
+            "mouseevent" | "mouseevents" => Some(EventInterface::MouseEvent),
+            "keyboardevent" => Some(EventInterface::KeyboardEvent),
+            "focusevent" => Some(EventInterface::FocusEvent),
+            "compositionevent" | "textevent" => Some(EventInterface::CompositionEvent),
+            "touchevent" => Some(EventInterface::TouchEvent),
+            "customevent" => Some(EventInterface::CustomEvent),
+            _ => None,
+        }
+    }
+
+    /// The name of the interface, as scripts see it
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventInterface::Event => "Event",
+            EventInterface::UiEvent => "UIEvent",
+            EventInterface::MouseEvent => "MouseEvent",
+            EventInterface::PointerEvent => "PointerEvent",
+            EventInterface::KeyboardEvent => "KeyboardEvent",
+            EventInterface::FocusEvent => "FocusEvent",
+            EventInterface::InputEvent => "InputEvent",
+            EventInterface::CompositionEvent => "CompositionEvent",
+            EventInterface::TouchEvent => "TouchEvent",
+            EventInterface::CustomEvent => "CustomEvent",
+        }
+    }
+
+    /// The interface this one inherits from
+    pub fn parent(&self) -> Option<Self> {
+        match self {
+            EventInterface::Event => None,
+            EventInterface::UiEvent | EventInterface::CustomEvent => Some(EventInterface::Event),
+            EventInterface::PointerEvent => Some(EventInterface::MouseEvent),
+            _ => Some(EventInterface::UiEvent),
+        }
+    }
+}
+
+/// An event of any interface
+#[derive(Clone)]
+pub enum AnyEvent {
+    /// An `Event` or a `UIEvent`, which adds no fields
+    Event(Event),
+    Mouse(MouseEvent),
+    Pointer(PointerEvent),
+    Keyboard(KeyboardEvent),
+    Focus(FocusEvent),
+    Input(InputEvent),
+    Composition(CompositionEvent),
+    Touch(TouchEvent),
+    Custom(CustomEvent),
+}
+
+impl AnyEvent {
+    /// Create an event of an interface with default attributes
+    pub fn new(interface: EventInterface, event_type: &str, bubbles: bool, cancelable: bool) -> Self {
+        match interface {
+            EventInterface::Event | EventInterface::UiEvent => AnyEvent::Event(Event::new(event_type, bubbles, cancelable)),
+            EventInterface::MouseEvent => AnyEvent::Mouse(MouseEvent::new(event_type, bubbles, cancelable)),
+            EventInterface::PointerEvent => AnyEvent::Pointer(PointerEvent::new(event_type, bubbles, cancelable)),
+            EventInterface::KeyboardEvent => AnyEvent::Keyboard(KeyboardEvent::new(event_type, bubbles, cancelable)),
+            EventInterface::FocusEvent => AnyEvent::Focus(FocusEvent::new(event_type, bubbles, cancelable)),
+            EventInterface::InputEvent => AnyEvent::Input(InputEvent::new(event_type, bubbles, cancelable)),
+            EventInterface::CompositionEvent => AnyEvent::Composition(CompositionEvent::new(event_type, bubbles, cancelable)),
+            EventInterface::TouchEvent => AnyEvent::Touch(TouchEvent::new(event_type, bubbles, cancelable)),
+            EventInterface::CustomEvent => AnyEvent::Custom(CustomEvent::new(event_type, bubbles, cancelable)),
+        }
+    }
+
+    /// The fields every event has
+    pub fn base(&self) -> &Event {
+        match self {
+            AnyEvent::Event(event) => event,
+            AnyEvent::Mouse(event) => &event.base,
+            AnyEvent::Pointer(event) => &event.mouse.base,
+            AnyEvent::Keyboard(event) => &event.base,
+            AnyEvent::Focus(event) => &event.base,
+            AnyEvent::Input(event) => &event.base,
+            AnyEvent::Composition(event) => &event.base,
+            AnyEvent::Touch(event) => &event.base,
+            AnyEvent::Custom(event) => &event.base,
+        }
+    }
+
+    /// The fields every event has, mutably
+    pub fn base_mut(&mut self) -> &mut Event {
+        match self {
+            AnyEvent::Event(event) => event,
+            AnyEvent::Mouse(event) => &mut event.base,
+            AnyEvent::Pointer(event) => &mut event.mouse.base,
+            AnyEvent::Keyboard(event) => &mut event.base,
+            AnyEvent::Focus(event) => &mut event.base,
+            AnyEvent::Input(event) => &mut event.base,
+            AnyEvent::Composition(event) => &mut event.base,
+            AnyEvent::Touch(event) => &mut event.base,
+            AnyEvent::Custom(event) => &mut event.base,
+        }
+    }
 }
 
 /// Mouse event
@@ -131,12 +294,56 @@ impl MouseEvent {
     }
 }
 
+/// Pointer event, a mouse event that also describes the pointer
+///
+/// The mouse fields hold the position, buttons and modifiers; a pen or a
+/// touch contact sets them the way a mouse would.
+#[derive(Clone)]
+pub struct PointerEvent {
+    pub mouse: MouseEvent,
+    pub pointer_id: i32,
+    /// `"mouse"`, `"pen"` or `"touch"`, or empty when unknown
+    pub pointer_type: String,
+    /// Normalized pressure, from 0 to 1
+    pub pressure: f32,
+    /// Size of the contact geometry in CSS pixels
+    pub width: f64,
+    pub height: f64,
+    /// Whether this is the primary pointer of its type
+    pub is_primary: bool,
+}
+
+impl PointerEvent {
+    pub fn new(event_type: &str, bubbles: bool, cancelable: bool) -> Self {
+        Self {
+            mouse: MouseEvent::new(event_type, bubbles, cancelable),
+            pointer_id: 0,
+            pointer_type: String::new(),
+            pressure: 0.0,
+            width: 1.0,
+            height: 1.0,
+            is_primary: false,
+        }
+    }
+}
+
+/// Location of a key on the keyboard (`KeyboardEvent.location`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyLocation {
+    #[default]
+    Standard = 0,
+    Left = 1,
+    Right = 2,
+    Numpad = 3,
+}
+
 /// Keyboard event
 #[derive(Clone)]
 pub struct KeyboardEvent {
     pub base: Event,
     pub key: String,
     pub code: String,
+    pub location: KeyLocation,
     pub key_code: u32,
     pub char_code: u32,
     pub ctrl_key: bool,
@@ -144,6 +351,7 @@ pub struct KeyboardEvent {
     pub alt_key: bool,
     pub meta_key: bool,
     pub repeat: bool,
+    pub is_composing: bool,
 }
 
 impl KeyboardEvent {
@@ -152,6 +360,7 @@ impl KeyboardEvent {
             base: Event::new(event_type, bubbles, cancelable),
             key: String::new(),
             code: String::new(),
+            location: KeyLocation::Standard,
             key_code: 0,
             char_code: 0,
             ctrl_key: false,
@@ -159,6 +368,107 @@ impl KeyboardEvent {
             alt_key: false,
             meta_key: false,
             repeat: false,
+            is_composing: false,
+        }
+    }
+
+    /// Query a modifier by its key value, like `getModifierState()`
+    pub fn get_modifier_state(&self, key: &str) -> bool {
+        match key {
+            "Control" => self.ctrl_key,
+            "Shift" => self.shift_key,
+            "Alt" => self.alt_key,
+            "Meta" => self.meta_key,
+            _ => false,
+        }
+    }
+
+    /// Whether this key produces a character, i.e. whether `keydown` is
+    /// followed by `keypress`
+    ///
+    /// Shortcuts (Control or Meta held) and keys pressed during an IME
+    /// composition never do.
+    pub fn produces_character(&self) -> bool {
+        if self.ctrl_key || self.meta_key || self.is_composing {
+            return false;
+        }
+        self.key == "Enter" || self.key.chars().count() == 1
+    }
+}
+
+/// Composition event (`compositionstart`, `compositionupdate`, `compositionend`)
+#[derive(Clone)]
+pub struct CompositionEvent {
+    pub base: Event,
+    pub data: String,
+}
+
+impl CompositionEvent {
+    pub fn new(event_type: &str, bubbles: bool, cancelable: bool) -> Self {
+        Self {
+            base: Event::new(event_type, bubbles, cancelable),
+            data: String::new(),
+        }
+    }
+}
+
+/// A point of contact with a touch surface
+#[derive(Clone)]
+pub struct Touch {
+    pub identifier: i32,
+    /// The element the contact started on, which keeps receiving its
+    /// events wherever it moves
+    pub target: Option<Rc<crate::Node>>,
+    pub client_x: f64,
+    pub client_y: f64,
+    /// Radii of the contact ellipse in CSS pixels
+    pub radius_x: f64,
+    pub radius_y: f64,
+    /// Normalized pressure, from 0 to 1
+    pub force: f64,
+}
+
+impl Touch {
+    pub fn new(identifier: i32, target: Option<Rc<crate::Node>>, client_x: f64, client_y: f64) -> Self {
+        Self {
+            identifier,
+            target,
+            client_x,
+            client_y,
+            radius_x: 1.0,
+            radius_y: 1.0,
+            force: 0.0,
+        }
+    }
+}
+
+/// Touch event (`touchstart`, `touchmove`, `touchend`, `touchcancel`)
+#[derive(Clone)]
+pub struct TouchEvent {
+    pub base: Event,
+    /// Every contact on the surface
+    pub touches: Vec<Touch>,
+    /// The contacts that started on the target of the event
+    pub target_touches: Vec<Touch>,
+    /// The contacts this event is about
+    pub changed_touches: Vec<Touch>,
+    pub ctrl_key: bool,
+    pub shift_key: bool,
+    pub alt_key: bool,
+    pub meta_key: bool,
+}
+
+impl TouchEvent {
+    pub fn new(event_type: &str, bubbles: bool, cancelable: bool) -> Self {
+        Self {
+            base: Event::new(event_type, bubbles, cancelable),
+            touches: Vec::new(),
+            target_touches: Vec::new(),
+            changed_touches: Vec::new(),
+            ctrl_key: false,
+            shift_key: false,
+            alt_key: false,
+            meta_key: false,
         }
     }
 }
@@ -183,6 +493,23 @@ impl InputEvent {
     }
 }
 
+/// Clipboard event (`copy`, `cut`, `paste`)
+#[derive(Clone)]
+pub struct ClipboardEvent {
+    pub base: Event,
+    /// The `text/plain` entry of `clipboardData`
+    pub clipboard_data: Option<String>,
+}
+
+impl ClipboardEvent {
+    pub fn new(event_type: &str, bubbles: bool, cancelable: bool) -> Self {
+        Self {
+            base: Event::new(event_type, bubbles, cancelable),
+            clipboard_data: None,
+        }
+    }
+}
+
 /// Focus event
 #[derive(Clone)]
 pub struct FocusEvent {
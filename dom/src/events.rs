@@ -23,10 +23,14 @@ impl EventDispatcher {
     }
 
     /// Dispatch an event through the DOM tree with proper propagation
+    ///
+    /// The event is borrowed so the caller can read its canceled flag and
+    /// decide whether to run the default action. Returns whether the event
+    /// was not canceled.
     pub fn dispatch_event(
         &mut self,
         target: Rc<RefCell<dyn EventTarget>>,
-        mut event: Event,
+        event: &mut Event,
         document_root: Option<Rc<RefCell<Node>>>,
     ) -> bool {
         let start_time = std::time::Instant::now();
@@ -46,7 +50,7 @@ impl EventDispatcher {
                 if i == 0 { break; } // Skip target in capturing phase
                 event.current_target = Some(node.clone());
                 
-                if self.execute_listeners(node, &mut event) {
+                if self.execute_listeners(node, event) {
                     break; // stop_immediate_propagation called
                 }
             }
@@ -56,7 +60,7 @@ impl EventDispatcher {
         if !event.propagation_stopped {
             event.phase = EventPhase::AtTarget;
             event.current_target = Some(target.clone());
-            self.execute_listeners(&target, &mut event);
+            self.execute_listeners(&target, event);
         }
 
         // Phase 3: Bubbling phase (target → root)
@@ -65,7 +69,7 @@ impl EventDispatcher {
             for node in event_path.iter().skip(1) {
                 event.current_target = Some(node.clone());
                 
-                if self.execute_listeners(node, &mut event) {
+                if self.execute_listeners(node, event) {
                     break; // stop_immediate_propagation called
                 }
             }
@@ -76,6 +80,8 @@ impl EventDispatcher {
         self.total_dispatch_time += dispatch_time;
         self.max_dispatch_time = self.max_dispatch_time.max(dispatch_time);
 
+        event.phase = EventPhase::None;
+        event.current_target = None;
         !event.default_prevented
     }
 
@@ -250,6 +256,100 @@ impl EventDelegationManager {
     }
 }
 
+/// Dictionary describing a key event, modelled on `KeyboardEventInit`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyboardEventInit {
+    /// The key value (`"a"`, `"A"`, `"Enter"`, `"ArrowLeft"`, ...)
+    pub key: String,
+    /// The physical key (`"KeyA"`, `"Enter"`, `"ShiftLeft"`, ...)
+    pub code: String,
+    pub location: KeyLocation,
+    pub repeat: bool,
+    pub ctrl_key: bool,
+    pub shift_key: bool,
+    pub alt_key: bool,
+    pub meta_key: bool,
+    pub is_composing: bool,
+}
+
+impl KeyboardEventInit {
+    /// Describe a key with no modifiers held
+    pub fn new(key: &str, code: &str) -> Self {
+        Self {
+            key: key.to_string(),
+            code: code.to_string(),
+            ..Self::default()
+        }
+    }
+}
+
+/// Legacy `keyCode` for a key, derived from its physical code like
+/// browsers do on a US layout
+pub fn legacy_key_code(key: &str, code: &str) -> u32 {
+    if key == "Process" {
+        return 229;
+    }
+    if let Some(letter) = code.strip_prefix("Key") {
+        if let [byte] = letter.as_bytes() {
+            if byte.is_ascii_uppercase() {
+                return *byte as u32;
+            }
+        }
+    }
+    if let Some(digit) = code.strip_prefix("Digit").and_then(|d| d.parse::<u32>().ok()) {
+        return 48 + digit;
+    }
+    if let Some(digit) = code.strip_prefix("Numpad").and_then(|d| d.parse::<u32>().ok()) {
+        return 96 + digit;
+    }
+    if let Some(n) = code.strip_prefix('F').and_then(|n| n.parse::<u32>().ok()) {
+        if (1..=24).contains(&n) {
+            return 111 + n;
+        }
+    }
+    match code {
+        "Backspace" => 8,
+        "Tab" => 9,
+        "Enter" | "NumpadEnter" => 13,
+        "ShiftLeft" | "ShiftRight" => 16,
+        "ControlLeft" | "ControlRight" => 17,
+        "AltLeft" | "AltRight" => 18,
+        "Pause" => 19,
+        "CapsLock" => 20,
+        "Escape" => 27,
+        "Space" => 32,
+        "PageUp" => 33,
+        "PageDown" => 34,
+        "End" => 35,
+        "Home" => 36,
+        "ArrowLeft" => 37,
+        "ArrowUp" => 38,
+        "ArrowRight" => 39,
+        "ArrowDown" => 40,
+        "Insert" => 45,
+        "Delete" => 46,
+        "MetaLeft" => 91,
+        "MetaRight" => 92,
+        "NumpadMultiply" => 106,
+        "NumpadAdd" => 107,
+        "NumpadSubtract" => 109,
+        "NumpadDecimal" => 110,
+        "NumpadDivide" => 111,
+        "Semicolon" => 186,
+        "Equal" => 187,
+        "Comma" => 188,
+        "Minus" => 189,
+        "Period" => 190,
+        "Slash" => 191,
+        "Backquote" => 192,
+        "BracketLeft" => 219,
+        "Backslash" => 220,
+        "BracketRight" => 221,
+        "Quote" => 222,
+        _ => 0,
+    }
+}
+
 /// Synthetic event factory
 pub struct SyntheticEventFactory;
 
@@ -264,6 +364,26 @@ impl SyntheticEventFactory {
         event
     }
 
+    /// Create a pointer event for the primary pointer of a type
+    ///
+    /// The pressure is 0.5 while a button is pressed and 0 otherwise, as
+    /// for hardware that does not report pressure. `pointerenter` and
+    /// `pointerleave` do not bubble, and only the events that have a
+    /// default action to prevent are cancelable.
+    pub fn create_pointer_event(event_type: &str, pointer_id: i32, pointer_type: &str, x: f64, y: f64, buttons: u32) -> PointerEvent {
+        let bubbles = !matches!(event_type, "pointerenter" | "pointerleave");
+        let cancelable = matches!(event_type, "pointerdown" | "pointermove" | "pointerup" | "pointerover" | "pointerout");
+        let mut event = PointerEvent::new(event_type, bubbles, cancelable);
+        event.mouse.client_x = x;
+        event.mouse.client_y = y;
+        event.mouse.buttons = buttons;
+        event.pointer_id = pointer_id;
+        event.pointer_type = pointer_type.to_string();
+        event.pressure = if buttons != 0 { 0.5 } else { 0.0 };
+        event.is_primary = true;
+        event
+    }
+
     /// Create a synthetic mouseover event
     pub fn create_mouseover_event(x: f64, y: f64) -> MouseEvent {
         let mut event = MouseEvent::new("mouseover", true, true);
@@ -316,6 +436,62 @@ impl SyntheticEventFactory {
         event
     }
 
+    /// Create a `keydown`, `keyup` or `keypress` event from an init dictionary
+    ///
+    /// Fills in the legacy `keyCode`/`charCode` pair: `keypress` carries the
+    /// character code in both, and keys pressed during composition report
+    /// `keyCode` 229.
+    pub fn create_keyboard_event(event_type: &str, init: &KeyboardEventInit) -> KeyboardEvent {
+        let mut event = KeyboardEvent::new(event_type, true, true);
+        event.key = init.key.clone();
+        event.code = init.code.clone();
+        event.location = init.location;
+        event.repeat = init.repeat;
+        event.ctrl_key = init.ctrl_key;
+        event.shift_key = init.shift_key;
+        event.alt_key = init.alt_key;
+        event.meta_key = init.meta_key;
+        event.is_composing = init.is_composing;
+
+        if event_type == "keypress" {
+            let char_code = match init.key.as_str() {
+                "Enter" => 13,
+                key => key.chars().next().map_or(0, |c| c as u32),
+            };
+            event.key_code = char_code;
+            event.char_code = char_code;
+        } else if init.is_composing {
+            event.key_code = 229;
+        } else {
+            event.key_code = legacy_key_code(&init.key, &init.code);
+        }
+        event
+    }
+
+    /// Create a cancelable `beforeinput` event
+    pub fn create_beforeinput_event(data: Option<String>, input_type: &str, is_composing: bool) -> InputEvent {
+        let mut event = InputEvent::new("beforeinput", true, true);
+        event.data = data;
+        event.input_type = input_type.to_string();
+        event.is_composing = is_composing;
+        event
+    }
+
+    /// Create a composition event
+    pub fn create_composition_event(event_type: &str, data: &str) -> CompositionEvent {
+        // Only compositionstart is cancelable
+        let mut event = CompositionEvent::new(event_type, true, event_type == "compositionstart");
+        event.data = data.to_string();
+        event
+    }
+
+    /// Create a clipboard event carrying `text/plain` data
+    pub fn create_clipboard_event(event_type: &str, data: Option<String>) -> ClipboardEvent {
+        let mut event = ClipboardEvent::new(event_type, true, true);
+        event.clipboard_data = data;
+        event
+    }
+
     /// Create a custom event
     pub fn create_custom_event(event_type: &str, detail: Option<String>) -> CustomEvent {
         let mut event = CustomEvent::new(event_type, true, true);
@@ -0,0 +1,531 @@
+//! # Form Controls
+//!
+//! This module tracks the state of form controls (`<input>`, `<select>`,
+//! `<textarea>`, `<button>`) and builds form submissions.
+//!
+//! ## Design Principles
+//!
+//! 1. **Attributes vs State**: Controls start from their content attributes
+//!    (`value`, `checked`, `selected`) and switch to their own dirty state once
+//!    modified, just like the `value`/`checked` IDL attributes in browsers.
+//!
+//! 2. **Encoding Elsewhere**: This module only produces the form data set;
+//!    URL encoding and the actual request live in the networking crate.
+
+use std::rc::Rc;
+use crate::Node;
+use crate::bidi::{BidiParagraph, Direction};
+
+/// The kind of form control a node represents
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FormControlType {
+    Text,
+    Password,
+    Checkbox,
+    Radio,
+    Submit,
+    Reset,
+    Button,
+    Hidden,
+    Select,
+    TextArea,
+}
+
+impl FormControlType {
+    /// Determine the control type of a node, if it is a form control
+    pub fn of(node: &Node) -> Option<Self> {
+        match node.tag_name()? {
+            "input" => {
+                let input_type = node.get_attribute("type").unwrap_or_default().to_ascii_lowercase();
+                Some(match input_type.as_str() {
+                    "password" => FormControlType::Password,
+                    "checkbox" => FormControlType::Checkbox,
+                    "radio" => FormControlType::Radio,
+                    "submit" => FormControlType::Submit,
+                    "reset" => FormControlType::Reset,
+                    "button" => FormControlType::Button,
+                    "hidden" => FormControlType::Hidden,
+                    // Unknown types fall back to a text field
+                    _ => FormControlType::Text,
+                })
+            }
+            "button" => {
+                let button_type = node.get_attribute("type").unwrap_or_default().to_ascii_lowercase();
+                Some(match button_type.as_str() {
+                    "reset" => FormControlType::Reset,
+                    "button" => FormControlType::Button,
+                    _ => FormControlType::Submit,
+                })
+            }
+            "select" => Some(FormControlType::Select),
+            "textarea" => Some(FormControlType::TextArea),
+            _ => None,
+        }
+    }
+
+    /// Whether this control is a checkbox or radio button
+    pub fn is_checkable(&self) -> bool {
+        matches!(self, FormControlType::Checkbox | FormControlType::Radio)
+    }
+
+    /// Whether activating this control submits its form
+    pub fn is_submit_button(&self) -> bool {
+        matches!(self, FormControlType::Submit)
+    }
+
+    /// Whether pressing Enter in this control triggers implicit submission
+    pub fn is_text_entry(&self) -> bool {
+        matches!(self, FormControlType::Text | FormControlType::Password)
+    }
+}
+
+/// Runtime state of a form control
+///
+/// `None` means the control has not been modified and still reflects its
+/// content attributes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FormControlState {
+    pub value: Option<String>,
+    pub checked: Option<bool>,
+    pub selected_index: Option<i32>,
+    /// Selection anchor and focus as character offsets into the value;
+    /// `None` puts the caret at the end of the value
+    pub selection: Option<(usize, usize)>,
+}
+
+impl Node {
+    /// Get the form control type of this node
+    pub fn form_control_type(&self) -> Option<FormControlType> {
+        FormControlType::of(self)
+    }
+
+    /// Get the current value of a form control
+    pub fn value(&self) -> String {
+        let control_type = match self.form_control_type() {
+            Some(control_type) => control_type,
+            None => return String::new(),
+        };
+
+        match control_type {
+            FormControlType::Select => {
+                let index = self.selected_index();
+                if index < 0 {
+                    return String::new();
+                }
+                self.options()
+                    .get(index as usize)
+                    .map(|option| option_value(option))
+                    .unwrap_or_default()
+            }
+            _ => {
+                if let Some(value) = &self.form_state.borrow().value {
+                    return value.clone();
+                }
+                match control_type {
+                    FormControlType::TextArea => self.text_content(),
+                    FormControlType::Checkbox | FormControlType::Radio => {
+                        self.get_attribute("value").unwrap_or_else(|| "on".to_string())
+                    }
+                    _ => self.get_attribute("value").unwrap_or_default(),
+                }
+            }
+        }
+    }
+
+    /// Set the value of a form control
+    ///
+    /// For `<select>` this selects the first option with a matching value.
+    pub fn set_value(&self, value: &str) {
+        match self.form_control_type() {
+            Some(FormControlType::Select) => {
+                let index = self
+                    .options()
+                    .iter()
+                    .position(|option| option_value(option) == value)
+                    .map(|index| index as i32)
+                    .unwrap_or(-1);
+                self.form_state.borrow_mut().selected_index = Some(index);
+            }
+            Some(_) => {
+                let mut state = self.form_state.borrow_mut();
+                state.value = Some(value.to_string());
+                state.selection = None;
+            }
+            None => {}
+        }
+    }
+
+    /// Get the selected range of a text control as ordered character
+    /// offsets; a collapsed range is the caret
+    pub fn selection_range(&self) -> (usize, usize) {
+        let len = self.value().chars().count();
+        match self.form_state.borrow().selection {
+            Some((anchor, focus)) => (anchor.min(focus).min(len), anchor.max(focus).min(len)),
+            None => (len, len),
+        }
+    }
+
+    /// Select a range of a text control, `start` being the anchor
+    pub fn set_selection_range(&self, start: usize, end: usize) {
+        let len = self.value().chars().count();
+        self.form_state.borrow_mut().selection = Some((start.min(len), end.min(len)));
+    }
+
+    /// Move the caret of a text control one character left or right on
+    /// screen, extending the selection if `extend` is set
+    ///
+    /// Movement follows the visual order of the value laid out in the
+    /// control's directionality, so it runs through right-to-left text the
+    /// way the text is displayed. Without `extend`, a selection collapses
+    /// to its start or end, whichever lies in the direction of movement.
+    pub fn move_caret(&self, rightward: bool, extend: bool) {
+        let value = self.value();
+        let len = value.chars().count();
+        let (anchor, focus) = self.form_state.borrow().selection.unwrap_or((len, len));
+        let direction = self.directionality();
+        let paragraph = BidiParagraph::new(&value, Some(direction));
+        let selection = if extend {
+            (anchor, paragraph.move_caret(focus, rightward))
+        } else if anchor != focus {
+            let forward = rightward == (direction == Direction::Ltr);
+            let caret = if forward { anchor.max(focus) } else { anchor.min(focus) };
+            (caret, caret)
+        } else {
+            let caret = paragraph.move_caret(focus, rightward);
+            (caret, caret)
+        };
+        self.form_state.borrow_mut().selection = Some(selection);
+    }
+
+    /// Get the checkedness of a checkbox or radio button
+    pub fn checked(&self) -> bool {
+        match self.form_state.borrow().checked {
+            Some(checked) => checked,
+            None => self.has_attribute("checked"),
+        }
+    }
+
+    /// Set the checkedness of a checkbox or radio button
+    ///
+    /// Checking a radio button unchecks the other radio buttons in its group
+    /// (same `name` within the same form owner).
+    pub fn set_checked(self: &Rc<Self>, checked: bool) {
+        let control_type = match self.form_control_type() {
+            Some(control_type) if control_type.is_checkable() => control_type,
+            _ => return,
+        };
+
+        if checked && control_type == FormControlType::Radio {
+            for other in self.radio_group() {
+                other.form_state.borrow_mut().checked = Some(false);
+            }
+        }
+
+        self.form_state.borrow_mut().checked = Some(checked);
+    }
+
+    /// Get the other radio buttons in the group of a radio button
+    ///
+    /// The group is the radio buttons with the same non-empty name in the
+    /// same form, or in the same tree when there is no form owner.
+    pub fn radio_group(self: &Rc<Self>) -> Vec<Rc<Node>> {
+        if self.form_control_type() != Some(FormControlType::Radio) {
+            return Vec::new();
+        }
+        let Some(name) = self.get_attribute("name").filter(|name| !name.is_empty()) else {
+            return Vec::new();
+        };
+        let scope = self.form_owner().unwrap_or_else(|| tree_root(self));
+        descendants(&scope)
+            .into_iter()
+            .filter(|other| {
+                !Rc::ptr_eq(other, self)
+                    && other.form_control_type() == Some(FormControlType::Radio)
+                    && other.get_attribute("name").as_deref() == Some(name.as_str())
+            })
+            .collect()
+    }
+
+    /// Get the `<option>` elements of a `<select>`
+    pub fn options(&self) -> Vec<Rc<Node>> {
+        let mut options = Vec::new();
+        for child in self.children.borrow().iter() {
+            collect_options(child, &mut options);
+        }
+        options
+    }
+
+    /// Get the index of the selected option, or -1 if there is none
+    pub fn selected_index(&self) -> i32 {
+        if let Some(index) = self.form_state.borrow().selected_index {
+            return index;
+        }
+        let options = self.options();
+        if options.is_empty() {
+            return -1;
+        }
+        options
+            .iter()
+            .position(|option| option.has_attribute("selected"))
+            .unwrap_or(0) as i32
+    }
+
+    /// Select the option at the given index (-1 clears the selection)
+    pub fn set_selected_index(&self, index: i32) {
+        let count = self.options().len() as i32;
+        let index = if index >= 0 && index < count { index } else { -1 };
+        self.form_state.borrow_mut().selected_index = Some(index);
+    }
+
+    /// Get the snapshot of this control's runtime state
+    pub fn form_state(&self) -> FormControlState {
+        self.form_state.borrow().clone()
+    }
+
+    /// Replace this control's runtime state, e.g. when restoring a session
+    ///
+    /// Unlike `set_checked`, this does not touch other radio buttons; the
+    /// state is expected to come from a snapshot of the whole document.
+    pub fn set_form_state(&self, state: FormControlState) {
+        *self.form_state.borrow_mut() = state;
+    }
+
+    /// Find the `<form>` element that owns this control
+    pub fn form_owner(self: &Rc<Self>) -> Option<Rc<Node>> {
+        let mut current = self.parent.borrow().upgrade();
+        while let Some(node) = current {
+            if node.tag_name() == Some("form") {
+                return Some(node);
+            }
+            current = node.parent.borrow().upgrade();
+        }
+        None
+    }
+}
+
+/// HTTP method used to submit a form
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FormMethod {
+    Get,
+    Post,
+}
+
+/// A form submission ready to be sent by the networking layer
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormSubmission {
+    /// The `action` attribute, unresolved (empty means the document URL)
+    pub action: String,
+    pub method: FormMethod,
+    /// The form data set in tree order
+    pub entries: Vec<(String, String)>,
+}
+
+/// Build the submission for a form
+///
+/// Only the submit button that triggered the submission contributes its
+/// own name/value pair.
+pub fn build_form_submission(form: &Rc<Node>, submitter: Option<&Rc<Node>>) -> FormSubmission {
+    let method = match form.get_attribute("method") {
+        Some(method) if method.eq_ignore_ascii_case("post") => FormMethod::Post,
+        _ => FormMethod::Get,
+    };
+
+    FormSubmission {
+        action: form.get_attribute("action").unwrap_or_default(),
+        method,
+        entries: construct_form_data_set(form, submitter),
+    }
+}
+
+/// Construct the form data set of a form
+pub fn construct_form_data_set(form: &Rc<Node>, submitter: Option<&Rc<Node>>) -> Vec<(String, String)> {
+    let mut entries = Vec::new();
+
+    for control in descendants(form) {
+        let control_type = match control.form_control_type() {
+            Some(control_type) => control_type,
+            None => continue,
+        };
+        let name = match control.get_attribute("name") {
+            Some(name) if !name.is_empty() => name,
+            _ => continue,
+        };
+        if control.has_attribute("disabled") {
+            continue;
+        }
+
+        match control_type {
+            FormControlType::Checkbox | FormControlType::Radio => {
+                if control.checked() {
+                    entries.push((name, control.value()));
+                }
+            }
+            FormControlType::Submit => {
+                if submitter.is_some_and(|submitter| Rc::ptr_eq(submitter, &control)) {
+                    entries.push((name, control.value()));
+                }
+            }
+            FormControlType::Reset | FormControlType::Button => {}
+            FormControlType::Select => {
+                if control.selected_index() >= 0 {
+                    entries.push((name, control.value()));
+                }
+            }
+            _ => entries.push((name, control.value())),
+        }
+    }
+
+    entries
+}
+
+/// All form controls under a node, in tree order
+pub fn form_controls(root: &Rc<Node>) -> Vec<Rc<Node>> {
+    descendants(root)
+        .into_iter()
+        .filter(|node| node.form_control_type().is_some())
+        .collect()
+}
+
+/// Find the default button of a form (its first submit button)
+pub fn default_button(form: &Rc<Node>) -> Option<Rc<Node>> {
+    descendants(form)
+        .into_iter()
+        .find(|node| node.form_control_type().is_some_and(|t| t.is_submit_button()))
+}
+
+/// The value of an `<option>`: its `value` attribute or its text
+fn option_value(option: &Node) -> String {
+    option
+        .get_attribute("value")
+        .unwrap_or_else(|| option.text_content().trim().to_string())
+}
+
+fn collect_options(node: &Rc<Node>, options: &mut Vec<Rc<Node>>) {
+    match node.tag_name() {
+        Some("option") => options.push(Rc::clone(node)),
+        Some("optgroup") => {
+            for child in node.children.borrow().iter() {
+                collect_options(child, options);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Get the root of the tree `node` is in: its document, if it is connected
+pub(crate) fn tree_root(node: &Rc<Node>) -> Rc<Node> {
+    let mut current = Rc::clone(node);
+    loop {
+        let parent = current.parent.borrow().upgrade();
+        match parent {
+            Some(parent) => current = parent,
+            None => return current,
+        }
+    }
+}
+
+/// All descendants of a node in tree order
+fn descendants(node: &Rc<Node>) -> Vec<Rc<Node>> {
+    let mut result = Vec::new();
+    for child in node.children.borrow().iter() {
+        result.push(Rc::clone(child));
+        result.extend(descendants(child));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Document, NodeType};
+    use std::collections::HashMap;
+
+    fn element(doc: &Document, tag: &str, attrs: &[(&str, &str)]) -> Rc<Node> {
+        let attributes: HashMap<String, String> = attrs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        doc.create_node(NodeType::Element {
+            tag_name: tag.to_string(),
+            attributes,
+        })
+    }
+
+    #[test]
+    fn test_text_input_value() {
+        let doc = Document::new();
+        let input = element(&doc, "input", &[("type", "text"), ("value", "hello")]);
+        assert_eq!(input.form_control_type(), Some(FormControlType::Text));
+        assert_eq!(input.value(), "hello");
+
+        input.set_value("world");
+        assert_eq!(input.value(), "world");
+        assert_eq!(input.get_attribute("value"), Some("hello".to_string()));
+    }
+
+    #[test]
+    fn test_radio_group() {
+        let doc = Document::new();
+        let form = element(&doc, "form", &[]);
+        let a = element(&doc, "input", &[("type", "radio"), ("name", "size"), ("value", "s"), ("checked", "")]);
+        let b = element(&doc, "input", &[("type", "radio"), ("name", "size"), ("value", "m")]);
+        form.append_child(&a);
+        form.append_child(&b);
+
+        assert!(a.checked());
+        b.set_checked(true);
+        assert!(!a.checked());
+        assert!(b.checked());
+    }
+
+    #[test]
+    fn test_select_state() {
+        let doc = Document::new();
+        let select = element(&doc, "select", &[("name", "color")]);
+        let red = element(&doc, "option", &[("value", "red")]);
+        let blue = element(&doc, "option", &[]);
+        blue.append_child(&doc.create_text_node(" blue "));
+        select.append_child(&red);
+        select.append_child(&blue);
+
+        assert_eq!(select.selected_index(), 0);
+        assert_eq!(select.value(), "red");
+
+        select.set_value("blue");
+        assert_eq!(select.selected_index(), 1);
+        assert_eq!(select.value(), "blue");
+
+        select.set_selected_index(5);
+        assert_eq!(select.selected_index(), -1);
+        assert_eq!(select.value(), "");
+    }
+
+    #[test]
+    fn test_form_data_set() {
+        let doc = Document::new();
+        let form = element(&doc, "form", &[("action", "/search"), ("method", "POST")]);
+        let query = element(&doc, "input", &[("name", "q"), ("value", "rust")]);
+        let unchecked = element(&doc, "input", &[("type", "checkbox"), ("name", "safe")]);
+        let checked = element(&doc, "input", &[("type", "checkbox"), ("name", "lang"), ("checked", "")]);
+        let disabled = element(&doc, "input", &[("name", "skip"), ("disabled", "")]);
+        let go = element(&doc, "button", &[("name", "go"), ("value", "1")]);
+        let other = element(&doc, "input", &[("type", "submit"), ("name", "other")]);
+        for control in [&query, &unchecked, &checked, &disabled, &go, &other] {
+            form.append_child(control);
+        }
+
+        assert!(Rc::ptr_eq(&default_button(&form).unwrap(), &go));
+
+        let submission = build_form_submission(&form, Some(&go));
+        assert_eq!(submission.method, FormMethod::Post);
+        assert_eq!(submission.action, "/search");
+        assert_eq!(
+            submission.entries,
+            vec![
+                ("q".to_string(), "rust".to_string()),
+                ("lang".to_string(), "on".to_string()),
+                ("go".to_string(), "1".to_string()),
+            ]
+        );
+    }
+}
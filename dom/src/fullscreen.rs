@@ -0,0 +1,166 @@
+//! # Fullscreen
+//!
+//! This module implements the Fullscreen API: `requestFullscreen()` and
+//! `exitFullscreen()`. A fullscreen element joins the top layer, matches
+//! `:fullscreen` and fills the viewport, and the shell puts the window in
+//! fullscreen mode while its document has one.
+//!
+//! ## Design Principles
+//!
+//! 1. **A Stack in the Top Layer**: Fullscreen is a state flag on elements
+//!    in the top layer. The topmost flagged element is the document's
+//!    fullscreen element; exiting unflags it and uncovers the one below.
+//!
+//! 2. **The Document Drives the Window**: The shell reads the fullscreen
+//!    element to decide the window's mode rather than being told, so a
+//!    change from a script, a key press or the removal of the element all
+//!    reach the window the same way.
+//!
+//! 3. **Callers Fire Events**: Operations return the elements whose state
+//!    changed, and the caller fires `fullscreenchange` at each, through the
+//!    DOM event manager for the user agent or through the script bindings
+//!    for scripts.
+
+use std::fmt;
+use std::rc::Rc;
+use crate::element_state::ElementState;
+use crate::{top_layer, Node, NodeType};
+
+/// Why fullscreen cannot be entered or exited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FullscreenError {
+    /// The element is not in a document
+    NotConnected,
+    /// The element is not an element, or is a modal dialog
+    NotAllowed,
+    /// `exitFullscreen()` while no element is fullscreen
+    NotFullscreen,
+}
+
+impl FullscreenError {
+    /// Get the name of the error the promise is rejected with
+    pub fn name(&self) -> &'static str {
+        "TypeError"
+    }
+}
+
+impl fmt::Display for FullscreenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FullscreenError::NotConnected => write!(f, "the element is not connected"),
+            FullscreenError::NotAllowed => write!(f, "the element cannot be shown fullscreen"),
+            FullscreenError::NotFullscreen => write!(f, "the document is not in fullscreen"),
+        }
+    }
+}
+
+impl std::error::Error for FullscreenError {}
+
+impl Node {
+    /// Check whether this element is fullscreen, possibly under another one
+    pub fn is_fullscreen(&self) -> bool {
+        self.element_state().contains(ElementState::FULLSCREEN)
+    }
+
+    /// Make this element the fullscreen element, like `requestFullscreen()`
+    ///
+    /// Requesting fullscreen for the fullscreen element does nothing and
+    /// returns `Ok(false)`; otherwise returns `Ok(true)` and the caller
+    /// fires `fullscreenchange` at the element.
+    pub fn request_fullscreen(self: &Rc<Self>) -> Result<bool, FullscreenError> {
+        if !matches!(self.node_type, NodeType::Element { .. }) || self.is_modal() {
+            return Err(FullscreenError::NotAllowed);
+        }
+        if !self.is_connected() {
+            return Err(FullscreenError::NotConnected);
+        }
+        if fullscreen_element(self).is_some_and(|current| current.id == self.id) {
+            return Ok(false);
+        }
+        self.set_element_state(ElementState::FULLSCREEN, true);
+        top_layer::add(self);
+        Ok(true)
+    }
+}
+
+/// Get the fullscreen element of the document that owns `node`
+pub fn fullscreen_element(node: &Node) -> Option<Rc<Node>> {
+    top_layer::elements(node).into_iter().rev().find(|element| element.is_fullscreen())
+}
+
+/// Take the fullscreen element of the document that owns `node` out of
+/// fullscreen, like `document.exitFullscreen()`
+///
+/// The element below it, if any, becomes the fullscreen element. Returns
+/// the element that left fullscreen.
+pub fn exit_fullscreen(node: &Node) -> Result<Rc<Node>, FullscreenError> {
+    let element = fullscreen_element(node).ok_or(FullscreenError::NotFullscreen)?;
+    unfullscreen(&element);
+    Ok(element)
+}
+
+/// Take every element of the document that owns `node` out of fullscreen,
+/// as when the user presses Escape or the window leaves fullscreen mode
+///
+/// Returns the elements that left fullscreen, topmost first.
+pub fn fully_exit_fullscreen(node: &Node) -> Vec<Rc<Node>> {
+    let elements: Vec<Rc<Node>> = top_layer::elements(node)
+        .into_iter()
+        .rev()
+        .filter(|element| element.is_fullscreen())
+        .collect();
+    for element in &elements {
+        unfullscreen(element);
+    }
+    elements
+}
+
+fn unfullscreen(element: &Node) {
+    element.set_element_state(ElementState::FULLSCREEN, false);
+    top_layer::remove(element);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dom_event_integration::DomEventManager;
+    use crate::events::{KeyboardEventInit, SyntheticEventFactory};
+    use crate::Document;
+
+    #[test]
+    fn test_fullscreen_stack_and_escape() {
+        let document = Document::new();
+        let body = document.create_element("body");
+        document.root.append_child(&body);
+        let video = document.create_element("video");
+        let panel = document.create_element("div");
+        body.append_child(&video);
+        body.append_child(&panel);
+
+        assert_eq!(exit_fullscreen(&body).unwrap_err(), FullscreenError::NotFullscreen);
+        assert_eq!(document.create_element("div").request_fullscreen(), Err(FullscreenError::NotConnected));
+        assert_eq!(video.request_fullscreen(), Ok(true));
+        assert_eq!(video.request_fullscreen(), Ok(false));
+        assert_eq!(panel.request_fullscreen(), Ok(true));
+        assert!(fullscreen_element(&body).is_some_and(|element| Rc::ptr_eq(&element, &panel)));
+
+        // Exiting uncovers the element below
+        assert!(Rc::ptr_eq(&exit_fullscreen(&body).unwrap(), &panel));
+        assert!(!panel.is_fullscreen() && !top_layer::contains(&panel));
+        assert!(fullscreen_element(&body).is_some_and(|element| Rc::ptr_eq(&element, &video)));
+
+        // Escape fully exits fullscreen before anything else
+        let mut manager = DomEventManager::new();
+        manager.enable_dispatch_log();
+        panel.request_fullscreen().unwrap();
+        let escape = KeyboardEventInit { key: "Escape".to_string(), code: "Escape".to_string(), ..Default::default() };
+        manager.dispatch_keyboard_event(&body, SyntheticEventFactory::create_keyboard_event("keydown", &escape));
+        assert_eq!(manager.take_dispatch_log(), vec!["keydown", "fullscreenchange", "fullscreenchange"]);
+        assert!(fullscreen_element(&body).is_none() && !video.is_fullscreen());
+
+        // A removed element leaves fullscreen
+        video.request_fullscreen().unwrap();
+        body.remove_child(&video);
+        assert!(fullscreen_element(&body).is_none() && !video.is_fullscreen());
+    }
+}
@@ -0,0 +1,175 @@
+//! # Gamepads
+//!
+//! This module holds the state behind the Gamepad API. A `GamepadBackend`,
+//! such as one reading gilrs, reports what the connected devices look like
+//! each time it is polled; `GamepadRegistry` gives the devices their
+//! `navigator.getGamepads()` slots and reports which connected and which
+//! went away, for the `gamepadconnected` and `gamepaddisconnected` events.
+//!
+//! ## Design Principles
+//!
+//! 1. **Polled, Not Pushed**: As in the Gamepad API, the backend is polled
+//!    once per frame and scripts read the latest snapshot. Button presses
+//!    between two polls are not queued.
+//!
+//! 2. **Stable Slots**: A device keeps its index while it is connected.
+//!    The slot of a disconnected device stays empty until another device
+//!    takes it, as in browsers.
+//!
+//! 3. **Backend Agnostic**: Nothing here knows a device API, so tests and
+//!    headless runs use a scripted backend.
+
+use crate::event_types::time_origin;
+
+/// A button or trigger of a gamepad
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct GamepadButton {
+    pub pressed: bool,
+    pub touched: bool,
+    /// How far the button is pressed, from 0 to 1; analog triggers report
+    /// values in between
+    pub value: f64,
+}
+
+/// The state of a device, as a backend reports it
+#[derive(Debug, Clone, PartialEq)]
+pub struct GamepadSnapshot {
+    /// Identifies the device across polls
+    pub device: u64,
+    /// The product name, the `id` scripts see
+    pub name: String,
+    /// Whether buttons and axes follow the standard gamepad layout
+    pub standard_mapping: bool,
+    /// Stick positions, from -1 to 1
+    pub axes: Vec<f64>,
+    pub buttons: Vec<GamepadButton>,
+}
+
+/// A connected gamepad, as scripts see it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gamepad {
+    pub index: usize,
+    pub id: String,
+    /// `"standard"` or empty
+    pub mapping: &'static str,
+    pub connected: bool,
+    /// Milliseconds since `time_origin()` when the state last changed
+    pub timestamp: f64,
+    pub axes: Vec<f64>,
+    pub buttons: Vec<GamepadButton>,
+}
+
+/// A device connecting or disconnecting
+#[derive(Debug, Clone, PartialEq)]
+pub enum GamepadChange {
+    Connected(Gamepad),
+    /// The gamepad as it was last seen, with `connected` cleared
+    Disconnected(Gamepad),
+}
+
+/// A source of gamepad state
+pub trait GamepadBackend {
+    /// Report the state of every connected device
+    fn poll(&mut self) -> Vec<GamepadSnapshot>;
+}
+
+/// The gamepads of a page
+#[derive(Debug, Default)]
+pub struct GamepadRegistry {
+    slots: Vec<Option<(u64, Gamepad)>>,
+}
+
+impl GamepadRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take the state of a poll, returning the devices that connected and
+    /// disconnected since the last one
+    pub fn update(&mut self, snapshots: Vec<GamepadSnapshot>) -> Vec<GamepadChange> {
+        let now = time_origin().elapsed().as_secs_f64() * 1000.0;
+        let mut changes = Vec::new();
+
+        for slot in self.slots.iter_mut() {
+            let gone = slot.as_ref().is_some_and(|(device, _)| snapshots.iter().all(|snapshot| snapshot.device != *device));
+            if gone {
+                if let Some((_, mut gamepad)) = slot.take() {
+                    gamepad.connected = false;
+                    changes.push(GamepadChange::Disconnected(gamepad));
+                }
+            }
+        }
+
+        for snapshot in snapshots {
+            let existing = self.slots.iter_mut().flatten().find(|(device, _)| *device == snapshot.device);
+            if let Some((_, gamepad)) = existing {
+                if gamepad.axes != snapshot.axes || gamepad.buttons != snapshot.buttons {
+                    gamepad.axes = snapshot.axes;
+                    gamepad.buttons = snapshot.buttons;
+                    gamepad.timestamp = now;
+                }
+                continue;
+            }
+            let index = match self.slots.iter().position(Option::is_none) {
+                Some(index) => index,
+                None => {
+                    self.slots.push(None);
+                    self.slots.len() - 1
+                }
+            };
+            let gamepad = Gamepad {
+                index,
+                id: snapshot.name,
+                mapping: if snapshot.standard_mapping { "standard" } else { "" },
+                connected: true,
+                timestamp: now,
+                axes: snapshot.axes,
+                buttons: snapshot.buttons,
+            };
+            changes.push(GamepadChange::Connected(gamepad.clone()));
+            self.slots[index] = Some((snapshot.device, gamepad));
+        }
+        changes
+    }
+
+    /// Get the gamepads by index, with `None` for empty slots, like
+    /// `navigator.getGamepads()`
+    pub fn gamepads(&self) -> Vec<Option<Gamepad>> {
+        self.slots.iter().map(|slot| slot.as_ref().map(|(_, gamepad)| gamepad.clone())).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pad(device: u64, trigger: f64) -> GamepadSnapshot {
+        GamepadSnapshot {
+            device,
+            name: format!("Pad {}", device),
+            standard_mapping: true,
+            axes: vec![0.0, 0.0],
+            buttons: vec![GamepadButton { pressed: trigger > 0.5, touched: trigger > 0.0, value: trigger }],
+        }
+    }
+
+    #[test]
+    fn test_devices_keep_their_slot_while_connected() {
+        let mut registry = GamepadRegistry::new();
+        let changes = registry.update(vec![pad(10, 0.0), pad(20, 0.0)]);
+        assert!(matches!(&changes[..], [GamepadChange::Connected(a), GamepadChange::Connected(b)] if a.index == 0 && b.index == 1));
+
+        let before = registry.gamepads()[1].clone().unwrap();
+        let changes = registry.update(vec![pad(20, 0.8)]);
+        assert!(matches!(&changes[..], [GamepadChange::Disconnected(gamepad)] if gamepad.index == 0 && !gamepad.connected));
+        let after = registry.gamepads();
+        assert!(after[0].is_none());
+        let pressed = after[1].clone().unwrap();
+        assert!(pressed.buttons[0].pressed && pressed.timestamp >= before.timestamp);
+
+        // A new device takes the free slot
+        let changes = registry.update(vec![pad(20, 0.8), pad(30, 0.0)]);
+        assert!(matches!(&changes[..], [GamepadChange::Connected(gamepad)] if gamepad.index == 0 && gamepad.id == "Pad 30"));
+        assert!(registry.update(vec![pad(20, 0.8), pad(30, 0.0)]).is_empty());
+    }
+}
@@ -0,0 +1,360 @@
+//! # Image Loading
+//!
+//! This module implements the loading of `<img>` elements: when the
+//! source is fetched, what `loading` and `decoding` ask for, the state
+//! `complete` reports and the `load` and `error` events. Fetching is left
+//! to an `ImageBackend`.
+//!
+//! ## Design Principles
+//!
+//! 1. **The Loader Decides When**: Eager images are fetched on the first
+//!    update after their source is set. `loading="lazy"` images wait until
+//!    layout puts them near the viewport, so the caller passes the lazy
+//!    images it found there.
+//!
+//! 2. **Pluggable Fetching**: A backend only answers whether a source can
+//!    be fetched and its natural size. The stub backend loads every source
+//!    without a size, so layout keeps using the `width` and `height`
+//!    attributes.
+//!
+//! 3. **Queued Events**: As with media elements, `load` and `error` are
+//!    queued and fired by the caller afterwards, so listeners never run in
+//!    the middle of an update.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::rc::Rc;
+use crate::Node;
+use crate::dom_event_integration::DomEventManager;
+use crate::media::fire_media_events;
+
+/// The `loading` attribute of an image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoadingMode {
+    #[default]
+    Eager,
+    Lazy,
+}
+
+impl LoadingMode {
+    /// Parse the attribute value; unknown values are eager
+    pub fn parse(value: Option<&str>) -> LoadingMode {
+        match value {
+            Some(value) if value.trim().eq_ignore_ascii_case("lazy") => LoadingMode::Lazy,
+            _ => LoadingMode::Eager,
+        }
+    }
+
+    /// Get the attribute value the mode reflects as
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LoadingMode::Eager => "eager",
+            LoadingMode::Lazy => "lazy",
+        }
+    }
+}
+
+/// The `decoding` attribute of an image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DecodingHint {
+    Sync,
+    Async,
+    #[default]
+    Auto,
+}
+
+impl DecodingHint {
+    /// Parse the attribute value; unknown values are `auto`
+    pub fn parse(value: Option<&str>) -> DecodingHint {
+        match value.map(|value| value.trim().to_ascii_lowercase()).as_deref() {
+            Some("sync") => DecodingHint::Sync,
+            Some("async") => DecodingHint::Async,
+            _ => DecodingHint::Auto,
+        }
+    }
+
+    /// Get the attribute value the hint reflects as
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DecodingHint::Sync => "sync",
+            DecodingHint::Async => "async",
+            DecodingHint::Auto => "auto",
+        }
+    }
+}
+
+/// Why an image could not be loaded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageError {
+    /// The `src` attribute is empty
+    EmptySource,
+    /// The source could not be fetched
+    Network,
+    /// The response is not an image
+    Decode,
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::EmptySource => write!(f, "Image source is empty"),
+            ImageError::Network => write!(f, "Image could not be fetched"),
+            ImageError::Decode => write!(f, "Image could not be decoded"),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+/// A fetcher for image sources
+pub trait ImageBackend: fmt::Debug {
+    /// Fetch a source and report its natural size, if known
+    fn fetch(&self, src: &str) -> Result<Option<(u32, u32)>, ImageError>;
+}
+
+/// The stub backend: every source loads, without a natural size
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullImageBackend;
+
+impl ImageBackend for NullImageBackend {
+    fn fetch(&self, _src: &str) -> Result<Option<(u32, u32)>, ImageError> {
+        Ok(None)
+    }
+}
+
+/// The state of an image's current request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ImageStatus {
+    /// Nothing was fetched yet, e.g. a lazy image far from the viewport
+    #[default]
+    Unavailable,
+    /// The source is fetched
+    Complete,
+    /// The source could not be loaded
+    Broken,
+}
+
+/// Runtime state of an image element
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ImageState {
+    pub status: ImageStatus,
+    /// The source of the current request
+    pub src: Option<String>,
+    pub natural_size: Option<(u32, u32)>,
+    /// Set once the image is decoded, at load for `decoding="sync"` and
+    /// otherwise when `decode()` asks for it
+    pub decoded: bool,
+}
+
+impl Node {
+    /// Check whether this node is an `<img>` element
+    pub fn is_image(&self) -> bool {
+        self.tag_name().is_some_and(|tag| tag.eq_ignore_ascii_case("img"))
+    }
+
+    /// Get the `loading` mode of an image
+    pub fn loading_mode(&self) -> LoadingMode {
+        LoadingMode::parse(self.get_attribute("loading").as_deref())
+    }
+
+    /// Get the `decoding` hint of an image
+    pub fn decoding_hint(&self) -> DecodingHint {
+        DecodingHint::parse(self.get_attribute("decoding").as_deref())
+    }
+
+    /// Get a copy of the loading state of an image
+    pub fn image_state(&self) -> ImageState {
+        self.image_state.borrow().clone()
+    }
+
+    /// Check whether an image is `complete`: it has no source, or its
+    /// current source is loaded or broken
+    pub fn image_complete(&self) -> bool {
+        let state = self.image_state.borrow();
+        match self.get_attribute("src") {
+            None => true,
+            Some(src) if src.is_empty() => true,
+            Some(src) => state.src.as_deref() == Some(src.as_str()) && state.status != ImageStatus::Unavailable,
+        }
+    }
+
+    /// Mark a loaded image as decoded, as `decode()` does
+    ///
+    /// Returns `false` if the current source is not loaded.
+    pub fn decode_image(&self) -> bool {
+        let mut state = self.image_state.borrow_mut();
+        let current = state.status == ImageStatus::Complete && state.src == self.get_attribute("src");
+        if current {
+            state.decoded = true;
+        }
+        current
+    }
+}
+
+/// Drives image elements: fetches their sources through a backend when
+/// they are due and queues the events they fire
+#[derive(Debug)]
+pub struct ImageLoader {
+    backend: Box<dyn ImageBackend>,
+    /// Events waiting to be fired, in order
+    pending: Vec<(Rc<Node>, &'static str)>,
+}
+
+impl Default for ImageLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ImageLoader {
+    /// Create a loader using the stub backend
+    pub fn new() -> Self {
+        Self::with_backend(Box::new(NullImageBackend))
+    }
+
+    /// Create a loader using a fetcher
+    pub fn with_backend(backend: Box<dyn ImageBackend>) -> Self {
+        ImageLoader { backend, pending: Vec::new() }
+    }
+
+    /// Replace the fetcher
+    pub fn set_backend(&mut self, backend: Box<dyn ImageBackend>) {
+        self.backend = backend;
+    }
+
+    /// Load the images below `root` whose source changed
+    ///
+    /// Lazy images are only loaded if they are in `near_viewport`. Returns
+    /// the number of images loaded.
+    pub fn update(&mut self, root: &Rc<Node>, near_viewport: &HashSet<u64>) -> usize {
+        let mut images = Vec::new();
+        collect_images(root, &mut images);
+        images
+            .iter()
+            .filter(|image| image.loading_mode() == LoadingMode::Eager || near_viewport.contains(&image.id))
+            .filter(|image| self.load(image).is_some())
+            .count()
+    }
+
+    /// Load an image's source if it changed, whatever its `loading` mode
+    ///
+    /// Fires `load` or `error`. Returns `None` if there was nothing to do.
+    pub fn load(&mut self, node: &Rc<Node>) -> Option<Result<(), ImageError>> {
+        let src = node.get_attribute("src")?;
+        if node.image_state.borrow().src.as_deref() == Some(src.as_str()) {
+            return None;
+        }
+        let result = if src.trim().is_empty() { Err(ImageError::EmptySource) } else { self.backend.fetch(&src) };
+        let mut state = node.image_state.borrow_mut();
+        *state = ImageState { src: Some(src), ..ImageState::default() };
+        match result {
+            Ok(natural_size) => {
+                state.status = ImageStatus::Complete;
+                state.natural_size = natural_size;
+                state.decoded = node.decoding_hint() == DecodingHint::Sync;
+                drop(state);
+                self.queue(node, "load");
+                Some(Ok(()))
+            }
+            Err(error) => {
+                state.status = ImageStatus::Broken;
+                drop(state);
+                self.queue(node, "error");
+                Some(Err(error))
+            }
+        }
+    }
+
+    /// Check whether any image event is waiting
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Take the queued events, in order
+    ///
+    /// Lets the caller release the loader before firing them with
+    /// `fire_media_events`, which image events share.
+    pub fn take_events(&mut self) -> Vec<(Rc<Node>, &'static str)> {
+        std::mem::take(&mut self.pending)
+    }
+
+    /// Fire the queued events, in order
+    ///
+    /// Returns the number of events fired.
+    pub fn dispatch_events(&mut self, manager: &mut DomEventManager) -> usize {
+        fire_media_events(self.take_events(), manager)
+    }
+
+    fn queue(&mut self, node: &Rc<Node>, event_type: &'static str) {
+        self.pending.push((Rc::clone(node), event_type));
+    }
+}
+
+/// Collect the `<img>` elements below a node, in tree order
+fn collect_images(node: &Rc<Node>, images: &mut Vec<Rc<Node>>) {
+    if node.is_image() {
+        images.push(Rc::clone(node));
+    }
+    for child in node.children.borrow().iter() {
+        collect_images(child, images);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Document;
+
+    #[derive(Debug)]
+    struct PhotoBackend;
+
+    impl ImageBackend for PhotoBackend {
+        fn fetch(&self, src: &str) -> Result<Option<(u32, u32)>, ImageError> {
+            match src {
+                "photo.png" => Ok(Some((320, 240))),
+                _ => Err(ImageError::Network),
+            }
+        }
+    }
+
+    #[test]
+    fn test_lazy_images_wait_for_the_viewport() {
+        let document = Rc::new(Document::new());
+        let hero = document.create_element("img");
+        hero.set_attribute("src", "photo.png");
+        hero.set_attribute("decoding", "SYNC");
+        let below = document.create_element("img");
+        below.set_attribute("src", "photo.png");
+        below.set_attribute("loading", "lazy");
+        let missing = document.create_element("img");
+        missing.set_attribute("src", "missing.png");
+        for image in [&hero, &below, &missing] {
+            document.root.append_child(image);
+        }
+
+        let mut manager = DomEventManager::new();
+        manager.set_document(Rc::clone(&document));
+        manager.enable_dispatch_log();
+        let mut loader = ImageLoader::with_backend(Box::new(PhotoBackend));
+
+        assert_eq!(loader.update(&document.root, &HashSet::new()), 2);
+        assert_eq!(hero.image_state().natural_size, Some((320, 240)));
+        assert!(hero.image_state().decoded && hero.image_complete());
+        assert_eq!(missing.image_state().status, ImageStatus::Broken);
+        assert!(!below.image_complete());
+        assert_eq!(loader.dispatch_events(&mut manager), 2);
+        assert_eq!(manager.take_dispatch_log(), vec!["load", "error"]);
+
+        // Nothing is fetched again until a source changes or a lazy image
+        // comes near the viewport
+        assert_eq!(loader.update(&document.root, &HashSet::new()), 0);
+        assert_eq!(loader.update(&document.root, &HashSet::from([below.id])), 1);
+        assert!(below.image_complete() && !below.image_state().decoded);
+        assert!(below.decode_image());
+
+        below.set_attribute("src", "");
+        assert_eq!(loader.load(&below), Some(Err(ImageError::EmptySource)));
+        loader.dispatch_events(&mut manager);
+        assert_eq!(manager.take_dispatch_log(), vec!["load", "error"]);
+    }
+}
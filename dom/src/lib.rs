@@ -19,8 +19,9 @@
 //!    and properties as the browser engine evolves.
 
 use std::rc::{Rc, Weak};
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 // Event system modules
 pub mod event_types;
@@ -28,6 +29,50 @@ pub mod events;
 pub mod delegation;
 pub mod element;
 pub mod dom_event_integration;
+pub mod origin;
+pub mod forms;
+pub mod autofill;
+pub mod element_state;
+pub mod clipboard;
+pub mod scroll_events;
+pub mod media;
+pub mod images;
+pub mod spellcheck;
+pub mod webgl;
+pub mod bidi;
+pub mod selectors;
+pub mod namespaces;
+pub mod serialization;
+pub mod mutations;
+pub mod collections;
+pub mod diff;
+pub mod traversal;
+pub mod adoption;
+pub mod connection;
+pub mod pointer;
+pub mod gamepad;
+pub mod touch;
+pub mod top_layer;
+pub mod dialog;
+pub mod popover;
+pub mod fullscreen;
+pub mod page_visibility;
+pub mod clock;
+pub mod sandboxing;
+
+pub use origin::{Origin, SecurityError, SecurityPolicy};
+pub use element_state::ElementState;
+pub use namespaces::{NamespaceError, QualifiedName};
+pub use mutations::Mutation;
+pub use collections::LiveCollection;
+pub use diff::{Patch, PatchError};
+pub use traversal::TreeWalker;
+pub use adoption::AdoptError;
+pub use pointer::{PointerCaptureError, PointerCaptures};
+
+/// Source of node ids, shared by all documents so nodes keep their id
+/// when they move between documents
+static NEXT_NODE_ID: AtomicU64 = AtomicU64::new(1);
 
 #[cfg(test)]
 mod event_tests;
@@ -62,31 +107,372 @@ pub struct Node {
     pub children: RefCell<Vec<Rc<Node>>>,
     /// Unique identifier for this node
     pub id: u64,
+    /// Attribute changes made after parsing (`None` marks a removed attribute)
+    ///
+    /// The attributes stored in `NodeType::Element` are the parsed attributes
+    /// and are immutable; runtime mutations are layered on top of them here.
+    attribute_changes: RefCell<HashMap<String, Option<String>>>,
+    /// Attributes in a namespace, set with `set_attribute_ns`, in the order
+    /// they were added
+    namespaced_attributes: RefCell<Vec<(QualifiedName, String)>>,
+    /// Namespace of an element; parsed elements are in the HTML namespace
+    namespace: Option<String>,
+    /// Prefix of an element created with `create_element_ns`
+    prefix: Option<String>,
+    /// Character data set after parsing, replacing the text of a `Text` node
+    ///
+    /// Like the attributes, the parsed text in `NodeType::Text` is immutable.
+    data_change: RefCell<Option<String>>,
+    /// Runtime state for form controls (value, checkedness, selection)
+    form_state: RefCell<forms::FormControlState>,
+    /// Playback state for media elements (paused, position, metadata)
+    media_state: RefCell<media::MediaState>,
+    /// Loading state for image elements (request, natural size)
+    image_state: RefCell<images::ImageState>,
+    /// State of dialog elements (return value)
+    dialog_state: RefCell<dialog::DialogState>,
+    /// Interaction state matched by `:hover`, `:active` and `:focus`
+    state: Cell<ElementState>,
+    /// Id of the owner document, 0 until the node is created by or
+    /// inserted into a document
+    owner: Cell<u64>,
+    /// Set on elements the HTML parser inserted
+    parser_inserted: Cell<bool>,
+    /// The "already started" flag of scripts, set once a script ran
+    already_started: Cell<bool>,
 }
 
 impl Node {
     /// Create a new node with the given type and ID
+    ///
+    /// Elements are in the HTML namespace.
     pub fn new(node_type: NodeType, id: u64) -> Rc<Node> {
+        let namespace = matches!(node_type, NodeType::Element { .. }).then(|| namespaces::HTML_NAMESPACE.to_string());
+        Self::new_namespaced(node_type, id, namespace, None)
+    }
+
+    /// Create a new node with the given namespace and prefix
+    fn new_namespaced(node_type: NodeType, id: u64, namespace: Option<String>, prefix: Option<String>) -> Rc<Node> {
         Rc::new(Node {
             node_type,
             parent: RefCell::new(Weak::new()),
             children: RefCell::new(Vec::new()),
             id,
+            attribute_changes: RefCell::new(HashMap::new()),
+            namespaced_attributes: RefCell::new(Vec::new()),
+            namespace,
+            prefix,
+            data_change: RefCell::new(None),
+            form_state: RefCell::new(forms::FormControlState::default()),
+            media_state: RefCell::new(media::MediaState::default()),
+            image_state: RefCell::new(images::ImageState::default()),
+            dialog_state: RefCell::new(dialog::DialogState::default()),
+            state: Cell::new(ElementState::EMPTY),
+            owner: Cell::new(0),
+            parser_inserted: Cell::new(false),
+            already_started: Cell::new(false),
+        })
+    }
+
+    /// Get the interaction state flags of this node
+    pub fn element_state(&self) -> ElementState {
+        self.state.get()
+    }
+
+    /// Set or clear interaction state flags, returning whether they changed
+    ///
+    /// Usually called through `element_state::InteractionState`, which keeps
+    /// hover and active chains consistent.
+    pub fn set_element_state(&self, flags: ElementState, on: bool) -> bool {
+        let mut state = self.state.get();
+        let before = state;
+        if on {
+            state.insert(flags);
+        } else {
+            state.remove(flags);
+        }
+        self.state.set(state);
+        state != before
+    }
+
+    /// Check whether the pointer is over this node or a descendant
+    pub fn is_hovered(&self) -> bool {
+        self.state.get().contains(ElementState::HOVER)
+    }
+
+    /// Check whether this node or a descendant is being activated
+    pub fn is_active(&self) -> bool {
+        self.state.get().contains(ElementState::ACTIVE)
+    }
+
+    /// Get the tag name if this node is an element
+    pub fn tag_name(&self) -> Option<&str> {
+        match &self.node_type {
+            NodeType::Element { tag_name, .. } => Some(tag_name.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Get the namespace URI of an element
+    pub fn namespace_uri(&self) -> Option<&str> {
+        self.namespace.as_deref()
+    }
+
+    /// Get the namespace prefix of an element
+    pub fn prefix(&self) -> Option<&str> {
+        self.prefix.as_deref()
+    }
+
+    /// Get the local name of an element: the tag name without its prefix
+    pub fn local_name(&self) -> Option<&str> {
+        let tag_name = self.tag_name()?;
+        Some(match &self.prefix {
+            Some(prefix) => tag_name.strip_prefix(prefix.as_str()).and_then(|rest| rest.strip_prefix(':')).unwrap_or(tag_name),
+            None => tag_name,
+        })
+    }
+
+    /// Check whether this is an element in the HTML namespace
+    pub fn is_html_element(&self) -> bool {
+        self.namespace.as_deref() == Some(namespaces::HTML_NAMESPACE)
+    }
+
+    /// Get the current value of an attribute by its qualified name
+    ///
+    /// Runtime changes take precedence over the parsed attributes, and
+    /// attributes without a namespace over namespaced ones.
+    pub fn get_attribute(&self, name: &str) -> Option<String> {
+        self.get_unnamespaced_attribute(name).or_else(|| {
+            self.namespaced_attributes
+                .borrow()
+                .iter()
+                .find(|(attribute, _)| attribute.qualified_name() == name)
+                .map(|(_, value)| value.clone())
         })
     }
 
+    /// Get an attribute without a namespace
+    fn get_unnamespaced_attribute(&self, name: &str) -> Option<String> {
+        if let Some(change) = self.attribute_changes.borrow().get(name) {
+            return change.clone();
+        }
+        match &self.node_type {
+            NodeType::Element { attributes, .. } => attributes.get(name).cloned(),
+            _ => None,
+        }
+    }
+
+    /// Check whether an attribute is present
+    pub fn has_attribute(&self, name: &str) -> bool {
+        self.get_attribute(name).is_some()
+    }
+
+    /// Set an attribute value
+    ///
+    /// A namespaced attribute with the qualified name is changed if there is
+    /// no attribute without a namespace of that name. Has no effect on
+    /// non-element nodes.
+    pub fn set_attribute(&self, name: &str, value: &str) {
+        if self.tag_name().is_none() {
+            return;
+        }
+        let mut updated_namespaced = false;
+        if self.get_unnamespaced_attribute(name).is_none() {
+            let mut namespaced = self.namespaced_attributes.borrow_mut();
+            if let Some((_, existing)) = namespaced.iter_mut().find(|(attribute, _)| attribute.qualified_name() == name) {
+                *existing = value.to_string();
+                updated_namespaced = true;
+            }
+        }
+        if !updated_namespaced {
+            self.attribute_changes
+                .borrow_mut()
+                .insert(name.to_string(), Some(value.to_string()));
+        }
+        self.notify_attribute(name);
+    }
+
+    /// Announce a change to an attribute of this element
+    fn notify_attribute(&self, name: &str) {
+        mutations::notify(Mutation::Attribute { element: self.id, name: name.to_string() });
+    }
+
+    /// Remove an attribute by its qualified name
+    pub fn remove_attribute(&self, name: &str) {
+        if self.tag_name().is_none() {
+            return;
+        }
+        if self.get_unnamespaced_attribute(name).is_some() {
+            self.attribute_changes.borrow_mut().insert(name.to_string(), None);
+            self.notify_attribute(name);
+            return;
+        }
+        let index = self.namespaced_attributes.borrow().iter().position(|(attribute, _)| attribute.qualified_name() == name);
+        if let Some(index) = index {
+            self.namespaced_attributes.borrow_mut().remove(index);
+            self.notify_attribute(name);
+        }
+    }
+
+    /// Toggle a boolean attribute, returning whether it is now present
+    pub fn toggle_attribute(&self, name: &str) -> bool {
+        if self.has_attribute(name) {
+            self.remove_attribute(name);
+            false
+        } else {
+            self.set_attribute(name, "");
+            self.tag_name().is_some()
+        }
+    }
+
+    /// Get an attribute by namespace and local name, like `getAttributeNS`
+    pub fn get_attribute_ns(&self, namespace: Option<&str>, local_name: &str) -> Option<String> {
+        match namespace.filter(|namespace| !namespace.is_empty()) {
+            None => self.get_unnamespaced_attribute(local_name),
+            Some(namespace) => self
+                .namespaced_attributes
+                .borrow()
+                .iter()
+                .find(|(attribute, _)| attribute.is_in(Some(namespace)) && attribute.local_name == local_name)
+                .map(|(_, value)| value.clone()),
+        }
+    }
+
+    /// Check whether an attribute with the namespace and local name is present
+    pub fn has_attribute_ns(&self, namespace: Option<&str>, local_name: &str) -> bool {
+        self.get_attribute_ns(namespace, local_name).is_some()
+    }
+
+    /// Set an attribute in a namespace, like `setAttributeNS`
+    ///
+    /// An existing attribute with the same namespace and local name keeps
+    /// its prefix. Has no effect on non-element nodes.
+    pub fn set_attribute_ns(&self, namespace: Option<&str>, qualified_name: &str, value: &str) -> Result<(), NamespaceError> {
+        let name = namespaces::validate_and_extract(namespace, qualified_name)?;
+        if self.tag_name().is_none() {
+            return Ok(());
+        }
+        let qualified_name = name.qualified_name();
+        if name.namespace.is_none() {
+            self.attribute_changes
+                .borrow_mut()
+                .insert(name.local_name, Some(value.to_string()));
+        } else {
+            let mut namespaced = self.namespaced_attributes.borrow_mut();
+            match namespaced
+                .iter_mut()
+                .find(|(attribute, _)| attribute.namespace == name.namespace && attribute.local_name == name.local_name)
+            {
+                Some((_, existing)) => *existing = value.to_string(),
+                None => namespaced.push((name, value.to_string())),
+            }
+        }
+        self.notify_attribute(&qualified_name);
+        Ok(())
+    }
+
+    /// Remove an attribute by namespace and local name, like
+    /// `removeAttributeNS`
+    pub fn remove_attribute_ns(&self, namespace: Option<&str>, local_name: &str) {
+        match namespace.filter(|namespace| !namespace.is_empty()) {
+            None => {
+                if self.get_unnamespaced_attribute(local_name).is_some() {
+                    self.attribute_changes.borrow_mut().insert(local_name.to_string(), None);
+                    self.notify_attribute(local_name);
+                }
+            }
+            Some(namespace) => {
+                let index = self
+                    .namespaced_attributes
+                    .borrow()
+                    .iter()
+                    .position(|(attribute, _)| attribute.is_in(Some(namespace)) && attribute.local_name == local_name);
+                if let Some(index) = index {
+                    let (attribute, _) = self.namespaced_attributes.borrow_mut().remove(index);
+                    self.notify_attribute(&attribute.qualified_name());
+                }
+            }
+        }
+    }
+
+    /// Get all current attributes by qualified name, including runtime
+    /// changes
+    ///
+    /// Attributes without a namespace win over namespaced attributes with
+    /// the same qualified name; use `attributes_ns` to see both.
+    pub fn attributes(&self) -> HashMap<String, String> {
+        let mut result = match &self.node_type {
+            NodeType::Element { attributes, .. } => attributes.clone(),
+            _ => return HashMap::new(),
+        };
+        for (name, change) in self.attribute_changes.borrow().iter() {
+            match change {
+                Some(value) => {
+                    result.insert(name.clone(), value.clone());
+                }
+                None => {
+                    result.remove(name);
+                }
+            }
+        }
+        for (attribute, value) in self.namespaced_attributes.borrow().iter() {
+            result.entry(attribute.qualified_name()).or_insert_with(|| value.clone());
+        }
+        result
+    }
+
+    /// Get all current attributes with their namespaces
+    ///
+    /// Attributes without a namespace come first, sorted by name, then the
+    /// namespaced ones in the order they were added.
+    pub fn attributes_ns(&self) -> Vec<(QualifiedName, String)> {
+        let mut unnamespaced: Vec<_> = match &self.node_type {
+            NodeType::Element { attributes, .. } => attributes.keys().cloned().collect(),
+            _ => return Vec::new(),
+        };
+        unnamespaced.extend(self.attribute_changes.borrow().keys().cloned());
+        unnamespaced.sort();
+        unnamespaced.dedup();
+        let mut result: Vec<_> = unnamespaced
+            .into_iter()
+            .filter_map(|name| self.get_unnamespaced_attribute(&name).map(|value| (QualifiedName::local(&name), value)))
+            .collect();
+        result.extend(self.namespaced_attributes.borrow().iter().cloned());
+        result
+    }
+
+    /// Get the current character data of a `Text` node
+    ///
+    /// Returns `None` for other nodes. Runtime changes take precedence over
+    /// the parsed text.
+    pub fn character_data(&self) -> Option<String> {
+        match &self.node_type {
+            NodeType::Text(text) => Some(self.data_change.borrow().clone().unwrap_or_else(|| text.clone())),
+            _ => None,
+        }
+    }
+
+    /// Replace the character data of a `Text` node
+    ///
+    /// Has no effect on other nodes.
+    pub fn set_character_data(&self, data: &str) {
+        if matches!(self.node_type, NodeType::Text(_)) {
+            *self.data_change.borrow_mut() = Some(data.to_string());
+            mutations::notify(Mutation::CharacterData { node: self.id });
+        }
+    }
+
     /// Add a child node to this node
     /// 
     /// This method:
     /// 1. Adds the child to this node's children list
     /// 2. Sets this node as the child's parent
     /// 3. Maintains the tree structure invariants
+    ///
+    /// See `insert_child_at` for moving and the insertion steps.
     pub fn append_child(self: &Rc<Self>, child: &Rc<Node>) {
-        // Set this node as the parent of the child
-        *child.parent.borrow_mut() = Rc::downgrade(self);
-        
-        // Add child to this node's children
-        self.children.borrow_mut().push(Rc::clone(child));
+        let index = self.children.borrow().len();
+        self.insert_child_at(index, child);
     }
 
     /// Get the text content of this node and all its descendants
@@ -94,9 +480,9 @@ impl Node {
     /// This is useful for extracting all text from a document or element
     /// without the HTML markup.
     pub fn text_content(&self) -> String {
-        match &self.node_type {
-            NodeType::Text(text) => text.clone(),
-            _ => {
+        match self.character_data() {
+            Some(text) => text,
+            None => {
                 // For non-text nodes, concatenate all descendant text
                 self.children
                     .borrow()
@@ -108,6 +494,90 @@ impl Node {
         }
     }
 
+    /// Replace the text of this node, as the `textContent` setter does
+    ///
+    /// A `Text` node gets the text as its data. Any other node loses all its
+    /// children and gets a single new text node instead, or none for empty
+    /// text. Returns the removed children so callers can keep references
+    /// to them valid.
+    pub fn set_text_content(self: &Rc<Self>, text: &str, document: &Document) -> Vec<Rc<Node>> {
+        if matches!(self.node_type, NodeType::Text(_)) {
+            self.set_character_data(text);
+            return Vec::new();
+        }
+        let removed = self.remove_children();
+        if !text.is_empty() {
+            self.append_child(&document.create_text_node(text));
+        }
+        removed
+    }
+
+    /// Replace the children of this node as the `innerText` setter does
+    ///
+    /// Like `set_text_content`, except that line breaks in the text become
+    /// `<br>` elements.
+    pub fn set_inner_text(self: &Rc<Self>, text: &str, document: &Document) -> Vec<Rc<Node>> {
+        if matches!(self.node_type, NodeType::Text(_)) {
+            self.set_character_data(text);
+            return Vec::new();
+        }
+        let removed = self.remove_children();
+        let normalized = text.replace("\r\n", "\n").replace('\r', "\n");
+        for (index, line) in normalized.split('\n').enumerate() {
+            if index > 0 {
+                self.append_child(&document.create_element("br"));
+            }
+            if !line.is_empty() {
+                self.append_child(&document.create_text_node(line));
+            }
+        }
+        removed
+    }
+
+    /// Merge adjacent text nodes and drop empty ones in this subtree
+    ///
+    /// The first node of each run of text nodes keeps the joined data and
+    /// the others are detached, as `Node.normalize()` specifies. Returns the
+    /// detached text nodes.
+    pub fn normalize(&self) -> Vec<Rc<Node>> {
+        let children = self.children.borrow().clone();
+        let mut kept = Vec::with_capacity(children.len());
+        let mut removed = Vec::new();
+        let mut run: Option<(Rc<Node>, String)> = None;
+        for child in children {
+            let Some(data) = child.character_data() else {
+                flush_text_run(run.take());
+                removed.extend(child.normalize());
+                kept.push(child);
+                continue;
+            };
+            match &mut run {
+                _ if data.is_empty() => removed.push(child),
+                Some((_, joined)) => {
+                    joined.push_str(&data);
+                    removed.push(child);
+                }
+                None => {
+                    kept.push(Rc::clone(&child));
+                    run = Some((child, data));
+                }
+            }
+        }
+        flush_text_run(run);
+        if kept.len() != self.children.borrow().len() {
+            let detached: Vec<Rc<Node>> = self.children.borrow().iter().filter(|child| !kept.iter().any(|node| Rc::ptr_eq(node, child))).cloned().collect();
+            for child in &detached {
+                *child.parent.borrow_mut() = Weak::new();
+            }
+            *self.children.borrow_mut() = kept;
+            mutations::notify(Mutation::ChildList { parent: self.id });
+            for child in &detached {
+                connection::notify_removal(child);
+            }
+        }
+        removed
+    }
+
     /// Find the first descendant element with the given tag name
     /// 
     /// This implements a depth-first search for elements by tag name.
@@ -121,6 +591,19 @@ impl Node {
                     parent: RefCell::new(Weak::new()),
                     children: RefCell::new(Vec::new()),
                     id: self.id,
+                    attribute_changes: RefCell::new(self.attribute_changes.borrow().clone()),
+                    namespaced_attributes: RefCell::new(self.namespaced_attributes.borrow().clone()),
+                    namespace: self.namespace.clone(),
+                    prefix: self.prefix.clone(),
+                    data_change: RefCell::new(self.data_change.borrow().clone()),
+                    form_state: RefCell::new(self.form_state.borrow().clone()),
+                    media_state: RefCell::new(self.media_state.borrow().clone()),
+                    image_state: RefCell::new(self.image_state.borrow().clone()),
+                    dialog_state: RefCell::new(self.dialog_state.borrow().clone()),
+                    state: Cell::new(self.state.get()),
+                    owner: Cell::new(self.owner.get()),
+                    parser_inserted: Cell::new(self.parser_inserted.get()),
+                    already_started: Cell::new(self.already_started.get()),
                 }));
             }
         }
@@ -149,6 +632,19 @@ impl Node {
                     parent: RefCell::new(Weak::new()),
                     children: RefCell::new(Vec::new()),
                     id: self.id,
+                    attribute_changes: RefCell::new(self.attribute_changes.borrow().clone()),
+                    namespaced_attributes: RefCell::new(self.namespaced_attributes.borrow().clone()),
+                    namespace: self.namespace.clone(),
+                    prefix: self.prefix.clone(),
+                    data_change: RefCell::new(self.data_change.borrow().clone()),
+                    form_state: RefCell::new(self.form_state.borrow().clone()),
+                    media_state: RefCell::new(self.media_state.borrow().clone()),
+                    image_state: RefCell::new(self.image_state.borrow().clone()),
+                    dialog_state: RefCell::new(self.dialog_state.borrow().clone()),
+                    state: Cell::new(self.state.get()),
+                    owner: Cell::new(self.owner.get()),
+                    parser_inserted: Cell::new(self.parser_inserted.get()),
+                    already_started: Cell::new(self.already_started.get()),
                 }));
             }
         }
@@ -170,25 +666,97 @@ impl Node {
 pub struct Document {
     /// The root document node
     pub root: Rc<Node>,
-    /// Counter for generating unique node IDs
-    next_id: RefCell<u64>,
+    /// The URL this document was loaded from, after redirects
+    url: RefCell<Option<String>>,
+    /// The origin this document was loaded from
+    origin: RefCell<Origin>,
+    /// Security policy governing cross-origin access from this document
+    security_policy: RefCell<SecurityPolicy>,
+    /// What the document may do, if it is loaded in a sandboxed frame
+    sandbox_flags: Cell<Option<sandboxing::SandboxFlags>>,
+    /// The `<html>`, `<head>` and `<body>` elements as the parser inserted
+    /// them, checked against the tree before use
+    landmarks: RefCell<Landmarks>,
+}
+
+/// Weak references to the elements `Document` has accessors for
+#[derive(Debug, Default)]
+struct Landmarks {
+    document_element: Weak<Node>,
+    head: Weak<Node>,
+    body: Weak<Node>,
+}
+
+/// Store the joined data of a run of text nodes in its first node
+fn flush_text_run(run: Option<(Rc<Node>, String)>) {
+    if let Some((first, joined)) = run {
+        if first.character_data().as_deref() != Some(joined.as_str()) {
+            first.set_character_data(&joined);
+        }
+    }
+}
+
+/// Check whether `parent` is the parent of `node`
+fn is_child_of(node: &Rc<Node>, parent: &Rc<Node>) -> bool {
+    node.parent.borrow().upgrade().is_some_and(|actual| Rc::ptr_eq(&actual, parent))
 }
 
 impl Document {
     /// Create a new empty document
     pub fn new() -> Self {
-        let root = Node::new(NodeType::Document, 0);
+        let root = Node::new(NodeType::Document, NEXT_NODE_ID.fetch_add(1, Ordering::Relaxed));
+        root.owner.set(root.id);
         Document {
             root,
-            next_id: RefCell::new(1),
+            url: RefCell::new(None),
+            origin: RefCell::new(Origin::opaque()),
+            security_policy: RefCell::new(SecurityPolicy::default()),
+            sandbox_flags: Cell::new(None),
+            landmarks: RefCell::new(Landmarks::default()),
+        }
+    }
+
+    /// Create a new empty document with the URL and origin of the given URL
+    pub fn new_with_url(url: &str) -> Self {
+        let document = Self::new();
+        document.set_url(url);
+        document
+    }
+
+    /// Get the URL this document was loaded from
+    pub fn url(&self) -> Option<String> {
+        self.url.borrow().clone()
+    }
+
+    /// Set the URL of this document, and its origin from it
+    ///
+    /// Called by the loader once the final (post-redirect) URL is known.
+    pub fn set_url(&self, url: &str) {
+        *self.url.borrow_mut() = Some(url.to_string());
+        self.set_origin(Origin::from_url(url));
+    }
+
+    /// Get the `href` of the first `<base>` element with one, in tree order
+    ///
+    /// The value is as written; resolving it against the document URL gives
+    /// the base URL, see `networking::document_base_url`.
+    pub fn base_href(&self) -> Option<String> {
+        fn find(node: &Rc<Node>) -> Option<String> {
+            if node.is_html_element() && node.local_name() == Some("base") {
+                if let Some(href) = node.get_attribute("href") {
+                    return Some(href);
+                }
+            }
+            node.children.borrow().iter().find_map(find)
         }
+        find(&self.root)
     }
 
     /// Create a new node with an automatically assigned ID
     pub fn create_node(&self, node_type: NodeType) -> Rc<Node> {
-        let id = *self.next_id.borrow();
-        *self.next_id.borrow_mut() += 1;
-        Node::new(node_type, id)
+        let node = Node::new(node_type, self.get_next_id());
+        node.owner.set(self.id());
+        node
     }
 
     /// Create a new element node with the given tag name
@@ -199,34 +767,160 @@ impl Document {
         })
     }
 
+    /// Create a new element in a namespace, like `createElementNS`
+    ///
+    /// The tag name is the qualified name, prefix included.
+    pub fn create_element_ns(&self, namespace: Option<&str>, qualified_name: &str) -> Result<Rc<Node>, NamespaceError> {
+        let name = namespaces::validate_and_extract(namespace, qualified_name)?;
+        let node_type = NodeType::Element {
+            tag_name: qualified_name.to_string(),
+            attributes: HashMap::new(),
+        };
+        let element = Node::new_namespaced(node_type, self.get_next_id(), name.namespace, name.prefix);
+        element.owner.set(self.id());
+        Ok(element)
+    }
+
     /// Create a new text node with the given content
     pub fn create_text_node(&self, content: &str) -> Rc<Node> {
         self.create_node(NodeType::Text(content.to_string()))
     }
 
-    /// Get the document element (usually the `<html>` element)
-    /// 
-    /// This is a convenience method to find the root HTML element
-    /// of the document.
+    /// Record an element the parser inserted, if it is the document
+    /// element or the `<head>` or `<body>` of it
+    ///
+    /// The accessors use the recorded elements while they are still in
+    /// place, and search the tree once scripts have moved them.
+    pub fn record_landmark(&self, element: &Rc<Node>) {
+        let Some(parent) = element.parent.borrow().upgrade() else {
+            return;
+        };
+        let mut landmarks = self.landmarks.borrow_mut();
+        if Rc::ptr_eq(&parent, &self.root) {
+            if landmarks.document_element.upgrade().is_none() && element.tag_name().is_some() {
+                landmarks.document_element = Rc::downgrade(element);
+            }
+            return;
+        }
+        if !landmarks.document_element.upgrade().is_some_and(|html| Rc::ptr_eq(&html, &parent)) {
+            return;
+        }
+        match element.tag_name().map(|tag| tag.to_ascii_lowercase()).as_deref() {
+            Some("head") if landmarks.head.upgrade().is_none() => landmarks.head = Rc::downgrade(element),
+            Some("body" | "frameset") if landmarks.body.upgrade().is_none() => landmarks.body = Rc::downgrade(element),
+            _ => {}
+        }
+    }
+
+    /// Get the document element: the element child of the document,
+    /// usually `<html>`
     pub fn document_element(&self) -> Option<Rc<Node>> {
-        self.root.get_element_by_tag_name("html")
+        let recorded = self.landmarks.borrow().document_element.upgrade();
+        if let Some(element) = recorded.filter(|element| is_child_of(element, &self.root)) {
+            return Some(element);
+        }
+        self.root.children.borrow().iter().find(|child| child.tag_name().is_some()).cloned()
     }
 
-    /// Get the body element of the document
-    /// 
-    /// This is a convenience method to find the `<body>` element
-    /// which typically contains the visible content.
+    /// Get the first `<head>` child of the document element
+    pub fn head(&self) -> Option<Rc<Node>> {
+        let html = self.document_element()?;
+        let recorded = self.landmarks.borrow().head.upgrade();
+        if let Some(head) = recorded.filter(|head| is_child_of(head, &html)) {
+            return Some(head);
+        }
+        let children = html.children.borrow();
+        children.iter().find(|child| child.tag_name().is_some_and(|tag| tag.eq_ignore_ascii_case("head"))).cloned()
+    }
+
+    /// Get the body element: the first `<body>` or `<frameset>` child of
+    /// the document element
+    ///
+    /// Documents parsed from fragments without an `<html>` element use a
+    /// `<body>` at the top level.
     pub fn body(&self) -> Option<Rc<Node>> {
-        self.root.get_element_by_tag_name("body")
+        let html = self.document_element()?;
+        if html.tag_name().is_some_and(|tag| tag.eq_ignore_ascii_case("body")) {
+            return Some(html);
+        }
+        let recorded = self.landmarks.borrow().body.upgrade();
+        if let Some(body) = recorded.filter(|body| is_child_of(body, &html)) {
+            return Some(body);
+        }
+        let children = html.children.borrow();
+        children
+            .iter()
+            .find(|child| child.tag_name().is_some_and(|tag| tag.eq_ignore_ascii_case("body") || tag.eq_ignore_ascii_case("frameset")))
+            .cloned()
     }
 
     /// Get the next available node ID
     /// 
-    /// This is used internally by the HTML parser to assign unique IDs to nodes
+    /// This is used internally by the HTML parser to assign unique IDs to
+    /// nodes. Ids are unique across all documents.
     pub fn get_next_id(&self) -> u64 {
-        let id = *self.next_id.borrow();
-        *self.next_id.borrow_mut() += 1;
-        id
+        NEXT_NODE_ID.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Get the origin of this document
+    pub fn origin(&self) -> Origin {
+        self.origin.borrow().clone()
+    }
+
+    /// Set the origin of this document
+    ///
+    /// Called by the loader once the final (post-redirect) URL is known. A
+    /// sandboxed document gets an opaque origin instead, see
+    /// `SandboxFlags::origin_for`.
+    pub fn set_origin(&self, origin: Origin) {
+        *self.origin.borrow_mut() = match self.sandbox_flags.get() {
+            Some(flags) => flags.origin_for(origin),
+            None => origin,
+        };
+    }
+
+    /// Get what this document may do, if it is loaded in a sandboxed frame
+    pub fn sandbox_flags(&self) -> Option<sandboxing::SandboxFlags> {
+        self.sandbox_flags.get()
+    }
+
+    /// Sandbox this document, or lift its sandbox with `None`
+    ///
+    /// The origin is derived from the URL again under the new flags.
+    pub fn set_sandbox_flags(&self, flags: Option<sandboxing::SandboxFlags>) {
+        self.sandbox_flags.set(flags);
+        let origin = match self.url() {
+            Some(url) => Origin::from_url(&url),
+            None => self.origin(),
+        };
+        self.set_origin(origin);
+    }
+
+    /// Get the security policy of this document
+    pub fn security_policy(&self) -> SecurityPolicy {
+        self.security_policy.borrow().clone()
+    }
+
+    /// Replace the security policy of this document
+    ///
+    /// Use `SecurityPolicy::permissive()` to disable same-origin checks in tests.
+    pub fn set_security_policy(&self, policy: SecurityPolicy) {
+        *self.security_policy.borrow_mut() = policy;
+    }
+
+    /// Check whether this document is same-origin with another document
+    pub fn is_same_origin(&self, other: &Document) -> bool {
+        self.origin.borrow().is_same_origin(&other.origin.borrow())
+    }
+
+    /// Check whether this document may access the DOM of another document
+    ///
+    /// This is the gate for cross-document access such as iframe
+    /// `contentDocument` lookups.
+    pub fn check_dom_access(&self, other: &Document) -> Result<(), SecurityError> {
+        self.security_policy
+            .borrow()
+            .check_dom_access(&self.origin.borrow(), &other.origin.borrow())
     }
 }
 
@@ -246,6 +940,92 @@ mod tests {
         assert_eq!(doc.root.node_type, NodeType::Document);
     }
 
+    #[test]
+    fn test_document_origin() {
+        let doc = Document::new();
+        assert!(doc.origin().is_opaque());
+
+        let a = Document::new_with_url("https://example.com/index.html");
+        let b = Document::new_with_url("https://example.com/other.html");
+        let c = Document::new_with_url("https://evil.com/");
+
+        assert!(a.is_same_origin(&b));
+        assert!(a.check_dom_access(&b).is_ok());
+        assert!(a.check_dom_access(&c).is_err());
+
+        a.set_security_policy(SecurityPolicy::permissive());
+        assert!(a.check_dom_access(&c).is_ok());
+    }
+
+    #[test]
+    fn test_document_url_and_base_href() {
+        let doc = Document::new_with_url("https://example.com/docs/page.html");
+        assert_eq!(doc.url().as_deref(), Some("https://example.com/docs/page.html"));
+        assert!(!doc.origin().is_opaque());
+        assert_eq!(doc.base_href(), None);
+
+        let head = doc.create_element("head");
+        doc.root.append_child(&head);
+        head.append_child(&doc.create_element("base"));
+        let base = doc.create_element("base");
+        base.set_attribute("href", "/static/");
+        head.append_child(&base);
+        assert_eq!(doc.base_href().as_deref(), Some("/static/"));
+    }
+
+    #[test]
+    fn test_attribute_mutation() {
+        let mut attributes = HashMap::new();
+        attributes.insert("id".to_string(), "main".to_string());
+        let doc = Document::new();
+        let element = doc.create_node(NodeType::Element {
+            tag_name: "details".to_string(),
+            attributes,
+        });
+
+        assert_eq!(element.get_attribute("id"), Some("main".to_string()));
+        assert!(!element.has_attribute("open"));
+
+        assert!(element.toggle_attribute("open"));
+        assert!(element.has_attribute("open"));
+        element.set_attribute("id", "other");
+        assert_eq!(element.attributes().get("id"), Some(&"other".to_string()));
+
+        assert!(!element.toggle_attribute("open"));
+        element.remove_attribute("id");
+        assert!(element.attributes().is_empty());
+
+        let text = doc.create_text_node("hi");
+        text.set_attribute("id", "x");
+        assert!(!text.has_attribute("id"));
+    }
+
+    #[test]
+    fn test_namespaced_attributes() {
+        use namespaces::{SVG_NAMESPACE, XLINK_NAMESPACE};
+        let doc = Document::new();
+        let element = doc.create_element_ns(Some(SVG_NAMESPACE), "svg:use").unwrap();
+        assert_eq!(element.namespace_uri(), Some(SVG_NAMESPACE));
+        assert_eq!((element.prefix(), element.local_name()), (Some("svg"), Some("use")));
+        assert!(doc.create_element_ns(None, "svg:use").is_err());
+        assert!(doc.create_element("div").is_html_element());
+
+        element.set_attribute_ns(Some(XLINK_NAMESPACE), "xlink:href", "#a").unwrap();
+        element.set_attribute_ns(Some(XLINK_NAMESPACE), "other:href", "#b").unwrap();
+        assert_eq!(element.get_attribute_ns(Some(XLINK_NAMESPACE), "href").as_deref(), Some("#b"));
+        assert_eq!(element.get_attribute("xlink:href").as_deref(), Some("#b"));
+        assert!(!element.has_attribute("href") && !element.has_attribute_ns(None, "href"));
+
+        element.set_attribute("href", "plain");
+        assert_eq!(element.get_attribute_ns(Some(""), "href").as_deref(), Some("plain"));
+        assert_eq!(element.attributes_ns().len(), 2);
+        assert_eq!(element.attributes().len(), 2);
+
+        element.remove_attribute_ns(Some(XLINK_NAMESPACE), "href");
+        assert!(!element.has_attribute("xlink:href"));
+        assert!(element.set_attribute_ns(Some(XLINK_NAMESPACE), "xml:href", "x").is_err());
+    }
+
     #[test]
     fn test_create_element() {
         let doc = Document::new();
@@ -294,4 +1074,30 @@ mod tests {
         
         assert_eq!(div.text_content(), "Hello World!");
     }
+
+    #[test]
+    fn test_text_content_setter_and_normalize() {
+        let doc = Document::new();
+        let div = doc.create_element("div");
+        let span = doc.create_element("span");
+        div.append_child(&span);
+        for data in ["a", "", "b"] {
+            div.append_child(&doc.create_text_node(data));
+        }
+        span.append_child(&doc.create_text_node("c"));
+
+        let removed = div.normalize();
+        assert_eq!(removed.len(), 2);
+        assert_eq!(div.children.borrow().len(), 2);
+        assert_eq!(div.text_content(), "cab");
+
+        let text = div.children.borrow()[1].clone();
+        assert_eq!(text.set_text_content("changed", &doc).len(), 0);
+        assert_eq!(text.character_data().as_deref(), Some("changed"));
+
+        let removed = div.set_text_content("new", &doc);
+        assert_eq!(removed.len(), 2);
+        assert!(span.parent.borrow().upgrade().is_none());
+        assert_eq!(div.text_content(), "new");
+    }
 }
@@ -0,0 +1,452 @@
+//! # Media Elements
+//!
+//! This module implements the playback state of `<video>` and `<audio>`
+//! elements: `paused`, `currentTime`, `play()` and `pause()` and the events
+//! they fire. Decoding is left to a `MediaBackend`.
+//!
+//! ## Design Principles
+//!
+//! 1. **The DOM Owns the Clock**: The playback position lives on the node
+//!    and is advanced by the frame, so every backend sees the same timeline
+//!    and scripts never wait on a decoder to read `currentTime`.
+//!
+//! 2. **Pluggable Decoding**: A backend only answers what a source is
+//!    (duration, size) and what it shows at a time. The stub backend plays
+//!    silence with no frames, and a real decoder can replace it without
+//!    touching the DOM, layout or paint.
+//!
+//! 3. **Queued Events**: Like browsers, media events are queued as tasks
+//!    rather than fired from inside `play()`, so listeners never run in the
+//!    middle of a script's call.
+
+use std::fmt;
+use std::rc::{Rc, Weak};
+use crate::Node;
+use crate::dom_event_integration::DomEventManager;
+use crate::event_types::Event;
+
+/// The kind of media element a node is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MediaKind {
+    Video,
+    Audio,
+}
+
+impl MediaKind {
+    /// Get the media kind of a node, if it is a media element
+    pub fn of(node: &Node) -> Option<MediaKind> {
+        match node.tag_name()?.to_ascii_lowercase().as_str() {
+            "video" => Some(MediaKind::Video),
+            "audio" => Some(MediaKind::Audio),
+            _ => None,
+        }
+    }
+}
+
+/// Why a source cannot be played, as in `MediaError.code`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MediaError {
+    Aborted,
+    Network,
+    Decode,
+    SourceNotSupported,
+}
+
+impl MediaError {
+    /// Get the `MediaError.code` of the error
+    pub fn code(&self) -> u16 {
+        match self {
+            MediaError::Aborted => 1,
+            MediaError::Network => 2,
+            MediaError::Decode => 3,
+            MediaError::SourceNotSupported => 4,
+        }
+    }
+}
+
+impl fmt::Display for MediaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MediaError::Aborted => write!(f, "Media loading aborted"),
+            MediaError::Network => write!(f, "Media could not be fetched"),
+            MediaError::Decode => write!(f, "Media could not be decoded"),
+            MediaError::SourceNotSupported => write!(f, "Media source not supported"),
+        }
+    }
+}
+
+impl std::error::Error for MediaError {}
+
+/// What a backend knows about a source once it is loaded
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaMetadata {
+    /// Duration in seconds; infinite for a stream, NaN if unknown
+    pub duration: f64,
+    /// Natural video size in pixels, `None` for audio
+    pub video_size: Option<(u32, u32)>,
+}
+
+/// A decoded video frame, as RGBA pixels
+#[derive(Debug, Clone, PartialEq)]
+pub struct VideoFrame {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// A decoder for media sources
+pub trait MediaBackend: fmt::Debug {
+    /// Load a source and report its metadata
+    fn load(&self, src: &str) -> Result<MediaMetadata, MediaError>;
+
+    /// Get the frame a video shows at a playback position
+    fn frame_at(&self, src: &str, time: f64) -> Option<VideoFrame>;
+}
+
+/// The stub backend: every source is silence of unknown length with no
+/// frames, so videos show their poster
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullMediaBackend;
+
+impl MediaBackend for NullMediaBackend {
+    fn load(&self, _src: &str) -> Result<MediaMetadata, MediaError> {
+        Ok(MediaMetadata { duration: f64::NAN, video_size: None })
+    }
+
+    fn frame_at(&self, _src: &str, _time: f64) -> Option<VideoFrame> {
+        None
+    }
+}
+
+/// `HTMLMediaElement.readyState`
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub enum ReadyState {
+    #[default]
+    HaveNothing,
+    HaveMetadata,
+    HaveEnoughData,
+}
+
+/// Runtime state of a media element
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaState {
+    pub paused: bool,
+    /// Playback position in seconds
+    pub current_time: f64,
+    pub ended: bool,
+    pub ready_state: ReadyState,
+    /// The source metadata was loaded for
+    pub loaded_src: Option<String>,
+    pub metadata: Option<MediaMetadata>,
+    pub error: Option<MediaError>,
+}
+
+impl Default for MediaState {
+    fn default() -> Self {
+        MediaState {
+            paused: true,
+            current_time: 0.0,
+            ended: false,
+            ready_state: ReadyState::HaveNothing,
+            loaded_src: None,
+            metadata: None,
+            error: None,
+        }
+    }
+}
+
+impl Node {
+    /// Get the media kind of this node
+    pub fn media_kind(&self) -> Option<MediaKind> {
+        MediaKind::of(self)
+    }
+
+    /// Get a copy of the playback state of a media element
+    pub fn media_state(&self) -> MediaState {
+        self.media_state.borrow().clone()
+    }
+
+    /// Check whether a media element is paused
+    pub fn paused(&self) -> bool {
+        self.media_state.borrow().paused
+    }
+
+    /// Get the playback position of a media element, in seconds
+    pub fn current_time(&self) -> f64 {
+        self.media_state.borrow().current_time
+    }
+
+    /// Get the duration of a media element, NaN until it is known
+    pub fn duration(&self) -> f64 {
+        self.media_state.borrow().metadata.as_ref().map_or(f64::NAN, |metadata| metadata.duration)
+    }
+
+    /// Get the natural size of a video once its metadata is loaded
+    pub fn video_size(&self) -> Option<(u32, u32)> {
+        self.media_state.borrow().metadata.as_ref().and_then(|metadata| metadata.video_size)
+    }
+}
+
+/// Drives media elements: loads their sources through a backend, advances
+/// their clocks and queues the events they fire
+#[derive(Debug)]
+pub struct MediaController {
+    backend: Box<dyn MediaBackend>,
+    /// Elements that are playing
+    playing: Vec<Weak<Node>>,
+    /// Events waiting to be fired, in order
+    pending: Vec<(Rc<Node>, &'static str)>,
+}
+
+impl Default for MediaController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MediaController {
+    /// Create a controller using the stub backend
+    pub fn new() -> Self {
+        Self::with_backend(Box::new(NullMediaBackend))
+    }
+
+    /// Create a controller using a decoder
+    pub fn with_backend(backend: Box<dyn MediaBackend>) -> Self {
+        MediaController { backend, playing: Vec::new(), pending: Vec::new() }
+    }
+
+    /// Replace the decoder
+    pub fn set_backend(&mut self, backend: Box<dyn MediaBackend>) {
+        self.backend = backend;
+    }
+
+    /// Load the metadata of an element's source if it changed
+    ///
+    /// Fires `loadedmetadata` or `error`.
+    pub fn load(&mut self, node: &Rc<Node>) -> Result<(), MediaError> {
+        let src = node.get_attribute("src").unwrap_or_default();
+        if src.trim().is_empty() {
+            return Err(MediaError::SourceNotSupported);
+        }
+        if node.media_state.borrow().loaded_src.as_deref() == Some(src.as_str()) {
+            return node.media_state.borrow().error.map_or(Ok(()), Err);
+        }
+        let result = self.backend.load(&src);
+        let mut state = node.media_state.borrow_mut();
+        *state = MediaState { paused: state.paused, loaded_src: Some(src), ..MediaState::default() };
+        match result {
+            Ok(metadata) => {
+                state.metadata = Some(metadata);
+                state.ready_state = ReadyState::HaveEnoughData;
+                drop(state);
+                self.queue(node, "loadedmetadata");
+                Ok(())
+            }
+            Err(error) => {
+                state.error = Some(error);
+                drop(state);
+                self.queue(node, "error");
+                Err(error)
+            }
+        }
+    }
+
+    /// Start playing a media element, as in `play()`
+    ///
+    /// Fires `play` and then `playing` once the source is loaded.
+    pub fn play(&mut self, node: &Rc<Node>) -> Result<(), MediaError> {
+        let result = self.load(node);
+        if let Err(error) = result {
+            println!("⚠️  Cannot play <{}>: {}", node.tag_name().unwrap_or_default(), error);
+            return Err(error);
+        }
+        let was_paused = {
+            let mut state = node.media_state.borrow_mut();
+            if state.ended {
+                state.current_time = 0.0;
+                state.ended = false;
+            }
+            std::mem::replace(&mut state.paused, false)
+        };
+        if was_paused {
+            self.queue(node, "play");
+            self.playing.push(Rc::downgrade(node));
+        }
+        self.queue(node, "playing");
+        Ok(())
+    }
+
+    /// Pause a media element, as in `pause()`
+    ///
+    /// Fires `timeupdate` and `pause` if it was playing.
+    pub fn pause(&mut self, node: &Rc<Node>) {
+        if std::mem::replace(&mut node.media_state.borrow_mut().paused, true) {
+            return;
+        }
+        self.playing.retain(|playing| playing.upgrade().is_some_and(|playing| !Rc::ptr_eq(&playing, node)));
+        self.queue(node, "timeupdate");
+        self.queue(node, "pause");
+    }
+
+    /// Move the playback position, as in setting `currentTime`
+    ///
+    /// Fires `seeking`, `timeupdate` and `seeked`.
+    pub fn seek(&mut self, node: &Rc<Node>, time: f64) {
+        if !time.is_finite() {
+            return;
+        }
+        {
+            let mut state = node.media_state.borrow_mut();
+            let duration = state.metadata.as_ref().map_or(f64::NAN, |metadata| metadata.duration);
+            state.current_time = if duration.is_finite() { time.clamp(0.0, duration) } else { time.max(0.0) };
+            state.ended = false;
+        }
+        self.queue(node, "seeking");
+        self.queue(node, "timeupdate");
+        self.queue(node, "seeked");
+    }
+
+    /// Advance the clocks of playing elements by `elapsed` seconds
+    ///
+    /// Called once per frame. Elements that reach their end are paused and
+    /// fire `ended`.
+    pub fn advance(&mut self, elapsed: f64) {
+        let mut still_playing = Vec::new();
+        for node in std::mem::take(&mut self.playing).iter().filter_map(Weak::upgrade) {
+            let ended = {
+                let mut state = node.media_state.borrow_mut();
+                if state.paused {
+                    continue;
+                }
+                state.current_time += elapsed;
+                let duration = state.metadata.as_ref().map_or(f64::NAN, |metadata| metadata.duration);
+                if duration.is_finite() && state.current_time >= duration {
+                    state.current_time = duration;
+                    state.ended = true;
+                    state.paused = true;
+                }
+                state.ended
+            };
+            self.queue(&node, "timeupdate");
+            if ended {
+                self.queue(&node, "pause");
+                self.queue(&node, "ended");
+            } else {
+                still_playing.push(Rc::downgrade(&node));
+            }
+        }
+        self.playing = still_playing;
+    }
+
+    /// Get the frame a video shows now, if the backend decoded one
+    pub fn current_frame(&self, node: &Node) -> Option<VideoFrame> {
+        let state = node.media_state.borrow();
+        self.backend.frame_at(state.loaded_src.as_deref()?, state.current_time)
+    }
+
+    /// Check whether any media event is waiting
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Take the queued events, in order
+    ///
+    /// Lets the caller release the controller before firing them with
+    /// `fire_media_events`, so listeners may play and pause elements.
+    pub fn take_events(&mut self) -> Vec<(Rc<Node>, &'static str)> {
+        std::mem::take(&mut self.pending)
+    }
+
+    /// Fire the queued events, in order
+    ///
+    /// Returns the number of events fired.
+    pub fn dispatch_events(&mut self, manager: &mut DomEventManager) -> usize {
+        fire_media_events(self.take_events(), manager)
+    }
+
+    fn queue(&mut self, node: &Rc<Node>, event_type: &'static str) {
+        self.pending.push((Rc::clone(node), event_type));
+    }
+}
+
+/// Fire media events taken from a controller
+///
+/// Media events do not bubble and cannot be cancelled. Returns the number
+/// of events fired.
+pub fn fire_media_events(events: Vec<(Rc<Node>, &'static str)>, manager: &mut DomEventManager) -> usize {
+    for (node, event_type) in &events {
+        let mut event = Event::new(event_type, false, false);
+        event.is_trusted = true;
+        manager.dispatch_event(node, event);
+    }
+    events.len()
+}
+
+/// Get the poster image URL of a video
+pub fn poster(node: &Node) -> Option<String> {
+    (node.media_kind() == Some(MediaKind::Video))
+        .then(|| node.get_attribute("poster"))
+        .flatten()
+        .filter(|poster| !poster.trim().is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Document;
+
+    #[derive(Debug)]
+    struct ClipBackend;
+
+    impl MediaBackend for ClipBackend {
+        fn load(&self, src: &str) -> Result<MediaMetadata, MediaError> {
+            match src {
+                "clip.webm" => Ok(MediaMetadata { duration: 2.0, video_size: Some((640, 360)) }),
+                _ => Err(MediaError::SourceNotSupported),
+            }
+        }
+
+        fn frame_at(&self, _src: &str, _time: f64) -> Option<VideoFrame> {
+            None
+        }
+    }
+
+    #[test]
+    fn test_play_pause_and_end_fire_events() {
+        let document = Rc::new(Document::new());
+        let video = document.create_element("video");
+        video.set_attribute("src", "clip.webm");
+        document.root.append_child(&video);
+
+        let mut manager = DomEventManager::new();
+        manager.set_document(Rc::clone(&document));
+        manager.enable_dispatch_log();
+        let mut controller = MediaController::with_backend(Box::new(ClipBackend));
+
+        assert!(video.paused());
+        controller.play(&video).unwrap();
+        assert!(!video.paused());
+        assert_eq!(video.video_size(), Some((640, 360)));
+
+        // Nothing fires until the queued events are dispatched
+        assert!(manager.take_dispatch_log().is_empty());
+        assert_eq!(controller.dispatch_events(&mut manager), 3);
+        assert_eq!(manager.take_dispatch_log(), vec!["loadedmetadata", "play", "playing"]);
+
+        controller.advance(0.5);
+        controller.pause(&video);
+        controller.dispatch_events(&mut manager);
+        assert_eq!(video.current_time(), 0.5);
+        assert_eq!(manager.take_dispatch_log(), vec!["timeupdate", "timeupdate", "pause"]);
+
+        controller.play(&video).unwrap();
+        controller.advance(5.0);
+        controller.dispatch_events(&mut manager);
+        assert_eq!(video.current_time(), 2.0);
+        assert!(video.paused() && video.media_state().ended);
+        assert_eq!(manager.take_dispatch_log(), vec!["play", "playing", "timeupdate", "pause", "ended"]);
+
+        video.set_attribute("src", "clip.mkv");
+        assert_eq!(controller.play(&video), Err(MediaError::SourceNotSupported));
+    }
+}
@@ -0,0 +1,124 @@
+//! # Mutation Notifications
+//!
+//! This module announces changes to DOM trees: children added or removed,
+//! attributes set or removed and character data replaced. Caches derived
+//! from the tree, such as live collections, compare the mutation
+//! generation to know when to recompute, and observers are called with
+//! every mutation.
+//!
+//! ## Design Principles
+//!
+//! 1. **Generation Counter**: Every mutation increments a counter. A cache
+//!    that stored the generation it was computed at is valid exactly while
+//!    the counter has not moved, which makes invalidation free.
+//!
+//! 2. **Notified by the Tree**: The `Node` methods that mutate the tree
+//!    notify on their own, so callers cannot forget. Code that edits
+//!    `children` directly must call `notify` itself.
+//!
+//! 3. **Per Thread**: DOM trees are `Rc` based and never leave their
+//!    thread, so the counter and observers are thread-local. Mutations of
+//!    one document invalidate caches of another document on the same
+//!    thread, which is correct, only conservative.
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+/// A change to a DOM tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mutation {
+    /// Children of the node were added or removed
+    ChildList { parent: u64 },
+    /// An attribute of the element was set or removed
+    Attribute { element: u64, name: String },
+    /// The data of a text node was replaced
+    CharacterData { node: u64 },
+}
+
+/// Identifier of a registered mutation observer
+pub type ObserverId = u64;
+
+type Observer = Rc<dyn Fn(&Mutation)>;
+
+thread_local! {
+    static GENERATION: Cell<u64> = const { Cell::new(0) };
+    static NEXT_OBSERVER_ID: Cell<ObserverId> = const { Cell::new(1) };
+    static OBSERVERS: RefCell<Vec<(ObserverId, Observer)>> = RefCell::new(Vec::new());
+}
+
+/// Get the number of mutations so far on this thread
+pub fn generation() -> u64 {
+    GENERATION.with(Cell::get)
+}
+
+/// Announce a mutation to caches and observers
+///
+/// Observers may read the tree, but must not be called while a `children`
+/// or attribute borrow is held.
+pub fn notify(mutation: Mutation) {
+    GENERATION.with(|generation| generation.set(generation.get() + 1));
+    let observers: Vec<Observer> = OBSERVERS.with(|observers| observers.borrow().iter().map(|(_, observer)| Rc::clone(observer)).collect());
+    for observer in observers {
+        observer(&mutation);
+    }
+}
+
+/// Call `callback` with every mutation until `unobserve` is called
+pub fn observe(callback: impl Fn(&Mutation) + 'static) -> ObserverId {
+    let id = NEXT_OBSERVER_ID.with(|next| {
+        let id = next.get();
+        next.set(id + 1);
+        id
+    });
+    OBSERVERS.with(|observers| observers.borrow_mut().push((id, Rc::new(callback))));
+    id
+}
+
+/// Stop calling an observer, returning whether it was registered
+pub fn unobserve(id: ObserverId) -> bool {
+    OBSERVERS.with(|observers| {
+        let mut observers = observers.borrow_mut();
+        let before = observers.len();
+        observers.retain(|(observer_id, _)| *observer_id != id);
+        observers.len() != before
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Document;
+
+    #[test]
+    fn test_mutations_are_announced() {
+        let document = Document::new();
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let id = observe({
+            let seen = Rc::clone(&seen);
+            move |mutation| seen.borrow_mut().push(mutation.clone())
+        });
+
+        let before = generation();
+        let div = document.create_element("div");
+        document.root.append_child(&div);
+        div.set_attribute("class", "a");
+        let text = document.create_text_node("x");
+        div.append_child(&text);
+        text.set_character_data("y");
+        assert_eq!(generation(), before + 4);
+        assert_eq!(
+            *seen.borrow(),
+            vec![
+                Mutation::ChildList { parent: document.root.id },
+                Mutation::Attribute { element: div.id, name: "class".to_string() },
+                Mutation::ChildList { parent: div.id },
+                Mutation::CharacterData { node: text.id },
+            ]
+        );
+
+        assert!(unobserve(id));
+        div.remove_attribute("class");
+        assert_eq!(seen.borrow().len(), 4);
+        assert_eq!(generation(), before + 5);
+    }
+}
@@ -0,0 +1,147 @@
+//! # Namespaces
+//!
+//! This module holds the namespace URIs the DOM knows about and the
+//! qualified names of namespaced elements and attributes, for
+//! `createElementNS`, `setAttributeNS` and friends.
+//!
+//! ## Design Principles
+//!
+//! 1. **Null Namespace Is `None`**: Attributes set with `setAttribute` and
+//!    attributes from the HTML parser have no namespace; an empty namespace
+//!    URI is treated the same way, as the DOM specification requires.
+//!
+//! 2. **Validate and Extract**: Qualified names are split into prefix and
+//!    local name and checked against the namespace in one place, with the
+//!    same errors the specification names.
+//!
+//! 3. **Parsed Elements Are HTML**: Elements created by the parser and by
+//!    `createElement` are in the HTML namespace until foreign content
+//!    (SVG, MathML) is parsed.
+
+use std::fmt;
+
+pub const HTML_NAMESPACE: &str = "http://www.w3.org/1999/xhtml";
+pub const SVG_NAMESPACE: &str = "http://www.w3.org/2000/svg";
+pub const MATHML_NAMESPACE: &str = "http://www.w3.org/1998/Math/MathML";
+pub const XLINK_NAMESPACE: &str = "http://www.w3.org/1999/xlink";
+pub const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+pub const XMLNS_NAMESPACE: &str = "http://www.w3.org/2000/xmlns/";
+
+/// A namespace, prefix and local name, as elements and attributes have
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct QualifiedName {
+    pub namespace: Option<String>,
+    pub prefix: Option<String>,
+    pub local_name: String,
+}
+
+impl QualifiedName {
+    /// A name without namespace or prefix
+    pub fn local(local_name: &str) -> Self {
+        QualifiedName { namespace: None, prefix: None, local_name: local_name.to_string() }
+    }
+
+    /// The name with its prefix, as `getAttribute` and `tagName` use it
+    pub fn qualified_name(&self) -> String {
+        match &self.prefix {
+            Some(prefix) => format!("{}:{}", prefix, self.local_name),
+            None => self.local_name.clone(),
+        }
+    }
+
+    /// Check whether the name is in the given namespace, `None` being the
+    /// null namespace
+    pub fn is_in(&self, namespace: Option<&str>) -> bool {
+        self.namespace.as_deref() == namespace
+    }
+}
+
+/// A qualified name that is invalid or does not fit its namespace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NamespaceError {
+    /// The name is not a valid `QName`
+    InvalidCharacter(String),
+    /// The prefix and namespace do not go together
+    Namespace(String),
+}
+
+impl NamespaceError {
+    /// The name of the `DOMException` scripts see
+    pub fn name(&self) -> &'static str {
+        match self {
+            NamespaceError::InvalidCharacter(_) => "InvalidCharacterError",
+            NamespaceError::Namespace(_) => "NamespaceError",
+        }
+    }
+}
+
+impl fmt::Display for NamespaceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NamespaceError::InvalidCharacter(name) => write!(f, "'{}' is not a valid qualified name", name),
+            NamespaceError::Namespace(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for NamespaceError {}
+
+/// Split a qualified name and check it against the namespace, as the
+/// DOM's "validate and extract" steps do
+pub fn validate_and_extract(namespace: Option<&str>, qualified_name: &str) -> Result<QualifiedName, NamespaceError> {
+    let namespace = namespace.filter(|namespace| !namespace.is_empty());
+    let (prefix, local_name) = match qualified_name.split_once(':') {
+        Some((prefix, local_name)) => (Some(prefix), local_name),
+        None => (None, qualified_name),
+    };
+    if !prefix.is_none_or(is_ncname) || !is_ncname(local_name) {
+        return Err(NamespaceError::InvalidCharacter(qualified_name.to_string()));
+    }
+    let mismatch = |message: &str| Err(NamespaceError::Namespace(format!("'{}': {}", qualified_name, message)));
+    if prefix.is_some() && namespace.is_none() {
+        return mismatch("a prefix needs a namespace");
+    }
+    if prefix == Some("xml") && namespace != Some(XML_NAMESPACE) {
+        return mismatch("the xml prefix is reserved for the XML namespace");
+    }
+    let is_xmlns = qualified_name == "xmlns" || prefix == Some("xmlns");
+    if is_xmlns != (namespace == Some(XMLNS_NAMESPACE)) {
+        return mismatch("xmlns is used for, and only for, the XMLNS namespace");
+    }
+    Ok(QualifiedName {
+        namespace: namespace.map(str::to_string),
+        prefix: prefix.map(str::to_string),
+        local_name: local_name.to_string(),
+    })
+}
+
+/// Check whether a name is an XML `NCName`, a name without colons
+fn is_ncname(name: &str) -> bool {
+    let mut chars = name.chars();
+    let Some(first) = chars.next() else {
+        return false;
+    };
+    (first.is_ascii_alphabetic() || first == '_' || !first.is_ascii())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_') || !c.is_ascii())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_and_extract() {
+        let name = validate_and_extract(Some(XLINK_NAMESPACE), "xlink:href").unwrap();
+        assert_eq!(name.prefix.as_deref(), Some("xlink"));
+        assert_eq!(name.local_name, "href");
+        assert_eq!(name.qualified_name(), "xlink:href");
+        assert!(validate_and_extract(Some(""), "id").unwrap().is_in(None));
+        assert!(validate_and_extract(Some(XMLNS_NAMESPACE), "xmlns").is_ok());
+
+        assert_eq!(validate_and_extract(None, "1abc").unwrap_err().name(), "InvalidCharacterError");
+        assert_eq!(validate_and_extract(Some(SVG_NAMESPACE), "a:b:c").unwrap_err().name(), "InvalidCharacterError");
+        for (namespace, name) in [(None, "x:y"), (Some(SVG_NAMESPACE), "xml:lang"), (Some(SVG_NAMESPACE), "xmlns"), (Some(XMLNS_NAMESPACE), "foo")] {
+            assert_eq!(validate_and_extract(namespace, name).unwrap_err().name(), "NamespaceError", "{}", name);
+        }
+    }
+}
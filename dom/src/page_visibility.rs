@@ -0,0 +1,109 @@
+//! # Page Visibility
+//!
+//! This module keeps whether each document is visible to the user, for
+//! `document.visibilityState` and `visibilitychange`. A document is hidden
+//! while its tab is in the background or its window is minimized.
+//!
+//! ## Design Principles
+//!
+//! 1. **Visible Until Hidden**: Only hidden documents are recorded, so a
+//!    document is visible unless the shell says otherwise.
+//!
+//! 2. **Keyed by Owner**: Like the top layer, the state is kept under the
+//!    id of the document that owns the nodes, so the renderer, which only
+//!    holds the layout tree, reaches it from any node.
+//!
+//! 3. **Callers Fire Events**: `set_visibility_state` reports whether the
+//!    state changed, and the caller fires `visibilitychange` at the
+//!    document, through the DOM event manager for the user agent or
+//!    through the script bindings for scripts.
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt;
+use crate::Node;
+
+thread_local! {
+    static HIDDEN_DOCUMENTS: RefCell<HashSet<u64>> = RefCell::new(HashSet::new());
+}
+
+/// The `document.visibilityState` of a document
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum VisibilityState {
+    /// At least partly visible on some screen
+    #[default]
+    Visible,
+    /// In a background tab or a minimized window
+    Hidden,
+}
+
+impl VisibilityState {
+    /// Get the value `document.visibilityState` returns
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VisibilityState::Visible => "visible",
+            VisibilityState::Hidden => "hidden",
+        }
+    }
+
+    /// Get the state of a page that is hidden or not
+    pub fn from_hidden(hidden: bool) -> Self {
+        if hidden { VisibilityState::Hidden } else { VisibilityState::Visible }
+    }
+}
+
+impl fmt::Display for VisibilityState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Get the visibility state of the document that owns `node`
+pub fn visibility_state(node: &Node) -> VisibilityState {
+    let owner = node.owner_document_id().unwrap_or(0);
+    VisibilityState::from_hidden(HIDDEN_DOCUMENTS.with(|hidden| hidden.borrow().contains(&owner)))
+}
+
+/// Set the visibility state of the document that owns `node`
+///
+/// Returns whether the state changed, in which case the caller fires
+/// `visibilitychange` at the document.
+pub fn set_visibility_state(node: &Node, state: VisibilityState) -> bool {
+    let owner = node.owner_document_id().unwrap_or(0);
+    HIDDEN_DOCUMENTS.with(|hidden| {
+        let mut hidden = hidden.borrow_mut();
+        match state {
+            VisibilityState::Hidden => hidden.insert(owner),
+            VisibilityState::Visible => hidden.remove(&owner),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dom_event_integration::DomEventManager;
+    use crate::Document;
+
+    #[test]
+    fn test_visibility_is_kept_per_document() {
+        let document = Document::new();
+        let body = document.create_element("body");
+        document.root.append_child(&body);
+        let other = Document::new();
+        assert_eq!(visibility_state(&body), VisibilityState::Visible);
+
+        assert!(set_visibility_state(&document.root, VisibilityState::Hidden));
+        assert!(!set_visibility_state(&body, VisibilityState::Hidden));
+        assert_eq!(visibility_state(&body).as_str(), "hidden");
+        assert_eq!(visibility_state(&other.root), VisibilityState::Visible);
+
+        // The event manager fires `visibilitychange` at the document
+        let mut manager = DomEventManager::new();
+        manager.enable_dispatch_log();
+        assert!(manager.set_visibility_state(&body, VisibilityState::Visible));
+        assert!(!manager.set_visibility_state(&body, VisibilityState::Visible));
+        assert_eq!(manager.take_dispatch_log(), vec!["visibilitychange"]);
+        assert_eq!(visibility_state(&document.root), VisibilityState::Visible);
+    }
+}
@@ -0,0 +1,238 @@
+//! # Pointer Capture
+//!
+//! This module keeps the state behind `setPointerCapture()`,
+//! `releasePointerCapture()` and `hasPointerCapture()`. While an element
+//! has captured a pointer, the pointer events of that pointer are targeted
+//! at it instead of the element under the pointer, which is how sliders
+//! and drawing surfaces keep receiving moves outside their box.
+//!
+//! ## Design Principles
+//!
+//! 1. **Pending, Then Active**: Capture changes take effect at the next
+//!    pointer event of the pointer, as in Pointer Events. That is when
+//!    `gotpointercapture` and `lostpointercapture` fire.
+//!
+//! 2. **Released With the Pointer**: Capture ends implicitly when the
+//!    pointer goes up or is cancelled, and when the capturing element
+//!    leaves the document.
+//!
+//! 3. **One State, Many Handles**: `PointerCaptures` is a handle. The event
+//!    manager and the script bindings clone it, so a capture set by script
+//!    retargets the events the user agent dispatches.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use crate::Node;
+
+/// The `pointerId` of the mouse, which is always active
+pub const MOUSE_POINTER_ID: i32 = 1;
+
+/// A capture request that cannot be honored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointerCaptureError {
+    /// No active pointer has the id
+    NotFound(i32),
+    /// The element is not connected
+    InvalidState,
+}
+
+impl PointerCaptureError {
+    /// The name of the `DOMException` scripts see
+    pub fn name(&self) -> &'static str {
+        match self {
+            PointerCaptureError::NotFound(_) => "NotFoundError",
+            PointerCaptureError::InvalidState => "InvalidStateError",
+        }
+    }
+}
+
+impl fmt::Display for PointerCaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PointerCaptureError::NotFound(pointer_id) => write!(f, "no active pointer with id {}", pointer_id),
+            PointerCaptureError::InvalidState => write!(f, "the element is not connected"),
+        }
+    }
+}
+
+impl std::error::Error for PointerCaptureError {}
+
+/// The elements losing and getting a pointer's capture
+#[derive(Debug, Default)]
+pub struct CaptureChange {
+    /// Receives `lostpointercapture`
+    pub lost: Option<Rc<Node>>,
+    /// Receives `gotpointercapture`
+    pub got: Option<Rc<Node>>,
+}
+
+#[derive(Debug, Default)]
+struct PointerRecord {
+    /// Whether a button is pressed or the contact is down
+    pressed: bool,
+    /// The element capture moves to at the next pointer event
+    pending: Option<Rc<Node>>,
+    /// The element events of the pointer are targeted at
+    capture: Option<Rc<Node>>,
+}
+
+#[derive(Debug, Default)]
+struct CaptureState {
+    pointers: HashMap<i32, PointerRecord>,
+}
+
+impl CaptureState {
+    fn record(&mut self, pointer_id: i32) -> Option<&mut PointerRecord> {
+        if pointer_id == MOUSE_POINTER_ID {
+            return Some(self.pointers.entry(pointer_id).or_default());
+        }
+        self.pointers.get_mut(&pointer_id)
+    }
+}
+
+/// The pointer capture state of a document
+///
+/// Cloning shares the state.
+#[derive(Debug, Clone, Default)]
+pub struct PointerCaptures {
+    state: Rc<RefCell<CaptureState>>,
+}
+
+impl PointerCaptures {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check whether a pointer is active: the mouse always is, other
+    /// pointers between going down and going up
+    pub fn is_active(&self, pointer_id: i32) -> bool {
+        pointer_id == MOUSE_POINTER_ID || self.state.borrow().pointers.contains_key(&pointer_id)
+    }
+
+    /// Record that a pointer went down, activating it
+    pub fn pointer_down(&self, pointer_id: i32) {
+        self.state.borrow_mut().pointers.entry(pointer_id).or_default().pressed = true;
+    }
+
+    /// Record that a pointer went up or was cancelled
+    ///
+    /// Its capture is released implicitly; call `process_pending` next to
+    /// fire `lostpointercapture`. Pointers other than the mouse stay known
+    /// until then, and are deactivated by it.
+    pub fn pointer_up(&self, pointer_id: i32) {
+        if let Some(record) = self.state.borrow_mut().pointers.get_mut(&pointer_id) {
+            record.pressed = false;
+            record.pending = None;
+        }
+    }
+
+    /// Capture a pointer for an element, like `setPointerCapture()`
+    ///
+    /// Does nothing while the pointer has no button pressed.
+    pub fn set_capture(&self, element: &Rc<Node>, pointer_id: i32) -> Result<(), PointerCaptureError> {
+        let mut state = self.state.borrow_mut();
+        let record = state.record(pointer_id).ok_or(PointerCaptureError::NotFound(pointer_id))?;
+        if !element.is_connected() {
+            return Err(PointerCaptureError::InvalidState);
+        }
+        if record.pressed {
+            record.pending = Some(Rc::clone(element));
+        }
+        Ok(())
+    }
+
+    /// Release a pointer captured by an element, like
+    /// `releasePointerCapture()`
+    pub fn release_capture(&self, element: &Rc<Node>, pointer_id: i32) -> Result<(), PointerCaptureError> {
+        let mut state = self.state.borrow_mut();
+        let record = state.record(pointer_id).ok_or(PointerCaptureError::NotFound(pointer_id))?;
+        if record.pending.as_ref().is_some_and(|pending| Rc::ptr_eq(pending, element)) {
+            record.pending = None;
+        }
+        Ok(())
+    }
+
+    /// Check whether an element has captured a pointer or will at its next
+    /// event, like `hasPointerCapture()`
+    pub fn has_capture(&self, element: &Rc<Node>, pointer_id: i32) -> bool {
+        self.state
+            .borrow()
+            .pointers
+            .get(&pointer_id)
+            .and_then(|record| record.pending.as_ref())
+            .is_some_and(|pending| Rc::ptr_eq(pending, element))
+    }
+
+    /// Get the element the events of a pointer are targeted at, if any
+    pub fn capture_target(&self, pointer_id: i32) -> Option<Rc<Node>> {
+        self.state.borrow().pointers.get(&pointer_id).and_then(|record| record.capture.clone())
+    }
+
+    /// Make the pending capture of a pointer its capture, before one of
+    /// its events is dispatched or after it went up
+    ///
+    /// A capturing element that left the document loses the capture.
+    pub fn process_pending(&self, pointer_id: i32) -> CaptureChange {
+        let mut state = self.state.borrow_mut();
+        let Some(record) = state.pointers.get_mut(&pointer_id) else {
+            return CaptureChange::default();
+        };
+        if record.pending.as_ref().is_some_and(|pending| !pending.is_connected()) {
+            record.pending = None;
+        }
+        let same = match (&record.capture, &record.pending) {
+            (Some(capture), Some(pending)) => Rc::ptr_eq(capture, pending),
+            (None, None) => true,
+            _ => false,
+        };
+        let change = if same {
+            CaptureChange::default()
+        } else {
+            let lost = std::mem::replace(&mut record.capture, record.pending.clone());
+            CaptureChange { lost, got: record.capture.clone() }
+        };
+        if !record.pressed && pointer_id != MOUSE_POINTER_ID {
+            state.pointers.remove(&pointer_id);
+        }
+        change
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Document;
+
+    #[test]
+    fn test_capture_takes_effect_at_the_next_event_and_ends_with_the_pointer() {
+        let document = Document::new();
+        let slider = document.create_element("div");
+        document.root.append_child(&slider);
+        let captures = PointerCaptures::new();
+        let touch = 7;
+
+        assert_eq!(captures.set_capture(&slider, touch), Err(PointerCaptureError::NotFound(touch)));
+        assert_eq!(captures.set_capture(&document.create_element("div"), MOUSE_POINTER_ID), Err(PointerCaptureError::InvalidState));
+
+        captures.pointer_down(touch);
+        captures.set_capture(&slider, touch).unwrap();
+        assert!(captures.has_capture(&slider, touch));
+        assert!(captures.capture_target(touch).is_none());
+        let change = captures.process_pending(touch);
+        assert!(change.lost.is_none() && change.got.is_some_and(|got| Rc::ptr_eq(&got, &slider)));
+        assert!(Rc::ptr_eq(&captures.capture_target(touch).unwrap(), &slider));
+        assert!(captures.process_pending(touch).got.is_none());
+
+        captures.pointer_up(touch);
+        let change = captures.process_pending(touch);
+        assert!(change.lost.is_some_and(|lost| Rc::ptr_eq(&lost, &slider)));
+        assert!(!captures.is_active(touch));
+        assert!(captures.is_active(MOUSE_POINTER_ID));
+
+        // Without a pressed button the mouse cannot be captured
+        captures.set_capture(&slider, MOUSE_POINTER_ID).unwrap();
+        assert!(!captures.has_capture(&slider, MOUSE_POINTER_ID));
+    }
+}
@@ -0,0 +1,371 @@
+//! # Popovers
+//!
+//! This module implements the `popover` attribute: `showPopover()`,
+//! `hidePopover()` and `togglePopover()`, buttons that invoke popovers
+//! through `popovertarget`, and light dismiss. An open popover joins the
+//! top layer and matches `:popover-open`.
+//!
+//! ## Design Principles
+//!
+//! 1. **The Top Layer Is the Stack**: Open `auto` popovers are stacked in
+//!    the order they joined the top layer. Hiding one hides those above it,
+//!    so no separate popover list has to be kept in step with the layer.
+//!
+//! 2. **Nesting by Tree or Invoker**: Showing an `auto` popover keeps the
+//!    open ones that contain it, or the button that invoked it, and hides
+//!    the rest, so menus and their submenus stay open together.
+//!
+//! 3. **Callers Fire Events**: Operations return the popovers they opened
+//!    and closed, in order, and the caller fires `toggle` at each, through
+//!    the DOM event manager for the user agent or through the script
+//!    bindings for scripts.
+
+use std::fmt;
+use std::rc::Rc;
+use crate::element_state::ElementState;
+use crate::forms::FormControlType;
+use crate::{top_layer, Node};
+
+/// The kind of popover an element is, from its `popover` attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PopoverType {
+    /// `popover` or `popover="auto"`: light dismissed, and closes the
+    /// other `auto` popovers that do not contain it
+    Auto,
+    /// Any other value: only opened and closed explicitly
+    Manual,
+}
+
+/// Why a popover cannot be shown or hidden
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PopoverError {
+    /// The element has no `popover` attribute
+    NotPopover,
+    /// The popover is not in a document
+    NotConnected,
+}
+
+impl PopoverError {
+    /// Get the DOMException name of the error
+    pub fn name(&self) -> &'static str {
+        match self {
+            PopoverError::NotPopover => "NotSupportedError",
+            PopoverError::NotConnected => "InvalidStateError",
+        }
+    }
+}
+
+impl fmt::Display for PopoverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PopoverError::NotPopover => write!(f, "the element is not a popover"),
+            PopoverError::NotConnected => write!(f, "the popover is not connected"),
+        }
+    }
+}
+
+impl std::error::Error for PopoverError {}
+
+/// A popover that opened or closed
+#[derive(Debug, Clone)]
+pub struct PopoverToggle {
+    pub popover: Rc<Node>,
+    /// Whether the popover is now open
+    pub open: bool,
+}
+
+impl PopoverToggle {
+    /// Get the `oldState` of the `toggle` event
+    pub fn old_state(&self) -> &'static str {
+        if self.open { "closed" } else { "open" }
+    }
+
+    /// Get the `newState` of the `toggle` event
+    pub fn new_state(&self) -> &'static str {
+        if self.open { "open" } else { "closed" }
+    }
+}
+
+/// What a `popovertarget` button does to its popover
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PopoverTargetAction {
+    Toggle,
+    Show,
+    Hide,
+}
+
+impl Node {
+    /// Get the kind of popover this element is, if it is one
+    pub fn popover_type(&self) -> Option<PopoverType> {
+        if !self.is_html_element() {
+            return None;
+        }
+        let value = self.get_attribute("popover")?;
+        Some(match value.to_ascii_lowercase().as_str() {
+            "" | "auto" => PopoverType::Auto,
+            _ => PopoverType::Manual,
+        })
+    }
+
+    /// Check whether this element has the `popover` attribute
+    pub fn is_popover(&self) -> bool {
+        self.popover_type().is_some()
+    }
+
+    /// Check whether this popover is showing
+    pub fn is_popover_open(&self) -> bool {
+        self.element_state().contains(ElementState::POPOVER_OPEN)
+    }
+
+    /// Show a popover, like `showPopover()`
+    ///
+    /// `invoker` is the button that showed it, if any. Showing an `auto`
+    /// popover first hides the open `auto` popovers that contain neither
+    /// it nor its invoker. Showing an open popover does nothing.
+    pub fn show_popover(self: &Rc<Self>, invoker: Option<&Rc<Node>>) -> Result<Vec<PopoverToggle>, PopoverError> {
+        let popover_type = self.popover_type().ok_or(PopoverError::NotPopover)?;
+        if !self.is_connected() {
+            return Err(PopoverError::NotConnected);
+        }
+        if self.is_popover_open() {
+            return Ok(Vec::new());
+        }
+
+        let mut toggles = Vec::new();
+        if popover_type == PopoverType::Auto {
+            let keep = open_auto_popovers(self)
+                .into_iter()
+                .rev()
+                .find(|open| contains_node(open, self) || invoker.is_some_and(|invoker| contains_node(open, invoker)));
+            toggles.extend(hide_auto_popovers_above(self, keep.as_deref()));
+        }
+        self.set_element_state(ElementState::POPOVER_OPEN, true);
+        top_layer::add(self);
+        toggles.push(PopoverToggle { popover: Rc::clone(self), open: true });
+        Ok(toggles)
+    }
+
+    /// Hide a popover, like `hidePopover()`
+    ///
+    /// Hiding an `auto` popover also hides the `auto` popovers stacked
+    /// above it, topmost first. Hiding a closed popover does nothing.
+    pub fn hide_popover(&self) -> Result<Vec<PopoverToggle>, PopoverError> {
+        let popover_type = self.popover_type().ok_or(PopoverError::NotPopover)?;
+        let Some(this) = top_layer::elements(self).into_iter().find(|element| element.id == self.id) else {
+            return Ok(Vec::new());
+        };
+        if !self.is_popover_open() {
+            return Ok(Vec::new());
+        }
+
+        let mut toggles = Vec::new();
+        if popover_type == PopoverType::Auto {
+            toggles.extend(hide_auto_popovers_above(self, Some(self)));
+        }
+        hide(&this);
+        toggles.push(PopoverToggle { popover: this, open: false });
+        Ok(toggles)
+    }
+
+    /// Toggle a popover, like `togglePopover(force)`
+    ///
+    /// With `force`, the popover is only shown, or only hidden.
+    pub fn toggle_popover(self: &Rc<Self>, force: Option<bool>, invoker: Option<&Rc<Node>>) -> Result<Vec<PopoverToggle>, PopoverError> {
+        if force.unwrap_or(!self.is_popover_open()) {
+            self.show_popover(invoker)
+        } else {
+            self.hide_popover()
+        }
+    }
+}
+
+/// Get the open `auto` popovers of the document that owns `node`, bottom
+/// first
+fn open_auto_popovers(node: &Node) -> Vec<Rc<Node>> {
+    top_layer::elements(node)
+        .into_iter()
+        .filter(|element| element.is_popover_open() && element.popover_type() == Some(PopoverType::Auto))
+        .collect()
+}
+
+/// Hide the open `auto` popovers above `keep`, or all of them without it,
+/// topmost first
+fn hide_auto_popovers_above(node: &Node, keep: Option<&Node>) -> Vec<PopoverToggle> {
+    let open = open_auto_popovers(node);
+    let first = keep
+        .and_then(|keep| open.iter().position(|popover| popover.id == keep.id))
+        .map_or(0, |index| index + 1);
+    open[first..]
+        .iter()
+        .rev()
+        .map(|popover| {
+            hide(popover);
+            PopoverToggle { popover: Rc::clone(popover), open: false }
+        })
+        .collect()
+}
+
+fn hide(popover: &Node) {
+    popover.set_element_state(ElementState::POPOVER_OPEN, false);
+    top_layer::remove(popover);
+}
+
+/// Check whether `node` is `ancestor` or one of its descendants
+fn contains_node(ancestor: &Node, node: &Node) -> bool {
+    if node.id == ancestor.id {
+        return true;
+    }
+    let mut current = node.parent.borrow().upgrade();
+    while let Some(parent) = current {
+        if parent.id == ancestor.id {
+            return true;
+        }
+        current = parent.parent.borrow().upgrade();
+    }
+    false
+}
+
+/// Light dismiss popovers after the pointer went down on `target`
+///
+/// Hides the open `auto` popovers above the topmost one that contains the
+/// target, or the button that invokes it, and all of them when the pointer
+/// went down outside every popover. Returns the popovers hidden, topmost
+/// first.
+pub fn light_dismiss(target: &Rc<Node>) -> Vec<PopoverToggle> {
+    let invoked = invoker_button(target).and_then(|button| invoked_popover(&button)).map(|(popover, _)| popover);
+    let keep = open_auto_popovers(target)
+        .into_iter()
+        .rev()
+        .find(|open| contains_node(open, target) || invoked.as_ref().is_some_and(|invoked| invoked.id == open.id));
+    hide_auto_popovers_above(target, keep.as_deref())
+}
+
+/// Get the topmost open `auto` popover of the document that owns `node`,
+/// the one Escape closes
+pub fn topmost_auto_popover(node: &Node) -> Option<Rc<Node>> {
+    open_auto_popovers(node).pop()
+}
+
+/// Get the nearest button with a `popovertarget` that contains `node`
+pub fn invoker_button(node: &Rc<Node>) -> Option<Rc<Node>> {
+    let mut current = Some(Rc::clone(node));
+    while let Some(candidate) = current {
+        if candidate.has_attribute("popovertarget") && matches!(candidate.tag_name(), Some("button" | "input")) {
+            return Some(candidate);
+        }
+        current = candidate.parent.borrow().upgrade();
+    }
+    None
+}
+
+/// Get the popover a button's `popovertarget` names and what the button's
+/// `popovertargetaction` does to it
+///
+/// Disabled buttons and the submit buttons of a form do not invoke
+/// popovers.
+pub fn invoked_popover(button: &Rc<Node>) -> Option<(Rc<Node>, PopoverTargetAction)> {
+    let submits = button.form_control_type() == Some(FormControlType::Submit) && button.form_owner().is_some();
+    if button.has_attribute("disabled") || submits {
+        return None;
+    }
+    let id = button.get_attribute("popovertarget")?;
+    let mut root = Rc::clone(button);
+    loop {
+        let parent = root.parent.borrow().upgrade();
+        match parent {
+            Some(parent) => root = parent,
+            None => break,
+        }
+    }
+    let popover = find_by_id(&root, &id).filter(|popover| popover.is_popover())?;
+    let action = match button.get_attribute("popovertargetaction").map(|action| action.to_ascii_lowercase()).as_deref() {
+        Some("show") => PopoverTargetAction::Show,
+        Some("hide") => PopoverTargetAction::Hide,
+        _ => PopoverTargetAction::Toggle,
+    };
+    Some((popover, action))
+}
+
+fn find_by_id(node: &Rc<Node>, id: &str) -> Option<Rc<Node>> {
+    if node.get_attribute("id").as_deref() == Some(id) {
+        return Some(Rc::clone(node));
+    }
+    node.children.borrow().iter().find_map(|child| find_by_id(child, id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dom_event_integration::DomEventManager;
+    use crate::event_types::MouseEvent;
+    use crate::events::{KeyboardEventInit, SyntheticEventFactory};
+    use crate::Document;
+
+    fn ids(toggles: &[PopoverToggle]) -> Vec<(u64, bool)> {
+        toggles.iter().map(|toggle| (toggle.popover.id, toggle.open)).collect()
+    }
+
+    #[test]
+    fn test_auto_popovers_nest_and_light_dismiss() {
+        let document = Document::new();
+        let body = document.create_element("body");
+        document.root.append_child(&body);
+        let menu = document.create_element("div");
+        menu.set_attribute("popover", "");
+        let button = document.create_element("button");
+        button.set_attribute("popovertarget", "submenu");
+        menu.append_child(&button);
+        let submenu = document.create_element("div");
+        submenu.set_attribute("popover", "auto");
+        submenu.set_attribute("id", "submenu");
+        let toast = document.create_element("div");
+        toast.set_attribute("popover", "manual");
+        let other = document.create_element("div");
+        other.set_attribute("popover", "");
+        let outside = document.create_element("p");
+        for node in [&menu, &submenu, &toast, &other, &outside] {
+            body.append_child(node);
+        }
+
+        assert_eq!(outside.show_popover(None).unwrap_err(), PopoverError::NotPopover);
+        assert_eq!(ids(&menu.show_popover(None).unwrap()), vec![(menu.id, true)]);
+        assert!(menu.show_popover(None).unwrap().is_empty());
+        // The submenu's invoker is inside the menu, so the menu stays open
+        let (invoked, action) = invoked_popover(&button).unwrap();
+        assert!(Rc::ptr_eq(&invoked, &submenu) && action == PopoverTargetAction::Toggle);
+        assert_eq!(ids(&submenu.toggle_popover(None, Some(&button)).unwrap()), vec![(submenu.id, true)]);
+        toast.show_popover(None).unwrap();
+        assert!(topmost_auto_popover(&body).is_some_and(|top| Rc::ptr_eq(&top, &submenu)));
+
+        // Clicking in the menu closes the submenu only; manual popovers stay
+        assert_eq!(ids(&light_dismiss(&button)), Vec::new());
+        assert_eq!(ids(&light_dismiss(&menu)), vec![(submenu.id, false)]);
+        submenu.show_popover(Some(&button)).unwrap();
+        assert_eq!(ids(&other.show_popover(None).unwrap()), vec![(submenu.id, false), (menu.id, false), (other.id, true)]);
+        assert_eq!(ids(&light_dismiss(&outside)), vec![(other.id, false)]);
+        assert!(toast.is_popover_open() && !other.is_popover_open());
+
+        // Hiding a popover hides the ones stacked above it
+        menu.show_popover(None).unwrap();
+        submenu.show_popover(Some(&button)).unwrap();
+        assert_eq!(ids(&menu.hide_popover().unwrap()), vec![(submenu.id, false), (menu.id, false)]);
+        assert!(menu.hide_popover().unwrap().is_empty());
+
+        // Clicking the invoker toggles the submenu, and Escape hides the
+        // topmost auto popover
+        let mut manager = DomEventManager::new();
+        manager.enable_dispatch_log();
+        menu.show_popover(None).unwrap();
+        manager.light_dismiss_popovers(&button);
+        manager.dispatch_event(&button, MouseEvent::new("click", true, true).base);
+        assert!(submenu.is_popover_open() && menu.is_popover_open());
+        let escape = KeyboardEventInit { key: "Escape".to_string(), code: "Escape".to_string(), ..Default::default() };
+        manager.dispatch_keyboard_event(&body, SyntheticEventFactory::create_keyboard_event("keydown", &escape));
+        assert!(!submenu.is_popover_open() && menu.is_popover_open());
+        assert_eq!(manager.take_dispatch_log(), vec!["click", "toggle", "keydown", "toggle"]);
+
+        body.remove_child(&toast);
+        assert!(!toast.is_popover_open() && !top_layer::contains(&toast));
+        assert_eq!(toast.show_popover(None).unwrap_err(), PopoverError::NotConnected);
+    }
+}
@@ -0,0 +1,211 @@
+//! # Iframe Sandboxing
+//!
+//! This module parses the `sandbox` attribute of `<iframe>` elements into
+//! the flags of what a document loaded in the frame may still do: run
+//! scripts, submit forms, navigate the top-level page, open popups, show
+//! modal dialogs and start downloads. A sandboxed document also gets a
+//! unique opaque origin unless the frame allows `allow-same-origin`.
+//!
+//! ## Design Principles
+//!
+//! 1. **Everything Off, Tokens Turn On**: An empty `sandbox` attribute
+//!    denies everything, and each `allow-*` token lifts one restriction, as
+//!    in browsers. Unknown tokens are ignored, so pages written for newer
+//!    browsers keep the restrictions they do name.
+//! 2. **Kept on the Document**: The flags belong to the document loaded in
+//!    the frame, which applies them to its own origin, so every check sees
+//!    the same answer however it reaches the document.
+//! 3. **Enforced Where Acted On**: Each restriction is checked where the
+//!    action happens, e.g. form submission and link activation in the DOM
+//!    event manager and scripts in the script engine, so each check sits
+//!    next to the action it guards.
+
+use crate::{Node, Origin};
+
+/// Name of the attribute sandboxing a frame
+pub const SANDBOX_ATTRIBUTE: &str = "sandbox";
+
+/// What a document in a sandboxed frame is allowed to do
+///
+/// Each field is `true` if its `allow-*` token is present.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct SandboxFlags {
+    /// `allow-scripts`
+    pub scripts: bool,
+    /// `allow-forms`
+    pub forms: bool,
+    /// `allow-same-origin`: keep the origin of the URL instead of a unique
+    /// opaque one
+    pub same_origin: bool,
+    /// `allow-top-navigation`
+    pub top_navigation: bool,
+    /// `allow-top-navigation-by-user-activation`
+    pub top_navigation_by_user_activation: bool,
+    /// `allow-popups`
+    pub popups: bool,
+    /// `allow-popups-to-escape-sandbox`: popups are not sandboxed
+    pub popups_to_escape_sandbox: bool,
+    /// `allow-modals`: `alert`, `confirm`, `prompt` and the
+    /// `beforeunload` prompt
+    pub modals: bool,
+    /// `allow-downloads`
+    pub downloads: bool,
+}
+
+impl SandboxFlags {
+    /// Parse the value of a `sandbox` attribute
+    ///
+    /// Tokens are ASCII case-insensitive and separated by whitespace.
+    pub fn parse(value: &str) -> Self {
+        let mut flags = SandboxFlags::default();
+        for token in value.split_ascii_whitespace() {
+            match token.to_ascii_lowercase().as_str() {
+                "allow-scripts" => flags.scripts = true,
+                "allow-forms" => flags.forms = true,
+                "allow-same-origin" => flags.same_origin = true,
+                "allow-top-navigation" => flags.top_navigation = true,
+                "allow-top-navigation-by-user-activation" => flags.top_navigation_by_user_activation = true,
+                "allow-popups" => flags.popups = true,
+                "allow-popups-to-escape-sandbox" => flags.popups_to_escape_sandbox = true,
+                "allow-modals" => flags.modals = true,
+                "allow-downloads" => flags.downloads = true,
+                _ => {}
+            }
+        }
+        flags
+    }
+
+    /// Get the flags of an `<iframe>`, or `None` if it is not sandboxed
+    pub fn of_frame(frame: &Node) -> Option<Self> {
+        if !frame.tag_name().is_some_and(|tag| tag.eq_ignore_ascii_case("iframe")) {
+            return None;
+        }
+        frame.get_attribute(SANDBOX_ATTRIBUTE).map(|value| Self::parse(&value))
+    }
+
+    /// Get the origin a document loaded from a URL of `origin` gets in the
+    /// frame: a unique opaque origin unless `allow-same-origin` is present
+    pub fn origin_for(&self, origin: Origin) -> Origin {
+        if self.same_origin {
+            origin
+        } else {
+            Origin::opaque()
+        }
+    }
+
+    /// Check whether the document may navigate `target`
+    ///
+    /// `user_activated` is whether the navigation comes from a click or
+    /// key press rather than from a script on its own.
+    pub fn allows_navigation(&self, target: NavigationTarget, user_activated: bool) -> bool {
+        match target {
+            NavigationTarget::SelfFrame => true,
+            NavigationTarget::Top => self.top_navigation || (user_activated && self.top_navigation_by_user_activation),
+            NavigationTarget::NewWindow => self.popups,
+        }
+    }
+}
+
+/// The browsing context a link or form navigates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NavigationTarget {
+    /// The frame itself: no `target`, `_self` or `_parent`
+    ///
+    /// Frames are not nested, so the parent of a frame is the top-level
+    /// page only when the frame is directly in it; `_parent` is treated as
+    /// the frame itself, which is never less strict.
+    SelfFrame,
+    /// The top-level page: `_top`
+    Top,
+    /// A new window or tab: `_blank` or a name
+    NewWindow,
+}
+
+impl NavigationTarget {
+    /// Get the target named by a `target` attribute
+    pub fn parse(target: Option<&str>) -> Self {
+        match target.map(str::trim) {
+            None | Some("") => NavigationTarget::SelfFrame,
+            Some(target) if target.eq_ignore_ascii_case("_self") || target.eq_ignore_ascii_case("_parent") => NavigationTarget::SelfFrame,
+            Some(target) if target.eq_ignore_ascii_case("_top") => NavigationTarget::Top,
+            Some(_) => NavigationTarget::NewWindow,
+        }
+    }
+
+    /// Get the target of a link or form element
+    pub fn of(node: &Node) -> Self {
+        Self::parse(node.get_attribute("target").as_deref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+    use crate::Document;
+    use crate::dom_event_integration::DomEventManager;
+
+    #[test]
+    fn test_sandbox_tokens_and_unique_origin() {
+        let empty = SandboxFlags::parse("");
+        assert_eq!(empty, SandboxFlags::default());
+        let flags = SandboxFlags::parse("  ALLOW-scripts\tallow-forms allow-future-feature ");
+        assert!(flags.scripts && flags.forms);
+        assert!(!flags.same_origin && !flags.popups);
+
+        // Frames without the attribute are not sandboxed
+        let document = Document::new();
+        let frame = document.create_element("iframe");
+        assert_eq!(SandboxFlags::of_frame(&frame), None);
+        frame.set_attribute(SANDBOX_ATTRIBUTE, "allow-popups");
+        assert_eq!(SandboxFlags::of_frame(&frame).map(|flags| flags.popups), Some(true));
+        assert_eq!(SandboxFlags::of_frame(&document.create_element("div")), None);
+
+        // A sandboxed document keeps an opaque origin whatever its URL
+        let framed = Document::new();
+        framed.set_sandbox_flags(Some(flags));
+        framed.set_url("https://example.com/widget");
+        assert!(framed.origin().is_opaque());
+        framed.set_sandbox_flags(Some(SandboxFlags { same_origin: true, ..flags }));
+        assert_eq!(framed.origin(), Origin::from_url("https://example.com/widget"));
+
+        // Top-level navigation needs a token, or a user click with the
+        // by-user-activation one
+        assert!(flags.allows_navigation(NavigationTarget::parse(Some("_parent")), false));
+        assert!(!flags.allows_navigation(NavigationTarget::parse(Some("_top")), true));
+        let by_click = SandboxFlags::parse("allow-top-navigation-by-user-activation");
+        assert!(by_click.allows_navigation(NavigationTarget::Top, true));
+        assert!(!by_click.allows_navigation(NavigationTarget::Top, false));
+        assert!(!by_click.allows_navigation(NavigationTarget::parse(Some("_blank")), true));
+
+        // The event manager of a sandboxed document drops what it denies
+        let page = Document::new();
+        let body = page.create_element("body");
+        for (id, target) in [("inside", None), ("escape", Some("_top")), ("popup", Some("_blank"))] {
+            let link = page.create_element("a");
+            link.set_attribute("id", id);
+            link.set_attribute("href", &format!("/{}", id));
+            if let Some(target) = target {
+                link.set_attribute("target", target);
+            }
+            body.append_child(&link);
+        }
+        let form = page.create_element("form");
+        let submit = page.create_element("button");
+        submit.set_attribute("id", "send");
+        submit.set_attribute("type", "submit");
+        form.append_child(&submit);
+        body.append_child(&form);
+        page.root.append_child(&body);
+        page.set_sandbox_flags(Some(SandboxFlags::parse("allow-popups")));
+        let mut manager = DomEventManager::new();
+        manager.set_document(Rc::new(page));
+        for id in ["inside", "escape", "popup", "send"] {
+            manager.simulate_click(id);
+        }
+        assert_eq!(manager.take_navigation().as_deref(), Some("/inside"));
+        assert_eq!(manager.take_navigation().as_deref(), Some("/popup"));
+        assert!(manager.take_navigation().is_none());
+        assert!(manager.take_form_submission().is_none());
+    }
+}
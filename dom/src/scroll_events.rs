@@ -0,0 +1,131 @@
+//! # Scroll Events
+//!
+//! This module queues `scroll` events for scrolled boxes and the document
+//! and fires them once per rendering frame.
+//!
+//! ## Design Principles
+//!
+//! 1. **Once Per Frame**: Scrolling may move a box many times between two
+//!    frames. Each target is queued at most once and the queue is drained
+//!    by the frame, so listeners run at most once per frame and target.
+//!
+//! 2. **Spec Targets**: A scrolled element receives a `scroll` event that
+//!    does not bubble. Scrolling the viewport fires a bubbling `scroll` at
+//!    the document instead.
+//!
+//! 3. **Scroll First**: Scroll events report a scroll that already
+//!    happened and cannot be cancelled, so they never hold up scrolling.
+
+use std::rc::Rc;
+use crate::Node;
+use crate::dom_event_integration::DomEventManager;
+use crate::event_types::Event;
+
+/// Something that scrolled
+#[derive(Debug, Clone)]
+pub enum ScrollTarget {
+    /// The viewport; the event is fired at the document
+    Document,
+    /// A scrolling box
+    Element(Rc<Node>),
+}
+
+impl ScrollTarget {
+    fn same_as(&self, other: &ScrollTarget) -> bool {
+        match (self, other) {
+            (ScrollTarget::Document, ScrollTarget::Document) => true,
+            (ScrollTarget::Element(a), ScrollTarget::Element(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+/// Scroll events waiting for the next frame
+#[derive(Debug, Default)]
+pub struct ScrollEventQueue {
+    pending: Vec<ScrollTarget>,
+    /// Scrolls folded into an event already queued
+    coalesced: u64,
+}
+
+impl ScrollEventQueue {
+    /// Create an empty queue
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Note that a target scrolled
+    ///
+    /// Returns `false` if the target already has an event queued for this
+    /// frame.
+    pub fn schedule(&mut self, target: ScrollTarget) -> bool {
+        if self.pending.iter().any(|pending| pending.same_as(&target)) {
+            self.coalesced += 1;
+            return false;
+        }
+        self.pending.push(target);
+        true
+    }
+
+    /// Check whether any scroll event is waiting
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Get the number of scrolls folded into an already queued event
+    pub fn coalesced(&self) -> u64 {
+        self.coalesced
+    }
+
+    /// Fire the queued events, in the order their targets first scrolled
+    ///
+    /// Called once per frame. Returns the number of events fired.
+    pub fn dispatch_frame(&mut self, manager: &mut DomEventManager) -> usize {
+        let mut fired = 0;
+        for target in std::mem::take(&mut self.pending) {
+            let (node, bubbles) = match target {
+                ScrollTarget::Element(node) => (node, false),
+                ScrollTarget::Document => match manager.document() {
+                    Some(document) => (Rc::clone(&document.root), true),
+                    None => continue,
+                },
+            };
+            let mut event = Event::new("scroll", bubbles, false);
+            event.is_trusted = true;
+            manager.dispatch_event(&node, event);
+            fired += 1;
+        }
+        fired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Document;
+
+    #[test]
+    fn test_scroll_events_fire_once_per_frame() {
+        let document = Rc::new(Document::new());
+        let list = document.create_element("ul");
+        document.root.append_child(&list);
+
+        let mut manager = DomEventManager::new();
+        manager.set_document(Rc::clone(&document));
+        manager.enable_dispatch_log();
+
+        let mut queue = ScrollEventQueue::new();
+        assert!(queue.schedule(ScrollTarget::Document));
+        assert!(queue.schedule(ScrollTarget::Element(Rc::clone(&list))));
+        assert!(!queue.schedule(ScrollTarget::Document));
+        assert!(!queue.schedule(ScrollTarget::Element(Rc::clone(&list))));
+        assert_eq!(queue.coalesced(), 2);
+
+        assert_eq!(queue.dispatch_frame(&mut manager), 2);
+        assert_eq!(manager.take_dispatch_log(), vec!["scroll", "scroll"]);
+
+        // The next frame starts empty
+        assert!(!queue.has_pending());
+        assert_eq!(queue.dispatch_frame(&mut manager), 0);
+    }
+}
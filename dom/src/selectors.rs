@@ -0,0 +1,892 @@
+//! # Selector Matching
+//!
+//! This module parses selector lists and matches them against elements,
+//! for `Element.matches()`, `Element.closest()` and `querySelector()`.
+//! It covers type, class,
+//! id and attribute selectors, the four combinators, and the structural,
+//! interaction, form, logical and relational (`:has()`) pseudo-classes.
+//!
+//! Type and attribute selectors are namespace aware: `*|rect` and `|rect`
+//! select elements in any or no namespace, `[*|href]` attributes in any
+//! namespace. Without `@namespace` rules there are no other prefixes.
+//!
+//! ## Design Principles
+//!
+//! 1. **Parse Once, Match Many**: Selector text is parsed into a
+//!    `SelectorList` that can be matched against any number of elements,
+//!    so `closest` parses once for the whole ancestor walk.
+//!
+//! 2. **Right to Left**: Complex selectors are matched from the rightmost
+//!    compound, walking to parents and siblings only when it matched, as
+//!    browsers do.
+//!
+//! 3. **Invalid Is an Error**: Selector text that does not parse is a
+//!    `SelectorError`, which script bindings throw as a `SyntaxError`;
+//!    nothing is silently treated as non-matching.
+//!
+//! 4. **Private History**: `:visited` never matches from script, so pages
+//!    cannot probe which links the user visited.
+
+use std::fmt;
+use std::rc::Rc;
+use crate::element_state::{is_link, ElementState};
+use crate::{Node, NodeType};
+
+/// Selector text that could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectorError {
+    pub selector: String,
+    pub message: String,
+}
+
+impl fmt::Display for SelectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "'{}' is not a valid selector: {}", self.selector, self.message)
+    }
+}
+
+impl std::error::Error for SelectorError {}
+
+/// How two compounds of a complex selector relate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Combinator {
+    /// Whitespace
+    Descendant,
+    /// `>`
+    Child,
+    /// `+`
+    NextSibling,
+    /// `~`
+    SubsequentSibling,
+}
+
+/// How an attribute selector compares the attribute value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AttributeOperator {
+    /// `[name]`
+    Exists,
+    /// `[name=value]`
+    Equals,
+    /// `[name~=value]`, one of the whitespace separated words
+    Includes,
+    /// `[name|=value]`, the value or the value followed by `-`
+    DashMatch,
+    /// `[name^=value]`
+    Prefix,
+    /// `[name$=value]`
+    Suffix,
+    /// `[name*=value]`
+    Substring,
+}
+
+/// Which namespaces a type or attribute selector accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NamespaceConstraint {
+    /// `*|`, or no prefix on a type selector
+    Any,
+    /// `|`, or no prefix on an attribute selector
+    Null,
+}
+
+impl NamespaceConstraint {
+    fn matches(&self, namespace: Option<&str>) -> bool {
+        match self {
+            NamespaceConstraint::Any => true,
+            NamespaceConstraint::Null => namespace.is_none(),
+        }
+    }
+}
+
+/// The `An+B` argument of the `:nth-*` pseudo-classes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Nth {
+    a: i32,
+    b: i32,
+}
+
+impl Nth {
+    /// Whether the 1-based `position` is `An+B` for some `n >= 0`
+    fn matches(&self, position: i32) -> bool {
+        if self.a == 0 {
+            return position == self.b;
+        }
+        let offset = position - self.b;
+        offset % self.a == 0 && offset / self.a >= 0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum PseudoClass {
+    State(ElementState),
+    AnyLink,
+    Link,
+    Visited,
+    Root,
+    Scope,
+    Empty,
+    /// `:nth-child` and friends, with whether they count from the end and
+    /// only siblings of the same type
+    Nth { nth: Nth, from_end: bool, of_type: bool },
+    /// `:only-child` or `:only-of-type`
+    Only { of_type: bool },
+    Checked,
+    Disabled,
+    Enabled,
+    Not(SelectorList),
+    /// `:is()` and `:where()`
+    Is(SelectorList),
+    /// `:has()`, with each relative selector anchored by a leading `:scope`
+    /// compound
+    Has(Vec<ComplexSelector>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum SimpleSelector {
+    Universal(NamespaceConstraint),
+    Type { namespace: NamespaceConstraint, name: String },
+    Id(String),
+    Class(String),
+    Attribute { namespace: NamespaceConstraint, name: String, operator: AttributeOperator, value: String, case_insensitive: bool },
+    PseudoClass(PseudoClass),
+    /// Pseudo-elements are valid but never match an element
+    PseudoElement,
+}
+
+/// Compounds joined by combinators, stored left to right
+#[derive(Debug, Clone, PartialEq)]
+struct ComplexSelector {
+    compounds: Vec<Vec<SimpleSelector>>,
+    /// `combinators[i]` joins `compounds[i]` and `compounds[i + 1]`
+    combinators: Vec<Combinator>,
+}
+
+/// A parsed comma separated list of selectors
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectorList {
+    selectors: Vec<ComplexSelector>,
+}
+
+impl SelectorList {
+    /// Parse selector text such as `ul > li.item:not(.done), a[href^=http]`
+    pub fn parse(text: &str) -> Result<Self, SelectorError> {
+        let mut parser = Parser { text, chars: text.chars().collect(), position: 0 };
+        let list = parser.parse_list()?;
+        parser.skip_whitespace();
+        if parser.position < parser.chars.len() {
+            return Err(parser.error(&format!("unexpected '{}'", parser.chars[parser.position])));
+        }
+        Ok(list)
+    }
+
+    /// Check whether an element matches any selector of the list
+    pub fn matches(&self, element: &Rc<Node>) -> bool {
+        self.matches_in_scope(element, element)
+    }
+
+    /// Match with `:scope` referring to `scope`
+    fn matches_in_scope(&self, element: &Rc<Node>, scope: &Rc<Node>) -> bool {
+        element.tag_name().is_some() && self.selectors.iter().any(|selector| selector.matches(selector.compounds.len() - 1, element, scope))
+    }
+}
+
+impl ComplexSelector {
+    /// Match the compounds up to `index` with `element` as the subject of
+    /// the compound at `index`
+    fn matches(&self, index: usize, element: &Rc<Node>, scope: &Rc<Node>) -> bool {
+        if !self.compounds[index].iter().all(|simple| simple.matches(element, scope)) {
+            return false;
+        }
+        if index == 0 {
+            return true;
+        }
+        match self.combinators[index - 1] {
+            Combinator::Child => parent_element(element).is_some_and(|parent| self.matches(index - 1, &parent, scope)),
+            Combinator::Descendant => {
+                let mut ancestor = parent_element(element);
+                while let Some(node) = ancestor {
+                    if self.matches(index - 1, &node, scope) {
+                        return true;
+                    }
+                    ancestor = parent_element(&node);
+                }
+                false
+            }
+            Combinator::NextSibling => preceding_elements(element).last().is_some_and(|sibling| self.matches(index - 1, sibling, scope)),
+            Combinator::SubsequentSibling => preceding_elements(element).iter().any(|sibling| self.matches(index - 1, sibling, scope)),
+        }
+    }
+}
+
+impl ComplexSelector {
+    /// Match a relative selector of `:has()` anchored at `anchor`
+    ///
+    /// The leading `:scope` compound is the anchor itself; the compounds
+    /// after it are the steps of `relative_steps_match`, matched with
+    /// `:scope` pinned to the anchor.
+    fn matches_relative(&self, anchor: &Rc<Node>) -> bool {
+        let steps: Vec<(Combinator, &Vec<SimpleSelector>)> = self.combinators.iter().copied().zip(&self.compounds[1..]).collect();
+        relative_steps_match(anchor, &steps, &|compound, candidate| compound.iter().all(|simple| simple.matches(candidate, anchor)))
+    }
+
+    /// Whether any compound uses `:has()`, which may not be nested
+    fn uses_has(&self) -> bool {
+        self.compounds.iter().flatten().any(|simple| match simple {
+            SimpleSelector::PseudoClass(PseudoClass::Has(_)) => true,
+            SimpleSelector::PseudoClass(PseudoClass::Not(list) | PseudoClass::Is(list)) => list.selectors.iter().any(ComplexSelector::uses_has),
+            _ => false,
+        })
+    }
+}
+
+impl SimpleSelector {
+    fn matches(&self, element: &Rc<Node>, scope: &Rc<Node>) -> bool {
+        match self {
+            SimpleSelector::Universal(namespace) => namespace.matches(element.namespace_uri()),
+            SimpleSelector::Type { namespace, name } => namespace.matches(element.namespace_uri()) && has_local_name(element, name),
+            SimpleSelector::Id(id) => element.get_attribute("id").as_deref() == Some(id.as_str()),
+            SimpleSelector::Class(class) => element
+                .get_attribute("class")
+                .is_some_and(|classes| classes.split_ascii_whitespace().any(|name| name == class)),
+            SimpleSelector::Attribute { namespace, name, operator, value, case_insensitive } => {
+                // Attribute names are case-insensitive on HTML elements only
+                let html = element.is_html_element();
+                let attributes = element.attributes_ns();
+                let Some(actual) = attributes
+                    .iter()
+                    .find(|(attribute, _)| {
+                        namespace.matches(attribute.namespace.as_deref())
+                            && (attribute.local_name == *name || html && attribute.local_name.eq_ignore_ascii_case(name))
+                    })
+                    .map(|(_, value)| value)
+                else {
+                    return false;
+                };
+                let (actual, value) = if *case_insensitive {
+                    (actual.to_ascii_lowercase(), value.to_ascii_lowercase())
+                } else {
+                    (actual.clone(), value.clone())
+                };
+                match operator {
+                    AttributeOperator::Exists => true,
+                    AttributeOperator::Equals => actual == value,
+                    AttributeOperator::Includes => !value.is_empty() && actual.split_ascii_whitespace().any(|word| word == value),
+                    AttributeOperator::DashMatch => actual == value || actual.starts_with(&format!("{}-", value)),
+                    AttributeOperator::Prefix => !value.is_empty() && actual.starts_with(&value),
+                    AttributeOperator::Suffix => !value.is_empty() && actual.ends_with(&value),
+                    AttributeOperator::Substring => !value.is_empty() && actual.contains(&value),
+                }
+            }
+            SimpleSelector::PseudoClass(pseudo) => pseudo.matches(element, scope),
+            SimpleSelector::PseudoElement => false,
+        }
+    }
+}
+
+impl PseudoClass {
+    fn matches(&self, element: &Rc<Node>, scope: &Rc<Node>) -> bool {
+        match self {
+            PseudoClass::State(flag) => element.element_state().contains(*flag),
+            PseudoClass::AnyLink | PseudoClass::Link => is_link(element),
+            PseudoClass::Visited => false,
+            PseudoClass::Root => element
+                .parent
+                .borrow()
+                .upgrade()
+                .is_some_and(|parent| matches!(parent.node_type, NodeType::Document)),
+            PseudoClass::Scope => Rc::ptr_eq(element, scope),
+            PseudoClass::Empty => element.children.borrow().iter().all(|child| match &child.node_type {
+                NodeType::Text(_) => child.character_data().is_none_or(|text| text.is_empty()),
+                NodeType::Element { .. } => false,
+                _ => true,
+            }),
+            PseudoClass::Nth { nth, from_end, of_type } => {
+                let Some(siblings) = sibling_elements(element, *of_type) else {
+                    return false;
+                };
+                let Some(index) = siblings.iter().position(|sibling| Rc::ptr_eq(sibling, element)) else {
+                    return false;
+                };
+                let position = if *from_end { siblings.len() - index } else { index + 1 };
+                nth.matches(position as i32)
+            }
+            PseudoClass::Only { of_type } => sibling_elements(element, *of_type).is_some_and(|siblings| siblings.len() == 1),
+            PseudoClass::Checked => match element.tag_name().map(|tag| tag.to_ascii_lowercase()).as_deref() {
+                Some("input") => element.checked(),
+                Some("option") => element.has_attribute("selected"),
+                _ => false,
+            },
+            PseudoClass::Disabled => is_form_control(element) && element.has_attribute("disabled"),
+            PseudoClass::Enabled => is_form_control(element) && !element.has_attribute("disabled"),
+            PseudoClass::Not(list) => !list.matches_in_scope(element, scope),
+            PseudoClass::Is(list) => list.matches_in_scope(element, scope),
+            PseudoClass::Has(relative) => relative.iter().any(|selector| selector.matches_relative(element)),
+        }
+    }
+}
+
+/// Check an element's local name, ignoring case for HTML elements
+fn has_local_name(element: &Node, name: &str) -> bool {
+    element.local_name().is_some_and(|local_name| {
+        local_name == name || element.is_html_element() && local_name.eq_ignore_ascii_case(name)
+    })
+}
+
+/// Elements that `:enabled` and `:disabled` apply to
+fn is_form_control(element: &Node) -> bool {
+    matches!(
+        element.tag_name().map(|tag| tag.to_ascii_lowercase()).as_deref(),
+        Some("button" | "input" | "select" | "textarea" | "option" | "optgroup" | "fieldset")
+    )
+}
+
+/// Get the parent of a node if it is an element
+fn parent_element(node: &Rc<Node>) -> Option<Rc<Node>> {
+    node.parent.borrow().upgrade().filter(|parent| parent.tag_name().is_some())
+}
+
+/// Check whether an element reached from `anchor` through the steps of a
+/// relative selector, such as an argument of `:has()`, matches them all
+///
+/// Each step is a combinator and the compound it leads to, which
+/// `matches` checks an element against. Steps are walked left to right, so
+/// only elements the anchor reaches are tried: its descendants, or after a
+/// sibling combinator its following siblings and their descendants. The
+/// style system matches its own selectors with the same walk.
+pub fn relative_steps_match<C>(anchor: &Node, steps: &[(Combinator, C)], matches: &dyn Fn(&C, &Rc<Node>) -> bool) -> bool {
+    let Some(((combinator, compound), rest)) = steps.split_first() else {
+        return true;
+    };
+    let reached = |candidate: &Rc<Node>| {
+        candidate.tag_name().is_some() && matches(compound, candidate) && relative_steps_match(candidate, rest, matches)
+    };
+    match combinator {
+        Combinator::Child => anchor.children.borrow().iter().any(reached),
+        Combinator::Descendant => any_descendant(anchor, &reached),
+        Combinator::NextSibling => following_elements(anchor).first().is_some_and(reached),
+        Combinator::SubsequentSibling => following_elements(anchor).iter().any(reached),
+    }
+}
+
+/// Get the element siblings before a node, in document order
+pub fn preceding_elements(node: &Node) -> Vec<Rc<Node>> {
+    let Some(parent) = node.parent.borrow().upgrade() else {
+        return Vec::new();
+    };
+    let children = parent.children.borrow();
+    children
+        .iter()
+        .take_while(|child| !std::ptr::eq(child.as_ref(), node))
+        .filter(|child| child.tag_name().is_some())
+        .cloned()
+        .collect()
+}
+
+fn any_descendant(node: &Node, predicate: &dyn Fn(&Rc<Node>) -> bool) -> bool {
+    node.children.borrow().iter().any(|child| predicate(child) || any_descendant(child, predicate))
+}
+
+/// Get the element siblings after a node, in document order
+fn following_elements(node: &Node) -> Vec<Rc<Node>> {
+    let Some(parent) = node.parent.borrow().upgrade() else {
+        return Vec::new();
+    };
+    let children = parent.children.borrow();
+    children
+        .iter()
+        .skip_while(|child| !std::ptr::eq(child.as_ref(), node))
+        .skip(1)
+        .filter(|child| child.tag_name().is_some())
+        .cloned()
+        .collect()
+}
+
+/// Get the element children of a node's parent, optionally only those
+/// with the node's namespace and local name
+fn sibling_elements(node: &Rc<Node>, of_type: bool) -> Option<Vec<Rc<Node>>> {
+    let parent = node.parent.borrow().upgrade()?;
+    let local_name = node.local_name()?;
+    let siblings = parent
+        .children
+        .borrow()
+        .iter()
+        .filter(|child| child.tag_name().is_some() && (!of_type || child.namespace_uri() == node.namespace_uri() && has_local_name(child, local_name)))
+        .cloned()
+        .collect();
+    Some(siblings)
+}
+
+/// A recursive descent parser over the characters of selector text
+struct Parser<'a> {
+    text: &'a str,
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> SelectorError {
+        SelectorError { selector: self.text.to_string(), message: message.to_string() }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) -> bool {
+        let start = self.position;
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.position += 1;
+        }
+        self.position > start
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), SelectorError> {
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected)))
+        }
+    }
+
+    fn parse_list(&mut self) -> Result<SelectorList, SelectorError> {
+        let mut selectors = Vec::new();
+        loop {
+            self.skip_whitespace();
+            selectors.push(self.parse_complex()?);
+            self.skip_whitespace();
+            if self.peek() == Some(',') {
+                self.position += 1;
+            } else {
+                return Ok(SelectorList { selectors });
+            }
+        }
+    }
+
+    /// Parse the relative selectors of `:has()`, such as `> img, + p`
+    fn parse_relative_list(&mut self) -> Result<Vec<ComplexSelector>, SelectorError> {
+        let mut selectors = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let combinator = match self.peek() {
+                Some('>') => Combinator::Child,
+                Some('+') => Combinator::NextSibling,
+                Some('~') => Combinator::SubsequentSibling,
+                _ => Combinator::Descendant,
+            };
+            if combinator != Combinator::Descendant {
+                self.position += 1;
+                self.skip_whitespace();
+            }
+            let mut selector = self.parse_complex()?;
+            if selector.uses_has() {
+                return Err(self.error(":has() cannot be nested"));
+            }
+            selector.compounds.insert(0, vec![SimpleSelector::PseudoClass(PseudoClass::Scope)]);
+            selector.combinators.insert(0, combinator);
+            selectors.push(selector);
+            self.skip_whitespace();
+            if self.peek() == Some(',') {
+                self.position += 1;
+            } else {
+                return Ok(selectors);
+            }
+        }
+    }
+
+    fn parse_complex(&mut self) -> Result<ComplexSelector, SelectorError> {
+        let mut compounds = vec![self.parse_compound()?];
+        let mut combinators = Vec::new();
+        loop {
+            let had_whitespace = self.skip_whitespace();
+            let combinator = match self.peek() {
+                Some('>') => Combinator::Child,
+                Some('+') => Combinator::NextSibling,
+                Some('~') => Combinator::SubsequentSibling,
+                Some(',') | Some(')') | None => break,
+                _ if had_whitespace => Combinator::Descendant,
+                Some(c) => return Err(self.error(&format!("unexpected '{}'", c))),
+            };
+            if combinator != Combinator::Descendant {
+                self.position += 1;
+                self.skip_whitespace();
+            }
+            combinators.push(combinator);
+            compounds.push(self.parse_compound()?);
+        }
+        Ok(ComplexSelector { compounds, combinators })
+    }
+
+    fn parse_compound(&mut self) -> Result<Vec<SimpleSelector>, SelectorError> {
+        let mut compound = Vec::new();
+        if matches!(self.peek(), Some(c) if c == '*' || c == '|' || is_ident_start(c)) {
+            compound.push(match self.parse_qualified_name(NamespaceConstraint::Any, true)? {
+                (namespace, Some(name)) => SimpleSelector::Type { namespace, name },
+                (namespace, None) => SimpleSelector::Universal(namespace),
+            });
+        }
+        loop {
+            match self.peek() {
+                Some('#') => {
+                    self.position += 1;
+                    compound.push(SimpleSelector::Id(self.parse_ident()?));
+                }
+                Some('.') => {
+                    self.position += 1;
+                    compound.push(SimpleSelector::Class(self.parse_ident()?));
+                }
+                Some('[') => compound.push(self.parse_attribute()?),
+                Some(':') => compound.push(self.parse_pseudo()?),
+                _ => break,
+            }
+        }
+        if compound.is_empty() {
+            return Err(match self.peek() {
+                Some(c) => self.error(&format!("unexpected '{}'", c)),
+                None => self.error("expected a selector"),
+            });
+        }
+        Ok(compound)
+    }
+
+    fn parse_ident(&mut self) -> Result<String, SelectorError> {
+        let mut ident = String::new();
+        while let Some(c) = self.peek() {
+            if c == '\\' {
+                self.position += 1;
+                ident.extend(self.peek());
+                self.position += 1;
+            } else if is_ident_start(c) || c.is_ascii_digit() || c == '-' {
+                ident.push(c);
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+        if ident.is_empty() || ident.starts_with(|c: char| c.is_ascii_digit()) || ident == "-" {
+            return Err(self.error("expected an identifier"));
+        }
+        Ok(ident)
+    }
+
+    /// Parse a name with an optional namespace prefix: `name`, `ns|name`,
+    /// `*|name` or `|name`, where the name may be `*` if `allow_universal`
+    /// is set, giving `None`
+    fn parse_qualified_name(&mut self, default: NamespaceConstraint, allow_universal: bool) -> Result<(NamespaceConstraint, Option<String>), SelectorError> {
+        // `None` for an empty prefix, `Some(None)` for `*`
+        let first = match self.peek() {
+            Some('*') => {
+                self.position += 1;
+                Some(None)
+            }
+            Some('|') => None,
+            _ => Some(Some(self.parse_ident()?)),
+        };
+        // `|=` is an attribute operator, not a namespace separator
+        if self.peek() == Some('|') && self.chars.get(self.position + 1) != Some(&'=') {
+            self.position += 1;
+            let namespace = match first {
+                None => NamespaceConstraint::Null,
+                Some(None) => NamespaceConstraint::Any,
+                Some(Some(prefix)) => return Err(self.error(&format!("undeclared namespace prefix '{}'", prefix))),
+            };
+            if allow_universal && self.peek() == Some('*') {
+                self.position += 1;
+                return Ok((namespace, None));
+            }
+            return Ok((namespace, Some(self.parse_ident()?)));
+        }
+        match first {
+            Some(Some(name)) => Ok((default, Some(name))),
+            Some(None) if allow_universal => Ok((default, None)),
+            _ => Err(self.error("expected an identifier")),
+        }
+    }
+
+    fn parse_attribute(&mut self) -> Result<SimpleSelector, SelectorError> {
+        self.expect('[')?;
+        self.skip_whitespace();
+        let (namespace, name) = self.parse_qualified_name(NamespaceConstraint::Null, false)?;
+        let name = name.unwrap_or_default();
+        self.skip_whitespace();
+        let operator = match self.peek() {
+            Some(']') => {
+                self.position += 1;
+                return Ok(SimpleSelector::Attribute { namespace, name, operator: AttributeOperator::Exists, value: String::new(), case_insensitive: false });
+            }
+            Some('=') => AttributeOperator::Equals,
+            Some('~') => AttributeOperator::Includes,
+            Some('|') => AttributeOperator::DashMatch,
+            Some('^') => AttributeOperator::Prefix,
+            Some('$') => AttributeOperator::Suffix,
+            Some('*') => AttributeOperator::Substring,
+            _ => return Err(self.error("expected an attribute operator")),
+        };
+        self.position += 1;
+        if operator != AttributeOperator::Equals {
+            self.expect('=')?;
+        }
+        self.skip_whitespace();
+        let value = match self.peek() {
+            Some(quote @ ('"' | '\'')) => {
+                self.position += 1;
+                let mut value = String::new();
+                loop {
+                    match self.peek() {
+                        Some(c) if c == quote => break,
+                        Some('\\') => {
+                            self.position += 1;
+                            value.extend(self.peek());
+                        }
+                        Some(c) => value.push(c),
+                        None => return Err(self.error("unterminated string")),
+                    }
+                    self.position += 1;
+                }
+                self.position += 1;
+                value
+            }
+            _ => self.parse_ident()?,
+        };
+        self.skip_whitespace();
+        let mut case_insensitive = false;
+        if let Some(flag @ ('i' | 'I' | 's' | 'S')) = self.peek() {
+            case_insensitive = flag.eq_ignore_ascii_case(&'i');
+            self.position += 1;
+            self.skip_whitespace();
+        }
+        self.expect(']')?;
+        Ok(SimpleSelector::Attribute { namespace, name, operator, value, case_insensitive })
+    }
+
+    fn parse_pseudo(&mut self) -> Result<SimpleSelector, SelectorError> {
+        self.expect(':')?;
+        if self.peek() == Some(':') {
+            self.position += 1;
+            self.parse_ident()?;
+            return Ok(SimpleSelector::PseudoElement);
+        }
+        let name = self.parse_ident()?.to_ascii_lowercase();
+        if self.peek() == Some('(') {
+            self.position += 1;
+            self.skip_whitespace();
+            let pseudo = match name.as_str() {
+                "not" => PseudoClass::Not(self.parse_list()?),
+                "is" | "where" | "matches" => PseudoClass::Is(self.parse_list()?),
+                "has" => PseudoClass::Has(self.parse_relative_list()?),
+                "nth-child" | "nth-last-child" | "nth-of-type" | "nth-last-of-type" => PseudoClass::Nth {
+                    nth: self.parse_nth()?,
+                    from_end: name.contains("last"),
+                    of_type: name.ends_with("of-type"),
+                },
+                _ => return Err(self.error(&format!("unknown pseudo-class ':{}()'", name))),
+            };
+            self.skip_whitespace();
+            self.expect(')')?;
+            return Ok(SimpleSelector::PseudoClass(pseudo));
+        }
+        let pseudo = match name.as_str() {
+            "any-link" => PseudoClass::AnyLink,
+            "link" => PseudoClass::Link,
+            "visited" => PseudoClass::Visited,
+            "root" => PseudoClass::Root,
+            "scope" => PseudoClass::Scope,
+            "empty" => PseudoClass::Empty,
+            "first-child" => PseudoClass::Nth { nth: Nth { a: 0, b: 1 }, from_end: false, of_type: false },
+            "last-child" => PseudoClass::Nth { nth: Nth { a: 0, b: 1 }, from_end: true, of_type: false },
+            "first-of-type" => PseudoClass::Nth { nth: Nth { a: 0, b: 1 }, from_end: false, of_type: true },
+            "last-of-type" => PseudoClass::Nth { nth: Nth { a: 0, b: 1 }, from_end: true, of_type: true },
+            "only-child" => PseudoClass::Only { of_type: false },
+            "only-of-type" => PseudoClass::Only { of_type: true },
+            "checked" => PseudoClass::Checked,
+            "disabled" => PseudoClass::Disabled,
+            "enabled" => PseudoClass::Enabled,
+            // Legacy single-colon pseudo-elements
+            "before" | "after" | "first-line" | "first-letter" => return Ok(SimpleSelector::PseudoElement),
+            _ => match ElementState::from_pseudo_class(&name) {
+                Some(flag) => PseudoClass::State(flag),
+                None => return Err(self.error(&format!("unknown pseudo-class ':{}'", name))),
+            },
+        };
+        Ok(SimpleSelector::PseudoClass(pseudo))
+    }
+
+    /// Parse `An+B`, `odd` or `even`
+    fn parse_nth(&mut self) -> Result<Nth, SelectorError> {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | ' ')) {
+            self.position += 1;
+        }
+        let text: String = self.chars[start..self.position].iter().filter(|c| !c.is_whitespace()).collect::<String>().to_ascii_lowercase();
+        let invalid = || self.error(&format!("invalid An+B '{}'", text));
+        match text.as_str() {
+            "odd" => return Ok(Nth { a: 2, b: 1 }),
+            "even" => return Ok(Nth { a: 2, b: 0 }),
+            _ => {}
+        }
+        match text.split_once('n') {
+            Some((a, b)) => {
+                let a = match a {
+                    "" | "+" => 1,
+                    "-" => -1,
+                    _ => a.parse().map_err(|_| invalid())?,
+                };
+                let b = if b.is_empty() { 0 } else { b.parse().map_err(|_| invalid())? };
+                Ok(Nth { a, b })
+            }
+            None => Ok(Nth { a: 0, b: text.parse().map_err(|_| invalid())? }),
+        }
+    }
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || c == '-' || c == '\\' || !c.is_ascii()
+}
+
+impl Node {
+    /// Check whether this element matches a selector list, like
+    /// `Element.matches()`
+    pub fn matches(self: &Rc<Self>, selectors: &str) -> Result<bool, SelectorError> {
+        Ok(SelectorList::parse(selectors)?.matches(self))
+    }
+
+    /// Find the closest inclusive ancestor element matching a selector
+    /// list, like `Element.closest()`
+    ///
+    /// `:scope` refers to this element for every ancestor tried.
+    pub fn closest(self: &Rc<Self>, selectors: &str) -> Result<Option<Rc<Node>>, SelectorError> {
+        let list = SelectorList::parse(selectors)?;
+        let mut current = Some(Rc::clone(self)).filter(|node| node.tag_name().is_some());
+        while let Some(node) = current {
+            if list.matches_in_scope(&node, self) {
+                return Ok(Some(node));
+            }
+            current = parent_element(&node);
+        }
+        Ok(None)
+    }
+
+    /// Find the first descendant element matching a selector list, like
+    /// `querySelector()`
+    pub fn query_selector(self: &Rc<Self>, selectors: &str) -> Result<Option<Rc<Node>>, SelectorError> {
+        let list = SelectorList::parse(selectors)?;
+        let mut found = None;
+        self.visit_matching_descendants(&list, self, &mut |element| {
+            found = Some(Rc::clone(element));
+            false
+        });
+        Ok(found)
+    }
+
+    /// Find all descendant elements matching a selector list in tree
+    /// order, like `querySelectorAll()`
+    ///
+    /// The result is a snapshot that later mutations do not change.
+    pub fn query_selector_all(self: &Rc<Self>, selectors: &str) -> Result<Vec<Rc<Node>>, SelectorError> {
+        let list = SelectorList::parse(selectors)?;
+        let mut found = Vec::new();
+        self.visit_matching_descendants(&list, self, &mut |element| {
+            found.push(Rc::clone(element));
+            true
+        });
+        Ok(found)
+    }
+
+    /// Call `visit` with the matching descendants until it returns false,
+    /// returning whether it never did
+    fn visit_matching_descendants(&self, list: &SelectorList, scope: &Rc<Node>, visit: &mut dyn FnMut(&Rc<Node>) -> bool) -> bool {
+        for child in self.children.borrow().iter() {
+            if child.tag_name().is_some() && list.matches_in_scope(child, scope) && !visit(child) {
+                return false;
+            }
+            if !child.visit_matching_descendants(list, scope, visit) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Document;
+
+    fn element(document: &Document, parent: &Rc<Node>, tag: &str, attributes: &[(&str, &str)]) -> Rc<Node> {
+        let node = document.create_element(tag);
+        for (name, value) in attributes {
+            node.set_attribute(name, value);
+        }
+        parent.append_child(&node);
+        node
+    }
+
+    #[test]
+    fn test_matches_and_closest() {
+        let document = Document::new();
+        let html = element(&document, &document.root, "html", &[]);
+        let body = element(&document, &html, "body", &[]);
+        let list = element(&document, &body, "ul", &[("id", "todo"), ("data-kind", "Tasks")]);
+        let first = element(&document, &list, "li", &[("class", "item done")]);
+        let second = element(&document, &list, "li", &[("class", "item")]);
+        let link = element(&document, &second, "a", &[("href", "https://example.com/a")]);
+
+        assert!(first.matches("li.item.done").unwrap());
+        assert!(second.matches("ul#todo > li:not(.done)").unwrap());
+        assert!(second.matches("li + li, p").unwrap());
+        assert!(second.matches(".done ~ li:last-child:nth-child(2n)").unwrap());
+        assert!(!first.matches("li:nth-child(even)").unwrap());
+        assert!(link.matches("body a[href^='https:']:any-link").unwrap());
+        assert!(!link.matches(":visited").unwrap());
+        assert!(list.matches("[data-kind=tasks i]").unwrap());
+        assert!(!list.matches("[data-kind=tasks]").unwrap());
+        assert!(html.matches(":root").unwrap());
+        assert!(first.matches(":is(ol, ul) > :first-of-type").unwrap());
+        assert!(list.matches("ul:has(> li.done, p)").unwrap());
+        assert!(second.matches("li:has(a[href])").unwrap() && !first.matches("li:has(a)").unwrap());
+        assert!(first.matches(":has(+ li > a)").unwrap() && !second.matches(":has(~ li)").unwrap());
+        assert!(!list.matches(":has(> a)").unwrap());
+
+        assert!(Rc::ptr_eq(&link.closest("li").unwrap().unwrap(), &second));
+        assert!(Rc::ptr_eq(&link.closest("a").unwrap().unwrap(), &link));
+        assert!(Rc::ptr_eq(&link.closest("#todo > :scope, ul").unwrap().unwrap(), &list));
+        assert!(link.closest("table").unwrap().is_none());
+
+        assert!(Rc::ptr_eq(&document.root.query_selector("li + li").unwrap().unwrap(), &second));
+        let items = body.query_selector_all("li, a").unwrap();
+        assert_eq!(items.iter().map(|item| item.id).collect::<Vec<_>>(), vec![first.id, second.id, link.id]);
+        assert_eq!(list.query_selector_all(":scope > li").unwrap().len(), 2);
+        assert!(list.query_selector("ul").unwrap().is_none());
+        assert!(list.query_selector_all("li >").is_err());
+
+        for invalid in ["", "li >", "a[href", ":bogus", "li..item", "li:nth-child(x)", ":has()", "li:has(:is(:has(a)))"] {
+            assert!(first.matches(invalid).is_err(), "{} should not parse", invalid);
+        }
+    }
+
+    #[test]
+    fn test_namespace_aware_matching() {
+        use crate::namespaces::{SVG_NAMESPACE, XLINK_NAMESPACE};
+        let document = Document::new();
+        let body = element(&document, &document.root, "body", &[("data-x", "1")]);
+        let svg = document.create_element_ns(Some(SVG_NAMESPACE), "svg").unwrap();
+        body.append_child(&svg);
+        let object = document.create_element_ns(Some(SVG_NAMESPACE), "foreignObject").unwrap();
+        object.set_attribute_ns(Some(XLINK_NAMESPACE), "xlink:href", "#a").unwrap();
+        svg.append_child(&object);
+        let plain = document.create_element_ns(None, "svg").unwrap();
+        body.append_child(&plain);
+
+        assert!(object.matches("svg > foreignObject").unwrap());
+        assert!(!object.matches("foreignobject").unwrap());
+        assert!(body.matches("BODY[DATA-X]").unwrap());
+        assert!(object.matches("[*|href='#a']").unwrap());
+        assert!(!object.matches("[href]").unwrap());
+        assert!(plain.matches("|svg").unwrap() && !svg.matches("|svg").unwrap());
+        assert!(svg.matches("*|svg:first-of-type").unwrap() && plain.matches("svg:last-of-type").unwrap());
+        assert!(body.matches("[data-x|='1']").unwrap());
+
+        for invalid in ["svg|rect", "[xlink|href]", "|", "[*]"] {
+            assert!(body.matches(invalid).is_err(), "{} should not parse", invalid);
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//! # HTML Serialization
+//!
+//! This module turns DOM trees back into HTML markup, following the HTML
+//! fragment serialization algorithm, for `innerHTML` and `outerHTML`
+//! style output.
+//!
+//! ## Design Principles
+//!
+//! 1. **Namespace Aware Names**: Elements in the HTML, SVG and MathML
+//!    namespaces are written with their local name and others with their
+//!    qualified name. Attributes in the XML, XMLNS and XLink namespaces get
+//!    their conventional prefixes, whatever prefix they were set with.
+//!
+//! 2. **Current State**: Runtime attribute and text changes are
+//!    serialized, not the parsed values.
+//!
+//! 3. **Deterministic Output**: Attributes without a namespace are written
+//!    sorted by name, as their storage is unordered.
+
+use crate::namespaces::{QualifiedName, HTML_NAMESPACE, MATHML_NAMESPACE, SVG_NAMESPACE, XLINK_NAMESPACE, XMLNS_NAMESPACE, XML_NAMESPACE};
+use crate::{Node, NodeType};
+
+/// HTML elements that have no end tag and no children
+const VOID_ELEMENTS: [&str; 16] = [
+    "area", "base", "basefont", "bgsound", "br", "col", "embed", "frame", "hr", "img", "input", "keygen", "link", "meta", "source", "track",
+];
+
+/// HTML elements whose text children are written without escaping
+const RAW_TEXT_ELEMENTS: [&str; 8] = ["style", "script", "xmp", "iframe", "noembed", "noframes", "plaintext", "noscript"];
+
+impl Node {
+    /// Serialize the children of this node, like `innerHTML`
+    pub fn inner_html(&self) -> String {
+        let mut output = String::new();
+        for child in self.children.borrow().iter() {
+            serialize_node(child, self, &mut output);
+        }
+        output
+    }
+
+    /// Serialize this node and its descendants, like `outerHTML`
+    pub fn outer_html(&self) -> String {
+        match self.node_type {
+            NodeType::Document => self.inner_html(),
+            _ => {
+                let mut output = String::new();
+                let parent = self.parent.borrow().upgrade();
+                serialize_node(self, parent.as_deref().unwrap_or(self), &mut output);
+                output
+            }
+        }
+    }
+}
+
+/// Append the markup of `node`, a child of `parent`
+fn serialize_node(node: &Node, parent: &Node, output: &mut String) {
+    match &node.node_type {
+        NodeType::Document => output.push_str(&node.inner_html()),
+        NodeType::Text(_) => {
+            let text = node.character_data().unwrap_or_default();
+            let raw = parent.is_html_element() && parent.local_name().is_some_and(|name| RAW_TEXT_ELEMENTS.contains(&name));
+            if raw {
+                output.push_str(&text);
+            } else {
+                output.push_str(&escape(&text, false));
+            }
+        }
+        NodeType::Element { tag_name, .. } => {
+            let name = match node.namespace_uri() {
+                Some(HTML_NAMESPACE | SVG_NAMESPACE | MATHML_NAMESPACE) => node.local_name().unwrap_or(tag_name),
+                _ => tag_name,
+            };
+            output.push('<');
+            output.push_str(name);
+            for (attribute, value) in node.attributes_ns() {
+                output.push(' ');
+                output.push_str(&serialized_attribute_name(&attribute));
+                output.push_str("=\"");
+                output.push_str(&escape(&value, true));
+                output.push('"');
+            }
+            output.push('>');
+            if node.is_html_element() && VOID_ELEMENTS.contains(&name) {
+                return;
+            }
+            for child in node.children.borrow().iter() {
+                serialize_node(child, node, output);
+            }
+            output.push_str("</");
+            output.push_str(name);
+            output.push('>');
+        }
+    }
+}
+
+/// The name an attribute is written with
+fn serialized_attribute_name(attribute: &QualifiedName) -> String {
+    match attribute.namespace.as_deref() {
+        None => attribute.local_name.clone(),
+        Some(XML_NAMESPACE) => format!("xml:{}", attribute.local_name),
+        Some(XMLNS_NAMESPACE) if attribute.local_name == "xmlns" => "xmlns".to_string(),
+        Some(XMLNS_NAMESPACE) => format!("xmlns:{}", attribute.local_name),
+        Some(XLINK_NAMESPACE) => format!("xlink:{}", attribute.local_name),
+        Some(_) => attribute.qualified_name(),
+    }
+}
+
+/// Escape text or, in attribute mode, an attribute value
+fn escape(text: &str, attribute_mode: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '\u{a0}' => escaped.push_str("&nbsp;"),
+            '"' if attribute_mode => escaped.push_str("&quot;"),
+            '<' if !attribute_mode => escaped.push_str("&lt;"),
+            '>' if !attribute_mode => escaped.push_str("&gt;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::namespaces::{SVG_NAMESPACE, XLINK_NAMESPACE};
+    use crate::Document;
+
+    #[test]
+    fn test_serialize_namespaced_tree() {
+        let document = Document::new();
+        let div = document.create_element("div");
+        div.set_attribute("title", "a \"quoted\" & <b>");
+        document.root.append_child(&div);
+        div.append_child(&document.create_text_node("1 < 2 & 3"));
+        div.append_child(&document.create_element("br"));
+
+        let svg = document.create_element_ns(Some(SVG_NAMESPACE), "svg").unwrap();
+        let link = document.create_element_ns(Some(SVG_NAMESPACE), "a").unwrap();
+        link.set_attribute_ns(Some(XLINK_NAMESPACE), "l:href", "#target").unwrap();
+        svg.append_child(&link);
+        div.append_child(&svg);
+        let custom = document.create_element_ns(Some("urn:example"), "ex:widget").unwrap();
+        div.append_child(&custom);
+
+        let style = document.create_element("style");
+        style.append_child(&document.create_text_node("a > b {}"));
+        div.append_child(&style);
+
+        assert_eq!(
+            document.root.outer_html(),
+            "<div title=\"a &quot;quoted&quot; &amp; <b>\">1 &lt; 2 &amp; 3<br>\
+             <svg><a xlink:href=\"#target\"></a></svg><ex:widget></ex:widget><style>a > b {}</style></div>"
+        );
+        assert_eq!(link.outer_html(), "<a xlink:href=\"#target\"></a>");
+    }
+}
@@ -0,0 +1,227 @@
+//! # Spellchecking
+//!
+//! This module finds the misspelled words of editable text: which
+//! elements are checked, how their text splits into words and the
+//! `Spellchecker` that judges each word and suggests replacements. Painting
+//! the marks and offering the suggestions is left to the renderer and the
+//! shell.
+//!
+//! ## Design Principles
+//!
+//! 1. **Pluggable Checkers**: Everything goes through the `Spellchecker`
+//!    trait. The default `DictionarySpellchecker` loads a hunspell `.aff`
+//!    and `.dic` pair with spellbook, and a US English pair ships with the
+//!    engine, so a platform checker can replace it without touching paint.
+//! 2. **Editable Text Only**: Only text the user can edit is checked, and
+//!    the `spellcheck` attribute, inherited like `lang`, turns it off.
+//! 3. **Words, Not Tokens**: Numbers, single letters and all-caps
+//!    acronyms are not words a dictionary can judge, so they are never
+//!    marked.
+//! 4. **Character Offsets**: Misspellings are reported as character
+//!    ranges, which is what the paint pass and text replacement index by.
+
+use std::fmt;
+use crate::Node;
+
+/// The affix rules and word list of the US English dictionary bundled
+/// with the engine
+const BUNDLED_AFFIXES: &str = include_str!("../dictionaries/en_US.aff");
+const BUNDLED_DICTIONARY: &str = include_str!("../dictionaries/en_US.dic");
+
+/// Maximum number of suggestions offered for a word
+pub const MAX_SUGGESTIONS: usize = 5;
+
+/// Judges words and suggests replacements for misspelled ones
+pub trait Spellchecker: fmt::Debug {
+    /// Check whether a word is spelled correctly
+    fn check(&self, word: &str) -> bool;
+
+    /// Suggest replacements for a misspelled word, best first
+    fn suggest(&self, word: &str) -> Vec<String>;
+
+    /// Accept a word from now on, as in "Add to Dictionary"
+    fn add_word(&mut self, word: &str);
+}
+
+/// A misspelled word in a run of text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Misspelling {
+    /// Character offset of the first character
+    pub start: usize,
+    /// Character offset after the last character
+    pub end: usize,
+    pub word: String,
+}
+
+/// Split text into words, as character ranges
+///
+/// A word is a run of letters, with apostrophes inside it kept, as in
+/// "don't". Runs that contain digits, single letters and all-caps runs are
+/// skipped.
+pub fn words(text: &str) -> Vec<(usize, usize, String)> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut words = Vec::new();
+    let mut index = 0;
+    while index < chars.len() {
+        if !chars[index].is_alphanumeric() {
+            index += 1;
+            continue;
+        }
+        let start = index;
+        while index < chars.len()
+            && (chars[index].is_alphanumeric()
+                || (is_apostrophe(chars[index]) && chars.get(index + 1).is_some_and(|next| next.is_alphabetic())))
+        {
+            index += 1;
+        }
+        let word: String = chars[start..index].iter().collect();
+        let letters = word.chars().filter(|ch| ch.is_alphabetic()).count();
+        let acronym = letters > 1 && !word.chars().any(|ch| ch.is_lowercase());
+        if letters > 1 && !acronym && !word.chars().any(|ch| ch.is_numeric()) {
+            words.push((start, index, word));
+        }
+    }
+    words
+}
+
+fn is_apostrophe(ch: char) -> bool {
+    ch == '\'' || ch == '\u{2019}'
+}
+
+/// Find the misspelled words of a run of text
+pub fn misspellings(text: &str, checker: &dyn Spellchecker) -> Vec<Misspelling> {
+    words(text)
+        .into_iter()
+        .filter(|(_, _, word)| !checker.check(word))
+        .map(|(start, end, word)| Misspelling { start, end, word })
+        .collect()
+}
+
+/// Find the word at a character offset, including the offset just after
+/// it, where a caret after the word sits
+pub fn word_at(text: &str, offset: usize) -> Option<(usize, usize, String)> {
+    words(text).into_iter().find(|(start, end, _)| (*start..=*end).contains(&offset))
+}
+
+impl Node {
+    /// Check whether the text of this node is spellchecked
+    ///
+    /// Text is checked if its element is editable and no `spellcheck`
+    /// attribute on it or an ancestor is `false`. Text nodes are checked
+    /// with their element.
+    pub fn spellcheck_enabled(&self) -> bool {
+        if self.tag_name().is_none() {
+            return self.parent.borrow().upgrade().is_some_and(|parent| parent.spellcheck_enabled());
+        }
+        self.is_editable() && self.spellcheck_attribute().unwrap_or(true)
+    }
+
+    /// Get the state of the nearest `spellcheck` attribute, if any
+    fn spellcheck_attribute(&self) -> Option<bool> {
+        match self.get_attribute("spellcheck") {
+            Some(value) if value.is_empty() || value.eq_ignore_ascii_case("true") => Some(true),
+            Some(value) if value.eq_ignore_ascii_case("false") => Some(false),
+            _ => self.parent.borrow().upgrade().and_then(|parent| parent.spellcheck_attribute()),
+        }
+    }
+}
+
+/// A spellchecker backed by a hunspell dictionary
+///
+/// spellbook applies the dictionary's affix rules, so the inflections,
+/// prefixes and compounds its `.aff` file allows are accepted without
+/// being listed, and suggests replacements the way hunspell does.
+#[derive(Debug, Clone)]
+pub struct DictionarySpellchecker {
+    dictionary: spellbook::Dictionary,
+}
+
+impl DictionarySpellchecker {
+    /// Create a checker with the bundled US English dictionary
+    pub fn new() -> Self {
+        Self::from_hunspell(BUNDLED_AFFIXES, BUNDLED_DICTIONARY).expect("the bundled dictionary is valid")
+    }
+
+    /// Create a checker from the contents of a hunspell `.aff` and `.dic`
+    /// pair
+    pub fn from_hunspell(aff: &str, dic: &str) -> Result<Self, spellbook::ParseDictionaryError> {
+        Ok(DictionarySpellchecker { dictionary: spellbook::Dictionary::new(aff, dic)? })
+    }
+}
+
+impl Default for DictionarySpellchecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Spellchecker for DictionarySpellchecker {
+    fn check(&self, word: &str) -> bool {
+        self.dictionary.check(word)
+    }
+
+    fn suggest(&self, word: &str) -> Vec<String> {
+        let mut suggestions = Vec::new();
+        self.dictionary.suggest(word, &mut suggestions);
+        suggestions.truncate(MAX_SUGGESTIONS);
+        suggestions
+    }
+
+    fn add_word(&mut self, word: &str) {
+        // Anything after a slash would be read as affix flags
+        let word = word.trim().replace('/', "");
+        if !word.is_empty() {
+            if let Err(error) = self.dictionary.add(&word) {
+                println!("⚠️ Could not add {:?} to the dictionary: {}", word, error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Document;
+
+    #[test]
+    fn test_misspellings_in_editable_text() {
+        let mut checker = DictionarySpellchecker::new();
+        // Inflections and prefixes come from the affix rules
+        for word in ["running", "happiest", "studies", "reopened", "countries", "children's", "unhappily", "Don\u{2019}t"] {
+            assert!(checker.check(word), "{word}");
+        }
+        for word in ["runing", "happyest", "studys", "childs"] {
+            assert!(!checker.check(word), "{word}");
+        }
+
+        let text = "Teh quick brown fox jumped over the dogs' NASA beds 42 times, don't recieve";
+        let found: Vec<String> = misspellings(text, &checker).into_iter().map(|misspelling| misspelling.word).collect();
+        assert_eq!(found, vec!["Teh", "recieve"]);
+        let first = &misspellings(text, &checker)[0];
+        assert_eq!((first.start, first.end), (0, 3));
+        assert_eq!(checker.suggest("teh")[0], "the");
+        assert!(checker.suggest("recieve").contains(&"receive".to_string()));
+        assert_eq!(word_at(text, 3).map(|(_, _, word)| word), Some("Teh".to_string()));
+
+        checker.add_word("recieve");
+        assert!(checker.check("recieve"));
+
+        let document = Document::new();
+        let editor = document.create_element("div");
+        editor.set_attribute("contenteditable", "");
+        let code = document.create_element("code");
+        code.set_attribute("spellcheck", "false");
+        let text = document.create_text_node("fn mian");
+        code.append_child(&text);
+        editor.append_child(&code);
+        let paragraph = document.create_element("p");
+        document.root.append_child(&editor);
+        document.root.append_child(&paragraph);
+
+        assert!(editor.spellcheck_enabled());
+        assert!(!text.spellcheck_enabled());
+        assert!(!paragraph.spellcheck_enabled());
+        code.set_attribute("spellcheck", "");
+        assert!(text.spellcheck_enabled());
+    }
+}
@@ -0,0 +1,154 @@
+//! # Top Layer
+//!
+//! This module keeps the top layer of each document: the elements, such as
+//! modal dialogs, open popovers and fullscreen elements, that render above
+//! everything else in the page whatever their place in the tree. It also decides which nodes
+//! are inert, because a modal element in the top layer blocks interaction
+//! with the rest of the document.
+//!
+//! ## Design Principles
+//!
+//! 1. **Ordered, Not Z-Indexed**: The top layer is a list. An element added
+//!    last paints last and is the one a user interacts with; adding an
+//!    element that is already in the layer moves it to the end.
+//!
+//! 2. **Keyed by Owner**: Each document's layer is kept under the id of the
+//!    document that owns the nodes, so layout, input and script bindings
+//!    reach it from any node without holding the `Document`.
+//!
+//! 3. **Removal Leaves the Layer**: An element that is removed from the
+//!    tree leaves the top layer in its removal steps, so the layer never
+//!    holds disconnected elements.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+use crate::element_state::ElementState;
+use crate::Node;
+
+thread_local! {
+    static TOP_LAYERS: RefCell<HashMap<u64, Vec<Weak<Node>>>> = RefCell::new(HashMap::new());
+}
+
+/// Add an element to the end of its document's top layer
+pub fn add(element: &Rc<Node>) {
+    let owner = element.owner_document_id().unwrap_or(0);
+    remove(element);
+    TOP_LAYERS.with(|layers| layers.borrow_mut().entry(owner).or_default().push(Rc::downgrade(element)));
+}
+
+/// Remove an element from its document's top layer, returning whether it
+/// was in it
+pub fn remove(element: &Node) -> bool {
+    let owner = element.owner_document_id().unwrap_or(0);
+    TOP_LAYERS.with(|layers| {
+        let mut layers = layers.borrow_mut();
+        let Some(layer) = layers.get_mut(&owner) else {
+            return false;
+        };
+        let before = layer.len();
+        layer.retain(|entry| entry.upgrade().is_some_and(|node| node.id != element.id));
+        layer.len() != before
+    })
+}
+
+/// Check whether an element is in the top layer
+pub fn contains(element: &Node) -> bool {
+    elements(element).iter().any(|node| node.id == element.id)
+}
+
+/// Take an element that is being removed from the tree out of the top
+/// layer, ending its modality, closing it if it is a popover and taking it
+/// out of fullscreen
+pub(crate) fn removal_steps(element: &Node) {
+    if remove(element) {
+        element.set_element_state(ElementState::MODAL, false);
+        element.set_element_state(ElementState::POPOVER_OPEN, false);
+        element.set_element_state(ElementState::FULLSCREEN, false);
+    }
+}
+
+/// Get the top layer of the document that owns `node`, bottom first
+pub fn elements(node: &Node) -> Vec<Rc<Node>> {
+    let owner = node.owner_document_id().unwrap_or(0);
+    TOP_LAYERS.with(|layers| {
+        layers
+            .borrow()
+            .get(&owner)
+            .map(|layer| layer.iter().filter_map(Weak::upgrade).collect())
+            .unwrap_or_default()
+    })
+}
+
+/// Get the topmost modal element of the document that owns `node`, the one
+/// that makes the rest of the document inert
+pub fn blocking_element(node: &Node) -> Option<Rc<Node>> {
+    elements(node)
+        .into_iter()
+        .rev()
+        .find(|element| element.element_state().contains(ElementState::MODAL))
+}
+
+impl Node {
+    /// Check whether this node is inert: it, or an ancestor, has the
+    /// `inert` attribute, or a modal element in the top layer blocks it
+    ///
+    /// Inert nodes are not hit by the pointer and cannot be focused.
+    pub fn is_inert(&self) -> bool {
+        let blocking = blocking_element(self);
+        let mut inside_blocking = blocking.as_ref().is_none_or(|blocking| blocking.id == self.id);
+        if self.has_attribute("inert") {
+            return true;
+        }
+        let mut current = self.parent.borrow().upgrade();
+        while let Some(node) = current {
+            if node.has_attribute("inert") {
+                return true;
+            }
+            inside_blocking |= blocking.as_ref().is_some_and(|blocking| blocking.id == node.id);
+            current = node.parent.borrow().upgrade();
+        }
+        !inside_blocking
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Document;
+
+    #[test]
+    fn test_modal_elements_make_the_rest_inert() {
+        let document = Document::new();
+        let body = document.create_element("body");
+        document.root.append_child(&body);
+        let button = document.create_element("button");
+        let first = document.create_element("dialog");
+        let second = document.create_element("dialog");
+        let inner = document.create_element("input");
+        second.append_child(&inner);
+        for node in [&button, &first, &second] {
+            body.append_child(node);
+        }
+        assert!(!button.is_inert());
+
+        add(&first);
+        add(&second);
+        add(&first);
+        let layer: Vec<u64> = elements(&body).iter().map(|node| node.id).collect();
+        assert_eq!(layer, vec![second.id, first.id]);
+        // Only modal elements block
+        assert!(blocking_element(&body).is_none() && !button.is_inert());
+
+        second.set_element_state(ElementState::MODAL, true);
+        assert!(blocking_element(&body).is_some_and(|node| Rc::ptr_eq(&node, &second)));
+        assert!(button.is_inert() && first.is_inert() && !inner.is_inert() && !second.is_inert());
+
+        // Removing an element from the tree takes it out of the layer
+        body.remove_child(&second);
+        assert!(!contains(&second) && !button.is_inert());
+
+        button.set_attribute("inert", "");
+        assert!(button.is_inert());
+    }
+}
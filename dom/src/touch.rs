@@ -0,0 +1,271 @@
+//! # Touch Input
+//!
+//! This module turns the contacts of a touch surface into DOM touch events
+//! and into the gestures the shell performs when the page lets it: a
+//! one-finger pan scrolls, a two-finger pinch zooms, and a tap clicks.
+//!
+//! ## Design Principles
+//!
+//! 1. **Touches Stay With Their Target**: A contact's events go to the
+//!    element it started on, however far it moves, as in Touch Events.
+//!
+//! 2. **The Page Decides First**: Gestures are recognized from the same
+//!    contacts, but a touch event the page cancels ends the gesture of the
+//!    sequence, so `preventDefault()` on `touchmove` stops the scroll and on
+//!    `touchstart` or `touchend` stops the click.
+//!
+//! 3. **No Click Delay**: There is no double-tap zoom to wait for, so a
+//!    tap clicks as soon as the finger lifts instead of 300ms later.
+
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use crate::event_types::{Touch, TouchEvent};
+use crate::Node;
+
+/// How far a contact may move, in CSS pixels, and still be a tap
+pub const TAP_SLOP: f64 = 10.0;
+
+/// How long a contact may stay down and still be a tap
+pub const TAP_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The stage a contact is in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TouchPhase {
+    Start,
+    Move,
+    End,
+    Cancel,
+}
+
+impl TouchPhase {
+    /// The type of the touch event of this phase
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            TouchPhase::Start => "touchstart",
+            TouchPhase::Move => "touchmove",
+            TouchPhase::End => "touchend",
+            TouchPhase::Cancel => "touchcancel",
+        }
+    }
+}
+
+/// The contacts on a touch surface
+#[derive(Default)]
+pub struct TouchTracker {
+    touches: Vec<Touch>,
+    /// The contact that went down first in the current sequence
+    primary: Option<i32>,
+}
+
+impl TouchTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the number of contacts on the surface
+    pub fn active_count(&self) -> usize {
+        self.touches.len()
+    }
+
+    /// Check whether a contact is the primary one, the first to go down
+    /// while no other was
+    pub fn is_primary(&self, identifier: i32) -> bool {
+        self.primary == Some(identifier)
+    }
+
+    /// Record a contact change and build its touch event
+    ///
+    /// `hit` is the element under a starting contact. Returns `None` for
+    /// moves and ends of contacts that never started.
+    pub fn update(&mut self, phase: TouchPhase, identifier: i32, x: f64, y: f64, hit: Option<Rc<Node>>) -> Option<TouchEvent> {
+        let changed = match phase {
+            TouchPhase::Start => {
+                self.touches.retain(|touch| touch.identifier != identifier);
+                if self.touches.is_empty() {
+                    self.primary = Some(identifier);
+                }
+                let touch = Touch::new(identifier, hit, x, y);
+                self.touches.push(touch.clone());
+                touch
+            }
+            TouchPhase::Move => {
+                let touch = self.touches.iter_mut().find(|touch| touch.identifier == identifier)?;
+                touch.client_x = x;
+                touch.client_y = y;
+                touch.clone()
+            }
+            TouchPhase::End | TouchPhase::Cancel => {
+                let index = self.touches.iter().position(|touch| touch.identifier == identifier)?;
+                let mut touch = self.touches.remove(index);
+                touch.client_x = x;
+                touch.client_y = y;
+                touch
+            }
+        };
+
+        let mut event = TouchEvent::new(phase.event_type(), true, phase != TouchPhase::Cancel);
+        event.touches = self.touches.clone();
+        event.target_touches = self
+            .touches
+            .iter()
+            .filter(|touch| same_target(&touch.target, &changed.target))
+            .cloned()
+            .collect();
+        event.changed_touches = vec![changed];
+        Some(event)
+    }
+}
+
+fn same_target(a: &Option<Rc<Node>>, b: &Option<Rc<Node>>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// A gesture the shell performs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gesture {
+    /// Scroll the viewport by a distance, in CSS pixels
+    Pan { dx: f64, dy: f64 },
+    /// Scale the page around a point
+    Pinch { scale: f64, center_x: f64, center_y: f64 },
+    /// Click at a point
+    Tap { x: f64, y: f64 },
+}
+
+struct Contact {
+    identifier: i32,
+    start: (f64, f64),
+    last: (f64, f64),
+}
+
+/// Recognizes pans, pinches and taps in a sequence of contacts
+///
+/// A sequence runs from the first contact going down until the last one
+/// lifts.
+#[derive(Default)]
+pub struct GestureRecognizer {
+    contacts: Vec<Contact>,
+    started: Option<Instant>,
+    /// A contact left the tap slop, or two were down
+    moved: bool,
+    /// The page cancelled a touch event of the sequence
+    blocked: bool,
+}
+
+impl GestureRecognizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stop recognizing gestures until the sequence ends, because the page
+    /// cancelled one of its touch events
+    pub fn block(&mut self) {
+        self.blocked = true;
+    }
+
+    /// Record a contact change, returning the gesture step it makes
+    pub fn update(&mut self, phase: TouchPhase, identifier: i32, x: f64, y: f64, now: Instant) -> Option<Gesture> {
+        match phase {
+            TouchPhase::Start => {
+                if self.contacts.is_empty() {
+                    self.started = Some(now);
+                    self.moved = false;
+                    self.blocked = false;
+                }
+                self.contacts.retain(|contact| contact.identifier != identifier);
+                self.contacts.push(Contact { identifier, start: (x, y), last: (x, y) });
+                if self.contacts.len() > 1 {
+                    self.moved = true;
+                }
+                None
+            }
+            TouchPhase::Move => {
+                let index = self.contacts.iter().position(|contact| contact.identifier == identifier)?;
+                let previous = self.contacts[index].last;
+                let old_span = self.span();
+                self.contacts[index].last = (x, y);
+                let start = self.contacts[index].start;
+                if (x - start.0).hypot(y - start.1) > TAP_SLOP {
+                    self.moved = true;
+                }
+                if self.blocked || !self.moved {
+                    return None;
+                }
+                match (self.contacts.len(), old_span, self.span()) {
+                    (1, _, _) => Some(Gesture::Pan { dx: previous.0 - x, dy: previous.1 - y }),
+                    (2, Some((old, _)), Some((new, center))) if old > 0.0 => {
+                        Some(Gesture::Pinch { scale: new / old, center_x: center.0, center_y: center.1 })
+                    }
+                    _ => None,
+                }
+            }
+            TouchPhase::End | TouchPhase::Cancel => {
+                let index = self.contacts.iter().position(|contact| contact.identifier == identifier)?;
+                self.contacts.remove(index);
+                if phase == TouchPhase::Cancel {
+                    self.blocked = true;
+                }
+                let quick = self.started.is_some_and(|started| now.duration_since(started) < TAP_TIMEOUT);
+                let tap = self.contacts.is_empty() && quick && !self.moved && !self.blocked;
+                tap.then_some(Gesture::Tap { x, y })
+            }
+        }
+    }
+
+    /// The distance between the first two contacts and their midpoint
+    fn span(&self) -> Option<(f64, (f64, f64))> {
+        let [a, b, ..] = &self.contacts[..] else {
+            return None;
+        };
+        let distance = (a.last.0 - b.last.0).hypot(a.last.1 - b.last.1);
+        Some((distance, ((a.last.0 + b.last.0) / 2.0, (a.last.1 + b.last.1) / 2.0)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Document;
+
+    #[test]
+    fn test_touch_lists_and_gestures() {
+        let document = Document::new();
+        let left = document.create_element("div");
+        let right = document.create_element("div");
+        let mut tracker = TouchTracker::new();
+
+        tracker.update(TouchPhase::Start, 1, 10.0, 10.0, Some(Rc::clone(&left))).unwrap();
+        let second = tracker.update(TouchPhase::Start, 2, 90.0, 10.0, Some(Rc::clone(&right))).unwrap();
+        assert_eq!((second.touches.len(), second.target_touches.len()), (2, 1));
+        assert!(tracker.is_primary(1) && !tracker.is_primary(2));
+        let moved = tracker.update(TouchPhase::Move, 1, 50.0, 50.0, None).unwrap();
+        assert!(moved.changed_touches[0].target.as_ref().is_some_and(|target| Rc::ptr_eq(target, &left)));
+        let ended = tracker.update(TouchPhase::End, 1, 50.0, 50.0, None).unwrap();
+        assert_eq!((ended.base.event_type.as_str(), ended.touches.len(), ended.changed_touches[0].identifier), ("touchend", 1, 1));
+        assert!(tracker.update(TouchPhase::Move, 1, 0.0, 0.0, None).is_none());
+
+        let now = Instant::now();
+        let mut gestures = GestureRecognizer::new();
+        gestures.update(TouchPhase::Start, 1, 100.0, 100.0, now);
+        assert_eq!(gestures.update(TouchPhase::Move, 1, 104.0, 100.0, now), None);
+        assert_eq!(gestures.update(TouchPhase::End, 1, 104.0, 100.0, now), Some(Gesture::Tap { x: 104.0, y: 100.0 }));
+
+        gestures.update(TouchPhase::Start, 1, 100.0, 100.0, now);
+        assert_eq!(gestures.update(TouchPhase::Move, 1, 100.0, 70.0, now), Some(Gesture::Pan { dx: 0.0, dy: 30.0 }));
+        assert_eq!(gestures.update(TouchPhase::End, 1, 100.0, 70.0, now), None);
+
+        gestures.update(TouchPhase::Start, 1, 100.0, 100.0, now);
+        gestures.update(TouchPhase::Start, 2, 200.0, 100.0, now);
+        assert_eq!(gestures.update(TouchPhase::Move, 2, 300.0, 100.0, now), Some(Gesture::Pinch { scale: 2.0, center_x: 200.0, center_y: 100.0 }));
+
+        // A cancelled touchstart means no click
+        gestures.update(TouchPhase::End, 1, 100.0, 100.0, now);
+        gestures.update(TouchPhase::End, 2, 300.0, 100.0, now);
+        gestures.update(TouchPhase::Start, 3, 10.0, 10.0, now);
+        gestures.block();
+        assert_eq!(gestures.update(TouchPhase::End, 3, 10.0, 10.0, now), None);
+    }
+}
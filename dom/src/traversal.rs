@@ -0,0 +1,159 @@
+//! # Tree Traversal
+//!
+//! This module provides a `TreeWalker` over a subtree, like
+//! `document.createTreeWalker()`, and the find-and-replace built on it
+//! that rewrites matching text throughout a subtree.
+//!
+//! ## Design Principles
+//!
+//! 1. **Filtered by Kind**: A walker only stops at the node kinds in its
+//!    `what_to_show` mask, with the DOM's `NodeFilter` bit values.
+//!
+//! 2. **Walks the Live Tree**: The walker keeps only its current node and
+//!    finds the next one from there, so it follows mutations made between
+//!    steps, including changes to the text it just returned.
+//!
+//! 3. **Rendered Text Only**: Find-and-replace skips the contents of
+//!    `<script>` and `<style>`, whose text is not shown to the user.
+
+use std::rc::Rc;
+use crate::{Node, NodeType};
+
+/// `NodeFilter.SHOW_ALL`
+pub const SHOW_ALL: u32 = 0xFFFF_FFFF;
+/// `NodeFilter.SHOW_ELEMENT`
+pub const SHOW_ELEMENT: u32 = 0x1;
+/// `NodeFilter.SHOW_TEXT`
+pub const SHOW_TEXT: u32 = 0x4;
+/// `NodeFilter.SHOW_DOCUMENT`
+pub const SHOW_DOCUMENT: u32 = 0x100;
+
+/// A cursor over the nodes of a subtree in tree order
+#[derive(Debug)]
+pub struct TreeWalker {
+    root: Rc<Node>,
+    what_to_show: u32,
+    current: Rc<Node>,
+}
+
+impl TreeWalker {
+    /// Create a walker over `root` and its descendants, positioned at the root
+    pub fn new(root: Rc<Node>, what_to_show: u32) -> Self {
+        TreeWalker { current: Rc::clone(&root), root, what_to_show }
+    }
+
+    /// The node the walker is at
+    pub fn current_node(&self) -> &Rc<Node> {
+        &self.current
+    }
+
+    /// Move to the next shown node in tree order, like `nextNode()`
+    pub fn next_node(&mut self) -> Option<Rc<Node>> {
+        let mut node = Rc::clone(&self.current);
+        loop {
+            node = self.following(&node)?;
+            if self.shows(&node) {
+                self.current = Rc::clone(&node);
+                return Some(node);
+            }
+        }
+    }
+
+    /// Check whether the walker stops at a node
+    fn shows(&self, node: &Node) -> bool {
+        let bit = match node.node_type {
+            NodeType::Element { .. } => SHOW_ELEMENT,
+            NodeType::Text(_) => SHOW_TEXT,
+            NodeType::Document => SHOW_DOCUMENT,
+        };
+        self.what_to_show & bit != 0
+    }
+
+    /// The node after `node` in tree order, within the root
+    fn following(&self, node: &Rc<Node>) -> Option<Rc<Node>> {
+        if let Some(first) = node.children.borrow().first() {
+            return Some(Rc::clone(first));
+        }
+        let mut node = Rc::clone(node);
+        while !Rc::ptr_eq(&node, &self.root) {
+            let parent = node.parent.borrow().upgrade()?;
+            let next = {
+                let siblings = parent.children.borrow();
+                let index = siblings.iter().position(|sibling| Rc::ptr_eq(sibling, &node))?;
+                siblings.get(index + 1).cloned()
+            };
+            if next.is_some() {
+                return next;
+            }
+            node = parent;
+        }
+        None
+    }
+}
+
+/// Replace every occurrence of `find` in the rendered text below `root`,
+/// returning the number of replacements
+///
+/// Matches are found within single text nodes.
+pub fn find_and_replace(root: &Rc<Node>, find: &str, replacement: &str) -> usize {
+    if find.is_empty() {
+        return 0;
+    }
+    let mut walker = TreeWalker::new(Rc::clone(root), SHOW_TEXT);
+    let mut count = 0;
+    while let Some(text) = walker.next_node() {
+        if in_unrendered_element(&text, root) {
+            continue;
+        }
+        let data = text.character_data().unwrap_or_default();
+        let matches = data.matches(find).count();
+        if matches > 0 {
+            text.set_character_data(&data.replace(find, replacement));
+            count += matches;
+        }
+    }
+    count
+}
+
+/// Check whether a node is inside a `<script>` or `<style>` below `root`
+fn in_unrendered_element(node: &Rc<Node>, root: &Rc<Node>) -> bool {
+    let mut current = node.parent.borrow().upgrade();
+    while let Some(ancestor) = current {
+        if ancestor.is_html_element() && matches!(ancestor.local_name(), Some("script" | "style")) {
+            return true;
+        }
+        if Rc::ptr_eq(&ancestor, root) {
+            break;
+        }
+        current = ancestor.parent.borrow().upgrade();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Document;
+
+    #[test]
+    fn test_walker_and_find_and_replace() {
+        let document = Document::new();
+        let body = document.create_element("body");
+        document.root.append_child(&body);
+        let paragraph = document.create_element("p");
+        paragraph.append_child(&document.create_text_node("cats and cats"));
+        body.append_child(&paragraph);
+        let script = document.create_element("script");
+        script.append_child(&document.create_text_node("var cats;"));
+        body.append_child(&script);
+        body.append_child(&document.create_text_node("more cats"));
+
+        let mut walker = TreeWalker::new(Rc::clone(&body), SHOW_ELEMENT);
+        let names: Vec<_> = std::iter::from_fn(|| walker.next_node()).map(|node| node.tag_name().unwrap().to_string()).collect();
+        assert_eq!(names, ["p", "script"]);
+
+        assert_eq!(find_and_replace(&body, "cats", "dogs"), 3);
+        assert_eq!(body.text_content(), "dogs and dogsvar cats;more dogs");
+        assert_eq!(find_and_replace(&paragraph, "", "x"), 0);
+    }
+}
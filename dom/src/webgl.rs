@@ -0,0 +1,588 @@
+//! # WebGL
+//!
+//! This module keeps the state behind `canvas.getContext('webgl')`: the
+//! shaders, programs and buffers a page creates, and the clears and draws
+//! it issues, recorded as commands for the renderer to replay on the
+//! canvas's own GPU texture.
+//!
+//! ## Design Principles
+//!
+//! 1. **Record, Then Replay**: Calls never touch the GPU. They validate
+//!    their arguments, set `getError()` like a driver would and append
+//!    commands, so scripts run the same with or without a GPU.
+//!
+//! 2. **A Shader Subset**: GLSL is not compiled. A vertex shader that
+//!    passes an attribute through to `gl_Position` and a fragment shader
+//!    that writes a constant or a `uniform vec4` to `gl_FragColor` are
+//!    understood; other programs still link, so feature detection and
+//!    setup code work, but say why in their info log and do not draw.
+//!
+//! 3. **Triangles Only**: Strips and fans are expanded into triangle
+//!    lists here, so the renderer has a single pipeline to run.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+pub const DEPTH_BUFFER_BIT: u32 = 0x0100;
+pub const COLOR_BUFFER_BIT: u32 = 0x4000;
+pub const POINTS: u32 = 0x0000;
+pub const LINES: u32 = 0x0001;
+pub const TRIANGLES: u32 = 0x0004;
+pub const TRIANGLE_STRIP: u32 = 0x0005;
+pub const TRIANGLE_FAN: u32 = 0x0006;
+pub const ARRAY_BUFFER: u32 = 0x8892;
+pub const STATIC_DRAW: u32 = 0x88E4;
+pub const DYNAMIC_DRAW: u32 = 0x88E8;
+pub const FLOAT: u32 = 0x1406;
+pub const FRAGMENT_SHADER: u32 = 0x8B30;
+pub const VERTEX_SHADER: u32 = 0x8B31;
+pub const COMPILE_STATUS: u32 = 0x8B81;
+pub const LINK_STATUS: u32 = 0x8B82;
+pub const NO_ERROR: u32 = 0;
+pub const INVALID_ENUM: u32 = 0x0500;
+pub const INVALID_VALUE: u32 = 0x0501;
+pub const INVALID_OPERATION: u32 = 0x0502;
+
+/// A recorded operation on the canvas
+#[derive(Debug, Clone, PartialEq)]
+pub enum WebGlCommand {
+    /// Fill the whole canvas with a color
+    Clear { color: [f32; 4] },
+    /// Set the area draws map clip space onto, in canvas pixels from the
+    /// bottom left
+    Viewport { x: i32, y: i32, width: u32, height: u32 },
+    /// Fill triangles in clip space, three vertices each
+    DrawTriangles { vertices: Vec<[f32; 2]>, color: [f32; 4] },
+}
+
+/// Where a program's fragment color comes from
+#[derive(Debug, Clone, PartialEq)]
+enum FragmentColor {
+    Constant([f32; 4]),
+    Uniform(String),
+}
+
+#[derive(Debug)]
+struct Shader {
+    is_vertex: bool,
+    source: String,
+    compiled: bool,
+}
+
+#[derive(Debug, Default)]
+struct Program {
+    shaders: Vec<u32>,
+    linked: bool,
+    info_log: String,
+    /// Attributes of the vertex shader; the index is the location
+    attributes: Vec<String>,
+    /// The attribute passed to `gl_Position`
+    position: Option<String>,
+    color: Option<FragmentColor>,
+    uniforms: HashMap<String, [f32; 4]>,
+}
+
+impl Program {
+    fn draws(&self) -> bool {
+        self.position.is_some() && self.color.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct AttributePointer {
+    buffer: u32,
+    size: usize,
+    stride: usize,
+    offset: usize,
+}
+
+#[derive(Debug)]
+struct WebGlState {
+    width: u32,
+    height: u32,
+    clear_color: [f32; 4],
+    next_id: u32,
+    shaders: HashMap<u32, Shader>,
+    programs: HashMap<u32, Program>,
+    buffers: HashMap<u32, Vec<f32>>,
+    array_buffer: Option<u32>,
+    current_program: Option<u32>,
+    pointers: HashMap<u32, AttributePointer>,
+    enabled: HashSet<u32>,
+    error: u32,
+    commands: Vec<WebGlCommand>,
+    unsupported_draws: u64,
+}
+
+impl WebGlState {
+    fn fail(&mut self, error: u32) {
+        // Only the first error is kept until it is read
+        if self.error == NO_ERROR {
+            self.error = error;
+        }
+    }
+}
+
+/// The WebGL state of one canvas
+///
+/// Cloning shares the context, so the script binding records commands
+/// the renderer takes.
+#[derive(Debug, Clone)]
+pub struct WebGlContext {
+    state: Arc<Mutex<WebGlState>>,
+}
+
+impl WebGlContext {
+    /// Create the context of a canvas of the given size
+    pub fn new(width: u32, height: u32) -> Self {
+        let state = WebGlState {
+            width,
+            height,
+            clear_color: [0.0; 4],
+            next_id: 1,
+            shaders: HashMap::new(),
+            programs: HashMap::new(),
+            buffers: HashMap::new(),
+            array_buffer: None,
+            current_program: None,
+            pointers: HashMap::new(),
+            enabled: HashSet::new(),
+            error: NO_ERROR,
+            commands: Vec::new(),
+            unsupported_draws: 0,
+        };
+        WebGlContext { state: Arc::new(Mutex::new(state)) }
+    }
+
+    /// Get the drawing buffer size
+    pub fn size(&self) -> (u32, u32) {
+        let state = self.lock();
+        (state.width, state.height)
+    }
+
+    /// Take the commands recorded since the last call
+    pub fn take_commands(&self) -> Vec<WebGlCommand> {
+        std::mem::take(&mut self.lock().commands)
+    }
+
+    /// Get the number of draws skipped because their program is outside
+    /// the supported shader subset
+    pub fn unsupported_draws(&self) -> u64 {
+        self.lock().unsupported_draws
+    }
+
+    /// `getError()`: get and reset the first error since the last call
+    pub fn get_error(&self) -> u32 {
+        std::mem::replace(&mut self.lock().error, NO_ERROR)
+    }
+
+    pub fn clear_color(&self, color: [f32; 4]) {
+        self.lock().clear_color = color.map(|channel| channel.clamp(0.0, 1.0));
+    }
+
+    pub fn clear(&self, mask: u32) {
+        let mut state = self.lock();
+        if mask & !(COLOR_BUFFER_BIT | DEPTH_BUFFER_BIT | 0x0400) != 0 {
+            return state.fail(INVALID_VALUE);
+        }
+        if mask & COLOR_BUFFER_BIT != 0 {
+            let color = state.clear_color;
+            state.commands.push(WebGlCommand::Clear { color });
+        }
+    }
+
+    pub fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        let mut state = self.lock();
+        if width < 0 || height < 0 {
+            return state.fail(INVALID_VALUE);
+        }
+        state.commands.push(WebGlCommand::Viewport { x, y, width: width as u32, height: height as u32 });
+    }
+
+    /// `createShader()`; `None` for an unknown shader type
+    pub fn create_shader(&self, shader_type: u32) -> Option<u32> {
+        let mut state = self.lock();
+        let is_vertex = match shader_type {
+            VERTEX_SHADER => true,
+            FRAGMENT_SHADER => false,
+            _ => {
+                state.fail(INVALID_ENUM);
+                return None;
+            }
+        };
+        let id = next_id(&mut state);
+        state.shaders.insert(id, Shader { is_vertex, source: String::new(), compiled: false });
+        Some(id)
+    }
+
+    pub fn shader_source(&self, shader: u32, source: &str) {
+        let mut state = self.lock();
+        match state.shaders.get_mut(&shader) {
+            Some(entry) => entry.source = source.to_string(),
+            None => state.fail(INVALID_VALUE),
+        }
+    }
+
+    /// `compileShader()`: succeeds for any source with a `main` function
+    pub fn compile_shader(&self, shader: u32) {
+        let mut state = self.lock();
+        match state.shaders.get_mut(&shader) {
+            Some(entry) => entry.compiled = entry.source.contains("main"),
+            None => state.fail(INVALID_VALUE),
+        }
+    }
+
+    /// `getShaderParameter(shader, COMPILE_STATUS)`
+    pub fn shader_compiled(&self, shader: u32) -> bool {
+        self.lock().shaders.get(&shader).is_some_and(|entry| entry.compiled)
+    }
+
+    pub fn create_program(&self) -> u32 {
+        let mut state = self.lock();
+        let id = next_id(&mut state);
+        state.programs.insert(id, Program::default());
+        id
+    }
+
+    pub fn attach_shader(&self, program: u32, shader: u32) {
+        let mut state = self.lock();
+        if !state.shaders.contains_key(&shader) {
+            return state.fail(INVALID_VALUE);
+        }
+        match state.programs.get_mut(&program) {
+            Some(entry) if !entry.shaders.contains(&shader) => entry.shaders.push(shader),
+            Some(_) => state.fail(INVALID_OPERATION),
+            None => state.fail(INVALID_VALUE),
+        }
+    }
+
+    /// `linkProgram()`: needs a compiled vertex and fragment shader
+    pub fn link_program(&self, program: u32) {
+        let mut state = self.lock();
+        let Some(entry) = state.programs.get(&program) else {
+            return state.fail(INVALID_VALUE);
+        };
+        let shader = |is_vertex: bool| {
+            entry.shaders.iter().filter_map(|id| state.shaders.get(id)).find(|shader| shader.is_vertex == is_vertex)
+        };
+        let (vertex, fragment) = match (shader(true), shader(false)) {
+            (Some(vertex), Some(fragment)) if vertex.compiled && fragment.compiled => (vertex.source.clone(), fragment.source.clone()),
+            _ => {
+                let entry = state.programs.get_mut(&program).expect("program checked above");
+                entry.linked = false;
+                entry.info_log = "A compiled vertex and fragment shader are required".to_string();
+                return;
+            }
+        };
+        let attributes = declarations(&vertex, "attribute");
+        let position = assigned_expression(&vertex, "gl_Position")
+            .and_then(|expression| attributes.iter().find(|name| contains_word(&expression, name)).cloned());
+        let color = fragment_color(&fragment);
+
+        let entry = state.programs.get_mut(&program).expect("program checked above");
+        entry.linked = true;
+        entry.info_log = match (&position, &color) {
+            (Some(_), Some(_)) => String::new(),
+            (None, _) => "Unsupported vertex shader: gl_Position must come from an attribute".to_string(),
+            (_, None) => "Unsupported fragment shader: gl_FragColor must be a constant or a uniform vec4".to_string(),
+        };
+        entry.attributes = attributes;
+        entry.position = position;
+        entry.color = color;
+        entry.uniforms.clear();
+    }
+
+    /// `getProgramParameter(program, LINK_STATUS)`
+    pub fn program_linked(&self, program: u32) -> bool {
+        self.lock().programs.get(&program).is_some_and(|entry| entry.linked)
+    }
+
+    pub fn program_info_log(&self, program: u32) -> String {
+        self.lock().programs.get(&program).map(|entry| entry.info_log.clone()).unwrap_or_default()
+    }
+
+    /// Check whether a linked program is inside the supported shader
+    /// subset and will draw
+    pub fn program_draws(&self, program: u32) -> bool {
+        self.lock().programs.get(&program).is_some_and(|entry| entry.linked && entry.draws())
+    }
+
+    pub fn use_program(&self, program: Option<u32>) {
+        let mut state = self.lock();
+        match program {
+            Some(id) if !state.programs.get(&id).is_some_and(|entry| entry.linked) => state.fail(INVALID_OPERATION),
+            _ => state.current_program = program,
+        }
+    }
+
+    /// `getAttribLocation()`; -1 for an unknown attribute
+    pub fn attribute_location(&self, program: u32, name: &str) -> i32 {
+        let state = self.lock();
+        state
+            .programs
+            .get(&program)
+            .and_then(|entry| entry.attributes.iter().position(|attribute| attribute == name))
+            .map_or(-1, |index| index as i32)
+    }
+
+    pub fn uniform4f(&self, name: &str, value: [f32; 4]) {
+        let mut state = self.lock();
+        let Some(program) = state.current_program else {
+            return state.fail(INVALID_OPERATION);
+        };
+        if let Some(entry) = state.programs.get_mut(&program) {
+            entry.uniforms.insert(name.to_string(), value);
+        }
+    }
+
+    pub fn create_buffer(&self) -> u32 {
+        let mut state = self.lock();
+        let id = next_id(&mut state);
+        state.buffers.insert(id, Vec::new());
+        id
+    }
+
+    pub fn bind_buffer(&self, target: u32, buffer: Option<u32>) {
+        let mut state = self.lock();
+        if target != ARRAY_BUFFER {
+            return state.fail(INVALID_ENUM);
+        }
+        match buffer {
+            Some(id) if !state.buffers.contains_key(&id) => state.fail(INVALID_OPERATION),
+            _ => state.array_buffer = buffer,
+        }
+    }
+
+    pub fn buffer_data(&self, target: u32, data: Vec<f32>) {
+        let mut state = self.lock();
+        if target != ARRAY_BUFFER {
+            return state.fail(INVALID_ENUM);
+        }
+        match state.array_buffer {
+            Some(id) => {
+                state.buffers.insert(id, data);
+            }
+            None => state.fail(INVALID_OPERATION),
+        }
+    }
+
+    pub fn enable_vertex_attrib_array(&self, index: u32) {
+        self.lock().enabled.insert(index);
+    }
+
+    pub fn disable_vertex_attrib_array(&self, index: u32) {
+        self.lock().enabled.remove(&index);
+    }
+
+    /// `vertexAttribPointer()` for the bound buffer; only `FLOAT` data is
+    /// supported
+    pub fn vertex_attrib_pointer(&self, index: u32, size: u32, data_type: u32, stride: u32, offset: u32) {
+        let mut state = self.lock();
+        if data_type != FLOAT {
+            return state.fail(INVALID_ENUM);
+        }
+        if !(1..=4).contains(&size) || !stride.is_multiple_of(4) || !offset.is_multiple_of(4) {
+            return state.fail(INVALID_VALUE);
+        }
+        let Some(buffer) = state.array_buffer else {
+            return state.fail(INVALID_OPERATION);
+        };
+        let pointer = AttributePointer { buffer, size: size as usize, stride: stride as usize / 4, offset: offset as usize / 4 };
+        state.pointers.insert(index, pointer);
+    }
+
+    /// `drawArrays()`
+    pub fn draw_arrays(&self, mode: u32, first: i32, count: i32) {
+        let mut state = self.lock();
+        if !matches!(mode, POINTS | LINES | 0x0002 | 0x0003 | TRIANGLES | TRIANGLE_STRIP | TRIANGLE_FAN) {
+            return state.fail(INVALID_ENUM);
+        }
+        if first < 0 || count < 0 {
+            return state.fail(INVALID_VALUE);
+        }
+        let Some(program) = state.current_program.and_then(|id| state.programs.get(&id)) else {
+            return state.fail(INVALID_OPERATION);
+        };
+        let (Some(position), Some(color)) = (&program.position, &program.color) else {
+            state.unsupported_draws += 1;
+            return;
+        };
+        let color = match color {
+            FragmentColor::Constant(color) => *color,
+            FragmentColor::Uniform(name) => program.uniforms.get(name).copied().unwrap_or([0.0; 4]),
+        };
+        let location = program.attributes.iter().position(|name| name == position).unwrap_or(0) as u32;
+        let pointer = match state.pointers.get(&location) {
+            Some(pointer) if state.enabled.contains(&location) && pointer.size >= 2 => *pointer,
+            _ => return state.fail(INVALID_OPERATION),
+        };
+        let data = state.buffers.get(&pointer.buffer).map(Vec::as_slice).unwrap_or_default();
+        let stride = if pointer.stride == 0 { pointer.size } else { pointer.stride };
+        let mut points = Vec::with_capacity(count as usize);
+        for vertex in first as usize..(first + count) as usize {
+            let start = pointer.offset + vertex * stride;
+            match data.get(start..start + 2) {
+                Some(xy) => points.push([xy[0], xy[1]]),
+                None => return state.fail(INVALID_OPERATION),
+            }
+        }
+        let Some(vertices) = triangle_list(mode, &points) else {
+            state.unsupported_draws += 1;
+            return;
+        };
+        if !vertices.is_empty() {
+            state.commands.push(WebGlCommand::DrawTriangles { vertices, color });
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, WebGlState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn next_id(state: &mut WebGlState) -> u32 {
+    let id = state.next_id;
+    state.next_id += 1;
+    id
+}
+
+/// Expand triangles, strips and fans into a triangle list
+///
+/// Returns `None` for points and lines.
+pub fn triangle_list(mode: u32, points: &[[f32; 2]]) -> Option<Vec<[f32; 2]>> {
+    let mut vertices = Vec::new();
+    match mode {
+        TRIANGLES => vertices.extend_from_slice(&points[..points.len() / 3 * 3]),
+        TRIANGLE_STRIP => {
+            for i in 2..points.len() {
+                // Every other triangle is flipped to keep the winding
+                if i % 2 == 0 {
+                    vertices.extend_from_slice(&[points[i - 2], points[i - 1], points[i]]);
+                } else {
+                    vertices.extend_from_slice(&[points[i - 1], points[i - 2], points[i]]);
+                }
+            }
+        }
+        TRIANGLE_FAN => {
+            for i in 2..points.len() {
+                vertices.extend_from_slice(&[points[0], points[i - 1], points[i]]);
+            }
+        }
+        _ => return None,
+    }
+    Some(vertices)
+}
+
+/// Get the names of the variables a shader declares with a qualifier,
+/// such as `attribute vec2 position;`
+fn declarations(source: &str, qualifier: &str) -> Vec<String> {
+    source
+        .split(';')
+        .filter_map(|statement| {
+            let words: Vec<&str> = statement.split_whitespace().collect();
+            let at = words.iter().position(|word| *word == qualifier)?;
+            // Skip a precision qualifier between the type and the name
+            words.get(at + 1..).and_then(|rest| rest.last()).map(|name| name.to_string())
+        })
+        .collect()
+}
+
+/// Get the right-hand side of the first assignment to a variable
+fn assigned_expression(source: &str, variable: &str) -> Option<String> {
+    let at = source.find(variable)?;
+    let rest = source[at + variable.len()..].trim_start().strip_prefix('=')?;
+    Some(rest.split(';').next()?.trim().to_string())
+}
+
+fn contains_word(expression: &str, word: &str) -> bool {
+    expression
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .any(|token| token == word)
+}
+
+/// Work out what a fragment shader writes to `gl_FragColor`
+fn fragment_color(source: &str) -> Option<FragmentColor> {
+    let expression = assigned_expression(source, "gl_FragColor")?;
+    if let Some(arguments) = expression.strip_prefix("vec4").map(str::trim).and_then(|rest| rest.strip_prefix('(')?.strip_suffix(')')) {
+        let channels: Vec<f32> = arguments.split(',').filter_map(|channel| channel.trim().parse().ok()).collect();
+        return match channels[..] {
+            [r, g, b, a] if arguments.split(',').count() == 4 => Some(FragmentColor::Constant([r, g, b, a])),
+            _ => None,
+        };
+    }
+    declarations(source, "uniform")
+        .into_iter()
+        .find(|name| *name == expression)
+        .map(FragmentColor::Uniform)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VERTEX: &str = "attribute vec2 a_position;
+        void main() { gl_Position = vec4(a_position, 0.0, 1.0); }";
+
+    #[test]
+    fn test_records_clear_and_triangle_draws() {
+        let gl = WebGlContext::new(300, 150);
+        gl.clear_color([0.0, 0.0, 0.0, 1.0]);
+        gl.clear(COLOR_BUFFER_BIT);
+
+        let program = gl.create_program();
+        for (shader_type, source) in [
+            (VERTEX_SHADER, VERTEX),
+            (FRAGMENT_SHADER, "precision mediump float; uniform vec4 u_color; void main() { gl_FragColor = u_color; }"),
+        ] {
+            let shader = gl.create_shader(shader_type).unwrap();
+            gl.shader_source(shader, source);
+            gl.compile_shader(shader);
+            assert!(gl.shader_compiled(shader));
+            gl.attach_shader(program, shader);
+        }
+        gl.link_program(program);
+        assert!(gl.program_linked(program) && gl.program_draws(program));
+        gl.use_program(Some(program));
+        gl.uniform4f("u_color", [1.0, 0.0, 0.0, 1.0]);
+
+        let buffer = gl.create_buffer();
+        gl.bind_buffer(ARRAY_BUFFER, Some(buffer));
+        gl.buffer_data(ARRAY_BUFFER, vec![-1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, 1.0]);
+        let location = gl.attribute_location(program, "a_position");
+        assert_eq!(location, 0);
+        gl.enable_vertex_attrib_array(0);
+        gl.vertex_attrib_pointer(0, 2, FLOAT, 0, 0);
+        gl.draw_arrays(TRIANGLE_STRIP, 0, 4);
+        assert_eq!(gl.get_error(), NO_ERROR);
+
+        let commands = gl.take_commands();
+        assert_eq!(commands[0], WebGlCommand::Clear { color: [0.0, 0.0, 0.0, 1.0] });
+        match &commands[1] {
+            WebGlCommand::DrawTriangles { vertices, color } => {
+                assert_eq!(vertices.len(), 6);
+                assert_eq!(*color, [1.0, 0.0, 0.0, 1.0]);
+            }
+            other => panic!("unexpected command {:?}", other),
+        }
+
+        // A shader outside the subset links but does not draw
+        let textured = gl.create_program();
+        for (shader_type, source) in [(VERTEX_SHADER, VERTEX), (FRAGMENT_SHADER, "void main() { gl_FragColor = texture2D(t, uv); }")] {
+            let shader = gl.create_shader(shader_type).unwrap();
+            gl.shader_source(shader, source);
+            gl.compile_shader(shader);
+            gl.attach_shader(textured, shader);
+        }
+        gl.link_program(textured);
+        assert!(gl.program_linked(textured) && !gl.program_draws(textured));
+        assert!(gl.program_info_log(textured).contains("Unsupported fragment shader"));
+        gl.use_program(Some(textured));
+        gl.draw_arrays(TRIANGLES, 0, 3);
+        assert_eq!(gl.unsupported_draws(), 1);
+        assert!(gl.take_commands().is_empty());
+
+        gl.bind_buffer(0x8893, None);
+        assert_eq!(gl.get_error(), INVALID_ENUM);
+        assert_eq!(gl.get_error(), NO_ERROR);
+    }
+}
@@ -0,0 +1,18 @@
+//! Fuzz `HtmlParser::parse`
+//!
+//! Run from `html_parser/` with the pathological seeds:
+//! `cargo fuzz run parser fuzz/corpus/parser fuzz/seeds`
+
+#![no_main]
+
+use html_parser::HtmlParser;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(parser) = HtmlParser::new(data.to_vec()) else {
+        return;
+    };
+    // Dropping the document walks the whole tree, so deep nesting that
+    // slipped past the depth limit overflows the stack here
+    let _ = parser.parse();
+});
@@ -0,0 +1,22 @@
+//! Fuzz `Tokenizer::next_token`
+//!
+//! Run from `html_parser/` with the pathological seeds:
+//! `cargo fuzz run tokenizer fuzz/corpus/tokenizer fuzz/seeds`
+
+#![no_main]
+
+use html_parser::{Token, Tokenizer};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(mut tokenizer) = Tokenizer::new(data.to_vec()) else {
+        return;
+    };
+    // Every token consumes input, so the end is reached in bounded steps
+    for _ in 0..=data.len() {
+        if let Ok(Token::Eof) = tokenizer.next_token() {
+            return;
+        }
+    }
+    panic!("tokenizer did not reach the end of {} bytes", data.len());
+});
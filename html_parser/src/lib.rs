@@ -10,6 +10,12 @@
 //! 3. **Error Recovery**: Robust error recovery for malformed HTML
 //! 4. **Performance**: Optimized for real-world web pages
 //! 5. **External Resources**: Parses and queues external CSS/JS resources
+//! 6. **Bounded Depth**: Elements nested deeper than a configurable limit are
+//!    inserted as siblings, so hostile markup cannot exhaust the stack of
+//!    recursive tree walks
+//! 7. **Checkpoints**: Tokenizer and parser state can be saved and restored,
+//!    so parsing done ahead of a script is rolled back when the script calls
+//!    `document.write`, instead of restarting the whole parse
 
 use dom::{Document, Node, NodeType};
 use std::rc::Rc;
@@ -17,6 +23,11 @@ use std::collections::HashMap;
 use encoding_rs::UTF_8;
 use thiserror::Error;
 
+/// Default maximum element nesting depth
+///
+/// Matches the limit used by major engines; real pages stay far below it.
+pub const DEFAULT_MAX_NESTING_DEPTH: usize = 512;
+
 /// Errors that can occur during HTML parsing
 #[derive(Error, Debug)]
 pub enum ParseError {
@@ -72,6 +83,23 @@ pub struct Tokenizer {
     chars: Vec<char>,
     _position: usize,
     char_position: usize,
+    /// Position and length of each insertion into the stream
+    insertions: Vec<(usize, usize)>,
+}
+
+/// Saved tokenizer state, see `Tokenizer::checkpoint`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenizerCheckpoint {
+    position: usize,
+    /// Number of insertions made before the checkpoint
+    insertions: usize,
+}
+
+impl TokenizerCheckpoint {
+    /// Get the saved position, in characters
+    pub fn position(&self) -> usize {
+        self.position
+    }
 }
 
 impl Tokenizer {
@@ -95,9 +123,53 @@ impl Tokenizer {
             chars,
             _position: 0,
             char_position: 0,
+            insertions: Vec::new(),
         })
     }
 
+    /// Get the current position, in characters
+    pub fn position(&self) -> usize {
+        self.char_position
+    }
+
+    /// Save the tokenizer state
+    pub fn checkpoint(&self) -> TokenizerCheckpoint {
+        TokenizerCheckpoint {
+            position: self.char_position,
+            insertions: self.insertions.len(),
+        }
+    }
+
+    /// Return to a saved state
+    ///
+    /// Text inserted since the checkpoint stays in the stream. Insertions
+    /// before the saved position move it, so it still points at the same
+    /// input; text inserted exactly at it is tokenized next.
+    pub fn restore(&mut self, checkpoint: TokenizerCheckpoint) {
+        let mut position = checkpoint.position;
+        for &(at, length) in self.insertions.iter().skip(checkpoint.insertions) {
+            if at < position {
+                position += length;
+            }
+        }
+        self.char_position = position.min(self.chars.len());
+    }
+
+    /// Insert text into the stream, e.g. from `document.write`
+    ///
+    /// If the insertion is before the current position, the position moves
+    /// with the text after it.
+    pub fn insert(&mut self, position: usize, text: &str) {
+        let position = position.min(self.chars.len());
+        let chars: Vec<char> = text.chars().collect();
+        let length = chars.len();
+        self.chars.splice(position..position, chars);
+        self.insertions.push((position, length));
+        if self.char_position > position {
+            self.char_position += length;
+        }
+    }
+
     /// Get the next token from the input
     /// 
     /// Implements the HTML5 tokenization state machine
@@ -185,7 +257,7 @@ impl Tokenizer {
         self.char_position += 2; // Skip '<!'
         
         // Check for DOCTYPE (case-insensitive)
-        if self.char_position + 7 < self.chars.len() {
+        if self.char_position + 7 <= self.chars.len() {
             let next_chars: String = self.chars[self.char_position..self.char_position + 7]
                 .iter().collect();
             if next_chars.to_uppercase() == "DOCTYPE" {
@@ -193,8 +265,11 @@ impl Tokenizer {
             }
         }
         
-        // Parse as comment
-        self.parse_comment()
+        if self.chars[self.char_position..].starts_with(&['-', '-']) {
+            self.parse_comment()
+        } else {
+            self.parse_bogus_comment()
+        }
     }
 
     /// Parse a DOCTYPE declaration
@@ -252,23 +327,41 @@ impl Tokenizer {
 
     /// Parse a comment like `<!-- comment -->`
     fn parse_comment(&mut self) -> Result<Token, ParseError> {
-        self.char_position += 2; // Skip '!'
+        self.char_position += 2; // Skip '--'
+        
+        // `<!-->` and `<!--->` are empty comments
+        for abrupt_end in [&['>'][..], &['-', '>'][..]] {
+            if self.chars[self.char_position..].starts_with(abrupt_end) {
+                self.char_position += abrupt_end.len();
+                return Ok(Token::Comment(String::new()));
+            }
+        }
         
         let mut comment_content = String::new();
         
         // Look for comment end
-        while self.char_position + 2 < self.chars.len() {
-            if self.chars[self.char_position] == '-' 
-                && self.chars[self.char_position + 1] == '-' 
-                && self.chars[self.char_position + 2] == '>' {
-                break;
+        while self.char_position < self.chars.len() {
+            if self.chars[self.char_position..].starts_with(&['-', '-', '>']) {
+                self.char_position += 3; // Skip '-->'
+                return Ok(Token::Comment(comment_content));
             }
             comment_content.push(self.chars[self.char_position]);
             self.char_position += 1;
         }
         
-        if self.char_position + 2 < self.chars.len() {
-            self.char_position += 3; // Skip '-->'
+        // An unterminated comment runs to the end of the input
+        Ok(Token::Comment(comment_content))
+    }
+
+    /// Parse a bogus comment like `<!foo>`, which ends at the first '>'
+    fn parse_bogus_comment(&mut self) -> Result<Token, ParseError> {
+        let start = self.char_position;
+        while self.char_position < self.chars.len() && self.chars[self.char_position] != '>' {
+            self.char_position += 1;
+        }
+        let comment_content: String = self.chars[start..self.char_position].iter().collect();
+        if self.char_position < self.chars.len() {
+            self.char_position += 1; // Skip '>'
         }
         
         Ok(Token::Comment(comment_content))
@@ -470,12 +563,48 @@ impl Tokenizer {
     }
 }
 
+/// Maximum parse errors before giving up on severely malformed HTML
+const MAX_ERRORS: usize = 100;
+
 /// HTML parser that builds a DOM tree from tokens
 pub struct HtmlParser {
     tokenizer: Tokenizer,
     document: Document,
     open_elements: Vec<Rc<Node>>,
     external_resources: Vec<ExternalResource>,
+    max_nesting_depth: usize,
+    error_count: usize,
+    pending_script: Option<PendingScript>,
+}
+
+/// Saved parser state, see `HtmlParser::checkpoint`
+#[derive(Debug, Clone)]
+pub struct ParserCheckpoint {
+    tokenizer: TokenizerCheckpoint,
+    /// The open elements and how many children each had
+    open_elements: Vec<(Rc<Node>, usize)>,
+    external_resources: usize,
+    error_count: usize,
+}
+
+/// Why `HtmlParser::pump` returned
+#[derive(Debug, Clone)]
+pub enum ParserPause {
+    /// A script element was closed and must run before parsing continues
+    Script(Rc<Node>),
+    /// The whole input has been parsed
+    Finished,
+}
+
+/// A script the parser paused for, which has not run yet
+#[derive(Debug, Clone)]
+struct PendingScript {
+    /// State right after the script's end tag
+    checkpoint: ParserCheckpoint,
+    /// Where `document.write` inserts markup
+    insertion_point: usize,
+    /// Whether the parser has run ahead of the script
+    speculated: bool,
 }
 
 /// Represents an external resource that needs to be fetched
@@ -484,6 +613,9 @@ pub struct ExternalResource {
     pub resource_type: ResourceType,
     pub url: String,
     pub attributes: HashMap<String, String>,
+    /// Whether the element was found in the document's head, i.e. inside
+    /// `<head>` or before `<body>` started
+    pub in_head: bool,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -506,64 +638,165 @@ impl HtmlParser {
             document,
             open_elements: vec![root_ref],
             external_resources: Vec::new(),
+            max_nesting_depth: DEFAULT_MAX_NESTING_DEPTH,
+            error_count: 0,
+            pending_script: None,
         })
     }
 
+    /// Set the maximum element nesting depth
+    ///
+    /// An element opened at the limit is inserted as a sibling of the
+    /// deepest open element instead of its child. The limit is at least 1.
+    pub fn with_max_nesting_depth(mut self, depth: usize) -> Self {
+        self.max_nesting_depth = depth.max(1);
+        self
+    }
+
     /// Parse the HTML and return the DOM tree
     pub fn parse(mut self) -> Result<(Document, Vec<ExternalResource>), ParseError> {
-        let mut error_count = 0;
-        const MAX_ERRORS: usize = 100; // Prevent infinite loops on severely malformed HTML
-        
+        while let ParserPause::Script(_) = self.pump()? {}
+        Ok(self.finish())
+    }
+
+    /// Parse until a script element closes or the input ends
+    ///
+    /// A script pause is the point where the script would run: markup it
+    /// writes with `document_write` is parsed next. Calling `pump` again
+    /// means the script has run.
+    pub fn pump(&mut self) -> Result<ParserPause, ParseError> {
+        self.pending_script = None;
+        let pause = self.run()?;
+        if let ParserPause::Script(_) = pause {
+            let checkpoint = self.checkpoint();
+            self.pending_script = Some(PendingScript {
+                insertion_point: checkpoint.tokenizer.position(),
+                checkpoint,
+                speculated: false,
+            });
+        }
+        Ok(pause)
+    }
+
+    /// Parse ahead of a pending script before it runs
+    ///
+    /// Parses up to the next script or the end of the input, and returns the
+    /// external resources found on the way so they can be preloaded. The
+    /// work is kept if the script does not write, and rolled back to the
+    /// script's end tag if it does.
+    pub fn speculate(&mut self) -> Result<Vec<ExternalResource>, ParseError> {
+        let Some(pending) = self.pending_script.as_mut() else {
+            return Ok(Vec::new());
+        };
+        pending.speculated = true;
+        let start = self.external_resources.len();
+        self.run()?;
+        Ok(self.external_resources[start..].to_vec())
+    }
+
+    /// Insert markup written by the running script
+    ///
+    /// The markup goes at the insertion point right after the script's end
+    /// tag, after any markup the script wrote before. Parsing done ahead of
+    /// the script is rolled back first, so only the input after the script
+    /// is parsed again.
+    pub fn document_write(&mut self, markup: &str) {
+        let Some(mut pending) = self.pending_script.take() else {
+            let position = self.tokenizer.position();
+            self.tokenizer.insert(position, markup);
+            return;
+        };
+        if pending.speculated {
+            println!("🔸 document.write invalidated speculative parsing, rolling back");
+            self.restore(&pending.checkpoint);
+            pending.speculated = false;
+        }
+        self.tokenizer.insert(pending.insertion_point, markup);
+        pending.insertion_point += markup.chars().count();
+        self.pending_script = Some(pending);
+    }
+
+    /// Save the parser state
+    pub fn checkpoint(&self) -> ParserCheckpoint {
+        ParserCheckpoint {
+            tokenizer: self.tokenizer.checkpoint(),
+            open_elements: self
+                .open_elements
+                .iter()
+                .map(|element| (Rc::clone(element), element.children.borrow().len()))
+                .collect(),
+            external_resources: self.external_resources.len(),
+            error_count: self.error_count,
+        }
+    }
+
+    /// Return to a saved state, discarding nodes and resources parsed since
+    ///
+    /// Nodes can only be added below elements that were open at the
+    /// checkpoint, so truncating their children removes all later nodes.
+    pub fn restore(&mut self, checkpoint: &ParserCheckpoint) {
+        self.tokenizer.restore(checkpoint.tokenizer);
+        for (element, child_count) in &checkpoint.open_elements {
+            let later: Vec<Rc<Node>> = element.children.borrow().iter().skip(*child_count).cloned().collect();
+            for child in later.iter().rev() {
+                element.remove_child(child);
+            }
+        }
+        self.open_elements = checkpoint.open_elements.iter().map(|(element, _)| Rc::clone(element)).collect();
+        self.external_resources.truncate(checkpoint.external_resources);
+        self.error_count = checkpoint.error_count;
+    }
+
+    /// Finish parsing and return the DOM tree
+    pub fn finish(self) -> (Document, Vec<ExternalResource>) {
+        (self.document, self.external_resources)
+    }
+
+    /// Process tokens until a script element closes or the input ends
+    fn run(&mut self) -> Result<ParserPause, ParseError> {
         loop {
-            match self.tokenizer.next_token() {
+            let result = match self.tokenizer.next_token() {
                 Ok(Token::StartTag { name, attributes, self_closing }) => {
-                    if let Err(e) = self.handle_start_tag(name, attributes, self_closing) {
-                        error_count += 1;
-                        if error_count > MAX_ERRORS {
-                            return Err(ParseError::ParseError(0, format!("Too many parsing errors: {}", e)));
-                        }
-                        // Continue parsing despite the error
-                    }
-                }
-                Ok(Token::EndTag { name }) => {
-                    if let Err(e) = self.handle_end_tag(name) {
-                        error_count += 1;
-                        if error_count > MAX_ERRORS {
-                            return Err(ParseError::ParseError(0, format!("Too many parsing errors: {}", e)));
-                        }
-                        // Continue parsing despite the error
-                    }
+                    self.handle_start_tag(name, attributes, self_closing).map(|_| None)
                 }
+                Ok(Token::EndTag { name }) => self.handle_end_tag(name),
                 Ok(Token::Text(text)) => {
                     if !text.trim().is_empty() {
-                        if let Err(e) = self.handle_text(text) {
-                            error_count += 1;
-                            if error_count > MAX_ERRORS {
-                                return Err(ParseError::ParseError(0, format!("Too many parsing errors: {}", e)));
-                            }
-                            // Continue parsing despite the error
-                        }
+                        self.handle_text(text).map(|_| None)
+                    } else {
+                        Ok(None)
                     }
                 }
                 Ok(Token::Comment(_)) => {
                     // Comments are ignored in DOM tree
+                    Ok(None)
                 }
                 Ok(Token::Doctype { .. }) => {
                     // DOCTYPE is handled by the document
+                    Ok(None)
                 }
-                Ok(Token::Eof) => break,
+                Ok(Token::Eof) => return Ok(ParserPause::Finished),
                 Err(e) => {
-                    error_count += 1;
-                    if error_count > MAX_ERRORS {
-                        return Err(ParseError::ParseError(0, format!("Too many parsing errors: {}", e)));
-                    }
                     // Try to recover by advancing position
                     self.tokenizer.char_position += 1;
+                    Err(e)
+                }
+            };
+            
+            match result {
+                Ok(Some(closed)) if closed.tag_name() == Some("script") => {
+                    return Ok(ParserPause::Script(closed));
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    // Continue parsing despite the error
+                    self.error_count += 1;
+                    if self.error_count > MAX_ERRORS {
+                        return Err(ParseError::ParseError(0, format!("Too many parsing errors: {}", e)));
+                    }
                 }
             }
         }
-        
-        Ok((self.document, self.external_resources))
     }
 
     /// Handle a start tag
@@ -579,10 +812,18 @@ impl HtmlParser {
             },
             self.document.get_next_id(),
         );
+        node.mark_parser_inserted();
+        
+        // Keep the tree within the nesting limit; the document root is not
+        // counted
+        if self.open_elements.len() > self.max_nesting_depth {
+            self.open_elements.truncate(self.max_nesting_depth);
+        }
         
         // Add to current parent
         if let Some(parent) = self.open_elements.last() {
             parent.append_child(&node);
+            self.document.record_landmark(&node);
         }
         
         // Add to open elements stack if not self-closing
@@ -593,20 +834,21 @@ impl HtmlParser {
         Ok(())
     }
 
-    /// Handle an end tag
-    fn handle_end_tag(&mut self, name: String) -> Result<(), ParseError> {
+    /// Handle an end tag, returning the element it closed
+    fn handle_end_tag(&mut self, name: String) -> Result<Option<Rc<Node>>, ParseError> {
         // Find matching opening tag
         for i in (0..self.open_elements.len()).rev() {
             if let NodeType::Element { tag_name, .. } = &self.open_elements[i].node_type {
                 if tag_name == &name {
+                    let closed = Rc::clone(&self.open_elements[i]);
                     // Remove this element and all elements after it
                     self.open_elements.truncate(i);
-                    break;
+                    return Ok(Some(closed));
                 }
             }
         }
         
-        Ok(())
+        Ok(None)
     }
 
     /// Handle text content
@@ -630,6 +872,8 @@ impl HtmlParser {
 
     /// Check for external resources in the element
     fn check_external_resources(&mut self, tag_name: &str, attributes: &HashMap<String, String>) {
+        let is_open = |name: &str| self.open_elements.iter().any(|element| element.tag_name() == Some(name));
+        let in_head = is_open("head") || !is_open("body");
         match tag_name {
             "link" => {
                 if let Some(rel) = attributes.get("rel") {
@@ -639,6 +883,7 @@ impl HtmlParser {
                                 resource_type: ResourceType::Stylesheet,
                                 url: href.clone(),
                                 attributes: attributes.clone(),
+                                in_head,
                             });
                         }
                     }
@@ -650,6 +895,7 @@ impl HtmlParser {
                         resource_type: ResourceType::Script,
                         url: src.clone(),
                         attributes: attributes.clone(),
+                        in_head,
                     });
                 }
             }
@@ -659,6 +905,7 @@ impl HtmlParser {
                         resource_type: ResourceType::Image,
                         url: src.clone(),
                         attributes: attributes.clone(),
+                        in_head,
                     });
                 }
             }
@@ -699,6 +946,16 @@ mod tests {
         assert_eq!(resources.len(), 2);
         assert_eq!(resources[0].resource_type, ResourceType::Stylesheet);
         assert_eq!(resources[1].resource_type, ResourceType::Script);
+        assert!(resources.iter().all(|resource| resource.in_head));
+    }
+
+    #[test]
+    fn test_body_resources_are_not_in_head() {
+        let html = r#"<html><link rel="stylesheet" href="early.css"><body><p>Text</p><link rel="stylesheet" href="late.css"></body></html>"#;
+        let (_, resources) = parse_html_string(html).unwrap();
+        
+        let in_head: Vec<(&str, bool)> = resources.iter().map(|resource| (resource.url.as_str(), resource.in_head)).collect();
+        assert_eq!(in_head, vec![("early.css", true), ("late.css", false)]);
     }
 
     #[test]
@@ -717,4 +974,129 @@ mod tests {
         
         // Should handle UTF-8 properly
     }
-}
\ No newline at end of file
+
+    fn max_depth(node: &Rc<Node>) -> usize {
+        // Iterative, so the check itself cannot overflow the stack
+        let mut deepest = 0;
+        let mut stack = vec![(Rc::clone(node), 0)];
+        while let Some((node, depth)) = stack.pop() {
+            deepest = deepest.max(depth);
+            for child in node.children.borrow().iter() {
+                stack.push((Rc::clone(child), depth + 1));
+            }
+        }
+        deepest
+    }
+
+    #[test]
+    fn test_nesting_depth_limit() {
+        let html = "<div><div><div><div><p>deep</p></div></div></div></div>";
+        let parser = HtmlParser::new(html.as_bytes().to_vec()).unwrap().with_max_nesting_depth(3);
+        let (document, _) = parser.parse().unwrap();
+
+        // Three elements and the text inside the deepest one
+        assert_eq!(max_depth(&document.root), 4);
+        assert_eq!(document.root.text_content(), "deep");
+    }
+
+    #[test]
+    fn test_malformed_comments() {
+        let (document, _) = parse_html_string("<!foo><p>kept</p><!-- never closed <div>").unwrap();
+        let children = document.root.children.borrow();
+        assert_eq!(children.len(), 1);
+        assert_eq!(children[0].tag_name(), Some("p"));
+        drop(children);
+
+        let mut tokenizer = Tokenizer::new(b"<!--><!DOCTYPE".to_vec()).unwrap();
+        assert_eq!(tokenizer.next_token().unwrap(), Token::Comment(String::new()));
+        assert!(matches!(tokenizer.next_token().unwrap(), Token::Doctype { .. }));
+        assert_eq!(tokenizer.next_token().unwrap(), Token::Eof);
+    }
+
+    #[test]
+    fn test_fuzz_seeds() {
+        let seeds = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/seeds");
+        for entry in std::fs::read_dir(seeds).unwrap() {
+            let path = entry.unwrap().path();
+            let input = std::fs::read(&path).unwrap();
+
+            // The same checks as the fuzz targets
+            if let Ok(mut tokenizer) = Tokenizer::new(input.clone()) {
+                let reached_eof = (0..=input.len()).any(|_| matches!(tokenizer.next_token(), Ok(Token::Eof)));
+                assert!(reached_eof, "{}: tokenizer did not reach the end", path.display());
+            }
+            if let Ok(parser) = HtmlParser::new(input) {
+                if let Ok((document, _)) = parser.parse() {
+                    assert!(max_depth(&document.root) <= DEFAULT_MAX_NESTING_DEPTH + 1, "{}", path.display());
+                }
+            }
+        }
+    }
+
+    fn child_tags(node: &Rc<Node>) -> Vec<String> {
+        node.children.borrow().iter().filter_map(|child| child.tag_name().map(str::to_string)).collect()
+    }
+
+    #[test]
+    fn test_tokenizer_checkpoint_and_insert() {
+        let mut tokenizer = Tokenizer::new(b"<a><b>".to_vec()).unwrap();
+        tokenizer.next_token().unwrap();
+        let checkpoint = tokenizer.checkpoint();
+        tokenizer.next_token().unwrap();
+
+        // Text inserted at the checkpoint is tokenized after restoring
+        tokenizer.insert(checkpoint.position(), "<i>");
+        tokenizer.restore(checkpoint);
+        assert!(matches!(tokenizer.next_token().unwrap(), Token::StartTag { name, .. } if name == "i"));
+        assert!(matches!(tokenizer.next_token().unwrap(), Token::StartTag { name, .. } if name == "b"));
+    }
+
+    #[test]
+    fn test_document_write_rolls_back_speculation() {
+        let html = "<body><script>write()</script><div><img src=\"ahead.png\"></div></body>";
+        let mut parser = HtmlParser::new(html.as_bytes().to_vec()).unwrap();
+        assert!(matches!(parser.pump().unwrap(), ParserPause::Script(_)));
+
+        // The preload scanner finds the image before the script runs
+        let preloads = parser.speculate().unwrap();
+        assert_eq!(preloads.len(), 1);
+        assert_eq!(preloads[0].url, "ahead.png");
+
+        // The script writes, so parsing resumes right after it
+        parser.document_write("<p>first</p>");
+        parser.document_write("<span>second</span>");
+        assert!(matches!(parser.pump().unwrap(), ParserPause::Finished));
+
+        let (document, resources) = parser.finish();
+        let body = document.root.children.borrow()[0].clone();
+        assert_eq!(child_tags(&body), vec!["script", "p", "span", "div"]);
+        assert_eq!(resources.len(), 1);
+    }
+
+    #[test]
+    fn test_speculation_kept_without_write() {
+        let html = "<script>noop()</script><div>ahead</div>";
+        let mut parser = HtmlParser::new(html.as_bytes().to_vec()).unwrap();
+        parser.pump().unwrap();
+        parser.speculate().unwrap();
+        assert!(matches!(parser.pump().unwrap(), ParserPause::Finished));
+
+        let (document, _) = parser.finish();
+        assert_eq!(child_tags(&document.root), vec!["script", "div"]);
+    }
+
+    #[test]
+    fn test_parser_records_document_landmarks() {
+        let (document, _) = parse_html_string("<html><head><title>T</title></head><body><div><body></body></div></body></html>").unwrap();
+        let html = document.document_element().unwrap();
+        assert_eq!(html.tag_name(), Some("html"));
+        assert_eq!(document.head().unwrap().tag_name(), Some("head"));
+        let body = document.body().unwrap();
+        assert!(Rc::ptr_eq(&body, &html.children.borrow()[1]));
+
+        // A document without <html> has its top-level element as body
+        let (fragment, _) = parse_html_string("<body><p>Hi</p></body>").unwrap();
+        assert_eq!(fragment.body().unwrap().tag_name(), Some("body"));
+        assert!(fragment.head().is_none());
+    }
+}
@@ -9,15 +9,19 @@
 //! 2. **Integration**: Works with fetch() and other async operations
 //! 3. **Error Handling**: Proper error handling with AbortError
 //! 4. **Event Handling**: Supports abort event listeners
+//! 5. **One Signal**: A script's `AbortSignal` object carries the Rust
+//!    `AbortSignal` as native data, so aborting from script cancels the
+//!    operations observing its token
 
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use boa_engine::{
-    object::ObjectInitializer,
+    object::{FunctionObjectBuilder, ObjectInitializer},
     property::Attribute,
-    Context, JsValue, NativeFunction,
+    Context, JsData, JsValue, NativeFunction,
     js_string, JsNativeError, JsObject,
 };
+use boa_gc::{Finalize, Trace};
 use thiserror::Error;
 use tokio::sync::broadcast;
 
@@ -47,6 +51,8 @@ pub struct AbortSignal {
     pub listeners: Arc<Mutex<Vec<JsObject>>>,
     /// Broadcast channel for abort events
     pub sender: broadcast::Sender<JsValue>,
+    /// Network-level signal observed by in-flight requests
+    pub token: networking::AbortToken,
 }
 
 impl AbortSignal {
@@ -59,6 +65,7 @@ impl AbortSignal {
             reason: Arc::new(Mutex::new(None)),
             listeners: Arc::new(Mutex::new(Vec::new())),
             sender,
+            token: networking::AbortToken::new(),
         }
     }
 
@@ -79,11 +86,30 @@ impl AbortSignal {
             *aborted = true;
             *self.reason.lock().unwrap() = Some(reason.clone());
             
+            // Cancel any requests observing this signal
+            self.token.abort();
+            
             // Notify listeners
             let _ = self.sender.send(reason);
         }
     }
 
+    /// Get the token that fetch, XHR and the resource loader observe
+    pub fn token(&self) -> networking::AbortToken {
+        self.token.clone()
+    }
+
+    /// Get the value an aborted operation should reject with
+    /// 
+    /// This is the abort reason if one was given, otherwise an `AbortError`
+    /// DOMException.
+    pub fn rejection_reason(&self, context: &mut Context) -> JsValue {
+        match self.get_reason() {
+            Some(reason) if !reason.is_undefined() => reason,
+            _ => create_abort_error(context, "The operation was aborted."),
+        }
+    }
+
     /// Add an event listener
     pub fn add_event_listener(&self, listener: JsObject) {
         self.listeners.lock().unwrap().push(listener);
@@ -96,6 +122,17 @@ impl AbortSignal {
     }
 }
 
+/// Create an `AbortError` DOMException
+pub fn create_abort_error(context: &mut Context, message: &str) -> JsValue {
+    ObjectInitializer::new(context)
+        .property(js_string!("name"), js_string!("AbortError"), Attribute::all())
+        .property(js_string!("message"), js_string!(message), Attribute::all())
+        // Legacy DOMException code for ABORT_ERR
+        .property(js_string!("code"), 20, Attribute::all())
+        .build()
+        .into()
+}
+
 /// AbortController implementation
 #[derive(Clone)]
 pub struct AbortController {
@@ -126,7 +163,7 @@ impl AbortController {
 }
 
 /// Host for managing AbortController instances
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct AbortControllerHost {
     /// Next available ID
     next_id: Arc<Mutex<u64>>,
@@ -145,229 +182,154 @@ impl AbortControllerHost {
 
     /// Initialize AbortController bindings in the JavaScript context
     pub fn initialize_abort_controller_bindings(&self, context: &mut Context) -> AbortResult<()> {
-        // Create AbortController constructor
-        let abort_controller_constructor = ObjectInitializer::new(context)
-            .function(
-                NativeFunction::from_fn_ptr(Self::abort_controller_constructor),
-                js_string!("AbortController"),
-                0,
-            )
-            .build();
-
-        // Create AbortSignal constructor
-        let abort_signal_constructor = ObjectInitializer::new(context)
-            .function(
-                NativeFunction::from_fn_ptr(Self::abort_signal_constructor),
-                js_string!("AbortSignal"),
-                0,
-            )
-            .build();
-
-        // Create AbortError constructor
-        let abort_error_constructor = ObjectInitializer::new(context)
-            .function(
-                NativeFunction::from_fn_ptr(Self::abort_error_constructor),
-                js_string!("AbortError"),
-                1,
-            )
-            .build();
-
-        // Register global constructors
-        let _ = context.register_global_property(
-            js_string!("AbortController"),
-            abort_controller_constructor,
-            Attribute::all(),
-        );
-
-        let _ = context.register_global_property(
-            js_string!("AbortSignal"),
-            abort_signal_constructor,
-            Attribute::all(),
-        );
-
-        let _ = context.register_global_property(
-            js_string!("AbortError"),
-            abort_error_constructor,
-            Attribute::all(),
-        );
-
-        Ok(())
+        initialize_abort_controller_bindings(context)
     }
 
-    /// AbortController constructor implementation
-    fn abort_controller_constructor(
-        _this: &JsValue,
-        _args: &[JsValue],
-        context: &mut Context,
-    ) -> boa_engine::JsResult<JsValue> {
-        // Create a new AbortController
-        let controller = AbortController::new(0); // ID will be set by the host
-        
-        // Create the JavaScript object
-        let controller_obj = ObjectInitializer::new(context)
-            .property(js_string!("signal"), JsValue::undefined(), Attribute::all())
-            .function(
-                NativeFunction::from_fn_ptr(Self::abort_controller_abort),
-                js_string!("abort"),
-                1,
-            )
-            .build();
-
-        // Create the signal object
-        let signal_obj = Self::create_abort_signal_object(controller.signal.clone(), context)?;
-        
-        // Set the signal property
-        controller_obj.set(js_string!("signal"), signal_obj, false, context)?;
+    /// Create a new AbortController
+    pub fn create_controller(&self) -> AbortController {
+        let id = {
+            let mut next_id = self.next_id.lock().unwrap();
+            *next_id += 1;
+            *next_id
+        };
 
-        Ok(controller_obj.into())
+        let controller = AbortController::new(id);
+        self.controllers.lock().unwrap().insert(id, controller.clone());
+        controller
     }
 
-    /// AbortController.abort() implementation
-    fn abort_controller_abort(
-        this: &JsValue,
-        args: &[JsValue],
-        context: &mut Context,
-    ) -> boa_engine::JsResult<JsValue> {
-        let reason = args.get(0).cloned().unwrap_or(js_string!("Aborted").into());
-        
-        if let Some(controller_obj) = this.as_object() {
-            if let Ok(signal) = controller_obj.get(js_string!("signal"), context) {
-                if let Some(signal_obj) = signal.as_object() {
-                    // Set aborted state
-                    signal_obj.set(js_string!("aborted"), true, false, context)?;
-                    signal_obj.set(js_string!("reason"), reason.clone(), false, context)?;
-                    
-                    // Trigger abort event
-                    if let Ok(listeners) = signal_obj.get(js_string!("_listeners"), context) {
-                        if let Some(listeners_array) = listeners.as_object() {
-                            // Call all listeners
-                            let length = listeners_array.get(js_string!("length"), context)?;
-                            if let Ok(length_num) = length.to_number(context) {
-                                for i in 0..(length_num as usize) {
-                                    if let Ok(listener) = listeners_array.get(js_string!(i.to_string()), context) {
-                                        if let Some(listener_fn) = listener.as_object() {
-                                            let _ = listener_fn.call(&signal_obj.clone().into(), &[reason.clone()], context);
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
-
-        Ok(JsValue::undefined())
+    /// Get an AbortController by ID
+    pub fn get_controller(&self, id: u64) -> Option<AbortController> {
+        self.controllers.lock().unwrap().get(&id).cloned()
     }
 
-    /// AbortSignal constructor implementation
-    fn abort_signal_constructor(
-        _this: &JsValue,
-        _args: &[JsValue],
-        context: &mut Context,
-    ) -> boa_engine::JsResult<JsValue> {
-        let signal = AbortSignal::new(0);
-        Self::create_abort_signal_object(signal, context)
+    /// Remove an AbortController
+    pub fn remove_controller(&self, id: u64) -> Option<AbortController> {
+        self.controllers.lock().unwrap().remove(&id)
     }
+}
 
-    /// Create an AbortSignal JavaScript object
-    fn create_abort_signal_object(
-        _signal: AbortSignal,
-        context: &mut Context,
-    ) -> boa_engine::JsResult<JsValue> {
-        let signal_obj = ObjectInitializer::new(context)
-            .property(js_string!("aborted"), false, Attribute::all())
-            .property(js_string!("reason"), JsValue::undefined(), Attribute::all())
-            .property(js_string!("_listeners"), JsValue::undefined(), Attribute::all())
-            .function(
-                NativeFunction::from_fn_ptr(Self::abort_signal_add_event_listener),
-                js_string!("addEventListener"),
-                2,
-            )
-            .function(
-                NativeFunction::from_fn_ptr(Self::abort_signal_remove_event_listener),
-                js_string!("removeEventListener"),
-                2,
-            )
-            .function(
-                NativeFunction::from_fn_ptr(Self::abort_signal_throw_if_aborted),
-                js_string!("throwIfAborted"),
-                0,
-            )
-            .build();
-
-        // Initialize listeners array
-        let listeners_array = ObjectInitializer::new(context)
-            .property(js_string!("length"), 0, Attribute::all())
-            .build();
-        signal_obj.set(js_string!("_listeners"), listeners_array, false, context)?;
-
-        Ok(signal_obj.into())
-    }
+/// Native data of a script's AbortSignal object
+#[derive(Trace, Finalize, JsData)]
+struct SignalData {
+    #[unsafe_ignore_trace]
+    signal: AbortSignal,
+}
 
-    /// AbortSignal.addEventListener implementation
-    fn abort_signal_add_event_listener(
-        this: &JsValue,
-        args: &[JsValue],
-        context: &mut Context,
-    ) -> boa_engine::JsResult<JsValue> {
-        if args.len() < 2 {
-            return Err(JsNativeError::typ().with_message("addEventListener requires 2 arguments").into());
-        }
+/// Get the Rust signal behind a script's AbortSignal object, for the
+/// operations a script passes it to
+pub fn signal_of(object: &JsObject) -> Option<AbortSignal> {
+    object.downcast_ref::<SignalData>().map(|data| data.signal.clone())
+}
 
-        let event_type = args[0].to_string(context)?;
-        let listener = args[1].clone();
+/// Initialize AbortController bindings in the JavaScript context
+pub fn initialize_abort_controller_bindings(context: &mut Context) -> AbortResult<()> {
+    // Create AbortController constructor; the constructors return the
+    // object they build
+    let abort_controller_constructor = FunctionObjectBuilder::new(context.realm(), NativeFunction::from_fn_ptr(abort_controller_constructor))
+        .name(js_string!("AbortController"))
+        .length(0)
+        .constructor(true)
+        .build();
+
+    // Create AbortSignal constructor
+    let abort_signal_constructor = FunctionObjectBuilder::new(context.realm(), NativeFunction::from_fn_ptr(abort_signal_constructor))
+        .name(js_string!("AbortSignal"))
+        .length(0)
+        .constructor(true)
+        .build();
+
+    // Create AbortError constructor
+    let abort_error_constructor = FunctionObjectBuilder::new(context.realm(), NativeFunction::from_fn_ptr(abort_error_constructor))
+        .name(js_string!("AbortError"))
+        .length(1)
+        .constructor(true)
+        .build();
+
+    // Register global constructors
+    let _ = context.register_global_property(
+        js_string!("AbortController"),
+        abort_controller_constructor,
+        Attribute::all(),
+    );
+
+    let _ = context.register_global_property(
+        js_string!("AbortSignal"),
+        abort_signal_constructor,
+        Attribute::all(),
+    );
+
+    let _ = context.register_global_property(
+        js_string!("AbortError"),
+        abort_error_constructor,
+        Attribute::all(),
+    );
+
+    Ok(())
+}
 
-        if event_type.to_std_string_escaped() == "abort" {
-            if let Some(signal_obj) = this.as_object() {
-                if let Ok(listeners) = signal_obj.get(js_string!("_listeners"), context) {
-                    if let Some(listeners_array) = listeners.as_object() {
-                        let length = listeners_array.get(js_string!("length"), context)?;
-                        let length_num = length.to_number(context)? as usize;
-                        
-                        // Add listener to array
-                        listeners_array.set(js_string!(length_num.to_string()), listener, false, context)?;
-                        listeners_array.set(js_string!("length"), length_num + 1, false, context)?;
-                    }
-                }
-            }
-        }
+/// AbortController constructor implementation
+fn abort_controller_constructor(
+    _this: &JsValue,
+    _args: &[JsValue],
+    context: &mut Context,
+) -> boa_engine::JsResult<JsValue> {
+    // Create the JavaScript object
+    let controller_obj = ObjectInitializer::new(context)
+        .property(js_string!("signal"), JsValue::undefined(), Attribute::all())
+        .function(
+            NativeFunction::from_fn_ptr(abort_controller_abort),
+            js_string!("abort"),
+            1,
+        )
+        .build();
+
+    // Create the signal object
+    let controller = AbortController::new(0);
+    let signal_obj = create_abort_signal_object(controller.signal, context)?;
+    
+    // Set the signal property
+    controller_obj.set(js_string!("signal"), signal_obj, false, context)?;
 
-        Ok(JsValue::undefined())
-    }
+    Ok(controller_obj.into())
+}
 
-    /// AbortSignal.removeEventListener implementation
-    fn abort_signal_remove_event_listener(
-        this: &JsValue,
-        args: &[JsValue],
-        context: &mut Context,
-    ) -> boa_engine::JsResult<JsValue> {
-        if args.len() < 2 {
-            return Err(JsNativeError::typ().with_message("removeEventListener requires 2 arguments").into());
-        }
+/// AbortController.abort() implementation
+fn abort_controller_abort(
+    this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context,
+) -> boa_engine::JsResult<JsValue> {
+    let reason = args.first().cloned().unwrap_or(js_string!("Aborted").into());
+    
+    if let Some(controller_obj) = this.as_object() {
+        if let Ok(signal) = controller_obj.get(js_string!("signal"), context) {
+            if let Some(signal_obj) = signal.as_object() {
+                // Aborting twice does nothing
+                if signal_obj.get(js_string!("aborted"), context)?.to_boolean() {
+                    return Ok(JsValue::undefined());
+                }
 
-        let event_type = args[0].to_string(context)?;
-        let listener = args[1].clone();
+                // Cancel the operations observing the signal
+                if let Some(signal) = signal_of(signal_obj) {
+                    signal.abort(reason.clone());
+                }
 
-        if event_type.to_std_string_escaped() == "abort" {
-            if let Some(signal_obj) = this.as_object() {
+                // Set aborted state
+                signal_obj.set(js_string!("aborted"), true, false, context)?;
+                signal_obj.set(js_string!("reason"), reason.clone(), false, context)?;
+                
+                // Trigger abort event
                 if let Ok(listeners) = signal_obj.get(js_string!("_listeners"), context) {
                     if let Some(listeners_array) = listeners.as_object() {
+                        // Call all listeners
                         let length = listeners_array.get(js_string!("length"), context)?;
-                        let length_num = length.to_number(context)? as usize;
-                        
-                        // Find and remove listener
-                        for i in 0..length_num {
-                            if let Ok(existing_listener) = listeners_array.get(js_string!(i.to_string()), context) {
-                                if existing_listener == listener {
-                                    // Remove by shifting array
-                                    for j in i..(length_num - 1) {
-                                        if let Ok(next_listener) = listeners_array.get(js_string!((j + 1).to_string()), context) {
-                                            listeners_array.set(js_string!(j.to_string()), next_listener, false, context)?;
-                                        }
+                        if let Ok(length_num) = length.to_number(context) {
+                            for i in 0..(length_num as usize) {
+                                if let Ok(listener) = listeners_array.get(js_string!(i.to_string()), context) {
+                                    if let Some(listener_fn) = listener.as_object() {
+                                        let _ = listener_fn.call(&signal_obj.clone().into(), std::slice::from_ref(&reason), context);
                                     }
-                                    listeners_array.set(js_string!("length"), length_num - 1, false, context)?;
-                                    break;
                                 }
                             }
                         }
@@ -375,68 +337,164 @@ impl AbortControllerHost {
                 }
             }
         }
+    }
 
-        Ok(JsValue::undefined())
+    Ok(JsValue::undefined())
+}
+
+/// AbortSignal constructor implementation
+fn abort_signal_constructor(
+    _this: &JsValue,
+    _args: &[JsValue],
+    context: &mut Context,
+) -> boa_engine::JsResult<JsValue> {
+    let signal = AbortSignal::new(0);
+    create_abort_signal_object(signal, context)
+}
+
+/// Create an AbortSignal JavaScript object carrying `signal`
+pub fn create_abort_signal_object(
+    signal: AbortSignal,
+    context: &mut Context,
+) -> boa_engine::JsResult<JsValue> {
+    let signal_obj = ObjectInitializer::with_native_data(SignalData { signal }, context)
+        .property(js_string!("aborted"), false, Attribute::all())
+        .property(js_string!("reason"), JsValue::undefined(), Attribute::all())
+        .property(js_string!("_listeners"), JsValue::undefined(), Attribute::all())
+        .function(
+            NativeFunction::from_fn_ptr(abort_signal_add_event_listener),
+            js_string!("addEventListener"),
+            2,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(abort_signal_remove_event_listener),
+            js_string!("removeEventListener"),
+            2,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(abort_signal_throw_if_aborted),
+            js_string!("throwIfAborted"),
+            0,
+        )
+        .build();
+
+    // Initialize listeners array
+    let listeners_array = ObjectInitializer::new(context)
+        .property(js_string!("length"), 0, Attribute::all())
+        .build();
+    signal_obj.set(js_string!("_listeners"), listeners_array, false, context)?;
+
+    Ok(signal_obj.into())
+}
+
+/// AbortSignal.addEventListener implementation
+fn abort_signal_add_event_listener(
+    this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context,
+) -> boa_engine::JsResult<JsValue> {
+    if args.len() < 2 {
+        return Err(JsNativeError::typ().with_message("addEventListener requires 2 arguments").into());
     }
 
-    /// AbortSignal.throwIfAborted implementation
-    fn abort_signal_throw_if_aborted(
-        this: &JsValue,
-        _args: &[JsValue],
-        context: &mut Context,
-    ) -> boa_engine::JsResult<JsValue> {
+    let event_type = args[0].to_string(context)?;
+    let listener = args[1].clone();
+
+    if event_type.to_std_string_escaped() == "abort" {
         if let Some(signal_obj) = this.as_object() {
-            if let Ok(aborted) = signal_obj.get(js_string!("aborted"), context) {
-                if aborted.to_boolean() {
-                    let _reason = signal_obj.get(js_string!("reason"), context).unwrap_or(js_string!("Aborted").into());
-                    return Err(JsNativeError::typ().with_message("AbortError").into());
+            if let Ok(listeners) = signal_obj.get(js_string!("_listeners"), context) {
+                if let Some(listeners_array) = listeners.as_object() {
+                    let length = listeners_array.get(js_string!("length"), context)?;
+                    let length_num = length.to_number(context)? as usize;
+                    
+                    // Add listener to array
+                    listeners_array.set(js_string!(length_num.to_string()), listener, false, context)?;
+                    listeners_array.set(js_string!("length"), length_num + 1, false, context)?;
                 }
             }
         }
-
-        Ok(JsValue::undefined())
     }
 
-    /// AbortError constructor implementation
-    fn abort_error_constructor(
-        _this: &JsValue,
-        args: &[JsValue],
-        context: &mut Context,
-    ) -> boa_engine::JsResult<JsValue> {
-        let message = args.get(0)
-            .map(|arg| arg.to_string(context).unwrap_or(js_string!("Aborted")))
-            .unwrap_or(js_string!("Aborted"));
-
-        let error_obj = ObjectInitializer::new(context)
-            .property(js_string!("name"), js_string!("AbortError"), Attribute::all())
-            .property(js_string!("message"), message, Attribute::all())
-            .build();
-
-        Ok(error_obj.into())
+    Ok(JsValue::undefined())
+}
+
+/// AbortSignal.removeEventListener implementation
+fn abort_signal_remove_event_listener(
+    this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context,
+) -> boa_engine::JsResult<JsValue> {
+    if args.len() < 2 {
+        return Err(JsNativeError::typ().with_message("removeEventListener requires 2 arguments").into());
     }
 
-    /// Create a new AbortController
-    pub fn create_controller(&self) -> AbortController {
-        let id = {
-            let mut next_id = self.next_id.lock().unwrap();
-            *next_id += 1;
-            *next_id
-        };
+    let event_type = args[0].to_string(context)?;
+    let listener = args[1].clone();
 
-        let controller = AbortController::new(id);
-        self.controllers.lock().unwrap().insert(id, controller.clone());
-        controller
+    if event_type.to_std_string_escaped() == "abort" {
+        if let Some(signal_obj) = this.as_object() {
+            if let Ok(listeners) = signal_obj.get(js_string!("_listeners"), context) {
+                if let Some(listeners_array) = listeners.as_object() {
+                    let length = listeners_array.get(js_string!("length"), context)?;
+                    let length_num = length.to_number(context)? as usize;
+                    
+                    // Find and remove listener
+                    for i in 0..length_num {
+                        if let Ok(existing_listener) = listeners_array.get(js_string!(i.to_string()), context) {
+                            if existing_listener == listener {
+                                // Remove by shifting array
+                                for j in i..(length_num - 1) {
+                                    if let Ok(next_listener) = listeners_array.get(js_string!((j + 1).to_string()), context) {
+                                        listeners_array.set(js_string!(j.to_string()), next_listener, false, context)?;
+                                    }
+                                }
+                                listeners_array.set(js_string!("length"), length_num - 1, false, context)?;
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+        }
     }
 
-    /// Get an AbortController by ID
-    pub fn get_controller(&self, id: u64) -> Option<AbortController> {
-        self.controllers.lock().unwrap().get(&id).cloned()
-    }
+    Ok(JsValue::undefined())
+}
 
-    /// Remove an AbortController
-    pub fn remove_controller(&self, id: u64) -> Option<AbortController> {
-        self.controllers.lock().unwrap().remove(&id)
+/// AbortSignal.throwIfAborted implementation
+fn abort_signal_throw_if_aborted(
+    this: &JsValue,
+    _args: &[JsValue],
+    context: &mut Context,
+) -> boa_engine::JsResult<JsValue> {
+    if let Some(signal_obj) = this.as_object() {
+        if let Ok(aborted) = signal_obj.get(js_string!("aborted"), context) {
+            if aborted.to_boolean() {
+                let _reason = signal_obj.get(js_string!("reason"), context).unwrap_or(js_string!("Aborted").into());
+                return Err(JsNativeError::typ().with_message("AbortError").into());
+            }
+        }
     }
+
+    Ok(JsValue::undefined())
+}
+
+/// AbortError constructor implementation
+fn abort_error_constructor(
+    _this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context,
+) -> boa_engine::JsResult<JsValue> {
+    let message = args.first()
+        .map(|arg| arg.to_string(context).unwrap_or(js_string!("Aborted")))
+        .unwrap_or(js_string!("Aborted"));
+
+    let error_obj = ObjectInitializer::new(context)
+        .property(js_string!("name"), js_string!("AbortError"), Attribute::all())
+        .property(js_string!("message"), message, Attribute::all())
+        .build();
+
+    Ok(error_obj.into())
 }
 
 #[cfg(test)]
@@ -465,17 +523,14 @@ mod tests {
     #[test]
     fn test_abort_controller_bindings() {
         let context = &mut Context::default();
-        let host = AbortControllerHost::new();
-        
-        let result = host.initialize_abort_controller_bindings(context);
+        let result = initialize_abort_controller_bindings(context);
         assert!(result.is_ok());
     }
 
     #[test]
     fn test_abort_controller_javascript() {
         let context = &mut Context::default();
-        let host = AbortControllerHost::new();
-        host.initialize_abort_controller_bindings(context).unwrap();
+        initialize_abort_controller_bindings(context).unwrap();
         
         // Test AbortController creation
         let code = r#"
@@ -496,8 +551,7 @@ mod tests {
     #[test]
     fn test_abort_controller_abort() {
         let context = &mut Context::default();
-        let host = AbortControllerHost::new();
-        host.initialize_abort_controller_bindings(context).unwrap();
+        initialize_abort_controller_bindings(context).unwrap();
         
         // Test abort functionality
         let code = r#"
@@ -515,4 +569,47 @@ mod tests {
             }
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_abort_signal_cancels_token() {
+        let context = &mut Context::default();
+        let signal = AbortSignal::new(1);
+        let token = signal.token();
+        assert!(!token.is_aborted());
+
+        signal.abort(JsValue::undefined());
+        assert!(token.is_aborted());
+
+        // Without an explicit reason, operations reject with an AbortError DOMException
+        let rejection = signal.rejection_reason(context);
+        let error = rejection.as_object().unwrap();
+        let name = error.get(js_string!("name"), context).unwrap();
+        assert_eq!(name.to_string(context).unwrap().to_std_string_escaped(), "AbortError");
+        let code = error.get(js_string!("code"), context).unwrap();
+        assert_eq!(code.to_number(context).unwrap(), 20.0);
+    }
+
+    #[test]
+    fn test_script_abort_cancels_pending_operation() {
+        let context = &mut Context::default();
+        initialize_abort_controller_bindings(context).unwrap();
+        let signal = context
+            .eval(Source::from_bytes("globalThis.controller = new AbortController(); controller.signal"))
+            .unwrap();
+        let signal = signal_of(signal.as_object().unwrap()).unwrap();
+
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+        runtime.block_on(async {
+            let token = signal.token();
+            let pending = tokio::spawn(async move { token.aborted().await });
+            tokio::task::yield_now().await;
+            assert!(!pending.is_finished());
+
+            context.eval(Source::from_bytes("controller.abort('Stop')")).unwrap();
+            tokio::time::timeout(std::time::Duration::from_secs(1), pending).await.unwrap().unwrap();
+        });
+        assert!(signal.is_aborted());
+        let reason = signal.get_reason().unwrap();
+        assert_eq!(reason.to_string(context).unwrap().to_std_string_escaped(), "Stop");
+    }
+}
@@ -0,0 +1,602 @@
+//! # Web Animations
+//!
+//! This module implements `element.animate(keyframes, options)`. It
+//! returns an `Animation` with `play()`, `pause()`, `cancel()`,
+//! `finish()`, `playbackRate`, `currentTime`, `playState` and a
+//! `finished` promise.
+//!
+//! ## Design Principles
+//!
+//! 1. **Shared Engine**: Keyframes and timing go to `layout::animation`,
+//!    the engine CSS animations use, so both interpolate and ease alike.
+//! 2. **No Style Writes**: Animated values are handed to the layout engine
+//!    each frame instead of written to `style`, so an animation never
+//!    triggers the page's own style mutation handling.
+//! 3. **Engine Time**: The engine advances the timeline once per frame;
+//!    between frames scripts see a stable `currentTime`.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use boa_engine::{
+    object::{
+        builtins::{JsArray, JsPromise},
+        ObjectInitializer,
+    },
+    property::{Attribute, PropertyDescriptor},
+    builtins::promise::ResolvingFunctions,
+    js_string, Context, JsError, JsNativeError, JsObject, JsResult, JsValue, NativeFunction,
+};
+use boa_gc::{Finalize, Trace};
+use layout::animation::{AnimationTimeline, EffectTiming, Keyframe, KeyframeEffect, PlayState};
+use layout::{AnimationDirection, AnimationFillMode, TimingFunction};
+use crate::node_handles::NodeHandleTable;
+
+/// The `finished` promise of an animation
+struct FinishedPromise {
+    promise: JsPromise,
+    resolvers: ResolvingFunctions,
+}
+
+impl FinishedPromise {
+    fn new(context: &mut Context) -> Self {
+        let (promise, resolvers) = JsPromise::new_pending(context);
+        FinishedPromise { promise, resolvers }
+    }
+}
+
+/// An `Animation` handed out to a script
+struct AnimationRecord {
+    id: u64,
+    object: JsObject,
+    finished: FinishedPromise,
+}
+
+struct AnimationState {
+    timeline: AnimationTimeline,
+    /// Timeline time of the current frame, in milliseconds
+    now: f64,
+    records: Vec<AnimationRecord>,
+}
+
+/// The script animations of a document
+///
+/// Cloning shares the animations.
+#[derive(Clone)]
+pub struct ScriptAnimations {
+    state: Rc<RefCell<AnimationState>>,
+}
+
+impl Default for ScriptAnimations {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScriptAnimations {
+    pub fn new() -> Self {
+        Self {
+            state: Rc::new(RefCell::new(AnimationState { timeline: AnimationTimeline::new(), now: 0.0, records: Vec::new() })),
+        }
+    }
+
+    /// Get the timeline time of the current frame
+    pub fn now(&self) -> f64 {
+        self.state.borrow().now
+    }
+
+    /// Check whether any animation needs further frames
+    pub fn is_animating(&self) -> bool {
+        let state = self.state.borrow();
+        state.timeline.is_animating(state.now)
+    }
+
+    /// Get the values animations give nodes in the current frame
+    pub fn animated_styles(&self) -> HashMap<u64, Vec<(String, String)>> {
+        let state = self.state.borrow();
+        state.timeline.animated_styles(state.now)
+    }
+
+    /// Advance the timeline to `now` milliseconds and resolve the
+    /// `finished` promises of animations that finished
+    ///
+    /// Returns the number of promises resolved.
+    pub fn advance(&self, now: f64, context: &mut Context) -> usize {
+        let finished: Vec<(JsObject, ResolvingFunctions)> = {
+            let mut state = self.state.borrow_mut();
+            state.now = state.now.max(now);
+            let now = state.now;
+            let ids = state.timeline.take_finished(now);
+            state
+                .records
+                .iter()
+                .filter(|record| ids.contains(&record.id))
+                .map(|record| (record.object.clone(), record.finished.resolvers.clone()))
+                .collect()
+        };
+        for (object, resolvers) in &finished {
+            if let Err(e) = resolvers.resolve.call(&JsValue::undefined(), &[object.clone().into()], context) {
+                println!("❌ Failed to resolve Animation.finished: {}", e);
+            }
+        }
+        finished.len()
+    }
+
+    /// Run an operation on an animation at the current frame time
+    fn with_animation<R>(&self, id: u64, operation: impl FnOnce(&mut layout::animation::Animation, f64) -> R) -> Option<R> {
+        let mut state = self.state.borrow_mut();
+        let now = state.now;
+        state.timeline.get_mut(id).map(|animation| operation(animation, now))
+    }
+
+    /// Replace a settled `finished` promise with a pending one
+    ///
+    /// An animation that finished and plays again gets a new promise.
+    fn renew_finished(&self, id: u64, context: &mut Context) {
+        let mut state = self.state.borrow_mut();
+        let now = state.now;
+        let finished = state.timeline.get(id).is_some_and(|animation| animation.play_state(now) == PlayState::Finished);
+        if let Some(record) = state.records.iter_mut().find(|record| record.id == id) {
+            let settled = !matches!(record.finished.promise.state(), boa_engine::builtins::promise::PromiseState::Pending);
+            if settled && !finished {
+                record.finished = FinishedPromise::new(context);
+            }
+        }
+    }
+}
+
+/// Captures for `element.animate()`
+#[derive(Clone, Trace, Finalize)]
+struct AnimateBinding {
+    #[unsafe_ignore_trace]
+    handles: Rc<RefCell<NodeHandleTable>>,
+    #[unsafe_ignore_trace]
+    animations: ScriptAnimations,
+}
+
+/// Captures for the members of one `Animation`
+#[derive(Clone, Trace, Finalize)]
+struct AnimationBinding {
+    #[unsafe_ignore_trace]
+    animations: ScriptAnimations,
+    id: u64,
+}
+
+/// Create a DOMException-like error object
+fn create_dom_exception(context: &mut Context, name: &str, message: &str, code: u16) -> JsValue {
+    ObjectInitializer::new(context)
+        .property(js_string!("name"), js_string!(name), Attribute::all())
+        .property(js_string!("message"), js_string!(message), Attribute::all())
+        .property(js_string!("code"), code, Attribute::all())
+        .build()
+        .into()
+}
+
+/// Convert a camel-cased keyframe member such as `backgroundColor` to its
+/// CSS property name
+fn css_property_name(member: &str) -> String {
+    if member == "cssFloat" {
+        return "float".to_string();
+    }
+    let mut name = String::with_capacity(member.len() + 2);
+    for c in member.chars() {
+        if c.is_ascii_uppercase() {
+            name.push('-');
+            name.push(c.to_ascii_lowercase());
+        } else {
+            name.push(c);
+        }
+    }
+    name
+}
+
+fn parse_easing(value: &JsValue, context: &mut Context) -> JsResult<TimingFunction> {
+    let text = value.to_string(context)?.to_std_string_escaped();
+    TimingFunction::parse(&text)
+        .ok_or_else(|| JsNativeError::typ().with_message(format!("Invalid easing '{}'", text)).into())
+}
+
+fn parse_offset(value: &JsValue, context: &mut Context) -> JsResult<Option<f64>> {
+    if value.is_null_or_undefined() {
+        Ok(None)
+    } else {
+        value.to_number(context).map(Some)
+    }
+}
+
+/// Read keyframes given as a list: `[{ opacity: 0 }, { opacity: 1 }]`
+fn parse_keyframe_list(array: &JsArray, context: &mut Context) -> JsResult<Vec<Keyframe>> {
+    let mut keyframes = Vec::new();
+    for index in 0..array.length(context)? {
+        let Some(object) = array.get(index, context)?.as_object().cloned() else {
+            return Err(JsNativeError::typ().with_message("Keyframes must be objects").into());
+        };
+        let mut keyframe = Keyframe { offset: None, easing: TimingFunction::Linear, values: Vec::new() };
+        for key in object.own_property_keys(context)? {
+            let member = key.to_string();
+            let value = object.get(key, context)?;
+            match member.as_str() {
+                "offset" => keyframe.offset = parse_offset(&value, context)?,
+                "easing" => keyframe.easing = parse_easing(&value, context)?,
+                "composite" => {}
+                _ => {
+                    let value = value.to_string(context)?.to_std_string_escaped();
+                    keyframe.values.push((css_property_name(&member), value));
+                }
+            }
+        }
+        keyframes.push(keyframe);
+    }
+    Ok(keyframes)
+}
+
+/// Read keyframes given per property: `{ opacity: [0, 1] }`
+fn parse_property_indexed(object: &JsObject, context: &mut Context) -> JsResult<Vec<Keyframe>> {
+    let mut columns: Vec<(String, Vec<String>)> = Vec::new();
+    let mut offsets = Vec::new();
+    let mut easing = TimingFunction::Linear;
+    for key in object.own_property_keys(context)? {
+        let member = key.to_string();
+        let value = object.get(key, context)?;
+        let values = match value.as_object().and_then(|object| JsArray::from_object(object.clone()).ok()) {
+            Some(array) => {
+                let mut values = Vec::new();
+                for index in 0..array.length(context)? {
+                    values.push(array.get(index, context)?);
+                }
+                values
+            }
+            None => vec![value],
+        };
+        match member.as_str() {
+            "offset" => {
+                offsets = values.iter().map(|value| parse_offset(value, context)).collect::<JsResult<_>>()?;
+            }
+            "easing" => {
+                if let Some(value) = values.first() {
+                    easing = parse_easing(value, context)?;
+                }
+            }
+            "composite" => {}
+            _ => {
+                let values = values
+                    .iter()
+                    .map(|value| value.to_string(context).map(|value| value.to_std_string_escaped()))
+                    .collect::<JsResult<_>>()?;
+                columns.push((css_property_name(&member), values));
+            }
+        }
+    }
+
+    // Each property's values are spaced evenly over its own keyframes
+    let mut keyframes: Vec<Keyframe> = Vec::new();
+    for (property, values) in columns {
+        let count = values.len();
+        for (index, value) in values.into_iter().enumerate() {
+            let offset = if count == 1 { 1.0 } else { index as f64 / (count - 1) as f64 };
+            match keyframes.iter_mut().find(|keyframe| keyframe.offset == Some(offset)) {
+                Some(keyframe) => keyframe.values.push((property.clone(), value)),
+                None => keyframes.push(Keyframe { offset: Some(offset), easing, values: vec![(property.clone(), value)] }),
+            }
+        }
+    }
+    keyframes.sort_by(|a, b| a.offset.partial_cmp(&b.offset).unwrap_or(std::cmp::Ordering::Equal));
+    for (keyframe, offset) in keyframes.iter_mut().zip(offsets) {
+        if offset.is_some() {
+            keyframe.offset = offset;
+        }
+    }
+    Ok(keyframes)
+}
+
+/// Read the timing argument: a duration or a `KeyframeAnimationOptions`
+fn parse_timing(options: &JsValue, context: &mut Context) -> JsResult<EffectTiming> {
+    let mut timing = EffectTiming::default();
+    let Some(options) = options.as_object().cloned() else {
+        if !options.is_undefined() {
+            timing.duration = options.to_number(context)?;
+        }
+        return Ok(timing);
+    };
+    let duration = options.get(js_string!("duration"), context)?;
+    let auto = duration.is_string() && duration.to_string(context)?.to_std_string_escaped() == "auto";
+    if !duration.is_undefined() && !auto {
+        timing.duration = duration.to_number(context)?;
+    }
+    let delay = options.get(js_string!("delay"), context)?;
+    if !delay.is_undefined() {
+        timing.delay = delay.to_number(context)?;
+    }
+    let iterations = options.get(js_string!("iterations"), context)?;
+    if !iterations.is_undefined() {
+        timing.iterations = iterations.to_number(context)?;
+    }
+    let easing = options.get(js_string!("easing"), context)?;
+    if !easing.is_undefined() {
+        timing.easing = parse_easing(&easing, context)?;
+    }
+    let direction = options.get(js_string!("direction"), context)?;
+    if !direction.is_undefined() {
+        timing.direction = match direction.to_string(context)?.to_std_string_escaped().as_str() {
+            "normal" => AnimationDirection::Normal,
+            "reverse" => AnimationDirection::Reverse,
+            "alternate" => AnimationDirection::Alternate,
+            "alternate-reverse" => AnimationDirection::AlternateReverse,
+            other => return Err(JsNativeError::typ().with_message(format!("Invalid direction '{}'", other)).into()),
+        };
+    }
+    let fill = options.get(js_string!("fill"), context)?;
+    if !fill.is_undefined() {
+        timing.fill = match fill.to_string(context)?.to_std_string_escaped().as_str() {
+            "none" | "auto" => AnimationFillMode::None,
+            "forwards" => AnimationFillMode::Forwards,
+            "backwards" => AnimationFillMode::Backwards,
+            "both" => AnimationFillMode::Both,
+            other => return Err(JsNativeError::typ().with_message(format!("Invalid fill '{}'", other)).into()),
+        };
+    }
+    if timing.duration.is_nan() || timing.duration < 0.0 {
+        return Err(JsNativeError::typ().with_message("duration must be a non-negative number").into());
+    }
+    if timing.iterations.is_nan() || timing.iterations < 0.0 {
+        return Err(JsNativeError::typ().with_message("iterations must be a non-negative number").into());
+    }
+    Ok(timing)
+}
+
+/// A method of the `Animation` object and what it does
+type AnimationMethod = (&'static str, fn(&AnimationBinding, &mut Context) -> JsResult<()>);
+
+/// Create the `Animation` object of an animation
+fn create_animation_object(animations: &ScriptAnimations, id: u64, context: &mut Context) -> JsResult<JsObject> {
+    let binding = AnimationBinding { animations: animations.clone(), id };
+    let object = ObjectInitializer::new(context).build();
+
+    let methods: [AnimationMethod; 4] = [
+        ("play", |binding, context| {
+            binding.animations.with_animation(binding.id, |animation, now| animation.play(now));
+            binding.animations.renew_finished(binding.id, context);
+            Ok(())
+        }),
+        ("pause", |binding, context| {
+            binding.animations.with_animation(binding.id, |animation, now| animation.pause(now));
+            binding.animations.renew_finished(binding.id, context);
+            Ok(())
+        }),
+        ("cancel", |binding, context| {
+            let was_idle = binding.animations.with_animation(binding.id, |animation, now| {
+                let was_idle = animation.play_state(now) == PlayState::Idle;
+                animation.cancel();
+                was_idle
+            });
+            if was_idle == Some(false) {
+                // A cancelled animation rejects its pending promise
+                let resolvers = {
+                    let state = binding.animations.state.borrow();
+                    state.records.iter().find(|record| record.id == binding.id).map(|record| {
+                        let pending = matches!(record.finished.promise.state(), boa_engine::builtins::promise::PromiseState::Pending);
+                        pending.then(|| record.finished.resolvers.clone())
+                    })
+                };
+                if let Some(Some(resolvers)) = resolvers {
+                    let error = create_dom_exception(context, "AbortError", "The animation was cancelled", 20);
+                    resolvers.reject.call(&JsValue::undefined(), &[error], context)?;
+                }
+                binding.animations.renew_finished(binding.id, context);
+            }
+            Ok(())
+        }),
+        ("finish", |binding, context| {
+            match binding.animations.with_animation(binding.id, |animation, now| animation.finish(now)) {
+                Some(false) => Err(JsError::from_opaque(create_dom_exception(
+                    context,
+                    "InvalidStateError",
+                    "Cannot finish an animation with an infinite end or a playback rate of zero",
+                    11,
+                ))),
+                _ => Ok(()),
+            }
+        }),
+    ];
+    for (name, method) in methods {
+        let function = NativeFunction::from_copy_closure_with_captures(
+            move |_this, _args, binding: &AnimationBinding, context| {
+                method(binding, context)?;
+                Ok(JsValue::undefined())
+            },
+            binding.clone(),
+        );
+        object.define_property_or_throw(
+            js_string!(name),
+            PropertyDescriptor::builder()
+                .value(function.to_js_function(context.realm()))
+                .writable(true)
+                .enumerable(false)
+                .configurable(true),
+            context,
+        )?;
+    }
+
+    let get_playback_rate = NativeFunction::from_copy_closure_with_captures(
+        |_this, _args, binding: &AnimationBinding, _context| {
+            Ok(binding.animations.with_animation(binding.id, |animation, _now| animation.playback_rate()).unwrap_or(1.0).into())
+        },
+        binding.clone(),
+    );
+    let set_playback_rate = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &AnimationBinding, context| {
+            let rate = args.first().cloned().unwrap_or_default().to_number(context)?;
+            if !rate.is_finite() {
+                return Err(JsNativeError::typ().with_message("playbackRate must be a finite number").into());
+            }
+            binding.animations.with_animation(binding.id, |animation, now| animation.set_playback_rate(rate, now));
+            binding.animations.renew_finished(binding.id, context);
+            Ok(JsValue::undefined())
+        },
+        binding.clone(),
+    );
+    let get_current_time = NativeFunction::from_copy_closure_with_captures(
+        |_this, _args, binding: &AnimationBinding, _context| {
+            let time = binding.animations.with_animation(binding.id, |animation, now| animation.current_time(now)).flatten();
+            Ok(time.map_or(JsValue::null(), JsValue::from))
+        },
+        binding.clone(),
+    );
+    let set_current_time = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &AnimationBinding, context| {
+            let time = args.first().cloned().unwrap_or_default().to_number(context)?;
+            if !time.is_finite() {
+                return Err(JsNativeError::typ().with_message("currentTime must be a finite number").into());
+            }
+            binding.animations.with_animation(binding.id, |animation, now| animation.set_current_time(time, now));
+            binding.animations.renew_finished(binding.id, context);
+            Ok(JsValue::undefined())
+        },
+        binding.clone(),
+    );
+    for (name, getter, setter) in [("playbackRate", get_playback_rate, set_playback_rate), ("currentTime", get_current_time, set_current_time)] {
+        object.define_property_or_throw(
+            js_string!(name),
+            PropertyDescriptor::builder()
+                .get(getter.to_js_function(context.realm()))
+                .set(setter.to_js_function(context.realm()))
+                .enumerable(true)
+                .configurable(true),
+            context,
+        )?;
+    }
+
+    let get_play_state = NativeFunction::from_copy_closure_with_captures(
+        |_this, _args, binding: &AnimationBinding, _context| {
+            let play_state = binding.animations.with_animation(binding.id, |animation, now| animation.play_state(now));
+            let name = match play_state.unwrap_or(PlayState::Idle) {
+                PlayState::Idle => "idle",
+                PlayState::Running => "running",
+                PlayState::Paused => "paused",
+                PlayState::Finished => "finished",
+            };
+            Ok(js_string!(name).into())
+        },
+        binding.clone(),
+    );
+    let get_finished = NativeFunction::from_copy_closure_with_captures(
+        |_this, _args, binding: &AnimationBinding, _context| {
+            let state = binding.animations.state.borrow();
+            let record = state.records.iter().find(|record| record.id == binding.id);
+            Ok(record.map_or(JsValue::undefined(), |record| record.finished.promise.clone().into()))
+        },
+        binding,
+    );
+    for (name, getter) in [("playState", get_play_state), ("finished", get_finished)] {
+        object.define_property_or_throw(
+            js_string!(name),
+            PropertyDescriptor::builder()
+                .get(getter.to_js_function(context.realm()))
+                .enumerable(true)
+                .configurable(true),
+            context,
+        )?;
+    }
+    Ok(object)
+}
+
+/// Add `animate()` to the wrapper of an element
+pub fn install_animate(
+    wrapper: &JsObject,
+    handles: Rc<RefCell<NodeHandleTable>>,
+    animations: ScriptAnimations,
+    context: &mut Context,
+) -> JsResult<()> {
+    let animate = NativeFunction::from_copy_closure_with_captures(
+        |this, args, binding: &AnimateBinding, context| {
+            let node = this
+                .as_object()
+                .and_then(|wrapper| binding.handles.borrow().resolve_wrapper(wrapper, context))
+                .ok_or_else(|| JsNativeError::typ().with_message("Illegal invocation: not an element"))?;
+            let keyframes = match args.first().and_then(JsValue::as_object).cloned() {
+                Some(object) if object.is_array() => parse_keyframe_list(&JsArray::from_object(object)?, context)?,
+                Some(object) => parse_property_indexed(&object, context)?,
+                None => Vec::new(),
+            };
+            let timing = parse_timing(&args.get(1).cloned().unwrap_or_default(), context)?;
+            let effect = KeyframeEffect::new(keyframes, timing)
+                .map_err(|error| JsNativeError::typ().with_message(error.to_string()))?;
+
+            let animations = &binding.animations;
+            let id = {
+                let mut state = animations.state.borrow_mut();
+                let now = state.now;
+                state.timeline.add(node.id, effect, now)
+            };
+            let object = create_animation_object(animations, id, context)?;
+            let finished = FinishedPromise::new(context);
+            animations.state.borrow_mut().records.push(AnimationRecord { id, object: object.clone(), finished });
+            Ok(object.into())
+        },
+        AnimateBinding { handles, animations },
+    );
+    wrapper.define_property_or_throw(
+        js_string!("animate"),
+        PropertyDescriptor::builder()
+            .value(animate.to_js_function(context.realm()))
+            .writable(true)
+            .enumerable(false)
+            .configurable(true),
+        context,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use boa_engine::Source;
+    use dom::Document;
+
+    fn eval_string(context: &mut Context, code: &str) -> String {
+        let value = context.eval(Source::from_bytes(code)).unwrap();
+        value.to_string(context).unwrap().to_std_string_escaped()
+    }
+
+    #[test]
+    fn test_animate_from_script() {
+        let document = Document::new();
+        let box_element = document.create_element("div");
+        let handles = Rc::new(RefCell::new(NodeHandleTable::new()));
+        let animations = ScriptAnimations::new();
+
+        let mut context = Context::default();
+        let wrapper = handles.borrow_mut().wrap_node(&box_element, &mut context);
+        install_animate(&wrapper, Rc::clone(&handles), animations.clone(), &mut context).unwrap();
+        context.register_global_property(js_string!("box"), wrapper, Attribute::all()).unwrap();
+
+        let code = "var log = [];
+            var animation = box.animate({ opacity: [0, 1], marginLeft: ['0px', '40px'] }, { duration: 1000, fill: 'forwards' });
+            animation.finished.then((a) => log.push('finished', a === animation));
+            animation.playState";
+        assert_eq!(eval_string(&mut context, code), "running");
+
+        animations.advance(250.0, &mut context);
+        let styles = animations.animated_styles();
+        assert_eq!(styles[&box_element.id], vec![
+            ("opacity".to_string(), "0.25".to_string()),
+            ("margin-left".to_string(), "10px".to_string()),
+        ]);
+
+        assert_eq!(eval_string(&mut context, "animation.pause(); animation.playbackRate = 2; animation.play(); animation.currentTime"), "250");
+        assert_eq!(animations.advance(625.0, &mut context), 1);
+        context.run_jobs();
+        assert_eq!(eval_string(&mut context, "log.join(',') + ' ' + animation.playState"), "finished,true finished");
+        assert_eq!(animations.animated_styles()[&box_element.id][1].1, "40px");
+
+        // Cancelling removes the effect and rejects the new promise
+        let code = "animation.play(); var reason = ''; animation.finished.catch((e) => reason = e.name); animation.cancel();";
+        context.eval(Source::from_bytes(code)).unwrap();
+        context.run_jobs();
+        assert_eq!(eval_string(&mut context, "reason + ' ' + animation.playState"), "AbortError idle");
+        assert!(animations.animated_styles().is_empty());
+
+        let code = "try { box.animate([{ opacity: 0 }], { iterations: Infinity, duration: 10 }).finish() } catch (e) { e.name }";
+        assert_eq!(eval_string(&mut context, code), "InvalidStateError");
+    }
+}
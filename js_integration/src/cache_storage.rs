@@ -0,0 +1,380 @@
+//! # Cache Storage API
+//!
+//! This module exposes `caches` and its `Cache` objects to scripts, on top
+//! of the networking crate's `CacheStorage`.
+//!
+//! ## Design Principles
+//!
+//! 1. **Origin Partitioned**: A page only reaches the caches of its own
+//!    origin, and only secure origins get `caches` at all, as in browsers.
+//! 2. **Promise Based**: Every method returns a promise. Storage errors and
+//!    invalid requests reject it with a `TypeError`.
+//! 3. **Settled Before Returning**: The storage is local, so promises are
+//!    settled right away. `add` and `addAll` fetch on a helper thread with
+//!    a runtime of their own, so they also work when called from inside
+//!    the shell's async runtime.
+
+use boa_engine::{
+    object::{builtins::{JsArray, JsPromise}, ObjectInitializer},
+    property::Attribute,
+    js_string, Context, JsNativeError, JsObject, JsResult, JsValue, NativeFunction,
+};
+use boa_gc::{Finalize, Trace};
+use dom::Origin;
+use networking::cache_storage::{self, CacheStorage};
+use networking::disk_cache::{CachedResponse, DiskCache};
+use networking::HttpClient;
+use networking::UrlResolver;
+
+use crate::fetch_binding::{FetchBinding, StreamingFetchResponse};
+use crate::readable_stream::ReadableStream;
+
+/// Check whether an origin may use the Cache Storage API
+///
+/// Only HTTPS origins and the local host are secure contexts.
+pub fn is_secure_origin(origin: &Origin) -> bool {
+    match origin {
+        Origin::Tuple { scheme, host, .. } => {
+            scheme == "https" || host == "localhost" || host == "127.0.0.1" || host == "[::1]"
+        }
+        Origin::Opaque => false,
+    }
+}
+
+/// Captures for the methods of `caches`
+#[derive(Trace, Finalize)]
+struct StorageBinding {
+    #[unsafe_ignore_trace]
+    storage: CacheStorage,
+    #[unsafe_ignore_trace]
+    resolver: UrlResolver,
+}
+
+/// Captures for the methods of one `Cache` object
+#[derive(Trace, Finalize)]
+struct CacheBinding {
+    #[unsafe_ignore_trace]
+    cache: DiskCache,
+    #[unsafe_ignore_trace]
+    resolver: UrlResolver,
+}
+
+/// Install `caches` for a document with the base URL `base_url`
+///
+/// `storage` should already be the view of the document's origin, see
+/// `CacheStorage::for_origin`. Relative request URLs resolve against
+/// `base_url`.
+pub fn initialize_cache_storage_bindings(context: &mut Context, storage: CacheStorage, base_url: Option<&str>) -> JsResult<()> {
+    let resolver = base_url.and_then(UrlResolver::with_base).unwrap_or_default();
+    let binding = || StorageBinding { storage: storage.clone(), resolver: resolver.clone() };
+
+    let open = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &StorageBinding, context| {
+            let result = cache_name(args, context).and_then(|name| {
+                let cache = binding.storage.open_cache(&name).map_err(storage_error)?;
+                Ok(cache_object(cache, binding.resolver.clone(), context).into())
+            });
+            Ok(settle(result, context))
+        },
+        binding(),
+    );
+    let has = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &StorageBinding, context| {
+            let result = cache_name(args, context).map(|name| binding.storage.has(&name).into());
+            Ok(settle(result, context))
+        },
+        binding(),
+    );
+    let delete = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &StorageBinding, context| {
+            let result = cache_name(args, context)
+                .and_then(|name| binding.storage.delete(&name).map(JsValue::from).map_err(storage_error));
+            Ok(settle(result, context))
+        },
+        binding(),
+    );
+    let keys = NativeFunction::from_copy_closure_with_captures(
+        |_this, _args, binding: &StorageBinding, context| {
+            let names = binding.storage.keys().into_iter().map(|name| js_string!(name).into());
+            let names = JsArray::from_iter(names, context);
+            Ok(JsPromise::resolve(names, context).into())
+        },
+        binding(),
+    );
+    let match_any = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &StorageBinding, context| {
+            let result = request_url(args.first(), &binding.resolver, context)
+                .and_then(|url| response_or_undefined(binding.storage.match_url(&url), context));
+            Ok(settle(result, context))
+        },
+        binding(),
+    );
+
+    let caches = ObjectInitializer::new(context)
+        .function(open, js_string!("open"), 1)
+        .function(has, js_string!("has"), 1)
+        .function(delete, js_string!("delete"), 1)
+        .function(keys, js_string!("keys"), 0)
+        .function(match_any, js_string!("match"), 1)
+        .build();
+    context.register_global_property(js_string!("caches"), caches, Attribute::all())?;
+    Ok(())
+}
+
+/// Create a `Cache` object
+fn cache_object(cache: DiskCache, resolver: UrlResolver, context: &mut Context) -> JsObject {
+    let binding = || CacheBinding { cache: cache.clone(), resolver: resolver.clone() };
+
+    let match_request = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &CacheBinding, context| {
+            let result = request_url(args.first(), &binding.resolver, context)
+                .and_then(|url| response_or_undefined(binding.cache.get(&url), context));
+            Ok(settle(result, context))
+        },
+        binding(),
+    );
+    let put = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &CacheBinding, context| {
+            let result = (|| {
+                check_get_request(args.first(), context)?;
+                let url = request_url(args.first(), &binding.resolver, context)?;
+                let response = stored_response(&url, args.get(1), context)?;
+                binding.cache.put(response).map_err(storage_error)?;
+                Ok(JsValue::undefined())
+            })();
+            Ok(settle(result, context))
+        },
+        binding(),
+    );
+    let add = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &CacheBinding, context| {
+            let result = request_url(args.first(), &binding.resolver, context)
+                .and_then(|url| fetch_into(&binding.cache, vec![url]));
+            Ok(settle(result, context))
+        },
+        binding(),
+    );
+    let add_all = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &CacheBinding, context| {
+            let result = (|| {
+                let requests = args.first().and_then(JsValue::as_object).cloned().ok_or_else(|| {
+                    JsNativeError::typ().with_message("Cache.addAll: argument is not a list of requests")
+                })?;
+                let length = requests.get(js_string!("length"), context)?.to_length(context)?;
+                let mut urls = Vec::new();
+                for index in 0..length {
+                    let request = requests.get(index, context)?;
+                    urls.push(request_url(Some(&request), &binding.resolver, context)?);
+                }
+                fetch_into(&binding.cache, urls)
+            })();
+            Ok(settle(result, context))
+        },
+        binding(),
+    );
+    let delete = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &CacheBinding, context| {
+            let result = request_url(args.first(), &binding.resolver, context)
+                .and_then(|url| binding.cache.remove(&url).map(JsValue::from).map_err(storage_error));
+            Ok(settle(result, context))
+        },
+        binding(),
+    );
+    let keys = NativeFunction::from_copy_closure_with_captures(
+        |_this, _args, binding: &CacheBinding, context| {
+            let requests: Vec<JsValue> = binding
+                .cache
+                .urls()
+                .into_iter()
+                .map(|url| request_object(&url, context).into())
+                .collect();
+            let requests = JsArray::from_iter(requests, context);
+            Ok(JsPromise::resolve(requests, context).into())
+        },
+        binding(),
+    );
+
+    ObjectInitializer::new(context)
+        .function(match_request, js_string!("match"), 1)
+        .function(put, js_string!("put"), 2)
+        .function(add, js_string!("add"), 1)
+        .function(add_all, js_string!("addAll"), 1)
+        .function(delete, js_string!("delete"), 1)
+        .function(keys, js_string!("keys"), 0)
+        .build()
+}
+
+/// Turn the outcome of a method into a settled promise
+fn settle(result: JsResult<JsValue>, context: &mut Context) -> JsValue {
+    match result {
+        Ok(value) => JsPromise::resolve(value, context).into(),
+        Err(error) => JsPromise::reject(error, context).into(),
+    }
+}
+
+fn storage_error(error: std::io::Error) -> boa_engine::JsError {
+    JsNativeError::typ().with_message(format!("Cache storage failed: {}", error)).into()
+}
+
+fn cache_name(args: &[JsValue], context: &mut Context) -> JsResult<String> {
+    Ok(args.first().cloned().unwrap_or_default().to_string(context)?.to_std_string_escaped())
+}
+
+/// Get the absolute URL of a request given as a URL string or a
+/// `Request`-like object with a `url`
+fn request_url(request: Option<&JsValue>, resolver: &UrlResolver, context: &mut Context) -> JsResult<String> {
+    let request = request.cloned().unwrap_or_default();
+    let text = match request.as_object() {
+        Some(object) => object.get(js_string!("url"), context)?,
+        None => request,
+    }
+    .to_string(context)?
+    .to_std_string_escaped();
+    let url = resolver.resolve(&text).ok_or_else(|| JsNativeError::typ().with_message(format!("Invalid request URL: {}", text)))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(JsNativeError::typ().with_message(format!("Request scheme '{}' is unsupported", url.scheme())).into());
+    }
+    Ok(url.to_string())
+}
+
+/// Reject requests whose `method` is not GET, which caches cannot hold
+fn check_get_request(request: Option<&JsValue>, context: &mut Context) -> JsResult<()> {
+    let Some(object) = request.and_then(JsValue::as_object) else {
+        return Ok(());
+    };
+    let method = object.get(js_string!("method"), context)?;
+    if method.is_undefined() {
+        return Ok(());
+    }
+    let method = method.to_string(context)?.to_std_string_escaped();
+    if !method.eq_ignore_ascii_case("GET") {
+        return Err(JsNativeError::typ().with_message(format!("Request method '{}' is unsupported", method)).into());
+    }
+    Ok(())
+}
+
+/// Read the response a script passed to `put`
+fn stored_response(url: &str, response: Option<&JsValue>, context: &mut Context) -> JsResult<CachedResponse> {
+    let response = response.and_then(JsValue::as_object).cloned().ok_or_else(|| {
+        JsNativeError::typ().with_message("Cache.put: argument is not a Response")
+    })?;
+    let status = match response.get(js_string!("status"), context)? {
+        status if status.is_undefined() => 200,
+        status => status.to_u32(context)? as u16,
+    };
+    if status == 206 {
+        return Err(JsNativeError::typ().with_message("Partial responses cannot be cached").into());
+    }
+    let mut headers = Vec::new();
+    if let Some(header_object) = response.get(js_string!("headers"), context)?.as_object() {
+        for key in header_object.own_property_keys(context)? {
+            let name = key.to_string();
+            let value = header_object.get(key, context)?.to_string(context)?.to_std_string_escaped();
+            headers.push((name, value));
+        }
+    }
+    let body = match response.get(js_string!("_body"), context)? {
+        body if body.is_null_or_undefined() => Vec::new(),
+        body => body.to_string(context)?.to_std_string_escaped().into_bytes(),
+    };
+    Ok(CachedResponse::new(url, status, headers, body))
+}
+
+/// Fetch URLs and store their responses, all or none
+fn fetch_into(cache: &DiskCache, urls: Vec<String>) -> JsResult<JsValue> {
+    let cache = cache.clone();
+    let fetched = std::thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())?;
+        runtime
+            .block_on(cache_storage::add_all(&cache, &HttpClient::new(), &urls))
+            .map_err(|e| e.to_string())
+    })
+    .join()
+    .unwrap_or_else(|_| Err("fetch thread panicked".to_string()));
+    match fetched {
+        Ok(_) => Ok(JsValue::undefined()),
+        Err(e) => Err(JsNativeError::typ().with_message(format!("Failed to fetch: {}", e)).into()),
+    }
+}
+
+fn request_object(url: &str, context: &mut Context) -> JsObject {
+    ObjectInitializer::new(context)
+        .property(js_string!("url"), js_string!(url), Attribute::all())
+        .property(js_string!("method"), js_string!("GET"), Attribute::all())
+        .build()
+}
+
+fn response_or_undefined(response: Option<CachedResponse>, context: &mut Context) -> JsResult<JsValue> {
+    match response {
+        Some(response) => response_object(&response, context),
+        None => Ok(JsValue::undefined()),
+    }
+}
+
+/// Create a `Response` for a stored response
+fn response_object(response: &CachedResponse, context: &mut Context) -> JsResult<JsValue> {
+    let streaming = StreamingFetchResponse {
+        status: response.status,
+        status_text: response.status_text.clone(),
+        headers: response.headers.iter().cloned().collect(),
+        url: response.url.clone(),
+        ok: response.is_ok(),
+        body: ReadableStream::from_bytes(response.body.clone()),
+    };
+    let object = FetchBinding::create_streaming_response_object(&streaming, context)?;
+    if let Some(object) = object.as_object() {
+        let body = String::from_utf8_lossy(&response.body).into_owned();
+        object.set(js_string!("_body"), js_string!(body), false, context)?;
+        let text = NativeFunction::from_fn_ptr(FetchBinding::response_text).to_js_function(context.realm());
+        object.set(js_string!("text"), text, false, context)?;
+    }
+    Ok(object)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use boa_engine::Source;
+
+    // `FetchBinding` shares its promise host the way the engine does
+    #[test]
+    #[allow(clippy::arc_with_non_send_sync)]
+    fn test_cache_put_match_and_partitioning() {
+        let storage = CacheStorage::new();
+        let context = &mut Context::default();
+        let promise_host = std::sync::Arc::new(std::sync::Mutex::new(crate::promise_host::PromiseHost::new()));
+        FetchBinding::new(promise_host).initialize_fetch_bindings(context).unwrap();
+        initialize_cache_storage_bindings(context, storage.for_origin("https://app.example"), Some("https://app.example/index.html"))
+            .unwrap();
+
+        let code = r#"
+            globalThis.log = [];
+            caches.open("v1").then((cache) => {
+                cache.put("/data.json", Response.Response("[1,2]", { status: 200 }));
+                cache.put({ url: "/form", method: "POST" }, Response.Response("x")).catch((e) => log.push(e.name));
+                return cache.match("data.json");
+            }).then((response) => {
+                log.push(response.status, response.text());
+                return caches.has("v1");
+            }).then((has) => {
+                log.push(has);
+                return caches.match("https://app.example/missing");
+            }).then((missing) => log.push(String(missing)));
+        "#;
+        context.eval(Source::from_bytes(code)).unwrap();
+        context.run_jobs();
+
+        let log = context.eval(Source::from_bytes("log.join(',')")).unwrap();
+        assert_eq!(log.to_string(context).unwrap().to_std_string_escaped(), "TypeError,200,[1,2],true,undefined");
+
+        // The entry is stored in the origin's partition of the shared storage
+        assert_eq!(storage.keys(), vec!["https://app.example v1".to_string()]);
+        let stored = storage.for_origin("https://app.example").match_url("https://app.example/data.json").unwrap();
+        assert_eq!(stored.body, b"[1,2]");
+        assert!(is_secure_origin(&Origin::from_url("https://app.example/")));
+        assert!(!is_secure_origin(&Origin::from_url("http://app.example/")));
+    }
+}
@@ -0,0 +1,245 @@
+//! # Async Clipboard API
+//!
+//! This module exposes `navigator.clipboard.readText()` and
+//! `navigator.clipboard.writeText()` on top of the shared clipboard in the
+//! DOM crate.
+//!
+//! ## Design Principles
+//!
+//! 1. **Permission Gated**: Every read and write is checked against the
+//!    document's origin; the first request for each kind of access asks the
+//!    shell through a prompt callback and the answer is remembered.
+//! 2. **Promise Based**: Both methods return promises that reject with a
+//!    `NotAllowedError` when access is denied, as in browsers.
+//! 3. **One Clipboard**: The binding shares its `Clipboard` handle with the
+//!    input handler, so text written by a copy button can be pasted into an
+//!    input with the keyboard.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use boa_engine::{
+    object::{builtins::JsPromise, ObjectInitializer},
+    property::Attribute,
+    js_string, Context, JsError, JsObject, JsResult, JsValue, NativeFunction,
+};
+use boa_gc::{Finalize, Trace};
+use dom::clipboard::{Clipboard, ClipboardError};
+
+/// The kind of clipboard access a page asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClipboardAccess {
+    Read,
+    Write,
+}
+
+impl fmt::Display for ClipboardAccess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClipboardAccess::Read => write!(f, "read"),
+            ClipboardAccess::Write => write!(f, "write"),
+        }
+    }
+}
+
+/// Callback asking the user whether an origin may access the clipboard
+pub type PermissionPrompt = Arc<dyn Fn(&str, ClipboardAccess) -> bool + Send + Sync>;
+
+/// Clipboard permission decisions per origin
+#[derive(Clone)]
+pub struct ClipboardPermissions {
+    prompt: PermissionPrompt,
+    decisions: Arc<Mutex<HashMap<(String, ClipboardAccess), bool>>>,
+}
+
+impl ClipboardPermissions {
+    /// Create permissions that ask `prompt` the first time an origin needs
+    /// each kind of access
+    pub fn new(prompt: PermissionPrompt) -> Self {
+        Self {
+            prompt,
+            decisions: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Create permissions that grant every request without asking
+    pub fn allow_all() -> Self {
+        Self::new(Arc::new(|_, _| true))
+    }
+
+    /// Create permissions that deny every request without asking
+    pub fn deny_all() -> Self {
+        Self::new(Arc::new(|_, _| false))
+    }
+
+    /// Check whether an origin may access the clipboard, prompting if it
+    /// has not been decided yet
+    pub fn check(&self, origin: &str, access: ClipboardAccess) -> bool {
+        let key = (origin.to_string(), access);
+        if let Some(allowed) = self.decisions.lock().unwrap().get(&key) {
+            return *allowed;
+        }
+        // Prompt without holding the lock; the prompt may block on the user
+        let allowed = (self.prompt)(origin, access);
+        self.decisions.lock().unwrap().insert(key, allowed);
+        allowed
+    }
+
+    /// Record a decision, e.g. from the shell's site settings
+    pub fn set(&self, origin: &str, access: ClipboardAccess, allowed: bool) {
+        self.decisions.lock().unwrap().insert((origin.to_string(), access), allowed);
+    }
+
+    /// Forget all decisions, so the next request prompts again
+    pub fn reset(&self) {
+        self.decisions.lock().unwrap().clear();
+    }
+}
+
+impl fmt::Debug for ClipboardPermissions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClipboardPermissions")
+            .field("decisions", &self.decisions.lock().unwrap())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Captures for the clipboard methods
+#[derive(Trace, Finalize)]
+struct ClipboardBinding {
+    #[unsafe_ignore_trace]
+    clipboard: Clipboard,
+    #[unsafe_ignore_trace]
+    permissions: ClipboardPermissions,
+    #[unsafe_ignore_trace]
+    origin: String,
+}
+
+impl ClipboardBinding {
+    fn deny(&self, access: ClipboardAccess, context: &mut Context) -> JsValue {
+        println!("🛑 Clipboard {} denied for {}", access, self.origin);
+        let error = create_not_allowed_error(context, &format!("Clipboard {} access was denied", access));
+        JsPromise::reject(JsError::from_opaque(error), context).into()
+    }
+}
+
+/// Create a `NotAllowedError` DOMException
+pub fn create_not_allowed_error(context: &mut Context, message: &str) -> JsValue {
+    ObjectInitializer::new(context)
+        .property(js_string!("name"), js_string!("NotAllowedError"), Attribute::all())
+        .property(js_string!("message"), js_string!(message), Attribute::all())
+        // NotAllowedError has no legacy code
+        .property(js_string!("code"), 0, Attribute::all())
+        .build()
+        .into()
+}
+
+/// Install `navigator.clipboard` for a document from `origin`
+///
+/// Creates `navigator` if the context does not have one yet.
+pub fn initialize_clipboard_bindings(
+    context: &mut Context,
+    clipboard: Clipboard,
+    permissions: ClipboardPermissions,
+    origin: &str,
+) -> JsResult<()> {
+    let binding = || ClipboardBinding {
+        clipboard: clipboard.clone(),
+        permissions: permissions.clone(),
+        origin: origin.to_string(),
+    };
+
+    let read_text = NativeFunction::from_copy_closure_with_captures(
+        |_this, _args, binding: &ClipboardBinding, context| {
+            if !binding.permissions.check(&binding.origin, ClipboardAccess::Read) {
+                return Ok(binding.deny(ClipboardAccess::Read, context));
+            }
+            match binding.clipboard.read_text() {
+                Ok(text) => Ok(JsPromise::resolve(js_string!(text), context).into()),
+                Err(ClipboardError::Empty) => Ok(JsPromise::resolve(js_string!(""), context).into()),
+                Err(error) => {
+                    let error = create_not_allowed_error(context, &error.to_string());
+                    Ok(JsPromise::reject(JsError::from_opaque(error), context).into())
+                }
+            }
+        },
+        binding(),
+    );
+
+    let write_text = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &ClipboardBinding, context| {
+            let text = args
+                .first()
+                .cloned()
+                .unwrap_or_default()
+                .to_string(context)?
+                .to_std_string_escaped();
+            if !binding.permissions.check(&binding.origin, ClipboardAccess::Write) {
+                return Ok(binding.deny(ClipboardAccess::Write, context));
+            }
+            match binding.clipboard.write_text(&text) {
+                Ok(()) => Ok(JsPromise::resolve(JsValue::undefined(), context).into()),
+                Err(error) => {
+                    let error = create_not_allowed_error(context, &error.to_string());
+                    Ok(JsPromise::reject(JsError::from_opaque(error), context).into())
+                }
+            }
+        },
+        binding(),
+    );
+
+    let clipboard_object = ObjectInitializer::new(context)
+        .function(read_text, js_string!("readText"), 0)
+        .function(write_text, js_string!("writeText"), 1)
+        .build();
+
+    let navigator = navigator_object(context)?;
+    navigator.set(js_string!("clipboard"), clipboard_object, false, context)?;
+    Ok(())
+}
+
+/// Get the global `navigator` object, creating it if needed
+pub(crate) fn navigator_object(context: &mut Context) -> JsResult<JsObject> {
+    let global = context.global_object();
+    if let Some(navigator) = global.get(js_string!("navigator"), context)?.as_object() {
+        return Ok(navigator.clone());
+    }
+    let navigator = ObjectInitializer::new(context).build();
+    context.register_global_property(js_string!("navigator"), navigator.clone(), Attribute::all())?;
+    Ok(navigator)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use boa_engine::Source;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_clipboard_api_is_permission_gated() {
+        let context = &mut Context::default();
+        let clipboard = Clipboard::in_memory();
+        let prompts = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&prompts);
+        let permissions = ClipboardPermissions::new(Arc::new(move |origin, access| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            origin == "https://example.com" && access == ClipboardAccess::Write
+        }));
+        initialize_clipboard_bindings(context, clipboard.clone(), permissions, "https://example.com").unwrap();
+
+        let code = r#"
+            globalThis.log = [];
+            navigator.clipboard.writeText("share link").then(() => log.push("written"));
+            navigator.clipboard.writeText("again").then(() => log.push("written"));
+            navigator.clipboard.readText().catch((error) => log.push(error.name));
+        "#;
+        context.eval(Source::from_bytes(code)).unwrap();
+        context.run_jobs();
+
+        let log = context.eval(Source::from_bytes("log.join(',')")).unwrap();
+        assert_eq!(log.to_string(context).unwrap().to_std_string_escaped(), "written,written,NotAllowedError");
+        assert_eq!(clipboard.read_text().unwrap(), "again");
+        // One prompt per kind of access
+        assert_eq!(prompts.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,230 @@
+//! # Collections
+//!
+//! This module creates the collection objects DOM methods return: live
+//! `HTMLCollection`s for `children`, `getElementsByTagName()` and
+//! `getElementsByClassName()`, and static `NodeList`s for
+//! `querySelectorAll()`.
+//!
+//! ## Design Principles
+//!
+//! 1. **Live Through a Proxy**: An `HTMLCollection` is a `Proxy` whose
+//!    traps answer indexed and named properties from a
+//!    `dom::LiveCollection`, so `collection[0]` and `collection.length`
+//!    change with the tree without rebuilding the object.
+//! 2. **Cached in the DOM**: Reading a collection in a loop does not walk
+//!    the tree each time; the `LiveCollection` caches its elements until
+//!    the next mutation.
+//! 3. **Static Lists Are Plain**: A `NodeList` from `querySelectorAll()`
+//!    is a snapshot, so it is an ordinary object with indexed properties.
+//! 4. **Iterable Like Arrays**: Both kinds borrow `Array.prototype` for
+//!    `Symbol.iterator`, and `NodeList` also for `forEach()`, `keys()`,
+//!    `values()` and `entries()`.
+
+use std::rc::Rc;
+use boa_engine::{
+    builtins::object::OrdinaryObject,
+    object::{builtins::{JsArray, JsProxy}, ObjectInitializer},
+    property::{Attribute, PropertyDescriptor, PropertyKey},
+    js_string, Context, JsData, JsNativeError, JsObject, JsResult, JsSymbol, JsValue, NativeFunction,
+};
+use boa_gc::{Finalize, Trace};
+use dom::{LiveCollection, Node};
+use crate::element_wrappers::ElementWrappers;
+
+/// Native data of the target behind an `HTMLCollection` proxy
+#[derive(Clone, Trace, Finalize, JsData)]
+struct CollectionData {
+    #[unsafe_ignore_trace]
+    collection: Rc<LiveCollection>,
+    wrappers: ElementWrappers,
+}
+
+/// Create a live `HTMLCollection` object for a collection
+pub fn html_collection(collection: LiveCollection, wrappers: ElementWrappers, context: &mut Context) -> JsResult<JsObject> {
+    let data = CollectionData { collection: Rc::new(collection), wrappers };
+
+    let item = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, data: &CollectionData, context| {
+            let index = args.first().cloned().unwrap_or_default().to_u32(context)?;
+            Ok(data.wrap_or_null(data.collection.item(index as usize), context))
+        },
+        data.clone(),
+    );
+    let named_item = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, data: &CollectionData, context| {
+            let name = args.first().cloned().unwrap_or_default().to_string(context)?.to_std_string_escaped();
+            Ok(data.wrap_or_null(data.collection.named_item(&name), context))
+        },
+        data.clone(),
+    );
+    let length = NativeFunction::from_copy_closure_with_captures(
+        |_this, _args, data: &CollectionData, _context| Ok(JsValue::from(data.collection.len() as u32)),
+        data.clone(),
+    );
+    let length = length.to_js_function(context.realm());
+    let values = array_method("values", context)?;
+
+    let target = ObjectInitializer::with_native_data(data, context)
+        .function(item, js_string!("item"), 1)
+        .function(named_item, js_string!("namedItem"), 1)
+        .accessor(js_string!("length"), Some(length), None, Attribute::CONFIGURABLE)
+        .property(JsSymbol::iterator(), values, Attribute::WRITABLE | Attribute::CONFIGURABLE)
+        .property(JsSymbol::to_string_tag(), js_string!("HTMLCollection"), Attribute::CONFIGURABLE)
+        .build();
+
+    let proxy = JsProxy::builder(target)
+        .get(collection_get)
+        .has(collection_has)
+        .get_own_property_descriptor(collection_get_own_property_descriptor)
+        .own_keys(collection_own_keys)
+        .build(context);
+    Ok(proxy.into())
+}
+
+/// Create a static `NodeList` object for a snapshot of nodes
+pub fn static_node_list(nodes: &[Rc<Node>], wrappers: &ElementWrappers, context: &mut Context) -> JsResult<JsObject> {
+    let mut list = ObjectInitializer::new(context);
+    for (index, node) in nodes.iter().enumerate() {
+        let wrapper = wrappers.wrap(node, list.context());
+        list.property(index, wrapper, Attribute::ENUMERABLE);
+    }
+    list.property(js_string!("length"), nodes.len() as u32, Attribute::empty())
+        .function(NativeFunction::from_fn_ptr(node_list_item), js_string!("item"), 1)
+        .property(JsSymbol::to_string_tag(), js_string!("NodeList"), Attribute::CONFIGURABLE);
+    let list = list.build();
+
+    for name in ["forEach", "keys", "values", "entries"] {
+        let method = array_method(name, context)?;
+        list.define_property_or_throw(
+            js_string!(name),
+            PropertyDescriptor::builder().value(method).writable(true).enumerable(false).configurable(true),
+            context,
+        )?;
+    }
+    let values = array_method("values", context)?;
+    list.define_property_or_throw(
+        JsSymbol::iterator(),
+        PropertyDescriptor::builder().value(values).writable(true).enumerable(false).configurable(true),
+        context,
+    )?;
+    Ok(list)
+}
+
+impl CollectionData {
+    /// Wrap an element, or return `null` for none
+    fn wrap_or_null(&self, element: Option<Rc<Node>>, context: &mut Context) -> JsValue {
+        match element {
+            Some(element) => self.wrappers.wrap(&element, context).into(),
+            None => JsValue::null(),
+        }
+    }
+
+    /// The element a supported property name refers to, if the name is
+    /// not shadowed by a member of the collection
+    fn named_property(&self, target: &JsObject, key: &PropertyKey, context: &mut Context) -> JsResult<Option<Rc<Node>>> {
+        let PropertyKey::String(name) = key else {
+            return Ok(None);
+        };
+        if target.has_property(key.clone(), context)? {
+            return Ok(None);
+        }
+        Ok(self.collection.named_item(&name.to_std_string_escaped()))
+    }
+}
+
+/// Get a method of `Array.prototype` to share
+fn array_method(name: &str, context: &mut Context) -> JsResult<JsValue> {
+    context.intrinsics().constructors().array().prototype().get(js_string!(name), context)
+}
+
+/// Resolve the target of a trap and its collection data
+fn collection_target(args: &[JsValue]) -> JsResult<(JsObject, CollectionData)> {
+    let target = args
+        .first()
+        .and_then(JsValue::as_object)
+        .cloned()
+        .ok_or_else(|| JsNativeError::typ().with_message("Illegal invocation: not a collection"))?;
+    let data = target
+        .downcast_ref::<CollectionData>()
+        .map(|data| data.clone())
+        .ok_or_else(|| JsNativeError::typ().with_message("Illegal invocation: not a collection"))?;
+    Ok((target, data))
+}
+
+/// The property key a trap was called with
+fn trap_key(args: &[JsValue], context: &mut Context) -> JsResult<PropertyKey> {
+    args.get(1).cloned().unwrap_or_default().to_property_key(context)
+}
+
+/// `get` trap: indices and names read the live collection
+fn collection_get(_this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+    let (target, data) = collection_target(args)?;
+    let key = trap_key(args, context)?;
+    if let PropertyKey::Index(index) = &key {
+        return Ok(match data.collection.item(index.get() as usize) {
+            Some(element) => data.wrappers.wrap(&element, context).into(),
+            None => JsValue::undefined(),
+        });
+    }
+    if let Some(element) = data.named_property(&target, &key, context)? {
+        return Ok(data.wrappers.wrap(&element, context).into());
+    }
+    target.get(key, context)
+}
+
+/// `has` trap: indices below the length and supported names exist
+fn collection_has(_this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+    let (target, data) = collection_target(args)?;
+    let key = trap_key(args, context)?;
+    let has = match &key {
+        PropertyKey::Index(index) => (index.get() as usize) < data.collection.len(),
+        _ => data.named_property(&target, &key, context)?.is_some() || target.has_property(key, context)?,
+    };
+    Ok(has.into())
+}
+
+/// `getOwnPropertyDescriptor` trap: indices are enumerable read-only data
+/// properties, names are not enumerable
+fn collection_get_own_property_descriptor(_this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+    let (target, data) = collection_target(args)?;
+    let key = trap_key(args, context)?;
+    let (element, enumerable) = match &key {
+        PropertyKey::Index(index) => match data.collection.item(index.get() as usize) {
+            Some(element) => (element, true),
+            None => return Ok(JsValue::undefined()),
+        },
+        _ => match data.named_property(&target, &key, context)? {
+            Some(element) => (element, false),
+            None => return OrdinaryObject::get_own_property_descriptor(&JsValue::undefined(), &[target.into(), key.into()], context),
+        },
+    };
+    let wrapper = data.wrappers.wrap(&element, context);
+    Ok(ObjectInitializer::new(context)
+        .property(js_string!("value"), wrapper, Attribute::all())
+        .property(js_string!("writable"), false, Attribute::all())
+        .property(js_string!("enumerable"), enumerable, Attribute::all())
+        .property(js_string!("configurable"), true, Attribute::all())
+        .build()
+        .into())
+}
+
+/// `ownKeys` trap: the current indices, then the members of the target
+fn collection_own_keys(_this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+    let (target, data) = collection_target(args)?;
+    let indices = (0..data.collection.len()).map(|index| JsValue::from(js_string!(index.to_string())));
+    let members: Vec<JsValue> = target.own_property_keys(context)?.into_iter().map(JsValue::from).collect();
+    Ok(JsArray::from_iter(indices.chain(members), context).into())
+}
+
+/// `NodeList.prototype.item()` for static lists
+fn node_list_item(this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+    let list = this
+        .as_object()
+        .ok_or_else(|| JsNativeError::typ().with_message("Illegal invocation: not a NodeList"))?;
+    let index = args.first().cloned().unwrap_or_default().to_u32(context)?;
+    let length = list.get(js_string!("length"), context)?.to_u32(context)?;
+    if index >= length {
+        return Ok(JsValue::null());
+    }
+    list.get(index, context)
+}
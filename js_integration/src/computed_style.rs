@@ -0,0 +1,263 @@
+//! # Computed Styles
+//!
+//! This module implements `window.getComputedStyle()`. The element's
+//! styles are computed by the same cascade the layout engine uses and
+//! handed to the script as a read-only declaration object.
+//!
+//! ## Design Principles
+//!
+//! 1. **One Cascade**: Styles come from `css_parser::CSSCascadeEngine`, so
+//!    scripts see what the page is drawn with.
+//! 2. **Visitedness Stays Private**: Styles are computed as if every link
+//!    were unvisited, so a script cannot learn the user's history by
+//!    reading the color of a `:visited` link.
+//! 3. **Snapshots**: The returned object is a copy; later style changes
+//!    need another call, which keeps the binding free of live state.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use boa_engine::{
+    object::ObjectInitializer,
+    property::Attribute,
+    js_string, Context, JsNativeError, JsResult, JsValue, NativeFunction,
+};
+use boa_gc::{Finalize, Trace};
+use css_parser::media::MediaFeatures;
+use css_parser::{CSSCascadeEngine, ComputedStyles, Stylesheet};
+use layout::{LayoutBox, LayoutEngine};
+use crate::node_handles::NodeHandleTable;
+
+struct SourceState {
+    stylesheet: Option<Stylesheet>,
+    features: MediaFeatures,
+    cascade: CSSCascadeEngine,
+}
+
+/// The stylesheet and environment `getComputedStyle` computes against
+///
+/// Cloning shares the source.
+#[derive(Clone)]
+pub struct StyleSource {
+    state: Rc<RefCell<SourceState>>,
+}
+
+impl StyleSource {
+    fn new() -> Self {
+        let state = SourceState {
+            stylesheet: None,
+            features: MediaFeatures::default(),
+            cascade: Self::cascade(None, &MediaFeatures::default()),
+        };
+        Self { state: Rc::new(RefCell::new(state)) }
+    }
+
+    fn cascade(stylesheet: Option<&Stylesheet>, features: &MediaFeatures) -> CSSCascadeEngine {
+        let mut cascade = CSSCascadeEngine::new();
+        cascade.set_hide_visited(true);
+        cascade.set_media_features(features.clone());
+        if let Some(stylesheet) = stylesheet {
+            cascade.add_stylesheet(stylesheet.clone());
+        }
+        cascade
+    }
+
+    /// Use the document's stylesheet
+    pub fn set_stylesheet(&self, stylesheet: Stylesheet) {
+        let mut state = self.state.borrow_mut();
+        state.cascade = Self::cascade(Some(&stylesheet), &state.features);
+        state.stylesheet = Some(stylesheet);
+    }
+
+    /// Evaluate media queries against a new environment
+    pub fn set_media_features(&self, features: MediaFeatures) {
+        let mut state = self.state.borrow_mut();
+        state.cascade = Self::cascade(state.stylesheet.as_ref(), &features);
+        state.features = features;
+    }
+
+    /// Lay out the tree containing `node` with the source's stylesheet and
+    /// media features
+    ///
+    /// Used by bindings that need rendering, such as `innerText`.
+    pub fn layout(&self, node: &Rc<dom::Node>) -> LayoutBox {
+        let mut root = Rc::clone(node);
+        loop {
+            let parent = root.parent.borrow().upgrade();
+            match parent {
+                Some(parent) => root = parent,
+                None => break,
+            }
+        }
+        let state = self.state.borrow();
+        let stylesheet = state.stylesheet.clone().unwrap_or_else(|| css_parser::parse_css(""));
+        let mut engine = LayoutEngine::new(stylesheet);
+        engine.set_media_features(state.features.clone());
+        engine.layout_tree(&root)
+    }
+
+    /// Compute the styles of a node as a script sees them
+    pub fn compute(&self, node: &dom::Node) -> ComputedStyles {
+        self.state.borrow().cascade.compute_element_styles(node)
+    }
+}
+
+impl fmt::Debug for StyleSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.borrow();
+        f.debug_struct("StyleSource")
+            .field("stylesheet", &state.stylesheet.as_ref().map(|stylesheet| stylesheet.rules.len()))
+            .field("features", &state.features)
+            .finish()
+    }
+}
+
+/// Get the CSS property names and values of computed styles
+pub fn style_properties(styles: &ComputedStyles) -> Vec<(&'static str, Option<&str>)> {
+    [
+        ("display", &styles.display),
+        ("width", &styles.width),
+        ("height", &styles.height),
+        ("margin-top", &styles.margin_top),
+        ("margin-right", &styles.margin_right),
+        ("margin-bottom", &styles.margin_bottom),
+        ("margin-left", &styles.margin_left),
+        ("padding-top", &styles.padding_top),
+        ("padding-right", &styles.padding_right),
+        ("padding-bottom", &styles.padding_bottom),
+        ("padding-left", &styles.padding_left),
+        ("border-width", &styles.border_width),
+        ("border-style", &styles.border_style),
+        ("border-color", &styles.border_color),
+        ("color", &styles.color),
+        ("background-color", &styles.background_color),
+        ("font-family", &styles.font_family),
+        ("font-size", &styles.font_size),
+        ("font-weight", &styles.font_weight),
+        ("text-align", &styles.text_align),
+        ("line-height", &styles.line_height),
+        ("position", &styles.position),
+        ("top", &styles.top),
+        ("right", &styles.right),
+        ("bottom", &styles.bottom),
+        ("left", &styles.left),
+        ("z-index", &styles.z_index),
+        ("overflow", &styles.overflow),
+        ("visibility", &styles.visibility),
+        ("opacity", &styles.opacity),
+        ("content-visibility", &styles.content_visibility),
+        ("contain-intrinsic-size", &styles.contain_intrinsic_size),
+        ("object-fit", &styles.object_fit),
+        ("object-position", &styles.object_position),
+        ("filter", &styles.filter),
+        ("mix-blend-mode", &styles.mix_blend_mode),
+        ("isolation", &styles.isolation),
+        ("will-change", &styles.will_change),
+        ("direction", &styles.direction),
+        ("unicode-bidi", &styles.unicode_bidi),
+        ("hyphens", &styles.hyphens),
+        ("font-style", &styles.font_style),
+        ("font-variant", &styles.font_variant),
+        ("font-feature-settings", &styles.font_feature_settings),
+        ("font-synthesis", &styles.font_synthesis),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name, value.as_deref()))
+    .collect()
+}
+
+/// Convert a CSS property name to its camelCase attribute name
+fn camel_case(name: &str) -> String {
+    let mut result = String::with_capacity(name.len());
+    let mut upper = false;
+    for c in name.chars() {
+        if c == '-' {
+            upper = true;
+        } else if upper {
+            result.push(c.to_ascii_uppercase());
+            upper = false;
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+/// Captures for `getComputedStyle`
+#[derive(Trace, Finalize)]
+struct ComputedStyleBinding {
+    #[unsafe_ignore_trace]
+    handles: Rc<RefCell<NodeHandleTable>>,
+    #[unsafe_ignore_trace]
+    source: StyleSource,
+}
+
+/// Captures for `getPropertyValue` of one declaration object
+#[derive(Trace, Finalize)]
+struct DeclarationBinding {
+    #[unsafe_ignore_trace]
+    values: Rc<HashMap<&'static str, String>>,
+}
+
+/// Install `getComputedStyle` for the nodes of a handle table
+///
+/// Returns the style source, which the engine updates when the stylesheet
+/// or environment changes.
+pub fn initialize_computed_style_bindings(context: &mut Context, handles: Rc<RefCell<NodeHandleTable>>) -> JsResult<StyleSource> {
+    let source = StyleSource::new();
+    let get_computed_style = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &ComputedStyleBinding, context| {
+            let wrapper = args.first().and_then(JsValue::as_object).cloned().ok_or_else(|| {
+                JsNativeError::typ().with_message("getComputedStyle: argument is not an Element")
+            })?;
+            let node = binding.handles.borrow().resolve_wrapper(&wrapper, context).ok_or_else(|| {
+                JsNativeError::typ().with_message("getComputedStyle: argument is not an Element")
+            })?;
+            let styles = binding.source.compute(&node);
+            Ok(declaration_object(&styles, context).into())
+        },
+        ComputedStyleBinding { handles, source: source.clone() },
+    );
+    context.register_global_builtin_callable(js_string!("getComputedStyle"), 1, get_computed_style)?;
+    Ok(source)
+}
+
+/// Create the object returned by `getComputedStyle()`
+fn declaration_object(styles: &ComputedStyles, context: &mut Context) -> boa_engine::JsObject {
+    let values: HashMap<&'static str, String> = style_properties(styles)
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|value| (name, value.to_string())))
+        .collect();
+    let values = Rc::new(values);
+
+    let get_property_value = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &DeclarationBinding, context| {
+            let name = args.first().cloned().unwrap_or_default().to_string(context)?.to_std_string_escaped();
+            let value = binding.values.get(name.trim().to_ascii_lowercase().as_str()).cloned().unwrap_or_default();
+            Ok(js_string!(value).into())
+        },
+        DeclarationBinding { values: Rc::clone(&values) },
+    );
+
+    let mut initializer = ObjectInitializer::new(context);
+    for (name, value) in style_properties(styles) {
+        let value = value.unwrap_or_default();
+        initializer.property(js_string!(camel_case(name)), js_string!(value), Attribute::READONLY | Attribute::ENUMERABLE);
+    }
+    initializer.property(js_string!("length"), values.len() as u32, Attribute::READONLY);
+    initializer.function(get_property_value, js_string!("getPropertyValue"), 1);
+    initializer.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_camel_case_property_names() {
+        assert_eq!(camel_case("background-color"), "backgroundColor");
+        assert_eq!(camel_case("color"), "color");
+        assert_eq!(camel_case("contain-intrinsic-size"), "containIntrinsicSize");
+    }
+}
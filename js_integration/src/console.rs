@@ -0,0 +1,197 @@
+//! # Console
+//!
+//! This module implements the `console` object and the sink its messages
+//! go to. The engine reports its own problems to the same sink, such as a
+//! script blocked by an integrity check, so the shell shows page and engine
+//! messages in one place.
+//!
+//! ## Design Principles
+//!
+//! 1. **One Sink per Page**: The sink is shared by cloning, so the loader,
+//!    the script engine and the shell all see the same messages.
+//! 2. **Echo and Record**: Messages are printed as they arrive and kept
+//!    until taken, so tests and tools can inspect them.
+//! 3. **Original Call Sites**: Messages logged from scripts with source
+//!    maps carry their call site in the original source.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use boa_engine::{
+    object::ObjectInitializer,
+    js_string, Context, JsResult, JsValue, NativeFunction, Source,
+};
+use boa_gc::{Finalize, Trace};
+use crate::source_maps::ScriptRegistry;
+
+/// Severity of a console message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleLevel {
+    Log,
+    Info,
+    Warn,
+    Error,
+}
+
+impl ConsoleLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConsoleLevel::Log => "log",
+            ConsoleLevel::Info => "info",
+            ConsoleLevel::Warn => "warn",
+            ConsoleLevel::Error => "error",
+        }
+    }
+}
+
+/// A message written to the console
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsoleMessage {
+    pub level: ConsoleLevel,
+    pub text: String,
+    /// URL of the resource the message is about, for engine messages, or
+    /// the original call site of a message from a source mapped script
+    pub source: Option<String>,
+}
+
+impl ConsoleMessage {
+    /// Whether the message reports an insecure request blocked on a secure
+    /// page
+    pub fn is_blocked_mixed_content(&self) -> bool {
+        self.level == ConsoleLevel::Error && self.text.starts_with("Mixed Content:")
+    }
+
+    /// Whether the message reports a request the content blocker blocked
+    pub fn is_blocked_by_client(&self) -> bool {
+        self.level == ConsoleLevel::Error && self.text.starts_with("Blocked by client:")
+    }
+}
+
+impl fmt::Display for ConsoleMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.source {
+            Some(source) => write!(f, "[{}] {} ({})", self.level.as_str(), self.text, source),
+            None => write!(f, "[{}] {}", self.level.as_str(), self.text),
+        }
+    }
+}
+
+/// Where console messages of a page go
+///
+/// Cloning shares the messages.
+#[derive(Debug, Clone, Default)]
+pub struct ConsoleSink {
+    messages: Rc<RefCell<Vec<ConsoleMessage>>>,
+}
+
+impl ConsoleSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a message and print it
+    pub fn report(&self, level: ConsoleLevel, text: impl Into<String>, source: Option<&str>) {
+        let message = ConsoleMessage { level, text: text.into(), source: source.map(str::to_string) };
+        match level {
+            ConsoleLevel::Log | ConsoleLevel::Info => println!("{}", message.text),
+            ConsoleLevel::Warn => println!("⚠️  {}", message),
+            ConsoleLevel::Error => println!("❌ {}", message),
+        }
+        self.messages.borrow_mut().push(message);
+    }
+
+    /// Get the messages recorded so far
+    pub fn messages(&self) -> Vec<ConsoleMessage> {
+        self.messages.borrow().clone()
+    }
+
+    /// Remove and return the messages recorded so far
+    pub fn take(&self) -> Vec<ConsoleMessage> {
+        std::mem::take(&mut *self.messages.borrow_mut())
+    }
+}
+
+/// Captures for the `console` methods
+#[derive(Trace, Finalize)]
+struct ConsoleBinding {
+    #[unsafe_ignore_trace]
+    sink: ConsoleSink,
+    #[unsafe_ignore_trace]
+    level: ConsoleLevel,
+    #[unsafe_ignore_trace]
+    scripts: ScriptRegistry,
+    /// Whether the method is `console.trace`
+    trace: bool,
+}
+
+/// Install a `console` object writing to `sink`
+pub fn initialize_console_bindings(context: &mut Context, sink: ConsoleSink) -> JsResult<()> {
+    initialize_console_bindings_with_scripts(context, sink, ScriptRegistry::new())
+}
+
+/// Install a `console` object writing to `sink`, naming call sites in the
+/// source mapped scripts of `scripts`
+pub fn initialize_console_bindings_with_scripts(context: &mut Context, sink: ConsoleSink, scripts: ScriptRegistry) -> JsResult<()> {
+    let method = |level, trace| {
+        NativeFunction::from_copy_closure_with_captures(
+            |_this, args, binding: &ConsoleBinding, context| {
+                let mut parts = Vec::with_capacity(args.len());
+                for arg in args {
+                    parts.push(arg.to_string(context)?.to_std_string_escaped());
+                }
+                let mut text = parts.join(" ");
+                if binding.trace {
+                    text = if text.is_empty() { "Trace".to_string() } else { format!("Trace: {}", text) };
+                    for frame in binding.scripts.capture(context) {
+                        text.push_str(&format!("\n    {}", frame));
+                    }
+                }
+                let call_site = binding.scripts.call_site(context).map(|frame| frame.location());
+                binding.sink.report(binding.level, text, call_site.as_deref());
+                Ok(JsValue::undefined())
+            },
+            ConsoleBinding { sink: sink.clone(), level, scripts: scripts.clone(), trace },
+        )
+    };
+    let methods = ObjectInitializer::new(context)
+        .function(method(ConsoleLevel::Log, false), js_string!("log"), 1)
+        .function(method(ConsoleLevel::Log, false), js_string!("debug"), 1)
+        .function(method(ConsoleLevel::Info, false), js_string!("info"), 1)
+        .function(method(ConsoleLevel::Warn, false), js_string!("warn"), 1)
+        .function(method(ConsoleLevel::Error, false), js_string!("error"), 1)
+        .function(method(ConsoleLevel::Log, true), js_string!("trace"), 0)
+        .build();
+    // Each method calls its native function through a JavaScript one, which
+    // puts the caller's frame on the stack the native function sees
+    let wrap = context.eval(Source::from_bytes(
+        "(function (methods) { const console = {}; for (const name of Object.getOwnPropertyNames(methods)) { const method = methods[name]; console[name] = function (...args) { return method(...args); }; } return console; })",
+    ))?;
+    let console = match wrap.as_callable() {
+        Some(wrap) => wrap.call(&JsValue::undefined(), &[methods.into()], context)?,
+        None => methods.into(),
+    };
+    context.global_object().set(js_string!("console"), console, false, context)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use boa_engine::Source;
+
+    #[test]
+    fn test_console_messages_reach_sink() {
+        let mut context = Context::default();
+        let sink = ConsoleSink::new();
+        initialize_console_bindings(&mut context, sink.clone()).unwrap();
+        context.eval(Source::from_bytes("console.log('a', 1); console.warn('careful'); console.error('broken')")).unwrap();
+        sink.report(ConsoleLevel::Error, "blocked", Some("https://cdn.example.com/lib.js"));
+
+        let messages = sink.take();
+        let levels: Vec<_> = messages.iter().map(|message| message.level).collect();
+        assert_eq!(levels, vec![ConsoleLevel::Log, ConsoleLevel::Warn, ConsoleLevel::Error, ConsoleLevel::Error]);
+        assert_eq!(messages[0].text, "a 1");
+        assert_eq!(messages[3].to_string(), "[error] blocked (https://cdn.example.com/lib.js)");
+        assert!(sink.messages().is_empty());
+    }
+}
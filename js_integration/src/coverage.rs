@@ -0,0 +1,453 @@
+//! # Script Coverage
+//!
+//! This module records which functions of a page's scripts ran, for
+//! coverage reports of the bytes each script used.
+//!
+//! Boa has no hook that fires when a function is entered, so while coverage
+//! is on, scripts are instrumented before they run: a call to a counting
+//! native function is added at the start of every function body. Function
+//! declarations and expressions, block-bodied arrow functions, and class and
+//! object methods are found by a scanner that skips strings, template
+//! literals, comments and regular expressions.
+//!
+//! ## Design Principles
+//!
+//! 1. **Opt In**: Scripts run unchanged unless coverage was started, so
+//!    error positions and timings are unaffected in normal browsing.
+//! 2. **Function Granularity**: A function that ran counts as used as a
+//!    whole; a function that never ran is an unused byte range. Top level
+//!    code always runs and counts as used.
+//! 3. **Ranges in the Original Text**: Reports give byte ranges of the
+//!    script as fetched, never of the instrumented copy.
+
+use std::cell::RefCell;
+use std::ops::Range;
+use std::rc::Rc;
+use boa_engine::{js_string, Context, JsResult, JsValue, NativeFunction};
+use boa_gc::{Finalize, Trace};
+
+/// Global function instrumented scripts call on entering a function
+pub const COVERAGE_HOOK: &str = "__dubbyCoverage";
+
+/// Keywords that take a parenthesized head and then a block, unlike a
+/// method definition
+const CONTROL_KEYWORDS: &[&str] = &["if", "for", "while", "switch", "catch", "with", "function"];
+
+/// Keywords after which a `/` starts a regular expression
+const EXPRESSION_KEYWORDS: &[&str] = &[
+    "return", "typeof", "instanceof", "in", "of", "new", "delete", "void", "throw", "case", "do", "else", "yield", "await",
+];
+
+/// How often a function ran
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionCoverage {
+    /// Function name; `None` for anonymous functions
+    pub name: Option<String>,
+    /// Byte range of the function in the script
+    pub range: Range<usize>,
+    pub count: u32,
+}
+
+/// The functions of one script and how often they ran
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptCoverageReport {
+    pub url: String,
+    /// Length of the script in bytes
+    pub length: usize,
+    pub functions: Vec<FunctionCoverage>,
+}
+
+impl ScriptCoverageReport {
+    /// Byte ranges of the functions that never ran, merged and in order
+    pub fn unused_ranges(&self) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = self.functions.iter().filter(|function| function.count == 0).map(|function| function.range.clone()).collect();
+        ranges.sort_by_key(|range| range.start);
+        let mut merged: Vec<Range<usize>> = Vec::new();
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        merged
+    }
+
+    pub fn unused_bytes(&self) -> usize {
+        self.unused_ranges().iter().map(|range| range.len()).sum()
+    }
+
+    pub fn used_bytes(&self) -> usize {
+        self.length - self.unused_bytes()
+    }
+}
+
+#[derive(Debug, Default)]
+struct CoverageState {
+    enabled: bool,
+    scripts: Vec<ScriptCoverageReport>,
+    inline_scripts: usize,
+}
+
+/// Coverage of the scripts of a page
+///
+/// Cloning shares the recorded coverage.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptCoverage {
+    state: Rc<RefCell<CoverageState>>,
+}
+
+impl ScriptCoverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Instrument the scripts that run from now on
+    pub fn start(&self) {
+        self.state.borrow_mut().enabled = true;
+    }
+
+    /// Run later scripts unchanged; counts of instrumented ones go on
+    pub fn stop(&self) {
+        self.state.borrow_mut().enabled = false;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.state.borrow().enabled
+    }
+
+    /// Prepare a script to run, instrumenting it if coverage is on
+    ///
+    /// Inline scripts pass no URL and are named by their order.
+    pub fn instrument(&self, url: Option<&str>, source: &str) -> String {
+        let mut state = self.state.borrow_mut();
+        if !state.enabled {
+            return source.to_string();
+        }
+        let url = match url {
+            Some(url) => url.to_string(),
+            None => {
+                state.inline_scripts += 1;
+                format!("inline script #{}", state.inline_scripts)
+            }
+        };
+        let script = state.scripts.len();
+        let functions = find_functions(source);
+
+        let mut bodies: Vec<(usize, usize)> = functions.iter().enumerate().map(|(index, function)| (function.body, index)).collect();
+        bodies.sort();
+        let mut instrumented = String::with_capacity(source.len() + bodies.len() * 32);
+        let mut copied = 0;
+        for (body, index) in bodies {
+            instrumented.push_str(&source[copied..body]);
+            instrumented.push_str(&format!("{}({},{});", COVERAGE_HOOK, script, index));
+            copied = body;
+        }
+        instrumented.push_str(&source[copied..]);
+
+        state.scripts.push(ScriptCoverageReport {
+            url,
+            length: source.len(),
+            functions: functions.into_iter().map(|function| FunctionCoverage { name: function.name, range: function.range, count: 0 }).collect(),
+        });
+        instrumented
+    }
+
+    /// Count a call of a function of an instrumented script
+    pub fn hit(&self, script: usize, function: usize) {
+        if let Some(function) = self.state.borrow_mut().scripts.get_mut(script).and_then(|script| script.functions.get_mut(function)) {
+            function.count += 1;
+        }
+    }
+
+    /// The coverage recorded so far
+    pub fn report(&self) -> Vec<ScriptCoverageReport> {
+        self.state.borrow().scripts.clone()
+    }
+}
+
+/// Captures of the coverage hook
+#[derive(Trace, Finalize)]
+struct CoverageBinding {
+    #[unsafe_ignore_trace]
+    coverage: ScriptCoverage,
+}
+
+/// Install the function instrumented scripts report calls to
+pub fn initialize_coverage_bindings(context: &mut Context, coverage: ScriptCoverage) -> JsResult<()> {
+    let hook = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &CoverageBinding, context| {
+            let script = args.first().cloned().unwrap_or_default().to_u32(context)?;
+            let function = args.get(1).cloned().unwrap_or_default().to_u32(context)?;
+            binding.coverage.hit(script as usize, function as usize);
+            Ok(JsValue::undefined())
+        },
+        CoverageBinding { coverage },
+    );
+    context.register_global_builtin_callable(js_string!(COVERAGE_HOOK), 2, hook)?;
+    Ok(())
+}
+
+/// A function found in a script
+#[derive(Debug, Clone, PartialEq)]
+struct FoundFunction {
+    name: Option<String>,
+    range: Range<usize>,
+    /// Byte offset just inside the body's `{`
+    body: usize,
+}
+
+/// What an open bracket belongs to
+enum Open {
+    Block,
+    /// A parenthesized list, with the identifier before it and the function
+    /// whose parameters it holds
+    Paren { before: Option<(String, usize)>, parameters_of: Option<usize> },
+    Body(usize),
+    TemplateExpression,
+}
+
+/// Find the functions with block bodies in a script
+fn find_functions(source: &str) -> Vec<FoundFunction> {
+    let bytes = source.as_bytes();
+    let mut functions: Vec<(Option<String>, usize, usize, Option<usize>)> = Vec::new();
+    let mut stack: Vec<Open> = Vec::new();
+    // A `function` keyword waiting for its parameters
+    let mut awaiting_parameters: Option<usize> = None;
+    // What a `{` right here would be the body of
+    let mut body_of: Option<usize> = None;
+    let mut arrow: Option<usize> = None;
+    let mut method: Option<(String, usize)> = None;
+    let mut previous_identifier: Option<(String, usize)> = None;
+    let mut regex_allowed = true;
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let byte = bytes[index];
+        if byte.is_ascii_whitespace() {
+            index += 1;
+            continue;
+        }
+        if byte == b'/' && bytes.get(index + 1) == Some(&b'/') {
+            index = source[index..].find('\n').map_or(bytes.len(), |end| index + end);
+            continue;
+        }
+        if byte == b'/' && bytes.get(index + 1) == Some(&b'*') {
+            index = source[index + 2..].find("*/").map_or(bytes.len(), |end| index + 2 + end + 2);
+            continue;
+        }
+        if byte != b'{' {
+            body_of = None;
+            arrow = None;
+            method = None;
+        }
+
+        match byte {
+            b'"' | b'\'' => {
+                index = skip_string(bytes, index);
+                regex_allowed = false;
+                previous_identifier = None;
+            }
+            b'`' => {
+                index = skip_template(bytes, index + 1, &mut stack);
+                regex_allowed = false;
+                previous_identifier = None;
+            }
+            b'0'..=b'9' => {
+                while index < bytes.len() && (bytes[index].is_ascii_alphanumeric() || bytes[index] == b'.' || bytes[index] == b'_') {
+                    index += 1;
+                }
+                regex_allowed = false;
+                previous_identifier = None;
+            }
+            byte if byte.is_ascii_alphabetic() || byte == b'_' || byte == b'$' || byte >= 0x80 => {
+                let start = index;
+                while index < bytes.len() && (bytes[index].is_ascii_alphanumeric() || bytes[index] == b'_' || bytes[index] == b'$' || bytes[index] >= 0x80) {
+                    index += 1;
+                }
+                let identifier = &source[start..index];
+                if identifier == "function" {
+                    functions.push((None, start, 0, None));
+                    awaiting_parameters = Some(functions.len() - 1);
+                } else if let Some(function) = awaiting_parameters {
+                    functions[function].0.get_or_insert_with(|| identifier.to_string());
+                }
+                regex_allowed = EXPRESSION_KEYWORDS.contains(&identifier);
+                previous_identifier = Some((identifier.to_string(), start));
+            }
+            b'(' => {
+                stack.push(Open::Paren { before: previous_identifier.take(), parameters_of: awaiting_parameters.take() });
+                regex_allowed = true;
+                index += 1;
+            }
+            b')' => {
+                if let Some(Open::Paren { before, parameters_of }) = stack.pop() {
+                    match (parameters_of, before) {
+                        (Some(function), _) => body_of = Some(function),
+                        (None, Some((name, start))) if !CONTROL_KEYWORDS.contains(&name.as_str()) => method = Some((name, start)),
+                        _ => {}
+                    }
+                }
+                regex_allowed = false;
+                previous_identifier = None;
+                index += 1;
+            }
+            b'{' => {
+                let opened = if let Some(function) = body_of.take() {
+                    Some(function)
+                } else if let Some(start) = arrow.take() {
+                    functions.push((None, start, 0, None));
+                    Some(functions.len() - 1)
+                } else if let Some((name, start)) = method.take() {
+                    functions.push((Some(name), start, 0, None));
+                    Some(functions.len() - 1)
+                } else {
+                    None
+                };
+                match opened {
+                    Some(function) => {
+                        functions[function].2 = index + 1;
+                        stack.push(Open::Body(function));
+                    }
+                    None => stack.push(Open::Block),
+                }
+                regex_allowed = true;
+                previous_identifier = None;
+                index += 1;
+            }
+            b'}' => {
+                index += 1;
+                match stack.pop() {
+                    Some(Open::Body(function)) => functions[function].3 = Some(index),
+                    Some(Open::TemplateExpression) => index = skip_template(bytes, index, &mut stack),
+                    _ => {}
+                }
+                regex_allowed = true;
+                previous_identifier = None;
+            }
+            b'=' if bytes.get(index + 1) == Some(&b'>') => {
+                arrow = Some(index);
+                regex_allowed = true;
+                previous_identifier = None;
+                index += 2;
+            }
+            b'/' if regex_allowed => {
+                index = skip_regex(bytes, index);
+                regex_allowed = false;
+                previous_identifier = None;
+            }
+            b']' => {
+                regex_allowed = false;
+                previous_identifier = None;
+                index += 1;
+            }
+            _ => {
+                regex_allowed = true;
+                previous_identifier = None;
+                index += 1;
+            }
+        }
+    }
+
+    functions
+        .into_iter()
+        .filter_map(|(name, start, body, end)| Some(FoundFunction { name, range: start..end?, body }))
+        .collect()
+}
+
+/// Skip a quoted string starting at `start`
+fn skip_string(bytes: &[u8], start: usize) -> usize {
+    let quote = bytes[start];
+    let mut index = start + 1;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'\\' => index += 2,
+            byte if byte == quote || byte == b'\n' => return index + 1,
+            _ => index += 1,
+        }
+    }
+    bytes.len()
+}
+
+/// Skip template literal text from `index` to the closing backtick, or into
+/// a `${` expression, which is pushed on the stack
+fn skip_template(bytes: &[u8], mut index: usize, stack: &mut Vec<Open>) -> usize {
+    while index < bytes.len() {
+        match bytes[index] {
+            b'\\' => index += 2,
+            b'`' => return index + 1,
+            b'$' if bytes.get(index + 1) == Some(&b'{') => {
+                stack.push(Open::TemplateExpression);
+                return index + 2;
+            }
+            _ => index += 1,
+        }
+    }
+    bytes.len()
+}
+
+/// Skip a regular expression literal and its flags
+fn skip_regex(bytes: &[u8], start: usize) -> usize {
+    let mut index = start + 1;
+    let mut in_class = false;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'\\' => index += 1,
+            b'[' => in_class = true,
+            b']' => in_class = false,
+            b'/' if !in_class => break,
+            // Not a regular expression after all; treat it as division
+            b'\n' => return start + 1,
+            _ => {}
+        }
+        index += 1;
+    }
+    index += 1;
+    while index < bytes.len() && bytes[index].is_ascii_alphabetic() {
+        index += 1;
+    }
+    index.min(bytes.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use boa_engine::Source;
+
+    #[test]
+    fn test_instrumented_functions_count_calls() {
+        let script = r#"// a } comment
+function used(a) { return `${a}{` + "}" + /[}]/.source; }
+function unused() { var nested = function () {}; }
+const arrow = (x) => { return x * 2; };
+const concise = x => x;
+class Point { constructor() {} unusedMethod() { if (true) {} } }
+used(1); arrow(2); new Point();
+"#;
+        let names: Vec<Option<String>> = find_functions(script).into_iter().map(|function| function.name).collect();
+        assert_eq!(names, vec![
+            Some("used".to_string()),
+            Some("unused".to_string()),
+            None,
+            None,
+            Some("constructor".to_string()),
+            Some("unusedMethod".to_string()),
+        ]);
+
+        let coverage = ScriptCoverage::new();
+        assert_eq!(coverage.instrument(None, script), script);
+        coverage.start();
+        let mut context = Context::default();
+        initialize_coverage_bindings(&mut context, coverage.clone()).unwrap();
+        context.eval(Source::from_bytes(&coverage.instrument(Some("https://example.com/app.js"), script))).unwrap();
+        context.eval(Source::from_bytes(&coverage.instrument(None, "function later() {} later(); later();"))).unwrap();
+
+        let report = coverage.report();
+        let counts: Vec<u32> = report[0].functions.iter().map(|function| function.count).collect();
+        assert_eq!(counts, vec![1, 0, 0, 1, 1, 0]);
+        let unused: Vec<&str> = report[0].unused_ranges().into_iter().map(|range| &script[range]).collect();
+        assert_eq!(unused, vec!["function unused() { var nested = function () {}; }", "unusedMethod() { if (true) {} }"]);
+        assert_eq!(report[0].used_bytes(), script.len() - unused.iter().map(|text| text.len()).sum::<usize>());
+        assert_eq!(report[1].url, "inline script #1");
+        assert_eq!(report[1].functions[0].count, 2);
+    }
+}
@@ -0,0 +1,123 @@
+//! # Deterministic Scripts
+//!
+//! This module removes the sources of nondeterminism scripts can observe
+//! besides timers: `Date` reads the engine clock through `ClockHooks`, and
+//! `Math.random()` can be replaced by a seeded generator. Together with a
+//! `dom::clock::ManualClock` a page's scripts produce the same results on
+//! every run, which JS tests and reftests rely on.
+//!
+//! ## Design Principles
+//!
+//! 1. **One Clock**: `Date.now()` and `new Date()` read `dom::clock`, the
+//!    clock timers and `performance.now()` use, so a test advancing the
+//!    clock moves them all together.
+//! 2. **UTC Under a Manual Clock**: The local timezone is UTC while a
+//!    manual clock is installed, so dates format the same on every machine.
+//! 3. **Seeded, Not Secure**: The seeded `Math.random()` is xorshift64*,
+//!    fast and reproducible; nothing security-sensitive may rely on it.
+
+use std::cell::Cell;
+use std::rc::Rc;
+use boa_engine::{
+    context::{DefaultHooks, HostHooks},
+    js_string, Context, JsResult, JsValue, NativeFunction,
+};
+use boa_gc::{Finalize, Trace};
+
+/// Host hooks reading the time from the engine clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClockHooks;
+
+/// The hooks every `JsEngine` context is built with
+pub static CLOCK_HOOKS: ClockHooks = ClockHooks;
+
+impl HostHooks for ClockHooks {
+    fn utc_now(&self) -> i64 {
+        dom::clock::unix_time().as_millis() as i64
+    }
+
+    fn local_timezone_offset_seconds(&self, unix_time_seconds: i64) -> i32 {
+        if dom::clock::uses_system_clock() {
+            DefaultHooks.local_timezone_offset_seconds(unix_time_seconds)
+        } else {
+            0
+        }
+    }
+}
+
+/// A xorshift64* generator
+#[derive(Debug, Clone)]
+pub struct SeededRandom {
+    state: Rc<Cell<u64>>,
+}
+
+impl SeededRandom {
+    pub fn new(seed: u64) -> Self {
+        // Zero is a fixed point of xorshift
+        SeededRandom { state: Rc::new(Cell::new(seed.max(1))) }
+    }
+
+    /// Get the next number in `[0, 1)`
+    pub fn next_f64(&self) -> f64 {
+        let mut x = self.state.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state.set(x);
+        // The top 53 bits fill the mantissa
+        (x.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Captures for the seeded `Math.random`
+#[derive(Clone, Trace, Finalize)]
+struct RandomBinding {
+    #[unsafe_ignore_trace]
+    random: SeededRandom,
+}
+
+/// Replace `Math.random()` with a generator seeded with `seed`
+pub fn install_seeded_random(context: &mut Context, seed: u64) -> JsResult<()> {
+    let function = NativeFunction::from_copy_closure_with_captures(
+        |_this, _args, binding: &RandomBinding, _context| Ok(JsValue::new(binding.random.next_f64())),
+        RandomBinding { random: SeededRandom::new(seed) },
+    )
+    .to_js_function(context.realm());
+    let math = context.global_object().get(js_string!("Math"), context)?;
+    if let Some(math) = math.as_object() {
+        math.set(js_string!("random"), function, true, context)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use boa_engine::Source;
+    use dom::clock::{self, ManualClock, MANUAL_CLOCK_EPOCH};
+    use std::time::Duration;
+
+    #[test]
+    fn test_dates_and_random_numbers_repeat() {
+        let draw = |seed| {
+            let mut context = Context::builder().host_hooks(&CLOCK_HOOKS).build().unwrap();
+            install_seeded_random(&mut context, seed).unwrap();
+            let values = context.eval(Source::from_bytes("[Math.random(), Math.random(), Math.random()].join()")).unwrap();
+            values.to_string(&mut context).unwrap().to_std_string_escaped()
+        };
+        assert_eq!(draw(7), draw(7));
+        assert_ne!(draw(7), draw(8));
+        let random = SeededRandom::new(0);
+        assert!((0..1000).map(|_| random.next_f64()).all(|value| (0.0..1.0).contains(&value)));
+
+        let clock = ManualClock::new();
+        clock::set_clock(std::sync::Arc::new(clock.clone()));
+        clock.advance(Duration::from_millis(1500));
+        let mut context = Context::builder().host_hooks(&CLOCK_HOOKS).build().unwrap();
+        let now = context.eval(Source::from_bytes("Date.now()")).unwrap().to_number(&mut context).unwrap();
+        assert_eq!(now, (MANUAL_CLOCK_EPOCH + Duration::from_millis(1500)).as_millis() as f64);
+        let hours = context.eval(Source::from_bytes("new Date().getHours()")).unwrap().to_number(&mut context).unwrap();
+        assert_eq!(hours, 0.0);
+        clock::reset_clock();
+    }
+}
@@ -0,0 +1,925 @@
+//! # Element Wrappers
+//!
+//! This module creates the JavaScript wrappers of DOM nodes with all their
+//! members, and the document accessors that hand out such wrappers:
+//! `document.documentElement`, `document.head`, `document.body` and
+//! `document.fullscreenElement`. Every element wrapper gets `matches()`,
+//! `closest()`, `innerText`, the pointer capture methods
+//! `setPointerCapture()`, `releasePointerCapture()` and
+//! `hasPointerCapture()`, the popover members `popover`, `showPopover()`,
+//! `hidePopover()` and `togglePopover()`, `requestFullscreen()`, and the
+//! collection members `children`, `getElementsByTagName()`,
+//! `getElementsByClassName()`, `querySelector()` and `querySelectorAll()`,
+//! which the document gets as well, with `createTreeWalker()`,
+//! `createEvent()`, `adoptNode()`, `importNode()` and `exitFullscreen()`.
+//! Every node wrapper gets `textContent`, `normalize()`, `baseURI` and the
+//! event target methods, see `event_targets`, which the document gets as
+//! well. Dialog wrappers also get `open`, `returnValue`, `show()`,
+//! `showModal()` and `close()`.
+//!
+//! ## Design Principles
+//!
+//! 1. **One Factory**: `ElementWrappers` is the single place wrappers are
+//!    made, so an element reached through `closest()` or `document.body`
+//!    has the same members as one wrapped by the engine.
+//! 2. **Live Accessors**: Document accessors are getters that ask the
+//!    `Document` on every read, so they follow scripts moving elements.
+//! 3. **DOM Errors**: Invalid selectors throw a `SyntaxError` DOMException,
+//!    as in browsers.
+//! 4. **Rendered Text on Demand**: `innerText` lays out the element's tree
+//!    when it is read, so it reflects the styles at that moment.
+//! 5. **Removed Nodes Stay Reachable**: Nodes that text setters and
+//!    `normalize()` remove are pinned in the handle table, so wrappers a
+//!    script still holds keep working.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use boa_engine::{
+    object::ObjectInitializer,
+    object::builtins::JsPromise,
+    property::{Attribute, PropertyDescriptor},
+    js_string, Context, JsError, JsNativeError, JsObject, JsResult, JsValue, NativeFunction,
+};
+use boa_gc::{Finalize, Trace};
+use dom::dialog::DialogError;
+use dom::event_types::EventInterface;
+use dom::fullscreen::{self, FullscreenError};
+use dom::popover::{PopoverError, PopoverToggle, PopoverType};
+use dom::page_visibility::{self, VisibilityState};
+use dom::traversal::{self, TreeWalker};
+use dom::{Document, Node, NodeType, PointerCaptureError, PointerCaptures};
+use layout::inner_text;
+use crate::animations::{self, ScriptAnimations};
+use crate::collections;
+use crate::computed_style::StyleSource;
+use crate::event_objects::{self, EventPrototypes};
+use crate::event_targets::{BoundTarget, EventListeners, EventTargetBinding};
+use crate::images;
+use crate::media;
+use crate::node_handles::NodeHandleTable;
+use crate::webgl::{self, WebGlContexts};
+
+/// DOMException code of `SyntaxError`
+const SYNTAX_ERR: u16 = 12;
+/// DOMException code of `WrongDocumentError`
+const WRONG_DOCUMENT_ERR: u16 = 4;
+/// DOMException code of `NotSupportedError`
+const NOT_SUPPORTED_ERR: u16 = 9;
+/// DOMException code of `NotFoundError`
+const NOT_FOUND_ERR: u16 = 8;
+/// DOMException code of `InvalidStateError`
+const INVALID_STATE_ERR: u16 = 11;
+
+/// Everything the members of element wrappers share
+#[derive(Clone, Trace, Finalize)]
+pub struct ElementWrappers {
+    #[unsafe_ignore_trace]
+    pub handles: Rc<RefCell<NodeHandleTable>>,
+    #[unsafe_ignore_trace]
+    pub media_controller: Rc<RefCell<dom::media::MediaController>>,
+    #[unsafe_ignore_trace]
+    pub image_decodes: images::ImageDecodes,
+    #[unsafe_ignore_trace]
+    pub webgl_contexts: WebGlContexts,
+    #[unsafe_ignore_trace]
+    pub animations: ScriptAnimations,
+    /// Creates the nodes text setters insert
+    #[unsafe_ignore_trace]
+    pub document: Option<Rc<Document>>,
+    /// Styles `innerText` lays out with
+    #[unsafe_ignore_trace]
+    pub style_source: StyleSource,
+    /// Prototypes `document.createEvent()` makes events with
+    pub events: EventPrototypes,
+    #[unsafe_ignore_trace]
+    pub listeners: EventListeners,
+    /// Shared with the event manager, which retargets captured pointers
+    #[unsafe_ignore_trace]
+    pub pointer_captures: PointerCaptures,
+}
+
+impl ElementWrappers {
+    /// Create the wrapper of a node with the members of its kind
+    ///
+    /// Wrappers of `<video>` and `<audio>` get the media element members,
+    /// see `media`, wrappers of `<img>` get the loading members, see
+    /// `images`, wrappers of `<canvas>` get `getContext`, see `webgl`,
+    /// wrappers of `<dialog>` get the dialog members,
+    /// every element gets `animate()`, `matches()`, `closest()`,
+    /// `innerText`, the pointer capture methods, the popover members,
+    /// `requestFullscreen()` and the collection members, and every node gets
+    /// `textContent`, `normalize()` and `baseURI`.
+    pub fn wrap(&self, node: &Rc<Node>, context: &mut Context) -> JsObject {
+        let wrapper = self.handles.borrow_mut().wrap_node(node, context);
+        let installed = if node.media_kind().is_some() {
+            media::install_media_members(&wrapper, Rc::clone(&self.handles), Rc::clone(&self.media_controller), context)
+        } else if node.is_image() {
+            images::install_image_members(&wrapper, Rc::clone(&self.handles), self.image_decodes.clone(), context)
+        } else if node.tag_name().is_some_and(|tag| tag.eq_ignore_ascii_case("canvas")) {
+            webgl::install_canvas_members(&wrapper, Rc::clone(&self.handles), Rc::clone(&self.webgl_contexts), context)
+        } else if node.is_dialog() {
+            self.install_dialog_members(&wrapper, context)
+        } else {
+            Ok(())
+        };
+        let installed = installed.and_then(|()| match node.node_type {
+            NodeType::Element { .. } => animations::install_animate(&wrapper, Rc::clone(&self.handles), self.animations.clone(), context)
+                .and_then(|()| self.install_selector_members(&wrapper, context))
+                .and_then(|()| self.install_pointer_capture_members(&wrapper, context))
+                .and_then(|()| self.install_popover_members(&wrapper, context))
+                .and_then(|()| self.install_fullscreen_members(&wrapper, context))
+                .and_then(|()| self.install_collection_members(&wrapper, context))
+                .and_then(|()| self.install_inner_text(&wrapper, context)),
+            _ => Ok(()),
+        });
+        let installed = installed
+            .and_then(|()| self.install_text_members(&wrapper, context))
+            .and_then(|()| self.install_base_uri(&wrapper, context))
+            .and_then(|()| EventTargetBinding { target: BoundTarget::Wrapper, wrappers: self.clone() }.install(&wrapper, context));
+        if let Err(e) = installed {
+            println!("❌ Failed to install element members: {}", e);
+        }
+        wrapper
+    }
+
+    /// Wrap a node, or return `null` for none
+    fn wrap_or_null(&self, node: Option<Rc<Node>>, context: &mut Context) -> JsValue {
+        match node {
+            Some(node) => self.wrap(&node, context).into(),
+            None => JsValue::null(),
+        }
+    }
+
+    /// Resolve the element a method was called on
+    fn this_element(&self, this: &JsValue, context: &mut Context) -> JsResult<Rc<Node>> {
+        self.this_node(this, context)
+            .ok()
+            .filter(|node| node.tag_name().is_some())
+            .ok_or_else(|| JsNativeError::typ().with_message("Illegal invocation: not an element").into())
+    }
+
+    /// Resolve the node a method was called on
+    pub(crate) fn this_node(&self, this: &JsValue, context: &mut Context) -> JsResult<Rc<Node>> {
+        this.as_object()
+            .and_then(|wrapper| self.handles.borrow().resolve_wrapper(wrapper, context))
+            .ok_or_else(|| JsNativeError::typ().with_message("Illegal invocation: not a node").into())
+    }
+
+    /// Keep the wrappers of nodes no tree holds, and their descendants,
+    /// valid
+    fn pin_removed(&self, removed: &[Rc<Node>]) {
+        let mut handles = self.handles.borrow_mut();
+        for node in removed {
+            handles.pin_subtree(node);
+        }
+    }
+
+    /// Replace the children of a node with text using one of the setters
+    fn replace_text(
+        &self,
+        this: &JsValue,
+        args: &[JsValue],
+        set: fn(&Rc<Node>, &str, &Document) -> Vec<Rc<Node>>,
+        context: &mut Context,
+    ) -> JsResult<JsValue> {
+        let node = self.this_node(this, context)?;
+        // `null` clears the node, like the empty string
+        let text = match args.first() {
+            None => String::new(),
+            Some(value) if value.is_null() => String::new(),
+            Some(value) => value.to_string(context)?.to_std_string_escaped(),
+        };
+        // Text nodes only change their data, which needs no document
+        if node.character_data().is_some() {
+            node.set_character_data(&text);
+            return Ok(JsValue::undefined());
+        }
+        let document = self
+            .document
+            .as_ref()
+            .ok_or_else(|| JsNativeError::error().with_message("No document to create text nodes in"))?;
+        // The removed children are pinned by the engine's removal observer
+        set(&node, &text, document);
+        Ok(JsValue::undefined())
+    }
+
+    /// Add `textContent` and `normalize()` to a node wrapper
+    fn install_text_members(&self, wrapper: &JsObject, context: &mut Context) -> JsResult<()> {
+        let getter = NativeFunction::from_copy_closure_with_captures(
+            |this, _args, wrappers: &ElementWrappers, context| {
+                let node = wrappers.this_node(this, context)?;
+                Ok(js_string!(node.text_content()).into())
+            },
+            self.clone(),
+        );
+        let setter = NativeFunction::from_copy_closure_with_captures(
+            |this, args, wrappers: &ElementWrappers, context| wrappers.replace_text(this, args, Node::set_text_content, context),
+            self.clone(),
+        );
+        define_accessor(wrapper, "textContent", getter, setter, context)?;
+
+        let normalize = NativeFunction::from_copy_closure_with_captures(
+            |this, _args, wrappers: &ElementWrappers, context| {
+                let node = wrappers.this_node(this, context)?;
+                node.normalize();
+                Ok(JsValue::undefined())
+            },
+            self.clone(),
+        );
+        define_method(wrapper, "normalize", normalize, context)
+    }
+
+    /// Add `baseURI` to a node wrapper
+    ///
+    /// Nodes report the base URL of the document, `about:blank` when it
+    /// has none.
+    fn install_base_uri(&self, wrapper: &JsObject, context: &mut Context) -> JsResult<()> {
+        let getter = NativeFunction::from_copy_closure_with_captures(
+            |_this, _args, wrappers: &ElementWrappers, _context| {
+                let base_url = wrappers.document.as_deref().and_then(networking::document_base_url);
+                let base_url = base_url.map_or_else(|| "about:blank".to_string(), |url| url.to_string());
+                Ok(js_string!(base_url).into())
+            },
+            self.clone(),
+        );
+        wrapper.define_property_or_throw(
+            js_string!("baseURI"),
+            PropertyDescriptor::builder()
+                .get(getter.to_js_function(context.realm()))
+                .enumerable(true)
+                .configurable(true),
+            context,
+        )?;
+        Ok(())
+    }
+
+    /// Add `innerText` to an element wrapper
+    fn install_inner_text(&self, wrapper: &JsObject, context: &mut Context) -> JsResult<()> {
+        let getter = NativeFunction::from_copy_closure_with_captures(
+            |this, _args, wrappers: &ElementWrappers, context| {
+                let element = wrappers.this_element(this, context)?;
+                let layout = wrappers.style_source.layout(&element);
+                let text = inner_text::inner_text(&layout, &element).unwrap_or_else(|| element.text_content());
+                Ok(js_string!(text).into())
+            },
+            self.clone(),
+        );
+        let setter = NativeFunction::from_copy_closure_with_captures(
+            |this, args, wrappers: &ElementWrappers, context| {
+                wrappers.this_element(this, context)?;
+                wrappers.replace_text(this, args, Node::set_inner_text, context)
+            },
+            self.clone(),
+        );
+        define_accessor(wrapper, "innerText", getter, setter, context)
+    }
+
+    /// Add `matches()` and `closest()` to an element wrapper
+    fn install_selector_members(&self, wrapper: &JsObject, context: &mut Context) -> JsResult<()> {
+        let matches = NativeFunction::from_copy_closure_with_captures(
+            |this, args, wrappers: &ElementWrappers, context| {
+                let element = wrappers.this_element(this, context)?;
+                let selectors = string_argument(args, context)?;
+                element.matches(&selectors).map(JsValue::from).map_err(|error| syntax_error(context, &error.to_string()))
+            },
+            self.clone(),
+        );
+        let closest = NativeFunction::from_copy_closure_with_captures(
+            |this, args, wrappers: &ElementWrappers, context| {
+                let element = wrappers.this_element(this, context)?;
+                let selectors = string_argument(args, context)?;
+                let found = element.closest(&selectors).map_err(|error| syntax_error(context, &error.to_string()))?;
+                Ok(wrappers.wrap_or_null(found, context))
+            },
+            self.clone(),
+        );
+        for (name, function) in [("matches", matches), ("closest", closest)] {
+            define_method(wrapper, name, function, context)?;
+        }
+        Ok(())
+    }
+
+    /// Add `setPointerCapture()`, `releasePointerCapture()` and
+    /// `hasPointerCapture()` to an element wrapper
+    fn install_pointer_capture_members(&self, wrapper: &JsObject, context: &mut Context) -> JsResult<()> {
+        let set = NativeFunction::from_copy_closure_with_captures(
+            |this, args, wrappers: &ElementWrappers, context| {
+                let element = wrappers.this_element(this, context)?;
+                let pointer_id = pointer_id_argument("setPointerCapture", args, context)?;
+                wrappers.pointer_captures.set_capture(&element, pointer_id).map_err(|error| pointer_capture_error(context, &error))?;
+                Ok(JsValue::undefined())
+            },
+            self.clone(),
+        );
+        let release = NativeFunction::from_copy_closure_with_captures(
+            |this, args, wrappers: &ElementWrappers, context| {
+                let element = wrappers.this_element(this, context)?;
+                let pointer_id = pointer_id_argument("releasePointerCapture", args, context)?;
+                wrappers.pointer_captures.release_capture(&element, pointer_id).map_err(|error| pointer_capture_error(context, &error))?;
+                Ok(JsValue::undefined())
+            },
+            self.clone(),
+        );
+        let has = NativeFunction::from_copy_closure_with_captures(
+            |this, args, wrappers: &ElementWrappers, context| {
+                let element = wrappers.this_element(this, context)?;
+                let pointer_id = pointer_id_argument("hasPointerCapture", args, context)?;
+                Ok(wrappers.pointer_captures.has_capture(&element, pointer_id).into())
+            },
+            self.clone(),
+        );
+        for (name, function) in [("setPointerCapture", set), ("releasePointerCapture", release), ("hasPointerCapture", has)] {
+            define_method(wrapper, name, function, context)?;
+        }
+        Ok(())
+    }
+
+    /// Add `popover`, `showPopover()`, `hidePopover()` and
+    /// `togglePopover()` to an element wrapper
+    ///
+    /// `toggle` is fired right away at every popover the call opened or
+    /// closed, with `oldState` and `newState` saying how it changed.
+    fn install_popover_members(&self, wrapper: &JsObject, context: &mut Context) -> JsResult<()> {
+        let get_popover = NativeFunction::from_copy_closure_with_captures(
+            |this, _args, wrappers: &ElementWrappers, context| {
+                Ok(match wrappers.this_element(this, context)?.popover_type() {
+                    Some(PopoverType::Auto) => js_string!("auto").into(),
+                    Some(PopoverType::Manual) => js_string!("manual").into(),
+                    None => JsValue::null(),
+                })
+            },
+            self.clone(),
+        );
+        let set_popover = NativeFunction::from_copy_closure_with_captures(
+            |this, args, wrappers: &ElementWrappers, context| {
+                let element = wrappers.this_element(this, context)?;
+                match args.first() {
+                    Some(value) if !value.is_null_or_undefined() => element.set_attribute("popover", &value.to_string(context)?.to_std_string_escaped()),
+                    _ => element.remove_attribute("popover"),
+                }
+                Ok(JsValue::undefined())
+            },
+            self.clone(),
+        );
+        define_accessor(wrapper, "popover", get_popover, set_popover, context)?;
+
+        let show = NativeFunction::from_copy_closure_with_captures(
+            |this, _args, wrappers: &ElementWrappers, context| {
+                let element = wrappers.this_element(this, context)?;
+                let toggles = element.show_popover(None).map_err(|error| popover_error(context, &error))?;
+                wrappers.fire_popover_toggles(toggles, context)?;
+                Ok(JsValue::undefined())
+            },
+            self.clone(),
+        );
+        let hide = NativeFunction::from_copy_closure_with_captures(
+            |this, _args, wrappers: &ElementWrappers, context| {
+                let element = wrappers.this_element(this, context)?;
+                let toggles = element.hide_popover().map_err(|error| popover_error(context, &error))?;
+                wrappers.fire_popover_toggles(toggles, context)?;
+                Ok(JsValue::undefined())
+            },
+            self.clone(),
+        );
+        let toggle = NativeFunction::from_copy_closure_with_captures(
+            |this, args, wrappers: &ElementWrappers, context| {
+                let element = wrappers.this_element(this, context)?;
+                let force = args.first().filter(|force| !force.is_undefined()).map(JsValue::to_boolean);
+                let toggles = element.toggle_popover(force, None).map_err(|error| popover_error(context, &error))?;
+                wrappers.fire_popover_toggles(toggles, context)?;
+                Ok(element.is_popover_open().into())
+            },
+            self.clone(),
+        );
+        for (name, function) in [("showPopover", show), ("hidePopover", hide), ("togglePopover", toggle)] {
+            define_method(wrapper, name, function, context)?;
+        }
+        Ok(())
+    }
+
+    /// Add `requestFullscreen()` to an element wrapper
+    ///
+    /// The returned promise is settled and `fullscreenchange` or
+    /// `fullscreenerror` fired at the element before the call returns.
+    fn install_fullscreen_members(&self, wrapper: &JsObject, context: &mut Context) -> JsResult<()> {
+        let request = NativeFunction::from_copy_closure_with_captures(
+            |this, _args, wrappers: &ElementWrappers, context| {
+                let element = wrappers.this_element(this, context)?;
+                match element.request_fullscreen() {
+                    Ok(changed) => {
+                        if changed {
+                            wrappers.fire_fullscreen_event(&element, "fullscreenchange", context)?;
+                        }
+                        Ok(JsPromise::resolve(JsValue::undefined(), context).into())
+                    }
+                    Err(error) => {
+                        wrappers.fire_fullscreen_event(&element, "fullscreenerror", context)?;
+                        Ok(JsPromise::reject(fullscreen_error(&error), context).into())
+                    }
+                }
+            },
+            self.clone(),
+        );
+        define_method(wrapper, "requestFullscreen", request, context)
+    }
+
+    /// Fire a bubbling `fullscreenchange` or `fullscreenerror` at an element
+    fn fire_fullscreen_event(&self, element: &Rc<Node>, event_type: &str, context: &mut Context) -> JsResult<()> {
+        let binding = EventTargetBinding { target: BoundTarget::Wrapper, wrappers: self.clone() };
+        let event = event_objects::create_trusted_event(&self.events, EventInterface::Event, event_type, true, false);
+        let target = self.wrap(element, context);
+        binding.dispatch(Some(element), &target, &event, context)?;
+        Ok(())
+    }
+
+    /// Fire `toggle` at each popover that opened or closed, in order
+    fn fire_popover_toggles(&self, toggles: Vec<PopoverToggle>, context: &mut Context) -> JsResult<()> {
+        let binding = EventTargetBinding { target: BoundTarget::Wrapper, wrappers: self.clone() };
+        for toggle in toggles {
+            let event = event_objects::create_trusted_event(&self.events, EventInterface::Event, "toggle", false, false);
+            for (name, state) in [("oldState", toggle.old_state()), ("newState", toggle.new_state())] {
+                event.define_property_or_throw(
+                    js_string!(name),
+                    PropertyDescriptor::builder().value(js_string!(state)).writable(false).enumerable(true).configurable(true),
+                    context,
+                )?;
+            }
+            let target = self.wrap(&toggle.popover, context);
+            binding.dispatch(Some(&toggle.popover), &target, &event, context)?;
+        }
+        Ok(())
+    }
+
+    /// Add `open`, `returnValue`, `show()`, `showModal()` and `close()` to
+    /// the wrapper of a dialog
+    ///
+    /// `close()` fires `close` at the dialog right away rather than from a
+    /// queued task.
+    fn install_dialog_members(&self, wrapper: &JsObject, context: &mut Context) -> JsResult<()> {
+        let get_open = NativeFunction::from_copy_closure_with_captures(
+            |this, _args, wrappers: &ElementWrappers, context| Ok(wrappers.this_element(this, context)?.has_attribute("open").into()),
+            self.clone(),
+        );
+        let set_open = NativeFunction::from_copy_closure_with_captures(
+            |this, args, wrappers: &ElementWrappers, context| {
+                let dialog = wrappers.this_element(this, context)?;
+                if args.first().is_some_and(JsValue::to_boolean) {
+                    dialog.set_attribute("open", "");
+                } else {
+                    dialog.remove_attribute("open");
+                }
+                Ok(JsValue::undefined())
+            },
+            self.clone(),
+        );
+        define_accessor(wrapper, "open", get_open, set_open, context)?;
+
+        let get_return_value = NativeFunction::from_copy_closure_with_captures(
+            |this, _args, wrappers: &ElementWrappers, context| Ok(js_string!(wrappers.this_element(this, context)?.return_value()).into()),
+            self.clone(),
+        );
+        let set_return_value = NativeFunction::from_copy_closure_with_captures(
+            |this, args, wrappers: &ElementWrappers, context| {
+                let dialog = wrappers.this_element(this, context)?;
+                dialog.set_return_value(&string_argument(args, context)?);
+                Ok(JsValue::undefined())
+            },
+            self.clone(),
+        );
+        define_accessor(wrapper, "returnValue", get_return_value, set_return_value, context)?;
+
+        let show = NativeFunction::from_copy_closure_with_captures(
+            |this, _args, wrappers: &ElementWrappers, context| {
+                let dialog = wrappers.this_element(this, context)?;
+                dialog.show_dialog().map_err(|error| dialog_error(context, &error))?;
+                Ok(JsValue::undefined())
+            },
+            self.clone(),
+        );
+        let show_modal = NativeFunction::from_copy_closure_with_captures(
+            |this, _args, wrappers: &ElementWrappers, context| {
+                let dialog = wrappers.this_element(this, context)?;
+                dialog.show_modal().map_err(|error| dialog_error(context, &error))?;
+                Ok(JsValue::undefined())
+            },
+            self.clone(),
+        );
+        let close = NativeFunction::from_copy_closure_with_captures(
+            |this, args, wrappers: &ElementWrappers, context| {
+                let dialog = wrappers.this_element(this, context)?;
+                let return_value = match args.first() {
+                    Some(value) if !value.is_undefined() => Some(value.to_string(context)?.to_std_string_escaped()),
+                    _ => None,
+                };
+                if dialog.close_dialog(return_value.as_deref()) {
+                    let event = event_objects::create_trusted_event(&wrappers.events, EventInterface::Event, "close", false, false);
+                    let binding = EventTargetBinding { target: BoundTarget::Wrapper, wrappers: wrappers.clone() };
+                    if let Some(target) = this.as_object() {
+                        binding.dispatch(Some(&dialog), target, &event, context)?;
+                    }
+                }
+                Ok(JsValue::undefined())
+            },
+            self.clone(),
+        );
+        for (name, function) in [("show", show), ("showModal", show_modal), ("close", close)] {
+            define_method(wrapper, name, function, context)?;
+        }
+        Ok(())
+    }
+
+    /// Add `children`, `getElementsByTagName()`, `getElementsByClassName()`,
+    /// `querySelector()` and `querySelectorAll()` to an element wrapper
+    fn install_collection_members(&self, wrapper: &JsObject, context: &mut Context) -> JsResult<()> {
+        let binding = CollectionBinding { root: None, wrappers: self.clone() };
+        binding.install(wrapper, context)
+    }
+}
+
+/// Captures of the collection members, rooted at the node they are called
+/// on or, for the document, at the document node
+#[derive(Clone, Trace, Finalize)]
+struct CollectionBinding {
+    #[unsafe_ignore_trace]
+    root: Option<Rc<Node>>,
+    wrappers: ElementWrappers,
+}
+
+impl CollectionBinding {
+    /// Resolve the node the collection members query
+    fn root(&self, this: &JsValue, context: &mut Context) -> JsResult<Rc<Node>> {
+        match &self.root {
+            Some(root) => Ok(Rc::clone(root)),
+            None => self.wrappers.this_element(this, context),
+        }
+    }
+
+    /// Define the collection members on an object
+    fn install(self, object: &JsObject, context: &mut Context) -> JsResult<()> {
+        let children = NativeFunction::from_copy_closure_with_captures(
+            |this, _args, binding: &CollectionBinding, context| {
+                let root = binding.root(this, context)?;
+                Ok(collections::html_collection(root.children_collection(), binding.wrappers.clone(), context)?.into())
+            },
+            self.clone(),
+        );
+        object.define_property_or_throw(
+            js_string!("children"),
+            PropertyDescriptor::builder()
+                .get(children.to_js_function(context.realm()))
+                .enumerable(true)
+                .configurable(true),
+            context,
+        )?;
+
+        let by_tag_name = NativeFunction::from_copy_closure_with_captures(
+            |this, args, binding: &CollectionBinding, context| {
+                let root = binding.root(this, context)?;
+                let name = string_argument(args, context)?;
+                Ok(collections::html_collection(root.elements_by_tag_name(&name), binding.wrappers.clone(), context)?.into())
+            },
+            self.clone(),
+        );
+        let by_class_name = NativeFunction::from_copy_closure_with_captures(
+            |this, args, binding: &CollectionBinding, context| {
+                let root = binding.root(this, context)?;
+                let names = string_argument(args, context)?;
+                Ok(collections::html_collection(root.elements_by_class_name(&names), binding.wrappers.clone(), context)?.into())
+            },
+            self.clone(),
+        );
+        let query_selector = NativeFunction::from_copy_closure_with_captures(
+            |this, args, binding: &CollectionBinding, context| {
+                let root = binding.root(this, context)?;
+                let selectors = string_argument(args, context)?;
+                let found = root.query_selector(&selectors).map_err(|error| syntax_error(context, &error.to_string()))?;
+                Ok(binding.wrappers.wrap_or_null(found, context))
+            },
+            self.clone(),
+        );
+        let query_selector_all = NativeFunction::from_copy_closure_with_captures(
+            |this, args, binding: &CollectionBinding, context| {
+                let root = binding.root(this, context)?;
+                let selectors = string_argument(args, context)?;
+                let found = root.query_selector_all(&selectors).map_err(|error| syntax_error(context, &error.to_string()))?;
+                Ok(collections::static_node_list(&found, &binding.wrappers, context)?.into())
+            },
+            self,
+        );
+        let methods = [
+            ("getElementsByTagName", by_tag_name),
+            ("getElementsByClassName", by_class_name),
+            ("querySelector", query_selector),
+            ("querySelectorAll", query_selector_all),
+        ];
+        for (name, function) in methods {
+            define_method(object, name, function, context)?;
+        }
+        Ok(())
+    }
+}
+
+/// Define a method on a wrapper
+pub(crate) fn define_method(wrapper: &JsObject, name: &str, function: NativeFunction, context: &mut Context) -> JsResult<()> {
+    wrapper.define_property_or_throw(
+        js_string!(name),
+        PropertyDescriptor::builder()
+            .value(function.to_js_function(context.realm()))
+            .writable(true)
+            .enumerable(false)
+            .configurable(true),
+        context,
+    )?;
+    Ok(())
+}
+
+/// Define an accessor property on a wrapper
+fn define_accessor(wrapper: &JsObject, name: &str, getter: NativeFunction, setter: NativeFunction, context: &mut Context) -> JsResult<()> {
+    wrapper.define_property_or_throw(
+        js_string!(name),
+        PropertyDescriptor::builder()
+            .get(getter.to_js_function(context.realm()))
+            .set(setter.to_js_function(context.realm()))
+            .enumerable(true)
+            .configurable(true),
+        context,
+    )?;
+    Ok(())
+}
+
+/// Captures of the document accessors
+#[derive(Clone, Trace, Finalize)]
+struct DocumentBinding {
+    #[unsafe_ignore_trace]
+    document: Rc<Document>,
+    wrappers: ElementWrappers,
+}
+
+/// A `document` member that reads a node of the document
+type DocumentAccessor = (&'static str, fn(&Document) -> Option<Rc<Node>>);
+
+/// A `document` member that reads the visibility state
+type VisibilityAccessor = (&'static str, fn(VisibilityState) -> JsValue);
+
+/// Define `documentElement`, `head`, `body`, `createTreeWalker()`,
+/// `createEvent()`, `adoptNode()`, `importNode()`, `visibilityState`,
+/// `hidden`, the fullscreen members, the collection members and the event target methods on the `document`
+/// object
+///
+/// The collection and event target members replace the placeholders the
+/// global `document` starts with, such as `querySelector()` and
+/// `addEventListener()`.
+pub fn install_document_accessors(
+    document_object: &JsObject,
+    document: Rc<Document>,
+    wrappers: ElementWrappers,
+    context: &mut Context,
+) -> JsResult<()> {
+    let collection_binding = CollectionBinding { root: Some(Rc::clone(&document.root)), wrappers: wrappers.clone() };
+    collection_binding.install(document_object, context)?;
+    let event_target_binding = EventTargetBinding { target: BoundTarget::Document(Rc::clone(&document.root)), wrappers: wrappers.clone() };
+    event_target_binding.install(document_object, context)?;
+    let binding = DocumentBinding { document, wrappers };
+    let accessors: [DocumentAccessor; 3] = [
+        ("documentElement", Document::document_element),
+        ("head", Document::head),
+        ("body", Document::body),
+    ];
+    for (name, read) in accessors {
+        let getter = NativeFunction::from_copy_closure_with_captures(
+            move |_this, _args, binding: &DocumentBinding, context| Ok(binding.wrappers.wrap_or_null(read(&binding.document), context)),
+            binding.clone(),
+        );
+        document_object.define_property_or_throw(
+            js_string!(name),
+            PropertyDescriptor::builder()
+                .get(getter.to_js_function(context.realm()))
+                .enumerable(true)
+                .configurable(true),
+            context,
+        )?;
+    }
+
+    let create_tree_walker = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &DocumentBinding, context| {
+            let root = binding.node_argument("createTreeWalker", args, context)?;
+            let what_to_show = match args.get(1) {
+                Some(value) if !value.is_undefined() => value.to_u32(context)?,
+                _ => traversal::SHOW_ALL,
+            };
+            let walker = TreeWalkerBinding { walker: Rc::new(RefCell::new(TreeWalker::new(root, what_to_show))), wrappers: binding.wrappers.clone() };
+            Ok(walker.build(context)?.into())
+        },
+        binding.clone(),
+    );
+    define_method(document_object, "createTreeWalker", create_tree_walker, context)?;
+
+    let create_event = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &DocumentBinding, context| {
+            let name = string_argument(args, context)?;
+            Ok(event_objects::create_event(&binding.wrappers.events, &name, context)?.into())
+        },
+        binding.clone(),
+    );
+    define_method(document_object, "createEvent", create_event, context)?;
+
+    let adopt_node = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &DocumentBinding, context| {
+            let node = binding.node_argument("adoptNode", args, context)?;
+            binding.document.adopt_node(&node).map_err(|error| adopt_error(context, &error))?;
+            let wrapper = binding.wrappers.wrap(&node, context);
+            binding.wrappers.pin_removed(&[node]);
+            Ok(wrapper.into())
+        },
+        binding.clone(),
+    );
+    let import_node = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &DocumentBinding, context| {
+            let node = binding.node_argument("importNode", args, context)?;
+            let deep = args.get(1).is_some_and(JsValue::to_boolean);
+            let copy = binding.document.import_node(&node, deep).map_err(|error| adopt_error(context, &error))?;
+            // Nothing but the wrapper holds the copy until it is inserted
+            let wrapper = binding.wrappers.wrap(&copy, context);
+            binding.wrappers.pin_removed(&[copy]);
+            Ok(wrapper.into())
+        },
+        binding.clone(),
+    );
+    define_method(document_object, "adoptNode", adopt_node, context)?;
+    define_method(document_object, "importNode", import_node, context)?;
+
+    let visibility_accessors: [VisibilityAccessor; 2] = [
+        ("visibilityState", |state| js_string!(state.as_str()).into()),
+        ("hidden", |state| (state == VisibilityState::Hidden).into()),
+    ];
+    for (name, read) in visibility_accessors {
+        let getter = NativeFunction::from_copy_closure_with_captures(
+            move |_this, _args, binding: &DocumentBinding, _context| Ok(read(page_visibility::visibility_state(&binding.document.root))),
+            binding.clone(),
+        );
+        document_object.define_property_or_throw(
+            js_string!(name),
+            PropertyDescriptor::builder()
+                .get(getter.to_js_function(context.realm()))
+                .enumerable(true)
+                .configurable(true),
+            context,
+        )?;
+    }
+    install_document_fullscreen_members(document_object, binding, context)
+}
+
+/// Define `fullscreenElement`, `fullscreenEnabled` and `exitFullscreen()`
+/// on the `document` object
+fn install_document_fullscreen_members(document_object: &JsObject, binding: DocumentBinding, context: &mut Context) -> JsResult<()> {
+    let fullscreen_element = NativeFunction::from_copy_closure_with_captures(
+        |_this, _args, binding: &DocumentBinding, context| {
+            let element = fullscreen::fullscreen_element(&binding.document.root);
+            Ok(binding.wrappers.wrap_or_null(element, context))
+        },
+        binding.clone(),
+    );
+    document_object.define_property_or_throw(
+        js_string!("fullscreenElement"),
+        PropertyDescriptor::builder()
+            .get(fullscreen_element.to_js_function(context.realm()))
+            .enumerable(true)
+            .configurable(true),
+        context,
+    )?;
+    document_object.define_property_or_throw(
+        js_string!("fullscreenEnabled"),
+        PropertyDescriptor::builder().value(true).writable(false).enumerable(true).configurable(true),
+        context,
+    )?;
+
+    let exit_fullscreen = NativeFunction::from_copy_closure_with_captures(
+        |_this, _args, binding: &DocumentBinding, context| match fullscreen::exit_fullscreen(&binding.document.root) {
+            Ok(element) => {
+                binding.wrappers.fire_fullscreen_event(&element, "fullscreenchange", context)?;
+                Ok(JsPromise::resolve(JsValue::undefined(), context).into())
+            }
+            Err(error) => Ok(JsPromise::reject(fullscreen_error(&error), context).into()),
+        },
+        binding,
+    );
+    define_method(document_object, "exitFullscreen", exit_fullscreen, context)
+}
+
+impl DocumentBinding {
+    /// Resolve the node argument of a document method
+    fn node_argument(&self, method: &str, args: &[JsValue], context: &mut Context) -> JsResult<Rc<Node>> {
+        self.wrappers
+            .this_node(args.first().unwrap_or(&JsValue::undefined()), context)
+            .map_err(|_| JsNativeError::typ().with_message(format!("{}: parameter 1 is not of type 'Node'", method)).into())
+    }
+}
+
+/// Captures of the members of a `TreeWalker` object
+#[derive(Clone, Trace, Finalize)]
+struct TreeWalkerBinding {
+    #[unsafe_ignore_trace]
+    walker: Rc<RefCell<TreeWalker>>,
+    wrappers: ElementWrappers,
+}
+
+impl TreeWalkerBinding {
+    /// Create the `TreeWalker` object with `currentNode` and `nextNode()`
+    fn build(self, context: &mut Context) -> JsResult<JsObject> {
+        let current_node = NativeFunction::from_copy_closure_with_captures(
+            |_this, _args, binding: &TreeWalkerBinding, context| {
+                let current = Rc::clone(binding.walker.borrow().current_node());
+                Ok(binding.wrappers.wrap(&current, context).into())
+            },
+            self.clone(),
+        );
+        let next_node = NativeFunction::from_copy_closure_with_captures(
+            |_this, _args, binding: &TreeWalkerBinding, context| {
+                let next = binding.walker.borrow_mut().next_node();
+                Ok(binding.wrappers.wrap_or_null(next, context))
+            },
+            self,
+        );
+        let walker = ObjectInitializer::new(context).build();
+        walker.define_property_or_throw(
+            js_string!("currentNode"),
+            PropertyDescriptor::builder()
+                .get(current_node.to_js_function(context.realm()))
+                .enumerable(true)
+                .configurable(true),
+            context,
+        )?;
+        define_method(&walker, "nextNode", next_node, context)?;
+        Ok(walker)
+    }
+}
+
+/// Convert the first argument to a string, as WebIDL `DOMString` does
+fn string_argument(args: &[JsValue], context: &mut Context) -> JsResult<String> {
+    match args.first() {
+        Some(value) => Ok(value.to_string(context)?.to_std_string_escaped()),
+        None => Err(JsNativeError::typ().with_message("1 argument required, but only 0 present").into()),
+    }
+}
+
+/// Create a `SyntaxError` DOMException to throw
+fn syntax_error(context: &mut Context, message: &str) -> JsError {
+    dom_exception(context, "SyntaxError", message, SYNTAX_ERR)
+}
+
+/// Create the DOMException for a node that cannot be adopted or imported
+fn adopt_error(context: &mut Context, error: &dom::AdoptError) -> JsError {
+    let code = match error {
+        dom::AdoptError::NotSupported => NOT_SUPPORTED_ERR,
+        dom::AdoptError::WrongDocument { .. } => WRONG_DOCUMENT_ERR,
+    };
+    dom_exception(context, error.name(), &error.to_string(), code)
+}
+
+/// Read the `pointerId` argument of the pointer capture methods
+fn pointer_id_argument(method: &str, args: &[JsValue], context: &mut Context) -> JsResult<i32> {
+    match args.first() {
+        Some(pointer_id) => pointer_id.to_i32(context),
+        None => Err(JsNativeError::typ()
+            .with_message(format!("Failed to execute '{}': 1 argument required, but only 0 present", method))
+            .into()),
+    }
+}
+
+fn pointer_capture_error(context: &mut Context, error: &PointerCaptureError) -> JsError {
+    let code = match error {
+        PointerCaptureError::NotFound(_) => NOT_FOUND_ERR,
+        PointerCaptureError::InvalidState => INVALID_STATE_ERR,
+    };
+    dom_exception(context, error.name(), &error.to_string(), code)
+}
+
+/// Convert a popover error into the DOMException it throws
+fn popover_error(context: &mut Context, error: &PopoverError) -> JsError {
+    let code = match error {
+        PopoverError::NotPopover => NOT_SUPPORTED_ERR,
+        PopoverError::NotConnected => INVALID_STATE_ERR,
+    };
+    dom_exception(context, error.name(), &error.to_string(), code)
+}
+
+/// Convert a fullscreen error into the `TypeError` its promise is rejected
+/// with
+fn fullscreen_error(error: &FullscreenError) -> JsError {
+    JsNativeError::typ().with_message(error.to_string()).into()
+}
+
+/// Convert a dialog error into the DOMException it throws
+fn dialog_error(context: &mut Context, error: &DialogError) -> JsError {
+    dom_exception(context, error.name(), &error.to_string(), INVALID_STATE_ERR)
+}
+
+/// Create a DOMException to throw
+pub(crate) fn dom_exception(context: &mut Context, name: &str, message: &str, code: u16) -> JsError {
+    let exception = ObjectInitializer::new(context)
+        .property(js_string!("name"), js_string!(name), Attribute::all())
+        .property(js_string!("message"), js_string!(message), Attribute::all())
+        .property(js_string!("code"), code, Attribute::all())
+        .build();
+    JsError::from_opaque(exception.into())
+}
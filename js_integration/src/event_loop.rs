@@ -0,0 +1,1093 @@
+//! # Unified Event Loop
+//!
+//! This module implements a single scheduler for everything that advances
+//! script execution: timers, DOM and user-interaction tasks, network
+//! completions, microtasks and the rendering steps. It follows the HTML
+//! event-loop processing model.
+//!
+//! ## Design Principles
+//!
+//! 1. **Task Sources**: Every task belongs to a `TaskSource` with its own
+//!    FIFO queue. Ordering within a source is always preserved.
+//! 2. **Microtask Checkpoints**: A microtask checkpoint runs after every task
+//!    and after animation frame callbacks, never in the middle of a task.
+//! 3. **Rendering Opportunities**: Animation frame callbacks and the render
+//!    steps run at most once per frame interval.
+//! 4. **Cross-Thread Wakeups**: Network completions arrive from tokio tasks
+//!    through an `EventLoopHandle`, which wakes the embedder (for example the
+//!    winit event loop) so that it spins the loop again.
+//! 5. **Idle Work Last**: `requestIdleCallback` callbacks and low-priority
+//!    background tasks only run in idle periods, when no task is runnable and
+//!    the next frame or timer is not yet due, so they never delay input or
+//!    rendering.
+//! 6. **Traceable**: Task ordering can be recorded for debugging.
+//! 7. **Injected Clock**: Times are passed in by the embedder, and what
+//!    the loop reads itself, such as the end of an idle period, comes from
+//!    its `SharedClock`, so tests step virtual time with a `ManualClock`.
+//! 8. **Throttled When Hidden**: The embedder can raise the minimum timer
+//!    delay and suspend rendering opportunities, as browsers do for pages
+//!    in background tabs; the timers and frame callbacks are kept and run
+//!    at their normal pace once the page is shown again.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use boa_engine::Context;
+use dom::clock::{self, SharedClock};
+use thiserror::Error;
+
+use crate::TimerTask;
+
+/// Custom error types for event loop operations
+#[derive(Error, Debug)]
+pub enum EventLoopError {
+    #[error("Task {id} from {task_source} failed: {message}")]
+    TaskFailed {
+        id: u64,
+        task_source: TaskSource,
+        message: String,
+    },
+
+    #[error("Microtask checkpoint failed: {0}")]
+    MicrotaskCheckpointFailed(String),
+}
+
+/// Result type for event loop operations
+pub type EventLoopResult<T> = Result<T, EventLoopError>;
+
+/// The source a task was queued from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum TaskSource {
+    /// Input events such as clicks and key presses
+    UserInteraction,
+    /// DOM mutations and events fired in response to them
+    DomManipulation,
+    /// Completions of network requests
+    Networking,
+    /// `setTimeout` and `setInterval` callbacks
+    Timer,
+    /// Cross-document and worker messages
+    PostedMessage,
+    /// Low-priority work that only runs in idle periods
+    Background,
+}
+
+impl TaskSource {
+    /// All task sources that run as regular tasks, in the order their
+    /// queues are considered; background tasks are scheduled separately
+    pub const ALL: [TaskSource; 5] = [
+        TaskSource::UserInteraction,
+        TaskSource::DomManipulation,
+        TaskSource::Networking,
+        TaskSource::Timer,
+        TaskSource::PostedMessage,
+    ];
+}
+
+impl fmt::Display for TaskSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TaskSource::UserInteraction => "user-interaction",
+            TaskSource::DomManipulation => "dom-manipulation",
+            TaskSource::Networking => "networking",
+            TaskSource::Timer => "timer",
+            TaskSource::PostedMessage => "posted-message",
+            TaskSource::Background => "background",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Native work that runs against the JavaScript context
+pub type NativeWork = Box<dyn FnOnce(&mut Context) -> Result<(), String> + Send>;
+
+/// Work carried by a task
+pub enum TaskAction {
+    /// JavaScript source to evaluate
+    Script(String),
+    /// Native work that runs against the JavaScript context
+    Native(NativeWork),
+}
+
+impl fmt::Debug for TaskAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaskAction::Script(code) => f.debug_tuple("Script").field(code).finish(),
+            TaskAction::Native(_) => f.write_str("Native(..)"),
+        }
+    }
+}
+
+/// A queued task
+#[derive(Debug)]
+pub struct Task {
+    /// Unique identifier, also the global enqueue order
+    pub id: u64,
+    /// The source the task was queued from
+    pub source: TaskSource,
+    /// The work to perform
+    pub action: TaskAction,
+}
+
+/// How long a script run by the event loop took, reported to profilers
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskTiming {
+    /// What ran: `task` or `idle callback`
+    pub kind: &'static str,
+    /// Source of the script, empty for native tasks
+    pub script: String,
+    pub start: Instant,
+    pub duration: Duration,
+}
+
+/// Longest idle period, so idle work never delays newly arriving input
+/// by more than this
+pub const MAX_IDLE_PERIOD: Duration = Duration::from_millis(50);
+
+/// Deadline passed to idle callbacks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IdleDeadline {
+    /// End of the idle period
+    pub deadline: Instant,
+    /// Whether the callback runs because its timeout expired
+    pub did_timeout: bool,
+}
+
+impl IdleDeadline {
+    /// Time left in the idle period, by the thread's clock
+    pub fn time_remaining(&self) -> Duration {
+        self.time_remaining_at(clock::now())
+    }
+
+    /// Time left in the idle period at `now`
+    pub fn time_remaining_at(&self, now: Instant) -> Duration {
+        if self.did_timeout {
+            Duration::ZERO
+        } else {
+            self.deadline.saturating_duration_since(now)
+        }
+    }
+}
+
+/// A pending `requestIdleCallback` callback
+#[derive(Debug, Clone)]
+struct IdleCallback {
+    id: u32,
+    callback: String,
+    /// When the callback must run even without an idle period
+    timeout_at: Option<Instant>,
+}
+
+/// Something that can wake the embedder's event loop
+///
+/// For winit, wrap an `EventLoopProxy` in a closure that sends a user event:
+/// `move || { let _ = proxy.send_event(UserEvent::SpinEventLoop); }`.
+pub trait EventLoopWaker: Send + Sync {
+    /// Ask the embedder to spin the event loop soon
+    fn wake(&self);
+}
+
+impl<F: Fn() + Send + Sync> EventLoopWaker for F {
+    fn wake(&self) {
+        self()
+    }
+}
+
+/// Cross-thread handle used to queue tasks into the event loop
+///
+/// Handles are cheap to clone and can be moved into tokio tasks; queued
+/// tasks are picked up on the next iteration of the loop.
+#[derive(Clone)]
+pub struct EventLoopHandle {
+    inbox: Arc<Mutex<VecDeque<(TaskSource, TaskAction)>>>,
+    waker: Arc<Mutex<Option<Arc<dyn EventLoopWaker>>>>,
+}
+
+impl EventLoopHandle {
+    /// Queue a task from any thread and wake the embedder
+    pub fn queue_task(&self, source: TaskSource, action: TaskAction) {
+        self.inbox.lock().unwrap().push_back((source, action));
+        if let Some(waker) = self.waker.lock().unwrap().as_ref() {
+            waker.wake();
+        }
+    }
+
+    /// Queue the completion of a network request
+    pub fn queue_network_completion(&self, action: TaskAction) {
+        self.queue_task(TaskSource::Networking, action);
+    }
+}
+
+/// The embedder-side operations the event loop drives
+pub trait EventLoopHost {
+    /// Run a single task
+    fn run_task(&mut self, action: TaskAction) -> Result<(), String>;
+    /// Run all pending microtasks, returning how many ran
+    fn perform_microtask_checkpoint(&mut self) -> Result<usize, String>;
+    /// Run the render steps (style, layout and paint) for this frame
+    fn update_rendering(&mut self, timestamp: f64);
+    /// Run an idle callback with its deadline
+    fn run_idle_callback(&mut self, callback: String, _deadline: IdleDeadline) -> Result<(), String> {
+        self.run_task(TaskAction::Script(callback))
+    }
+}
+
+/// An entry in the task ordering trace
+#[derive(Debug, Clone, PartialEq)]
+pub enum TraceEvent {
+    /// A task ran
+    Task { id: u64, source: TaskSource },
+    /// A microtask checkpoint ran the given number of microtasks
+    MicrotaskCheckpoint { count: usize },
+    /// Animation frame callbacks ran
+    AnimationFrameCallbacks { count: usize },
+    /// The render steps ran
+    Render,
+    /// An idle period ran idle callbacks and background tasks
+    IdlePeriod { callbacks: usize, background_tasks: usize },
+}
+
+/// What a single iteration of the event loop did
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct IterationReport {
+    /// Number of tasks run
+    pub tasks_run: usize,
+    /// Number of microtasks run across all checkpoints
+    pub microtasks_run: usize,
+    /// Whether the render steps ran
+    pub rendered: bool,
+    /// Number of idle callbacks run, including timed-out ones
+    pub idle_callbacks_run: usize,
+    /// Number of low-priority background tasks run
+    pub background_tasks_run: usize,
+}
+
+/// Statistics for the event loop
+#[derive(Debug, Default, Clone)]
+pub struct EventLoopStats {
+    /// Tasks run, by source
+    pub tasks_by_source: HashMap<TaskSource, usize>,
+    /// Number of microtask checkpoints performed
+    pub microtask_checkpoints: usize,
+    /// Number of frames rendered
+    pub frames_rendered: usize,
+    /// Number of tasks that failed
+    pub failed_tasks: usize,
+    /// Number of idle periods entered
+    pub idle_periods: usize,
+}
+
+/// The unified event loop
+pub struct EventLoop {
+    /// One FIFO queue per task source
+    task_queues: HashMap<TaskSource, VecDeque<Task>>,
+    /// Tasks queued from other threads
+    inbox: Arc<Mutex<VecDeque<(TaskSource, TaskAction)>>>,
+    /// Waker for the embedder's event loop
+    waker: Arc<Mutex<Option<Arc<dyn EventLoopWaker>>>>,
+    /// Pending timers
+    timers: HashMap<u32, TimerTask>,
+    next_timer_id: u32,
+    next_task_id: u64,
+    /// Pending animation frame callbacks
+    animation_frame_callbacks: Vec<(u32, String)>,
+    next_animation_frame_id: u32,
+    /// Whether a render was requested outside of animation frames
+    render_requested: bool,
+    /// Minimum time between rendering opportunities
+    frame_interval: Duration,
+    last_render: Option<Instant>,
+    // Throttling of pages the user cannot see
+    min_timer_delay: Duration,
+    rendering_suspended: bool,
+    /// Where the loop reads the time when it is not given one
+    clock: SharedClock,
+    /// When the loop was created, used for animation frame timestamps
+    time_origin: Instant,
+    /// Pending idle callbacks
+    idle_callbacks: Vec<IdleCallback>,
+    next_idle_callback_id: u32,
+    /// Low-priority tasks that only run in idle periods
+    background_tasks: VecDeque<Task>,
+    trace_enabled: bool,
+    trace: Vec<TraceEvent>,
+    stats: EventLoopStats,
+}
+
+impl EventLoop {
+    /// Create a new event loop with a 60Hz frame interval
+    pub fn new() -> Self {
+        Self {
+            task_queues: HashMap::new(),
+            inbox: Arc::new(Mutex::new(VecDeque::new())),
+            waker: Arc::new(Mutex::new(None)),
+            timers: HashMap::new(),
+            next_timer_id: 1,
+            next_task_id: 1,
+            animation_frame_callbacks: Vec::new(),
+            next_animation_frame_id: 1,
+            render_requested: false,
+            frame_interval: Duration::from_micros(16_667),
+            last_render: None,
+            min_timer_delay: Duration::ZERO,
+            rendering_suspended: false,
+            clock: clock::thread_clock(),
+            time_origin: clock::now(),
+            idle_callbacks: Vec::new(),
+            next_idle_callback_id: 1,
+            background_tasks: VecDeque::new(),
+            trace_enabled: false,
+            trace: Vec::new(),
+            stats: EventLoopStats::default(),
+        }
+    }
+
+    /// Get a handle for queueing tasks from other threads
+    pub fn handle(&self) -> EventLoopHandle {
+        EventLoopHandle {
+            inbox: Arc::clone(&self.inbox),
+            waker: Arc::clone(&self.waker),
+        }
+    }
+
+    /// Install the waker used to notify the embedder of new work
+    pub fn set_waker(&mut self, waker: Arc<dyn EventLoopWaker>) {
+        *self.waker.lock().unwrap() = Some(waker);
+    }
+
+    /// Read the time from `clock` instead of the thread's clock
+    ///
+    /// The time origin of animation frame timestamps moves to the clock's
+    /// current time.
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.time_origin = clock.now();
+        self.clock = clock;
+    }
+
+    /// Get the clock the loop reads the time from
+    pub fn clock(&self) -> SharedClock {
+        Arc::clone(&self.clock)
+    }
+
+    /// Get the current time by the loop's clock
+    pub fn now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    /// Set the minimum time between rendering opportunities
+    pub fn set_frame_interval(&mut self, interval: Duration) {
+        self.frame_interval = interval;
+    }
+
+    /// Set the minimum delay of timers, counted from when each was set or
+    /// last ran; `Duration::ZERO` lets timers run at their own delay
+    pub fn set_min_timer_delay(&mut self, delay: Duration) {
+        self.min_timer_delay = delay;
+    }
+
+    /// Suspend or resume rendering opportunities
+    ///
+    /// While suspended, animation frame callbacks and the render steps do
+    /// not run and requested frames wait until rendering resumes.
+    pub fn set_rendering_suspended(&mut self, suspended: bool) {
+        self.rendering_suspended = suspended;
+    }
+
+    /// Enable or disable task ordering tracing
+    pub fn set_trace_enabled(&mut self, enabled: bool) {
+        self.trace_enabled = enabled;
+    }
+
+    /// Get the recorded task ordering trace
+    pub fn trace(&self) -> &[TraceEvent] {
+        &self.trace
+    }
+
+    /// Clear the recorded trace
+    pub fn clear_trace(&mut self) {
+        self.trace.clear();
+    }
+
+    /// Get event loop statistics
+    pub fn stats(&self) -> &EventLoopStats {
+        &self.stats
+    }
+
+    /// Queue a task on the given source
+    pub fn queue_task(&mut self, source: TaskSource, action: TaskAction) -> u64 {
+        if source == TaskSource::Background {
+            return self.queue_background_task(action);
+        }
+        let id = self.next_task_id;
+        self.next_task_id += 1;
+        self.task_queues
+            .entry(source)
+            .or_default()
+            .push_back(Task { id, source, action });
+        id
+    }
+
+    /// Schedule a timer, returning its ID
+    pub fn set_timer(&mut self, callback: String, delay: Duration, repeat: bool, now: Instant) -> u32 {
+        let id = self.next_timer_id;
+        self.next_timer_id += 1;
+        // A zero-delay interval would otherwise be due forever
+        let delay = if repeat { delay.max(Duration::from_millis(1)) } else { delay };
+        self.timers.insert(id, TimerTask {
+            id,
+            callback,
+            delay,
+            repeat,
+            created_at: now,
+        });
+        id
+    }
+
+    /// Cancel a timer, returning whether it existed
+    pub fn clear_timer(&mut self, timer_id: u32) -> bool {
+        self.timers.remove(&timer_id).is_some()
+    }
+
+    /// Number of pending timers
+    pub fn timer_count(&self) -> usize {
+        self.timers.len()
+    }
+
+    /// Request an animation frame callback, returning its ID
+    pub fn request_animation_frame(&mut self, callback: String) -> u32 {
+        let id = self.next_animation_frame_id;
+        self.next_animation_frame_id += 1;
+        self.animation_frame_callbacks.push((id, callback));
+        id
+    }
+
+    /// Cancel an animation frame callback
+    pub fn cancel_animation_frame(&mut self, id: u32) {
+        self.animation_frame_callbacks.retain(|(callback_id, _)| *callback_id != id);
+    }
+
+    /// Request an idle callback, returning its ID
+    ///
+    /// With a timeout, the callback runs as soon as the timeout expires
+    /// even if the loop never becomes idle.
+    pub fn request_idle_callback(&mut self, callback: String, timeout: Option<Duration>, now: Instant) -> u32 {
+        let id = self.next_idle_callback_id;
+        self.next_idle_callback_id += 1;
+        self.idle_callbacks.push(IdleCallback {
+            id,
+            callback,
+            timeout_at: timeout.map(|timeout| now + timeout),
+        });
+        id
+    }
+
+    /// Cancel an idle callback
+    pub fn cancel_idle_callback(&mut self, id: u32) {
+        self.idle_callbacks.retain(|callback| callback.id != id);
+    }
+
+    /// Drop every pending timer, task and callback, e.g. when the
+    /// document is unloaded
+    ///
+    /// Tasks other threads queued but the loop has not taken yet are
+    /// dropped too. Returns the number of items dropped.
+    pub fn discard_pending(&mut self) -> usize {
+        let inbox = std::mem::take(&mut *self.inbox.lock().unwrap()).len();
+        let tasks: usize = self.task_queues.drain().map(|(_, queue)| queue.len()).sum();
+        let count = inbox
+            + tasks
+            + self.timers.len()
+            + self.animation_frame_callbacks.len()
+            + self.idle_callbacks.len()
+            + self.background_tasks.len();
+        self.timers.clear();
+        self.animation_frame_callbacks.clear();
+        self.idle_callbacks.clear();
+        self.background_tasks.clear();
+        self.render_requested = false;
+        count
+    }
+
+    /// Queue a low-priority task that only runs in idle periods
+    ///
+    /// Used by the shell for work like prefetching and devtools
+    /// aggregation that must not compete with input and rendering.
+    pub fn queue_background_task(&mut self, action: TaskAction) -> u64 {
+        let id = self.next_task_id;
+        self.next_task_id += 1;
+        self.background_tasks.push_back(Task {
+            id,
+            source: TaskSource::Background,
+            action,
+        });
+        id
+    }
+
+    /// Whether idle callbacks or background tasks are waiting
+    pub fn has_idle_work(&self) -> bool {
+        !self.idle_callbacks.is_empty() || !self.background_tasks.is_empty()
+    }
+
+    /// Request that the render steps run at the next rendering opportunity
+    pub fn request_render(&mut self) {
+        self.render_requested = true;
+    }
+
+    /// Whether any task is runnable right now
+    pub fn has_pending_tasks(&self) -> bool {
+        self.task_queues.values().any(|queue| !queue.is_empty())
+            || !self.inbox.lock().unwrap().is_empty()
+    }
+
+    /// The next instant at which the loop has work to do
+    ///
+    /// Embedders use this to sleep, e.g. with winit's
+    /// `ControlFlow::WaitUntil`. Returns `None` when the loop is idle.
+    pub fn next_wakeup(&self, now: Instant) -> Option<Instant> {
+        if self.has_pending_tasks() {
+            return Some(now);
+        }
+
+        if !self.background_tasks.is_empty() {
+            return Some(now);
+        }
+
+        let next_timer = self.next_timer_due();
+        let next_frame = self.next_frame_due(now);
+        let next_idle = if self.idle_callbacks.is_empty() {
+            None
+        } else {
+            // Idle callbacks run as soon as the loop is idle
+            Some(now)
+        };
+
+        [next_timer, next_frame, next_idle].into_iter().flatten().min()
+    }
+
+    fn next_timer_due(&self) -> Option<Instant> {
+        self.timers.values().map(|timer| self.timer_due(timer)).min()
+    }
+
+    /// The instant a timer is due, after throttling
+    fn timer_due(&self, timer: &TimerTask) -> Instant {
+        timer.created_at + timer.delay.max(self.min_timer_delay)
+    }
+
+    fn next_frame_due(&self, now: Instant) -> Option<Instant> {
+        if self.rendering_suspended {
+            None
+        } else if self.render_requested || !self.animation_frame_callbacks.is_empty() {
+            Some(self.last_render.map_or(now, |last| last + self.frame_interval))
+        } else {
+            None
+        }
+    }
+
+    /// Run one iteration of the event loop
+    ///
+    /// Every task that is runnable when the iteration starts is run, oldest
+    /// first (user interaction tasks take priority), each followed by a
+    /// microtask checkpoint. If a rendering opportunity has arrived, the
+    /// animation frame callbacks and render steps run afterwards. Idle
+    /// callbacks whose timeout expired run next, and finally, if the loop is
+    /// idle, an idle period runs idle callbacks and background tasks.
+    pub fn run_iteration(&mut self, host: &mut dyn EventLoopHost, now: Instant) -> EventLoopResult<IterationReport> {
+        let mut report = IterationReport::default();
+
+        self.drain_inbox();
+        self.queue_due_timers(now);
+
+        let runnable: usize = self.task_queues.values().map(|queue| queue.len()).sum();
+        for _ in 0..runnable {
+            let task = match self.pop_next_task() {
+                Some(task) => task,
+                None => break,
+            };
+
+            let (id, source) = (task.id, task.source);
+            self.record(TraceEvent::Task { id, source });
+            *self.stats.tasks_by_source.entry(source).or_insert(0) += 1;
+
+            let result = host.run_task(task.action);
+            report.tasks_run += 1;
+            report.microtasks_run += self.microtask_checkpoint(host)?;
+
+            if let Err(message) = result {
+                self.stats.failed_tasks += 1;
+                return Err(EventLoopError::TaskFailed { id, task_source: source, message });
+            }
+        }
+
+        if self.is_rendering_opportunity(now) {
+            report.microtasks_run += self.update_rendering(host, now)?;
+            report.rendered = true;
+        }
+
+        let timed_out = self.run_timed_out_idle_callbacks(host, now, &mut report)?;
+        report.idle_callbacks_run += timed_out;
+
+        if let Some(deadline) = self.idle_period_deadline(now) {
+            self.run_idle_period(host, deadline, &mut report)?;
+        }
+
+        Ok(report)
+    }
+
+    /// Run iterations until no task or timer is due at `now`
+    pub fn run_until_idle(&mut self, host: &mut dyn EventLoopHost, now: Instant) -> EventLoopResult<IterationReport> {
+        let mut total = IterationReport::default();
+        loop {
+            let report = self.run_iteration(host, now)?;
+            total.tasks_run += report.tasks_run;
+            total.microtasks_run += report.microtasks_run;
+            total.rendered |= report.rendered;
+            total.idle_callbacks_run += report.idle_callbacks_run;
+            total.background_tasks_run += report.background_tasks_run;
+
+            if report.tasks_run == 0 && !self.has_pending_tasks() {
+                return Ok(total);
+            }
+        }
+    }
+
+    /// Move tasks queued from other threads into their task queues
+    fn drain_inbox(&mut self) {
+        let pending: Vec<_> = self.inbox.lock().unwrap().drain(..).collect();
+        for (source, action) in pending {
+            self.queue_task(source, action);
+        }
+    }
+
+    /// Queue a timer task for every timer whose delay has elapsed
+    fn queue_due_timers(&mut self, now: Instant) {
+        let mut due: Vec<(Instant, u32)> = self.timers
+            .values()
+            .map(|timer| (self.timer_due(timer), timer.id))
+            .filter(|(due, _)| now >= *due)
+            .collect();
+        due.sort();
+
+        for (_, timer_id) in due {
+            let timer = match self.timers.remove(&timer_id) {
+                Some(timer) => timer,
+                None => continue,
+            };
+
+            self.queue_task(TaskSource::Timer, TaskAction::Script(timer.callback.clone()));
+
+            if timer.repeat {
+                self.timers.insert(timer_id, TimerTask {
+                    created_at: now,
+                    ..timer
+                });
+            }
+        }
+    }
+
+    /// Pick the next task: user interaction first, then the oldest task
+    fn pop_next_task(&mut self) -> Option<Task> {
+        if let Some(task) = self.task_queues
+            .get_mut(&TaskSource::UserInteraction)
+            .and_then(|queue| queue.pop_front())
+        {
+            return Some(task);
+        }
+
+        let source = TaskSource::ALL
+            .iter()
+            .filter_map(|source| {
+                self.task_queues
+                    .get(source)
+                    .and_then(|queue| queue.front())
+                    .map(|task| (task.id, *source))
+            })
+            .min()
+            .map(|(_, source)| source)?;
+
+        self.task_queues.get_mut(&source).and_then(|queue| queue.pop_front())
+    }
+
+    fn microtask_checkpoint(&mut self, host: &mut dyn EventLoopHost) -> EventLoopResult<usize> {
+        let count = host
+            .perform_microtask_checkpoint()
+            .map_err(EventLoopError::MicrotaskCheckpointFailed)?;
+        self.stats.microtask_checkpoints += 1;
+        self.record(TraceEvent::MicrotaskCheckpoint { count });
+        Ok(count)
+    }
+
+    fn is_rendering_opportunity(&self, now: Instant) -> bool {
+        if self.rendering_suspended || !self.render_requested && self.animation_frame_callbacks.is_empty() {
+            return false;
+        }
+        match self.last_render {
+            Some(last) => now.duration_since(last) >= self.frame_interval,
+            None => true,
+        }
+    }
+
+    /// Run animation frame callbacks followed by the render steps
+    fn update_rendering(&mut self, host: &mut dyn EventLoopHost, now: Instant) -> EventLoopResult<usize> {
+        let timestamp = now.duration_since(self.time_origin).as_secs_f64() * 1000.0;
+        let mut microtasks = 0;
+
+        // Callbacks requested while these run belong to the next frame
+        let callbacks = std::mem::take(&mut self.animation_frame_callbacks);
+        if !callbacks.is_empty() {
+            self.record(TraceEvent::AnimationFrameCallbacks { count: callbacks.len() });
+            for (_, callback) in callbacks {
+                if let Err(message) = host.run_task(TaskAction::Script(callback)) {
+                    eprintln!("❌ Animation frame callback failed: {}", message);
+                }
+                microtasks += self.microtask_checkpoint(host)?;
+            }
+        }
+
+        host.update_rendering(timestamp);
+        self.record(TraceEvent::Render);
+        self.stats.frames_rendered += 1;
+        self.render_requested = false;
+        self.last_render = Some(now);
+
+        Ok(microtasks)
+    }
+
+    /// Run idle callbacks whose timeout has expired
+    fn run_timed_out_idle_callbacks(
+        &mut self,
+        host: &mut dyn EventLoopHost,
+        now: Instant,
+        report: &mut IterationReport,
+    ) -> EventLoopResult<usize> {
+        let (expired, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.idle_callbacks)
+            .into_iter()
+            .partition(|callback| callback.timeout_at.is_some_and(|timeout_at| timeout_at <= now));
+        self.idle_callbacks = pending;
+
+        let deadline = IdleDeadline { deadline: now, did_timeout: true };
+        for callback in &expired {
+            if let Err(message) = host.run_idle_callback(callback.callback.clone(), deadline) {
+                eprintln!("❌ Idle callback {} failed: {}", callback.id, message);
+            }
+            report.microtasks_run += self.microtask_checkpoint(host)?;
+        }
+        Ok(expired.len())
+    }
+
+    /// End of the idle period starting at `now`, if the loop is idle
+    ///
+    /// The loop is idle when no task is runnable; the period ends at the
+    /// next timer or frame, and lasts at most `MAX_IDLE_PERIOD`.
+    fn idle_period_deadline(&self, now: Instant) -> Option<Instant> {
+        if !self.has_idle_work() || self.has_pending_tasks() {
+            return None;
+        }
+
+        let deadline = [self.next_timer_due(), self.next_frame_due(now), Some(now + MAX_IDLE_PERIOD)]
+            .into_iter()
+            .flatten()
+            .min()?;
+        if deadline > now {
+            Some(deadline)
+        } else {
+            None
+        }
+    }
+
+    /// Run idle callbacks, then background tasks, until the deadline passes
+    ///
+    /// At least one unit of work runs per idle period so idle work always
+    /// makes progress.
+    fn run_idle_period(
+        &mut self,
+        host: &mut dyn EventLoopHost,
+        deadline: Instant,
+        report: &mut IterationReport,
+    ) -> EventLoopResult<()> {
+        self.stats.idle_periods += 1;
+        let mut callbacks = 0;
+        let mut background_tasks = 0;
+        let idle_deadline = IdleDeadline { deadline, did_timeout: false };
+
+        // Callbacks requested during this period wait for the next one
+        let mut runnable: VecDeque<IdleCallback> = std::mem::take(&mut self.idle_callbacks).into();
+        loop {
+            let ran_any = callbacks + background_tasks > 0;
+            if ran_any && self.clock.now() >= deadline {
+                break;
+            }
+
+            if let Some(callback) = runnable.pop_front() {
+                if let Err(message) = host.run_idle_callback(callback.callback, idle_deadline) {
+                    eprintln!("❌ Idle callback {} failed: {}", callback.id, message);
+                }
+                callbacks += 1;
+            } else if let Some(task) = self.background_tasks.pop_front() {
+                *self.stats.tasks_by_source.entry(task.source).or_insert(0) += 1;
+                if let Err(message) = host.run_task(task.action) {
+                    self.stats.failed_tasks += 1;
+                    eprintln!("❌ Background task {} failed: {}", task.id, message);
+                }
+                background_tasks += 1;
+            } else {
+                break;
+            }
+            report.microtasks_run += self.microtask_checkpoint(host)?;
+        }
+
+        // Unrun callbacks keep their place ahead of newly requested ones
+        runnable.extend(self.idle_callbacks.drain(..));
+        self.idle_callbacks = runnable.into();
+
+        report.idle_callbacks_run += callbacks;
+        report.background_tasks_run += background_tasks;
+        self.record(TraceEvent::IdlePeriod { callbacks, background_tasks });
+        Ok(())
+    }
+
+    fn record(&mut self, event: TraceEvent) {
+        if self.trace_enabled {
+            println!("🔸 Event loop: {:?}", event);
+            self.trace.push(event);
+        }
+    }
+}
+
+impl Default for EventLoop {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dom::clock::{Clock, ManualClock};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Host that records what it was asked to do
+    #[derive(Default)]
+    struct RecordingHost {
+        log: Vec<String>,
+        pending_microtasks: usize,
+        idle_deadlines: Vec<IdleDeadline>,
+        /// Handle used by "busy" tasks to queue another task
+        requeue: Option<EventLoopHandle>,
+    }
+
+    impl EventLoopHost for RecordingHost {
+        fn run_task(&mut self, action: TaskAction) -> Result<(), String> {
+            match action {
+                TaskAction::Script(code) => {
+                    if code == "queue-microtask" {
+                        self.pending_microtasks += 1;
+                    }
+                    if code == "busy" {
+                        if let Some(handle) = &self.requeue {
+                            handle.queue_task(TaskSource::DomManipulation, TaskAction::Script("busy".to_string()));
+                        }
+                    }
+                    self.log.push(code);
+                    Ok(())
+                }
+                TaskAction::Native(_) => {
+                    self.log.push("native".to_string());
+                    Ok(())
+                }
+            }
+        }
+
+        fn perform_microtask_checkpoint(&mut self) -> Result<usize, String> {
+            let count = std::mem::take(&mut self.pending_microtasks);
+            if count > 0 {
+                self.log.push(format!("microtasks:{}", count));
+            }
+            Ok(count)
+        }
+
+        fn update_rendering(&mut self, _timestamp: f64) {
+            self.log.push("render".to_string());
+        }
+
+        fn run_idle_callback(&mut self, callback: String, deadline: IdleDeadline) -> Result<(), String> {
+            self.idle_deadlines.push(deadline);
+            self.run_task(TaskAction::Script(callback))
+        }
+    }
+
+    #[test]
+    fn test_task_ordering_and_microtask_checkpoints() {
+        let mut event_loop = EventLoop::new();
+        let mut host = RecordingHost::default();
+        let now = Instant::now();
+
+        event_loop.set_trace_enabled(true);
+        event_loop.queue_task(TaskSource::Networking, TaskAction::Script("net".to_string()));
+        event_loop.queue_task(TaskSource::DomManipulation, TaskAction::Script("queue-microtask".to_string()));
+        event_loop.queue_task(TaskSource::UserInteraction, TaskAction::Script("click".to_string()));
+
+        let report = event_loop.run_iteration(&mut host, now).unwrap();
+        assert_eq!(report.tasks_run, 3);
+        assert_eq!(report.microtasks_run, 1);
+        assert!(!report.rendered);
+        assert_eq!(host.log, vec!["click", "net", "queue-microtask", "microtasks:1"]);
+
+        // Every task is followed by a checkpoint in the trace
+        let checkpoints = event_loop
+            .trace()
+            .iter()
+            .filter(|event| matches!(event, TraceEvent::MicrotaskCheckpoint { .. }))
+            .count();
+        assert_eq!(checkpoints, 3);
+    }
+
+    #[test]
+    fn test_timers_and_rendering_opportunities() {
+        let clock = ManualClock::new();
+        let mut event_loop = EventLoop::new();
+        event_loop.set_clock(Arc::new(clock.clone()));
+        let mut host = RecordingHost::default();
+        let start = event_loop.now();
+
+        event_loop.set_timer("late".to_string(), Duration::from_millis(20), false, event_loop.now());
+        event_loop.set_timer("early".to_string(), Duration::from_millis(10), false, event_loop.now());
+        event_loop.request_animation_frame("raf".to_string());
+
+        let report = event_loop.run_iteration(&mut host, event_loop.now()).unwrap();
+        assert_eq!(report.tasks_run, 0);
+        assert!(report.rendered);
+        assert_eq!(host.log, vec!["raf", "render"]);
+        assert_eq!(event_loop.next_wakeup(event_loop.now()), Some(start + Duration::from_millis(10)));
+
+        host.log.clear();
+        event_loop.request_render();
+        clock.advance(Duration::from_millis(25));
+        let report = event_loop.run_iteration(&mut host, event_loop.now()).unwrap();
+        assert_eq!(report.tasks_run, 2);
+        assert!(report.rendered);
+        assert_eq!(host.log, vec!["early", "late", "render"]);
+        assert_eq!(event_loop.timer_count(), 0);
+
+        // No new frame before the frame interval elapses
+        event_loop.request_render();
+        clock.advance(Duration::from_millis(5));
+        let report = event_loop.run_iteration(&mut host, event_loop.now()).unwrap();
+        assert!(!report.rendered);
+    }
+
+    #[test]
+    fn test_throttled_timers_and_suspended_rendering() {
+        let mut event_loop = EventLoop::new();
+        let mut host = RecordingHost::default();
+        let start = Instant::now();
+
+        event_loop.set_min_timer_delay(Duration::from_secs(1));
+        event_loop.set_rendering_suspended(true);
+        event_loop.set_timer("tick".to_string(), Duration::from_millis(10), true, start);
+        event_loop.request_animation_frame("raf".to_string());
+        assert_eq!(event_loop.next_wakeup(start), Some(start + Duration::from_secs(1)));
+
+        let report = event_loop.run_iteration(&mut host, start + Duration::from_millis(500)).unwrap();
+        assert_eq!(report.tasks_run, 0);
+        assert!(!report.rendered);
+        event_loop.run_iteration(&mut host, start + Duration::from_secs(1)).unwrap();
+        assert_eq!(host.log, vec!["tick"]);
+
+        // Shown again, the waiting frame and the interval's own delay resume
+        event_loop.set_min_timer_delay(Duration::ZERO);
+        event_loop.set_rendering_suspended(false);
+        let report = event_loop.run_iteration(&mut host, start + Duration::from_millis(1010)).unwrap();
+        assert!(report.rendered);
+        assert_eq!(host.log, vec!["tick", "tick", "raf", "render"]);
+    }
+
+    #[test]
+    fn test_cross_thread_completion_wakes_embedder() {
+        let mut event_loop = EventLoop::new();
+        let wakeups = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&wakeups);
+        event_loop.set_waker(Arc::new(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }));
+
+        let handle = event_loop.handle();
+        std::thread::spawn(move || {
+            handle.queue_network_completion(TaskAction::Native(Box::new(|_| Ok(()))));
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(wakeups.load(Ordering::SeqCst), 1);
+        assert!(event_loop.has_pending_tasks());
+
+        let mut host = RecordingHost::default();
+        let report = event_loop.run_until_idle(&mut host, Instant::now()).unwrap();
+        assert_eq!(report.tasks_run, 1);
+        assert_eq!(host.log, vec!["native"]);
+        assert_eq!(event_loop.stats().tasks_by_source.get(&TaskSource::Networking), Some(&1));
+    }
+
+    #[test]
+    fn test_idle_work_runs_after_tasks_and_rendering() {
+        let clock = ManualClock::new();
+        let mut event_loop = EventLoop::new();
+        event_loop.set_clock(Arc::new(clock.clone()));
+        let mut host = RecordingHost::default();
+        let now = clock.now();
+
+        event_loop.request_idle_callback("idle".to_string(), None, now);
+        event_loop.queue_background_task(TaskAction::Script("prefetch".to_string()));
+        event_loop.queue_task(TaskSource::UserInteraction, TaskAction::Script("click".to_string()));
+        event_loop.request_animation_frame("raf".to_string());
+
+        let report = event_loop.run_iteration(&mut host, now).unwrap();
+        assert_eq!(host.log, vec!["click", "raf", "render", "idle", "prefetch"]);
+        assert_eq!(report.idle_callbacks_run, 1);
+        assert_eq!(report.background_tasks_run, 1);
+        assert!(!event_loop.has_idle_work());
+        assert_eq!(event_loop.stats().idle_periods, 1);
+
+        // Nothing else is scheduled, so the idle period lasts the maximum,
+        // all of it left while the clock stands still
+        assert!(!host.idle_deadlines[0].did_timeout);
+        assert_eq!(host.idle_deadlines[0].deadline, now + MAX_IDLE_PERIOD);
+        assert_eq!(host.idle_deadlines[0].time_remaining_at(event_loop.now()), MAX_IDLE_PERIOD);
+
+        // With a frame pending, the idle period ends at the next frame
+        event_loop.request_idle_callback("idle".to_string(), None, now);
+        event_loop.request_render();
+        clock.advance(Duration::from_millis(5));
+        event_loop.run_iteration(&mut host, event_loop.now()).unwrap();
+        assert_eq!(host.idle_deadlines[1].deadline, now + event_loop.frame_interval);
+        assert_eq!(host.idle_deadlines[1].time_remaining_at(event_loop.now()), event_loop.frame_interval - Duration::from_millis(5));
+    }
+
+    #[test]
+    fn test_idle_callback_timeout_while_busy() {
+        let mut event_loop = EventLoop::new();
+        let mut host = RecordingHost {
+            requeue: Some(event_loop.handle()),
+            ..Default::default()
+        };
+        let now = Instant::now();
+
+        let cancelled = event_loop.request_idle_callback("cancelled".to_string(), None, now);
+        event_loop.request_idle_callback("forced".to_string(), Some(Duration::from_millis(10)), now);
+        event_loop.request_idle_callback("patient".to_string(), None, now);
+        event_loop.cancel_idle_callback(cancelled);
+        event_loop.queue_task(TaskSource::DomManipulation, TaskAction::Script("busy".to_string()));
+
+        // A busy loop never idles, so idle callbacks wait
+        let report = event_loop.run_iteration(&mut host, now).unwrap();
+        assert_eq!(report.idle_callbacks_run, 0);
+
+        // Until their timeout expires
+        let report = event_loop.run_iteration(&mut host, now + Duration::from_millis(10)).unwrap();
+        assert_eq!(report.idle_callbacks_run, 1);
+        assert_eq!(host.log, vec!["busy", "busy", "forced"]);
+        assert!(host.idle_deadlines[0].did_timeout);
+        assert_eq!(host.idle_deadlines[0].time_remaining(), Duration::ZERO);
+        assert!(event_loop.has_idle_work());
+    }
+}
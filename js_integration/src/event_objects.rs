@@ -0,0 +1,849 @@
+//! # Event Objects
+//!
+//! This module implements the event objects scripts make: the `Event`,
+//! `UIEvent`, `MouseEvent`, `PointerEvent`, `KeyboardEvent`, `FocusEvent`,
+//! `InputEvent`, `CompositionEvent`, `TouchEvent` and `CustomEvent`
+//! constructors along with `Touch`, and the legacy way older libraries
+//! still use, `document.createEvent()` followed by
+//! `initEvent()`, `initUIEvent()`, `initMouseEvent()`,
+//! `initKeyboardEvent()`, `initCompositionEvent()` or `initCustomEvent()`.
+//!
+//! ## Design Principles
+//!
+//! 1. **DOM Events Inside**: Every event object wraps an `AnyEvent` from
+//!    `dom::event_types` as native data, so an event made either way is the
+//!    same Rust value, and dispatch reads its flags from there.
+//! 2. **One Prototype per Interface**: Attributes are accessors on the
+//!    prototype of their interface, and the prototypes inherit like the
+//!    DOM's interfaces, so `instanceof` and subclasses of `Event` work.
+//! 3. **Initialized Before Dispatch**: `createEvent()` returns an event that
+//!    is not initialized, which cannot be dispatched until an init method
+//!    runs. Init methods do nothing while the event is being dispatched.
+
+use boa_engine::{
+    object::FunctionObjectBuilder,
+    property::{Attribute, PropertyDescriptor},
+    js_string, Context, JsData, JsError, JsNativeError, JsObject, JsResult, JsSymbol, JsValue, NativeFunction,
+};
+use boa_gc::{Finalize, Trace};
+use dom::event_types::{AnyEvent, EventInterface, KeyLocation};
+use crate::element_wrappers::dom_exception;
+
+/// DOMException code of `NotSupportedError`
+const NOT_SUPPORTED_ERR: u16 = 9;
+
+/// The touch lists of a `TouchEvent`, in the order of `touch_lists`
+const TOUCH_LISTS: [&str; 3] = ["touches", "targetTouches", "changedTouches"];
+
+/// Native data of an event object
+#[derive(Trace, Finalize, JsData)]
+pub(crate) struct EventData {
+    #[unsafe_ignore_trace]
+    pub(crate) event: AnyEvent,
+    /// `detail` of UI and custom events
+    pub(crate) detail: JsValue,
+    /// `view` of UI events
+    pub(crate) view: JsValue,
+    /// `relatedTarget` of mouse and focus events
+    pub(crate) related_target: JsValue,
+    /// `touches`, `targetTouches` and `changedTouches` of touch events
+    pub(crate) touch_lists: Vec<JsValue>,
+    pub(crate) target: JsValue,
+    pub(crate) current_target: JsValue,
+    /// Whether the event was constructed or an init method ran on it
+    pub(crate) initialized: bool,
+    pub(crate) dispatching: bool,
+    /// Whether a passive listener is running, which cannot cancel the event
+    pub(crate) in_passive_listener: bool,
+}
+
+impl EventData {
+    fn new(interface: EventInterface, event_type: &str, initialized: bool) -> Self {
+        let detail = match interface {
+            EventInterface::CustomEvent => JsValue::null(),
+            _ => JsValue::from(0),
+        };
+        EventData {
+            event: AnyEvent::new(interface, event_type, false, false),
+            detail,
+            view: JsValue::null(),
+            related_target: JsValue::null(),
+            touch_lists: Vec::new(),
+            target: JsValue::null(),
+            current_target: JsValue::null(),
+            initialized,
+            dispatching: false,
+            in_passive_listener: false,
+        }
+    }
+
+    /// Run the shared part of the init methods, returning `false` if the
+    /// event is being dispatched and must not change
+    fn init(&mut self, event_type: &str, bubbles: bool, cancelable: bool) -> bool {
+        if self.dispatching {
+            return false;
+        }
+        self.initialized = true;
+        self.event.base_mut().init_event(event_type, bubbles, cancelable);
+        self.target = JsValue::null();
+        true
+    }
+}
+
+/// The prototypes of the event interfaces, for `createEvent()`
+#[derive(Clone, Trace, Finalize)]
+pub struct EventPrototypes {
+    /// Prototypes in the order of `EventInterface::ALL`
+    prototypes: Vec<JsObject>,
+}
+
+impl EventPrototypes {
+    /// Get the prototype of an interface
+    fn get(&self, interface: EventInterface) -> JsObject {
+        let index = EventInterface::ALL.iter().position(|candidate| *candidate == interface).unwrap_or(0);
+        self.prototypes[index].clone()
+    }
+}
+
+/// Register the event constructors
+pub fn initialize_event_bindings(context: &mut Context) -> JsResult<EventPrototypes> {
+    let mut prototypes: Vec<JsObject> = Vec::new();
+    let mut constructors: Vec<JsObject> = Vec::new();
+    for interface in EventInterface::ALL {
+        let parent = interface.parent().and_then(|parent| EventInterface::ALL.iter().position(|candidate| *candidate == parent));
+        let prototype = JsObject::with_object_proto(context.intrinsics());
+        if let Some(parent) = parent {
+            prototype.set_prototype(Some(prototypes[parent].clone()));
+        }
+        install_members(interface, &prototype, context)?;
+
+        let construct = NativeFunction::from_copy_closure_with_captures(
+            |this, args, binding: &ConstructorBinding, context| construct_event(binding, this, args, context),
+            ConstructorBinding { interface, prototype: prototype.clone() },
+        );
+        let constructor = FunctionObjectBuilder::new(context.realm(), construct)
+            .name(js_string!(interface.name()))
+            .length(1)
+            .constructor(true)
+            .build();
+        if let Some(parent) = parent {
+            constructor.set_prototype(Some(constructors[parent].clone()));
+        }
+        constructor.define_property_or_throw(
+            js_string!("prototype"),
+            PropertyDescriptor::builder().value(prototype.clone()).writable(false).enumerable(false).configurable(false),
+            context,
+        )?;
+        prototype.define_property_or_throw(
+            js_string!("constructor"),
+            PropertyDescriptor::builder().value(constructor.clone()).writable(true).enumerable(false).configurable(true),
+            context,
+        )?;
+        prototype.define_property_or_throw(
+            JsSymbol::to_string_tag(),
+            PropertyDescriptor::builder().value(js_string!(interface.name())).writable(false).enumerable(false).configurable(true),
+            context,
+        )?;
+        if interface == EventInterface::Event {
+            for object in [&constructor, &prototype] {
+                for (name, phase) in [("NONE", 0), ("CAPTURING_PHASE", 1), ("AT_TARGET", 2), ("BUBBLING_PHASE", 3)] {
+                    object.define_property_or_throw(
+                        js_string!(name),
+                        PropertyDescriptor::builder().value(phase).writable(false).enumerable(true).configurable(false),
+                        context,
+                    )?;
+                }
+            }
+        }
+        context.register_global_property(js_string!(interface.name()), constructor.clone(), Attribute::WRITABLE | Attribute::CONFIGURABLE)?;
+        prototypes.push(prototype);
+        constructors.push(constructor.into());
+    }
+
+    let touch = FunctionObjectBuilder::new(context.realm(), NativeFunction::from_fn_ptr(construct_touch))
+        .name(js_string!("Touch"))
+        .length(1)
+        .constructor(true)
+        .build();
+    context.register_global_property(js_string!("Touch"), touch, Attribute::WRITABLE | Attribute::CONFIGURABLE)?;
+    Ok(EventPrototypes { prototypes })
+}
+
+/// `new Touch(init)`, a contact for the touch lists of a `TouchEvent`
+///
+/// Touches are plain objects with read-only attributes.
+fn construct_touch(new_target: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+    if new_target.is_undefined() {
+        return Err(JsNativeError::typ().with_message("Failed to construct 'Touch': Please use the 'new' operator").into());
+    }
+    let init = argument(args, 0);
+    let identifier = member(&init, "identifier", context)?;
+    let target = member(&init, "target", context)?;
+    if identifier.is_undefined() || !target.is_object() {
+        return Err(JsNativeError::typ()
+            .with_message("Failed to construct 'Touch': required members identifier and target are missing")
+            .into());
+    }
+    let touch = JsObject::with_object_proto(context.intrinsics());
+    let define = |name: &str, value: JsValue, context: &mut Context| {
+        touch.define_property_or_throw(
+            js_string!(name),
+            PropertyDescriptor::builder().value(value).writable(false).enumerable(true).configurable(true),
+            context,
+        )
+    };
+    define("identifier", identifier.to_i32(context)?.into(), context)?;
+    define("target", target, context)?;
+    for (name, fallback) in [
+        ("clientX", 0.0), ("clientY", 0.0), ("screenX", 0.0), ("screenY", 0.0), ("pageX", 0.0), ("pageY", 0.0),
+        ("radiusX", 0.0), ("radiusY", 0.0), ("rotationAngle", 0.0), ("force", 0.0),
+    ] {
+        let value = member(&init, name, context)?;
+        let number = if value.is_undefined() { fallback } else { value.to_number(context)? };
+        define(name, number.into(), context)?;
+    }
+    Ok(touch.into())
+}
+
+/// Create a `TouchList`: an array-like of touches with `item()`
+fn touch_list(touches: Vec<JsValue>, context: &mut Context) -> JsObject {
+    let list = JsObject::with_object_proto(context.intrinsics());
+    let length = touches.len();
+    for (index, touch) in touches.into_iter().enumerate() {
+        let _ = list.define_property_or_throw(index, PropertyDescriptor::builder().value(touch).writable(false).enumerable(true).configurable(false), context);
+    }
+    let _ = list.define_property_or_throw(js_string!("length"), PropertyDescriptor::builder().value(length).writable(false).enumerable(false).configurable(false), context);
+    let item = NativeFunction::from_copy_closure(|this, args, context| {
+        let Some(list) = this.as_object() else {
+            return Err(illegal_invocation());
+        };
+        let index = argument(args, 0).to_u32(context)?;
+        let value = list.get(index, context)?;
+        Ok(if value.is_undefined() { JsValue::null() } else { value })
+    });
+    let item = item.to_js_function(context.realm());
+    let _ = list.define_property_or_throw(js_string!("item"), PropertyDescriptor::builder().value(item).writable(true).enumerable(false).configurable(true), context);
+    list
+}
+
+/// Create an uninitialized event, like `document.createEvent()`
+///
+/// Throws a `NotSupportedError` for names without an interface.
+pub fn create_event(prototypes: &EventPrototypes, name: &str, context: &mut Context) -> JsResult<JsObject> {
+    let interface = EventInterface::from_legacy_name(name).ok_or_else(|| {
+        dom_exception(context, "NotSupportedError", &format!("The provided event type ('{}') is invalid", name), NOT_SUPPORTED_ERR)
+    })?;
+    let mut data = EventData::new(interface, "", false);
+    if interface == EventInterface::TouchEvent {
+        data.touch_lists = (0..TOUCH_LISTS.len()).map(|_| touch_list(Vec::new(), context).into()).collect();
+    }
+    Ok(JsObject::from_proto_and_data(prototypes.get(interface), data))
+}
+
+/// Create an initialized event fired by the user agent, whose `isTrusted`
+/// is true
+pub fn create_trusted_event(prototypes: &EventPrototypes, interface: EventInterface, event_type: &str, bubbles: bool, cancelable: bool) -> JsObject {
+    let mut data = EventData::new(interface, event_type, true);
+    data.event = AnyEvent::new(interface, event_type, bubbles, cancelable);
+    data.event.base_mut().is_trusted = true;
+    JsObject::from_proto_and_data(prototypes.get(interface), data)
+}
+
+/// Captures of an event constructor
+#[derive(Clone, Trace, Finalize)]
+struct ConstructorBinding {
+    #[unsafe_ignore_trace]
+    interface: EventInterface,
+    prototype: JsObject,
+}
+
+/// `new Event(type, init)` and the constructors of the other interfaces
+fn construct_event(binding: &ConstructorBinding, new_target: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+    let name = binding.interface.name();
+    let Some(new_target) = new_target.as_object() else {
+        return Err(JsNativeError::typ()
+            .with_message(format!("Failed to construct '{}': Please use the 'new' operator", name))
+            .into());
+    };
+    let Some(event_type) = args.first() else {
+        return Err(JsNativeError::typ()
+            .with_message(format!("Failed to construct '{}': 1 argument required, but only 0 present", name))
+            .into());
+    };
+    let event_type = event_type.to_string(context)?.to_std_string_escaped();
+    let init = args.get(1).cloned().unwrap_or_default();
+
+    let mut data = EventData::new(binding.interface, &event_type, true);
+    let bubbles = member(&init, "bubbles", context)?.to_boolean();
+    let cancelable = member(&init, "cancelable", context)?.to_boolean();
+    data.event.base_mut().init_event(&event_type, bubbles, cancelable);
+    if binding.interface != EventInterface::Event {
+        let detail = member(&init, "detail", context)?;
+        if !detail.is_undefined() {
+            data.detail = detail;
+        }
+        data.view = member(&init, "view", context)?;
+        if data.view.is_undefined() {
+            data.view = JsValue::null();
+        }
+        data.related_target = member(&init, "relatedTarget", context)?;
+        if data.related_target.is_undefined() {
+            data.related_target = JsValue::null();
+        }
+    }
+    let modifiers = [
+        member(&init, "ctrlKey", context)?.to_boolean(),
+        member(&init, "shiftKey", context)?.to_boolean(),
+        member(&init, "altKey", context)?.to_boolean(),
+        member(&init, "metaKey", context)?.to_boolean(),
+    ];
+    if let AnyEvent::Pointer(pointer) = &mut data.event {
+        pointer.pointer_id = member(&init, "pointerId", context)?.to_i32(context)?;
+        pointer.pointer_type = string_member(&init, "pointerType", context)?;
+        pointer.pressure = member(&init, "pressure", context)?.to_number(context).map(zero_nan)? as f32;
+        for (name, size) in [("width", &mut pointer.width), ("height", &mut pointer.height)] {
+            let value = member(&init, name, context)?;
+            if !value.is_undefined() {
+                *size = value.to_number(context)?;
+            }
+        }
+        pointer.is_primary = member(&init, "isPrimary", context)?.to_boolean();
+    }
+    match &mut data.event {
+        AnyEvent::Mouse(mouse) | AnyEvent::Pointer(dom::event_types::PointerEvent { mouse, .. }) => {
+            mouse.screen_x = member(&init, "screenX", context)?.to_number(context).map(zero_nan)?;
+            mouse.screen_y = member(&init, "screenY", context)?.to_number(context).map(zero_nan)?;
+            mouse.client_x = member(&init, "clientX", context)?.to_number(context).map(zero_nan)?;
+            mouse.client_y = member(&init, "clientY", context)?.to_number(context).map(zero_nan)?;
+            mouse.button = member(&init, "button", context)?.to_i32(context)?;
+            mouse.buttons = member(&init, "buttons", context)?.to_u32(context)?;
+            [mouse.ctrl_key, mouse.shift_key, mouse.alt_key, mouse.meta_key] = modifiers;
+        }
+        AnyEvent::Keyboard(keyboard) => {
+            keyboard.key = string_member(&init, "key", context)?;
+            keyboard.code = string_member(&init, "code", context)?;
+            keyboard.location = key_location(member(&init, "location", context)?.to_u32(context)?);
+            keyboard.key_code = member(&init, "keyCode", context)?.to_u32(context)?;
+            keyboard.char_code = member(&init, "charCode", context)?.to_u32(context)?;
+            keyboard.repeat = member(&init, "repeat", context)?.to_boolean();
+            keyboard.is_composing = member(&init, "isComposing", context)?.to_boolean();
+            [keyboard.ctrl_key, keyboard.shift_key, keyboard.alt_key, keyboard.meta_key] = modifiers;
+        }
+        AnyEvent::Input(input) => {
+            let value = member(&init, "data", context)?;
+            input.data = if value.is_null_or_undefined() { None } else { Some(value.to_string(context)?.to_std_string_escaped()) };
+            input.input_type = string_member(&init, "inputType", context)?;
+            input.is_composing = member(&init, "isComposing", context)?.to_boolean();
+        }
+        AnyEvent::Composition(composition) => {
+            composition.data = string_member(&init, "data", context)?;
+        }
+        AnyEvent::Touch(touch) => {
+            [touch.ctrl_key, touch.shift_key, touch.alt_key, touch.meta_key] = modifiers;
+            for name in TOUCH_LISTS {
+                let touches = member(&init, name, context)?;
+                let items = match touches.as_object() {
+                    Some(touches) => {
+                        let length = touches.get(js_string!("length"), context)?.to_u32(context)?;
+                        (0..length).map(|index| touches.get(index, context)).collect::<JsResult<Vec<_>>>()?
+                    }
+                    None => Vec::new(),
+                };
+                data.touch_lists.push(touch_list(items, context).into());
+            }
+        }
+        _ => {}
+    }
+
+    let prototype = match new_target.get(js_string!("prototype"), context)?.as_object() {
+        Some(prototype) => prototype.clone(),
+        None => binding.prototype.clone(),
+    };
+    Ok(JsObject::from_proto_and_data(prototype, data).into())
+}
+
+/// Define the attributes and methods of an interface on its prototype
+fn install_members(interface: EventInterface, prototype: &JsObject, context: &mut Context) -> JsResult<()> {
+    let getters: &[(&str, Getter)] = match interface {
+        EventInterface::Event => &[
+            ("type", |data| Some(js_string!(data.event.base().event_type.as_str()).into())),
+            ("bubbles", |data| Some(data.event.base().bubbles.into())),
+            ("cancelable", |data| Some(data.event.base().cancelable.into())),
+            ("defaultPrevented", |data| Some(data.event.base().default_prevented.into())),
+            ("eventPhase", |data| Some((data.event.base().phase as u8).into())),
+            ("timeStamp", |data| Some(data.event.base().timestamp.into())),
+            ("isTrusted", |data| Some(data.event.base().is_trusted.into())),
+            ("composed", |_| Some(false.into())),
+            ("target", |data| Some(data.target.clone())),
+            ("srcElement", |data| Some(data.target.clone())),
+            ("currentTarget", |data| Some(data.current_target.clone())),
+        ],
+        EventInterface::UiEvent => &[
+            ("view", |data| Some(data.view.clone())),
+            ("detail", |data| Some(data.detail.clone())),
+            ("which", |data| match &data.event {
+                AnyEvent::Keyboard(keyboard) => Some(keyboard.key_code.into()),
+                _ => Some(mouse(data).map_or(0, |mouse| mouse.button.max(-1) + 1).into()),
+            }),
+        ],
+        EventInterface::MouseEvent => &[
+            ("screenX", |data| mouse(data).map(|mouse| mouse.screen_x.into())),
+            ("screenY", |data| mouse(data).map(|mouse| mouse.screen_y.into())),
+            ("clientX", |data| mouse(data).map(|mouse| mouse.client_x.into())),
+            ("clientY", |data| mouse(data).map(|mouse| mouse.client_y.into())),
+            ("pageX", |data| mouse(data).map(|mouse| mouse.client_x.into())),
+            ("pageY", |data| mouse(data).map(|mouse| mouse.client_y.into())),
+            ("button", |data| mouse(data).map(|mouse| mouse.button.max(0).into())),
+            ("buttons", |data| mouse(data).map(|mouse| mouse.buttons.into())),
+            ("ctrlKey", |data| mouse(data).map(|mouse| mouse.ctrl_key.into())),
+            ("shiftKey", |data| mouse(data).map(|mouse| mouse.shift_key.into())),
+            ("altKey", |data| mouse(data).map(|mouse| mouse.alt_key.into())),
+            ("metaKey", |data| mouse(data).map(|mouse| mouse.meta_key.into())),
+            ("relatedTarget", |data| mouse(data).map(|_| data.related_target.clone())),
+        ],
+        EventInterface::PointerEvent => &[
+            ("pointerId", |data| pointer(data).map(|pointer| pointer.pointer_id.into())),
+            ("pointerType", |data| pointer(data).map(|pointer| js_string!(pointer.pointer_type.as_str()).into())),
+            ("pressure", |data| pointer(data).map(|pointer| f64::from(pointer.pressure).into())),
+            ("tangentialPressure", |data| pointer(data).map(|_| 0.into())),
+            ("width", |data| pointer(data).map(|pointer| pointer.width.into())),
+            ("height", |data| pointer(data).map(|pointer| pointer.height.into())),
+            ("tiltX", |data| pointer(data).map(|_| 0.into())),
+            ("tiltY", |data| pointer(data).map(|_| 0.into())),
+            ("twist", |data| pointer(data).map(|_| 0.into())),
+            ("isPrimary", |data| pointer(data).map(|pointer| pointer.is_primary.into())),
+        ],
+        EventInterface::KeyboardEvent => &[
+            ("key", |data| keyboard(data).map(|keyboard| js_string!(keyboard.key.as_str()).into())),
+            ("code", |data| keyboard(data).map(|keyboard| js_string!(keyboard.code.as_str()).into())),
+            ("location", |data| keyboard(data).map(|keyboard| (keyboard.location as u32).into())),
+            ("keyCode", |data| keyboard(data).map(|keyboard| keyboard.key_code.into())),
+            ("charCode", |data| keyboard(data).map(|keyboard| keyboard.char_code.into())),
+            ("ctrlKey", |data| keyboard(data).map(|keyboard| keyboard.ctrl_key.into())),
+            ("shiftKey", |data| keyboard(data).map(|keyboard| keyboard.shift_key.into())),
+            ("altKey", |data| keyboard(data).map(|keyboard| keyboard.alt_key.into())),
+            ("metaKey", |data| keyboard(data).map(|keyboard| keyboard.meta_key.into())),
+            ("repeat", |data| keyboard(data).map(|keyboard| keyboard.repeat.into())),
+            ("isComposing", |data| keyboard(data).map(|keyboard| keyboard.is_composing.into())),
+        ],
+        EventInterface::FocusEvent => &[
+            ("relatedTarget", |data| matches!(data.event, AnyEvent::Focus(_)).then(|| data.related_target.clone())),
+        ],
+        EventInterface::InputEvent => &[
+            ("data", |data| match &data.event {
+                AnyEvent::Input(input) => Some(input.data.as_deref().map_or(JsValue::null(), |text| js_string!(text).into())),
+                _ => None,
+            }),
+            ("inputType", |data| match &data.event {
+                AnyEvent::Input(input) => Some(js_string!(input.input_type.as_str()).into()),
+                _ => None,
+            }),
+            ("isComposing", |data| match &data.event {
+                AnyEvent::Input(input) => Some(input.is_composing.into()),
+                _ => None,
+            }),
+        ],
+        EventInterface::CompositionEvent => &[
+            ("data", |data| match &data.event {
+                AnyEvent::Composition(composition) => Some(js_string!(composition.data.as_str()).into()),
+                _ => None,
+            }),
+        ],
+        EventInterface::TouchEvent => &[
+            ("touches", |data| touch(data).map(|_| data.touch_lists.first().cloned().unwrap_or_else(JsValue::null))),
+            ("targetTouches", |data| touch(data).map(|_| data.touch_lists.get(1).cloned().unwrap_or_else(JsValue::null))),
+            ("changedTouches", |data| touch(data).map(|_| data.touch_lists.get(2).cloned().unwrap_or_else(JsValue::null))),
+            ("ctrlKey", |data| touch(data).map(|touch| touch.ctrl_key.into())),
+            ("shiftKey", |data| touch(data).map(|touch| touch.shift_key.into())),
+            ("altKey", |data| touch(data).map(|touch| touch.alt_key.into())),
+            ("metaKey", |data| touch(data).map(|touch| touch.meta_key.into())),
+        ],
+        EventInterface::CustomEvent => &[
+            ("detail", |data| matches!(data.event, AnyEvent::Custom(_)).then(|| data.detail.clone())),
+        ],
+    };
+    for (name, read) in getters {
+        let read = *read;
+        let getter = NativeFunction::from_copy_closure(move |this, _args, _context| {
+            let object = this_event(this)?;
+            let data = object.downcast_ref::<EventData>().ok_or_else(illegal_invocation)?;
+            read(&data).ok_or_else(illegal_invocation)
+        });
+        define_accessor(prototype, name, getter, None, context)?;
+    }
+
+    let methods: &[(&str, usize, Method)] = match interface {
+        EventInterface::Event => &[
+            ("preventDefault", 0, |object, _args, _context| {
+                with_data(object, |data| {
+                    if !data.in_passive_listener {
+                        data.event.base_mut().prevent_default();
+                    }
+                })?;
+                Ok(JsValue::undefined())
+            }),
+            ("stopPropagation", 0, |object, _args, _context| {
+                with_data(object, |data| data.event.base_mut().stop_propagation())?;
+                Ok(JsValue::undefined())
+            }),
+            ("stopImmediatePropagation", 0, |object, _args, _context| {
+                with_data(object, |data| data.event.base_mut().stop_immediate_propagation())?;
+                Ok(JsValue::undefined())
+            }),
+            ("initEvent", 1, |object, args, context| {
+                let (event_type, bubbles, cancelable) = init_arguments("initEvent", args, context)?;
+                with_data(object, |data| data.init(&event_type, bubbles, cancelable))?;
+                Ok(JsValue::undefined())
+            }),
+        ],
+        EventInterface::UiEvent => &[
+            ("initUIEvent", 1, |object, args, context| {
+                let (event_type, bubbles, cancelable) = init_arguments("initUIEvent", args, context)?;
+                let detail = argument(args, 4).to_i32(context)?;
+                with_data(object, |data| {
+                    if data.init(&event_type, bubbles, cancelable) {
+                        data.view = nullable(argument(args, 3));
+                        data.detail = detail.into();
+                    }
+                })?;
+                Ok(JsValue::undefined())
+            }),
+        ],
+        EventInterface::MouseEvent => &[
+            ("initMouseEvent", 1, |object, args, context| {
+                let (event_type, bubbles, cancelable) = init_arguments("initMouseEvent", args, context)?;
+                let detail = argument(args, 4).to_i32(context)?;
+                let mut coordinates = [0.0; 4];
+                for (offset, coordinate) in coordinates.iter_mut().enumerate() {
+                    *coordinate = zero_nan(argument(args, 5 + offset).to_number(context)?);
+                }
+                let button = argument(args, 13).to_i32(context)?;
+                with_data(object, |data| {
+                    if mouse(data).is_none() {
+                        return Err(illegal_invocation());
+                    }
+                    if data.init(&event_type, bubbles, cancelable) {
+                        data.view = nullable(argument(args, 3));
+                        data.detail = detail.into();
+                        data.related_target = nullable(argument(args, 14));
+                        if let AnyEvent::Mouse(mouse) | AnyEvent::Pointer(dom::event_types::PointerEvent { mouse, .. }) = &mut data.event {
+                            [mouse.screen_x, mouse.screen_y, mouse.client_x, mouse.client_y] = coordinates;
+                            mouse.ctrl_key = argument(args, 9).to_boolean();
+                            mouse.alt_key = argument(args, 10).to_boolean();
+                            mouse.shift_key = argument(args, 11).to_boolean();
+                            mouse.meta_key = argument(args, 12).to_boolean();
+                            mouse.button = button;
+                        }
+                    }
+                    Ok(JsValue::undefined())
+                })?
+            }),
+            ("getModifierState", 1, |object, args, context| {
+                let key = argument(args, 0).to_string(context)?.to_std_string_escaped();
+                with_data(object, |data| {
+                    let mouse = mouse(data).ok_or_else(illegal_invocation)?;
+                    let state = match key.as_str() {
+                        "Control" => mouse.ctrl_key,
+                        "Shift" => mouse.shift_key,
+                        "Alt" => mouse.alt_key,
+                        "Meta" => mouse.meta_key,
+                        _ => false,
+                    };
+                    Ok(state.into())
+                })?
+            }),
+        ],
+        EventInterface::KeyboardEvent => &[
+            ("initKeyboardEvent", 1, |object, args, context| {
+                let (event_type, bubbles, cancelable) = init_arguments("initKeyboardEvent", args, context)?;
+                let key = argument(args, 4).to_string(context)?.to_std_string_escaped();
+                let location = key_location(argument(args, 5).to_u32(context)?);
+                with_data(object, |data| {
+                    if !matches!(data.event, AnyEvent::Keyboard(_)) {
+                        return Err(illegal_invocation());
+                    }
+                    if data.init(&event_type, bubbles, cancelable) {
+                        data.view = nullable(argument(args, 3));
+                        if let AnyEvent::Keyboard(keyboard) = &mut data.event {
+                            keyboard.key = key;
+                            keyboard.location = location;
+                            keyboard.ctrl_key = argument(args, 6).to_boolean();
+                            keyboard.alt_key = argument(args, 7).to_boolean();
+                            keyboard.shift_key = argument(args, 8).to_boolean();
+                            keyboard.meta_key = argument(args, 9).to_boolean();
+                        }
+                    }
+                    Ok(JsValue::undefined())
+                })?
+            }),
+            ("getModifierState", 1, |object, args, context| {
+                let key = argument(args, 0).to_string(context)?.to_std_string_escaped();
+                with_data(object, |data| Ok(keyboard(data).ok_or_else(illegal_invocation)?.get_modifier_state(&key).into()))?
+            }),
+        ],
+        EventInterface::CompositionEvent => &[
+            ("initCompositionEvent", 1, |object, args, context| {
+                let (event_type, bubbles, cancelable) = init_arguments("initCompositionEvent", args, context)?;
+                let text = argument(args, 4);
+                let text = if text.is_undefined() { String::new() } else { text.to_string(context)?.to_std_string_escaped() };
+                with_data(object, |data| {
+                    if !matches!(data.event, AnyEvent::Composition(_)) {
+                        return Err(illegal_invocation());
+                    }
+                    if data.init(&event_type, bubbles, cancelable) {
+                        data.view = nullable(argument(args, 3));
+                        if let AnyEvent::Composition(composition) = &mut data.event {
+                            composition.data = text;
+                        }
+                    }
+                    Ok(JsValue::undefined())
+                })?
+            }),
+        ],
+        EventInterface::CustomEvent => &[
+            ("initCustomEvent", 1, |object, args, context| {
+                let (event_type, bubbles, cancelable) = init_arguments("initCustomEvent", args, context)?;
+                with_data(object, |data| {
+                    if !matches!(data.event, AnyEvent::Custom(_)) {
+                        return Err(illegal_invocation());
+                    }
+                    if data.init(&event_type, bubbles, cancelable) {
+                        data.detail = nullable(argument(args, 3));
+                    }
+                    Ok(JsValue::undefined())
+                })?
+            }),
+        ],
+        EventInterface::PointerEvent => &[
+            ("getCoalescedEvents", 0, |object, _args, context| {
+                with_data(object, |data| pointer(data).map(|_| ()).ok_or_else(illegal_invocation))??;
+                Ok(boa_engine::object::builtins::JsArray::new(context).into())
+            }),
+        ],
+        EventInterface::FocusEvent | EventInterface::InputEvent | EventInterface::TouchEvent => &[],
+    };
+    for (name, length, method) in methods {
+        let method = *method;
+        let function = NativeFunction::from_copy_closure(move |this, args, context| {
+            method(this_event(this)?, args, context)
+        });
+        prototype.define_property_or_throw(
+            js_string!(*name),
+            PropertyDescriptor::builder()
+                .value(FunctionObjectBuilder::new(context.realm(), function).name(js_string!(*name)).length(*length).build())
+                .writable(true)
+                .enumerable(true)
+                .configurable(true),
+            context,
+        )?;
+    }
+
+    if interface == EventInterface::Event {
+        // `returnValue` and `cancelBubble` are the legacy spellings of
+        // `defaultPrevented` and `stopPropagation()`
+        let return_value = NativeFunction::from_copy_closure(|this, _args, _context| {
+            let object = this_event(this)?;
+            let data = object.downcast_ref::<EventData>().ok_or_else(illegal_invocation)?;
+            Ok((!data.event.base().default_prevented).into())
+        });
+        let set_return_value = NativeFunction::from_copy_closure(|this, args, _context| {
+            let object = this_event(this)?;
+            let mut data = object.downcast_mut::<EventData>().ok_or_else(illegal_invocation)?;
+            if !argument(args, 0).to_boolean() && !data.in_passive_listener {
+                data.event.base_mut().prevent_default();
+            }
+            Ok(JsValue::undefined())
+        });
+        define_accessor(prototype, "returnValue", return_value, Some(set_return_value), context)?;
+        let cancel_bubble = NativeFunction::from_copy_closure(|this, _args, _context| {
+            let object = this_event(this)?;
+            let data = object.downcast_ref::<EventData>().ok_or_else(illegal_invocation)?;
+            Ok(data.event.base().propagation_stopped.into())
+        });
+        let set_cancel_bubble = NativeFunction::from_copy_closure(|this, args, _context| {
+            let object = this_event(this)?;
+            let mut data = object.downcast_mut::<EventData>().ok_or_else(illegal_invocation)?;
+            if argument(args, 0).to_boolean() {
+                data.event.base_mut().stop_propagation();
+            }
+            Ok(JsValue::undefined())
+        });
+        define_accessor(prototype, "cancelBubble", cancel_bubble, Some(set_cancel_bubble), context)?;
+    }
+    Ok(())
+}
+
+/// Reads an attribute, or `None` if the event lacks the interface
+type Getter = fn(&EventData) -> Option<JsValue>;
+
+/// Runs a method on an event object
+///
+/// Methods convert their arguments before borrowing the native data, since
+/// conversions can run scripts that read the event.
+type Method = fn(&JsObject, &[JsValue], &mut Context) -> JsResult<JsValue>;
+
+/// Define an accessor property on a prototype
+fn define_accessor(prototype: &JsObject, name: &str, getter: NativeFunction, setter: Option<NativeFunction>, context: &mut Context) -> JsResult<()> {
+    let mut descriptor = PropertyDescriptor::builder()
+        .get(getter.to_js_function(context.realm()))
+        .enumerable(true)
+        .configurable(true);
+    if let Some(setter) = setter {
+        descriptor = descriptor.set(setter.to_js_function(context.realm()));
+    }
+    prototype.define_property_or_throw(js_string!(name), descriptor, context)?;
+    Ok(())
+}
+
+/// Run a closure on the native data of an event object
+fn with_data<R>(object: &JsObject, f: impl FnOnce(&mut EventData) -> R) -> JsResult<R> {
+    let mut data = object.downcast_mut::<EventData>().ok_or_else(illegal_invocation)?;
+    Ok(f(&mut data))
+}
+
+/// Resolve the event object a member was called on
+fn this_event(this: &JsValue) -> JsResult<&JsObject> {
+    this.as_object().filter(|object| object.downcast_ref::<EventData>().is_some()).ok_or_else(illegal_invocation)
+}
+
+fn illegal_invocation() -> JsError {
+    JsNativeError::typ().with_message("Illegal invocation").into()
+}
+
+fn mouse(data: &EventData) -> Option<&dom::event_types::MouseEvent> {
+    match &data.event {
+        AnyEvent::Mouse(mouse) => Some(mouse),
+        AnyEvent::Pointer(pointer) => Some(&pointer.mouse),
+        _ => None,
+    }
+}
+
+fn pointer(data: &EventData) -> Option<&dom::event_types::PointerEvent> {
+    match &data.event {
+        AnyEvent::Pointer(pointer) => Some(pointer),
+        _ => None,
+    }
+}
+
+fn touch(data: &EventData) -> Option<&dom::event_types::TouchEvent> {
+    match &data.event {
+        AnyEvent::Touch(touch) => Some(touch),
+        _ => None,
+    }
+}
+
+fn keyboard(data: &EventData) -> Option<&dom::event_types::KeyboardEvent> {
+    match &data.event {
+        AnyEvent::Keyboard(keyboard) => Some(keyboard),
+        _ => None,
+    }
+}
+
+/// Get an argument, `undefined` when missing
+fn argument(args: &[JsValue], index: usize) -> JsValue {
+    args.get(index).cloned().unwrap_or_default()
+}
+
+/// Convert `undefined` to `null`, for nullable object arguments
+fn nullable(value: JsValue) -> JsValue {
+    if value.is_undefined() {
+        JsValue::null()
+    } else {
+        value
+    }
+}
+
+/// Read the type, bubbles and cancelable arguments every init method starts with
+fn init_arguments(method: &str, args: &[JsValue], context: &mut Context) -> JsResult<(String, bool, bool)> {
+    let Some(event_type) = args.first() else {
+        return Err(JsNativeError::typ()
+            .with_message(format!("Failed to execute '{}': 1 argument required, but only 0 present", method))
+            .into());
+    };
+    let event_type = event_type.to_string(context)?.to_std_string_escaped();
+    Ok((event_type, argument(args, 1).to_boolean(), argument(args, 2).to_boolean()))
+}
+
+/// Read a member of an init dictionary, `undefined` when missing
+fn member(init: &JsValue, name: &str, context: &mut Context) -> JsResult<JsValue> {
+    match init.as_object() {
+        Some(init) => init.get(js_string!(name), context),
+        None => Ok(JsValue::undefined()),
+    }
+}
+
+/// Read a string member of an init dictionary, empty when missing
+fn string_member(init: &JsValue, name: &str, context: &mut Context) -> JsResult<String> {
+    let value = member(init, name, context)?;
+    if value.is_undefined() {
+        return Ok(String::new());
+    }
+    Ok(value.to_string(context)?.to_std_string_escaped())
+}
+
+/// Treat `NaN` from a missing number as zero, as WebIDL `double` defaults do
+fn zero_nan(number: f64) -> f64 {
+    if number.is_nan() {
+        0.0
+    } else {
+        number
+    }
+}
+
+fn key_location(location: u32) -> KeyLocation {
+    match location {
+        1 => KeyLocation::Left,
+        2 => KeyLocation::Right,
+        3 => KeyLocation::Numpad,
+        _ => KeyLocation::Standard,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use boa_engine::Source;
+
+    #[test]
+    fn test_constructors_and_legacy_creation() {
+        let mut context = Context::default();
+        initialize_event_bindings(&mut context).unwrap();
+        let code = "const click = new MouseEvent('click', { bubbles: true, clientX: 5, ctrlKey: true, button: 1 });
+            const custom = new CustomEvent('done', { detail: { id: 7 } });
+            class Ping extends Event {}
+            [
+                click instanceof UIEvent && click instanceof Event, click.type, click.bubbles, click.cancelable,
+                click.clientX, click.ctrlKey, click.which, click.getModifierState('Control'),
+                custom.detail.id, new Ping('ping') instanceof Ping, Object.prototype.toString.call(click),
+                new KeyboardEvent('keydown', { key: 'a', keyCode: 65 }).which, Event.BUBBLING_PHASE,
+            ].join()";
+        let result = context.eval(Source::from_bytes(code)).unwrap();
+        assert_eq!(
+            result.to_string(&mut context).unwrap().to_std_string_escaped(),
+            "true,click,true,false,5,true,2,true,7,true,[object MouseEvent],65,3"
+        );
+
+        let error = context.eval(Source::from_bytes("Event('x')")).unwrap_err();
+        assert!(error.to_string().contains("new"));
+        let error = context.eval(Source::from_bytes("MouseEvent.prototype.clientX")).unwrap_err();
+        assert!(error.to_string().contains("Illegal invocation"));
+    }
+
+    #[test]
+    fn test_touch_events_carry_touch_lists() {
+        let mut context = Context::default();
+        initialize_event_bindings(&mut context).unwrap();
+        let code = "const target = {};
+            const finger = new Touch({ identifier: 3, target, clientX: 12, force: 0.5 });
+            const start = new TouchEvent('touchstart', { touches: [finger], changedTouches: [finger], shiftKey: true });
+            let missing;
+            try { new Touch({ identifier: 1 }); } catch (error) { missing = error.name; }
+            [
+                start instanceof UIEvent, start.touches.length, start.touches.item(0) === finger, start.touches.item(1),
+                start.targetTouches.length, start.changedTouches[0].clientX, start.changedTouches[0].target === target,
+                start.shiftKey, finger.force, missing,
+            ].join()";
+        let result = context.eval(Source::from_bytes(code)).unwrap();
+        assert_eq!(result.to_string(&mut context).unwrap().to_std_string_escaped(), "true,1,true,,0,12,true,true,0.5,TypeError");
+    }
+}
@@ -0,0 +1,481 @@
+//! # Event Targets
+//!
+//! This module implements `addEventListener()`, `removeEventListener()` and
+//! `dispatchEvent()` for node wrappers, the `document` object and `window`.
+//! Events made by `event_objects` travel through the target's ancestors in
+//! a capture pass and a bubble pass, as in the DOM, so code that triggers
+//! events itself, like jQuery plugins and older libraries, sees the same
+//! propagation as with events from the user. Events in the document reach
+//! `window` after the document, except `load`.
+//!
+//! ## Design Principles
+//!
+//! 1. **Listeners by Node Id**: Listeners are kept per node id, so every
+//!    wrapper of a node shares them and they stay with a node adopted into
+//!    another document. `window` uses id 0, which no node has.
+//! 2. **Fixed Path, Live Removal**: The propagation path and each node's
+//!    listeners are taken before they run, so listeners that move nodes or
+//!    add listeners do not change the dispatch in progress, but a removed
+//!    listener never runs.
+//! 3. **Errors Stay in Listeners**: A throwing listener is reported and the
+//!    remaining listeners still run; `dispatchEvent()` only throws for an
+//!    event that cannot be dispatched.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use boa_engine::{js_string, Context, JsNativeError, JsObject, JsResult, JsValue, NativeFunction};
+use boa_gc::{Finalize, Trace};
+use dom::event_types::EventPhase;
+use dom::Node;
+use crate::element_wrappers::{define_method, dom_exception, ElementWrappers};
+use crate::event_objects::EventData;
+
+/// DOMException code of `InvalidStateError`
+const INVALID_STATE_ERR: u16 = 11;
+
+/// The listener key of `window`; node ids start at 1
+const WINDOW: u64 = 0;
+
+/// A listener a script added to a node
+struct ScriptListener {
+    event_type: String,
+    callback: JsObject,
+    capture: bool,
+    once: bool,
+    passive: bool,
+    removed: Cell<bool>,
+}
+
+#[derive(Default)]
+struct ListenerState {
+    listeners: HashMap<u64, Vec<Rc<ScriptListener>>>,
+    /// The id of the document's root and the `document` object standing
+    /// for it as a current target
+    document: Option<(u64, JsObject)>,
+    /// The global object, the current target of `window` listeners
+    window: Option<JsObject>,
+}
+
+/// The listeners scripts added to nodes
+#[derive(Clone, Default)]
+pub struct EventListeners {
+    state: Rc<RefCell<ListenerState>>,
+}
+
+impl EventListeners {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the number of listeners for a type on a node
+    pub fn count(&self, node: &Node, event_type: &str) -> usize {
+        self.state
+            .borrow()
+            .listeners
+            .get(&node.id)
+            .map_or(0, |listeners| listeners.iter().filter(|listener| listener.event_type == event_type).count())
+    }
+
+    fn add(&self, node_id: u64, listener: ScriptListener) {
+        let mut state = self.state.borrow_mut();
+        let listeners = state.listeners.entry(node_id).or_default();
+        let duplicate = listeners.iter().any(|existing| {
+            existing.event_type == listener.event_type && existing.callback == listener.callback && existing.capture == listener.capture
+        });
+        if !duplicate {
+            listeners.push(Rc::new(listener));
+        }
+    }
+
+    fn remove(&self, node_id: u64, matches: impl Fn(&ScriptListener) -> bool) {
+        let mut state = self.state.borrow_mut();
+        if let Some(listeners) = state.listeners.get_mut(&node_id) {
+            listeners.retain(|listener| {
+                let keep = !matches(listener);
+                if !keep {
+                    listener.removed.set(true);
+                }
+                keep
+            });
+        }
+    }
+
+    /// Take the listeners of one pass for a type on a node
+    fn snapshot(&self, node_id: u64, event_type: &str, capture: bool) -> Vec<Rc<ScriptListener>> {
+        self.state.borrow().listeners.get(&node_id).map_or_else(Vec::new, |listeners| {
+            listeners
+                .iter()
+                .filter(|listener| listener.event_type == event_type && listener.capture == capture)
+                .cloned()
+                .collect()
+        })
+    }
+
+    fn document_object(&self, node_id: u64) -> Option<JsObject> {
+        match &self.state.borrow().document {
+            Some((root, object)) if *root == node_id => Some(object.clone()),
+            _ => None,
+        }
+    }
+
+    fn window_object(&self) -> Option<JsObject> {
+        self.state.borrow().window.clone()
+    }
+}
+
+/// The target the event target methods act on
+#[derive(Clone)]
+pub(crate) enum BoundTarget {
+    /// The node of the wrapper a method is called on
+    Wrapper,
+    /// The document's root, for which the `document` object stands
+    Document(Rc<Node>),
+    /// The global object
+    Window,
+}
+
+/// Captures of the event target methods
+#[derive(Clone, Trace, Finalize)]
+pub(crate) struct EventTargetBinding {
+    #[unsafe_ignore_trace]
+    pub(crate) target: BoundTarget,
+    pub(crate) wrappers: ElementWrappers,
+}
+
+impl EventTargetBinding {
+    /// Define `addEventListener()`, `removeEventListener()` and
+    /// `dispatchEvent()` on a node wrapper, the `document` object or the
+    /// global object
+    pub(crate) fn install(self, object: &JsObject, context: &mut Context) -> JsResult<()> {
+        match &self.target {
+            BoundTarget::Wrapper => {}
+            BoundTarget::Document(root) => self.wrappers.listeners.state.borrow_mut().document = Some((root.id, object.clone())),
+            BoundTarget::Window => self.wrappers.listeners.state.borrow_mut().window = Some(object.clone()),
+        }
+        let add = NativeFunction::from_copy_closure_with_captures(
+            |this, args, binding: &EventTargetBinding, context| {
+                let key = binding.this_key(this, context)?;
+                let (event_type, callback) = listener_arguments("addEventListener", args, context)?;
+                let Some(callback) = callback else {
+                    return Ok(JsValue::undefined());
+                };
+                let options = args.get(2).cloned().unwrap_or_default();
+                let (capture, once, passive) = match options.as_object() {
+                    Some(options) => (
+                        options.get(js_string!("capture"), context)?.to_boolean(),
+                        options.get(js_string!("once"), context)?.to_boolean(),
+                        options.get(js_string!("passive"), context)?.to_boolean(),
+                    ),
+                    None => (options.to_boolean(), false, false),
+                };
+                let listener = ScriptListener { event_type, callback, capture, once, passive, removed: Cell::new(false) };
+                binding.wrappers.listeners.add(key, listener);
+                Ok(JsValue::undefined())
+            },
+            self.clone(),
+        );
+        let remove = NativeFunction::from_copy_closure_with_captures(
+            |this, args, binding: &EventTargetBinding, context| {
+                let key = binding.this_key(this, context)?;
+                let (event_type, callback) = listener_arguments("removeEventListener", args, context)?;
+                let Some(callback) = callback else {
+                    return Ok(JsValue::undefined());
+                };
+                let options = args.get(2).cloned().unwrap_or_default();
+                let capture = match options.as_object() {
+                    Some(options) => options.get(js_string!("capture"), context)?.to_boolean(),
+                    None => options.to_boolean(),
+                };
+                binding.wrappers.listeners.remove(key, |listener| {
+                    listener.event_type == event_type && listener.callback == callback && listener.capture == capture
+                });
+                Ok(JsValue::undefined())
+            },
+            self.clone(),
+        );
+        let dispatch = NativeFunction::from_copy_closure_with_captures(
+            |this, args, binding: &EventTargetBinding, context| {
+                let node = match binding.target {
+                    BoundTarget::Window => None,
+                    _ => Some(binding.this_node(this, context)?),
+                };
+                let event = args
+                    .first()
+                    .and_then(JsValue::as_object)
+                    .filter(|event| event.downcast_ref::<EventData>().is_some())
+                    .cloned()
+                    .ok_or_else(|| JsNativeError::typ().with_message("Failed to execute 'dispatchEvent': parameter 1 is not of type 'Event'"))?;
+                let target = this.as_object().cloned().ok_or_else(|| JsNativeError::typ().with_message("Illegal invocation"))?;
+                binding.dispatch(node.as_ref(), &target, &event, context).map(JsValue::from)
+            },
+            self.clone(),
+        );
+        define_method(object, "addEventListener", add, context)?;
+        define_method(object, "removeEventListener", remove, context)?;
+        define_method(object, "dispatchEvent", dispatch, context)
+    }
+
+    /// Resolve the node a method was called on
+    fn this_node(&self, this: &JsValue, context: &mut Context) -> JsResult<Rc<Node>> {
+        match &self.target {
+            BoundTarget::Document(root) => Ok(Rc::clone(root)),
+            _ => self.wrappers.this_node(this, context),
+        }
+    }
+
+    /// Resolve the listener key of the target a method was called on
+    fn this_key(&self, this: &JsValue, context: &mut Context) -> JsResult<u64> {
+        match &self.target {
+            BoundTarget::Window => Ok(WINDOW),
+            _ => self.this_node(this, context).map(|node| node.id),
+        }
+    }
+
+    /// Dispatch an event to a node, or to `window` for `None`, returning
+    /// `false` if a listener canceled it
+    pub(crate) fn dispatch(&self, target: Option<&Rc<Node>>, target_object: &JsObject, event: &JsObject, context: &mut Context) -> JsResult<bool> {
+        let started = event.downcast_mut::<EventData>().map(|mut data| {
+            if data.dispatching {
+                return Err("The event is already being dispatched");
+            }
+            if !data.initialized {
+                return Err("The event is not initialized");
+            }
+            data.dispatching = true;
+            data.target = target_object.clone().into();
+            Ok((data.event.base().bubbles, data.event.base().event_type != "load"))
+        });
+        let (bubbles, reaches_window) = match started {
+            Some(Ok(started)) => started,
+            Some(Err(message)) => return Err(dom_exception(context, "InvalidStateError", message, INVALID_STATE_ERR)),
+            None => return Err(JsNativeError::typ().with_message("Illegal invocation").into()),
+        };
+
+        // `None` stands for `window` in the path
+        let mut path = vec![target.cloned()];
+        let mut current = target.and_then(|target| target.parent.borrow().upgrade());
+        while let Some(node) = current {
+            current = node.parent.borrow().upgrade();
+            path.push(Some(node));
+        }
+        let in_document = path
+            .last()
+            .and_then(|root| root.as_ref())
+            .is_some_and(|root| self.wrappers.listeners.document_object(root.id).is_some());
+        if target.is_some() && in_document && reaches_window {
+            path.push(None);
+        }
+
+        // The capture pass runs from the root down to the target, and the
+        // bubble pass back up if the event bubbles
+        for (index, node) in path.iter().enumerate().rev() {
+            let (phase, target) = if index == 0 { (EventPhase::AtTarget, Some(target_object)) } else { (EventPhase::Capturing, None) };
+            self.invoke(node.as_ref(), target, event, phase, true, context);
+        }
+        for (index, node) in path.iter().enumerate() {
+            if index > 0 && !bubbles {
+                break;
+            }
+            let (phase, target) = if index == 0 { (EventPhase::AtTarget, Some(target_object)) } else { (EventPhase::Bubbling, None) };
+            self.invoke(node.as_ref(), target, event, phase, false, context);
+        }
+
+        let mut data = event.downcast_mut::<EventData>().ok_or_else(|| JsNativeError::typ().with_message("Illegal invocation"))?;
+        data.dispatching = false;
+        data.current_target = JsValue::null();
+        let base = data.event.base_mut();
+        base.phase = EventPhase::None;
+        base.propagation_stopped = false;
+        base.immediate_propagation_stopped = false;
+        Ok(!base.default_prevented)
+    }
+
+    /// Run the listeners of one node for one pass of a dispatch
+    ///
+    /// `node` is `None` for `window`. `target` is the object the event was
+    /// dispatched to when `node` is its target.
+    fn invoke(&self, node: Option<&Rc<Node>>, target: Option<&JsObject>, event: &JsObject, phase: EventPhase, capture: bool, context: &mut Context) {
+        if stopped(event, |data| data.event.base().propagation_stopped) {
+            return;
+        }
+        let event_type = event.downcast_ref::<EventData>().map(|data| data.event.base().event_type.clone()).unwrap_or_default();
+        let key = node.map_or(WINDOW, |node| node.id);
+        let listeners = self.wrappers.listeners.snapshot(key, &event_type, capture);
+        if listeners.is_empty() {
+            return;
+        }
+        let current_target: JsValue = match (target, node) {
+            (Some(target), _) => target.clone().into(),
+            (None, None) => self.wrappers.listeners.window_object().map_or_else(JsValue::undefined, JsValue::from),
+            (None, Some(node)) => match self.wrappers.listeners.document_object(node.id) {
+                Some(document) => document.into(),
+                None => self.wrappers.wrap(node, context).into(),
+            },
+        };
+        if let Some(mut data) = event.downcast_mut::<EventData>() {
+            data.event.base_mut().phase = phase;
+            data.current_target = current_target.clone();
+        }
+
+        for listener in listeners {
+            if listener.removed.get() {
+                continue;
+            }
+            if listener.once {
+                self.wrappers.listeners.remove(key, |candidate| std::ptr::eq(candidate, &*listener));
+            }
+            set_passive(event, listener.passive);
+            let result = if listener.callback.is_callable() {
+                listener.callback.call(&current_target, &[event.clone().into()], context)
+            } else {
+                match listener.callback.get(js_string!("handleEvent"), context) {
+                    Ok(handle_event) => match handle_event.as_callable() {
+                        Some(handle_event) => handle_event.call(&listener.callback.clone().into(), &[event.clone().into()], context),
+                        None => Err(JsNativeError::typ().with_message("handleEvent is not a function").into()),
+                    },
+                    Err(e) => Err(e),
+                }
+            };
+            set_passive(event, false);
+            if let Err(e) = result {
+                println!("❌ Uncaught exception in '{}' listener: {}", event_type, e);
+            }
+            if stopped(event, |data| data.event.base().immediate_propagation_stopped) {
+                break;
+            }
+        }
+    }
+}
+
+fn stopped(event: &JsObject, flag: fn(&EventData) -> bool) -> bool {
+    event.downcast_ref::<EventData>().is_none_or(|data| flag(&data))
+}
+
+fn set_passive(event: &JsObject, passive: bool) {
+    if let Some(mut data) = event.downcast_mut::<EventData>() {
+        data.in_passive_listener = passive;
+    }
+}
+
+/// Read the type and callback arguments of `addEventListener()` and
+/// `removeEventListener()`
+///
+/// A `null` callback is allowed and does nothing.
+fn listener_arguments(method: &str, args: &[JsValue], context: &mut Context) -> JsResult<(String, Option<JsObject>)> {
+    if args.len() < 2 {
+        return Err(JsNativeError::typ()
+            .with_message(format!("Failed to execute '{}': 2 arguments required, but only {} present", method, args.len()))
+            .into());
+    }
+    let event_type = args[0].to_string(context)?.to_std_string_escaped();
+    match &args[1] {
+        value if value.is_null_or_undefined() => Ok((event_type, None)),
+        value => match value.as_object() {
+            Some(callback) => Ok((event_type, Some(callback.clone()))),
+            None => Err(JsNativeError::typ()
+                .with_message(format!("Failed to execute '{}': parameter 2 is not of type 'Object'", method))
+                .into()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::JsEngine;
+
+    /// Load a page and run a script, returning its result as a string
+    fn run(html: &str, code: &str) -> String {
+        let (document, _) = html_parser::parse_html_string(html).unwrap();
+        let mut engine = JsEngine::new();
+        engine.set_document(std::rc::Rc::new(document));
+        let result = engine.execute(code).unwrap();
+        result.to_string(&mut engine.context).unwrap().to_std_string_escaped()
+    }
+
+    const PAGE: &str = "<html><body><form><input name=\"q\"><a href=\"#\">go</a></form></body></html>";
+
+    #[test]
+    fn test_legacy_created_events_propagate() {
+        // The way jQuery 1.x and many plugins fire native events
+        let code = "const log = [];
+            const form = document.querySelector('form');
+            const input = document.querySelector('input');
+            document.addEventListener('change', e => log.push('document:' + e.eventPhase + ':' + (e.target === input)));
+            form.addEventListener('change', e => log.push('form-capture:' + e.eventPhase), true);
+            input.addEventListener('change', function (e) { log.push('input:' + e.eventPhase + ':' + (this === e.currentTarget)); });
+            const event = document.createEvent('HTMLEvents');
+            try { input.dispatchEvent(event); } catch (error) { log.push(error.name); }
+            event.initEvent('change', true, true);
+            log.push(input.dispatchEvent(event), event.eventPhase, event.currentTarget);
+            let unsupported;
+            try { document.createEvent('NoSuchEvents'); } catch (error) { unsupported = error.name; }
+            log.concat(unsupported).join()";
+        assert_eq!(
+            run(PAGE, code),
+            "InvalidStateError,form-capture:1,input:2:true,document:3:true,true,0,,NotSupportedError"
+        );
+    }
+
+    #[test]
+    fn test_delegated_handlers_cancel_and_stop() {
+        // Delegation as `$(document).on('click', 'a', handler)` does it
+        let code = "const log = [];
+            const link = document.querySelector('a');
+            document.addEventListener('click', function (e) {
+                if (e.target.matches('a')) { log.push('delegated:' + e.clientX + ':' + e.ctrlKey); e.preventDefault(); }
+            });
+            const click = document.createEvent('MouseEvents');
+            click.initMouseEvent('click', true, true, null, 1, 0, 0, 12, 34, true, false, false, false, 0, null);
+            log.push(link.dispatchEvent(click), click.defaultPrevented);
+
+            const form = document.querySelector('form');
+            const stop = e => { log.push('form'); e.stopPropagation(); };
+            form.addEventListener('click', stop);
+            log.push(link.dispatchEvent(new MouseEvent('click', { bubbles: true, cancelable: true })));
+            form.removeEventListener('click', stop);
+
+            const passive = new Event('touchstart', { bubbles: true, cancelable: true });
+            link.addEventListener('touchstart', e => e.preventDefault(), { passive: true });
+            log.push(link.dispatchEvent(passive));
+            log.join()";
+        assert_eq!(run(PAGE, code), "delegated:12:true,false,true,form,true,true");
+    }
+
+    #[test]
+    fn test_custom_events_once_and_handle_event() {
+        let code = "const log = [];
+            const input = document.querySelector('input');
+            const handler = { handleEvent(e) { log.push('object:' + e.detail.id + ':' + (this === handler)); } };
+            input.addEventListener('refresh', handler);
+            input.addEventListener('refresh', e => log.push('once'), { once: true });
+            input.addEventListener('refresh', () => { throw new Error('listener failed'); });
+            input.addEventListener('refresh', e => {
+                e.stopImmediatePropagation();
+                e.initEvent('changed', false, false);
+                log.push('last:' + e.type);
+            });
+            input.addEventListener('refresh', () => log.push('never'));
+            const event = document.createEvent('CustomEvent');
+            event.initCustomEvent('refresh', true, false, { id: 7 });
+            input.dispatchEvent(event);
+            input.dispatchEvent(event);
+            log.join()";
+        assert_eq!(run(PAGE, code), "object:7:true,once,last:refresh,object:7:true,last:refresh");
+    }
+
+    #[test]
+    fn test_window_ends_the_path_and_pointer_capture_checks_the_pointer() {
+        let code = "const log = [];
+            const link = document.querySelector('a');
+            window.addEventListener('pointerdown', e => log.push('window:' + e.eventPhase + ':' + (e.currentTarget === window)));
+            window.addEventListener('load', () => log.push('load'));
+            link.addEventListener('pointerdown', e => log.push(e.constructor.name + ':' + e.pointerType + ':' + e.pointerId));
+            link.dispatchEvent(new PointerEvent('pointerdown', { bubbles: true, pointerId: 1, pointerType: 'mouse' }));
+            link.dispatchEvent(new Event('load'));
+            try { link.setPointerCapture(42); } catch (error) { log.push(error.name); }
+            log.push(link.hasPointerCapture(1));
+            log.join()";
+        assert_eq!(run(PAGE, code), "PointerEvent:mouse:1,window:3:true,NotFoundError,false");
+    }
+}
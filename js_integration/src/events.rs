@@ -341,13 +341,13 @@ impl JsEventIntegration {
     pub fn dispatch_synthetic_event(
         &self,
         target: Rc<RefCell<dyn EventTarget>>,
-        event: Event,
+        mut event: Event,
     ) -> bool {
         let mut dispatcher = self.dispatcher.borrow_mut();
         let mut profiler = self.profiler.borrow_mut();
         
         profiler.start_dispatch();
-        let result = dispatcher.dispatch_event(target, event, None);
+        let result = dispatcher.dispatch_event(target, &mut event, None);
         profiler.end_dispatch();
         
         result
@@ -0,0 +1,733 @@
+//! # Blob, File and FormData Bindings
+//!
+//! This module implements the `Blob`, `File` and `FormData` objects used by
+//! file-upload and API-client code, and converts them into request bodies
+//! for fetch and XMLHttpRequest.
+//!
+//! ## Design Principles
+//!
+//! 1. **Rust Core**: `Blob`, `File` and `FormData` are plain Rust types that
+//!    the JavaScript objects wrap as native data, so the network layer can
+//!    use them without touching the JS context.
+//! 2. **Immutable Blobs**: Blob contents never change; `slice()` copies the
+//!    requested range into a new blob.
+//! 3. **Shared Encoder**: FormData bodies are encoded with the multipart
+//!    encoder in the networking crate.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+use boa_engine::{
+    object::{
+        builtins::{JsArray, JsArrayBuffer, JsPromise, JsTypedArray},
+        ObjectInitializer,
+    },
+    property::Attribute,
+    js_string, Context, JsData, JsNativeError, JsObject, JsResult, JsValue, NativeFunction,
+};
+use boa_gc::{Finalize, Trace};
+use networking::MultipartPart;
+
+/// An immutable chunk of bytes with a MIME type
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Blob {
+    data: Vec<u8>,
+    content_type: String,
+}
+
+impl Blob {
+    /// Create a blob from its parts
+    pub fn new(parts: Vec<Vec<u8>>, content_type: &str) -> Self {
+        Self {
+            data: parts.concat(),
+            content_type: normalize_type(content_type),
+        }
+    }
+
+    /// Size of the blob in bytes
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// MIME type of the blob, or an empty string if unknown
+    pub fn content_type(&self) -> &str {
+        &self.content_type
+    }
+
+    /// The blob contents
+    pub fn bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The blob contents decoded as UTF-8
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.data).into_owned()
+    }
+
+    /// Copy a range of the blob into a new blob
+    ///
+    /// Negative offsets count from the end, as in `Blob.prototype.slice`.
+    pub fn slice(&self, start: i64, end: Option<i64>, content_type: Option<&str>) -> Blob {
+        let size = self.data.len() as i64;
+        let resolve = |offset: i64| {
+            if offset < 0 {
+                (size + offset).max(0)
+            } else {
+                offset.min(size)
+            }
+        };
+        let start = resolve(start);
+        let end = resolve(end.unwrap_or(size)).max(start);
+
+        Blob {
+            data: self.data[start as usize..end as usize].to_vec(),
+            content_type: content_type.map(normalize_type).unwrap_or_default(),
+        }
+    }
+}
+
+/// A blob with a file name and modification time
+#[derive(Debug, Clone, PartialEq)]
+pub struct File {
+    pub blob: Blob,
+    pub name: String,
+    /// Milliseconds since the Unix epoch
+    pub last_modified: i64,
+}
+
+impl File {
+    /// Create a file from its parts
+    pub fn new(parts: Vec<Vec<u8>>, name: &str, content_type: &str, last_modified: Option<i64>) -> Self {
+        Self {
+            blob: Blob::new(parts, content_type),
+            name: name.to_string(),
+            last_modified: last_modified.unwrap_or_else(now_millis),
+        }
+    }
+}
+
+/// A value stored in a `FormData` entry
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormDataValue {
+    String(String),
+    File(File),
+}
+
+/// An ordered list of form entries
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FormData {
+    entries: Vec<(String, FormDataValue)>,
+}
+
+impl FormData {
+    /// Create an empty form data set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append an entry
+    pub fn append(&mut self, name: &str, value: FormDataValue) {
+        self.entries.push((name.to_string(), value));
+    }
+
+    /// Replace the first entry with the given name and remove the rest,
+    /// or append if there is none
+    pub fn set(&mut self, name: &str, value: FormDataValue) {
+        match self.entries.iter().position(|(entry_name, _)| entry_name == name) {
+            Some(index) => {
+                self.entries[index].1 = value;
+                let mut seen = 0;
+                self.entries.retain(|(entry_name, _)| {
+                    if entry_name == name {
+                        seen += 1;
+                        seen == 1
+                    } else {
+                        true
+                    }
+                });
+            }
+            None => self.append(name, value),
+        }
+    }
+
+    /// Get the first value with the given name
+    pub fn get(&self, name: &str) -> Option<&FormDataValue> {
+        self.entries
+            .iter()
+            .find(|(entry_name, _)| entry_name == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Get all values with the given name
+    pub fn get_all(&self, name: &str) -> Vec<&FormDataValue> {
+        self.entries
+            .iter()
+            .filter(|(entry_name, _)| entry_name == name)
+            .map(|(_, value)| value)
+            .collect()
+    }
+
+    /// Check whether an entry with the given name exists
+    pub fn has(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Remove all entries with the given name
+    pub fn delete(&mut self, name: &str) {
+        self.entries.retain(|(entry_name, _)| entry_name != name);
+    }
+
+    /// All entries in insertion order
+    pub fn entries(&self) -> &[(String, FormDataValue)] {
+        &self.entries
+    }
+
+    /// Convert the entries into parts for the multipart encoder
+    pub fn to_multipart_parts(&self) -> Vec<MultipartPart> {
+        self.entries
+            .iter()
+            .map(|(name, value)| match value {
+                FormDataValue::String(value) => MultipartPart::Text {
+                    name: name.clone(),
+                    value: value.clone(),
+                },
+                FormDataValue::File(file) => MultipartPart::File {
+                    name: name.clone(),
+                    filename: file.name.clone(),
+                    content_type: file.blob.content_type().to_string(),
+                    data: file.blob.bytes().to_vec(),
+                },
+            })
+            .collect()
+    }
+}
+
+/// A request body extracted from a JavaScript value
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtractedBody {
+    pub bytes: Vec<u8>,
+    /// Content-Type implied by the body, if any
+    pub content_type: Option<String>,
+}
+
+/// Lowercase a MIME type, dropping it if it contains invalid characters
+fn normalize_type(content_type: &str) -> String {
+    if content_type.chars().all(|c| (' '..='~').contains(&c)) {
+        content_type.to_ascii_lowercase()
+    } else {
+        String::new()
+    }
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// Native data for Blob and File objects
+#[derive(Trace, Finalize, JsData)]
+struct BlobData {
+    #[unsafe_ignore_trace]
+    blob: Blob,
+    /// Name and modification time for File objects
+    #[unsafe_ignore_trace]
+    file: Option<(String, i64)>,
+}
+
+/// Native data for FormData objects
+#[derive(Trace, Finalize, JsData)]
+struct FormDataData {
+    #[unsafe_ignore_trace]
+    form_data: FormData,
+}
+
+/// Get the blob wrapped by a Blob or File object
+pub fn blob_from_object(object: &JsObject) -> Option<Blob> {
+    object.downcast_ref::<BlobData>().map(|data| data.blob.clone())
+}
+
+/// Get the file wrapped by a File object
+pub fn file_from_object(object: &JsObject) -> Option<File> {
+    object.downcast_ref::<BlobData>().and_then(|data| {
+        data.file.as_ref().map(|(name, last_modified)| File {
+            blob: data.blob.clone(),
+            name: name.clone(),
+            last_modified: *last_modified,
+        })
+    })
+}
+
+/// Get the entries of a FormData object
+pub fn form_data_from_object(object: &JsObject) -> Option<FormData> {
+    object.downcast_ref::<FormDataData>().map(|data| data.form_data.clone())
+}
+
+/// Register the `Blob`, `File` and `FormData` constructors
+pub fn initialize_file_api_bindings(context: &mut Context) -> JsResult<()> {
+    context.register_global_callable(js_string!("Blob"), 0, NativeFunction::from_fn_ptr(blob_constructor))?;
+    context.register_global_callable(js_string!("File"), 2, NativeFunction::from_fn_ptr(file_constructor))?;
+    context.register_global_callable(js_string!("FormData"), 0, NativeFunction::from_fn_ptr(form_data_constructor))?;
+    Ok(())
+}
+
+/// Extract a request body from a fetch/XHR body value
+///
+/// Strings are sent as UTF-8 text, Blobs with their own type, buffers as
+/// raw bytes and FormData as `multipart/form-data`. Returns `None` for
+/// `null` and `undefined`.
+pub fn extract_body(value: &JsValue, context: &mut Context) -> JsResult<Option<ExtractedBody>> {
+    if value.is_null_or_undefined() {
+        return Ok(None);
+    }
+
+    if let Some(object) = value.as_object() {
+        if let Some(form_data) = form_data_from_object(object) {
+            let boundary = networking::generate_multipart_boundary();
+            let bytes = networking::encode_multipart_form_data(&form_data.to_multipart_parts(), &boundary);
+            return Ok(Some(ExtractedBody {
+                bytes,
+                content_type: Some(format!("multipart/form-data; boundary={}", boundary)),
+            }));
+        }
+
+        if let Some(blob) = blob_from_object(object) {
+            let content_type = Some(blob.content_type().to_string()).filter(|t| !t.is_empty());
+            return Ok(Some(ExtractedBody {
+                bytes: blob.bytes().to_vec(),
+                content_type,
+            }));
+        }
+
+        if let Some(bytes) = buffer_bytes(object, context)? {
+            return Ok(Some(ExtractedBody { bytes, content_type: None }));
+        }
+    }
+
+    let text = value.to_string(context)?.to_std_string_escaped();
+    Ok(Some(ExtractedBody {
+        bytes: text.into_bytes(),
+        content_type: Some("text/plain;charset=UTF-8".to_string()),
+    }))
+}
+
+/// Read the bytes of an ArrayBuffer or typed array
+///
+/// A typed array gives the bytes of its view of the buffer.
+pub(crate) fn buffer_bytes(object: &JsObject, context: &mut Context) -> JsResult<Option<Vec<u8>>> {
+    if let Ok(buffer) = JsArrayBuffer::from_object(object.clone()) {
+        return Ok(Some(buffer.data().map(|data| data.to_vec()).unwrap_or_default()));
+    }
+
+    if let Ok(array) = JsTypedArray::from_object(object.clone()) {
+        let offset = array.byte_offset(context)?;
+        let length = array.byte_length(context)?;
+        let buffer = array.buffer(context)?.as_object().and_then(|buffer| JsArrayBuffer::from_object(buffer.clone()).ok());
+        let bytes = buffer.and_then(|buffer| buffer.data().and_then(|data| data.get(offset..offset + length).map(<[u8]>::to_vec)));
+        return Ok(Some(bytes.unwrap_or_default()));
+    }
+
+    Ok(None)
+}
+
+/// Convert a `blobParts` sequence into byte chunks
+fn blob_parts(value: &JsValue, context: &mut Context) -> JsResult<Vec<Vec<u8>>> {
+    let object = match value.as_object() {
+        Some(object) => object.clone(),
+        None if value.is_undefined() => return Ok(Vec::new()),
+        None => {
+            return Err(JsNativeError::typ()
+                .with_message("Blob parts must be a sequence")
+                .into())
+        }
+    };
+
+    let length = object.get(js_string!("length"), context)?.to_length(context)?;
+    let mut parts = Vec::with_capacity(length as usize);
+    for index in 0..length {
+        let part = object.get(index, context)?;
+        let bytes = match part.as_object() {
+            Some(part_object) => match blob_from_object(part_object) {
+                Some(blob) => blob.bytes().to_vec(),
+                None => match buffer_bytes(part_object, context)? {
+                    Some(bytes) => bytes,
+                    None => part.to_string(context)?.to_std_string_escaped().into_bytes(),
+                },
+            },
+            None => part.to_string(context)?.to_std_string_escaped().into_bytes(),
+        };
+        parts.push(bytes);
+    }
+    Ok(parts)
+}
+
+/// Read a string option from an options bag
+fn string_option(options: &JsValue, name: &str, context: &mut Context) -> JsResult<Option<String>> {
+    match options.as_object() {
+        Some(options) => {
+            let value = options.get(js_string!(name), context)?;
+            if value.is_undefined() {
+                Ok(None)
+            } else {
+                Ok(Some(value.to_string(context)?.to_std_string_escaped()))
+            }
+        }
+        None => Ok(None),
+    }
+}
+
+/// Create the JavaScript object for a blob, or a file when `file` is set
+pub fn create_blob_object(blob: Blob, file: Option<(String, i64)>, context: &mut Context) -> JsObject {
+    let size = blob.size();
+    let content_type = js_string!(blob.content_type());
+    let file_info = file.clone();
+
+    let mut initializer = ObjectInitializer::with_native_data(BlobData { blob, file }, context);
+    initializer
+        .property(js_string!("size"), size, Attribute::READONLY)
+        .property(js_string!("type"), content_type, Attribute::READONLY)
+        .function(NativeFunction::from_fn_ptr(blob_slice), js_string!("slice"), 3)
+        .function(NativeFunction::from_fn_ptr(blob_text), js_string!("text"), 0)
+        .function(NativeFunction::from_fn_ptr(blob_array_buffer), js_string!("arrayBuffer"), 0);
+
+    if let Some((name, last_modified)) = file_info {
+        initializer
+            .property(js_string!("name"), js_string!(name.as_str()), Attribute::READONLY)
+            .property(js_string!("lastModified"), last_modified as f64, Attribute::READONLY);
+    }
+
+    initializer.build()
+}
+
+fn this_blob(this: &JsValue) -> JsResult<Blob> {
+    this.as_object()
+        .and_then(blob_from_object)
+        .ok_or_else(|| JsNativeError::typ().with_message("Illegal invocation").into())
+}
+
+/// `new Blob(parts, options)` implementation
+fn blob_constructor(
+    _this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context,
+) -> JsResult<JsValue> {
+    let parts = blob_parts(args.first().unwrap_or(&JsValue::undefined()), context)?;
+    let options = args.get(1).cloned().unwrap_or_default();
+    let content_type = string_option(&options, "type", context)?.unwrap_or_default();
+
+    Ok(create_blob_object(Blob::new(parts, &content_type), None, context).into())
+}
+
+/// `new File(parts, name, options)` implementation
+fn file_constructor(
+    _this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context,
+) -> JsResult<JsValue> {
+    if args.len() < 2 {
+        return Err(JsNativeError::typ()
+            .with_message("File constructor requires 2 arguments")
+            .into());
+    }
+
+    let parts = blob_parts(&args[0], context)?;
+    let name = args[1].to_string(context)?.to_std_string_escaped();
+    let options = args.get(2).cloned().unwrap_or_default();
+    let content_type = string_option(&options, "type", context)?.unwrap_or_default();
+    let last_modified = match options.as_object() {
+        Some(options) => {
+            let value = options.get(js_string!("lastModified"), context)?;
+            if value.is_undefined() {
+                None
+            } else {
+                Some(value.to_number(context)? as i64)
+            }
+        }
+        None => None,
+    };
+
+    let file = File::new(parts, &name, &content_type, last_modified);
+    Ok(create_blob_object(file.blob, Some((file.name, file.last_modified)), context).into())
+}
+
+/// Blob.slice() implementation
+fn blob_slice(
+    this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context,
+) -> JsResult<JsValue> {
+    let blob = this_blob(this)?;
+    let start = match args.first() {
+        Some(value) if !value.is_undefined() => value.to_number(context)? as i64,
+        _ => 0,
+    };
+    let end = match args.get(1) {
+        Some(value) if !value.is_undefined() => Some(value.to_number(context)? as i64),
+        _ => None,
+    };
+    let content_type = match args.get(2) {
+        Some(value) if !value.is_undefined() => Some(value.to_string(context)?.to_std_string_escaped()),
+        _ => None,
+    };
+
+    let slice = blob.slice(start, end, content_type.as_deref());
+    Ok(create_blob_object(slice, None, context).into())
+}
+
+/// Blob.text() implementation
+fn blob_text(
+    this: &JsValue,
+    _args: &[JsValue],
+    context: &mut Context,
+) -> JsResult<JsValue> {
+    let text = this_blob(this)?.text();
+    Ok(JsPromise::resolve(js_string!(text), context).into())
+}
+
+/// Blob.arrayBuffer() implementation
+fn blob_array_buffer(
+    this: &JsValue,
+    _args: &[JsValue],
+    context: &mut Context,
+) -> JsResult<JsValue> {
+    let bytes = this_blob(this)?.bytes().to_vec();
+    let buffer = JsArrayBuffer::from_byte_block(bytes, context)?;
+    Ok(JsPromise::resolve(buffer, context).into())
+}
+
+/// `new FormData()` implementation
+fn form_data_constructor(
+    _this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context,
+) -> JsResult<JsValue> {
+    if args.first().is_some_and(|form| !form.is_undefined()) {
+        return Err(JsNativeError::typ()
+            .with_message("FormData does not support form elements yet")
+            .into());
+    }
+
+    let form_data = ObjectInitializer::with_native_data(FormDataData { form_data: FormData::new() }, context)
+        .function(NativeFunction::from_fn_ptr(form_data_append), js_string!("append"), 2)
+        .function(NativeFunction::from_fn_ptr(form_data_set), js_string!("set"), 2)
+        .function(NativeFunction::from_fn_ptr(form_data_get), js_string!("get"), 1)
+        .function(NativeFunction::from_fn_ptr(form_data_get_all), js_string!("getAll"), 1)
+        .function(NativeFunction::from_fn_ptr(form_data_has), js_string!("has"), 1)
+        .function(NativeFunction::from_fn_ptr(form_data_delete), js_string!("delete"), 1)
+        .function(NativeFunction::from_fn_ptr(form_data_entries), js_string!("entries"), 0)
+        .build();
+
+    Ok(form_data.into())
+}
+
+/// Run `f` against the FormData wrapped by `this`
+fn with_form_data<R>(this: &JsValue, f: impl FnOnce(&mut FormData) -> R) -> JsResult<R> {
+    let object = this
+        .as_object()
+        .ok_or_else(|| JsNativeError::typ().with_message("Illegal invocation"))?;
+    let mut data = object
+        .downcast_mut::<FormDataData>()
+        .ok_or_else(|| JsNativeError::typ().with_message("Illegal invocation"))?;
+    Ok(f(&mut data.form_data))
+}
+
+/// Convert `append`/`set` arguments into an entry
+fn form_data_entry(args: &[JsValue], context: &mut Context) -> JsResult<(String, FormDataValue)> {
+    if args.len() < 2 {
+        return Err(JsNativeError::typ()
+            .with_message("FormData entries require a name and a value")
+            .into());
+    }
+
+    let name = args[0].to_string(context)?.to_std_string_escaped();
+    let filename = match args.get(2) {
+        Some(value) if !value.is_undefined() => Some(value.to_string(context)?.to_std_string_escaped()),
+        _ => None,
+    };
+
+    let value = match args[1].as_object() {
+        Some(object) if blob_from_object(object).is_some() => {
+            // Blobs are stored as Files; plain Blobs are named "blob"
+            let mut file = file_from_object(object).unwrap_or_else(|| File {
+                blob: blob_from_object(object).unwrap_or_default(),
+                name: "blob".to_string(),
+                last_modified: now_millis(),
+            });
+            if let Some(filename) = filename {
+                file.name = filename;
+            }
+            FormDataValue::File(file)
+        }
+        _ => FormDataValue::String(args[1].to_string(context)?.to_std_string_escaped()),
+    };
+
+    Ok((name, value))
+}
+
+fn form_data_value_to_js(value: &FormDataValue, context: &mut Context) -> JsValue {
+    match value {
+        FormDataValue::String(value) => js_string!(value.as_str()).into(),
+        FormDataValue::File(file) => create_blob_object(
+            file.blob.clone(),
+            Some((file.name.clone(), file.last_modified)),
+            context,
+        )
+        .into(),
+    }
+}
+
+/// FormData.append() implementation
+fn form_data_append(
+    this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context,
+) -> JsResult<JsValue> {
+    let (name, value) = form_data_entry(args, context)?;
+    with_form_data(this, |form_data| form_data.append(&name, value))?;
+    Ok(JsValue::undefined())
+}
+
+/// FormData.set() implementation
+fn form_data_set(
+    this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context,
+) -> JsResult<JsValue> {
+    let (name, value) = form_data_entry(args, context)?;
+    with_form_data(this, |form_data| form_data.set(&name, value))?;
+    Ok(JsValue::undefined())
+}
+
+/// FormData.get() implementation
+fn form_data_get(
+    this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context,
+) -> JsResult<JsValue> {
+    let name = args.first().cloned().unwrap_or_default().to_string(context)?.to_std_string_escaped();
+    let value = with_form_data(this, |form_data| form_data.get(&name).cloned())?;
+    Ok(match value {
+        Some(value) => form_data_value_to_js(&value, context),
+        None => JsValue::null(),
+    })
+}
+
+/// FormData.getAll() implementation
+fn form_data_get_all(
+    this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context,
+) -> JsResult<JsValue> {
+    let name = args.first().cloned().unwrap_or_default().to_string(context)?.to_std_string_escaped();
+    let values: Vec<FormDataValue> =
+        with_form_data(this, |form_data| form_data.get_all(&name).into_iter().cloned().collect())?;
+    let values: Vec<JsValue> = values.iter().map(|value| form_data_value_to_js(value, context)).collect();
+    Ok(JsArray::from_iter(values, context).into())
+}
+
+/// FormData.has() implementation
+fn form_data_has(
+    this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context,
+) -> JsResult<JsValue> {
+    let name = args.first().cloned().unwrap_or_default().to_string(context)?.to_std_string_escaped();
+    Ok(with_form_data(this, |form_data| form_data.has(&name))?.into())
+}
+
+/// FormData.delete() implementation
+fn form_data_delete(
+    this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context,
+) -> JsResult<JsValue> {
+    let name = args.first().cloned().unwrap_or_default().to_string(context)?.to_std_string_escaped();
+    with_form_data(this, |form_data| form_data.delete(&name))?;
+    Ok(JsValue::undefined())
+}
+
+/// FormData.entries() implementation
+///
+/// Returns an array of `[name, value]` pairs, which can be iterated with
+/// `for...of` like the iterator the specification describes.
+fn form_data_entries(
+    this: &JsValue,
+    _args: &[JsValue],
+    context: &mut Context,
+) -> JsResult<JsValue> {
+    let entries = with_form_data(this, |form_data| form_data.entries().to_vec())?;
+    let mut pairs = Vec::with_capacity(entries.len());
+    for (name, value) in &entries {
+        let value = form_data_value_to_js(value, context);
+        pairs.push(JsArray::from_iter([js_string!(name.as_str()).into(), value], context).into());
+    }
+    Ok(JsArray::from_iter(pairs, context).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use boa_engine::Source;
+
+    #[test]
+    fn test_blob_slice_and_form_data() {
+        let blob = Blob::new(vec![b"hello ".to_vec(), b"world".to_vec()], "Text/Plain");
+        assert_eq!(blob.size(), 11);
+        assert_eq!(blob.content_type(), "text/plain");
+        assert_eq!(blob.slice(-5, None, None).text(), "world");
+        assert_eq!(blob.slice(3, Some(1), None).size(), 0);
+
+        let mut form_data = FormData::new();
+        form_data.append("a", FormDataValue::String("1".to_string()));
+        form_data.append("a", FormDataValue::String("2".to_string()));
+        form_data.append("b", FormDataValue::File(File::new(vec![b"x".to_vec()], "x.txt", "text/plain", Some(0))));
+        assert_eq!(form_data.get_all("a").len(), 2);
+
+        form_data.set("a", FormDataValue::String("3".to_string()));
+        assert_eq!(form_data.get("a"), Some(&FormDataValue::String("3".to_string())));
+        assert_eq!(form_data.get_all("a").len(), 1);
+
+        let parts = form_data.to_multipart_parts();
+        assert!(matches!(&parts[1], MultipartPart::File { filename, .. } if filename == "x.txt"));
+    }
+
+    #[test]
+    fn test_file_api_javascript() {
+        let context = &mut Context::default();
+        initialize_file_api_bindings(context).unwrap();
+
+        let code = r#"
+            var text = "";
+            const blob = new Blob(["abc", new Uint8Array([100, 101])], { type: "text/plain" });
+            const file = new File([blob], "notes.txt", { lastModified: 42 });
+            const form = new FormData();
+            form.append("title", "Hi");
+            form.append("upload", file);
+            form.append("raw", blob, "raw.bin");
+            blob.slice(1, 4).text().then((value) => { text = value; });
+            [blob.size, blob.type, file.name, file.lastModified, form.get("title"),
+             form.get("raw").name, form.has("missing"), form.entries().length].join(",")
+        "#;
+        let result = context.eval(Source::from_bytes(code)).unwrap();
+        context.run_jobs();
+        assert_eq!(
+            result.to_string(context).unwrap().to_std_string_escaped(),
+            "5,text/plain,notes.txt,42,Hi,raw.bin,false,3"
+        );
+
+        let text = context.eval(Source::from_bytes("text")).unwrap();
+        assert_eq!(text.to_string(context).unwrap().to_std_string_escaped(), "bcd");
+
+        // FormData becomes a multipart body
+        let form = context.eval(Source::from_bytes("form")).unwrap();
+        let body = extract_body(&form, context).unwrap().unwrap();
+        assert!(body.content_type.unwrap().starts_with("multipart/form-data; boundary="));
+        let body = String::from_utf8_lossy(&body.bytes).into_owned();
+        assert!(body.contains("filename=\"notes.txt\""));
+        assert!(body.contains("abcde"));
+
+        let blob = context.eval(Source::from_bytes("blob")).unwrap();
+        let body = extract_body(&blob, context).unwrap().unwrap();
+        assert_eq!(body.bytes, b"abcde");
+        assert_eq!(body.content_type.as_deref(), Some("text/plain"));
+    }
+}
@@ -0,0 +1,139 @@
+//! # Gamepads
+//!
+//! This module implements `navigator.getGamepads()`. The engine is handed
+//! the state of the connected devices once per frame; scripts read it
+//! through `getGamepads()` and hear about devices coming and going through
+//! `gamepadconnected` and `gamepaddisconnected` on `window`.
+//!
+//! ## Design Principles
+//!
+//! 1. **Snapshots**: `getGamepads()` returns fresh objects holding the
+//!    state of the last poll, so a script that keeps one sees it unchanged,
+//!    as in Chromium.
+//! 2. **Registry in the DOM Crate**: Slots and connection changes come from
+//!    `dom::gamepad::GamepadRegistry`; this module only converts.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use boa_engine::{
+    object::{builtins::JsArray, ObjectInitializer},
+    property::Attribute,
+    js_string, Context, JsObject, JsResult, JsValue, NativeFunction,
+};
+use boa_gc::{Finalize, Trace};
+use dom::gamepad::{Gamepad, GamepadChange, GamepadRegistry, GamepadSnapshot};
+use crate::clipboard::navigator_object;
+
+/// The gamepads of a script context
+///
+/// Cloning shares the registry.
+#[derive(Clone, Debug, Default)]
+pub struct Gamepads {
+    registry: Rc<RefCell<GamepadRegistry>>,
+}
+
+impl Gamepads {
+    /// Take the state of a poll, returning the devices that connected and
+    /// disconnected since the last one
+    pub fn update(&self, snapshots: Vec<GamepadSnapshot>) -> Vec<GamepadChange> {
+        self.registry.borrow_mut().update(snapshots)
+    }
+}
+
+/// Captures for `getGamepads`
+#[derive(Trace, Finalize)]
+struct GamepadsBinding {
+    #[unsafe_ignore_trace]
+    gamepads: Gamepads,
+}
+
+/// Install `navigator.getGamepads()`
+///
+/// Returns the gamepads, which the engine updates from the shell's polls.
+pub fn initialize_gamepad_bindings(context: &mut Context) -> JsResult<Gamepads> {
+    let gamepads = Gamepads::default();
+    let get_gamepads = NativeFunction::from_copy_closure_with_captures(
+        |_this, _args, binding: &GamepadsBinding, context| {
+            let slots = binding.gamepads.registry.borrow().gamepads();
+            let array = JsArray::new(context);
+            for slot in slots {
+                let value = match slot {
+                    Some(gamepad) => gamepad_object(&gamepad, context)?.into(),
+                    None => JsValue::null(),
+                };
+                array.push(value, context)?;
+            }
+            Ok(array.into())
+        },
+        GamepadsBinding { gamepads: gamepads.clone() },
+    );
+    let function = get_gamepads.to_js_function(context.realm());
+    navigator_object(context)?.set(js_string!("getGamepads"), function, false, context)?;
+    Ok(gamepads)
+}
+
+/// Create the object scripts see for a gamepad
+pub fn gamepad_object(gamepad: &Gamepad, context: &mut Context) -> JsResult<JsObject> {
+    let axes = JsArray::from_iter(gamepad.axes.iter().map(|axis| JsValue::from(*axis)), context);
+    let buttons = JsArray::new(context);
+    for button in &gamepad.buttons {
+        let button = ObjectInitializer::new(context)
+            .property(js_string!("pressed"), button.pressed, Attribute::READONLY | Attribute::ENUMERABLE)
+            .property(js_string!("touched"), button.touched, Attribute::READONLY | Attribute::ENUMERABLE)
+            .property(js_string!("value"), button.value, Attribute::READONLY | Attribute::ENUMERABLE)
+            .build();
+        buttons.push(button, context)?;
+    }
+    let attributes = Attribute::READONLY | Attribute::ENUMERABLE;
+    Ok(ObjectInitializer::new(context)
+        .property(js_string!("id"), js_string!(gamepad.id.as_str()), attributes)
+        .property(js_string!("index"), gamepad.index as u32, attributes)
+        .property(js_string!("mapping"), js_string!(gamepad.mapping), attributes)
+        .property(js_string!("connected"), gamepad.connected, attributes)
+        .property(js_string!("timestamp"), gamepad.timestamp, attributes)
+        .property(js_string!("axes"), axes, attributes)
+        .property(js_string!("buttons"), buttons, attributes)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use dom::gamepad::{GamepadButton, GamepadSnapshot};
+    use crate::JsEngine;
+
+    fn pad(device: u64, pressed: bool) -> GamepadSnapshot {
+        GamepadSnapshot {
+            device,
+            name: "Test Pad".to_string(),
+            standard_mapping: true,
+            axes: vec![0.25, -1.0],
+            buttons: vec![GamepadButton { pressed, touched: pressed, value: if pressed { 1.0 } else { 0.0 } }],
+        }
+    }
+
+    #[test]
+    fn test_connection_events_and_snapshots() {
+        let (document, _) = html_parser::parse_html_string("<html><body></body></html>").unwrap();
+        let mut engine = JsEngine::new();
+        engine.set_document(Rc::new(document));
+        engine
+            .execute(
+                "var log = [];
+                window.addEventListener('gamepadconnected', e => log.push('+' + e.gamepad.index + ':' + e.gamepad.id + ':' + e.isTrusted));
+                window.addEventListener('gamepaddisconnected', e => log.push('-' + e.gamepad.index + ':' + e.gamepad.connected));",
+            )
+            .unwrap();
+
+        assert_eq!(engine.update_gamepads(vec![pad(5, false)]), 1);
+        assert_eq!(engine.update_gamepads(vec![pad(5, true)]), 0);
+        let code = "const pad = navigator.getGamepads()[0];
+            [pad.mapping, pad.axes.join('/'), pad.buttons[0].pressed, navigator.getGamepads().length].join()";
+        let result = engine.execute(code).unwrap();
+        assert_eq!(result.to_string(&mut engine.context).unwrap().to_std_string_escaped(), "standard,0.25/-1,true,1");
+
+        assert_eq!(engine.update_gamepads(Vec::new()), 1);
+        let result = engine.execute("log.join() + '|' + navigator.getGamepads()[0]").unwrap();
+        assert_eq!(result.to_string(&mut engine.context).unwrap().to_std_string_escaped(), "+0:Test Pad:true,-0:false|null");
+    }
+}
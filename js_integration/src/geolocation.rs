@@ -0,0 +1,385 @@
+//! # Geolocation
+//!
+//! This module implements `navigator.geolocation`: `getCurrentPosition()`,
+//! `watchPosition()` and `clearWatch()`. Positions come from a
+//! `LocationProvider` the shell chooses, and pages only get them once the
+//! user grants their origin the `geolocation` permission.
+//!
+//! ## Design Principles
+//!
+//! 1. **Pluggable Providers**: The engine never asks the operating system
+//!    itself. The default provider reports a fixed location, so pages that
+//!    use geolocation behave the same in every test run; the shell can plug
+//!    in one backed by a real location service.
+//! 2. **Permission Gated**: Every request is checked against the document's
+//!    origin through the shared permissions store, and a denied or
+//!    dismissed prompt fails with `PERMISSION_DENIED`.
+//! 3. **Callbacks Run Later**: Success and error callbacks never run inside
+//!    the call that registered them, as in browsers. Watches are updated
+//!    when the engine polls the provider, and only hear about positions
+//!    that changed.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use boa_engine::{
+    object::{builtins::{JsFunction, JsPromise}, ObjectInitializer},
+    property::Attribute,
+    js_string, Context, JsNativeError, JsObject, JsResult, JsValue, NativeFunction,
+};
+use boa_gc::{Finalize, Trace};
+
+use crate::clipboard::navigator_object;
+use crate::permissions::{PermissionName, PermissionState, Permissions};
+
+/// Where the device is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Coordinates {
+    /// Degrees north of the equator
+    pub latitude: f64,
+    /// Degrees east of the prime meridian
+    pub longitude: f64,
+    /// Radius of the area the device is in, in meters
+    pub accuracy: f64,
+    /// Meters above the WGS 84 ellipsoid, if known
+    pub altitude: Option<f64>,
+    /// Degrees clockwise from true north the device moves in, if known
+    pub heading: Option<f64>,
+    /// Meters per second, if known
+    pub speed: Option<f64>,
+}
+
+/// Why a position could not be acquired, with the `code` scripts see
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PositionError {
+    /// The origin does not have the `geolocation` permission
+    PermissionDenied = 1,
+    /// The provider cannot tell where the device is
+    PositionUnavailable = 2,
+    /// The provider did not answer in time
+    Timeout = 3,
+}
+
+impl PositionError {
+    /// Get the `code` of the `GeolocationPositionError`
+    pub fn code(&self) -> u16 {
+        *self as u16
+    }
+
+    /// Get the name of the constant for the code
+    pub fn name(&self) -> &'static str {
+        match self {
+            PositionError::PermissionDenied => "PERMISSION_DENIED",
+            PositionError::PositionUnavailable => "POSITION_UNAVAILABLE",
+            PositionError::Timeout => "TIMEOUT",
+        }
+    }
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PositionError::PermissionDenied => write!(f, "User denied Geolocation"),
+            PositionError::PositionUnavailable => write!(f, "Position unavailable"),
+            PositionError::Timeout => write!(f, "Timeout expired"),
+        }
+    }
+}
+
+impl std::error::Error for PositionError {}
+
+/// A source of the device's position
+pub trait LocationProvider {
+    /// Get where the device is now
+    fn current_position(&self) -> Result<Coordinates, PositionError>;
+}
+
+/// A provider that always reports the same location
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FixedLocationProvider {
+    pub coordinates: Coordinates,
+}
+
+impl FixedLocationProvider {
+    /// Report a location with the given accuracy in meters
+    pub fn new(latitude: f64, longitude: f64, accuracy: f64) -> Self {
+        FixedLocationProvider {
+            coordinates: Coordinates { latitude, longitude, accuracy, altitude: None, heading: None, speed: None },
+        }
+    }
+}
+
+impl Default for FixedLocationProvider {
+    /// The Royal Observatory in Greenwich
+    fn default() -> Self {
+        Self::new(51.4769, -0.0005, 20.0)
+    }
+}
+
+impl LocationProvider for FixedLocationProvider {
+    fn current_position(&self) -> Result<Coordinates, PositionError> {
+        Ok(self.coordinates)
+    }
+}
+
+/// A `watchPosition()` registration
+struct Watch {
+    success: JsObject,
+    error: Option<JsObject>,
+    /// What the watch was last told, to only report changes
+    last: Option<Result<Coordinates, PositionError>>,
+}
+
+#[derive(Default)]
+struct WatchState {
+    watches: BTreeMap<u32, Watch>,
+    next_id: u32,
+}
+
+/// The geolocation of a script context: the provider, the permissions
+/// and the page's watches
+///
+/// Cloning shares the watches.
+#[derive(Clone)]
+pub struct Geolocation {
+    provider: Rc<dyn LocationProvider>,
+    permissions: Permissions,
+    origin: String,
+    state: Rc<RefCell<WatchState>>,
+}
+
+impl Geolocation {
+    /// Get the position for the page, if its origin may know it
+    fn locate(&self) -> Result<Coordinates, PositionError> {
+        match self.permissions.request(&self.origin, PermissionName::Geolocation) {
+            PermissionState::Granted => self.provider.current_position(),
+            _ => Err(PositionError::PermissionDenied),
+        }
+    }
+
+    /// Ask the provider where the device is and call the watches whose
+    /// position changed
+    ///
+    /// Called by the engine when it polls. Returns the number of watches
+    /// called.
+    pub fn update(&self, context: &mut Context) -> JsResult<usize> {
+        let result = match self.permissions.state(&self.origin, PermissionName::Geolocation) {
+            PermissionState::Granted => self.provider.current_position(),
+            _ => Err(PositionError::PermissionDenied),
+        };
+        let mut due = Vec::new();
+        for watch in self.state.borrow_mut().watches.values_mut() {
+            if watch.last != Some(result) {
+                watch.last = Some(result);
+                due.push((watch.success.clone(), watch.error.clone()));
+            }
+        }
+        let count = due.len();
+        for (success, error) in due {
+            report(result, &success, error.as_ref(), context)?;
+        }
+        Ok(count)
+    }
+
+    /// Clear every watch, e.g. when the document is unloaded
+    ///
+    /// Returns the number of watches cleared.
+    pub fn clear_watches(&self) -> usize {
+        std::mem::take(&mut self.state.borrow_mut().watches).len()
+    }
+
+    /// Get the number of active watches
+    pub fn watch_count(&self) -> usize {
+        self.state.borrow().watches.len()
+    }
+}
+
+impl fmt::Debug for Geolocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Geolocation")
+            .field("origin", &self.origin)
+            .field("watches", &self.watch_count())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Create the `GeolocationPosition` scripts see
+fn position_object(coordinates: &Coordinates, context: &mut Context) -> JsObject {
+    let optional = |value: Option<f64>| value.map_or(JsValue::null(), JsValue::from);
+    let coords = ObjectInitializer::new(context)
+        .property(js_string!("latitude"), coordinates.latitude, Attribute::READONLY | Attribute::ENUMERABLE)
+        .property(js_string!("longitude"), coordinates.longitude, Attribute::READONLY | Attribute::ENUMERABLE)
+        .property(js_string!("accuracy"), coordinates.accuracy, Attribute::READONLY | Attribute::ENUMERABLE)
+        .property(js_string!("altitude"), optional(coordinates.altitude), Attribute::READONLY | Attribute::ENUMERABLE)
+        .property(js_string!("altitudeAccuracy"), JsValue::null(), Attribute::READONLY | Attribute::ENUMERABLE)
+        .property(js_string!("heading"), optional(coordinates.heading), Attribute::READONLY | Attribute::ENUMERABLE)
+        .property(js_string!("speed"), optional(coordinates.speed), Attribute::READONLY | Attribute::ENUMERABLE)
+        .build();
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0.0, |elapsed| elapsed.as_millis() as f64);
+    ObjectInitializer::new(context)
+        .property(js_string!("coords"), coords, Attribute::READONLY | Attribute::ENUMERABLE)
+        .property(js_string!("timestamp"), timestamp, Attribute::READONLY | Attribute::ENUMERABLE)
+        .build()
+}
+
+/// Create the `GeolocationPositionError` scripts see
+fn error_object(error: PositionError, context: &mut Context) -> JsObject {
+    let mut initializer = ObjectInitializer::new(context);
+    initializer
+        .property(js_string!("code"), error.code(), Attribute::READONLY | Attribute::ENUMERABLE)
+        .property(js_string!("message"), js_string!(error.to_string()), Attribute::READONLY | Attribute::ENUMERABLE);
+    for constant in [PositionError::PermissionDenied, PositionError::PositionUnavailable, PositionError::Timeout] {
+        initializer.property(js_string!(constant.name()), constant.code(), Attribute::READONLY);
+    }
+    initializer.build()
+}
+
+/// Call the success or error callback with a result, from a microtask
+fn report(result: Result<Coordinates, PositionError>, success: &JsObject, error: Option<&JsObject>, context: &mut Context) -> JsResult<()> {
+    let (callback, argument) = match result {
+        Ok(coordinates) => (Some(success), position_object(&coordinates, context)),
+        Err(failure) => (error, error_object(failure, context)),
+    };
+    let Some(callback) = callback.cloned().and_then(JsFunction::from_object) else {
+        return Ok(());
+    };
+    JsPromise::resolve(argument, context).then(Some(callback), None, context);
+    Ok(())
+}
+
+/// Get the success and error callbacks of a request
+fn callbacks(args: &[JsValue]) -> JsResult<(JsObject, Option<JsObject>)> {
+    let success = args
+        .first()
+        .and_then(JsValue::as_callable)
+        .cloned()
+        .ok_or_else(|| JsNativeError::typ().with_message("The success callback is not a function"))?;
+    let error = args.get(1).and_then(JsValue::as_callable).cloned();
+    Ok((success, error))
+}
+
+/// Captures of the `navigator.geolocation` methods
+#[derive(Clone, Trace, Finalize)]
+struct GeolocationBinding {
+    #[unsafe_ignore_trace]
+    geolocation: Geolocation,
+}
+
+/// Install `navigator.geolocation` for a document from `origin`
+///
+/// Returns the geolocation, which the engine polls to update watches.
+/// Creates `navigator` if the context does not have one yet.
+pub fn initialize_geolocation_bindings(
+    context: &mut Context,
+    provider: Rc<dyn LocationProvider>,
+    permissions: Permissions,
+    origin: &str,
+) -> JsResult<Geolocation> {
+    let geolocation = Geolocation { provider, permissions, origin: origin.to_string(), state: Rc::default() };
+    let binding = GeolocationBinding { geolocation: geolocation.clone() };
+
+    let get_current_position = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &GeolocationBinding, context| {
+            let (success, error) = callbacks(args)?;
+            report(binding.geolocation.locate(), &success, error.as_ref(), context)?;
+            Ok(JsValue::undefined())
+        },
+        binding.clone(),
+    );
+    let watch_position = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &GeolocationBinding, context| {
+            let (success, error) = callbacks(args)?;
+            let result = binding.geolocation.locate();
+            let id = {
+                let mut state = binding.geolocation.state.borrow_mut();
+                state.next_id += 1;
+                let id = state.next_id;
+                state.watches.insert(id, Watch { success: success.clone(), error: error.clone(), last: Some(result) });
+                id
+            };
+            report(result, &success, error.as_ref(), context)?;
+            Ok(id.into())
+        },
+        binding.clone(),
+    );
+    let clear_watch = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &GeolocationBinding, context| {
+            let id = args.first().cloned().unwrap_or_default().to_u32(context)?;
+            binding.geolocation.state.borrow_mut().watches.remove(&id);
+            Ok(JsValue::undefined())
+        },
+        binding,
+    );
+
+    let geolocation_object = ObjectInitializer::new(context)
+        .function(get_current_position, js_string!("getCurrentPosition"), 1)
+        .function(watch_position, js_string!("watchPosition"), 1)
+        .function(clear_watch, js_string!("clearWatch"), 1)
+        .build();
+    navigator_object(context)?.set(js_string!("geolocation"), geolocation_object, false, context)?;
+    Ok(geolocation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::sync::Arc;
+    use boa_engine::Source;
+
+    /// A provider the test moves around
+    struct MovingProvider {
+        latitude: Cell<f64>,
+    }
+
+    impl LocationProvider for MovingProvider {
+        fn current_position(&self) -> Result<Coordinates, PositionError> {
+            Ok(FixedLocationProvider::new(self.latitude.get(), 4.9, 5.0).coordinates)
+        }
+    }
+
+    fn log(context: &mut Context) -> String {
+        let log = context.eval(Source::from_bytes("log.join(',')")).unwrap();
+        log.to_string(context).unwrap().to_std_string_escaped()
+    }
+
+    #[test]
+    fn test_positions_are_permission_gated_and_watched() {
+        let context = &mut Context::default();
+        let provider = Rc::new(MovingProvider { latitude: Cell::new(52.37) });
+        let permissions = Permissions::new().with_prompt(Arc::new(|origin, _| Some(origin == "https://maps.example")));
+        let geolocation = initialize_geolocation_bindings(context, provider.clone(), permissions.clone(), "https://maps.example").unwrap();
+
+        let code = r#"
+            globalThis.log = [];
+            navigator.geolocation.getCurrentPosition((position) => log.push(position.coords.latitude, position.coords.speed));
+            log.push("sync");
+            globalThis.watch = navigator.geolocation.watchPosition((position) => log.push("watch:" + position.coords.latitude));
+        "#;
+        context.eval(Source::from_bytes(code)).unwrap();
+        context.run_jobs();
+        assert_eq!(log(context), "sync,52.37,,watch:52.37");
+
+        // Watches only hear about changes
+        assert_eq!(geolocation.update(context).unwrap(), 0);
+        provider.latitude.set(52.38);
+        assert_eq!(geolocation.update(context).unwrap(), 1);
+        context.run_jobs();
+        context.eval(Source::from_bytes("navigator.geolocation.clearWatch(watch)")).unwrap();
+        assert_eq!(geolocation.watch_count(), 0);
+        assert_eq!(log(context), "sync,52.37,,watch:52.37,watch:52.38");
+
+        // Other origins are denied
+        let context = &mut Context::default();
+        initialize_geolocation_bindings(context, Rc::new(FixedLocationProvider::default()), permissions, "https://ads.example").unwrap();
+        let code = r#"
+            globalThis.log = [];
+            navigator.geolocation.getCurrentPosition(() => log.push("located"), (error) => log.push(error.code === error.PERMISSION_DENIED, error.message));
+        "#;
+        context.eval(Source::from_bytes(code)).unwrap();
+        context.run_jobs();
+        assert_eq!(log(context), "true,User denied Geolocation");
+    }
+}
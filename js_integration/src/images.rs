@@ -0,0 +1,305 @@
+//! # Image Element Bindings
+//!
+//! This module gives the wrappers of `<img>` elements the loading members
+//! of `HTMLImageElement`: `complete`, `naturalWidth`, `naturalHeight`, the
+//! reflected `loading` and `decoding` attributes and `decode()`.
+//!
+//! ## Design Principles
+//!
+//! 1. **State on the Node**: The members read the loading state the DOM
+//!    crate keeps on the node, which the engine's `ImageLoader` updates.
+//! 2. **Decoding Is a Task**: `decode()` never settles its promise during
+//!    the call. Promises wait until the engine settles them on its next
+//!    turn, or after the image loaded, so a lazy image's promise waits for
+//!    it to come near the viewport.
+//! 3. **Sources Are Checked Again**: A promise rejects with an
+//!    `EncodingError` if the image breaks or its source changes before it
+//!    is decoded, as in browsers.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use boa_engine::{
+    object::{builtins::JsPromise, ObjectInitializer},
+    property::{Attribute, PropertyDescriptor},
+    builtins::promise::ResolvingFunctions,
+    js_string, Context, JsError, JsNativeError, JsObject, JsResult, JsValue, NativeFunction,
+};
+use boa_gc::{Finalize, Trace};
+use dom::images::ImageStatus;
+use dom::Node;
+use crate::node_handles::NodeHandleTable;
+
+/// A promise returned by `decode()` that is not settled yet
+struct PendingDecode {
+    node: Rc<Node>,
+    /// The source when `decode()` was called
+    src: String,
+    resolvers: ResolvingFunctions,
+}
+
+/// The `decode()` promises of one script context
+#[derive(Clone, Default)]
+pub struct ImageDecodes {
+    pending: Rc<RefCell<Vec<PendingDecode>>>,
+}
+
+impl ImageDecodes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check whether any `decode()` promise is waiting
+    pub fn has_pending(&self) -> bool {
+        !self.pending.borrow().is_empty()
+    }
+
+    /// Settle the promises of images that loaded, broke or changed source
+    ///
+    /// Loaded images are decoded first. Returns the number of promises
+    /// settled.
+    pub fn settle(&self, context: &mut Context) -> usize {
+        let mut settled = Vec::new();
+        self.pending.borrow_mut().retain(|decode| {
+            let outcome = decode_outcome(decode);
+            if let Some(outcome) = outcome {
+                settled.push((decode.resolvers.clone(), outcome));
+            }
+            outcome.is_none()
+        });
+        for (resolvers, outcome) in &settled {
+            let result = match outcome {
+                Ok(()) => resolvers.resolve.call(&JsValue::undefined(), &[], context),
+                Err(message) => {
+                    let error = create_encoding_error(context, message);
+                    resolvers.reject.call(&JsValue::undefined(), &[error], context)
+                }
+            };
+            if let Err(e) = result {
+                println!("❌ Failed to settle decode(): {}", e);
+            }
+        }
+        settled.len()
+    }
+}
+
+/// Decide whether a `decode()` promise can settle now
+fn decode_outcome(decode: &PendingDecode) -> Option<Result<(), &'static str>> {
+    if decode.node.get_attribute("src").as_deref() != Some(decode.src.as_str()) {
+        return Some(Err("The source image changed before it was decoded"));
+    }
+    let state = decode.node.image_state();
+    if state.src.as_deref() != Some(decode.src.as_str()) {
+        return None;
+    }
+    match state.status {
+        ImageStatus::Unavailable => None,
+        ImageStatus::Complete => {
+            decode.node.decode_image();
+            Some(Ok(()))
+        }
+        ImageStatus::Broken => Some(Err("The source image cannot be decoded")),
+    }
+}
+
+/// Create an `EncodingError` DOMException, which has no legacy code
+fn create_encoding_error(context: &mut Context, message: &str) -> JsValue {
+    ObjectInitializer::new(context)
+        .property(js_string!("name"), js_string!("EncodingError"), Attribute::all())
+        .property(js_string!("message"), js_string!(message), Attribute::all())
+        .property(js_string!("code"), 0, Attribute::all())
+        .build()
+        .into()
+}
+
+/// Captures for the image element members
+#[derive(Clone, Trace, Finalize)]
+struct ImageBinding {
+    #[unsafe_ignore_trace]
+    handles: Rc<RefCell<NodeHandleTable>>,
+    #[unsafe_ignore_trace]
+    decodes: ImageDecodes,
+}
+
+impl ImageBinding {
+    fn node(&self, this: &JsValue, context: &mut Context) -> JsResult<Rc<Node>> {
+        this.as_object()
+            .and_then(|wrapper| self.handles.borrow().resolve_wrapper(wrapper, context))
+            .filter(|node| node.is_image())
+            .ok_or_else(|| JsNativeError::typ().with_message("Illegal invocation: not an image").into())
+    }
+}
+
+/// A read-only image member and how it reads the node
+type ImageGetter = (&'static str, fn(&Node) -> JsValue);
+
+/// An attribute reflected as one of its known values
+type ReflectedAttribute = (&'static str, fn(&Node) -> &'static str);
+
+/// Add the `HTMLImageElement` loading members to the wrapper of an image
+pub fn install_image_members(
+    wrapper: &JsObject,
+    handles: Rc<RefCell<NodeHandleTable>>,
+    decodes: ImageDecodes,
+    context: &mut Context,
+) -> JsResult<()> {
+    let binding = ImageBinding { handles, decodes };
+
+    let getters: [ImageGetter; 3] = [
+        ("complete", |node| node.image_complete().into()),
+        ("naturalWidth", |node| node.image_state().natural_size.map_or(0, |(width, _)| width).into()),
+        ("naturalHeight", |node| node.image_state().natural_size.map_or(0, |(_, height)| height).into()),
+    ];
+    for (name, read) in getters {
+        let getter = NativeFunction::from_copy_closure_with_captures(
+            move |this, _args, binding: &ImageBinding, context| {
+                let node = binding.node(this, context)?;
+                Ok(read(&node))
+            },
+            binding.clone(),
+        );
+        wrapper.define_property_or_throw(
+            js_string!(name),
+            PropertyDescriptor::builder()
+                .get(getter.to_js_function(context.realm()))
+                .enumerable(true)
+                .configurable(true),
+            context,
+        )?;
+    }
+
+    // `loading` and `decoding` reflect their attributes, limited to the
+    // known values
+    let reflected: [ReflectedAttribute; 2] = [
+        ("loading", |node| node.loading_mode().as_str()),
+        ("decoding", |node| node.decoding_hint().as_str()),
+    ];
+    for (name, read) in reflected {
+        let getter = NativeFunction::from_copy_closure_with_captures(
+            move |this, _args, binding: &ImageBinding, context| {
+                let node = binding.node(this, context)?;
+                Ok(js_string!(read(&node)).into())
+            },
+            binding.clone(),
+        );
+        let setter = NativeFunction::from_copy_closure_with_captures(
+            move |this, args, binding: &ImageBinding, context| {
+                let node = binding.node(this, context)?;
+                let value = args.first().cloned().unwrap_or_default().to_string(context)?.to_std_string_escaped();
+                node.set_attribute(name, &value);
+                Ok(JsValue::undefined())
+            },
+            binding.clone(),
+        );
+        wrapper.define_property_or_throw(
+            js_string!(name),
+            PropertyDescriptor::builder()
+                .get(getter.to_js_function(context.realm()))
+                .set(setter.to_js_function(context.realm()))
+                .enumerable(true)
+                .configurable(true),
+            context,
+        )?;
+    }
+
+    let decode = NativeFunction::from_copy_closure_with_captures(
+        |this, _args, binding: &ImageBinding, context| {
+            let node = binding.node(this, context)?;
+            let src = node.get_attribute("src").filter(|src| !src.trim().is_empty());
+            let Some(src) = src else {
+                let error = create_encoding_error(context, "The image has no source");
+                return Ok(JsPromise::reject(JsError::from_opaque(error), context).into());
+            };
+            let (promise, resolvers) = JsPromise::new_pending(context);
+            binding.decodes.pending.borrow_mut().push(PendingDecode { node, src, resolvers });
+            Ok(promise.into())
+        },
+        binding,
+    );
+    wrapper.define_property_or_throw(
+        js_string!("decode"),
+        PropertyDescriptor::builder()
+            .value(decode.to_js_function(context.realm()))
+            .writable(true)
+            .enumerable(false)
+            .configurable(true),
+        context,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use boa_engine::Source;
+    use dom::images::ImageLoader;
+    use dom::Document;
+    use std::collections::HashSet;
+
+    fn eval(context: &mut Context, script: &str) -> String {
+        let value = context.eval(Source::from_bytes(script)).unwrap();
+        value.to_string(context).unwrap().to_std_string_escaped()
+    }
+
+    #[test]
+    fn test_decode_waits_for_lazy_images() {
+        let document = Document::new();
+        let image = document.create_element("img");
+        image.set_attribute("src", "photo.png");
+        image.set_attribute("loading", "lazy");
+        document.root.append_child(&image);
+        let handles = Rc::new(RefCell::new(NodeHandleTable::new()));
+        let decodes = ImageDecodes::new();
+        let mut loader = ImageLoader::new();
+
+        let mut context = Context::default();
+        let wrapper = handles.borrow_mut().wrap_node(&image, &mut context);
+        install_image_members(&wrapper, Rc::clone(&handles), decodes.clone(), &mut context).unwrap();
+        context.register_global_property(js_string!("img"), wrapper, Attribute::all()).unwrap();
+
+        let code = "var log = [img.loading, img.decoding, img.complete];
+            img.decode().then(() => log.push('decoded', img.complete));
+            img.decoding = 'bogus';
+            log.push(img.decoding);";
+        eval(&mut context, code);
+        context.run_jobs();
+        assert_eq!(eval(&mut context, "log.join()"), "lazy,auto,false,auto");
+
+        // The lazy image is not loaded until it comes near the viewport
+        assert_eq!(loader.update(&document.root, &HashSet::new()), 0);
+        assert_eq!(decodes.settle(&mut context), 0);
+        loader.update(&document.root, &HashSet::from([image.id]));
+        assert_eq!(decodes.settle(&mut context), 1);
+        context.run_jobs();
+        assert_eq!(eval(&mut context, "log.join()"), "lazy,auto,false,auto,decoded,true");
+        assert!(image.image_state().decoded);
+
+        // Changing the source rejects a pending promise
+        let code = "var rejected = ''; img.decode().catch((e) => rejected = e.name);";
+        eval(&mut context, code);
+        image.set_attribute("src", "other.png");
+        decodes.settle(&mut context);
+        context.run_jobs();
+        assert_eq!(eval(&mut context, "rejected"), "EncodingError");
+    }
+
+    #[test]
+    fn test_engine_fires_load_events() {
+        let html = "<html><body><img id=\"hero\" src=\"hero.png\"><img id=\"lazy\" src=\"lazy.png\" loading=\"lazy\" style=\"display: none\"></body></html>";
+        let (document, _) = html_parser::parse_html_string(html).unwrap();
+        let mut engine = crate::JsEngine::new();
+        engine.set_document(Rc::new(document));
+        engine.set_stylesheet(css_parser::parse_css(""));
+        engine
+            .execute("var log = []; \
+                      document.querySelectorAll('img').forEach(function (img) { \
+                          img.addEventListener('load', function () { log.push(img.matches('#hero') + ':' + img.complete); }); \
+                      });")
+            .unwrap();
+
+        let layout = engine.execute_with_layout_update("").unwrap().unwrap();
+        let viewport = layout::Dimensions::new(0.0, 0.0, 800.0, 600.0);
+        assert_eq!(engine.run_image_steps(&layout, viewport), 1);
+        assert_eq!(engine.run_image_steps(&layout, viewport), 0);
+        assert_eq!(eval(&mut engine.context, "log.join()"), "true:true");
+    }
+}
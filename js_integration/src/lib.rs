@@ -18,18 +18,24 @@
 //!    when DOM is modified by JavaScript.
 
 use dom::{Document, Node, NodeType};
+use dom::connection::{ConnectionChange, ConnectionEffect};
 use dom::event_types::*;
 use dom::events::*;
 use dom::delegation::*;
 use dom::dom_event_integration::*;
 use layout::{LayoutBox, LayoutEngine};
+use networking::integrity::IntegrityMetadata;
+use networking::content_blocking::{BlockDecision, ContentBlocker};
+use networking::mixed_content::{check_mixed_content, MixedContentDecision, RequestDestination};
 use css_parser::Stylesheet;
+use css_parser::media::MediaFeatures;
 use boa_engine::{
     object::ObjectInitializer,
     property::Attribute,
     Context, JsValue, NativeFunction, Source,
     js_string,
 };
+use std::cell::RefCell;
 use std::rc::Rc;
 use std::collections::HashMap;
 use std::time::{Duration, Instant};
@@ -43,6 +49,99 @@ pub mod microtask_queue;
 pub mod promise_host;
 pub mod fetch_binding;
 pub mod abort_controller;
+pub mod readable_stream;
+pub mod file_api;
+
+// Unified task scheduling
+pub mod event_loop;
+
+// DOM node handles exposed to JavaScript
+pub mod node_handles;
+
+// Capability and resource limits for untrusted scripts
+pub mod sandbox;
+
+// Async clipboard API
+pub mod clipboard;
+
+// Per-origin permissions, navigator.permissions and Notification
+pub mod permissions;
+pub mod notifications;
+
+// navigator.geolocation and the providers positions come from
+pub mod geolocation;
+
+// caches and Cache objects of the Cache Storage API
+pub mod cache_storage;
+
+// Resource and paint timing
+pub mod performance;
+
+// window.matchMedia and media query change events
+pub mod media_queries;
+
+// console object and the sink page and engine messages go to
+pub mod console;
+
+// window.getComputedStyle
+pub mod computed_style;
+
+// HTMLMediaElement members of <video> and <audio> wrappers
+pub mod media;
+
+// Loading members and decode() of <img> wrappers
+pub mod images;
+
+// canvas.getContext('webgl')
+pub mod webgl;
+
+// element.animate() and the Animation objects it returns
+pub mod animations;
+
+// The devtools console: evaluating input in the page's context
+pub mod repl;
+
+// Source maps of bundled scripts, for errors and console call sites
+pub mod source_maps;
+
+// Which functions of the page's scripts ran
+pub mod coverage;
+
+// Node wrappers, element.matches/closest and document.body and friends
+pub mod element_wrappers;
+
+// Live HTMLCollections and static NodeLists
+pub mod collections;
+
+// Event constructors and document.createEvent
+pub mod event_objects;
+
+// addEventListener and dispatchEvent on nodes
+pub mod event_targets;
+
+// navigator.getGamepads and gamepad connection events
+pub mod gamepad;
+
+// alert, confirm and prompt
+pub mod user_prompts;
+
+// Date on the engine clock and a seeded Math.random
+pub mod deterministic;
+
+// The URL interface
+pub mod url_binding;
+
+// ResizeObserver on the layout engine's geometry observers
+pub mod resize_observer;
+
+// beforeunload, pagehide and unload, and the teardown of left documents
+pub mod unload;
+
+// The origin-checked channel between shell features and their page helpers
+pub mod shell_bridge;
+
+// The WebAssembly namespace: compile, instantiate and streaming
+pub mod webassembly;
 
 use thiserror::Error;
 
@@ -99,10 +198,12 @@ pub struct JsEngine {
     document: Option<Rc<Document>>,
     stylesheet: Option<Stylesheet>,
     event_listeners: HashMap<String, Vec<JsValue>>,
-    timers: HashMap<u32, TimerTask>,
-    next_timer_id: u32,
+    // Scheduler for timers, tasks, microtasks and rendering
+    event_loop: event_loop::EventLoop,
+    render_callback: Option<Box<dyn FnMut(f64)>>,
+    /// Observer of the time each task takes, set by profilers
+    task_observer: Option<Box<dyn FnMut(event_loop::TaskTiming)>>,
     // New event system components
-    event_dispatcher: EventDispatcher,
     event_delegation: EventDelegationSystem,
     delegation_optimizer: DelegationOptimizer,
     pub dom_event_manager: DomEventManager,
@@ -113,15 +214,75 @@ pub struct JsEngine {
     // Promise and microtask system
     promise_host: promise_host::PromiseHost,
     fetch_binding: fetch_binding::FetchBinding,
-    abort_controller_host: abort_controller::AbortControllerHost,
     // Microtask processing
     microtask_trace_enabled: bool,
+    // Handles for DOM nodes referenced from JavaScript, and the removal
+    // observer that pins them when their nodes are detached
+    node_handles: Rc<RefCell<node_handles::NodeHandleTable>>,
+    removal_observer: dom::mutations::ObserverId,
+    // Capabilities exposed to scripts and per-API call counts
+    sandbox_policy: sandbox::SandboxPolicy,
+    api_calls: sandbox::ApiCallCounters,
+    // Resource and paint timing, and the observers of new entries
+    performance_timeline: performance::PerformanceTimeline,
+    performance_observers: performance::PerformanceObservers,
+    // `matchMedia` lists, updated when the shell's media features change
+    media_query_lists: media_queries::MediaQueryLists,
+    // Messages from `console` and from the engine about the page
+    console: console::ConsoleSink,
+    // External scripts that have run and their source maps
+    scripts: source_maps::ScriptRegistry,
+    // Functions of instrumented scripts and how often they ran
+    coverage: coverage::ScriptCoverage,
+    // What `getComputedStyle` computes styles against
+    style_source: computed_style::StyleSource,
+    // Playback of media elements and the events it queues
+    media_controller: Rc<RefCell<dom::media::MediaController>>,
+    // Loading of images, the events it queues and `decode()` promises
+    image_loader: dom::images::ImageLoader,
+    image_decodes: images::ImageDecodes,
+    // WebGL contexts of canvases, for the renderer to replay
+    webgl_contexts: webgl::WebGlContexts,
+    // Animations started with `element.animate()`
+    animations: animations::ScriptAnimations,
+    // Prototypes of event objects and the listeners scripts added to nodes
+    event_prototypes: event_objects::EventPrototypes,
+    node_listeners: event_targets::EventListeners,
+    gamepads: gamepad::Gamepads,
+    // The shell's handler of `alert`, `confirm` and `prompt`
+    user_prompts: user_prompts::UserPrompts,
+    // Scripts inserted by scripts, waiting to run, and the connection
+    // observer of the document that queues them
+    inserted_scripts: Rc<RefCell<Vec<Rc<Node>>>>,
+    connection_observer: Option<dom::mutations::ObserverId>,
+    // The visibility scripts last saw, to fire `visibilitychange` on change
+    visibility_state: dom::page_visibility::VisibilityState,
+    // `navigator.geolocation`, if the shell enabled it
+    geolocation: Option<geolocation::Geolocation>,
+    // `ResizeObserver`s and the sizes layout reported to them
+    resize_observers: resize_observer::ResizeObservers,
+    // Filter lists the scripts of the page are checked against
+    content_blocker: ContentBlocker,
+    // Whether the document was unloaded, see `unload`
+    unloaded: bool,
+    // Helper scripts of shell features and the messages they posted
+    shell_bridge: shell_bridge::ShellBridge,
+}
+
+/// An external script referenced by a `<script src>` element
+#[derive(Debug, Clone)]
+struct ExternalScript {
+    url: String,
+    integrity: Option<String>,
 }
 
 impl JsEngine {
     /// Create a new JavaScript engine
     pub fn new() -> Self {
-        let mut context = Context::default();
+        let mut context = Context::builder()
+            .host_hooks(&deterministic::CLOCK_HOOKS)
+            .build()
+            .expect("Failed to create JavaScript context");
         
         // Set up the global object with DOM bindings
         Self::setup_global_object(&mut context);
@@ -140,19 +301,67 @@ impl JsEngine {
         fetch_binding.initialize_fetch_bindings(&mut context)
             .expect("Failed to initialize fetch bindings");
         
-        let abort_controller_host = abort_controller::AbortControllerHost::new();
-        abort_controller_host.initialize_abort_controller_bindings(&mut context)
+        abort_controller::initialize_abort_controller_bindings(&mut context)
             .expect("Failed to initialize AbortController bindings");
         
+        file_api::initialize_file_api_bindings(&mut context)
+            .expect("Failed to initialize Blob/File/FormData bindings");
+        
+        url_binding::initialize_url_bindings(&mut context)
+            .expect("Failed to initialize URL bindings");
+        
+        webassembly::initialize_webassembly_bindings(&mut context)
+            .expect("Failed to initialize WebAssembly bindings");
+        
+        let event_prototypes = event_objects::initialize_event_bindings(&mut context)
+            .expect("Failed to initialize event constructors");
+        
+        let performance_timeline = performance::PerformanceTimeline::new();
+        let performance_observers = performance::initialize_performance_bindings(&mut context, performance_timeline.clone())
+            .expect("Failed to initialize performance bindings");
+        
+        let media_query_lists = media_queries::initialize_media_query_bindings(&mut context, MediaFeatures::default())
+            .expect("Failed to initialize matchMedia bindings");
+        
+        let console = console::ConsoleSink::new();
+        let scripts = source_maps::ScriptRegistry::new();
+        console::initialize_console_bindings_with_scripts(&mut context, console.clone(), scripts.clone())
+            .expect("Failed to initialize console bindings");
+        
+        let coverage = coverage::ScriptCoverage::new();
+        coverage::initialize_coverage_bindings(&mut context, coverage.clone())
+            .expect("Failed to initialize coverage bindings");
+        
+        let gamepads = gamepad::initialize_gamepad_bindings(&mut context)
+            .expect("Failed to initialize Gamepad bindings");
+        
+        let user_prompts = user_prompts::initialize_user_prompt_bindings(&mut context)
+            .expect("Failed to initialize alert, confirm and prompt");
+        
+        let node_handles = Rc::new(RefCell::new(node_handles::NodeHandleTable::new()));
+        let removal_observer = dom::connection::observe_removals({
+            let handles = Rc::downgrade(&node_handles);
+            move |node| {
+                if let Some(handles) = handles.upgrade() {
+                    handles.borrow_mut().pin_subtree(node);
+                }
+            }
+        });
+        let style_source = computed_style::initialize_computed_style_bindings(&mut context, Rc::clone(&node_handles))
+            .expect("Failed to initialize getComputedStyle bindings");
+        
+        let resize_observers = resize_observer::initialize_resize_observer_bindings(&mut context, Rc::clone(&node_handles))
+            .expect("Failed to initialize ResizeObserver bindings");
+        
         JsEngine {
             context,
             document: None,
             stylesheet: None,
             event_listeners: HashMap::new(),
-            timers: HashMap::new(),
-            next_timer_id: 1,
+            event_loop: event_loop::EventLoop::new(),
+            render_callback: None,
+            task_observer: None,
             // Initialize new event system components
-            event_dispatcher: EventDispatcher::new(),
             event_delegation: EventDelegationSystem::new(),
             delegation_optimizer: DelegationOptimizer::new(),
             dom_event_manager: DomEventManager::new(),
@@ -163,19 +372,382 @@ impl JsEngine {
             // Initialize Promise and microtask system
             promise_host,
             fetch_binding,
-            abort_controller_host,
             microtask_trace_enabled: false,
+            node_handles,
+            removal_observer,
+            sandbox_policy: sandbox::SandboxPolicy::unrestricted(),
+            api_calls: sandbox::ApiCallCounters::new(),
+            performance_timeline,
+            performance_observers,
+            media_query_lists,
+            console,
+            scripts,
+            coverage,
+            style_source,
+            media_controller: Rc::new(RefCell::new(dom::media::MediaController::new())),
+            image_loader: dom::images::ImageLoader::new(),
+            image_decodes: images::ImageDecodes::new(),
+            webgl_contexts: Rc::new(RefCell::new(HashMap::new())),
+            animations: animations::ScriptAnimations::new(),
+            event_prototypes,
+            node_listeners: event_targets::EventListeners::new(),
+            gamepads,
+            user_prompts,
+            inserted_scripts: Rc::new(RefCell::new(Vec::new())),
+            connection_observer: None,
+            visibility_state: dom::page_visibility::VisibilityState::Visible,
+            geolocation: None,
+            resize_observers,
+            content_blocker: ContentBlocker::new(),
+            unloaded: false,
+            shell_bridge: shell_bridge::ShellBridge::new(),
+        }
+    }
+
+    /// Create a JavaScript engine restricted by a sandbox policy
+    pub fn with_sandbox_policy(policy: sandbox::SandboxPolicy) -> JsResult<Self> {
+        let mut engine = Self::new();
+        engine.apply_sandbox_policy(policy)?;
+        Ok(engine)
+    }
+
+    /// Restrict this engine with a sandbox policy
+    ///
+    /// The effective policy is the intersection with the current one:
+    /// globals removed earlier are never restored.
+    pub fn apply_sandbox_policy(&mut self, policy: sandbox::SandboxPolicy) -> JsResult<()> {
+        let effective = self.sandbox_policy.intersect(&policy);
+        sandbox::apply_sandbox_policy(&mut self.context, &effective, &self.api_calls)?;
+        self.user_prompts.set_blocked(!effective.allow_modals);
+        self.sandbox_policy = effective;
+        Ok(())
+    }
+
+    /// Get the sandbox policy in effect
+    pub fn sandbox_policy(&self) -> &sandbox::SandboxPolicy {
+        &self.sandbox_policy
+    }
+
+    /// Get the number of calls made to each exposed capability API
+    ///
+    /// Calls are counted once a policy with `count_api_calls` is applied.
+    pub fn api_call_counts(&self) -> HashMap<String, u64> {
+        self.api_calls.snapshot()
+    }
+
+    /// Expose `navigator.clipboard` to scripts from `origin`
+    ///
+    /// Access is checked against `permissions`, which prompt the shell the
+    /// first time the origin reads or writes.
+    pub fn enable_clipboard(
+        &mut self,
+        clipboard: dom::clipboard::Clipboard,
+        permissions: clipboard::ClipboardPermissions,
+        origin: &str,
+    ) -> JsResult<()> {
+        clipboard::initialize_clipboard_bindings(&mut self.context, clipboard, permissions, origin)?;
+        Ok(())
+    }
+
+    /// Replace `Math.random()` with a generator seeded with `seed`, so
+    /// scripts draw the same numbers on every run
+    pub fn set_random_seed(&mut self, seed: u64) -> JsResult<()> {
+        deterministic::install_seeded_random(&mut self.context, seed)?;
+        Ok(())
+    }
+
+    /// Expose `navigator.permissions` and `Notification` to a document
+    /// from `origin`
+    ///
+    /// Permissions are read from and recorded in `permissions`, and
+    /// notifications the page may show are handed to `handler`.
+    pub fn enable_permissions(
+        &mut self,
+        permissions: permissions::Permissions,
+        origin: &str,
+        handler: notifications::NotificationHandler,
+    ) -> JsResult<()> {
+        permissions::initialize_permissions_bindings(&mut self.context, permissions.clone(), origin)?;
+        notifications::initialize_notification_bindings(&mut self.context, permissions, origin, handler)?;
+        Ok(())
+    }
+
+    /// Expose `navigator.geolocation` to a document from `origin`, with
+    /// positions from `provider` once `permissions` grant them
+    pub fn enable_geolocation(
+        &mut self,
+        provider: Rc<dyn geolocation::LocationProvider>,
+        permissions: permissions::Permissions,
+        origin: &str,
+    ) -> JsResult<()> {
+        let geolocation = geolocation::initialize_geolocation_bindings(&mut self.context, provider, permissions, origin)?;
+        self.geolocation = Some(geolocation);
+        Ok(())
+    }
+
+    /// Poll the location provider and call the `watchPosition()` callbacks
+    /// of positions that changed
+    ///
+    /// Returns the number of watches called.
+    pub fn update_geolocation(&mut self) -> usize {
+        let Some(geolocation) = self.geolocation.clone() else {
+            return 0;
+        };
+        match geolocation.update(&mut self.context) {
+            Ok(count) => {
+                self.context.run_jobs();
+                count
+            }
+            Err(e) => {
+                println!("❌ Failed to update geolocation watches: {}", e);
+                0
+            }
+        }
+    }
+
+    /// Show the `alert()`, `confirm()` and `prompt()` of scripts with the
+    /// shell's handler
+    ///
+    /// The handler blocks until the user answers, which pauses the page.
+    /// Without one, every prompt is dismissed.
+    pub fn set_user_prompt_handler(&mut self, handler: user_prompts::UserPromptHandler) {
+        self.user_prompts.set_handler(handler);
+    }
+
+    /// Expose `caches` to scripts of a document at `document_url`
+    ///
+    /// The page sees the caches of its origin in `storage`. Documents
+    /// that are not secure contexts do not get `caches`; returns whether
+    /// the binding was installed.
+    pub fn enable_cache_storage(
+        &mut self,
+        storage: &networking::cache_storage::CacheStorage,
+        document_url: &str,
+    ) -> JsResult<bool> {
+        let origin = dom::Origin::from_url(document_url);
+        if !cache_storage::is_secure_origin(&origin) {
+            return Ok(false);
+        }
+        // Requests resolve against the base URL of the loaded document
+        let base_url = self
+            .document
+            .as_deref()
+            .and_then(networking::document_base_url)
+            .map(|url| url.to_string())
+            .unwrap_or_else(|| document_url.to_string());
+        cache_storage::initialize_cache_storage_bindings(&mut self.context, storage.for_origin(&origin.serialize()), Some(&base_url))?;
+        Ok(true)
+    }
+
+    /// Use the timeline the shell records the document's resource and
+    /// paint timings on
+    ///
+    /// Replaces `performance` and `PerformanceObserver`; observers created
+    /// before the call no longer receive entries.
+    pub fn set_performance_timeline(&mut self, timeline: performance::PerformanceTimeline) -> JsResult<()> {
+        self.performance_observers = performance::initialize_performance_bindings(&mut self.context, timeline.clone())?;
+        self.performance_timeline = timeline;
+        Ok(())
+    }
+
+    /// Read the time from `clock` instead of the thread's clock
+    ///
+    /// Timers, idle deadlines, animation frame timestamps and
+    /// `performance.now()` follow the clock; a test injecting a
+    /// `dom::clock::ManualClock` steps them through virtual time. `Date`
+    /// still reads the thread's clock, see `dom::clock::set_clock`.
+    pub fn set_clock(&mut self, clock: dom::clock::SharedClock) {
+        self.performance_timeline.set_clock(clock.clone());
+        self.event_loop.set_clock(clock);
+    }
+
+    /// Get the current time by the engine's clock
+    pub fn now(&self) -> Instant {
+        self.event_loop.now()
+    }
+
+    /// Get the timeline behind `performance`
+    pub fn performance_timeline(&self) -> &performance::PerformanceTimeline {
+        &self.performance_timeline
+    }
+
+    /// Deliver new performance entries to `PerformanceObserver` callbacks
+    ///
+    /// Returns the number of callbacks that ran.
+    pub fn deliver_performance_entries(&mut self) -> usize {
+        let count = self.performance_observers.deliver(&mut self.context);
+        if self.microtask_trace_enabled && count > 0 {
+            println!("🔸 Delivered performance entries to {} observers", count);
+        }
+        count
+    }
+
+    /// Deliver the sizes layout reported since the last call to the
+    /// `ResizeObserver` callbacks
+    ///
+    /// Returns the number of callbacks that ran. Called by
+    /// `process_event_loop`.
+    pub fn deliver_resize_observations(&mut self) -> usize {
+        if !self.resize_observers.has_pending() {
+            return 0;
+        }
+        let wrappers = self.element_wrappers();
+        let count = self.resize_observers.deliver(&wrappers, &mut self.context);
+        if self.microtask_trace_enabled && count > 0 {
+            println!("🔸 Delivered sizes to {} resize observers", count);
+        }
+        count
+    }
+
+    /// Use the geometry observers the shell's layout engines notify, so
+    /// `ResizeObserver`s see the page's layouts
+    pub fn set_geometry_observers(&mut self, observers: layout::GeometryObservers) {
+        self.resize_observers.set_geometry(observers);
+    }
+
+    /// Get the geometry observers `ResizeObserver` targets are subscribed
+    /// with
+    pub fn geometry_observers(&self) -> layout::GeometryObservers {
+        self.resize_observers.geometry()
+    }
+
+    /// Set the environment `matchMedia` queries are evaluated against
+    ///
+    /// Fires `change` on the `MediaQueryList`s whose result flipped and
+    /// returns their number.
+    pub fn set_media_features(&mut self, features: MediaFeatures) -> usize {
+        self.style_source.set_media_features(features.clone());
+        let count = self.media_query_lists.update(features, &mut self.context);
+        if self.microtask_trace_enabled && count > 0 {
+            println!("🔸 Media queries changed for {} lists", count);
         }
+        count
+    }
+
+    /// Set the visibility state of the document, firing `visibilitychange`
+    /// at `document` if it differs from the one scripts last saw
+    ///
+    /// The shell also throttles the event loop of hidden pages, see
+    /// `EventLoop::set_min_timer_delay`. Returns whether the event fired.
+    pub fn set_visibility_state(&mut self, state: dom::page_visibility::VisibilityState) -> bool {
+        let Some(document) = self.document.clone() else {
+            return false;
+        };
+        dom::page_visibility::set_visibility_state(&document.root, state);
+        if state == self.visibility_state {
+            return false;
+        }
+        self.visibility_state = state;
+        let document_object = self.context.global_object().get(js_string!("document"), &mut self.context).ok().and_then(|value| value.as_object().cloned());
+        let Some(document_object) = document_object else {
+            return false;
+        };
+        let binding = event_targets::EventTargetBinding { target: event_targets::BoundTarget::Document(Rc::clone(&document.root)), wrappers: self.element_wrappers() };
+        let event = event_objects::create_trusted_event(&self.event_prototypes, dom::event_types::EventInterface::Event, "visibilitychange", true, false);
+        if let Err(e) = binding.dispatch(Some(&document.root), &document_object, &event, &mut self.context) {
+            println!("❌ Failed to fire visibilitychange: {}", e);
+        }
+        true
+    }
+
+    /// Get the environment `matchMedia` queries are evaluated against
+    pub fn media_features(&self) -> MediaFeatures {
+        self.media_query_lists.features()
+    }
+
+    /// Get the sink `console` messages and engine reports go to
+    pub fn console(&self) -> &console::ConsoleSink {
+        &self.console
+    }
+
+    /// Get the coverage of the scripts run since `coverage().start()`
+    pub fn coverage(&self) -> &coverage::ScriptCoverage {
+        &self.coverage
+    }
+
+    /// Send `console` messages and engine reports to a shared sink
+    pub fn set_console_sink(&mut self, sink: console::ConsoleSink) -> JsResult<()> {
+        console::initialize_console_bindings_with_scripts(&mut self.context, sink.clone(), self.scripts.clone())?;
+        self.console = sink;
+        Ok(())
     }
 
     /// Set the document for this JavaScript engine
+    ///
+    /// `document.documentElement`, `head` and `body` read the new document.
     pub fn set_document(&mut self, document: Rc<Document>) {
         self.document = Some(Rc::clone(&document));
+        let document_object = self.context.global_object().get(js_string!("document"), &mut self.context).ok().and_then(|value| value.as_object().cloned());
+        if let Some(document_object) = document_object {
+            let wrappers = self.element_wrappers();
+            if let Err(e) = element_wrappers::install_document_accessors(&document_object, Rc::clone(&document), wrappers, &mut self.context) {
+                println!("❌ Failed to install document accessors: {}", e);
+            }
+        }
+        let global = self.context.global_object();
+        let window = event_targets::EventTargetBinding { target: event_targets::BoundTarget::Window, wrappers: self.element_wrappers() };
+        if let Err(e) = window.install(&global, &mut self.context) {
+            println!("❌ Failed to install window event target: {}", e);
+        }
+        self.observe_connections(&document);
+        self.visibility_state = dom::page_visibility::visibility_state(&document.root);
         self.dom_event_manager.set_document(document);
     }
 
+    /// Queue the scripts that are inserted into a document other than by
+    /// the parser, for `run_inserted_scripts`
+    fn observe_connections(&mut self, document: &Rc<Document>) {
+        if let Some(id) = self.connection_observer.take() {
+            dom::connection::unobserve(id);
+        }
+        self.inserted_scripts.borrow_mut().clear();
+        let queue = Rc::clone(&self.inserted_scripts);
+        let document = Rc::downgrade(document);
+        self.connection_observer = Some(dom::connection::observe(move |element, change| {
+            let owned = document.upgrade().is_some_and(|document| document.owns(element));
+            if change == ConnectionChange::Connected
+                && owned
+                && !element.is_parser_inserted()
+                && dom::connection::effect(element) == Some(ConnectionEffect::RunScript)
+            {
+                queue.borrow_mut().push(Rc::clone(element));
+            }
+        }));
+    }
+
+    /// Run the inline scripts that were inserted into the document since
+    /// the last call, in insertion order
+    ///
+    /// Each script runs at most once, and only if it is still connected.
+    /// Returns the number of scripts run; a failing script is reported to
+    /// the console and does not stop the others.
+    pub fn run_inserted_scripts(&mut self) -> usize {
+        if !self.sandbox_policy.allow_scripts {
+            self.inserted_scripts.borrow_mut().clear();
+            return 0;
+        }
+        let mut count = 0;
+        loop {
+            let pending = std::mem::take(&mut *self.inserted_scripts.borrow_mut());
+            if pending.is_empty() {
+                return count;
+            }
+            for script in pending {
+                if !script.is_connected() || script.has_attribute("src") || !script.start_script() {
+                    continue;
+                }
+                let code = self.coverage.instrument(None, &Self::extract_text_content(&script));
+                count += 1;
+                if let Err(e) = Self::eval_script(&mut self.context, &mut self.metrics, &code) {
+                    self.console.report(console::ConsoleLevel::Error, e.to_string(), None);
+                }
+            }
+        }
+    }
+
     /// Set the stylesheet for this JavaScript engine
     pub fn set_stylesheet(&mut self, stylesheet: Stylesheet) {
+        self.style_source.set_stylesheet(stylesheet.clone());
         self.stylesheet = Some(stylesheet);
     }
 
@@ -190,10 +762,17 @@ impl JsEngine {
     }
 
     /// Execute all inline script tags in the document
+    ///
+    /// Does nothing if the sandbox policy does not allow the document's
+    /// scripts, as in frames sandboxed without `allow-scripts`.
     pub fn execute_inline_scripts(&mut self) -> JsResult<()> {
+        if !self.sandbox_policy.allow_scripts {
+            println!("🛑 Sandbox blocked the document's scripts");
+            return Ok(());
+        }
         if let Some(ref document) = self.document {
             let start_time = Instant::now();
-            Self::extract_and_execute_scripts(&document.root, &mut self.context)?;
+            Self::extract_and_execute_scripts(&document.root, &mut self.context, &self.coverage)?;
             self.metrics.total_execution_time += start_time.elapsed();
             self.metrics.script_count += 1;
         }
@@ -201,13 +780,24 @@ impl JsEngine {
     }
 
     /// Execute all external script tags in the document
+    ///
+    /// Does nothing if the sandbox policy does not allow the document's
+    /// scripts.
     pub fn execute_external_scripts(&mut self) -> JsResult<()> {
-        if let Some(ref document) = self.document {
+        if !self.sandbox_policy.allow_scripts {
+            return Ok(());
+        }
+        if let Some(document) = self.document.clone() {
             let start_time = Instant::now();
             let external_scripts = Self::extract_external_scripts(&document.root);
             
-            for script_url in external_scripts {
-                match self.fetch_and_execute_script(&script_url) {
+            for script in external_scripts {
+                let Some(script_url) = networking::resolve_url(&document, &script.url).map(|url| url.to_string()) else {
+                    println!("⚠️  Failed to resolve external script URL {}", script.url);
+                    self.metrics.error_count += 1;
+                    continue;
+                };
+                match self.fetch_and_execute_script_with_integrity(&script_url, script.integrity.as_deref()) {
                     Ok(_) => {
                         println!("✅ Successfully loaded and executed external script: {}", script_url);
                         self.metrics.script_count += 1;
@@ -226,15 +816,26 @@ impl JsEngine {
 
     /// Fetch and execute an external JavaScript file
     pub fn fetch_and_execute_script(&mut self, url: &str) -> JsResult<()> {
+        self.fetch_and_execute_script_with_integrity(url, None)
+    }
+
+    /// Fetch an external JavaScript file and execute it if it matches the
+    /// `integrity` attribute of its element
+    /// 
+    /// A script failing the check is not executed, and the failure is
+    /// reported to the console.
+    pub fn fetch_and_execute_script_with_integrity(&mut self, url: &str, integrity: Option<&str>) -> JsResult<()> {
         let start_time = Instant::now();
+        self.check_script_mixed_content(url)?;
+        self.check_script_blocked(url)?;
         
         // Use the runtime to fetch the script
-        let script_content = self.runtime.block_on(async {
+        let script_bytes = self.runtime.block_on(async {
             match reqwest::get(url).await {
                 Ok(response) => {
                     if response.status().is_success() {
-                        match response.text().await {
-                            Ok(text) => Ok(text),
+                        match response.bytes().await {
+                            Ok(bytes) => Ok(bytes),
                             Err(e) => Err(JsIntegrationError::ExecutionError(format!("Failed to read script content: {}", e))),
                         }
                     } else {
@@ -244,10 +845,73 @@ impl JsEngine {
                 Err(e) => Err(JsIntegrationError::ExecutionError(format!("Network error: {}", e))),
             }
         })?;
+        self.execute_fetched_script(url, &script_bytes, integrity, start_time)
+    }
+
+    /// Refuse to load an insecure script into a secure document
+    /// 
+    /// Blocked scripts are reported to the console.
+    fn check_script_mixed_content(&mut self, url: &str) -> JsResult<()> {
+        let (Some(document), Ok(parsed)) = (&self.document, url::Url::parse(url)) else {
+            return Ok(());
+        };
+        if check_mixed_content(&document.origin(), &parsed, RequestDestination::Script, true) == MixedContentDecision::Blocked {
+            let error = networking::NetworkError::MixedContent {
+                url: url.to_string(),
+                destination: RequestDestination::Script.as_str(),
+            };
+            self.metrics.error_count += 1;
+            self.console.report(console::ConsoleLevel::Error, error.to_string(), Some(url));
+            return Err(JsIntegrationError::ExecutionError(error.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Refuse to load a script the content blocker's filter lists match
+    /// 
+    /// Blocked scripts are reported to the console.
+    fn check_script_blocked(&mut self, url: &str) -> JsResult<()> {
+        let Ok(parsed) = url::Url::parse(url) else {
+            return Ok(());
+        };
+        let origin = self.document.as_ref().map(|document| document.origin());
+        if let BlockDecision::Blocked { rule } = self.content_blocker.classify(&parsed, origin.as_ref(), RequestDestination::Script) {
+            let error = networking::NetworkError::BlockedByClient {
+                url: url.to_string(),
+                destination: RequestDestination::Script.as_str(),
+                rule,
+            };
+            self.console.report(console::ConsoleLevel::Error, error.to_string(), Some(url));
+            return Err(JsIntegrationError::ExecutionError(error.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Execute the body of a fetched script after checking its integrity
+    fn execute_fetched_script(&mut self, url: &str, script_bytes: &[u8], integrity: Option<&str>, start_time: Instant) -> JsResult<()> {
+        if let Some(integrity) = integrity {
+            if let Err(e) = IntegrityMetadata::parse(integrity).verify(script_bytes) {
+                self.metrics.error_count += 1;
+                self.console.report(
+                    console::ConsoleLevel::Error,
+                    format!("Blocked script from '{}': {}", url, e),
+                    Some(url),
+                );
+                return Err(JsIntegrationError::ExecutionError(e.to_string()));
+            }
+        }
+
+        let text = String::from_utf8_lossy(script_bytes);
+        let map = self.load_source_map(url, &text);
+        self.scripts.register(url, &text, map);
 
         // Execute the script content
-        let source = Source::from_bytes(&script_content);
-        match self.context.eval(source) {
+        let code = self.coverage.instrument(Some(url), &text);
+        let source = Source::from_bytes(&code);
+        self.scripts.enter(url);
+        let result = self.context.eval(source);
+        self.scripts.leave();
+        match result {
             Ok(_) => {
                 self.metrics.total_execution_time += start_time.elapsed();
                 self.metrics.script_count += 1;
@@ -255,19 +919,48 @@ impl JsEngine {
             }
             Err(e) => {
                 self.metrics.error_count += 1;
-                Err(JsIntegrationError::ExecutionError(format!("Script execution error: {}", e)))
+                let message = self.scripts.remap_error(url, &e.to_string());
+                Err(JsIntegrationError::ExecutionError(format!("Script execution error: {}", message)))
             }
         }
     }
 
-    /// Extract external script URLs from the DOM
-    fn extract_external_scripts(node: &Rc<Node>) -> Vec<String> {
+    /// Fetch and parse the source map named by a script's
+    /// `//# sourceMappingURL=` comment
+    ///
+    /// A map that fails to load is reported to the console, and the script
+    /// runs unmapped.
+    fn load_source_map(&mut self, script_url: &str, script: &str) -> Option<source_maps::SourceMap> {
+        let map_url = source_maps::resolve_map_url(script_url, source_maps::source_mapping_url(script)?)?;
+        let json = match source_maps::decode_data_url(&map_url) {
+            Some(json) => json.map_err(|e| e.to_string()),
+            None => self.runtime.block_on(async {
+                let response = reqwest::get(&map_url).await.map_err(|e| e.to_string())?;
+                if !response.status().is_success() {
+                    return Err(format!("HTTP error: {}", response.status()));
+                }
+                response.text().await.map_err(|e| e.to_string())
+            }),
+        };
+        // Inline maps resolve their sources against the script
+        let base = if map_url.starts_with("data:") { script_url } else { map_url.as_str() };
+        match json.and_then(|json| source_maps::SourceMap::parse(&json, Some(base)).map_err(|e| e.to_string())) {
+            Ok(map) => Some(map),
+            Err(e) => {
+                self.console.report(console::ConsoleLevel::Warn, format!("Failed to load source map '{}': {}", map_url, e), Some(script_url));
+                None
+            }
+        }
+    }
+
+    /// Extract external scripts from the DOM
+    fn extract_external_scripts(node: &Rc<Node>) -> Vec<ExternalScript> {
         let mut scripts = Vec::new();
         
         if let NodeType::Element { tag_name, attributes } = &node.node_type {
             if tag_name.to_lowercase() == "script" {
                 if let Some(src) = attributes.get("src") {
-                    scripts.push(src.clone());
+                    scripts.push(ExternalScript { url: src.clone(), integrity: attributes.get("integrity").cloned() });
                 }
             }
         }
@@ -315,50 +1008,87 @@ impl JsEngine {
         self.dispatch_event_legacy(element_id, "click")
     }
 
-    /// Process the event loop - execute microtasks first, then timers
-    pub fn process_event_loop(&mut self) -> JsResult<()> {
-        // First, process all pending microtasks
+    /// Process the event loop
+    /// 
+    /// Delivers new performance entries to their observers and runs a
+    /// microtask checkpoint for work queued outside of a task, then one
+    /// iteration of the unified event loop: every runnable task (including due
+    /// timers and network completions), each followed by a microtask
+    /// checkpoint, and the rendering steps if a frame is due.
+    pub fn process_event_loop(&mut self) -> JsResult<event_loop::IterationReport> {
+        self.deliver_performance_entries();
+        self.deliver_resize_observations();
+        self.settle_image_decodes();
         self.process_microtasks()?;
-        
-        // Then, process ready timers (macrotasks)
-        let now = Instant::now();
-        let mut ready_timers = Vec::new();
 
-        // Find timers that are ready to execute
-        for (id, timer) in &self.timers {
-            if now.duration_since(timer.created_at) >= timer.delay {
-                ready_timers.push(*id);
-            }
-        }
+        let mut host = EngineTaskHost {
+            context: &mut self.context,
+            promise_host: &self.promise_host,
+            metrics: &mut self.metrics,
+            render_callback: &mut self.render_callback,
+            task_observer: &mut self.task_observer,
+            trace_enabled: self.microtask_trace_enabled,
+            clock: self.event_loop.clock(),
+        };
 
-        // Execute ready timers
-        for timer_id in ready_timers {
-            if let Some(timer) = self.timers.remove(&timer_id) {
-                if self.microtask_trace_enabled {
-                    println!("🔸 Executing macrotask timer {}: {}", timer_id, timer.callback);
-                }
-                
-                // Execute the timer callback
-                self.execute(&timer.callback)?;
-                
-                // Process any microtasks that were scheduled by the timer
-                self.process_microtasks()?;
-                
-                // If it's a repeating timer, reschedule it
-                if timer.repeat {
-                    let new_timer = TimerTask {
-                        id: timer.id,
-                        callback: timer.callback,
-                        delay: timer.delay,
-                        repeat: timer.repeat,
-                        created_at: now,
-                    };
-                    self.timers.insert(timer_id, new_timer);
-                }
-            }
-        }
+        let now = self.event_loop.now();
+        self.event_loop
+            .run_iteration(&mut host, now)
+            .map_err(|e| JsIntegrationError::ExecutionError(e.to_string()))
+    }
 
-        Ok(())
+    /// Get the event loop
+    pub fn event_loop(&mut self) -> &mut event_loop::EventLoop {
+        &mut self.event_loop
+    }
+
+    /// Get a handle for queueing tasks from other threads
+    pub fn event_loop_handle(&self) -> event_loop::EventLoopHandle {
+        self.event_loop.handle()
+    }
+
+    /// Queue a task on the given task source
+    pub fn queue_task(&mut self, source: event_loop::TaskSource, code: &str) -> u64 {
+        self.event_loop.queue_task(source, event_loop::TaskAction::Script(code.to_string()))
+    }
+
+    /// Request an animation frame callback
+    pub fn request_animation_frame(&mut self, callback: &str) -> u32 {
+        self.event_loop.request_animation_frame(callback.to_string())
+    }
+
+    /// Cancel an animation frame callback
+    pub fn cancel_animation_frame(&mut self, id: u32) {
+        self.event_loop.cancel_animation_frame(id);
+    }
+
+    /// Request an idle callback, optionally forced to run after `timeout_ms`
+    pub fn request_idle_callback(&mut self, callback: &str, timeout_ms: Option<u64>) -> u32 {
+        self.event_loop.request_idle_callback(
+            callback.to_string(),
+            timeout_ms.map(Duration::from_millis),
+            self.event_loop.now(),
+        )
+    }
+
+    /// Cancel an idle callback
+    pub fn cancel_idle_callback(&mut self, id: u32) {
+        self.event_loop.cancel_idle_callback(id);
+    }
+
+    /// Queue low-priority script that only runs in idle periods
+    pub fn queue_background_task(&mut self, code: &str) -> u64 {
+        self.event_loop.queue_background_task(event_loop::TaskAction::Script(code.to_string()))
+    }
+
+    /// Set the callback that runs the render steps for each frame
+    pub fn set_render_callback(&mut self, callback: Box<dyn FnMut(f64)>) {
+        self.render_callback = Some(callback);
+    }
+
+    /// Set the observer told how long each task and idle callback took
+    pub fn set_task_observer(&mut self, observer: Box<dyn FnMut(event_loop::TaskTiming)>) {
+        self.task_observer = Some(observer);
     }
 
     /// Process microtasks
@@ -381,28 +1111,315 @@ impl JsEngine {
     pub fn set_microtask_trace_enabled(&mut self, enabled: bool) {
         self.microtask_trace_enabled = enabled;
         self.promise_host.set_trace_enabled(enabled);
+        self.event_loop.set_trace_enabled(enabled);
     }
 
     /// Get comprehensive telemetry for the JavaScript engine
     pub fn get_telemetry(&self) -> String {
         let promise_telemetry = self.promise_host.get_telemetry();
-        let timer_count = self.timers.len();
+        let timer_count = self.event_loop.timer_count();
         let event_listener_count = self.event_listeners.values().map(|v| v.len()).sum::<usize>();
+        let handle_stats = self.node_handles.borrow().stats();
         
         format!(
             "🚀 JavaScript Engine Telemetry:\n\
             ├─ Active Timers: {}\n\
             ├─ Event Listeners: {}\n\
             ├─ Microtask Trace: {}\n\
+            ├─ Node Handles: {} live, {} pinned, {} released by GC, {} dangling swept\n\
+            ├─ Suspected DOM Leaks: {} handles retaining {} nodes\n\
             └─ Performance Metrics: {:?}\n\n{}",
             timer_count,
             event_listener_count,
             self.microtask_trace_enabled,
+            handle_stats.live_handles,
+            handle_stats.pinned_handles,
+            handle_stats.released_by_gc,
+            handle_stats.dangling_swept,
+            handle_stats.suspected_leaks,
+            handle_stats.leaked_nodes,
             self.metrics,
             promise_telemetry
         )
     }
 
+    /// Create a JavaScript wrapper for a DOM node
+    /// 
+    /// The wrapper refers to the node through the handle table rather than
+    /// owning it, see `node_handles` for the lifetime rules, and has the
+    /// members of its element kind, see `element_wrappers`.
+    pub fn wrap_node(&mut self, node: &Rc<Node>) -> boa_engine::JsObject {
+        self.element_wrappers().wrap(node, &mut self.context)
+    }
+
+    /// Get the factory of node wrappers, sharing the engine's state
+    fn element_wrappers(&self) -> element_wrappers::ElementWrappers {
+        element_wrappers::ElementWrappers {
+            handles: Rc::clone(&self.node_handles),
+            media_controller: Rc::clone(&self.media_controller),
+            image_decodes: self.image_decodes.clone(),
+            webgl_contexts: Rc::clone(&self.webgl_contexts),
+            animations: self.animations.clone(),
+            document: self.document.clone(),
+            style_source: self.style_source.clone(),
+            events: self.event_prototypes.clone(),
+            listeners: self.node_listeners.clone(),
+            pointer_captures: self.dom_event_manager.pointer_captures().clone(),
+        }
+    }
+
+    /// Dispatch an event object to `window`, returning `false` if a
+    /// listener canceled it
+    pub fn dispatch_window_event(&mut self, event: &boa_engine::JsObject) -> JsResult<bool> {
+        let global = self.context.global_object();
+        let window = event_targets::EventTargetBinding { target: event_targets::BoundTarget::Window, wrappers: self.element_wrappers() };
+        Ok(window.dispatch(None, &global, event, &mut self.context)?)
+    }
+
+    /// Fire `beforeunload` at `window` and, if the page asks to confirm
+    /// leaving, show the shell's prompt
+    ///
+    /// Returns whether the document may be unloaded: `true` if the page
+    /// did not ask or the user chose to leave, and for documents that were
+    /// already unloaded.
+    pub fn prompt_to_unload(&mut self) -> bool {
+        // Sandboxed documents without `allow-modals` cannot ask
+        if self.unloaded || !self.sandbox_policy.allow_modals {
+            return true;
+        }
+        let requested = unload::before_unload_event(&self.event_prototypes, &mut self.context)
+            .map_err(JsIntegrationError::from)
+            .and_then(|event| {
+                let canceled = !self.dispatch_window_event(&event)?;
+                Ok(unload::confirmation_requested(&event, canceled, &mut self.context)?)
+            });
+        match requested {
+            Ok(true) => self.user_prompts.show(&user_prompts::UserPrompt::BeforeUnload).is_some(),
+            Ok(false) => true,
+            Err(e) => {
+                println!("❌ Failed to fire beforeunload: {}", e);
+                true
+            }
+        }
+    }
+
+    /// Unload the document: fire `pagehide` and `unload` at `window`, then
+    /// abort its fetches and drop its timers, tasks and observers
+    ///
+    /// Runs at most once; later calls return an empty report.
+    pub fn unload(&mut self) -> unload::TeardownReport {
+        if self.unloaded {
+            return unload::TeardownReport::default();
+        }
+        for (event_type, persisted) in [("pagehide", Some(false)), ("unload", None)] {
+            let event = match persisted {
+                Some(persisted) => unload::page_transition_event(&self.event_prototypes, event_type, persisted, &mut self.context),
+                None => Ok(event_objects::create_trusted_event(&self.event_prototypes, dom::event_types::EventInterface::Event, event_type, false, false)),
+            };
+            let fired = event.map_err(JsIntegrationError::from).and_then(|event| self.dispatch_window_event(&event));
+            if let Err(e) = fired {
+                println!("❌ Failed to fire {}: {}", event_type, e);
+            }
+        }
+        self.unloaded = true;
+
+        if let Some(id) = self.connection_observer.take() {
+            dom::connection::unobserve(id);
+        }
+        self.inserted_scripts.borrow_mut().clear();
+        self.shell_bridge.clear();
+        let disconnected_observers = self.resize_observers.disconnect_all()
+            + self.performance_observers.disconnect_all()
+            + self.geolocation.as_ref().map_or(0, geolocation::Geolocation::clear_watches);
+        unload::TeardownReport {
+            aborted_requests: self.fetch_binding.abort_all(),
+            dropped_tasks: self.event_loop.discard_pending(),
+            disconnected_observers,
+        }
+    }
+
+    /// Check whether the document was unloaded
+    pub fn is_unloaded(&self) -> bool {
+        self.unloaded
+    }
+
+    /// Install a shell feature's helper script in the document
+    ///
+    /// `source` is a function expression called with the helper's port,
+    /// see `shell_bridge`. The helper belongs to the document's origin.
+    pub fn install_helper(&mut self, name: &str, source: &str) -> shell_bridge::BridgeResult<()> {
+        let origin = self.document_origin();
+        self.shell_bridge.install(name, &origin, source, &mut self.context)
+    }
+
+    /// Post a message to a helper script, if the document is still of
+    /// `origin`
+    ///
+    /// Returns the number of the helper's listeners that ran.
+    pub fn post_to_helper(&mut self, origin: &str, name: &str, data: &serde_json::Value) -> shell_bridge::BridgeResult<usize> {
+        let current = self.document_origin();
+        if current != origin {
+            return Err(shell_bridge::BridgeError::OriginMismatch {
+                helper: name.to_string(),
+                expected: current,
+                actual: origin.to_string(),
+            });
+        }
+        self.shell_bridge.post(origin, name, data, &mut self.context)
+    }
+
+    /// Take the messages helper scripts posted to the shell
+    pub fn take_helper_messages(&mut self) -> Vec<shell_bridge::BridgeMessage> {
+        self.shell_bridge.take_messages()
+    }
+
+    /// Get the helper scripts of shell features
+    pub fn shell_bridge(&self) -> &shell_bridge::ShellBridge {
+        &self.shell_bridge
+    }
+
+    /// Get the serialized origin of the document, `null` without one
+    fn document_origin(&self) -> String {
+        self.document.as_ref().map_or_else(|| dom::Origin::Opaque.serialize(), |document| document.origin().serialize())
+    }
+
+    /// Take the state of the gamepads from a poll of the shell's backend
+    /// and fire `gamepadconnected` and `gamepaddisconnected` at `window`
+    ///
+    /// Called once per frame. Returns the number of events fired.
+    pub fn update_gamepads(&mut self, snapshots: Vec<dom::gamepad::GamepadSnapshot>) -> usize {
+        let changes = self.gamepads.update(snapshots);
+        let count = changes.len();
+        for change in changes {
+            let (event_type, gamepad) = match change {
+                dom::gamepad::GamepadChange::Connected(gamepad) => ("gamepadconnected", gamepad),
+                dom::gamepad::GamepadChange::Disconnected(gamepad) => ("gamepaddisconnected", gamepad),
+            };
+            let event = event_objects::create_trusted_event(&self.event_prototypes, dom::event_types::EventInterface::Event, event_type, false, false);
+            let fired = gamepad::gamepad_object(&gamepad, &mut self.context)
+                .and_then(|gamepad| event.set(js_string!("gamepad"), gamepad, false, &mut self.context))
+                .map_err(JsIntegrationError::from)
+                .and_then(|_| self.dispatch_window_event(&event));
+            if let Err(e) = fired {
+                println!("❌ Failed to fire {}: {}", event_type, e);
+            }
+        }
+        count
+    }
+
+    /// Get the WebGL context a script created for a canvas
+    pub fn webgl_context(&self, canvas: &Node) -> Option<dom::webgl::WebGlContext> {
+        self.webgl_contexts.borrow().get(&canvas.id).cloned()
+    }
+
+    /// Use a decoder for media elements instead of the stub backend
+    pub fn set_media_backend(&mut self, backend: Box<dyn dom::media::MediaBackend>) {
+        self.media_controller.borrow_mut().set_backend(backend);
+    }
+
+    /// Advance playing media elements by `elapsed` seconds and fire the
+    /// media events queued so far
+    ///
+    /// Called once per frame. Returns the number of events fired.
+    pub fn run_media_steps(&mut self, elapsed: f64) -> usize {
+        let events = {
+            let mut controller = self.media_controller.borrow_mut();
+            controller.advance(elapsed);
+            controller.take_events()
+        };
+        dom::media::fire_media_events(events, &mut self.dom_event_manager)
+    }
+
+    /// Check the page's scripts and `fetch()` requests against a (usually
+    /// shared) content blocker
+    pub fn set_content_blocker(&mut self, blocker: ContentBlocker) {
+        self.fetch_binding.set_content_blocker(blocker.clone());
+        self.content_blocker = blocker;
+    }
+
+    /// Use a fetcher for images instead of the stub backend
+    pub fn set_image_backend(&mut self, backend: Box<dyn dom::images::ImageBackend>) {
+        self.image_loader.set_backend(backend);
+    }
+
+    /// Load the images of the document that are due after a layout for
+    /// `viewport`, fire their `load` and `error` events and settle the
+    /// `decode()` promises they unblock
+    ///
+    /// Eager images load once their source is set, `loading="lazy"` ones
+    /// once layout puts them near the viewport. Called after layout and
+    /// scrolling. Returns the number of images loaded.
+    pub fn run_image_steps(&mut self, layout: &layout::LayoutBox, viewport: layout::Dimensions) -> usize {
+        let Some(document) = self.document.clone() else {
+            return 0;
+        };
+        let near_viewport = layout::lazy_images_near_viewport(layout, viewport);
+        let loaded = self.image_loader.update(&document.root, &near_viewport);
+        let wrappers = self.element_wrappers();
+        let binding = event_targets::EventTargetBinding { target: event_targets::BoundTarget::Wrapper, wrappers: wrappers.clone() };
+        for (image, event_type) in self.image_loader.take_events() {
+            let event = event_objects::create_trusted_event(&self.event_prototypes, dom::event_types::EventInterface::Event, event_type, false, false);
+            let target = wrappers.wrap(&image, &mut self.context);
+            if let Err(e) = binding.dispatch(Some(&image), &target, &event, &mut self.context) {
+                println!("❌ Failed to fire {}: {}", event_type, e);
+            }
+        }
+        if self.settle_image_decodes() > 0 {
+            self.context.run_jobs();
+        }
+        loaded
+    }
+
+    /// Settle the `decode()` promises of images that loaded, broke or
+    /// changed source
+    ///
+    /// Returns the number of promises settled.
+    pub fn settle_image_decodes(&mut self) -> usize {
+        if !self.image_decodes.has_pending() {
+            return 0;
+        }
+        self.image_decodes.settle(&mut self.context)
+    }
+
+    /// Advance `element.animate()` animations to `now` milliseconds on the
+    /// document timeline and resolve the `finished` promises due
+    ///
+    /// Called once per frame. Returns the values animations give nodes,
+    /// for `LayoutEngine::set_animated_styles`.
+    pub fn run_animation_steps(&mut self, now: f64) -> HashMap<u64, Vec<(String, String)>> {
+        let finished = self.animations.advance(now, &mut self.context);
+        if finished > 0 {
+            self.context.run_jobs();
+        }
+        self.animations.animated_styles()
+    }
+
+    /// Check whether script animations need further frames
+    pub fn has_running_animations(&self) -> bool {
+        self.animations.is_animating()
+    }
+
+    /// Resolve a node wrapper created by `wrap_node`
+    pub fn resolve_node(&mut self, wrapper: &boa_engine::JsObject) -> Option<Rc<Node>> {
+        self.node_handles.borrow().resolve_wrapper(wrapper, &mut self.context)
+    }
+
+    /// Run a garbage collection followed by a node handle sweep
+    pub fn collect_garbage(&mut self) -> node_handles::SweepReport {
+        boa_gc::force_collect();
+        let root = self.document.as_ref().map(|document| Rc::clone(&document.root));
+        let report = self.node_handles.borrow_mut().sweep(root.as_ref());
+        if self.microtask_trace_enabled {
+            println!("🔸 Node handle sweep: {:?}", report);
+        }
+        report
+    }
+
+    /// Get node handle statistics
+    pub fn get_node_handle_stats(&self) -> node_handles::NodeHandleStats {
+        self.node_handles.borrow().stats()
+    }
+
     /// Get microtask metrics
     pub fn get_microtask_metrics(&self) -> microtask_queue::MicrotaskMetrics {
         self.promise_host.get_microtask_metrics()
@@ -411,31 +1428,21 @@ impl JsEngine {
     /// Add a timer task
     #[allow(dead_code)]
     fn add_timer(&mut self, callback: String, delay_ms: u64, repeat: bool) -> u32 {
-        let id = self.next_timer_id;
-        self.next_timer_id += 1;
-
-        let timer = TimerTask {
-            id,
-            callback,
-            delay: Duration::from_millis(delay_ms),
-            repeat,
-            created_at: Instant::now(),
-        };
-
-        self.timers.insert(id, timer);
+        let now = self.event_loop.now();
+        let id = self.event_loop.set_timer(callback, Duration::from_millis(delay_ms), repeat, now);
         println!("Added timer {} with delay {}ms, repeat: {}", id, delay_ms, repeat);
         id
     }
 
     /// Clear a timer
     pub fn clear_timer(&mut self, timer_id: u32) {
-        if self.timers.remove(&timer_id).is_some() {
+        if self.event_loop.clear_timer(timer_id) {
             println!("Cleared timer {}", timer_id);
         }
     }
 
     /// Recursively extract and execute script tags from the DOM
-    fn extract_and_execute_scripts(node: &Rc<Node>, context: &mut Context) -> JsResult<()> {
+    fn extract_and_execute_scripts(node: &Rc<Node>, context: &mut Context, coverage: &coverage::ScriptCoverage) -> JsResult<()> {
         // Check if this node is a script element
         if let NodeType::Element { tag_name, .. } = &node.node_type {
             if tag_name.to_lowercase() == "script" {
@@ -445,6 +1452,7 @@ impl JsEngine {
                     println!("Executing inline script: {}", script_content.chars().take(50).collect::<String>());
                     
                     // Execute the script content
+                    let script_content = coverage.instrument(None, &script_content);
                     let source = Source::from_bytes(&script_content);
                     context.eval(source)?;
                 }
@@ -453,7 +1461,7 @@ impl JsEngine {
 
         // Recursively process children
         for child in node.children.borrow().iter() {
-            Self::extract_and_execute_scripts(child, context)?;
+            Self::extract_and_execute_scripts(child, context, coverage)?;
         }
 
         Ok(())
@@ -464,8 +1472,8 @@ impl JsEngine {
         let mut content = String::new();
         
         match &node.node_type {
-            NodeType::Text(text) => {
-                content.push_str(text);
+            NodeType::Text(_) => {
+                content.push_str(&node.text_content());
             }
             _ => {
                 // For non-text nodes, collect text from children
@@ -520,19 +1528,24 @@ impl JsEngine {
     /// 
     /// A `JsResult<JsValue>` containing the result of execution
     pub fn execute(&mut self, code: &str) -> JsResult<JsValue> {
+        Self::eval_script(&mut self.context, &mut self.metrics, code)
+    }
+    
+    /// Evaluate a script and record execution metrics
+    fn eval_script(context: &mut Context, metrics: &mut JsPerformanceMetrics, code: &str) -> JsResult<JsValue> {
         let start_time = Instant::now();
         let source = Source::from_bytes(code);
         
-        match self.context.eval(source) {
+        match context.eval(source) {
             Ok(value) => {
-                self.metrics.total_execution_time += start_time.elapsed();
-                self.metrics.script_count += 1;
+                metrics.total_execution_time += start_time.elapsed();
+                metrics.script_count += 1;
                 // Estimate statement count (rough approximation)
-                self.metrics.statement_count += code.matches(';').count() + 1;
+                metrics.statement_count += code.matches(';').count() + 1;
                 Ok(value)
             }
             Err(e) => {
-                self.metrics.error_count += 1;
+                metrics.error_count += 1;
                 Err(JsIntegrationError::ExecutionError(e.to_string()))
             }
         }
@@ -568,17 +1581,6 @@ impl JsEngine {
         
         global.set(js_string!("document"), document, false, context).unwrap();
         
-        // Create console object for debugging
-        let console = ObjectInitializer::new(context)
-            .function(
-                NativeFunction::from_fn_ptr(Self::console_log),
-                js_string!("log"),
-                1,
-            )
-            .build();
-        
-        global.set(js_string!("console"), console, false, context).unwrap();
-        
         // Create window object (global object reference)
         let window = global.clone();
         global.set(js_string!("window"), window, false, context).unwrap();
@@ -1054,19 +2056,6 @@ impl JsEngine {
     }
 
     
-    /// Console API: console.log
-    fn console_log(
-        _this: &JsValue,
-        args: &[JsValue],
-        _context: &mut Context,
-    ) -> boa_engine::JsResult<JsValue> {
-        for arg in args {
-            print!("{} ", arg.to_string(_context)?.to_std_string_escaped());
-        }
-        println!();
-        Ok(JsValue::undefined())
-    }
-    
     /// Execute JavaScript code that modifies the DOM
     /// 
     /// This method executes JavaScript code and automatically triggers
@@ -1085,7 +2074,8 @@ impl JsEngine {
         
         // If we have a document and stylesheet, recalculate layout
         if let (Some(document), Some(stylesheet)) = (&self.document, &self.stylesheet) {
-            let layout_engine = LayoutEngine::new(stylesheet.clone());
+            let mut layout_engine = LayoutEngine::new(stylesheet.clone());
+            layout_engine.set_geometry_observers(self.resize_observers.geometry());
             let layout = layout_engine.layout_document(document);
             return Ok(Some(layout));
         }
@@ -1094,12 +2084,122 @@ impl JsEngine {
     }
 }
 
+/// Event loop host backed by the engine's context and promise host
+struct EngineTaskHost<'a> {
+    context: &'a mut Context,
+    promise_host: &'a promise_host::PromiseHost,
+    metrics: &'a mut JsPerformanceMetrics,
+    render_callback: &'a mut Option<Box<dyn FnMut(f64)>>,
+    task_observer: &'a mut Option<Box<dyn FnMut(event_loop::TaskTiming)>>,
+    trace_enabled: bool,
+    /// The event loop's clock, for `deadline.timeRemaining()`
+    clock: dom::clock::SharedClock,
+}
+
+/// Captures for `deadline.timeRemaining()`
+#[derive(Clone, boa_gc::Trace, boa_gc::Finalize)]
+struct IdleDeadlineBinding {
+    #[unsafe_ignore_trace]
+    deadline: event_loop::IdleDeadline,
+    #[unsafe_ignore_trace]
+    clock: dom::clock::SharedClock,
+}
+
+impl event_loop::EventLoopHost for EngineTaskHost<'_> {
+    fn run_task(&mut self, action: event_loop::TaskAction) -> Result<(), String> {
+        match action {
+            event_loop::TaskAction::Script(code) => {
+                if self.trace_enabled {
+                    println!("🔸 Executing task: {}", code);
+                }
+                let start = Instant::now();
+                let result = JsEngine::eval_script(self.context, self.metrics, &code)
+                    .map(|_| ())
+                    .map_err(|e| e.to_string());
+                self.observe("task", &code, start);
+                result
+            }
+            event_loop::TaskAction::Native(work) => {
+                let start = Instant::now();
+                let result = work(self.context);
+                self.observe("task", "", start);
+                result
+            }
+        }
+    }
+
+    fn perform_microtask_checkpoint(&mut self) -> Result<usize, String> {
+        self.promise_host
+            .process_microtasks(self.context)
+            .map(|count| count as usize)
+            .map_err(|e| e.to_string())
+    }
+
+    fn update_rendering(&mut self, timestamp: f64) {
+        if let Some(callback) = self.render_callback.as_mut() {
+            callback(timestamp);
+        }
+    }
+
+    fn run_idle_callback(&mut self, callback: String, deadline: event_loop::IdleDeadline) -> Result<(), String> {
+        if self.trace_enabled {
+            println!("🔸 Executing idle callback: {}", callback);
+        }
+        let start = Instant::now();
+        let result = self.call_idle_callback(&callback, deadline);
+        self.observe("idle callback", &callback, start);
+        result
+    }
+}
+
+impl EngineTaskHost<'_> {
+    /// Tell the task observer how long a script took
+    fn observe(&mut self, kind: &'static str, script: &str, start: Instant) {
+        if let Some(observer) = self.task_observer.as_mut() {
+            observer(event_loop::TaskTiming { kind, script: script.to_string(), start, duration: start.elapsed() });
+        }
+    }
+
+    /// Evaluate an idle callback and call it with its deadline
+    fn call_idle_callback(&mut self, callback: &str, deadline: event_loop::IdleDeadline) -> Result<(), String> {
+        let value = JsEngine::eval_script(self.context, self.metrics, callback).map_err(|e| e.to_string())?;
+        let Some(function) = value.as_callable() else {
+            return Ok(());
+        };
+
+        let time_remaining = NativeFunction::from_copy_closure_with_captures(
+            |_this, _args, binding: &IdleDeadlineBinding, _context| {
+                let remaining = binding.deadline.time_remaining_at(binding.clock.now());
+                Ok(JsValue::from(remaining.as_secs_f64() * 1000.0))
+            },
+            IdleDeadlineBinding { deadline, clock: self.clock.clone() },
+        );
+        let deadline_object = ObjectInitializer::new(self.context)
+            .property(js_string!("didTimeout"), deadline.did_timeout, Attribute::READONLY)
+            .function(time_remaining, js_string!("timeRemaining"), 0)
+            .build();
+        function
+            .call(&JsValue::undefined(), &[deadline_object.into()], self.context)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
 impl Default for JsEngine {
     fn default() -> Self {
         Self::new()
     }
 }
 
+impl Drop for JsEngine {
+    fn drop(&mut self) {
+        dom::connection::unobserve(self.removal_observer);
+        if let Some(id) = self.connection_observer.take() {
+            dom::connection::unobserve(id);
+        }
+    }
+}
+
 /// Trait to convert Rust values to JavaScript values
 #[allow(dead_code)]
 trait ToJsValue {
@@ -1448,4 +2548,401 @@ mod tests {
         ");
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_sandboxed_engine_hides_network_apis() {
+        let mut engine = JsEngine::with_sandbox_policy(sandbox::SandboxPolicy::untrusted()).unwrap();
+        let fetch_type = engine.execute("typeof fetch").unwrap();
+        assert_eq!(fetch_type.to_string(&mut engine.context).unwrap().to_std_string_escaped(), "undefined");
+
+        engine.execute("setTimeout.setTimeout('1', 0); setTimeout.setTimeout('2', 0);").unwrap();
+        assert_eq!(engine.api_call_counts().get("setTimeout"), Some(&2));
+
+        // Later policies can only narrow what is exposed
+        engine.apply_sandbox_policy(sandbox::SandboxPolicy::unrestricted()).unwrap();
+        assert!(!engine.sandbox_policy().allows(sandbox::Capability::Network));
+    }
+
+    #[test]
+    fn test_frame_sandbox_blocks_page_scripts_and_modals() {
+        use std::sync::{Arc, Mutex};
+        let (document, _) = html_parser::parse_html_string("<html><body><script>window.ran = true; alert('hi');</script></body></html>").unwrap();
+        let flags = dom::sandboxing::SandboxFlags::parse("allow-forms");
+        let mut engine = JsEngine::with_sandbox_policy(sandbox::SandboxPolicy::for_sandbox_flags(&flags)).unwrap();
+        engine.set_document(Rc::new(document));
+        let shown = Arc::new(Mutex::new(0));
+        let count = Arc::clone(&shown);
+        engine.set_user_prompt_handler(Arc::new(move |_prompt: &user_prompts::UserPrompt| {
+            *count.lock().unwrap() += 1;
+            Some(String::new())
+        }));
+
+        // The page's scripts do not run, and without a same origin there
+        // is no storage
+        engine.execute_inline_scripts().unwrap();
+        let result = engine.execute("typeof ran + ',' + typeof localStorage").unwrap();
+        assert_eq!(result.to_string(&mut engine.context).unwrap().to_std_string_escaped(), "undefined,undefined");
+
+        // Prompts are dismissed unseen, and leaving is never held up
+        let result = engine.execute("window.addEventListener('beforeunload', e => e.preventDefault()); confirm('Sure?')").unwrap();
+        assert_eq!(result.as_boolean(), Some(false));
+        assert!(engine.prompt_to_unload());
+        assert_eq!(*shown.lock().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_script_integrity_blocks_mismatched_scripts() {
+        use networking::integrity::IntegrityAlgorithm;
+        
+        let mut engine = JsEngine::new();
+        let body = b"globalThis.loaded = (globalThis.loaded || 0) + 1;";
+        let integrity = format!("sha384-{}", IntegrityAlgorithm::Sha384.digest(body));
+        let url = "https://cdn.example.com/lib.js";
+        
+        engine.execute_fetched_script(url, body, Some(&integrity), Instant::now()).unwrap();
+        let tampered = b"globalThis.loaded = 100; globalThis.stolen = true;";
+        assert!(engine.execute_fetched_script(url, tampered, Some(&integrity), Instant::now()).is_err());
+        // Metadata without a known algorithm does not block
+        engine.execute_fetched_script(url, body, Some("md5-abc"), Instant::now()).unwrap();
+        
+        let result = engine.execute("loaded + ',' + typeof stolen").unwrap();
+        assert_eq!(result.to_string(&mut engine.context).unwrap().to_std_string_escaped(), "2,undefined");
+        let messages = engine.console().take();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].level, console::ConsoleLevel::Error);
+        assert_eq!(messages[0].source.as_deref(), Some(url));
+        assert!(messages[0].text.contains("sha384"));
+    }
+
+    #[test]
+    fn test_source_maps_remap_errors_and_call_sites() {
+        use base64::Engine;
+        let inline_map = |map: &str| format!("//# sourceMappingURL=data:application/json;base64,{}", base64::engine::general_purpose::STANDARD.encode(map));
+
+        let mut engine = JsEngine::new();
+        let map = r#"{"version": 3, "sources": ["app.js"], "sourceRoot": "src/", "names": ["greet"], "mappings": "SAASA;AAGT"}"#;
+        let script = format!("function a(){{console.log(\"hi\");console.trace()}}\na();\n{}", inline_map(map));
+        engine.execute_fetched_script("https://example.com/dist/app.min.js", script.as_bytes(), None, Instant::now()).unwrap();
+        let messages = engine.console().take();
+        assert_eq!(messages[0].text, "hi");
+        assert_eq!(messages[0].source.as_deref(), Some("https://example.com/dist/src/app.js:1:10"));
+        assert_eq!(messages[1].text, "Trace\n    at greet (https://example.com/dist/src/app.js:1:10)\n    at https://example.com/dist/app.min.js");
+
+        let map = r#"{"version": 3, "sources": ["bad.ts"], "names": [], "mappings": "AAAA,QAGE"}"#;
+        let script = format!("var x = ;\n{}", inline_map(map));
+        let error = engine.execute_fetched_script("https://example.com/bad.js", script.as_bytes(), None, Instant::now()).unwrap_err();
+        assert!(error.to_string().ends_with("at https://example.com/bad.ts:4:3"), "{}", error);
+
+        // Scripts without maps log as before
+        engine.execute_fetched_script("https://example.com/plain.js", b"function b(){console.log('plain')}\nb();", None, Instant::now()).unwrap();
+        assert_eq!(engine.console().take()[0].source, None);
+    }
+
+    #[test]
+    fn test_get_computed_style_hides_visited_links() {
+        let document = Document::new();
+        let link = document.create_element("a");
+        link.set_attribute("href", "https://example.com/read");
+        link.set_element_state(dom::ElementState::VISITED, true);
+        document.root.append_child(&link);
+        
+        let mut engine = JsEngine::new();
+        engine.set_stylesheet(css_parser::parse_css("a:link {\n  color: blue;\n}\na:visited {\n  color: purple;\n}"));
+        let wrapper = engine.wrap_node(&link);
+        engine.context.register_global_property(boa_engine::js_string!("link"), wrapper, boa_engine::property::Attribute::all()).unwrap();
+        
+        let result = engine.execute("const style = getComputedStyle(link); style.color + ' ' + style.getPropertyValue('color')").unwrap();
+        assert_eq!(result.to_string(&mut engine.context).unwrap().to_std_string_escaped(), "blue blue");
+        assert!(engine.execute("getComputedStyle({})").is_err());
+    }
+
+    #[test]
+    fn test_document_accessors_and_selector_methods() {
+        let (document, _) = html_parser::parse_html_string("<html><head><title>T</title></head><body><ul id=\"list\"><li class=\"item\"><a href=\"/x\">x</a></li></ul></body></html>").unwrap();
+        let mut engine = JsEngine::new();
+        engine.set_document(Rc::new(document));
+
+        let result = engine.execute("[document.documentElement.matches(':root'), document.head.matches('html > head'), document.body.matches('body:last-child')].join()").unwrap();
+        assert_eq!(result.to_string(&mut engine.context).unwrap().to_std_string_escaped(), "true,true,true");
+
+        let result = engine.execute("document.body.closest('html').matches(':root') && document.body.closest('ul') === null").unwrap();
+        assert_eq!(result.as_boolean(), Some(true));
+        let result = engine.execute("let error; try { document.body.matches('li >'); } catch (e) { error = e; } error.name + ' ' + error.code").unwrap();
+        assert_eq!(result.to_string(&mut engine.context).unwrap().to_std_string_escaped(), "SyntaxError 12");
+
+        let anchor = engine.document.as_ref().unwrap().root.children.borrow()[0].children.borrow()[1].children.borrow()[0].children.borrow()[0].children.borrow()[0].clone();
+        let wrapper = engine.wrap_node(&anchor);
+        engine.context.register_global_property(boa_engine::js_string!("anchor"), wrapper, boa_engine::property::Attribute::all()).unwrap();
+        let result = engine.execute("const item = anchor.closest('ul > .item'); [anchor.matches('#list a[href]'), item.matches('li'), typeof item.closest, item.closest('#list').matches('ul')].join()").unwrap();
+        assert_eq!(result.to_string(&mut engine.context).unwrap().to_std_string_escaped(), "true,true,function,true");
+    }
+
+    #[test]
+    fn test_text_content_inner_text_and_normalize() {
+        let (document, _) = html_parser::parse_html_string("<html><body><div id=\"box\"><p>One</p><p>Two <span class=\"secret\">hidden</span></p></div></body></html>").unwrap();
+        let mut engine = JsEngine::new();
+        engine.set_document(Rc::new(document));
+        engine.set_stylesheet(css_parser::parse_css(".secret {\n  visibility: hidden;\n}"));
+
+        let body = engine.document.as_ref().unwrap().body().unwrap();
+        let div = body.children.borrow()[0].clone();
+        let wrapper = engine.wrap_node(&div);
+        engine.context.register_global_property(boa_engine::js_string!("div"), wrapper, boa_engine::property::Attribute::all()).unwrap();
+        let result = engine.execute("[JSON.stringify(div.textContent), JSON.stringify(div.innerText)].join()").unwrap();
+        assert_eq!(result.to_string(&mut engine.context).unwrap().to_std_string_escaped(), "\"OneTwo hidden\",\"One\\n\\nTwo\"");
+
+        engine.execute("div.innerText = 'a\\nb';").unwrap();
+        let tags: Vec<_> = div.children.borrow().iter().map(|child| child.tag_name().unwrap_or("#text").to_string()).collect();
+        assert_eq!(tags, ["#text", "br", "#text"]);
+
+        let document = engine.document.clone().unwrap();
+        div.remove_children();
+        for part in ["x", "", "y"] {
+            div.append_child(&document.create_text_node(part));
+        }
+        engine.execute("div.normalize(); div.textContent += '!';").unwrap();
+        assert_eq!(div.children.borrow().len(), 1);
+        assert_eq!(div.text_content(), "xy!");
+        engine.execute("div.textContent = '';").unwrap();
+        assert!(div.children.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_nodes_detached_outside_scripts_stay_valid() {
+        let (document, _) = html_parser::parse_html_string("<html><body><ul><li>One</li></ul></body></html>").unwrap();
+        let mut engine = JsEngine::new();
+        engine.set_document(Rc::new(document));
+
+        let body = engine.document.as_ref().unwrap().body().unwrap();
+        let list = body.children.borrow()[0].clone();
+        let item = list.children.borrow()[0].clone();
+        let wrapper = engine.wrap_node(&item);
+        engine.context.register_global_property(boa_engine::js_string!("item"), wrapper, boa_engine::property::Attribute::all()).unwrap();
+        drop(item);
+
+        // Only the wrapper refers to the item once the list is emptied
+        body.remove_child(&list);
+        list.remove_children();
+        drop(list);
+        let result = engine.execute("item.textContent").unwrap();
+        assert_eq!(result.to_string(&mut engine.context).unwrap().to_std_string_escaped(), "One");
+        assert_eq!(engine.get_node_handle_stats().pinned_handles, 1);
+    }
+
+    #[test]
+    fn test_base_uri_follows_base_element() {
+        let (document, _) = html_parser::parse_html_string("<html><head><base href=\"/assets/\"></head><body><p>x</p></body></html>").unwrap();
+        document.set_url("https://example.com/docs/page.html");
+        let mut engine = JsEngine::new();
+        engine.set_document(Rc::new(document));
+
+        let body = engine.document.as_ref().unwrap().body().unwrap();
+        let paragraph = body.children.borrow()[0].clone();
+        let wrapper = engine.wrap_node(&paragraph);
+        engine.context.register_global_property(boa_engine::js_string!("p"), wrapper, boa_engine::property::Attribute::all()).unwrap();
+        let result = engine.execute("p.baseURI").unwrap();
+        assert_eq!(result.to_string(&mut engine.context).unwrap().to_std_string_escaped(), "https://example.com/assets/");
+    }
+
+    #[test]
+    fn test_live_collections_and_static_node_lists() {
+        let (document, _) = html_parser::parse_html_string("<html><body><ul id=\"list\"><li class=\"item\">a</li><li class=\"item\" id=\"b\">b</li></ul></body></html>").unwrap();
+        let mut engine = JsEngine::new();
+        engine.set_document(Rc::new(document));
+
+        let result = engine.execute("const list = document.querySelector('#list');
+            const items = document.getElementsByTagName('li');
+            const marked = list.getElementsByClassName('item');
+            const children = list.children;
+            const snapshot = document.querySelectorAll('li');
+            [items.length, items[1].matches('#b'), items.namedItem('b').matches('li:last-child'), 'b' in items, 2 in items, items.item(5), [...snapshot].length, String(items)].join()").unwrap();
+        assert_eq!(result.to_string(&mut engine.context).unwrap().to_std_string_escaped(), "2,true,true,true,false,,2,[object HTMLCollection]");
+
+        let document = engine.document.clone().unwrap();
+        let list = document.root.query_selector("#list").unwrap().unwrap();
+        list.append_child(&document.create_element("li"));
+        list.children.borrow()[0].remove_attribute("class");
+        let result = engine.execute("[items.length, children.length, marked.length, snapshot.length, items[2].matches('li:last-child')].join()").unwrap();
+        assert_eq!(result.to_string(&mut engine.context).unwrap().to_std_string_escaped(), "3,3,1,2,true");
+
+        let result = engine.execute("let error; try { document.querySelectorAll('li >'); } catch (e) { error = e; } error.name").unwrap();
+        assert_eq!(result.to_string(&mut engine.context).unwrap().to_std_string_escaped(), "SyntaxError");
+    }
+
+    #[test]
+    fn test_tree_walker() {
+        let (document, _) = html_parser::parse_html_string("<html><body><p>a<b>b</b></p><p>c</p></body></html>").unwrap();
+        let mut engine = JsEngine::new();
+        engine.set_document(Rc::new(document));
+
+        let result = engine.execute("const walker = document.createTreeWalker(document.body, 4); const texts = [];
+            while (walker.nextNode()) { texts.push(walker.currentNode.textContent); }
+            texts.join()").unwrap();
+        assert_eq!(result.to_string(&mut engine.context).unwrap().to_std_string_escaped(), "a,b,c");
+        assert!(engine.execute("document.createTreeWalker({})").is_err());
+    }
+
+    #[test]
+    fn test_adopt_and_import_node() {
+        let (document, _) = html_parser::parse_html_string("<html><body><ul><li class=\"a\">x</li></ul></body></html>").unwrap();
+        let mut engine = JsEngine::new();
+        engine.set_document(Rc::new(document));
+
+        let result = engine.execute("const item = document.querySelector('li');
+            const copy = document.importNode(item, true);
+            const shallow = document.importNode(item);
+            const adopted = document.adoptNode(item);
+            [copy.matches('li.a'), copy.textContent, shallow.textContent, document.querySelectorAll('li').length, adopted.textContent].join()").unwrap();
+        assert_eq!(result.to_string(&mut engine.context).unwrap().to_std_string_escaped(), "true,x,,0,x");
+
+        let result = engine.execute("let error; try { document.adoptNode({}); } catch (e) { error = e; } error instanceof TypeError").unwrap();
+        assert_eq!(result.as_boolean(), Some(true));
+    }
+
+    #[test]
+    fn test_inserted_scripts_run_once_when_connected() {
+        let (document, _) = html_parser::parse_html_string("<html><body><script>globalThis.runs = 0;</script></body></html>").unwrap();
+        let document = Rc::new(document);
+        let mut engine = JsEngine::new();
+        engine.set_document(Rc::clone(&document));
+        engine.execute_inline_scripts().unwrap();
+        assert_eq!(engine.run_inserted_scripts(), 0);
+
+        let body = document.body().unwrap();
+        let script = document.create_element("script");
+        script.append_child(&document.create_text_node("runs += 1;"));
+        let detached = document.create_element("div");
+        detached.append_child(&script);
+        assert_eq!(engine.run_inserted_scripts(), 0);
+
+        body.append_child(&detached);
+        body.append_child(&script);
+        assert_eq!(engine.run_inserted_scripts(), 1);
+        assert_eq!(engine.execute("runs").unwrap().as_number(), Some(1.0));
+    }
+
+    #[test]
+    fn test_dialog_show_modal_and_close() {
+        let (document, _) = html_parser::parse_html_string("<html><body><dialog><form>Sure?</form></dialog><button>Go</button></body></html>").unwrap();
+        let mut engine = JsEngine::new();
+        engine.set_document(Rc::new(document));
+
+        let code = "const dialog = document.querySelector('dialog');
+            const log = [];
+            dialog.addEventListener('close', e => log.push(e.type + ':' + dialog.returnValue + ':' + dialog.open));
+            dialog.showModal();
+            let error;
+            try { dialog.show(); } catch (e) { error = e; }
+            log.push(dialog.open, dialog.matches(':modal'), error.name);
+            dialog.close('yes');
+            dialog.close('again');
+            log.join()";
+        let result = engine.execute(code).unwrap();
+        assert_eq!(result.to_string(&mut engine.context).unwrap().to_std_string_escaped(), "true,true,InvalidStateError,close:yes:false");
+
+        let result = engine.execute("dialog.show(); [dialog.open, dialog.matches(':modal'), log.length].join()").unwrap();
+        assert_eq!(result.to_string(&mut engine.context).unwrap().to_std_string_escaped(), "true,false,4");
+    }
+
+    #[test]
+    fn test_popovers_toggle_and_fire_toggle_events() {
+        let html = r#"<html><body><div id="menu" popover=""><div id="sub" popover="">More</div></div><p id="tip" popover="hint">Tip</p><span>Text</span></body></html>"#;
+        let (document, _) = html_parser::parse_html_string(html).unwrap();
+        let mut engine = JsEngine::new();
+        engine.set_document(Rc::new(document));
+
+        let code = "const menu = document.querySelector('#menu');
+            const sub = document.querySelector('#sub');
+            const tip = document.querySelector('#tip');
+            const log = [];
+            for (const [name, popover] of [['menu', menu], ['sub', sub], ['tip', tip]]) {
+                popover.addEventListener('toggle', e => log.push(name + ':' + e.oldState + '>' + e.newState));
+            }
+            menu.showPopover();
+            sub.togglePopover();
+            tip.showPopover();
+            log.push(menu.matches(':popover-open'), tip.popover);
+            menu.hidePopover();
+            let error;
+            try { document.querySelector('span').showPopover(); } catch (e) { error = e; }
+            log.push(menu.togglePopover(false), error.name, document.querySelector('span').popover);
+            log.join()";
+        let result = engine.execute(code).unwrap();
+        assert_eq!(
+            result.to_string(&mut engine.context).unwrap().to_std_string_escaped(),
+            "menu:closed>open,sub:closed>open,tip:closed>open,true,manual,sub:open>closed,menu:open>closed,false,NotSupportedError,"
+        );
+    }
+
+    #[test]
+    fn test_request_and_exit_fullscreen() {
+        let (document, _) = html_parser::parse_html_string("<html><body><video></video><dialog></dialog></body></html>").unwrap();
+        let mut engine = JsEngine::new();
+        engine.set_document(Rc::new(document));
+
+        let code = "const video = document.querySelector('video');
+            const log = [];
+            document.addEventListener('fullscreenchange', e => log.push(e.type + ':' + String(document.fullscreenElement && document.fullscreenElement.matches('video'))));
+            document.addEventListener('fullscreenerror', e => log.push(e.type));
+            video.requestFullscreen().then(() => log.push('entered'));
+            log.push(video.matches(':fullscreen'), document.fullscreenEnabled);
+            document.exitFullscreen().then(() => log.push('exited'));
+            document.exitFullscreen().catch(e => log.push(e.name));
+            const dialog = document.querySelector('dialog');
+            dialog.showModal();
+            dialog.requestFullscreen().catch(e => log.push(e.name));
+            log";
+        engine.execute(code).unwrap();
+        engine.context.run_jobs();
+        let result = engine.execute("log.join()").unwrap();
+        assert_eq!(
+            result.to_string(&mut engine.context).unwrap().to_std_string_escaped(),
+            "fullscreenchange:true,true,true,fullscreenchange:null,fullscreenerror,entered,exited,TypeError,TypeError"
+        );
+    }
+
+    #[test]
+    fn test_visibility_state_and_visibilitychange() {
+        use dom::page_visibility::VisibilityState;
+        let (document, _) = html_parser::parse_html_string("<html><body></body></html>").unwrap();
+        let mut engine = JsEngine::new();
+        assert!(!engine.set_visibility_state(VisibilityState::Hidden));
+        engine.set_document(Rc::new(document));
+
+        let code = "const log = [document.visibilityState, document.hidden];
+            document.addEventListener('visibilitychange', e => log.push(e.type + ':' + document.visibilityState + ':' + document.hidden));
+            log";
+        engine.execute(code).unwrap();
+        assert!(engine.set_visibility_state(VisibilityState::Hidden));
+        assert!(!engine.set_visibility_state(VisibilityState::Hidden));
+        assert!(engine.set_visibility_state(VisibilityState::Visible));
+        let result = engine.execute("log.join()").unwrap();
+        assert_eq!(
+            result.to_string(&mut engine.context).unwrap().to_std_string_escaped(),
+            "visible,false,visibilitychange:hidden:true,visibilitychange:visible:false"
+        );
+    }
+
+    #[test]
+    fn test_injected_clock_steps_timers_and_idle_deadlines() {
+        let clock = dom::clock::ManualClock::new();
+        let mut engine = JsEngine::new();
+        engine.set_clock(std::sync::Arc::new(clock.clone()));
+        engine.execute("var log = []; var start = performance.now();").unwrap();
+        let now = engine.now();
+        engine.event_loop().set_timer("log.push('timer@' + (performance.now() - start))".to_string(), Duration::from_millis(100), false, now);
+
+        // The timer waits for virtual time, not for the test to run long
+        clock.advance(Duration::from_millis(99));
+        engine.process_event_loop().unwrap();
+        assert_eq!(engine.execute("log.length").unwrap().as_number(), Some(0.0));
+        clock.advance(Duration::from_millis(1));
+        engine.process_event_loop().unwrap();
+        engine.request_idle_callback("(deadline) => log.push('idle:' + deadline.timeRemaining())", None);
+        engine.process_event_loop().unwrap();
+
+        let result = engine.execute("log.join()").unwrap();
+        let log = result.to_string(&mut engine.context).unwrap().to_std_string_escaped();
+        assert_eq!(log, format!("timer@100,idle:{}", event_loop::MAX_IDLE_PERIOD.as_millis()));
+    }
 }
@@ -0,0 +1,201 @@
+//! # Media Element Bindings
+//!
+//! This module gives the wrappers of `<video>` and `<audio>` elements the
+//! `HTMLMediaElement` members scripts use most: `paused`, `currentTime`,
+//! `duration`, `ended`, `readyState`, `play()` and `pause()`.
+//!
+//! ## Design Principles
+//!
+//! 1. **State on the Node**: The members read and drive the playback state
+//!    the DOM crate keeps on the node, through the engine's shared
+//!    `MediaController`, so there is no second copy to fall out of sync.
+//! 2. **Promise Based**: `play()` returns a promise that rejects with a
+//!    `NotSupportedError` when the backend cannot play the source, as in
+//!    browsers.
+//! 3. **Events as Tasks**: The events `play()` and `pause()` cause are
+//!    queued on the controller and fired by the engine afterwards.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use boa_engine::{
+    object::{builtins::JsPromise, ObjectInitializer},
+    property::{Attribute, PropertyDescriptor},
+    js_string, Context, JsError, JsNativeError, JsObject, JsResult, JsValue, NativeFunction,
+};
+use boa_gc::{Finalize, Trace};
+use dom::media::{MediaController, ReadyState};
+use dom::Node;
+use crate::node_handles::NodeHandleTable;
+
+/// Captures for the media element members
+#[derive(Clone, Trace, Finalize)]
+struct MediaBinding {
+    #[unsafe_ignore_trace]
+    handles: Rc<RefCell<NodeHandleTable>>,
+    #[unsafe_ignore_trace]
+    controller: Rc<RefCell<MediaController>>,
+}
+
+impl MediaBinding {
+    fn node(&self, this: &JsValue, context: &mut Context) -> JsResult<Rc<Node>> {
+        this.as_object()
+            .and_then(|wrapper| self.handles.borrow().resolve_wrapper(wrapper, context))
+            .filter(|node| node.media_kind().is_some())
+            .ok_or_else(|| JsNativeError::typ().with_message("Illegal invocation: not a media element").into())
+    }
+}
+
+/// A read-only media member and how it reads the node
+type MediaGetter = (&'static str, fn(&Node) -> JsValue);
+
+/// Create a `NotSupportedError` DOMException
+fn create_not_supported_error(context: &mut Context, message: &str) -> JsValue {
+    ObjectInitializer::new(context)
+        .property(js_string!("name"), js_string!("NotSupportedError"), Attribute::all())
+        .property(js_string!("message"), js_string!(message), Attribute::all())
+        .property(js_string!("code"), 9, Attribute::all())
+        .build()
+        .into()
+}
+
+/// Add the `HTMLMediaElement` members to the wrapper of a media element
+pub fn install_media_members(
+    wrapper: &JsObject,
+    handles: Rc<RefCell<NodeHandleTable>>,
+    controller: Rc<RefCell<MediaController>>,
+    context: &mut Context,
+) -> JsResult<()> {
+    let binding = MediaBinding { handles, controller };
+
+    let getters: [MediaGetter; 4] = [
+        ("paused", |node| node.paused().into()),
+        ("duration", |node| node.duration().into()),
+        ("ended", |node| node.media_state().ended.into()),
+        ("readyState", |node| {
+            match node.media_state().ready_state {
+                ReadyState::HaveNothing => 0,
+                ReadyState::HaveMetadata => 1,
+                ReadyState::HaveEnoughData => 4,
+            }
+            .into()
+        }),
+    ];
+    for (name, read) in getters {
+        let getter = NativeFunction::from_copy_closure_with_captures(
+            move |this, _args, binding: &MediaBinding, context| {
+                let node = binding.node(this, context)?;
+                Ok(read(&node))
+            },
+            binding.clone(),
+        );
+        wrapper.define_property_or_throw(
+            js_string!(name),
+            PropertyDescriptor::builder()
+                .get(getter.to_js_function(context.realm()))
+                .enumerable(true)
+                .configurable(true),
+            context,
+        )?;
+    }
+
+    let get_current_time = NativeFunction::from_copy_closure_with_captures(
+        |this, _args, binding: &MediaBinding, context| Ok(binding.node(this, context)?.current_time().into()),
+        binding.clone(),
+    );
+    let set_current_time = NativeFunction::from_copy_closure_with_captures(
+        |this, args, binding: &MediaBinding, context| {
+            let node = binding.node(this, context)?;
+            let time = args.first().cloned().unwrap_or_default().to_number(context)?;
+            if !time.is_finite() {
+                return Err(JsNativeError::typ().with_message("currentTime must be a finite number").into());
+            }
+            binding.controller.borrow_mut().seek(&node, time);
+            Ok(JsValue::undefined())
+        },
+        binding.clone(),
+    );
+    wrapper.define_property_or_throw(
+        js_string!("currentTime"),
+        PropertyDescriptor::builder()
+            .get(get_current_time.to_js_function(context.realm()))
+            .set(set_current_time.to_js_function(context.realm()))
+            .enumerable(true)
+            .configurable(true),
+        context,
+    )?;
+
+    let play = NativeFunction::from_copy_closure_with_captures(
+        |this, _args, binding: &MediaBinding, context| {
+            let node = binding.node(this, context)?;
+            let result = binding.controller.borrow_mut().play(&node);
+            match result {
+                Ok(()) => Ok(JsPromise::resolve(JsValue::undefined(), context).into()),
+                Err(error) => {
+                    let error = create_not_supported_error(context, &error.to_string());
+                    Ok(JsPromise::reject(JsError::from_opaque(error), context).into())
+                }
+            }
+        },
+        binding.clone(),
+    );
+    let pause = NativeFunction::from_copy_closure_with_captures(
+        |this, _args, binding: &MediaBinding, context| {
+            let node = binding.node(this, context)?;
+            binding.controller.borrow_mut().pause(&node);
+            Ok(JsValue::undefined())
+        },
+        binding,
+    );
+    for (name, function) in [("play", play), ("pause", pause)] {
+        wrapper.define_property_or_throw(
+            js_string!(name),
+            PropertyDescriptor::builder()
+                .value(function.to_js_function(context.realm()))
+                .writable(true)
+                .enumerable(false)
+                .configurable(true),
+            context,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use boa_engine::Source;
+    use dom::Document;
+
+    #[test]
+    fn test_play_and_pause_from_script() {
+        let document = Document::new();
+        let video = document.create_element("video");
+        video.set_attribute("src", "clip.webm");
+        let handles = Rc::new(RefCell::new(NodeHandleTable::new()));
+        let controller = Rc::new(RefCell::new(MediaController::new()));
+
+        let mut context = Context::default();
+        let wrapper = handles.borrow_mut().wrap_node(&video, &mut context);
+        install_media_members(&wrapper, Rc::clone(&handles), Rc::clone(&controller), &mut context).unwrap();
+        context.register_global_property(js_string!("video"), wrapper, Attribute::all()).unwrap();
+
+        let code = "var log = [video.paused];
+            video.play().then(() => log.push('played', video.paused));
+            video.currentTime = 3;
+            video.pause();
+            log.push(video.paused, video.currentTime);";
+        context.eval(Source::from_bytes(code)).unwrap();
+        context.run_jobs();
+        let log = context.eval(Source::from_bytes("log.join(',')")).unwrap();
+        assert_eq!(log.to_string(&mut context).unwrap().to_std_string_escaped(), "true,true,3,played,true");
+        assert!(controller.borrow().has_pending());
+
+        // Without a source the promise rejects
+        video.remove_attribute("src");
+        let code = "var rejected = ''; video.play().catch((e) => rejected = e.name);";
+        context.eval(Source::from_bytes(code)).unwrap();
+        context.run_jobs();
+        let rejected = context.eval(Source::from_bytes("rejected")).unwrap();
+        assert_eq!(rejected.to_string(&mut context).unwrap().to_std_string_escaped(), "NotSupportedError");
+    }
+}
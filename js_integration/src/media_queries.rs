@@ -0,0 +1,285 @@
+//! # Media Query Lists
+//!
+//! This module implements `window.matchMedia()`. Scripts get a
+//! `MediaQueryList` whose `matches` follows the environment, and `change`
+//! listeners run when the shell changes a preference such as the color
+//! scheme.
+//!
+//! ## Design Principles
+//!
+//! 1. **Same Evaluation as CSS**: Queries are parsed and evaluated by
+//!    `css_parser::media`, so scripts and stylesheets always agree.
+//! 2. **Push, Don't Poll**: The engine is told when features change and
+//!    fires `change` only on lists whose result flipped.
+//! 3. **Listeners Are Isolated**: A listener that throws is reported and
+//!    does not stop the others.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use boa_engine::{
+    object::ObjectInitializer,
+    property::Attribute,
+    js_string, Context, JsObject, JsResult, JsValue, NativeFunction,
+};
+use boa_gc::{Finalize, Trace};
+use css_parser::media::{MediaFeatures, MediaQueryList};
+
+/// A `MediaQueryList` handed out to a script
+struct ListRecord {
+    id: u64,
+    query: MediaQueryList,
+    matches: bool,
+    object: JsObject,
+    listeners: Vec<JsObject>,
+}
+
+struct ListState {
+    features: MediaFeatures,
+    lists: Vec<ListRecord>,
+    next_id: u64,
+}
+
+/// The `MediaQueryList` objects of a script context
+///
+/// Cloning shares the lists.
+#[derive(Clone)]
+pub struct MediaQueryLists {
+    state: Rc<RefCell<ListState>>,
+}
+
+impl MediaQueryLists {
+    fn new(features: MediaFeatures) -> Self {
+        Self {
+            state: Rc::new(RefCell::new(ListState { features, lists: Vec::new(), next_id: 1 })),
+        }
+    }
+
+    /// Get the environment queries are currently evaluated against
+    pub fn features(&self) -> MediaFeatures {
+        self.state.borrow().features.clone()
+    }
+
+    /// Change the environment and fire `change` on the lists whose result
+    /// flipped
+    ///
+    /// Returns the number of lists that changed.
+    pub fn update(&self, features: MediaFeatures, context: &mut Context) -> usize {
+        let mut changes = Vec::new();
+        {
+            let mut state = self.state.borrow_mut();
+            state.features = features;
+            let ListState { features, lists, .. } = &mut *state;
+            for record in lists.iter_mut() {
+                let matches = record.query.matches(features);
+                if matches != record.matches {
+                    record.matches = matches;
+                    changes.push((record.object.clone(), record.query.text().to_string(), matches, record.listeners.clone()));
+                }
+            }
+        }
+
+        let count = changes.len();
+        for (object, media, matches, listeners) in changes {
+            if let Err(e) = object.set(js_string!("matches"), matches, false, context) {
+                println!("❌ Failed to update MediaQueryList: {}", e);
+                continue;
+            }
+            let event = ObjectInitializer::new(context)
+                .property(js_string!("type"), js_string!("change"), Attribute::READONLY)
+                .property(js_string!("media"), js_string!(media), Attribute::READONLY)
+                .property(js_string!("matches"), matches, Attribute::READONLY)
+                .build();
+            let this = JsValue::from(object.clone());
+            let mut callbacks = listeners;
+            if let Ok(handler) = object.get(js_string!("onchange"), context) {
+                if let Some(handler) = handler.as_callable() {
+                    callbacks.push(handler.clone());
+                }
+            }
+            for callback in callbacks {
+                if let Err(e) = callback.call(&this, &[event.clone().into()], context) {
+                    println!("❌ MediaQueryList change listener failed: {}", e);
+                }
+            }
+        }
+        count
+    }
+
+    fn register(&self, query: MediaQueryList, object: JsObject) -> (u64, bool) {
+        let mut state = self.state.borrow_mut();
+        let id = state.next_id;
+        state.next_id += 1;
+        let matches = query.matches(&state.features);
+        state.lists.push(ListRecord { id, query, matches, object, listeners: Vec::new() });
+        (id, matches)
+    }
+
+    fn add_listener(&self, id: u64, listener: JsObject) {
+        let mut state = self.state.borrow_mut();
+        if let Some(record) = state.lists.iter_mut().find(|record| record.id == id) {
+            if !record.listeners.iter().any(|existing| existing == &listener) {
+                record.listeners.push(listener);
+            }
+        }
+    }
+
+    fn remove_listener(&self, id: u64, listener: &JsObject) {
+        let mut state = self.state.borrow_mut();
+        if let Some(record) = state.lists.iter_mut().find(|record| record.id == id) {
+            record.listeners.retain(|existing| existing != listener);
+        }
+    }
+}
+
+impl fmt::Debug for MediaQueryLists {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.borrow();
+        f.debug_struct("MediaQueryLists")
+            .field("features", &state.features)
+            .field("lists", &state.lists.len())
+            .finish()
+    }
+}
+
+/// Captures for `matchMedia`
+#[derive(Trace, Finalize)]
+struct ListsBinding {
+    #[unsafe_ignore_trace]
+    lists: MediaQueryLists,
+}
+
+/// Captures for the methods of one `MediaQueryList`
+#[derive(Trace, Finalize)]
+struct ListBinding {
+    #[unsafe_ignore_trace]
+    lists: MediaQueryLists,
+    #[unsafe_ignore_trace]
+    id: Rc<RefCell<u64>>,
+}
+
+/// Install `matchMedia` for an environment
+///
+/// Returns the lists, which the engine updates when the environment
+/// changes.
+pub fn initialize_media_query_bindings(context: &mut Context, features: MediaFeatures) -> JsResult<MediaQueryLists> {
+    let lists = MediaQueryLists::new(features);
+    let match_media = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &ListsBinding, context| {
+            let text = match args.first() {
+                Some(value) if !value.is_undefined() => value.to_string(context)?.to_std_string_escaped(),
+                _ => String::new(),
+            };
+            let query = MediaQueryList::parse(&text);
+            let media = query.text().to_string();
+            let id = Rc::new(RefCell::new(0));
+            let object = list_object(&binding.lists, &id, &media, context);
+            let (list_id, matches) = binding.lists.register(query, object.clone());
+            *id.borrow_mut() = list_id;
+            object.set(js_string!("matches"), matches, false, context)?;
+            Ok(object.into())
+        },
+        ListsBinding { lists: lists.clone() },
+    );
+    context.register_global_builtin_callable(js_string!("matchMedia"), 1, match_media)?;
+    Ok(lists)
+}
+
+/// Create the object returned by `matchMedia()`
+///
+/// The list is registered after the object exists, so the methods read
+/// its id through a shared cell.
+fn list_object(lists: &MediaQueryLists, id: &Rc<RefCell<u64>>, media: &str, context: &mut Context) -> JsObject {
+    let binding = || ListBinding { lists: lists.clone(), id: Rc::clone(id) };
+
+    let add_event_listener = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &ListBinding, context| {
+            let event_type = args.first().cloned().unwrap_or_default().to_string(context)?.to_std_string_escaped();
+            if let (true, Some(listener)) = (event_type == "change", args.get(1).and_then(JsValue::as_callable)) {
+                binding.lists.add_listener(*binding.id.borrow(), listener.clone());
+            }
+            Ok(JsValue::undefined())
+        },
+        binding(),
+    );
+    let remove_event_listener = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &ListBinding, context| {
+            let event_type = args.first().cloned().unwrap_or_default().to_string(context)?.to_std_string_escaped();
+            if let (true, Some(listener)) = (event_type == "change", args.get(1).and_then(JsValue::as_object)) {
+                binding.lists.remove_listener(*binding.id.borrow(), listener);
+            }
+            Ok(JsValue::undefined())
+        },
+        binding(),
+    );
+    // The legacy `addListener`/`removeListener` take only the callback
+    let add_listener = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &ListBinding, _context| {
+            if let Some(listener) = args.first().and_then(JsValue::as_callable) {
+                binding.lists.add_listener(*binding.id.borrow(), listener.clone());
+            }
+            Ok(JsValue::undefined())
+        },
+        binding(),
+    );
+    let remove_listener = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &ListBinding, _context| {
+            if let Some(listener) = args.first().and_then(JsValue::as_object) {
+                binding.lists.remove_listener(*binding.id.borrow(), listener);
+            }
+            Ok(JsValue::undefined())
+        },
+        binding(),
+    );
+
+    ObjectInitializer::new(context)
+        .property(js_string!("media"), js_string!(media), Attribute::READONLY)
+        .property(js_string!("matches"), false, Attribute::all())
+        .property(js_string!("onchange"), JsValue::null(), Attribute::all())
+        .function(add_event_listener, js_string!("addEventListener"), 2)
+        .function(remove_event_listener, js_string!("removeEventListener"), 2)
+        .function(add_listener, js_string!("addListener"), 1)
+        .function(remove_listener, js_string!("removeListener"), 1)
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use boa_engine::Source;
+    use css_parser::media::ColorScheme;
+
+    fn eval_string(context: &mut Context, code: &str) -> String {
+        let value = context.eval(Source::from_bytes(code)).unwrap();
+        value.to_string(context).unwrap().to_std_string_escaped()
+    }
+
+    #[test]
+    fn test_match_media_change_events() {
+        let mut context = Context::default();
+        let lists = initialize_media_query_bindings(&mut context, MediaFeatures::default()).unwrap();
+        eval_string(&mut context, r#"
+            var log = [];
+            var dark = matchMedia('(prefers-color-scheme: dark)');
+            var motion = matchMedia('(prefers-reduced-motion: reduce)');
+            var wide = matchMedia('(min-width: 600px)');
+            dark.addEventListener('change', function (e) { log.push(e.media + '=' + e.matches); });
+            motion.onchange = function (e) { log.push('motion=' + e.matches); };
+            function ignored() { log.push('removed listener ran'); }
+            wide.addListener(ignored);
+            wide.removeListener(ignored);
+        "#);
+        assert_eq!(eval_string(&mut context, "dark.matches + ',' + wide.matches + ',' + dark.media"), "false,true,(prefers-color-scheme: dark)");
+
+        let dark = MediaFeatures { color_scheme: ColorScheme::Dark, reduced_motion: true, ..MediaFeatures::default() };
+        assert_eq!(lists.update(dark.clone(), &mut context), 2);
+        assert_eq!(eval_string(&mut context, "log.join(';')"), "(prefers-color-scheme: dark)=true;motion=true");
+        assert_eq!(eval_string(&mut context, "dark.matches"), "true");
+
+        // Nothing flips when the environment stays the same
+        assert_eq!(lists.update(dark, &mut context), 0);
+        let narrow = MediaFeatures { viewport_width: 400.0, ..MediaFeatures::default() };
+        assert_eq!(lists.update(narrow, &mut context), 3);
+        assert_eq!(eval_string(&mut context, "log.length + ',' + wide.matches"), "4,false");
+    }
+}
@@ -0,0 +1,438 @@
+//! # DOM Node Handle Table
+//!
+//! This module mediates every reference from JavaScript objects to DOM nodes.
+//! JavaScript wrappers never own an `Rc<Node>` directly; they carry a handle
+//! id that is resolved through the `NodeHandleTable`.
+//!
+//! ## Design Principles
+//!
+//! 1. **Weak by Default**: While a node is attached to the document, the tree
+//!    owns it and the handle only holds a `Weak<Node>`, so JS wrappers cannot
+//!    keep removed subtrees alive by accident.
+//! 2. **Pinning on Removal**: When a node that JS still references is
+//!    detached, by any path through the DOM's removal observers, its handle
+//!    is pinned (upgraded to a strong `Rc<Node>`) so the JS object keeps
+//!    working, matching DOM semantics.
+//! 3. **GC Driven Release**: Each wrapper holds a GC token; after a Boa
+//!    collection, handles whose token died are released by `sweep`.
+//! 4. **Leak Detection**: Handles pinned to detached subtrees for several
+//!    sweeps are reported as suspected leaks in the engine telemetry.
+
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+use boa_engine::{
+    object::JsObject,
+    property::PropertyDescriptor,
+    js_string, Context, JsData, JsValue,
+};
+use boa_gc::{Finalize, Gc, Trace, WeakGc};
+use dom::Node;
+
+/// Identifier of a node handle as seen by JavaScript
+pub type NodeHandleId = u64;
+
+/// Number of consecutive sweeps a detached subtree may stay pinned before it
+/// is reported as a suspected leak
+pub const LEAK_SWEEP_THRESHOLD: u32 = 3;
+
+/// GC-managed token owned by the JS wrapper object
+///
+/// The table keeps a `WeakGc` to the token; once the wrapper object is
+/// collected the token dies with it.
+#[derive(Debug, Trace, Finalize)]
+pub struct HandleToken {
+    pub handle_id: NodeHandleId,
+}
+
+/// Native data attached to node wrapper objects
+#[derive(Debug, Trace, Finalize, JsData)]
+struct NodeWrapperData {
+    token: Gc<HandleToken>,
+}
+
+/// How a handle refers to its node
+#[derive(Debug)]
+enum HandleRef {
+    /// The DOM tree owns the node
+    Weak(Weak<Node>),
+    /// The handle keeps a detached node alive on behalf of JavaScript
+    Pinned(Rc<Node>),
+}
+
+#[derive(Debug)]
+struct HandleEntry {
+    node_id: u64,
+    reference: HandleRef,
+    /// Weak references to the tokens of every wrapper created for this handle
+    tokens: Vec<WeakGc<HandleToken>>,
+    /// Number of consecutive sweeps this handle stayed pinned to a detached node
+    detached_sweeps: u32,
+}
+
+impl HandleEntry {
+    fn node(&self) -> Option<Rc<Node>> {
+        match &self.reference {
+            HandleRef::Weak(weak) => weak.upgrade(),
+            HandleRef::Pinned(node) => Some(Rc::clone(node)),
+        }
+    }
+
+    fn is_pinned(&self) -> bool {
+        matches!(self.reference, HandleRef::Pinned(_))
+    }
+
+    /// Whether JavaScript can still reach this handle
+    ///
+    /// Handles created without a wrapper are considered reachable.
+    fn wrapper_alive(&self) -> bool {
+        self.tokens.is_empty() || self.tokens.iter().any(|token| token.upgrade().is_some())
+    }
+}
+
+/// Counters describing the handle table, surfaced in engine telemetry
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct NodeHandleStats {
+    /// Handles currently in the table
+    pub live_handles: usize,
+    /// Handles currently holding a strong reference
+    pub pinned_handles: usize,
+    /// Total handles created
+    pub total_created: usize,
+    /// Handles released because their JS wrapper was collected
+    pub released_by_gc: usize,
+    /// Handles dropped because their node was freed while only weakly held
+    pub dangling_swept: usize,
+    /// Number of sweeps performed
+    pub sweep_count: usize,
+    /// Handles pinned to detached subtrees for too long
+    pub suspected_leaks: usize,
+    /// Nodes kept alive by suspected leaks (including descendants)
+    pub leaked_nodes: usize,
+}
+
+/// Result of a single sweep
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SweepReport {
+    pub released: usize,
+    pub dangling: usize,
+    pub pinned: usize,
+    pub unpinned: usize,
+}
+
+/// Table of DOM node handles exposed to JavaScript
+#[derive(Debug, Default)]
+pub struct NodeHandleTable {
+    entries: HashMap<NodeHandleId, HandleEntry>,
+    by_node: HashMap<u64, NodeHandleId>,
+    next_id: NodeHandleId,
+    stats: NodeHandleStats,
+}
+
+impl NodeHandleTable {
+    /// Create an empty handle table
+    pub fn new() -> Self {
+        Self {
+            next_id: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Get the handle for a node, creating a weak handle if none exists
+    pub fn handle_for(&mut self, node: &Rc<Node>) -> NodeHandleId {
+        if let Some(&id) = self.by_node.get(&node.id) {
+            if let Some(entry) = self.entries.get(&id) {
+                if entry.node().is_some_and(|existing| Rc::ptr_eq(&existing, node)) {
+                    return id;
+                }
+            }
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.entries.insert(id, HandleEntry {
+            node_id: node.id,
+            reference: HandleRef::Weak(Rc::downgrade(node)),
+            tokens: Vec::new(),
+            detached_sweeps: 0,
+        });
+        self.by_node.insert(node.id, id);
+        self.stats.total_created += 1;
+        id
+    }
+
+    /// Create a JS wrapper object for a node
+    ///
+    /// The wrapper carries the handle id in its `__nodeHandle` property and a
+    /// GC token that lets `sweep` notice when the wrapper is collected. The
+    /// handle stays alive while any of its wrappers is reachable.
+    pub fn wrap_node(&mut self, node: &Rc<Node>, context: &mut Context) -> JsObject {
+        let handle_id = self.handle_for(node);
+        let token = Gc::new(HandleToken { handle_id });
+        if let Some(entry) = self.entries.get_mut(&handle_id) {
+            entry.tokens.retain(|token| token.upgrade().is_some());
+            entry.tokens.push(WeakGc::new(&token));
+        }
+
+        let wrapper = JsObject::from_proto_and_data(None, NodeWrapperData { token });
+        let _ = wrapper.define_property_or_throw(
+            js_string!("__nodeHandle"),
+            PropertyDescriptor::builder()
+                .value(JsValue::from(handle_id as f64))
+                .writable(false)
+                .enumerable(false)
+                .configurable(false),
+            context,
+        );
+        wrapper
+    }
+
+    /// Resolve a handle to its node
+    ///
+    /// Returns `None` if the handle is unknown or the node has been freed.
+    pub fn resolve(&self, handle_id: NodeHandleId) -> Option<Rc<Node>> {
+        self.entries.get(&handle_id).and_then(|entry| entry.node())
+    }
+
+    /// Resolve the handle stored on a JS wrapper object
+    pub fn resolve_wrapper(&self, wrapper: &JsObject, context: &mut Context) -> Option<Rc<Node>> {
+        let value = wrapper.get(js_string!("__nodeHandle"), context).ok()?;
+        let handle_id = value.as_number()? as NodeHandleId;
+        self.resolve(handle_id)
+    }
+
+    /// Pin a handle so that it keeps its node alive
+    pub fn pin(&mut self, handle_id: NodeHandleId) -> bool {
+        match self.entries.get_mut(&handle_id) {
+            Some(entry) => match entry.node() {
+                Some(node) => {
+                    entry.reference = HandleRef::Pinned(node);
+                    true
+                }
+                None => false,
+            },
+            None => false,
+        }
+    }
+
+    /// Downgrade a pinned handle back to a weak reference
+    pub fn unpin(&mut self, handle_id: NodeHandleId) {
+        if let Some(entry) = self.entries.get_mut(&handle_id) {
+            if let HandleRef::Pinned(node) = &entry.reference {
+                entry.reference = HandleRef::Weak(Rc::downgrade(node));
+                entry.detached_sweeps = 0;
+            }
+        }
+    }
+
+    /// Pin every handle whose node is in the subtree rooted at `root`
+    ///
+    /// Call this when a subtree is removed from its parent, while the
+    /// caller still holds it, so that JavaScript references to it stay
+    /// valid.
+    pub fn pin_subtree(&mut self, root: &Rc<Node>) -> usize {
+        let mut pinned = 0;
+        if self.by_node.is_empty() {
+            return pinned;
+        }
+        let mut stack = vec![Rc::clone(root)];
+        while let Some(node) = stack.pop() {
+            if let Some(&handle_id) = self.by_node.get(&node.id) {
+                let is_same_node = self
+                    .resolve(handle_id)
+                    .is_some_and(|existing| Rc::ptr_eq(&existing, &node));
+                if is_same_node && self.pin(handle_id) {
+                    pinned += 1;
+                }
+            }
+            stack.extend(node.children.borrow().iter().cloned());
+        }
+        pinned
+    }
+
+    /// Sweep the table after a garbage collection
+    ///
+    /// - handles whose JS wrapper was collected are released
+    /// - weak handles whose node was freed are dropped as dangling
+    /// - live handles to detached nodes are pinned, handles to attached
+    ///   nodes are downgraded to weak references
+    /// - handles pinned to detached nodes for `LEAK_SWEEP_THRESHOLD` sweeps
+    ///   are counted as suspected leaks
+    pub fn sweep(&mut self, document_root: Option<&Rc<Node>>) -> SweepReport {
+        let mut report = SweepReport::default();
+        let mut removed = Vec::new();
+
+        for (&handle_id, entry) in self.entries.iter_mut() {
+            if !entry.wrapper_alive() {
+                removed.push((handle_id, entry.node_id));
+                report.released += 1;
+                continue;
+            }
+
+            let node = match entry.node() {
+                Some(node) => node,
+                None => {
+                    removed.push((handle_id, entry.node_id));
+                    report.dangling += 1;
+                    continue;
+                }
+            };
+
+            let attached = match document_root {
+                Some(root) => is_attached_to(&node, root),
+                None => false,
+            };
+
+            if attached {
+                if entry.is_pinned() {
+                    entry.reference = HandleRef::Weak(Rc::downgrade(&node));
+                    report.unpinned += 1;
+                }
+                entry.detached_sweeps = 0;
+            } else {
+                if !entry.is_pinned() {
+                    entry.reference = HandleRef::Pinned(Rc::clone(&node));
+                    report.pinned += 1;
+                }
+                entry.detached_sweeps += 1;
+            }
+        }
+
+        for (handle_id, node_id) in removed {
+            self.entries.remove(&handle_id);
+            if self.by_node.get(&node_id) == Some(&handle_id) {
+                self.by_node.remove(&node_id);
+            }
+        }
+
+        self.stats.released_by_gc += report.released;
+        self.stats.dangling_swept += report.dangling;
+        self.stats.sweep_count += 1;
+        self.refresh_stats();
+        report
+    }
+
+    /// Get the current handle statistics
+    pub fn stats(&self) -> NodeHandleStats {
+        let mut stats = self.stats.clone();
+        stats.live_handles = self.entries.len();
+        stats.pinned_handles = self.entries.values().filter(|entry| entry.is_pinned()).count();
+        stats
+    }
+
+    /// Number of handles in the table
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the table is empty
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn refresh_stats(&mut self) {
+        let leaking: Vec<Rc<Node>> = self
+            .entries
+            .values()
+            .filter(|entry| entry.is_pinned() && entry.detached_sweeps >= LEAK_SWEEP_THRESHOLD)
+            .filter_map(|entry| entry.node())
+            .collect();
+
+        self.stats.suspected_leaks = leaking.len();
+        self.stats.leaked_nodes = leaking.iter().map(subtree_size).sum();
+    }
+}
+
+/// Check whether a node is connected to the given document root
+fn is_attached_to(node: &Rc<Node>, root: &Rc<Node>) -> bool {
+    let mut current = Rc::clone(node);
+    loop {
+        if Rc::ptr_eq(&current, root) {
+            return true;
+        }
+        let parent = current.parent.borrow().upgrade();
+        match parent {
+            Some(parent) => current = parent,
+            None => return false,
+        }
+    }
+}
+
+fn subtree_size(node: &Rc<Node>) -> usize {
+    1 + node.children.borrow().iter().map(subtree_size).sum::<usize>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dom::Document;
+
+    #[test]
+    fn test_handles_are_weak_while_attached() {
+        let doc = Document::new();
+        let div = doc.create_element("div");
+        doc.root.append_child(&div);
+
+        let mut table = NodeHandleTable::new();
+        let handle = table.handle_for(&div);
+        assert_eq!(table.handle_for(&div), handle);
+        assert!(Rc::ptr_eq(&table.resolve(handle).unwrap(), &div));
+
+        table.sweep(Some(&doc.root));
+        assert_eq!(table.stats().pinned_handles, 0);
+    }
+
+    #[test]
+    fn test_detached_nodes_are_pinned_and_leaks_reported() {
+        let doc = Document::new();
+        let mut table = NodeHandleTable::new();
+
+        let handle = {
+            let detached = doc.create_element("div");
+            detached.append_child(&doc.create_text_node("leak"));
+            table.handle_for(&detached)
+        };
+        // The node was freed before the first sweep: the handle dangles
+        assert!(table.resolve(handle).is_none());
+        assert_eq!(table.sweep(Some(&doc.root)).dangling, 1);
+
+        let detached = doc.create_element("div");
+        detached.append_child(&doc.create_text_node("kept"));
+        let handle = table.handle_for(&detached);
+        assert_eq!(table.sweep(Some(&doc.root)).pinned, 1);
+        drop(detached);
+
+        // The pinned handle keeps the detached subtree alive
+        assert!(table.resolve(handle).is_some());
+        table.sweep(Some(&doc.root));
+        table.sweep(Some(&doc.root));
+        let stats = table.stats();
+        assert_eq!(stats.suspected_leaks, 1);
+        assert_eq!(stats.leaked_nodes, 2);
+
+        // Reattaching the node downgrades the handle again
+        let node = table.resolve(handle).unwrap();
+        doc.root.append_child(&node);
+        assert_eq!(table.sweep(Some(&doc.root)).unpinned, 1);
+        assert_eq!(table.stats().suspected_leaks, 0);
+    }
+
+    #[test]
+    fn test_wrapper_collection_releases_handle() {
+        let mut context = Context::default();
+        let doc = Document::new();
+        let div = doc.create_element("div");
+        doc.root.append_child(&div);
+
+        let mut table = NodeHandleTable::new();
+        let wrapper = table.wrap_node(&div, &mut context);
+        assert!(Rc::ptr_eq(&table.resolve_wrapper(&wrapper, &mut context).unwrap(), &div));
+
+        boa_gc::force_collect();
+        assert_eq!(table.sweep(Some(&doc.root)).released, 0);
+
+        drop(wrapper);
+        boa_gc::force_collect();
+        assert_eq!(table.sweep(Some(&doc.root)).released, 1);
+        assert!(table.is_empty());
+    }
+}
@@ -0,0 +1,241 @@
+//! # Notifications
+//!
+//! This module implements the `Notification` constructor, its static
+//! `permission` and `requestPermission()`, and `close()`. A notification
+//! of an origin with the `notifications` permission is handed to the
+//! shell, which shows it on the desktop.
+//!
+//! ## Design Principles
+//!
+//! 1. **Permission First**: Only `requestPermission()` prompts. A page
+//!    without the permission can construct notifications, but they are
+//!    never shown.
+//! 2. **The Shell Owns the UI**: Scripts never see how a notification is
+//!    shown; the shell may use the desktop's notification service, print
+//!    it or record it for tests.
+
+use std::fmt;
+use std::sync::Arc;
+use boa_engine::{
+    object::{builtins::JsPromise, FunctionObjectBuilder, ObjectInitializer},
+    property::{Attribute, PropertyDescriptor},
+    js_string, Context, JsNativeError, JsObject, JsResult, JsValue, NativeFunction,
+};
+use boa_gc::{Finalize, Trace};
+
+use crate::permissions::{PermissionName, PermissionState, Permissions};
+
+/// A notification a page shows
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DesktopNotification {
+    /// The origin of the page, for the shell to attribute it
+    pub origin: String,
+    pub title: String,
+    pub body: String,
+    /// Notifications with the same non-empty tag replace each other
+    pub tag: String,
+    /// URL of the icon, if any
+    pub icon: String,
+}
+
+impl fmt::Display for DesktopNotification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.title, self.origin)
+    }
+}
+
+/// Callback showing a notification to the user
+pub type NotificationHandler = Arc<dyn Fn(&DesktopNotification) + Send + Sync>;
+
+/// Captures of the `Notification` constructor and its static members
+#[derive(Clone, Trace, Finalize)]
+struct NotificationBinding {
+    #[unsafe_ignore_trace]
+    permissions: Permissions,
+    #[unsafe_ignore_trace]
+    handler: NotificationHandler,
+    #[unsafe_ignore_trace]
+    origin: String,
+    prototype: JsObject,
+}
+
+/// Get the value of `Notification.permission` for a permission state
+fn permission_value(state: PermissionState) -> &'static str {
+    match state {
+        PermissionState::Prompt => "default",
+        state => state.as_str(),
+    }
+}
+
+/// Read a string member of the options of `new Notification()`
+fn option(options: &JsValue, name: &str, context: &mut Context) -> JsResult<String> {
+    let Some(options) = options.as_object() else {
+        return Ok(String::new());
+    };
+    let value = options.get(js_string!(name), context)?;
+    if value.is_undefined() {
+        return Ok(String::new());
+    }
+    Ok(value.to_string(context)?.to_std_string_escaped())
+}
+
+/// `new Notification(title, options)`
+fn construct_notification(binding: &NotificationBinding, new_target: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+    if new_target.as_object().is_none() {
+        return Err(JsNativeError::typ()
+            .with_message("Failed to construct 'Notification': Please use the 'new' operator")
+            .into());
+    }
+    let Some(title) = args.first() else {
+        return Err(JsNativeError::typ()
+            .with_message("Failed to construct 'Notification': 1 argument required, but only 0 present")
+            .into());
+    };
+    let options = args.get(1).cloned().unwrap_or_default();
+    let notification = DesktopNotification {
+        origin: binding.origin.clone(),
+        title: title.to_string(context)?.to_std_string_escaped(),
+        body: option(&options, "body", context)?,
+        tag: option(&options, "tag", context)?,
+        icon: option(&options, "icon", context)?,
+    };
+
+    let object = ObjectInitializer::new(context)
+        .property(js_string!("title"), js_string!(notification.title.as_str()), Attribute::READONLY | Attribute::ENUMERABLE)
+        .property(js_string!("body"), js_string!(notification.body.as_str()), Attribute::READONLY | Attribute::ENUMERABLE)
+        .property(js_string!("tag"), js_string!(notification.tag.as_str()), Attribute::READONLY | Attribute::ENUMERABLE)
+        .property(js_string!("icon"), js_string!(notification.icon.as_str()), Attribute::READONLY | Attribute::ENUMERABLE)
+        .build();
+    object.set_prototype(Some(binding.prototype.clone()));
+
+    if binding.permissions.state(&binding.origin, PermissionName::Notifications) == PermissionState::Granted {
+        println!("🔔 Notification from {}: {}", notification.origin, notification.title);
+        (binding.handler)(&notification);
+    } else {
+        println!("🛑 Notification from {} not shown: permission not granted", notification.origin);
+    }
+    Ok(object.into())
+}
+
+/// Install `Notification` for a document from `origin`
+///
+/// Notifications are shown with `handler` once the user grants `origin`
+/// the `notifications` permission, see `Permissions::request`.
+pub fn initialize_notification_bindings(
+    context: &mut Context,
+    permissions: Permissions,
+    origin: &str,
+    handler: NotificationHandler,
+) -> JsResult<()> {
+    let prototype = JsObject::with_object_proto(context.intrinsics());
+    let close = NativeFunction::from_fn_ptr(|_this, _args, _context| Ok(JsValue::undefined()));
+    prototype.define_property_or_throw(
+        js_string!("close"),
+        PropertyDescriptor::builder()
+            .value(FunctionObjectBuilder::new(context.realm(), close).name(js_string!("close")).length(0).build())
+            .writable(true)
+            .enumerable(false)
+            .configurable(true),
+        context,
+    )?;
+    let binding = NotificationBinding { permissions, handler, origin: origin.to_string(), prototype: prototype.clone() };
+
+    let construct = NativeFunction::from_copy_closure_with_captures(
+        |this, args, binding: &NotificationBinding, context| construct_notification(binding, this, args, context),
+        binding.clone(),
+    );
+    let constructor = FunctionObjectBuilder::new(context.realm(), construct)
+        .name(js_string!("Notification"))
+        .length(1)
+        .constructor(true)
+        .build();
+    constructor.define_property_or_throw(
+        js_string!("prototype"),
+        PropertyDescriptor::builder().value(prototype.clone()).writable(false).enumerable(false).configurable(false),
+        context,
+    )?;
+    prototype.define_property_or_throw(
+        js_string!("constructor"),
+        PropertyDescriptor::builder().value(constructor.clone()).writable(true).enumerable(false).configurable(true),
+        context,
+    )?;
+
+    let permission = NativeFunction::from_copy_closure_with_captures(
+        |_this, _args, binding: &NotificationBinding, _context| {
+            let state = binding.permissions.state(&binding.origin, PermissionName::Notifications);
+            Ok(js_string!(permission_value(state)).into())
+        },
+        binding.clone(),
+    );
+    constructor.define_property_or_throw(
+        js_string!("permission"),
+        PropertyDescriptor::builder()
+            .get(permission.to_js_function(context.realm()))
+            .enumerable(true)
+            .configurable(true),
+        context,
+    )?;
+
+    let request_permission = NativeFunction::from_copy_closure_with_captures(
+        |_this, _args, binding: &NotificationBinding, context| {
+            let state = binding.permissions.request(&binding.origin, PermissionName::Notifications);
+            Ok(JsPromise::resolve(js_string!(permission_value(state)), context).into())
+        },
+        binding,
+    );
+    constructor.define_property_or_throw(
+        js_string!("requestPermission"),
+        PropertyDescriptor::builder()
+            .value(FunctionObjectBuilder::new(context.realm(), request_permission).name(js_string!("requestPermission")).length(0).build())
+            .writable(true)
+            .enumerable(true)
+            .configurable(true),
+        context,
+    )?;
+
+    context.global_object().set(js_string!("Notification"), constructor, false, context)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use boa_engine::Source;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_notifications_are_shown_once_permitted() {
+        let context = &mut Context::default();
+        let shown = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&shown);
+        let permissions = Permissions::new().with_prompt(Arc::new(|_, _| Some(true)));
+        initialize_notification_bindings(
+            context,
+            permissions.clone(),
+            "https://chat.example",
+            Arc::new(move |notification: &DesktopNotification| log.lock().unwrap().push(notification.clone())),
+        )
+        .unwrap();
+
+        let code = r#"
+            globalThis.log = [Notification.permission];
+            new Notification("Too early");
+            Notification.requestPermission().then((permission) => {
+                log.push(permission, Notification.permission);
+                const message = new Notification("New message", { body: "Hi!", tag: "chat" });
+                log.push(message.title, message.body, message instanceof Notification);
+                message.close();
+            });
+        "#;
+        context.eval(Source::from_bytes(code)).unwrap();
+        context.run_jobs();
+        let log = context.eval(Source::from_bytes("log.join(',')")).unwrap();
+        assert_eq!(log.to_string(context).unwrap().to_std_string_escaped(), "default,granted,granted,New message,Hi!,true");
+
+        let shown = shown.lock().unwrap();
+        assert_eq!(shown.len(), 1);
+        assert_eq!(shown[0].tag, "chat");
+        assert_eq!(shown[0].origin, "https://chat.example");
+        assert!(context.eval(Source::from_bytes("Notification('x')")).is_err());
+    }
+}
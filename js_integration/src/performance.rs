@@ -0,0 +1,916 @@
+//! # Resource and Paint Timing
+//!
+//! This module records when resources were fetched and when the document was
+//! first painted, and exposes the entries to scripts through
+//! `performance.getEntriesByType()` and `PerformanceObserver`.
+//!
+//! ## Design Principles
+//!
+//! 1. **One Timeline**: The shell records entries on a `PerformanceTimeline`
+//!    shared with the script engine, so pages and the shell's own metrics
+//!    see the same numbers.
+//! 2. **Relative Timestamps**: Every timestamp is in milliseconds since the
+//!    time origin, the start of the navigation, as in browsers.
+//! 3. **Observers Are Tasks**: New entries are queued for each observer and
+//!    delivered when the engine next spins its event loop, never while an
+//!    entry is being recorded.
+//! 4. **Bounded Buffer**: Only the first `DEFAULT_RESOURCE_BUFFER_SIZE`
+//!    resource entries stay in the timeline; observers still receive all of
+//!    them.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Instant;
+use dom::clock::{self, SharedClock};
+use boa_engine::{
+    object::{builtins::JsArray, ObjectInitializer},
+    property::Attribute,
+    js_string, Context, JsNativeError, JsObject, JsResult, JsValue, NativeFunction,
+};
+use boa_gc::{Finalize, Trace};
+use dom::NodeType;
+use layout::LayoutBox;
+use networking::FetchTiming;
+
+/// Number of resource entries kept in the timeline by default
+pub const DEFAULT_RESOURCE_BUFFER_SIZE: usize = 250;
+
+/// Name of the paint entry for the first frame
+pub const FIRST_PAINT: &str = "first-paint";
+
+/// Name of the paint entry for the first frame with text or images
+pub const FIRST_CONTENTFUL_PAINT: &str = "first-contentful-paint";
+
+/// The kind of a performance entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryType {
+    Resource,
+    Paint,
+}
+
+impl EntryType {
+    /// Entry types that can be observed, for `supportedEntryTypes`
+    pub const SUPPORTED: [EntryType; 2] = [EntryType::Paint, EntryType::Resource];
+
+    /// Parse an entry type name as used by scripts
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "resource" => Some(EntryType::Resource),
+            "paint" => Some(EntryType::Paint),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EntryType::Resource => "resource",
+            EntryType::Paint => "paint",
+        }
+    }
+}
+
+impl fmt::Display for EntryType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Timing details of a fetched resource
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceTiming {
+    /// What started the fetch, e.g. `link`, `script` or `img`
+    pub initiator_type: String,
+    pub fetch_start: f64,
+    pub response_start: f64,
+    pub response_end: f64,
+    /// Size of the response body in bytes
+    pub transfer_size: usize,
+    pub response_status: u16,
+}
+
+/// A recorded performance entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct PerformanceEntry {
+    /// The resource URL, or the paint name
+    pub name: String,
+    pub entry_type: EntryType,
+    /// Milliseconds since the time origin
+    pub start_time: f64,
+    pub duration: f64,
+    /// Set for resource entries
+    pub resource: Option<ResourceTiming>,
+}
+
+/// Entries queued for one set of observers
+type EntryFeed = Arc<Mutex<Vec<PerformanceEntry>>>;
+
+struct TimelineState {
+    /// Where `now()` reads the time
+    clock: SharedClock,
+    time_origin: Instant,
+    /// The time origin in milliseconds since the Unix epoch
+    time_origin_unix: f64,
+    entries: Vec<PerformanceEntry>,
+    resource_buffer_size: usize,
+    feeds: Vec<Weak<Mutex<Vec<PerformanceEntry>>>>,
+}
+
+/// The performance entries of one document
+#[derive(Clone)]
+pub struct PerformanceTimeline {
+    state: Arc<Mutex<TimelineState>>,
+}
+
+impl PerformanceTimeline {
+    /// Create a timeline whose time origin is now
+    pub fn new() -> Self {
+        Self::with_time_origin(clock::now())
+    }
+
+    /// Create a timeline with the given time origin, usually the start of
+    /// the navigation
+    pub fn with_time_origin(time_origin: Instant) -> Self {
+        Self::with_clock(clock::thread_clock(), time_origin)
+    }
+
+    /// Create a timeline reading the time from `clock`
+    pub fn with_clock(clock: SharedClock, time_origin: Instant) -> Self {
+        let unix_now = clock.unix_time().as_secs_f64() * 1000.0;
+        let since_origin = clock.now().saturating_duration_since(time_origin).as_secs_f64() * 1000.0;
+        Self {
+            state: Arc::new(Mutex::new(TimelineState {
+                clock,
+                time_origin,
+                time_origin_unix: unix_now - since_origin,
+                entries: Vec::new(),
+                resource_buffer_size: DEFAULT_RESOURCE_BUFFER_SIZE,
+                feeds: Vec::new(),
+            })),
+        }
+    }
+
+    pub fn time_origin(&self) -> Instant {
+        self.state.lock().unwrap().time_origin
+    }
+
+    /// Get the time origin in milliseconds since the Unix epoch
+    pub fn time_origin_unix(&self) -> f64 {
+        self.state.lock().unwrap().time_origin_unix
+    }
+
+    /// Get the current time in milliseconds since the time origin
+    pub fn now(&self) -> f64 {
+        self.timestamp(self.clock().now())
+    }
+
+    /// Get the clock the timeline reads the time from
+    pub fn clock(&self) -> SharedClock {
+        Arc::clone(&self.state.lock().unwrap().clock)
+    }
+
+    /// Read the time from `clock` from now on
+    ///
+    /// The time origin moves to the clock's current time, so call this
+    /// before recording entries.
+    pub fn set_clock(&self, clock: SharedClock) {
+        let mut state = self.state.lock().unwrap();
+        state.time_origin = clock.now();
+        state.time_origin_unix = clock.unix_time().as_secs_f64() * 1000.0;
+        state.clock = clock;
+    }
+
+    /// Convert an instant to milliseconds since the time origin
+    ///
+    /// Instants before the time origin are clamped to zero.
+    pub fn timestamp(&self, instant: Instant) -> f64 {
+        let time_origin = self.time_origin();
+        instant.saturating_duration_since(time_origin).as_secs_f64() * 1000.0
+    }
+
+    /// Record the fetch of a resource
+    pub fn record_resource(
+        &self,
+        url: &str,
+        initiator_type: &str,
+        timing: &FetchTiming,
+        transfer_size: usize,
+        response_status: u16,
+    ) {
+        let fetch_start = self.timestamp(timing.fetch_start);
+        let response_end = self.timestamp(timing.response_end);
+        self.add_entry(PerformanceEntry {
+            name: url.to_string(),
+            entry_type: EntryType::Resource,
+            start_time: fetch_start,
+            duration: response_end - fetch_start,
+            resource: Some(ResourceTiming {
+                initiator_type: initiator_type.to_string(),
+                fetch_start,
+                response_start: self.timestamp(timing.response_start),
+                response_end,
+                transfer_size,
+                response_status,
+            }),
+        });
+    }
+
+    /// Record that a frame showing `layout` was painted now
+    pub fn record_paint(&self, layout: &LayoutBox) {
+        self.record_paint_at(layout, self.clock().now());
+    }
+
+    /// Record that a frame showing `layout` was painted at `painted_at`
+    ///
+    /// The first frame is the first paint. The first frame with text or an
+    /// image is the first contentful paint. Later frames add no entries.
+    pub fn record_paint_at(&self, layout: &LayoutBox, painted_at: Instant) {
+        let start_time = self.timestamp(painted_at);
+        if self.first_paint().is_none() {
+            self.add_entry(paint_entry(FIRST_PAINT, start_time));
+        }
+        if self.first_contentful_paint().is_none() && has_contentful_content(layout) {
+            self.add_entry(paint_entry(FIRST_CONTENTFUL_PAINT, start_time));
+        }
+    }
+
+    /// Get the time of the first paint, if the document was painted
+    pub fn first_paint(&self) -> Option<f64> {
+        self.paint_time(FIRST_PAINT)
+    }
+
+    /// Get the time of the first contentful paint, if there was one
+    pub fn first_contentful_paint(&self) -> Option<f64> {
+        self.paint_time(FIRST_CONTENTFUL_PAINT)
+    }
+
+    fn paint_time(&self, name: &str) -> Option<f64> {
+        self.entries_by_name(name, Some(EntryType::Paint))
+            .first()
+            .map(|entry| entry.start_time)
+    }
+
+    /// Get all entries in the timeline, ordered by start time
+    pub fn entries(&self) -> Vec<PerformanceEntry> {
+        let mut entries = self.state.lock().unwrap().entries.clone();
+        entries.sort_by(|a, b| a.start_time.total_cmp(&b.start_time));
+        entries
+    }
+
+    /// Get the entries of one type, ordered by start time
+    pub fn entries_by_type(&self, entry_type: EntryType) -> Vec<PerformanceEntry> {
+        self.entries()
+            .into_iter()
+            .filter(|entry| entry.entry_type == entry_type)
+            .collect()
+    }
+
+    /// Get the entries with a name, optionally of one type only
+    pub fn entries_by_name(&self, name: &str, entry_type: Option<EntryType>) -> Vec<PerformanceEntry> {
+        self.entries()
+            .into_iter()
+            .filter(|entry| entry.name == name && entry_type.is_none_or(|t| entry.entry_type == t))
+            .collect()
+    }
+
+    /// Set how many resource entries the timeline keeps
+    pub fn set_resource_timing_buffer_size(&self, size: usize) {
+        self.state.lock().unwrap().resource_buffer_size = size;
+    }
+
+    /// Remove all resource entries from the timeline
+    pub fn clear_resource_timings(&self) {
+        self.state
+            .lock()
+            .unwrap()
+            .entries
+            .retain(|entry| entry.entry_type != EntryType::Resource);
+    }
+
+    /// Get a feed that receives every entry recorded from now on
+    fn subscribe(&self) -> EntryFeed {
+        let feed = Arc::new(Mutex::new(Vec::new()));
+        self.state.lock().unwrap().feeds.push(Arc::downgrade(&feed));
+        feed
+    }
+
+    fn add_entry(&self, entry: PerformanceEntry) {
+        let mut state = self.state.lock().unwrap();
+        state.feeds.retain(|feed| match feed.upgrade() {
+            Some(feed) => {
+                feed.lock().unwrap().push(entry.clone());
+                true
+            }
+            None => false,
+        });
+
+        let resources = state
+            .entries
+            .iter()
+            .filter(|existing| existing.entry_type == EntryType::Resource)
+            .count();
+        if entry.entry_type == EntryType::Resource && resources >= state.resource_buffer_size {
+            return;
+        }
+        state.entries.push(entry);
+    }
+}
+
+impl Default for PerformanceTimeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for PerformanceTimeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PerformanceTimeline")
+            .field("entries", &self.state.lock().unwrap().entries)
+            .finish_non_exhaustive()
+    }
+}
+
+fn paint_entry(name: &str, start_time: f64) -> PerformanceEntry {
+    PerformanceEntry {
+        name: name.to_string(),
+        entry_type: EntryType::Paint,
+        start_time,
+        duration: 0.0,
+        resource: None,
+    }
+}
+
+/// Check whether a layout tree paints text or an image
+fn has_contentful_content(layout_box: &LayoutBox) -> bool {
+    let contentful = match &layout_box.node.node_type {
+        NodeType::Text(_) => !layout_box.node.text_content().trim().is_empty(),
+        NodeType::Element { tag_name, .. } => {
+            matches!(tag_name.as_str(), "img" | "svg" | "canvas" | "video")
+        }
+        _ => false,
+    };
+    contentful || layout_box.children.iter().any(has_contentful_content)
+}
+
+/// An observer registered with `PerformanceObserver.observe()`
+struct ObserverRecord {
+    id: u64,
+    callback: JsObject,
+    observer: JsObject,
+    entry_types: Vec<EntryType>,
+    buffer: Vec<PerformanceEntry>,
+}
+
+struct ObserverState {
+    feed: EntryFeed,
+    observers: Vec<ObserverRecord>,
+    next_id: u64,
+}
+
+/// The `PerformanceObserver`s of one script context
+#[derive(Clone)]
+pub struct PerformanceObservers {
+    timeline: PerformanceTimeline,
+    state: Rc<RefCell<ObserverState>>,
+}
+
+impl PerformanceObservers {
+    fn new(timeline: PerformanceTimeline) -> Self {
+        let feed = timeline.subscribe();
+        Self {
+            timeline,
+            state: Rc::new(RefCell::new(ObserverState {
+                feed,
+                observers: Vec::new(),
+                next_id: 1,
+            })),
+        }
+    }
+
+    /// Disconnect every observer, e.g. when the document is unloaded
+    ///
+    /// Returns the number of observers disconnected.
+    pub fn disconnect_all(&self) -> usize {
+        let mut state = self.state.borrow_mut();
+        state.feed.lock().unwrap().clear();
+        std::mem::take(&mut state.observers).len()
+    }
+
+    /// Check whether any entries are waiting to be delivered
+    pub fn has_pending(&self) -> bool {
+        let state = self.state.borrow();
+        !state.feed.lock().unwrap().is_empty()
+            || state.observers.iter().any(|record| !record.buffer.is_empty())
+    }
+
+    /// Deliver queued entries to their observers' callbacks
+    ///
+    /// Returns the number of callbacks that ran. A callback that throws is
+    /// reported and does not stop delivery to the other observers.
+    pub fn deliver(&self, context: &mut Context) -> usize {
+        let mut deliveries = Vec::new();
+        {
+            let mut state = self.state.borrow_mut();
+            let new_entries: Vec<PerformanceEntry> = state.feed.lock().unwrap().drain(..).collect();
+            for record in state.observers.iter_mut() {
+                record.buffer.extend(
+                    new_entries
+                        .iter()
+                        .filter(|entry| record.entry_types.contains(&entry.entry_type))
+                        .cloned(),
+                );
+                if !record.buffer.is_empty() {
+                    let entries = std::mem::take(&mut record.buffer);
+                    deliveries.push((record.callback.clone(), record.observer.clone(), entries));
+                }
+            }
+        }
+
+        let count = deliveries.len();
+        for (callback, observer, entries) in deliveries {
+            let list = entry_list_object(entries, context);
+            let observer_value = JsValue::from(observer);
+            if let Err(e) = callback.call(&observer_value, &[list, observer_value.clone()], context) {
+                println!("❌ PerformanceObserver callback failed: {}", e);
+            }
+        }
+        count
+    }
+
+    fn register(&self, callback: JsObject, observer: JsObject) -> u64 {
+        let mut state = self.state.borrow_mut();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.observers.push(ObserverRecord {
+            id,
+            callback,
+            observer,
+            entry_types: Vec::new(),
+            buffer: Vec::new(),
+        });
+        id
+    }
+
+    /// Start observing entry types, optionally queueing the entries
+    /// already in the timeline
+    fn observe(&self, id: u64, entry_types: Vec<EntryType>, replace: bool, buffered: bool) {
+        let buffered_entries: Vec<PerformanceEntry> = if buffered {
+            entry_types
+                .iter()
+                .flat_map(|entry_type| self.timeline.entries_by_type(*entry_type))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let mut state = self.state.borrow_mut();
+        if let Some(record) = state.observers.iter_mut().find(|record| record.id == id) {
+            if replace {
+                record.entry_types.clear();
+            }
+            for entry_type in entry_types {
+                if !record.entry_types.contains(&entry_type) {
+                    record.entry_types.push(entry_type);
+                }
+            }
+            record.buffer.extend(buffered_entries);
+        }
+    }
+
+    fn disconnect(&self, id: u64) {
+        let mut state = self.state.borrow_mut();
+        if let Some(record) = state.observers.iter_mut().find(|record| record.id == id) {
+            record.entry_types.clear();
+            record.buffer.clear();
+        }
+    }
+
+    fn take_records(&self, id: u64) -> Vec<PerformanceEntry> {
+        let mut state = self.state.borrow_mut();
+        let new_entries: Vec<PerformanceEntry> = state.feed.lock().unwrap().drain(..).collect();
+        let mut records = Vec::new();
+        for record in state.observers.iter_mut() {
+            record.buffer.extend(
+                new_entries
+                    .iter()
+                    .filter(|entry| record.entry_types.contains(&entry.entry_type))
+                    .cloned(),
+            );
+            if record.id == id {
+                records = std::mem::take(&mut record.buffer);
+            }
+        }
+        records
+    }
+}
+
+impl fmt::Debug for PerformanceObservers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PerformanceObservers")
+            .field("observers", &self.state.borrow().observers.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Captures for the `performance` methods
+#[derive(Trace, Finalize)]
+struct TimelineBinding {
+    #[unsafe_ignore_trace]
+    timeline: PerformanceTimeline,
+}
+
+/// Captures for the `PerformanceObserver` constructor
+#[derive(Trace, Finalize)]
+struct ObserversBinding {
+    #[unsafe_ignore_trace]
+    observers: PerformanceObservers,
+}
+
+/// Captures for the methods of a `PerformanceObserverEntryList`
+#[derive(Trace, Finalize)]
+struct EntryListBinding {
+    #[unsafe_ignore_trace]
+    entries: Vec<PerformanceEntry>,
+}
+
+/// Install `performance` and `PerformanceObserver` for a timeline
+///
+/// Returns the observers, which the engine delivers entries to when it
+/// spins the event loop.
+pub fn initialize_performance_bindings(
+    context: &mut Context,
+    timeline: PerformanceTimeline,
+) -> JsResult<PerformanceObservers> {
+    let binding = || TimelineBinding { timeline: timeline.clone() };
+
+    let now = NativeFunction::from_copy_closure_with_captures(
+        |_this, _args, binding: &TimelineBinding, _context| Ok(JsValue::from(binding.timeline.now())),
+        binding(),
+    );
+
+    let get_entries = NativeFunction::from_copy_closure_with_captures(
+        |_this, _args, binding: &TimelineBinding, context| Ok(entry_array(&binding.timeline.entries(), context)),
+        binding(),
+    );
+
+    let get_entries_by_type = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &TimelineBinding, context| {
+            let entries = match string_argument(args, 0, context)?.as_deref().and_then(EntryType::parse) {
+                Some(entry_type) => binding.timeline.entries_by_type(entry_type),
+                None => Vec::new(),
+            };
+            Ok(entry_array(&entries, context))
+        },
+        binding(),
+    );
+
+    let get_entries_by_name = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &TimelineBinding, context| {
+            let name = string_argument(args, 0, context)?.unwrap_or_default();
+            let entries = match string_argument(args, 1, context)? {
+                Some(entry_type) => match EntryType::parse(&entry_type) {
+                    Some(entry_type) => binding.timeline.entries_by_name(&name, Some(entry_type)),
+                    None => Vec::new(),
+                },
+                None => binding.timeline.entries_by_name(&name, None),
+            };
+            Ok(entry_array(&entries, context))
+        },
+        binding(),
+    );
+
+    let clear_resource_timings = NativeFunction::from_copy_closure_with_captures(
+        |_this, _args, binding: &TimelineBinding, _context| {
+            binding.timeline.clear_resource_timings();
+            Ok(JsValue::undefined())
+        },
+        binding(),
+    );
+
+    let set_resource_timing_buffer_size = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &TimelineBinding, context| {
+            let size = args.first().cloned().unwrap_or_default().to_length(context)?;
+            binding.timeline.set_resource_timing_buffer_size(size as usize);
+            Ok(JsValue::undefined())
+        },
+        binding(),
+    );
+
+    let performance = ObjectInitializer::new(context)
+        .property(js_string!("timeOrigin"), timeline.time_origin_unix(), Attribute::READONLY)
+        .function(now, js_string!("now"), 0)
+        .function(get_entries, js_string!("getEntries"), 0)
+        .function(get_entries_by_type, js_string!("getEntriesByType"), 1)
+        .function(get_entries_by_name, js_string!("getEntriesByName"), 1)
+        .function(clear_resource_timings, js_string!("clearResourceTimings"), 0)
+        .function(set_resource_timing_buffer_size, js_string!("setResourceTimingBufferSize"), 1)
+        .build();
+    let global = context.global_object();
+    global.set(js_string!("performance"), performance, false, context)?;
+
+    let observers = PerformanceObservers::new(timeline);
+    let constructor = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &ObserversBinding, context| {
+            let Some(callback) = args.first().and_then(JsValue::as_callable).cloned() else {
+                return Err(JsNativeError::typ()
+                    .with_message("PerformanceObserver requires a callback function")
+                    .into());
+            };
+            let observer = observer_object(&binding.observers, context);
+            let id = binding.observers.register(callback, observer.clone());
+            observer.set(js_string!("__observerId"), id as f64, false, context)?;
+            Ok(observer.into())
+        },
+        ObserversBinding { observers: observers.clone() },
+    );
+    context.register_global_callable(js_string!("PerformanceObserver"), 1, constructor)?;
+
+    let supported: Vec<JsValue> = EntryType::SUPPORTED
+        .iter()
+        .map(|entry_type| js_string!(entry_type.as_str()).into())
+        .collect();
+    let supported = JsArray::from_iter(supported, context);
+    if let Some(constructor) = global.get(js_string!("PerformanceObserver"), context)?.as_object() {
+        constructor.set(js_string!("supportedEntryTypes"), supported, false, context)?;
+    }
+
+    Ok(observers)
+}
+
+/// Create the object returned by `new PerformanceObserver()`
+///
+/// The methods find their record through the id stored on the object, so
+/// they are created before the record is registered.
+fn observer_object(observers: &PerformanceObservers, context: &mut Context) -> JsObject {
+    let observe = NativeFunction::from_copy_closure_with_captures(
+        |this, args, binding: &ObserversBinding, context| {
+            let id = observer_id(this, context)?;
+            let options = args.first().and_then(JsValue::as_object).cloned().ok_or_else(|| {
+                JsNativeError::typ().with_message("observe() requires an options object")
+            })?;
+
+            let entry_types = options.get(js_string!("entryTypes"), context)?;
+            let entry_type = options.get(js_string!("type"), context)?;
+            let (names, replace) = if let Some(list) = entry_types.as_object() {
+                let length = list.get(js_string!("length"), context)?.to_length(context)?;
+                let mut names = Vec::new();
+                for index in 0..length {
+                    let name = list.get(index, context)?.to_string(context)?.to_std_string_escaped();
+                    names.push(name);
+                }
+                (names, true)
+            } else if !entry_type.is_undefined() {
+                (vec![entry_type.to_string(context)?.to_std_string_escaped()], false)
+            } else {
+                return Err(JsNativeError::typ()
+                    .with_message("observe() requires either type or entryTypes")
+                    .into());
+            };
+
+            let mut parsed = Vec::new();
+            for name in names {
+                match EntryType::parse(&name) {
+                    Some(entry_type) => parsed.push(entry_type),
+                    None => println!("🔸 PerformanceObserver ignoring unsupported entry type '{}'", name),
+                }
+            }
+            let buffered = options.get(js_string!("buffered"), context)?.to_boolean();
+            binding.observers.observe(id, parsed, replace, buffered && !replace);
+            Ok(JsValue::undefined())
+        },
+        ObserversBinding { observers: observers.clone() },
+    );
+
+    let disconnect = NativeFunction::from_copy_closure_with_captures(
+        |this, _args, binding: &ObserversBinding, context| {
+            binding.observers.disconnect(observer_id(this, context)?);
+            Ok(JsValue::undefined())
+        },
+        ObserversBinding { observers: observers.clone() },
+    );
+
+    let take_records = NativeFunction::from_copy_closure_with_captures(
+        |this, _args, binding: &ObserversBinding, context| {
+            let records = binding.observers.take_records(observer_id(this, context)?);
+            Ok(entry_array(&records, context))
+        },
+        ObserversBinding { observers: observers.clone() },
+    );
+
+    ObjectInitializer::new(context)
+        .function(observe, js_string!("observe"), 1)
+        .function(disconnect, js_string!("disconnect"), 0)
+        .function(take_records, js_string!("takeRecords"), 0)
+        .build()
+}
+
+/// Get the id of the observer a method was called on
+fn observer_id(this: &JsValue, context: &mut Context) -> JsResult<u64> {
+    let id = this
+        .as_object()
+        .map(|object| object.get(js_string!("__observerId"), context))
+        .transpose()?
+        .and_then(|id| id.as_number());
+    id.map(|id| id as u64)
+        .ok_or_else(|| JsNativeError::typ().with_message("Illegal invocation").into())
+}
+
+/// Create the `PerformanceObserverEntryList` passed to observer callbacks
+fn entry_list_object(entries: Vec<PerformanceEntry>, context: &mut Context) -> JsValue {
+    let binding = || EntryListBinding { entries: entries.clone() };
+
+    let get_entries = NativeFunction::from_copy_closure_with_captures(
+        |_this, _args, binding: &EntryListBinding, context| Ok(entry_array(&binding.entries, context)),
+        binding(),
+    );
+
+    let get_entries_by_type = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &EntryListBinding, context| {
+            let entry_type = string_argument(args, 0, context)?.as_deref().and_then(EntryType::parse);
+            let entries: Vec<PerformanceEntry> = binding
+                .entries
+                .iter()
+                .filter(|entry| Some(entry.entry_type) == entry_type)
+                .cloned()
+                .collect();
+            Ok(entry_array(&entries, context))
+        },
+        binding(),
+    );
+
+    let get_entries_by_name = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &EntryListBinding, context| {
+            let name = string_argument(args, 0, context)?.unwrap_or_default();
+            let entry_type = string_argument(args, 1, context)?;
+            let entries: Vec<PerformanceEntry> = binding
+                .entries
+                .iter()
+                .filter(|entry| {
+                    entry.name == name
+                        && entry_type.as_deref().is_none_or(|t| entry.entry_type.as_str() == t)
+                })
+                .cloned()
+                .collect();
+            Ok(entry_array(&entries, context))
+        },
+        binding(),
+    );
+
+    ObjectInitializer::new(context)
+        .function(get_entries, js_string!("getEntries"), 0)
+        .function(get_entries_by_type, js_string!("getEntriesByType"), 1)
+        .function(get_entries_by_name, js_string!("getEntriesByName"), 1)
+        .build()
+        .into()
+}
+
+/// Convert entries to a JavaScript array of entry objects
+fn entry_array(entries: &[PerformanceEntry], context: &mut Context) -> JsValue {
+    let values: Vec<JsValue> = entries.iter().map(|entry| entry_object(entry, context)).collect();
+    JsArray::from_iter(values, context).into()
+}
+
+/// Convert an entry to a `PerformanceEntry` object
+fn entry_object(entry: &PerformanceEntry, context: &mut Context) -> JsValue {
+    let mut object = ObjectInitializer::new(context);
+    object
+        .property(js_string!("name"), js_string!(entry.name.clone()), Attribute::READONLY)
+        .property(js_string!("entryType"), js_string!(entry.entry_type.as_str()), Attribute::READONLY)
+        .property(js_string!("startTime"), entry.start_time, Attribute::READONLY)
+        .property(js_string!("duration"), entry.duration, Attribute::READONLY);
+    if let Some(resource) = &entry.resource {
+        object
+            .property(js_string!("initiatorType"), js_string!(resource.initiator_type.clone()), Attribute::READONLY)
+            .property(js_string!("fetchStart"), resource.fetch_start, Attribute::READONLY)
+            .property(js_string!("responseStart"), resource.response_start, Attribute::READONLY)
+            .property(js_string!("responseEnd"), resource.response_end, Attribute::READONLY)
+            .property(js_string!("transferSize"), resource.transfer_size as f64, Attribute::READONLY)
+            .property(js_string!("responseStatus"), resource.response_status, Attribute::READONLY);
+    }
+    object.build().into()
+}
+
+/// Get an optional string argument
+fn string_argument(args: &[JsValue], index: usize, context: &mut Context) -> JsResult<Option<String>> {
+    match args.get(index) {
+        Some(value) if !value.is_undefined() => Ok(Some(value.to_string(context)?.to_std_string_escaped())),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use boa_engine::Source;
+    use dom::clock::Clock;
+    use std::time::Duration;
+
+    fn fetch_timing(origin: Instant, start: u64, response: u64, end: u64) -> FetchTiming {
+        FetchTiming {
+            fetch_start: origin + Duration::from_millis(start),
+            response_start: origin + Duration::from_millis(response),
+            response_end: origin + Duration::from_millis(end),
+        }
+    }
+
+    fn eval_string(context: &mut Context, code: &str) -> String {
+        let value = context.eval(Source::from_bytes(code)).unwrap();
+        value.to_string(context).unwrap().to_std_string_escaped()
+    }
+
+    #[test]
+    fn test_timeline_records_resources_and_paints() {
+        let origin = Instant::now();
+        let timeline = PerformanceTimeline::with_time_origin(origin);
+        timeline.record_resource("https://example.com/a.css", "link", &fetch_timing(origin, 10, 30, 40), 512, 200);
+
+        let document = html_parser::parse_html(b"<html><body><div></div></body></html>".to_vec()).unwrap().0;
+        let empty = layout::LayoutEngine::new_empty().layout_document(&document);
+        timeline.record_paint_at(&empty, origin + Duration::from_millis(50));
+        assert_eq!(timeline.first_paint(), Some(50.0));
+        assert_eq!(timeline.first_contentful_paint(), None);
+
+        let document = html_parser::parse_html(b"<html><body><p>Hello</p></body></html>".to_vec()).unwrap().0;
+        let text = layout::LayoutEngine::new_empty().layout_document(&document);
+        timeline.record_paint_at(&text, origin + Duration::from_millis(70));
+        timeline.record_paint_at(&text, origin + Duration::from_millis(90));
+        assert_eq!(timeline.first_paint(), Some(50.0));
+        assert_eq!(timeline.first_contentful_paint(), Some(70.0));
+
+        let resources = timeline.entries_by_type(EntryType::Resource);
+        assert_eq!(resources.len(), 1);
+        assert_eq!(resources[0].start_time, 10.0);
+        assert_eq!(resources[0].duration, 30.0);
+        assert_eq!(resources[0].resource.as_ref().unwrap().response_start, 30.0);
+        assert_eq!(timeline.entries().len(), 3);
+
+        timeline.set_resource_timing_buffer_size(1);
+        timeline.record_resource("https://example.com/b.css", "link", &fetch_timing(origin, 60, 65, 80), 64, 200);
+        assert_eq!(timeline.entries_by_type(EntryType::Resource).len(), 1);
+        timeline.clear_resource_timings();
+        assert!(timeline.entries_by_type(EntryType::Resource).is_empty());
+    }
+
+    #[test]
+    fn test_performance_entries_from_script() {
+        let context = &mut Context::default();
+        let clock = dom::clock::ManualClock::new();
+        let origin = clock.now();
+        let timeline = PerformanceTimeline::with_clock(Arc::new(clock.clone()), origin);
+        timeline.record_resource("https://example.com/app.js", "script", &fetch_timing(origin, 5, 15, 25), 2048, 200);
+        initialize_performance_bindings(context, timeline.clone()).unwrap();
+
+        let resource = eval_string(
+            context,
+            "const [r] = performance.getEntriesByType('resource'); [r.name, r.initiatorType, r.duration, r.transferSize].join(',')",
+        );
+        assert_eq!(resource, "https://example.com/app.js,script,20,2048");
+        assert_eq!(eval_string(context, "performance.getEntriesByType('paint').length"), "0");
+        assert_eq!(eval_string(context, "performance.now()"), "0");
+        clock.advance(Duration::from_millis(125));
+        assert_eq!(eval_string(context, "performance.now()"), "125");
+        assert_eq!(eval_string(context, "PerformanceObserver.supportedEntryTypes.join(',')"), "paint,resource");
+    }
+
+    #[test]
+    fn test_performance_observer_delivery() {
+        let context = &mut Context::default();
+        let origin = Instant::now();
+        let timeline = PerformanceTimeline::with_time_origin(origin);
+        timeline.record_resource("https://example.com/early.css", "link", &fetch_timing(origin, 1, 2, 3), 10, 200);
+        let observers = initialize_performance_bindings(context, timeline.clone()).unwrap();
+
+        let code = r#"
+            globalThis.log = [];
+            new PerformanceObserver((list, observer) => {
+                for (const entry of list.getEntries()) log.push("buffered:" + entry.name);
+            }).observe({ type: "resource", buffered: true });
+            globalThis.paints = new PerformanceObserver((list) => {
+                for (const entry of list.getEntriesByType("paint")) log.push(entry.name);
+            });
+            paints.observe({ entryTypes: ["paint", "bogus"] });
+        "#;
+        context.eval(Source::from_bytes(code)).unwrap();
+        assert!(observers.has_pending());
+        assert_eq!(observers.deliver(context), 1);
+
+        let document = html_parser::parse_html(b"<html><body>Hi</body></html>".to_vec()).unwrap().0;
+        let layout = layout::LayoutEngine::new_empty().layout_document(&document);
+        timeline.record_paint_at(&layout, origin + Duration::from_millis(20));
+        timeline.record_resource("https://example.com/late.css", "link", &fetch_timing(origin, 21, 22, 23), 10, 200);
+        // Entries are only delivered when the engine spins the event loop
+        assert_eq!(eval_string(context, "log.join(',')"), "buffered:https://example.com/early.css");
+        assert_eq!(observers.deliver(context), 2);
+        assert_eq!(
+            eval_string(context, "log.join(',')"),
+            "buffered:https://example.com/early.css,buffered:https://example.com/late.css,first-paint,first-contentful-paint"
+        );
+
+        eval_string(context, "paints.disconnect()");
+        timeline.record_resource("https://example.com/last.css", "link", &fetch_timing(origin, 30, 31, 32), 10, 200);
+        assert_eq!(eval_string(context, "paints.takeRecords().length"), "0");
+        assert_eq!(observers.deliver(context), 1);
+        assert!(!observers.has_pending());
+    }
+}
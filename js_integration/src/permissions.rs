@@ -0,0 +1,356 @@
+//! # Permissions
+//!
+//! This module keeps what each origin may do, for the APIs that need the
+//! user's consent, and exposes `navigator.permissions.query()`. An origin's
+//! permission is granted, denied, or not decided yet, in which case the
+//! first request asks the user through the shell's prompt.
+//!
+//! ## Design Principles
+//!
+//! 1. **One Model for Every API**: Notifications, geolocation and the
+//!    clipboard ask through the same store, so a site's settings live in
+//!    one place and the shell shows one kind of prompt.
+//! 2. **Persistent and Shared**: A store is shared by cloning and, when
+//!    opened from a profile directory, saved on every change, so decisions
+//!    survive restarts and apply to every tab.
+//! 3. **Dismissing Decides Nothing**: A prompt the user dismisses leaves
+//!    the permission undecided, so the page may ask again later.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use boa_engine::{
+    object::{builtins::JsPromise, ObjectInitializer},
+    property::Attribute,
+    js_string, Context, JsNativeError, JsResult, JsValue, NativeFunction,
+};
+use boa_gc::{Finalize, Trace};
+use serde::{Deserialize, Serialize};
+
+use crate::clipboard::navigator_object;
+
+/// Name of the permissions file in a profile directory
+pub const PERMISSIONS_FILE: &str = "permissions.json";
+
+/// A powerful feature pages need permission for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PermissionName {
+    Notifications,
+    Geolocation,
+    ClipboardRead,
+    ClipboardWrite,
+    /// Filling saved passwords into the origin's login forms; asked by
+    /// the shell and not visible to `query()`
+    Autofill,
+}
+
+impl PermissionName {
+    /// Get the name scripts query the permission by
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PermissionName::Notifications => "notifications",
+            PermissionName::Geolocation => "geolocation",
+            PermissionName::ClipboardRead => "clipboard-read",
+            PermissionName::ClipboardWrite => "clipboard-write",
+            PermissionName::Autofill => "autofill",
+        }
+    }
+
+    /// Parse the name of a permission, as given to `query()`
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "notifications" => Some(PermissionName::Notifications),
+            "geolocation" => Some(PermissionName::Geolocation),
+            "clipboard-read" => Some(PermissionName::ClipboardRead),
+            "clipboard-write" => Some(PermissionName::ClipboardWrite),
+            _ => None,
+        }
+    }
+
+    /// Describe what the permission allows, for prompts
+    pub fn description(&self) -> &'static str {
+        match self {
+            PermissionName::Notifications => "show notifications",
+            PermissionName::Geolocation => "know your location",
+            PermissionName::ClipboardRead => "read the clipboard",
+            PermissionName::ClipboardWrite => "write to the clipboard",
+            PermissionName::Autofill => "fill in your saved passwords",
+        }
+    }
+}
+
+impl fmt::Display for PermissionName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Whether an origin may use a feature
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PermissionState {
+    Granted,
+    Denied,
+    /// Not decided yet; the next request asks the user
+    #[default]
+    Prompt,
+}
+
+impl PermissionState {
+    /// Get the `state` of a `PermissionStatus`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PermissionState::Granted => "granted",
+            PermissionState::Denied => "denied",
+            PermissionState::Prompt => "prompt",
+        }
+    }
+}
+
+impl fmt::Display for PermissionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Callback asking the user whether an origin may use a feature
+///
+/// Returns `None` when the user dismisses the prompt.
+pub type PermissionPrompt = Arc<dyn Fn(&str, PermissionName) -> Option<bool> + Send + Sync>;
+
+#[derive(Debug, Default)]
+struct PermissionsState {
+    /// Decisions by origin; undecided permissions are not kept
+    decisions: BTreeMap<String, BTreeMap<PermissionName, PermissionState>>,
+    /// File the decisions are saved to, if the store is persistent
+    path: Option<PathBuf>,
+}
+
+/// Permission decisions per origin, and the prompt that makes them
+///
+/// Cloning shares the decisions.
+#[derive(Clone, Default)]
+pub struct Permissions {
+    state: Arc<Mutex<PermissionsState>>,
+    prompt: Option<PermissionPrompt>,
+}
+
+impl Permissions {
+    /// Create an empty store that is not saved anywhere and dismisses
+    /// every prompt
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open the store saved in `dir`, creating an empty one if there is none
+    /// yet
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let path = dir.as_ref().join(PERMISSIONS_FILE);
+        let decisions = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e),
+        };
+        let state = PermissionsState { decisions, path: Some(path) };
+        Ok(Permissions { state: Arc::new(Mutex::new(state)), prompt: None })
+    }
+
+    /// Get a handle on the same decisions that asks `prompt` for new ones
+    pub fn with_prompt(&self, prompt: PermissionPrompt) -> Self {
+        Permissions { state: Arc::clone(&self.state), prompt: Some(prompt) }
+    }
+
+    /// Get whether an origin may use a feature, without asking
+    pub fn state(&self, origin: &str, name: PermissionName) -> PermissionState {
+        self.lock()
+            .decisions
+            .get(origin)
+            .and_then(|decisions| decisions.get(&name).copied())
+            .unwrap_or_default()
+    }
+
+    /// Get whether an origin may use a feature, asking the user if it has
+    /// not been decided yet and remembering their answer
+    pub fn request(&self, origin: &str, name: PermissionName) -> PermissionState {
+        let state = self.state(origin, name);
+        if state != PermissionState::Prompt {
+            return state;
+        }
+        // Ask without holding the lock; the prompt may block on the user
+        let answer = self.prompt.as_ref().and_then(|prompt| prompt(origin, name));
+        let state = match answer {
+            Some(true) => PermissionState::Granted,
+            Some(false) => PermissionState::Denied,
+            None => return PermissionState::Prompt,
+        };
+        if let Err(e) = self.set(origin, name, state) {
+            println!("❌ Failed to save permissions: {}", e);
+        }
+        state
+    }
+
+    /// Record a decision, e.g. from the shell's site settings;
+    /// `PermissionState::Prompt` forgets it
+    pub fn set(&self, origin: &str, name: PermissionName, state: PermissionState) -> io::Result<()> {
+        let mut guard = self.lock();
+        let decisions = guard.decisions.entry(origin.to_string()).or_default();
+        if state == PermissionState::Prompt {
+            decisions.remove(&name);
+        } else {
+            decisions.insert(name, state);
+        }
+        if decisions.is_empty() {
+            guard.decisions.remove(origin);
+        }
+        guard.save()
+    }
+
+    /// Forget every decision about an origin
+    pub fn reset(&self, origin: &str) -> io::Result<()> {
+        let mut guard = self.lock();
+        guard.decisions.remove(origin);
+        guard.save()
+    }
+
+    /// Get every decision, by origin and then permission
+    pub fn decisions(&self) -> Vec<(String, PermissionName, PermissionState)> {
+        self.lock()
+            .decisions
+            .iter()
+            .flat_map(|(origin, decisions)| decisions.iter().map(move |(name, state)| (origin.clone(), *name, *state)))
+            .collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, PermissionsState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl fmt::Debug for Permissions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Permissions")
+            .field("decisions", &self.lock().decisions)
+            .field("has_prompt", &self.prompt.is_some())
+            .finish()
+    }
+}
+
+impl PermissionsState {
+    fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let contents = serde_json::to_string_pretty(&self.decisions).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let temporary = path.with_extension("json.tmp");
+        fs::write(&temporary, contents)?;
+        fs::rename(&temporary, path)
+    }
+}
+
+/// Captures of `navigator.permissions`
+#[derive(Clone, Trace, Finalize)]
+struct PermissionsBinding {
+    #[unsafe_ignore_trace]
+    permissions: Permissions,
+    #[unsafe_ignore_trace]
+    origin: String,
+}
+
+/// Install `navigator.permissions` for a document from `origin`
+///
+/// `query({ name })` resolves to a `PermissionStatus` with the `name` and
+/// `state` of the permission; it never prompts. Creates `navigator` if the
+/// context does not have one yet.
+pub fn initialize_permissions_bindings(context: &mut Context, permissions: Permissions, origin: &str) -> JsResult<()> {
+    let binding = PermissionsBinding { permissions, origin: origin.to_string() };
+    let query = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &PermissionsBinding, context| {
+            let descriptor = args.first().and_then(JsValue::as_object).cloned();
+            let name = match descriptor {
+                Some(descriptor) => descriptor.get(js_string!("name"), context)?.to_string(context)?.to_std_string_escaped(),
+                None => {
+                    let error = JsNativeError::typ().with_message("Failed to execute 'query' on 'Permissions': parameter 1 is not an object");
+                    return Ok(JsPromise::reject(error, context).into());
+                }
+            };
+            let Some(permission) = PermissionName::parse(&name) else {
+                let error = JsNativeError::typ().with_message(format!("Failed to execute 'query' on 'Permissions': '{}' is not a valid permission name", name));
+                return Ok(JsPromise::reject(error, context).into());
+            };
+            let state = binding.permissions.state(&binding.origin, permission);
+            let status = ObjectInitializer::new(context)
+                .property(js_string!("name"), js_string!(permission.as_str()), Attribute::READONLY | Attribute::ENUMERABLE)
+                .property(js_string!("state"), js_string!(state.as_str()), Attribute::READONLY | Attribute::ENUMERABLE)
+                .build();
+            Ok(JsPromise::resolve(status, context).into())
+        },
+        binding,
+    );
+    let permissions_object = ObjectInitializer::new(context)
+        .function(query, js_string!("query"), 1)
+        .build();
+    let navigator = navigator_object(context)?;
+    navigator.set(js_string!("permissions"), permissions_object, false, context)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use boa_engine::Source;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_permissions_are_asked_once_and_saved() {
+        let dir = std::env::temp_dir().join(format!("permissions-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let prompts = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&prompts);
+        let store = Permissions::open(&dir).unwrap();
+        let permissions = store.with_prompt(Arc::new(move |origin, name| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            match name {
+                PermissionName::Geolocation => None,
+                _ => Some(origin == "https://example.com"),
+            }
+        }));
+
+        assert_eq!(permissions.request("https://example.com", PermissionName::Notifications), PermissionState::Granted);
+        assert_eq!(permissions.request("https://example.com", PermissionName::Notifications), PermissionState::Granted);
+        assert_eq!(permissions.request("https://ads.example", PermissionName::Notifications), PermissionState::Denied);
+        // A dismissed prompt decides nothing
+        assert_eq!(permissions.request("https://example.com", PermissionName::Geolocation), PermissionState::Prompt);
+        assert_eq!(permissions.request("https://example.com", PermissionName::Geolocation), PermissionState::Prompt);
+        assert_eq!(prompts.load(Ordering::SeqCst), 4);
+        // Without a prompt nothing is asked
+        assert_eq!(store.request("https://other.example", PermissionName::Notifications), PermissionState::Prompt);
+
+        let reopened = Permissions::open(&dir).unwrap();
+        assert_eq!(reopened.state("https://example.com", PermissionName::Notifications), PermissionState::Granted);
+        assert_eq!(reopened.decisions().len(), 2);
+        reopened.reset("https://ads.example").unwrap();
+        assert_eq!(Permissions::open(&dir).unwrap().decisions().len(), 1);
+
+        let context = &mut Context::default();
+        initialize_permissions_bindings(context, permissions, "https://example.com").unwrap();
+        let code = r#"
+            globalThis.log = [];
+            navigator.permissions.query({ name: "notifications" }).then((status) => log.push(status.name + ":" + status.state));
+            navigator.permissions.query({ name: "geolocation" }).then((status) => log.push(status.state));
+            navigator.permissions.query({ name: "midi" }).catch((error) => log.push(error.name));
+        "#;
+        context.eval(Source::from_bytes(code)).unwrap();
+        context.run_jobs();
+        let log = context.eval(Source::from_bytes("log.join(',')")).unwrap();
+        assert_eq!(log.to_string(context).unwrap().to_std_string_escaped(), "notifications:granted,prompt,TypeError");
+        assert_eq!(prompts.load(Ordering::SeqCst), 4);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
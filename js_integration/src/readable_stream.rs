@@ -0,0 +1,545 @@
+//! # ReadableStream Implementation
+//!
+//! This module provides a minimal byte `ReadableStream` so scripts can
+//! consume response bodies incrementally with a `getReader().read()` loop
+//! instead of waiting for the whole payload to be buffered.
+//!
+//! ## Design Principles
+//!
+//! 1. **Thread-Safe Queue**: The chunk queue lives behind an `Arc<Mutex<..>>`
+//!    so the network pump can fill it from a tokio task.
+//! 2. **Backpressure**: The pump stops pulling from the network while the
+//!    queued bytes exceed the high-water mark, so multi-megabyte payloads
+//!    are never held in memory at once.
+//! 3. **Promise Based Reads**: `read()` returns a native Promise. Reads that
+//!    arrive before data are parked and fulfilled on the JS thread when the
+//!    pump queues a networking task through the event loop.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use boa_engine::{
+    builtins::promise::ResolvingFunctions,
+    object::{builtins::{JsPromise, JsUint8Array}, ObjectInitializer},
+    property::Attribute,
+    js_string, Context, JsData, JsNativeError, JsObject, JsValue, NativeFunction,
+};
+use boa_gc::{Finalize, Trace};
+use tokio::sync::Notify;
+
+use crate::event_loop::{EventLoopHandle, TaskAction};
+
+/// Default number of queued bytes above which the pump stops reading
+pub const DEFAULT_HIGH_WATER_MARK: usize = 64 * 1024;
+
+static NEXT_STREAM_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    /// Reads waiting for data, keyed by stream id
+    ///
+    /// Resolving functions are JS values and must stay on the JS thread,
+    /// while the stream itself is shared with the network pump.
+    static PENDING_READS: RefCell<HashMap<u64, VecDeque<ResolvingFunctions>>> =
+        RefCell::new(HashMap::new());
+}
+
+/// Outcome of a single read from the stream queue
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReadOutcome {
+    /// A chunk of bytes
+    Chunk(Vec<u8>),
+    /// The stream is closed and drained
+    Done,
+    /// No data yet; the stream is still open
+    Pending,
+    /// The stream errored
+    Errored(String),
+}
+
+#[derive(Debug, Default)]
+struct StreamState {
+    queue: VecDeque<Vec<u8>>,
+    queued_bytes: usize,
+    closed: bool,
+    cancelled: bool,
+    locked: bool,
+    error: Option<String>,
+}
+
+#[derive(Debug)]
+struct StreamShared {
+    state: Mutex<StreamState>,
+    space_available: Notify,
+    high_water_mark: usize,
+}
+
+/// A byte stream shared between a producer and a JavaScript reader
+#[derive(Debug, Clone)]
+pub struct ReadableStream {
+    id: u64,
+    shared: Arc<StreamShared>,
+}
+
+impl ReadableStream {
+    /// Create an open, empty stream
+    pub fn new(high_water_mark: usize) -> Self {
+        Self {
+            id: NEXT_STREAM_ID.fetch_add(1, Ordering::Relaxed),
+            shared: Arc::new(StreamShared {
+                state: Mutex::new(StreamState::default()),
+                space_available: Notify::new(),
+                high_water_mark,
+            }),
+        }
+    }
+
+    /// Create a closed stream holding the given bytes
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        let stream = Self::new(DEFAULT_HIGH_WATER_MARK);
+        if !bytes.is_empty() {
+            stream.enqueue(bytes);
+        }
+        stream.close();
+        stream
+    }
+
+    /// Unique identifier of the stream
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Queue a chunk, returning false if the stream no longer accepts data
+    pub fn enqueue(&self, chunk: Vec<u8>) -> bool {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.closed || state.error.is_some() {
+            return false;
+        }
+        state.queued_bytes += chunk.len();
+        state.queue.push_back(chunk);
+        true
+    }
+
+    /// Close the stream; queued chunks can still be read
+    pub fn close(&self) {
+        self.shared.state.lock().unwrap().closed = true;
+        self.shared.space_available.notify_waiters();
+    }
+
+    /// Error the stream, discarding queued chunks
+    pub fn error(&self, message: String) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.queue.clear();
+        state.queued_bytes = 0;
+        state.error = Some(message);
+        drop(state);
+        self.shared.space_available.notify_waiters();
+    }
+
+    /// Cancel the stream on behalf of the consumer
+    ///
+    /// Queued chunks are dropped and the producer stops pulling data.
+    pub fn cancel(&self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.queue.clear();
+        state.queued_bytes = 0;
+        state.closed = true;
+        state.cancelled = true;
+        drop(state);
+        self.shared.space_available.notify_waiters();
+    }
+
+    /// Whether the consumer cancelled the stream
+    pub fn is_cancelled(&self) -> bool {
+        self.shared.state.lock().unwrap().cancelled
+    }
+
+    /// Whether a reader currently holds the lock
+    pub fn is_locked(&self) -> bool {
+        self.shared.state.lock().unwrap().locked
+    }
+
+    /// Number of bytes queued but not yet read
+    pub fn queued_bytes(&self) -> usize {
+        self.shared.state.lock().unwrap().queued_bytes
+    }
+
+    /// Bytes the producer may queue before reaching the high-water mark
+    pub fn desired_size(&self) -> i64 {
+        self.shared.high_water_mark as i64 - self.queued_bytes() as i64
+    }
+
+    /// Take the next chunk off the queue
+    pub fn try_read(&self) -> ReadOutcome {
+        let mut state = self.shared.state.lock().unwrap();
+        if let Some(error) = &state.error {
+            return ReadOutcome::Errored(error.clone());
+        }
+
+        match state.queue.pop_front() {
+            Some(chunk) => {
+                state.queued_bytes -= chunk.len();
+                drop(state);
+                self.shared.space_available.notify_waiters();
+                ReadOutcome::Chunk(chunk)
+            }
+            None if state.closed => ReadOutcome::Done,
+            None => ReadOutcome::Pending,
+        }
+    }
+
+    /// Wait until the queue drops below the high-water mark
+    ///
+    /// Returns immediately once the stream is closed, errored or cancelled.
+    pub async fn wait_for_space(&self) {
+        loop {
+            // Register before checking so a concurrent read cannot be missed
+            let notified = self.shared.space_available.notified();
+            {
+                let state = self.shared.state.lock().unwrap();
+                if state.queued_bytes < self.shared.high_water_mark
+                    || state.closed
+                    || state.error.is_some()
+                {
+                    return;
+                }
+            }
+            notified.await;
+        }
+    }
+
+    fn set_locked(&self, locked: bool) {
+        self.shared.state.lock().unwrap().locked = locked;
+    }
+}
+
+/// Pump a streaming network response into a stream
+///
+/// Chunks are pulled from the network only while the stream is below its
+/// high-water mark. When an event loop handle is given, a networking task is
+/// queued after every chunk to fulfil reads that are waiting for data.
+/// Returns the number of body bytes transferred.
+pub async fn pump_response(
+    mut response: networking::StreamingResponse,
+    stream: ReadableStream,
+    event_loop: Option<EventLoopHandle>,
+) -> networking::NetworkResult<usize> {
+    let mut transferred = 0;
+    loop {
+        stream.wait_for_space().await;
+        if stream.is_cancelled() {
+            // Dropping the response releases the connection
+            return Ok(transferred);
+        }
+
+        match response.next_chunk().await {
+            Ok(Some(chunk)) => {
+                transferred += chunk.len();
+                stream.enqueue(chunk);
+                notify_readers(&stream, &event_loop);
+            }
+            Ok(None) => {
+                stream.close();
+                notify_readers(&stream, &event_loop);
+                return Ok(transferred);
+            }
+            Err(e) => {
+                stream.error(e.to_string());
+                notify_readers(&stream, &event_loop);
+                return Err(e);
+            }
+        }
+    }
+}
+
+fn notify_readers(stream: &ReadableStream, event_loop: &Option<EventLoopHandle>) {
+    if let Some(handle) = event_loop {
+        let stream = stream.clone();
+        handle.queue_network_completion(TaskAction::Native(Box::new(move |context| {
+            fulfill_pending_reads(&stream, context);
+            Ok(())
+        })));
+    }
+}
+
+/// Fulfil reads that were waiting for data on this stream
+///
+/// Must run on the JS thread. Returns the number of reads settled.
+pub fn fulfill_pending_reads(stream: &ReadableStream, context: &mut Context) -> usize {
+    let mut settled = 0;
+    loop {
+        let has_pending = PENDING_READS.with(|pending| {
+            pending
+                .borrow()
+                .get(&stream.id)
+                .is_some_and(|reads| !reads.is_empty())
+        });
+        if !has_pending {
+            break;
+        }
+
+        let outcome = stream.try_read();
+        if outcome == ReadOutcome::Pending {
+            break;
+        }
+
+        let resolvers = PENDING_READS.with(|pending| {
+            pending
+                .borrow_mut()
+                .get_mut(&stream.id)
+                .and_then(|reads| reads.pop_front())
+        });
+        if let Some(resolvers) = resolvers {
+            settle_read(&resolvers, outcome, context);
+            settled += 1;
+        }
+    }
+
+    PENDING_READS.with(|pending| {
+        let mut pending = pending.borrow_mut();
+        if pending.get(&stream.id).is_some_and(|reads| reads.is_empty()) {
+            pending.remove(&stream.id);
+        }
+    });
+    settled
+}
+
+fn settle_read(resolvers: &ResolvingFunctions, outcome: ReadOutcome, context: &mut Context) {
+    let result = match outcome {
+        ReadOutcome::Chunk(chunk) => read_result(Some(chunk), context)
+            .map(|value| resolvers.resolve.call(&JsValue::undefined(), &[value], context)),
+        ReadOutcome::Done => read_result(None, context)
+            .map(|value| resolvers.resolve.call(&JsValue::undefined(), &[value], context)),
+        ReadOutcome::Errored(message) => {
+            let error = JsNativeError::typ().with_message(message).to_opaque(context);
+            Ok(resolvers.reject.call(&JsValue::undefined(), &[error.into()], context))
+        }
+        ReadOutcome::Pending => return,
+    };
+
+    if let Err(e) | Ok(Err(e)) = result {
+        eprintln!("❌ Failed to settle stream read: {}", e);
+    }
+}
+
+/// Build a `{ value, done }` read result
+fn read_result(chunk: Option<Vec<u8>>, context: &mut Context) -> boa_engine::JsResult<JsValue> {
+    let (value, done): (JsValue, bool) = match chunk {
+        Some(chunk) => (JsUint8Array::from_iter(chunk, context)?.into(), false),
+        None => (JsValue::undefined(), true),
+    };
+
+    Ok(ObjectInitializer::new(context)
+        .property(js_string!("value"), value, Attribute::all())
+        .property(js_string!("done"), done, Attribute::all())
+        .build()
+        .into())
+}
+
+/// Native data shared by stream and reader objects
+#[derive(Trace, Finalize, JsData)]
+struct StreamData {
+    #[unsafe_ignore_trace]
+    stream: ReadableStream,
+}
+
+fn stream_of(this: &JsValue) -> boa_engine::JsResult<ReadableStream> {
+    this.as_object()
+        .and_then(|object| object.downcast_ref::<StreamData>().map(|data| data.stream.clone()))
+        .ok_or_else(|| JsNativeError::typ().with_message("Illegal invocation").into())
+}
+
+/// Create the JavaScript `ReadableStream` object for a stream
+pub fn create_stream_object(stream: ReadableStream, context: &mut Context) -> JsObject {
+    ObjectInitializer::with_native_data(StreamData { stream }, context)
+        .function(
+            NativeFunction::from_fn_ptr(stream_get_reader),
+            js_string!("getReader"),
+            0,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(stream_cancel),
+            js_string!("cancel"),
+            1,
+        )
+        .build()
+}
+
+/// ReadableStream.getReader() implementation
+fn stream_get_reader(
+    this: &JsValue,
+    _args: &[JsValue],
+    context: &mut Context,
+) -> boa_engine::JsResult<JsValue> {
+    let stream = stream_of(this)?;
+    if stream.is_locked() {
+        return Err(JsNativeError::typ()
+            .with_message("ReadableStream is already locked to a reader")
+            .into());
+    }
+    stream.set_locked(true);
+
+    let reader = ObjectInitializer::with_native_data(StreamData { stream }, context)
+        .function(
+            NativeFunction::from_fn_ptr(reader_read),
+            js_string!("read"),
+            0,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(reader_release_lock),
+            js_string!("releaseLock"),
+            0,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(stream_cancel),
+            js_string!("cancel"),
+            1,
+        )
+        .build();
+
+    Ok(reader.into())
+}
+
+/// ReadableStream.cancel() / reader.cancel() implementation
+fn stream_cancel(
+    this: &JsValue,
+    _args: &[JsValue],
+    context: &mut Context,
+) -> boa_engine::JsResult<JsValue> {
+    let stream = stream_of(this)?;
+    stream.cancel();
+    fulfill_pending_reads(&stream, context);
+    Ok(JsPromise::resolve(JsValue::undefined(), context).into())
+}
+
+/// reader.read() implementation
+fn reader_read(
+    this: &JsValue,
+    _args: &[JsValue],
+    context: &mut Context,
+) -> boa_engine::JsResult<JsValue> {
+    let stream = stream_of(this)?;
+    if !stream.is_locked() {
+        return Err(JsNativeError::typ()
+            .with_message("Reader has been released")
+            .into());
+    }
+
+    match stream.try_read() {
+        ReadOutcome::Chunk(chunk) => {
+            let result = read_result(Some(chunk), context)?;
+            Ok(JsPromise::resolve(result, context).into())
+        }
+        ReadOutcome::Done => {
+            let result = read_result(None, context)?;
+            Ok(JsPromise::resolve(result, context).into())
+        }
+        ReadOutcome::Errored(message) => {
+            Ok(JsPromise::reject(JsNativeError::typ().with_message(message), context).into())
+        }
+        ReadOutcome::Pending => {
+            let (promise, resolvers) = JsPromise::new_pending(context);
+            PENDING_READS.with(|pending| {
+                pending
+                    .borrow_mut()
+                    .entry(stream.id)
+                    .or_default()
+                    .push_back(resolvers);
+            });
+            Ok(promise.into())
+        }
+    }
+}
+
+/// reader.releaseLock() implementation
+fn reader_release_lock(
+    this: &JsValue,
+    _args: &[JsValue],
+    _context: &mut Context,
+) -> boa_engine::JsResult<JsValue> {
+    stream_of(this)?.set_locked(false);
+    Ok(JsValue::undefined())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use boa_engine::Source;
+
+    #[test]
+    fn test_backpressure_and_queue() {
+        let stream = ReadableStream::new(8);
+        assert_eq!(stream.desired_size(), 8);
+
+        stream.enqueue(vec![0; 6]);
+        stream.enqueue(vec![1; 6]);
+        assert_eq!(stream.desired_size(), -4);
+
+        assert_eq!(stream.try_read(), ReadOutcome::Chunk(vec![0; 6]));
+        assert_eq!(stream.queued_bytes(), 6);
+        stream.close();
+        assert!(!stream.enqueue(vec![2]));
+        assert_eq!(stream.try_read(), ReadOutcome::Chunk(vec![1; 6]));
+        assert_eq!(stream.try_read(), ReadOutcome::Done);
+    }
+
+    #[test]
+    fn test_reader_loop_in_javascript() {
+        let context = &mut Context::default();
+        let stream = ReadableStream::new(DEFAULT_HIGH_WATER_MARK);
+        stream.enqueue(b"hello ".to_vec());
+
+        let object = create_stream_object(stream.clone(), context);
+        context
+            .register_global_property(js_string!("body"), object, Attribute::all())
+            .unwrap();
+
+        let code = r#"
+            var received = "";
+            var finished = false;
+            (async () => {
+                const reader = body.getReader();
+                while (true) {
+                    const { value, done } = await reader.read();
+                    if (done) break;
+                    received += String.fromCharCode(...value);
+                }
+                finished = true;
+            })();
+        "#;
+        context.eval(Source::from_bytes(code)).unwrap();
+        context.run_jobs();
+
+        // The second read is parked until more data arrives
+        let finished = context.eval(Source::from_bytes("finished")).unwrap();
+        assert!(!finished.to_boolean());
+
+        stream.enqueue(b"world".to_vec());
+        stream.close();
+        assert!(fulfill_pending_reads(&stream, context) >= 1);
+        context.run_jobs();
+        fulfill_pending_reads(&stream, context);
+        context.run_jobs();
+
+        let received = context.eval(Source::from_bytes("received")).unwrap();
+        assert_eq!(received.to_string(context).unwrap().to_std_string_escaped(), "hello world");
+        let finished = context.eval(Source::from_bytes("finished")).unwrap();
+        assert!(finished.to_boolean());
+    }
+
+    #[test]
+    fn test_reader_lock() {
+        let context = &mut Context::default();
+        let object = create_stream_object(ReadableStream::from_bytes(b"x".to_vec()), context);
+        context
+            .register_global_property(js_string!("body"), object, Attribute::all())
+            .unwrap();
+
+        let result = context.eval(Source::from_bytes("const first = body.getReader(); body.getReader();"));
+        assert!(result.is_err());
+
+        // Releasing the lock lets another reader take over
+        let result = context.eval(Source::from_bytes("first.releaseLock(); body.getReader(); true"));
+        assert!(result.unwrap().to_boolean());
+    }
+}
@@ -0,0 +1,319 @@
+//! # Console REPL
+//!
+//! This module evaluates what the user types into the devtools console in
+//! a page's script context, and turns the results into previews that can
+//! be shown on one line or expanded into a tree.
+//!
+//! ## Design Principles
+//!
+//! 1. **The Page's Context**: Input runs in the same `JsEngine` as the
+//!    page's scripts, so it sees and changes the page's globals.
+//! 2. **Conveniences as Globals**: `$0` (the selected node) and `$_` (the
+//!    last result) are set on the global object before every evaluation,
+//!    like the console of other browsers.
+//! 3. **Previews Are Snapshots**: A preview copies what it shows when it is
+//!    made, down to a depth, so printing it later does not run getters.
+//! 4. **One Stream**: The console messages an evaluation caused are
+//!    returned with its result, so they are shown inline, in order.
+
+use std::fmt;
+use std::rc::Rc;
+use boa_engine::{js_string, JsObject, JsValue};
+use dom::{Node, NodeType};
+use crate::console::ConsoleMessage;
+use crate::{JsEngine, JsIntegrationError};
+
+/// Levels of nested objects a result preview captures
+pub const PREVIEW_DEPTH: usize = 2;
+
+/// Levels `expand_last` captures
+pub const EXPANDED_DEPTH: usize = 4;
+
+/// Properties or items a preview keeps of one object
+pub const PREVIEW_PROPERTIES: usize = 20;
+
+/// A snapshot of a JavaScript value for display
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValuePreview {
+    /// `undefined`, `null`, booleans, numbers and quoted strings
+    Primitive(String),
+    /// A function and its name
+    Function(String),
+    /// A DOM node, described like its start tag
+    Node(String),
+    /// An error object, as `Name: message`
+    Error(String),
+    Array {
+        length: usize,
+        items: Vec<ValuePreview>,
+    },
+    Object {
+        properties: Vec<(String, ValuePreview)>,
+        /// Whether properties past `PREVIEW_PROPERTIES` were left out
+        truncated: bool,
+    },
+    /// An object past the preview depth
+    Collapsed(String),
+}
+
+impl ValuePreview {
+    /// Get the preview of a value, capturing `depth` levels of nesting
+    pub fn of(engine: &mut JsEngine, value: &JsValue, depth: usize) -> Self {
+        let Some(object) = value.as_object() else {
+            return ValuePreview::Primitive(match value {
+                JsValue::String(text) => format!("{:?}", text.to_std_string_escaped()),
+                other => other.display().to_string(),
+            });
+        };
+        if let Some(node) = engine.resolve_node(object) {
+            return ValuePreview::Node(describe_node(&node));
+        }
+        if object.is_callable() {
+            let name = get(engine, object, "name").map(|name| name.display().to_string()).unwrap_or_default();
+            return ValuePreview::Function(name.trim_matches('"').to_string());
+        }
+        if let Some(error) = describe_error(engine, object) {
+            return ValuePreview::Error(error);
+        }
+        let is_array = object.is_array();
+        if depth == 0 {
+            return ValuePreview::Collapsed(if is_array { format!("Array({})", array_length(engine, object)) } else { "{…}".to_string() });
+        }
+        if is_array {
+            let length = array_length(engine, object);
+            let items = (0..length.min(PREVIEW_PROPERTIES))
+                .map(|index| {
+                    let item = object.get(index as u32, &mut engine.context).unwrap_or_default();
+                    ValuePreview::of(engine, &item, depth - 1)
+                })
+                .collect();
+            return ValuePreview::Array { length, items };
+        }
+
+        let keys = object.own_property_keys(&mut engine.context).unwrap_or_default();
+        let truncated = keys.len() > PREVIEW_PROPERTIES;
+        let properties = keys
+            .into_iter()
+            .take(PREVIEW_PROPERTIES)
+            .map(|key| {
+                let value = object.get(key.clone(), &mut engine.context).unwrap_or_default();
+                (key.to_string(), ValuePreview::of(engine, &value, depth - 1))
+            })
+            .collect();
+        ValuePreview::Object { properties, truncated }
+    }
+
+    /// Render on one line; nested objects show as `{…}`
+    pub fn summary(&self) -> String {
+        match self {
+            ValuePreview::Array { length, items } => {
+                let mut parts: Vec<String> = items.iter().map(ValuePreview::short).collect();
+                if *length > items.len() {
+                    parts.push("…".to_string());
+                }
+                format!("Array({}) [{}]", length, parts.join(", "))
+            }
+            ValuePreview::Object { properties, truncated } => {
+                let mut parts: Vec<String> = properties.iter().map(|(key, value)| format!("{}: {}", key, value.short())).collect();
+                if *truncated {
+                    parts.push("…".to_string());
+                }
+                format!("{{{}}}", parts.join(", "))
+            }
+            other => other.short(),
+        }
+    }
+
+    /// Render as a value nested inside another's summary
+    fn short(&self) -> String {
+        match self {
+            ValuePreview::Primitive(text) | ValuePreview::Node(text) | ValuePreview::Error(text) | ValuePreview::Collapsed(text) => text.clone(),
+            ValuePreview::Function(name) => format!("ƒ {}()", name),
+            ValuePreview::Array { length, .. } => format!("Array({})", length),
+            ValuePreview::Object { .. } => "{…}".to_string(),
+        }
+    }
+
+    fn children(&self) -> Vec<(String, &ValuePreview)> {
+        match self {
+            ValuePreview::Array { items, .. } => items.iter().enumerate().map(|(index, item)| (index.to_string(), item)).collect(),
+            ValuePreview::Object { properties, .. } => properties.iter().map(|(key, value)| (key.clone(), value)).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Render as a tree, one property per line, down to the captured depth
+    pub fn expanded(&self) -> String {
+        let mut lines = vec![format!("▼ {}", self.summary())];
+        self.expand_into(&mut lines, 1);
+        lines.join("\n")
+    }
+
+    fn expand_into(&self, lines: &mut Vec<String>, indent: usize) {
+        for (key, child) in self.children() {
+            let pad = "  ".repeat(indent);
+            if child.children().is_empty() {
+                lines.push(format!("{}  {}: {}", pad, key, child.summary()));
+            } else {
+                lines.push(format!("{}▼ {}: {}", pad, key, child.summary()));
+                child.expand_into(lines, indent + 1);
+            }
+        }
+    }
+}
+
+impl fmt::Display for ValuePreview {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.summary())
+    }
+}
+
+fn get(engine: &mut JsEngine, object: &JsObject, name: &str) -> Option<JsValue> {
+    object.get(js_string!(name), &mut engine.context).ok().filter(|value| !value.is_undefined())
+}
+
+fn array_length(engine: &mut JsEngine, object: &JsObject) -> usize {
+    get(engine, object, "length").and_then(|length| length.as_number()).unwrap_or(0.0) as usize
+}
+
+/// Describe an object whose `name` ends in `Error` and that has a `message`
+fn describe_error(engine: &mut JsEngine, object: &JsObject) -> Option<String> {
+    let name = get(engine, object, "name")?.as_string()?.to_std_string_escaped();
+    let message = get(engine, object, "message")?.as_string()?.to_std_string_escaped();
+    name.ends_with("Error").then(|| format!("{}: {}", name, message))
+}
+
+/// Describe a node the way the elements panel shows it
+fn describe_node(node: &Node) -> String {
+    match &node.node_type {
+        NodeType::Text(_) => format!("#text {:?}", node.text_content().trim()),
+        NodeType::Element { .. } => {
+            let tag = node.tag_name().unwrap_or_default().to_ascii_lowercase();
+            let mut description = format!("<{}", tag);
+            for name in ["id", "class"] {
+                if let Some(value) = node.get_attribute(name) {
+                    description.push_str(&format!(" {}=\"{}\"", name, value));
+                }
+            }
+            description.push('>');
+            description
+        }
+        _ => "#document".to_string(),
+    }
+}
+
+/// What one evaluation printed and returned
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplOutput {
+    /// Console messages written while it ran, in order
+    pub messages: Vec<ConsoleMessage>,
+    /// The preview of the result, or the uncaught exception
+    pub result: Result<ValuePreview, String>,
+}
+
+impl fmt::Display for ReplOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.result {
+            Ok(preview) => write!(f, "← {}", preview),
+            Err(error) => write!(f, "❌ Uncaught {}", error),
+        }
+    }
+}
+
+/// The state of a console attached to a page
+#[derive(Debug, Default)]
+pub struct ConsoleRepl {
+    /// The node selected in the elements panel, `$0`
+    selected: Option<Rc<Node>>,
+    /// The result of the last evaluation that did not throw, `$_`
+    last_result: JsValue,
+}
+
+impl ConsoleRepl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Select the node `$0` refers to
+    pub fn select(&mut self, node: Option<Rc<Node>>) {
+        self.selected = node;
+    }
+
+    pub fn selected(&self) -> Option<&Rc<Node>> {
+        self.selected.as_ref()
+    }
+
+    /// Evaluate console input in the page's context
+    ///
+    /// Microtasks queued by the input run before returning, so promise
+    /// callbacks log alongside the result.
+    pub fn evaluate(&mut self, engine: &mut JsEngine, input: &str) -> ReplOutput {
+        let start = engine.console().messages().len();
+        let selected = match &self.selected {
+            Some(node) => JsValue::from(engine.wrap_node(node)),
+            None => JsValue::null(),
+        };
+        let global = engine.context.global_object();
+        let installed = global
+            .set(js_string!("$0"), selected, false, &mut engine.context)
+            .and_then(|_| global.set(js_string!("$_"), self.last_result.clone(), false, &mut engine.context));
+        if let Err(e) = installed {
+            println!("⚠️ Failed to set console conveniences: {}", e);
+        }
+
+        let result = engine.execute(input);
+        let _ = engine.process_microtasks();
+        let messages = engine.console().messages().into_iter().skip(start).collect();
+        let result = match result {
+            Ok(value) => {
+                self.last_result = value.clone();
+                Ok(ValuePreview::of(engine, &value, PREVIEW_DEPTH))
+            }
+            Err(JsIntegrationError::ExecutionError(message)) => Err(message),
+            Err(e) => Err(e.to_string()),
+        };
+        ReplOutput { messages, result }
+    }
+
+    /// Preview the last result deeper than evaluation did
+    pub fn expand_last(&self, engine: &mut JsEngine) -> ValuePreview {
+        ValuePreview::of(engine, &self.last_result, EXPANDED_DEPTH)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::{ConsoleLevel, ConsoleSink};
+    use dom::Document;
+
+    #[test]
+    fn test_repl_previews_and_conveniences() {
+        let mut engine = JsEngine::new();
+        engine.set_console_sink(ConsoleSink::new()).unwrap();
+        let mut repl = ConsoleRepl::new();
+
+        let output = repl.evaluate(&mut engine, "console.log('hi'); ({a: 1, b: {c: [1, 2]}, s: 'x', f: function go() {}})");
+        assert_eq!(output.messages.len(), 1);
+        assert_eq!(output.messages[0].level, ConsoleLevel::Log);
+        let preview = output.result.unwrap();
+        assert_eq!(preview.summary(), "{a: 1, b: {…}, s: \"x\", f: ƒ go()}");
+        assert_eq!(repl.expand_last(&mut engine).expanded(), "▼ {a: 1, b: {…}, s: \"x\", f: ƒ go()}\n    a: 1\n  ▼ b: {c: Array(2)}\n    ▼ c: Array(2) [1, 2]\n        0: 1\n        1: 2\n    s: \"x\"\n    f: ƒ go()");
+
+        // $_ is the last result; errors are shown but leave it alone
+        assert_eq!(repl.evaluate(&mut engine, "$_.a + 1").result, Ok(ValuePreview::Primitive("2".to_string())));
+        let thrown = repl.evaluate(&mut engine, "throw new TypeError('nope')");
+        assert!(thrown.result.unwrap_err().contains("nope"));
+        assert_eq!(repl.evaluate(&mut engine, "$_").result.unwrap().summary(), "2");
+        assert_eq!(repl.evaluate(&mut engine, "new RangeError('bad')").result, Ok(ValuePreview::Error("RangeError: bad".to_string())));
+
+        // $0 is the selected node
+        let doc = Document::new();
+        let heading = doc.create_element("h1");
+        heading.set_attribute("id", "title");
+        doc.root.append_child(&heading);
+        assert_eq!(repl.evaluate(&mut engine, "$0").result.unwrap().summary(), "null");
+        repl.select(Some(heading));
+        assert_eq!(repl.evaluate(&mut engine, "$0").result.unwrap().summary(), "<h1 id=\"title\">");
+    }
+}
@@ -0,0 +1,357 @@
+//! # ResizeObserver
+//!
+//! This module implements `ResizeObserver` on top of the layout engine's
+//! geometry observers: observing an element subscribes to its border box,
+//! and the changes layout reports are delivered to the callbacks when the
+//! engine spins the event loop.
+//!
+//! ## Design Principles
+//!
+//! 1. **One Source of Geometry**: Sizes come from `layout::GeometryObservers`,
+//!    the same subscriptions engine code uses, so scripts and the engine
+//!    agree on when a box changed.
+//! 2. **Latest Size Wins**: Several layouts between two deliveries report a
+//!    target once, with its last size, and an element that lost its box
+//!    reports a zero size, as in browsers.
+//! 3. **Border Boxes**: Layout reports border boxes, so `contentRect` and
+//!    `contentBoxSize` carry the border box size as well.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use boa_engine::{
+    object::{builtins::JsArray, ObjectInitializer},
+    property::Attribute,
+    js_string, Context, JsNativeError, JsObject, JsResult, JsValue, NativeFunction,
+};
+use boa_gc::{Finalize, Trace};
+use dom::Node;
+use layout::{Dimensions, GeometryObserverId, GeometryObservers};
+use crate::element_wrappers::ElementWrappers;
+use crate::node_handles::NodeHandleTable;
+
+/// Sizes reported for the targets of one observer since the last delivery,
+/// by node id
+type PendingSizes = Rc<RefCell<HashMap<u64, Option<Dimensions>>>>;
+
+/// An element observed by a `ResizeObserver`
+struct Target {
+    node: Rc<Node>,
+    subscription: GeometryObserverId,
+}
+
+/// An observer created with `new ResizeObserver()`
+struct ObserverRecord {
+    id: u64,
+    callback: JsObject,
+    observer: Option<JsObject>,
+    targets: Vec<Target>,
+    pending: PendingSizes,
+}
+
+struct ObserverState {
+    geometry: GeometryObservers,
+    observers: Vec<ObserverRecord>,
+    next_id: u64,
+}
+
+/// The `ResizeObserver`s of one script context
+#[derive(Clone)]
+pub struct ResizeObservers {
+    state: Rc<RefCell<ObserverState>>,
+}
+
+impl ResizeObservers {
+    fn new() -> Self {
+        let state = ObserverState { geometry: GeometryObservers::new(), observers: Vec::new(), next_id: 1 };
+        Self { state: Rc::new(RefCell::new(state)) }
+    }
+
+    /// Get the geometry observers the targets are subscribed with
+    pub fn geometry(&self) -> GeometryObservers {
+        self.state.borrow().geometry.clone()
+    }
+
+    /// Subscribe the targets with the geometry observers layout engines
+    /// share, see `LayoutEngine::set_geometry_observers`
+    pub fn set_geometry(&self, geometry: GeometryObservers) {
+        let mut state = self.state.borrow_mut();
+        let old = std::mem::replace(&mut state.geometry, geometry.clone());
+        for record in state.observers.iter_mut() {
+            for target in record.targets.iter_mut() {
+                old.unobserve(target.subscription);
+                target.subscription = subscribe(&geometry, &target.node, &record.pending);
+            }
+        }
+    }
+
+    /// Disconnect every observer, e.g. when the document is unloaded
+    ///
+    /// Returns the number of observers disconnected.
+    pub fn disconnect_all(&self) -> usize {
+        let mut state = self.state.borrow_mut();
+        let observers = std::mem::take(&mut state.observers);
+        for target in observers.iter().flat_map(|record| &record.targets) {
+            state.geometry.unobserve(target.subscription);
+        }
+        observers.len()
+    }
+
+    /// Check whether any sizes are waiting to be delivered
+    pub fn has_pending(&self) -> bool {
+        self.state.borrow().observers.iter().any(|record| !record.pending.borrow().is_empty())
+    }
+
+    /// Deliver the sizes reported since the last delivery to the observers'
+    /// callbacks
+    ///
+    /// Returns the number of callbacks that ran. A callback that throws is
+    /// reported and does not stop delivery to the other observers.
+    pub fn deliver(&self, wrappers: &ElementWrappers, context: &mut Context) -> usize {
+        let mut deliveries = Vec::new();
+        for record in self.state.borrow().observers.iter() {
+            let sizes = std::mem::take(&mut *record.pending.borrow_mut());
+            let entries: Vec<(Rc<Node>, Option<Dimensions>)> = record
+                .targets
+                .iter()
+                .filter_map(|target| sizes.get(&target.node.id).map(|size| (Rc::clone(&target.node), *size)))
+                .collect();
+            if let (false, Some(observer)) = (entries.is_empty(), &record.observer) {
+                deliveries.push((record.callback.clone(), observer.clone(), entries));
+            }
+        }
+
+        let count = deliveries.len();
+        for (callback, observer, entries) in deliveries {
+            let entries: Vec<JsValue> = entries
+                .iter()
+                .map(|(node, size)| entry_object(wrappers.wrap(node, context), size.unwrap_or(Dimensions::new(0.0, 0.0, 0.0, 0.0)), context).into())
+                .collect();
+            let entries = JsArray::from_iter(entries, context);
+            let observer = JsValue::from(observer);
+            if let Err(e) = callback.call(&observer, &[entries.into(), observer.clone()], context) {
+                println!("❌ ResizeObserver callback failed: {}", e);
+            }
+        }
+        count
+    }
+
+    fn register(&self, callback: JsObject) -> u64 {
+        let mut state = self.state.borrow_mut();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.observers.push(ObserverRecord { id, callback, observer: None, targets: Vec::new(), pending: PendingSizes::default() });
+        id
+    }
+
+    fn set_observer(&self, id: u64, observer: JsObject) {
+        if let Some(record) = self.state.borrow_mut().observers.iter_mut().find(|record| record.id == id) {
+            record.observer = Some(observer);
+        }
+    }
+
+    fn observe(&self, id: u64, node: Rc<Node>) {
+        let mut state = self.state.borrow_mut();
+        let geometry = state.geometry.clone();
+        if let Some(record) = state.observers.iter_mut().find(|record| record.id == id) {
+            if record.targets.iter().all(|target| !Rc::ptr_eq(&target.node, &node)) {
+                let subscription = subscribe(&geometry, &node, &record.pending);
+                record.targets.push(Target { node, subscription });
+            }
+        }
+    }
+
+    fn unobserve(&self, id: u64, node: Option<&Rc<Node>>) {
+        let mut state = self.state.borrow_mut();
+        let geometry = state.geometry.clone();
+        if let Some(record) = state.observers.iter_mut().find(|record| record.id == id) {
+            record.targets.retain(|target| {
+                let keep = node.is_some_and(|node| !Rc::ptr_eq(&target.node, node));
+                if !keep {
+                    geometry.unobserve(target.subscription);
+                    record.pending.borrow_mut().remove(&target.node.id);
+                }
+                keep
+            });
+        }
+    }
+}
+
+impl fmt::Debug for ResizeObservers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResizeObservers")
+            .field("observers", &self.state.borrow().observers.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Queue the sizes layout reports for `node` on an observer
+fn subscribe(geometry: &GeometryObservers, node: &Rc<Node>, pending: &PendingSizes) -> GeometryObserverId {
+    let pending = Rc::clone(pending);
+    geometry.observe(node.id, move |change| {
+        pending.borrow_mut().insert(change.node_id, change.border_box);
+    })
+}
+
+/// Captures for the `ResizeObserver` constructor
+#[derive(Trace, Finalize)]
+struct ObserversBinding {
+    #[unsafe_ignore_trace]
+    observers: ResizeObservers,
+    #[unsafe_ignore_trace]
+    handles: Rc<RefCell<NodeHandleTable>>,
+}
+
+/// Captures for the methods of one `ResizeObserver`
+#[derive(Trace, Finalize)]
+struct ObserverBinding {
+    #[unsafe_ignore_trace]
+    observers: ResizeObservers,
+    #[unsafe_ignore_trace]
+    handles: Rc<RefCell<NodeHandleTable>>,
+    id: u64,
+}
+
+impl ObserverBinding {
+    fn target(&self, args: &[JsValue], method: &str, context: &mut Context) -> JsResult<Rc<Node>> {
+        let wrapper = args.first().and_then(JsValue::as_object).cloned();
+        wrapper
+            .and_then(|wrapper| self.handles.borrow().resolve_wrapper(&wrapper, context))
+            .ok_or_else(|| JsNativeError::typ().with_message(format!("ResizeObserver.{}: argument is not an Element", method)).into())
+    }
+}
+
+/// Install `ResizeObserver` for the nodes of a handle table
+///
+/// Returns the observers, which the engine delivers sizes to when it spins
+/// the event loop.
+pub fn initialize_resize_observer_bindings(context: &mut Context, handles: Rc<RefCell<NodeHandleTable>>) -> JsResult<ResizeObservers> {
+    let observers = ResizeObservers::new();
+    let constructor = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &ObserversBinding, context| {
+            let Some(callback) = args.first().and_then(JsValue::as_callable).cloned() else {
+                return Err(JsNativeError::typ()
+                    .with_message("ResizeObserver requires a callback function")
+                    .into());
+            };
+            let id = binding.observers.register(callback);
+            let observer = observer_object(&binding.observers, &binding.handles, id, context);
+            binding.observers.set_observer(id, observer.clone());
+            Ok(observer.into())
+        },
+        ObserversBinding { observers: observers.clone(), handles },
+    );
+    context.register_global_callable(js_string!("ResizeObserver"), 1, constructor)?;
+    Ok(observers)
+}
+
+/// Create the object returned by `new ResizeObserver()`
+fn observer_object(observers: &ResizeObservers, handles: &Rc<RefCell<NodeHandleTable>>, id: u64, context: &mut Context) -> JsObject {
+    let binding = || ObserverBinding { observers: observers.clone(), handles: Rc::clone(handles), id };
+
+    let observe = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &ObserverBinding, context| {
+            let node = binding.target(args, "observe", context)?;
+            binding.observers.observe(binding.id, node);
+            Ok(JsValue::undefined())
+        },
+        binding(),
+    );
+    let unobserve = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &ObserverBinding, context| {
+            let node = binding.target(args, "unobserve", context)?;
+            binding.observers.unobserve(binding.id, Some(&node));
+            Ok(JsValue::undefined())
+        },
+        binding(),
+    );
+    let disconnect = NativeFunction::from_copy_closure_with_captures(
+        |_this, _args, binding: &ObserverBinding, _context| {
+            binding.observers.unobserve(binding.id, None);
+            Ok(JsValue::undefined())
+        },
+        binding(),
+    );
+
+    ObjectInitializer::new(context)
+        .function(observe, js_string!("observe"), 1)
+        .function(unobserve, js_string!("unobserve"), 1)
+        .function(disconnect, js_string!("disconnect"), 0)
+        .build()
+}
+
+/// Create a `ResizeObserverEntry`
+fn entry_object(target: JsObject, size: Dimensions, context: &mut Context) -> JsObject {
+    let content_rect = ObjectInitializer::new(context)
+        .property(js_string!("x"), 0, Attribute::READONLY | Attribute::ENUMERABLE)
+        .property(js_string!("y"), 0, Attribute::READONLY | Attribute::ENUMERABLE)
+        .property(js_string!("width"), size.width, Attribute::READONLY | Attribute::ENUMERABLE)
+        .property(js_string!("height"), size.height, Attribute::READONLY | Attribute::ENUMERABLE)
+        .property(js_string!("top"), 0, Attribute::READONLY | Attribute::ENUMERABLE)
+        .property(js_string!("left"), 0, Attribute::READONLY | Attribute::ENUMERABLE)
+        .property(js_string!("right"), size.width, Attribute::READONLY | Attribute::ENUMERABLE)
+        .property(js_string!("bottom"), size.height, Attribute::READONLY | Attribute::ENUMERABLE)
+        .build();
+    let box_size = |context: &mut Context| {
+        let size = ObjectInitializer::new(context)
+            .property(js_string!("inlineSize"), size.width, Attribute::READONLY | Attribute::ENUMERABLE)
+            .property(js_string!("blockSize"), size.height, Attribute::READONLY | Attribute::ENUMERABLE)
+            .build();
+        JsArray::from_iter([size.into()], context)
+    };
+    let border_box_size = box_size(context);
+    let content_box_size = box_size(context);
+    ObjectInitializer::new(context)
+        .property(js_string!("target"), target, Attribute::READONLY | Attribute::ENUMERABLE)
+        .property(js_string!("contentRect"), content_rect, Attribute::READONLY | Attribute::ENUMERABLE)
+        .property(js_string!("borderBoxSize"), border_box_size, Attribute::READONLY | Attribute::ENUMERABLE)
+        .property(js_string!("contentBoxSize"), content_box_size, Attribute::READONLY | Attribute::ENUMERABLE)
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::JsEngine;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_resize_observer_sees_layouts() {
+        let (document, _) = html_parser::parse_html_string("<html><body><div id=\"panel\" style=\"height: 100px\"></div></body></html>").unwrap();
+        let document = Rc::new(document);
+        let mut engine = JsEngine::new();
+        engine.set_document(Rc::clone(&document));
+        engine.set_stylesheet(css_parser::parse_css(""));
+        engine
+            .execute("var sizes = []; \
+                      var observer = new ResizeObserver(function (entries, self) { \
+                          entries.forEach(function (entry) { sizes.push(entry.target.matches('#panel') + ':' + entry.contentRect.height + ':' + entry.borderBoxSize[0].blockSize); }); \
+                      }); \
+                      observer.observe(document.querySelector('#panel'));")
+            .unwrap();
+        let sizes = |engine: &mut JsEngine| {
+            let value = engine.execute("sizes.join()").unwrap();
+            value.to_string(&mut engine.context).unwrap().to_std_string_escaped()
+        };
+
+        engine.execute_with_layout_update("").unwrap();
+        engine.execute_with_layout_update("").unwrap();
+        engine.process_event_loop().unwrap();
+        assert_eq!(sizes(&mut engine), "true:100:100");
+
+        // Only the size after the last layout is delivered
+        let panel = document.root.query_selector("#panel").unwrap().unwrap();
+        panel.set_attribute("style", "height: 120px");
+        engine.execute_with_layout_update("").unwrap();
+        panel.set_attribute("style", "height: 150px");
+        engine.execute_with_layout_update("").unwrap();
+        assert_eq!(engine.deliver_resize_observations(), 1);
+        assert_eq!(sizes(&mut engine), "true:100:100,true:150:150");
+
+        engine.execute("observer.disconnect()").unwrap();
+        panel.set_attribute("style", "height: 10px");
+        engine.execute_with_layout_update("").unwrap();
+        assert_eq!(engine.deliver_resize_observations(), 0);
+        assert!(engine.geometry_observers().is_empty());
+    }
+}
@@ -0,0 +1,415 @@
+//! # Script Sandbox Policy
+//!
+//! This module controls which capabilities a document's scripts can use.
+//! A `SandboxPolicy` decides which groups of globals (network, storage,
+//! timers, workers) are exposed, sets the Boa runtime limits, and enables
+//! per-API call counters, so untrusted pages can be loaded with
+//! progressively restricted capabilities.
+//!
+//! ## Design Principles
+//!
+//! 1. **Absent, Not Throwing**: Globals for a denied capability are removed,
+//!    so feature detection (`typeof fetch === "undefined"`) behaves as in a
+//!    browser without that API.
+//! 2. **Restrict Only**: Applying a policy never restores globals removed by
+//!    an earlier one; widening capabilities requires a new engine.
+//! 3. **Observable**: Calls to exposed capability APIs are counted per API so
+//!    the shell can see what a page actually uses.
+
+use boa_engine::{js_string, Context, JsObject, JsResult, JsValue, NativeFunction};
+use boa_gc::{Finalize, Trace};
+use dom::sandboxing::SandboxFlags;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// A group of globals that a policy can expose or withhold
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// fetch, XMLHttpRequest, WebSocket and friends
+    Network,
+    /// Web storage, IndexedDB and Cache Storage
+    Storage,
+    /// Timers and frame/idle callbacks
+    Timers,
+    /// Dedicated and shared workers
+    Workers,
+}
+
+impl Capability {
+    /// All capabilities
+    pub const ALL: [Capability; 4] = [
+        Capability::Network,
+        Capability::Storage,
+        Capability::Timers,
+        Capability::Workers,
+    ];
+
+    /// Names of the globals belonging to this capability
+    pub fn globals(&self) -> &'static [&'static str] {
+        match self {
+            Capability::Network => &[
+                "fetch",
+                "XMLHttpRequest",
+                "WebSocket",
+                "EventSource",
+                "Request",
+                "Response",
+                "Headers",
+            ],
+            Capability::Storage => &["localStorage", "sessionStorage", "indexedDB", "caches"],
+            Capability::Timers => &[
+                "setTimeout",
+                "setInterval",
+                "clearTimeout",
+                "clearInterval",
+                "requestAnimationFrame",
+                "cancelAnimationFrame",
+                "requestIdleCallback",
+                "cancelIdleCallback",
+            ],
+            Capability::Workers => &["Worker", "SharedWorker"],
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Capability::Network => "network",
+            Capability::Storage => "storage",
+            Capability::Timers => "timers",
+            Capability::Workers => "workers",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Boa runtime limits applied to sandboxed scripts
+///
+/// Boa has no cap on heap size; these limits bound the VM stack, call
+/// recursion and loop iterations, which is what keeps runaway scripts from
+/// exhausting memory or hanging the engine. `None` keeps Boa's default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SandboxLimits {
+    /// Maximum number of values on the VM stack
+    pub max_stack_size: Option<usize>,
+    /// Maximum call depth
+    pub max_recursion_depth: Option<usize>,
+    /// Maximum iterations of a single loop
+    pub max_loop_iterations: Option<u64>,
+}
+
+/// Capabilities and limits for a document's scripts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SandboxPolicy {
+    pub allow_network: bool,
+    pub allow_storage: bool,
+    pub allow_timers: bool,
+    pub allow_workers: bool,
+    /// Whether the document's own scripts run; scripts the shell runs,
+    /// such as the devtools console's, always do
+    pub allow_scripts: bool,
+    /// Whether `alert`, `confirm`, `prompt` and the `beforeunload` prompt
+    /// are shown
+    pub allow_modals: bool,
+    pub limits: SandboxLimits,
+    /// Count calls to exposed capability APIs
+    pub count_api_calls: bool,
+}
+
+impl SandboxPolicy {
+    /// Create a policy exposing every capability, with Boa's default limits
+    pub fn unrestricted() -> Self {
+        SandboxPolicy {
+            allow_network: true,
+            allow_storage: true,
+            allow_timers: true,
+            allow_workers: true,
+            allow_scripts: true,
+            allow_modals: true,
+            limits: SandboxLimits::default(),
+            count_api_calls: true,
+        }
+    }
+
+    /// Create a policy for untrusted content
+    ///
+    /// Only timers remain, and the runtime limits are tight enough that a
+    /// hostile script fails quickly instead of hanging the shell.
+    pub fn untrusted() -> Self {
+        SandboxPolicy {
+            allow_network: false,
+            allow_storage: false,
+            allow_timers: true,
+            allow_workers: false,
+            allow_scripts: true,
+            allow_modals: true,
+            limits: SandboxLimits {
+                max_stack_size: Some(64 * 1024),
+                max_recursion_depth: Some(256),
+                max_loop_iterations: Some(10_000_000),
+            },
+            count_api_calls: true,
+        }
+    }
+
+    /// Create a policy for a document in a frame sandboxed with `flags`
+    ///
+    /// Scripts and modals need their `allow-*` tokens, and storage needs
+    /// `allow-same-origin`, since a document with a unique opaque origin
+    /// has no storage of its own.
+    pub fn for_sandbox_flags(flags: &SandboxFlags) -> Self {
+        SandboxPolicy {
+            allow_storage: flags.same_origin,
+            allow_scripts: flags.scripts,
+            allow_modals: flags.modals,
+            ..SandboxPolicy::unrestricted()
+        }
+    }
+
+    /// Return this policy with a capability allowed or denied
+    pub fn with_capability(mut self, capability: Capability, allowed: bool) -> Self {
+        match capability {
+            Capability::Network => self.allow_network = allowed,
+            Capability::Storage => self.allow_storage = allowed,
+            Capability::Timers => self.allow_timers = allowed,
+            Capability::Workers => self.allow_workers = allowed,
+        }
+        self
+    }
+
+    /// Return this policy with the given runtime limits
+    pub fn with_limits(mut self, limits: SandboxLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Whether the policy exposes a capability
+    pub fn allows(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::Network => self.allow_network,
+            Capability::Storage => self.allow_storage,
+            Capability::Timers => self.allow_timers,
+            Capability::Workers => self.allow_workers,
+        }
+    }
+
+    /// Combine two policies, keeping only what both allow and the tighter
+    /// of each limit
+    pub fn intersect(&self, other: &SandboxPolicy) -> SandboxPolicy {
+        fn tighter<T: Ord>(a: Option<T>, b: Option<T>) -> Option<T> {
+            match (a, b) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            }
+        }
+
+        SandboxPolicy {
+            allow_network: self.allow_network && other.allow_network,
+            allow_storage: self.allow_storage && other.allow_storage,
+            allow_timers: self.allow_timers && other.allow_timers,
+            allow_workers: self.allow_workers && other.allow_workers,
+            allow_scripts: self.allow_scripts && other.allow_scripts,
+            allow_modals: self.allow_modals && other.allow_modals,
+            limits: SandboxLimits {
+                max_stack_size: tighter(self.limits.max_stack_size, other.limits.max_stack_size),
+                max_recursion_depth: tighter(self.limits.max_recursion_depth, other.limits.max_recursion_depth),
+                max_loop_iterations: tighter(self.limits.max_loop_iterations, other.limits.max_loop_iterations),
+            },
+            count_api_calls: self.count_api_calls || other.count_api_calls,
+        }
+    }
+}
+
+impl Default for SandboxPolicy {
+    fn default() -> Self {
+        Self::unrestricted()
+    }
+}
+
+/// Shared per-API call counters
+#[derive(Debug, Clone, Default)]
+pub struct ApiCallCounters {
+    counts: Arc<Mutex<HashMap<String, u64>>>,
+}
+
+impl ApiCallCounters {
+    /// Create empty counters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a call to an API
+    pub fn record(&self, api: &str) {
+        *self.counts.lock().unwrap().entry(api.to_string()).or_insert(0) += 1;
+    }
+
+    /// Number of calls recorded for an API
+    pub fn get(&self, api: &str) -> u64 {
+        self.counts.lock().unwrap().get(api).copied().unwrap_or(0)
+    }
+
+    /// Snapshot of all counters
+    pub fn snapshot(&self) -> HashMap<String, u64> {
+        self.counts.lock().unwrap().clone()
+    }
+
+    /// Reset all counters
+    pub fn reset(&self) {
+        self.counts.lock().unwrap().clear();
+    }
+}
+
+/// Captures for a counting wrapper around an API function
+#[derive(Trace, Finalize)]
+struct CountedApi {
+    original: JsObject,
+    #[unsafe_ignore_trace]
+    name: String,
+    #[unsafe_ignore_trace]
+    counters: ApiCallCounters,
+}
+
+/// Apply a policy to a context
+///
+/// Removes the globals of denied capabilities, sets the runtime limits and,
+/// if enabled, wraps the remaining capability APIs with call counters.
+/// Returns the names of the globals that were removed.
+pub fn apply_sandbox_policy(
+    context: &mut Context,
+    policy: &SandboxPolicy,
+    counters: &ApiCallCounters,
+) -> JsResult<Vec<String>> {
+    let limits = context.runtime_limits_mut();
+    if let Some(size) = policy.limits.max_stack_size {
+        limits.set_stack_size_limit(size);
+    }
+    if let Some(depth) = policy.limits.max_recursion_depth {
+        limits.set_recursion_limit(depth);
+    }
+    if let Some(iterations) = policy.limits.max_loop_iterations {
+        limits.set_loop_iteration_limit(iterations);
+    }
+
+    let global = context.global_object();
+    let mut removed = Vec::new();
+    for capability in Capability::ALL {
+        for name in capability.globals() {
+            let key = js_string!(*name);
+            if !global.has_own_property(key.clone(), context)? {
+                continue;
+            }
+
+            if !policy.allows(capability) {
+                global.delete_property_or_throw(key, context)?;
+                removed.push(name.to_string());
+            } else if policy.count_api_calls {
+                wrap_with_counter(&global, name, counters, context)?;
+            }
+        }
+    }
+
+    if !removed.is_empty() {
+        println!("🛑 Sandbox removed globals: {}", removed.join(", "));
+    }
+    Ok(removed)
+}
+
+/// Replace a global API function with one that counts its calls
+///
+/// Handles both plain functions and the namespace objects some bindings
+/// expose, where the function is a same-named method of the global.
+fn wrap_with_counter(
+    global: &JsObject,
+    name: &str,
+    counters: &ApiCallCounters,
+    context: &mut Context,
+) -> JsResult<()> {
+    let value = global.get(js_string!(name), context)?;
+    let Some(object) = value.as_object().cloned() else {
+        return Ok(());
+    };
+
+    // Wrapping twice would count every call twice
+    if object.get(js_string!("__sandboxCounted"), context)?.to_boolean() {
+        return Ok(());
+    }
+
+    let (holder, function) = if object.is_callable() {
+        (global.clone(), object)
+    } else {
+        match object.get(js_string!(name), context)?.as_object() {
+            Some(method) if method.is_callable() => (object.clone(), method.clone()),
+            _ => return Ok(()),
+        }
+    };
+
+    let captures = CountedApi {
+        original: function,
+        name: name.to_string(),
+        counters: counters.clone(),
+    };
+    let wrapper = NativeFunction::from_copy_closure_with_captures(
+        |this, args, captures: &CountedApi, context| {
+            captures.counters.record(&captures.name);
+            captures.original.call(this, args, context)
+        },
+        captures,
+    )
+    .to_js_function(context.realm());
+
+    holder.set(js_string!(name), JsValue::from(wrapper.clone()), false, context)?;
+    let marker_target: JsObject = if holder == *global { wrapper.into() } else { holder };
+    marker_target.set(js_string!("__sandboxCounted"), true, false, context)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use boa_engine::Source;
+
+    #[test]
+    fn test_denied_capabilities_are_removed() {
+        let mut context = Context::default();
+        context
+            .eval(Source::from_bytes("globalThis.fetch = function() { return 1; }; globalThis.setTimeout = function() { return 2; };"))
+            .unwrap();
+
+        let counters = ApiCallCounters::new();
+        let policy = SandboxPolicy::unrestricted().with_capability(Capability::Network, false);
+        let removed = apply_sandbox_policy(&mut context, &policy, &counters).unwrap();
+        assert_eq!(removed, vec!["fetch".to_string()]);
+
+        let fetch_type = context.eval(Source::from_bytes("typeof fetch")).unwrap();
+        assert_eq!(fetch_type.to_string(&mut context).unwrap().to_std_string_escaped(), "undefined");
+
+        let result = context.eval(Source::from_bytes("setTimeout() + setTimeout()")).unwrap();
+        assert_eq!(result.as_number(), Some(4.0));
+        assert_eq!(counters.get("setTimeout"), 2);
+    }
+
+    #[test]
+    fn test_runtime_limits_stop_runaway_scripts() {
+        let mut context = Context::default();
+        let policy = SandboxPolicy::untrusted().with_limits(SandboxLimits {
+            max_loop_iterations: Some(1_000),
+            ..SandboxLimits::default()
+        });
+        apply_sandbox_policy(&mut context, &policy, &ApiCallCounters::new()).unwrap();
+
+        assert!(context.eval(Source::from_bytes("while (true) {}")).is_err());
+        assert!(context.eval(Source::from_bytes("for (let i = 0; i < 10; i++) {}")).is_ok());
+    }
+
+    #[test]
+    fn test_policy_intersection() {
+        let site = SandboxPolicy::unrestricted().with_capability(Capability::Storage, false);
+        let combined = site.intersect(&SandboxPolicy::untrusted());
+        assert!(!combined.allows(Capability::Network));
+        assert!(!combined.allows(Capability::Storage));
+        assert!(combined.allows(Capability::Timers));
+        assert_eq!(combined.limits.max_recursion_depth, Some(256));
+    }
+}
@@ -0,0 +1,341 @@
+//! # Shell Bridge
+//!
+//! This module implements the privileged channel between shell features,
+//! such as find-in-page or a reader mode toggle, and the helper scripts
+//! they install in a page. A helper is a function the shell hands a port
+//! to; the shell posts JSON messages to the port's `message` listeners and
+//! takes the messages the helper posts back, instead of building ad-hoc
+//! script strings for `JsEngine::execute`.
+//!
+//! ## Design Principles
+//!
+//! 1. **Helpers Get a Port, Pages Get Nothing**: The port is passed to the
+//!    helper function and never put on the global object, so page scripts
+//!    cannot post to the shell or listen to it unless a helper leaks it.
+//! 2. **Origin Checked**: A helper belongs to the origin of the document it
+//!    was installed in. Posting names the origin the shell thinks it talks
+//!    to, and a message for another origin is refused rather than delivered
+//!    to whatever page the tab shows now.
+//! 3. **Data, Not Code**: Messages are JSON values in both directions, so
+//!    what the shell sends is never evaluated and what the page sends back
+//!    is never more than data.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use boa_engine::{
+    object::ObjectInitializer,
+    property::Attribute,
+    js_string, Context, JsNativeError, JsObject, JsValue, NativeFunction, Source,
+};
+use boa_gc::{Finalize, Trace};
+use thiserror::Error;
+
+/// Errors talking to a helper script
+#[derive(Error, Debug, PartialEq)]
+pub enum BridgeError {
+    #[error("No helper named {0} is installed")]
+    UnknownHelper(String),
+
+    #[error("Helper {helper} belongs to {expected}, not {actual}")]
+    OriginMismatch { helper: String, expected: String, actual: String },
+
+    #[error("Helper {helper} failed: {message}")]
+    HelperFailed { helper: String, message: String },
+}
+
+/// Result type for bridge operations
+pub type BridgeResult<T> = Result<T, BridgeError>;
+
+/// A message a helper posted to the shell
+#[derive(Debug, Clone, PartialEq)]
+pub struct BridgeMessage {
+    /// Name of the helper that posted it
+    pub helper: String,
+    /// Serialized origin of the document the helper runs in
+    pub origin: String,
+    pub data: serde_json::Value,
+}
+
+/// A helper script installed by the shell
+struct Helper {
+    name: String,
+    origin: String,
+    listeners: Vec<JsObject>,
+}
+
+#[derive(Default)]
+struct BridgeState {
+    helpers: Vec<Helper>,
+    outbox: Vec<BridgeMessage>,
+}
+
+/// The helpers of one script context and the messages they posted
+#[derive(Clone, Default)]
+pub struct ShellBridge {
+    state: Rc<RefCell<BridgeState>>,
+}
+
+impl ShellBridge {
+    /// Create a bridge without helpers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Install a helper for a document of `origin`
+    ///
+    /// `source` is a function expression, called once with the helper's
+    /// port. Installing a helper again under the same name replaces it.
+    pub fn install(&self, name: &str, origin: &str, source: &str, context: &mut Context) -> BridgeResult<()> {
+        let failed = |message: String| BridgeError::HelperFailed { helper: name.to_string(), message };
+        let function = context
+            .eval(Source::from_bytes(&format!("({})", source)))
+            .map_err(|e| failed(e.to_string()))?;
+        let Some(function) = function.as_callable().cloned() else {
+            return Err(failed("source is not a function".to_string()));
+        };
+
+        {
+            let mut state = self.state.borrow_mut();
+            state.helpers.retain(|helper| helper.name != name);
+            state.helpers.push(Helper { name: name.to_string(), origin: origin.to_string(), listeners: Vec::new() });
+        }
+        let port = port_object(self, name, origin, context);
+        if let Err(e) = function.call(&JsValue::undefined(), &[port.into()], context) {
+            self.state.borrow_mut().helpers.retain(|helper| helper.name != name);
+            return Err(failed(e.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Post `data` to the `message` listeners of a helper
+    ///
+    /// `origin` is the origin the shell means to talk to; a helper of
+    /// another origin is not posted to. Returns the number of listeners
+    /// that ran.
+    pub fn post(&self, origin: &str, name: &str, data: &serde_json::Value, context: &mut Context) -> BridgeResult<usize> {
+        let listeners = {
+            let state = self.state.borrow();
+            let helper = state
+                .helpers
+                .iter()
+                .find(|helper| helper.name == name)
+                .ok_or_else(|| BridgeError::UnknownHelper(name.to_string()))?;
+            if helper.origin != origin {
+                return Err(BridgeError::OriginMismatch {
+                    helper: name.to_string(),
+                    expected: helper.origin.clone(),
+                    actual: origin.to_string(),
+                });
+            }
+            helper.listeners.clone()
+        };
+
+        let failed = |message: String| BridgeError::HelperFailed { helper: name.to_string(), message };
+        let data = JsValue::from_json(data, context).map_err(|e| failed(e.to_string()))?;
+        let event = ObjectInitializer::new(context)
+            .property(js_string!("type"), js_string!("message"), Attribute::READONLY | Attribute::ENUMERABLE)
+            .property(js_string!("data"), data, Attribute::READONLY | Attribute::ENUMERABLE)
+            .build();
+        for listener in &listeners {
+            listener
+                .call(&JsValue::undefined(), &[event.clone().into()], context)
+                .map_err(|e| failed(e.to_string()))?;
+        }
+        Ok(listeners.len())
+    }
+
+    /// Take the messages the helpers posted since the last call
+    pub fn take_messages(&self) -> Vec<BridgeMessage> {
+        std::mem::take(&mut self.state.borrow_mut().outbox)
+    }
+
+    /// Get the names of the installed helpers
+    pub fn helpers(&self) -> Vec<String> {
+        self.state.borrow().helpers.iter().map(|helper| helper.name.clone()).collect()
+    }
+
+    /// Check whether a helper is installed
+    pub fn has_helper(&self, name: &str) -> bool {
+        self.state.borrow().helpers.iter().any(|helper| helper.name == name)
+    }
+
+    /// Remove every helper and drop the messages not taken yet, e.g. when
+    /// the document is unloaded
+    ///
+    /// Returns the number of helpers removed.
+    pub fn clear(&self) -> usize {
+        let mut state = self.state.borrow_mut();
+        state.outbox.clear();
+        std::mem::take(&mut state.helpers).len()
+    }
+
+    fn add_listener(&self, name: &str, listener: JsObject) {
+        if let Some(helper) = self.state.borrow_mut().helpers.iter_mut().find(|helper| helper.name == name) {
+            helper.listeners.push(listener);
+        }
+    }
+
+    fn remove_listener(&self, name: &str, listener: &JsObject) {
+        if let Some(helper) = self.state.borrow_mut().helpers.iter_mut().find(|helper| helper.name == name) {
+            helper.listeners.retain(|existing| existing != listener);
+        }
+    }
+
+    fn push_message(&self, message: BridgeMessage) {
+        let mut state = self.state.borrow_mut();
+        // A replaced or removed helper keeps its port but no longer talks
+        // to the shell
+        if state.helpers.iter().any(|helper| helper.name == message.helper && helper.origin == message.origin) {
+            state.outbox.push(message);
+        }
+    }
+}
+
+impl fmt::Debug for ShellBridge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.borrow();
+        f.debug_struct("ShellBridge")
+            .field("helpers", &state.helpers.iter().map(|helper| &helper.name).collect::<Vec<_>>())
+            .field("outbox", &state.outbox.len())
+            .finish()
+    }
+}
+
+/// Captures for the methods of a helper's port
+#[derive(Trace, Finalize)]
+struct PortBinding {
+    #[unsafe_ignore_trace]
+    bridge: ShellBridge,
+    #[unsafe_ignore_trace]
+    helper: String,
+    #[unsafe_ignore_trace]
+    origin: String,
+}
+
+impl PortBinding {
+    fn message_listener(&self, args: &[JsValue], method: &str) -> boa_engine::JsResult<Option<JsObject>> {
+        let event_type = args.first().and_then(JsValue::as_string).map(|s| s.to_std_string_escaped());
+        if event_type.as_deref() != Some("message") {
+            return Ok(None);
+        }
+        args.get(1)
+            .and_then(JsValue::as_callable)
+            .cloned()
+            .map(Some)
+            .ok_or_else(|| JsNativeError::typ().with_message(format!("port.{}: listener is not a function", method)).into())
+    }
+}
+
+/// Create the port a helper is called with
+fn port_object(bridge: &ShellBridge, helper: &str, origin: &str, context: &mut Context) -> JsObject {
+    let binding = || PortBinding { bridge: bridge.clone(), helper: helper.to_string(), origin: origin.to_string() };
+
+    let post_message = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &PortBinding, context| {
+            let data = structured_data(args.first().cloned().unwrap_or_default(), context)?;
+            binding.bridge.push_message(BridgeMessage { helper: binding.helper.clone(), origin: binding.origin.clone(), data });
+            Ok(JsValue::undefined())
+        },
+        binding(),
+    );
+    let add_event_listener = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &PortBinding, _context| {
+            if let Some(listener) = binding.message_listener(args, "addEventListener")? {
+                binding.bridge.add_listener(&binding.helper, listener);
+            }
+            Ok(JsValue::undefined())
+        },
+        binding(),
+    );
+    let remove_event_listener = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &PortBinding, _context| {
+            if let Some(listener) = binding.message_listener(args, "removeEventListener")? {
+                binding.bridge.remove_listener(&binding.helper, &listener);
+            }
+            Ok(JsValue::undefined())
+        },
+        binding(),
+    );
+
+    ObjectInitializer::new(context)
+        .function(post_message, js_string!("postMessage"), 1)
+        .function(add_event_listener, js_string!("addEventListener"), 2)
+        .function(remove_event_listener, js_string!("removeEventListener"), 2)
+        .property(js_string!("origin"), js_string!(origin), Attribute::READONLY | Attribute::ENUMERABLE)
+        .build()
+}
+
+/// Copy a value a helper posted into JSON
+///
+/// Goes through `JSON.stringify`, so values JSON cannot hold, such as
+/// functions and `undefined`, become `null` or are left out as usual.
+fn structured_data(value: JsValue, context: &mut Context) -> boa_engine::JsResult<serde_json::Value> {
+    let json = context.global_object().get(js_string!("JSON"), context)?;
+    let stringify = json
+        .as_object()
+        .map(|json| json.get(js_string!("stringify"), context))
+        .transpose()?
+        .and_then(|stringify| stringify.as_callable().cloned())
+        .ok_or_else(|| JsNativeError::typ().with_message("port.postMessage: JSON.stringify is not available"))?;
+    let text = stringify.call(&json, &[value], context)?;
+    let Some(text) = text.as_string() else {
+        return Ok(serde_json::Value::Null);
+    };
+    serde_json::from_str(&text.to_std_string_escaped())
+        .map_err(|e| JsNativeError::typ().with_message(format!("port.postMessage: {}", e)).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use serde_json::json;
+    use super::*;
+    use crate::JsEngine;
+
+    fn engine_at(url: &str, html: &str) -> JsEngine {
+        let (document, _) = html_parser::parse_html_string(html).unwrap();
+        document.set_url(url);
+        let mut engine = JsEngine::new();
+        engine.set_document(Rc::new(document));
+        engine
+    }
+
+    const ECHO_HELPER: &str = "function (port) {
+        port.addEventListener('message', e => port.postMessage({ echo: e.data, words: document.body.textContent.trim().split(' ').length }));
+    }";
+
+    #[test]
+    fn test_helpers_talk_to_the_shell_through_their_port() {
+        let mut engine = engine_at("https://example.com/article", "<html><body>one two three</body></html>");
+        engine.install_helper("echo", ECHO_HELPER).unwrap();
+        assert_eq!(engine.shell_bridge().helpers(), ["echo"]);
+
+        // Messages go both ways as JSON and are stamped with the origin
+        assert_eq!(engine.post_to_helper("https://example.com", "echo", &json!({ "query": "two" })).unwrap(), 1);
+        let messages = engine.take_helper_messages();
+        assert_eq!(messages, [BridgeMessage {
+            helper: "echo".to_string(),
+            origin: "https://example.com".to_string(),
+            data: json!({ "echo": { "query": "two" }, "words": 3 }),
+        }]);
+
+        // Another origin, an unknown helper and the page itself are refused
+        assert_eq!(
+            engine.post_to_helper("https://evil.example", "echo", &json!(null)),
+            Err(BridgeError::OriginMismatch {
+                helper: "echo".to_string(),
+                expected: "https://example.com".to_string(),
+                actual: "https://evil.example".to_string(),
+            })
+        );
+        assert_eq!(engine.post_to_helper("https://example.com", "reader", &json!(null)), Err(BridgeError::UnknownHelper("reader".to_string())));
+        let page = engine.execute("typeof port + typeof postMessageToShell").unwrap();
+        assert_eq!(page.to_string(&mut engine.context).unwrap().to_std_string_escaped(), "undefinedundefined");
+        assert!(matches!(engine.install_helper("broken", "42"), Err(BridgeError::HelperFailed { .. })));
+
+        // Helpers do not outlive the document
+        engine.unload();
+        assert!(engine.shell_bridge().helpers().is_empty());
+    }
+}
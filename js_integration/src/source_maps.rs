@@ -0,0 +1,525 @@
+//! # Source Maps
+//!
+//! This module parses version 3 source maps and uses them to point errors
+//! and console call sites at the original files of bundled or minified
+//! scripts.
+//!
+//! - Maps are found through a script's `//# sourceMappingURL=` comment,
+//!   either as a URL or inline as a `data:` URL
+//! - Syntax error positions are remapped to the original file, line and
+//!   column
+//! - Console messages from mapped scripts name their call site, and
+//!   `console.trace()` prints the remapped stack
+//!
+//! ## Design Principles
+//!
+//! 1. **Function Frames**: The engine reports which functions are on the
+//!    stack but not the position inside them, so a frame points at the
+//!    definition of its function. Mapped through the source map that is
+//!    the original function and its original name.
+//! 2. **Registry Shared by Cloning**: The engine registers scripts as it
+//!    runs them, and the console reads the same registry when it captures a
+//!    call site.
+//! 3. **Unmapped Means Unchanged**: Errors and messages from scripts
+//!    without a map are reported as before.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use base64::Engine;
+use boa_engine::Context;
+use networking::UrlResolver;
+use thiserror::Error;
+
+/// Name the engine gives the top level code of a script
+const TOP_LEVEL_FRAME: &str = "<main>";
+
+/// Errors parsing a source map
+#[derive(Error, Debug, PartialEq)]
+pub enum SourceMapError {
+    #[error("Invalid source map JSON: {0}")]
+    Json(String),
+
+    #[error("Unsupported source map version {0}")]
+    UnsupportedVersion(u64),
+
+    #[error("Indexed source maps are not supported")]
+    Indexed,
+
+    #[error("Invalid mappings: {0}")]
+    InvalidMappings(String),
+}
+
+/// One mapped position in a generated line
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    generated_column: u32,
+    source: usize,
+    line: u32,
+    column: u32,
+    name: Option<usize>,
+}
+
+/// A position in an original source, 1-based
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OriginalPosition {
+    pub source: String,
+    pub line: u32,
+    pub column: u32,
+    pub name: Option<String>,
+}
+
+/// A parsed version 3 source map
+#[derive(Debug, Clone)]
+pub struct SourceMap {
+    sources: Vec<String>,
+    names: Vec<String>,
+    sources_content: Vec<Option<String>>,
+    /// Segments of each generated line, by generated column
+    lines: Vec<Vec<Segment>>,
+}
+
+impl SourceMap {
+    /// Parse a source map; `map_url` resolves relative source paths
+    pub fn parse(json: &str, map_url: Option<&str>) -> Result<Self, SourceMapError> {
+        let value: serde_json::Value = serde_json::from_str(json).map_err(|e| SourceMapError::Json(e.to_string()))?;
+        if value.get("sections").is_some() {
+            return Err(SourceMapError::Indexed);
+        }
+        let version = value.get("version").and_then(serde_json::Value::as_u64).unwrap_or(0);
+        if version != 3 {
+            return Err(SourceMapError::UnsupportedVersion(version));
+        }
+        let strings = |key: &str| -> Vec<Option<String>> {
+            value.get(key).and_then(serde_json::Value::as_array).map_or_else(Vec::new, |items| {
+                items.iter().map(|item| item.as_str().map(str::to_string)).collect()
+            })
+        };
+
+        let root = value.get("sourceRoot").and_then(serde_json::Value::as_str).unwrap_or_default();
+        let resolver = map_url.and_then(UrlResolver::with_base);
+        let sources = strings("sources").into_iter().map(|source| {
+            let path = format!("{}{}", root, source.unwrap_or_default());
+            resolver.as_ref().and_then(|resolver| resolver.resolve(&path)).map_or(path, String::from)
+        }).collect();
+        let names = strings("names").into_iter().map(Option::unwrap_or_default).collect();
+        let mappings = value.get("mappings").and_then(serde_json::Value::as_str).unwrap_or_default();
+
+        Ok(SourceMap { sources, names, sources_content: strings("sourcesContent"), lines: decode_mappings(mappings)? })
+    }
+
+    /// Find the original position of a generated 1-based line and column
+    pub fn lookup(&self, line: u32, column: u32) -> Option<OriginalPosition> {
+        let segments = self.lines.get(line.checked_sub(1)? as usize)?;
+        let column = column.saturating_sub(1);
+        let index = segments.partition_point(|segment| segment.generated_column <= column).checked_sub(1)?;
+        let segment = segments[index];
+        Some(OriginalPosition {
+            source: self.sources.get(segment.source)?.clone(),
+            line: segment.line + 1,
+            column: segment.column + 1,
+            name: segment.name.and_then(|name| self.names.get(name).cloned()),
+        })
+    }
+
+    pub fn sources(&self) -> &[String] {
+        &self.sources
+    }
+
+    /// The embedded text of an original source
+    pub fn source_content(&self, source: &str) -> Option<&str> {
+        let index = self.sources.iter().position(|candidate| candidate == source)?;
+        self.sources_content.get(index)?.as_deref()
+    }
+}
+
+/// Decode the `mappings` field into segments per generated line
+fn decode_mappings(mappings: &str) -> Result<Vec<Vec<Segment>>, SourceMapError> {
+    let mut lines = Vec::new();
+    // Everything but the generated column carries over between lines
+    let (mut source, mut line, mut column, mut name) = (0i64, 0i64, 0i64, 0i64);
+    for text in mappings.split(';') {
+        let mut segments = Vec::new();
+        let mut generated_column = 0i64;
+        for field in text.split(',').filter(|field| !field.is_empty()) {
+            let values = decode_vlq(field)?;
+            generated_column += values[0];
+            match values.len() {
+                1 => continue,
+                4 | 5 => {}
+                count => return Err(SourceMapError::InvalidMappings(format!("segment with {} fields", count))),
+            }
+            source += values[1];
+            line += values[2];
+            column += values[3];
+            let segment_name = if values.len() == 5 {
+                name += values[4];
+                Some(name)
+            } else {
+                None
+            };
+            if generated_column < 0 || source < 0 || line < 0 || column < 0 || name < 0 {
+                return Err(SourceMapError::InvalidMappings("negative position".to_string()));
+            }
+            segments.push(Segment {
+                generated_column: generated_column as u32,
+                source: source as usize,
+                line: line as u32,
+                column: column as u32,
+                name: segment_name.map(|name| name as usize),
+            });
+        }
+        segments.sort_by_key(|segment| segment.generated_column);
+        lines.push(segments);
+    }
+    Ok(lines)
+}
+
+/// Decode a run of base64 VLQ values
+fn decode_vlq(field: &str) -> Result<Vec<i64>, SourceMapError> {
+    let mut values = Vec::new();
+    let (mut value, mut shift) = (0i64, 0u32);
+    for byte in field.bytes() {
+        let digit = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return Err(SourceMapError::InvalidMappings(format!("invalid character '{}'", byte as char))),
+        } as i64;
+        if shift > 60 {
+            return Err(SourceMapError::InvalidMappings("value too large".to_string()));
+        }
+        value += (digit & 31) << shift;
+        if digit & 32 != 0 {
+            shift += 5;
+            continue;
+        }
+        values.push(if value & 1 == 1 { -(value >> 1) } else { value >> 1 });
+        value = 0;
+        shift = 0;
+    }
+    if shift != 0 {
+        return Err(SourceMapError::InvalidMappings("truncated value".to_string()));
+    }
+    Ok(values)
+}
+
+/// The map URL of a script's last `//# sourceMappingURL=` comment
+pub fn source_mapping_url(script: &str) -> Option<&str> {
+    script.lines().rev().take_while(|line| line.trim().is_empty() || line.trim_start().starts_with("//")).find_map(|line| {
+        let comment = line.trim().strip_prefix("//")?;
+        let url = comment.strip_prefix("# sourceMappingURL=").or_else(|| comment.strip_prefix("@ sourceMappingURL="))?;
+        Some(url.trim()).filter(|url| !url.is_empty())
+    })
+}
+
+/// Resolve a map URL against the URL of its script
+pub fn resolve_map_url(script_url: &str, map_url: &str) -> Option<String> {
+    if map_url.starts_with("data:") {
+        return Some(map_url.to_string());
+    }
+    UrlResolver::with_base(script_url)?.resolve(map_url).map(String::from)
+}
+
+/// Decode an inline `data:` map
+///
+/// Returns `None` for URLs that must be fetched.
+pub fn decode_data_url(map_url: &str) -> Option<Result<String, SourceMapError>> {
+    let (meta, data) = map_url.strip_prefix("data:")?.split_once(',')?;
+    if meta.split(';').any(|part| part.eq_ignore_ascii_case("base64")) {
+        let decoded = base64::engine::general_purpose::STANDARD.decode(data.trim())
+            .map_err(|e| SourceMapError::Json(e.to_string()))
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned());
+        return Some(decoded);
+    }
+    let bytes: Vec<u8> = url::form_urlencoded::parse(data.as_bytes()).flat_map(|(key, value)| {
+        // Undo the form decoding's treatment of `+`, `=` and `&`
+        let mut part = key.into_owned();
+        if !value.is_empty() {
+            part.push('=');
+            part.push_str(&value);
+        }
+        part.into_bytes()
+    }).collect();
+    Some(Ok(String::from_utf8_lossy(&bytes).into_owned()))
+}
+
+/// A frame of a stack trace or a console call site
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackFrame {
+    /// Function name; `None` for a script's top level code
+    pub function: Option<String>,
+    pub url: Option<String>,
+    /// 1-based position, when known
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+}
+
+impl StackFrame {
+    /// The `file:line:column` part of the frame
+    pub fn location(&self) -> String {
+        let url = self.url.as_deref().unwrap_or("<anonymous>");
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => format!("{}:{}:{}", url, line, column),
+            (Some(line), None) => format!("{}:{}", url, line),
+            _ => url.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for StackFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.function {
+            Some(function) => write!(f, "at {} ({})", function, self.location()),
+            None => write!(f, "at {}", self.location()),
+        }
+    }
+}
+
+/// A script the engine has run
+#[derive(Debug, Clone)]
+struct RegisteredScript {
+    url: String,
+    source: String,
+    map: Option<SourceMap>,
+}
+
+/// The scripts of a page and their source maps
+///
+/// Cloning shares the registry.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptRegistry {
+    scripts: Rc<RefCell<Vec<RegisteredScript>>>,
+    /// URLs of the scripts whose top level code is running, innermost last
+    running: Rc<RefCell<Vec<String>>>,
+}
+
+impl ScriptRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a script, replacing an earlier one with the same URL
+    pub fn register(&self, url: &str, source: &str, map: Option<SourceMap>) {
+        let mut scripts = self.scripts.borrow_mut();
+        scripts.retain(|script| script.url != url);
+        scripts.push(RegisteredScript { url: url.to_string(), source: source.to_string(), map });
+    }
+
+    /// Whether a script has a source map
+    pub fn has_map(&self, url: &str) -> bool {
+        self.scripts.borrow().iter().any(|script| script.url == url && script.map.is_some())
+    }
+
+    /// Note that a script's top level code started running
+    pub fn enter(&self, url: &str) {
+        self.running.borrow_mut().push(url.to_string());
+    }
+
+    /// Note that the innermost running script finished
+    pub fn leave(&self) {
+        self.running.borrow_mut().pop();
+    }
+
+    /// Map a frame of a generated script to its original source
+    ///
+    /// Frames of scripts without a map are returned unchanged.
+    pub fn remap(&self, frame: &StackFrame) -> StackFrame {
+        let scripts = self.scripts.borrow();
+        let original = frame.url.as_ref()
+            .and_then(|url| scripts.iter().find(|script| &script.url == url))
+            .and_then(|script| script.map.as_ref())
+            .zip(frame.line)
+            .and_then(|(map, line)| map.lookup(line, frame.column.unwrap_or(1)));
+        match original {
+            Some(position) => StackFrame {
+                function: frame.function.as_ref().map(|function| position.name.clone().unwrap_or_else(|| function.clone())),
+                url: Some(position.source),
+                line: Some(position.line),
+                column: Some(position.column),
+            },
+            None => frame.clone(),
+        }
+    }
+
+    /// Remap the `at line L, col C` position of an engine error message
+    /// from a mapped script
+    pub fn remap_error(&self, url: &str, message: &str) -> String {
+        let Some(start) = message.rfind(" at line ") else {
+            return message.to_string();
+        };
+        let position = &message[start + " at line ".len()..];
+        let Some((line, column)) = position.split_once(", col ") else {
+            return message.to_string();
+        };
+        let (Ok(line), Ok(column)) = (line.trim().parse(), column.trim().parse()) else {
+            return message.to_string();
+        };
+        let frame = StackFrame { function: None, url: Some(url.to_string()), line: Some(line), column: Some(column) };
+        let remapped = self.remap(&frame);
+        if remapped == frame {
+            return message.to_string();
+        }
+        format!("{} at {}", &message[..start], remapped.location())
+    }
+
+    /// Locate a function's definition among the registered scripts
+    fn locate_function(&self, name: &str) -> Option<StackFrame> {
+        let scripts = self.scripts.borrow();
+        scripts.iter().rev().find_map(|script| {
+            let offset = find_function_definition(&script.source, name)?;
+            let before = &script.source[..offset];
+            let line = before.matches('\n').count() as u32 + 1;
+            let column = before.rsplit('\n').next().map_or(0, |line| line.chars().count()) as u32 + 1;
+            Some(StackFrame { function: Some(name.to_string()), url: Some(script.url.clone()), line: Some(line), column: Some(column) })
+        })
+    }
+
+    /// Capture the frames on a context's stack, innermost first, remapped
+    ///
+    /// The engine leaves the running frame out of its stack trace, so a
+    /// native function sees its caller's frame only when it is called
+    /// through a JavaScript function.
+    pub fn capture(&self, context: &Context) -> Vec<StackFrame> {
+        let mut running = self.running.borrow().clone();
+        let mut frames: Vec<_> = context.stack_trace().collect();
+        // The bottom frame is the engine's placeholder, not code
+        frames.pop();
+        frames.into_iter().map(|frame| {
+            let name = frame.code_block().name().to_std_string_escaped();
+            let frame = if name == TOP_LEVEL_FRAME {
+                StackFrame { function: None, url: running.pop(), line: None, column: None }
+            } else {
+                let function = if name.is_empty() { "(anonymous)".to_string() } else { name };
+                self.locate_function(&function).unwrap_or(StackFrame { function: Some(function), url: None, line: None, column: None })
+            };
+            self.remap(&frame)
+        }).collect()
+    }
+
+    /// The innermost frame of a context's stack, if it lies in a mapped
+    /// script
+    pub fn call_site(&self, context: &Context) -> Option<StackFrame> {
+        let frame = self.capture(context).into_iter().next()?;
+        let mapped = self.scripts.borrow().iter().any(|script| {
+            script.map.as_ref().is_some_and(|map| frame.url.as_ref().is_some_and(|url| map.sources().contains(url)))
+        });
+        mapped.then_some(frame)
+    }
+}
+
+/// Byte offset of the name in `function name`, `name = function`,
+/// `name: function` or `name = (...) =>`
+fn find_function_definition(source: &str, name: &str) -> Option<usize> {
+    let is_identifier = |c: char| c.is_alphanumeric() || c == '_' || c == '$';
+    let mut from = 0;
+    while let Some(found) = source[from..].find(name) {
+        let start = from + found;
+        let end = start + name.len();
+        from = end;
+        if source[..start].chars().next_back().is_some_and(is_identifier) || source[end..].chars().next().is_some_and(is_identifier) {
+            continue;
+        }
+        let before = source[..start].trim_end();
+        if let Some(keyword) = before.strip_suffix("function") {
+            if !keyword.chars().next_back().is_some_and(is_identifier) {
+                return Some(start);
+            }
+        }
+        let after = source[end..].trim_start();
+        let Some(value) = after.strip_prefix('=').or_else(|| after.strip_prefix(':')) else {
+            continue;
+        };
+        let value = value.trim_start();
+        let is_arrow = value.starts_with('(') && value.split_once(')').is_some_and(|(_, rest)| rest.trim_start().starts_with("=>"));
+        if !after.starts_with("==") && (value.starts_with("function") || is_arrow) {
+            return Some(start);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use boa_engine::{js_string, JsValue, NativeFunction, Source};
+    use boa_gc::{Finalize, Trace};
+
+    #[derive(Trace, Finalize)]
+    struct Capture {
+        #[unsafe_ignore_trace]
+        scripts: ScriptRegistry,
+        #[unsafe_ignore_trace]
+        frames: Rc<RefCell<Vec<StackFrame>>>,
+    }
+
+    // Generated from src/app.js:
+    //
+    //     function greet(name) {
+    //       return "Hello, " + name;
+    //     }
+    //
+    // into `function a(n){return"Hello, "+n}`
+    const MAP: &str = r#"{
+        "version": 3,
+        "sources": ["app.js"],
+        "sourceRoot": "src/",
+        "names": ["greet", "name"],
+        "mappings": "AAAA,SAASA,EAAMC,GACb,MAAO,UAAYA",
+        "sourcesContent": ["function greet(name) {\n  return \"Hello, \" + name;\n}\n"]
+    }"#;
+
+    #[test]
+    fn test_maps_positions_errors_and_call_sites() {
+        let map = SourceMap::parse(MAP, Some("https://example.com/dist/app.min.js.map")).unwrap();
+        assert_eq!(map.sources(), ["https://example.com/dist/src/app.js"]);
+        assert_eq!(map.lookup(1, 10), Some(OriginalPosition {
+            source: "https://example.com/dist/src/app.js".to_string(),
+            line: 1,
+            column: 10,
+            name: Some("greet".to_string()),
+        }));
+        // Columns between segments belong to the segment before them
+        assert_eq!(map.lookup(1, 16).unwrap().line, 2);
+        assert!(map.lookup(2, 1).is_none());
+        assert!(map.source_content("https://example.com/dist/src/app.js").unwrap().starts_with("function greet"));
+        assert_eq!(SourceMap::parse(r#"{"version": 2}"#, None).unwrap_err(), SourceMapError::UnsupportedVersion(2));
+        assert_eq!(decode_vlq("2Hw+B").unwrap(), vec![123, 1000]);
+
+        let script = "function a(n){return\"Hello, \"+n}\n//# sourceMappingURL=app.min.js.map\n";
+        assert_eq!(source_mapping_url(script), Some("app.min.js.map"));
+        assert_eq!(resolve_map_url("https://example.com/dist/app.min.js", "app.min.js.map").as_deref(), Some("https://example.com/dist/app.min.js.map"));
+        let inline = format!("data:application/json;base64,{}", base64::engine::general_purpose::STANDARD.encode(MAP));
+        assert!(SourceMap::parse(&decode_data_url(&inline).unwrap().unwrap(), None).is_ok());
+
+        let registry = ScriptRegistry::new();
+        registry.register("https://example.com/dist/app.min.js", script, Some(map));
+        assert_eq!(
+            registry.remap_error("https://example.com/dist/app.min.js", "SyntaxError: unexpected token at line 1, col 10"),
+            "SyntaxError: unexpected token at https://example.com/dist/src/app.js:1:10"
+        );
+        assert_eq!(registry.remap_error("https://example.com/other.js", "Error at line 1, col 1"), "Error at line 1, col 1");
+
+        // A call from inside the minified function is reported in the
+        // original function, under its original name
+        let mut context = Context::default();
+        let captured = Rc::new(RefCell::new(Vec::new()));
+        let capture = NativeFunction::from_copy_closure_with_captures(
+            |_, _, capture: &Capture, context| {
+                capture.frames.borrow_mut().extend(capture.scripts.capture(context));
+                Ok(JsValue::undefined())
+            },
+            Capture { scripts: registry.clone(), frames: captured.clone() },
+        );
+        context.register_global_builtin_callable(js_string!("nativeCapture"), 0, capture).unwrap();
+        context.eval(Source::from_bytes("function capture() { nativeCapture(); }")).unwrap();
+        registry.enter("https://example.com/dist/app.min.js");
+        context.eval(Source::from_bytes("function a(n){capture();return\"Hello, \"+n}\na('x');")).unwrap();
+        registry.leave();
+        let frames = captured.borrow();
+        assert_eq!(frames[0].to_string(), "at greet (https://example.com/dist/src/app.js:1:10)");
+        assert_eq!(frames[1].to_string(), "at https://example.com/dist/app.min.js");
+    }
+}
@@ -0,0 +1,139 @@
+//! # Document Unloading
+//!
+//! This module implements the unload steps a document goes through when
+//! the tab navigates away from it: `beforeunload`, which lets the page ask
+//! the user to confirm leaving, then `pagehide` and `unload`, and finally
+//! the teardown of everything the page left running.
+//!
+//! ## Design Principles
+//!
+//! 1. **The Page Asks, the User Decides**: A page requests a confirmation
+//!    by canceling `beforeunload` or setting its `returnValue`; the prompt
+//!    itself is the shell's, with a fixed message, see
+//!    `UserPrompt::BeforeUnload`.
+//! 2. **Nothing Outlives the Document**: After `unload`, in-flight fetches
+//!    are aborted and timers, tasks and observers are dropped, so a page
+//!    that was left cannot run or touch the network again.
+//! 3. **Once Only**: A document is unloaded at most once; later calls do
+//!    nothing, so every navigation path can run the steps without
+//!    checking whether another already did.
+
+use boa_engine::property::PropertyDescriptor;
+use boa_engine::{js_string, Context, JsObject, JsResult};
+use dom::event_types::EventInterface;
+use crate::event_objects::{create_trusted_event, EventPrototypes};
+
+/// What tearing down an unloaded document stopped
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TeardownReport {
+    /// Fetches that were still in flight
+    pub aborted_requests: usize,
+    /// Timers, tasks and frame and idle callbacks that were pending
+    pub dropped_tasks: usize,
+    /// `ResizeObserver`s, `PerformanceObserver`s and geolocation watches
+    pub disconnected_observers: usize,
+}
+
+impl TeardownReport {
+    /// Check whether the document had nothing left running
+    pub fn is_empty(&self) -> bool {
+        *self == TeardownReport::default()
+    }
+}
+
+/// Create the `beforeunload` event
+///
+/// Its `returnValue` is a string, as on `BeforeUnloadEvent`, shadowing the
+/// boolean legacy accessor of `Event`.
+pub(crate) fn before_unload_event(prototypes: &EventPrototypes, context: &mut Context) -> JsResult<JsObject> {
+    let event = create_trusted_event(prototypes, EventInterface::Event, "beforeunload", false, true);
+    event.define_property_or_throw(
+        js_string!("returnValue"),
+        PropertyDescriptor::builder().value(js_string!("")).writable(true).enumerable(true).configurable(true),
+        context,
+    )?;
+    Ok(event)
+}
+
+/// Check whether a dispatched `beforeunload` event asks to confirm leaving
+///
+/// `canceled` is whether a listener called `preventDefault()`; setting
+/// `returnValue` to a non-empty string asks too.
+pub(crate) fn confirmation_requested(event: &JsObject, canceled: bool, context: &mut Context) -> JsResult<bool> {
+    if canceled {
+        return Ok(true);
+    }
+    let return_value = event.get(js_string!("returnValue"), context)?;
+    Ok(return_value.as_string().is_some_and(|value| !value.is_empty()))
+}
+
+/// Create a `pagehide` or `pageshow` event
+///
+/// `persisted` is whether the document is kept for going back to it.
+pub(crate) fn page_transition_event(prototypes: &EventPrototypes, event_type: &str, persisted: bool, context: &mut Context) -> JsResult<JsObject> {
+    let event = create_trusted_event(prototypes, EventInterface::Event, event_type, false, false);
+    event.define_property_or_throw(
+        js_string!("persisted"),
+        PropertyDescriptor::builder().value(persisted).writable(false).enumerable(true).configurable(true),
+        context,
+    )?;
+    Ok(event)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use crate::user_prompts::UserPrompt;
+    use crate::JsEngine;
+
+    fn engine_for(html: &str) -> JsEngine {
+        let (document, _) = html_parser::parse_html_string(html).unwrap();
+        let mut engine = JsEngine::new();
+        engine.set_document(Rc::new(document));
+        engine
+    }
+
+    fn evaluate(engine: &mut JsEngine, code: &str) -> String {
+        let result = engine.execute(code).unwrap();
+        result.to_string(&mut engine.context).unwrap().to_std_string_escaped()
+    }
+
+    #[test]
+    fn test_unload_sequence_and_teardown() {
+        // Pages that do not ask are left without a prompt
+        let prompts = Arc::new(Mutex::new(Vec::new()));
+        let mut engine = engine_for("<html><body></body></html>");
+        let shown = Arc::clone(&prompts);
+        engine.set_user_prompt_handler(Arc::new(move |prompt: &UserPrompt| {
+            shown.lock().unwrap().push(prompt.clone());
+            None
+        }));
+        assert!(engine.prompt_to_unload());
+        assert!(prompts.lock().unwrap().is_empty());
+
+        // Setting `returnValue` asks, and dismissing the prompt stays
+        evaluate(&mut engine, "var log = []; window.addEventListener('beforeunload', e => { log.push(e.cancelable); e.returnValue = 'unsaved'; });");
+        assert!(!engine.prompt_to_unload());
+        assert_eq!(*prompts.lock().unwrap(), [UserPrompt::BeforeUnload]);
+        engine.set_user_prompt_handler(Arc::new(|_prompt: &UserPrompt| Some(String::new())));
+        assert!(engine.prompt_to_unload());
+
+        // `pagehide` comes before `unload`, then everything is torn down
+        evaluate(&mut engine, "window.addEventListener('pagehide', e => log.push('pagehide:' + e.persisted)); window.addEventListener('unload', e => log.push('unload:' + e.isTrusted));");
+        engine.queue_task(crate::event_loop::TaskSource::Timer, "log.push('late task')");
+        let now = engine.now();
+        engine.event_loop().set_timer("log.push('late timer')".to_string(), Duration::from_millis(10), true, now);
+        let report = engine.unload();
+        assert_eq!(report.dropped_tasks, 2);
+        assert_eq!(evaluate(&mut engine, "log.join()"), "true,true,pagehide:false,unload:true");
+        assert_eq!(engine.event_loop().timer_count(), 0);
+        assert!(!engine.event_loop().has_pending_tasks());
+
+        // An unloaded document is not unloaded again
+        assert!(engine.unload().is_empty());
+        assert!(engine.prompt_to_unload());
+        assert_eq!(evaluate(&mut engine, "log.length"), "4");
+    }
+}
@@ -0,0 +1,246 @@
+//! # URL Binding
+//!
+//! This module implements the `URL` interface, giving scripts the parsed
+//! components of a URL: `new URL(url, base)`, the `href`, `protocol`,
+//! `host`, `pathname`, `search`, `hash` and other accessors, and the
+//! static `URL.canParse` and `URL.parse`.
+//!
+//! ## Design Principles
+//!
+//! 1. **Same Parser as the Engine**: URLs are resolved by the networking
+//!    crate's `UrlResolver`, so a script sees exactly the URL the engine
+//!    would fetch, with punycode hosts and percent-encoded paths.
+//! 2. **Setters Follow the Standard**: Components are read and written with
+//!    the URL Standard's getter and setter steps. Only setting `href` to an
+//!    invalid URL throws; other invalid values are ignored, as in browsers.
+//! 3. **No Implicit Base**: As in browsers, a relative URL needs an explicit
+//!    base; scripts pass the URL of the page themselves.
+
+use std::cell::RefCell;
+use boa_engine::{
+    object::FunctionObjectBuilder,
+    property::PropertyDescriptor,
+    js_string, Context, JsData, JsNativeError, JsObject, JsResult, JsValue, NativeFunction,
+};
+use boa_gc::{Finalize, Trace};
+use networking::UrlResolver;
+use url::{quirks, Url};
+
+/// The accessors of URL objects, in the order the standard lists them
+const COMPONENTS: [&str; 11] = [
+    "href", "origin", "protocol", "username", "password", "host", "hostname", "port", "pathname", "search", "hash",
+];
+
+/// Native data of URL objects
+#[derive(Trace, Finalize, JsData)]
+struct UrlData {
+    #[unsafe_ignore_trace]
+    url: RefCell<Url>,
+}
+
+/// Captures for the `URL` constructor
+#[derive(Clone, Trace, Finalize)]
+struct UrlBinding {
+    prototype: JsObject,
+}
+
+/// Get the URL wrapped by a URL object
+pub fn url_from_object(object: &JsObject) -> Option<Url> {
+    object.downcast_ref::<UrlData>().map(|data| data.url.borrow().clone())
+}
+
+/// Register the `URL` constructor
+pub fn initialize_url_bindings(context: &mut Context) -> JsResult<()> {
+    let prototype = JsObject::with_object_proto(context.intrinsics());
+    for name in COMPONENTS {
+        let get = NativeFunction::from_copy_closure(move |this, _args, _context| {
+            let url = this_url(this)?;
+            Ok(js_string!(component(&url, name)).into())
+        });
+        let mut descriptor = PropertyDescriptor::builder().get(get.to_js_function(context.realm())).enumerable(true).configurable(true);
+        if name != "origin" {
+            let set = NativeFunction::from_copy_closure(move |this, args, context| {
+                let value = args.first().cloned().unwrap_or_default().to_string(context)?.to_std_string_escaped();
+                let data = this.as_object().and_then(|object| object.downcast_ref::<UrlData>().map(|data| set_component(&mut data.url.borrow_mut(), name, &value)));
+                match data {
+                    Some(Ok(())) => Ok(JsValue::undefined()),
+                    Some(Err(())) => Err(JsNativeError::typ().with_message(format!("Invalid URL: {}", value)).into()),
+                    None => Err(illegal_invocation()),
+                }
+            });
+            descriptor = descriptor.set(set.to_js_function(context.realm()));
+        }
+        prototype.define_property_or_throw(js_string!(name), descriptor, context)?;
+    }
+    for name in ["toString", "toJSON"] {
+        let method = NativeFunction::from_fn_ptr(|this, _args, _context| Ok(js_string!(this_url(this)?.as_str()).into()));
+        prototype.define_property_or_throw(
+            js_string!(name),
+            PropertyDescriptor::builder()
+                .value(FunctionObjectBuilder::new(context.realm(), method).name(js_string!(name)).length(0).build())
+                .writable(true)
+                .enumerable(false)
+                .configurable(true),
+            context,
+        )?;
+    }
+
+    let binding = UrlBinding { prototype: prototype.clone() };
+    let construct = NativeFunction::from_copy_closure_with_captures(
+        |new_target, args, binding: &UrlBinding, context| {
+            if new_target.as_object().is_none() {
+                return Err(JsNativeError::typ()
+                    .with_message("Failed to construct 'URL': Please use the 'new' operator")
+                    .into());
+            }
+            let url = parse_arguments(args, context)?.map_err(|message| JsNativeError::typ().with_message(format!("Failed to construct 'URL': {}", message)))?;
+            Ok(url_object(url, binding).into())
+        },
+        binding.clone(),
+    );
+    let constructor = FunctionObjectBuilder::new(context.realm(), construct)
+        .name(js_string!("URL"))
+        .length(1)
+        .constructor(true)
+        .build();
+    constructor.define_property_or_throw(
+        js_string!("prototype"),
+        PropertyDescriptor::builder().value(prototype.clone()).writable(false).enumerable(false).configurable(false),
+        context,
+    )?;
+    prototype.define_property_or_throw(
+        js_string!("constructor"),
+        PropertyDescriptor::builder().value(constructor.clone()).writable(true).enumerable(false).configurable(true),
+        context,
+    )?;
+
+    let can_parse = NativeFunction::from_fn_ptr(|_this, args, context| Ok(parse_arguments(args, context)?.is_ok().into()));
+    let parse = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &UrlBinding, context| {
+            Ok(parse_arguments(args, context)?.map_or(JsValue::null(), |url| url_object(url, binding).into()))
+        },
+        binding,
+    );
+    for (name, function) in [("canParse", can_parse), ("parse", parse)] {
+        constructor.define_property_or_throw(
+            js_string!(name),
+            PropertyDescriptor::builder()
+                .value(FunctionObjectBuilder::new(context.realm(), function).name(js_string!(name)).length(1).build())
+                .writable(true)
+                .enumerable(false)
+                .configurable(true),
+            context,
+        )?;
+    }
+
+    context.global_object().set(js_string!("URL"), constructor, false, context)?;
+    Ok(())
+}
+
+fn url_object(url: Url, binding: &UrlBinding) -> JsObject {
+    JsObject::from_proto_and_data(Some(binding.prototype.clone()), UrlData { url: RefCell::new(url) })
+}
+
+/// Parse the `url` and `base` arguments of the constructor, `canParse`
+/// and `parse`
+///
+/// The outer error is an exception converting the arguments, the inner
+/// one the reason they are not a valid URL.
+fn parse_arguments(args: &[JsValue], context: &mut Context) -> JsResult<Result<Url, String>> {
+    let url = args.first().cloned().unwrap_or_default().to_string(context)?.to_std_string_escaped();
+    let resolver = match args.get(1).filter(|base| !base.is_undefined()) {
+        Some(base) => {
+            let base = base.to_string(context)?.to_std_string_escaped();
+            match UrlResolver::with_base(&base) {
+                Some(resolver) => resolver,
+                None => return Ok(Err(format!("Invalid base URL: {}", base))),
+            }
+        }
+        None => UrlResolver::default(),
+    };
+    Ok(resolver.resolve(&url).ok_or_else(|| format!("Invalid URL: {}", url)))
+}
+
+fn this_url(this: &JsValue) -> JsResult<Url> {
+    this.as_object().and_then(url_from_object).ok_or_else(illegal_invocation)
+}
+
+fn illegal_invocation() -> boa_engine::JsError {
+    JsNativeError::typ().with_message("Illegal invocation").into()
+}
+
+/// Read a component with the URL Standard's getter steps
+fn component(url: &Url, name: &str) -> String {
+    match name {
+        "href" => quirks::href(url).to_string(),
+        "origin" => quirks::origin(url),
+        "protocol" => quirks::protocol(url).to_string(),
+        "username" => quirks::username(url).to_string(),
+        "password" => quirks::password(url).to_string(),
+        "host" => quirks::host(url).to_string(),
+        "hostname" => quirks::hostname(url).to_string(),
+        "port" => quirks::port(url).to_string(),
+        "pathname" => quirks::pathname(url).to_string(),
+        "search" => quirks::search(url).to_string(),
+        "hash" => quirks::hash(url).to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Write a component with the URL Standard's setter steps
+///
+/// Fails only for an invalid `href`.
+fn set_component(url: &mut Url, name: &str, value: &str) -> Result<(), ()> {
+    // The other setters leave the URL unchanged when the value is invalid
+    let _ = match name {
+        "href" => return quirks::set_href(url, value).map_err(|_| ()),
+        "protocol" => quirks::set_protocol(url, value),
+        "username" => quirks::set_username(url, value),
+        "password" => quirks::set_password(url, value),
+        "host" => quirks::set_host(url, value),
+        "hostname" => quirks::set_hostname(url, value),
+        "port" => quirks::set_port(url, value),
+        "pathname" => {
+            quirks::set_pathname(url, value);
+            Ok(())
+        }
+        "search" => {
+            quirks::set_search(url, value);
+            Ok(())
+        }
+        "hash" => {
+            quirks::set_hash(url, value);
+            Ok(())
+        }
+        _ => Ok(()),
+    };
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use boa_engine::Source;
+
+    fn eval(context: &mut Context, script: &str) -> String {
+        let value = context.eval(Source::from_bytes(script)).unwrap();
+        value.to_string(context).unwrap().to_std_string_escaped()
+    }
+
+    #[test]
+    fn test_url_components() {
+        let mut context = Context::default();
+        initialize_url_bindings(&mut context).unwrap();
+
+        let url = "var url = new URL('../img/a b.png?q=ü#top', 'https://user:pw@bücher.example:8080/docs/page.html'); \
+                   [url.protocol, url.username, url.hostname, url.port, url.pathname, url.search, url.hash, url.origin].join(' ')";
+        assert_eq!(eval(&mut context, url), "https: user xn--bcher-kva.example 8080 /img/a%20b.png ?q=%C3%BC #top https://xn--bcher-kva.example:8080");
+        assert_eq!(eval(&mut context, "url.port = '8443'; url.pathname = '/x'; url.hash = ''; url.href"), "https://user:pw@xn--bcher-kva.example:8443/x?q=%C3%BC");
+        assert_eq!(eval(&mut context, "url.port = 'abc'; url.host"), "xn--bcher-kva.example:8443");
+        assert_eq!(eval(&mut context, "JSON.stringify({ url }) + String(url instanceof URL)"), "{\"url\":\"https://user:pw@xn--bcher-kva.example:8443/x?q=%C3%BC\"}true");
+
+        assert_eq!(eval(&mut context, "try { new URL('relative.html'); 'parsed' } catch (e) { e.name }"), "TypeError");
+        assert_eq!(eval(&mut context, "try { url.href = 'not a url'; 'set' } catch (e) { e.name }"), "TypeError");
+        assert_eq!(eval(&mut context, "[URL.canParse('/a', 'https://example.com'), URL.canParse('/a'), URL.parse('/a')].join()"), "true,false,");
+    }
+}
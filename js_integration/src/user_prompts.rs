@@ -0,0 +1,203 @@
+//! # User Prompts
+//!
+//! This module implements `alert()`, `confirm()` and `prompt()`. Each call
+//! hands the prompt to the shell's handler, which shows it to the user and
+//! returns their answer to the script.
+//!
+//! ## Design Principles
+//!
+//! 1. **Blocking by Design**: The handler is called from the script's own
+//!    call and the script only continues once it returns, so the page's
+//!    event loop is paused while the prompt is up, as in browsers: timers
+//!    and input that come due meanwhile run after the user answers.
+//! 2. **The Shell Owns the UI**: Scripts never see how a prompt is shown.
+//!    The shell may show a window, ask on the terminal or answer by itself.
+//! 3. **Cancel Without a Handler**: An engine without a handler, such as a
+//!    headless one, dismisses every prompt: `confirm()` returns `false` and
+//!    `prompt()` returns `null`.
+
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::rc::Rc;
+use std::sync::Arc;
+use boa_engine::{js_string, Context, JsResult, JsValue, NativeFunction};
+use boa_gc::{Finalize, Trace};
+
+/// Message of the prompt shown when a page asks to confirm leaving it
+///
+/// Pages cannot choose the text, so they cannot use it to scare users
+/// into staying.
+pub const BEFORE_UNLOAD_MESSAGE: &str = "Leave site? Changes you made may not be saved.";
+
+/// A prompt a script shows
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UserPrompt {
+    /// `alert(message)`
+    Alert(String),
+    /// `confirm(message)`
+    Confirm(String),
+    /// `prompt(message, default)`
+    Prompt { message: String, default_value: String },
+    /// A `beforeunload` listener asked to confirm leaving the page; an
+    /// answer leaves, dismissing the prompt stays
+    BeforeUnload,
+}
+
+impl UserPrompt {
+    /// Get the message the page shows
+    pub fn message(&self) -> &str {
+        match self {
+            UserPrompt::Alert(message) | UserPrompt::Confirm(message) => message,
+            UserPrompt::Prompt { message, .. } => message,
+            UserPrompt::BeforeUnload => BEFORE_UNLOAD_MESSAGE,
+        }
+    }
+}
+
+impl fmt::Display for UserPrompt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UserPrompt::Alert(_) => write!(f, "alert"),
+            UserPrompt::Confirm(_) => write!(f, "confirm"),
+            UserPrompt::Prompt { .. } => write!(f, "prompt"),
+            UserPrompt::BeforeUnload => write!(f, "beforeunload"),
+        }
+    }
+}
+
+/// Callback showing a prompt to the user and waiting for their answer
+///
+/// Returns `None` when the user dismisses the prompt, and otherwise the
+/// text they entered; the text is ignored for alerts and confirms.
+pub type UserPromptHandler = Arc<dyn Fn(&UserPrompt) -> Option<String> + Send + Sync>;
+
+/// The prompt handler of a script context
+///
+/// Cloning shares the handler.
+#[derive(Clone, Default)]
+pub struct UserPrompts {
+    handler: Rc<RefCell<Option<UserPromptHandler>>>,
+    /// Whether prompts are dismissed without showing them, as in frames
+    /// sandboxed without `allow-modals`
+    blocked: Rc<Cell<bool>>,
+}
+
+impl UserPrompts {
+    /// Set the handler prompts are shown with
+    pub fn set_handler(&self, handler: UserPromptHandler) {
+        *self.handler.borrow_mut() = Some(handler);
+    }
+
+    /// Dismiss every prompt without showing it, or show them again
+    pub fn set_blocked(&self, blocked: bool) {
+        self.blocked.set(blocked);
+    }
+
+    /// Show a prompt, returning the user's answer
+    ///
+    /// Blocked prompts are dismissed at once.
+    pub fn show(&self, prompt: &UserPrompt) -> Option<String> {
+        if self.blocked.get() {
+            return None;
+        }
+        // Clone the handler so a handler that runs script can prompt again
+        let handler = self.handler.borrow().clone();
+        handler.and_then(|handler| handler(prompt))
+    }
+}
+
+impl fmt::Debug for UserPrompts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UserPrompts").field("has_handler", &self.handler.borrow().is_some()).finish()
+    }
+}
+
+/// Captures for the prompt functions
+#[derive(Clone, Trace, Finalize)]
+struct PromptsBinding {
+    #[unsafe_ignore_trace]
+    prompts: UserPrompts,
+}
+
+/// Convert an optional argument to a string, with `default` for a missing
+/// or undefined one
+fn string_or(value: Option<&JsValue>, default: &str, context: &mut Context) -> JsResult<String> {
+    match value {
+        Some(value) if !value.is_undefined() => Ok(value.to_string(context)?.to_std_string_escaped()),
+        _ => Ok(default.to_string()),
+    }
+}
+
+/// Install `alert()`, `confirm()` and `prompt()` on the global object
+///
+/// Returns the handle the engine sets the shell's handler on.
+pub fn initialize_user_prompt_bindings(context: &mut Context) -> JsResult<UserPrompts> {
+    let prompts = UserPrompts::default();
+    let binding = PromptsBinding { prompts: prompts.clone() };
+
+    let alert = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &PromptsBinding, context| {
+            let message = string_or(args.first(), "", context)?;
+            binding.prompts.show(&UserPrompt::Alert(message));
+            Ok(JsValue::undefined())
+        },
+        binding.clone(),
+    );
+    let confirm = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &PromptsBinding, context| {
+            let message = string_or(args.first(), "", context)?;
+            Ok(binding.prompts.show(&UserPrompt::Confirm(message)).is_some().into())
+        },
+        binding.clone(),
+    );
+    let prompt = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, binding: &PromptsBinding, context| {
+            let message = string_or(args.first(), "", context)?;
+            let default_value = string_or(args.get(1), "", context)?;
+            Ok(match binding.prompts.show(&UserPrompt::Prompt { message, default_value }) {
+                Some(answer) => js_string!(answer).into(),
+                None => JsValue::null(),
+            })
+        },
+        binding,
+    );
+
+    for (name, function) in [("alert", alert), ("confirm", confirm), ("prompt", prompt)] {
+        let function = function.to_js_function(context.realm());
+        context.global_object().set(js_string!(name), function, false, context)?;
+    }
+    Ok(prompts)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use super::UserPrompt;
+    use crate::JsEngine;
+
+    #[test]
+    fn test_prompts_return_the_users_answers() {
+        let mut engine = JsEngine::new();
+        let result = engine.execute("[confirm('Sure?'), prompt('Name?')].join()").unwrap();
+        assert_eq!(result.to_string(&mut engine.context).unwrap().to_std_string_escaped(), "false,");
+
+        let shown = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&shown);
+        engine.set_user_prompt_handler(Arc::new(move |prompt: &UserPrompt| {
+            log.lock().unwrap().push(prompt.clone());
+            match prompt {
+                UserPrompt::Prompt { default_value, .. } => Some(format!("{}!", default_value)),
+                UserPrompt::Confirm(message) => (message == "Yes?").then(String::new),
+                UserPrompt::Alert(_) | UserPrompt::BeforeUnload => None,
+            }
+        }));
+        let code = "alert('Hi', 'ignored');
+            [confirm('Yes?'), confirm('No?'), prompt('Name?', 'Ada'), typeof alert()].join()";
+        let result = engine.execute(code).unwrap();
+        assert_eq!(result.to_string(&mut engine.context).unwrap().to_std_string_escaped(), "true,false,Ada!,undefined");
+        let shown = shown.lock().unwrap();
+        assert_eq!(shown[0], UserPrompt::Alert("Hi".to_string()));
+        assert_eq!(shown[3], UserPrompt::Prompt { message: "Name?".to_string(), default_value: "Ada".to_string() });
+        assert_eq!(shown[4].message(), "");
+    }
+}
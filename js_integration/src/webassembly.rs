@@ -0,0 +1,983 @@
+//! # WebAssembly JavaScript API
+//!
+//! This module exposes the `WebAssembly` namespace to scripts on top of
+//! the wasmi engine: `validate`, `compile`, `instantiate`, the `Module`,
+//! `Instance`, `Memory`, `Table` and `Global` classes, the error classes
+//! and `compileStreaming`/`instantiateStreaming` for fetched responses.
+//!
+//! ## Design Principles
+//!
+//! 1. **One Store per Context**: Every module, instance, memory, table and
+//!    global a context creates lives in one wasmi store, so any of them can
+//!    be imported by any instance of that context.
+//! 2. **Synced Buffers**: wasmi keeps a memory's bytes in its store, so
+//!    the `ArrayBuffer` a script gets from `memory.buffer` is copied into
+//!    the memory whenever wasm starts running and back whenever it stops,
+//!    views stay in sync both ways. Growing the memory, from either side,
+//!    detaches the buffer, as in browsers.
+//! 3. **Settled Before Returning**: Compiling and instantiating run on the
+//!    calling thread, so the promises of `compile` and `instantiate` are
+//!    settled right away, like those of the Cache API.
+//! 4. **Streaming in Script**: `compileStreaming` and `instantiateStreaming`
+//!    are JavaScript glue that reads the response body with its reader, so
+//!    they accept whatever `Response` fetch or the Cache API produce, and
+//!    promises for them.
+//! 5. **Script Calls Suspend Wasm**: An imported script function runs
+//!    outside wasm: its host function suspends the call, the script runs
+//!    with the store released, so it can call back into wasm, and the call
+//!    resumes with its results. A script exception ends the call unchanged;
+//!    traps become `WebAssembly.RuntimeError`. Start functions cannot be
+//!    suspended, so they cannot call script functions.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use boa_engine::{
+    object::{builtins::{JsArray, JsArrayBuffer, JsPromise}, FunctionObjectBuilder, IntegrityLevel, ObjectInitializer},
+    property::{Attribute, PropertyDescriptor},
+    js_string, Context, JsBigInt, JsData, JsError, JsNativeError, JsObject, JsResult, JsValue, NativeFunction, Source,
+};
+use boa_gc::{Finalize, Trace};
+use wasmi::core::{HostError, Pages, ValType, F32, F64};
+use wasmi::errors::ErrorKind;
+use wasmi::{
+    Engine, Error, Extern, ExternRef, ExternType, Func, FuncRef, Global, Instance, Linker, Memory, MemoryType, Module, Mutability,
+    ResumableCall, Store, Table, TableType, Val,
+};
+
+use crate::file_api;
+
+/// Script part of the namespace: the error classes and the streaming
+/// functions
+const GLUE: &str = r#"
+(function (WebAssembly) {
+    'use strict';
+    class CompileError extends Error {}
+    class LinkError extends Error {}
+    class RuntimeError extends Error {}
+    for (const error of [CompileError, LinkError, RuntimeError]) {
+        Object.defineProperty(error.prototype, 'name', { value: error.name, writable: true, configurable: true });
+        Object.defineProperty(WebAssembly, error.name, { value: error, writable: true, configurable: true });
+    }
+
+    function contentType(headers) {
+        if (headers === null || typeof headers !== 'object') {
+            return null;
+        }
+        if (typeof headers.get === 'function') {
+            return headers.get('content-type');
+        }
+        for (const name of Object.keys(headers)) {
+            if (name.toLowerCase() === 'content-type') {
+                return String(headers[name]);
+            }
+        }
+        return null;
+    }
+
+    async function responseBytes(source) {
+        const response = await source;
+        if (response === null || typeof response !== 'object' || !('body' in response)) {
+            throw new TypeError('WebAssembly: Argument 0 must be a Response or a Promise for one');
+        }
+        const type = contentType(response.headers);
+        if (type === null || type.trim().toLowerCase() !== 'application/wasm') {
+            throw new TypeError(`WebAssembly: Incorrect response MIME type ${JSON.stringify(type)}. Expected 'application/wasm'.`);
+        }
+        if (response.ok === false) {
+            throw new TypeError(`WebAssembly: HTTP status ${response.status} of the response is not ok`);
+        }
+        const chunks = [];
+        let length = 0;
+        if (response.body) {
+            const reader = response.body.getReader();
+            for (;;) {
+                const { done, value } = await reader.read();
+                if (done) {
+                    break;
+                }
+                chunks.push(value);
+                length += value.length;
+            }
+        }
+        const bytes = new Uint8Array(length);
+        let offset = 0;
+        for (const chunk of chunks) {
+            bytes.set(chunk, offset);
+            offset += chunk.length;
+        }
+        return bytes;
+    }
+
+    const streaming = {
+        async compileStreaming(source) {
+            return WebAssembly.compile(await responseBytes(source));
+        },
+        async instantiateStreaming(source, imports) {
+            return WebAssembly.instantiate(await responseBytes(source), imports);
+        },
+    };
+    for (const name of Object.keys(streaming)) {
+        Object.defineProperty(WebAssembly, name, { value: streaming[name], writable: true, configurable: true });
+    }
+})
+"#;
+
+/// What the store keeps for the script side
+#[derive(Default)]
+struct StoreData {
+    /// The script functions instances import, by the index their host
+    /// functions report
+    imports: Vec<JsObject>,
+    /// The memories whose buffer scripts have seen, with that buffer
+    buffers: Vec<(Memory, JsArrayBuffer)>,
+}
+
+/// Raised by the host function of an imported script function, so the
+/// call leaves wasm and the script function runs
+#[derive(Debug)]
+struct ScriptCall {
+    import: usize,
+    params: Vec<Val>,
+}
+
+impl fmt::Display for ScriptCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "call of imported script function #{}", self.import)
+    }
+}
+
+impl HostError for ScriptCall {}
+
+/// The store, prototypes and error classes of the namespace
+#[derive(Clone, Trace, Finalize)]
+struct Bindings {
+    #[unsafe_ignore_trace]
+    store: Rc<RefCell<Store<StoreData>>>,
+    module: JsObject,
+    instance: JsObject,
+    memory: JsObject,
+    table: JsObject,
+    global: JsObject,
+    compile_error: JsObject,
+    link_error: JsObject,
+    runtime_error: JsObject,
+}
+
+#[derive(Trace, Finalize, JsData)]
+struct ModuleData {
+    #[unsafe_ignore_trace]
+    module: Rc<Module>,
+}
+
+#[derive(Trace, Finalize, JsData)]
+struct InstanceData {
+    #[unsafe_ignore_trace]
+    _instance: Instance,
+}
+
+#[derive(Trace, Finalize, JsData)]
+struct MemoryData {
+    #[unsafe_ignore_trace]
+    memory: Memory,
+}
+
+#[derive(Trace, Finalize, JsData)]
+struct TableData {
+    #[unsafe_ignore_trace]
+    table: Table,
+}
+
+#[derive(Trace, Finalize, JsData)]
+struct GlobalData {
+    #[unsafe_ignore_trace]
+    global: Global,
+}
+
+/// Captures for the script function of an exported wasm function
+#[derive(Trace, Finalize)]
+struct ExportedFunction {
+    #[unsafe_ignore_trace]
+    function: Func,
+    bindings: Bindings,
+}
+
+/// Register the `WebAssembly` namespace
+pub fn initialize_webassembly_bindings(context: &mut Context) -> JsResult<()> {
+    let namespace = JsObject::with_object_proto(context.intrinsics());
+    let glue = context.eval(Source::from_bytes(GLUE))?;
+    let glue = glue.as_object().ok_or_else(|| JsNativeError::typ().with_message("WebAssembly glue is not a function"))?;
+    glue.call(&JsValue::undefined(), &[namespace.clone().into()], context)?;
+    let mut error_class = |name: &str| -> JsResult<JsObject> {
+        namespace
+            .get(js_string!(name), context)?
+            .as_object()
+            .cloned()
+            .ok_or_else(|| JsNativeError::typ().with_message(format!("WebAssembly.{} is missing", name)).into())
+    };
+    let bindings = Bindings {
+        compile_error: error_class("CompileError")?,
+        link_error: error_class("LinkError")?,
+        runtime_error: error_class("RuntimeError")?,
+        store: Rc::new(RefCell::new(Store::new(&Engine::default(), StoreData::default()))),
+        module: JsObject::with_object_proto(context.intrinsics()),
+        instance: JsObject::with_object_proto(context.intrinsics()),
+        memory: JsObject::with_object_proto(context.intrinsics()),
+        table: JsObject::with_object_proto(context.intrinsics()),
+        global: JsObject::with_object_proto(context.intrinsics()),
+    };
+
+    let validate_function = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, bindings: &Bindings, context| {
+            let bytes = source_bytes(args.first(), context)?;
+            let engine = bindings.store.borrow().engine().clone();
+            Ok(Module::validate(&engine, &bytes).is_ok().into())
+        },
+        bindings.clone(),
+    );
+    let compile_function = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, bindings: &Bindings, context| {
+            let module = compile(args.first(), bindings, context).map(|module| module_object(module, bindings).into());
+            Ok(settle(module, context))
+        },
+        bindings.clone(),
+    );
+    let instantiate_function = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, bindings: &Bindings, context| {
+            let imports = args.get(1).cloned().unwrap_or_default();
+            let result = match args.first().and_then(module_of) {
+                // A module resolves to just its instance
+                Some(module) => instantiate(&module, &imports, bindings, context).map(JsValue::from),
+                None => compile(args.first(), bindings, context).and_then(|module| {
+                    let instance = instantiate(&module, &imports, bindings, context)?;
+                    let result = ObjectInitializer::new(context)
+                        .property(js_string!("module"), module_object(module, bindings), Attribute::all())
+                        .property(js_string!("instance"), instance, Attribute::all())
+                        .build();
+                    Ok(result.into())
+                }),
+            };
+            Ok(settle(result, context))
+        },
+        bindings.clone(),
+    );
+    for (name, length, function) in [("validate", 1, validate_function), ("compile", 1, compile_function), ("instantiate", 1, instantiate_function)] {
+        define_method(&namespace, name, length, function, context)?;
+    }
+
+    let classes = [
+        ("Module", 1, &bindings.module, NativeFunction::from_copy_closure_with_captures(construct_module, bindings.clone())),
+        ("Instance", 1, &bindings.instance, NativeFunction::from_copy_closure_with_captures(construct_instance, bindings.clone())),
+        ("Memory", 1, &bindings.memory, NativeFunction::from_copy_closure_with_captures(construct_memory, bindings.clone())),
+        ("Table", 1, &bindings.table, NativeFunction::from_copy_closure_with_captures(construct_table, bindings.clone())),
+        ("Global", 1, &bindings.global, NativeFunction::from_copy_closure_with_captures(construct_global, bindings.clone())),
+    ];
+    for (name, length, prototype, construct) in classes {
+        let constructor = define_class(name, length, prototype, construct, context)?;
+        namespace.define_property_or_throw(
+            js_string!(name),
+            PropertyDescriptor::builder().value(constructor.clone()).writable(true).enumerable(false).configurable(true),
+            context,
+        )?;
+        if name == "Module" {
+            let exports = NativeFunction::from_fn_ptr(|_this, args, context| module_descriptions(args, false, context));
+            let imports = NativeFunction::from_fn_ptr(|_this, args, context| module_descriptions(args, true, context));
+            define_method(&constructor, "exports", 1, exports, context)?;
+            define_method(&constructor, "imports", 1, imports, context)?;
+        }
+    }
+
+    initialize_memory_prototype(&bindings, context)?;
+    initialize_table_prototype(&bindings, context)?;
+    initialize_global_prototype(&bindings, context)?;
+
+    context.register_global_property(js_string!("WebAssembly"), namespace, Attribute::WRITABLE | Attribute::CONFIGURABLE)?;
+    Ok(())
+}
+
+
+fn initialize_memory_prototype(bindings: &Bindings, context: &mut Context) -> JsResult<()> {
+    let buffer = NativeFunction::from_copy_closure_with_captures(
+        |this, _args, bindings: &Bindings, context| {
+            let memory = this_data::<MemoryData, _>(this, |data| data.memory)?;
+            let mut store = bindings.store.borrow_mut();
+            if let Some((_, buffer)) = store.data().buffers.iter().find(|(seen, _)| same_memory(seen, &memory)) {
+                return Ok(buffer.clone().into());
+            }
+            let buffer = JsArrayBuffer::from_byte_block(memory.data(&*store).to_vec(), context)?;
+            store.data_mut().buffers.push((memory, buffer.clone()));
+            Ok(buffer.into())
+        },
+        bindings.clone(),
+    );
+    define_getter(&bindings.memory, "buffer", buffer, None, context)?;
+    let grow = NativeFunction::from_copy_closure_with_captures(
+        |this, args, bindings: &Bindings, context| {
+            let memory = this_data::<MemoryData, _>(this, |data| data.memory)?;
+            let delta = index_argument(args.first(), "delta", context)?;
+            let previous = run_wasm(bindings, |store| Pages::new(delta).and_then(|delta| memory.grow(store, delta).ok()));
+            match previous {
+                Some(pages) => Ok(u32::from(pages).into()),
+                None => Err(JsNativeError::range().with_message("WebAssembly.Memory.grow(): Maximum memory size exceeded").into()),
+            }
+        },
+        bindings.clone(),
+    );
+    define_method(&bindings.memory, "grow", 1, grow, context)
+}
+
+fn initialize_table_prototype(bindings: &Bindings, context: &mut Context) -> JsResult<()> {
+    let prototype = &bindings.table;
+    let length = NativeFunction::from_copy_closure_with_captures(
+        |this, _args, bindings: &Bindings, _context| {
+            let table = this_data::<TableData, _>(this, |data| data.table)?;
+            let length = table.size(&*bindings.store.borrow());
+            Ok(length.into())
+        },
+        bindings.clone(),
+    );
+    define_getter(prototype, "length", length, None, context)?;
+    let get = NativeFunction::from_copy_closure_with_captures(
+        |this, args, bindings: &Bindings, context| {
+            let table = this_data::<TableData, _>(this, |data| data.table)?;
+            let index = index_argument(args.first(), "index", context)?;
+            let element = table.get(&*bindings.store.borrow(), index);
+            match element {
+                Some(element) => Ok(to_js(&element, bindings, context)),
+                None => Err(JsNativeError::range().with_message("WebAssembly.Table.get(): invalid address").into()),
+            }
+        },
+        bindings.clone(),
+    );
+    define_method(prototype, "get", 1, get, context)?;
+    let grow = NativeFunction::from_copy_closure_with_captures(
+        |this, args, bindings: &Bindings, context| {
+            let table = this_data::<TableData, _>(this, |data| data.table)?;
+            let delta = index_argument(args.first(), "delta", context)?;
+            let previous = table.grow(&mut *bindings.store.borrow_mut(), delta, Val::FuncRef(FuncRef::null()));
+            match previous {
+                Ok(length) => Ok(length.into()),
+                Err(_) => Err(JsNativeError::range().with_message("WebAssembly.Table.grow(): Maximum table size exceeded").into()),
+            }
+        },
+        bindings.clone(),
+    );
+    define_method(prototype, "grow", 1, grow, context)
+}
+
+fn initialize_global_prototype(bindings: &Bindings, context: &mut Context) -> JsResult<()> {
+    let get = NativeFunction::from_copy_closure_with_captures(
+        |this, _args, bindings: &Bindings, context| {
+            let global = this_data::<GlobalData, _>(this, |data| data.global)?;
+            let value = global.get(&*bindings.store.borrow());
+            Ok(to_js(&value, bindings, context))
+        },
+        bindings.clone(),
+    );
+    let set = NativeFunction::from_copy_closure_with_captures(
+        |this, args, bindings: &Bindings, context| {
+            let global = this_data::<GlobalData, _>(this, |data| data.global)?;
+            let ty = global.ty(&*bindings.store.borrow());
+            if ty.mutability().is_const() {
+                return Err(JsNativeError::typ().with_message("WebAssembly.Global: Can't set the value of an immutable global").into());
+            }
+            let value = from_js(&args.first().cloned().unwrap_or_default(), ty.content(), context)?;
+            global.set(&mut *bindings.store.borrow_mut(), value).map_err(|error| JsNativeError::typ().with_message(error.to_string()))?;
+            Ok(JsValue::undefined())
+        },
+        bindings.clone(),
+    );
+    define_getter(&bindings.global, "value", get.clone(), Some(set), context)?;
+    define_method(&bindings.global, "valueOf", 0, get, context)
+}
+
+/// `new WebAssembly.Module(bytes)`
+fn construct_module(new_target: &JsValue, args: &[JsValue], bindings: &Bindings, context: &mut Context) -> JsResult<JsValue> {
+    require_new(new_target, "Module")?;
+    let module = compile(args.first(), bindings, context)?;
+    Ok(module_object(module, bindings).into())
+}
+
+/// `new WebAssembly.Instance(module, imports)`
+fn construct_instance(new_target: &JsValue, args: &[JsValue], bindings: &Bindings, context: &mut Context) -> JsResult<JsValue> {
+    require_new(new_target, "Instance")?;
+    let module = args.first().and_then(module_of).ok_or_else(|| {
+        JsNativeError::typ().with_message("WebAssembly.Instance(): Argument 0 must be a WebAssembly.Module")
+    })?;
+    Ok(instantiate(&module, &args.get(1).cloned().unwrap_or_default(), bindings, context)?.into())
+}
+
+/// `new WebAssembly.Memory({ initial, maximum })`
+fn construct_memory(new_target: &JsValue, args: &[JsValue], bindings: &Bindings, context: &mut Context) -> JsResult<JsValue> {
+    require_new(new_target, "Memory")?;
+    let (minimum, maximum) = descriptor_limits(args.first(), "Memory", context)?;
+    let memory = MemoryType::new(minimum, maximum)
+        .and_then(|ty| Memory::new(&mut *bindings.store.borrow_mut(), ty))
+        .map_err(|error| JsNativeError::range().with_message(format!("WebAssembly.Memory(): {}", error)))?;
+    Ok(JsObject::from_proto_and_data(Some(bindings.memory.clone()), MemoryData { memory }).into())
+}
+
+/// `new WebAssembly.Table({ element: "anyfunc", initial, maximum })`
+fn construct_table(new_target: &JsValue, args: &[JsValue], bindings: &Bindings, context: &mut Context) -> JsResult<JsValue> {
+    require_new(new_target, "Table")?;
+    let element = match args.first().and_then(JsValue::as_object) {
+        Some(descriptor) => descriptor.get(js_string!("element"), context)?.to_string(context)?.to_std_string_escaped(),
+        None => String::new(),
+    };
+    if element != "anyfunc" && element != "funcref" {
+        return Err(JsNativeError::typ().with_message("WebAssembly.Table(): Descriptor property 'element' must be 'anyfunc'").into());
+    }
+    let (minimum, maximum) = descriptor_limits(args.first(), "Table", context)?;
+    let ty = TableType::new(ValType::FuncRef, minimum, maximum);
+    let table = Table::new(&mut *bindings.store.borrow_mut(), ty, Val::FuncRef(FuncRef::null()))
+        .map_err(|error| JsNativeError::range().with_message(format!("WebAssembly.Table(): {}", error)))?;
+    Ok(JsObject::from_proto_and_data(Some(bindings.table.clone()), TableData { table }).into())
+}
+
+/// `new WebAssembly.Global({ value, mutable }, value)`
+fn construct_global(new_target: &JsValue, args: &[JsValue], bindings: &Bindings, context: &mut Context) -> JsResult<JsValue> {
+    require_new(new_target, "Global")?;
+    let descriptor = args.first().and_then(JsValue::as_object).ok_or_else(|| {
+        JsNativeError::typ().with_message("WebAssembly.Global(): Argument 0 must be a global descriptor")
+    })?;
+    let mutability = if descriptor.get(js_string!("mutable"), context)?.to_boolean() { Mutability::Var } else { Mutability::Const };
+    let name = descriptor.get(js_string!("value"), context)?.to_string(context)?.to_std_string_escaped();
+    let value_type = value_type(&name).ok_or_else(|| {
+        JsNativeError::typ().with_message("WebAssembly.Global(): Descriptor property 'value' must be a WebAssembly type")
+    })?;
+    let value = match args.get(1).filter(|value| !value.is_undefined()) {
+        Some(value) => from_js(value, value_type, context)?,
+        None => Val::default(value_type),
+    };
+    let global = Global::new(&mut *bindings.store.borrow_mut(), value, mutability);
+    Ok(global_object(global, bindings).into())
+}
+
+/// `WebAssembly.Module.exports(module)` and `WebAssembly.Module.imports(module)`
+fn module_descriptions(args: &[JsValue], imports: bool, context: &mut Context) -> JsResult<JsValue> {
+    let module = args.first().and_then(module_of).ok_or_else(|| {
+        JsNativeError::typ().with_message("WebAssembly.Module: Argument 0 must be a WebAssembly.Module")
+    })?;
+    let descriptions: Vec<JsValue> = if imports {
+        module
+            .imports()
+            .map(|import| {
+                ObjectInitializer::new(context)
+                    .property(js_string!("module"), js_string!(import.module()), Attribute::all())
+                    .property(js_string!("name"), js_string!(import.name()), Attribute::all())
+                    .property(js_string!("kind"), js_string!(kind_name(import.ty())), Attribute::all())
+                    .build()
+                    .into()
+            })
+            .collect()
+    } else {
+        module
+            .exports()
+            .map(|export| {
+                ObjectInitializer::new(context)
+                    .property(js_string!("name"), js_string!(export.name()), Attribute::all())
+                    .property(js_string!("kind"), js_string!(kind_name(export.ty())), Attribute::all())
+                    .build()
+                    .into()
+            })
+            .collect()
+    };
+    Ok(JsArray::from_iter(descriptions, context).into())
+}
+
+fn compile(source: Option<&JsValue>, bindings: &Bindings, context: &mut Context) -> JsResult<Rc<Module>> {
+    let bytes = source_bytes(source, context)?;
+    let engine = bindings.store.borrow().engine().clone();
+    Module::new(&engine, &bytes).map(Rc::new).map_err(|error| script_error(&bindings.compile_error, &error.to_string(), context))
+}
+
+/// Instantiate a module, returning its `Instance` object
+fn instantiate(module: &Module, imports: &JsValue, bindings: &Bindings, context: &mut Context) -> JsResult<JsObject> {
+    let imports = resolve_imports(module, imports, bindings, context)?;
+    let mut linker = Linker::new(module.engine());
+    let started = run_wasm(bindings, |store| {
+        for (module_name, name, value) in imports {
+            // A name imported twice resolves to its first value
+            if linker.get(&*store, &module_name, &name).is_none() {
+                linker.define(&module_name, &name, value)?;
+            }
+        }
+        linker.instantiate(&mut *store, module)?.start(&mut *store)
+    });
+    let instance = started.map_err(|error| wasm_error(error, bindings, context))?;
+    let exports: Vec<(String, Extern)> = instance
+        .exports(&*bindings.store.borrow())
+        .map(|export| (export.name().to_string(), export.into_extern()))
+        .collect();
+    let exports_object = JsObject::with_null_proto();
+    for (name, value) in exports {
+        let value: JsValue = match value {
+            Extern::Func(function) => function_object(function, &name, bindings, context).into(),
+            Extern::Memory(memory) => JsObject::from_proto_and_data(Some(bindings.memory.clone()), MemoryData { memory }).into(),
+            Extern::Table(table) => JsObject::from_proto_and_data(Some(bindings.table.clone()), TableData { table }).into(),
+            Extern::Global(global) => global_object(global, bindings).into(),
+        };
+        exports_object.define_property_or_throw(
+            js_string!(name.as_str()),
+            PropertyDescriptor::builder().value(value).writable(false).enumerable(true).configurable(false),
+            context,
+        )?;
+    }
+    exports_object.set_integrity_level(IntegrityLevel::Frozen, context)?;
+    let object = JsObject::from_proto_and_data(Some(bindings.instance.clone()), InstanceData { _instance: instance });
+    object.define_property_or_throw(
+        js_string!("exports"),
+        PropertyDescriptor::builder().value(exports_object).writable(false).enumerable(true).configurable(false),
+        context,
+    )?;
+    Ok(object)
+}
+
+/// Look up the values a module imports in the import object, as module
+/// name, name and value
+fn resolve_imports(module: &Module, imports: &JsValue, bindings: &Bindings, context: &mut Context) -> JsResult<Vec<(String, String, Extern)>> {
+    if module.imports().len() == 0 {
+        return Ok(Vec::new());
+    }
+    let imports = imports.as_object().ok_or_else(|| {
+        JsNativeError::typ().with_message("WebAssembly.Instance(): Imports argument must be present and must be an object")
+    })?;
+    let mut resolved = Vec::new();
+    for (index, import) in module.imports().enumerate() {
+        let namespace = imports.get(js_string!(import.module()), context)?;
+        let namespace = namespace.as_object().ok_or_else(|| {
+            JsNativeError::typ().with_message(format!("WebAssembly.Instance(): Import #{} \"{}\": module is not an object or function", index, import.module()))
+        })?;
+        let value = namespace.get(js_string!(import.name()), context)?;
+        let object = value.as_object();
+        let link_error = |message: &str, context: &mut Context| {
+            let message = format!("Import #{} \"{}\" \"{}\": {}", index, import.module(), import.name(), message);
+            script_error(&bindings.link_error, &message, context)
+        };
+        let extern_value = match import.ty() {
+            ExternType::Func(ty) => match object.filter(|object| object.is_callable()) {
+                Some(callable) => {
+                    let mut store = bindings.store.borrow_mut();
+                    let slot = store.data().imports.len();
+                    store.data_mut().imports.push(callable.clone());
+                    let function = Func::new(&mut *store, ty.clone(), move |_caller, params, _results| {
+                        Err(Error::host(ScriptCall { import: slot, params: params.to_vec() }))
+                    });
+                    Extern::Func(function)
+                }
+                None => return Err(link_error("function import requires a callable", context)),
+            },
+            ExternType::Memory(_) => match object.and_then(|object| object.downcast_ref::<MemoryData>().map(|data| data.memory)) {
+                Some(memory) => Extern::Memory(memory),
+                None => return Err(link_error("memory import must be a WebAssembly.Memory object", context)),
+            },
+            ExternType::Table(_) => match object.and_then(|object| object.downcast_ref::<TableData>().map(|data| data.table)) {
+                Some(table) => Extern::Table(table),
+                None => return Err(link_error("table import requires a WebAssembly.Table", context)),
+            },
+            ExternType::Global(ty) => match object.and_then(|object| object.downcast_ref::<GlobalData>().map(|data| data.global)) {
+                Some(global) => Extern::Global(global),
+                // Plain numbers make immutable globals
+                None if ty.mutability().is_const() && (value.is_number() || value.is_bigint()) => {
+                    if (ty.content() == ValType::I64) != value.is_bigint() {
+                        return Err(link_error("global import must be a BigInt for i64 and a Number otherwise", context));
+                    }
+                    let value = from_js(&value, ty.content(), context)?;
+                    Extern::Global(Global::new(&mut *bindings.store.borrow_mut(), value, Mutability::Const))
+                }
+                None => return Err(link_error("global import must be a number, BigInt or WebAssembly.Global object", context)),
+            },
+        };
+        resolved.push((import.module().to_string(), import.name().to_string(), extern_value));
+    }
+    Ok(resolved)
+}
+
+/// Create the script function of a wasm function
+fn function_object(function: Func, name: &str, bindings: &Bindings, context: &mut Context) -> JsObject {
+    let length = function.ty(&*bindings.store.borrow()).params().len();
+    let call = NativeFunction::from_copy_closure_with_captures(
+        |_this, args, exported: &ExportedFunction, context| {
+            let bindings = &exported.bindings;
+            let ty = exported.function.ty(&*bindings.store.borrow());
+            let args = ty
+                .params()
+                .iter()
+                .enumerate()
+                .map(|(index, param)| from_js(&args.get(index).cloned().unwrap_or_default(), *param, context))
+                .collect::<JsResult<Vec<_>>>()?;
+            let results = call_function(exported.function, &args, bindings, context)?;
+            Ok(match results.as_slice() {
+                [] => JsValue::undefined(),
+                [result] => to_js(result, bindings, context),
+                results => {
+                    let values: Vec<JsValue> = results.iter().map(|result| to_js(result, bindings, context)).collect();
+                    JsArray::from_iter(values, context).into()
+                }
+            })
+        },
+        ExportedFunction { function, bindings: bindings.clone() },
+    );
+    FunctionObjectBuilder::new(context.realm(), call).name(js_string!(name)).length(length).build().into()
+}
+
+/// Call a wasm function, running the script functions it calls on the way
+fn call_function(function: Func, args: &[Val], bindings: &Bindings, context: &mut Context) -> JsResult<Vec<Val>> {
+    let ty = function.ty(&*bindings.store.borrow());
+    let mut results: Vec<Val> = ty.results().iter().map(|result| Val::default(*result)).collect();
+    let mut call = run_wasm(bindings, |store| function.call_resumable(store, args, &mut results));
+    loop {
+        let invocation = match call {
+            Ok(ResumableCall::Finished) => return Ok(results),
+            Ok(ResumableCall::Resumable(invocation)) => invocation,
+            Err(error) => return Err(wasm_error(error, bindings, context)),
+        };
+        let Some(ScriptCall { import, params }) = invocation.host_error().downcast_ref::<ScriptCall>() else {
+            return Err(script_error(&bindings.runtime_error, &invocation.host_error().to_string(), context));
+        };
+        let (callable, result_types) = {
+            let store = bindings.store.borrow();
+            (store.data().imports[*import].clone(), invocation.host_func().ty(&*store).results().to_vec())
+        };
+        let args: Vec<JsValue> = params.iter().map(|param| to_js(param, bindings, context)).collect();
+        let returned = callable.call(&JsValue::undefined(), &args, context)?;
+        let returned = results_from_js(&returned, &result_types, context)?;
+        call = run_wasm(bindings, |store| invocation.resume(store, &returned, &mut results));
+    }
+}
+
+/// Run wasm on the store, with the buffers scripts hold copied into their
+/// memories before and back after
+fn run_wasm<T>(bindings: &Bindings, run: impl FnOnce(&mut Store<StoreData>) -> T) -> T {
+    let mut store = bindings.store.borrow_mut();
+    load_buffers(&mut store);
+    let result = run(&mut store);
+    store_buffers(&mut store);
+    result
+}
+
+/// Copy the buffers scripts hold into their memories
+fn load_buffers(store: &mut Store<StoreData>) {
+    for (memory, buffer) in store.data().buffers.clone() {
+        if let Some(bytes) = buffer.data() {
+            let data = memory.data_mut(&mut *store);
+            if data.len() == bytes.len() {
+                data.copy_from_slice(&bytes);
+            }
+        }
+    }
+}
+
+/// Copy the memories into the buffers scripts hold, detaching the buffers
+/// of memories that grew
+fn store_buffers(store: &mut Store<StoreData>) {
+    let mut buffers = std::mem::take(&mut store.data_mut().buffers);
+    buffers.retain(|(memory, buffer)| {
+        let bytes = memory.data(&*store);
+        let copied = match buffer.data_mut() {
+            Some(mut data) if data.len() == bytes.len() => {
+                data.copy_from_slice(bytes);
+                true
+            }
+            _ => false,
+        };
+        if !copied {
+            // Detaching empties the views scripts hold on the old buffer
+            let _ = buffer.detach(&JsValue::undefined());
+        }
+        copied
+    });
+    store.data_mut().buffers = buffers;
+}
+
+/// Whether two handles name the same memory; wasmi handles do not compare,
+/// but their debug form names the entity in the store
+fn same_memory(a: &Memory, b: &Memory) -> bool {
+    format!("{:?}", a) == format!("{:?}", b)
+}
+
+fn module_object(module: Rc<Module>, bindings: &Bindings) -> JsObject {
+    JsObject::from_proto_and_data(Some(bindings.module.clone()), ModuleData { module })
+}
+
+fn global_object(global: Global, bindings: &Bindings) -> JsObject {
+    JsObject::from_proto_and_data(Some(bindings.global.clone()), GlobalData { global })
+}
+
+fn module_of(value: &JsValue) -> Option<Rc<Module>> {
+    value.as_object().and_then(|object| object.downcast_ref::<ModuleData>().map(|data| Rc::clone(&data.module)))
+}
+
+/// Turn an engine error into the exception scripts see
+fn wasm_error(error: Error, bindings: &Bindings, context: &mut Context) -> JsError {
+    let class = match error.kind() {
+        ErrorKind::Linker(_) | ErrorKind::Instantiation(_) => &bindings.link_error,
+        _ => &bindings.runtime_error,
+    };
+    script_error(class, &error.to_string(), context)
+}
+
+/// Create an exception of one of the namespace's error classes
+fn script_error(class: &JsObject, message: &str, context: &mut Context) -> JsError {
+    match class.construct(&[js_string!(message).into()], None, context) {
+        Ok(exception) => JsError::from_opaque(exception.into()),
+        Err(error) => error,
+    }
+}
+
+/// The name of an import or export kind, as `Module.imports` reports it
+fn kind_name(ty: &ExternType) -> &'static str {
+    match ty {
+        ExternType::Func(_) => "function",
+        ExternType::Table(_) => "table",
+        ExternType::Memory(_) => "memory",
+        ExternType::Global(_) => "global",
+    }
+}
+
+/// The value type a global descriptor names
+fn value_type(name: &str) -> Option<ValType> {
+    match name {
+        "i32" => Some(ValType::I32),
+        "i64" => Some(ValType::I64),
+        "f32" => Some(ValType::F32),
+        "f64" => Some(ValType::F64),
+        _ => None,
+    }
+}
+
+fn to_js(value: &Val, bindings: &Bindings, context: &mut Context) -> JsValue {
+    match value {
+        Val::I32(value) => (*value).into(),
+        Val::I64(value) => JsBigInt::new(*value).into(),
+        Val::F32(value) => f64::from(value.to_float()).into(),
+        Val::F64(value) => value.to_float().into(),
+        Val::FuncRef(reference) => match reference.func() {
+            Some(function) => function_object(*function, "", bindings, context).into(),
+            None => JsValue::null(),
+        },
+        // Scripts only pass null external references in
+        Val::ExternRef(_) => JsValue::null(),
+    }
+}
+
+fn from_js(value: &JsValue, value_type: ValType, context: &mut Context) -> JsResult<Val> {
+    Ok(match value_type {
+        ValType::I32 => Val::I32(value.to_i32(context)?),
+        ValType::I64 => Val::I64(value.to_big_int64(context)?),
+        ValType::F32 => Val::F32(F32::from_float(value.to_number(context)? as f32)),
+        ValType::F64 => Val::F64(F64::from_float(value.to_number(context)?)),
+        ValType::FuncRef if value.is_null() => Val::FuncRef(FuncRef::null()),
+        ValType::ExternRef if value.is_null() => Val::ExternRef(ExternRef::null()),
+        ValType::FuncRef | ValType::ExternRef => {
+            return Err(JsNativeError::typ().with_message("WebAssembly: only null converts to a reference").into());
+        }
+    })
+}
+
+/// Convert what an imported function returned to its result types
+fn results_from_js(result: &JsValue, types: &[ValType], context: &mut Context) -> JsResult<Vec<Val>> {
+    match types {
+        [] => Ok(Vec::new()),
+        [value_type] => Ok(vec![from_js(result, *value_type, context)?]),
+        types => {
+            let values = result.as_object().ok_or_else(|| JsNativeError::typ().with_message("multi-return value must be iterable"))?;
+            types
+                .iter()
+                .enumerate()
+                .map(|(index, value_type)| from_js(&values.get(index, context)?, *value_type, context))
+                .collect()
+        }
+    }
+}
+
+/// Read the bytes of a buffer source argument
+fn source_bytes(source: Option<&JsValue>, context: &mut Context) -> JsResult<Vec<u8>> {
+    match source.and_then(JsValue::as_object) {
+        Some(object) => file_api::buffer_bytes(object, context)?,
+        None => None,
+    }
+    .ok_or_else(|| JsNativeError::typ().with_message("WebAssembly: Argument 0 must be a buffer source").into())
+}
+
+/// Read the `initial` and `maximum` of a memory or table descriptor
+fn descriptor_limits(descriptor: Option<&JsValue>, class: &str, context: &mut Context) -> JsResult<(u32, Option<u32>)> {
+    let descriptor = descriptor.and_then(JsValue::as_object).ok_or_else(|| {
+        JsNativeError::typ().with_message(format!("WebAssembly.{}(): Argument 0 must be a {} descriptor", class, class.to_lowercase()))
+    })?;
+    let initial = descriptor.get(js_string!("initial"), context)?;
+    if initial.is_undefined() {
+        return Err(JsNativeError::typ().with_message(format!("WebAssembly.{}(): Property 'initial' is required", class)).into());
+    }
+    let minimum = index_argument(Some(&initial), "initial", context)?;
+    let maximum = descriptor.get(js_string!("maximum"), context)?;
+    let maximum = if maximum.is_undefined() { None } else { Some(index_argument(Some(&maximum), "maximum", context)?) };
+    if maximum.is_some_and(|maximum| maximum < minimum) {
+        return Err(JsNativeError::range().with_message(format!("WebAssembly.{}(): Property 'maximum' must not be less than 'initial'", class)).into());
+    }
+    Ok((minimum, maximum))
+}
+/// Convert an argument to a 32-bit unsigned index, as `[EnforceRange]`
+fn index_argument(value: Option<&JsValue>, name: &str, context: &mut Context) -> JsResult<u32> {
+    let number = value.cloned().unwrap_or_default().to_number(context)?;
+    if !number.is_finite() || number < 0.0 || number.trunc() > f64::from(u32::MAX) {
+        return Err(JsNativeError::typ().with_message(format!("WebAssembly: '{}' must be convertible to a valid number", name)).into());
+    }
+    Ok(number.trunc() as u32)
+}
+
+fn this_data<D: JsData + Trace + 'static, T>(this: &JsValue, read: impl FnOnce(&D) -> T) -> JsResult<T> {
+    this.as_object()
+        .and_then(|object| object.downcast_ref::<D>().map(|data| read(&data)))
+        .ok_or_else(|| JsNativeError::typ().with_message("Illegal invocation").into())
+}
+
+fn require_new(new_target: &JsValue, class: &str) -> JsResult<()> {
+    if new_target.is_undefined() {
+        return Err(JsNativeError::typ().with_message(format!("WebAssembly.{}: Please use the 'new' operator", class)).into());
+    }
+    Ok(())
+}
+
+/// Turn the outcome of a function into a settled promise
+fn settle(result: JsResult<JsValue>, context: &mut Context) -> JsValue {
+    match result {
+        Ok(value) => JsPromise::resolve(value, context).into(),
+        Err(error) => JsPromise::reject(error, context).into(),
+    }
+}
+
+fn define_class(name: &str, length: usize, prototype: &JsObject, construct: NativeFunction, context: &mut Context) -> JsResult<JsObject> {
+    let constructor: JsObject = FunctionObjectBuilder::new(context.realm(), construct)
+        .name(js_string!(name))
+        .length(length)
+        .constructor(true)
+        .build()
+        .into();
+    constructor.define_property_or_throw(
+        js_string!("prototype"),
+        PropertyDescriptor::builder().value(prototype.clone()).writable(false).enumerable(false).configurable(false),
+        context,
+    )?;
+    prototype.define_property_or_throw(
+        js_string!("constructor"),
+        PropertyDescriptor::builder().value(constructor.clone()).writable(true).enumerable(false).configurable(true),
+        context,
+    )?;
+    Ok(constructor)
+}
+
+fn define_method(object: &JsObject, name: &str, length: usize, function: NativeFunction, context: &mut Context) -> JsResult<()> {
+    let function = FunctionObjectBuilder::new(context.realm(), function).name(js_string!(name)).length(length).build();
+    object.define_property_or_throw(
+        js_string!(name),
+        PropertyDescriptor::builder().value(function).writable(true).enumerable(true).configurable(true),
+        context,
+    )?;
+    Ok(())
+}
+
+fn define_getter(object: &JsObject, name: &str, get: NativeFunction, set: Option<NativeFunction>, context: &mut Context) -> JsResult<()> {
+    let mut descriptor = PropertyDescriptor::builder().get(get.to_js_function(context.realm())).enumerable(true).configurable(true);
+    if let Some(set) = set {
+        descriptor = descriptor.set(set.to_js_function(context.realm()));
+    }
+    object.define_property_or_throw(js_string!(name), descriptor, context)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::fetch_binding::{FetchBinding, StreamingFetchResponse};
+    use crate::readable_stream::ReadableStream;
+
+    /// A module importing `env.log` and `env.memory`, exporting `add` and
+    /// `checksum`, which sums the bytes below its argument and logs the
+    /// sum
+    const MODULE: [u8; 128] = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        // Types: (i32) -> (), (i32, i32) -> (i32), (i32) -> (i32)
+        0x01, 0x10, 0x03, 0x60, 0x01, 0x7f, 0x00, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f, 0x60, 0x01, 0x7f, 0x01, 0x7f,
+        // Imports: env.log, env.memory
+        0x02, 0x19, 0x02, 0x03, b'e', b'n', b'v', 0x03, b'l', b'o', b'g', 0x00, 0x00,
+        0x03, b'e', b'n', b'v', 0x06, b'm', b'e', b'm', b'o', b'r', b'y', 0x02, 0x00, 0x01,
+        // Functions
+        0x03, 0x03, 0x02, 0x01, 0x02,
+        // Exports: add, checksum
+        0x07, 0x12, 0x02, 0x03, b'a', b'd', b'd', 0x00, 0x01, 0x08, b'c', b'h', b'e', b'c', b'k', b's', b'u', b'm', 0x00, 0x02,
+        // Code
+        0x0a, 0x30, 0x02,
+        0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0x6a, 0x0b,
+        0x26, 0x01, 0x01, 0x7f, 0x02, 0x40, 0x03, 0x40, 0x20, 0x00, 0x45, 0x0d, 0x01, 0x20, 0x00, 0x41, 0x01, 0x6b, 0x22, 0x00,
+        0x2d, 0x00, 0x00, 0x20, 0x01, 0x6a, 0x21, 0x01, 0x0c, 0x00, 0x0b, 0x0b, 0x20, 0x01, 0x10, 0x00, 0x20, 0x01, 0x0b,
+    ];
+
+    fn eval(context: &mut Context, script: &str) -> String {
+        let value = context.eval(Source::from_bytes(script)).unwrap();
+        context.run_jobs();
+        value.to_string(context).unwrap().to_std_string_escaped()
+    }
+
+    #[test]
+    fn test_modules_run_with_script_imports() {
+        let mut context = Context::default();
+        initialize_webassembly_bindings(&mut context).unwrap();
+        let bytes = MODULE.iter().map(|byte| byte.to_string()).collect::<Vec<_>>().join(",");
+        eval(&mut context, &format!("var bytes = new Uint8Array([{}]);", bytes));
+
+        assert_eq!(eval(&mut context, "WebAssembly.validate(bytes) + ' ' + WebAssembly.validate(new Uint8Array([0, 97]))"), "true false");
+        assert_eq!(eval(&mut context, "JSON.stringify(WebAssembly.Module.imports(new WebAssembly.Module(bytes)))"),
+            "[{\"module\":\"env\",\"name\":\"log\",\"kind\":\"function\"},{\"module\":\"env\",\"name\":\"memory\",\"kind\":\"memory\"}]");
+
+        // Scripts write into the memory the module reads, through its buffer
+        let script = "var logged = []; var memory = new WebAssembly.Memory({ initial: 1, maximum: 2 }); \
+                      var view = new Uint8Array(memory.buffer); view.set([1, 2, 3, 4]); \
+                      var imports = { env: { log: (sum) => logged.push(sum), memory } }; \
+                      var result; WebAssembly.instantiate(bytes, imports).then(({ module, instance }) => { \
+                          result = [module instanceof WebAssembly.Module, instance.exports.add(2, 40), instance.exports.checksum(4)]; }); \
+                      'pending'";
+        assert_eq!(eval(&mut context, script), "pending");
+        assert_eq!(eval(&mut context, "result.join() + ' ' + logged.join()"), "true,42,10 10");
+
+        // Growing detaches the old buffer and keeps the bytes
+        assert_eq!(eval(&mut context, "memory.grow(1) + ' ' + view.length + ' ' + new Uint8Array(memory.buffer)[3] + ' ' + memory.buffer.byteLength"), "1 0 4 131072");
+        assert_eq!(eval(&mut context, "try { memory.grow(1) } catch (e) { e.name }"), "RangeError");
+
+        // Imported functions can call back into wasm while it waits on them
+        let script = "var reentrant = new WebAssembly.Instance(new WebAssembly.Module(bytes), \
+                          { env: { log: (sum) => logged.push(reentrant.exports.add(sum, 100)), memory } }); \
+                      reentrant.exports.checksum(2) + ' ' + logged.join()";
+        assert_eq!(eval(&mut context, script), "3 10,103");
+
+        // Script exceptions pass through wasm, compile and link errors have their classes
+        let script = "var instance = new WebAssembly.Instance(new WebAssembly.Module(bytes), { env: { log() { throw new Error('from log') }, memory } }); \
+                      var errors = []; try { instance.exports.checksum(1) } catch (e) { errors.push(e.message) } \
+                      try { new WebAssembly.Module(new Uint8Array([0, 97, 115, 109, 2, 0, 0, 0])) } catch (e) { errors.push(e instanceof WebAssembly.CompileError) } \
+                      try { new WebAssembly.Instance(new WebAssembly.Module(bytes), { env: { log: 1, memory } }) } catch (e) { errors.push(e.name) } \
+                      errors.join()";
+        assert_eq!(eval(&mut context, script), "from log,true,LinkError");
+
+        // Fetched responses with the wasm MIME type stream in; others are refused
+        for (name, content_type) in [("wasmResponse", "application/wasm"), ("htmlResponse", "text/html")] {
+            let response = StreamingFetchResponse {
+                status: 200,
+                status_text: "OK".to_string(),
+                headers: HashMap::from([("content-type".to_string(), content_type.to_string())]),
+                url: "https://example.com/checksum.wasm".to_string(),
+                ok: true,
+                body: ReadableStream::from_bytes(MODULE.to_vec()),
+            };
+            let response = FetchBinding::create_streaming_response_object(&response, &mut context).unwrap();
+            context.register_global_property(js_string!(name), response, Attribute::all()).unwrap();
+        }
+        let script = "var streamed = []; \
+                      WebAssembly.instantiateStreaming(Promise.resolve(wasmResponse), imports) \
+                          .then(({ instance }) => streamed.push(instance.exports.add(1, 2))); \
+                      WebAssembly.compileStreaming(htmlResponse).catch((e) => streamed.push(e.name)); \
+                      'pending'";
+        assert_eq!(eval(&mut context, script), "pending");
+        assert_eq!(eval(&mut context, "streamed.join()"), "TypeError,3");
+    }
+}
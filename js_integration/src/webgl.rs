@@ -0,0 +1,388 @@
+//! # WebGL Bindings
+//!
+//! This module implements `canvas.getContext('webgl')` on top of the
+//! recorded WebGL state in the DOM crate: the common entry points for
+//! shaders, programs, buffers, `clear()` and `drawArrays()`.
+//!
+//! ## Design Principles
+//!
+//! 1. **Feature Detection Works**: `getContext('webgl')` returns a context
+//!    whose setup calls all succeed, so pages that probe for WebGL take
+//!    their WebGL path without throwing. `webgl2` and `2d` return `null`,
+//!    which pages already handle.
+//! 2. **One Context per Canvas**: Repeated calls return the same object,
+//!    and the engine keeps each canvas's `WebGlContext` so the renderer
+//!    can replay what was drawn.
+//! 3. **Objects by Id**: Shaders, programs and buffers are plain objects
+//!    holding the id the DOM-side state knows them by.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use boa_engine::{
+    object::{builtins::JsArray, ObjectInitializer},
+    property::{Attribute, PropertyDescriptor},
+    js_string, Context, JsNativeError, JsObject, JsResult, JsValue, NativeFunction,
+};
+use boa_gc::{Finalize, Trace};
+use dom::webgl::{self, WebGlContext};
+use crate::node_handles::NodeHandleTable;
+
+/// The WebGL contexts of a document's canvases, by node id
+pub type WebGlContexts = Rc<RefCell<HashMap<u64, WebGlContext>>>;
+
+/// A context method: the context, the arguments and the JS context
+type Method = fn(&WebGlContext, &[JsValue], &mut Context) -> JsResult<JsValue>;
+
+/// Captures for `getContext`
+#[derive(Trace, Finalize)]
+struct CanvasBinding {
+    #[unsafe_ignore_trace]
+    handles: Rc<RefCell<NodeHandleTable>>,
+    #[unsafe_ignore_trace]
+    contexts: WebGlContexts,
+}
+
+/// Captures for one method of a WebGL context
+#[derive(Trace, Finalize)]
+struct MethodBinding {
+    #[unsafe_ignore_trace]
+    gl: WebGlContext,
+    #[unsafe_ignore_trace]
+    method: Method,
+}
+
+/// Add `getContext` to the wrapper of a `<canvas>`
+pub fn install_canvas_members(
+    wrapper: &JsObject,
+    handles: Rc<RefCell<NodeHandleTable>>,
+    contexts: WebGlContexts,
+    context: &mut Context,
+) -> JsResult<()> {
+    let get_context = NativeFunction::from_copy_closure_with_captures(
+        |this, args, binding: &CanvasBinding, context| {
+            let wrapper = this.as_object().cloned().ok_or_else(|| {
+                JsNativeError::typ().with_message("Illegal invocation: not a canvas")
+            })?;
+            let node = binding.handles.borrow().resolve_wrapper(&wrapper, context).ok_or_else(|| {
+                JsNativeError::typ().with_message("Illegal invocation: not a canvas")
+            })?;
+            let kind = args.first().cloned().unwrap_or_default().to_string(context)?.to_std_string_escaped();
+            if !matches!(kind.as_str(), "webgl" | "experimental-webgl") {
+                return Ok(JsValue::null());
+            }
+            let existing = wrapper.get(js_string!("__webglContext"), context)?;
+            if existing.is_object() {
+                return Ok(existing);
+            }
+            let gl = binding
+                .contexts
+                .borrow_mut()
+                .entry(node.id)
+                .or_insert_with(|| {
+                    let size = |name: &str, default: u32| {
+                        node.get_attribute(name).and_then(|value| value.trim().parse().ok()).unwrap_or(default)
+                    };
+                    WebGlContext::new(size("width", 300), size("height", 150))
+                })
+                .clone();
+            let object = context_object(gl, context);
+            wrapper.define_property_or_throw(
+                js_string!("__webglContext"),
+                PropertyDescriptor::builder().value(object.clone()).writable(false).enumerable(false).configurable(false),
+                context,
+            )?;
+            Ok(object.into())
+        },
+        CanvasBinding { handles, contexts },
+    );
+    wrapper.define_property_or_throw(
+        js_string!("getContext"),
+        PropertyDescriptor::builder()
+            .value(get_context.to_js_function(context.realm()))
+            .writable(true)
+            .enumerable(false)
+            .configurable(true),
+        context,
+    )?;
+    Ok(())
+}
+
+/// Create the `WebGLRenderingContext` object of a canvas
+fn context_object(gl: WebGlContext, context: &mut Context) -> JsObject {
+    let methods: [(&str, usize, Method); 30] = [
+        ("getError", 0, |gl, _, _| Ok(gl.get_error().into())),
+        ("isContextLost", 0, |_, _, _| Ok(false.into())),
+        ("getExtension", 1, |_, _, _| Ok(JsValue::null())),
+        ("getSupportedExtensions", 0, |_, _, context| Ok(JsArray::new(context).into())),
+        ("getContextAttributes", 0, |_, _, context| Ok(context_attributes(context).into())),
+        ("getParameter", 1, |_, args, context| {
+            let value = match number(args, 0, context)? as u32 {
+                0x1F00 => "dubby",
+                0x1F01 => "dubby WebGL subset",
+                0x1F02 => "WebGL 1.0",
+                0x8B8C => "WebGL GLSL ES 1.0",
+                _ => return Ok(JsValue::null()),
+            };
+            Ok(js_string!(value).into())
+        }),
+        ("viewport", 4, |gl, args, context| {
+            gl.viewport(number(args, 0, context)? as i32, number(args, 1, context)? as i32, number(args, 2, context)? as i32, number(args, 3, context)? as i32);
+            Ok(JsValue::undefined())
+        }),
+        ("clearColor", 4, |gl, args, context| {
+            gl.clear_color([number(args, 0, context)? as f32, number(args, 1, context)? as f32, number(args, 2, context)? as f32, number(args, 3, context)? as f32]);
+            Ok(JsValue::undefined())
+        }),
+        ("clear", 1, |gl, args, context| {
+            gl.clear(number(args, 0, context)? as u32);
+            Ok(JsValue::undefined())
+        }),
+        ("createShader", 1, |gl, args, context| {
+            Ok(gl.create_shader(number(args, 0, context)? as u32).map_or(JsValue::null(), |id| gl_object(id, context).into()))
+        }),
+        ("shaderSource", 2, |gl, args, context| {
+            if let Some(shader) = object_id(args, 0, context)? {
+                let source = args.get(1).cloned().unwrap_or_default().to_string(context)?.to_std_string_escaped();
+                gl.shader_source(shader, &source);
+            }
+            Ok(JsValue::undefined())
+        }),
+        ("compileShader", 1, |gl, args, context| {
+            if let Some(shader) = object_id(args, 0, context)? {
+                gl.compile_shader(shader);
+            }
+            Ok(JsValue::undefined())
+        }),
+        ("getShaderParameter", 2, |gl, args, context| {
+            let shader = object_id(args, 0, context)?.unwrap_or(0);
+            Ok(match number(args, 1, context)? as u32 {
+                webgl::COMPILE_STATUS => gl.shader_compiled(shader).into(),
+                _ => JsValue::null(),
+            })
+        }),
+        ("getShaderInfoLog", 1, |_, _, _| Ok(js_string!("").into())),
+        ("createProgram", 0, |gl, _, context| Ok(gl_object(gl.create_program(), context).into())),
+        ("attachShader", 2, |gl, args, context| {
+            if let (Some(program), Some(shader)) = (object_id(args, 0, context)?, object_id(args, 1, context)?) {
+                gl.attach_shader(program, shader);
+            }
+            Ok(JsValue::undefined())
+        }),
+        ("linkProgram", 1, |gl, args, context| {
+            if let Some(program) = object_id(args, 0, context)? {
+                gl.link_program(program);
+                if gl.program_linked(program) && !gl.program_draws(program) {
+                    println!("⚠️  WebGL program will not draw: {}", gl.program_info_log(program));
+                }
+            }
+            Ok(JsValue::undefined())
+        }),
+        ("getProgramParameter", 2, |gl, args, context| {
+            let program = object_id(args, 0, context)?.unwrap_or(0);
+            Ok(match number(args, 1, context)? as u32 {
+                webgl::LINK_STATUS => gl.program_linked(program).into(),
+                _ => JsValue::null(),
+            })
+        }),
+        ("getProgramInfoLog", 1, |gl, args, context| {
+            let program = object_id(args, 0, context)?.unwrap_or(0);
+            Ok(js_string!(gl.program_info_log(program)).into())
+        }),
+        ("useProgram", 1, |gl, args, context| {
+            gl.use_program(object_id(args, 0, context)?);
+            Ok(JsValue::undefined())
+        }),
+        ("getAttribLocation", 2, |gl, args, context| {
+            let program = object_id(args, 0, context)?.unwrap_or(0);
+            let name = args.get(1).cloned().unwrap_or_default().to_string(context)?.to_std_string_escaped();
+            Ok(gl.attribute_location(program, &name).into())
+        }),
+        ("getUniformLocation", 2, |_, args, context| {
+            let name = args.get(1).cloned().unwrap_or_default().to_string(context)?;
+            Ok(ObjectInitializer::new(context).property(js_string!("__uniform"), name, Attribute::empty()).build().into())
+        }),
+        ("uniform4f", 5, |gl, args, context| {
+            if let Some(name) = uniform_name(args, context)? {
+                gl.uniform4f(&name, [number(args, 1, context)? as f32, number(args, 2, context)? as f32, number(args, 3, context)? as f32, number(args, 4, context)? as f32]);
+            }
+            Ok(JsValue::undefined())
+        }),
+        ("uniform4fv", 2, |gl, args, context| {
+            let values = float_array(args.get(1), context)?;
+            if let (Some(name), &[r, g, b, a, ..]) = (uniform_name(args, context)?, &values[..]) {
+                gl.uniform4f(&name, [r, g, b, a]);
+            }
+            Ok(JsValue::undefined())
+        }),
+        ("createBuffer", 0, |gl, _, context| Ok(gl_object(gl.create_buffer(), context).into())),
+        ("bindBuffer", 2, |gl, args, context| {
+            gl.bind_buffer(number(args, 0, context)? as u32, object_id(args, 1, context)?);
+            Ok(JsValue::undefined())
+        }),
+        ("bufferData", 3, |gl, args, context| {
+            gl.buffer_data(number(args, 0, context)? as u32, float_array(args.get(1), context)?);
+            Ok(JsValue::undefined())
+        }),
+        ("enableVertexAttribArray", 1, |gl, args, context| {
+            gl.enable_vertex_attrib_array(number(args, 0, context)? as u32);
+            Ok(JsValue::undefined())
+        }),
+        ("vertexAttribPointer", 6, |gl, args, context| {
+            gl.vertex_attrib_pointer(
+                number(args, 0, context)? as u32,
+                number(args, 1, context)? as u32,
+                number(args, 2, context)? as u32,
+                number(args, 4, context)? as u32,
+                number(args, 5, context)? as u32,
+            );
+            Ok(JsValue::undefined())
+        }),
+        ("drawArrays", 3, |gl, args, context| {
+            gl.draw_arrays(number(args, 0, context)? as u32, number(args, 1, context)? as i32, number(args, 2, context)? as i32);
+            Ok(JsValue::undefined())
+        }),
+    ];
+    let constants = [
+        ("DEPTH_BUFFER_BIT", webgl::DEPTH_BUFFER_BIT),
+        ("COLOR_BUFFER_BIT", webgl::COLOR_BUFFER_BIT),
+        ("POINTS", webgl::POINTS),
+        ("LINES", webgl::LINES),
+        ("TRIANGLES", webgl::TRIANGLES),
+        ("TRIANGLE_STRIP", webgl::TRIANGLE_STRIP),
+        ("TRIANGLE_FAN", webgl::TRIANGLE_FAN),
+        ("ARRAY_BUFFER", webgl::ARRAY_BUFFER),
+        ("STATIC_DRAW", webgl::STATIC_DRAW),
+        ("DYNAMIC_DRAW", webgl::DYNAMIC_DRAW),
+        ("FLOAT", webgl::FLOAT),
+        ("FRAGMENT_SHADER", webgl::FRAGMENT_SHADER),
+        ("VERTEX_SHADER", webgl::VERTEX_SHADER),
+        ("COMPILE_STATUS", webgl::COMPILE_STATUS),
+        ("LINK_STATUS", webgl::LINK_STATUS),
+        ("NO_ERROR", webgl::NO_ERROR),
+        ("INVALID_ENUM", webgl::INVALID_ENUM),
+        ("INVALID_VALUE", webgl::INVALID_VALUE),
+        ("INVALID_OPERATION", webgl::INVALID_OPERATION),
+    ];
+    let (width, height) = gl.size();
+
+    let mut initializer = ObjectInitializer::new(context);
+    for (name, length, method) in methods {
+        let function = NativeFunction::from_copy_closure_with_captures(
+            |_this, args, binding: &MethodBinding, context| (binding.method)(&binding.gl, args, context),
+            MethodBinding { gl: gl.clone(), method },
+        );
+        initializer.function(function, js_string!(name), length);
+    }
+    for (name, value) in constants {
+        initializer.property(js_string!(name), value, Attribute::READONLY | Attribute::ENUMERABLE);
+    }
+    initializer.property(js_string!("drawingBufferWidth"), width, Attribute::READONLY | Attribute::ENUMERABLE);
+    initializer.property(js_string!("drawingBufferHeight"), height, Attribute::READONLY | Attribute::ENUMERABLE);
+    initializer.build()
+}
+
+/// Create the object a shader, program or buffer is known by
+fn gl_object(id: u32, context: &mut Context) -> JsObject {
+    ObjectInitializer::new(context).property(js_string!("__glId"), id, Attribute::empty()).build()
+}
+
+fn context_attributes(context: &mut Context) -> JsObject {
+    ObjectInitializer::new(context)
+        .property(js_string!("alpha"), true, Attribute::all())
+        .property(js_string!("antialias"), false, Attribute::all())
+        .property(js_string!("depth"), false, Attribute::all())
+        .property(js_string!("stencil"), false, Attribute::all())
+        .property(js_string!("premultipliedAlpha"), true, Attribute::all())
+        .property(js_string!("preserveDrawingBuffer"), false, Attribute::all())
+        .build()
+}
+
+fn number(args: &[JsValue], index: usize, context: &mut Context) -> JsResult<f64> {
+    args.get(index).cloned().unwrap_or_default().to_number(context)
+}
+
+/// Get the id of a shader, program or buffer argument; `None` for `null`
+fn object_id(args: &[JsValue], index: usize, context: &mut Context) -> JsResult<Option<u32>> {
+    let Some(object) = args.get(index).and_then(JsValue::as_object) else {
+        return Ok(None);
+    };
+    let id = object.get(js_string!("__glId"), context)?;
+    Ok(id.as_number().map(|id| id as u32))
+}
+
+fn uniform_name(args: &[JsValue], context: &mut Context) -> JsResult<Option<String>> {
+    let Some(location) = args.first().and_then(JsValue::as_object) else {
+        return Ok(None);
+    };
+    let name = location.get(js_string!("__uniform"), context)?;
+    Ok(name.as_string().map(|name| name.to_std_string_escaped()))
+}
+
+/// Read an array or typed array of numbers
+fn float_array(value: Option<&JsValue>, context: &mut Context) -> JsResult<Vec<f32>> {
+    let Some(object) = value.and_then(JsValue::as_object) else {
+        return Ok(Vec::new());
+    };
+    let length = object.get(js_string!("length"), context)?.to_length(context)?;
+    (0..length).map(|index| Ok(object.get(index, context)?.to_number(context)? as f32)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use boa_engine::Source;
+    use dom::Document;
+    use dom::webgl::WebGlCommand;
+
+    #[test]
+    fn test_webgl_demo_records_a_triangle() {
+        let document = Document::new();
+        let canvas = document.create_element("canvas");
+        canvas.set_attribute("width", "64");
+        let handles = Rc::new(RefCell::new(NodeHandleTable::new()));
+        let contexts: WebGlContexts = Rc::new(RefCell::new(HashMap::new()));
+
+        let mut context = Context::default();
+        let wrapper = handles.borrow_mut().wrap_node(&canvas, &mut context);
+        install_canvas_members(&wrapper, Rc::clone(&handles), Rc::clone(&contexts), &mut context).unwrap();
+        context.register_global_property(js_string!("canvas"), wrapper, Attribute::all()).unwrap();
+
+        let code = r#"
+            var gl = canvas.getContext('webgl');
+            var same = gl === canvas.getContext('webgl');
+            var noWebgl2 = canvas.getContext('webgl2') === null;
+            function compile(type, source) {
+                var shader = gl.createShader(type);
+                gl.shaderSource(shader, source);
+                gl.compileShader(shader);
+                return shader;
+            }
+            var program = gl.createProgram();
+            gl.attachShader(program, compile(gl.VERTEX_SHADER, 'attribute vec2 p; void main() { gl_Position = vec4(p, 0.0, 1.0); }'));
+            gl.attachShader(program, compile(gl.FRAGMENT_SHADER, 'void main() { gl_FragColor = vec4(0.0, 1.0, 0.0, 1.0); }'));
+            gl.linkProgram(program);
+            var linked = gl.getProgramParameter(program, gl.LINK_STATUS);
+            gl.useProgram(program);
+            gl.bindBuffer(gl.ARRAY_BUFFER, gl.createBuffer());
+            gl.bufferData(gl.ARRAY_BUFFER, [0, 1, -1, -1, 1, -1], gl.STATIC_DRAW);
+            var location = gl.getAttribLocation(program, 'p');
+            gl.enableVertexAttribArray(location);
+            gl.vertexAttribPointer(location, 2, gl.FLOAT, false, 0, 0);
+            gl.clearColor(0, 0, 0, 1);
+            gl.clear(gl.COLOR_BUFFER_BIT);
+            gl.drawArrays(gl.TRIANGLES, 0, 3);
+            [same, noWebgl2, linked, gl.getError(), gl.drawingBufferWidth].join(',');
+        "#;
+        let result = context.eval(Source::from_bytes(code)).unwrap();
+        assert_eq!(result.to_string(&mut context).unwrap().to_std_string_escaped(), "true,true,true,0,64");
+
+        let gl = contexts.borrow().get(&canvas.id).cloned().unwrap();
+        let commands = gl.take_commands();
+        assert_eq!(commands.len(), 2);
+        assert_eq!(
+            commands[1],
+            WebGlCommand::DrawTriangles { vertices: vec![[0.0, 1.0], [-1.0, -1.0], [1.0, -1.0]], color: [0.0, 1.0, 0.0, 1.0] }
+        );
+    }
+}
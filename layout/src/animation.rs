@@ -0,0 +1,615 @@
+//! # Animation Timing
+//!
+//! This module is the timing and keyframe engine behind both CSS
+//! animations and the animations scripts start with `element.animate()`.
+//! It turns a time into an iteration progress, eases it, and samples
+//! keyframes at that progress.
+//!
+//! ## Design Principles
+//!
+//! 1. **One Engine**: CSS animations and script animations compute their
+//!    progress through the same `EffectTiming`, so easing, iterations and
+//!    directions behave the same whichever started the animation.
+//! 2. **Values, Not Style Mutations**: A script animation produces
+//!    declarations per node that the style matcher applies above the
+//!    `style` attribute, so running one never rewrites the attribute and
+//!    never loops through the page's own style changes.
+//! 3. **Explicit Time**: Every call takes the timeline time in
+//!    milliseconds instead of reading a clock, so sampling is
+//!    deterministic.
+
+use std::collections::HashMap;
+use crate::{AnimationDirection, AnimationFillMode, TimingFunction};
+
+impl TimingFunction {
+    /// Parse a CSS easing function, such as `ease-out` or `steps(4)`
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().to_ascii_lowercase();
+        match value.as_str() {
+            "ease" => return Some(TimingFunction::Ease),
+            "linear" => return Some(TimingFunction::Linear),
+            "ease-in" => return Some(TimingFunction::EaseIn),
+            "ease-out" => return Some(TimingFunction::EaseOut),
+            "ease-in-out" => return Some(TimingFunction::EaseInOut),
+            "step-end" => return Some(TimingFunction::Steps(1)),
+            _ => {}
+        }
+        let (name, arguments) = value.strip_suffix(')')?.split_once('(')?;
+        let arguments: Vec<&str> = arguments.split(',').map(str::trim).collect();
+        match (name.trim(), arguments.as_slice()) {
+            ("cubic-bezier", [x1, y1, x2, y2]) => {
+                let [x1, y1, x2, y2] = [x1, y1, x2, y2].map(|argument| argument.parse::<f32>().ok());
+                let (x1, y1, x2, y2) = (x1?, y1?, x2?, y2?);
+                ((0.0..=1.0).contains(&x1) && (0.0..=1.0).contains(&x2)).then_some(TimingFunction::CubicBezier(x1, y1, x2, y2))
+            }
+            // Only the default `end` jump position is supported
+            ("steps", [count]) | ("steps", [count, "end" | "jump-end"]) => {
+                count.parse::<i32>().ok().filter(|count| *count > 0).map(TimingFunction::Steps)
+            }
+            _ => None,
+        }
+    }
+
+    /// Ease an input progress between 0 and 1
+    pub fn apply(&self, progress: f32) -> f32 {
+        match *self {
+            TimingFunction::Linear => progress,
+            TimingFunction::Ease => cubic_bezier(progress, 0.25, 0.1, 0.25, 1.0),
+            TimingFunction::EaseIn => cubic_bezier(progress, 0.42, 0.0, 1.0, 1.0),
+            TimingFunction::EaseOut => cubic_bezier(progress, 0.0, 0.0, 0.58, 1.0),
+            TimingFunction::EaseInOut => cubic_bezier(progress, 0.42, 0.0, 0.58, 1.0),
+            TimingFunction::CubicBezier(x1, y1, x2, y2) => cubic_bezier(progress, x1, y1, x2, y2),
+            TimingFunction::Steps(steps) => {
+                let steps = steps.max(1) as f32;
+                ((progress * steps).floor() / steps).clamp(0.0, 1.0)
+            }
+        }
+    }
+}
+
+/// Evaluate a cubic Bézier easing curve at an input progress
+///
+/// Solves the curve's x for the input by bisection, then returns its y.
+fn cubic_bezier(progress: f32, x1: f32, y1: f32, x2: f32, y2: f32) -> f32 {
+    if progress <= 0.0 || progress >= 1.0 {
+        return progress;
+    }
+    let curve = |t: f32, p1: f32, p2: f32| {
+        let mt = 1.0 - t;
+        3.0 * mt * mt * t * p1 + 3.0 * mt * t * t * p2 + t * t * t
+    };
+    let (mut low, mut high) = (0.0f32, 1.0f32);
+    let mut t = progress;
+    for _ in 0..32 {
+        let x = curve(t, x1, x2);
+        if (x - progress).abs() < 1e-6 {
+            break;
+        }
+        if x < progress {
+            low = t;
+        } else {
+            high = t;
+        }
+        t = (low + high) / 2.0;
+    }
+    curve(t, y1, y2)
+}
+
+/// The timing of an animation effect, in milliseconds
+#[derive(Debug, Clone, PartialEq)]
+pub struct EffectTiming {
+    pub duration: f64,
+    pub delay: f64,
+    /// Number of iterations; may be infinite
+    pub iterations: f64,
+    pub direction: AnimationDirection,
+    pub fill: AnimationFillMode,
+    pub easing: TimingFunction,
+}
+
+impl Default for EffectTiming {
+    fn default() -> Self {
+        EffectTiming {
+            duration: 0.0,
+            delay: 0.0,
+            iterations: 1.0,
+            direction: AnimationDirection::Normal,
+            fill: AnimationFillMode::None,
+            easing: TimingFunction::Linear,
+        }
+    }
+}
+
+/// Where an effect is at a point in time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IterationProgress {
+    /// The current iteration, counting from zero
+    pub iteration: f64,
+    /// Progress through the iteration after direction and easing
+    pub progress: f64,
+}
+
+impl EffectTiming {
+    /// Get the time all iterations take
+    pub fn active_duration(&self) -> f64 {
+        if self.duration == 0.0 || self.iterations == 0.0 {
+            0.0
+        } else {
+            self.duration * self.iterations
+        }
+    }
+
+    /// Get the time the effect ends, including its delay
+    pub fn end_time(&self) -> f64 {
+        (self.delay + self.active_duration()).max(0.0)
+    }
+
+    /// Compute the progress at a time since the animation started
+    ///
+    /// Returns `None` before the delay or after the end when the fill
+    /// mode does not hold the effect there.
+    pub fn iteration_progress(&self, local_time: f64) -> Option<IterationProgress> {
+        let active_duration = self.active_duration();
+        let fills_backwards = matches!(self.fill, AnimationFillMode::Backwards | AnimationFillMode::Both);
+        let fills_forwards = matches!(self.fill, AnimationFillMode::Forwards | AnimationFillMode::Both);
+        let (active_time, after) = if local_time < self.delay {
+            (fills_backwards.then_some(0.0)?, false)
+        } else if local_time >= self.delay + active_duration {
+            (fills_forwards.then_some(active_duration)?, true)
+        } else {
+            (local_time - self.delay, false)
+        };
+
+        let overall = if self.duration == 0.0 {
+            if after { self.iterations } else { 0.0 }
+        } else {
+            active_time / self.duration
+        };
+        let mut simple = if overall.is_infinite() { 0.0 } else { overall % 1.0 };
+        if simple == 0.0 && overall != 0.0 && self.iterations != 0.0 && (after || active_time == active_duration) {
+            simple = 1.0;
+        }
+        let iteration = if after && self.iterations.is_infinite() {
+            f64::INFINITY
+        } else if simple == 1.0 {
+            overall.floor() - 1.0
+        } else {
+            overall.floor()
+        };
+
+        let even = iteration.is_infinite() || iteration % 2.0 == 0.0;
+        let forwards = match self.direction {
+            AnimationDirection::Normal => true,
+            AnimationDirection::Reverse => false,
+            AnimationDirection::Alternate => even,
+            AnimationDirection::AlternateReverse => !even,
+        };
+        let directed = if forwards { simple } else { 1.0 - simple };
+        Some(IterationProgress { iteration, progress: self.easing.apply(directed as f32) as f64 })
+    }
+}
+
+/// One keyframe of an effect
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keyframe {
+    /// Position between 0 and 1; spaced evenly when unset
+    pub offset: Option<f64>,
+    /// Easing from this keyframe to the next
+    pub easing: TimingFunction,
+    /// Property names and CSS values
+    pub values: Vec<(String, String)>,
+}
+
+/// Why a list of keyframes was rejected
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyframeError {
+    /// An offset is outside 0 to 1
+    OffsetOutOfRange,
+    /// Offsets go backwards
+    OffsetsNotSorted,
+}
+
+impl std::fmt::Display for KeyframeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyframeError::OffsetOutOfRange => write!(f, "keyframe offsets must be between 0 and 1"),
+            KeyframeError::OffsetsNotSorted => write!(f, "keyframe offsets must be in ascending order"),
+        }
+    }
+}
+
+impl std::error::Error for KeyframeError {}
+
+/// Keyframes and the timing they are played with
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyframeEffect {
+    /// Keyframes with their offsets computed
+    keyframes: Vec<Keyframe>,
+    pub timing: EffectTiming,
+}
+
+impl KeyframeEffect {
+    /// Create an effect, spacing keyframes without an offset evenly
+    /// between their neighbours
+    pub fn new(mut keyframes: Vec<Keyframe>, timing: EffectTiming) -> Result<Self, KeyframeError> {
+        let mut previous = 0.0;
+        for offset in keyframes.iter().filter_map(|keyframe| keyframe.offset) {
+            if !(0.0..=1.0).contains(&offset) {
+                return Err(KeyframeError::OffsetOutOfRange);
+            }
+            if offset < previous {
+                return Err(KeyframeError::OffsetsNotSorted);
+            }
+            previous = offset;
+        }
+
+        let count = keyframes.len();
+        if let Some(last) = keyframes.last_mut() {
+            last.offset.get_or_insert(1.0);
+        }
+        if count > 1 {
+            keyframes[0].offset.get_or_insert(0.0);
+        }
+        let mut start = 0;
+        for index in 1..count {
+            if let Some(end_offset) = keyframes[index].offset {
+                let start_offset = keyframes[start].offset.unwrap_or(0.0);
+                let gaps = (index - start) as f64;
+                for (step, keyframe) in keyframes[start + 1..index].iter_mut().enumerate() {
+                    keyframe.offset = Some(start_offset + (end_offset - start_offset) * (step + 1) as f64 / gaps);
+                }
+                start = index;
+            }
+        }
+        Ok(KeyframeEffect { keyframes, timing })
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    /// Get the values of every animated property at an iteration progress
+    ///
+    /// Before the first or after the last keyframe of a property, its
+    /// nearest value holds: there is no underlying value to blend with.
+    pub fn sample(&self, progress: f64) -> Vec<(String, String)> {
+        let mut properties: Vec<&str> = Vec::new();
+        for (property, _) in self.keyframes.iter().flat_map(|keyframe| &keyframe.values) {
+            if !properties.contains(&property.as_str()) {
+                properties.push(property);
+            }
+        }
+
+        properties
+            .into_iter()
+            .filter_map(|property| {
+                let frames: Vec<(f64, &TimingFunction, &str)> = self
+                    .keyframes
+                    .iter()
+                    .filter_map(|keyframe| {
+                        let value = keyframe.values.iter().find(|(name, _)| name == property)?;
+                        Some((keyframe.offset.unwrap_or(1.0), &keyframe.easing, value.1.as_str()))
+                    })
+                    .collect();
+                let (first, last) = (frames.first()?, frames.last()?);
+                let value = if progress <= first.0 {
+                    first.2.to_string()
+                } else if progress >= last.0 {
+                    last.2.to_string()
+                } else {
+                    let segment = frames.windows(2).find(|pair| progress < pair[1].0)?;
+                    let (from, to) = (segment[0], segment[1]);
+                    let span = to.0 - from.0;
+                    let local = if span > 0.0 { (progress - from.0) / span } else { 1.0 };
+                    interpolate_value(from.2, to.2, from.1.apply(local as f32) as f64)
+                };
+                Some((property.to_string(), value))
+            })
+            .collect()
+    }
+}
+
+/// Split a CSS value into its text and its numbers
+///
+/// A number counts only where a token starts, so the digits of
+/// `translate3d` and of hex colors stay part of the text.
+fn split_numbers(value: &str) -> (Vec<String>, Vec<f64>) {
+    let chars: Vec<char> = value.chars().collect();
+    let mut text = vec![String::new()];
+    let mut numbers = Vec::new();
+    let mut index = 0;
+    while index < chars.len() {
+        let previous = index.checked_sub(1).map(|previous| chars[previous]);
+        let at_token_start = !previous.is_some_and(|c| c.is_ascii_alphanumeric() || c == '#' || c == '-' || c == '.');
+        let starts_number = |at: usize| chars.get(at).is_some_and(|c| c.is_ascii_digit())
+            || (chars.get(at) == Some(&'.') && chars.get(at + 1).is_some_and(|c| c.is_ascii_digit()));
+        let signed = chars[index] == '-' || chars[index] == '+';
+        if at_token_start && (starts_number(index) || (signed && starts_number(index + 1))) {
+            let start = index;
+            index += 1;
+            while index < chars.len() && (chars[index].is_ascii_digit() || chars[index] == '.') {
+                index += 1;
+            }
+            let number: String = chars[start..index].iter().collect();
+            match number.parse() {
+                Ok(number) => {
+                    numbers.push(number);
+                    text.push(String::new());
+                }
+                Err(_) => text.last_mut().unwrap().push_str(&number),
+            }
+        } else {
+            text.last_mut().unwrap().push(chars[index]);
+            index += 1;
+        }
+    }
+    (text, numbers)
+}
+
+/// Interpolate between two CSS values
+///
+/// Values that differ only in their numbers, such as `10px` and `40px` or
+/// two `rotate()`s, interpolate number by number. Anything else flips
+/// halfway, as discrete properties do.
+pub fn interpolate_value(from: &str, to: &str, progress: f64) -> String {
+    let (from_text, from_numbers) = split_numbers(from.trim());
+    let (to_text, to_numbers) = split_numbers(to.trim());
+    if from_text != to_text || from_numbers.is_empty() {
+        return if progress < 0.5 { from.to_string() } else { to.to_string() };
+    }
+    let mut value = from_text[0].clone();
+    for (index, (start, end)) in from_numbers.iter().zip(&to_numbers).enumerate() {
+        let number = start + (end - start) * progress;
+        let formatted = format!("{:.3}", number);
+        let formatted = formatted.trim_end_matches('0').trim_end_matches('.');
+        value.push_str(if formatted == "-0" { "0" } else { formatted });
+        value.push_str(&from_text[index + 1]);
+    }
+    value
+}
+
+/// Where an animation is in its lifecycle
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlayState {
+    Idle,
+    Running,
+    Paused,
+    Finished,
+}
+
+/// An animation playing a keyframe effect on one node
+#[derive(Debug, Clone, PartialEq)]
+pub struct Animation {
+    pub id: u64,
+    pub target: u64,
+    pub effect: KeyframeEffect,
+    /// Timeline time the animation started, when it is playing
+    start_time: Option<f64>,
+    /// Current time held while paused or stalled
+    hold_time: Option<f64>,
+    paused: bool,
+    playback_rate: f64,
+    /// Whether the current finish has been reported
+    finish_reported: bool,
+}
+
+impl Animation {
+    /// Create an idle animation
+    pub fn new(id: u64, target: u64, effect: KeyframeEffect) -> Self {
+        Animation { id, target, effect, start_time: None, hold_time: None, paused: false, playback_rate: 1.0, finish_reported: false }
+    }
+
+    pub fn playback_rate(&self) -> f64 {
+        self.playback_rate
+    }
+
+    /// Get the animation's time at a timeline time; `None` when idle
+    pub fn current_time(&self, now: f64) -> Option<f64> {
+        if let Some(hold_time) = self.hold_time {
+            return Some(hold_time);
+        }
+        let time = (now - self.start_time?) * self.playback_rate;
+        let end = self.effect.timing.end_time();
+        Some(if self.playback_rate > 0.0 { time.min(end) } else { time.max(0.0) })
+    }
+
+    /// Jump to a time, keeping the play state
+    pub fn set_current_time(&mut self, time: f64, now: f64) {
+        if self.paused || self.start_time.is_none() || self.playback_rate == 0.0 {
+            self.hold_time = Some(time);
+            self.start_time = None;
+        } else {
+            self.start_time = Some(now - time / self.playback_rate);
+        }
+        self.finish_reported = false;
+    }
+
+    pub fn play_state(&self, now: f64) -> PlayState {
+        let Some(time) = self.current_time(now) else {
+            return PlayState::Idle;
+        };
+        if self.paused {
+            PlayState::Paused
+        } else if (self.playback_rate > 0.0 && time >= self.effect.timing.end_time()) || (self.playback_rate < 0.0 && time <= 0.0) {
+            PlayState::Finished
+        } else {
+            PlayState::Running
+        }
+    }
+
+    /// Play from the current time, or from the start once finished
+    pub fn play(&mut self, now: f64) {
+        let end = self.effect.timing.end_time();
+        let time = match self.current_time(now) {
+            Some(time) if self.playback_rate > 0.0 && (time < 0.0 || time >= end) => 0.0,
+            Some(time) if self.playback_rate < 0.0 && (time <= 0.0 || time > end) => end,
+            Some(time) => time,
+            None if self.playback_rate < 0.0 => end,
+            None => 0.0,
+        };
+        self.paused = false;
+        self.hold_time = None;
+        self.start_time = Some(now);
+        self.set_current_time(time, now);
+    }
+
+    pub fn pause(&mut self, now: f64) {
+        let time = self.current_time(now).unwrap_or(if self.playback_rate < 0.0 { self.effect.timing.end_time() } else { 0.0 });
+        self.paused = true;
+        self.start_time = None;
+        self.hold_time = Some(time);
+    }
+
+    /// Stop the animation and remove its effect
+    pub fn cancel(&mut self) {
+        self.paused = false;
+        self.start_time = None;
+        self.hold_time = None;
+        self.finish_reported = false;
+    }
+
+    /// Jump to the end in the current direction
+    ///
+    /// Returns false, leaving the animation as it was, when there is no
+    /// end to jump to: at a zero rate or an infinite effect played forwards.
+    pub fn finish(&mut self, now: f64) -> bool {
+        let end = self.effect.timing.end_time();
+        if self.playback_rate == 0.0 || (self.playback_rate > 0.0 && end.is_infinite()) {
+            return false;
+        }
+        let limit = if self.playback_rate > 0.0 { end } else { 0.0 };
+        if self.paused || self.start_time.is_none() {
+            self.paused = false;
+            self.hold_time = Some(limit);
+            self.start_time = None;
+        } else {
+            self.start_time = Some(now - limit / self.playback_rate);
+        }
+        self.finish_reported = false;
+        true
+    }
+
+    /// Change the speed, keeping the current time
+    pub fn set_playback_rate(&mut self, rate: f64, now: f64) {
+        let time = self.current_time(now);
+        self.playback_rate = rate;
+        if let Some(time) = time {
+            self.set_current_time(time, now);
+        }
+    }
+
+    /// Get the animated values at a timeline time; `None` when the effect
+    /// does not apply
+    pub fn sample(&self, now: f64) -> Option<Vec<(String, String)>> {
+        let progress = self.effect.timing.iteration_progress(self.current_time(now)?)?;
+        Some(self.effect.sample(progress.progress))
+    }
+}
+
+/// The animations scripts started in a document
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AnimationTimeline {
+    animations: Vec<Animation>,
+    next_id: u64,
+}
+
+impl AnimationTimeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start playing an effect on a node; returns the animation's id
+    pub fn add(&mut self, target: u64, effect: KeyframeEffect, now: f64) -> u64 {
+        self.next_id += 1;
+        let mut animation = Animation::new(self.next_id, target, effect);
+        animation.play(now);
+        self.animations.push(animation);
+        self.next_id
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Animation> {
+        self.animations.iter().find(|animation| animation.id == id)
+    }
+
+    pub fn get_mut(&mut self, id: u64) -> Option<&mut Animation> {
+        self.animations.iter_mut().find(|animation| animation.id == id)
+    }
+
+    /// Check whether any animation needs further frames
+    pub fn is_animating(&self, now: f64) -> bool {
+        self.animations.iter().any(|animation| animation.play_state(now) == PlayState::Running)
+    }
+
+    /// Get the animated values of every node at a timeline time
+    ///
+    /// Values are listed in the order animations were created, so a later
+    /// animation of the same property wins when they are applied in order.
+    pub fn animated_styles(&self, now: f64) -> HashMap<u64, Vec<(String, String)>> {
+        let mut styles: HashMap<u64, Vec<(String, String)>> = HashMap::new();
+        for animation in &self.animations {
+            if let Some(values) = animation.sample(now) {
+                styles.entry(animation.target).or_default().extend(values);
+            }
+        }
+        styles
+    }
+
+    /// Get the animations that finished since the last call
+    pub fn take_finished(&mut self, now: f64) -> Vec<u64> {
+        let mut finished = Vec::new();
+        for animation in &mut self.animations {
+            if animation.play_state(now) == PlayState::Finished && !animation.finish_reported {
+                animation.finish_reported = true;
+                finished.push(animation.id);
+            }
+        }
+        finished
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timing_keyframes_and_playback() {
+        assert_eq!(TimingFunction::parse("steps(4, end)"), Some(TimingFunction::Steps(4)));
+        assert_eq!(TimingFunction::parse("cubic-bezier(2, 0, 0, 1)"), None);
+        assert!((TimingFunction::EaseInOut.apply(0.5) - 0.5).abs() < 1e-3);
+        assert!(TimingFunction::EaseIn.apply(0.25) < 0.25);
+
+        let timing = EffectTiming { duration: 1000.0, iterations: 2.0, direction: AnimationDirection::Alternate, ..EffectTiming::default() };
+        assert_eq!(timing.iteration_progress(250.0), Some(IterationProgress { iteration: 0.0, progress: 0.25 }));
+        assert_eq!(timing.iteration_progress(1250.0), Some(IterationProgress { iteration: 1.0, progress: 0.75 }));
+        assert_eq!(timing.iteration_progress(2000.0), None);
+
+        assert_eq!(interpolate_value("rotate(0deg) translateX(10px)", "rotate(90deg) translateX(-30px)", 0.5), "rotate(45deg) translateX(-10px)");
+        assert_eq!(interpolate_value("#000000", "#112233", 0.4), "#000000");
+        assert_eq!(interpolate_value("block", "none", 0.5), "none");
+
+        let keyframes = vec![
+            Keyframe { offset: None, easing: TimingFunction::Linear, values: vec![("opacity".into(), "0".into())] },
+            Keyframe { offset: None, easing: TimingFunction::Linear, values: vec![("width".into(), "100px".into())] },
+            Keyframe { offset: None, easing: TimingFunction::Linear, values: vec![("opacity".into(), "1".into()), ("width".into(), "200px".into())] },
+        ];
+        let timing = EffectTiming { duration: 1000.0, fill: AnimationFillMode::Forwards, ..EffectTiming::default() };
+        let effect = KeyframeEffect::new(keyframes, timing).unwrap();
+        assert_eq!(effect.keyframes()[1].offset, Some(0.5));
+        assert_eq!(effect.sample(0.75), vec![("opacity".into(), "0.75".into()), ("width".into(), "150px".into())]);
+
+        let mut timeline = AnimationTimeline::new();
+        let id = timeline.add(7, effect, 100.0);
+        assert_eq!(timeline.animated_styles(600.0)[&7][0].1, "0.5");
+        let animation = timeline.get_mut(id).unwrap();
+        animation.pause(600.0);
+        animation.set_playback_rate(2.0, 600.0);
+        assert_eq!(animation.play_state(5000.0), PlayState::Paused);
+        animation.play(5000.0);
+        assert_eq!(animation.current_time(5100.0), Some(700.0));
+        assert!(timeline.take_finished(5300.0).contains(&id));
+        assert!(timeline.take_finished(5400.0).is_empty());
+        assert_eq!(timeline.animated_styles(9000.0)[&7][1].1, "200px");
+        timeline.get_mut(id).unwrap().cancel();
+        assert!(timeline.animated_styles(9000.0).is_empty());
+    }
+}
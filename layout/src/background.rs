@@ -0,0 +1,164 @@
+//! # Backgrounds
+//!
+//! This module works out where a box's background is painted and where its
+//! image tiles start: `background-clip` picks the painting area,
+//! `background-origin` the positioning area, and `background-attachment`
+//! whether that area moves with the page or stays with the viewport.
+//!
+//! ## Design Principles
+//!
+//! 1. **Layout's Boxes**: The border, padding and content boxes are the
+//!    ones layout computed, so backgrounds line up with what hit testing
+//!    and geometry observers see.
+//!
+//! 2. **Fixed Means the Viewport**: A fixed background is positioned
+//!    against the viewport. Its tile origin is given for an unscrolled
+//!    page and painters add the scroll offset, so the tiles stay put while
+//!    the page scrolls beneath them.
+//!
+//! 3. **Concentric Corners**: The corner radius shrinks by the borders and
+//!    padding between the border box and the painting area, so a
+//!    background clipped to an inner box follows the outer curve.
+//!
+//! Boxes do not scroll their own contents yet, so `local` behaves as
+//! `scroll`.
+
+use crate::{Dimensions, LayoutBox};
+
+/// One of the boxes of the box model, for `background-clip` and
+/// `background-origin`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackgroundBox {
+    BorderBox,
+    PaddingBox,
+    ContentBox,
+}
+
+impl BackgroundBox {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "border-box" => Some(BackgroundBox::BorderBox),
+            "padding-box" => Some(BackgroundBox::PaddingBox),
+            "content-box" => Some(BackgroundBox::ContentBox),
+            _ => None,
+        }
+    }
+}
+
+/// `background-attachment`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BackgroundAttachment {
+    /// Fixed to the box, scrolling with the page
+    #[default]
+    Scroll,
+    /// Fixed to the viewport
+    Fixed,
+    /// Fixed to the box's contents
+    Local,
+}
+
+impl BackgroundAttachment {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "scroll" => Some(BackgroundAttachment::Scroll),
+            "fixed" => Some(BackgroundAttachment::Fixed),
+            "local" => Some(BackgroundAttachment::Local),
+            _ => None,
+        }
+    }
+}
+
+/// Where a box's background is painted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BackgroundGeometry {
+    /// The area the background covers, by `background-clip`
+    pub painting_area: Dimensions,
+    /// Corner radius of the painting area
+    pub radius: f32,
+    /// Top left corner of the first tile, by `background-origin`; for a
+    /// fixed background, relative to the viewport of an unscrolled page
+    pub tile_origin: (f32, f32),
+    /// Whether the background is fixed to the viewport
+    pub fixed: bool,
+}
+
+/// Work out where a box's background is painted
+pub fn background_geometry(layout_box: &LayoutBox) -> BackgroundGeometry {
+    let styles = &layout_box.styles;
+    let area = |background_box: BackgroundBox| match background_box {
+        BackgroundBox::BorderBox => layout_box.border,
+        BackgroundBox::PaddingBox => layout_box.padding,
+        BackgroundBox::ContentBox => layout_box.content,
+    };
+    // The top left corner's inset from the border box
+    let inset = match styles.background_clip {
+        BackgroundBox::BorderBox => 0.0,
+        BackgroundBox::PaddingBox => styles.box_model.border.top.max(styles.box_model.border.left),
+        BackgroundBox::ContentBox => {
+            (styles.box_model.border.top + styles.box_model.padding.top).max(styles.box_model.border.left + styles.box_model.padding.left)
+        }
+    };
+    let fixed = styles.background_attachment == BackgroundAttachment::Fixed;
+    let tile_origin = if fixed {
+        (0.0, 0.0)
+    } else {
+        let origin = area(styles.background_origin);
+        (origin.x, origin.y)
+    };
+    BackgroundGeometry {
+        painting_area: area(styles.background_clip),
+        radius: (styles.border_radius - inset).max(0.0),
+        tile_origin,
+        fixed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BoxSides, BoxStyles, ComputedStyles};
+    use std::rc::Rc;
+    use dom::{Node, NodeType};
+
+    #[test]
+    fn test_background_geometry() {
+        let styles = ComputedStyles {
+            box_model: Rc::new(BoxStyles {
+                border: BoxSides::new(2.0),
+                padding: BoxSides::new(5.0),
+                ..BoxStyles::default()
+            }),
+            border_radius: 10.0,
+            background_clip: BackgroundBox::PaddingBox,
+            background_origin: BackgroundBox::ContentBox,
+            ..ComputedStyles::default()
+        };
+        let mut layout_box = LayoutBox {
+            node: Node::new(NodeType::Text(String::new()), 1),
+            styles,
+            content: Dimensions::new(17.0, 27.0, 100.0, 50.0),
+            padding: Dimensions::new(12.0, 22.0, 110.0, 60.0),
+            border: Dimensions::new(10.0, 20.0, 114.0, 64.0),
+            margin: Dimensions::new(10.0, 20.0, 114.0, 64.0),
+            children: Vec::new(),
+            fragments: Vec::new(),
+            animation_state: Default::default(),
+        };
+
+        let geometry = background_geometry(&layout_box);
+        assert_eq!(geometry.painting_area, layout_box.padding);
+        assert_eq!(geometry.radius, 8.0);
+        assert_eq!(geometry.tile_origin, (17.0, 27.0));
+        assert!(!geometry.fixed);
+
+        // Clipping to the content box takes the padding off the curve too;
+        // fixed backgrounds start at the viewport
+        layout_box.styles.background_clip = BackgroundBox::ContentBox;
+        layout_box.styles.background_attachment = BackgroundAttachment::Fixed;
+        let geometry = background_geometry(&layout_box);
+        assert_eq!(geometry.painting_area, layout_box.content);
+        assert_eq!(geometry.radius, 3.0);
+        assert_eq!(geometry.tile_origin, (0.0, 0.0));
+        assert!(geometry.fixed);
+    }
+}
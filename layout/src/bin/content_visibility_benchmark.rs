@@ -0,0 +1,89 @@
+//! Content-visibility benchmark
+//! 
+//! Lays out a long article page with and without `content-visibility: auto`
+//! and reports how much layout work size containment saves.
+
+use std::time::{Duration, Instant};
+use dom::Document;
+use layout::{Dimensions, LayoutEngine};
+use css_parser::parse_css;
+
+const SECTIONS: usize = 200;
+const PARAGRAPHS_PER_SECTION: usize = 20;
+const ITERATIONS: u32 = 10;
+
+fn main() {
+    println!("🧪 Content-Visibility Layout Benchmark");
+    println!("======================================");
+    println!("📄 Article: {} sections × {} paragraphs", SECTIONS, PARAGRAPHS_PER_SECTION);
+
+    let viewport = Dimensions::new(0.0, 0.0, 1280.0, 800.0);
+
+    let plain = build_article(None);
+    let contained = build_article(Some("content-visibility: auto; contain-intrinsic-size: 1200px"));
+
+    let baseline = time_layout(&plain, viewport);
+    let (optimized, engine) = {
+        let mut engine = LayoutEngine::new(parse_css(""));
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            engine.layout_document_for_viewport(&contained, viewport);
+        }
+        (start.elapsed() / ITERATIONS, engine)
+    };
+    let stats = engine.containment_stats();
+
+    println!("\n📊 Results (average of {} runs)", ITERATIONS);
+    println!("   - Without containment: {:?}", baseline);
+    println!("   - With content-visibility: auto: {:?}", optimized);
+    println!("   - Rendered sections: {}", stats.active_auto_subtrees);
+    println!("   - Skipped sections: {}", stats.skipped_subtrees);
+    println!("   - Skipped nodes: {}", stats.skipped_nodes);
+
+    if optimized.as_secs_f64() > 0.0 {
+        println!("   - Speedup: {:.1}x", baseline.as_secs_f64() / optimized.as_secs_f64());
+    }
+
+    println!("\n✅ Content-visibility benchmark completed!");
+}
+
+/// Build an article of sections, each with a heading and paragraphs
+fn build_article(section_style: Option<&str>) -> Document {
+    let document = Document::new();
+    let body = document.create_element("body");
+    let article = document.create_element("article");
+    document.root.append_child(&body);
+    body.append_child(&article);
+
+    for section_index in 0..SECTIONS {
+        let section = document.create_element("section");
+        if let Some(style) = section_style {
+            section.set_attribute("style", style);
+        }
+        let heading = document.create_element("h2");
+        heading.append_child(&document.create_text_node(&format!("Section {}", section_index + 1)));
+        section.append_child(&heading);
+
+        for paragraph_index in 0..PARAGRAPHS_PER_SECTION {
+            let paragraph = document.create_element("p");
+            paragraph.append_child(&document.create_text_node(&format!(
+                "Paragraph {} of section {}: lorem ipsum dolor sit amet.",
+                paragraph_index + 1,
+                section_index + 1
+            )));
+            section.append_child(&paragraph);
+        }
+        article.append_child(&section);
+    }
+    document
+}
+
+/// Average layout time for a document without containment
+fn time_layout(document: &Document, viewport: Dimensions) -> Duration {
+    let mut engine = LayoutEngine::new(parse_css(""));
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        engine.layout_document_for_viewport(document, viewport);
+    }
+    start.elapsed() / ITERATIONS
+}
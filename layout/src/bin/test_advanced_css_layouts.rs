@@ -71,15 +71,15 @@ fn test_flexbox_layouts() {
     
     let flex_container = &layout_box.children[0];
     assert_eq!(flex_container.styles.display, DisplayType::Flex);
-    assert_eq!(flex_container.styles.flex_direction, Some(FlexDirection::Row));
-    assert_eq!(flex_container.styles.justify_content, Some(JustifyContent::SpaceBetween));
-    assert_eq!(flex_container.styles.align_items, Some(AlignItems::Center));
+    assert_eq!(flex_container.styles.flex.direction, Some(FlexDirection::Row));
+    assert_eq!(flex_container.styles.flex.justify_content, Some(JustifyContent::SpaceBetween));
+    assert_eq!(flex_container.styles.flex.align_items, Some(AlignItems::Center));
     
     println!("✅ Flexbox container layout verified");
     println!("   - Display: {:?}", flex_container.styles.display);
-    println!("   - Flex direction: {:?}", flex_container.styles.flex_direction);
-    println!("   - Justify content: {:?}", flex_container.styles.justify_content);
-    println!("   - Align items: {:?}", flex_container.styles.align_items);
+    println!("   - Flex direction: {:?}", flex_container.styles.flex.direction);
+    println!("   - Justify content: {:?}", flex_container.styles.flex.justify_content);
+    println!("   - Align items: {:?}", flex_container.styles.flex.align_items);
     println!("   - Container size: {}x{}", flex_container.content.width, flex_container.content.height);
     
     // Test different flex directions
@@ -102,9 +102,9 @@ fn test_flex_directions() {
     for (direction, name) in directions {
         let mut styles = ComputedStyles::default();
         styles.display = DisplayType::Flex;
-        styles.flex_direction = Some(direction);
-        styles.width = Some(400.0);
-        styles.height = Some(200.0);
+        styles.flex_mut().direction = Some(direction);
+        styles.box_model_mut().width = Some(400.0);
+        styles.box_model_mut().height = Some(200.0);
         
         println!("   ✅ {} direction: {:?}", name, direction);
     }
@@ -123,7 +123,7 @@ fn test_flex_wrap() {
     for (wrap, name) in wrap_types {
         let mut styles = ComputedStyles::default();
         styles.display = DisplayType::Flex;
-        styles.flex_wrap = Some(wrap);
+        styles.flex_mut().wrap = Some(wrap);
         
         println!("   ✅ {} wrap: {:?}", name, wrap);
     }
@@ -145,7 +145,7 @@ fn test_flex_justify_content() {
     for (justify, name) in justify_values {
         let mut styles = ComputedStyles::default();
         styles.display = DisplayType::Flex;
-        styles.justify_content = Some(justify);
+        styles.flex_mut().justify_content = Some(justify);
         
         println!("   ✅ {} justify: {:?}", name, justify);
     }
@@ -183,15 +183,15 @@ fn test_grid_layouts() {
     // Verify grid layout
     let grid_container = &layout_box.children[0];
     assert_eq!(grid_container.styles.display, DisplayType::Grid);
-    assert!(grid_container.styles.grid_template_columns.is_some());
-    assert!(grid_container.styles.grid_template_rows.is_some());
-    assert_eq!(grid_container.styles.grid_gap, Some(10.0));
+    assert!(grid_container.styles.grid.template_columns.is_some());
+    assert!(grid_container.styles.grid.template_rows.is_some());
+    assert_eq!(grid_container.styles.grid.gap, Some(10.0));
     
     println!("✅ Grid container layout verified");
     println!("   - Display: {:?}", grid_container.styles.display);
-    println!("   - Grid columns: {:?}", grid_container.styles.grid_template_columns);
-    println!("   - Grid rows: {:?}", grid_container.styles.grid_template_rows);
-    println!("   - Grid gap: {:?}", grid_container.styles.grid_gap);
+    println!("   - Grid columns: {:?}", grid_container.styles.grid.template_columns);
+    println!("   - Grid rows: {:?}", grid_container.styles.grid.template_rows);
+    println!("   - Grid gap: {:?}", grid_container.styles.grid.gap);
     println!("   - Container size: {}x{}", grid_container.content.width, grid_container.content.height);
     
     // Test grid track types
@@ -324,19 +324,19 @@ fn test_nested_layouts() {
     // Create a flexbox container with grid children
     let mut styles = ComputedStyles::default();
     styles.display = DisplayType::Flex;
-    styles.flex_direction = Some(FlexDirection::Column);
-    styles.justify_content = Some(JustifyContent::SpaceBetween);
+    styles.flex_mut().direction = Some(FlexDirection::Column);
+    styles.flex_mut().justify_content = Some(JustifyContent::SpaceBetween);
     
     // Child with grid layout
     let mut child_styles = ComputedStyles::default();
     child_styles.display = DisplayType::Grid;
-    child_styles.grid_template_columns = Some(vec![GridTrack::Fractional(1.0), GridTrack::Fractional(2.0)]);
-    child_styles.grid_template_rows = Some(vec![GridTrack::Fixed(100.0), GridTrack::Fixed(100.0)]);
-    child_styles.grid_gap = Some(10.0);
+    child_styles.grid_mut().template_columns = Some(vec![GridTrack::Fractional(1.0), GridTrack::Fractional(2.0)]);
+    child_styles.grid_mut().template_rows = Some(vec![GridTrack::Fixed(100.0), GridTrack::Fixed(100.0)]);
+    child_styles.grid_mut().gap = Some(10.0);
     
     println!("   ✅ Nested flexbox → grid layout");
-    println!("   ✅ Parent flex direction: {:?}", styles.flex_direction);
-    println!("   ✅ Child grid columns: {:?}", child_styles.grid_template_columns);
+    println!("   ✅ Parent flex direction: {:?}", styles.flex.direction);
+    println!("   ✅ Child grid columns: {:?}", child_styles.grid.template_columns);
 }
 
 /// Test responsive layout properties
@@ -346,22 +346,22 @@ fn test_responsive_properties() {
     // Test flexible sizing
     let mut styles = ComputedStyles::default();
     styles.display = DisplayType::Flex;
-    styles.flex_grow = Some(1.0);
-    styles.flex_shrink = Some(0.5);
-    styles.flex_basis = Some(200.0);
+    styles.flex_mut().grow = Some(1.0);
+    styles.flex_mut().shrink = Some(0.5);
+    styles.flex_mut().basis = Some(200.0);
     
-    println!("   ✅ Flex grow: {:?}", styles.flex_grow);
-    println!("   ✅ Flex shrink: {:?}", styles.flex_shrink);
-    println!("   ✅ Flex basis: {:?}", styles.flex_basis);
+    println!("   ✅ Flex grow: {:?}", styles.flex.grow);
+    println!("   ✅ Flex shrink: {:?}", styles.flex.shrink);
+    println!("   ✅ Flex basis: {:?}", styles.flex.basis);
     
     // Test grid positioning
-    styles.grid_column_start = Some(1);
-    styles.grid_column_end = Some(3);
-    styles.grid_row_start = Some(1);
-    styles.grid_row_end = Some(2);
+    styles.grid_mut().column_start = Some(1);
+    styles.grid_mut().column_end = Some(3);
+    styles.grid_mut().row_start = Some(1);
+    styles.grid_mut().row_end = Some(2);
     
-    println!("   ✅ Grid column: {} to {}", styles.grid_column_start.unwrap(), styles.grid_column_end.unwrap());
-    println!("   ✅ Grid row: {} to {}", styles.grid_row_start.unwrap(), styles.grid_row_end.unwrap());
+    println!("   ✅ Grid column: {} to {}", styles.grid.column_start.unwrap(), styles.grid.column_end.unwrap());
+    println!("   ✅ Grid row: {} to {}", styles.grid.row_start.unwrap(), styles.grid.row_end.unwrap());
 }
 
 /// Helper functions
@@ -391,15 +391,15 @@ fn create_element(tag_name: &str, id: &str) -> Element {
 
 fn create_flexbox_stylesheet() -> Stylesheet {
     // Create a simple stylesheet with flexbox properties
-    Stylesheet { rules: vec![], source_url: None }
+    Stylesheet { rules: vec![], page_rules: vec![], source_url: None }
 }
 
 fn create_grid_stylesheet() -> Stylesheet {
     // Create a simple stylesheet with grid properties
-    Stylesheet { rules: vec![], source_url: None }
+    Stylesheet { rules: vec![], page_rules: vec![], source_url: None }
 }
 
 fn create_animation_stylesheet() -> Stylesheet {
     // Create a simple stylesheet with animation properties
-    Stylesheet { rules: vec![], source_url: None }
+    Stylesheet { rules: vec![], page_rules: vec![], source_url: None }
 }
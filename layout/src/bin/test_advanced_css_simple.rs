@@ -34,30 +34,30 @@ fn test_flexbox_properties() {
     
     // Test flex direction
     styles.display = DisplayType::Flex;
-    styles.flex_direction = Some(FlexDirection::Row);
-    styles.justify_content = Some(JustifyContent::SpaceBetween);
-    styles.align_items = Some(AlignItems::Center);
-    styles.flex_wrap = Some(FlexWrap::Wrap);
+    styles.flex_mut().direction = Some(FlexDirection::Row);
+    styles.flex_mut().justify_content = Some(JustifyContent::SpaceBetween);
+    styles.flex_mut().align_items = Some(AlignItems::Center);
+    styles.flex_mut().wrap = Some(FlexWrap::Wrap);
     
     println!("✅ Flexbox properties set:");
     println!("   - Display: {:?}", styles.display);
-    println!("   - Flex direction: {:?}", styles.flex_direction);
-    println!("   - Justify content: {:?}", styles.justify_content);
-    println!("   - Align items: {:?}", styles.align_items);
-    println!("   - Flex wrap: {:?}", styles.flex_wrap);
+    println!("   - Flex direction: {:?}", styles.flex.direction);
+    println!("   - Justify content: {:?}", styles.flex.justify_content);
+    println!("   - Align items: {:?}", styles.flex.align_items);
+    println!("   - Flex wrap: {:?}", styles.flex.wrap);
     
     // Test flex item properties
     let mut item_styles = ComputedStyles::default();
-    item_styles.flex_grow = Some(1.0);
-    item_styles.flex_shrink = Some(0.5);
-    item_styles.flex_basis = Some(200.0);
-    item_styles.align_self = Some(AlignSelf::FlexEnd);
+    item_styles.flex_mut().grow = Some(1.0);
+    item_styles.flex_mut().shrink = Some(0.5);
+    item_styles.flex_mut().basis = Some(200.0);
+    item_styles.flex_mut().align_self = Some(AlignSelf::FlexEnd);
     
     println!("✅ Flex item properties set:");
-    println!("   - Flex grow: {:?}", item_styles.flex_grow);
-    println!("   - Flex shrink: {:?}", item_styles.flex_shrink);
-    println!("   - Flex basis: {:?}", item_styles.flex_basis);
-    println!("   - Align self: {:?}", item_styles.align_self);
+    println!("   - Flex grow: {:?}", item_styles.flex.grow);
+    println!("   - Flex shrink: {:?}", item_styles.flex.shrink);
+    println!("   - Flex basis: {:?}", item_styles.flex.basis);
+    println!("   - Align self: {:?}", item_styles.flex.align_self);
 }
 
 /// Test grid properties
@@ -69,40 +69,40 @@ fn test_grid_properties() {
     
     // Test grid container properties
     styles.display = DisplayType::Grid;
-    styles.grid_template_columns = Some(vec![
+    styles.grid_mut().template_columns = Some(vec![
         GridTrack::Fixed(100.0),
         GridTrack::Fractional(1.0),
         GridTrack::Fractional(2.0),
     ]);
-    styles.grid_template_rows = Some(vec![
+    styles.grid_mut().template_rows = Some(vec![
         GridTrack::Fixed(50.0),
         GridTrack::Auto,
         GridTrack::MinContent,
     ]);
-    styles.grid_gap = Some(10.0);
-    styles.grid_column_gap = Some(5.0);
-    styles.grid_row_gap = Some(15.0);
+    styles.grid_mut().gap = Some(10.0);
+    styles.grid_mut().column_gap = Some(5.0);
+    styles.grid_mut().row_gap = Some(15.0);
     
     println!("✅ Grid container properties set:");
     println!("   - Display: {:?}", styles.display);
-    println!("   - Grid columns: {:?}", styles.grid_template_columns);
-    println!("   - Grid rows: {:?}", styles.grid_template_rows);
-    println!("   - Grid gap: {:?}", styles.grid_gap);
-    println!("   - Column gap: {:?}", styles.grid_column_gap);
-    println!("   - Row gap: {:?}", styles.grid_row_gap);
+    println!("   - Grid columns: {:?}", styles.grid.template_columns);
+    println!("   - Grid rows: {:?}", styles.grid.template_rows);
+    println!("   - Grid gap: {:?}", styles.grid.gap);
+    println!("   - Column gap: {:?}", styles.grid.column_gap);
+    println!("   - Row gap: {:?}", styles.grid.row_gap);
     
     // Test grid item properties
     let mut item_styles = ComputedStyles::default();
-    item_styles.grid_column_start = Some(1);
-    item_styles.grid_column_end = Some(3);
-    item_styles.grid_row_start = Some(1);
-    item_styles.grid_row_end = Some(2);
+    item_styles.grid_mut().column_start = Some(1);
+    item_styles.grid_mut().column_end = Some(3);
+    item_styles.grid_mut().row_start = Some(1);
+    item_styles.grid_mut().row_end = Some(2);
     
     println!("✅ Grid item properties set:");
-    println!("   - Column start: {:?}", item_styles.grid_column_start);
-    println!("   - Column end: {:?}", item_styles.grid_column_end);
-    println!("   - Row start: {:?}", item_styles.grid_row_start);
-    println!("   - Row end: {:?}", item_styles.grid_row_end);
+    println!("   - Column start: {:?}", item_styles.grid.column_start);
+    println!("   - Column end: {:?}", item_styles.grid.column_end);
+    println!("   - Row start: {:?}", item_styles.grid.row_start);
+    println!("   - Row end: {:?}", item_styles.grid.row_end);
 }
 
 /// Test animation properties
@@ -166,7 +166,7 @@ fn _test_layout_engine_advanced() {
     println!("--------------------------------------------------");
     
     // Create a simple stylesheet
-    let stylesheet = Stylesheet { rules: vec![], source_url: None };
+    let stylesheet = Stylesheet { rules: vec![], page_rules: vec![], source_url: None };
     let _layout_engine = LayoutEngine::new(stylesheet);
     
     println!("✅ Layout engine created with advanced CSS support");
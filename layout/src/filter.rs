@@ -0,0 +1,226 @@
+//! # Filter Effects
+//!
+//! This module parses the `filter` property into the list of filter
+//! functions the compositor applies to an element and its descendants.
+//!
+//! ## Design Principles
+//!
+//! 1. **All or Nothing**: A list with an unknown function or a malformed
+//!    argument is invalid as a whole, as the spec requires, and leaves the
+//!    previous value in place.
+//!
+//! 2. **Resolved Arguments**: Defaults are filled in and percentages turned
+//!    into factors while parsing, so passes read plain numbers.
+//!
+//! 3. **Colors Stay Text**: Like every other color in `ComputedStyles`, a
+//!    `drop-shadow` color is kept as written and resolved by the painter.
+
+/// One function of a `filter` list
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterFunction {
+    /// Gaussian blur with this standard deviation, in pixels
+    Blur(f32),
+    /// Mix towards luminance; 0 leaves colors alone, 1 is fully gray
+    Grayscale(f32),
+    /// Multiply each channel; 1 leaves colors alone
+    Brightness(f32),
+    /// Scale each channel's distance from mid-gray; 1 leaves colors alone
+    Contrast(f32),
+    /// A blurred, offset copy of the alpha channel painted underneath
+    DropShadow {
+        offset_x: f32,
+        offset_y: f32,
+        /// Standard deviation of the shadow's blur, in pixels
+        blur: f32,
+        /// `None` uses the element's `color`
+        color: Option<String>,
+    },
+}
+
+impl FilterFunction {
+    /// Check whether the function only changes colors, pixel by pixel
+    pub fn is_color_only(&self) -> bool {
+        matches!(self, FilterFunction::Grayscale(_) | FilterFunction::Brightness(_) | FilterFunction::Contrast(_))
+    }
+}
+
+/// Parse a `filter` value such as `blur(2px) grayscale(100%)`
+///
+/// `none` is the empty list. Returns `None` for invalid values.
+pub fn parse_filter_list(value: &str) -> Option<Vec<FilterFunction>> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("none") {
+        return Some(Vec::new());
+    }
+    let mut filters = Vec::new();
+    let mut rest = value;
+    while !rest.is_empty() {
+        let open = rest.find('(')?;
+        let name = rest[..open].trim().to_ascii_lowercase();
+        let close = matching_paren(rest, open)?;
+        let arguments = &rest[open + 1..close];
+        filters.push(parse_function(&name, arguments)?);
+        rest = rest[close + 1..].trim_start();
+    }
+    if filters.is_empty() {
+        None
+    } else {
+        Some(filters)
+    }
+}
+
+/// Find the `)` closing the `(` at `open`
+fn matching_paren(text: &str, open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (index, c) in text.char_indices().skip_while(|(index, _)| *index < open) {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(index);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_function(name: &str, arguments: &str) -> Option<FilterFunction> {
+    let arguments = arguments.trim();
+    match name {
+        "blur" => {
+            let radius = if arguments.is_empty() { 0.0 } else { parse_length(arguments)? };
+            (radius >= 0.0).then_some(FilterFunction::Blur(radius))
+        }
+        "grayscale" => Some(FilterFunction::Grayscale(parse_amount(arguments)?.min(1.0))),
+        "brightness" => Some(FilterFunction::Brightness(parse_amount(arguments)?)),
+        "contrast" => Some(FilterFunction::Contrast(parse_amount(arguments)?)),
+        "drop-shadow" => parse_drop_shadow(arguments),
+        _ => None,
+    }
+}
+
+/// Parse a number or percentage argument; empty means 1
+fn parse_amount(argument: &str) -> Option<f32> {
+    if argument.is_empty() {
+        return Some(1.0);
+    }
+    let amount = match argument.strip_suffix('%') {
+        Some(percent) => percent.trim().parse::<f32>().ok()? / 100.0,
+        None => argument.parse::<f32>().ok()?,
+    };
+    (amount >= 0.0).then_some(amount)
+}
+
+/// Parse a length in pixels; `0` may omit its unit
+fn parse_length(argument: &str) -> Option<f32> {
+    let argument = argument.trim().to_ascii_lowercase();
+    let number_end = argument
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+        .unwrap_or(argument.len());
+    let (number, unit) = argument.split_at(number_end);
+    let number = number.parse::<f32>().ok()?;
+    match unit {
+        "px" => Some(number),
+        "em" | "rem" => Some(number * 16.0),
+        "" if number == 0.0 => Some(0.0),
+        _ => None,
+    }
+}
+
+/// Parse `drop-shadow(<color>? <offset-x> <offset-y> <blur>?)`, where the
+/// color may also come last
+fn parse_drop_shadow(arguments: &str) -> Option<FilterFunction> {
+    let mut lengths = Vec::new();
+    let mut color = None;
+    // Lengths must be contiguous, so none may follow a trailing color
+    let mut color_after_lengths = false;
+    for part in split_arguments(arguments) {
+        match parse_length(&part) {
+            Some(length) if !color_after_lengths => lengths.push(length),
+            Some(_) => return None,
+            None if color.is_none() && (lengths.is_empty() || lengths.len() >= 2) => {
+                color_after_lengths = !lengths.is_empty();
+                color = Some(part);
+            }
+            None => return None,
+        }
+    }
+    match lengths.as_slice() {
+        [offset_x, offset_y] => Some(FilterFunction::DropShadow { offset_x: *offset_x, offset_y: *offset_y, blur: 0.0, color }),
+        [offset_x, offset_y, blur] if *blur >= 0.0 => {
+            Some(FilterFunction::DropShadow { offset_x: *offset_x, offset_y: *offset_y, blur: *blur, color })
+        }
+        _ => None,
+    }
+}
+
+/// Split arguments on whitespace and commas outside nested functions
+fn split_arguments(arguments: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    for c in arguments.chars() {
+        match c {
+            '(' => {
+                depth += 1;
+                current.push(c);
+            }
+            ')' => {
+                depth -= 1;
+                current.push(c);
+            }
+            c if depth == 0 && (c.is_whitespace() || c == ',') => {
+                if !current.is_empty() {
+                    parts.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        parts.push(current);
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_filter_list() {
+        assert_eq!(
+            parse_filter_list("blur(2px) grayscale(50%) brightness(1.5) contrast()"),
+            Some(vec![
+                FilterFunction::Blur(2.0),
+                FilterFunction::Grayscale(0.5),
+                FilterFunction::Brightness(1.5),
+                FilterFunction::Contrast(1.0),
+            ])
+        );
+        assert_eq!(
+            parse_filter_list("drop-shadow(rgba(0, 0, 0, 0.5) 2px 4px 6px)"),
+            Some(vec![FilterFunction::DropShadow {
+                offset_x: 2.0,
+                offset_y: 4.0,
+                blur: 6.0,
+                color: Some("rgba(0, 0, 0, 0.5)".to_string()),
+            }])
+        );
+        assert_eq!(
+            parse_filter_list("drop-shadow(1px 1px red)"),
+            Some(vec![FilterFunction::DropShadow { offset_x: 1.0, offset_y: 1.0, blur: 0.0, color: Some("red".to_string()) }])
+        );
+        assert_eq!(parse_filter_list("grayscale(300%)"), Some(vec![FilterFunction::Grayscale(1.0)]));
+        assert_eq!(parse_filter_list("none"), Some(Vec::new()));
+
+        // One bad function invalidates the whole list
+        assert_eq!(parse_filter_list("blur(2px) sepia(1)"), None);
+        assert_eq!(parse_filter_list("blur(-1px)"), None);
+        assert_eq!(parse_filter_list("drop-shadow(1px)"), None);
+        assert_eq!(parse_filter_list("blur(2px"), None);
+    }
+}
@@ -0,0 +1,651 @@
+//! # Font Selection and Shaping
+//!
+//! This module resolves the font properties of computed styles into the
+//! OpenType features and the face that text is shaped with, and shapes
+//! runs of text into glyphs.
+//!
+//! ## Design Principles
+//!
+//! 1. **Features Are Data**: `font-variant-*` keywords and
+//!    `font-feature-settings` all resolve to one list of feature tags, with
+//!    the low-level settings applied last as CSS requires. The shaper only
+//!    ever sees tags.
+//!
+//! 2. **Synthesis as a Fallback**: Bold, italic and small caps are
+//!    synthesized only when the selected family has no face for them and
+//!    `font-synthesis` allows it, and the selection records that it did.
+//!
+//! 3. **Configurable Fallback Chains**: Each family can name the families
+//!    tried after it, loaded from a plain-text configuration, before the
+//!    registry's default family.
+//!
+//! Glyph advances still come from the average advance of `inline` until
+//! real font files are loaded; ligatures form single glyphs over the
+//! advance of their components.
+
+use std::collections::HashMap;
+use std::fmt;
+use crate::inline::AVERAGE_CHAR_WIDTH;
+use crate::measurement::FontKey;
+
+/// Synthesized small capitals are capitals scaled to this fraction
+pub const SMALL_CAPS_SCALE: f32 = 0.7;
+
+/// Extra advance of a synthetically emboldened glyph, in ems
+pub const SYNTHETIC_BOLD_ADVANCE: f32 = 0.02;
+
+/// Family of the test font bundled for deterministic layout
+pub const TEST_FONT_FAMILY: &str = "dubby-test";
+
+/// Ligatures formed by `liga`, longest first
+const COMMON_LIGATURES: [&str; 5] = ["ffi", "ffl", "ff", "fi", "fl"];
+
+/// Features on unless turned off
+const DEFAULT_FEATURES: [&str; 4] = ["calt", "clig", "kern", "liga"];
+
+/// An OpenType feature tag and its value
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FontFeature {
+    pub tag: String,
+    pub value: u32,
+}
+
+impl FontFeature {
+    pub fn new(tag: &str, value: u32) -> Self {
+        FontFeature { tag: tag.to_string(), value }
+    }
+}
+
+/// Parse `font-feature-settings`, such as `"liga" 0, "tnum"`
+///
+/// Returns `None` for an invalid value; `normal` is an empty list.
+pub fn parse_feature_settings(value: &str) -> Option<Vec<FontFeature>> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("normal") {
+        return Some(Vec::new());
+    }
+    let mut features: Vec<FontFeature> = Vec::new();
+    for token in value.split(|c: char| c.is_whitespace() || c == ',').filter(|token| !token.is_empty()) {
+        let quoted = token.len() == 6 && (token.starts_with('"') && token.ends_with('"') || token.starts_with('\'') && token.ends_with('\''));
+        if quoted {
+            let tag = &token[1..5];
+            if !tag.chars().all(|c| c.is_ascii_graphic()) {
+                return None;
+            }
+            features.push(FontFeature::new(tag, 1));
+            continue;
+        }
+        let value = match token.to_ascii_lowercase().as_str() {
+            "on" => 1,
+            "off" => 0,
+            number => number.parse::<u32>().ok()?,
+        };
+        features.last_mut()?.value = value;
+    }
+    Some(features)
+}
+
+/// `font-style`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum FontStyle {
+    #[default]
+    Normal,
+    Italic,
+    Oblique,
+}
+
+impl FontStyle {
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().to_ascii_lowercase();
+        match value.split_whitespace().next()? {
+            "normal" => Some(FontStyle::Normal),
+            "italic" => Some(FontStyle::Italic),
+            // An angle may follow
+            "oblique" => Some(FontStyle::Oblique),
+            _ => None,
+        }
+    }
+
+    pub fn is_slanted(&self) -> bool {
+        *self != FontStyle::Normal
+    }
+}
+
+/// `font-synthesis`: what may be synthesized when a face is missing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FontSynthesis {
+    pub weight: bool,
+    pub style: bool,
+    pub small_caps: bool,
+}
+
+impl Default for FontSynthesis {
+    fn default() -> Self {
+        FontSynthesis { weight: true, style: true, small_caps: true }
+    }
+}
+
+impl FontSynthesis {
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().to_ascii_lowercase();
+        let mut synthesis = FontSynthesis { weight: false, style: false, small_caps: false };
+        if value == "none" {
+            return Some(synthesis);
+        }
+        for keyword in value.split_whitespace() {
+            match keyword {
+                "weight" => synthesis.weight = true,
+                "style" => synthesis.style = true,
+                "small-caps" => synthesis.small_caps = true,
+                _ => return None,
+            }
+        }
+        Some(synthesis)
+    }
+}
+
+/// The font-variant longhands and `font-feature-settings`
+///
+/// Each field is `None` until set and then inherited; `Some` of an empty
+/// list is `normal`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FontFeatures {
+    pub ligatures: Option<Vec<String>>,
+    pub caps: Option<String>,
+    pub numeric: Option<Vec<String>>,
+    pub settings: Option<Vec<FontFeature>>,
+}
+
+/// The OpenType features a font keyword turns on
+type KeywordFeatures = fn(&str) -> Option<Vec<(&'static str, u32)>>;
+
+fn ligature_features(keyword: &str) -> Option<Vec<(&'static str, u32)>> {
+    Some(match keyword {
+        "none" => vec![("liga", 0), ("clig", 0), ("dlig", 0), ("hlig", 0), ("calt", 0)],
+        "common-ligatures" => vec![("liga", 1), ("clig", 1)],
+        "no-common-ligatures" => vec![("liga", 0), ("clig", 0)],
+        "discretionary-ligatures" => vec![("dlig", 1)],
+        "no-discretionary-ligatures" => vec![("dlig", 0)],
+        "historical-ligatures" => vec![("hlig", 1)],
+        "no-historical-ligatures" => vec![("hlig", 0)],
+        "contextual" => vec![("calt", 1)],
+        "no-contextual" => vec![("calt", 0)],
+        _ => return None,
+    })
+}
+
+fn caps_features(keyword: &str) -> Option<Vec<(&'static str, u32)>> {
+    Some(match keyword {
+        "normal" => Vec::new(),
+        "small-caps" => vec![("smcp", 1)],
+        "all-small-caps" => vec![("smcp", 1), ("c2sc", 1)],
+        "petite-caps" => vec![("pcap", 1)],
+        "all-petite-caps" => vec![("pcap", 1), ("c2pc", 1)],
+        "unicase" => vec![("unic", 1)],
+        "titling-caps" => vec![("titl", 1)],
+        _ => return None,
+    })
+}
+
+fn numeric_features(keyword: &str) -> Option<Vec<(&'static str, u32)>> {
+    Some(match keyword {
+        "lining-nums" => vec![("lnum", 1)],
+        "oldstyle-nums" => vec![("onum", 1)],
+        "proportional-nums" => vec![("pnum", 1)],
+        "tabular-nums" => vec![("tnum", 1)],
+        "diagonal-fractions" => vec![("frac", 1)],
+        "stacked-fractions" => vec![("afrc", 1)],
+        "ordinal" => vec![("ordn", 1)],
+        "slashed-zero" => vec![("zero", 1)],
+        _ => return None,
+    })
+}
+
+/// Parse a list of keywords that each map to features; `normal` is empty
+fn parse_keywords(value: &str, features: KeywordFeatures) -> Option<Vec<String>> {
+    let value = value.trim().to_ascii_lowercase();
+    if value == "normal" {
+        return Some(Vec::new());
+    }
+    let keywords: Vec<String> = value.split_whitespace().map(str::to_string).collect();
+    if keywords.is_empty() || keywords.iter().any(|keyword| features(keyword).is_none()) {
+        return None;
+    }
+    Some(keywords)
+}
+
+impl FontFeatures {
+    /// Set `font-variant-ligatures`, returning whether the value was valid
+    pub fn set_ligatures(&mut self, value: &str) -> bool {
+        let keywords = parse_keywords(value, ligature_features);
+        let valid = keywords.is_some();
+        self.ligatures = keywords.or(self.ligatures.take());
+        valid
+    }
+
+    /// Set `font-variant-caps`, returning whether the value was valid
+    pub fn set_caps(&mut self, value: &str) -> bool {
+        let value = value.trim().to_ascii_lowercase();
+        if caps_features(&value).is_none() {
+            return false;
+        }
+        self.caps = Some(value);
+        true
+    }
+
+    /// Set `font-variant-numeric`, returning whether the value was valid
+    pub fn set_numeric(&mut self, value: &str) -> bool {
+        let keywords = parse_keywords(value, numeric_features);
+        let valid = keywords.is_some();
+        self.numeric = keywords.or(self.numeric.take());
+        valid
+    }
+
+    /// Set the `font-variant` shorthand, which resets the longhands it
+    /// does not name
+    pub fn set_variant(&mut self, value: &str) -> bool {
+        let value = value.trim().to_ascii_lowercase();
+        let (mut ligatures, mut caps, mut numeric) = (Vec::new(), None, Vec::new());
+        if value != "normal" {
+            for keyword in value.split_whitespace() {
+                if ligature_features(keyword).is_some() {
+                    ligatures.push(keyword.to_string());
+                } else if keyword != "normal" && caps_features(keyword).is_some() && caps.is_none() {
+                    caps = Some(keyword.to_string());
+                } else if numeric_features(keyword).is_some() {
+                    numeric.push(keyword.to_string());
+                } else {
+                    return false;
+                }
+            }
+        }
+        self.ligatures = Some(ligatures);
+        self.caps = Some(caps.unwrap_or_else(|| "normal".to_string()));
+        self.numeric = Some(numeric);
+        true
+    }
+
+    /// Set `font-feature-settings`, returning whether the value was valid
+    pub fn set_settings(&mut self, value: &str) -> bool {
+        match parse_feature_settings(value) {
+            Some(settings) => {
+                self.settings = Some(settings);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Take the parent's value for every longhand not set here
+    pub fn inherit(&mut self, parent: &FontFeatures) {
+        if self.ligatures.is_none() {
+            self.ligatures = parent.ligatures.clone();
+        }
+        if self.caps.is_none() {
+            self.caps = parent.caps.clone();
+        }
+        if self.numeric.is_none() {
+            self.numeric = parent.numeric.clone();
+        }
+        if self.settings.is_none() {
+            self.settings = parent.settings.clone();
+        }
+    }
+
+    /// Resolve the features text is shaped with, sorted by tag
+    pub fn resolve(&self) -> Vec<FontFeature> {
+        let mut features: HashMap<String, u32> = DEFAULT_FEATURES.iter().map(|tag| (tag.to_string(), 1)).collect();
+        let mut apply = |list: Vec<(&'static str, u32)>| {
+            for (tag, value) in list {
+                features.insert(tag.to_string(), value);
+            }
+        };
+        for keyword in self.ligatures.iter().flatten() {
+            apply(ligature_features(keyword).unwrap_or_default());
+        }
+        if let Some(caps) = &self.caps {
+            apply(caps_features(caps).unwrap_or_default());
+        }
+        for keyword in self.numeric.iter().flatten() {
+            apply(numeric_features(keyword).unwrap_or_default());
+        }
+        for setting in self.settings.iter().flatten() {
+            features.insert(setting.tag.clone(), setting.value);
+        }
+        let mut resolved: Vec<FontFeature> = features.into_iter().map(|(tag, value)| FontFeature { tag, value }).collect();
+        resolved.sort();
+        resolved
+    }
+}
+
+/// Check whether a feature is on in a resolved list
+pub fn feature_enabled(features: &[FontFeature], tag: &str) -> bool {
+    features.iter().any(|feature| feature.tag == tag && feature.value > 0)
+}
+
+/// Get the numeric value of a `font-weight`
+pub fn weight_value(weight: &str) -> u16 {
+    match weight.trim().to_ascii_lowercase().as_str() {
+        "bold" | "bolder" => 700,
+        "lighter" => 300,
+        "normal" | "" => 400,
+        number => number.parse::<f32>().map(|weight| weight.clamp(1.0, 1000.0) as u16).unwrap_or(400),
+    }
+}
+
+/// A face the registry can select
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontFace {
+    pub family: String,
+    pub weight: u16,
+    pub italic: bool,
+    /// Whether the face has small capital glyphs (`smcp`)
+    pub small_caps: bool,
+}
+
+impl FontFace {
+    pub fn new(family: &str, weight: u16, italic: bool) -> Self {
+        FontFace { family: family.to_ascii_lowercase(), weight, italic, small_caps: false }
+    }
+
+    /// Get the metrics of the face
+    ///
+    /// Until font files are loaded the x-height is typical of the face's
+    /// generic family, and half an em otherwise, as CSS suggests when it
+    /// cannot be measured; the zero is as wide as any glyph.
+    pub fn metrics(&self) -> FontMetrics {
+        let x_height = match self.family.as_str() {
+            "serif" => 0.45,
+            "sans-serif" => 0.52,
+            "monospace" => 0.53,
+            _ => 0.5,
+        };
+        FontMetrics { x_height, zero_advance: AVERAGE_CHAR_WIDTH }
+    }
+}
+
+/// Metrics of a face, in ems
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FontMetrics {
+    /// Height of lowercase letters, which `ex` refers to
+    pub x_height: f32,
+    /// Advance of the digit zero, which `ch` refers to
+    pub zero_advance: f32,
+}
+
+/// The face chosen for a font, and what is synthesized on top of it
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontSelection {
+    pub face: FontFace,
+    pub synthetic_bold: bool,
+    /// Slanted by the renderer, as faces without italics are
+    pub synthetic_italic: bool,
+    /// Whether small caps are drawn, natively or synthesized
+    pub small_caps: bool,
+    pub synthetic_small_caps: bool,
+}
+
+/// Errors in a font fallback configuration
+#[derive(Debug, Clone, PartialEq)]
+pub enum FontConfigError {
+    /// A line is not `family = fallback, fallback`
+    InvalidLine(usize),
+}
+
+impl fmt::Display for FontConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FontConfigError::InvalidLine(line) => write!(f, "line {} is not `family = fallback, ...`", line),
+        }
+    }
+}
+
+impl std::error::Error for FontConfigError {}
+
+/// Split a `font-family` list into lowercase names without quotes
+fn family_names(families: &str) -> Vec<String> {
+    families
+        .split(',')
+        .map(|family| family.trim().trim_matches(|c| c == '"' || c == '\'').to_ascii_lowercase())
+        .filter(|family| !family.is_empty())
+        .collect()
+}
+
+/// The faces available to layout and the fallback chain of each family
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontRegistry {
+    faces: Vec<FontFace>,
+    fallbacks: HashMap<String, Vec<String>>,
+    default_family: String,
+}
+
+impl Default for FontRegistry {
+    /// The generic families, each with regular, bold and italic faces
+    fn default() -> Self {
+        let mut registry = FontRegistry::new("sans-serif");
+        for family in ["serif", "sans-serif", "monospace"] {
+            for (weight, italic) in [(400, false), (700, false), (400, true), (700, true)] {
+                registry.add_face(FontFace::new(family, weight, italic));
+            }
+        }
+        registry
+    }
+}
+
+impl FontRegistry {
+    /// Create an empty registry that falls back to `default_family`
+    pub fn new(default_family: &str) -> Self {
+        FontRegistry { faces: Vec::new(), fallbacks: HashMap::new(), default_family: default_family.to_ascii_lowercase() }
+    }
+
+    /// The bundled test font alone, for deterministic layout
+    ///
+    /// Every `font-family` resolves to `TEST_FONT_FAMILY`, so a page lays
+    /// out the same whatever fonts the machine has.
+    pub fn test_fonts() -> Self {
+        let mut registry = FontRegistry::new(TEST_FONT_FAMILY);
+        for (weight, italic) in [(400, false), (700, false), (400, true), (700, true)] {
+            registry.add_face(FontFace::new(TEST_FONT_FAMILY, weight, italic));
+        }
+        registry
+    }
+
+    pub fn add_face(&mut self, face: FontFace) {
+        self.faces.push(face);
+    }
+
+    /// Set the families tried, in order, when `family` has no faces
+    pub fn set_fallbacks(&mut self, family: &str, chain: &[&str]) {
+        self.fallbacks.insert(family.to_ascii_lowercase(), chain.iter().map(|name| name.to_ascii_lowercase()).collect());
+    }
+
+    /// Load fallback chains from lines such as `Helvetica = Arial, sans-serif`
+    ///
+    /// Blank lines and lines starting with `#` are skipped. Returns the
+    /// number of chains loaded.
+    pub fn load_fallback_config(&mut self, config: &str) -> Result<usize, FontConfigError> {
+        let mut loaded = 0;
+        for (index, line) in config.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (family, chain) = line.split_once('=').ok_or(FontConfigError::InvalidLine(index + 1))?;
+            let family = family.trim().to_ascii_lowercase();
+            let chain = family_names(chain);
+            if family.is_empty() || chain.is_empty() {
+                return Err(FontConfigError::InvalidLine(index + 1));
+            }
+            self.fallbacks.insert(family, chain);
+            loaded += 1;
+        }
+        Ok(loaded)
+    }
+
+    /// Get the families tried for a `font-family` list, in order
+    fn candidates(&self, families: &str) -> Vec<String> {
+        let mut candidates: Vec<String> = Vec::new();
+        for family in family_names(families) {
+            let chain = self.fallbacks.get(&family).cloned().unwrap_or_default();
+            for name in std::iter::once(family).chain(chain) {
+                if !candidates.contains(&name) {
+                    candidates.push(name);
+                }
+            }
+        }
+        if !candidates.contains(&self.default_family) {
+            candidates.push(self.default_family.clone());
+        }
+        candidates
+    }
+
+    /// Get the metrics of the first family of a `font-family` list with a
+    /// face
+    pub fn metrics(&self, families: &str) -> FontMetrics {
+        self.candidates(families)
+            .iter()
+            .find_map(|family| self.faces.iter().find(|face| &face.family == family))
+            .map_or_else(|| FontFace::new(&self.default_family, 400, false).metrics(), FontFace::metrics)
+    }
+
+    /// Select the face for a font
+    ///
+    /// The first family with any face wins. Within it the face with the
+    /// requested slant and the nearest weight is chosen, and whatever it
+    /// lacks is synthesized if `font-synthesis` allows.
+    pub fn select(&self, font: &FontKey) -> FontSelection {
+        let weight = weight_value(&font.weight);
+        let slanted = font.style.is_slanted();
+        let faces: Vec<&FontFace> = self
+            .candidates(&font.family)
+            .iter()
+            .map(|family| self.faces.iter().filter(|face| &face.family == family).collect::<Vec<_>>())
+            .find(|faces| !faces.is_empty())
+            .unwrap_or_default();
+        let face = faces
+            .iter()
+            .min_by_key(|face| ((face.italic != slanted) as u32, (face.weight as i32 - weight as i32).unsigned_abs()))
+            .map(|face| (*face).clone())
+            .unwrap_or_else(|| FontFace::new(&self.default_family, 400, false));
+
+        let wants_small_caps = feature_enabled(&font.features, "smcp") || feature_enabled(&font.features, "c2sc");
+        let synthetic_small_caps = wants_small_caps && !face.small_caps && font.synthesis.small_caps;
+        FontSelection {
+            synthetic_bold: weight >= 600 && face.weight < 600 && font.synthesis.weight,
+            synthetic_italic: slanted && !face.italic && font.synthesis.style,
+            small_caps: wants_small_caps && (face.small_caps || synthetic_small_caps),
+            synthetic_small_caps,
+            face,
+        }
+    }
+}
+
+/// A shaped glyph and the characters it covers
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShapedGlyph {
+    /// The characters drawn, after case mapping for small caps
+    pub text: String,
+    pub advance: f32,
+}
+
+/// A run of shaped text
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShapedText {
+    pub glyphs: Vec<ShapedGlyph>,
+    /// The features the run was shaped with
+    pub features: Vec<FontFeature>,
+    pub selection: FontSelection,
+}
+
+impl ShapedText {
+    pub fn width(&self) -> f32 {
+        self.glyphs.iter().map(|glyph| glyph.advance).sum()
+    }
+}
+
+/// Shape a run of text with a font and its selected face
+pub fn shape(text: &str, font: &FontKey, selection: &FontSelection) -> ShapedText {
+    let advance = font.size() * AVERAGE_CHAR_WIDTH;
+    let embolden = if selection.synthetic_bold { font.size() * SYNTHETIC_BOLD_ADVANCE } else { 0.0 };
+    // Small capitals are case-mapped one character at a time
+    let ligatures = feature_enabled(&font.features, "liga") && !selection.small_caps;
+    let capitals_too = feature_enabled(&font.features, "c2sc");
+
+    let chars: Vec<char> = text.chars().collect();
+    let mut glyphs = Vec::new();
+    let mut index = 0;
+    while index < chars.len() {
+        if ligatures {
+            let rest: String = chars[index..chars.len().min(index + 3)].iter().collect();
+            if let Some(ligature) = COMMON_LIGATURES.iter().find(|ligature| rest.starts_with(*ligature)) {
+                let count = ligature.len();
+                glyphs.push(ShapedGlyph { text: ligature.to_string(), advance: advance * count as f32 + embolden });
+                index += count;
+                continue;
+            }
+        }
+        let c = chars[index];
+        let small = selection.small_caps && (c.is_lowercase() || (capitals_too && c.is_uppercase()));
+        let (text, scale) = if small { (c.to_uppercase().collect(), SMALL_CAPS_SCALE) } else { (c.to_string(), 1.0) };
+        glyphs.push(ShapedGlyph { text, advance: advance * scale + embolden });
+        index += 1;
+    }
+    ShapedText { glyphs, features: font.features.clone(), selection: selection.clone() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ComputedStyles;
+
+    #[test]
+    fn test_features_synthesis_and_fallbacks() {
+        let mut features = FontFeatures::default();
+        assert!(features.set_variant("small-caps tabular-nums no-common-ligatures"));
+        assert!(features.set_settings("\"liga\" 1, 'zero'"));
+        assert!(!features.set_numeric("tabular-nums sideways"));
+        let resolved = features.resolve();
+        for tag in ["smcp", "tnum", "zero", "liga", "kern"] {
+            assert!(feature_enabled(&resolved, tag), "{} should be on", tag);
+        }
+        // Settings win over the variant that turned ligatures off
+        assert!(!feature_enabled(&resolved, "clig"));
+        assert_eq!(parse_feature_settings("\"liga\" off"), Some(vec![FontFeature::new("liga", 0)]));
+        assert_eq!(parse_feature_settings("\"toolong\""), None);
+
+        let mut registry = FontRegistry::new("sans-serif");
+        registry.add_face(FontFace::new("sans-serif", 400, false));
+        registry.add_face(FontFace::new("Inter", 400, false));
+        assert_eq!(registry.load_fallback_config("# comment\nHelvetica = \"Inter\", Arial\n"), Ok(1));
+        assert_eq!(registry.load_fallback_config("broken"), Err(FontConfigError::InvalidLine(1)));
+
+        let mut styles = ComputedStyles::default();
+        let font_styles = styles.font_mut();
+        font_styles.family = Some("Helvetica".to_string());
+        font_styles.weight = Some("bold".to_string());
+        font_styles.style = Some(FontStyle::Italic);
+        font_styles.features = features;
+        let font = FontKey::from_styles(&styles);
+        let selection = registry.select(&font);
+        assert_eq!(selection.face.family, "inter");
+        assert!(selection.synthetic_bold && selection.synthetic_italic && selection.synthetic_small_caps);
+
+        // 16px: 8px advances, small capitals at 70% and 0.32px emboldening
+        let glyph_texts = |shaped: &ShapedText| shaped.glyphs.iter().map(|glyph| glyph.text.clone()).collect::<Vec<_>>();
+        let shaped = shape("Afi", &font, &selection);
+        assert_eq!(glyph_texts(&shaped), vec!["A", "F", "I"]);
+        assert!((shaped.width() - (8.0 + 2.0 * 5.6 + 3.0 * 0.32)).abs() < 0.001);
+
+        // With synthesis turned off, the face is used as it is, and "fi"
+        // becomes a ligature
+        let plain = FontKey { synthesis: FontSynthesis::parse("none").unwrap(), ..font };
+        let selection = registry.select(&plain);
+        assert!(!selection.synthetic_bold && !selection.synthetic_italic && !selection.small_caps);
+        let shaped = shape("fit", &plain, &selection);
+        assert_eq!(glyph_texts(&shaped), vec!["fi", "t"]);
+        assert_eq!(shaped.width(), 24.0);
+
+        // The test fonts stand in for every family
+        assert_eq!(FontRegistry::test_fonts().select(&plain).face.family, TEST_FONT_FAMILY);
+    }
+}
@@ -0,0 +1,204 @@
+//! # Forced Colors
+//!
+//! This module implements forced-colors mode, used by high-contrast
+//! settings: author colors are replaced with the colors of a small system
+//! palette, so text always stands out from its background. It also
+//! resolves the CSS system color keywords, such as `Canvas` and
+//! `LinkText`, against that palette.
+//!
+//! ## Design Principles
+//!
+//! 1. **Forced After the Cascade**: Colors are replaced once an element's
+//!    styles are final, so author rules, `!important` and inheritance cannot
+//!    undo the palette.
+//!
+//! 2. **Roles, Not Colors**: An element's role picks the palette entry:
+//!    links get `LinkText`, buttons `ButtonText` on `ButtonFace`, disabled
+//!    controls `GrayText`, and everything else `CanvasText` on `Canvas`.
+//!    Transparent backgrounds stay transparent so layering is kept.
+//!
+//! 3. **Opting Out Is Inherited**: `forced-color-adjust: none` leaves an
+//!    element and its descendants with the author's colors, for content
+//!    such as color pickers whose colors carry meaning.
+
+use crate::ComputedStyles;
+use css_parser::media::ColorScheme;
+use dom::{ElementState, Node};
+
+/// `forced-color-adjust`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ForcedColorAdjust {
+    /// Colors are forced in forced-colors mode
+    #[default]
+    Auto,
+    /// The author's colors are kept
+    None,
+}
+
+impl ForcedColorAdjust {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "auto" => Some(ForcedColorAdjust::Auto),
+            "none" => Some(ForcedColorAdjust::None),
+            _ => None,
+        }
+    }
+}
+
+/// The colors of the system palette, one per system color keyword
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SystemPalette {
+    pub canvas: &'static str,
+    pub canvas_text: &'static str,
+    pub link_text: &'static str,
+    pub visited_text: &'static str,
+    pub button_face: &'static str,
+    pub button_text: &'static str,
+    pub gray_text: &'static str,
+    pub highlight: &'static str,
+    pub highlight_text: &'static str,
+}
+
+impl SystemPalette {
+    /// Get the palette for a color scheme: black on white for light, and
+    /// the high-contrast black theme for dark
+    pub fn for_scheme(scheme: ColorScheme) -> Self {
+        match scheme {
+            ColorScheme::Light => SystemPalette {
+                canvas: "#ffffff",
+                canvas_text: "#000000",
+                link_text: "#0000ee",
+                visited_text: "#551a8b",
+                button_face: "#efefef",
+                button_text: "#000000",
+                gray_text: "#6d6d6d",
+                highlight: "#0078d7",
+                highlight_text: "#ffffff",
+            },
+            ColorScheme::Dark => SystemPalette {
+                canvas: "#000000",
+                canvas_text: "#ffffff",
+                link_text: "#ffff00",
+                visited_text: "#ffff00",
+                button_face: "#000000",
+                button_text: "#ffffff",
+                gray_text: "#3ff23f",
+                highlight: "#1aebff",
+                highlight_text: "#000000",
+            },
+        }
+    }
+
+    /// Resolve a system color keyword, case-insensitively
+    pub fn resolve(&self, keyword: &str) -> Option<&'static str> {
+        match keyword.to_ascii_lowercase().as_str() {
+            "canvas" => Some(self.canvas),
+            "canvastext" => Some(self.canvas_text),
+            "linktext" => Some(self.link_text),
+            "visitedtext" => Some(self.visited_text),
+            "buttonface" | "buttonborder" => Some(self.button_face),
+            "buttontext" => Some(self.button_text),
+            "graytext" => Some(self.gray_text),
+            "highlight" | "selecteditem" => Some(self.highlight),
+            "highlighttext" | "selecteditemtext" => Some(self.highlight_text),
+            "field" => Some(self.canvas),
+            "fieldtext" => Some(self.canvas_text),
+            _ => None,
+        }
+    }
+}
+
+/// Replace an element's colors with the palette's, unless it opted out
+///
+/// `is_root` gives the root element the `Canvas` background even when the
+/// page leaves it transparent, so the page as a whole is repainted.
+pub fn force_colors(styles: &mut ComputedStyles, element: &Node, palette: &SystemPalette, is_root: bool) {
+    if styles.forced_color_adjust == Some(ForcedColorAdjust::None) {
+        return;
+    }
+    let disabled = element.has_attribute("disabled") && is_control(element);
+    let (text, background) = if disabled {
+        (palette.gray_text, palette.canvas)
+    } else if is_button(element) {
+        (palette.button_text, palette.button_face)
+    } else if dom::element_state::is_link(element) {
+        let visited = element.element_state().contains(ElementState::VISITED);
+        (if visited { palette.visited_text } else { palette.link_text }, palette.canvas)
+    } else {
+        (palette.canvas_text, palette.canvas)
+    };
+    // Text nodes take the color of the element around them
+    if element.tag_name().is_some() {
+        styles.color = Some(text.to_string());
+    }
+    let transparent = styles.background_color.as_deref().is_none_or(|color| color.eq_ignore_ascii_case("transparent"));
+    if !transparent || is_root || is_button(element) {
+        styles.background_color = Some(background.to_string());
+    }
+}
+
+fn is_control(element: &Node) -> bool {
+    matches!(element.tag_name(), Some("button" | "input" | "select" | "textarea" | "option" | "optgroup" | "fieldset"))
+}
+
+fn is_button(element: &Node) -> bool {
+    match element.tag_name() {
+        Some("button" | "select") => true,
+        Some("input") => element
+            .get_attribute("type")
+            .is_some_and(|kind| matches!(kind.to_ascii_lowercase().as_str(), "button" | "submit" | "reset" | "color" | "file")),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StyleMatcher;
+    use css_parser::media::MediaFeatures;
+    use css_parser::CSSParser;
+    use dom::Document;
+
+    #[test]
+    fn test_forced_colors_replace_author_colors() {
+        let document = Document::new();
+        let html = document.create_element("html");
+        document.root.append_child(&html);
+        let paragraph = document.create_element("p");
+        let link = document.create_element("a");
+        link.set_attribute("href", "/next");
+        let button = document.create_element("button");
+        let swatch = document.create_element("div");
+        swatch.set_attribute("class", "swatch");
+        let chip = document.create_element("span");
+        for element in [&paragraph, &link, &button, &swatch] {
+            html.append_child(element);
+        }
+        swatch.append_child(&chip);
+        let css = "p { color: #aa7733; background-color: #cc9955; }\n\
+                   .swatch { color: #aa7733; forced-color-adjust: none; }\n\
+                   span { color: CanvasText; }\n\
+                   @media (forced-colors: active) { p { border: 2px; } }";
+        let mut matcher = StyleMatcher::new(CSSParser::new(css.to_string()).parse_stylesheet().unwrap());
+        assert_eq!(matcher.compute_styles(&paragraph).color.as_deref(), Some("#aa7733"));
+        assert_eq!(matcher.compute_styles(&chip).color.as_deref(), Some("#000000"));
+
+        matcher.set_media_features(MediaFeatures { forced_colors: true, color_scheme: ColorScheme::Dark, ..MediaFeatures::default() });
+        let palette = SystemPalette::for_scheme(ColorScheme::Dark);
+        let styles = matcher.compute_styles(&paragraph);
+        assert_eq!(styles.color.as_deref(), Some(palette.canvas_text));
+        assert_eq!(styles.background_color.as_deref(), Some(palette.canvas));
+        assert_eq!(styles.box_model.border.top, 2.0);
+        assert_eq!(matcher.compute_styles(&html).background_color.as_deref(), Some(palette.canvas));
+        assert_eq!(matcher.compute_styles(&link).color.as_deref(), Some(palette.link_text));
+        let styles = matcher.compute_styles(&button);
+        assert_eq!((styles.color.as_deref(), styles.background_color.as_deref()), (Some(palette.button_text), Some(palette.button_face)));
+        button.set_attribute("disabled", "");
+        assert_eq!(matcher.compute_styles(&button).color.as_deref(), Some(palette.gray_text));
+
+        // Opting out keeps the author's colors, and is inherited
+        assert_eq!(matcher.compute_styles(&swatch).color.as_deref(), Some("#aa7733"));
+        assert_eq!(matcher.compute_styles(&chip).color.as_deref(), Some(palette.canvas_text));
+        assert_eq!(matcher.compute_styles(&chip).forced_color_adjust, Some(ForcedColorAdjust::None));
+    }
+}
@@ -0,0 +1,360 @@
+//! # Layout Fuzzer
+//!
+//! Test-only generator of random DOM trees with random styles, and a
+//! checker for the invariants every layout must satisfy.
+//!
+//! ## Design Principles
+//!
+//! 1. **Deterministic**: Trees come from a seeded xorshift generator, so a
+//!    failing seed reproduces exactly. `LAYOUT_FUZZ_ITERATIONS` and
+//!    `LAYOUT_FUZZ_SEED` run more or different cases.
+//!
+//! 2. **Invariants, Not Snapshots**: Sizes are finite and non-negative,
+//!    block children are stacked in order, and children stay inside a
+//!    parent whose size depends on them unless they have a size of their
+//!    own that overflows it. Children of form controls and contents of
+//!    flex items, which their container sizes, are allowed to overflow.
+//!
+//! 3. **Shrinking**: A failing tree is reduced by dropping subtrees,
+//!    hoisting children and dropping declarations while it still fails,
+//!    and reported as markup.
+
+use crate::{DisplayType, LayoutBox, LayoutEngine, Dimensions};
+use css_parser::parse_css;
+use dom::{Document, Node};
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+
+/// Rounding slack for containment checks
+const EPSILON: f32 = 0.01;
+
+/// Stylesheet exercising the pseudo-element paths of inline layout
+const FUZZ_STYLESHEET: &str = "p::first-letter {\n  float: left;\n  font-size: 40px;\n}\nli::first-line {\n  font-size: 24px;\n}";
+
+/// Seeded xorshift64* generator
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+
+    fn pick<'a>(&mut self, options: &[&'a str]) -> &'a str {
+        options[self.below(options.len())]
+    }
+
+    fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+}
+
+/// A generated node, kept separate from the DOM so it can be shrunk
+#[derive(Debug, Clone)]
+enum FuzzNode {
+    Element {
+        tag: String,
+        style: Vec<(String, String)>,
+        children: Vec<FuzzNode>,
+    },
+    Text(String),
+}
+
+impl FuzzNode {
+    fn to_markup(&self) -> String {
+        match self {
+            FuzzNode::Text(text) => text.clone(),
+            FuzzNode::Element { tag, style, children } => {
+                let style = style
+                    .iter()
+                    .map(|(property, value)| format!("{}: {}", property, value))
+                    .collect::<Vec<_>>()
+                    .join("; ");
+                let children: String = children.iter().map(FuzzNode::to_markup).collect();
+                format!("<{} style=\"{}\">{}</{}>", tag, style, children, tag)
+            }
+        }
+    }
+
+    fn build(&self, document: &Document) -> Rc<Node> {
+        match self {
+            FuzzNode::Text(text) => document.create_text_node(text),
+            FuzzNode::Element { tag, style, children } => {
+                let element = document.create_element(tag);
+                if !style.is_empty() {
+                    let style = style
+                        .iter()
+                        .map(|(property, value)| format!("{}: {}", property, value))
+                        .collect::<Vec<_>>()
+                        .join("; ");
+                    element.set_attribute("style", &style);
+                }
+                for child in children {
+                    element.append_child(&child.build(document));
+                }
+                element
+            }
+        }
+    }
+}
+
+fn random_length(rng: &mut Rng, max: usize) -> String {
+    format!("{}px", rng.below(max + 1))
+}
+
+fn random_declaration(rng: &mut Rng) -> (String, String) {
+    let property = rng.pick(&[
+        "display", "width", "height", "margin", "padding", "border-width", "font-size",
+        "flex-direction", "flex-wrap", "justify-content", "align-items", "align-content",
+        "align-self", "order", "content-visibility", "contain-intrinsic-size", "position",
+        "top", "float",
+    ]);
+    let value = match property {
+        "display" => rng.pick(&["block", "inline", "inline-block", "flex", "contents", "none"]).to_string(),
+        "width" => random_length(rng, 400),
+        "height" => random_length(rng, 300),
+        "margin" | "padding" | "border-width" => random_length(rng, 30),
+        "font-size" => format!("{}px", 8 + rng.below(33)),
+        "flex-direction" => rng.pick(&["row", "row-reverse", "column", "column-reverse"]).to_string(),
+        "flex-wrap" => rng.pick(&["nowrap", "wrap", "wrap-reverse"]).to_string(),
+        "justify-content" => rng
+            .pick(&["flex-start", "flex-end", "center", "space-between", "space-around", "space-evenly"])
+            .to_string(),
+        "align-items" => rng.pick(&["stretch", "flex-start", "flex-end", "center", "baseline"]).to_string(),
+        "align-content" => rng
+            .pick(&["stretch", "flex-start", "flex-end", "center", "space-between", "space-around"])
+            .to_string(),
+        "align-self" => rng.pick(&["auto", "stretch", "flex-start", "flex-end", "center"]).to_string(),
+        "order" => (rng.below(7) as i32 - 3).to_string(),
+        "content-visibility" => rng.pick(&["visible", "auto", "hidden"]).to_string(),
+        "contain-intrinsic-size" => random_length(rng, 500),
+        "position" => rng.pick(&["static", "relative", "sticky"]).to_string(),
+        "top" => random_length(rng, 50),
+        _ => rng.pick(&["left", "right", "none"]).to_string(),
+    };
+    (property.to_string(), value)
+}
+
+fn random_text(rng: &mut Rng) -> String {
+    let words = ["lorem", "ipsum", "dolor", "sit", "amet", "“quoted”", "a", "supercalifragilistic"];
+    let count = 1 + rng.below(12);
+    (0..count).map(|_| rng.pick(&words)).collect::<Vec<_>>().join(" ")
+}
+
+fn random_tree(rng: &mut Rng, depth: usize) -> FuzzNode {
+    if depth > 0 && rng.chance(25) {
+        return FuzzNode::Text(random_text(rng));
+    }
+    let tag = rng.pick(&["div", "p", "span", "section", "ul", "li", "input", "button"]).to_string();
+    let style = (0..rng.below(5)).map(|_| random_declaration(rng)).collect();
+    let children = if depth >= 4 {
+        Vec::new()
+    } else {
+        (0..rng.below(5)).map(|_| random_tree(rng, depth + 1)).collect()
+    };
+    FuzzNode::Element { tag, style, children }
+}
+
+/// Lay out a generated tree and check the invariants
+///
+/// Returns a description of the first violation, including panics.
+fn check_tree(tree: &FuzzNode, viewport_layout: bool) -> Result<(), String> {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let document = Document::new();
+        document.root.append_child(&tree.build(&document));
+        let mut engine = LayoutEngine::new(parse_css(FUZZ_STYLESHEET));
+        let layout = if viewport_layout {
+            engine.layout_document_for_viewport(&document, Dimensions::new(0.0, 0.0, 800.0, 600.0))
+        } else {
+            engine.layout_document(&document)
+        };
+        check_invariants(&layout, "root", false)
+    }));
+    match result {
+        Ok(outcome) => outcome,
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<String>()
+                .cloned()
+                .or_else(|| payload.downcast_ref::<&str>().map(|message| message.to_string()))
+                .unwrap_or_default();
+            Err(format!("layout panicked: {}", message))
+        }
+    }
+}
+
+fn describe(layout_box: &LayoutBox) -> String {
+    layout_box.node.tag_name().map_or_else(|| "#text".to_string(), str::to_string)
+}
+
+/// Check the invariants of a box and its descendants
+///
+/// `flex_item` boxes are sized by their flex container, so their contents
+/// may overflow them even without a height of their own.
+fn check_invariants(layout_box: &LayoutBox, path: &str, flex_item: bool) -> Result<(), String> {
+    for (name, dimensions) in [
+        ("content", &layout_box.content),
+        ("padding", &layout_box.padding),
+        ("border", &layout_box.border),
+        ("margin", &layout_box.margin),
+    ] {
+        let values = [dimensions.x, dimensions.y, dimensions.width, dimensions.height];
+        if values.iter().any(|value| !value.is_finite()) {
+            return Err(format!("{}: non-finite {} box {:?}", path, name, dimensions));
+        }
+        if dimensions.width < 0.0 || dimensions.height < 0.0 {
+            return Err(format!("{}: negative {} size {:?}", path, name, dimensions));
+        }
+    }
+
+    // Form controls are replaced elements; their children are not in flow
+    let replaced = layout_box.node.form_control_type().is_some();
+    let display = &layout_box.styles.display;
+    let block_flow = !replaced && matches!(display, DisplayType::Block | DisplayType::Inline | DisplayType::InlineBlock);
+    let mut previous_y = f32::NEG_INFINITY;
+    for (index, child) in layout_box.children.iter().enumerate() {
+        let child_path = format!("{} > {}[{}]", path, describe(child), index);
+
+        if block_flow {
+            if child.content.y < previous_y {
+                return Err(format!(
+                    "{}: block offset {} is above the previous sibling at {}",
+                    child_path, child.content.y, previous_y
+                ));
+            }
+            previous_y = child.content.y;
+
+            // Boxes without a width of their own fit their container,
+            // unless their margins, borders and padding alone overflow it
+            let sized = child.styles.box_model.width.is_some() || child.node.form_control_type().is_some();
+            let overflows = child.content.width > 0.0 && child.margin.width > layout_box.content.width + EPSILON;
+            if child.content.x < 0.0 || (!sized && overflows) {
+                return Err(format!(
+                    "{}: {}px wide at x {} overflows a {}px container",
+                    child_path, child.margin.width, child.content.x, layout_box.content.width
+                ));
+            }
+        }
+
+        // A parent sized by its content contains its children
+        let auto_height = layout_box.styles.box_model.height.is_none() && !flex_item;
+        let laid_out_children = block_flow || (!replaced && *display == DisplayType::Flex);
+        if auto_height && laid_out_children && child.content.y + child.content.height > layout_box.content.height + EPSILON {
+            return Err(format!(
+                "{}: bottom {} is below its auto-height container's {}",
+                child_path,
+                child.content.y + child.content.height,
+                layout_box.content.height
+            ));
+        }
+
+        check_invariants(child, &child_path, *display == DisplayType::Flex)?;
+    }
+    Ok(())
+}
+
+/// Reduce a failing tree while it keeps failing
+fn shrink(tree: FuzzNode, fails: &dyn Fn(&FuzzNode) -> bool) -> FuzzNode {
+    let mut current = tree;
+    loop {
+        let Some(smaller) = shrink_candidates(&current).into_iter().find(|candidate| fails(candidate)) else {
+            return current;
+        };
+        current = smaller;
+    }
+}
+
+/// All trees one simplification away from `tree`
+fn shrink_candidates(tree: &FuzzNode) -> Vec<FuzzNode> {
+    let FuzzNode::Element { tag, style, children } = tree else {
+        return match tree {
+            FuzzNode::Text(text) if text.split_whitespace().count() > 1 => {
+                let words: Vec<&str> = text.split_whitespace().collect();
+                vec![FuzzNode::Text(words[..words.len() / 2].join(" ")), FuzzNode::Text(words[1..].join(" "))]
+            }
+            _ => Vec::new(),
+        };
+    };
+
+    let mut candidates = Vec::new();
+    // Replace the element by one of its element children
+    for child in children {
+        if matches!(child, FuzzNode::Element { .. }) {
+            candidates.push(child.clone());
+        }
+    }
+    // Drop a child
+    for index in 0..children.len() {
+        let mut fewer = children.clone();
+        fewer.remove(index);
+        candidates.push(FuzzNode::Element { tag: tag.clone(), style: style.clone(), children: fewer });
+    }
+    // Drop a declaration
+    for index in 0..style.len() {
+        let mut fewer = style.clone();
+        fewer.remove(index);
+        candidates.push(FuzzNode::Element { tag: tag.clone(), style: fewer, children: children.clone() });
+    }
+    // Simplify a child
+    for (index, child) in children.iter().enumerate() {
+        for simpler in shrink_candidates(child) {
+            let mut changed = children.clone();
+            changed[index] = simpler;
+            candidates.push(FuzzNode::Element { tag: tag.clone(), style: style.clone(), children: changed });
+        }
+    }
+    candidates
+}
+
+fn env_number(name: &str, default: u64) -> u64 {
+    std::env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default)
+}
+
+#[test]
+fn fuzz_layout_invariants() {
+    let iterations = env_number("LAYOUT_FUZZ_ITERATIONS", 300);
+    let first_seed = env_number("LAYOUT_FUZZ_SEED", 1);
+
+    for seed in first_seed..first_seed + iterations {
+        let mut rng = Rng::new(seed);
+        let tree = random_tree(&mut rng, 0);
+        let viewport_layout = rng.chance(30);
+        if let Err(violation) = check_tree(&tree, viewport_layout) {
+            let minimal = shrink(tree, &|candidate| check_tree(candidate, viewport_layout).is_err());
+            let violation = check_tree(&minimal, viewport_layout).err().unwrap_or(violation);
+            panic!(
+                "layout invariant violated for seed {}: {}\nminimal reproducer: {}",
+                seed,
+                violation,
+                minimal.to_markup()
+            );
+        }
+    }
+}
+
+#[test]
+fn shrinking_finds_minimal_reproducer() {
+    let leaf = FuzzNode::Element {
+        tag: "span".to_string(),
+        style: vec![("width".to_string(), "10px".to_string())],
+        children: Vec::new(),
+    };
+    let tree = FuzzNode::Element {
+        tag: "div".to_string(),
+        style: vec![("margin".to_string(), "4px".to_string())],
+        children: vec![FuzzNode::Text("some words here".to_string()), leaf],
+    };
+    // Pretend any tree containing a span fails
+    let minimal = shrink(tree, &|candidate| candidate.to_markup().contains("<span"));
+    assert_eq!(minimal.to_markup(), "<span style=\"\"></span>");
+}
@@ -0,0 +1,511 @@
+//! # Generated Content
+//!
+//! This module resolves the `content` property of `::before` and `::after`
+//! boxes, and `attr()` in the values of other properties. Content is a list
+//! of quoted strings, `attr()` references and `counter()`/`counters()`
+//! values, which use the counters `counter-reset`, `counter-set` and
+//! `counter-increment` maintain.
+//!
+//! ## Design Principles
+//!
+//! 1. **Computed-Value Time**: `attr()` is substituted while styles are
+//!    computed, against the attributes of the originating element, so a
+//!    pseudo-element reads its element's attributes and a changed attribute
+//!    shows after a restyle.
+//!
+//! 2. **Counters Follow the Tree**: Counters are applied in tree order. A
+//!    counter instance is scoped to the element that creates it, its
+//!    descendants and its following siblings; `counters()` joins every
+//!    instance in scope, outermost first.
+//!
+//! 3. **Fallbacks Over Failure**: A missing or unparsable attribute takes
+//!    the `attr()` fallback. A value without one is invalid at computed-value
+//!    time and its declaration is dropped, never applied half-substituted.
+
+use css_parser::CSSDeclaration;
+use dom::Node;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// One component of a `content` value
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContentItem {
+    /// A quoted string, or an attribute value resolved at computed-value time
+    Text(String),
+    /// `attr(name)` or `attr(name, "fallback")`
+    Attr { name: String, fallback: Option<String> },
+    /// `counter(name)` or `counter(name, style)`
+    Counter { name: String, style: CounterStyle },
+    /// `counters(name, "separator")` or `counters(name, "separator", style)`
+    Counters { name: String, separator: String, style: CounterStyle },
+}
+
+/// How a counter value is written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CounterStyle {
+    #[default]
+    Decimal,
+    DecimalLeadingZero,
+    LowerAlpha,
+    UpperAlpha,
+    LowerRoman,
+    UpperRoman,
+    Disc,
+    Circle,
+    Square,
+    None,
+}
+
+impl CounterStyle {
+    /// Parse a counter style name; unknown styles are `None`
+    pub fn parse(name: &str) -> Option<Self> {
+        Some(match name.trim().to_ascii_lowercase().as_str() {
+            "decimal" => CounterStyle::Decimal,
+            "decimal-leading-zero" => CounterStyle::DecimalLeadingZero,
+            "lower-alpha" | "lower-latin" => CounterStyle::LowerAlpha,
+            "upper-alpha" | "upper-latin" => CounterStyle::UpperAlpha,
+            "lower-roman" => CounterStyle::LowerRoman,
+            "upper-roman" => CounterStyle::UpperRoman,
+            "disc" => CounterStyle::Disc,
+            "circle" => CounterStyle::Circle,
+            "square" => CounterStyle::Square,
+            "none" => CounterStyle::None,
+            _ => return None,
+        })
+    }
+
+    /// Write a counter value in this style
+    ///
+    /// Values outside the range of an alphabetic or roman style fall back
+    /// to decimal.
+    pub fn format(&self, value: i32) -> String {
+        match self {
+            CounterStyle::Decimal => value.to_string(),
+            CounterStyle::DecimalLeadingZero if (0..10).contains(&value) => format!("0{}", value),
+            CounterStyle::DecimalLeadingZero => value.to_string(),
+            CounterStyle::LowerAlpha => alphabetic(value).unwrap_or_else(|| value.to_string()),
+            CounterStyle::UpperAlpha => alphabetic(value).map_or_else(|| value.to_string(), |text| text.to_uppercase()),
+            CounterStyle::LowerRoman => roman(value).map_or_else(|| value.to_string(), |text| text.to_lowercase()),
+            CounterStyle::UpperRoman => roman(value).unwrap_or_else(|| value.to_string()),
+            CounterStyle::Disc => "•".to_string(),
+            CounterStyle::Circle => "◦".to_string(),
+            CounterStyle::Square => "▪".to_string(),
+            CounterStyle::None => String::new(),
+        }
+    }
+}
+
+/// Write a positive value as `a`, `b`, ... `z`, `aa`, ...
+fn alphabetic(value: i32) -> Option<String> {
+    if value < 1 {
+        return None;
+    }
+    let mut value = value as u32;
+    let mut letters = Vec::new();
+    while value > 0 {
+        value -= 1;
+        letters.push(char::from(b'a' + (value % 26) as u8));
+        value /= 26;
+    }
+    Some(letters.iter().rev().collect())
+}
+
+/// Write a value from 1 to 3999 in upper case roman numerals
+fn roman(value: i32) -> Option<String> {
+    if !(1..4000).contains(&value) {
+        return None;
+    }
+    const NUMERALS: [(i32, &str); 13] = [
+        (1000, "M"), (900, "CM"), (500, "D"), (400, "CD"), (100, "C"), (90, "XC"),
+        (50, "L"), (40, "XL"), (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I"),
+    ];
+    let mut value = value;
+    let mut text = String::new();
+    for (amount, numeral) in NUMERALS {
+        while value >= amount {
+            text.push_str(numeral);
+            value -= amount;
+        }
+    }
+    Some(text)
+}
+
+/// Parse a `content` value
+///
+/// `normal` and `none` are an empty list, which generates no box. Returns
+/// `None` if the value is invalid, so the declaration is ignored.
+pub fn parse_content(text: &str) -> Option<Vec<ContentItem>> {
+    let text = text.trim();
+    if text.eq_ignore_ascii_case("normal") || text.eq_ignore_ascii_case("none") {
+        return Some(Vec::new());
+    }
+    let mut items = Vec::new();
+    for component in split_top_level(text, char::is_whitespace) {
+        if let Some(string) = unquote(&component) {
+            items.push(ContentItem::Text(string));
+            continue;
+        }
+        let (name, arguments) = split_function(&component)?;
+        let arguments = split_top_level(arguments, |ch| ch == ',');
+        let item = match (name.to_ascii_lowercase().as_str(), arguments.as_slice()) {
+            ("attr", [reference]) => ContentItem::Attr { name: attr_name(reference)?, fallback: None },
+            ("attr", [reference, fallback]) => ContentItem::Attr { name: attr_name(reference)?, fallback: Some(unquote(fallback)?) },
+            ("counter", [name]) => ContentItem::Counter { name: counter_name(name)?, style: CounterStyle::Decimal },
+            ("counter", [name, style]) => ContentItem::Counter { name: counter_name(name)?, style: CounterStyle::parse(style)? },
+            ("counters", [name, separator]) => ContentItem::Counters {
+                name: counter_name(name)?,
+                separator: unquote(separator)?,
+                style: CounterStyle::Decimal,
+            },
+            ("counters", [name, separator, style]) => ContentItem::Counters {
+                name: counter_name(name)?,
+                separator: unquote(separator)?,
+                style: CounterStyle::parse(style)?,
+            },
+            _ => return None,
+        };
+        items.push(item);
+    }
+    (!items.is_empty()).then_some(items)
+}
+
+/// Replace the `attr()` items of a content value with the attributes of
+/// the originating element
+pub fn resolve_attrs(items: Vec<ContentItem>, element: &Node) -> Vec<ContentItem> {
+    items
+        .into_iter()
+        .map(|item| match item {
+            ContentItem::Attr { name, fallback } => {
+                ContentItem::Text(element.get_attribute(&name).or(fallback).unwrap_or_default())
+            }
+            item => item,
+        })
+        .collect()
+}
+
+/// Write out a content value with the counters in scope
+pub fn resolve_content(items: &[ContentItem], counters: &CounterState) -> String {
+    items
+        .iter()
+        .map(|item| match item {
+            ContentItem::Text(text) => text.clone(),
+            // Unresolved references read nothing, like a missing attribute
+            ContentItem::Attr { fallback, .. } => fallback.clone().unwrap_or_default(),
+            ContentItem::Counter { name, style } => style.format(counters.value(name)),
+            ContentItem::Counters { name, separator, style } => {
+                let values = match counters.values(name) {
+                    values if values.is_empty() => vec![0],
+                    values => values,
+                };
+                values.iter().map(|value| style.format(*value)).collect::<Vec<_>>().join(separator)
+            }
+        })
+        .collect()
+}
+
+/// Parse a `counter-reset`, `counter-set` or `counter-increment` value
+///
+/// Each counter name may be followed by an integer; `default` is used for
+/// names without one. `none` is an empty list, and invalid values are
+/// `None`.
+pub fn parse_counter_changes(text: &str, default: i32) -> Option<Vec<(String, i32)>> {
+    let text = text.trim();
+    if text.eq_ignore_ascii_case("none") {
+        return Some(Vec::new());
+    }
+    let mut changes: Vec<(String, i32)> = Vec::new();
+    for word in text.split_whitespace() {
+        match (word.parse::<i32>(), changes.last_mut()) {
+            (Ok(value), Some(last)) => last.1 = value,
+            (Ok(_), None) => return None,
+            (Err(_), _) => changes.push((counter_name(word)?, default)),
+        }
+    }
+    (!changes.is_empty()).then_some(changes)
+}
+
+/// Substitute the `attr()` values of a declaration with the attributes of
+/// `element`
+///
+/// Typed references such as `attr(data-size px, 10px)` append their unit
+/// to a numeric attribute; `attr(name string)` quotes it. Declarations
+/// without `attr()`, and `content`, which resolves its own, are returned
+/// as they are. Returns `None` if a reference has neither a usable
+/// attribute nor a fallback.
+pub fn substitute_attr<'a>(declaration: &'a CSSDeclaration, element: &Node) -> Option<Cow<'a, CSSDeclaration>> {
+    let text = declaration.value.to_css_text();
+    if declaration.property == "content" || !text.contains("attr(") {
+        return Some(Cow::Borrowed(declaration));
+    }
+    let mut substituted = String::new();
+    let mut rest = text.as_str();
+    while let Some(start) = rest.find("attr(") {
+        substituted.push_str(&rest[..start]);
+        let call = &rest[start..];
+        let end = matching_paren(call)?;
+        let (_, arguments) = split_function(&call[..=end])?;
+        substituted.push_str(&attr_value(arguments, element)?);
+        rest = &call[end + 1..];
+    }
+    substituted.push_str(rest);
+    let mut value = css_parser::parse_inline_style(&format!("{}: {}", declaration.property, substituted)).pop()?;
+    value.important = declaration.important;
+    Some(Cow::Owned(value))
+}
+
+/// Resolve the arguments of a typed `attr()` outside `content`
+fn attr_value(arguments: &str, element: &Node) -> Option<String> {
+    let arguments = split_top_level(arguments, |ch| ch == ',');
+    let (reference, fallback) = match arguments.as_slice() {
+        [reference] => (reference.as_str(), None),
+        [reference, fallback] => (reference.as_str(), Some(fallback.clone())),
+        _ => return None,
+    };
+    let mut words = reference.split_whitespace();
+    let name = attr_name(words.next()?)?;
+    let unit = words.next().map(str::to_ascii_lowercase);
+    let value = element.get_attribute(&name).and_then(|value| {
+        let value = value.trim();
+        match unit.as_deref() {
+            None | Some("raw-string") => Some(value.to_string()),
+            Some("string") => Some(format!("\"{}\"", value)),
+            Some("number") => value.parse::<f32>().ok().map(|_| value.to_string()),
+            Some(unit) if unit == "%" || unit.chars().all(|ch| ch.is_ascii_alphabetic()) => {
+                value.parse::<f32>().ok().map(|number| format!("{}{}", number, unit))
+            }
+            Some(_) => None,
+        }
+    });
+    value.or(fallback)
+}
+
+/// Get the names of the attributes a content value or declaration reads
+/// through `attr()`
+pub fn referenced_attributes(text: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("attr(") {
+        let call = &rest[start + "attr(".len()..];
+        if let Some(name) = call.trim_start().split(|ch: char| ch == ',' || ch == ')' || ch.is_whitespace()).next().and_then(attr_name) {
+            names.push(name);
+        }
+        rest = call;
+    }
+    names
+}
+
+/// The counters in scope at a point of a tree walk
+///
+/// Each counter name maps to its nested instances, innermost last, with
+/// the depth of the element that created each one.
+#[derive(Debug, Clone, Default)]
+pub struct CounterState {
+    counters: HashMap<String, Vec<(usize, i32)>>,
+}
+
+impl CounterState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply the counter properties of an element at `depth`
+    ///
+    /// Resets come first, then increments, then sets. A reset on an
+    /// element whose previous sibling created the counter replaces that
+    /// instance; otherwise it nests a new one.
+    pub fn apply(&mut self, reset: &[(String, i32)], increment: &[(String, i32)], set: &[(String, i32)], depth: usize) {
+        for (name, value) in reset {
+            let instances = self.counters.entry(name.clone()).or_default();
+            match instances.last_mut() {
+                Some(last) if last.0 == depth => last.1 = *value,
+                _ => instances.push((depth, *value)),
+            }
+        }
+        for (name, amount) in increment {
+            let instance = self.instance(name, depth);
+            *instance = instance.wrapping_add(*amount);
+        }
+        for (name, value) in set {
+            *self.instance(name, depth) = *value;
+        }
+    }
+
+    /// Get the innermost instance of a counter, creating one on the element
+    /// at `depth` if none is in scope
+    fn instance(&mut self, name: &str, depth: usize) -> &mut i32 {
+        let instances = self.counters.entry(name.to_string()).or_default();
+        if instances.is_empty() {
+            instances.push((depth, 0));
+        }
+        &mut instances.last_mut().expect("an instance was just created").1
+    }
+
+    /// Leave the children of an element at `depth`, dropping the counters
+    /// they created
+    pub fn leave(&mut self, depth: usize) {
+        for instances in self.counters.values_mut() {
+            instances.retain(|(created, _)| *created <= depth);
+        }
+    }
+
+    /// Get the value of the innermost instance of a counter, 0 if none is in
+    /// scope
+    pub fn value(&self, name: &str) -> i32 {
+        self.counters.get(name).and_then(|instances| instances.last()).map_or(0, |(_, value)| *value)
+    }
+
+    /// Get the values of every instance of a counter, outermost first
+    pub fn values(&self, name: &str) -> Vec<i32> {
+        self.counters.get(name).map(|instances| instances.iter().map(|(_, value)| *value).collect()).unwrap_or_default()
+    }
+}
+
+/// Split text at separators outside quotes and parentheses, dropping empty
+/// parts
+fn split_top_level(text: &str, is_separator: impl Fn(char) -> bool) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    for ch in text.chars() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if ch == '\\' => escaped = true,
+            Some(open) if ch == open => quote = None,
+            Some(_) => {}
+            None if ch == '"' || ch == '\'' => quote = Some(ch),
+            None if ch == '(' => depth += 1,
+            None if ch == ')' => depth -= 1,
+            None if depth == 0 && is_separator(ch) => {
+                if !current.trim().is_empty() {
+                    parts.push(current.trim().to_string());
+                }
+                current.clear();
+                continue;
+            }
+            None => {}
+        }
+        current.push(ch);
+    }
+    if !current.trim().is_empty() {
+        parts.push(current.trim().to_string());
+    }
+    parts
+}
+
+/// Get the index of the parenthesis closing the first one opened in `text`
+fn matching_paren(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+    for (index, ch) in text.char_indices() {
+        match quote {
+            Some(open) if ch == open => quote = None,
+            Some(_) => {}
+            None if ch == '"' || ch == '\'' => quote = Some(ch),
+            None if ch == '(' => depth += 1,
+            None if ch == ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(index);
+                }
+            }
+            None => {}
+        }
+    }
+    None
+}
+
+/// Split `name(arguments)` into its name and arguments
+fn split_function(text: &str) -> Option<(&str, &str)> {
+    let (name, rest) = text.split_once('(')?;
+    let arguments = rest.strip_suffix(')')?;
+    (!name.is_empty() && name.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '-')).then_some((name, arguments))
+}
+
+/// Unquote a CSS string, resolving backslash escapes
+fn unquote(text: &str) -> Option<String> {
+    let text = text.trim();
+    let quote = text.chars().next().filter(|ch| *ch == '"' || *ch == '\'')?;
+    let inner = text.strip_prefix(quote)?.strip_suffix(quote)?;
+    let mut string = String::new();
+    let mut chars = inner.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            // `\A` is a line feed; other escapes stand for the character
+            '\\' => match chars.next() {
+                Some('a') | Some('A') => string.push('\n'),
+                Some(escaped) => string.push(escaped),
+                None => {}
+            },
+            ch => string.push(ch),
+        }
+    }
+    Some(string)
+}
+
+/// Validate the attribute name of an `attr()` reference
+fn attr_name(text: &str) -> Option<String> {
+    let name = text.trim();
+    let valid = !name.is_empty() && name.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_' || ch == ':');
+    valid.then(|| name.to_ascii_lowercase())
+}
+
+/// Validate a counter name; CSS-wide keywords and `none` are not names
+fn counter_name(text: &str) -> Option<String> {
+    let name = text.trim();
+    let reserved = ["none", "initial", "inherit", "unset", "default"].iter().any(|keyword| name.eq_ignore_ascii_case(keyword));
+    let valid = !name.is_empty()
+        && !name.starts_with(|ch: char| ch.is_ascii_digit())
+        && name.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_');
+    (valid && !reserved).then(|| name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use css_parser::CSSValue;
+    use dom::Document;
+
+    #[test]
+    fn test_content_and_counters() {
+        let document = Document::new();
+        let element = document.create_element("li");
+        element.set_attribute("data-label", "Note");
+        element.set_attribute("data-size", "12");
+
+        let items = parse_content(r#""[" attr(data-label) "] " attr(data-missing, "none") counter(item, upper-roman) counters(item, ".")"#).unwrap();
+        assert_eq!(items.len(), 6);
+        assert!(parse_content("counter()").is_none());
+        assert!(parse_content("counters(item)").is_none());
+        assert_eq!(parse_content("none"), Some(Vec::new()));
+
+        // Nested lists: the inner reset nests, the sibling reset replaces
+        let mut counters = CounterState::new();
+        counters.apply(&[("item".to_string(), 0)], &[], &[], 0);
+        counters.apply(&[], &[("item".to_string(), 1)], &[], 1);
+        counters.apply(&[("item".to_string(), 0)], &[], &[], 2);
+        counters.apply(&[], &[("item".to_string(), 1)], &[], 3);
+        counters.apply(&[], &[("item".to_string(), 1)], &[], 3);
+        let items = resolve_attrs(items, &element);
+        assert_eq!(resolve_content(&items, &counters), "[Note] noneII1.2");
+        counters.leave(1);
+        assert_eq!(counters.values("item"), vec![1]);
+        counters.apply(&[], &[], &[("item".to_string(), 7)], 1);
+        assert_eq!(CounterStyle::LowerAlpha.format(counters.value("item")), "g");
+        assert_eq!(CounterStyle::LowerAlpha.format(28), "ab");
+        assert_eq!(CounterStyle::DecimalLeadingZero.format(3), "03");
+
+        assert_eq!(parse_counter_changes("chapter section 2", 1), Some(vec![("chapter".to_string(), 1), ("section".to_string(), 2)]));
+        assert!(parse_counter_changes("3", 1).is_none());
+
+        // attr() in other properties, with units and fallbacks
+        let width = CSSDeclaration { property: "width".to_string(), value: CSSValue::Keyword("attr(data-size px, 5px)".to_string()), important: false };
+        assert_eq!(substitute_attr(&width, &element).unwrap().value, CSSValue::Dimension(12.0, "px".to_string()));
+        element.set_attribute("data-size", "wide");
+        assert_eq!(substitute_attr(&width, &element).unwrap().value, CSSValue::Dimension(5.0, "px".to_string()));
+        let height = CSSDeclaration { property: "height".to_string(), value: CSSValue::Keyword("attr(data-missing px)".to_string()), important: false };
+        assert!(substitute_attr(&height, &element).is_none());
+        assert_eq!(referenced_attributes("attr(data-size px, 5px) attr( title )"), vec!["data-size", "title"]);
+    }
+}
@@ -0,0 +1,207 @@
+//! # Geometry Observers
+//!
+//! This module lets Rust code follow the border box of a node across
+//! layouts, for engine code that would otherwise need a `ResizeObserver`,
+//! such as overlays drawn over an element, and for the JS `ResizeObserver`
+//! binding itself.
+//!
+//! ## Design Principles
+//!
+//! 1. **After Layout**: Callbacks run when a layout finishes, once per
+//!    observed node whose border box changed. The first layout after a node
+//!    is observed reports its initial box, as `ResizeObserver` does.
+//! 2. **Shared Across Engines**: `GeometryObservers` is a shared handle.
+//!    The shell creates a `LayoutEngine` per layout and hands each the same
+//!    observers, so subscriptions outlive any one engine.
+//! 3. **Re-entrant**: Callbacks run after the registry is released, so a
+//!    callback may observe or unobserve nodes.
+//! 4. **Nothing to Do Without Observers**: Boxes are only collected while
+//!    some node is observed.
+
+use crate::{Dimensions, LayoutBox};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+/// A change of a node's border box between two layouts
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeometryChange {
+    pub node_id: u64,
+    /// The box before, `None` for the first report
+    pub previous: Option<Dimensions>,
+    /// The box now, in document coordinates; `None` if the node no longer
+    /// generates a box
+    pub border_box: Option<Dimensions>,
+}
+
+/// Identifies a subscription made with `GeometryObservers::observe`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GeometryObserverId(u64);
+
+/// A callback notified of geometry changes
+pub type GeometryCallback = Rc<dyn Fn(&GeometryChange)>;
+
+struct Subscription {
+    id: GeometryObserverId,
+    node_id: u64,
+    callback: GeometryCallback,
+    /// The box last reported, `None` before the first report
+    reported: Option<Option<Dimensions>>,
+}
+
+#[derive(Default)]
+struct ObserverState {
+    subscriptions: Vec<Subscription>,
+    next_id: u64,
+}
+
+/// Subscriptions to the geometry of nodes
+///
+/// Cloning shares the subscriptions.
+#[derive(Clone, Default)]
+pub struct GeometryObservers {
+    state: Rc<RefCell<ObserverState>>,
+}
+
+impl GeometryObservers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Call `callback` after every layout that changes the border box of
+    /// the node `node_id`
+    pub fn observe(&self, node_id: u64, callback: impl Fn(&GeometryChange) + 'static) -> GeometryObserverId {
+        let mut state = self.state.borrow_mut();
+        state.next_id += 1;
+        let id = GeometryObserverId(state.next_id);
+        state.subscriptions.push(Subscription { id, node_id, callback: Rc::new(callback), reported: None });
+        id
+    }
+
+    /// End a subscription
+    ///
+    /// Returns `false` if it had already ended.
+    pub fn unobserve(&self, id: GeometryObserverId) -> bool {
+        let mut state = self.state.borrow_mut();
+        let before = state.subscriptions.len();
+        state.subscriptions.retain(|subscription| subscription.id != id);
+        state.subscriptions.len() != before
+    }
+
+    /// Check whether no node is observed
+    pub fn is_empty(&self) -> bool {
+        self.state.borrow().subscriptions.is_empty()
+    }
+
+    /// Report the boxes of a finished layout to the subscriptions whose
+    /// node's box changed
+    ///
+    /// Returns the number of callbacks run.
+    pub fn notify(&self, root: &LayoutBox) -> usize {
+        if self.is_empty() {
+            return 0;
+        }
+        let boxes = border_boxes(root);
+        let mut notifications = Vec::new();
+        for subscription in self.state.borrow_mut().subscriptions.iter_mut() {
+            let border_box = boxes.get(&subscription.node_id).copied();
+            let previous = match subscription.reported {
+                Some(previous) if previous == border_box => continue,
+                Some(previous) => previous,
+                None => None,
+            };
+            subscription.reported = Some(border_box);
+            let change = GeometryChange { node_id: subscription.node_id, previous, border_box };
+            notifications.push((Rc::clone(&subscription.callback), change));
+        }
+        for (callback, change) in &notifications {
+            callback(change);
+        }
+        notifications.len()
+    }
+}
+
+impl fmt::Debug for GeometryObservers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GeometryObservers")
+            .field("subscriptions", &self.state.borrow().subscriptions.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Collect the border box of every node in a laid out tree, in document
+/// coordinates
+///
+/// A node with several boxes, such as an element with a `::backdrop`,
+/// reports its first. Flow layout positions content boxes relative to the
+/// parent box, so offsets are accumulated from `content` as in
+/// `sticky::collect_sticky_constraints`.
+pub fn border_boxes(root: &LayoutBox) -> HashMap<u64, Dimensions> {
+    let mut boxes = HashMap::new();
+    collect_border_boxes(root, 0.0, 0.0, &mut boxes);
+    boxes
+}
+
+fn collect_border_boxes(layout_box: &LayoutBox, parent_x: f32, parent_y: f32, boxes: &mut HashMap<u64, Dimensions>) {
+    let x = parent_x + layout_box.content.x;
+    let y = parent_y + layout_box.content.y;
+    // Boxes laid out from pre-computed styles have no border box
+    let width = layout_box.border.width.max(layout_box.content.width);
+    let height = layout_box.border.height.max(layout_box.content.height);
+    boxes.entry(layout_box.node.id).or_insert_with(|| Dimensions::new(x, y, width, height));
+    for child in &layout_box.children {
+        collect_border_boxes(child, x, y, boxes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LayoutEngine;
+    use css_parser::parse_css;
+    use dom::Document;
+
+    #[test]
+    fn test_observers_follow_border_boxes_across_engines() {
+        let document = Document::new();
+        let header = document.create_element("div");
+        header.set_attribute("style", "height: 40px");
+        let panel = document.create_element("div");
+        panel.set_attribute("style", "height: 100px");
+        document.root.append_child(&header);
+        document.root.append_child(&panel);
+
+        let observers = GeometryObservers::new();
+        let changes: Rc<RefCell<Vec<GeometryChange>>> = Rc::default();
+        let log = Rc::clone(&changes);
+        let mut engine = LayoutEngine::new(parse_css(""));
+        engine.set_geometry_observers(observers.clone());
+        let id = engine.observe_geometry(panel.id, move |change| log.borrow_mut().push(change.clone()));
+
+        engine.layout_document(&document);
+        let first = changes.borrow()[0].clone();
+        assert_eq!(first.previous, None);
+        let initial = first.border_box.unwrap();
+        assert_eq!((initial.width, initial.height), (800.0, 100.0));
+
+        // An unchanged box is not reported again, by this or another engine
+        engine.layout_document(&document);
+        let mut next = LayoutEngine::new(parse_css(""));
+        next.set_geometry_observers(observers.clone());
+        next.layout_document(&document);
+        assert_eq!(changes.borrow().len(), 1);
+
+        header.set_attribute("style", "height: 60px");
+        assert_eq!(next.layout_document(&document).children.len(), 2);
+        let moved = changes.borrow()[1].clone();
+        assert_eq!(moved.previous, Some(initial));
+        assert!(moved.border_box.unwrap().y > initial.y);
+
+        document.root.remove_child(&panel);
+        next.layout_document(&document);
+        assert_eq!(changes.borrow()[2].border_box, None);
+        assert!(next.unobserve_geometry(id));
+        assert!(observers.is_empty());
+    }
+}
@@ -0,0 +1,180 @@
+//! # Hyphenation
+//!
+//! This module finds the places where a word may be broken with a hyphen:
+//! soft hyphens (`&shy;`) written by the author, and, under
+//! `hyphens: auto`, the hyphenation points of a language dictionary chosen
+//! by the `lang` attribute.
+//!
+//! ## Design Principles
+//!
+//! 1. **Standard Dictionaries**: Dictionaries are the TeX hyphenation
+//!    patterns the `hyphenation` crate embeds, one per language it knows.
+//!    A dictionary is loaded the first time its language is asked for and
+//!    kept for the rest of the run.
+//!
+//! 2. **Authors First**: A word with soft hyphens only breaks at them, so
+//!    an author's choices are never mixed with the dictionary's.
+//!
+//! 3. **Invisible Until Used**: Soft hyphens take no space. The hyphen
+//!    glyph is only added to the part of a word that ends a line.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+use ::hyphenation::{Hyphenator, Language, Load, Standard};
+
+/// Soft hyphen, a break opportunity that is invisible unless used
+pub const SOFT_HYPHEN: char = '\u{ad}';
+
+/// The glyph shown at the end of a hyphenated line
+pub const HYPHEN: char = '\u{2010}';
+
+/// A hyphenation dictionary
+pub type Dictionary = Standard;
+
+/// `hyphens`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Hyphens {
+    /// Words never break inside, soft hyphens included
+    None,
+    /// Words break only at soft hyphens
+    #[default]
+    Manual,
+    /// Words also break where the language dictionary allows
+    Auto,
+}
+
+impl Hyphens {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "none" => Some(Hyphens::None),
+            "manual" => Some(Hyphens::Manual),
+            "auto" => Some(Hyphens::Auto),
+            _ => None,
+        }
+    }
+}
+
+/// Get the language of a tag, such as `en-GB`: the whole tag when a
+/// dictionary has it, otherwise the language of its primary subtag
+fn language(lang: &str) -> Option<Language> {
+    let tag = lang.trim().replace('_', "-").to_ascii_lowercase();
+    let primary = tag.split('-').next().unwrap_or_default();
+    Language::try_from_code(&tag).or_else(|| match primary {
+        // Languages whose dictionaries are all for a region or spelling
+        "en" => Some(Language::EnglishUS),
+        "de" => Some(Language::German1996),
+        "el" => Some(Language::GreekMono),
+        "mn" => Some(Language::Mongolian),
+        "sr" => Some(Language::SerbianCyrillic),
+        _ => Language::try_from_code(primary),
+    })
+}
+
+/// Get the hyphenation dictionary of a language tag, such as `en-GB`
+pub fn dictionary(lang: &str) -> Option<&'static Dictionary> {
+    static DICTIONARIES: OnceLock<Mutex<HashMap<Language, Option<&'static Dictionary>>>> = OnceLock::new();
+    let language = language(lang)?;
+    let mut dictionaries = DICTIONARIES.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner());
+    *dictionaries.entry(language).or_insert_with(|| match Standard::from_embedded(language) {
+        // Loaded once per language and kept for the rest of the run
+        Ok(dictionary) => Some(Box::leak(Box::new(dictionary))),
+        Err(e) => {
+            println!("⚠️  No hyphenation dictionary for {}: {}", language, e);
+            None
+        }
+    })
+}
+
+/// Get the character indices of a word before which it may break
+///
+/// Leading and trailing punctuation is skipped, and a word with anything
+/// but letters between them is not hyphenated.
+pub fn opportunities(dictionary: &Dictionary, word: &str) -> Vec<usize> {
+    let chars: Vec<char> = word.chars().collect();
+    let start = chars.iter().position(|c| c.is_alphabetic()).unwrap_or(chars.len());
+    let end = chars.iter().rposition(|c| c.is_alphabetic()).map_or(start, |end| end + 1);
+    let letters: String = chars[start..end].iter().collect();
+    if !letters.chars().all(char::is_alphabetic) {
+        return Vec::new();
+    }
+    // The dictionary reports byte offsets into the letters
+    dictionary
+        .hyphenate(&letters)
+        .breaks
+        .into_iter()
+        .map(|offset| start + letters[..offset].chars().count())
+        .collect()
+}
+
+/// Remove the soft hyphens of a word
+pub fn visible_text(word: &str) -> String {
+    word.chars().filter(|&c| c != SOFT_HYPHEN).collect()
+}
+
+/// Break a word at its last opportunity whose head, with a hyphen added,
+/// still `fits`
+///
+/// Returns the head as painted and the rest of the word, which keeps its
+/// soft hyphens for later breaks.
+pub fn break_word(word: &str, hyphens: Hyphens, dictionary: Option<&Dictionary>, mut fits: impl FnMut(&str) -> bool) -> Option<(String, String)> {
+    if hyphens == Hyphens::None {
+        return None;
+    }
+    let chars: Vec<char> = word.chars().collect();
+    let soft: Vec<usize> = chars.iter().enumerate().filter(|(_, &c)| c == SOFT_HYPHEN).map(|(index, _)| index).collect();
+
+    // (index of the break, whether a soft hyphen there is dropped)
+    let breaks: Vec<(usize, bool)> = if !soft.is_empty() {
+        soft.into_iter().map(|index| (index, true)).collect()
+    } else if hyphens == Hyphens::Auto {
+        dictionary.map(|dictionary| opportunities(dictionary, word)).unwrap_or_default().into_iter().map(|index| (index, false)).collect()
+    } else {
+        Vec::new()
+    };
+
+    for &(index, drop) in breaks.iter().rev() {
+        let head: String = chars[..index].iter().filter(|&&c| c != SOFT_HYPHEN).chain(std::iter::once(&HYPHEN)).collect();
+        let tail: String = chars[index + drop as usize..].iter().collect();
+        if head.chars().count() > 1 && !tail.is_empty() && fits(&head) {
+            return Some((head, tail));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_soft_hyphens_and_dictionary_breaks() {
+        let english = dictionary("en-US").unwrap();
+        assert_eq!(opportunities(english, "hyphenation"), vec![2, 6, 7]);
+        assert_eq!(opportunities(english, "(Hyphenation)"), vec![3, 7, 8]);
+        assert!(dictionary("tlh").is_none());
+
+        // Tags pick a regional dictionary, or the language's default one
+        assert_eq!(dictionary("en_GB").map(|dictionary| dictionary.language()), Some(Language::EnglishGB));
+        let german = dictionary("de-AT").unwrap();
+        assert_eq!(german.language(), Language::German1996);
+        assert_eq!(opportunities(german, "Silbentrennung"), vec![3, 6, 10]);
+
+        // The last break that fits wins
+        let fits = |head: &str| head.chars().count() <= 8;
+        assert_eq!(
+            break_word("hyphenation", Hyphens::Auto, Some(english), fits),
+            Some(("hyphena\u{2010}".to_string(), "tion".to_string()))
+        );
+        assert_eq!(break_word("hyphenation", Hyphens::Manual, Some(english), fits), None);
+
+        // Soft hyphens take priority over the dictionary and vanish
+        let word = "hyphe\u{ad}na\u{ad}tion";
+        assert_eq!(visible_text(word), "hyphenation");
+        assert_eq!(
+            break_word(word, Hyphens::Auto, Some(english), fits),
+            Some(("hyphena\u{2010}".to_string(), "tion".to_string()))
+        );
+        assert_eq!(break_word(word, Hyphens::None, Some(english), |_| true), None);
+    }
+}
@@ -0,0 +1,438 @@
+//! # Inline Text Layout
+//!
+//! This module breaks the text of a text node into lines and produces the
+//! fragments that are painted for it, including the `::first-line` and
+//! `::first-letter` pseudo-elements of its block container.
+//!
+//! ## Design Principles
+//!
+//! 1. **Greedy Line Breaking**: Whitespace is collapsed and words are placed
+//!    on a line until the next one does not fit. A word wider than the line
+//!    overflows on a line of its own.
+//!
+//! 2. **Styles Decide Fragmentation**: `::first-line` styles apply to
+//!    whatever fits on the first line, and the first line is measured with
+//!    those styles, so the cascade and the line breaker work together.
+//!
+//! 3. **Drop Caps**: A floated `::first-letter` takes space beside as many
+//!    lines as its height covers; an inline one starts the first line.
+//!
+//! 4. **Break, Then Reorder**: Lines are broken in logical order and each
+//!    line is then split into bidi runs placed in visual order, so mixed
+//!    left-to-right and right-to-left text reorders within its line box.
+//!    Right-to-left paragraphs start at the right edge of the line.
+//!
+//! 5. **Hyphenation**: A word that does not fit is broken at its last soft
+//!    hyphen or, under `hyphens: auto`, dictionary break whose head fits,
+//!    and the hyphen glyph is added to that head only.
+//!
+//! Text is measured with an average advance per character until real font
+//! shaping lands. Words are measured through a `TextMeasurementCache`, so
+//! relayout of unchanged text reuses earlier measurements.
+
+use crate::measurement::{FontKey, TextMeasurementCache};
+use crate::hyphenation;
+use crate::{ComputedStyles, Float, UnicodeBidi};
+use dom::bidi::{self, BidiParagraph, Direction};
+
+/// Average glyph advance as a fraction of the font size
+pub const AVERAGE_CHAR_WIDTH: f32 = 0.5;
+
+/// Line height as a multiple of the font size
+pub const LINE_HEIGHT_FACTOR: f32 = 1.2;
+
+/// Pseudo-elements layout generates: those of a block container's first
+/// line, the backdrop of a top layer element, and generated content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PseudoElement {
+    FirstLine,
+    FirstLetter,
+    Backdrop,
+    Before,
+    After,
+}
+
+impl PseudoElement {
+    /// Get the name used in selectors, without colons
+    pub fn name(&self) -> &'static str {
+        match self {
+            PseudoElement::FirstLine => "first-line",
+            PseudoElement::FirstLetter => "first-letter",
+            PseudoElement::Backdrop => "backdrop",
+            PseudoElement::Before => "before",
+            PseudoElement::After => "after",
+        }
+    }
+}
+
+/// A run of text painted with one set of styles
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextFragment {
+    /// The text of the run, with whitespace collapsed
+    pub text: String,
+    /// Position relative to the text box's content area
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    /// The pseudo-element the run belongs to, if any
+    pub pseudo: Option<PseudoElement>,
+    /// Bidi embedding level; the text of an odd, right-to-left level is
+    /// stored in visual order, ready to paint left to right
+    pub level: u8,
+    /// Styles of the run
+    pub styles: ComputedStyles,
+}
+
+/// Result of laying out a text node
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextLayout {
+    pub fragments: Vec<TextFragment>,
+    /// Height of all lines and floats
+    pub height: f32,
+}
+
+/// Measure a run of text
+pub fn measure_text(text: &str, font_size: f32) -> f32 {
+    text.chars().count() as f32 * font_size * AVERAGE_CHAR_WIDTH
+}
+
+/// Split the first typographic letter unit off a word
+///
+/// The unit is the first letter or digit together with any punctuation
+/// before and directly after it, e.g. `“A` from `“All`.
+pub fn split_first_letter(word: &str) -> (String, String) {
+    let is_punctuation = |c: char| !c.is_alphanumeric() && !c.is_whitespace();
+    let mut letter = String::new();
+    let mut chars = word.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if !is_punctuation(c) {
+            break;
+        }
+        letter.push(c);
+        chars.next();
+    }
+    match chars.next() {
+        Some(c) => letter.push(c),
+        // Only punctuation: there is no first letter
+        None => return (String::new(), word.to_string()),
+    }
+    while let Some(&c) = chars.peek() {
+        if !is_punctuation(c) {
+            break;
+        }
+        letter.push(c);
+        chars.next();
+    }
+    (letter, chars.collect())
+}
+
+fn font_size(styles: &ComputedStyles) -> f32 {
+    styles.font.size.unwrap_or(16.0)
+}
+
+/// Space taken by a floated first letter
+struct FloatArea {
+    side: Float,
+    outer_width: f32,
+    bottom: f32,
+}
+
+/// Split a line into bidi runs in visual order
+///
+/// The runs are laid out from the start edge of the line: its left edge,
+/// or `end_x` minus the line's width in a right-to-left paragraph.
+fn reorder_line(line: TextFragment, direction: Direction, unicode_bidi: UnicodeBidi, end_x: f32, cache: &mut TextMeasurementCache) -> Vec<TextFragment> {
+    let paragraph = if unicode_bidi.is_override() {
+        BidiParagraph::overridden(&line.text, direction)
+    } else {
+        BidiParagraph::new(&line.text, Some(direction))
+    };
+    let mut x = match direction {
+        Direction::Ltr => line.x,
+        Direction::Rtl => end_x - line.width,
+    };
+    let runs = paragraph.visual_runs(0..paragraph.len());
+    if let [run] = runs.as_slice() {
+        return vec![TextFragment { x, level: run.level, text: paragraph.visual_text(run), ..line }];
+    }
+    let font = FontKey::from_styles(&line.styles);
+    runs.iter()
+        .map(|run| {
+            let text = paragraph.visual_text(run);
+            let width = cache.measure(&text, &font);
+            let fragment = TextFragment { text, x, width, level: run.level, ..line.clone() };
+            x += width;
+            fragment
+        })
+        .collect()
+}
+
+/// Break text into lines and fragments
+///
+/// `first_line` and `first_letter` are the pseudo-element styles of the
+/// block container when the text starts its first line. Each line is
+/// reordered for bidi text in the direction of `styles`.
+pub fn layout_text(
+    text: &str,
+    available_width: f32,
+    styles: &ComputedStyles,
+    first_line: Option<&ComputedStyles>,
+    first_letter: Option<&ComputedStyles>,
+    cache: &mut TextMeasurementCache,
+) -> TextLayout {
+    let mut words: Vec<String> = text.split_whitespace().map(str::to_string).collect();
+    if words.is_empty() {
+        return TextLayout::default();
+    }
+    words.reverse();
+    let direction = match styles.unicode_bidi {
+        UnicodeBidi::Plaintext => bidi::first_strong_direction(text).unwrap_or_default(),
+        _ => styles.direction.unwrap_or_default(),
+    };
+    let hyphens = styles.hyphens.unwrap_or_default();
+    let dictionary = styles.lang.as_deref().and_then(hyphenation::dictionary);
+
+    let mut fragments = Vec::new();
+    let mut float_area: Option<FloatArea> = None;
+    let mut inline_letter: Option<TextFragment> = None;
+    // The rest of the first word continues the letter without a space
+    let mut glued = false;
+
+    if let Some(letter_styles) = first_letter {
+        let first_word = words.pop().unwrap_or_default();
+        let (letter, rest) = split_first_letter(&first_word);
+        if !rest.is_empty() {
+            words.push(rest);
+            glued = !letter.is_empty();
+        }
+        if !letter.is_empty() {
+            let size = font_size(letter_styles);
+            let margin = &letter_styles.box_model.margin;
+            let mut fragment = TextFragment {
+                width: cache.measure(&letter, &FontKey::from_styles(letter_styles)),
+                height: size * LINE_HEIGHT_FACTOR,
+                text: letter,
+                x: 0.0,
+                y: margin.top,
+                pseudo: Some(PseudoElement::FirstLetter),
+                level: 0,
+                styles: letter_styles.clone(),
+            };
+            let outer_width = margin.left + fragment.width + margin.right;
+            let bottom = margin.top + fragment.height + margin.bottom;
+            match letter_styles.float {
+                Float::Left => {
+                    fragment.x = margin.left;
+                    float_area = Some(FloatArea { side: Float::Left, outer_width, bottom });
+                    fragments.push(fragment);
+                }
+                Float::Right => {
+                    fragment.x = available_width - margin.right - fragment.width;
+                    float_area = Some(FloatArea { side: Float::Right, outer_width, bottom });
+                    fragments.push(fragment);
+                }
+                Float::None => {
+                    fragment.y = 0.0;
+                    inline_letter = Some(fragment);
+                }
+            }
+        }
+    }
+
+    let mut y = 0.0;
+    let mut line_index = 0;
+    while !words.is_empty() || inline_letter.is_some() {
+        let (line_styles, pseudo) = match (line_index, first_line) {
+            (0, Some(first_line)) => (first_line, Some(PseudoElement::FirstLine)),
+            _ => (styles, None),
+        };
+        let size = font_size(line_styles);
+        let font = FontKey::from_styles(line_styles);
+        let space = cache.measure(" ", &font);
+        let mut line_height = size * LINE_HEIGHT_FACTOR;
+
+        // Lines beside a floated letter are shortened
+        let (mut x, end_x) = match &float_area {
+            Some(area) if y < area.bottom => match area.side {
+                Float::Right => (0.0, available_width - area.outer_width),
+                _ => (area.outer_width, available_width),
+            },
+            _ => (0.0, available_width),
+        };
+
+        let mut needs_space = false;
+        if let Some(mut letter) = inline_letter.take() {
+            letter.x = x;
+            x += letter.width;
+            line_height = line_height.max(letter.height);
+            fragments.push(letter);
+            needs_space = !glued;
+        }
+
+        let mut line_text = String::new();
+        let mut text_x = x;
+        while let Some(word) = words.pop() {
+            let gap = if line_text.is_empty() && !needs_space { 0.0 } else { space };
+            let mut text = hyphenation::visible_text(&word);
+            let mut width = cache.measure(&text, &font);
+            let mut hyphenated = false;
+            if x + gap + width > end_x {
+                // Break the word with a hyphen if its head fits
+                let room = end_x - x - gap;
+                match hyphenation::break_word(&word, hyphens, dictionary, |head| cache.measure(head, &font) <= room) {
+                    Some((head, tail)) => {
+                        width = cache.measure(&head, &font);
+                        text = head;
+                        words.push(tail);
+                        hyphenated = true;
+                    }
+                    None if !line_text.is_empty() => {
+                        words.push(word);
+                        break;
+                    }
+                    None => {}
+                }
+            }
+            if line_text.is_empty() {
+                text_x = x + gap;
+            } else {
+                line_text.push(' ');
+            }
+            line_text.push_str(&text);
+            x += gap + width;
+            needs_space = true;
+            if hyphenated {
+                break;
+            }
+        }
+
+        if !line_text.is_empty() {
+            let line = TextFragment {
+                width: x - text_x,
+                height: size * LINE_HEIGHT_FACTOR,
+                text: line_text,
+                x: text_x,
+                y,
+                pseudo,
+                level: direction.level(),
+                styles: line_styles.clone(),
+            };
+            fragments.extend(reorder_line(line, direction, styles.unicode_bidi, end_x, cache));
+        }
+        y += line_height;
+        line_index += 1;
+    }
+
+    let height = float_area.map_or(y, |area| y.max(area.bottom));
+    TextLayout { fragments, height }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Hyphens;
+
+    #[test]
+    fn test_split_first_letter() {
+        assert_eq!(split_first_letter("Once"), ("O".to_string(), "nce".to_string()));
+        assert_eq!(split_first_letter("“A”"), ("“A”".to_string(), String::new()));
+        assert_eq!(split_first_letter("..."), (String::new(), "...".to_string()));
+    }
+
+    #[test]
+    fn test_first_line_styles_change_line_breaks() {
+        let styles = ComputedStyles::default();
+        let text = "aaaa bbbb cccc dddd";
+        // 16px text: 8px per character, so two words fit in 80px
+        let plain = layout_text(text, 80.0, &styles, None, None, &mut TextMeasurementCache::default());
+        assert_eq!(plain.fragments.len(), 2);
+        assert_eq!(plain.fragments[0].text, "aaaa bbbb");
+
+        // A larger first line holds a single word
+        let mut first_line = styles.clone();
+        first_line.font_mut().size = Some(32.0);
+        let styled = layout_text(text, 80.0, &styles, Some(&first_line), None, &mut TextMeasurementCache::default());
+        assert_eq!(styled.fragments[0].text, "aaaa");
+        assert_eq!(styled.fragments[0].pseudo, Some(PseudoElement::FirstLine));
+        assert_eq!(styled.fragments[1].text, "bbbb cccc");
+        assert_eq!(styled.fragments[1].y, 32.0 * LINE_HEIGHT_FACTOR);
+        assert_eq!(styled.fragments[1].pseudo, None);
+    }
+
+    #[test]
+    fn test_floated_first_letter_shortens_lines() {
+        let styles = ComputedStyles::default();
+        let mut drop_cap = ComputedStyles { float: Float::Left, ..styles.clone() };
+        drop_cap.font_mut().size = Some(40.0);
+        let layout = layout_text("Once upon a time there was a dragon", 100.0, &styles, None, Some(&drop_cap), &mut TextMeasurementCache::default());
+
+        let letter = &layout.fragments[0];
+        assert_eq!(letter.text, "O");
+        assert_eq!(letter.pseudo, Some(PseudoElement::FirstLetter));
+        assert_eq!(letter.width, 20.0);
+
+        // The rest of the word continues beside the letter
+        let first_line = &layout.fragments[1];
+        assert_eq!(first_line.text, "nce upon a");
+        assert_eq!(first_line.x, 20.0);
+        // The letter is 48px tall, so only the fourth line is below it
+        assert_eq!(layout.fragments[3].text, "was a");
+        assert_eq!(layout.fragments[3].x, 20.0);
+        assert_eq!(layout.fragments[4].text, "dragon");
+        assert_eq!(layout.fragments[4].x, 0.0);
+        assert!((layout.height - 4.0 * 16.0 * LINE_HEIGHT_FACTOR).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_bidi_lines_reorder_into_visual_runs() {
+        // "abc" followed by the Hebrew letters alef and bet
+        let text = "abc \u{5d0}\u{5d1}";
+        let ltr = ComputedStyles::default();
+        let layout = layout_text(text, 200.0, &ltr, None, None, &mut TextMeasurementCache::default());
+        let runs: Vec<(&str, f32, u8)> = layout.fragments.iter().map(|f| (f.text.as_str(), f.x, f.level)).collect();
+        assert_eq!(runs, vec![("abc ", 0.0, 0), ("\u{5d1}\u{5d0}", 32.0, 1)]);
+
+        // A right-to-left paragraph starts at the right edge and puts the
+        // Hebrew word first
+        let rtl = ComputedStyles { direction: Some(Direction::Rtl), ..ltr.clone() };
+        let layout = layout_text(text, 200.0, &rtl, None, None, &mut TextMeasurementCache::default());
+        let runs: Vec<(&str, f32, u8)> = layout.fragments.iter().map(|f| (f.text.as_str(), f.x, f.level)).collect();
+        assert_eq!(runs, vec![("\u{5d1}\u{5d0} ", 152.0, 1), ("abc", 176.0, 2)]);
+
+        // plaintext takes the direction from the text, and overrides force
+        // one run
+        let plaintext = ComputedStyles { unicode_bidi: UnicodeBidi::Plaintext, ..ltr.clone() };
+        let layout = layout_text("\u{5d0} abc", 200.0, &plaintext, None, None, &mut TextMeasurementCache::default());
+        assert_eq!(layout.fragments[0].text, "abc");
+        assert_eq!(layout.fragments[0].x, 160.0);
+        let overridden = ComputedStyles { unicode_bidi: UnicodeBidi::BidiOverride, ..rtl };
+        let layout = layout_text("abc", 200.0, &overridden, None, None, &mut TextMeasurementCache::default());
+        assert_eq!(layout.fragments.len(), 1);
+        assert_eq!(layout.fragments[0].text, "cba");
+    }
+
+    #[test]
+    fn test_words_break_at_hyphenation_points() {
+        let texts = |layout: &TextLayout| layout.fragments.iter().map(|f| f.text.clone()).collect::<Vec<_>>();
+        // 8px per character: ten fit on an 80px line
+        let auto = ComputedStyles { hyphens: Some(Hyphens::Auto), lang: Some("en".to_string()), ..ComputedStyles::default() };
+        let layout = layout_text("hyphenation is fun", 80.0, &auto, None, None, &mut TextMeasurementCache::default());
+        assert_eq!(texts(&layout), vec!["hyphena\u{2010}", "tion is", "fun"]);
+
+        // Soft hyphens are invisible unless a line breaks at them
+        let manual = ComputedStyles::default();
+        let layout = layout_text("hy\u{ad}phen\u{ad}ation is fun", 80.0, &manual, None, None, &mut TextMeasurementCache::default());
+        assert_eq!(texts(&layout), vec!["hyphen\u{2010}", "ation is", "fun"]);
+        let layout = layout_text("a\u{ad}b is fun", 80.0, &manual, None, None, &mut TextMeasurementCache::default());
+        assert_eq!(texts(&layout), vec!["ab is fun"]);
+
+        // Without a dictionary or with hyphens: none, the word overflows
+        let unknown = ComputedStyles { lang: None, ..auto.clone() };
+        let layout = layout_text("hyphenation is fun", 80.0, &unknown, None, None, &mut TextMeasurementCache::default());
+        assert_eq!(texts(&layout), vec!["hyphenation", "is fun"]);
+        let none = ComputedStyles { hyphens: Some(Hyphens::None), ..manual };
+        let layout = layout_text("hy\u{ad}phen\u{ad}ation", 80.0, &none, None, None, &mut TextMeasurementCache::default());
+        assert_eq!(texts(&layout), vec!["hyphenation"]);
+    }
+}
@@ -0,0 +1,218 @@
+//! # Rendered Text
+//!
+//! This module implements the getter of `innerText`: the text of an element
+//! as it is rendered, taken from the layout tree rather than the DOM.
+//!
+//! ## Design Principles
+//!
+//! 1. **Layout Aware**: Only boxes that are rendered contribute. Elements
+//!    with `display: none` are skipped, and the text of boxes that are not
+//!    `visibility: visible` is left out while their descendants, which can
+//!    be visible again, are still visited.
+//!
+//! 2. **Required Line Breaks**: Block boxes are separated by one line break
+//!    and paragraphs by two. Adjacent required breaks merge into the largest
+//!    of them and none are kept at the start or the end, as the HTML
+//!    specification describes.
+//!
+//! 3. **Collapsed Whitespace**: Runs of whitespace in text become a single
+//!    space, and spaces at the start and end of lines are removed. `<br>`
+//!    gives a line break, table cells are separated by tabs and table rows
+//!    by line breaks.
+//!
+//! 4. **Not Rendered Means None**: For a node without a box `inner_text`
+//!    returns `None`, and callers fall back to the text content, as the
+//!    getter does for elements that are not being rendered.
+
+use dom::Node;
+use crate::{DisplayType, LayoutBox, Visibility};
+
+/// Elements the user agent style sheet never renders
+pub(crate) const UNRENDERED_ELEMENTS: [&str; 7] = ["head", "script", "style", "template", "title", "meta", "link"];
+
+/// A piece of rendered text
+#[derive(Debug, Clone, PartialEq)]
+enum Item {
+    /// Text with whitespace already collapsed
+    Text(String),
+    /// A number of line breaks that merges with its neighbours
+    RequiredBreak(usize),
+}
+
+/// Get the rendered text of `node`, or `None` if it has no box in `root`
+pub fn inner_text(root: &LayoutBox, node: &Node) -> Option<String> {
+    let layout_box = find_box(root, node.id)?;
+    if layout_box.styles.display == DisplayType::None {
+        return None;
+    }
+    let mut items = Vec::new();
+    for child in &layout_box.children {
+        collect_items(child, &mut items);
+    }
+    Some(join_items(items))
+}
+
+/// Find the box generated for the node with the given id
+fn find_box(layout_box: &LayoutBox, node_id: u64) -> Option<&LayoutBox> {
+    if layout_box.node.id == node_id {
+        return Some(layout_box);
+    }
+    layout_box.children.iter().find_map(|child| find_box(child, node_id))
+}
+
+/// Append the items of a box and its descendants
+fn collect_items(layout_box: &LayoutBox, items: &mut Vec<Item>) {
+    let styles = &layout_box.styles;
+    let tag = layout_box.node.tag_name().map(|tag| tag.to_ascii_lowercase());
+    if styles.display == DisplayType::None || tag.as_deref().is_some_and(|tag| UNRENDERED_ELEMENTS.contains(&tag)) {
+        return;
+    }
+    let visible = styles.visibility.unwrap_or_default() == Visibility::Visible;
+    if let Some(data) = layout_box.node.character_data() {
+        if visible {
+            items.push(Item::Text(collapse_whitespace(&data)));
+        }
+        return;
+    }
+    let breaks = match tag.as_deref() {
+        _ if !visible => 0,
+        Some("p") => 2,
+        Some("tr") => 1,
+        Some("td" | "th" | "br") => 0,
+        _ if matches!(styles.display, DisplayType::Block | DisplayType::Flex | DisplayType::Grid) => 1,
+        _ => 0,
+    };
+    if breaks > 0 {
+        items.push(Item::RequiredBreak(breaks));
+    }
+    if visible && tag.as_deref() == Some("br") {
+        items.push(Item::Text("\n".to_string()));
+    }
+    for child in &layout_box.children {
+        collect_items(child, items);
+    }
+    if visible && matches!(tag.as_deref(), Some("td" | "th")) && has_next_cell(layout_box) {
+        items.push(Item::Text("\t".to_string()));
+    }
+    if breaks > 0 {
+        items.push(Item::RequiredBreak(breaks));
+    }
+}
+
+/// Check whether a table cell is followed by another cell in its row
+fn has_next_cell(cell: &LayoutBox) -> bool {
+    let Some(row) = cell.node.parent.borrow().upgrade() else {
+        return false;
+    };
+    let children = row.children.borrow();
+    children
+        .iter()
+        .skip_while(|sibling| sibling.id != cell.node.id)
+        .skip(1)
+        .any(|sibling| matches!(sibling.tag_name().map(|tag| tag.to_ascii_lowercase()).as_deref(), Some("td" | "th")))
+}
+
+/// Replace every run of whitespace with a single space
+pub(crate) fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut in_whitespace = false;
+    for ch in text.chars() {
+        if ch.is_whitespace() {
+            if !in_whitespace {
+                collapsed.push(' ');
+            }
+            in_whitespace = true;
+        } else {
+            collapsed.push(ch);
+            in_whitespace = false;
+        }
+    }
+    collapsed
+}
+
+/// Join items into text, merging required breaks and trimming line edges
+fn join_items(items: Vec<Item>) -> String {
+    let mut output = String::new();
+    let mut pending_breaks = 0;
+    for item in items {
+        match item {
+            Item::RequiredBreak(count) => pending_breaks = pending_breaks.max(count),
+            Item::Text(text) => {
+                let at_line_start = output.is_empty() || output.ends_with('\n') || pending_breaks > 0;
+                let text = if at_line_start || output.ends_with(' ') { text.trim_start_matches(' ') } else { text.as_str() };
+                if text.is_empty() {
+                    continue;
+                }
+                if pending_breaks > 0 {
+                    trim_trailing_spaces(&mut output);
+                    if !output.is_empty() {
+                        output.push_str(&"\n".repeat(pending_breaks));
+                    }
+                    pending_breaks = 0;
+                }
+                if text.starts_with('\n') {
+                    trim_trailing_spaces(&mut output);
+                }
+                output.push_str(text);
+            }
+        }
+    }
+    trim_trailing_spaces(&mut output);
+    output
+}
+
+/// Remove spaces at the end of the current line
+fn trim_trailing_spaces(output: &mut String) {
+    output.truncate(output.trim_end_matches(' ').len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LayoutEngine;
+    use css_parser::parse_css;
+    use dom::Document;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_inner_text_follows_rendering() {
+        let document = Document::new();
+        let element = |parent: &Rc<Node>, tag: &str, class: Option<&str>| {
+            let node = document.create_element(tag);
+            if let Some(class) = class {
+                node.set_attribute("class", class);
+            }
+            parent.append_child(&node);
+            node
+        };
+        let text = |parent: &Rc<Node>, data: &str| parent.append_child(&document.create_text_node(data));
+
+        let root = element(&document.root, "div", None);
+        let first = element(&root, "p", None);
+        text(&first, "  First \n  paragraph ");
+        let second = element(&root, "p", None);
+        text(&second, "Second");
+        element(&second, "br", None);
+        text(&second, "line ");
+        let secret = element(&second, "span", Some("secret"));
+        text(&secret, "hidden ");
+        let shown = element(&secret, "em", Some("shown"));
+        text(&shown, "shown");
+        let gone = element(&root, "div", Some("gone"));
+        text(&gone, "gone");
+        let table = element(&root, "table", None);
+        for cells in [["a", "b"], ["c", "d"]] {
+            let row = element(&table, "tr", None);
+            for cell in cells {
+                let cell_element = element(&row, "td", None);
+                text(&cell_element, cell);
+            }
+        }
+
+        let stylesheet = parse_css(".secret {\n  visibility: hidden;\n}\n.shown {\n  visibility: visible;\n}\n.gone {\n  display: none;\n}");
+        let layout = LayoutEngine::new(stylesheet).layout_document(&document);
+        assert_eq!(inner_text(&layout, &root).unwrap(), "First paragraph\n\nSecond\nline shown\n\na\tb\nc\td");
+        assert!(root.text_content().contains("gone"));
+        assert_eq!(inner_text(&layout, &gone), None);
+    }
+}
@@ -0,0 +1,291 @@
+//! # Layer Promotion
+//!
+//! This module parses `will-change` and decides which boxes the
+//! compositor gives a layer of their own, and why.
+//!
+//! ## Design Principles
+//!
+//! 1. **Promote Ahead of Time**: A box hinted with `will-change` or
+//!    already animating is promoted at layout time, so its layer exists
+//!    before the first animated frame instead of being created during it.
+//! 2. **Reasons, Not Flags**: Every layer records why it was promoted, so
+//!    over-promotion can be traced back to the rule that caused it.
+//! 3. **Budgeted**: Layers cost texture memory. The layer tree reports its
+//!    size and flags pages whose layers add up to several viewports.
+//! 4. **The Top Layer Composites Last**: Dialogs and popovers in the top
+//!    layer get layers of their own. Their boxes come last in the layout
+//!    tree, so their layers stack above every other layer.
+
+use std::fmt;
+use dom::top_layer;
+use crate::{BlendMode, Dimensions, LayoutBox, Position};
+
+/// Layers may cover this many viewports before the page counts as
+/// over-promoted
+pub const OVER_PROMOTION_FACTOR: f32 = 4.0;
+
+/// Properties whose changes the compositor can apply to a layer without
+/// repainting it
+const COMPOSITED_PROPERTIES: [&str; 6] = ["transform", "opacity", "filter", "translate", "rotate", "scale"];
+
+/// Properties that create a stacking context when set to a non-initial
+/// value, and so do when hinted
+const STACKING_PROPERTIES: [&str; 9] = [
+    "transform", "opacity", "filter", "translate", "rotate", "scale", "isolation", "mix-blend-mode", "z-index",
+];
+
+/// The properties an element expects to change (`will-change`)
+///
+/// Empty means `auto`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WillChange {
+    pub properties: Vec<String>,
+}
+
+impl WillChange {
+    /// Parse a `will-change` value
+    ///
+    /// Returns `None` for an invalid value, such as one naming `all` or
+    /// `none`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().to_ascii_lowercase();
+        if value == "auto" {
+            return Some(WillChange::default());
+        }
+        let mut properties = Vec::new();
+        for property in value.split(',').map(str::trim) {
+            let valid = !property.is_empty()
+                && !matches!(property, "auto" | "none" | "all" | "will-change")
+                && property.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+            if !valid {
+                return None;
+            }
+            properties.push(property.to_string());
+        }
+        Some(WillChange { properties })
+    }
+
+    pub fn is_auto(&self) -> bool {
+        self.properties.is_empty()
+    }
+
+    /// Check whether a property is hinted
+    pub fn hints(&self, property: &str) -> bool {
+        self.properties.iter().any(|hinted| hinted == property)
+    }
+
+    /// Check whether the hints ask for a layer
+    ///
+    /// `scroll-position` and `contents` do not: they describe changes
+    /// that repaint anyway.
+    pub fn promotes(&self) -> bool {
+        COMPOSITED_PROPERTIES.iter().any(|property| self.hints(property))
+    }
+
+    /// Check whether the hints make the element a stacking context
+    pub fn creates_stacking_context(&self) -> bool {
+        STACKING_PROPERTIES.iter().any(|property| self.hints(property))
+    }
+}
+
+/// Why a box has a layer of its own
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayerReason {
+    /// `will-change` names composited properties
+    WillChange(Vec<String>),
+    /// A CSS animation is running
+    Animation(String),
+    Filter,
+    BlendMode(BlendMode),
+    FixedPosition,
+    StickyPosition,
+    /// A video or canvas, whose content changes without a relayout
+    ReplacedContent,
+    /// The element, or its `::backdrop`, is in the top layer
+    TopLayer,
+}
+
+impl fmt::Display for LayerReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayerReason::WillChange(properties) => write!(f, "will-change: {}", properties.join(", ")),
+            LayerReason::Animation(name) => write!(f, "animation {}", name),
+            LayerReason::Filter => write!(f, "filter"),
+            LayerReason::BlendMode(mode) => write!(f, "mix-blend-mode {:?}", mode),
+            LayerReason::FixedPosition => write!(f, "position: fixed"),
+            LayerReason::StickyPosition => write!(f, "position: sticky"),
+            LayerReason::ReplacedContent => write!(f, "video or canvas"),
+            LayerReason::TopLayer => write!(f, "top layer"),
+        }
+    }
+}
+
+/// Get the reasons a box is promoted to a layer; empty if it is not
+pub fn promotion_reasons(layout_box: &LayoutBox) -> Vec<LayerReason> {
+    let styles = &layout_box.styles;
+    let mut reasons = Vec::new();
+    if styles.will_change.promotes() {
+        let hinted = styles
+            .will_change
+            .properties
+            .iter()
+            .filter(|property| COMPOSITED_PROPERTIES.contains(&property.as_str()))
+            .cloned()
+            .collect();
+        reasons.push(LayerReason::WillChange(hinted));
+    }
+    if let Some(name) = styles.animation_name.as_ref().filter(|_| layout_box.animation_state.is_running) {
+        reasons.push(LayerReason::Animation(name.clone()));
+    }
+    if !styles.filter.is_empty() {
+        reasons.push(LayerReason::Filter);
+    }
+    if styles.mix_blend_mode != BlendMode::Normal {
+        reasons.push(LayerReason::BlendMode(styles.mix_blend_mode));
+    }
+    match styles.position {
+        Position::Fixed => reasons.push(LayerReason::FixedPosition),
+        Position::Sticky => reasons.push(LayerReason::StickyPosition),
+        _ => {}
+    }
+    if matches!(layout_box.node.tag_name().map(|tag| tag.to_ascii_lowercase()).as_deref(), Some("video" | "canvas")) {
+        reasons.push(LayerReason::ReplacedContent);
+    }
+    if top_layer::contains(&layout_box.node) {
+        reasons.push(LayerReason::TopLayer);
+    }
+    reasons
+}
+
+/// A box with a layer of its own
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompositedLayer {
+    pub node_id: u64,
+    /// The element as a selector, such as `div#menu`
+    pub label: String,
+    /// The border box the layer covers
+    pub bounds: Dimensions,
+    pub reasons: Vec<LayerReason>,
+}
+
+/// The layers of a page, in paint order
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LayerTree {
+    pub layers: Vec<CompositedLayer>,
+}
+
+impl LayerTree {
+    /// Assign layers to the boxes of a layout tree
+    pub fn from_layout(root: &LayoutBox) -> Self {
+        fn collect(layout_box: &LayoutBox, layers: &mut Vec<CompositedLayer>) {
+            let reasons = promotion_reasons(layout_box);
+            if !reasons.is_empty() {
+                let node = &layout_box.node;
+                let tag = node.tag_name().unwrap_or_default().to_ascii_lowercase();
+                let label = match node.get_attribute("id") {
+                    Some(id) => format!("{}#{}", tag, id),
+                    None => tag,
+                };
+                layers.push(CompositedLayer { node_id: node.id, label, bounds: layout_box.border, reasons });
+            }
+            for child in &layout_box.children {
+                collect(child, layers);
+            }
+        }
+        let mut layers = Vec::new();
+        collect(root, &mut layers);
+        LayerTree { layers }
+    }
+
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Check whether a node has a layer
+    pub fn is_promoted(&self, node_id: u64) -> bool {
+        self.layers.iter().any(|layer| layer.node_id == node_id)
+    }
+
+    /// Get the texture memory the layers need, at four bytes a pixel
+    pub fn texture_bytes(&self) -> u64 {
+        self.layers
+            .iter()
+            .map(|layer| (layer.bounds.width.max(0.0).ceil() * layer.bounds.height.max(0.0).ceil()) as u64 * 4)
+            .sum()
+    }
+
+    /// Check whether the layers cover more than `OVER_PROMOTION_FACTOR`
+    /// viewports
+    pub fn is_over_promoted(&self, viewport: &Dimensions) -> bool {
+        let area: f32 = self.layers.iter().map(|layer| layer.bounds.width * layer.bounds.height).sum();
+        area > viewport.width * viewport.height * OVER_PROMOTION_FACTOR
+    }
+
+    /// Describe the layers for debugging, one line each
+    pub fn report(&self) -> String {
+        self.layers
+            .iter()
+            .map(|layer| {
+                let reasons: Vec<String> = layer.reasons.iter().map(LayerReason::to_string).collect();
+                format!(
+                    "{} [{:.0},{:.0} {:.0}x{:.0}]: {}",
+                    layer.label, layer.bounds.x, layer.bounds.y, layer.bounds.width, layer.bounds.height, reasons.join("; ")
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LayoutEngine;
+    use css_parser::CSSParser;
+    use dom::Document;
+
+    #[test]
+    fn test_will_change_promotes_layers() {
+        assert_eq!(WillChange::parse("auto"), Some(WillChange::default()));
+        assert!(WillChange::parse("transform, all").is_none());
+        let hints = WillChange::parse("scroll-position, Opacity").unwrap();
+        assert!(hints.promotes() && hints.creates_stacking_context());
+        assert!(!WillChange::parse("scroll-position, contents").unwrap().promotes());
+
+        let doc = Document::new();
+        let body = doc.create_element("body");
+        let menu = doc.create_element("div");
+        menu.set_attribute("id", "menu");
+        let list = doc.create_element("div");
+        body.append_child(&menu);
+        body.append_child(&list);
+        doc.root.append_child(&body);
+
+        let css = "div { will-change: scroll-position } #menu { will-change: transform; height: 40px }";
+        let stylesheet = CSSParser::new(css.to_string()).parse_stylesheet().unwrap();
+        let layout = LayoutEngine::new(stylesheet).layout_document(&doc);
+        let layers = LayerTree::from_layout(&layout);
+
+        assert_eq!(layers.len(), 1);
+        assert!(layers.is_promoted(menu.id) && !layers.is_promoted(list.id));
+        assert_eq!(layers.layers[0].reasons, vec![LayerReason::WillChange(vec!["transform".to_string()])]);
+        assert!(layers.report().starts_with("div#menu ["));
+        assert!(!layers.is_over_promoted(&Dimensions::new(0.0, 0.0, 800.0, 600.0)));
+
+        // An open popover is composited above the rest of the page
+        let popover = doc.create_element("section");
+        popover.set_attribute("popover", "");
+        body.append_child(&popover);
+        let engine = LayoutEngine::new(CSSParser::new(css.to_string()).parse_stylesheet().unwrap());
+        assert!(!LayerTree::from_layout(&engine.layout_document(&doc)).is_promoted(popover.id));
+        popover.show_popover(None).unwrap();
+        let layers = LayerTree::from_layout(&engine.layout_document(&doc));
+        assert_eq!(layers.len(), 2);
+        assert_eq!(layers.layers[1].node_id, popover.id);
+        assert_eq!(layers.layers[1].reasons, vec![LayerReason::TopLayer]);
+    }
+}
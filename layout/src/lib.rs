@@ -17,12 +17,86 @@
 //! 
 //! 4. **Extensibility**: Designed to support additional layout modes like
 //!    flexbox and grid in the future.
+//! 
+//! 5. **Skipped Subtrees**: `display: contents` elements generate no box of
+//!    their own, and `content-visibility` lets size-contained subtrees skip
+//!    layout and paint until they come near the viewport.
+//! 
+//! 6. **Scroll-Linked Positioning**: Layout records constraint rectangles
+//!    for `position: sticky` boxes; the `sticky` module turns them into
+//!    paint-time offsets as the page scrolls.
+//! 
+//! 7. **Inline Text**: Text is broken into lines by the `inline` module,
+//!    which also splits off `::first-line` and `::first-letter` fragments.
+//!    `::before` and `::after` boxes lay out the text the
+//!    `generated_content` module resolves from `content`.
+//! 
+//! 8. **Top Layer**: Elements in a document's top layer, such as modal
+//!    dialogs, open popovers and fullscreen elements, are taken out of flow
+//!    and laid out last against the viewport, modal and fullscreen ones
+//!    above their `::backdrop`.
 
 use dom::{Document, Node, NodeType};
-use css_parser::{Stylesheet, Selector, CSSValue};
+use dom::forms::FormControlType;
+use dom::top_layer;
+use dom::bidi::Direction;
+use dom::clock::SharedClock;
+use css_parser::{Stylesheet, Selector, CSSValue, StyleInvalidation, PageRule};
+use dom::mutations::Mutation;
+use css_parser::media::MediaFeatures;
+use style_sharing::{SharingKey, StyleSharingCache};
 use std::rc::Rc;
 use std::time::{Duration, Instant};
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+
+pub mod inline;
+pub mod measurement;
+pub mod sticky;
+pub mod replaced;
+pub mod filter;
+pub mod layers;
+pub mod animation;
+pub mod hyphenation;
+pub mod fonts;
+pub mod inner_text;
+pub mod translation;
+pub mod geometry;
+pub mod generated_content;
+pub mod math;
+pub mod logical;
+pub mod background;
+pub mod paged;
+pub mod masking;
+pub mod ruby;
+pub mod spatial_navigation;
+pub mod forced_colors;
+pub mod style_sharing;
+pub mod style_structs;
+
+#[cfg(test)]
+mod fuzz;
+
+pub use inline::{PseudoElement, TextFragment};
+pub use measurement::{MeasurementStats, TextMeasurementCache};
+pub use sticky::{ScrollCompositor, StickyConstraint};
+pub use replaced::{ObjectFit, ObjectPosition, ReplacedRects};
+pub use filter::FilterFunction;
+pub use layers::{LayerTree, WillChange};
+pub use hyphenation::Hyphens;
+pub use fonts::{FontFeatures, FontMetrics, FontRegistry, FontStyle, FontSynthesis};
+pub use geometry::{GeometryChange, GeometryObserverId, GeometryObservers};
+pub use generated_content::{ContentItem, CounterState, CounterStyle};
+pub use math::{MathContext, MathExpression, MathType};
+pub use logical::{LogicalSide, PhysicalSide, SideDeclaration, WritingMode};
+pub use background::{BackgroundAttachment, BackgroundBox, BackgroundGeometry};
+pub use paged::{BreakBetween, BreakInside, Page, PageStyle, PagedLayout};
+pub use masking::{ClipPath, FillRule, MaskImage, MaskMode};
+pub use spatial_navigation::NavigationDirection;
+pub use forced_colors::{ForcedColorAdjust, SystemPalette};
+pub use style_sharing::StyleSharingStats;
+pub use style_structs::{BoxStyles, FlexStyles, FontStyles, GridStyles, StyleInterner, StyleMemory};
+pub use animation::{AnimationTimeline, EffectTiming, KeyframeEffect};
 
 /// Represents the computed styles for an element
 /// 
@@ -32,48 +106,31 @@ use std::collections::HashMap;
 pub struct ComputedStyles {
     /// Display property (block, inline, none, etc.)
     pub display: DisplayType,
-    /// Width of the content area
-    pub width: Option<f32>,
-    /// Height of the content area
-    pub height: Option<f32>,
-    /// Margin values (top, right, bottom, left)
-    pub margin: BoxSides,
-    /// Border values (top, right, bottom, left)
-    pub border: BoxSides,
-    /// Padding values (top, right, bottom, left)
-    pub padding: BoxSides,
+    /// Size, margin, border and padding
+    pub box_model: Rc<BoxStyles>,
     /// Background color
     pub background_color: Option<String>,
     /// Text color
     pub color: Option<String>,
-    /// Font size
-    pub font_size: Option<f32>,
-    /// Font family
-    pub font_family: Option<String>,
-    /// Font weight
-    pub font_weight: Option<String>,
+    /// Font properties, inherited
+    pub font: Rc<FontStyles>,
     /// Text alignment
     pub text_align: Option<String>,
+    /// Inline base direction, inherited; `None` is `ltr`
+    pub direction: Option<Direction>,
+    /// How the element's text takes part in bidi reordering
+    pub unicode_bidi: UnicodeBidi,
+    /// Where words may break with a hyphen, inherited; `None` is `manual`
+    pub hyphens: Option<Hyphens>,
+    /// Whether the box is painted, inherited; `None` is `visible`
+    pub visibility: Option<Visibility>,
+    /// Content language from the `lang` attribute, inherited; picks the
+    /// hyphenation dictionary
+    pub lang: Option<String>,
     /// Flexbox properties
-    pub flex_direction: Option<FlexDirection>,
-    pub flex_wrap: Option<FlexWrap>,
-    pub justify_content: Option<JustifyContent>,
-    pub align_items: Option<AlignItems>,
-    pub align_content: Option<AlignContent>,
-    pub flex_grow: Option<f32>,
-    pub flex_shrink: Option<f32>,
-    pub flex_basis: Option<f32>,
-    pub align_self: Option<AlignSelf>,
+    pub flex: Rc<FlexStyles>,
     /// Grid properties
-    pub grid_template_columns: Option<Vec<GridTrack>>,
-    pub grid_template_rows: Option<Vec<GridTrack>>,
-    pub grid_gap: Option<f32>,
-    pub grid_column_gap: Option<f32>,
-    pub grid_row_gap: Option<f32>,
-    pub grid_column_start: Option<i32>,
-    pub grid_column_end: Option<i32>,
-    pub grid_row_start: Option<i32>,
-    pub grid_row_end: Option<i32>,
+    pub grid: Rc<GridStyles>,
     /// Animation properties
     pub transition_duration: Option<f32>,
     pub transition_delay: Option<f32>,
@@ -85,6 +142,102 @@ pub struct ComputedStyles {
     pub animation_direction: Option<AnimationDirection>,
     pub animation_fill_mode: Option<AnimationFillMode>,
     pub animation_play_state: Option<AnimationPlayState>,
+    /// Containment properties
+    pub content_visibility: ContentVisibility,
+    /// Placeholder size (width, height) used while contents are skipped
+    pub contain_intrinsic_size: Option<(f32, f32)>,
+    /// Positioning scheme
+    pub position: Position,
+    /// `top`, `right`, `bottom` and `left` offsets
+    pub inset: Insets,
+    /// Float side
+    pub float: Float,
+    /// Sizing and placement of replaced content in the content box
+    pub object_fit: ObjectFit,
+    pub object_position: ObjectPosition,
+    /// Filter functions applied to the element and its descendants, in
+    /// order
+    pub filter: Vec<FilterFunction>,
+    /// How the element's layer blends with its backdrop
+    pub mix_blend_mode: BlendMode,
+    pub isolation: Isolation,
+    /// Properties the element expects to change, a layer promotion hint
+    pub will_change: WillChange,
+    /// Generated content of a `::before` or `::after` box, with `attr()`
+    /// resolved; empty for `normal` and `none`
+    pub content: Vec<ContentItem>,
+    /// Counters the element creates, increments and sets, with their values
+    pub counter_reset: Vec<(String, i32)>,
+    pub counter_increment: Vec<(String, i32)>,
+    pub counter_set: Vec<(String, i32)>,
+    /// Writing mode, inherited; `None` is `horizontal-tb`
+    pub writing_mode: Option<WritingMode>,
+    /// Box sides set by logical properties, and physical sides set after
+    /// them, in cascade order; mapped once the writing mode and direction
+    /// are known, and empty in computed styles
+    pub pending_sides: Vec<SideDeclaration>,
+    /// URL of the background image
+    pub background_image: Option<String>,
+    /// The box the background is painted in, and the one its image is
+    /// positioned in
+    pub background_clip: BackgroundBox,
+    pub background_origin: BackgroundBox,
+    pub background_attachment: BackgroundAttachment,
+    /// Corner radius of the border box
+    pub border_radius: f32,
+    /// Page breaks before, after and inside the box
+    pub break_before: BreakBetween,
+    pub break_after: BreakBetween,
+    pub break_inside: BreakInside,
+    /// Fewest lines of a paragraph left at the top and bottom of a page,
+    /// inherited; `None` is 2
+    pub widows: Option<u32>,
+    pub orphans: Option<u32>,
+    /// Shape the element and its descendants are clipped to
+    pub clip_path: Option<ClipPath>,
+    /// Image whose alpha or luminance masks the element and its
+    /// descendants
+    pub mask_image: Option<MaskImage>,
+    pub mask_mode: MaskMode,
+    /// Whether forced-colors mode replaces the element's colors (inherited)
+    pub forced_color_adjust: Option<ForcedColorAdjust>,
+}
+
+impl ComputedStyles {
+    /// Get the font properties for writing, copying them if shared
+    pub fn font_mut(&mut self) -> &mut FontStyles {
+        Rc::make_mut(&mut self.font)
+    }
+    
+    /// Get the box model properties for writing, copying them if shared
+    pub fn box_model_mut(&mut self) -> &mut BoxStyles {
+        Rc::make_mut(&mut self.box_model)
+    }
+    
+    /// Get the flexbox properties for writing, copying them if shared
+    pub fn flex_mut(&mut self) -> &mut FlexStyles {
+        Rc::make_mut(&mut self.flex)
+    }
+    
+    /// Get the grid properties for writing, copying them if shared
+    pub fn grid_mut(&mut self) -> &mut GridStyles {
+        Rc::make_mut(&mut self.grid)
+    }
+    
+    /// Check whether the element composites its descendants into a group
+    /// of their own before blending the group with its backdrop
+    /// 
+    /// Blend modes inside the group only see the group's contents, not
+    /// what is painted behind the element.
+    pub fn creates_isolated_group(&self) -> bool {
+        self.isolation == Isolation::Isolate
+            || self.mix_blend_mode != BlendMode::Normal
+            || !self.filter.is_empty()
+            || self.clip_path.is_some()
+            || self.mask_image.is_some()
+            || matches!(self.position, Position::Fixed | Position::Sticky)
+            || self.will_change.creates_stacking_context()
+    }
 }
 
 /// Represents the display type of an element
@@ -100,10 +253,152 @@ pub enum DisplayType {
     Flex,
     /// Grid container
     Grid,
+    /// No box of its own; children are laid out as children of the parent
+    Contents,
     /// Hidden element
     None,
 }
 
+/// Whether an element renders its contents (`content-visibility`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ContentVisibility {
+    /// Contents are always laid out and painted
+    #[default]
+    Visible,
+    /// Contents are skipped while the element is far from the viewport
+    Auto,
+    /// Contents are always skipped
+    Hidden,
+}
+
+/// Whether a box is painted (`visibility`)
+///
+/// Invisible boxes still take up space, and their descendants can be made
+/// visible again.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Visibility {
+    #[default]
+    Visible,
+    Hidden,
+    /// Like `hidden`, except for table rows and columns, which are removed
+    Collapse,
+}
+
+impl Visibility {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "visible" => Some(Visibility::Visible),
+            "hidden" => Some(Visibility::Hidden),
+            "collapse" => Some(Visibility::Collapse),
+            _ => None,
+        }
+    }
+}
+
+/// Positioning scheme (`position`)
+/// 
+/// Only `sticky` changes where a box is painted so far; the other schemes
+/// lay out in flow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Position {
+    #[default]
+    Static,
+    Relative,
+    Absolute,
+    Fixed,
+    /// In flow, but kept within the scrollport by its insets while its
+    /// containing block is visible
+    Sticky,
+}
+
+/// Float side (`float`)
+/// 
+/// Only `::first-letter` boxes float so far, for drop caps; floated
+/// elements lay out in flow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Float {
+    #[default]
+    None,
+    Left,
+    Right,
+}
+
+/// How a layer's colors combine with its backdrop (`mix-blend-mode`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    #[default]
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+}
+
+impl BlendMode {
+    /// Parse a `mix-blend-mode` keyword
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "normal" => Some(BlendMode::Normal),
+            "multiply" => Some(BlendMode::Multiply),
+            "screen" => Some(BlendMode::Screen),
+            "overlay" => Some(BlendMode::Overlay),
+            "darken" => Some(BlendMode::Darken),
+            "lighten" => Some(BlendMode::Lighten),
+            _ => None,
+        }
+    }
+}
+
+/// Whether an element starts a new compositing group (`isolation`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Isolation {
+    #[default]
+    Auto,
+    Isolate,
+}
+
+/// How an element's text takes part in bidi reordering (`unicode-bidi`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnicodeBidi {
+    #[default]
+    Normal,
+    Embed,
+    Isolate,
+    BidiOverride,
+    IsolateOverride,
+    /// The base direction comes from the text rather than `direction`
+    Plaintext,
+}
+
+impl UnicodeBidi {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "normal" => Some(UnicodeBidi::Normal),
+            "embed" => Some(UnicodeBidi::Embed),
+            "isolate" => Some(UnicodeBidi::Isolate),
+            "bidi-override" => Some(UnicodeBidi::BidiOverride),
+            "isolate-override" => Some(UnicodeBidi::IsolateOverride),
+            "plaintext" => Some(UnicodeBidi::Plaintext),
+            _ => None,
+        }
+    }
+
+    /// Check whether every character is forced to the element's direction
+    pub fn is_override(&self) -> bool {
+        matches!(self, UnicodeBidi::BidiOverride | UnicodeBidi::IsolateOverride)
+    }
+}
+
+/// Offsets of a positioned box; `None` is `auto`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Insets {
+    pub top: Option<f32>,
+    pub right: Option<f32>,
+    pub bottom: Option<f32>,
+    pub left: Option<f32>,
+}
+
 /// Flexbox direction
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum FlexDirection {
@@ -273,6 +568,8 @@ pub struct LayoutBox {
     pub margin: Dimensions,
     /// Child layout boxes
     pub children: Vec<LayoutBox>,
+    /// Line fragments of a text box, relative to its content area
+    pub fragments: Vec<TextFragment>,
     /// Animation state
     pub animation_state: AnimationState,
 }
@@ -298,37 +595,18 @@ impl Default for ComputedStyles {
     fn default() -> Self {
         ComputedStyles {
             display: DisplayType::Block,
-            width: None,
-            height: None,
-            margin: BoxSides::new(0.0),
-            border: BoxSides::new(0.0),
-            padding: BoxSides::new(0.0),
+            box_model: BoxStyles::initial(),
+            font: Rc::new(FontStyles {
+                size: Some(16.0),
+                family: Some("serif".to_string()),
+                weight: Some("normal".to_string()),
+                ..FontStyles::default()
+            }),
+            flex: FlexStyles::initial(),
+            grid: GridStyles::initial(),
             background_color: None,
             color: Some("black".to_string()),
-            font_size: Some(16.0),
-            font_family: Some("serif".to_string()),
-            font_weight: Some("normal".to_string()),
             text_align: Some("left".to_string()),
-            // Flexbox properties
-            flex_direction: None,
-            flex_wrap: None,
-            justify_content: None,
-            align_items: None,
-            align_content: None,
-            flex_grow: None,
-            flex_shrink: None,
-            flex_basis: None,
-            align_self: None,
-            // Grid properties
-            grid_template_columns: None,
-            grid_template_rows: None,
-            grid_gap: None,
-            grid_column_gap: None,
-            grid_row_gap: None,
-            grid_column_start: None,
-            grid_column_end: None,
-            grid_row_start: None,
-            grid_row_end: None,
             // Animation properties
             transition_duration: None,
             transition_delay: None,
@@ -340,6 +618,44 @@ impl Default for ComputedStyles {
             animation_direction: None,
             animation_fill_mode: None,
             animation_play_state: None,
+            // Containment properties
+            content_visibility: ContentVisibility::Visible,
+            contain_intrinsic_size: None,
+            // Positioning properties
+            position: Position::Static,
+            inset: Insets::default(),
+            float: Float::None,
+            object_fit: ObjectFit::Fill,
+            object_position: ObjectPosition::default(),
+            filter: Vec::new(),
+            mix_blend_mode: BlendMode::Normal,
+            isolation: Isolation::Auto,
+            will_change: WillChange::default(),
+            content: Vec::new(),
+            counter_reset: Vec::new(),
+            counter_increment: Vec::new(),
+            counter_set: Vec::new(),
+            writing_mode: None,
+            pending_sides: Vec::new(),
+            background_image: None,
+            background_clip: BackgroundBox::BorderBox,
+            background_origin: BackgroundBox::PaddingBox,
+            background_attachment: BackgroundAttachment::Scroll,
+            border_radius: 0.0,
+            break_before: BreakBetween::Auto,
+            break_after: BreakBetween::Auto,
+            break_inside: BreakInside::Auto,
+            widows: None,
+            orphans: None,
+            clip_path: None,
+            mask_image: None,
+            mask_mode: MaskMode::Alpha,
+            forced_color_adjust: None,
+            direction: None,
+            unicode_bidi: UnicodeBidi::Normal,
+            hyphens: None,
+            visibility: None,
+            lang: None,
         }
     }
 }
@@ -388,6 +704,34 @@ impl Dimensions {
     }
 }
 
+/// Intrinsic (width, height) of a form control in pixels
+/// 
+/// Mirrors the default sizes of native controls. Text fields honour the
+/// `size` attribute (in characters, 8px each).
+fn intrinsic_control_size(element: &Node) -> Option<(f32, f32)> {
+    match element.form_control_type()? {
+        FormControlType::Text | FormControlType::Password => {
+            let size = element
+                .get_attribute("size")
+                .and_then(|size| size.parse::<f32>().ok())
+                .unwrap_or(20.0);
+            Some((size * 8.0, 20.0))
+        }
+        FormControlType::Checkbox | FormControlType::Radio => Some((13.0, 13.0)),
+        FormControlType::Submit | FormControlType::Reset | FormControlType::Button => {
+            let label = if element.tag_name() == Some("button") {
+                element.text_content().trim().to_string()
+            } else {
+                element.value()
+            };
+            Some(((label.chars().count().max(6) as f32) * 8.0 + 16.0, 22.0))
+        }
+        FormControlType::Select => Some((120.0, 20.0)),
+        FormControlType::TextArea => Some((160.0, 36.0)),
+        FormControlType::Hidden => None,
+    }
+}
+
 /// A style matcher that matches CSS selectors against DOM elements
 /// 
 /// This struct handles the process of matching CSS selectors to DOM elements
@@ -395,32 +739,395 @@ impl Dimensions {
 pub struct StyleMatcher {
     /// The stylesheet containing CSS rules
     stylesheet: Stylesheet,
+    /// Rules of the user origin, such as the user's own `user.css`
+    user_stylesheet: Stylesheet,
+    /// Environment `@media` rules are evaluated against
+    media_features: MediaFeatures,
+    /// Whether any rule targets `::first-line`, `::first-letter` or
+    /// `::backdrop`
+    has_pseudo_element_rules: bool,
+    /// Declarations of running script animations, by node id
+    animated_declarations: HashMap<u64, Vec<css_parser::CSSDeclaration>>,
+    /// Indices of the stylesheet's rules that matched an element, for
+    /// coverage reports
+    matched_rules: RefCell<HashSet<usize>>,
+    /// Faces whose metrics `ex` and `ch` are measured from
+    font_registry: RefCell<FontRegistry>,
+    /// Fonts the element being cascaded resolves relative lengths against
+    font_context: Cell<FontContext>,
+    /// `:has()` selectors of the stylesheets, which style sharing checks
+    relational_selectors: Vec<Selector>,
+    /// Styles siblings can reuse during a layout's style pass
+    style_sharing: RefCell<StyleSharingCache>,
+    /// Style structs seen during a layout's style pass
+    style_interner: RefCell<StyleInterner>,
+    /// Smallest font size in pixels text is shown at, or 0 for none
+    minimum_font_size: f32,
+}
+
+/// Font sizes and metrics relative lengths resolve against while an
+/// element's styles are cascaded
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FontContext {
+    /// The parent's font, which `font-size` refers to
+    parent_size: f32,
+    parent_metrics: FontMetrics,
+    /// The element's own font, which other properties refer to
+    size: f32,
+    metrics: FontMetrics,
+}
+
+impl FontContext {
+    fn new(size: f32, metrics: FontMetrics) -> Self {
+        FontContext { parent_size: size, parent_metrics: metrics, size, metrics }
+    }
 }
 
 impl StyleMatcher {
     /// Create a new style matcher with the given stylesheet
     pub fn new(stylesheet: Stylesheet) -> Self {
-        StyleMatcher { stylesheet }
+        let has_pseudo_element_rules = has_pseudo_element_rules(&stylesheet);
+        let relational_selectors = style_sharing::relational_selectors(&[&stylesheet]);
+        StyleMatcher {
+            stylesheet,
+            user_stylesheet: Stylesheet { rules: vec![], page_rules: vec![], source_url: None },
+            media_features: MediaFeatures::default(),
+            has_pseudo_element_rules,
+            animated_declarations: HashMap::new(),
+            matched_rules: RefCell::new(HashSet::new()),
+            font_registry: RefCell::new(FontRegistry::default()),
+            font_context: Cell::new(FontContext::new(DEFAULT_FONT_SIZE, FontRegistry::default().metrics(""))),
+            relational_selectors,
+            style_sharing: RefCell::new(StyleSharingCache::default()),
+            style_interner: RefCell::new(StyleInterner::default()),
+            minimum_font_size: 0.0,
+        }
+    }
+    
+    /// Replace the faces `ex` and `ch` are measured from
+    pub fn set_font_registry(&self, registry: FontRegistry) {
+        *self.font_registry.borrow_mut() = registry;
+    }
+    
+    /// Indices of the stylesheet's rules that matched an element since the
+    /// matcher was created
+    pub fn matched_rules(&self) -> HashSet<usize> {
+        self.matched_rules.borrow().clone()
+    }
+    
+    /// Set the environment `@media` rules are evaluated against
+    pub fn set_media_features(&mut self, features: MediaFeatures) {
+        self.media_features = features;
+    }
+    
+    pub fn media_features(&self) -> &MediaFeatures {
+        &self.media_features
+    }
+    
+    /// Set the smallest font size in pixels elements are shown at
+    ///
+    /// Computed font sizes below it are raised to it after the element's
+    /// other lengths have resolved, so only text grows, not the boxes
+    /// around it. Children inherit the raised size. 0 turns the minimum off.
+    pub fn set_minimum_font_size(&mut self, size: f32) {
+        self.minimum_font_size = size.max(0.0);
+    }
+    
+    /// The palette system color keywords and forced-colors mode use
+    pub fn system_palette(&self) -> SystemPalette {
+        SystemPalette::for_scheme(self.media_features.color_scheme)
+    }
+    
+    /// The `@page` rules of the user and page stylesheets, in cascade order
+    pub fn page_rules(&self) -> Vec<&PageRule> {
+        self.user_stylesheet.page_rules.iter().chain(&self.stylesheet.page_rules).collect()
+    }
+    
+    /// Set the values script animations give nodes, see
+    /// `AnimationTimeline::animated_styles`
+    /// 
+    /// They apply above the `style` attribute and below important user
+    /// declarations.
+    pub fn set_animated_styles(&mut self, styles: HashMap<u64, Vec<(String, String)>>) {
+        self.animated_declarations = styles
+            .into_iter()
+            .map(|(node_id, values)| {
+                let text: Vec<String> = values.iter().map(|(property, value)| format!("{}: {}", property, value)).collect();
+                (node_id, css_parser::parse_inline_style(&text.join("; ")))
+            })
+            .collect();
+    }
+    
+    /// Set the stylesheet of the user origin
+    /// 
+    /// Its normal declarations apply below the page's rules and its
+    /// `!important` ones above everything the page sets, including the
+    /// `style` attribute.
+    pub fn set_user_stylesheet(&mut self, stylesheet: Stylesheet) {
+        self.has_pseudo_element_rules = has_pseudo_element_rules(&self.stylesheet) || has_pseudo_element_rules(&stylesheet);
+        self.relational_selectors = style_sharing::relational_selectors(&[&stylesheet, &self.stylesheet]);
+        self.user_stylesheet = stylesheet;
+    }
+    
+    /// Compute the styles of a `::first-line`, `::first-letter`,
+    /// `::backdrop`, `::before` or `::after` pseudo-element of an element
+    /// 
+    /// `parent_styles` are the styles the pseudo-element inherits from: the
+    /// element's own, its first line's for `::first-letter`, or the default
+    /// backdrop's for `::backdrop`. `attr()` reads the attributes of
+    /// `element`, the originating element. Returns
+    /// `None` if no rule targets the pseudo-element. `::first-line` only
+    /// takes font, color and background properties.
+    pub fn compute_pseudo_element_styles(&self, element: &Rc<Node>, pseudo: PseudoElement, parent_styles: &ComputedStyles) -> Option<ComputedStyles> {
+        if !self.has_pseudo_element_rules {
+            return None;
+        }
+        let mut styles = ComputedStyles {
+            display: DisplayType::Inline,
+            box_model: BoxStyles::initial(),
+            font: Rc::new(FontStyles {
+                style: None,
+                features: FontFeatures::default(),
+                synthesis: None,
+                ..(*parent_styles.font).clone()
+            }),
+            position: Position::Static,
+            inset: Insets::default(),
+            float: Float::None,
+            object_fit: ObjectFit::Fill,
+            object_position: ObjectPosition::default(),
+            filter: Vec::new(),
+            mix_blend_mode: BlendMode::Normal,
+            isolation: Isolation::Auto,
+            will_change: WillChange::default(),
+            content: Vec::new(),
+            counter_reset: Vec::new(),
+            counter_increment: Vec::new(),
+            counter_set: Vec::new(),
+            writing_mode: None,
+            pending_sides: Vec::new(),
+            background_image: None,
+            background_clip: BackgroundBox::BorderBox,
+            background_origin: BackgroundBox::PaddingBox,
+            background_attachment: BackgroundAttachment::Scroll,
+            border_radius: 0.0,
+            break_before: BreakBetween::Auto,
+            break_after: BreakBetween::Auto,
+            break_inside: BreakInside::Auto,
+            widows: None,
+            orphans: None,
+            clip_path: None,
+            mask_image: None,
+            mask_mode: MaskMode::Alpha,
+            forced_color_adjust: None,
+            direction: None,
+            unicode_bidi: UnicodeBidi::Normal,
+            hyphens: None,
+            visibility: None,
+            lang: None,
+            content_visibility: ContentVisibility::Visible,
+            contain_intrinsic_size: None,
+            ..parent_styles.clone()
+        };
+        let mut matched = false;
+        for rule in self.user_stylesheet.rules.iter().chain(&self.stylesheet.rules) {
+            if !rule.applies_to(&self.media_features) {
+                continue;
+            }
+            for selector in &rule.selectors {
+                if self.matches_pseudo_element_selector(selector, element, pseudo) {
+                    self.apply_rule(&mut styles, rule, element);
+                    matched = true;
+                }
+            }
+        }
+        if !matched {
+            return None;
+        }
+        logical::resolve_sides(&mut styles);
+        
+        match pseudo {
+            PseudoElement::FirstLetter | PseudoElement::Backdrop | PseudoElement::Before | PseudoElement::After => Some(styles),
+            PseudoElement::FirstLine => Some(ComputedStyles {
+                color: styles.color,
+                background_color: styles.background_color,
+                font: Rc::new(FontStyles {
+                    size: styles.font.size,
+                    family: styles.font.family.clone(),
+                    weight: styles.font.weight.clone(),
+                    ..(*parent_styles.font).clone()
+                }),
+                ..parent_styles.clone()
+            }),
+        }
+    }
+    
+    /// Check if a selector's subject is `pseudo` on this element
+    fn matches_pseudo_element_selector(&self, selector: &Selector, element: &Rc<Node>, pseudo: PseudoElement) -> bool {
+        match selector {
+            Selector::PseudoElement(_) | Selector::PseudoClass(_) => targets_pseudo_element(selector, pseudo),
+            Selector::Compound(selectors) => match selectors.split_last() {
+                Some((last, rest)) => {
+                    targets_pseudo_element(last, pseudo)
+                        && rest.iter().all(|selector| self.matches_selector(selector, element))
+                }
+                None => false,
+            },
+            Selector::Descendant(ancestor, descendant) => {
+                self.matches_pseudo_element_selector(descendant, element, pseudo)
+                    && self.has_ancestor_matching(element, ancestor)
+            }
+            Selector::Child(parent, child) => {
+                self.matches_pseudo_element_selector(child, element, pseudo)
+                    && self.has_parent_matching(element, parent)
+            }
+            Selector::Group(selectors) => {
+                selectors.iter().any(|selector| self.matches_pseudo_element_selector(selector, element, pseudo))
+            }
+            _ => false,
+        }
     }
     
     /// Compute styles for a DOM element
     /// 
     /// This method matches CSS selectors against the element and computes
-    /// the final styles based on specificity and inheritance.
+    /// the final styles based on specificity and inheritance. Presentational
+    /// hints apply below the stylesheet rules and the `style` attribute
+    /// above them; the user stylesheet brackets the page's styles.
     pub fn compute_styles(&self, element: &Rc<Node>) -> ComputedStyles {
+        if let Some(key) = self.sharing_key(element) {
+            if let Some((styles, context)) = self.style_sharing.borrow_mut().lookup(element.id, &key) {
+                self.font_context.set(context);
+                return styles;
+            }
+        }
+        let parent_styles = element.parent.borrow().upgrade().map(|parent| self.compute_styles(&parent));
+        let parent_size = parent_styles.as_ref().and_then(|styles| styles.font.size).unwrap_or(DEFAULT_FONT_SIZE);
+        let parent_family = parent_styles.as_ref().and_then(|styles| styles.font.family.clone()).unwrap_or_default();
+        let mut context = FontContext::new(parent_size, self.font_registry.borrow().metrics(&parent_family));
+        self.font_context.set(context);
+        let mut styles = self.cascade(element);
+        
+        // `em`, `ex` and `ch` outside `font-size` refer to the element's own
+        // font, which is only known once the cascade has picked it
+        context.size = styles.font.size.unwrap_or(parent_size);
+        context.metrics = self.font_registry.borrow().metrics(styles.font.family.as_deref().unwrap_or(&parent_family));
+        if context != self.font_context.get() {
+            self.font_context.set(context);
+            styles = self.cascade(element);
+        }
+        
+        styles.lang = element.get_attribute("lang");
+        
+        // Apply inherited styles
+        let is_root = element.tag_name().is_some() && element.parent.borrow().upgrade().is_none_or(|parent| parent.tag_name().is_none());
+        self.apply_inherited_styles(&mut styles, element, parent_styles);
+        logical::resolve_sides(&mut styles);
+        if styles.font.size.unwrap_or(DEFAULT_FONT_SIZE) < self.minimum_font_size {
+            styles.font_mut().size = Some(self.minimum_font_size);
+        }
+        if self.media_features.forced_colors {
+            forced_colors::force_colors(&mut styles, element, &self.system_palette(), is_root);
+        }
+        
+        if self.style_sharing.borrow().is_active() {
+            self.style_interner.borrow_mut().intern(&mut styles);
+            if element.tag_name().is_some() {
+                let key = self.sharing_key(element);
+                self.style_sharing.borrow_mut().insert(element.id, key, &styles, self.font_context.get());
+            }
+        }
+        
+        styles
+    }
+    
+    /// Get the key an element shares styles under in the current style
+    /// pass, or `None` if it cannot share or its parent is not styled yet
+    fn sharing_key(&self, element: &Rc<Node>) -> Option<SharingKey> {
+        let cache = self.style_sharing.borrow();
+        if !cache.is_active() || !style_sharing::can_share(element) || self.animated_declarations.contains_key(&element.id) {
+            return None;
+        }
+        let parent = element.parent.borrow().upgrade()?;
+        let parent_group = if parent.tag_name().is_some() { cache.group(parent.id)? } else { parent.id };
+        Some(SharingKey::new(element, parent_group, &self.relational_selectors))
+    }
+    
+    /// Start a style pass, in which siblings share computed styles and
+    /// equal style structs are interned
+    /// 
+    /// The DOM must not change until `end_style_pass`.
+    pub fn begin_style_pass(&self) {
+        self.style_sharing.borrow_mut().begin();
+        *self.style_interner.borrow_mut() = StyleInterner::default();
+    }
+    
+    /// End the style pass started by `begin_style_pass`
+    pub fn end_style_pass(&self) {
+        self.style_sharing.borrow_mut().end();
+        *self.style_interner.borrow_mut() = StyleInterner::default();
+    }
+    
+    /// Get how often elements shared styles in the last style pass
+    pub fn style_sharing_stats(&self) -> StyleSharingStats {
+        self.style_sharing.borrow().stats()
+    }
+    
+    /// Cascade the declarations that apply to an element over its default
+    /// styles, in the current font context
+    fn cascade(&self, element: &Rc<Node>) -> ComputedStyles {
         let mut styles = self.get_default_styles(element);
         
+        // Apply normal user declarations below everything the page sets
+        self.apply_user_declarations(&mut styles, element, false);
+        
+        // Apply presentational hints from legacy attributes
+        for declaration in &css_parser::presentational_hints(element) {
+            self.apply_element_declaration(&mut styles, declaration, element);
+        }
+        
         // Apply styles from matching rules
-        for rule in &self.stylesheet.rules {
+        for (index, rule) in self.stylesheet.rules.iter().enumerate() {
+            if !rule.applies_to(&self.media_features) {
+                continue;
+            }
             for selector in &rule.selectors {
                 if self.matches_selector(selector, element) {
-                    self.apply_rule(&mut styles, rule);
+                    self.matched_rules.borrow_mut().insert(index);
+                    self.apply_rule_through(&mut styles, rule, selector, element);
                 }
             }
         }
         
-        // Apply inherited styles
-        self.apply_inherited_styles(&mut styles, element);
+        // Apply the style attribute
+        if let Some(style) = element.get_attribute("style") {
+            for declaration in &css_parser::parse_inline_style(&style) {
+                self.apply_element_declaration(&mut styles, declaration, element);
+            }
+        }
+        
+        // Apply the values of script animations
+        for declaration in self.animated_declarations.get(&element.id).into_iter().flatten() {
+            self.apply_element_declaration(&mut styles, declaration, element);
+        }
+        
+        // Important user declarations override the page
+        self.apply_user_declarations(&mut styles, element, true);
+        
+        // So do the important user agent styles of a fullscreen element,
+        // which fills the viewport
+        if element.is_fullscreen() {
+            styles.box_model_mut().margin = BoxSides::new(0.0);
+            styles.box_model_mut().width = None;
+            styles.box_model_mut().height = None;
+            styles.box_model_mut().width_expression = None;
+            styles.box_model_mut().height_expression = None;
+        }
+        
+        // Content of a closed <details> and hidden inputs are never rendered
+        if css_parser::is_closed_details_content(element) || element.form_control_type() == Some(FormControlType::Hidden) {
+            styles.display = DisplayType::None;
+        }
         
         styles
     }
@@ -432,7 +1139,9 @@ impl StyleMatcher {
             dom::NodeType::Element { tag_name, .. } => {
                 match tag_name.as_str() {
                     "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "p" | "div" | "ul" | "ol" | "li" | "body" | "html" => DisplayType::Block,
-                    "span" | "a" | "em" | "strong" | "code" => DisplayType::Inline,
+                    "span" | "a" | "em" | "strong" | "code" | "ruby" | "rb" | "rt" => DisplayType::Inline,
+                    "rp" => DisplayType::None,
+                    "input" | "select" | "button" | "textarea" | "video" | "audio" => DisplayType::InlineBlock,
                     _ => DisplayType::Block, // Default to block for unknown elements
                 }
             },
@@ -440,39 +1149,62 @@ impl StyleMatcher {
             _ => DisplayType::Block,
         };
         
+        // The `hidden` attribute, closed dialogs, hidden popovers and media
+        // without a box map to display: none
+        let closed_dialog = element.is_dialog() && !element.has_attribute("open");
+        let hidden_popover = element.is_popover() && !element.is_popover_open() && !(element.is_dialog() && element.has_attribute("open"));
+        let boxless_media = element.media_kind().is_some() && replaced::media_box_size(element).is_none();
+        let default_display = if element.has_attribute("hidden") || closed_dialog || hidden_popover || boxless_media {
+            DisplayType::None
+        } else {
+            default_display
+        };
+        
+        // Form controls have an intrinsic size and a native border; dialogs
+        // and popovers have a medium border, padding and a white background
+        let control_size = intrinsic_control_size(element);
+        let media_size = replaced::media_box_size(element);
+        let border = if control_size.is_some() {
+            1.0
+        } else if element.is_dialog() || element.is_popover() {
+            DIALOG_BORDER
+        } else {
+            0.0
+        };
+        let padding = if element.is_dialog() {
+            DIALOG_PADDING
+        } else if element.is_popover() {
+            POPOVER_PADDING
+        } else {
+            0.0
+        };
+        
         ComputedStyles {
             display: default_display,
-            width: None,
-            height: None,
-            margin: BoxSides::new(0.0),
-            border: BoxSides::new(0.0),
-            padding: BoxSides::new(0.0),
-            background_color: None,
+            box_model: Rc::new(BoxStyles {
+                width: control_size.or(media_size).map(|(width, _)| width),
+                height: control_size.or(media_size).map(|(_, height)| height),
+                border: BoxSides::new(border),
+                padding: BoxSides::new(padding),
+                ..BoxStyles::default()
+            }),
+            // Font size is inherited, and ruby annotations are set smaller
+            // than their base text
+            font: Rc::new(FontStyles {
+                size: Some(if ruby::is_annotation(element) {
+                    self.font_context.get().parent_size * ruby::ANNOTATION_FONT_SCALE
+                } else {
+                    self.font_context.get().parent_size
+                }),
+                family: Some("serif".to_string()),
+                weight: Some("normal".to_string()),
+                ..FontStyles::default()
+            }),
+            flex: FlexStyles::initial(),
+            grid: GridStyles::initial(),
+            background_color: (element.is_dialog() || element.is_popover()).then(|| "white".to_string()),
             color: Some("black".to_string()),
-            font_size: Some(16.0),
-            font_family: Some("serif".to_string()),
-            font_weight: Some("normal".to_string()),
             text_align: Some("left".to_string()),
-            // Flexbox properties
-            flex_direction: None,
-            flex_wrap: None,
-            justify_content: None,
-            align_items: None,
-            align_content: None,
-            flex_grow: None,
-            flex_shrink: None,
-            flex_basis: None,
-            align_self: None,
-            // Grid properties
-            grid_template_columns: None,
-            grid_template_rows: None,
-            grid_gap: None,
-            grid_column_gap: None,
-            grid_row_gap: None,
-            grid_column_start: None,
-            grid_column_end: None,
-            grid_row_start: None,
-            grid_row_end: None,
             // Animation properties
             transition_duration: None,
             transition_delay: None,
@@ -484,6 +1216,43 @@ impl StyleMatcher {
             animation_direction: None,
             animation_fill_mode: None,
             animation_play_state: None,
+            // Containment properties
+            content_visibility: ContentVisibility::Visible,
+            contain_intrinsic_size: None,
+            position: Position::Static,
+            inset: Insets::default(),
+            float: Float::None,
+            object_fit: ObjectFit::Fill,
+            object_position: ObjectPosition::default(),
+            filter: Vec::new(),
+            mix_blend_mode: BlendMode::Normal,
+            isolation: Isolation::Auto,
+            will_change: WillChange::default(),
+            content: Vec::new(),
+            counter_reset: Vec::new(),
+            counter_increment: Vec::new(),
+            counter_set: Vec::new(),
+            writing_mode: None,
+            pending_sides: Vec::new(),
+            background_image: None,
+            background_clip: BackgroundBox::BorderBox,
+            background_origin: BackgroundBox::PaddingBox,
+            background_attachment: BackgroundAttachment::Scroll,
+            border_radius: 0.0,
+            break_before: BreakBetween::Auto,
+            break_after: BreakBetween::Auto,
+            break_inside: BreakInside::Auto,
+            widows: None,
+            orphans: None,
+            clip_path: None,
+            mask_image: None,
+            mask_mode: MaskMode::Alpha,
+            forced_color_adjust: None,
+            direction: None,
+            unicode_bidi: UnicodeBidi::Normal,
+            hyphens: None,
+            visibility: None,
+            lang: None,
         }
     }
     
@@ -499,20 +1268,12 @@ impl StyleMatcher {
                 }
             }
             Selector::Class(class_name) => {
-                if let NodeType::Element { attributes, .. } = &element.node_type {
-                    attributes.get("class").map_or(false, |class_attr| {
-                        class_attr.split_whitespace().any(|c| c == class_name)
-                    })
-                } else {
-                    false
-                }
+                element.get_attribute("class").is_some_and(|class_attr| {
+                    class_attr.split_whitespace().any(|c| c == class_name)
+                })
             }
             Selector::Id(id_name) => {
-                if let NodeType::Element { attributes, .. } = &element.node_type {
-                    attributes.get("id").map_or(false, |id_attr| id_attr == id_name)
-                } else {
-                    false
-                }
+                element.get_attribute("id").is_some_and(|id_attr| &id_attr == id_name)
             }
             Selector::Descendant(ancestor, descendant) => {
                 self.matches_selector(descendant, element) && 
@@ -522,6 +1283,14 @@ impl StyleMatcher {
                 self.matches_selector(child, element) && 
                 self.has_parent_matching(element, parent)
             }
+            Selector::PseudoClass(name) => css_parser::pseudo_class_matches(name, element),
+            Selector::Compound(selectors) => {
+                selectors.iter().all(|selector| self.matches_selector(selector, element))
+            }
+            Selector::Group(selectors) => {
+                selectors.iter().any(|selector| self.matches_selector(selector, element))
+            }
+            Selector::Has(relative) => css_parser::has_matches(relative, element),
             _ => false, // Other combinators not implemented yet
         }
     }
@@ -545,22 +1314,142 @@ impl StyleMatcher {
         false
     }
     
+    /// Apply the normal or `!important` declarations of matching user rules
+    fn apply_user_declarations(&self, styles: &mut ComputedStyles, element: &Rc<Node>, important: bool) {
+        for rule in &self.user_stylesheet.rules {
+            if !rule.applies_to(&self.media_features) {
+                continue;
+            }
+            let matched: Vec<&Selector> = rule.selectors.iter().filter(|selector| self.matches_selector(selector, element)).collect();
+            for declaration in rule.declarations.iter().filter(|declaration| declaration.important == important) {
+                if matched.iter().any(|selector| css_parser::declaration_allowed_through(selector, declaration)) {
+                    self.apply_element_declaration(styles, declaration, element);
+                }
+            }
+        }
+    }
+    
     /// Apply a CSS rule to the computed styles
-    fn apply_rule(&self, styles: &mut ComputedStyles, rule: &css_parser::CSSRule) {
+    fn apply_rule(&self, styles: &mut ComputedStyles, rule: &css_parser::CSSRule, element: &Node) {
         for declaration in &rule.declarations {
-            self.apply_declaration(styles, declaration);
+            self.apply_element_declaration(styles, declaration, element);
+        }
+    }
+    
+    /// Apply the declarations of a rule that matched through `selector`
+    /// 
+    /// Rules matching through `:visited` only set `color`.
+    fn apply_rule_through(&self, styles: &mut ComputedStyles, rule: &css_parser::CSSRule, selector: &Selector, element: &Node) {
+        for declaration in rule.declarations.iter().filter(|declaration| css_parser::declaration_allowed_through(selector, declaration)) {
+            self.apply_element_declaration(styles, declaration, element);
+        }
+    }
+    
+    /// Apply a declaration for `element`, resolving its `attr()` values
+    /// against the element's attributes first
+    /// 
+    /// A declaration whose `attr()` has neither a usable attribute nor a
+    /// fallback is invalid at computed-value time and is dropped.
+    fn apply_element_declaration(&self, styles: &mut ComputedStyles, declaration: &css_parser::CSSDeclaration, element: &Node) {
+        if declaration.property == "content" {
+            if let Some(items) = generated_content::parse_content(&declaration.value.to_css_text()) {
+                styles.content = generated_content::resolve_attrs(items, element);
+            }
+            return;
+        }
+        let Some(declaration) = generated_content::substitute_attr(declaration, element) else {
+            return;
+        };
+        match MathExpression::parse(&declaration.value.to_css_text()) {
+            Some((expression, kind)) => self.apply_math_declaration(styles, &declaration, expression, kind),
+            None => self.apply_declaration(styles, &declaration),
+        }
+    }
+    
+    /// Apply a declaration whose value is a math function
+    /// 
+    /// Widths and heights with percentages keep their expression until
+    /// layout knows the containing block. Other values are resolved now
+    /// and applied as pixels or numbers; `em` in `font-size` refers to the
+    /// parent's font, elsewhere to the element's. Values that cannot be
+    /// resolved, such as percentages of other properties, are ignored.
+    fn apply_math_declaration(&self, styles: &mut ComputedStyles, declaration: &css_parser::CSSDeclaration, expression: MathExpression, kind: MathType) {
+        if expression.has_percentage() {
+            match declaration.property.as_str() {
+                "width" => {
+                    styles.box_model_mut().width = None;
+                    styles.box_model_mut().width_expression = Some(expression);
+                }
+                "height" => {
+                    styles.box_model_mut().height = None;
+                    styles.box_model_mut().height_expression = Some(expression);
+                }
+                _ => {}
+            }
+            return;
+        }
+        let font = self.font_context.get();
+        let context = match declaration.property.as_str() {
+            "font-size" => self.math_context(FontContext::new(font.parent_size, font.parent_metrics), None),
+            _ => self.math_context(font, None),
+        };
+        let Some(value) = expression.resolve(&context) else {
+            return;
+        };
+        let value = match kind {
+            MathType::Number => CSSValue::Number(value),
+            MathType::Length => CSSValue::Dimension(value, "px".to_string()),
+        };
+        self.apply_declaration(styles, &css_parser::CSSDeclaration { value, ..declaration.clone() });
+    }
+    
+    /// Get the context math functions are resolved in
+    fn math_context(&self, font: FontContext, percentage_basis: Option<f32>) -> MathContext {
+        MathContext {
+            font_size: font.size,
+            root_font_size: DEFAULT_FONT_SIZE,
+            x_height: font.size * font.metrics.x_height,
+            zero_advance: font.size * font.metrics.zero_advance,
+            viewport_width: self.media_features.viewport_width,
+            viewport_height: self.media_features.viewport_height,
+            percentage_basis,
+        }
+    }
+    
+    /// Resolve the width and height expressions of styles against a
+    /// containing block, giving their used values
+    fn resolve_used_sizes(&self, styles: &mut ComputedStyles, containing_block: Dimensions) {
+        let font_size = styles.font.size.unwrap_or(DEFAULT_FONT_SIZE);
+        let font = FontContext::new(font_size, self.font_registry.borrow().metrics(styles.font.family.as_deref().unwrap_or_default()));
+        if let Some(expression) = &styles.box_model.width_expression {
+            styles.box_model_mut().width = expression.resolve(&self.math_context(font, Some(containing_block.width))).map(|width| width.max(0.0));
+        }
+        if let Some(expression) = &styles.box_model.height_expression {
+            styles.box_model_mut().height = expression.resolve(&self.math_context(font, Some(containing_block.height))).map(|height| height.max(0.0));
         }
     }
     
     /// Apply a CSS declaration to the computed styles
     fn apply_declaration(&self, styles: &mut ComputedStyles, declaration: &css_parser::CSSDeclaration) {
         match declaration.property.as_str() {
+            "counter-reset" | "counter-set" | "counter-increment" => {
+                let (counters, default) = match declaration.property.as_str() {
+                    "counter-reset" => (&mut styles.counter_reset, 0),
+                    "counter-set" => (&mut styles.counter_set, 0),
+                    _ => (&mut styles.counter_increment, 1),
+                };
+                if let Some(changes) = generated_content::parse_counter_changes(&declaration.value.to_css_text(), default) {
+                    *counters = changes;
+                }
+            }
             "display" => {
                 if let CSSValue::Keyword(value) = &declaration.value {
                     styles.display = match value.as_str() {
                         "block" => DisplayType::Block,
                         "inline" => DisplayType::Inline,
                         "inline-block" => DisplayType::InlineBlock,
+                        "flex" => DisplayType::Flex,
+                        "contents" => DisplayType::Contents,
                         "none" => DisplayType::None,
                         _ => styles.display.clone(),
                     };
@@ -568,26 +1457,58 @@ impl StyleMatcher {
             }
             "width" => {
                 if let CSSValue::Dimension(value, unit) = &declaration.value {
-                    styles.width = Some(self.convert_length(*value, unit));
+                    styles.box_model_mut().width = Some(self.convert_length(*value, unit));
+                    styles.box_model_mut().width_expression = None;
                 }
             }
             "height" => {
                 if let CSSValue::Dimension(value, unit) = &declaration.value {
-                    styles.height = Some(self.convert_length(*value, unit));
+                    styles.box_model_mut().height = Some(self.convert_length(*value, unit));
+                    styles.box_model_mut().height_expression = None;
                 }
             }
             "margin" => {
-                styles.margin = self.parse_box_sides(&declaration.value);
+                styles.box_model_mut().margin = self.parse_box_sides(&declaration.value);
             }
             "padding" => {
-                styles.padding = self.parse_box_sides(&declaration.value);
+                styles.box_model_mut().padding = self.parse_box_sides(&declaration.value);
+            }
+            "border" | "border-width" => {
+                styles.box_model_mut().border = self.parse_box_sides(&declaration.value);
+            }
+            "background-color" => {
+                match &declaration.value {
+                    CSSValue::Color(color) => styles.background_color = Some(color.clone()),
+                    CSSValue::Keyword(keyword) => {
+                        if let Some(color) = self.system_palette().resolve(keyword) {
+                            styles.background_color = Some(color.to_string());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            "background" => self.apply_background_shorthand(styles, &declaration.value),
+            "background-image" => {
+                match &declaration.value {
+                    CSSValue::Url(url) => styles.background_image = Some(url.trim_matches(|c| c == '"' || c == '\'').to_string()),
+                    CSSValue::Keyword(value) if value == "none" => styles.background_image = None,
+                    _ => {}
+                }
+            }
+            "background-clip" => {
+                styles.background_clip = BackgroundBox::parse(&declaration.value.to_css_text()).unwrap_or(styles.background_clip);
             }
-            "border" => {
-                styles.border = self.parse_box_sides(&declaration.value);
+            "background-origin" => {
+                styles.background_origin = BackgroundBox::parse(&declaration.value.to_css_text()).unwrap_or(styles.background_origin);
             }
-            "background-color" | "background" => {
-                if let CSSValue::Color(color) = &declaration.value {
-                    styles.background_color = Some(color.clone());
+            "background-attachment" => {
+                styles.background_attachment = BackgroundAttachment::parse(&declaration.value.to_css_text()).unwrap_or(styles.background_attachment);
+            }
+            "border-radius" => {
+                match &declaration.value {
+                    CSSValue::Dimension(value, unit) => styles.border_radius = self.convert_length(*value, unit),
+                    CSSValue::Number(value) => styles.border_radius = *value,
+                    _ => {}
                 }
             }
             "color" => {
@@ -596,24 +1517,50 @@ impl StyleMatcher {
                         styles.color = Some(color.clone());
                     }
                     CSSValue::Keyword(keyword) => {
-                        styles.color = Some(keyword.clone());
+                        let color = self.system_palette().resolve(keyword).map_or_else(|| keyword.clone(), str::to_string);
+                        styles.color = Some(color);
                     }
                     _ => {}
                 }
             }
             "font-size" => {
-                if let CSSValue::Dimension(value, unit) = &declaration.value {
-                    styles.font_size = Some(self.convert_length(*value, unit));
+                let font = self.font_context.get();
+                let size = match &declaration.value {
+                    // Relative to the parent's font
+                    CSSValue::Dimension(value, unit) => match unit.as_str() {
+                        "em" => Some(value * font.parent_size),
+                        "ex" => Some(value * font.parent_size * font.parent_metrics.x_height),
+                        "ch" => Some(value * font.parent_size * font.parent_metrics.zero_advance),
+                        _ => Some(self.convert_length(*value, unit)),
+                    },
+                    CSSValue::Percentage(percent) => Some(percent / 100.0 * font.parent_size),
+                    CSSValue::Keyword(keyword) => match keyword.as_str() {
+                        "xx-small" => Some(9.0),
+                        "x-small" => Some(10.0),
+                        "small" => Some(13.0),
+                        "medium" => Some(16.0),
+                        "large" => Some(18.0),
+                        "x-large" => Some(24.0),
+                        "xx-large" => Some(32.0),
+                        "xxx-large" => Some(48.0),
+                        "larger" => Some(font.parent_size * FONT_SIZE_STEP),
+                        "smaller" => Some(font.parent_size / FONT_SIZE_STEP),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                if let Some(size) = size.filter(|size| *size >= 0.0) {
+                    styles.font_mut().size = Some(size);
                 }
             }
             "font-family" => {
                 if let CSSValue::String(family) = &declaration.value {
-                    styles.font_family = Some(family.clone());
+                    styles.font_mut().family = Some(family.clone());
                 }
             }
             "font-weight" => {
                 if let CSSValue::Keyword(weight) = &declaration.value {
-                    styles.font_weight = Some(weight.clone());
+                    styles.font_mut().weight = Some(weight.clone());
                 }
             }
             "text-align" => {
@@ -621,57 +1568,814 @@ impl StyleMatcher {
                     styles.text_align = Some(align.clone());
                 }
             }
-            _ => {} // Ignore unknown properties
-        }
-    }
-    
-    /// Convert a CSS length value to pixels
-    fn convert_length(&self, value: f32, unit: &str) -> f32 {
-        match unit {
-            "px" => value,
-            "em" => value * 16.0, // Assuming 16px base font size
-            "rem" => value * 16.0,
-            "pt" => value * 1.33, // 1pt = 1.33px
-            "pc" => value * 16.0, // 1pc = 16px
-            "in" => value * 96.0, // 1in = 96px
-            "cm" => value * 37.8, // 1cm = 37.8px
-            "mm" => value * 3.78, // 1mm = 3.78px
-            _ => value, // Default to pixels
-        }
-    }
-    
-    /// Parse box sides from a CSS value
-    fn parse_box_sides(&self, value: &CSSValue) -> BoxSides {
-        match value {
-            CSSValue::Dimension(val, unit) => {
-                BoxSides::new(self.convert_length(*val, unit))
+            "flex-direction" => {
+                if let CSSValue::Keyword(value) = &declaration.value {
+                    styles.flex_mut().direction = match value.as_str() {
+                        "row" => Some(FlexDirection::Row),
+                        "row-reverse" => Some(FlexDirection::RowReverse),
+                        "column" => Some(FlexDirection::Column),
+                        "column-reverse" => Some(FlexDirection::ColumnReverse),
+                        _ => styles.flex.direction,
+                    };
+                }
             }
-            _ => BoxSides::new(0.0),
-        }
-    }
-    
-    /// Apply inherited styles from parent elements
-    fn apply_inherited_styles(&self, styles: &mut ComputedStyles, element: &Rc<Node>) {
-        if let Some(parent) = element.parent.borrow().upgrade() {
-            let parent_styles = self.compute_styles(&parent);
-            
+            "flex-wrap" => {
+                if let CSSValue::Keyword(value) = &declaration.value {
+                    styles.flex_mut().wrap = match value.as_str() {
+                        "nowrap" => Some(FlexWrap::Nowrap),
+                        "wrap" => Some(FlexWrap::Wrap),
+                        "wrap-reverse" => Some(FlexWrap::WrapReverse),
+                        _ => styles.flex.wrap,
+                    };
+                }
+            }
+            "justify-content" => {
+                if let CSSValue::Keyword(value) = &declaration.value {
+                    styles.flex_mut().justify_content = match value.as_str() {
+                        "flex-start" | "start" => Some(JustifyContent::FlexStart),
+                        "flex-end" | "end" => Some(JustifyContent::FlexEnd),
+                        "center" => Some(JustifyContent::Center),
+                        "space-between" => Some(JustifyContent::SpaceBetween),
+                        "space-around" => Some(JustifyContent::SpaceAround),
+                        "space-evenly" => Some(JustifyContent::SpaceEvenly),
+                        _ => styles.flex.justify_content,
+                    };
+                }
+            }
+            "align-items" => {
+                if let CSSValue::Keyword(value) = &declaration.value {
+                    styles.flex_mut().align_items = match value.as_str() {
+                        "stretch" | "normal" => Some(AlignItems::Stretch),
+                        "flex-start" | "start" => Some(AlignItems::FlexStart),
+                        "flex-end" | "end" => Some(AlignItems::FlexEnd),
+                        "center" => Some(AlignItems::Center),
+                        "baseline" => Some(AlignItems::Baseline),
+                        _ => styles.flex.align_items.clone(),
+                    };
+                }
+            }
+            "align-content" => {
+                if let CSSValue::Keyword(value) = &declaration.value {
+                    styles.flex_mut().align_content = match value.as_str() {
+                        "stretch" | "normal" => Some(AlignContent::Stretch),
+                        "flex-start" | "start" => Some(AlignContent::FlexStart),
+                        "flex-end" | "end" => Some(AlignContent::FlexEnd),
+                        "center" => Some(AlignContent::Center),
+                        "space-between" => Some(AlignContent::SpaceBetween),
+                        "space-around" => Some(AlignContent::SpaceAround),
+                        _ => styles.flex.align_content.clone(),
+                    };
+                }
+            }
+            "align-self" => {
+                if let CSSValue::Keyword(value) = &declaration.value {
+                    styles.flex_mut().align_self = match value.as_str() {
+                        "auto" => Some(AlignSelf::Auto),
+                        "stretch" => Some(AlignSelf::Stretch),
+                        "flex-start" | "start" => Some(AlignSelf::FlexStart),
+                        "flex-end" | "end" => Some(AlignSelf::FlexEnd),
+                        "center" => Some(AlignSelf::Center),
+                        "baseline" => Some(AlignSelf::Baseline),
+                        _ => styles.flex.align_self.clone(),
+                    };
+                }
+            }
+            "order" => {
+                match &declaration.value {
+                    CSSValue::Number(value) => styles.flex_mut().order = *value as i32,
+                    CSSValue::Keyword(value) => {
+                        if let Ok(order) = value.parse::<i32>() {
+                            styles.flex_mut().order = order;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            "content-visibility" => {
+                if let CSSValue::Keyword(value) = &declaration.value {
+                    styles.content_visibility = match value.as_str() {
+                        "visible" => ContentVisibility::Visible,
+                        "auto" => ContentVisibility::Auto,
+                        "hidden" => ContentVisibility::Hidden,
+                        _ => styles.content_visibility,
+                    };
+                }
+            }
+            "contain-intrinsic-size" => {
+                styles.contain_intrinsic_size = self.parse_intrinsic_size(&declaration.value);
+            }
+            "position" => {
+                if let CSSValue::Keyword(value) = &declaration.value {
+                    styles.position = match value.as_str() {
+                        "static" => Position::Static,
+                        "relative" => Position::Relative,
+                        "absolute" => Position::Absolute,
+                        "fixed" => Position::Fixed,
+                        "sticky" | "-webkit-sticky" => Position::Sticky,
+                        _ => styles.position,
+                    };
+                }
+            }
+            "float" => {
+                if let CSSValue::Keyword(value) = &declaration.value {
+                    styles.float = match value.as_str() {
+                        "left" | "inline-start" => Float::Left,
+                        "right" | "inline-end" => Float::Right,
+                        "none" => Float::None,
+                        _ => styles.float,
+                    };
+                }
+            }
+            "object-fit" => {
+                if let Some(fit) = ObjectFit::parse(&declaration.value.to_css_text()) {
+                    styles.object_fit = fit;
+                }
+            }
+            "object-position" => {
+                if let Some(position) = ObjectPosition::parse(&declaration.value.to_css_text()) {
+                    styles.object_position = position;
+                }
+            }
+            "filter" => {
+                if let Some(filter) = filter::parse_filter_list(&declaration.value.to_css_text()) {
+                    styles.filter = filter;
+                }
+            }
+            "clip-path" => {
+                if let Some(clip_path) = masking::parse_clip_path(&declaration.value.to_css_text()) {
+                    styles.clip_path = clip_path;
+                }
+            }
+            "mask-image" | "-webkit-mask-image" => {
+                if let Some(mask_image) = masking::parse_mask_image(&declaration.value.to_css_text()) {
+                    styles.mask_image = mask_image;
+                }
+            }
+            "mask-mode" => {
+                if let Some(mode) = MaskMode::parse(&declaration.value.to_css_text()) {
+                    styles.mask_mode = mode;
+                }
+            }
+            "mix-blend-mode" => {
+                if let Some(mode) = BlendMode::parse(&declaration.value.to_css_text()) {
+                    styles.mix_blend_mode = mode;
+                }
+            }
+            "isolation" => {
+                if let CSSValue::Keyword(value) = &declaration.value {
+                    styles.isolation = match value.as_str() {
+                        "isolate" => Isolation::Isolate,
+                        "auto" => Isolation::Auto,
+                        _ => styles.isolation,
+                    };
+                }
+            }
+            "will-change" => {
+                if let Some(will_change) = WillChange::parse(&declaration.value.to_css_text()) {
+                    styles.will_change = will_change;
+                }
+            }
+            "direction" => {
+                if let CSSValue::Keyword(value) = &declaration.value {
+                    styles.direction = Direction::parse(value).or(styles.direction);
+                }
+            }
+            "font-style" => {
+                styles.font_mut().style = FontStyle::parse(&declaration.value.to_css_text()).or(styles.font.style);
+            }
+            "font-variant" => {
+                styles.font_mut().features.set_variant(&declaration.value.to_css_text());
+            }
+            "font-variant-ligatures" => {
+                styles.font_mut().features.set_ligatures(&declaration.value.to_css_text());
+            }
+            "font-variant-caps" => {
+                styles.font_mut().features.set_caps(&declaration.value.to_css_text());
+            }
+            "font-variant-numeric" => {
+                styles.font_mut().features.set_numeric(&declaration.value.to_css_text());
+            }
+            "font-feature-settings" => {
+                styles.font_mut().features.set_settings(&declaration.value.to_css_text());
+            }
+            "font-synthesis" => {
+                styles.font_mut().synthesis = FontSynthesis::parse(&declaration.value.to_css_text()).or(styles.font.synthesis);
+            }
+            "hyphens" => {
+                if let CSSValue::Keyword(value) = &declaration.value {
+                    styles.hyphens = Hyphens::parse(value).or(styles.hyphens);
+                }
+            }
+            "visibility" => {
+                if let CSSValue::Keyword(value) = &declaration.value {
+                    styles.visibility = Visibility::parse(value).or(styles.visibility);
+                }
+            }
+            "unicode-bidi" => {
+                if let Some(unicode_bidi) = UnicodeBidi::parse(&declaration.value.to_css_text()) {
+                    styles.unicode_bidi = unicode_bidi;
+                }
+            }
+            "top" => styles.inset.top = self.parse_inset(&declaration.value),
+            "right" => styles.inset.right = self.parse_inset(&declaration.value),
+            "bottom" => styles.inset.bottom = self.parse_inset(&declaration.value),
+            "left" => styles.inset.left = self.parse_inset(&declaration.value),
+            "animation-name" => {
+                if let CSSValue::Keyword(value) | CSSValue::String(value) = &declaration.value {
+                    styles.animation_name = if value == "none" { None } else { Some(value.clone()) };
+                }
+            }
+            "animation-duration" => styles.animation_duration = parse_time(&declaration.value),
+            "animation-delay" => styles.animation_delay = parse_time(&declaration.value),
+            "animation-iteration-count" => {
+                styles.animation_iteration_count = match &declaration.value {
+                    CSSValue::Number(count) => Some(*count),
+                    CSSValue::Keyword(value) if value == "infinite" => Some(f32::INFINITY),
+                    CSSValue::Keyword(value) => value.parse().ok(),
+                    _ => styles.animation_iteration_count,
+                };
+            }
+            "writing-mode" => {
+                styles.writing_mode = WritingMode::parse(&declaration.value.to_css_text()).or(styles.writing_mode);
+            }
+            "break-before" => {
+                styles.break_before = BreakBetween::parse(&declaration.value.to_css_text()).unwrap_or(styles.break_before);
+            }
+            "break-after" => {
+                styles.break_after = BreakBetween::parse(&declaration.value.to_css_text()).unwrap_or(styles.break_after);
+            }
+            "page-break-before" => {
+                styles.break_before = BreakBetween::parse_legacy(&declaration.value.to_css_text()).unwrap_or(styles.break_before);
+            }
+            "page-break-after" => {
+                styles.break_after = BreakBetween::parse_legacy(&declaration.value.to_css_text()).unwrap_or(styles.break_after);
+            }
+            "break-inside" | "page-break-inside" => {
+                styles.break_inside = BreakInside::parse(&declaration.value.to_css_text()).unwrap_or(styles.break_inside);
+            }
+            "widows" | "orphans" => {
+                let count = match &declaration.value {
+                    CSSValue::Number(count) => Some(*count as u32),
+                    value => value.to_css_text().trim().parse().ok(),
+                };
+                // Zero and negative counts are invalid
+                if let Some(count) = count.filter(|count| *count > 0) {
+                    match declaration.property.as_str() {
+                        "widows" => styles.widows = Some(count),
+                        _ => styles.orphans = Some(count),
+                    }
+                }
+            }
+            "forced-color-adjust" => {
+                if let Some(adjust) = ForcedColorAdjust::parse(&declaration.value.to_css_text()) {
+                    styles.forced_color_adjust = Some(adjust);
+                }
+            }
+            property => {
+                // Logical sides wait for the writing mode and direction
+                if let Some((box_property, sides)) = logical::logical_property(property) {
+                    self.apply_logical_declaration(styles, box_property, &sides, &declaration.value);
+                }
+            }
+        }
+        
+        // Physical sides set after a logical one are replayed after it
+        if !styles.pending_sides.is_empty() {
+            if let Some((box_property, sides)) = logical::physical_property(&declaration.property) {
+                for side in sides {
+                    let value = logical::side_value(styles, box_property, side);
+                    styles.pending_sides.push(SideDeclaration { property: box_property, side: logical::Side::Physical(side), value });
+                }
+            }
+        }
+    }
+    
+    /// Apply the `background` shorthand
+    /// 
+    /// The shorthand may set the color, image, attachment and boxes, e.g.
+    /// `#fff url(tile.png) fixed padding-box`. One box keyword sets both
+    /// the origin and the clip; with two, the first is the origin.
+    fn apply_background_shorthand(&self, styles: &mut ComputedStyles, value: &CSSValue) {
+        let text = match value {
+            CSSValue::Color(color) => {
+                styles.background_color = Some(color.clone());
+                return;
+            }
+            value => value.to_css_text(),
+        };
+        let mut boxes = Vec::new();
+        for word in text.split_whitespace() {
+            if let Some(url) = word.strip_prefix("url(").and_then(|url| url.strip_suffix(')')) {
+                styles.background_image = Some(url.trim_matches(|c| c == '"' || c == '\'').to_string());
+            } else if let Some(attachment) = BackgroundAttachment::parse(word) {
+                styles.background_attachment = attachment;
+            } else if let Some(background_box) = BackgroundBox::parse(word) {
+                boxes.push(background_box);
+            } else if word.starts_with('#') {
+                styles.background_color = Some(word.to_string());
+            }
+        }
+        match boxes.as_slice() {
+            [both] => (styles.background_origin, styles.background_clip) = (*both, *both),
+            [origin, clip, ..] => (styles.background_origin, styles.background_clip) = (*origin, *clip),
+            [] => {}
+        }
+    }
+    
+    /// Hold back the sides set by a logical property such as
+    /// `margin-inline: 10px 20px`
+    /// 
+    /// Two-sided properties take one value for both sides or a start and an
+    /// end value. `auto` is kept for insets.
+    fn apply_logical_declaration(&self, styles: &mut ComputedStyles, property: logical::BoxProperty, sides: &[LogicalSide], value: &CSSValue) {
+        let values: Option<Vec<Option<f32>>> = value
+            .to_css_text()
+            .split_whitespace()
+            .map(|word| match word {
+                "auto" => Some(None),
+                "0" => Some(Some(0.0)),
+                _ => {
+                    let split = word.find(|ch: char| ch.is_ascii_alphabetic())?;
+                    let number: f32 = word[..split].parse().ok()?;
+                    Some(Some(self.convert_length(number, &word[split..])))
+                }
+            })
+            .collect();
+        let values = match (sides.len(), values.as_deref()) {
+            (2, Some([value])) => vec![*value, *value],
+            (2, Some([start, end])) => vec![*start, *end],
+            (1, Some([value])) => vec![*value],
+            _ => return,
+        };
+        for (side, value) in sides.iter().zip(values) {
+            styles.pending_sides.push(SideDeclaration { property, side: logical::Side::Logical(*side), value });
+        }
+    }
+    
+    /// Convert a CSS length value to pixels
+    fn convert_length(&self, value: f32, unit: &str) -> f32 {
+        let font = self.font_context.get();
+        match unit {
+            "px" => value,
+            "em" => value * font.size,
+            "ex" => value * font.size * font.metrics.x_height,
+            "ch" => value * font.size * font.metrics.zero_advance,
+            "rem" => value * DEFAULT_FONT_SIZE,
+            "pt" => value * 1.33, // 1pt = 1.33px
+            "pc" => value * 16.0, // 1pc = 16px
+            "in" => value * 96.0, // 1in = 96px
+            "cm" => value * 37.8, // 1cm = 37.8px
+            "mm" => value * 3.78, // 1mm = 3.78px
+            _ => value, // Default to pixels
+        }
+    }
+    
+    /// Parse `contain-intrinsic-size`: one length for both axes or a
+    /// width and a height, each optionally preceded by `auto`
+    fn parse_intrinsic_size(&self, value: &CSSValue) -> Option<(f32, f32)> {
+        let text = match value {
+            CSSValue::Dimension(size, unit) => {
+                let size = self.convert_length(*size, unit);
+                return Some((size, size));
+            }
+            CSSValue::Number(size) => return Some((*size, *size)),
+            CSSValue::Keyword(text) => text,
+            _ => return None,
+        };
+
+        let lengths: Vec<f32> = text
+            .split_whitespace()
+            .filter(|part| *part != "auto")
+            .map(|part| {
+                let number_end = part
+                    .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+                    .unwrap_or(part.len());
+                let (number, unit) = part.split_at(number_end);
+                number.parse::<f32>().ok().map(|number| self.convert_length(number, unit))
+            })
+            .collect::<Option<Vec<f32>>>()?;
+        match lengths.as_slice() {
+            [size] => Some((*size, *size)),
+            [width, height] => Some((*width, *height)),
+            _ => None,
+        }
+    }
+    
+    /// Parse an inset (`top`, `left`, ...); `auto` and unknown values are `None`
+    fn parse_inset(&self, value: &CSSValue) -> Option<f32> {
+        match value {
+            CSSValue::Dimension(val, unit) => Some(self.convert_length(*val, unit)),
+            CSSValue::Number(val) => Some(*val),
+            _ => None,
+        }
+    }
+    
+    /// Parse box sides from a CSS value
+    fn parse_box_sides(&self, value: &CSSValue) -> BoxSides {
+        match value {
+            CSSValue::Dimension(val, unit) => {
+                BoxSides::new(self.convert_length(*val, unit))
+            }
+            _ => BoxSides::new(0.0),
+        }
+    }
+    
+    /// Recompute the styles of invalidated boxes in a layout tree
+    /// 
+    /// Only boxes named by `invalidations`, the descendants of those
+    /// invalidated as a subtree and the children of boxes whose styles
+    /// changed, which may inherit them, are restyled.
+    pub fn restyle_layout_tree(&self, root: &mut LayoutBox, invalidations: &HashMap<u64, StyleInvalidation>) -> RestyleOutcome {
+        let mut outcome = RestyleOutcome::default();
+        self.restyle_box(root, invalidations, false, false, &mut outcome);
+        outcome
+    }
+    
+    fn restyle_box(&self, layout_box: &mut LayoutBox, invalidations: &HashMap<u64, StyleInvalidation>, in_subtree: bool, parent_changed: bool, outcome: &mut RestyleOutcome) {
+        let invalidation = invalidations.get(&layout_box.node.id).copied().unwrap_or(StyleInvalidation::None);
+        let mut changed = false;
+        if in_subtree || parent_changed || invalidation != StyleInvalidation::None {
+            let mut styles = self.compute_styles(&layout_box.node);
+            // Used sizes carry over while their expressions are unchanged
+            if styles.box_model.width_expression.is_some() && styles.box_model.width_expression == layout_box.styles.box_model.width_expression {
+                styles.box_model_mut().width = layout_box.styles.box_model.width;
+            }
+            if styles.box_model.height_expression.is_some() && styles.box_model.height_expression == layout_box.styles.box_model.height_expression {
+                styles.box_model_mut().height = layout_box.styles.box_model.height;
+            }
+            if styles != layout_box.styles {
+                outcome.needs_layout |= affects_layout(&layout_box.styles, &styles);
+                layout_box.styles = styles;
+                outcome.changed_boxes += 1;
+                changed = true;
+            }
+            outcome.restyled_boxes += 1;
+        }
+        
+        let in_subtree = in_subtree || invalidation == StyleInvalidation::Subtree;
+        for child in &mut layout_box.children {
+            // Generated content may change with its element's styles and
+            // attributes, and is resolved again by layout
+            if child.generated_by().is_some() {
+                let invalidated = invalidations.get(&child.node.id).is_some_and(|invalidation| *invalidation != StyleInvalidation::None);
+                outcome.needs_layout |= in_subtree || changed || invalidated;
+                continue;
+            }
+            self.restyle_box(child, invalidations, in_subtree, changed, outcome);
+        }
+    }
+    
+    /// Determine which elements must be restyled after a DOM mutation
+    /// 
+    /// Covers the elements that `:has()` rules and selectors testing a
+    /// changed attribute may now match differently, and values reading the
+    /// attribute through `attr()`. The cascade reads attributes such as
+    /// `style`, `hidden` and `open` itself, so changing one restyles the
+    /// element's subtree, unless it is data for scripts or assistive
+    /// technology, which only selectors and `attr()` can observe.
+    pub fn invalidations_for_mutation(&self, node: &Rc<Node>, mutation: &Mutation) -> HashMap<u64, StyleInvalidation> {
+        let mut invalidations = HashMap::new();
+        let mut invalidate = |id: u64, invalidation: StyleInvalidation| {
+            if invalidation != StyleInvalidation::None {
+                let entry = invalidations.entry(id).or_insert(StyleInvalidation::None);
+                *entry = (*entry).max(invalidation);
+            }
+        };
+        for stylesheet in [&self.user_stylesheet, &self.stylesheet] {
+            for (id, invalidation) in css_parser::invalidations_for_mutation(stylesheet, node, mutation) {
+                invalidate(id, invalidation);
+            }
+            if let Mutation::Attribute { name, .. } = mutation {
+                for (id, invalidation) in css_parser::invalidations_for_attribute_change(stylesheet, node, name) {
+                    invalidate(id, invalidation);
+                }
+            }
+        }
+        if let Mutation::Attribute { name, .. } = mutation {
+            invalidate(node.id, self.invalidation_for_attribute_change(node, name));
+            let name = name.to_ascii_lowercase();
+            let data = name.starts_with("data-") || name.starts_with("aria-") || matches!(name.as_str(), "title" | "role");
+            if !data {
+                invalidate(node.id, StyleInvalidation::Subtree);
+            }
+        }
+        invalidations
+    }
+    
+    /// Determine how an element must be restyled after its attribute
+    /// `name` changed, because a value of its own or of its `::before` or
+    /// `::after` reads the attribute through `attr()`
+    /// 
+    /// Selectors testing the attribute are not considered here; see
+    /// `invalidations_for_mutation`.
+    pub fn invalidation_for_attribute_change(&self, element: &Rc<Node>, name: &str) -> StyleInvalidation {
+        let name = name.to_ascii_lowercase();
+        let reads = |text: &str| generated_content::referenced_attributes(text).contains(&name);
+        if element.get_attribute("style").is_some_and(|style| reads(&style)) {
+            return StyleInvalidation::Element;
+        }
+        let matched = self.user_stylesheet.rules.iter().chain(&self.stylesheet.rules)
+            .filter(|rule| rule.applies_to(&self.media_features))
+            .filter(|rule| rule.declarations.iter().any(|declaration| reads(&declaration.value.to_css_text())))
+            .flat_map(|rule| &rule.selectors)
+            .any(|selector| {
+                self.matches_selector(selector, element)
+                    || [PseudoElement::Before, PseudoElement::After].iter().any(|pseudo| self.matches_pseudo_element_selector(selector, element, *pseudo))
+            });
+        if matched {
+            StyleInvalidation::Element
+        } else {
+            StyleInvalidation::None
+        }
+    }
+    
+    /// Apply inherited styles from parent elements
+    fn apply_inherited_styles(&self, styles: &mut ComputedStyles, element: &Rc<Node>, parent_styles: Option<ComputedStyles>) {
+        if let Some(parent_styles) = parent_styles {
             // Inherit certain properties
             if styles.color.is_none() {
                 styles.color = parent_styles.color;
             }
-            if styles.font_size.is_none() {
-                styles.font_size = parent_styles.font_size;
+            if styles.font.size.is_none() {
+                styles.font_mut().size = parent_styles.font.size;
             }
-            if styles.font_family.is_none() {
-                styles.font_family = parent_styles.font_family;
+            if styles.font.family.is_none() {
+                styles.font_mut().family = parent_styles.font.family.clone();
             }
-            if styles.font_weight.is_none() {
-                styles.font_weight = parent_styles.font_weight;
+            if styles.font.weight.is_none() {
+                styles.font_mut().weight = parent_styles.font.weight.clone();
             }
             if styles.text_align.is_none() {
                 styles.text_align = parent_styles.text_align;
             }
+            if styles.direction.is_none() {
+                styles.direction = parent_styles.direction;
+            }
+            if styles.hyphens.is_none() {
+                styles.hyphens = parent_styles.hyphens;
+            }
+            if styles.visibility.is_none() {
+                styles.visibility = parent_styles.visibility;
+            }
+            if styles.font.style.is_none() {
+                styles.font_mut().style = parent_styles.font.style;
+            }
+            styles.font_mut().features.inherit(&parent_styles.font.features);
+            if styles.font.synthesis.is_none() {
+                styles.font_mut().synthesis = parent_styles.font.synthesis;
+            }
+            if styles.lang.is_none() {
+                styles.lang = parent_styles.lang;
+            }
+            if styles.writing_mode.is_none() {
+                styles.writing_mode = parent_styles.writing_mode;
+            }
+            if styles.widows.is_none() {
+                styles.widows = parent_styles.widows;
+            }
+            if styles.orphans.is_none() {
+                styles.orphans = parent_styles.orphans;
+            }
+            if styles.forced_color_adjust.is_none() {
+                styles.forced_color_adjust = parent_styles.forced_color_adjust;
+            }
+            // Text runs belong to the inline box around them
+            if matches!(element.node_type, NodeType::Text(_)) {
+                styles.unicode_bidi = parent_styles.unicode_bidi;
+            }
+            // A font equal to the parent's is the parent's
+            if styles.font == parent_styles.font {
+                styles.font = parent_styles.font;
+            }
+        }
+    }
+}
+
+/// Result of restyling a layout tree
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RestyleOutcome {
+    /// Boxes whose styles were recomputed
+    pub restyled_boxes: usize,
+    /// Boxes whose styles actually changed
+    pub changed_boxes: usize,
+    /// Whether a changed property affects geometry, so layout must rerun;
+    /// otherwise a repaint is enough
+    pub needs_layout: bool,
+}
+
+/// Check whether a selector ends in `pseudo`, e.g. `p::first-letter`
+/// 
+/// The CSS2 single-colon spellings (`:first-line`) are accepted too.
+/// Parse a CSS time such as `2s` or `150ms` into seconds
+fn parse_time(value: &CSSValue) -> Option<f32> {
+    let (number, unit) = match value {
+        CSSValue::Dimension(number, unit) => (*number, unit.as_str()),
+        CSSValue::Number(number) if *number == 0.0 => (0.0, "s"),
+        CSSValue::Keyword(text) => {
+            let split = text.find(|ch: char| ch.is_ascii_alphabetic())?;
+            (text[..split].parse().ok()?, &text[split..])
         }
+        _ => return None,
+    };
+    match unit {
+        "s" => Some(number),
+        "ms" => Some(number / 1000.0),
+        _ => None,
+    }
+}
+
+/// Check whether any rule of a stylesheet targets `::first-line`,
+/// `::first-letter` or `::backdrop`
+/// Get the font features of styles computed by the CSS cascade
+fn css_font_features(css_styles: &css_parser::ComputedStyles) -> FontFeatures {
+    let mut features = FontFeatures::default();
+    if let Some(variant) = &css_styles.font_variant {
+        features.set_variant(variant);
+    }
+    if let Some(settings) = &css_styles.font_feature_settings {
+        features.set_settings(settings);
+    }
+    features
+}
+
+fn has_pseudo_element_rules(stylesheet: &Stylesheet) -> bool {
+    stylesheet.rules.iter().any(|rule| {
+        rule.selectors.iter().any(|selector| {
+            [PseudoElement::FirstLine, PseudoElement::FirstLetter, PseudoElement::Backdrop, PseudoElement::Before, PseudoElement::After]
+                .iter()
+                .any(|pseudo| targets_pseudo_element(selector, *pseudo))
+        })
+    })
+}
+
+fn targets_pseudo_element(selector: &Selector, pseudo: PseudoElement) -> bool {
+    match selector {
+        Selector::PseudoElement(name) | Selector::PseudoClass(name) => name == pseudo.name(),
+        Selector::Compound(selectors) => selectors.last().is_some_and(|last| targets_pseudo_element(last, pseudo)),
+        Selector::Descendant(_, subject) | Selector::Child(_, subject) => targets_pseudo_element(subject, pseudo),
+        Selector::Group(selectors) => selectors.iter().any(|selector| targets_pseudo_element(selector, pseudo)),
+        _ => false,
+    }
+}
+
+/// Check whether a style change affects box geometry
+fn affects_layout(old: &ComputedStyles, new: &ComputedStyles) -> bool {
+    old.display != new.display
+        || old.box_model.width != new.box_model.width
+        || old.box_model.height != new.box_model.height
+        || old.box_model.margin != new.box_model.margin
+        || old.box_model.border != new.box_model.border
+        || old.box_model.padding != new.box_model.padding
+        || old.font.size != new.font.size
+        || old.font.family != new.font.family
+        || old.font.weight != new.font.weight
+        || old.font.style != new.font.style
+        || old.font.features != new.font.features
+        || old.font.synthesis != new.font.synthesis
+        || old.direction != new.direction
+        || old.unicode_bidi != new.unicode_bidi
+        || old.hyphens != new.hyphens
+        || old.lang != new.lang
+        || old.content_visibility != new.content_visibility
+        || old.contain_intrinsic_size != new.contain_intrinsic_size
+        || old.position != new.position
+        || old.inset != new.inset
+        || old.float != new.float
+        || old.flex.order != new.flex.order
+        || old.flex.direction != new.flex.direction
+        || old.flex.wrap != new.flex.wrap
+        || old.flex.justify_content != new.flex.justify_content
+        || old.flex.align_items != new.flex.align_items
+        || old.flex.align_content != new.flex.align_content
+        || old.flex.align_self != new.flex.align_self
+}
+
+impl LayoutBox {
+    /// Get the pseudo-element whose generated content this box holds, if it
+    /// is a `::before` or `::after` box
+    pub fn generated_by(&self) -> Option<PseudoElement> {
+        self.fragments
+            .first()
+            .and_then(|fragment| fragment.pseudo)
+            .filter(|pseudo| matches!(pseudo, PseudoElement::Before | PseudoElement::After))
+    }
+    
+    /// Find the node under a point, in the same coordinates as the box
+    /// dimensions
+    /// 
+    /// Returns the deepest box whose border box contains the point. Later
+    /// siblings paint on top of earlier ones, so they are tested first.
+    /// Text boxes are skipped in favour of their element.
+    pub fn hit_test(&self, x: f32, y: f32) -> Option<Rc<Node>> {
+        if self.styles.display == DisplayType::None {
+            return None;
+        }
+        for child in self.children.iter().rev() {
+            if let Some(node) = child.hit_test(x, y) {
+                return Some(node);
+            }
+        }
+        
+        let border = &self.border;
+        let inside = x >= border.x && x < border.right() && y >= border.y && y < border.bottom();
+        if inside && matches!(self.node.node_type, NodeType::Element { .. }) {
+            Some(Rc::clone(&self.node))
+        } else {
+            None
+        }
+    }
+}
+
+/// Get the parent of a text node if the text is the first content of the
+/// parent, so its first line is the parent's first line
+fn first_line_container(text: &Rc<Node>) -> Option<Rc<Node>> {
+    let parent = text.parent.borrow().upgrade()?;
+    let first_content = parent
+        .children
+        .borrow()
+        .iter()
+        .find(|child| match &child.node_type {
+            NodeType::Text(_) => !child.text_content().trim().is_empty(),
+            NodeType::Element { .. } => true,
+            _ => false,
+        })
+        .cloned()?;
+    Rc::ptr_eq(&first_content, text).then_some(parent)
+}
+
+/// Number of nodes below an element
+fn count_descendants(node: &Rc<Node>) -> usize {
+    node.children.borrow().iter().map(|child| 1 + count_descendants(child)).sum()
+}
+
+/// Check whether a node is, or contains, the focused element
+fn contains_focus(node: &Rc<Node>) -> bool {
+    node.element_state().contains(dom::ElementState::FOCUS)
+        || node.children.borrow().iter().any(contains_focus)
+}
+
+/// Find the `content-visibility: auto` boxes near the viewport
+/// 
+/// Flow layout only positions content boxes, relative to the parent box,
+/// so offsets are accumulated from `content` as in painting. `seen`
+/// collects every auto element so stale activations can be dropped.
+fn collect_relevant_auto_content(
+    layout_box: &LayoutBox,
+    parent_x: f32,
+    parent_y: f32,
+    area: &Dimensions,
+    relevant: &mut HashSet<u64>,
+    seen: &mut HashSet<u64>,
+) {
+    let x = parent_x + layout_box.content.x;
+    let y = parent_y + layout_box.content.y;
+    if layout_box.styles.content_visibility == ContentVisibility::Auto {
+        seen.insert(layout_box.node.id);
+        if box_intersects(layout_box, x, y, area) || contains_focus(&layout_box.node) {
+            relevant.insert(layout_box.node.id);
+        }
+    }
+    for child in &layout_box.children {
+        collect_relevant_auto_content(child, x, y, area, relevant, seen);
+    }
+}
+
+/// Check whether the border box of a box placed at `x`, `y` intersects an
+/// area
+fn box_intersects(layout_box: &LayoutBox, x: f32, y: f32, area: &Dimensions) -> bool {
+    x < area.right() && x + layout_box.border.width > area.x && y < area.bottom() && y + layout_box.border.height > area.y
+}
+
+/// Find the `loading="lazy"` images whose box is within
+/// `LAZY_LOAD_MARGIN` of the viewport
+///
+/// This is the check `content-visibility: auto` uses, with a wider margin;
+/// pass the result to `dom::images::ImageLoader::update`. Images that
+/// generate no box, such as `display: none` ones, are never near.
+pub fn lazy_images_near_viewport(root: &LayoutBox, viewport: Dimensions) -> HashSet<u64> {
+    let area = Dimensions::new(
+        viewport.x - LAZY_LOAD_MARGIN,
+        viewport.y - LAZY_LOAD_MARGIN,
+        viewport.width + 2.0 * LAZY_LOAD_MARGIN,
+        viewport.height + 2.0 * LAZY_LOAD_MARGIN,
+    );
+    let mut near = HashSet::new();
+    collect_lazy_images(root, 0.0, 0.0, &area, &mut near);
+    near
+}
+
+fn collect_lazy_images(layout_box: &LayoutBox, parent_x: f32, parent_y: f32, area: &Dimensions, near: &mut HashSet<u64>) {
+    if layout_box.styles.display == DisplayType::None {
+        return;
+    }
+    let x = parent_x + layout_box.content.x;
+    let y = parent_y + layout_box.content.y;
+    let node = &layout_box.node;
+    if node.is_image() && node.loading_mode() == dom::images::LoadingMode::Lazy && box_intersects(layout_box, x, y, area) {
+        near.insert(node.id);
+    }
+    for child in &layout_box.children {
+        collect_lazy_images(child, x, y, area, near);
     }
 }
 
@@ -683,27 +2387,119 @@ impl StyleMatcher {
 pub struct LayoutEngine {
     /// The style matcher for computing styles
     style_matcher: StyleMatcher,
+    /// `content-visibility: auto` elements currently relevant to the user,
+    /// whose contents are laid out
+    active_auto_content: HashSet<u64>,
+    /// Bookkeeping of subtrees skipped by the last layout
+    containment_stats: RefCell<ContainmentStats>,
+    /// Constraint rectangles of the sticky boxes in the last layout
+    sticky_constraints: RefCell<Vec<StickyConstraint>>,
+    /// Measured text widths, kept across layouts
+    measurement_cache: RefCell<TextMeasurementCache>,
+    /// Styles and text of the `::before` and `::after` boxes of the current
+    /// layout, resolved in tree order before it
+    generated_content: RefCell<HashMap<(u64, PseudoElement), (ComputedStyles, String)>>,
+    /// Where `tick_animations` reads the time
+    clock: SharedClock,
+    /// Subscriptions notified of border box changes after each layout
+    geometry_observers: GeometryObservers,
+}
+
+/// How much of the tree the last layout skipped through containment
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ContainmentStats {
+    /// Size-contained elements whose contents were skipped
+    pub skipped_subtrees: usize,
+    /// DOM nodes inside skipped subtrees
+    pub skipped_nodes: usize,
+    /// `content-visibility: auto` elements whose contents were laid out
+    pub active_auto_subtrees: usize,
 }
 
+/// Distance from the viewport within which `content-visibility: auto`
+/// contents are laid out, so they are ready before they scroll into view
+pub const CONTENT_VISIBILITY_MARGIN: f32 = 600.0;
+
+/// Distance from the viewport within which `loading="lazy"` images are
+/// fetched, so they are loaded before they scroll into view
+pub const LAZY_LOAD_MARGIN: f32 = 1250.0;
+
+/// The default border width of a dialog, `medium`
+pub const DIALOG_BORDER: f32 = 3.0;
+
+/// The default padding of a dialog, `1em`
+pub const DIALOG_PADDING: f32 = 16.0;
+
+/// The default padding of a popover, `0.25em`
+pub const POPOVER_PADDING: f32 = 4.0;
+
+/// The default color of a `::backdrop`, a faint shade over the page
+pub const BACKDROP_COLOR: &str = "rgba(0, 0, 0, 0.1)";
+
+/// The default color of the `::backdrop` of a fullscreen element
+pub const FULLSCREEN_BACKDROP_COLOR: &str = "black";
+
+/// Font size of the root element, which `rem` refers to
+pub const DEFAULT_FONT_SIZE: f32 = 16.0;
+
+/// Ratio between adjacent sizes of `font-size: larger` and `smaller`
+pub const FONT_SIZE_STEP: f32 = 1.2;
+
 impl LayoutEngine {
     /// Create a new layout engine with the given stylesheet
     pub fn new(stylesheet: Stylesheet) -> Self {
         LayoutEngine {
             style_matcher: StyleMatcher::new(stylesheet),
+            active_auto_content: HashSet::new(),
+            containment_stats: RefCell::new(ContainmentStats::default()),
+            sticky_constraints: RefCell::new(Vec::new()),
+            measurement_cache: RefCell::new(TextMeasurementCache::default()),
+            generated_content: RefCell::new(HashMap::new()),
+            clock: dom::clock::thread_clock(),
+            geometry_observers: GeometryObservers::new(),
         }
     }
     
+    /// Set the stylesheet of the user origin, see `StyleMatcher::set_user_stylesheet`
+    pub fn set_user_stylesheet(&mut self, stylesheet: Stylesheet) {
+        self.style_matcher.set_user_stylesheet(stylesheet);
+    }
+    
+    /// Set the environment `@media` rules and reduced motion follow
+    pub fn set_media_features(&mut self, features: MediaFeatures) {
+        self.style_matcher.set_media_features(features);
+    }
+    
+    /// Set the smallest font size text is shown at, see `StyleMatcher::set_minimum_font_size`
+    pub fn set_minimum_font_size(&mut self, size: f32) {
+        self.style_matcher.set_minimum_font_size(size);
+    }
+    
+    /// Set the values script animations give nodes, see `StyleMatcher::set_animated_styles`
+    pub fn set_animated_styles(&mut self, styles: HashMap<u64, Vec<(String, String)>>) {
+        self.style_matcher.set_animated_styles(styles);
+    }
+    
     /// Create a new layout engine without a stylesheet (for use with computed styles)
     pub fn new_empty() -> Self {
         LayoutEngine {
-            style_matcher: StyleMatcher::new(Stylesheet { rules: vec![], source_url: None }),
+            style_matcher: StyleMatcher::new(Stylesheet { rules: vec![], page_rules: vec![], source_url: None }),
+            active_auto_content: HashSet::new(),
+            containment_stats: RefCell::new(ContainmentStats::default()),
+            sticky_constraints: RefCell::new(Vec::new()),
+            measurement_cache: RefCell::new(TextMeasurementCache::default()),
+            generated_content: RefCell::new(HashMap::new()),
+            clock: dom::clock::thread_clock(),
+            geometry_observers: GeometryObservers::new(),
         }
     }
     
     /// Compute layout using pre-computed styles from CSS cascade
     pub fn compute_layout_with_styles(&mut self, document: &Document, computed_styles: &HashMap<u64, css_parser::ComputedStyles>) -> LayoutBox {
         // Convert CSS parser styles to layout styles and create a simple layout
-        self.layout_element_with_computed_styles(&document.root, computed_styles, Dimensions::new(0.0, 0.0, 800.0, 600.0))
+        let layout = self.layout_element_with_computed_styles(&document.root, computed_styles, Dimensions::new(0.0, 0.0, 800.0, 600.0));
+        self.finish_layout(&layout);
+        layout
     }
     
     /// Layout element using pre-computed styles
@@ -720,38 +2516,29 @@ impl LayoutEngine {
                     Some("inline-block") => DisplayType::InlineBlock,
                     Some("flex") => DisplayType::Flex,
                     Some("grid") => DisplayType::Grid,
+                    Some("contents") => DisplayType::Contents,
                     Some("none") => DisplayType::None,
                     _ => DisplayType::Block,
                 },
-                width: css_styles.width.as_ref().and_then(|w| w.replace("px", "").parse::<f32>().ok()),
-                height: css_styles.height.as_ref().and_then(|h| h.replace("px", "").parse::<f32>().ok()),
-                margin: BoxSides::new(0.0), // Simplified for now
-                border: BoxSides::new(0.0),
-                padding: BoxSides::new(0.0),
+                box_model: Rc::new(BoxStyles {
+                    width: css_styles.width.as_ref().and_then(|w| w.replace("px", "").parse::<f32>().ok()),
+                    height: css_styles.height.as_ref().and_then(|h| h.replace("px", "").parse::<f32>().ok()),
+                    // Margins, borders and padding are simplified for now
+                    ..BoxStyles::default()
+                }),
+                font: Rc::new(FontStyles {
+                    size: css_styles.font_size.as_ref().and_then(|f| f.replace("px", "").parse::<f32>().ok()),
+                    family: css_styles.font_family.clone(),
+                    weight: css_styles.font_weight.clone(),
+                    style: css_styles.font_style.as_deref().and_then(FontStyle::parse),
+                    features: css_font_features(css_styles),
+                    synthesis: css_styles.font_synthesis.as_deref().and_then(FontSynthesis::parse),
+                }),
+                flex: FlexStyles::initial(),
+                grid: GridStyles::initial(),
                 background_color: css_styles.background_color.clone(),
                 color: css_styles.color.clone(),
-                font_size: css_styles.font_size.as_ref().and_then(|f| f.replace("px", "").parse::<f32>().ok()),
-                font_family: css_styles.font_family.clone(),
-                font_weight: css_styles.font_weight.clone(),
                 text_align: css_styles.text_align.clone(),
-                flex_direction: None,
-                flex_wrap: None,
-                justify_content: None,
-                align_items: None,
-                align_content: None,
-                flex_grow: None,
-                flex_shrink: None,
-                flex_basis: None,
-                align_self: None,
-                grid_template_columns: None,
-                grid_template_rows: None,
-                grid_gap: None,
-                grid_column_gap: None,
-                grid_row_gap: None,
-                grid_column_start: None,
-                grid_column_end: None,
-                grid_row_start: None,
-                grid_row_end: None,
                 transition_duration: None,
                 transition_delay: None,
                 transition_timing_function: None,
@@ -762,6 +2549,63 @@ impl LayoutEngine {
                 animation_direction: None,
                 animation_fill_mode: None,
                 animation_play_state: None,
+                content_visibility: match css_styles.content_visibility.as_deref() {
+                    Some("auto") => ContentVisibility::Auto,
+                    Some("hidden") => ContentVisibility::Hidden,
+                    _ => ContentVisibility::Visible,
+                },
+                contain_intrinsic_size: css_styles
+                    .contain_intrinsic_size
+                    .as_ref()
+                    .and_then(|size| self.style_matcher.parse_intrinsic_size(&CSSValue::Keyword(size.clone()))),
+                position: match css_styles.position.as_deref() {
+                    Some("relative") => Position::Relative,
+                    Some("absolute") => Position::Absolute,
+                    Some("fixed") => Position::Fixed,
+                    Some("sticky") | Some("-webkit-sticky") => Position::Sticky,
+                    _ => Position::Static,
+                },
+                float: Float::None,
+                object_fit: css_styles.object_fit.as_deref().and_then(ObjectFit::parse).unwrap_or_default(),
+                object_position: css_styles.object_position.as_deref().and_then(ObjectPosition::parse).unwrap_or_default(),
+                filter: css_styles.filter.as_deref().and_then(filter::parse_filter_list).unwrap_or_default(),
+                mix_blend_mode: css_styles.mix_blend_mode.as_deref().and_then(BlendMode::parse).unwrap_or_default(),
+                isolation: match css_styles.isolation.as_deref() {
+                    Some("isolate") => Isolation::Isolate,
+                    _ => Isolation::Auto,
+                },
+                will_change: css_styles.will_change.as_deref().and_then(WillChange::parse).unwrap_or_default(),
+                content: Vec::new(),
+                counter_reset: Vec::new(),
+                counter_increment: Vec::new(),
+                counter_set: Vec::new(),
+                writing_mode: None,
+                pending_sides: Vec::new(),
+                background_image: None,
+                background_clip: BackgroundBox::BorderBox,
+                background_origin: BackgroundBox::PaddingBox,
+                background_attachment: BackgroundAttachment::Scroll,
+                border_radius: 0.0,
+                break_before: BreakBetween::Auto,
+                break_after: BreakBetween::Auto,
+                break_inside: BreakInside::Auto,
+                widows: None,
+                orphans: None,
+                clip_path: None,
+                mask_image: None,
+                mask_mode: MaskMode::Alpha,
+                forced_color_adjust: None,
+                direction: css_styles.direction.as_deref().and_then(Direction::parse),
+                unicode_bidi: css_styles.unicode_bidi.as_deref().and_then(UnicodeBidi::parse).unwrap_or_default(),
+                hyphens: css_styles.hyphens.as_deref().and_then(Hyphens::parse),
+                visibility: css_styles.visibility.as_deref().and_then(Visibility::parse),
+                lang: None,
+                inset: Insets {
+                    top: css_styles.top.as_ref().and_then(|v| v.replace("px", "").parse::<f32>().ok()),
+                    right: css_styles.right.as_ref().and_then(|v| v.replace("px", "").parse::<f32>().ok()),
+                    bottom: css_styles.bottom.as_ref().and_then(|v| v.replace("px", "").parse::<f32>().ok()),
+                    left: css_styles.left.as_ref().and_then(|v| v.replace("px", "").parse::<f32>().ok()),
+                },
             }
         } else {
             // Use default styles
@@ -778,13 +2622,14 @@ impl LayoutEngine {
                 border: Dimensions::new(0.0, 0.0, 0.0, 0.0),
                 margin: Dimensions::new(0.0, 0.0, 0.0, 0.0),
                 children: Vec::new(),
+                fragments: Vec::new(),
                 animation_state: AnimationState::default(),
             };
         }
         
         // Calculate content dimensions
-        let content_width = styles.width.unwrap_or(containing_block.width);
-        let content_height = styles.height.unwrap_or(20.0); // Default height
+        let content_width = styles.box_model.width.unwrap_or(containing_block.width);
+        let content_height = styles.box_model.height.unwrap_or(20.0); // Default height
         
         let content = Dimensions::new(0.0, 0.0, content_width, content_height);
         
@@ -792,10 +2637,18 @@ impl LayoutEngine {
         let mut children = Vec::new();
         let mut child_y = 0.0;
         
-        for child in element.children.borrow().iter() {
-            let child_layout = self.layout_element_with_computed_styles(child, computed_styles, content);
-            child_y += child_layout.content.height;
-            children.push(child_layout);
+        if styles.content_visibility != ContentVisibility::Hidden {
+            for child in element.children.borrow().iter() {
+                let child_layout = self.layout_element_with_computed_styles(child, computed_styles, content);
+                if child_layout.styles.display == DisplayType::Contents {
+                    // No box of its own: promote its children
+                    child_y += child_layout.children.iter().map(|grandchild| grandchild.content.height).sum::<f32>();
+                    children.extend(child_layout.children);
+                } else {
+                    child_y += child_layout.content.height;
+                    children.push(child_layout);
+                }
+            }
         }
         
         // Update content height based on children
@@ -815,6 +2668,7 @@ impl LayoutEngine {
             border: Dimensions::new(0.0, 0.0, 0.0, 0.0),
             margin: Dimensions::new(0.0, 0.0, 0.0, 0.0),
             children,
+            fragments: Vec::new(),
             animation_state: AnimationState::default(),
         }
     }
@@ -827,15 +2681,311 @@ impl LayoutEngine {
     /// 3. Calculate dimensions and positions
     pub fn layout_document(&self, document: &Document) -> LayoutBox {
         // Try using the document root directly instead of document_element()
-        let root_element = &document.root;
+        self.layout_tree(&document.root)
+    }
+    
+    /// Layout a document in a viewport of the given size, e.g. one a page
+    /// zoom has shrunk
+    pub fn layout_document_in(&self, document: &Document, viewport: Dimensions) -> LayoutBox {
+        self.layout_tree_in(&document.root, viewport)
+    }
+    
+    /// Layout the tree rooted at `root`, which need not be in a document
+    pub fn layout_tree(&self, root: &Rc<Node>) -> LayoutBox {
+        self.layout_tree_in(root, Dimensions::new(0.0, 0.0, 800.0, 600.0))
+    }
+    
+    /// Lay a document out on pages for printing
+    /// 
+    /// The document is laid out for `print` media at the width of the
+    /// first page's page area and cut into pages, see `paged::paginate`.
+    /// The media features are restored afterwards.
+    pub fn layout_pages(&mut self, document: &Document) -> PagedLayout {
+        let page_area = PageStyle::for_page(self.style_matcher.page_rules(), 1).page_area();
+        let screen = self.style_matcher.media_features().clone();
+        self.style_matcher.set_media_features(MediaFeatures {
+            media_type: "print".to_string(),
+            viewport_width: page_area.width,
+            viewport_height: page_area.height,
+            ..screen.clone()
+        });
+        let layout = self.layout_tree_in(&document.root, Dimensions::new(0.0, 0.0, page_area.width, page_area.height));
+        let pages = paged::paginate(&layout, &self.style_matcher.page_rules());
+        self.style_matcher.set_media_features(screen);
+        PagedLayout { layout, pages }
+    }
+    
+    /// Layout the tree rooted at `root` in a viewport
+    fn layout_tree_in(&self, root: &Rc<Node>, viewport: Dimensions) -> LayoutBox {
+        *self.containment_stats.borrow_mut() = ContainmentStats::default();
+        self.style_matcher.begin_style_pass();
+        let mut generated = HashMap::new();
+        if self.style_matcher.has_pseudo_element_rules {
+            self.resolve_generated_content(root, 0, &mut CounterState::new(), &mut generated);
+        }
+        *self.generated_content.borrow_mut() = generated;
+        let mut layout = self.layout_element(root, viewport);
+        if matches!(root.node_type, NodeType::Document) {
+            self.layout_top_layer(&mut layout, viewport);
+        }
+        self.style_matcher.end_style_pass();
+        self.finish_layout(&layout);
+        layout
+    }
+    
+    /// Resolve the `::before` and `::after` content of a subtree in tree
+    /// order, so each counter value counts every element before it
+    /// 
+    /// The pseudo-elements take part in counting as the first and last
+    /// child of their element. Elements that are not rendered neither
+    /// count nor generate content.
+    fn resolve_generated_content(&self, node: &Rc<Node>, depth: usize, counters: &mut CounterState, generated: &mut HashMap<(u64, PseudoElement), (ComputedStyles, String)>) {
+        if !matches!(node.node_type, NodeType::Element { .. }) {
+            for child in node.children.borrow().iter() {
+                self.resolve_generated_content(child, depth, counters, generated);
+            }
+            return;
+        }
+        let styles = self.style_matcher.compute_styles(node);
+        if styles.display == DisplayType::None {
+            return;
+        }
+        counters.apply(&styles.counter_reset, &styles.counter_increment, &styles.counter_set, depth);
+        
+        let resolve = |pseudo: PseudoElement, counters: &mut CounterState, generated: &mut HashMap<_, _>| {
+            let pseudo_styles = match self.style_matcher.compute_pseudo_element_styles(node, pseudo, &styles) {
+                Some(pseudo_styles) if !pseudo_styles.content.is_empty() && pseudo_styles.display != DisplayType::None => pseudo_styles,
+                _ => return,
+            };
+            counters.apply(&pseudo_styles.counter_reset, &pseudo_styles.counter_increment, &pseudo_styles.counter_set, depth + 1);
+            let text = generated_content::resolve_content(&pseudo_styles.content, counters);
+            generated.insert((node.id, pseudo), (pseudo_styles, text));
+        };
+        resolve(PseudoElement::Before, counters, generated);
+        for child in node.children.borrow().iter() {
+            self.resolve_generated_content(child, depth + 1, counters, generated);
+        }
+        resolve(PseudoElement::After, counters, generated);
+        counters.leave(depth);
+    }
+    
+    /// Lay out the top layer of a document above its root box
+    /// 
+    /// Each element is centered in the viewport as `position: fixed;
+    /// inset: 0; margin: auto` does for dialogs and popovers. Elements
+    /// without a width take half of the viewport's, and fullscreen elements
+    /// fill it. Modal and fullscreen elements are preceded by their
+    /// `::backdrop`, a box covering the viewport; popovers have none, so the
+    /// pointer can still go down outside them and light dismiss them. The boxes are children of the root, in top layer
+    /// order, so they paint last and are hit first; the backdrop box
+    /// belongs to its element, so clicking it targets the element.
+    fn layout_top_layer(&self, root: &mut LayoutBox, viewport: Dimensions) {
+        for element in top_layer::elements(&root.node) {
+            if !element.is_connected() {
+                continue;
+            }
+            let element_styles = self.style_matcher.compute_styles(&element);
+            if element_styles.display == DisplayType::None {
+                continue;
+            }
+            
+            if element.is_modal() || element.is_fullscreen() {
+                let color = if element.is_fullscreen() { FULLSCREEN_BACKDROP_COLOR } else { BACKDROP_COLOR };
+                let default_backdrop = ComputedStyles {
+                    background_color: Some(color.to_string()),
+                    ..element_styles.clone()
+                };
+                let backdrop_styles = self
+                    .style_matcher
+                    .compute_pseudo_element_styles(&element, PseudoElement::Backdrop, &default_backdrop)
+                    .unwrap_or(default_backdrop);
+                let mut backdrop = LayoutBox {
+                    node: Rc::clone(&element),
+                    styles: ComputedStyles {
+                        display: DisplayType::Block,
+                        box_model: Rc::new(BoxStyles {
+                            width: Some(viewport.width),
+                            height: Some(viewport.height),
+                            margin: BoxSides::new(0.0),
+                            border: BoxSides::new(0.0),
+                            padding: BoxSides::new(0.0),
+                            ..(*backdrop_styles.box_model).clone()
+                        }),
+                        ..backdrop_styles
+                    },
+                    content: viewport,
+                    padding: Dimensions::new(0.0, 0.0, 0.0, 0.0),
+                    border: Dimensions::new(0.0, 0.0, 0.0, 0.0),
+                    margin: Dimensions::new(0.0, 0.0, 0.0, 0.0),
+                    children: Vec::new(),
+                    fragments: Vec::new(),
+                    animation_state: AnimationState::default(),
+                };
+                self.calculate_box_dimensions(&mut backdrop);
+                root.children.push(backdrop);
+            }
+            
+            let fullscreen = element.is_fullscreen();
+            let available_width = if element_styles.box_model.width.is_some() || element_styles.box_model.width_expression.is_some() || fullscreen { viewport.width } else { viewport.width / 2.0 };
+            let mut element_box = self.layout_element(&element, Dimensions::new(0.0, 0.0, available_width, viewport.height));
+            if fullscreen {
+                let styles = &element_box.styles;
+                let horizontal = styles.box_model.padding.left + styles.box_model.padding.right + styles.box_model.border.left + styles.box_model.border.right;
+                let vertical = styles.box_model.padding.top + styles.box_model.padding.bottom + styles.box_model.border.top + styles.box_model.border.bottom;
+                element_box.content = Dimensions::new(0.0, 0.0, (viewport.width - horizontal).max(0.0), (viewport.height - vertical).max(0.0));
+            } else {
+                element_box.content.x = ((viewport.width - element_box.margin.width) / 2.0).max(0.0);
+                element_box.content.y = ((viewport.height - element_box.margin.height) / 2.0).max(0.0);
+            }
+            self.calculate_box_dimensions(&mut element_box);
+            
+            root.children.push(element_box);
+        }
+    }
+    
+    /// Layout a document for a viewport, activating the
+    /// `content-visibility: auto` subtrees near it
+    /// 
+    /// Auto subtrees start out skipped at their `contain-intrinsic-size`.
+    /// After each pass the placeholders that intersect the viewport (plus
+    /// `CONTENT_VISIBILITY_MARGIN`) are activated and layout runs again,
+    /// until the set of active subtrees is stable.
+    pub fn layout_document_for_viewport(&mut self, document: &Document, viewport: Dimensions) -> LayoutBox {
+        // Activating a subtree can push later ones out of view, so bound
+        // the number of passes
+        const MAX_PASSES: usize = 4;
         
+        let mut layout = self.layout_document(document);
+        for _ in 1..MAX_PASSES {
+            if !self.update_content_visibility(&layout, viewport) {
+                break;
+            }
+            layout = self.layout_document(document);
+        }
+        layout
+    }
+    
+    /// Update which `content-visibility: auto` elements are relevant to
+    /// the user after a layout or a scroll
+    /// 
+    /// An element is relevant while its border box is within
+    /// `CONTENT_VISIBILITY_MARGIN` of the viewport or it contains the
+    /// focused element. Returns `true` if the set changed, in which case
+    /// layout must run again.
+    pub fn update_content_visibility(&mut self, root: &LayoutBox, viewport: Dimensions) -> bool {
+        let area = Dimensions::new(
+            viewport.x - CONTENT_VISIBILITY_MARGIN,
+            viewport.y - CONTENT_VISIBILITY_MARGIN,
+            viewport.width + 2.0 * CONTENT_VISIBILITY_MARGIN,
+            viewport.height + 2.0 * CONTENT_VISIBILITY_MARGIN,
+        );
+        let mut relevant = HashSet::new();
+        let mut seen = HashSet::new();
+        collect_relevant_auto_content(root, 0.0, 0.0, &area, &mut relevant, &mut seen);
         
-        self.layout_element(root_element, Dimensions::new(0.0, 0.0, 800.0, 600.0))
+        // Elements that no longer exist or are no longer auto drop out
+        let mut next: HashSet<u64> = self
+            .active_auto_content
+            .iter()
+            .copied()
+            .filter(|id| seen.contains(id))
+            .collect();
+        for id in &seen {
+            if relevant.contains(id) {
+                next.insert(*id);
+            } else {
+                next.remove(id);
+            }
+        }
+        
+        let changed = next != self.active_auto_content;
+        self.active_auto_content = next;
+        changed
+    }
+    
+    /// Record what later stages need from a finished layout and notify
+    /// the geometry observers
+    fn finish_layout(&self, layout: &LayoutBox) {
+        *self.sticky_constraints.borrow_mut() = sticky::collect_sticky_constraints(layout);
+        self.geometry_observers.notify(layout);
+    }
+    
+    /// Use observers shared with other layout engines, see
+    /// `GeometryObservers`
+    pub fn set_geometry_observers(&mut self, observers: GeometryObservers) {
+        self.geometry_observers = observers;
+    }
+    
+    /// Get the geometry observers notified after each layout
+    pub fn geometry_observers(&self) -> &GeometryObservers {
+        &self.geometry_observers
+    }
+    
+    /// Call `callback` after each layout that changes the border box of
+    /// the node `node_id`, starting with the next layout
+    pub fn observe_geometry(&self, node_id: u64, callback: impl Fn(&GeometryChange) + 'static) -> GeometryObserverId {
+        self.geometry_observers.observe(node_id, callback)
+    }
+    
+    /// End a subscription made with `observe_geometry`
+    pub fn unobserve_geometry(&self, id: GeometryObserverId) -> bool {
+        self.geometry_observers.unobserve(id)
+    }
+    
+    /// Get the sticky constraint rectangles of the last layout
+    /// 
+    /// Hand them to a `ScrollCompositor` to position sticky boxes while
+    /// scrolling without another layout.
+    pub fn sticky_constraints(&self) -> Vec<StickyConstraint> {
+        self.sticky_constraints.borrow().clone()
+    }
+    
+    /// Get the hit, miss and eviction counts of the text measurement cache
+    pub fn measurement_stats(&self) -> MeasurementStats {
+        self.measurement_cache.borrow().stats()
+    }
+    
+    /// Change how many measured text runs are kept between layouts
+    pub fn set_measurement_cache_capacity(&self, capacity: usize) {
+        self.measurement_cache.borrow_mut().set_capacity(capacity);
+    }
+    
+    /// Replace the faces and fallback chains text is shaped with
+    pub fn set_font_registry(&self, registry: FontRegistry) {
+        self.style_matcher.set_font_registry(registry.clone());
+        self.measurement_cache.borrow_mut().set_font_registry(registry);
+    }
+    
+    /// Forget measured text, e.g. after fonts change
+    pub fn clear_measurement_cache(&self) {
+        self.measurement_cache.borrow_mut().clear();
+    }
+    
+    /// Get how often elements reused a sibling's styles in the last layout
+    pub fn style_sharing_stats(&self) -> StyleSharingStats {
+        self.style_matcher.style_sharing_stats()
+    }
+    
+    /// Get the containment bookkeeping of the last layout
+    pub fn containment_stats(&self) -> ContainmentStats {
+        *self.containment_stats.borrow()
+    }
+    
+    /// Get the indices of the author stylesheet's rules that matched an
+    /// element in any layout by this engine
+    pub fn matched_rules(&self) -> HashSet<usize> {
+        self.style_matcher.matched_rules()
+    }
+    
+    /// Get the style matcher layouts compute styles with
+    pub fn style_matcher(&self) -> &StyleMatcher {
+        &self.style_matcher
     }
     
     /// Layout a single element and its children
     fn layout_element(&self, element: &Rc<Node>, containing_block: Dimensions) -> LayoutBox {
-        let styles = self.style_matcher.compute_styles(element);
+        let mut styles = self.style_matcher.compute_styles(element);
+        self.style_matcher.resolve_used_sizes(&mut styles, containing_block);
         
         // Skip elements with display: none
         if styles.display == DisplayType::None {
@@ -847,14 +2997,42 @@ impl LayoutEngine {
                 border: Dimensions::new(0.0, 0.0, 0.0, 0.0),
                 margin: Dimensions::new(0.0, 0.0, 0.0, 0.0),
                 children: Vec::new(),
+                fragments: Vec::new(),
                 animation_state: AnimationState::default(),
             };
         }
         
-        // Calculate content dimensions
-        let content_width = styles.width.unwrap_or(containing_block.width);
+        // display: contents generates no box; its children are promoted
+        // into the parent by `layout_child_boxes`
+        if styles.display == DisplayType::Contents {
+            let children = self.layout_child_boxes(element, containing_block);
+            return LayoutBox {
+                node: Rc::clone(element),
+                styles,
+                content: Dimensions::new(0.0, 0.0, 0.0, 0.0),
+                padding: Dimensions::new(0.0, 0.0, 0.0, 0.0),
+                border: Dimensions::new(0.0, 0.0, 0.0, 0.0),
+                margin: Dimensions::new(0.0, 0.0, 0.0, 0.0),
+                children,
+                fragments: Vec::new(),
+                animation_state: AnimationState::default(),
+            };
+        }
+        
+        let skip_contents = match styles.content_visibility {
+            ContentVisibility::Visible => false,
+            ContentVisibility::Hidden => true,
+            ContentVisibility::Auto => !self.active_auto_content.contains(&element.id),
+        };
+        
+        // Calculate content dimensions; an auto width fills the containing
+        // block less the box's own margin, border and padding
+        let horizontal_edges = styles.box_model.margin.left + styles.box_model.margin.right
+            + styles.box_model.border.left + styles.box_model.border.right
+            + styles.box_model.padding.left + styles.box_model.padding.right;
+        let content_width = styles.box_model.width.unwrap_or((containing_block.width - horizontal_edges).max(0.0));
         // For height, we'll calculate it based on content after laying out children
-        let content_height = styles.height.unwrap_or(0.0);
+        let content_height = styles.box_model.height.unwrap_or(0.0);
         
         let mut layout_box = LayoutBox {
             node: Rc::clone(element),
@@ -864,11 +3042,34 @@ impl LayoutEngine {
             border: Dimensions::new(0.0, 0.0, 0.0, 0.0),
             margin: Dimensions::new(0.0, 0.0, 0.0, 0.0),
             children: Vec::new(),
+            fragments: Vec::new(),
             animation_state: AnimationState::default(),
         };
         
-        // Layout children
-        self.layout_children(&mut layout_box, containing_block);
+        if skip_contents {
+            // Size containment: the box is sized as if it were empty, using
+            // contain-intrinsic-size as a placeholder for its contents
+            if let (None, Some((_, intrinsic_height))) = (styles.box_model.height, styles.contain_intrinsic_size) {
+                layout_box.content.height = intrinsic_height;
+            }
+            let mut stats = self.containment_stats.borrow_mut();
+            stats.skipped_subtrees += 1;
+            stats.skipped_nodes += count_descendants(element);
+        } else {
+            if styles.content_visibility == ContentVisibility::Auto {
+                self.containment_stats.borrow_mut().active_auto_subtrees += 1;
+            }
+            // Layout children; the content box is their containing block
+            let content_box = layout_box.content;
+            self.layout_children(&mut layout_box, content_box);
+            if ruby::is_ruby(element) {
+                ruby::layout_ruby(&mut layout_box);
+            }
+        }
+        
+        if let Some(text) = element.character_data() {
+            self.layout_text(&mut layout_box, element, &text);
+        }
         
         // Calculate total dimensions including padding, border, and margin
         self.calculate_box_dimensions(&mut layout_box);
@@ -876,6 +3077,90 @@ impl LayoutEngine {
         layout_box
     }
     
+    /// Break a text box into line fragments
+    /// 
+    /// If the text starts its block container, the container's
+    /// `::first-line` and `::first-letter` styles apply to the first line.
+    fn layout_text(&self, layout_box: &mut LayoutBox, element: &Rc<Node>, text: &str) {
+        let mut first_line = None;
+        let mut first_letter = None;
+        if let Some(container) = first_line_container(element) {
+            let container_styles = self.style_matcher.compute_styles(&container);
+            if matches!(container_styles.display, DisplayType::Block | DisplayType::InlineBlock) {
+                first_line = self.style_matcher.compute_pseudo_element_styles(&container, PseudoElement::FirstLine, &container_styles);
+                let letter_parent = first_line.as_ref().unwrap_or(&container_styles);
+                first_letter = self.style_matcher.compute_pseudo_element_styles(&container, PseudoElement::FirstLetter, letter_parent);
+            }
+        }
+        
+        let text_layout = inline::layout_text(
+            text,
+            layout_box.content.width,
+            &layout_box.styles,
+            first_line.as_ref(),
+            first_letter.as_ref(),
+            &mut self.measurement_cache.borrow_mut(),
+        );
+        if !text_layout.fragments.is_empty() {
+            layout_box.content.height = text_layout.height;
+            layout_box.fragments = text_layout.fragments;
+        }
+    }
+    
+    /// Layout the child nodes of an element, replacing `display: contents`
+    /// children with their own children
+    fn layout_child_boxes(&self, element: &Rc<Node>, containing_block: Dimensions) -> Vec<LayoutBox> {
+        let mut boxes: Vec<LayoutBox> = self.layout_generated_box(element, PseudoElement::Before, containing_block).into_iter().collect();
+        for child_node in element.children.borrow().iter() {
+            // Top layer elements are laid out above the root instead
+            if top_layer::contains(child_node) {
+                continue;
+            }
+            let child_layout = self.layout_element(child_node, containing_block);
+            if child_layout.styles.display == DisplayType::Contents {
+                boxes.extend(child_layout.children);
+            } else {
+                boxes.push(child_layout);
+            }
+        }
+        boxes.extend(self.layout_generated_box(element, PseudoElement::After, containing_block));
+        boxes
+    }
+    
+    /// Lay out the `::before` or `::after` box of an element, if it has one
+    /// 
+    /// The box belongs to its element, so it is hit as the element, and
+    /// its text fragments are marked with the pseudo-element. Content that
+    /// resolves to no text generates no box.
+    fn layout_generated_box(&self, element: &Rc<Node>, pseudo: PseudoElement, containing_block: Dimensions) -> Option<LayoutBox> {
+        let generated = self.generated_content.borrow();
+        let (computed_styles, text) = generated.get(&(element.id, pseudo))?;
+        let mut styles = computed_styles.clone();
+        self.style_matcher.resolve_used_sizes(&mut styles, containing_block);
+        let horizontal_edges = styles.box_model.margin.left + styles.box_model.margin.right
+            + styles.box_model.border.left + styles.box_model.border.right
+            + styles.box_model.padding.left + styles.box_model.padding.right;
+        let content_width = styles.box_model.width.unwrap_or((containing_block.width - horizontal_edges).max(0.0));
+        let text_layout = inline::layout_text(text, content_width, &styles, None, None, &mut self.measurement_cache.borrow_mut());
+        if text_layout.fragments.is_empty() {
+            return None;
+        }
+        
+        let mut layout_box = LayoutBox {
+            content: Dimensions::new(0.0, 0.0, content_width, styles.box_model.height.unwrap_or(text_layout.height)),
+            node: Rc::clone(element),
+            styles,
+            padding: Dimensions::new(0.0, 0.0, 0.0, 0.0),
+            border: Dimensions::new(0.0, 0.0, 0.0, 0.0),
+            margin: Dimensions::new(0.0, 0.0, 0.0, 0.0),
+            children: Vec::new(),
+            fragments: text_layout.fragments.into_iter().map(|fragment| TextFragment { pseudo: Some(pseudo), ..fragment }).collect(),
+            animation_state: AnimationState::default(),
+        };
+        self.calculate_box_dimensions(&mut layout_box);
+        Some(layout_box)
+    }
+    
     /// Layout the children of an element
     fn layout_children(&self, parent: &mut LayoutBox, containing_block: Dimensions) {
         match parent.styles.display {
@@ -895,9 +3180,8 @@ impl LayoutEngine {
     fn layout_block_children(&self, parent: &mut LayoutBox, containing_block: Dimensions) {
         let mut current_y = 0.0;
         
-        for child_node in parent.node.children.borrow().iter() {
-            let child_layout = self.layout_element(child_node, containing_block);
-            
+        let node = Rc::clone(&parent.node);
+        for child_layout in self.layout_child_boxes(&node, containing_block) {
             // Position the child
             let mut positioned_child = child_layout;
             positioned_child.content.y = current_y;
@@ -917,242 +3201,230 @@ impl LayoutEngine {
             parent.content.height = total_height.max(parent.content.height);
         } else {
             // If no children, set a minimum height based on font size for text content
-            let font_size = parent.styles.font_size.unwrap_or(16.0);
+            let font_size = parent.styles.font.size.unwrap_or(16.0);
             let min_height = font_size * 1.2; // Line height of 1.2
             parent.content.height = parent.content.height.max(min_height);
         }
     }
     
     /// Layout children using flexbox
+    /// 
+    /// Items are placed in `order`, broken into lines when wrapping, and
+    /// positioned along the main axis by `justify-content`; lines are
+    /// distributed along the cross axis by `align-content` and items
+    /// within their line by `align-items`/`align-self`. Reverse directions
+    /// mirror placement along the main axis, `wrap-reverse` along the
+    /// cross axis.
     fn layout_flex_children(&self, parent: &mut LayoutBox, containing_block: Dimensions) {
-        let flex_direction = parent.styles.flex_direction.clone().unwrap_or(FlexDirection::Row);
-        let justify_content = parent.styles.justify_content.clone().unwrap_or(JustifyContent::FlexStart);
-        let align_items = parent.styles.align_items.clone().unwrap_or(AlignItems::Stretch);
-        let flex_wrap = parent.styles.flex_wrap.clone().unwrap_or(FlexWrap::Nowrap);
+        let flex_direction = parent.styles.flex.direction.unwrap_or(FlexDirection::Row);
+        let justify_content = parent.styles.flex.justify_content.unwrap_or(JustifyContent::FlexStart);
+        let align_items = parent.styles.flex.align_items.clone().unwrap_or(AlignItems::Stretch);
+        let align_content = parent.styles.flex.align_content.clone().unwrap_or(AlignContent::Stretch);
+        let flex_wrap = parent.styles.flex.wrap.unwrap_or(FlexWrap::Nowrap);
+        let is_row = matches!(flex_direction, FlexDirection::Row | FlexDirection::RowReverse);
+        let is_reverse = matches!(flex_direction, FlexDirection::RowReverse | FlexDirection::ColumnReverse);
         
-        // First pass: layout all children
-        let mut children = Vec::new();
-        for child_node in parent.node.children.borrow().iter() {
-            let child_layout = self.layout_element(child_node, containing_block);
-            children.push(child_layout);
-        }
-        
-        // Calculate available space
-        let available_width = parent.content.width;
-        let available_height = parent.content.height;
-        
-        // Apply flexbox layout
-        match flex_direction {
-            FlexDirection::Row | FlexDirection::RowReverse => {
-                self.layout_flex_row(&mut children, available_width, available_height, 
-                                   justify_content, align_items, flex_wrap);
-            }
-            FlexDirection::Column | FlexDirection::ColumnReverse => {
-                self.layout_flex_column(&mut children, available_width, available_height, 
-                                      justify_content, align_items, flex_wrap);
-            }
-        }
+        // First pass: layout all children, then sort them by `order`. The
+        // sort is stable, so items with equal order keep document order.
+        let mut children = self.layout_child_boxes(&parent.node, containing_block);
+        children.sort_by_key(|child| child.styles.flex.order);
         
-        // Update parent dimensions
-        if !children.is_empty() {
-            let max_x = children.iter().map(|c| c.content.x + c.content.width).fold(0.0, f32::max);
-            let max_y = children.iter().map(|c| c.content.y + c.content.height).fold(0.0, f32::max);
-            parent.content.width = max_x.max(parent.content.width);
-            parent.content.height = max_y.max(parent.content.height);
+        // Items of a single row that overflow it shrink by `flex-shrink`
+        // (1 by default) weighted by their size, and are laid out again at
+        // their new width
+        if is_row && flex_wrap == FlexWrap::Nowrap {
+            self.shrink_flex_row(&mut children, parent.content.width, containing_block);
         }
         
-        parent.children = children;
-    }
-    
-    /// Layout flex items in a row
-    fn layout_flex_row(&self, children: &mut Vec<LayoutBox>, available_width: f32, available_height: f32,
-                      justify_content: JustifyContent, align_items: AlignItems, flex_wrap: FlexWrap) {
-        let mut current_x = 0.0;
-        let mut current_y = 0.0;
-        let mut line_height = 0.0;
+        // Definite container sizes; auto heights grow to fit the items
+        let (definite_main, definite_cross) = if is_row {
+            (Some(parent.content.width), parent.styles.box_model.height)
+        } else {
+            (parent.styles.box_model.height, Some(parent.content.width))
+        };
+        let main_of = |child: &LayoutBox| if is_row { child.content.width } else { child.content.height };
+        let cross_of = |child: &LayoutBox| if is_row { child.content.height } else { child.content.width };
         
-        for child in children.iter_mut() {
-            // Check if we need to wrap
-            if flex_wrap == FlexWrap::Wrap && current_x + child.content.width > available_width && current_x > 0.0 {
-                current_x = 0.0;
-                current_y += line_height;
-                line_height = 0.0;
+        // Break items into lines
+        let main_limit = definite_main.unwrap_or(f32::INFINITY);
+        let mut lines: Vec<std::ops::Range<usize>> = Vec::new();
+        let mut line_start = 0;
+        let mut line_main = 0.0;
+        for (index, child) in children.iter().enumerate() {
+            let size = main_of(child);
+            if flex_wrap != FlexWrap::Nowrap && index > line_start && line_main + size > main_limit {
+                lines.push(line_start..index);
+                line_start = index;
+                line_main = 0.0;
             }
-            
-            // Position the child
-            child.content.x = current_x;
-            child.content.y = current_y;
-            
-            // Align items vertically
-            match align_items {
-                AlignItems::FlexStart => {
-                    // Already positioned at top
-                }
-                AlignItems::FlexEnd => {
-                    child.content.y = current_y + available_height - child.content.height;
-                }
-                AlignItems::Center => {
-                    child.content.y = current_y + (available_height - child.content.height) / 2.0;
-                }
-                AlignItems::Stretch => {
-                    child.content.height = available_height;
-                }
-                AlignItems::Baseline => {
-                    // For now, treat as flex-start
-                }
-            }
-            
-            // Update position for next child
-            current_x += child.content.width;
-            line_height = line_height.max(child.content.height);
+            line_main += size;
         }
-        
-        // Apply justify-content
         if !children.is_empty() {
-            let total_width = children.iter().map(|c| c.content.width).sum::<f32>();
-            let extra_space = available_width - total_width;
-            
-            match justify_content {
-                JustifyContent::FlexStart => {
-                    // Already positioned correctly
-                }
-                JustifyContent::FlexEnd => {
-                    let offset = extra_space;
-                    for child in children.iter_mut() {
-                        child.content.x += offset;
-                    }
-                }
-                JustifyContent::Center => {
-                    let offset = extra_space / 2.0;
-                    for child in children.iter_mut() {
-                        child.content.x += offset;
-                    }
+            lines.push(line_start..children.len());
+        }
+        
+        let container_main = definite_main.unwrap_or_else(|| {
+            lines.iter().map(|line| children[line.clone()].iter().map(main_of).sum::<f32>()).fold(0.0, f32::max)
+        });
+        let mut line_cross: Vec<f32> = lines
+            .iter()
+            .map(|line| children[line.clone()].iter().map(cross_of).fold(0.0, f32::max))
+            .collect();
+        
+        // A single-line container's line fills its cross size; multi-line
+        // containers distribute free space between lines by align-content
+        let (mut cross_pos, line_gap) = match definite_cross {
+            Some(cross_size) if flex_wrap == FlexWrap::Nowrap => {
+                if let Some(line) = line_cross.first_mut() {
+                    *line = cross_size;
                 }
-                JustifyContent::SpaceBetween => {
-                    if children.len() > 1 {
-                        let gap = extra_space / (children.len() - 1) as f32;
-                        for (i, child) in children.iter_mut().enumerate() {
-                            if i > 0 {
-                                child.content.x += gap * i as f32;
+                (0.0, 0.0)
+            }
+            Some(cross_size) => {
+                let free = cross_size - line_cross.iter().sum::<f32>();
+                let count = line_cross.len() as f32;
+                match align_content {
+                    AlignContent::FlexStart => (0.0, 0.0),
+                    AlignContent::FlexEnd => (free, 0.0),
+                    AlignContent::Center => (free / 2.0, 0.0),
+                    AlignContent::SpaceBetween if free > 0.0 && count > 1.0 => (0.0, free / (count - 1.0)),
+                    AlignContent::SpaceBetween => (0.0, 0.0),
+                    AlignContent::SpaceAround if free > 0.0 => (free / count / 2.0, free / count),
+                    AlignContent::SpaceAround => (free / 2.0, 0.0),
+                    AlignContent::Stretch => {
+                        if free > 0.0 {
+                            for line in line_cross.iter_mut() {
+                                *line += free / count;
                             }
                         }
+                        (0.0, 0.0)
                     }
                 }
-                JustifyContent::SpaceAround => {
-                    let gap = extra_space / children.len() as f32;
-                    for (i, child) in children.iter_mut().enumerate() {
-                        child.content.x += gap * (i as f32 + 0.5);
+            }
+            None => (0.0, 0.0),
+        };
+        let container_cross = definite_cross.unwrap_or_else(|| line_cross.iter().sum());
+        
+        for (line, cross_size) in lines.iter().zip(line_cross) {
+            let count = line.len() as f32;
+            let free = container_main - children[line.clone()].iter().map(main_of).sum::<f32>();
+            let (mut main_pos, gap) = match justify_content {
+                JustifyContent::FlexStart => (0.0, 0.0),
+                JustifyContent::FlexEnd => (free, 0.0),
+                JustifyContent::Center => (free / 2.0, 0.0),
+                JustifyContent::SpaceBetween if count > 1.0 => (0.0, free / (count - 1.0)),
+                JustifyContent::SpaceBetween => (0.0, 0.0),
+                JustifyContent::SpaceAround => (free / count / 2.0, free / count),
+                JustifyContent::SpaceEvenly => (free / (count + 1.0), free / (count + 1.0)),
+            };
+            
+            for child in &mut children[line.clone()] {
+                let align = match &child.styles.flex.align_self {
+                    Some(AlignSelf::Stretch) => AlignItems::Stretch,
+                    Some(AlignSelf::FlexStart) => AlignItems::FlexStart,
+                    Some(AlignSelf::FlexEnd) => AlignItems::FlexEnd,
+                    Some(AlignSelf::Center) => AlignItems::Center,
+                    Some(AlignSelf::Baseline) => AlignItems::Baseline,
+                    Some(AlignSelf::Auto) | None => align_items.clone(),
+                };
+                let mut item_cross = cross_of(child);
+                let cross_offset = match align {
+                    AlignItems::FlexEnd => cross_size - item_cross,
+                    AlignItems::Center => (cross_size - item_cross) / 2.0,
+                    AlignItems::Stretch => {
+                        // Items with an explicit cross size are not stretched
+                        let explicit = if is_row { child.styles.box_model.height } else { child.styles.box_model.width };
+                        if explicit.is_none() {
+                            item_cross = cross_size;
+                        }
+                        0.0
                     }
+                    // Baseline alignment is treated as flex-start for now
+                    AlignItems::FlexStart | AlignItems::Baseline => 0.0,
+                };
+                
+                let size = main_of(child);
+                let main = if is_reverse { container_main - main_pos - size } else { main_pos };
+                let mut cross = cross_pos + cross_offset;
+                if flex_wrap == FlexWrap::WrapReverse {
+                    cross = container_cross - cross - item_cross;
                 }
-                JustifyContent::SpaceEvenly => {
-                    let gap = extra_space / (children.len() + 1) as f32;
-                    for (i, child) in children.iter_mut().enumerate() {
-                        child.content.x += gap * (i as f32 + 1.0);
-                    }
+                
+                if is_row {
+                    child.content.x = main;
+                    child.content.y = cross;
+                    child.content.height = item_cross;
+                } else {
+                    child.content.x = cross;
+                    child.content.y = main;
+                    child.content.width = item_cross;
                 }
+                main_pos += size + gap;
             }
+            cross_pos += cross_size + line_gap;
+        }
+        
+        // Update parent dimensions; the width is always definite, so only
+        // an auto height grows to fit the items
+        if !children.is_empty() {
+            let max_y = children.iter().map(|c| c.content.y + c.content.height).fold(0.0, f32::max);
+            parent.content.height = max_y.max(parent.content.height);
         }
+        
+        parent.children = children;
     }
     
-    /// Layout flex items in a column
-    fn layout_flex_column(&self, children: &mut Vec<LayoutBox>, available_width: f32, available_height: f32,
-                         justify_content: JustifyContent, align_items: AlignItems, flex_wrap: FlexWrap) {
-        let mut current_x = 0.0;
-        let mut current_y = 0.0;
-        let mut line_width = 0.0;
+    /// Shrink the items of a single-line row to fit its width
+    fn shrink_flex_row(&self, children: &mut [LayoutBox], available_width: f32, containing_block: Dimensions) {
+        let total: f32 = children.iter().map(|child| child.content.width).sum();
+        let overflow = total - available_width;
+        let weight = |child: &LayoutBox| child.styles.flex.shrink.unwrap_or(1.0).max(0.0) * child.content.width;
+        let total_weight: f32 = children.iter().map(weight).sum();
+        if overflow <= 0.0 || total_weight <= 0.0 {
+            return;
+        }
         
         for child in children.iter_mut() {
-            // Check if we need to wrap
-            if flex_wrap == FlexWrap::Wrap && current_y + child.content.height > available_height && current_y > 0.0 {
-                current_y = 0.0;
-                current_x += line_width;
-                line_width = 0.0;
+            let shrink = overflow * weight(child) / total_weight;
+            if shrink <= 0.0 {
+                continue;
             }
-            
-            // Position the child
-            child.content.x = current_x;
-            child.content.y = current_y;
-            
-            // Align items horizontally
-            match align_items {
-                AlignItems::FlexStart => {
-                    // Already positioned at left
-                }
-                AlignItems::FlexEnd => {
-                    child.content.x = current_x + available_width - child.content.width;
-                }
-                AlignItems::Center => {
-                    child.content.x = current_x + (available_width - child.content.width) / 2.0;
-                }
-                AlignItems::Stretch => {
-                    child.content.width = available_width;
-                }
-                AlignItems::Baseline => {
-                    // For now, treat as flex-start
+            let width = (child.content.width - shrink).max(0.0);
+            let styles = &child.styles;
+            let edges = styles.box_model.margin.left + styles.box_model.margin.right
+                + styles.box_model.border.left + styles.box_model.border.right
+                + styles.box_model.padding.left + styles.box_model.padding.right;
+            let node = Rc::clone(&child.node);
+            let shrunk_block = Dimensions::new(0.0, 0.0, width + edges, containing_block.height);
+            // Generated content has no children to lay out again
+            if let Some(pseudo) = child.generated_by() {
+                if let Some(generated) = self.layout_generated_box(&node, pseudo, shrunk_block) {
+                    *child = generated;
                 }
+                continue;
             }
-            
-            // Update position for next child
-            current_y += child.content.height;
-            line_width = line_width.max(child.content.width);
-        }
-        
-        // Apply justify-content
-        if !children.is_empty() {
-            let total_height = children.iter().map(|c| c.content.height).sum::<f32>();
-            let extra_space = available_height - total_height;
-            
-            match justify_content {
-                JustifyContent::FlexStart => {
-                    // Already positioned correctly
-                }
-                JustifyContent::FlexEnd => {
-                    let offset = extra_space;
-                    for child in children.iter_mut() {
-                        child.content.y += offset;
-                    }
-                }
-                JustifyContent::Center => {
-                    let offset = extra_space / 2.0;
-                    for child in children.iter_mut() {
-                        child.content.y += offset;
-                    }
-                }
-                JustifyContent::SpaceBetween => {
-                    if children.len() > 1 {
-                        let gap = extra_space / (children.len() - 1) as f32;
-                        for (i, child) in children.iter_mut().enumerate() {
-                            if i > 0 {
-                                child.content.y += gap * i as f32;
-                            }
-                        }
-                    }
-                }
-                JustifyContent::SpaceAround => {
-                    let gap = extra_space / children.len() as f32;
-                    for (i, child) in children.iter_mut().enumerate() {
-                        child.content.y += gap * (i as f32 + 0.5);
-                    }
-                }
-                JustifyContent::SpaceEvenly => {
-                    let gap = extra_space / (children.len() + 1) as f32;
-                    for (i, child) in children.iter_mut().enumerate() {
-                        child.content.y += gap * (i as f32 + 1.0);
-                    }
-                }
+            *child = self.layout_element(&node, shrunk_block);
+            // An explicit width is overridden by shrinking too, and the
+            // contents are laid out again at the shrunk width
+            if child.content.width != width {
+                child.content.width = width;
+                child.children.clear();
+                let content_box = child.content;
+                self.layout_children(child, content_box);
+                self.calculate_box_dimensions(child);
             }
         }
     }
     
     /// Layout children using CSS Grid
     fn layout_grid_children(&self, parent: &mut LayoutBox, containing_block: Dimensions) {
-        let grid_template_columns = parent.styles.grid_template_columns.clone().unwrap_or_else(|| {
+        let grid_template_columns = parent.styles.grid.template_columns.clone().unwrap_or_else(|| {
             vec![GridTrack::Fractional(1.0)]
         });
-        let grid_template_rows = parent.styles.grid_template_rows.clone().unwrap_or_else(|| {
+        let grid_template_rows = parent.styles.grid.template_rows.clone().unwrap_or_else(|| {
             vec![GridTrack::Fractional(1.0)]
         });
-        let grid_gap = parent.styles.grid_gap.unwrap_or(0.0);
+        let grid_gap = parent.styles.grid.gap.unwrap_or(0.0);
         
         // Calculate grid dimensions
         let (column_widths, row_heights) = self.calculate_grid_tracks(
@@ -1161,11 +3433,7 @@ impl LayoutEngine {
         );
         
         // First pass: layout all children
-        let mut children = Vec::new();
-        for child_node in parent.node.children.borrow().iter() {
-            let child_layout = self.layout_element(child_node, containing_block);
-            children.push(child_layout);
-        }
+        let mut children = self.layout_child_boxes(&parent.node, containing_block);
         
         // Position children in grid
         let mut child_index = 0;
@@ -1289,6 +3557,17 @@ impl LayoutEngine {
         (column_widths, row_heights)
     }
     
+    /// Read the time from `clock` instead of the thread's clock
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+    }
+
+    /// Update the animations of a layout tree to the current time by the
+    /// engine's clock
+    pub fn tick_animations(&self, layout_box: &mut LayoutBox) {
+        self.update_animations(layout_box, self.clock.now());
+    }
+
     /// Update animations for a layout tree
     pub fn update_animations(&self, layout_box: &mut LayoutBox, current_time: Instant) {
         self.update_element_animation(layout_box, current_time);
@@ -1300,6 +3579,10 @@ impl LayoutEngine {
     }
     
     /// Update animation for a single element
+    /// 
+    /// When the user prefers reduced motion, animations are non-essential:
+    /// finite ones jump to their end state and infinite ones stay on their
+    /// first frame.
     fn update_element_animation(&self, layout_box: &mut LayoutBox, current_time: Instant) {
         let styles = &layout_box.styles;
         
@@ -1310,6 +3593,15 @@ impl LayoutEngine {
             let iteration_count = styles.animation_iteration_count.unwrap_or(1.0);
             let play_state = styles.animation_play_state.clone().unwrap_or(AnimationPlayState::Running);
             
+            if self.style_matcher.media_features().reduced_motion {
+                let finite = iteration_count.is_finite();
+                layout_box.animation_state.is_running = false;
+                layout_box.animation_state.start_time = None;
+                layout_box.animation_state.progress = if finite { 1.0 } else { 0.0 };
+                layout_box.animation_state.iteration_count = if finite { iteration_count } else { 0.0 };
+                return;
+            }
+            
             // Initialize animation if not started
             if !layout_box.animation_state.is_running && play_state == AnimationPlayState::Running {
                 layout_box.animation_state.is_running = true;
@@ -1323,10 +3615,18 @@ impl LayoutEngine {
                 if play_state == AnimationPlayState::Running && !layout_box.animation_state.is_paused {
                     let elapsed = current_time.duration_since(start_time).as_secs_f32();
                     let total_duration = duration * iteration_count;
+                    let timing = EffectTiming {
+                        duration: duration as f64 * 1000.0,
+                        iterations: iteration_count as f64,
+                        direction: styles.animation_direction.clone().unwrap_or(AnimationDirection::Normal),
+                        ..EffectTiming::default()
+                    };
                     
                     if elapsed < total_duration {
-                        layout_box.animation_state.progress = (elapsed / duration) % 1.0;
-                        layout_box.animation_state.iteration_count = (elapsed / duration).floor();
+                        if let Some(iteration) = timing.iteration_progress(elapsed as f64 * 1000.0) {
+                            layout_box.animation_state.progress = iteration.progress as f32;
+                            layout_box.animation_state.iteration_count = iteration.iteration as f32;
+                        }
                         
                         // Apply animation transformations
                         self.apply_animation_transforms(layout_box);
@@ -1347,16 +3647,7 @@ impl LayoutEngine {
         let timing_function = &layout_box.styles.transition_timing_function;
         
         // Apply easing function
-        let eased_progress = match timing_function {
-            Some(TimingFunction::Ease) => self.ease_in_out_cubic(progress),
-            Some(TimingFunction::Linear) => progress,
-            Some(TimingFunction::EaseIn) => self.ease_in_cubic(progress),
-            Some(TimingFunction::EaseOut) => self.ease_out_cubic(progress),
-            Some(TimingFunction::EaseInOut) => self.ease_in_out_cubic(progress),
-            Some(TimingFunction::CubicBezier(c1, c2, c3, c4)) => self.cubic_bezier(progress, *c1, *c2, *c3, *c4),
-            Some(TimingFunction::Steps(steps)) => self.step_function(progress, *steps),
-            None => progress,
-        };
+        let eased_progress = timing_function.map_or(progress, |timing_function| timing_function.apply(progress));
         
         // Apply simple transform animations (scale, rotate, translate)
         // This is a simplified implementation - real CSS animations would be more complex
@@ -1371,41 +3662,6 @@ impl LayoutEngine {
         // during rendering rather than modifying the layout dimensions
     }
     
-    /// Easing functions for animations
-    fn ease_in_cubic(&self, t: f32) -> f32 {
-        t * t * t
-    }
-    
-    fn ease_out_cubic(&self, t: f32) -> f32 {
-        let f = t - 1.0;
-        f * f * f + 1.0
-    }
-    
-    fn ease_in_out_cubic(&self, t: f32) -> f32 {
-        if t < 0.5 {
-            4.0 * t * t * t
-        } else {
-            let f = 2.0 * t - 2.0;
-            0.5 * f * f * f + 1.0
-        }
-    }
-    
-    fn cubic_bezier(&self, t: f32, c1: f32, _c2: f32, c3: f32, _c4: f32) -> f32 {
-        // Simplified cubic bezier implementation
-        // Real implementation would use more sophisticated math
-        let t2 = t * t;
-        let t3 = t2 * t;
-        let mt = 1.0 - t;
-        let mt2 = mt * mt;
-        let _mt3 = mt2 * mt;
-        
-        3.0 * mt2 * t * c1 + 3.0 * mt * t2 * c3 + t3
-    }
-    
-    fn step_function(&self, t: f32, steps: i32) -> f32 {
-        (t * steps as f32).floor() / steps as f32
-    }
-    
     /// Calculate the total box dimensions including padding, border, and margin
     fn calculate_box_dimensions(&self, layout_box: &mut LayoutBox) {
         let styles = &layout_box.styles;
@@ -1414,24 +3670,24 @@ impl LayoutEngine {
         layout_box.padding = Dimensions::new(
             layout_box.content.x,
             layout_box.content.y,
-            layout_box.content.width + styles.padding.left + styles.padding.right,
-            layout_box.content.height + styles.padding.top + styles.padding.bottom,
+            layout_box.content.width + styles.box_model.padding.left + styles.box_model.padding.right,
+            layout_box.content.height + styles.box_model.padding.top + styles.box_model.padding.bottom,
         );
         
         // Calculate border dimensions
         layout_box.border = Dimensions::new(
             layout_box.padding.x,
             layout_box.padding.y,
-            layout_box.padding.width + styles.border.left + styles.border.right,
-            layout_box.padding.height + styles.border.top + styles.border.bottom,
+            layout_box.padding.width + styles.box_model.border.left + styles.box_model.border.right,
+            layout_box.padding.height + styles.box_model.border.top + styles.box_model.border.bottom,
         );
         
         // Calculate margin dimensions
         layout_box.margin = Dimensions::new(
             layout_box.border.x,
             layout_box.border.y,
-            layout_box.border.width + styles.margin.left + styles.margin.right,
-            layout_box.border.height + styles.margin.top + styles.margin.bottom,
+            layout_box.border.width + styles.box_model.margin.left + styles.box_model.margin.right,
+            layout_box.border.height + styles.box_model.margin.top + styles.box_model.margin.bottom,
         );
     }
 }
@@ -1445,37 +3701,25 @@ mod tests {
     fn test_computed_styles() {
         let styles = ComputedStyles {
             display: DisplayType::Block,
-            width: Some(100.0),
-            height: Some(50.0),
-            margin: BoxSides::new(10.0),
-            border: BoxSides::new(1.0),
-            padding: BoxSides::new(5.0),
+            box_model: Rc::new(BoxStyles {
+                width: Some(100.0),
+                height: Some(50.0),
+                margin: BoxSides::new(10.0),
+                border: BoxSides::new(1.0),
+                padding: BoxSides::new(5.0),
+                ..BoxStyles::default()
+            }),
+            font: Rc::new(FontStyles {
+                size: Some(16.0),
+                family: Some("Arial".to_string()),
+                weight: Some("bold".to_string()),
+                ..FontStyles::default()
+            }),
+            flex: FlexStyles::initial(),
+            grid: GridStyles::initial(),
             background_color: Some("red".to_string()),
             color: Some("white".to_string()),
-            font_size: Some(16.0),
-            font_family: Some("Arial".to_string()),
-            font_weight: Some("bold".to_string()),
             text_align: Some("center".to_string()),
-            // Flexbox properties
-            flex_direction: None,
-            flex_wrap: None,
-            justify_content: None,
-            align_items: None,
-            align_content: None,
-            flex_grow: None,
-            flex_shrink: None,
-            flex_basis: None,
-            align_self: None,
-            // Grid properties
-            grid_template_columns: None,
-            grid_template_rows: None,
-            grid_gap: None,
-            grid_column_gap: None,
-            grid_row_gap: None,
-            grid_column_start: None,
-            grid_column_end: None,
-            grid_row_start: None,
-            grid_row_end: None,
             // Animation properties
             transition_duration: None,
             transition_delay: None,
@@ -1487,10 +3731,47 @@ mod tests {
             animation_direction: None,
             animation_fill_mode: None,
             animation_play_state: None,
+            // Containment properties
+            content_visibility: ContentVisibility::Visible,
+            contain_intrinsic_size: None,
+            position: Position::Static,
+            inset: Insets::default(),
+            float: Float::None,
+            object_fit: ObjectFit::Fill,
+            object_position: ObjectPosition::default(),
+            filter: Vec::new(),
+            mix_blend_mode: BlendMode::Normal,
+            isolation: Isolation::Auto,
+            will_change: WillChange::default(),
+            content: Vec::new(),
+            counter_reset: Vec::new(),
+            counter_increment: Vec::new(),
+            counter_set: Vec::new(),
+            writing_mode: None,
+            pending_sides: Vec::new(),
+            background_image: None,
+            background_clip: BackgroundBox::BorderBox,
+            background_origin: BackgroundBox::PaddingBox,
+            background_attachment: BackgroundAttachment::Scroll,
+            border_radius: 0.0,
+            break_before: BreakBetween::Auto,
+            break_after: BreakBetween::Auto,
+            break_inside: BreakInside::Auto,
+            widows: None,
+            orphans: None,
+            clip_path: None,
+            mask_image: None,
+            mask_mode: MaskMode::Alpha,
+            forced_color_adjust: None,
+            direction: None,
+            unicode_bidi: UnicodeBidi::Normal,
+            hyphens: None,
+            visibility: None,
+            lang: None,
         };
         
         assert_eq!(styles.display, DisplayType::Block);
-        assert_eq!(styles.width, Some(100.0));
+        assert_eq!(styles.box_model.width, Some(100.0));
         assert_eq!(styles.background_color, Some("red".to_string()));
     }
 
@@ -1532,7 +3813,767 @@ mod tests {
         
         let styles = matcher.compute_styles(&h1);
         assert_eq!(styles.color, Some("red".to_string()));
-        assert_eq!(styles.font_size, Some(24.0));
+        assert_eq!(styles.font.size, Some(24.0));
     }
-}
 
+    #[test]
+    fn test_hidden_and_closed_details() {
+        let doc = Document::new();
+        let div = doc.create_element("div");
+        let details = doc.create_element("details");
+        let summary = doc.create_element("summary");
+        let content = doc.create_element("p");
+        doc.root.append_child(&div);
+        doc.root.append_child(&details);
+        details.append_child(&summary);
+        details.append_child(&content);
+        
+        let matcher = StyleMatcher::new(parse_css(""));
+        assert_eq!(matcher.compute_styles(&div).display, DisplayType::Block);
+        assert_eq!(matcher.compute_styles(&summary).display, DisplayType::Block);
+        assert_eq!(matcher.compute_styles(&content).display, DisplayType::None);
+        
+        div.set_attribute("hidden", "");
+        details.set_attribute("open", "");
+        assert_eq!(matcher.compute_styles(&div).display, DisplayType::None);
+        assert_eq!(matcher.compute_styles(&content).display, DisplayType::Block);
+    }
+
+    #[test]
+    fn test_closed_details_content_ignores_author_display() {
+        let doc = Document::new();
+        let details = doc.create_element("details");
+        let summary = doc.create_element("summary");
+        let content = doc.create_element("p");
+        doc.root.append_child(&details);
+        details.append_child(&summary);
+        details.append_child(&content);
+        content.set_attribute("style", "display: flex");
+        
+        let matcher = StyleMatcher::new(parse_css("p { display: block; }"));
+        assert_eq!(matcher.compute_styles(&content).display, DisplayType::None);
+        
+        details.set_attribute("open", "");
+        assert_eq!(matcher.compute_styles(&content).display, DisplayType::Flex);
+    }
+
+    #[test]
+    fn test_hidden_input_ignores_author_display() {
+        let doc = Document::new();
+        let input = doc.create_node(NodeType::Element {
+            tag_name: "input".to_string(),
+            attributes: [("type".to_string(), "hidden".to_string())].into_iter().collect(),
+        });
+        doc.root.append_child(&input);
+        
+        let matcher = StyleMatcher::new(parse_css("input { display: inline-block; }"));
+        assert_eq!(matcher.compute_styles(&input).display, DisplayType::None);
+    }
+
+    #[test]
+    fn test_form_control_intrinsic_size() {
+        let doc = Document::new();
+        let input = doc.create_element("input");
+        let checkbox = doc.create_node(NodeType::Element {
+            tag_name: "input".to_string(),
+            attributes: [("type".to_string(), "checkbox".to_string())].into_iter().collect(),
+        });
+        
+        let matcher = StyleMatcher::new(parse_css(""));
+        let styles = matcher.compute_styles(&input);
+        assert_eq!(styles.display, DisplayType::InlineBlock);
+        assert_eq!(styles.box_model.width, Some(160.0));
+        assert_eq!(styles.box_model.height, Some(20.0));
+        
+        let styles = matcher.compute_styles(&checkbox);
+        assert_eq!(styles.box_model.width, Some(13.0));
+        assert_eq!(styles.box_model.height, Some(13.0));
+    }
+
+    #[test]
+    fn test_presentational_hints_and_style_attribute() {
+        let doc = Document::new();
+        let img = doc.create_element("img");
+        img.set_attribute("width", "120");
+        img.set_attribute("height", "80px");
+        img.set_attribute("style", "height: 40px");
+        let font = doc.create_element("font");
+        font.set_attribute("size", "1");
+        
+        let matcher = StyleMatcher::new(parse_css("img {\n  width: 200px;\n}"));
+        let styles = matcher.compute_styles(&img);
+        assert_eq!(styles.box_model.width, Some(200.0));
+        assert_eq!(styles.box_model.height, Some(40.0));
+        
+        let styles = matcher.compute_styles(&font);
+        assert_eq!(styles.font.size, Some(10.0));
+    }
+
+    #[test]
+    fn test_hit_test_and_hover_restyle() {
+        let doc = Document::new();
+        let nav = doc.create_element("div");
+        nav.set_attribute("style", "width: 200px; height: 40px");
+        let link = doc.create_element("a");
+        link.set_attribute("style", "width: 50px; height: 20px");
+        doc.root.append_child(&nav);
+        nav.append_child(&link);
+        
+        let stylesheet = parse_css("a:hover {\n  color: #ff0000;\n}");
+        let engine = LayoutEngine::new(stylesheet.clone());
+        let mut layout = engine.layout_document(&doc);
+        
+        let hit = layout.hit_test(10.0, 10.0).expect("link under the pointer");
+        assert_eq!(hit.id, link.id);
+        assert_eq!(layout.hit_test(150.0, 30.0).map(|node| node.id), Some(nav.id));
+        assert!(layout.hit_test(500.0, 500.0).is_none());
+        
+        // Hovering flips the state bits; only the link is invalidated
+        let mut interaction = dom::element_state::InteractionState::new();
+        let invalidations: HashMap<u64, StyleInvalidation> = interaction
+            .set_hovered(Some(&hit))
+            .iter()
+            .map(|change| (change.node.id, css_parser::invalidation_for_state_change(&stylesheet, &change.node, change.changed)))
+            .filter(|(_, invalidation)| *invalidation != StyleInvalidation::None)
+            .collect();
+        assert_eq!(invalidations.len(), 1);
+        
+        let matcher = StyleMatcher::new(stylesheet);
+        let outcome = matcher.restyle_layout_tree(&mut layout, &invalidations);
+        assert_eq!(outcome.restyled_boxes, 1);
+        assert_eq!(outcome.changed_boxes, 1);
+        assert!(!outcome.needs_layout);
+    }
+
+    #[test]
+    fn test_generated_content_counters_and_attr() {
+        fn generated_text(layout_box: &LayoutBox, texts: &mut Vec<String>) {
+            if layout_box.generated_by().is_some() {
+                texts.push(layout_box.fragments.iter().map(|fragment| fragment.text.as_str()).collect::<Vec<_>>().join(" "));
+            }
+            layout_box.children.iter().for_each(|child| generated_text(child, texts));
+        }
+
+        let document = Document::new();
+        let list = document.create_element("ol");
+        document.root.append_child(&list);
+        let items: Vec<Rc<Node>> = ["first", "second"].iter().map(|label| {
+            let item = document.create_element("li");
+            item.set_attribute("data-label", label);
+            list.append_child(&item);
+            item
+        }).collect();
+        let nested = document.create_element("ol");
+        items[1].append_child(&nested);
+        nested.append_child(&document.create_element("li"));
+
+        let stylesheet = parse_css("ol {\n  counter-reset: item;\n}\nli {\n  counter-increment: item;\n}\nli::before {\n  content: counters(item, \".\") \" \" attr(data-label, \"none\");\n}");
+        let engine = LayoutEngine::new(stylesheet.clone());
+        let mut layout = engine.layout_document(&document);
+        let mut texts = Vec::new();
+        generated_text(&layout, &mut texts);
+        assert_eq!(texts, ["1 first", "2 second", "2.1 none"]);
+
+        // The generated box belongs to its element
+        let marker = &layout.children[0].children[0].children[0];
+        assert_eq!(marker.generated_by(), Some(PseudoElement::Before));
+        assert_eq!(marker.node.id, items[0].id);
+
+        // Changing the attribute the content reads invalidates its element
+        let matcher = StyleMatcher::new(stylesheet);
+        assert_eq!(matcher.invalidation_for_attribute_change(&items[0], "data-label"), StyleInvalidation::Element);
+        assert_eq!(matcher.invalidation_for_attribute_change(&items[0], "title"), StyleInvalidation::None);
+        assert_eq!(matcher.invalidation_for_attribute_change(&list, "data-label"), StyleInvalidation::None);
+        items[0].set_attribute("data-label", "changed");
+        let changed = Mutation::Attribute { element: items[0].id, name: "data-label".to_string() };
+        let invalidations = matcher.invalidations_for_mutation(&items[0], &changed);
+        assert_eq!(invalidations, HashMap::from([(items[0].id, StyleInvalidation::Element)]));
+        let title = Mutation::Attribute { element: items[0].id, name: "title".to_string() };
+        assert!(matcher.invalidations_for_mutation(&items[0], &title).is_empty());
+        let outcome = matcher.restyle_layout_tree(&mut layout, &invalidations);
+        assert!(outcome.needs_layout);
+        let mut texts = Vec::new();
+        generated_text(&engine.layout_document(&document), &mut texts);
+        assert_eq!(texts[0], "1 changed");
+    }
+
+    #[test]
+    fn test_pages_honor_breaks_widows_and_orphans() {
+        let document = Document::new();
+        let body = document.create_element("body");
+        document.root.append_child(&body);
+        let heading = document.create_element("h1");
+        heading.append_child(&document.create_text_node("Title"));
+        body.append_child(&heading);
+        let paragraph = document.create_element("p");
+        paragraph.append_child(&document.create_text_node(&"word ".repeat(60)));
+        body.append_child(&paragraph);
+        let chapter = document.create_element("h2");
+        chapter.append_child(&document.create_text_node("Chapter"));
+        body.append_child(&chapter);
+
+        let css = "@page { size: 300px 150px; margin: 0 } p { widows: 3; orphans: 3 } h2 { break-before: page }";
+        let mut engine = LayoutEngine::new(css_parser::CSSParser::new(css.to_string()).parse_stylesheet().unwrap());
+        engine.set_media_features(MediaFeatures { viewport_width: 1024.0, ..MediaFeatures::default() });
+        let paged = engine.layout_pages(&document);
+        assert_eq!(engine.style_matcher.media_features().viewport_width, 1024.0);
+        assert!(paged.pages.iter().all(|page| page.style.width == 300.0 && page.style.height == 150.0));
+
+        // The paragraph's lines, in document coordinates
+        let boxes = geometry::border_boxes(&paged.layout);
+        let text = &paged.layout.children[0].children[1].children[0];
+        let text_top = boxes[&text.node.id].y;
+        let lines: Vec<f32> = text.fragments.iter().map(|fragment| text_top + fragment.y).collect();
+        assert!(lines.len() > 6);
+        let split_page = |paged: &PagedLayout| {
+            let page = paged.pages.iter().find(|page| page.bottom > lines[0] && page.bottom < *lines.last().unwrap()).unwrap().clone();
+            (page.bottom, lines.iter().filter(|line| **line < page.bottom).count())
+        };
+        let (bottom, carried) = split_page(&paged);
+        assert!(lines.contains(&bottom));
+        assert_eq!(lines.len() - carried, 3);
+
+        // With one widow allowed the page keeps more of the paragraph
+        paragraph.set_attribute("style", "widows: 1");
+        let (_, kept) = split_page(&engine.layout_pages(&document));
+        assert!(kept > carried);
+
+        // The chapter starts a page, and the last page ends with its line
+        let chapter_top = boxes[&chapter.id].y;
+        let last = paged.pages.last().unwrap();
+        assert!(paged.pages.iter().any(|page| page.top == chapter_top));
+        assert!(last.bottom > chapter_top && last.bottom - chapter_top < 150.0);
+        assert_eq!(last.number, paged.pages.len());
+    }
+
+    #[test]
+    fn test_logical_properties_follow_direction() {
+        let document = Document::new();
+        let section = document.create_element("section");
+        section.set_attribute("dir", "rtl");
+        let paragraph = document.create_element("p");
+        document.root.append_child(&section);
+        section.append_child(&paragraph);
+
+        let matcher = StyleMatcher::new(parse_css("p {\n  margin-inline: 10px 20px;\n  padding-block-start: 4px;\n  inset-inline-start: 5px;\n  border-inline-end-width: 2px;\n}"));
+        let styles = matcher.compute_styles(&paragraph);
+        assert_eq!((styles.box_model.margin.left, styles.box_model.margin.right), (20.0, 10.0));
+        assert_eq!((styles.box_model.padding.top, styles.box_model.padding.bottom), (4.0, 0.0));
+        assert_eq!((styles.inset.left, styles.inset.right), (None, Some(5.0)));
+        assert_eq!(styles.box_model.border.left, 2.0);
+        assert!(styles.pending_sides.is_empty());
+
+        // Vertical writing modes turn the inline axis
+        paragraph.set_attribute("style", "writing-mode: vertical-rl; direction: ltr");
+        let styles = matcher.compute_styles(&paragraph);
+        assert_eq!((styles.box_model.margin.top, styles.box_model.margin.bottom, styles.box_model.margin.left), (10.0, 20.0, 0.0));
+        assert_eq!(styles.box_model.padding.right, 4.0);
+
+        // The later of a physical and a logical declaration wins
+        paragraph.set_attribute("style", "margin: 3px");
+        assert_eq!(matcher.compute_styles(&paragraph).box_model.margin, BoxSides::new(3.0));
+        paragraph.set_attribute("style", "margin-inline-end: 7px");
+        assert_eq!(matcher.compute_styles(&paragraph).box_model.margin.left, 7.0);
+    }
+
+    #[test]
+    fn test_math_functions_in_styles() {
+        let document = Document::new();
+        let container = document.create_element("div");
+        container.set_attribute("style", "width: 400px");
+        let paragraph = document.create_element("p");
+        document.root.append_child(&container);
+        container.append_child(&paragraph);
+
+        let stylesheet = parse_css("p {\n  font-size: clamp(1rem, 2.5vw, 1.5rem);\n  width: calc(50% - 2em);\n  height: max(10px, 1vh * 3);\n}");
+        let matcher = StyleMatcher::new(stylesheet.clone());
+        let styles = matcher.compute_styles(&paragraph);
+        assert_eq!(styles.font.size, Some(20.0));
+        assert_eq!(styles.box_model.height, Some(18.0));
+        assert_eq!(styles.box_model.width, None);
+        assert!(styles.box_model.width_expression.is_some());
+
+        // The percentage resolves against the containing block at layout
+        let mut layout = LayoutEngine::new(stylesheet).layout_document(&document);
+        assert_eq!(layout.children[0].children[0].content.width, 160.0);
+        let outcome = matcher.restyle_layout_tree(&mut layout, &HashMap::from([(paragraph.id, StyleInvalidation::Element)]));
+        assert_eq!(outcome.changed_boxes, 0);
+
+        // A later plain length overrides the expression
+        paragraph.set_attribute("style", "width: 30px");
+        assert_eq!(matcher.compute_styles(&paragraph).box_model.width_expression, None);
+        assert_eq!(matcher.compute_styles(&paragraph).box_model.width, Some(30.0));
+    }
+
+    #[test]
+    fn test_font_size_keywords_and_relative_units() {
+        let document = Document::new();
+        let section = document.create_element("section");
+        let heading = document.create_element("h2");
+        let note = document.create_element("small");
+        document.root.append_child(&section);
+        section.append_child(&heading);
+        heading.append_child(&note);
+
+        let css = "section { font-size: x-large } h2 { margin: 1em; font-size: larger } small { font-size: 50%; padding: 2ch; margin: 2ex; font-family: \"monospace\" }";
+        let matcher = StyleMatcher::new(css_parser::CSSParser::new(css.to_string()).parse_stylesheet().unwrap());
+        assert_eq!(matcher.compute_styles(&section).font.size, Some(24.0));
+        let heading_styles = matcher.compute_styles(&heading);
+        assert_eq!(heading_styles.font.size, Some(24.0 * FONT_SIZE_STEP));
+        // `em` refers to the element's own font size, wherever it is set
+        assert_eq!(heading_styles.box_model.margin.top, 24.0 * FONT_SIZE_STEP);
+
+        // `ch` and `ex` come from the element's own face
+        let note_styles = matcher.compute_styles(&note);
+        let size = 12.0 * FONT_SIZE_STEP;
+        assert_eq!(note_styles.font.size, Some(size));
+        let metrics = FontRegistry::default().metrics("monospace");
+        assert_eq!(note_styles.box_model.padding.left, 2.0 * size * metrics.zero_advance);
+        assert_eq!(note_styles.box_model.margin.left, 2.0 * size * metrics.x_height);
+
+        // Children inherit the computed size
+        note.append_child(&document.create_text_node("fine print"));
+        let text = note.children.borrow()[0].clone();
+        assert_eq!(matcher.compute_styles(&text).font.size, Some(size));
+    }
+
+    #[test]
+    fn test_minimum_font_size_clamps_computed_sizes() {
+        let document = Document::new();
+        let footer = document.create_element("footer");
+        let note = document.create_element("small");
+        let heading = document.create_element("h1");
+        document.root.append_child(&footer);
+        footer.append_child(&note);
+        footer.append_child(&heading);
+
+        let css = "footer { font-size: 10px } small { font-size: 50%; margin: 1em } h1 { font-size: 3em }";
+        let mut matcher = StyleMatcher::new(css_parser::CSSParser::new(css.to_string()).parse_stylesheet().unwrap());
+        assert_eq!(matcher.compute_styles(&note).font.size, Some(5.0));
+
+        matcher.set_minimum_font_size(12.0);
+        assert_eq!(matcher.compute_styles(&footer).font.size, Some(12.0));
+        let note_styles = matcher.compute_styles(&note);
+        assert_eq!(note_styles.font.size, Some(12.0));
+        // `em` lengths resolve against the size before it was raised
+        assert_eq!(note_styles.box_model.margin.top, 6.0);
+        // Children inherit the raised size, and sizes above the minimum are
+        // left alone
+        assert_eq!(matcher.compute_styles(&heading).font.size, Some(36.0));
+
+        matcher.set_minimum_font_size(0.0);
+        assert_eq!(matcher.compute_styles(&footer).font.size, Some(10.0));
+    }
+
+    #[test]
+    fn test_display_contents_promotes_children() {
+        let document = Document::new();
+        let list = document.create_element("div");
+        let wrapper = document.create_element("div");
+        wrapper.set_attribute("style", "display: contents");
+        document.root.append_child(&list);
+        list.append_child(&wrapper);
+        for _ in 0..2 {
+            let item = document.create_element("p");
+            item.set_attribute("style", "height: 20px");
+            wrapper.append_child(&item);
+        }
+
+        let layout = LayoutEngine::new(parse_css("")).layout_document(&document);
+        let list_box = &layout.children[0];
+        assert_eq!(list_box.children.len(), 2);
+        assert_eq!(list_box.children[0].node.tag_name(), Some("p"));
+        assert!(list_box.children[1].content.y > list_box.children[0].content.y);
+    }
+
+    #[test]
+    fn test_content_visibility_skips_offscreen_sections() {
+        let document = Document::new();
+        let article = document.create_element("article");
+        document.root.append_child(&article);
+        let mut sections = Vec::new();
+        for _ in 0..40 {
+            let section = document.create_element("section");
+            section.set_attribute("style", "content-visibility: auto; contain-intrinsic-size: 400px");
+            for _ in 0..10 {
+                let paragraph = document.create_element("p");
+                paragraph.set_attribute("style", "height: 40px");
+                section.append_child(&paragraph);
+            }
+            article.append_child(&section);
+            sections.push(section);
+        }
+        let hidden = document.create_element("div");
+        hidden.set_attribute("style", "content-visibility: hidden; height: 10px");
+        hidden.append_child(&document.create_element("p"));
+        article.append_child(&hidden);
+
+        let mut engine = LayoutEngine::new(parse_css(""));
+        let layout = engine.layout_document_for_viewport(&document, Dimensions::new(0.0, 0.0, 800.0, 600.0));
+        let section_boxes = &layout.children[0].children;
+
+        // Sections near the viewport are laid out, the rest keep their placeholder
+        assert_eq!(section_boxes[0].children.len(), 10);
+        assert!(section_boxes[39].children.is_empty());
+        assert_eq!(section_boxes[39].content.height, 400.0);
+        assert!(section_boxes[40].children.is_empty());
+
+        let stats = engine.containment_stats();
+        assert!(stats.active_auto_subtrees >= 1);
+        assert_eq!(stats.skipped_subtrees, 41 - stats.active_auto_subtrees);
+        assert_eq!(stats.skipped_nodes, (40 - stats.active_auto_subtrees) * 10 + 1);
+
+        // Focus keeps a far-away section rendered
+        sections[30].children.borrow()[0].set_element_state(dom::ElementState::FOCUS, true);
+        assert!(engine.update_content_visibility(&layout, Dimensions::new(0.0, 0.0, 800.0, 600.0)));
+        let layout = engine.layout_document(&document);
+        assert_eq!(layout.children[0].children[30].children.len(), 10);
+    }
+
+    #[test]
+    fn test_lazy_images_near_viewport() {
+        let document = Document::new();
+        let image = |loading: &str, style: &str| {
+            let image = document.create_element("img");
+            image.set_attribute("loading", loading);
+            image.set_attribute("width", "100");
+            image.set_attribute("height", "100");
+            image.set_attribute("style", style);
+            image
+        };
+        let top = image("lazy", "display: block");
+        let eager = image("eager", "display: block");
+        let hidden = image("lazy", "display: none");
+        let spacer = document.create_element("div");
+        spacer.set_attribute("style", "height: 5000px");
+        let bottom = image("LAZY", "display: block");
+        for node in [&top, &eager, &hidden, &spacer, &bottom] {
+            document.root.append_child(node);
+        }
+
+        let engine = LayoutEngine::new(parse_css(""));
+        let layout = engine.layout_document(&document);
+        let near = lazy_images_near_viewport(&layout, Dimensions::new(0.0, 0.0, 800.0, 600.0));
+        assert_eq!(near, HashSet::from([top.id]));
+
+        // Scrolling down brings the last image within the margin
+        let bottom_y = geometry::border_boxes(&layout)[&bottom.id].y;
+        let near = lazy_images_near_viewport(&layout, Dimensions::new(0.0, bottom_y - 1000.0, 800.0, 600.0));
+        assert_eq!(near, HashSet::from([bottom.id]));
+    }
+
+    #[test]
+    fn test_sticky_constraints_recorded_by_layout() {
+        let document = Document::new();
+        let section = document.create_element("section");
+        section.set_attribute("style", "height: 1000px");
+        let header = document.create_element("header");
+        header.set_attribute("style", "position: sticky; top: 0; height: 50px");
+        document.root.append_child(&section);
+        section.append_child(&header);
+
+        let engine = LayoutEngine::new(parse_css(""));
+        let layout = engine.layout_document(&document);
+        let constraints = engine.sticky_constraints();
+        assert_eq!(constraints.len(), 1);
+        assert_eq!(constraints[0].node_id, header.id);
+        assert_eq!(constraints[0].inset.top, Some(0.0));
+        assert_eq!(constraints[0].containing_rect.height, 1000.0);
+
+        // Scrolling moves the header without another layout
+        let mut compositor = ScrollCompositor::new(constraints, Dimensions::new(0.0, 0.0, 800.0, 600.0), (800.0, 2000.0));
+        assert!(compositor.scroll_to(0.0, 300.0));
+        assert_eq!(compositor.sticky_offset(header.id), (0.0, 300.0));
+        assert_eq!(layout.children[0].children[0].content.y, 0.0);
+    }
+
+    #[test]
+    fn test_first_letter_and_first_line_fragments() {
+        let css = "p::first-line {\n  color: #0000ff;\n}\np::first-letter {\n  float: left;\n  font-size: 48px;\n}";
+        let document = Document::new();
+        let paragraph = document.create_element("p");
+        paragraph.append_child(&document.create_text_node("Once upon a time"));
+        let other = document.create_element("div");
+        other.append_child(&document.create_text_node("Plain text"));
+        document.root.append_child(&paragraph);
+        document.root.append_child(&other);
+
+        let engine = LayoutEngine::new(parse_css(css));
+        let layout = engine.layout_document(&document);
+
+        let text_box = &layout.children[0].children[0];
+        let letter = &text_box.fragments[0];
+        assert_eq!(letter.pseudo, Some(PseudoElement::FirstLetter));
+        assert_eq!(letter.text, "O");
+        assert_eq!(letter.styles.font.size, Some(48.0));
+        // The letter inherits from the first line
+        assert_eq!(letter.styles.color.as_deref(), Some("#0000ff"));
+
+        let line = &text_box.fragments[1];
+        assert_eq!(line.pseudo, Some(PseudoElement::FirstLine));
+        assert_eq!(line.text, "nce upon a time");
+        assert_eq!(line.x, letter.width);
+        // The drop cap is taller than the line
+        assert_eq!(text_box.content.height, 48.0 * inline::LINE_HEIGHT_FACTOR);
+
+        // The element itself and other blocks are unaffected
+        assert_eq!(layout.children[0].styles.float, Float::None);
+        assert!(layout.children[1].children[0].fragments.iter().all(|fragment| fragment.pseudo.is_none()));
+    }
+
+    fn flex_container(document: &Document, style: &str, items: &[&str]) -> Rc<Node> {
+        let container = document.create_element("div");
+        container.set_attribute("style", style);
+        for item_style in items {
+            let item = document.create_element("div");
+            item.set_attribute("style", item_style);
+            container.append_child(&item);
+        }
+        document.root.append_child(&container);
+        container
+    }
+
+    #[test]
+    fn test_flex_order_and_reverse_directions() {
+        let document = Document::new();
+        let row = flex_container(
+            &document,
+            "display: flex; flex-direction: row-reverse; width: 300px",
+            &["width: 50px; height: 20px", "width: 50px; height: 20px; order: -1", "width: 50px; height: 20px"],
+        );
+        let column = flex_container(
+            &document,
+            "display: flex; flex-direction: column-reverse",
+            &["height: 20px", "height: 30px"],
+        );
+
+        let layout = LayoutEngine::new(parse_css("")).layout_document(&document);
+
+        // Ordered first, and placed from the main-end
+        let row_box = &layout.children[0];
+        assert!(Rc::ptr_eq(&row_box.children[0].node, &row.children.borrow()[1]));
+        let xs: Vec<f32> = row_box.children.iter().map(|child| child.content.x).collect();
+        assert_eq!(xs, vec![250.0, 200.0, 150.0]);
+
+        // An auto-height column grows to fit, stacking from the bottom
+        let column_box = &layout.children[1];
+        assert!(Rc::ptr_eq(&column_box.children[0].node, &column.children.borrow()[0]));
+        assert_eq!(column_box.children[0].content.y, 30.0);
+        assert_eq!(column_box.children[1].content.y, 0.0);
+        assert_eq!(column_box.content.height, 50.0);
+    }
+
+    #[test]
+    fn test_flex_align_content_distributes_lines() {
+        let document = Document::new();
+        let item = "width: 60px; height: 20px";
+        flex_container(
+            &document,
+            "display: flex; flex-wrap: wrap; align-content: space-between; width: 100px; height: 200px",
+            &[item, item, item, item],
+        );
+        flex_container(
+            &document,
+            "display: flex; flex-wrap: wrap-reverse; align-content: flex-start; width: 100px; height: 100px",
+            &[item, item],
+        );
+
+        let layout = LayoutEngine::new(parse_css("")).layout_document(&document);
+
+        let ys: Vec<f32> = layout.children[0].children.iter().map(|child| child.content.y).collect();
+        assert_eq!(ys, vec![0.0, 60.0, 120.0, 180.0]);
+
+        // Lines are stacked from the cross-end
+        let ys: Vec<f32> = layout.children[1].children.iter().map(|child| child.content.y).collect();
+        assert_eq!(ys, vec![80.0, 60.0]);
+    }
+
+    #[test]
+    fn test_relayout_reuses_text_measurements() {
+        let document = Document::new();
+        for _ in 0..3 {
+            let paragraph = document.create_element("p");
+            paragraph.append_child(&document.create_text_node("the same words in every paragraph"));
+            document.root.append_child(&paragraph);
+        }
+
+        let engine = LayoutEngine::new(parse_css(""));
+        let first = engine.layout_document(&document);
+        let after_first = engine.measurement_stats();
+        // Six distinct words and a space
+        assert_eq!(after_first.misses, 7);
+        assert!(after_first.hits > 0);
+
+        let second = engine.layout_document(&document);
+        let after_second = engine.measurement_stats();
+        assert_eq!(after_second.misses, 7);
+        assert_eq!(after_second.hits, after_first.hits * 2 + 7);
+        assert_eq!(first.children[0].children[0].fragments, second.children[0].children[0].fragments);
+    }
+
+    #[test]
+    fn test_user_stylesheet_brackets_page_styles() {
+        let doc = Document::new();
+        let heading = doc.create_element("h1");
+        let banner = doc.create_element("div");
+        banner.set_attribute("class", "ad");
+        banner.set_attribute("style", "display: block");
+        doc.root.append_child(&heading);
+        doc.root.append_child(&banner);
+        
+        let mut matcher = StyleMatcher::new(parse_css("h1 { color: red; }"));
+        let user = css_parser::CSSParser::new("h1 { color: white; background-color: #eeeeee } .ad { display: none !important }".to_string())
+            .parse_stylesheet()
+            .unwrap();
+        matcher.set_user_stylesheet(user);
+        
+        let styles = matcher.compute_styles(&heading);
+        assert_eq!(styles.color, Some("red".to_string()));
+        assert_eq!(styles.background_color, Some("#eeeeee".to_string()));
+        assert_eq!(matcher.compute_styles(&banner).display, DisplayType::None);
+    }
+
+    #[test]
+    fn test_media_features_and_reduced_motion() {
+        fn find_box(layout: &LayoutBox, id: u64) -> Option<&LayoutBox> {
+            if layout.node.id == id {
+                return Some(layout);
+            }
+            layout.children.iter().find_map(|child| find_box(child, id))
+        }
+        
+        let doc = Document::new();
+        let spinner = doc.create_element("div");
+        doc.root.append_child(&spinner);
+        let css = "div { color: black; animation-name: fade; animation-duration: 2s; }\n@media (prefers-color-scheme: dark) {\n  div { color: white; }\n}";
+        let mut engine = LayoutEngine::new(parse_css(css));
+        
+        let clock = dom::clock::ManualClock::new();
+        engine.set_clock(std::sync::Arc::new(clock.clone()));
+        let mut layout = engine.layout_document(&doc);
+        engine.tick_animations(&mut layout);
+        clock.advance(Duration::from_millis(500));
+        engine.tick_animations(&mut layout);
+        let animated = find_box(&layout, spinner.id).unwrap();
+        assert_eq!(animated.styles.color.as_deref(), Some("black"));
+        assert!(animated.animation_state.is_running);
+        assert_eq!(animated.animation_state.progress, 0.25);
+
+        // Past its 2s duration the animation has finished
+        clock.advance(Duration::from_secs(2));
+        engine.tick_animations(&mut layout);
+        let finished = find_box(&layout, spinner.id).unwrap();
+        assert!(!finished.animation_state.is_running);
+        assert_eq!(finished.animation_state.progress, 1.0);
+        
+        engine.set_media_features(MediaFeatures {
+            color_scheme: css_parser::media::ColorScheme::Dark,
+            reduced_motion: true,
+            ..MediaFeatures::default()
+        });
+        let mut layout = engine.layout_document(&doc);
+        engine.tick_animations(&mut layout);
+        let reduced = find_box(&layout, spinner.id).unwrap();
+        assert_eq!(reduced.styles.color.as_deref(), Some("white"));
+        assert!(!reduced.animation_state.is_running);
+        assert_eq!(reduced.animation_state.progress, 1.0);
+    }
+
+    #[test]
+    fn test_visited_rules_only_change_color() {
+        let doc = Document::new();
+        let link = doc.create_element("a");
+        link.set_attribute("href", "https://example.com/");
+        doc.root.append_child(&link);
+        
+        let css = "a:link { color: blue; }\na:visited { color: purple; width: 300px; display: block; }";
+        let matcher = StyleMatcher::new(parse_css(css));
+        assert_eq!(matcher.compute_styles(&link).color, Some("blue".to_string()));
+        link.set_element_state(dom::ElementState::VISITED, true);
+        let styles = matcher.compute_styles(&link);
+        assert_eq!(styles.color, Some("purple".to_string()));
+        assert_eq!(styles.box_model.width, None);
+        assert_eq!(styles.display, DisplayType::Inline);
+    }
+
+    #[test]
+    fn test_blend_mode_and_isolation() {
+        let doc = Document::new();
+        let card = doc.create_element("div");
+        card.set_attribute("class", "card");
+        let badge = doc.create_element("span");
+        
+        let matcher = StyleMatcher::new(parse_css(".card {\n  isolation: isolate;\n}\nspan {\n  mix-blend-mode: multiply;\n}"));
+        let styles = matcher.compute_styles(&card);
+        assert_eq!(styles.isolation, Isolation::Isolate);
+        assert!(styles.creates_isolated_group());
+        
+        let styles = matcher.compute_styles(&badge);
+        assert_eq!(styles.mix_blend_mode, BlendMode::Multiply);
+        // Blending with a backdrop also makes the element a group
+        assert!(styles.creates_isolated_group());
+        assert!(!ComputedStyles::default().creates_isolated_group());
+    }
+    
+    #[test]
+    fn test_modal_dialog_renders_in_the_top_layer() {
+        let doc = Document::new();
+        let html = doc.create_element("html");
+        let body = doc.create_element("body");
+        let dialog = doc.create_element("dialog");
+        dialog.set_attribute("style", "width: 200px; height: 100px");
+        doc.root.append_child(&html);
+        html.append_child(&body);
+        body.append_child(&dialog);
+        
+        let engine = LayoutEngine::new(parse_css("dialog::backdrop {\n  background-color: #000000;\n}"));
+        // A closed dialog is not rendered
+        let layout = engine.layout_document(&doc);
+        assert_eq!(layout.children.len(), 1);
+        assert_eq!(engine.style_matcher().compute_styles(&dialog).display, DisplayType::None);
+        
+        dialog.show_modal().unwrap();
+        let layout = engine.layout_document(&doc);
+        let [_, backdrop, dialog_box] = &layout.children[..] else {
+            panic!("expected a backdrop and a dialog above the root");
+        };
+        assert_eq!(backdrop.styles.background_color.as_deref(), Some("#000000"));
+        assert_eq!((backdrop.border.width, backdrop.border.height), (800.0, 600.0));
+        // 200x100 plus padding and border, centered in the viewport
+        assert_eq!(dialog_box.border.width, 200.0 + 2.0 * (DIALOG_PADDING + DIALOG_BORDER));
+        assert_eq!((dialog_box.border.x, dialog_box.border.y), ((800.0 - 238.0) / 2.0, (600.0 - 138.0) / 2.0));
+        // The dialog is not laid out in flow, and the backdrop belongs to it
+        assert!(layout.children[0].children[0].children.is_empty());
+        assert!(layout.hit_test(10.0, 10.0).is_some_and(|node| Rc::ptr_eq(&node, &dialog)));
+        
+        dialog.close_dialog(None);
+        assert_eq!(engine.layout_document(&doc).children.len(), 1);
+    }
+    
+    #[test]
+    fn test_fullscreen_element_fills_the_viewport() {
+        let doc = Document::new();
+        let html = doc.create_element("html");
+        let body = doc.create_element("body");
+        let video = doc.create_element("div");
+        video.set_attribute("style", "width: 320px; height: 180px; margin: 20px");
+        doc.root.append_child(&html);
+        html.append_child(&body);
+        body.append_child(&video);
+        
+        let engine = LayoutEngine::new(parse_css("div:fullscreen {\n  background-color: #336699;\n}"));
+        video.request_fullscreen().unwrap();
+        let layout = engine.layout_document(&doc);
+        let [_, backdrop, video_box] = &layout.children[..] else {
+            panic!("expected a backdrop and the fullscreen element above the root");
+        };
+        assert_eq!(backdrop.styles.background_color.as_deref(), Some(FULLSCREEN_BACKDROP_COLOR));
+        assert_eq!(video_box.styles.background_color.as_deref(), Some("#336699"));
+        assert_eq!((video_box.margin.x, video_box.margin.y, video_box.margin.width, video_box.margin.height), (0.0, 0.0, 800.0, 600.0));
+        
+        dom::fullscreen::exit_fullscreen(&video).unwrap();
+        let layout = engine.layout_document(&doc);
+        assert_eq!(layout.children.len(), 1);
+        assert_eq!(engine.style_matcher().compute_styles(&video).box_model.width, Some(320.0));
+    }
+}
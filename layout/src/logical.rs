@@ -0,0 +1,238 @@
+//! # Logical Properties
+//!
+//! This module maps the flow-relative box properties, such as
+//! `margin-inline`, `padding-block-start` and `inset-inline-end`, to the
+//! physical sides they stand for in an element's writing mode and
+//! direction.
+//!
+//! ## Design Principles
+//!
+//! 1. **Mapped After Inheritance**: `writing-mode` and `direction` are
+//!    inherited, so an element's own may only be known once the cascade
+//!    is done. Logical values are held back in cascade order and mapped
+//!    at the end of style resolution.
+//!
+//! 2. **One Cascade**: Physical declarations that follow a logical one
+//!    are held back with it and replayed in order, so whichever of
+//!    `margin-left` and `margin-inline-start` comes last wins, as if both
+//!    named the same side.
+//!
+//! Vertical writing modes only change which sides logical properties
+//! map to; text is still laid out horizontally.
+
+use crate::ComputedStyles;
+use dom::bidi::Direction;
+
+/// `writing-mode`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WritingMode {
+    /// Lines run left to right and stack top to bottom
+    #[default]
+    HorizontalTb,
+    /// Lines run top to bottom and stack right to left
+    VerticalRl,
+    /// Lines run top to bottom and stack left to right
+    VerticalLr,
+}
+
+impl WritingMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "horizontal-tb" => Some(WritingMode::HorizontalTb),
+            "vertical-rl" => Some(WritingMode::VerticalRl),
+            "vertical-lr" => Some(WritingMode::VerticalLr),
+            _ => None,
+        }
+    }
+}
+
+/// A side of the box in page terms
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhysicalSide {
+    Top,
+    Right,
+    Bottom,
+    Left,
+}
+
+/// A side of the box relative to the flow of its content
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogicalSide {
+    BlockStart,
+    BlockEnd,
+    InlineStart,
+    InlineEnd,
+}
+
+impl LogicalSide {
+    /// Get the physical side this side is in a writing mode and direction
+    pub fn to_physical(self, writing_mode: WritingMode, direction: Direction) -> PhysicalSide {
+        let rtl = direction == Direction::Rtl;
+        match (writing_mode, self) {
+            (WritingMode::HorizontalTb, LogicalSide::BlockStart) => PhysicalSide::Top,
+            (WritingMode::HorizontalTb, LogicalSide::BlockEnd) => PhysicalSide::Bottom,
+            (WritingMode::HorizontalTb, LogicalSide::InlineStart) if rtl => PhysicalSide::Right,
+            (WritingMode::HorizontalTb, LogicalSide::InlineStart) => PhysicalSide::Left,
+            (WritingMode::HorizontalTb, LogicalSide::InlineEnd) if rtl => PhysicalSide::Left,
+            (WritingMode::HorizontalTb, LogicalSide::InlineEnd) => PhysicalSide::Right,
+            (WritingMode::VerticalRl, LogicalSide::BlockStart) => PhysicalSide::Right,
+            (WritingMode::VerticalRl, LogicalSide::BlockEnd) => PhysicalSide::Left,
+            (WritingMode::VerticalLr, LogicalSide::BlockStart) => PhysicalSide::Left,
+            (WritingMode::VerticalLr, LogicalSide::BlockEnd) => PhysicalSide::Right,
+            (_, LogicalSide::InlineStart) if rtl => PhysicalSide::Bottom,
+            (_, LogicalSide::InlineStart) => PhysicalSide::Top,
+            (_, LogicalSide::InlineEnd) if rtl => PhysicalSide::Top,
+            (_, LogicalSide::InlineEnd) => PhysicalSide::Bottom,
+        }
+    }
+}
+
+/// The box properties with a value per side
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoxProperty {
+    Margin,
+    Padding,
+    Border,
+    Inset,
+}
+
+/// A side named physically or logically
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Physical(PhysicalSide),
+    Logical(LogicalSide),
+}
+
+/// A value for one side of a box property, held back until logical sides
+/// can be mapped; `None` is `auto`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SideDeclaration {
+    pub property: BoxProperty,
+    pub side: Side,
+    pub value: Option<f32>,
+}
+
+/// Get the box property and sides a logical property sets, e.g.
+/// `margin-inline` sets the inline start and end margins
+pub fn logical_property(name: &str) -> Option<(BoxProperty, Vec<LogicalSide>)> {
+    let (property, rest) = if let Some(rest) = name.strip_prefix("margin-") {
+        (BoxProperty::Margin, rest)
+    } else if let Some(rest) = name.strip_prefix("padding-") {
+        (BoxProperty::Padding, rest)
+    } else if let Some(rest) = name.strip_prefix("inset-") {
+        (BoxProperty::Inset, rest)
+    } else {
+        (BoxProperty::Border, name.strip_prefix("border-")?.strip_suffix("-width")?)
+    };
+    let sides = match rest {
+        "block" => vec![LogicalSide::BlockStart, LogicalSide::BlockEnd],
+        "inline" => vec![LogicalSide::InlineStart, LogicalSide::InlineEnd],
+        "block-start" => vec![LogicalSide::BlockStart],
+        "block-end" => vec![LogicalSide::BlockEnd],
+        "inline-start" => vec![LogicalSide::InlineStart],
+        "inline-end" => vec![LogicalSide::InlineEnd],
+        _ => return None,
+    };
+    Some((property, sides))
+}
+
+/// Get the box property and sides a physical property sets
+pub fn physical_property(name: &str) -> Option<(BoxProperty, Vec<PhysicalSide>)> {
+    const ALL: [PhysicalSide; 4] = [PhysicalSide::Top, PhysicalSide::Right, PhysicalSide::Bottom, PhysicalSide::Left];
+    Some(match name {
+        "margin" => (BoxProperty::Margin, ALL.to_vec()),
+        "padding" => (BoxProperty::Padding, ALL.to_vec()),
+        "border" | "border-width" => (BoxProperty::Border, ALL.to_vec()),
+        "top" => (BoxProperty::Inset, vec![PhysicalSide::Top]),
+        "right" => (BoxProperty::Inset, vec![PhysicalSide::Right]),
+        "bottom" => (BoxProperty::Inset, vec![PhysicalSide::Bottom]),
+        "left" => (BoxProperty::Inset, vec![PhysicalSide::Left]),
+        _ => return None,
+    })
+}
+
+/// Get the value of one side of a box property
+pub fn side_value(styles: &ComputedStyles, property: BoxProperty, side: PhysicalSide) -> Option<f32> {
+    let sides = match property {
+        BoxProperty::Margin => &styles.box_model.margin,
+        BoxProperty::Padding => &styles.box_model.padding,
+        BoxProperty::Border => &styles.box_model.border,
+        BoxProperty::Inset => {
+            return match side {
+                PhysicalSide::Top => styles.inset.top,
+                PhysicalSide::Right => styles.inset.right,
+                PhysicalSide::Bottom => styles.inset.bottom,
+                PhysicalSide::Left => styles.inset.left,
+            }
+        }
+    };
+    Some(match side {
+        PhysicalSide::Top => sides.top,
+        PhysicalSide::Right => sides.right,
+        PhysicalSide::Bottom => sides.bottom,
+        PhysicalSide::Left => sides.left,
+    })
+}
+
+/// Set one side of a box property; `auto` margins, padding and borders
+/// are 0
+pub fn set_side(styles: &mut ComputedStyles, property: BoxProperty, side: PhysicalSide, value: Option<f32>) {
+    let sides = match property {
+        BoxProperty::Margin => &mut styles.box_model_mut().margin,
+        BoxProperty::Padding => &mut styles.box_model_mut().padding,
+        BoxProperty::Border => &mut styles.box_model_mut().border,
+        BoxProperty::Inset => {
+            let inset = match side {
+                PhysicalSide::Top => &mut styles.inset.top,
+                PhysicalSide::Right => &mut styles.inset.right,
+                PhysicalSide::Bottom => &mut styles.inset.bottom,
+                PhysicalSide::Left => &mut styles.inset.left,
+            };
+            *inset = value;
+            return;
+        }
+    };
+    let value = value.unwrap_or(0.0);
+    match side {
+        PhysicalSide::Top => sides.top = value,
+        PhysicalSide::Right => sides.right = value,
+        PhysicalSide::Bottom => sides.bottom = value,
+        PhysicalSide::Left => sides.left = value,
+    }
+}
+
+/// Map the held back side declarations of computed styles to physical
+/// sides, in cascade order
+pub fn resolve_sides(styles: &mut ComputedStyles) {
+    let writing_mode = styles.writing_mode.unwrap_or_default();
+    let direction = styles.direction.unwrap_or_default();
+    for declaration in std::mem::take(&mut styles.pending_sides) {
+        let side = match declaration.side {
+            Side::Physical(side) => side,
+            Side::Logical(side) => side.to_physical(writing_mode, direction),
+        };
+        set_side(styles, declaration.property, side, declaration.value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_logical_sides_map_to_physical() {
+        use LogicalSide::*;
+        use PhysicalSide::*;
+        let map = |mode, direction| [BlockStart, BlockEnd, InlineStart, InlineEnd].map(|side: LogicalSide| side.to_physical(mode, direction));
+        assert_eq!(map(WritingMode::HorizontalTb, Direction::Ltr), [Top, Bottom, Left, Right]);
+        assert_eq!(map(WritingMode::HorizontalTb, Direction::Rtl), [Top, Bottom, Right, Left]);
+        assert_eq!(map(WritingMode::VerticalRl, Direction::Ltr), [Right, Left, Top, Bottom]);
+        assert_eq!(map(WritingMode::VerticalLr, Direction::Rtl), [Left, Right, Bottom, Top]);
+
+        assert_eq!(logical_property("margin-inline"), Some((BoxProperty::Margin, vec![InlineStart, InlineEnd])));
+        assert_eq!(logical_property("border-block-start-width"), Some((BoxProperty::Border, vec![BlockStart])));
+        assert_eq!(logical_property("inset-inline-start"), Some((BoxProperty::Inset, vec![InlineStart])));
+        assert_eq!(logical_property("margin-top"), None);
+        assert_eq!(logical_property("border-inline"), None);
+    }
+}
@@ -0,0 +1,517 @@
+//! # Clipping and Masking
+//!
+//! This module parses `clip-path` shapes and `mask-image` sources into the
+//! values the compositor clips and masks an element and its descendants
+//! with.
+//!
+//! ## Design Principles
+//!
+//! 1. **All or Nothing**: Like `filter`, a shape or source with a
+//!    malformed part is invalid as a whole and leaves the previous value
+//!    in place.
+//!
+//! 2. **Absolute Path Data**: Relative commands, the `H` and `V` shortcuts
+//!    and the reflected control points of `S` and `T` are resolved while
+//!    parsing, so a path is a list of absolute moves, lines, curves and
+//!    arcs.
+//!
+//! 3. **Resolved at Paint Time**: Polygon percentages stay percentages
+//!    until the painter knows the border box they refer to, and gradient
+//!    colors stay text, like every other color in `ComputedStyles`.
+//!
+//! A gradient to a corner points along the diagonal of a square box, and
+//! arc flags must be separated from the numbers around them.
+
+/// How the inside of a shape is decided where its outline crosses itself
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FillRule {
+    #[default]
+    NonZero,
+    EvenOdd,
+}
+
+/// A polygon coordinate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShapeLength {
+    Px(f32),
+    /// A percentage of the reference box's width or height, as a fraction
+    Percent(f32),
+}
+
+impl ShapeLength {
+    /// Resolve against the size of the reference box along the axis
+    pub fn resolve(self, basis: f32) -> f32 {
+        match self {
+            ShapeLength::Px(length) => length,
+            ShapeLength::Percent(fraction) => fraction * basis,
+        }
+    }
+}
+
+/// One absolute command of a path, in pixels from the reference box's
+/// top left corner
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathCommand {
+    MoveTo(f32, f32),
+    LineTo(f32, f32),
+    /// Control point, end point
+    QuadTo(f32, f32, f32, f32),
+    /// Two control points, end point
+    CubicTo(f32, f32, f32, f32, f32, f32),
+    /// Radii, x axis rotation in degrees, large arc and sweep flags, end
+    /// point
+    ArcTo(f32, f32, f32, bool, bool, f32, f32),
+    Close,
+}
+
+/// A `clip-path` shape, relative to the element's border box
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClipPath {
+    Polygon { fill_rule: FillRule, points: Vec<(ShapeLength, ShapeLength)> },
+    Path { fill_rule: FillRule, commands: Vec<PathCommand> },
+}
+
+impl ClipPath {
+    pub fn fill_rule(&self) -> FillRule {
+        match self {
+            ClipPath::Polygon { fill_rule, .. } | ClipPath::Path { fill_rule, .. } => *fill_rule,
+        }
+    }
+}
+
+/// A color stop of a gradient
+#[derive(Debug, Clone, PartialEq)]
+pub struct GradientStop {
+    pub color: String,
+    /// Position along the gradient line as a fraction, `None` to spread
+    /// evenly between its neighbours
+    pub position: Option<f32>,
+}
+
+/// A `mask-image` source; its alpha channel masks the element
+#[derive(Debug, Clone, PartialEq)]
+pub enum MaskImage {
+    Url(String),
+    LinearGradient {
+        /// Direction of the gradient line in degrees, clockwise from up
+        angle: f32,
+        stops: Vec<GradientStop>,
+    },
+}
+
+/// Which channel of a mask image masks the element
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MaskMode {
+    /// The alpha channel; `match-source` is alpha for images and gradients
+    #[default]
+    Alpha,
+    /// The luminance of the colors, times their alpha
+    Luminance,
+}
+
+impl MaskMode {
+    /// Parse a `mask-mode` value
+    pub fn parse(value: &str) -> Option<MaskMode> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "alpha" | "match-source" => Some(MaskMode::Alpha),
+            "luminance" => Some(MaskMode::Luminance),
+            _ => None,
+        }
+    }
+}
+
+/// Parse a `clip-path` value: `none`, `polygon()` or `path()`
+///
+/// Returns `Some(None)` for `none` and `None` for invalid values.
+pub fn parse_clip_path(value: &str) -> Option<Option<ClipPath>> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("none") {
+        return Some(None);
+    }
+    let (name, arguments) = function(value)?;
+    let (fill_rule, rest) = fill_rule(arguments);
+    match name.as_str() {
+        "polygon" => {
+            // Coordinates pair up in order, however the parser grouped them
+            let lengths = components(rest).into_iter().map(shape_length).collect::<Option<Vec<_>>>()?;
+            if lengths.len() < 6 || lengths.len() % 2 != 0 {
+                return None;
+            }
+            let points = lengths.chunks(2).map(|pair| (pair[0], pair[1])).collect();
+            Some(Some(ClipPath::Polygon { fill_rule, points }))
+        }
+        "path" => {
+            let data = rest.trim().strip_prefix(['"', '\''])?.strip_suffix(['"', '\''])?;
+            let commands = parse_path_data(data)?;
+            Some(Some(ClipPath::Path { fill_rule, commands }))
+        }
+        _ => None,
+    }
+}
+
+/// Parse a `mask-image` value: `none`, `url()` or `linear-gradient()`
+///
+/// Returns `Some(None)` for `none` and `None` for invalid values.
+pub fn parse_mask_image(value: &str) -> Option<Option<MaskImage>> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("none") {
+        return Some(None);
+    }
+    let (name, arguments) = function(value)?;
+    match name.as_str() {
+        "url" => {
+            let url = arguments.trim().trim_matches(|c| c == '"' || c == '\'');
+            (!url.is_empty()).then(|| Some(MaskImage::Url(url.to_string())))
+        }
+        "linear-gradient" => {
+            let mut parts = components(arguments).into_iter().peekable();
+            let angle = match parts.peek() {
+                Some(first) if first.eq_ignore_ascii_case("to") => {
+                    parts.next();
+                    let mut sides = Vec::new();
+                    while let Some(side) = parts.next_if(|part| matches!(part.to_ascii_lowercase().as_str(), "top" | "right" | "bottom" | "left")) {
+                        sides.push(side.to_ascii_lowercase());
+                    }
+                    side_angle(&sides)?
+                }
+                Some(first) => match parse_angle(first) {
+                    Some(angle) => {
+                        parts.next();
+                        angle
+                    }
+                    None => 180.0,
+                },
+                None => return None,
+            };
+            let mut stops: Vec<GradientStop> = Vec::new();
+            for part in parts {
+                match part.strip_suffix('%') {
+                    // A position follows the color it belongs to
+                    Some(percent) => {
+                        let stop = stops.last_mut().filter(|stop| stop.position.is_none())?;
+                        stop.position = Some(percent.parse::<f32>().ok()? / 100.0);
+                    }
+                    None => stops.push(GradientStop { color: part.to_string(), position: None }),
+                }
+            }
+            (stops.len() >= 2).then_some(Some(MaskImage::LinearGradient { angle, stops }))
+        }
+        _ => None,
+    }
+}
+
+/// Split `name(arguments)` into its lowercase name and arguments
+fn function(value: &str) -> Option<(String, &str)> {
+    let open = value.find('(')?;
+    let arguments = value[open + 1..].strip_suffix(')')?;
+    Some((value[..open].trim().to_ascii_lowercase(), arguments))
+}
+
+/// Take a leading fill rule off a shape's arguments
+fn fill_rule(arguments: &str) -> (FillRule, &str) {
+    let trimmed = arguments.trim_start();
+    for (keyword, rule) in [("evenodd", FillRule::EvenOdd), ("nonzero", FillRule::NonZero)] {
+        if let Some(rest) = trimmed.strip_prefix(keyword).and_then(|rest| rest.trim_start().strip_prefix(',')) {
+            return (rule, rest);
+        }
+    }
+    (FillRule::NonZero, arguments)
+}
+
+fn shape_length(text: &str) -> Option<ShapeLength> {
+    if let Some(percent) = text.strip_suffix('%') {
+        return Some(ShapeLength::Percent(percent.parse::<f32>().ok()? / 100.0));
+    }
+    let number = text.strip_suffix("px").unwrap_or(text);
+    let length: f32 = number.parse().ok()?;
+    // Only zero may omit its unit
+    (number.len() < text.len() || length == 0.0).then_some(ShapeLength::Px(length))
+}
+
+/// The angle of a gradient `to` a side or corner
+fn side_angle(sides: &[String]) -> Option<f32> {
+    let mut sides: Vec<&str> = sides.iter().map(String::as_str).collect();
+    sides.sort_unstable();
+    match sides.as_slice() {
+        ["top"] => Some(0.0),
+        ["right"] => Some(90.0),
+        ["bottom"] => Some(180.0),
+        ["left"] => Some(270.0),
+        ["right", "top"] => Some(45.0),
+        ["bottom", "right"] => Some(135.0),
+        ["bottom", "left"] => Some(225.0),
+        ["left", "top"] => Some(315.0),
+        _ => None,
+    }
+}
+
+/// Parse an angle in degrees, turns, radians or gradians
+fn parse_angle(text: &str) -> Option<f32> {
+    let text = text.to_ascii_lowercase();
+    let split = text.find(|c: char| c.is_ascii_alphabetic())?;
+    let number: f32 = text[..split].parse().ok()?;
+    match &text[split..] {
+        "deg" => Some(number),
+        "turn" => Some(number * 360.0),
+        "rad" => Some(number.to_degrees()),
+        "grad" => Some(number * 0.9),
+        _ => None,
+    }
+}
+
+/// Split arguments into components on commas and whitespace outside
+/// nested functions
+///
+/// The stylesheet parser keeps function arguments as a flat list, so
+/// shapes and gradients are read from their components in order rather
+/// than from comma-separated groups.
+fn components(arguments: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, c) in arguments.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' | ' ' | '\t' | '\n' if depth == 0 => {
+                parts.push(&arguments[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&arguments[start..]);
+    parts.retain(|part| !part.is_empty());
+    parts
+}
+
+/// Parse SVG path data into absolute commands
+pub fn parse_path_data(data: &str) -> Option<Vec<PathCommand>> {
+    let tokens = path_tokens(data)?;
+    let mut commands = Vec::new();
+    let mut index = 0;
+    let (mut x, mut y) = (0.0, 0.0);
+    let (mut start_x, mut start_y) = (0.0, 0.0);
+    // The last control point, reflected by `S` and `T`
+    let mut last_cubic: Option<(f32, f32)> = None;
+    let mut last_quad: Option<(f32, f32)> = None;
+    let mut command = None;
+    while index < tokens.len() {
+        if let PathToken::Command(letter) = tokens[index] {
+            command = Some(letter);
+            index += 1;
+        }
+        let letter = command?;
+        let relative = letter.is_ascii_lowercase();
+        let (base_x, base_y) = if relative { (x, y) } else { (0.0, 0.0) };
+        let mut numbers = |count: usize| -> Option<Vec<f32>> {
+            let values = tokens.get(index..index + count)?.iter().map(|token| match token {
+                PathToken::Number(value) => Some(*value),
+                PathToken::Command(_) => None,
+            });
+            let values = values.collect::<Option<Vec<f32>>>()?;
+            index += count;
+            Some(values)
+        };
+        let (cubic, quad) = match letter.to_ascii_uppercase() {
+            'M' => {
+                let point = numbers(2)?;
+                (x, y) = (base_x + point[0], base_y + point[1]);
+                (start_x, start_y) = (x, y);
+                commands.push(PathCommand::MoveTo(x, y));
+                // Further pairs are lines
+                command = Some(if relative { 'l' } else { 'L' });
+                (None, None)
+            }
+            'L' => {
+                let point = numbers(2)?;
+                (x, y) = (base_x + point[0], base_y + point[1]);
+                commands.push(PathCommand::LineTo(x, y));
+                (None, None)
+            }
+            'H' => {
+                x = base_x + numbers(1)?[0];
+                commands.push(PathCommand::LineTo(x, y));
+                (None, None)
+            }
+            'V' => {
+                y = base_y + numbers(1)?[0];
+                commands.push(PathCommand::LineTo(x, y));
+                (None, None)
+            }
+            'C' | 'S' => {
+                let (first, rest) = if letter.eq_ignore_ascii_case(&'C') {
+                    let values = numbers(6)?;
+                    ((base_x + values[0], base_y + values[1]), values[2..].to_vec())
+                } else {
+                    let reflected = last_cubic.map_or((x, y), |(cx, cy)| (2.0 * x - cx, 2.0 * y - cy));
+                    (reflected, numbers(4)?)
+                };
+                let control = (base_x + rest[0], base_y + rest[1]);
+                (x, y) = (base_x + rest[2], base_y + rest[3]);
+                commands.push(PathCommand::CubicTo(first.0, first.1, control.0, control.1, x, y));
+                (Some(control), None)
+            }
+            'Q' | 'T' => {
+                let control = if letter.eq_ignore_ascii_case(&'Q') {
+                    let values = numbers(2)?;
+                    (base_x + values[0], base_y + values[1])
+                } else {
+                    last_quad.map_or((x, y), |(cx, cy)| (2.0 * x - cx, 2.0 * y - cy))
+                };
+                let end = numbers(2)?;
+                (x, y) = (base_x + end[0], base_y + end[1]);
+                commands.push(PathCommand::QuadTo(control.0, control.1, x, y));
+                (None, Some(control))
+            }
+            'A' => {
+                let values = numbers(7)?;
+                let flag = |value: f32| (value == 0.0 || value == 1.0).then_some(value == 1.0);
+                let (large_arc, sweep) = (flag(values[3])?, flag(values[4])?);
+                (x, y) = (base_x + values[5], base_y + values[6]);
+                commands.push(PathCommand::ArcTo(values[0].abs(), values[1].abs(), values[2], large_arc, sweep, x, y));
+                (None, None)
+            }
+            'Z' => {
+                (x, y) = (start_x, start_y);
+                commands.push(PathCommand::Close);
+                command = None;
+                (None, None)
+            }
+            _ => return None,
+        };
+        last_cubic = cubic;
+        last_quad = quad;
+    }
+    matches!(commands.first(), Some(PathCommand::MoveTo(..))).then_some(commands)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PathToken {
+    Command(char),
+    Number(f32),
+}
+
+/// Split path data into command letters and numbers
+///
+/// Numbers may be separated by whitespace, commas, a sign, or a second
+/// decimal point, as in `M10-5.5.5`.
+fn path_tokens(data: &str) -> Option<Vec<PathToken>> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = data.chars().collect();
+    let mut index = 0;
+    while index < chars.len() {
+        let c = chars[index];
+        if c.is_whitespace() || c == ',' {
+            index += 1;
+        } else if "MmLlHhVvCcSsQqTtAaZz".contains(c) {
+            tokens.push(PathToken::Command(c));
+            index += 1;
+        } else if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' {
+            let start = index;
+            let mut seen_point = false;
+            let mut seen_exponent = false;
+            index += 1;
+            if c == '.' {
+                seen_point = true;
+            }
+            while index < chars.len() {
+                match chars[index] {
+                    digit if digit.is_ascii_digit() => {}
+                    '.' if !seen_point && !seen_exponent => seen_point = true,
+                    'e' | 'E' if !seen_exponent => {
+                        seen_exponent = true;
+                        if matches!(chars.get(index + 1), Some('-') | Some('+')) {
+                            index += 1;
+                        }
+                    }
+                    _ => break,
+                }
+                index += 1;
+            }
+            let number: String = chars[start..index].iter().collect();
+            tokens.push(PathToken::Number(number.parse().ok()?));
+        } else {
+            return None;
+        }
+    }
+    Some(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_clip_paths_and_masks() {
+        assert_eq!(
+            parse_clip_path("polygon(evenodd, 0 0, 100% 0, 50% 20px)"),
+            Some(Some(ClipPath::Polygon {
+                fill_rule: FillRule::EvenOdd,
+                points: vec![
+                    (ShapeLength::Px(0.0), ShapeLength::Px(0.0)),
+                    (ShapeLength::Percent(1.0), ShapeLength::Px(0.0)),
+                    (ShapeLength::Percent(0.5), ShapeLength::Px(20.0)),
+                ],
+            }))
+        );
+        assert_eq!(parse_clip_path("none"), Some(None));
+        assert_eq!(parse_clip_path("polygon(0 0, 10px 10px)"), None);
+        assert_eq!(parse_clip_path("polygon(0 0, 10 0, 10px 10px)"), None);
+        // As the stylesheet parser passes it on
+        assert_eq!(parse_clip_path("polygon(0, 0, 100%, 0, 50%, 20px)"), parse_clip_path("polygon(0 0, 100% 0, 50% 20px)"));
+
+        // Relative, shorthand and reflected commands come out absolute
+        let path = parse_clip_path("path('M10 10h20v-5.5.5s10 10 20 0Z')").unwrap().unwrap();
+        assert_eq!(
+            path,
+            ClipPath::Path {
+                fill_rule: FillRule::NonZero,
+                commands: vec![
+                    PathCommand::MoveTo(10.0, 10.0),
+                    PathCommand::LineTo(30.0, 10.0),
+                    PathCommand::LineTo(30.0, 4.5),
+                    PathCommand::LineTo(30.0, 5.0),
+                    PathCommand::CubicTo(30.0, 5.0, 40.0, 15.0, 50.0, 5.0),
+                    PathCommand::Close,
+                ],
+            }
+        );
+        assert_eq!(parse_clip_path("path(\"L 10 10\")"), None);
+        assert_eq!(
+            parse_path_data("M 0 0 A 5 5 0 0 1 10 10 a-5 5 30 1 0 10 0"),
+            Some(vec![
+                PathCommand::MoveTo(0.0, 0.0),
+                PathCommand::ArcTo(5.0, 5.0, 0.0, false, true, 10.0, 10.0),
+                PathCommand::ArcTo(5.0, 5.0, 30.0, true, false, 20.0, 10.0),
+            ])
+        );
+        assert_eq!(parse_path_data("M 0 0 A 5 5 0 2 1 10 10"), None);
+
+        assert_eq!(parse_mask_image("url(\"mask.png\")"), Some(Some(MaskImage::Url("mask.png".to_string()))));
+        assert_eq!(
+            parse_mask_image("linear-gradient(to right, rgba(0, 0, 0, 1) 20%, transparent)"),
+            Some(Some(MaskImage::LinearGradient {
+                angle: 90.0,
+                stops: vec![
+                    GradientStop { color: "rgba(0, 0, 0, 1)".to_string(), position: Some(0.2) },
+                    GradientStop { color: "transparent".to_string(), position: None },
+                ],
+            }))
+        );
+        assert_eq!(parse_mask_image("linear-gradient(0.5turn, black, white)").unwrap().map(|mask| match mask {
+            MaskImage::LinearGradient { angle, .. } => angle,
+            _ => 0.0,
+        }), Some(180.0));
+        assert_eq!(
+            parse_mask_image("linear-gradient(to, right, rgba(0, 0, 0, 1), 20%, transparent)"),
+            parse_mask_image("linear-gradient(to right, rgba(0, 0, 0, 1) 20%, transparent)")
+        );
+        assert_eq!(parse_mask_image("linear-gradient(to middle, black, white)"), None);
+        assert_eq!(parse_mask_image("radial-gradient(black, white)"), None);
+
+        assert_eq!(MaskMode::parse("Luminance"), Some(MaskMode::Luminance));
+        assert_eq!(MaskMode::parse("match-source"), Some(MaskMode::Alpha));
+        assert_eq!(MaskMode::parse("red"), None);
+    }
+}
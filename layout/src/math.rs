@@ -0,0 +1,348 @@
+//! # Math Functions
+//!
+//! This module parses and evaluates the CSS math functions `calc()`,
+//! `min()`, `max()` and `clamp()`, which nest freely and mix units, as in
+//! `font-size: clamp(1rem, 2vw + 0.5rem, 1.5rem)` or
+//! `width: min(100% - 2em, 60rem)`.
+//!
+//! ## Design Principles
+//!
+//! 1. **Typed at Parse Time**: Every subexpression is a number or a length
+//!    (percentages count as lengths). Adding a number to a length,
+//!    multiplying two lengths or dividing by a length makes the whole value
+//!    invalid, so the declaration is ignored like any other invalid one.
+//!
+//! 2. **Units Resolved Late**: Expressions keep their units. They are
+//!    evaluated against a `MathContext` holding the font size, the viewport
+//!    and, once layout knows it, the size percentages refer to; a
+//!    percentage without one makes the expression unresolvable.
+//!
+//! 3. **Bounded Nesting**: Parentheses and functions nest at most
+//!    `MAX_NESTING` deep, so hostile input cannot exhaust the stack.
+
+/// Nesting limit for parentheses and functions in an expression
+pub const MAX_NESTING: usize = 32;
+
+/// A parsed math expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum MathExpression {
+    Number(f32),
+    /// A length with its unit, lowercased
+    Length(f32, String),
+    Percentage(f32),
+    Sum(Box<MathExpression>, Box<MathExpression>),
+    Difference(Box<MathExpression>, Box<MathExpression>),
+    Product(Box<MathExpression>, Box<MathExpression>),
+    Quotient(Box<MathExpression>, Box<MathExpression>),
+    Min(Vec<MathExpression>),
+    Max(Vec<MathExpression>),
+    /// `clamp(minimum, preferred, maximum)`
+    Clamp(Box<MathExpression>, Box<MathExpression>, Box<MathExpression>),
+}
+
+/// What an expression evaluates to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MathType {
+    Number,
+    Length,
+}
+
+/// The sizes units in an expression resolve against
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MathContext {
+    /// Font size `em` refers to
+    pub font_size: f32,
+    /// Font size of the root element, which `rem` refers to
+    pub root_font_size: f32,
+    /// x-height and zero advance of the font, which `ex` and `ch` refer to
+    pub x_height: f32,
+    pub zero_advance: f32,
+    pub viewport_width: f32,
+    pub viewport_height: f32,
+    /// Size percentages refer to, if known yet
+    pub percentage_basis: Option<f32>,
+}
+
+impl MathExpression {
+    /// Parse a value that is a single math function, e.g. `calc(1px + 2em)`
+    ///
+    /// Returns `None` for other values and for invalid expressions.
+    pub fn parse(text: &str) -> Option<(MathExpression, MathType)> {
+        let text = text.trim();
+        let name = text.split('(').next()?.to_ascii_lowercase();
+        if !matches!(name.as_str(), "calc" | "min" | "max" | "clamp") {
+            return None;
+        }
+        let tokens = tokenize(text)?;
+        let mut parser = Parser { tokens, position: 0, depth: 0 };
+        let parsed = parser.parse_operand()?;
+        (parser.position == parser.tokens.len()).then_some(parsed)
+    }
+
+    /// Check whether any part of the expression is a percentage
+    pub fn has_percentage(&self) -> bool {
+        match self {
+            MathExpression::Percentage(_) => true,
+            MathExpression::Number(_) | MathExpression::Length(..) => false,
+            MathExpression::Sum(left, right)
+            | MathExpression::Difference(left, right)
+            | MathExpression::Product(left, right)
+            | MathExpression::Quotient(left, right) => left.has_percentage() || right.has_percentage(),
+            MathExpression::Min(arguments) | MathExpression::Max(arguments) => arguments.iter().any(MathExpression::has_percentage),
+            MathExpression::Clamp(minimum, preferred, maximum) => {
+                minimum.has_percentage() || preferred.has_percentage() || maximum.has_percentage()
+            }
+        }
+    }
+
+    /// Evaluate the expression, lengths in pixels
+    ///
+    /// Returns `None` for unknown units, percentages without a basis and
+    /// division by zero. When the minimum of `clamp()` exceeds its maximum,
+    /// the minimum wins.
+    pub fn resolve(&self, context: &MathContext) -> Option<f32> {
+        let value = match self {
+            MathExpression::Number(value) => *value,
+            MathExpression::Length(value, unit) => value * unit_size(unit, context)?,
+            MathExpression::Percentage(value) => value / 100.0 * context.percentage_basis?,
+            MathExpression::Sum(left, right) => left.resolve(context)? + right.resolve(context)?,
+            MathExpression::Difference(left, right) => left.resolve(context)? - right.resolve(context)?,
+            MathExpression::Product(left, right) => left.resolve(context)? * right.resolve(context)?,
+            MathExpression::Quotient(left, right) => match right.resolve(context)? {
+                0.0 => return None,
+                divisor => left.resolve(context)? / divisor,
+            },
+            MathExpression::Min(arguments) => resolve_all(arguments, context)?.into_iter().fold(f32::INFINITY, f32::min),
+            MathExpression::Max(arguments) => resolve_all(arguments, context)?.into_iter().fold(f32::NEG_INFINITY, f32::max),
+            MathExpression::Clamp(minimum, preferred, maximum) => {
+                preferred.resolve(context)?.min(maximum.resolve(context)?).max(minimum.resolve(context)?)
+            }
+        };
+        value.is_finite().then_some(value)
+    }
+}
+
+fn resolve_all(arguments: &[MathExpression], context: &MathContext) -> Option<Vec<f32>> {
+    arguments.iter().map(|argument| argument.resolve(context)).collect()
+}
+
+/// Get the size of one unit in pixels
+fn unit_size(unit: &str, context: &MathContext) -> Option<f32> {
+    Some(match unit {
+        "px" => 1.0,
+        "em" => context.font_size,
+        "rem" => context.root_font_size,
+        "ex" => context.x_height,
+        "ch" => context.zero_advance,
+        "vw" => context.viewport_width / 100.0,
+        "vh" => context.viewport_height / 100.0,
+        "vmin" => context.viewport_width.min(context.viewport_height) / 100.0,
+        "vmax" => context.viewport_width.max(context.viewport_height) / 100.0,
+        "pt" => 96.0 / 72.0,
+        "pc" => 16.0,
+        "in" => 96.0,
+        "cm" => 96.0 / 2.54,
+        "mm" => 96.0 / 25.4,
+        _ => return None,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// A number with its unit: empty for plain numbers, `%` for percentages
+    Numeric(f32, String),
+    Operator(char),
+    /// A function name and its opening parenthesis, or a bare parenthesis
+    /// with an empty name
+    Open(String),
+    Close,
+    Comma,
+}
+
+fn tokenize(text: &str) -> Option<Vec<Token>> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+    while index < chars.len() {
+        let ch = chars[index];
+        let starts_number = |at: usize| chars.get(at).is_some_and(|ch| ch.is_ascii_digit() || *ch == '.');
+        if ch.is_whitespace() {
+            index += 1;
+        } else if starts_number(index) || (matches!(ch, '+' | '-') && starts_number(index + 1)) {
+            let start = index;
+            index += 1;
+            while chars.get(index).is_some_and(|ch| ch.is_ascii_digit() || *ch == '.') {
+                index += 1;
+            }
+            let number: String = chars[start..index].iter().collect();
+            let unit_start = index;
+            while chars.get(index).is_some_and(|ch| ch.is_ascii_alphabetic() || *ch == '%') {
+                index += 1;
+            }
+            let unit: String = chars[unit_start..index].iter().collect();
+            tokens.push(Token::Numeric(number.parse().ok()?, unit.to_ascii_lowercase()));
+        } else if ch.is_ascii_alphabetic() {
+            let start = index;
+            while chars.get(index).is_some_and(|ch| ch.is_ascii_alphanumeric() || *ch == '-') {
+                index += 1;
+            }
+            if chars.get(index) != Some(&'(') {
+                return None;
+            }
+            tokens.push(Token::Open(chars[start..index].iter().collect::<String>().to_ascii_lowercase()));
+            index += 1;
+        } else {
+            tokens.push(match ch {
+                '(' => Token::Open(String::new()),
+                ')' => Token::Close,
+                ',' => Token::Comma,
+                '+' | '-' | '*' | '/' => Token::Operator(ch),
+                _ => return None,
+            });
+            index += 1;
+        }
+    }
+    Some(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    /// Parse sums and differences of products
+    fn parse_sum(&mut self) -> Option<(MathExpression, MathType)> {
+        let (mut expression, kind) = self.parse_product()?;
+        while let Some(Token::Operator(operator @ ('+' | '-'))) = self.peek().cloned() {
+            self.position += 1;
+            let (right, right_kind) = self.parse_product()?;
+            if right_kind != kind {
+                return None;
+            }
+            expression = match operator {
+                '+' => MathExpression::Sum(Box::new(expression), Box::new(right)),
+                _ => MathExpression::Difference(Box::new(expression), Box::new(right)),
+            };
+        }
+        Some((expression, kind))
+    }
+
+    /// Parse products and quotients of operands
+    fn parse_product(&mut self) -> Option<(MathExpression, MathType)> {
+        let (mut expression, mut kind) = self.parse_operand()?;
+        while let Some(Token::Operator(operator @ ('*' | '/'))) = self.peek().cloned() {
+            self.position += 1;
+            let (right, right_kind) = self.parse_operand()?;
+            expression = match (operator, kind, right_kind) {
+                (_, _, MathType::Number) if operator == '/' => MathExpression::Quotient(Box::new(expression), Box::new(right)),
+                ('*', MathType::Number, _) | ('*', _, MathType::Number) => {
+                    kind = if kind == MathType::Number { right_kind } else { kind };
+                    MathExpression::Product(Box::new(expression), Box::new(right))
+                }
+                _ => return None,
+            };
+        }
+        Some((expression, kind))
+    }
+
+    /// Parse a number, a length, or a parenthesized or function expression
+    fn parse_operand(&mut self) -> Option<(MathExpression, MathType)> {
+        match self.next()? {
+            Token::Numeric(value, unit) => Some(match unit.as_str() {
+                "" => (MathExpression::Number(value), MathType::Number),
+                "%" => (MathExpression::Percentage(value), MathType::Length),
+                _ => (MathExpression::Length(value, unit), MathType::Length),
+            }),
+            Token::Open(name) => {
+                self.depth += 1;
+                if self.depth > MAX_NESTING {
+                    return None;
+                }
+                let mut arguments = vec![self.parse_sum()?];
+                while self.peek() == Some(&Token::Comma) {
+                    self.position += 1;
+                    arguments.push(self.parse_sum()?);
+                }
+                if self.next()? != Token::Close {
+                    return None;
+                }
+                self.depth -= 1;
+
+                let kind = arguments[0].1;
+                if arguments.iter().any(|(_, argument_kind)| *argument_kind != kind) {
+                    return None;
+                }
+                let mut expressions: Vec<MathExpression> = arguments.into_iter().map(|(expression, _)| expression).collect();
+                let expression = match (name.as_str(), expressions.len()) {
+                    ("" | "calc", 1) => expressions.pop()?,
+                    ("min", _) => MathExpression::Min(expressions),
+                    ("max", _) => MathExpression::Max(expressions),
+                    ("clamp", 3) => {
+                        let maximum = expressions.pop()?;
+                        let preferred = expressions.pop()?;
+                        let minimum = expressions.pop()?;
+                        MathExpression::Clamp(Box::new(minimum), Box::new(preferred), Box::new(maximum))
+                    }
+                    _ => return None,
+                };
+                Some((expression, kind))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_resolve() {
+        let context = MathContext {
+            font_size: 20.0,
+            root_font_size: 16.0,
+            x_height: 9.0,
+            zero_advance: 10.0,
+            viewport_width: 1000.0,
+            viewport_height: 500.0,
+            percentage_basis: None,
+        };
+        let resolve = |text: &str| MathExpression::parse(text).and_then(|(expression, _)| expression.resolve(&context));
+
+        // Fluid typography picks the preferred size until a bound is hit
+        assert_eq!(resolve("clamp(1rem, 2vw, 1.5rem)"), Some(20.0));
+        assert_eq!(resolve("clamp(1rem, 1vw, 1.5rem)"), Some(16.0));
+        assert_eq!(resolve("clamp(1rem, 5vw, 1.5rem)"), Some(24.0));
+        assert_eq!(resolve("calc(2em + 10px * 3 - 1rem / 2)"), Some(62.0));
+        assert_eq!(resolve("max(10px, min(2em, 5vh), calc((1px + 1px) * 2))"), Some(25.0));
+        assert_eq!(resolve("calc(-2px - -3px)"), Some(1.0));
+        assert_eq!(resolve("calc(2ex + 1ch)"), Some(28.0));
+        assert_eq!(MathExpression::parse("calc(2 * 3)").map(|(_, kind)| kind), Some(MathType::Number));
+
+        // Percentages wait for a basis
+        let (width, _) = MathExpression::parse("min(100% - 2em, 600px)").unwrap();
+        assert!(width.has_percentage());
+        assert_eq!(width.resolve(&context), None);
+        assert_eq!(width.resolve(&MathContext { percentage_basis: Some(500.0), ..context }), Some(460.0));
+
+        // Type errors and malformed input are invalid
+        for invalid in ["calc(1px + 2)", "calc(1px * 2px)", "calc(2 / 1px)", "clamp(1px, 2px)", "calc()", "calc(1px", "min(1px, 2)", "blur(2px)", "calc(1px) 2px"] {
+            assert_eq!(MathExpression::parse(invalid), None, "{invalid}");
+        }
+        assert_eq!(resolve("calc(1px / 0)"), None);
+        let nested = format!("calc({}1px{})", "(".repeat(40), ")".repeat(40));
+        assert_eq!(MathExpression::parse(&nested), None);
+    }
+}
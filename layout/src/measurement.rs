@@ -0,0 +1,229 @@
+//! # Text Measurement Cache
+//!
+//! This module caches the measured width of text runs, so relayout of
+//! unchanged paragraphs does not measure their words again.
+//!
+//! ## Design Principles
+//!
+//! 1. **Word-Level Keys**: Entries are keyed by font (family, size, weight,
+//!    style and features) and the text of a single word or space. Words repeat across
+//!    paragraphs and survive edits elsewhere on the line.
+//!
+//! 2. **LRU Budget**: The cache holds at most `capacity` entries and evicts
+//!    the least recently used one when full. One cache is shared by every
+//!    layout an engine runs.
+//!
+//! 3. **Observable**: Hits, misses and evictions are counted, so the hit
+//!    rate of a relayout can be checked.
+
+use crate::fonts::{self, FontFeature, FontRegistry, FontStyle, FontSynthesis};
+use crate::ComputedStyles;
+use std::collections::{BTreeMap, HashMap};
+
+/// Default number of cached text runs
+pub const DEFAULT_MEASUREMENT_CACHE_CAPACITY: usize = 8192;
+
+/// The font properties that affect measurement
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FontKey {
+    pub family: String,
+    /// Bit pattern of the font size, so the key can be hashed
+    pub size_bits: u32,
+    pub weight: String,
+    pub style: FontStyle,
+    /// OpenType features, sorted by tag
+    pub features: Vec<FontFeature>,
+    pub synthesis: FontSynthesis,
+}
+
+impl FontKey {
+    /// Get the font key of computed styles
+    pub fn from_styles(styles: &ComputedStyles) -> Self {
+        FontKey {
+            family: styles.font.family.clone().unwrap_or_default(),
+            size_bits: styles.font.size.unwrap_or(16.0).to_bits(),
+            weight: styles.font.weight.clone().unwrap_or_default(),
+            style: styles.font.style.unwrap_or_default(),
+            features: styles.font.features.resolve(),
+            synthesis: styles.font.synthesis.unwrap_or_default(),
+        }
+    }
+
+    /// Get the font size in pixels
+    pub fn size(&self) -> f32 {
+        f32::from_bits(self.size_bits)
+    }
+}
+
+/// Counters of a measurement cache
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MeasurementStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    /// Runs currently cached
+    pub entries: usize,
+}
+
+impl MeasurementStats {
+    /// Fraction of lookups answered from the cache
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CachedWidth {
+    width: f32,
+    last_used: u64,
+}
+
+/// LRU cache of measured text widths
+#[derive(Debug, Clone)]
+pub struct TextMeasurementCache {
+    fonts: HashMap<FontKey, HashMap<String, CachedWidth>>,
+    /// Entries by last use, oldest first
+    recency: BTreeMap<u64, (FontKey, String)>,
+    capacity: usize,
+    clock: u64,
+    stats: MeasurementStats,
+    /// Faces that text is shaped with
+    registry: FontRegistry,
+}
+
+impl TextMeasurementCache {
+    /// Create a cache holding at most `capacity` runs
+    pub fn new(capacity: usize) -> Self {
+        TextMeasurementCache {
+            fonts: HashMap::new(),
+            recency: BTreeMap::new(),
+            capacity,
+            clock: 0,
+            stats: MeasurementStats::default(),
+            registry: FontRegistry::default(),
+        }
+    }
+
+    /// Measure a run of text, using the cached width if there is one
+    pub fn measure(&mut self, text: &str, font: &FontKey) -> f32 {
+        self.clock += 1;
+        let now = self.clock;
+
+        if let Some(cached) = self.fonts.get_mut(font).and_then(|runs| runs.get_mut(text)) {
+            self.stats.hits += 1;
+            let previous = std::mem::replace(&mut cached.last_used, now);
+            let width = cached.width;
+            if let Some(key) = self.recency.remove(&previous) {
+                self.recency.insert(now, key);
+            }
+            return width;
+        }
+
+        self.stats.misses += 1;
+        let width = fonts::shape(text, font, &self.registry.select(font)).width();
+        if self.capacity == 0 {
+            return width;
+        }
+        while self.recency.len() >= self.capacity {
+            self.evict_oldest();
+        }
+        self.fonts
+            .entry(font.clone())
+            .or_default()
+            .insert(text.to_string(), CachedWidth { width, last_used: now });
+        self.recency.insert(now, (font.clone(), text.to_string()));
+        width
+    }
+
+    /// Replace the available faces and fallback chains
+    ///
+    /// Cached widths were shaped with the old faces, so they are dropped.
+    pub fn set_font_registry(&mut self, registry: FontRegistry) {
+        self.registry = registry;
+        self.fonts.clear();
+        self.recency.clear();
+    }
+
+    /// Get the font registry
+    pub fn font_registry(&self) -> &FontRegistry {
+        &self.registry
+    }
+
+    /// Get the cache counters
+    pub fn stats(&self) -> MeasurementStats {
+        MeasurementStats {
+            entries: self.recency.len(),
+            ..self.stats
+        }
+    }
+
+    /// Reset the counters, keeping the cached runs
+    pub fn reset_stats(&mut self) {
+        self.stats = MeasurementStats::default();
+    }
+
+    /// Drop every cached run, e.g. after a web font loads
+    pub fn clear(&mut self) {
+        self.fonts.clear();
+        self.recency.clear();
+    }
+
+    /// Change the budget, evicting runs if it shrank
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.recency.len() > capacity {
+            self.evict_oldest();
+        }
+    }
+
+    fn evict_oldest(&mut self) {
+        let Some((_, (font, text))) = self.recency.pop_first() else {
+            return;
+        };
+        if let Some(runs) = self.fonts.get_mut(&font) {
+            runs.remove(&text);
+            if runs.is_empty() {
+                self.fonts.remove(&font);
+            }
+        }
+        self.stats.evictions += 1;
+    }
+}
+
+impl Default for TextMeasurementCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_MEASUREMENT_CACHE_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_hits_and_lru_eviction() {
+        let mut cache = TextMeasurementCache::new(2);
+        let font = FontKey::from_styles(&ComputedStyles::default());
+        let large = FontKey { size_bits: 32.0f32.to_bits(), ..font.clone() };
+
+        assert_eq!(cache.measure("word", &font), 32.0);
+        assert_eq!(cache.measure("word", &large), 64.0);
+        assert_eq!(cache.measure("word", &font), 32.0);
+        assert_eq!(cache.stats().hits, 1);
+
+        // The large-font entry is the least recently used
+        cache.measure("other", &font);
+        assert_eq!(cache.stats().evictions, 1);
+        cache.measure("word", &font);
+        cache.measure("word", &large);
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (2, 4, 2));
+        assert!((stats.hit_rate() - 1.0 / 3.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,438 @@
+//! # Paged Media
+//!
+//! This module lays a document out on pages for printing: it resolves the
+//! page box each page gets from `@page` rules, and cuts the laid out
+//! document into page-sized slices at the break opportunities
+//! `break-before`, `break-after`, `break-inside`, `widows` and `orphans`
+//! allow.
+//!
+//! ## Design Principles
+//!
+//! 1. **One Layout, Many Slices**: The document is laid out once at the
+//!    width of the page area, and pages are consecutive vertical slices of
+//!    it. Painting a page paints the slice, moved into the page area.
+//!
+//! 2. **Break Classes**: Breaks between sibling blocks and between the
+//!    lines of a paragraph are the only opportunities. A forced break is
+//!    always taken; breaks that an `avoid` value or `widows` and `orphans`
+//!    rule out are only used when a page has no other, and a page with no
+//!    opportunity at all is cut where it ends.
+//!
+//! 3. **Page Cascade**: `@page` rules apply in order of specificity, rules
+//!    without a selector first, then `:left` and `:right`, then `:first`;
+//!    rules of equal specificity in stylesheet order.
+//!
+//! Pages are all laid out at the width of the first page, and named pages
+//! are not supported, so rules with a page name never match.
+
+use crate::{BoxSides, Dimensions, LayoutBox};
+use css_parser::{CSSDeclaration, PageRule};
+
+/// Size of a page when no `@page` rule sets one: US letter at 96 pixels
+/// per inch
+pub const DEFAULT_PAGE_SIZE: (f32, f32) = (816.0, 1056.0);
+
+/// Margin of a page when no `@page` rule sets one, half an inch
+pub const DEFAULT_PAGE_MARGIN: f32 = 48.0;
+
+/// Widows and orphans when no style sets them
+pub const DEFAULT_WIDOWS: u32 = 2;
+pub const DEFAULT_ORPHANS: u32 = 2;
+
+/// `break-before` and `break-after`, and the legacy `page-break-before`
+/// and `page-break-after`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BreakBetween {
+    #[default]
+    Auto,
+    /// Avoid a page break here
+    Avoid,
+    /// Force a page break here
+    Page,
+}
+
+impl BreakBetween {
+    /// Parse a `break-before` or `break-after` value; column breaks do not
+    /// apply to pages
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "auto" | "column" | "avoid-column" => Some(BreakBetween::Auto),
+            "avoid" | "avoid-page" => Some(BreakBetween::Avoid),
+            "page" | "left" | "right" | "recto" | "verso" => Some(BreakBetween::Page),
+            _ => None,
+        }
+    }
+
+    /// Parse a `page-break-before` or `page-break-after` value
+    pub fn parse_legacy(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "auto" => Some(BreakBetween::Auto),
+            "avoid" => Some(BreakBetween::Avoid),
+            "always" | "left" | "right" => Some(BreakBetween::Page),
+            _ => None,
+        }
+    }
+}
+
+/// `break-inside` and the legacy `page-break-inside`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BreakInside {
+    #[default]
+    Auto,
+    /// Avoid page breaks inside the box
+    Avoid,
+}
+
+impl BreakInside {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "auto" | "avoid-column" => Some(BreakInside::Auto),
+            "avoid" | "avoid-page" => Some(BreakInside::Avoid),
+            _ => None,
+        }
+    }
+}
+
+/// The page box of one page
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageStyle {
+    pub width: f32,
+    pub height: f32,
+    pub margin: BoxSides,
+}
+
+impl Default for PageStyle {
+    fn default() -> Self {
+        PageStyle {
+            width: DEFAULT_PAGE_SIZE.0,
+            height: DEFAULT_PAGE_SIZE.1,
+            margin: BoxSides::new(DEFAULT_PAGE_MARGIN),
+        }
+    }
+}
+
+impl PageStyle {
+    /// Resolve the page box of a page from the `@page` rules, counting
+    /// pages from 1
+    pub fn for_page<'a>(rules: impl IntoIterator<Item = &'a PageRule>, number: usize) -> PageStyle {
+        let mut matching: Vec<(u8, &PageRule)> = rules
+            .into_iter()
+            .filter_map(|rule| selector_rank(rule.selector.as_deref(), number).map(|rank| (rank, rule)))
+            .collect();
+        matching.sort_by_key(|(rank, _)| *rank);
+        let mut style = PageStyle::default();
+        for declaration in matching.iter().flat_map(|(_, rule)| &rule.declarations) {
+            style.apply(declaration);
+        }
+        style
+    }
+
+    /// The area of the page content is laid out in, in page coordinates
+    pub fn page_area(&self) -> Dimensions {
+        Dimensions::new(
+            self.margin.left,
+            self.margin.top,
+            (self.width - self.margin.left - self.margin.right).max(1.0),
+            (self.height - self.margin.top - self.margin.bottom).max(1.0),
+        )
+    }
+
+    fn apply(&mut self, declaration: &CSSDeclaration) {
+        let value = declaration.value.to_css_text();
+        match declaration.property.as_str() {
+            "size" => {
+                if let Some((width, height)) = parse_page_size(&value) {
+                    self.width = width;
+                    self.height = height;
+                }
+            }
+            "margin" => {
+                let lengths: Option<Vec<f32>> = value.split_whitespace().map(parse_length).collect();
+                let (top, right, bottom, left) = match lengths.as_deref() {
+                    Some([all]) => (*all, *all, *all, *all),
+                    Some([vertical, horizontal]) => (*vertical, *horizontal, *vertical, *horizontal),
+                    Some([top, horizontal, bottom]) => (*top, *horizontal, *bottom, *horizontal),
+                    Some([top, right, bottom, left]) => (*top, *right, *bottom, *left),
+                    _ => return,
+                };
+                self.margin = BoxSides { top, right, bottom, left };
+            }
+            "margin-top" => self.margin.top = parse_length(&value).unwrap_or(self.margin.top),
+            "margin-right" => self.margin.right = parse_length(&value).unwrap_or(self.margin.right),
+            "margin-bottom" => self.margin.bottom = parse_length(&value).unwrap_or(self.margin.bottom),
+            "margin-left" => self.margin.left = parse_length(&value).unwrap_or(self.margin.left),
+            _ => {}
+        }
+    }
+}
+
+/// Get how specific a page selector is if it matches a page, counting
+/// pages from 1; the first page is a right page
+fn selector_rank(selector: Option<&str>, number: usize) -> Option<u8> {
+    let Some(selector) = selector.map(str::trim).filter(|selector| !selector.is_empty()) else {
+        return Some(0);
+    };
+    // A page name comes before the pseudo-classes
+    if !selector.starts_with(':') {
+        return None;
+    }
+    let mut rank = 0;
+    for pseudo in selector.split(':').skip(1) {
+        match pseudo.trim().to_ascii_lowercase().as_str() {
+            "first" if number == 1 => rank = rank.max(2),
+            "left" if number.is_multiple_of(2) => rank = rank.max(1),
+            "right" if number % 2 == 1 => rank = rank.max(1),
+            _ => return None,
+        }
+    }
+    Some(rank)
+}
+
+/// Parse an absolute length in pixels
+fn parse_length(text: &str) -> Option<f32> {
+    let text = text.trim().to_ascii_lowercase();
+    if text == "0" {
+        return Some(0.0);
+    }
+    let split = text.find(|c: char| c.is_ascii_alphabetic())?;
+    let (number, unit) = text.split_at(split);
+    let number: f32 = number.parse().ok()?;
+    let scale = match unit {
+        "px" => 1.0,
+        "in" => 96.0,
+        "cm" => 96.0 / 2.54,
+        "mm" => 96.0 / 25.4,
+        "q" => 96.0 / 101.6,
+        "pt" => 96.0 / 72.0,
+        "pc" => 16.0,
+        _ => return None,
+    };
+    Some(number * scale)
+}
+
+/// Parse a `size` value: `auto`, one or two lengths, or a page size name,
+/// either with an orientation
+fn parse_page_size(text: &str) -> Option<(f32, f32)> {
+    const MM: f32 = 96.0 / 25.4;
+    let mut size = None;
+    let mut landscape = None;
+    let lengths: Vec<f32> = text.split_whitespace().filter_map(parse_length).collect();
+    for word in text.split_whitespace() {
+        match word.to_ascii_lowercase().as_str() {
+            "auto" => size = Some(DEFAULT_PAGE_SIZE),
+            "portrait" => landscape = Some(false),
+            "landscape" => landscape = Some(true),
+            "a5" => size = Some((148.0 * MM, 210.0 * MM)),
+            "a4" => size = Some((210.0 * MM, 297.0 * MM)),
+            "a3" => size = Some((297.0 * MM, 420.0 * MM)),
+            "b5" => size = Some((176.0 * MM, 250.0 * MM)),
+            "b4" => size = Some((250.0 * MM, 353.0 * MM)),
+            "letter" => size = Some((816.0, 1056.0)),
+            "legal" => size = Some((816.0, 1344.0)),
+            "ledger" => size = Some((1056.0, 1632.0)),
+            _ if parse_length(word).is_some() => {}
+            _ => return None,
+        }
+    }
+    let (width, height) = match lengths.as_slice() {
+        [] => size.or(landscape.map(|_| DEFAULT_PAGE_SIZE))?,
+        // Explicit lengths take no orientation
+        [side] if size.is_none() && landscape.is_none() => return Some((*side, *side)),
+        [width, height] if size.is_none() && landscape.is_none() => return Some((*width, *height)),
+        _ => return None,
+    };
+    Some(match landscape {
+        Some(true) => (width.max(height), width.min(height)),
+        Some(false) => (width.min(height), width.max(height)),
+        None => (width, height),
+    })
+}
+
+/// One page of a paginated document
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page {
+    /// Page number, counting from 1
+    pub number: usize,
+    pub style: PageStyle,
+    /// The slice of the laid out document on the page, in document
+    /// coordinates
+    pub top: f32,
+    pub bottom: f32,
+}
+
+impl Page {
+    /// Get the offset that moves document coordinates onto the page
+    pub fn offset(&self) -> (f32, f32) {
+        (self.style.margin.left, self.style.margin.top - self.top)
+    }
+}
+
+/// A document laid out on pages
+#[derive(Debug, Clone)]
+pub struct PagedLayout {
+    /// The document, laid out at the width of the first page's page area
+    pub layout: LayoutBox,
+    pub pages: Vec<Page>,
+}
+
+/// A place the document may be broken between pages
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Breakpoint {
+    y: f32,
+    /// A `page` value forces the break
+    forced: bool,
+    /// An `avoid` value, `widows` or `orphans` rules the break out
+    avoided: bool,
+}
+
+/// What pagination needs to know about a laid out document
+#[derive(Debug, Default)]
+struct Fragmentation {
+    breakpoints: Vec<Breakpoint>,
+    /// Ranges a page must not end inside, as (top, bottom): lines, empty
+    /// boxes and `break-inside: avoid` boxes
+    unbreakable: Vec<(f32, f32)>,
+}
+
+impl Fragmentation {
+    /// Check whether a page may end at `y` without cutting through content
+    fn is_clear(&self, y: f32) -> bool {
+        !self.unbreakable.iter().any(|(top, bottom)| *top < y - 0.5 && *bottom > y + 0.5)
+    }
+}
+
+/// Cut a laid out document into pages
+///
+/// `rules` are the `@page` rules in cascade order. Pages are slices of
+/// the document in document coordinates, with content boxes offset from
+/// their parent's as in `geometry::border_boxes`. A page is filled to its
+/// end where that cuts through no content, and otherwise ends at the last
+/// break opportunity on it. The last page ends with the last line or empty
+/// box, so space boxes keep below their content adds no blank pages.
+/// There is always at least one page.
+pub fn paginate(layout: &LayoutBox, rules: &[&PageRule]) -> Vec<Page> {
+    let mut fragmentation = Fragmentation::default();
+    let mut top = layout.content.y;
+    let end = collect_breakpoints(layout, 0.0, layout.styles.break_inside == BreakInside::Avoid, &mut fragmentation).max(top);
+    fragmentation.breakpoints.sort_by(|a, b| a.y.total_cmp(&b.y));
+
+    let mut pages = Vec::new();
+    loop {
+        let number = pages.len() + 1;
+        let style = PageStyle::for_page(rules.iter().copied(), number);
+        let limit = top + style.page_area().height;
+        let candidates: Vec<&Breakpoint> = fragmentation
+            .breakpoints
+            .iter()
+            .filter(|breakpoint| breakpoint.y > top + 0.5 && breakpoint.y <= limit && breakpoint.y < end)
+            .collect();
+        let bottom = if let Some(forced) = candidates.iter().find(|breakpoint| breakpoint.forced) {
+            forced.y
+        } else if end <= limit {
+            end
+        } else if fragmentation.is_clear(limit) {
+            limit
+        } else {
+            candidates
+                .iter()
+                .rev()
+                .find(|breakpoint| !breakpoint.avoided)
+                .or(candidates.last())
+                .map_or(limit, |breakpoint| breakpoint.y)
+        };
+        pages.push(Page { number, style, top, bottom });
+        if bottom >= end {
+            break;
+        }
+        top = bottom;
+    }
+    pages
+}
+
+/// Collect the break opportunities inside a box whose parent's content
+/// box starts at `parent_y`: between its block children, between the
+/// lines of its text and after its last line
+///
+/// Returns the bottom of the box's content: its last line, or the box
+/// itself if it has no children.
+fn collect_breakpoints(layout_box: &LayoutBox, parent_y: f32, avoid_inside: bool, fragmentation: &mut Fragmentation) -> f32 {
+    let y = parent_y + layout_box.content.y;
+    let mut bottom = y;
+    if layout_box.children.is_empty() {
+        bottom = y + layout_box.border.height.max(layout_box.content.height);
+        fragmentation.unbreakable.push((y, bottom));
+    }
+    // Lines of the box's own text, as (top, bottom)
+    let mut lines: Vec<(f32, f32)> = Vec::new();
+    for child in layout_box.children.iter().filter(|child| !child.fragments.is_empty()) {
+        for fragment in &child.fragments {
+            let top = y + child.content.y + fragment.y;
+            match lines.iter_mut().find(|(line_top, _)| (line_top - top).abs() < 0.5) {
+                Some(line) => line.1 = line.1.max(top + fragment.height),
+                None => lines.push((top, top + fragment.height)),
+            }
+        }
+    }
+    lines.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let widows = layout_box.styles.widows.unwrap_or(DEFAULT_WIDOWS) as usize;
+    let orphans = layout_box.styles.orphans.unwrap_or(DEFAULT_ORPHANS) as usize;
+    for (index, (top, _)) in lines.iter().enumerate().skip(1) {
+        fragmentation.breakpoints.push(Breakpoint {
+            y: *top,
+            forced: false,
+            avoided: avoid_inside || index < orphans || lines.len() - index < widows,
+        });
+    }
+    if let Some((_, last)) = lines.last() {
+        fragmentation.breakpoints.push(Breakpoint { y: *last, forced: false, avoided: avoid_inside });
+        bottom = bottom.max(*last);
+    }
+    fragmentation.unbreakable.extend(lines);
+
+    let blocks: Vec<&LayoutBox> = layout_box.children.iter().filter(|child| child.fragments.is_empty()).collect();
+    for pair in blocks.windows(2) {
+        let (before, after) = (&pair[0].styles, &pair[1].styles);
+        fragmentation.breakpoints.push(Breakpoint {
+            y: y + pair[1].content.y,
+            forced: before.break_after == BreakBetween::Page || after.break_before == BreakBetween::Page,
+            avoided: avoid_inside || before.break_after == BreakBetween::Avoid || after.break_before == BreakBetween::Avoid,
+        });
+    }
+    for child in blocks {
+        let avoid = child.styles.break_inside == BreakInside::Avoid;
+        let child_bottom = collect_breakpoints(child, y, avoid_inside || avoid, fragmentation);
+        if avoid {
+            fragmentation.unbreakable.push((y + child.content.y, child_bottom));
+        }
+        bottom = bottom.max(child_bottom);
+    }
+    bottom
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use css_parser::CSSParser;
+
+    #[test]
+    fn test_page_styles_cascade() {
+        let css = "@page { size: A4 landscape; margin: 1in } @page :first { margin-top: 2in } @page :left { margin: 10px 20px }";
+        let stylesheet = CSSParser::new(css.to_string()).parse_stylesheet().unwrap();
+        let rules: Vec<&PageRule> = stylesheet.page_rules.iter().collect();
+
+        let first = PageStyle::for_page(rules.iter().copied(), 1);
+        assert!((first.width - 1122.5).abs() < 0.1 && (first.height - 793.7).abs() < 0.1);
+        assert_eq!(first.margin, BoxSides { top: 192.0, right: 96.0, bottom: 96.0, left: 96.0 });
+        let second = PageStyle::for_page(rules.iter().copied(), 2);
+        assert_eq!(second.margin, BoxSides { top: 10.0, right: 20.0, bottom: 10.0, left: 20.0 });
+        let third = PageStyle::for_page(rules.iter().copied(), 3);
+        assert_eq!(third.margin, BoxSides::new(96.0));
+        assert_eq!(third.page_area(), Dimensions::new(96.0, 96.0, third.width - 192.0, third.height - 192.0));
+
+        assert_eq!(parse_page_size("300px 200px"), Some((300.0, 200.0)));
+        assert_eq!(parse_page_size("letter portrait"), Some((816.0, 1056.0)));
+        assert_eq!(parse_page_size("A4 200px"), None);
+        assert_eq!(selector_rank(Some("chapter:first"), 1), None);
+        assert_eq!(BreakBetween::parse_legacy("always"), Some(BreakBetween::Page));
+    }
+}
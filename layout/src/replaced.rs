@@ -0,0 +1,277 @@
+//! # Replaced Content
+//!
+//! This module places the content of replaced elements (`<img>`,
+//! `<canvas>`, `<video>` and `<iframe>`) inside their content box according to
+//! `object-fit` and `object-position`.
+//!
+//! ## Design Principles
+//!
+//! 1. **Two Rectangles**: Fitting yields the part of the content to draw,
+//!    in the content's own pixels, and where to draw it, in page
+//!    coordinates. Painters only scale one onto the other.
+//!
+//! 2. **Clipped to the Box**: Content that overflows the content box, as
+//!    with `cover` or `none`, is cropped in the source rectangle, so the
+//!    destination never leaves the box.
+//!
+//! 3. **Attributes Before Decoding**: An image's `width` and `height`
+//!    attributes stand in for the natural size until its loader reports
+//!    one. Canvases, videos and iframes use theirs, defaulting to 300×150; a
+//!    video whose decoder reported a size uses that instead of the default.
+
+use crate::Dimensions;
+use dom::Node;
+use dom::media::MediaKind;
+
+/// How replaced content is sized in its box (`object-fit`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ObjectFit {
+    /// Stretched to fill the box
+    #[default]
+    Fill,
+    /// Scaled to fit inside the box, keeping its aspect ratio
+    Contain,
+    /// Scaled to cover the box, keeping its aspect ratio
+    Cover,
+    /// Kept at its natural size
+    None,
+    /// `none` or `contain`, whichever is smaller
+    ScaleDown,
+}
+
+impl ObjectFit {
+    /// Parse an `object-fit` keyword
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "fill" => Some(ObjectFit::Fill),
+            "contain" => Some(ObjectFit::Contain),
+            "cover" => Some(ObjectFit::Cover),
+            "none" => Some(ObjectFit::None),
+            "scale-down" => Some(ObjectFit::ScaleDown),
+            _ => None,
+        }
+    }
+}
+
+/// One axis of `object-position`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PositionComponent {
+    /// A percentage of the free space, 0 at the start edge
+    Percent(f32),
+    /// A length from the start edge, in pixels
+    Length(f32),
+}
+
+impl PositionComponent {
+    /// Resolve the offset from the start edge given the free space
+    fn resolve(&self, free_space: f32) -> f32 {
+        match self {
+            PositionComponent::Percent(percent) => free_space * percent / 100.0,
+            PositionComponent::Length(length) => *length,
+        }
+    }
+}
+
+/// Where replaced content sits in its box (`object-position`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ObjectPosition {
+    pub x: PositionComponent,
+    pub y: PositionComponent,
+}
+
+impl Default for ObjectPosition {
+    fn default() -> Self {
+        ObjectPosition { x: PositionComponent::Percent(50.0), y: PositionComponent::Percent(50.0) }
+    }
+}
+
+impl ObjectPosition {
+    /// Parse one or two position values such as `left top`, `25% 75%` or
+    /// `10px center`
+    ///
+    /// A single value applies to its own axis and centers the other.
+    pub fn parse(value: &str) -> Option<Self> {
+        let parts: Vec<&str> = value.split_whitespace().collect();
+        let center = PositionComponent::Percent(50.0);
+        match parts.as_slice() {
+            [single] => match single.to_ascii_lowercase().as_str() {
+                "top" | "bottom" => Some(ObjectPosition { x: center, y: parse_component(single, false)? }),
+                _ => Some(ObjectPosition { x: parse_component(single, true)?, y: center }),
+            },
+            [first, second] => {
+                let (first_lower, second_lower) = (first.to_ascii_lowercase(), second.to_ascii_lowercase());
+                // Keywords may come in either order, e.g. `top left`
+                if matches!(first_lower.as_str(), "top" | "bottom") || matches!(second_lower.as_str(), "left" | "right") {
+                    Some(ObjectPosition { x: parse_component(second, true)?, y: parse_component(first, false)? })
+                } else {
+                    Some(ObjectPosition { x: parse_component(first, true)?, y: parse_component(second, false)? })
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Parse one position value for the horizontal or vertical axis
+fn parse_component(value: &str, horizontal: bool) -> Option<PositionComponent> {
+    let value = value.to_ascii_lowercase();
+    match (value.as_str(), horizontal) {
+        ("center", _) => return Some(PositionComponent::Percent(50.0)),
+        ("left", true) | ("top", false) => return Some(PositionComponent::Percent(0.0)),
+        ("right", true) | ("bottom", false) => return Some(PositionComponent::Percent(100.0)),
+        ("left" | "right", false) | ("top" | "bottom", true) => return None,
+        _ => {}
+    }
+    if let Some(percent) = value.strip_suffix('%') {
+        return percent.parse().ok().map(PositionComponent::Percent);
+    }
+    let length = value.strip_suffix("px").unwrap_or(&value);
+    length.parse().ok().map(PositionComponent::Length)
+}
+
+/// The part of replaced content to draw and where to draw it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplacedRects {
+    /// Region of the content, in its natural pixels
+    pub source: Dimensions,
+    /// Region of the page, inside the content box
+    pub dest: Dimensions,
+}
+
+/// Fit content of a natural size into a content box
+///
+/// Returns `None` if the content or the box is empty, or if the content
+/// ends up entirely outside the box.
+pub fn fit_replaced_content(fit: ObjectFit, position: &ObjectPosition, natural: (f32, f32), content_box: &Dimensions) -> Option<ReplacedRects> {
+    let (natural_width, natural_height) = natural;
+    if natural_width <= 0.0 || natural_height <= 0.0 || content_box.width <= 0.0 || content_box.height <= 0.0 {
+        return None;
+    }
+
+    let contain_scale = (content_box.width / natural_width).min(content_box.height / natural_height);
+    let (width, height) = match fit {
+        ObjectFit::Fill => (content_box.width, content_box.height),
+        ObjectFit::Contain => (natural_width * contain_scale, natural_height * contain_scale),
+        ObjectFit::Cover => {
+            let scale = (content_box.width / natural_width).max(content_box.height / natural_height);
+            (natural_width * scale, natural_height * scale)
+        }
+        ObjectFit::None => (natural_width, natural_height),
+        ObjectFit::ScaleDown => {
+            let scale = contain_scale.min(1.0);
+            (natural_width * scale, natural_height * scale)
+        }
+    };
+
+    // The placed content, which may overflow the box
+    let placed_x = content_box.x + position.x.resolve(content_box.width - width);
+    let placed_y = content_box.y + position.y.resolve(content_box.height - height);
+
+    let left = placed_x.max(content_box.x);
+    let top = placed_y.max(content_box.y);
+    let right = (placed_x + width).min(content_box.right());
+    let bottom = (placed_y + height).min(content_box.bottom());
+    if right <= left || bottom <= top {
+        return None;
+    }
+
+    let scale_x = natural_width / width;
+    let scale_y = natural_height / height;
+    Some(ReplacedRects {
+        source: Dimensions::new(
+            (left - placed_x) * scale_x,
+            (top - placed_y) * scale_y,
+            (right - left) * scale_x,
+            (bottom - top) * scale_y,
+        ),
+        dest: Dimensions::new(left, top, right - left, bottom - top),
+    })
+}
+
+/// Get the size of the box a media element lays out to
+///
+/// Videos take their natural size. Audio has no picture: with `controls` it
+/// is as large as its control bar, without it is not rendered at all.
+pub fn media_box_size(node: &Node) -> Option<(f32, f32)> {
+    match node.media_kind()? {
+        MediaKind::Video => natural_size(node),
+        MediaKind::Audio => node.has_attribute("controls").then_some((300.0, 54.0)),
+    }
+}
+
+/// Check whether an element is replaced content this module places
+pub fn is_replaced_element(node: &Node) -> bool {
+    matches!(node.tag_name().map(|tag| tag.to_ascii_lowercase()).as_deref(), Some("img" | "canvas" | "video" | "iframe"))
+}
+
+/// Get the natural (width, height) of a replaced element
+///
+/// Returns `None` for an image without both `width` and `height`
+/// attributes, whose size is unknown until it is loaded.
+pub fn natural_size(node: &Node) -> Option<(f32, f32)> {
+    let attribute = |name: &str| {
+        node.get_attribute(name)
+            .and_then(|value| value.trim().trim_end_matches("px").parse::<f32>().ok())
+            .filter(|value| *value > 0.0)
+    };
+    match node.tag_name()?.to_ascii_lowercase().as_str() {
+        // An image's attributes win over the size its loader reports
+        "img" => {
+            let loaded = node.image_state().natural_size.map(|(width, height)| (width as f32, height as f32));
+            Some((
+                attribute("width").or(loaded.map(|(width, _)| width))?,
+                attribute("height").or(loaded.map(|(_, height)| height))?,
+            ))
+        }
+        "canvas" | "iframe" => Some((attribute("width").unwrap_or(300.0), attribute("height").unwrap_or(150.0))),
+        // A video's attributes win over the size its decoder reports
+        "video" => {
+            let decoded = node.video_size().map(|(width, height)| (width as f32, height as f32));
+            Some((
+                attribute("width").or(decoded.map(|(width, _)| width)).unwrap_or(300.0),
+                attribute("height").or(decoded.map(|(_, height)| height)).unwrap_or(150.0),
+            ))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_object_fit_letterboxes_and_crops() {
+        let content_box = Dimensions::new(0.0, 0.0, 200.0, 100.0);
+        let center = ObjectPosition::default();
+        let natural = (100.0, 100.0);
+
+        let fill = fit_replaced_content(ObjectFit::Fill, &center, natural, &content_box).unwrap();
+        assert_eq!(fill.dest, content_box);
+        assert_eq!(fill.source, Dimensions::new(0.0, 0.0, 100.0, 100.0));
+
+        // Letterboxed in the middle of the box
+        let contain = fit_replaced_content(ObjectFit::Contain, &center, natural, &content_box).unwrap();
+        assert_eq!(contain.dest, Dimensions::new(50.0, 0.0, 100.0, 100.0));
+
+        // Scaled to 200x200 and cropped to the middle half
+        let cover = fit_replaced_content(ObjectFit::Cover, &center, natural, &content_box).unwrap();
+        assert_eq!(cover.dest, content_box);
+        assert_eq!(cover.source, Dimensions::new(0.0, 25.0, 100.0, 50.0));
+
+        let top = ObjectPosition::parse("top").unwrap();
+        let cover_top = fit_replaced_content(ObjectFit::Cover, &top, natural, &content_box).unwrap();
+        assert_eq!(cover_top.source, Dimensions::new(0.0, 0.0, 100.0, 50.0));
+
+        // Small content keeps its natural size with scale-down
+        let scale_down = fit_replaced_content(ObjectFit::ScaleDown, &ObjectPosition::parse("left top").unwrap(), (40.0, 20.0), &content_box).unwrap();
+        assert_eq!(scale_down.dest, Dimensions::new(0.0, 0.0, 40.0, 20.0));
+
+        let none = fit_replaced_content(ObjectFit::None, &ObjectPosition::parse("right 10px").unwrap(), (300.0, 50.0), &content_box).unwrap();
+        assert_eq!(none.dest, Dimensions::new(0.0, 10.0, 200.0, 50.0));
+        assert_eq!(none.source, Dimensions::new(100.0, 0.0, 200.0, 50.0));
+
+        assert_eq!(ObjectFit::parse("scale-down"), Some(ObjectFit::ScaleDown));
+        assert_eq!(ObjectPosition::parse("left left"), None);
+    }
+}
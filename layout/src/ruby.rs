@@ -0,0 +1,207 @@
+//! # Ruby Annotations
+//!
+//! This module lays out `<ruby>` elements: runs of base text, each with an
+//! annotation (`<rt>`) shown above it in a smaller font, as used for the
+//! readings of Japanese and Chinese characters. Fallback parentheses in
+//! `<rp>` are hidden by the user agent styles.
+//!
+//! ## Design Principles
+//!
+//! 1. **Pairs, Left to Right**: The children of a `<ruby>` box are split
+//!    into pairs of base content and the `<rt>` that follows it. Pairs are
+//!    placed side by side, each as wide as the wider of its base and its
+//!    annotation, which is centered over the base.
+//!
+//! 2. **Annotations Grow the Line**: The annotations sit in a row of their
+//!    own above the bases, and the ruby box is as tall as both rows, so the
+//!    line holding it grows by the height of its annotation row instead of
+//!    the annotations overlapping the line above.
+//!
+//! 3. **Laid Out, Then Arranged**: Bases and annotations are laid out like
+//!    any other boxes and only moved into place afterwards, so their text
+//!    keeps its own styles and line breaking.
+
+use crate::LayoutBox;
+use dom::{Node, NodeType};
+
+/// Font size of ruby annotations relative to their base text, as in the
+/// `rt { font-size: 50% }` of browsers' user agent stylesheets
+pub const ANNOTATION_FONT_SCALE: f32 = 0.5;
+
+/// Check whether a node is a `<ruby>` element
+pub fn is_ruby(node: &Node) -> bool {
+    node.tag_name().is_some_and(|tag| tag.eq_ignore_ascii_case("ruby"))
+}
+
+/// Check whether a node is a ruby annotation, an `<rt>` element
+pub fn is_annotation(node: &Node) -> bool {
+    node.tag_name().is_some_and(|tag| tag.eq_ignore_ascii_case("rt"))
+}
+
+/// A run of base boxes and the annotation that follows it
+#[derive(Debug, Default)]
+struct RubyPair {
+    bases: Vec<usize>,
+    annotation: Option<usize>,
+}
+
+/// Arrange the laid out children of a `<ruby>` box into annotated pairs
+/// and size the box to fit them
+///
+/// An explicit width or height of the ruby box is kept.
+pub fn layout_ruby(ruby: &mut LayoutBox) {
+    let mut pairs = Vec::new();
+    let mut current = RubyPair::default();
+    for (index, child) in ruby.children.iter().enumerate() {
+        if is_annotation(&child.node) {
+            current.annotation = Some(index);
+            pairs.push(std::mem::take(&mut current));
+        } else if has_content(child) {
+            current.bases.push(index);
+        }
+    }
+    if !current.bases.is_empty() {
+        pairs.push(current);
+    }
+
+    let extents: Vec<(f32, f32)> = ruby.children.iter().map(inline_extent).collect();
+    let annotation_height = pairs
+        .iter()
+        .filter_map(|pair| pair.annotation)
+        .map(|index| extents[index].1)
+        .fold(0.0, f32::max);
+
+    let mut x = 0.0;
+    let mut base_height: f32 = 0.0;
+    for pair in &pairs {
+        let base_width: f32 = pair.bases.iter().map(|&index| extents[index].0).sum();
+        let annotation_width = pair.annotation.map_or(0.0, |index| extents[index].0);
+        let width = base_width.max(annotation_width);
+        if let Some(index) = pair.annotation {
+            move_box(&mut ruby.children[index], x + (width - annotation_width) / 2.0, 0.0);
+        }
+        let mut base_x = x + (width - base_width) / 2.0;
+        for &index in &pair.bases {
+            move_box(&mut ruby.children[index], base_x, annotation_height);
+            base_x += extents[index].0;
+            base_height = base_height.max(extents[index].1);
+        }
+        x += width;
+    }
+
+    // Children without content, such as whitespace between pairs, end up
+    // on the base row after the pairs
+    for (index, child) in ruby.children.iter_mut().enumerate() {
+        let paired = pairs.iter().any(|pair| pair.annotation == Some(index) || pair.bases.contains(&index));
+        if !paired {
+            move_box(child, x, annotation_height);
+        }
+    }
+
+    if ruby.styles.box_model.width.is_none() {
+        ruby.content.width = x;
+    }
+    if ruby.styles.box_model.height.is_none() {
+        ruby.content.height = annotation_height + base_height;
+    }
+}
+
+/// Check whether a box shows anything: whitespace-only text and hidden
+/// boxes take no part in pairing
+fn has_content(layout_box: &LayoutBox) -> bool {
+    match &layout_box.node.node_type {
+        NodeType::Text(_) => !layout_box.fragments.is_empty(),
+        _ => layout_box.styles.display != crate::DisplayType::None,
+    }
+}
+
+/// Get the width and height a box takes on a line: the extent of its text
+/// fragments and of its children, or its border box if it has neither
+fn inline_extent(layout_box: &LayoutBox) -> (f32, f32) {
+    if layout_box.styles.display == crate::DisplayType::None {
+        return (0.0, 0.0);
+    }
+    let mut extent = (0.0f32, 0.0f32);
+    for fragment in &layout_box.fragments {
+        extent.0 = extent.0.max(fragment.x + fragment.width);
+        extent.1 = extent.1.max(fragment.y + fragment.height);
+    }
+    let mut x = 0.0;
+    for child in &layout_box.children {
+        let (width, height) = inline_extent(child);
+        // Inline children follow each other along the line
+        x += width;
+        extent.1 = extent.1.max(height);
+    }
+    extent.0 = extent.0.max(x);
+    if layout_box.fragments.is_empty() && layout_box.children.is_empty() && !matches!(layout_box.node.node_type, NodeType::Text(_)) {
+        extent = (layout_box.border.width, layout_box.border.height);
+    }
+    extent
+}
+
+/// Move a laid out box to a position in its parent's content box
+fn move_box(layout_box: &mut LayoutBox, x: f32, y: f32) {
+    let (dx, dy) = (x - layout_box.content.x, y - layout_box.content.y);
+    for area in [&mut layout_box.content, &mut layout_box.padding, &mut layout_box.border, &mut layout_box.margin] {
+        area.x += dx;
+        area.y += dy;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{geometry, LayoutEngine};
+    use css_parser::parse_css;
+    use dom::Document;
+
+    #[test]
+    fn test_annotations_are_centered_above_their_bases() {
+        let document = Document::new();
+        let body = document.create_element("body");
+        let ruby = document.create_element("ruby");
+        ruby.set_attribute("id", "reading");
+        for (base, reading) in [("漢", "かんじ"), ("字", "じ")] {
+            ruby.append_child(&document.create_text_node(base));
+            let open = document.create_element("rp");
+            open.append_child(&document.create_text_node("("));
+            ruby.append_child(&open);
+            let annotation = document.create_element("rt");
+            annotation.append_child(&document.create_text_node(reading));
+            ruby.append_child(&annotation);
+        }
+        body.append_child(&ruby);
+        document.root.append_child(&body);
+
+        let engine = LayoutEngine::new(parse_css(""));
+        let layout = engine.layout_document(&document);
+        let ruby_box = &layout.children[0].children[0];
+        assert!(is_ruby(&ruby_box.node));
+
+        // Annotations are set at half the size of their base
+        let annotation = ruby_box.children.iter().find(|child| is_annotation(&child.node)).unwrap();
+        let base = &ruby_box.children[0];
+        assert_eq!(annotation.children[0].fragments[0].height * 2.0, base.fragments[0].height);
+
+        // The first annotation is wider than its base, so the base is
+        // centered under it, and the annotation row sits above the bases
+        let boxes = geometry::border_boxes(&layout);
+        let reading = &annotation.children[0].fragments[0];
+        let first_annotation = boxes[&annotation.node.id];
+        let first_base = boxes[&base.node.id];
+        assert!(reading.width > base.fragments[0].width);
+        assert_eq!(first_annotation.y, 0.0);
+        assert_eq!(first_base.y, reading.height);
+        assert_eq!(first_base.x, (reading.width - base.fragments[0].width) / 2.0);
+
+        // The box grows by the annotation row and shrinks to the pairs,
+        // the second as wide as its base
+        assert_eq!(ruby_box.content.height, reading.height + base.fragments[0].height);
+        assert_eq!(ruby_box.content.width, reading.width + base.fragments[0].width);
+
+        // Fallback parentheses are hidden and take no space
+        let fallback = ruby_box.children.iter().find(|child| child.node.tag_name() == Some("rp")).unwrap();
+        assert_eq!(fallback.styles.display, crate::DisplayType::None);
+    }
+}
@@ -0,0 +1,193 @@
+//! # Spatial Navigation
+//!
+//! This module picks the element the arrow keys move focus to when spatial
+//! navigation is on, for keyboard-only and TV-remote browsing: the
+//! focusable element nearest to the focused one in the pressed direction,
+//! measured on the border boxes of the last layout.
+//!
+//! ## Design Principles
+//!
+//! 1. **Sequential Order Decides Ties**: Candidates are the elements in
+//!    the sequential focus order, positive `tabindex` values first and in
+//!    ascending order, then the rest in tree order. Elements with a
+//!    negative `tabindex` are skipped, and equally near candidates go to
+//!    the one earlier in that order.
+//!
+//! 2. **Straight Ahead First**: Distance along the pressed direction is
+//!    added to twice the distance across it, so a farther element in line
+//!    wins over a nearer one off to the side.
+//!
+//! 3. **Only What Is Rendered**: Elements without a box, hidden elements
+//!    and empty boxes cannot take focus, since the user cannot see them.
+
+use crate::geometry::border_boxes;
+use crate::{Dimensions, DisplayType, LayoutBox, Visibility};
+use dom::Node;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+/// Weight of the distance across the pressed direction
+const CROSS_AXIS_WEIGHT: f32 = 2.0;
+
+/// A direction focus can move in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NavigationDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl NavigationDirection {
+    /// Get the direction of an arrow key's DOM `key` value
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "ArrowUp" => Some(NavigationDirection::Up),
+            "ArrowDown" => Some(NavigationDirection::Down),
+            "ArrowLeft" => Some(NavigationDirection::Left),
+            "ArrowRight" => Some(NavigationDirection::Right),
+            _ => None,
+        }
+    }
+}
+
+/// Get the rendered elements of the sequential focus order, with their
+/// border boxes
+pub fn focus_order(root: &LayoutBox) -> Vec<(Rc<Node>, Dimensions)> {
+    let boxes = border_boxes(root);
+    let mut candidates = Vec::new();
+    collect_candidates(root, &mut HashSet::new(), &mut candidates);
+    let mut ordered: Vec<(i32, Rc<Node>, Dimensions)> = candidates
+        .into_iter()
+        .filter_map(|node| {
+            let tab_index = tab_index(&node)?;
+            let border_box = boxes.get(&node.id).copied()?;
+            (border_box.width > 0.0 && border_box.height > 0.0).then_some((tab_index, node, border_box))
+        })
+        .collect();
+    // Stable, so tree order is kept within a tabindex value
+    ordered.sort_by_key(|(tab_index, _, _)| if *tab_index > 0 { *tab_index } else { i32::MAX });
+    ordered.into_iter().map(|(_, node, border_box)| (node, border_box)).collect()
+}
+
+/// Find the element focus moves to from `focused` in a direction
+///
+/// Without a focused element, or when the focused element is not in the
+/// focus order, the first element of the order is chosen. Returns `None`
+/// when nothing lies in that direction.
+pub fn navigate(root: &LayoutBox, focused: Option<&Rc<Node>>, direction: NavigationDirection) -> Option<Rc<Node>> {
+    let order = focus_order(root);
+    let Some(from) = focused.and_then(|focused| order.iter().find(|(node, _)| node.id == focused.id)).map(|(_, border_box)| *border_box) else {
+        return order.into_iter().next().map(|(node, _)| node);
+    };
+    let focused_id = focused.map(|node| node.id);
+    order
+        .into_iter()
+        .filter(|(node, _)| Some(node.id) != focused_id)
+        .filter_map(|(node, candidate)| distance(&from, &candidate, direction).map(|distance| (distance, node)))
+        // The first of equally near candidates wins
+        .fold(None, |best: Option<(f32, Rc<Node>)>, (distance, node)| match best {
+            Some((best_distance, _)) if best_distance <= distance => best,
+            _ => Some((distance, node)),
+        })
+        .map(|(_, node)| node)
+}
+
+/// Get how far `candidate` is from `from` in a direction, or `None` if it
+/// does not lie entirely beyond `from`'s edge
+fn distance(from: &Dimensions, candidate: &Dimensions, direction: NavigationDirection) -> Option<f32> {
+    let (along, across) = match direction {
+        NavigationDirection::Down => (candidate.y - from.bottom(), span_gap(from.x, from.right(), candidate.x, candidate.right())),
+        NavigationDirection::Up => (from.y - candidate.bottom(), span_gap(from.x, from.right(), candidate.x, candidate.right())),
+        NavigationDirection::Right => (candidate.x - from.right(), span_gap(from.y, from.bottom(), candidate.y, candidate.bottom())),
+        NavigationDirection::Left => (from.x - candidate.right(), span_gap(from.y, from.bottom(), candidate.y, candidate.bottom())),
+    };
+    (along >= 0.0).then_some(along + CROSS_AXIS_WEIGHT * across)
+}
+
+/// Get the gap between two spans on an axis, 0 if they overlap
+fn span_gap(start: f32, end: f32, other_start: f32, other_end: f32) -> f32 {
+    (other_start - end).max(start - other_end).max(0.0)
+}
+
+/// Get the sequential navigation `tabindex` of a focusable element:
+/// `None` if it cannot be focused from the keyboard, 0 without the
+/// attribute
+fn tab_index(node: &Rc<Node>) -> Option<i32> {
+    if !node.is_focusable() {
+        return None;
+    }
+    match node.get_attribute("tabindex").and_then(|value| value.trim().parse::<i32>().ok()) {
+        Some(tab_index) if tab_index < 0 => None,
+        Some(tab_index) => Some(tab_index),
+        None => Some(0),
+    }
+}
+
+/// Collect the visible elements of rendered boxes in tree order
+fn collect_candidates(layout_box: &LayoutBox, seen: &mut HashSet<u64>, candidates: &mut Vec<Rc<Node>>) {
+    if layout_box.styles.display == DisplayType::None {
+        return;
+    }
+    // Anonymous and generated boxes share their element's node
+    if layout_box.styles.visibility.unwrap_or_default() == Visibility::Visible && seen.insert(layout_box.node.id) {
+        candidates.push(Rc::clone(&layout_box.node));
+    }
+    for child in &layout_box.children {
+        collect_candidates(child, seen, candidates);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LayoutEngine;
+    use css_parser::CSSParser;
+    use dom::Document;
+
+    #[test]
+    fn test_arrows_move_to_nearest_in_tabindex_order() {
+        let document = Document::new();
+        let body = document.create_element("body");
+        document.root.append_child(&body);
+        let mut buttons = Vec::new();
+        for (id, tab_index) in [("a", None), ("b", Some("2")), ("c", Some("1")), ("d", Some("-1")), ("e", None)] {
+            let button = document.create_element("div");
+            button.set_attribute("id", id);
+            button.set_attribute("tabindex", tab_index.unwrap_or("0"));
+            button.append_child(&document.create_text_node(id));
+            body.append_child(&button);
+            buttons.push(button);
+        }
+        let css = "div { width: 100px; height: 20px }";
+        let engine = LayoutEngine::new(CSSParser::new(css.to_string()).parse_stylesheet().unwrap());
+        let layout = engine.layout_document(&document);
+        let id = |node: Option<Rc<Node>>| node.and_then(|node| node.get_attribute("id"));
+
+        // Positive tabindex values come first, and -1 is skipped
+        let order: Vec<_> = focus_order(&layout).into_iter().map(|(node, _)| id(Some(node)).unwrap()).collect();
+        assert_eq!(order, vec!["c", "b", "a", "e"]);
+        assert_eq!(id(navigate(&layout, None, NavigationDirection::Down)), Some("c".to_string()));
+
+        // The stacked boxes are reached one by one, skipping `d`
+        let boxes = border_boxes(&layout);
+        assert!(boxes[&buttons[1].id].y > boxes[&buttons[0].id].y);
+        assert_eq!(id(navigate(&layout, Some(&buttons[0]), NavigationDirection::Down)), Some("b".to_string()));
+        assert_eq!(id(navigate(&layout, Some(&buttons[2]), NavigationDirection::Down)), Some("e".to_string()));
+        assert_eq!(id(navigate(&layout, Some(&buttons[2]), NavigationDirection::Up)), Some("b".to_string()));
+        assert_eq!(id(navigate(&layout, Some(&buttons[0]), NavigationDirection::Up)), None);
+        assert_eq!(id(navigate(&layout, Some(&buttons[0]), NavigationDirection::Right)), None);
+    }
+
+    #[test]
+    fn test_distance_prefers_candidates_in_line() {
+        let from = Dimensions::new(0.0, 0.0, 100.0, 20.0);
+        let in_line = Dimensions::new(0.0, 100.0, 100.0, 20.0);
+        let off_to_the_side = Dimensions::new(150.0, 30.0, 100.0, 20.0);
+        let down = |candidate| distance(&from, candidate, NavigationDirection::Down);
+        assert_eq!(down(&in_line), Some(80.0));
+        assert_eq!(down(&off_to_the_side), Some(10.0 + 2.0 * 50.0));
+        assert_eq!(distance(&from, &off_to_the_side, NavigationDirection::Left), None);
+        assert_eq!(distance(&from, &in_line, NavigationDirection::Up), None);
+    }
+}
@@ -0,0 +1,307 @@
+//! # Sticky Positioning
+//!
+//! This module keeps `position: sticky` boxes in place while the page
+//! scrolls, without running layout again.
+//!
+//! ## Design Principles
+//!
+//! 1. **Constraints From Layout**: Layout records a `StickyConstraint` for
+//!    every sticky box. It holds the box's in-flow rectangle, the rectangle
+//!    of its containing block and its insets, all in document coordinates.
+//!
+//! 2. **Offsets At Paint Time**: `ScrollCompositor` turns the constraints
+//!    into a translation per sticky box for the current scroll offset. The
+//!    painter applies the translation to the box and its descendants.
+//!
+//! 3. **Cheap Scrolling**: A scroll only recomputes the offsets. The layout
+//!    tree is untouched, and a repaint is requested only if the scroll
+//!    offset or a sticky offset actually moved.
+
+use crate::{Dimensions, Insets, LayoutBox, Position};
+use std::collections::HashMap;
+
+/// The scroll-independent geometry of a sticky box
+#[derive(Debug, Clone, PartialEq)]
+pub struct StickyConstraint {
+    /// The DOM node of the sticky box
+    pub node_id: u64,
+    /// The box's in-flow rectangle, in document coordinates
+    pub sticky_rect: Dimensions,
+    /// The content rectangle of the box's containing block, in document
+    /// coordinates. The box never leaves this rectangle.
+    pub containing_rect: Dimensions,
+    /// The `top`, `right`, `bottom` and `left` insets
+    pub inset: Insets,
+}
+
+impl StickyConstraint {
+    /// Compute the translation of the sticky box for a scrollport
+    ///
+    /// `scrollport` is the visible rectangle in document coordinates, that
+    /// is the viewport moved by the scroll offset.
+    pub fn offset(&self, scrollport: &Dimensions) -> (f32, f32) {
+        let rect = &self.sticky_rect;
+        let container = &self.containing_rect;
+        let dx = sticky_axis_offset(
+            rect.x,
+            rect.width,
+            container.x,
+            container.right(),
+            scrollport.x,
+            scrollport.right(),
+            self.inset.left,
+            self.inset.right,
+        );
+        let dy = sticky_axis_offset(
+            rect.y,
+            rect.height,
+            container.y,
+            container.bottom(),
+            scrollport.y,
+            scrollport.bottom(),
+            self.inset.top,
+            self.inset.bottom,
+        );
+        (dx, dy)
+    }
+}
+
+/// Compute the sticky offset along one axis
+///
+/// The start inset pushes the box forward while the scrollport edge passes
+/// it, and the end inset pulls it back; both are clamped so the box stays
+/// inside its containing block. The start inset wins when both apply.
+#[allow(clippy::too_many_arguments)]
+fn sticky_axis_offset(
+    start: f32,
+    size: f32,
+    container_start: f32,
+    container_end: f32,
+    view_start: f32,
+    view_end: f32,
+    inset_start: Option<f32>,
+    inset_end: Option<f32>,
+) -> f32 {
+    let end = start + size;
+    let mut offset = 0.0;
+
+    if let Some(inset) = inset_end {
+        let limit = view_end - inset;
+        if end > limit {
+            // Never pulled back past the start of the containing block
+            offset = (limit - end).max((container_start - start).min(0.0));
+        }
+    }
+    if let Some(inset) = inset_start {
+        let limit = view_start + inset;
+        if start + offset < limit {
+            // Never pushed past the end of the containing block
+            offset = (limit - start).min((container_end - end).max(0.0));
+        }
+    }
+    offset
+}
+
+/// Collect the sticky constraints of a laid out tree
+///
+/// Flow layout positions content boxes relative to the parent box, so
+/// offsets are accumulated from `content` as in painting.
+pub fn collect_sticky_constraints(root: &LayoutBox) -> Vec<StickyConstraint> {
+    let mut constraints = Vec::new();
+    collect_constraints(root, 0.0, 0.0, None, &mut constraints);
+    constraints
+}
+
+fn collect_constraints(
+    layout_box: &LayoutBox,
+    parent_x: f32,
+    parent_y: f32,
+    containing_rect: Option<&Dimensions>,
+    constraints: &mut Vec<StickyConstraint>,
+) {
+    let x = parent_x + layout_box.content.x;
+    let y = parent_y + layout_box.content.y;
+    // Boxes laid out from pre-computed styles have no border box
+    let width = layout_box.border.width.max(layout_box.content.width);
+    let height = layout_box.border.height.max(layout_box.content.height);
+
+    if layout_box.styles.position == Position::Sticky {
+        if let Some(containing_rect) = containing_rect {
+            constraints.push(StickyConstraint {
+                node_id: layout_box.node.id,
+                sticky_rect: Dimensions::new(x, y, width, height),
+                containing_rect: *containing_rect,
+                inset: layout_box.styles.inset.clone(),
+            });
+        }
+    }
+
+    let content_rect = Dimensions::new(x, y, layout_box.content.width, layout_box.content.height);
+    for child in &layout_box.children {
+        collect_constraints(child, x, y, Some(&content_rect), constraints);
+    }
+}
+
+/// Scroll state of the root scroller and the sticky offsets it produces
+#[derive(Debug, Clone)]
+pub struct ScrollCompositor {
+    constraints: Vec<StickyConstraint>,
+    viewport: Dimensions,
+    content_size: (f32, f32),
+    scroll_offset: (f32, f32),
+    sticky_offsets: HashMap<u64, (f32, f32)>,
+}
+
+impl ScrollCompositor {
+    /// Create a compositor for a viewport, scrolled to the top
+    pub fn new(constraints: Vec<StickyConstraint>, viewport: Dimensions, content_size: (f32, f32)) -> Self {
+        let mut compositor = ScrollCompositor {
+            constraints,
+            viewport,
+            content_size,
+            scroll_offset: (0.0, 0.0),
+            sticky_offsets: HashMap::new(),
+        };
+        compositor.update_sticky_offsets();
+        compositor
+    }
+
+    /// Create a compositor for a laid out tree
+    pub fn from_layout(root: &LayoutBox, viewport: Dimensions) -> Self {
+        let content_size = (
+            root.margin.width.max(root.content.width),
+            root.margin.height.max(root.content.height),
+        );
+        Self::new(collect_sticky_constraints(root), viewport, content_size)
+    }
+
+    /// Replace the constraints after a relayout, keeping the scroll offset
+    /// where possible
+    pub fn set_layout(&mut self, constraints: Vec<StickyConstraint>, content_size: (f32, f32)) {
+        self.constraints = constraints;
+        self.content_size = content_size;
+        let (x, y) = self.scroll_offset;
+        self.scroll_offset = self.clamp_scroll(x, y);
+        self.update_sticky_offsets();
+    }
+
+    /// Resize the viewport
+    pub fn set_viewport(&mut self, viewport: Dimensions) {
+        self.viewport = viewport;
+        let (x, y) = self.scroll_offset;
+        self.scroll_offset = self.clamp_scroll(x, y);
+        self.update_sticky_offsets();
+    }
+
+    /// Scroll to an offset, clamped to the scrollable range
+    ///
+    /// Returns `true` if anything on screen moved, so a repaint is needed.
+    pub fn scroll_to(&mut self, x: f32, y: f32) -> bool {
+        let offset = self.clamp_scroll(x, y);
+        if offset == self.scroll_offset {
+            return false;
+        }
+        self.scroll_offset = offset;
+        self.update_sticky_offsets();
+        true
+    }
+
+    /// Scroll by a delta, clamped to the scrollable range
+    pub fn scroll_by(&mut self, dx: f32, dy: f32) -> bool {
+        let (x, y) = self.scroll_offset;
+        self.scroll_to(x + dx, y + dy)
+    }
+
+    /// Get the current scroll offset
+    pub fn scroll_offset(&self) -> (f32, f32) {
+        self.scroll_offset
+    }
+
+    /// Get the visible rectangle in document coordinates
+    pub fn scrollport(&self) -> Dimensions {
+        Dimensions::new(self.scroll_offset.0, self.scroll_offset.1, self.viewport.width, self.viewport.height)
+    }
+
+    /// Get the translation of a sticky box for the current scroll offset
+    pub fn sticky_offset(&self, node_id: u64) -> (f32, f32) {
+        self.sticky_offsets.get(&node_id).copied().unwrap_or((0.0, 0.0))
+    }
+
+    /// Get the sticky constraints from the last layout
+    pub fn constraints(&self) -> &[StickyConstraint] {
+        &self.constraints
+    }
+
+    fn clamp_scroll(&self, x: f32, y: f32) -> (f32, f32) {
+        let max_x = (self.content_size.0 - self.viewport.width).max(0.0);
+        let max_y = (self.content_size.1 - self.viewport.height).max(0.0);
+        (x.clamp(0.0, max_x), y.clamp(0.0, max_y))
+    }
+
+    fn update_sticky_offsets(&mut self) {
+        let scrollport = self.scrollport();
+        self.sticky_offsets = self
+            .constraints
+            .iter()
+            .map(|constraint| (constraint.node_id, constraint.offset(&scrollport)))
+            .filter(|(_, offset)| *offset != (0.0, 0.0))
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header_constraint() -> StickyConstraint {
+        StickyConstraint {
+            node_id: 1,
+            sticky_rect: Dimensions::new(0.0, 100.0, 800.0, 50.0),
+            containing_rect: Dimensions::new(0.0, 0.0, 800.0, 1000.0),
+            inset: Insets { top: Some(10.0), ..Insets::default() },
+        }
+    }
+
+    #[test]
+    fn test_sticky_offset_follows_scroll_within_container() {
+        let mut compositor = ScrollCompositor::new(
+            vec![header_constraint()],
+            Dimensions::new(0.0, 0.0, 800.0, 600.0),
+            (800.0, 3000.0),
+        );
+        assert_eq!(compositor.sticky_offset(1), (0.0, 0.0));
+
+        // Not yet at the threshold
+        assert!(compositor.scroll_to(0.0, 50.0));
+        assert_eq!(compositor.sticky_offset(1), (0.0, 0.0));
+
+        // Stuck 10px below the top of the scrollport
+        compositor.scroll_to(0.0, 300.0);
+        assert_eq!(compositor.sticky_offset(1), (0.0, 210.0));
+
+        // Stops at the bottom of its containing block
+        compositor.scroll_to(0.0, 2000.0);
+        assert_eq!(compositor.sticky_offset(1), (0.0, 850.0));
+
+        // Clamped to the scrollable range, so nothing moves
+        compositor.scroll_to(0.0, 2400.0);
+        assert!(!compositor.scroll_by(0.0, 500.0));
+        assert_eq!(compositor.scroll_offset(), (0.0, 2400.0));
+    }
+
+    #[test]
+    fn test_bottom_sticky_offset() {
+        let constraint = StickyConstraint {
+            node_id: 2,
+            sticky_rect: Dimensions::new(0.0, 900.0, 800.0, 40.0),
+            containing_rect: Dimensions::new(0.0, 500.0, 800.0, 500.0),
+            inset: Insets { bottom: Some(0.0), ..Insets::default() },
+        };
+        // Pulled up to the bottom edge of the scrollport
+        assert_eq!(constraint.offset(&Dimensions::new(0.0, 0.0, 800.0, 800.0)), (0.0, -140.0));
+        // But not above the start of its containing block
+        assert_eq!(constraint.offset(&Dimensions::new(0.0, 0.0, 800.0, 300.0)), (0.0, -400.0));
+        // In view: no offset
+        assert_eq!(constraint.offset(&Dimensions::new(0.0, 400.0, 800.0, 800.0)), (0.0, 0.0));
+    }
+}
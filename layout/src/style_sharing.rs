@@ -0,0 +1,251 @@
+//! # Style Sharing
+//!
+//! This module implements style sharing: siblings that no selector can tell
+//! apart, such as the items of a list, get the same computed styles, so
+//! only the first of them runs the cascade and the others reuse its
+//! result.
+//!
+//! ## Design Principles
+//!
+//! 1. **Share Only What Matching Cannot Tell Apart**: Two elements share
+//!    when their parents' styles are shared and they have the same tag,
+//!    attributes and interaction state, and every `:has()` selector gives
+//!    them the same answer. Elements with an `id`, running animations or
+//!    styles that depend on their content or loaded resources, like form
+//!    controls and images, never share.
+//!
+//! 2. **Whole Subtrees Share**: Elements that reused a style join the group
+//!    of the element they reused it from, and their children are keyed by
+//!    that group, so the children of two identical list items share as
+//!    well.
+//!
+//! 3. **One Style Pass at a Time**: The cache only lives while a layout
+//!    computes styles. Mutations between layouts cannot leave stale styles
+//!    behind, and styles computed outside a layout always run the cascade.
+
+use std::collections::HashMap;
+use css_parser::{Selector, Stylesheet};
+use dom::{ElementState, Node};
+use crate::{ComputedStyles, FontContext};
+
+/// How often elements reused a sibling's styles in the last style pass
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StyleSharingStats {
+    /// Elements that reused the styles of another element
+    pub hits: u64,
+    /// Elements that could share but ran the cascade
+    pub misses: u64,
+    /// Elements that can never share, e.g. because they have an `id`
+    pub ineligible: u64,
+}
+
+impl StyleSharingStats {
+    /// Fraction of styled elements that reused another element's styles
+    pub fn hit_rate(&self) -> f64 {
+        let elements = self.hits + self.misses + self.ineligible;
+        if elements == 0 {
+            0.0
+        } else {
+            self.hits as f64 / elements as f64
+        }
+    }
+}
+
+/// Everything an element's computed styles depend on when it can share
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct SharingKey {
+    /// The style group of the parent
+    parent_group: u64,
+    tag: String,
+    /// Attributes sorted by name
+    attributes: Vec<(String, String)>,
+    state: ElementState,
+    /// Whether each `:has()` selector of the stylesheets matches
+    relational: Vec<bool>,
+}
+
+impl SharingKey {
+    pub(crate) fn new(element: &Node, parent_group: u64, relational: &[Selector]) -> Self {
+        let mut attributes: Vec<(String, String)> = element.attributes().into_iter().collect();
+        attributes.sort();
+        SharingKey {
+            parent_group,
+            tag: element.tag_name().unwrap_or_default().to_string(),
+            attributes,
+            state: element.element_state(),
+            relational: relational
+                .iter()
+                .map(|selector| match selector {
+                    Selector::Has(relative) => css_parser::has_matches(relative, element),
+                    _ => false,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SharedStyle {
+    styles: ComputedStyles,
+    /// The font context the styles were computed in
+    context: FontContext,
+    /// The element that ran the cascade
+    group: u64,
+}
+
+/// Computed styles of the current style pass, by sharing key
+#[derive(Debug, Default)]
+pub(crate) struct StyleSharingCache {
+    active: bool,
+    entries: HashMap<SharingKey, SharedStyle>,
+    /// The style group of each element styled in the pass, by node id
+    groups: HashMap<u64, u64>,
+    stats: StyleSharingStats,
+}
+
+impl StyleSharingCache {
+    /// Start a style pass with an empty cache
+    pub(crate) fn begin(&mut self) {
+        *self = StyleSharingCache { active: true, ..StyleSharingCache::default() };
+    }
+
+    /// End the style pass, keeping only its statistics
+    pub(crate) fn end(&mut self) {
+        self.active = false;
+        self.entries.clear();
+        self.groups.clear();
+    }
+
+    pub(crate) fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub(crate) fn stats(&self) -> StyleSharingStats {
+        self.stats
+    }
+
+    /// Get the style group of an element styled in this pass
+    pub(crate) fn group(&self, node_id: u64) -> Option<u64> {
+        self.groups.get(&node_id).copied()
+    }
+
+    /// Get the styles an element can reuse, joining their group
+    pub(crate) fn lookup(&mut self, node_id: u64, key: &SharingKey) -> Option<(ComputedStyles, FontContext)> {
+        let shared = self.entries.get(key)?;
+        if self.groups.insert(node_id, shared.group).is_none() {
+            self.stats.hits += 1;
+        }
+        Some((shared.styles.clone(), shared.context))
+    }
+
+    /// Record the styles an element computed, shareable if it has a key
+    pub(crate) fn insert(&mut self, node_id: u64, key: Option<SharingKey>, styles: &ComputedStyles, context: FontContext) {
+        let first = !self.groups.contains_key(&node_id);
+        self.groups.entry(node_id).or_insert(node_id);
+        match key {
+            Some(key) => {
+                if first {
+                    self.stats.misses += 1;
+                }
+                self.entries.entry(key).or_insert_with(|| SharedStyle { styles: styles.clone(), context, group: node_id });
+            }
+            None if first => self.stats.ineligible += 1,
+            None => {}
+        }
+    }
+}
+
+/// Check whether an element's styles depend only on what a `SharingKey`
+/// captures
+pub(crate) fn can_share(element: &Node) -> bool {
+    let in_details = element
+        .parent
+        .borrow()
+        .upgrade()
+        .is_some_and(|parent| parent.tag_name() == Some("details"));
+    element.tag_name().is_some()
+        && !element.has_attribute("id")
+        && element.form_control_type().is_none()
+        && element.media_kind().is_none()
+        && !crate::replaced::is_replaced_element(element)
+        && !element.is_dialog()
+        && !element.is_popover()
+        && !element.is_fullscreen()
+        && !in_details
+}
+
+/// Collect the `:has()` selectors of stylesheets, whose answers can differ
+/// between siblings
+pub(crate) fn relational_selectors(stylesheets: &[&Stylesheet]) -> Vec<Selector> {
+    fn collect(selector: &Selector, found: &mut Vec<Selector>) {
+        match selector {
+            Selector::Has(_) if !found.contains(selector) => found.push(selector.clone()),
+            Selector::Compound(selectors) | Selector::Group(selectors) => {
+                selectors.iter().for_each(|selector| collect(selector, found));
+            }
+            Selector::Descendant(left, right) | Selector::Child(left, right) |
+            Selector::AdjacentSibling(left, right) | Selector::GeneralSibling(left, right) => {
+                collect(left, found);
+                collect(right, found);
+            }
+            _ => {}
+        }
+    }
+    let mut found = Vec::new();
+    for rule in stylesheets.iter().flat_map(|stylesheet| &stylesheet.rules) {
+        rule.selectors.iter().for_each(|selector| collect(selector, &mut found));
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{LayoutEngine, StyleSharingStats};
+    use css_parser::CSSParser;
+    use dom::{Document, ElementState};
+
+    #[test]
+    fn test_identical_siblings_share_styles() {
+        let document = Document::new();
+        let html = document.create_element("html");
+        document.root.append_child(&html);
+        let list = document.create_element("ul");
+        html.append_child(&list);
+        let mut items = Vec::new();
+        for _ in 0..4 {
+            let item = document.create_element("li");
+            item.set_attribute("class", "item");
+            let label = document.create_element("span");
+            item.append_child(&label);
+            list.append_child(&item);
+            items.push((item, label));
+        }
+        items[1].0.set_attribute("class", "item done");
+        items[2].0.set_attribute("id", "current");
+        let css = ".item { color: #aa2200; } .done { color: #bb3300; } #current { color: #cc4400; } \
+                   li:has(em) { padding: 3px; }";
+        let engine = LayoutEngine::new(CSSParser::new(css.to_string()).parse_stylesheet().unwrap());
+
+        engine.layout_tree(&document.root);
+        // The last item and its span reuse the first's; the other class and
+        // the id need their own cascade
+        let stats = engine.style_sharing_stats();
+        assert_eq!(stats, StyleSharingStats { hits: 2, misses: 7, ineligible: 1 });
+        assert_eq!(stats.hit_rate(), 0.2);
+
+        // Shared styles are the styles the cascade gives
+        let matcher = engine.style_matcher();
+        let colors: Vec<_> = items.iter().map(|(item, _)| matcher.compute_styles(item).color).collect();
+        assert_eq!(colors.iter().map(|color| color.as_deref()).collect::<Vec<_>>(), vec![Some("#aa2200"), Some("#bb3300"), Some("#cc4400"), Some("#aa2200")]);
+
+        // Content that a `:has()` selector sees, and interaction state, tell
+        // siblings apart
+        items[3].1.append_child(&document.create_element("em"));
+        items[0].0.set_element_state(ElementState::HOVER, true);
+        let layout = engine.layout_tree(&document.root);
+        assert_eq!(engine.style_sharing_stats().hits, 0);
+        let list_box = &layout.children[0].children[0];
+        assert_eq!(list_box.children[3].styles.box_model.padding.top, 3.0);
+        assert_eq!(list_box.children[0].styles.box_model.padding.top, 0.0);
+    }
+}
@@ -0,0 +1,296 @@
+//! # Style Structs
+//!
+//! This module groups related computed properties into style structs:
+//! fonts, which are inherited, and the box model, flexbox and grid
+//! properties, which are not. `ComputedStyles` holds each struct behind an
+//! `Rc`, so elements with the same values for a group point at one
+//! allocation instead of each storing its own copy.
+//!
+//! ## Design Principles
+//!
+//! 1. **Shared Until Written**: Cloning computed styles only clones the
+//!    pointers. Setting a property goes through `ComputedStyles::font_mut`
+//!    and friends, which copy the struct first if anyone else holds it.
+//!
+//! 2. **Initial Values Are One Allocation**: Elements that set no flexbox
+//!    or grid property point at the thread's initial struct, and children
+//!    whose font equals their parent's point at the parent's.
+//!
+//! 3. **Interned Per Style Pass**: Structs that end up equal anyway, like
+//!    the fonts of all `h2`s, are deduplicated by a `StyleInterner` while a
+//!    layout computes styles, against a bounded table of recent values so a
+//!    page with many distinct values cannot make interning slow.
+
+use std::collections::HashSet;
+use std::mem::size_of;
+use std::rc::Rc;
+use crate::{
+    AlignContent, AlignItems, AlignSelf, BoxSides, ComputedStyles, FlexDirection, FlexWrap, FontFeatures, FontStyle,
+    FontSynthesis, GridTrack, JustifyContent, LayoutBox, MathExpression,
+};
+
+/// Distinct values of each struct the interner remembers
+pub const INTERNER_CAPACITY: usize = 64;
+
+/// Font properties, inherited
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FontStyles {
+    pub size: Option<f32>,
+    pub family: Option<String>,
+    pub weight: Option<String>,
+    /// Font style; `None` is `normal`
+    pub style: Option<FontStyle>,
+    /// Font variants and OpenType feature settings
+    pub features: FontFeatures,
+    /// What may be synthesized for missing faces
+    pub synthesis: Option<FontSynthesis>,
+}
+
+/// Box model properties
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoxStyles {
+    /// Width of the content area
+    pub width: Option<f32>,
+    /// Height of the content area
+    pub height: Option<f32>,
+    /// Width and height given as math functions with percentages, resolved
+    /// against the containing block when the box is laid out
+    pub width_expression: Option<MathExpression>,
+    pub height_expression: Option<MathExpression>,
+    pub margin: BoxSides,
+    pub border: BoxSides,
+    pub padding: BoxSides,
+}
+
+impl Default for BoxStyles {
+    fn default() -> Self {
+        BoxStyles {
+            width: None,
+            height: None,
+            width_expression: None,
+            height_expression: None,
+            margin: BoxSides::new(0.0),
+            border: BoxSides::new(0.0),
+            padding: BoxSides::new(0.0),
+        }
+    }
+}
+
+/// Flexbox properties
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FlexStyles {
+    pub direction: Option<FlexDirection>,
+    pub wrap: Option<FlexWrap>,
+    pub justify_content: Option<JustifyContent>,
+    pub align_items: Option<AlignItems>,
+    pub align_content: Option<AlignContent>,
+    pub grow: Option<f32>,
+    pub shrink: Option<f32>,
+    pub basis: Option<f32>,
+    pub align_self: Option<AlignSelf>,
+    /// Position among flex items; lower values come first
+    pub order: i32,
+}
+
+/// Grid properties
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GridStyles {
+    pub template_columns: Option<Vec<GridTrack>>,
+    pub template_rows: Option<Vec<GridTrack>>,
+    pub gap: Option<f32>,
+    pub column_gap: Option<f32>,
+    pub row_gap: Option<f32>,
+    pub column_start: Option<i32>,
+    pub column_end: Option<i32>,
+    pub row_start: Option<i32>,
+    pub row_end: Option<i32>,
+}
+
+macro_rules! initial_struct {
+    ($ty:ty) => {
+        impl $ty {
+            /// The thread's shared struct of initial values
+            pub fn initial() -> Rc<$ty> {
+                thread_local! {
+                    static INITIAL: Rc<$ty> = Rc::new(<$ty>::default());
+                }
+                INITIAL.with(Rc::clone)
+            }
+        }
+    };
+}
+
+initial_struct!(FontStyles);
+initial_struct!(BoxStyles);
+initial_struct!(FlexStyles);
+initial_struct!(GridStyles);
+
+/// Recent distinct values of one style struct
+#[derive(Debug)]
+struct InternTable<T> {
+    values: Vec<Rc<T>>,
+}
+
+impl<T: PartialEq> InternTable<T> {
+    /// Point `value` at an equal remembered struct, or remember it
+    fn intern(&mut self, value: &mut Rc<T>) {
+        if let Some(index) = self.values.iter().position(|known| Rc::ptr_eq(known, value) || **known == **value) {
+            *value = Rc::clone(&self.values[index]);
+            // Keep the most used values at the front
+            if index > 0 {
+                self.values.swap(index, index - 1);
+            }
+            return;
+        }
+        if self.values.len() == INTERNER_CAPACITY {
+            self.values.pop();
+        }
+        self.values.push(Rc::clone(value));
+    }
+}
+
+/// Deduplicates the style structs of computed styles
+#[derive(Debug)]
+pub struct StyleInterner {
+    fonts: InternTable<FontStyles>,
+    boxes: InternTable<BoxStyles>,
+    flex: InternTable<FlexStyles>,
+    grid: InternTable<GridStyles>,
+}
+
+impl Default for StyleInterner {
+    /// An interner that knows the initial structs
+    fn default() -> Self {
+        StyleInterner {
+            fonts: InternTable { values: vec![FontStyles::initial()] },
+            boxes: InternTable { values: vec![BoxStyles::initial()] },
+            flex: InternTable { values: vec![FlexStyles::initial()] },
+            grid: InternTable { values: vec![GridStyles::initial()] },
+        }
+    }
+}
+
+impl StyleInterner {
+    /// Point each struct of `styles` at an equal one seen before
+    pub fn intern(&mut self, styles: &mut ComputedStyles) {
+        self.fonts.intern(&mut styles.font);
+        self.boxes.intern(&mut styles.box_model);
+        self.flex.intern(&mut styles.flex);
+        self.grid.intern(&mut styles.grid);
+    }
+}
+
+/// Shallow memory taken by the computed styles of a layout tree
+///
+/// Strings and vectors inside the styles are not counted; they are the
+/// same with or without sharing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StyleMemory {
+    /// Boxes in the tree
+    pub boxes: usize,
+    /// Bytes if every box stored its own copy of each struct
+    pub unshared_bytes: usize,
+    /// Bytes with structs shared between boxes
+    pub shared_bytes: usize,
+    /// Distinct style struct allocations
+    pub struct_allocations: usize,
+}
+
+impl StyleMemory {
+    /// Measure the styles of a layout tree
+    pub fn measure(root: &LayoutBox) -> Self {
+        fn visit(layout_box: &LayoutBox, memory: &mut StyleMemory, seen: &mut HashSet<*const ()>) {
+            let styles = &layout_box.styles;
+            memory.boxes += 1;
+            memory.unshared_bytes += UNSHARED_STYLE_BYTES;
+            memory.shared_bytes += size_of::<ComputedStyles>();
+            let structs = [
+                (Rc::as_ptr(&styles.font) as *const (), allocation_size::<FontStyles>()),
+                (Rc::as_ptr(&styles.box_model) as *const (), allocation_size::<BoxStyles>()),
+                (Rc::as_ptr(&styles.flex) as *const (), allocation_size::<FlexStyles>()),
+                (Rc::as_ptr(&styles.grid) as *const (), allocation_size::<GridStyles>()),
+            ];
+            for (pointer, size) in structs {
+                if seen.insert(pointer) {
+                    memory.shared_bytes += size;
+                    memory.struct_allocations += 1;
+                }
+            }
+            for child in &layout_box.children {
+                visit(child, memory, seen);
+            }
+        }
+        let mut memory = StyleMemory::default();
+        visit(root, &mut memory, &mut HashSet::new());
+        memory
+    }
+
+    /// Fraction of the unshared size saved by sharing
+    pub fn savings(&self) -> f64 {
+        if self.unshared_bytes == 0 {
+            0.0
+        } else {
+            1.0 - self.shared_bytes as f64 / self.unshared_bytes as f64
+        }
+    }
+}
+
+/// Bytes computed styles would take with the structs inline instead of
+/// behind their four pointers
+const UNSHARED_STYLE_BYTES: usize = size_of::<ComputedStyles>() - 4 * size_of::<Rc<FontStyles>>()
+    + size_of::<FontStyles>() + size_of::<BoxStyles>() + size_of::<FlexStyles>() + size_of::<GridStyles>();
+
+/// Bytes of an `Rc` allocation holding a `T`, with its two counts
+fn allocation_size<T>() -> usize {
+    size_of::<T>() + 2 * size_of::<usize>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LayoutEngine;
+    use css_parser::CSSParser;
+    use dom::Document;
+
+    #[test]
+    fn test_equal_style_structs_share_one_allocation() {
+        let document = Document::new();
+        let html = document.create_element("html");
+        document.root.append_child(&html);
+        for index in 0..20 {
+            let heading = document.create_element("h2");
+            heading.set_attribute("id", &format!("h{}", index));
+            html.append_child(&heading);
+            let paragraph = document.create_element("p");
+            paragraph.set_attribute("id", &format!("p{}", index));
+            html.append_child(&paragraph);
+        }
+        let css = "h2 { font-size: 20px; margin: 4px; } p { display: flex; }";
+        let engine = LayoutEngine::new(CSSParser::new(css.to_string()).parse_stylesheet().unwrap());
+        let layout = engine.layout_tree(&document.root);
+
+        // Every heading has an id, so none of them share styles, but their
+        // fonts and boxes are interned to one allocation
+        let boxes = &layout.children[0].children;
+        let (first_heading, first_paragraph) = (&boxes[0].styles, &boxes[1].styles);
+        assert_eq!(first_heading.font.size, Some(20.0));
+        assert!(boxes.iter().step_by(2).all(|heading| Rc::ptr_eq(&heading.styles.font, &first_heading.font)));
+        assert!(boxes.iter().step_by(2).all(|heading| Rc::ptr_eq(&heading.styles.box_model, &first_heading.box_model)));
+        // Paragraphs inherit the font of the root, and no element sets a
+        // grid property
+        assert!(Rc::ptr_eq(&first_paragraph.font, &layout.children[0].styles.font));
+        assert!(boxes.iter().all(|child| Rc::ptr_eq(&child.styles.grid, &GridStyles::initial())));
+
+        // Writing copies a shared struct first
+        let mut styles = first_heading.clone();
+        styles.font_mut().size = Some(30.0);
+        assert_eq!(first_heading.font.size, Some(20.0));
+        assert!(Rc::ptr_eq(&styles.box_model, &first_heading.box_model));
+
+        let memory = StyleMemory::measure(&layout);
+        assert_eq!(memory.boxes, 1 + 1 + 40);
+        assert!(memory.struct_allocations < 12);
+        assert!(memory.shared_bytes < memory.unshared_bytes);
+        assert!(memory.savings() > 0.3);
+    }
+}
@@ -0,0 +1,697 @@
+//! # Translatable Text
+//!
+//! This module extracts the visible text of a page as segments a
+//! translation service or a pseudo-localizer can work on, and writes their
+//! translations back into the document.
+//!
+//! ## Design Principles
+//!
+//! 1. **What the User Sees**: Segments are taken from the layout tree like
+//!    `innerText`, so text that is not rendered is not translated. Each
+//!    block's inline content is one segment, since sentences do not cross
+//!    block boundaries.
+//! 2. **Markup Stays Put**: Inline elements become numbered placeholders
+//!    such as `<g1>…</g1>` in the segment source, the way translation
+//!    memories mark them. A translation may move text between and into
+//!    them, but the elements themselves are never replaced, so links keep
+//!    their targets and listeners.
+//! 3. **Stable References**: A segment holds the text nodes it was made of
+//!    and is named after its block's node id, so a translation fetched
+//!    later still lands where the text was.
+//! 4. **Authors Can Opt Out**: Elements with `translate="no"` become empty
+//!    placeholders like `<x1/>` whose text is never touched.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use dom::Node;
+use crate::inner_text::{collapse_whitespace, UNRENDERED_ELEMENTS};
+use crate::{DisplayType, LayoutBox, Visibility};
+
+/// Name of a segment: the node id of its block and the position of the
+/// segment among the block's segments
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SegmentId {
+    pub node: u64,
+    pub index: usize,
+}
+
+impl fmt::Display for SegmentId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.node, self.index)
+    }
+}
+
+/// Why a translation could not be injected into a segment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TranslationError {
+    /// The translation is not well-formed, e.g. an unclosed placeholder
+    Malformed(String),
+    /// A placeholder of the source is missing from the translation
+    MissingPlaceholder(String),
+    /// The translation has a placeholder the source does not, repeats one
+    /// or moves one into another
+    UnexpectedPlaceholder(String),
+}
+
+impl fmt::Display for TranslationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TranslationError::Malformed(reason) => write!(f, "malformed translation: {}", reason),
+            TranslationError::MissingPlaceholder(placeholder) => write!(f, "translation is missing placeholder {}", placeholder),
+            TranslationError::UnexpectedPlaceholder(placeholder) => write!(f, "unexpected placeholder {} in translation", placeholder),
+        }
+    }
+}
+
+impl std::error::Error for TranslationError {}
+
+/// A placeholder of a segment source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Placeholder {
+    /// `<gN>…</gN>` around an inline element; `Group(0)` is the segment
+    Group(usize),
+    /// `<xN/>` for content that is not translated
+    Opaque(usize),
+}
+
+impl fmt::Display for Placeholder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Placeholder::Group(number) => write!(f, "<g{}>", number),
+            Placeholder::Opaque(number) => write!(f, "<x{}/>", number),
+        }
+    }
+}
+
+/// Part of the inline content of a segment
+#[derive(Debug, Clone)]
+enum Piece {
+    /// A text node, by its index in the segment's runs
+    Run(usize),
+    Group(Group),
+    /// An element whose content is not translated
+    Opaque(usize),
+}
+
+/// An inline element of a segment, or the segment itself
+#[derive(Debug, Clone, Default)]
+struct Group {
+    number: usize,
+    pieces: Vec<Piece>,
+}
+
+impl Group {
+    fn has_runs(&self) -> bool {
+        self.pieces.iter().any(|piece| match piece {
+            Piece::Run(_) => true,
+            Piece::Group(group) => group.has_runs(),
+            Piece::Opaque(_) => false,
+        })
+    }
+
+    /// The runs of the group and its descendants in document order
+    fn runs(&self) -> Vec<usize> {
+        self.pieces
+            .iter()
+            .flat_map(|piece| match piece {
+                Piece::Run(run) => vec![*run],
+                Piece::Group(group) => group.runs(),
+                Piece::Opaque(_) => Vec::new(),
+            })
+            .collect()
+    }
+}
+
+/// A token of a segment source
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Text(String),
+    Open(usize),
+    Close(usize),
+    Opaque(usize),
+}
+
+/// The visible text of one block, with its inline markup as placeholders
+#[derive(Debug, Clone)]
+pub struct TextSegment {
+    pub id: SegmentId,
+    /// The block the text is laid out in
+    pub block: Rc<Node>,
+    /// The language of the block, from the nearest `lang` attribute
+    pub lang: Option<String>,
+    /// The text with whitespace collapsed and inline elements marked by
+    /// placeholders, with `<`, `>` and `&` escaped
+    pub source: String,
+    /// The text nodes of the segment in document order
+    runs: Vec<Rc<Node>>,
+    root: Group,
+}
+
+impl TextSegment {
+    /// Get the text of the segment without placeholders
+    pub fn text(&self) -> String {
+        tokenize(&self.source)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|token| match token {
+                Token::Text(text) => Some(text),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Get the text nodes the segment was extracted from
+    pub fn text_nodes(&self) -> &[Rc<Node>] {
+        &self.runs
+    }
+
+    /// Replace the text of the segment with a translation of its source
+    ///
+    /// The translation must keep every placeholder of the source, each in
+    /// the same enclosing placeholder. Text between placeholders goes to
+    /// the text nodes between the same elements. Returns the number of text
+    /// nodes changed.
+    pub fn inject(&self, translation: &str) -> Result<usize, TranslationError> {
+        let levels = parse_levels(translation)?;
+        let mut expected = HashMap::new();
+        placeholder_parents(&self.root, &mut expected);
+        for (placeholder, level) in &levels {
+            if *placeholder != Placeholder::Group(0) && expected.get(placeholder) != Some(&level.parent) {
+                return Err(TranslationError::UnexpectedPlaceholder(placeholder.to_string()));
+            }
+        }
+        if let Some(missing) = expected.keys().find(|placeholder| !levels.contains_key(placeholder)) {
+            return Err(TranslationError::MissingPlaceholder(missing.to_string()));
+        }
+
+        let mut texts = vec![RunText::default(); self.runs.len()];
+        assign_texts(&self.root, &levels, &mut texts);
+        let mut changed = 0;
+        for (run, text) in self.runs.iter().zip(texts) {
+            let text = format!("{}{}{}", text.prefix, text.own, text.suffix);
+            if run.character_data().as_deref() != Some(text.as_str()) {
+                run.set_character_data(&text);
+                changed += 1;
+            }
+        }
+        Ok(changed)
+    }
+}
+
+/// Extract the visible text segments of a laid-out document in document
+/// order
+pub fn text_segments(root: &LayoutBox) -> Vec<TextSegment> {
+    let mut segments = Vec::new();
+    collect_segments(root, &mut segments);
+    segments
+}
+
+/// Turn a segment source into a pseudo-localized one, for finding text
+/// that is not translated or that overflows when translations get longer
+///
+/// Letters get accents, text is padded by about a third and the segment is
+/// bracketed; placeholders are kept.
+pub fn pseudo_localize(source: &str) -> String {
+    let mut output = String::from("[");
+    let mut letters: usize = 0;
+    let mut chars = source.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '<' | '&' => {
+                // Copy placeholders and escapes unchanged
+                let end = if ch == '<' { '>' } else { ';' };
+                output.push(ch);
+                for ch in chars.by_ref() {
+                    output.push(ch);
+                    if ch == end {
+                        break;
+                    }
+                }
+            }
+            ch => {
+                if ch.is_alphabetic() {
+                    letters += 1;
+                }
+                output.push(accented(ch));
+            }
+        }
+    }
+    output.push(' ');
+    output.push_str(&"~".repeat(letters.div_ceil(3)));
+    output.push(']');
+    output
+}
+
+fn accented(ch: char) -> char {
+    match ch {
+        'a' => 'á',
+        'c' => 'ç',
+        'e' => 'é',
+        'i' => 'í',
+        'n' => 'ñ',
+        'o' => 'ó',
+        'u' => 'ú',
+        'y' => 'ý',
+        'A' => 'Å',
+        'C' => 'Ç',
+        'E' => 'É',
+        'I' => 'Î',
+        'N' => 'Ñ',
+        'O' => 'Ö',
+        'U' => 'Ü',
+        ch => ch,
+    }
+}
+
+/// Check whether a box takes part in the inline content of its parent
+fn is_inline(layout_box: &LayoutBox) -> bool {
+    layout_box.node.character_data().is_some() || matches!(layout_box.styles.display, DisplayType::Inline | DisplayType::InlineBlock | DisplayType::Contents)
+}
+
+fn is_rendered(layout_box: &LayoutBox) -> bool {
+    let tag = layout_box.node.tag_name().map(|tag| tag.to_ascii_lowercase());
+    layout_box.styles.display != DisplayType::None && !tag.is_some_and(|tag| UNRENDERED_ELEMENTS.contains(&tag.as_str()))
+}
+
+/// Append the segments of a block and the blocks in it
+fn collect_segments(block: &LayoutBox, segments: &mut Vec<TextSegment>) {
+    if !is_rendered(block) {
+        return;
+    }
+    let mut builder = SegmentBuilder::new(block, 0);
+    let mut nested = Vec::new();
+    for child in &block.children {
+        if is_inline(child) {
+            builder.add_inline(child, &mut nested);
+            continue;
+        }
+        // A block in between ends the segment
+        let index = builder.id.index;
+        if let Some(segment) = builder.finish() {
+            segments.push(segment);
+        }
+        segments.append(&mut nested);
+        collect_segments(child, segments);
+        builder = SegmentBuilder::new(block, index + 1);
+    }
+    if let Some(segment) = builder.finish() {
+        segments.push(segment);
+    }
+    segments.append(&mut nested);
+}
+
+/// Collects the inline content of a block into a segment
+struct SegmentBuilder {
+    id: SegmentId,
+    block: Rc<Node>,
+    lang: Option<String>,
+    runs: Vec<Rc<Node>>,
+    /// The segment and the inline elements being collected
+    stack: Vec<Group>,
+    opaque_count: usize,
+}
+
+impl SegmentBuilder {
+    fn new(block: &LayoutBox, index: usize) -> Self {
+        SegmentBuilder {
+            id: SegmentId { node: block.node.id, index },
+            block: Rc::clone(&block.node),
+            lang: block.styles.lang.clone(),
+            runs: Vec::new(),
+            stack: vec![Group::default()],
+            opaque_count: 0,
+        }
+    }
+
+    fn push_piece(&mut self, piece: Piece) {
+        if let Some(group) = self.stack.last_mut() {
+            group.pieces.push(piece);
+        }
+    }
+
+    /// Add an inline box; blocks found inside, such as inline blocks, get
+    /// their own segments in `nested`
+    fn add_inline(&mut self, layout_box: &LayoutBox, nested: &mut Vec<TextSegment>) {
+        if !is_rendered(layout_box) {
+            return;
+        }
+        if layout_box.node.character_data().is_some() {
+            if layout_box.styles.visibility.unwrap_or_default() == Visibility::Visible {
+                self.runs.push(Rc::clone(&layout_box.node));
+                self.push_piece(Piece::Run(self.runs.len() - 1));
+            }
+            return;
+        }
+        let untranslated = layout_box.node.get_attribute("translate").is_some_and(|value| value.trim().eq_ignore_ascii_case("no"));
+        if untranslated || !is_inline(layout_box) || layout_box.styles.display == DisplayType::InlineBlock {
+            self.opaque_count += 1;
+            self.push_piece(Piece::Opaque(self.opaque_count));
+            if !untranslated {
+                collect_segments(layout_box, nested);
+            }
+            return;
+        }
+        self.stack.push(Group::default());
+        for child in &layout_box.children {
+            self.add_inline(child, nested);
+        }
+        let group = self.stack.pop().unwrap_or_default();
+        // Elements without text, such as images, need no placeholder
+        if group.has_runs() {
+            self.push_piece(Piece::Group(group));
+        } else {
+            for piece in group.pieces.into_iter().filter(|piece| matches!(piece, Piece::Opaque(_))) {
+                self.push_piece(piece);
+            }
+        }
+    }
+
+    fn finish(mut self) -> Option<TextSegment> {
+        let mut root = self.stack.swap_remove(0);
+        let mut next_group = 1;
+        number_groups(&mut root, &mut next_group);
+        let mut tokens = Vec::new();
+        self.tokens(&root, &mut tokens);
+        trim_tokens(&mut tokens);
+        if !tokens.iter().any(|token| matches!(token, Token::Text(text) if !text.trim().is_empty())) {
+            return None;
+        }
+        let source = tokens.iter().map(serialize_token).collect();
+        Some(TextSegment { id: self.id, block: self.block, lang: self.lang, source, runs: self.runs, root })
+    }
+
+    fn tokens(&self, group: &Group, tokens: &mut Vec<Token>) {
+        for piece in &group.pieces {
+            match piece {
+                Piece::Run(run) => {
+                    let text = collapse_whitespace(&self.runs[*run].character_data().unwrap_or_default());
+                    // Whitespace collapses across text nodes and inline
+                    // element boundaries, but not across untranslated content
+                    let previous_space = tokens.iter().rev().find_map(|token| match token {
+                        Token::Text(text) => Some(text.ends_with(' ')),
+                        Token::Opaque(_) => Some(false),
+                        Token::Open(_) | Token::Close(_) => None,
+                    });
+                    let text = if previous_space.unwrap_or(true) { text.trim_start_matches(' ').to_string() } else { text };
+                    tokens.push(Token::Text(text));
+                }
+                Piece::Group(child) => {
+                    tokens.push(Token::Open(child.number));
+                    self.tokens(child, tokens);
+                    tokens.push(Token::Close(child.number));
+                }
+                Piece::Opaque(number) => tokens.push(Token::Opaque(*number)),
+            }
+        }
+    }
+}
+
+/// Number the groups of a segment in document order
+fn number_groups(group: &mut Group, next: &mut usize) {
+    for piece in &mut group.pieces {
+        if let Piece::Group(child) = piece {
+            child.number = *next;
+            *next += 1;
+            number_groups(child, next);
+        }
+    }
+}
+
+/// Remove the spaces at the end of a segment source
+fn trim_tokens(tokens: &mut [Token]) {
+    for token in tokens.iter_mut().rev() {
+        if let Token::Text(text) = token {
+            text.truncate(text.trim_end_matches(' ').len());
+            if !text.is_empty() {
+                break;
+            }
+        }
+    }
+}
+
+fn serialize_token(token: &Token) -> String {
+    match token {
+        Token::Text(text) => text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;"),
+        Token::Open(number) => format!("<g{}>", number),
+        Token::Close(number) => format!("</g{}>", number),
+        Token::Opaque(number) => format!("<x{}/>", number),
+    }
+}
+
+/// Split a segment source or translation into tokens
+fn tokenize(source: &str) -> Result<Vec<Token>, TranslationError> {
+    let mut tokens = Vec::new();
+    let mut text = String::new();
+    let mut rest = source;
+    while let Some(ch) = rest.chars().next() {
+        if ch == '<' {
+            let end = rest.find('>').ok_or_else(|| TranslationError::Malformed("unterminated placeholder".to_string()))?;
+            let tag = &rest[1..end];
+            let number = |digits: &str| digits.parse::<usize>().ok().filter(|number| *number > 0);
+            let token = if let Some(digits) = tag.strip_prefix("/g") {
+                number(digits).map(Token::Close)
+            } else if let Some(digits) = tag.strip_prefix('g') {
+                number(digits).map(Token::Open)
+            } else if let Some(digits) = tag.strip_prefix('x').and_then(|tag| tag.strip_suffix('/')) {
+                number(digits).map(Token::Opaque)
+            } else {
+                None
+            };
+            let token = token.ok_or_else(|| TranslationError::Malformed(format!("unknown placeholder <{}>", tag)))?;
+            if !text.is_empty() {
+                tokens.push(Token::Text(std::mem::take(&mut text)));
+            }
+            tokens.push(token);
+            rest = &rest[end + 1..];
+            continue;
+        }
+        let escape = [("&lt;", '<'), ("&gt;", '>'), ("&amp;", '&')].into_iter().find(|(escape, _)| rest.starts_with(escape));
+        match escape {
+            Some((escape, unescaped)) => {
+                text.push(unescaped);
+                rest = &rest[escape.len()..];
+            }
+            None => {
+                text.push(ch);
+                rest = &rest[ch.len_utf8()..];
+            }
+        }
+    }
+    if !text.is_empty() {
+        tokens.push(Token::Text(text));
+    }
+    Ok(tokens)
+}
+
+/// The content of one placeholder of a translation
+#[derive(Debug, Default)]
+struct Level {
+    /// The enclosing placeholder
+    parent: Placeholder,
+    /// Text before, between and after the placeholders directly inside
+    slots: Vec<String>,
+}
+
+impl Default for Placeholder {
+    fn default() -> Self {
+        Placeholder::Group(0)
+    }
+}
+
+/// Parse a translation into the text inside each of its placeholders
+fn parse_levels(translation: &str) -> Result<HashMap<Placeholder, Level>, TranslationError> {
+    let mut levels = HashMap::new();
+    levels.insert(Placeholder::Group(0), Level { parent: Placeholder::Group(0), slots: vec![String::new()] });
+    let mut stack = vec![0];
+    for token in tokenize(translation)? {
+        let current = Placeholder::Group(*stack.last().unwrap_or(&0));
+        let child = match token {
+            Token::Text(text) => {
+                if let Some(slot) = levels.get_mut(&current).and_then(|level| level.slots.last_mut()) {
+                    slot.push_str(&text);
+                }
+                continue;
+            }
+            Token::Close(number) => {
+                if stack.len() == 1 || stack.pop() != Some(number) {
+                    return Err(TranslationError::Malformed(format!("unexpected </g{}>", number)));
+                }
+                continue;
+            }
+            Token::Open(number) => {
+                stack.push(number);
+                Placeholder::Group(number)
+            }
+            Token::Opaque(number) => Placeholder::Opaque(number),
+        };
+        if levels.contains_key(&child) {
+            return Err(TranslationError::UnexpectedPlaceholder(child.to_string()));
+        }
+        if let Some(level) = levels.get_mut(&current) {
+            level.slots.push(String::new());
+        }
+        levels.insert(child, Level { parent: current, slots: vec![String::new()] });
+    }
+    if stack.len() > 1 {
+        return Err(TranslationError::Malformed(format!("unclosed <g{}>", stack[stack.len() - 1])));
+    }
+    Ok(levels)
+}
+
+/// Record the enclosing placeholder of every placeholder of a segment
+fn placeholder_parents(group: &Group, parents: &mut HashMap<Placeholder, Placeholder>) {
+    for piece in &group.pieces {
+        match piece {
+            Piece::Run(_) => {}
+            Piece::Group(child) => {
+                parents.insert(Placeholder::Group(child.number), Placeholder::Group(group.number));
+                placeholder_parents(child, parents);
+            }
+            Piece::Opaque(number) => {
+                parents.insert(Placeholder::Opaque(*number), Placeholder::Group(group.number));
+            }
+        }
+    }
+}
+
+/// The new text of a text node: its own slot's text, and text of slots
+/// without text nodes placed around it
+#[derive(Debug, Clone, Default)]
+struct RunText {
+    prefix: String,
+    own: String,
+    suffix: String,
+}
+
+/// Distribute the text of a translated group over the group's text nodes
+fn assign_texts(group: &Group, levels: &HashMap<Placeholder, Level>, texts: &mut [RunText]) {
+    let Some(level) = levels.get(&Placeholder::Group(group.number)) else {
+        return;
+    };
+    // The pieces of each slot, split at child placeholders
+    let mut slots: Vec<Vec<&Piece>> = vec![Vec::new()];
+    for piece in &group.pieces {
+        match piece {
+            Piece::Run(_) => {
+                if let Some(slot) = slots.last_mut() {
+                    slot.push(piece);
+                }
+            }
+            Piece::Group(_) | Piece::Opaque(_) => slots.push(Vec::new()),
+        }
+    }
+    let runs_of = |slot: &[&Piece]| -> Vec<usize> {
+        slot.iter().filter_map(|piece| if let Piece::Run(run) = piece { Some(*run) } else { None }).collect()
+    };
+    let children: Vec<&Piece> = group.pieces.iter().filter(|piece| !matches!(piece, Piece::Run(_))).collect();
+    let deep_runs = |piece: &Piece| -> Vec<usize> {
+        match piece {
+            Piece::Group(child) => child.runs(),
+            _ => Vec::new(),
+        }
+    };
+
+    for (index, slot) in slots.iter().enumerate() {
+        let text = level.slots.get(index).cloned().unwrap_or_default();
+        let runs = runs_of(slot);
+        if let Some(first) = runs.first() {
+            texts[*first].own = text;
+            continue;
+        }
+        if text.is_empty() {
+            continue;
+        }
+        // No text node between these elements: attach the text to the
+        // nearest one before, else after
+        let before = slots[..index].iter().zip(&children).flat_map(|(slot, child)| {
+            let mut runs = runs_of(slot);
+            runs.extend(deep_runs(child));
+            runs
+        });
+        if let Some(run) = before.last() {
+            texts[run].suffix.push_str(&text);
+            continue;
+        }
+        let mut after = children[index..].iter().zip(&slots[index + 1..]).flat_map(|(child, slot)| {
+            let mut runs = deep_runs(child);
+            runs.extend(runs_of(slot));
+            runs
+        });
+        if let Some(run) = after.next() {
+            texts[run].prefix.insert_str(0, &text);
+        }
+    }
+    for piece in &group.pieces {
+        if let Piece::Group(child) = piece {
+            assign_texts(child, levels, texts);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LayoutEngine;
+    use css_parser::parse_css;
+    use dom::Document;
+
+    #[test]
+    fn test_segments_are_extracted_and_translated() {
+        let document = Document::new();
+        let element = |parent: &Rc<Node>, tag: &str, attributes: &[(&str, &str)]| {
+            let node = document.create_element(tag);
+            for (name, value) in attributes {
+                node.set_attribute(name, value);
+            }
+            parent.append_child(&node);
+            node
+        };
+        let text = |parent: &Rc<Node>, data: &str| {
+            let node = document.create_text_node(data);
+            parent.append_child(&node);
+            node
+        };
+
+        let body = element(&document.root, "div", &[("lang", "en")]);
+        let paragraph = element(&body, "p", &[]);
+        text(&paragraph, "\n  Read the ");
+        let link = element(&paragraph, "a", &[("href", "/docs")]);
+        let link_text = text(&link, "docs");
+        text(&paragraph, " for ");
+        let code = element(&paragraph, "code", &[("translate", "no")]);
+        text(&code, "cargo <build>");
+        text(&paragraph, " & more. ");
+        let hidden = element(&body, "div", &[("class", "gone")]);
+        text(&hidden, "Not rendered");
+        let french = element(&body, "p", &[("lang", "fr")]);
+        text(&french, "Bonjour");
+
+        let stylesheet = parse_css("p {\n  display: block;\n}\n.gone {\n  display: none;\n}\na {\n  display: inline;\n}\ncode {\n  display: inline;\n}");
+        let layout = LayoutEngine::new(stylesheet).layout_document(&document);
+        let segments = text_segments(&layout);
+        assert_eq!(segments.len(), 2);
+        let segment = &segments[0];
+        assert_eq!(segment.id, SegmentId { node: paragraph.id, index: 0 });
+        assert_eq!(segment.lang.as_deref(), Some("en"));
+        assert_eq!(segment.source, "Read the <g1>docs</g1> for <x1/> &amp; more.");
+        assert_eq!(segment.text(), "Read the docs for  & more.");
+        assert_eq!(segments[1].lang.as_deref(), Some("fr"));
+
+        // Translations move text around the link, which stays in place
+        assert_eq!(segment.inject("Lisez <g1>la doc</g1> pour <x1/> &amp; plus.").unwrap(), 4);
+        assert_eq!(paragraph.text_content(), "Lisez la doc pour cargo <build> & plus.");
+        assert!(Rc::ptr_eq(&link.children.borrow()[0], &link_text));
+        segment.inject("<g1>Docs</g1> <x1/>").unwrap();
+        assert_eq!(paragraph.text_content(), "Docs cargo <build>");
+
+        assert_eq!(segment.inject("Lisez <x1/>"), Err(TranslationError::MissingPlaceholder("<g1>".to_string())));
+        assert!(matches!(segment.inject("<g1>a <x1/></g1>"), Err(TranslationError::UnexpectedPlaceholder(_))));
+        assert!(matches!(segment.inject("<g1>a"), Err(TranslationError::Malformed(_))));
+
+        let pseudo = pseudo_localize(&segments[1].source);
+        assert_eq!(pseudo, "[Bóñjóúr ~~~]");
+        segments[1].inject(&pseudo).unwrap();
+        assert_eq!(french.text_content(), pseudo);
+    }
+}
@@ -0,0 +1,116 @@
+//! # Base URLs
+//!
+//! This module resolves the relative URLs a document contains. Every
+//! subresource, link, form action, stylesheet `url()` and script-initiated
+//! request is resolved by a `UrlResolver`, so `<base href>` is honored
+//! everywhere at once and every URL is parsed the same way.
+//!
+//! ## Design Principles
+//!
+//! 1. **One Resolver**: URLs are never assumed to be absolute or joined as
+//!    strings; callers hand the reference as written to a `UrlResolver`
+//!    for the document, stylesheet or script it appears in.
+//! 2. **First `<base>` Wins**: The base URL is the `href` of the first
+//!    `<base>` element with one, resolved against the document URL, as in
+//!    the HTML specification. A `href` that does not resolve is ignored.
+//! 3. **Absolute Without a Base**: Documents without a URL, such as those
+//!    loaded from a string, only accept absolute URLs.
+//! 4. **WHATWG Parsing**: References go through the URL Standard's parser,
+//!    which percent-encodes what must be encoded, normalizes `.` and `..`
+//!    segments and converts international domain names to punycode, so
+//!    the same resource always has the same serialization.
+
+use dom::Document;
+use url::Url;
+
+/// Resolves URL references against a base URL
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UrlResolver {
+    base: Option<Url>,
+}
+
+impl UrlResolver {
+    /// Create a resolver for `base`; without one only absolute URLs resolve
+    pub fn new(base: Option<Url>) -> Self {
+        UrlResolver { base }
+    }
+
+    /// Create a resolver for the URLs written in `document`
+    pub fn for_document(document: &Document) -> Self {
+        Self::new(document_base_url(document))
+    }
+
+    /// Create a resolver for the URLs written in the resource at `base`,
+    /// such as a stylesheet or script
+    ///
+    /// Returns `None` if `base` is not an absolute URL.
+    pub fn with_base(base: &str) -> Option<Self> {
+        Url::parse(trim_url(base)).ok().map(|base| Self::new(Some(base)))
+    }
+
+    /// Get the URL references are resolved against
+    pub fn base(&self) -> Option<&Url> {
+        self.base.as_ref()
+    }
+
+    /// Resolve a URL as written to an absolute URL
+    pub fn resolve(&self, reference: &str) -> Option<Url> {
+        let reference = trim_url(reference);
+        match &self.base {
+            Some(base) => base.join(reference).ok(),
+            None => Url::parse(reference).ok(),
+        }
+    }
+}
+
+/// Strip the leading and trailing C0 controls and spaces the URL parser
+/// ignores
+fn trim_url(url: &str) -> &str {
+    url.trim_matches(|ch: char| ch <= ' ')
+}
+
+/// Get the URL that relative URLs in `document` are resolved against
+pub fn document_base_url(document: &Document) -> Option<Url> {
+    let document_url = document.url().and_then(|url| Url::parse(&url).ok());
+    let from_base_element = document.base_href().and_then(|href| UrlResolver::new(document_url.clone()).resolve(&href));
+    from_base_element.or(document_url)
+}
+
+/// Resolve a URL as written in `document` to an absolute URL
+pub fn resolve_url(document: &Document, relative: &str) -> Option<Url> {
+    UrlResolver::for_document(document).resolve(relative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_against_base_element() {
+        let document = Document::new_with_url("https://example.com/docs/page.html");
+        assert_eq!(resolve_url(&document, "img.png").unwrap().as_str(), "https://example.com/docs/img.png");
+
+        let base = document.create_element("base");
+        base.set_attribute("href", "/static/");
+        document.root.append_child(&base);
+        assert_eq!(document_base_url(&document).unwrap().as_str(), "https://example.com/static/");
+        assert_eq!(resolve_url(&document, " app.js ").unwrap().as_str(), "https://example.com/static/app.js");
+        assert_eq!(resolve_url(&document, "https://cdn.example/x.css").unwrap().as_str(), "https://cdn.example/x.css");
+
+        let detached = Document::new();
+        assert!(resolve_url(&detached, "relative.css").is_none());
+        assert!(resolve_url(&detached, "https://example.com/a.css").is_some());
+    }
+
+    #[test]
+    fn test_resolver_normalizes_urls() {
+        let resolver = UrlResolver::with_base("https://example.com/a/b/sheet.css").unwrap();
+        assert_eq!(resolver.resolve("../img/bg image.png").unwrap().as_str(), "https://example.com/a/img/bg%20image.png");
+        assert_eq!(resolver.resolve("\t//cdn.example/x.js\n").unwrap().as_str(), "https://cdn.example/x.js");
+        assert_eq!(resolver.resolve("https://bücher.example/ä?q=ü").unwrap().as_str(), "https://xn--bcher-kva.example/%C3%A4?q=%C3%BC");
+        assert_eq!(resolver.resolve("HTTPS://EXAMPLE.COM:443/./x").unwrap().as_str(), "https://example.com/x");
+        assert!(resolver.resolve("https://exa mple.com/").is_none());
+        assert!(UrlResolver::with_base("relative/base").is_none());
+        assert!(UrlResolver::default().resolve("page.html").is_none());
+    }
+}
@@ -0,0 +1,216 @@
+//! # Cache Storage
+//!
+//! This module implements the storage behind the `caches` object of the
+//! Cache Storage API: named caches of request URLs and their responses,
+//! kept in a profile directory on top of `DiskCache`.
+//!
+//! ## Design Principles
+//!
+//! 1. **One Directory Per Cache**: Each named cache is a `DiskCache` in a
+//!    directory named after the hex-encoded cache name, so any name is a
+//!    valid directory name and names are recovered when the storage opens.
+//! 2. **All or Nothing**: `add_all` fetches every URL before storing any,
+//!    and stores none if one fails or answers with an error status, as
+//!    `cache.addAll()` requires.
+//! 3. **Partitioned By Origin**: Pages reach the storage through
+//!    `for_origin`, a view that prefixes cache names with the origin, so
+//!    one site can neither see nor change another site's caches.
+//! 4. **Shared Handle**: Cloning shares the storage, so the shell's
+//!    offline copies and pages' caches live in the same place.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::disk_cache::{CachedResponse, DiskCache};
+use crate::{HttpClient, HttpRequest, NetworkError, NetworkResult};
+
+/// Name of the cache storage directory in a profile directory
+pub const CACHES_DIR: &str = "caches";
+
+#[derive(Debug, Default)]
+struct StorageState {
+    caches: BTreeMap<String, DiskCache>,
+    /// Directory holding one directory per cache, if persistent
+    dir: Option<PathBuf>,
+}
+
+/// Named caches
+///
+/// Cloning shares the storage.
+#[derive(Debug, Clone, Default)]
+pub struct CacheStorage {
+    state: Arc<Mutex<StorageState>>,
+    /// Prefix of the names of the caches this view sees
+    prefix: String,
+}
+
+impl CacheStorage {
+    /// Create empty storage that is not saved anywhere
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open the caches saved in `dir`, creating the directory if needed
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let dir = dir.as_ref().join(CACHES_DIR);
+        fs::create_dir_all(&dir)?;
+        let mut caches = BTreeMap::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let Some(name) = path.file_name().and_then(|name| name.to_str()).and_then(decode_name) else {
+                continue;
+            };
+            caches.insert(name, DiskCache::open(&path)?);
+        }
+        Ok(CacheStorage { state: Arc::new(Mutex::new(StorageState { caches, dir: Some(dir) })), prefix: String::new() })
+    }
+
+    /// Get the view of the storage a page from `origin` sees
+    ///
+    /// The view shares the storage, but only sees the origin's caches.
+    pub fn for_origin(&self, origin: &str) -> CacheStorage {
+        CacheStorage { state: Arc::clone(&self.state), prefix: format!("{}{} ", self.prefix, origin) }
+    }
+
+    /// Get the cache with a name, creating it if it does not exist
+    pub fn open_cache(&self, name: &str) -> io::Result<DiskCache> {
+        let name = self.full_name(name);
+        let mut state = self.lock();
+        if let Some(cache) = state.caches.get(&name) {
+            return Ok(cache.clone());
+        }
+        let cache = match &state.dir {
+            Some(dir) => DiskCache::open(dir.join(encode_name(&name)))?,
+            None => DiskCache::new(),
+        };
+        state.caches.insert(name, cache.clone());
+        Ok(cache)
+    }
+
+    /// Check whether a cache with a name exists
+    pub fn has(&self, name: &str) -> bool {
+        self.lock().caches.contains_key(&self.full_name(name))
+    }
+
+    /// Delete a cache and its responses
+    ///
+    /// Returns whether the cache existed.
+    pub fn delete(&self, name: &str) -> io::Result<bool> {
+        let removed = self.lock().caches.remove(&self.full_name(name));
+        match removed {
+            Some(cache) => {
+                cache.clear()?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Get the names of the caches, sorted
+    ///
+    /// The storage itself also lists the caches of every origin, with
+    /// their origin prefix.
+    pub fn keys(&self) -> Vec<String> {
+        self.lock()
+            .caches
+            .keys()
+            .filter_map(|name| name.strip_prefix(&self.prefix))
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Find a response for a URL in any cache, trying caches by name
+    pub fn match_url(&self, url: &str) -> Option<CachedResponse> {
+        self.lock()
+            .caches
+            .iter()
+            .filter(|(name, _)| name.starts_with(&self.prefix))
+            .find_map(|(_, cache)| cache.get(url))
+    }
+
+    fn full_name(&self, name: &str) -> String {
+        format!("{}{}", self.prefix, name)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, StorageState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Fetch URLs and store all their responses in a cache, or none of them
+///
+/// Fails if a fetch fails or a response does not have an ok status.
+/// Returns the number of responses stored.
+pub async fn add_all(cache: &DiskCache, client: &HttpClient, urls: &[String]) -> NetworkResult<usize> {
+    let mut responses = Vec::with_capacity(urls.len());
+    for url in urls {
+        let response = client.send_request(HttpRequest::get(url.clone())).await?;
+        if !response.status.is_success() {
+            return Err(NetworkError::HttpError {
+                status: response.status_code,
+                message: format!("cannot cache {}", url),
+            });
+        }
+        // Store under the requested URL, so lookups by it match after redirects
+        let mut cached = CachedResponse::from(&response);
+        cached.url = url.clone();
+        responses.push(cached);
+    }
+    let stored = responses.len();
+    for response in responses {
+        cache.put(response).map_err(|e| NetworkError::CacheError(e.to_string()))?;
+    }
+    Ok(stored)
+}
+
+fn encode_name(name: &str) -> String {
+    name.bytes().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_name(encoded: &str) -> Option<String> {
+    if !encoded.len().is_multiple_of(2) {
+        return None;
+    }
+    let bytes = (0..encoded.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(encoded.get(index..index + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_named_caches_persist() {
+        let dir = std::env::temp_dir().join(format!("dubby-cache-storage-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let storage = CacheStorage::open(&dir).unwrap();
+        let cache = storage.open_cache("v1 / assets").unwrap();
+        cache.put(CachedResponse::new("https://example.com/app.css", 200, Vec::new(), b"body{}".to_vec())).unwrap();
+        storage.open_cache("empty").unwrap();
+
+        let reopened = CacheStorage::open(&dir).unwrap();
+        assert_eq!(reopened.keys(), vec!["empty".to_string(), "v1 / assets".to_string()]);
+        assert_eq!(reopened.match_url("https://example.com/app.css").unwrap().body, b"body{}");
+        assert!(reopened.match_url("https://example.com/missing.css").is_none());
+
+        assert!(reopened.delete("v1 / assets").unwrap());
+        assert!(!reopened.delete("v1 / assets").unwrap());
+        assert_eq!(CacheStorage::open(&dir).unwrap().keys(), vec!["empty".to_string()]);
+
+        // Origins only see their own caches
+        let site = reopened.for_origin("https://a.example");
+        site.open_cache("empty").unwrap().put(CachedResponse::new("https://a.example/", 200, Vec::new(), Vec::new())).unwrap();
+        assert_eq!(site.keys(), vec!["empty".to_string()]);
+        assert!(reopened.for_origin("https://b.example").keys().is_empty());
+        assert!(reopened.for_origin("https://b.example").match_url("https://a.example/").is_none());
+        assert!(reopened.open_cache("empty").unwrap().is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
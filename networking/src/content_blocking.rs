@@ -0,0 +1,672 @@
+//! # Content Blocking
+//!
+//! This module classifies requests against EasyList-style filter lists
+//! before they are sent, so ads and trackers never reach the network, and
+//! turns the lists' element hiding rules into a user stylesheet.
+//!
+//! ## Design Principles
+//!
+//! 1. **Decide Before Sending**: Requests are classified when they are
+//!    dispatched, next to the mixed content check, so a blocked request
+//!    costs no connection.
+//! 2. **Indexed by Token**: Each network rule is filed under one token of
+//!    its pattern, and a request only tries the rules filed under the
+//!    tokens of its URL. A small bloom filter skips the lookup for the
+//!    many URL tokens no rule uses, so lists of tens of thousands of rules
+//!    cost a few probes per request.
+//! 3. **Exceptions Win**: `@@` rules unblock what other rules block, as in
+//!    other blockers.
+//! 4. **Skip What We Cannot Honor**: Rules with options or syntax this
+//!    module does not understand are counted and skipped rather than
+//!    applied more broadly than written.
+//! 5. **Cosmetic Filters Are Styles**: Element hiding rules become
+//!    `display: none !important` declarations for the page's host, which
+//!    the shell adds to the user stylesheet.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use dom::Origin;
+use url::Url;
+use crate::mixed_content::RequestDestination;
+
+/// Directory of the profile holding the filter lists, as `.txt` files
+pub const FILTERS_DIR: &str = "filters";
+
+/// Number of bits of the token bloom filter
+const BLOOM_BITS: usize = 1 << 16;
+
+/// Shortest pattern token worth indexing
+const MIN_TOKEN_LEN: usize = 3;
+
+// Resource types of the `$script`, `$image`... options, as bits
+const TYPE_DOCUMENT: u8 = 1;
+const TYPE_SCRIPT: u8 = 1 << 1;
+const TYPE_STYLESHEET: u8 = 1 << 2;
+const TYPE_IMAGE: u8 = 1 << 3;
+const TYPE_MEDIA: u8 = 1 << 4;
+const TYPE_XMLHTTPREQUEST: u8 = 1 << 5;
+const TYPE_OTHER: u8 = 1 << 6;
+/// Types a rule without type options applies to; documents must be
+/// named with `$document`
+const TYPE_DEFAULT: u8 = !TYPE_DOCUMENT;
+
+fn destination_type(destination: RequestDestination) -> u8 {
+    match destination {
+        RequestDestination::Document => TYPE_DOCUMENT,
+        RequestDestination::Script => TYPE_SCRIPT,
+        RequestDestination::Style => TYPE_STYLESHEET,
+        RequestDestination::Image => TYPE_IMAGE,
+        RequestDestination::Media => TYPE_MEDIA,
+        RequestDestination::Empty => TYPE_XMLHTTPREQUEST,
+    }
+}
+
+/// The bits of a type option, or `None` if the option is not a type
+///
+/// Types this engine never requests, such as fonts and frames, have no
+/// bits: a rule naming only them never matches.
+fn option_type(name: &str) -> Option<u8> {
+    Some(match name {
+        "document" => TYPE_DOCUMENT,
+        "script" => TYPE_SCRIPT,
+        "stylesheet" => TYPE_STYLESHEET,
+        "image" => TYPE_IMAGE,
+        "media" => TYPE_MEDIA,
+        "xmlhttprequest" => TYPE_XMLHTTPREQUEST,
+        "other" => TYPE_OTHER,
+        "font" | "subdocument" | "object" | "ping" | "websocket" | "webrtc" | "popup" => 0,
+        _ => return None,
+    })
+}
+
+/// What the content blocker decided for a request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockDecision {
+    /// No rule matched
+    Allowed,
+    /// A rule blocked the request
+    Blocked { rule: String },
+    /// A rule would block the request but an exception rule allows it
+    Excepted { rule: String, exception: String },
+}
+
+impl BlockDecision {
+    pub fn is_blocked(&self) -> bool {
+        matches!(self, BlockDecision::Blocked { .. })
+    }
+}
+
+/// How many rules of a filter list were loaded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FilterListStats {
+    pub network: usize,
+    pub cosmetic: usize,
+    /// Rules with syntax or options that are not supported
+    pub skipped: usize,
+}
+
+/// Where a pattern must start in the URL
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Anchor {
+    Anywhere,
+    /// `|`: at the start of the URL
+    Start,
+    /// `||`: at the start of the host or of one of its subdomains
+    Domain,
+}
+
+/// A parsed network rule
+#[derive(Debug, Clone)]
+struct NetworkRule {
+    /// The rule as written in the list
+    text: String,
+    exception: bool,
+    anchor: Anchor,
+    /// The pattern between the anchors, with `*` and `^` kept
+    pattern: String,
+    /// `|` at the end: the pattern must reach the end of the URL
+    end_anchor: bool,
+    types: u8,
+    /// `Some(true)` for `$third-party`, `Some(false)` for `$~third-party`
+    third_party: Option<bool>,
+    include_domains: Vec<String>,
+    exclude_domains: Vec<String>,
+    match_case: bool,
+}
+
+impl NetworkRule {
+    fn parse(line: &str) -> Option<NetworkRule> {
+        let (exception, rest) = match line.strip_prefix("@@") {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let (pattern, options) = match rest.rfind('$') {
+            // A `$` followed by something that is no option list is part of
+            // the pattern
+            Some(index) if rest[index + 1..].split(',').all(|option| !option.is_empty() && !option.contains('/')) => {
+                (&rest[..index], Some(&rest[index + 1..]))
+            }
+            _ => (rest, None),
+        };
+        // Regular expression rules are not supported
+        if pattern.len() > 1 && pattern.starts_with('/') && pattern.ends_with('/') {
+            return None;
+        }
+
+        let mut rule = NetworkRule {
+            text: line.to_string(),
+            exception,
+            anchor: Anchor::Anywhere,
+            pattern: String::new(),
+            end_anchor: false,
+            types: TYPE_DEFAULT,
+            third_party: None,
+            include_domains: Vec::new(),
+            exclude_domains: Vec::new(),
+            match_case: false,
+        };
+        if let Some(options) = options {
+            rule.parse_options(options)?;
+        }
+
+        let mut pattern = pattern;
+        if let Some(rest) = pattern.strip_prefix("||") {
+            rule.anchor = Anchor::Domain;
+            pattern = rest;
+        } else if let Some(rest) = pattern.strip_prefix('|') {
+            rule.anchor = Anchor::Start;
+            pattern = rest;
+        }
+        if let Some(rest) = pattern.strip_suffix('|') {
+            rule.end_anchor = true;
+            pattern = rest;
+        }
+        // Leading and trailing wildcards only undo the anchors
+        if rule.anchor != Anchor::Domain && pattern.starts_with('*') {
+            rule.anchor = Anchor::Anywhere;
+        }
+        if pattern.ends_with('*') {
+            rule.end_anchor = false;
+        }
+        let pattern = pattern.trim_matches('*');
+        rule.pattern = if rule.match_case { pattern.to_string() } else { pattern.to_ascii_lowercase() };
+        Some(rule)
+    }
+
+    /// Apply the options after `$`; fails on options that are not supported
+    fn parse_options(&mut self, options: &str) -> Option<()> {
+        let mut included_types = None;
+        let mut excluded_types = 0;
+        for option in options.split(',') {
+            let option = option.trim().to_ascii_lowercase();
+            let (negated, name) = match option.strip_prefix('~') {
+                Some(name) => (true, name),
+                None => (false, option.as_str()),
+            };
+            if let Some(bits) = option_type(name) {
+                if negated {
+                    excluded_types |= bits;
+                } else {
+                    included_types = Some(included_types.unwrap_or(0) | bits);
+                }
+                continue;
+            }
+            match name {
+                "third-party" => self.third_party = Some(!negated),
+                "match-case" if !negated => self.match_case = true,
+                _ if !negated && name.starts_with("domain=") => {
+                    for domain in name["domain=".len()..].split('|').filter(|domain| !domain.is_empty()) {
+                        match domain.strip_prefix('~') {
+                            Some(domain) => self.exclude_domains.push(domain.to_string()),
+                            None => self.include_domains.push(domain.to_string()),
+                        }
+                    }
+                }
+                _ => return None,
+            }
+        }
+        // Listing a type restricts the rule to the listed types; `~type`
+        // alone removes types from the default
+        self.types = included_types.unwrap_or(TYPE_DEFAULT) & !excluded_types;
+        Some(())
+    }
+
+    /// The tokens the rule can be filed under: runs of letters and digits
+    /// that any matching URL must contain as whole tokens
+    fn index_tokens(&self) -> Vec<String> {
+        let pattern = self.pattern.as_bytes();
+        let mut tokens = Vec::new();
+        let mut start = 0;
+        while start < pattern.len() {
+            if !is_token_byte(pattern[start]) {
+                start += 1;
+                continue;
+            }
+            let mut end = start;
+            while end < pattern.len() && is_token_byte(pattern[end]) {
+                end += 1;
+            }
+            // A run touching a wildcard, or an unanchored end of the
+            // pattern, may be part of a longer URL token
+            let starts_token = if start == 0 { self.anchor != Anchor::Anywhere } else { pattern[start - 1] != b'*' };
+            let ends_token = if end == pattern.len() { self.end_anchor } else { pattern[end] != b'*' };
+            if starts_token && ends_token && end - start >= MIN_TOKEN_LEN {
+                tokens.push(String::from_utf8_lossy(&pattern[start..end]).to_ascii_lowercase());
+            }
+            start = end;
+        }
+        tokens
+    }
+
+    fn matches(&self, request: &ClassifiedRequest) -> bool {
+        if self.types & request.resource_type == 0 {
+            return false;
+        }
+        if let Some(third_party) = self.third_party {
+            if request.third_party != Some(third_party) {
+                return false;
+            }
+        }
+        if !self.include_domains.is_empty() || !self.exclude_domains.is_empty() {
+            let Some(source) = &request.source_host else {
+                return self.include_domains.is_empty();
+            };
+            if self.exclude_domains.iter().any(|domain| is_domain_or_subdomain(source, domain)) {
+                return false;
+            }
+            if !self.include_domains.is_empty() && !self.include_domains.iter().any(|domain| is_domain_or_subdomain(source, domain)) {
+                return false;
+            }
+        }
+        let url = if self.match_case { request.url.as_bytes() } else { request.lowercase_url.as_bytes() };
+        let pattern = self.pattern.as_bytes();
+        match self.anchor {
+            Anchor::Start => glob(url, pattern, self.end_anchor),
+            Anchor::Anywhere => (0..=url.len()).any(|start| glob(&url[start..], pattern, self.end_anchor)),
+            Anchor::Domain => request.host_starts.iter().any(|&start| glob(&url[start..], pattern, self.end_anchor)),
+        }
+    }
+}
+
+/// Match a pattern with `*` and `^` against the start of a URL
+fn glob(url: &[u8], pattern: &[u8], end_anchor: bool) -> bool {
+    match pattern.split_first() {
+        None => !end_anchor || url.is_empty(),
+        Some((b'*', rest)) => (0..=url.len()).any(|start| glob(&url[start..], rest, end_anchor)),
+        // `^` is a separator character or the end of the URL
+        Some((b'^', rest)) => match url.split_first() {
+            Some((&byte, tail)) => is_separator(byte) && glob(tail, rest, end_anchor),
+            None => glob(url, rest, end_anchor),
+        },
+        Some((&byte, rest)) => url.first() == Some(&byte) && glob(&url[1..], rest, end_anchor),
+    }
+}
+
+fn is_separator(byte: u8) -> bool {
+    !(byte.is_ascii_alphanumeric() || matches!(byte, b'_' | b'-' | b'.' | b'%'))
+}
+
+fn is_token_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'%'
+}
+
+fn is_domain_or_subdomain(host: &str, domain: &str) -> bool {
+    host == domain || host.strip_suffix(domain).is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+/// Approximate the registrable domain of a host by its last two labels
+fn site(host: &str) -> &str {
+    if host.parse::<std::net::IpAddr>().is_ok() || host.starts_with('[') {
+        return host;
+    }
+    match host.rmatch_indices('.').nth(1) {
+        Some((index, _)) => &host[index + 1..],
+        None => host,
+    }
+}
+
+fn hash_token(token: &[u8]) -> u64 {
+    // FNV-1a
+    token.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// A request as the rules see it
+struct ClassifiedRequest {
+    url: String,
+    lowercase_url: String,
+    /// Offsets in the URL where the host and each of its parent domains
+    /// start, for `||` rules
+    host_starts: Vec<usize>,
+    resource_type: u8,
+    source_host: Option<String>,
+    /// Whether the request leaves the site of its source, if known
+    third_party: Option<bool>,
+}
+
+impl ClassifiedRequest {
+    fn new(url: &Url, source: Option<&Origin>, destination: RequestDestination) -> Self {
+        let text = url.as_str().to_string();
+        let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+        let mut host_starts = Vec::new();
+        if let Some(start) = text.find("://").map(|index| index + 3) {
+            let start = text[start..].find(&host).map_or(start, |offset| start + offset);
+            host_starts.push(start);
+            host_starts.extend(host.match_indices('.').map(|(index, _)| start + index + 1));
+        }
+        let source_host = match source {
+            Some(Origin::Tuple { host, .. }) => Some(host.to_ascii_lowercase()),
+            _ => None,
+        };
+        let third_party = source_host.as_deref().map(|source| site(source) != site(&host));
+        ClassifiedRequest {
+            lowercase_url: text.to_ascii_lowercase(),
+            url: text,
+            host_starts,
+            resource_type: destination_type(destination),
+            source_host,
+            third_party,
+        }
+    }
+
+    /// Hashes of the tokens of the URL
+    fn token_hashes(&self) -> Vec<u64> {
+        self.lowercase_url
+            .as_bytes()
+            .split(|&byte| !is_token_byte(byte))
+            .filter(|token| token.len() >= MIN_TOKEN_LEN)
+            .map(hash_token)
+            .collect()
+    }
+}
+
+/// Network rules filed by token
+#[derive(Debug, Default)]
+struct RuleIndex {
+    rules: Vec<NetworkRule>,
+    by_token: HashMap<u64, Vec<usize>>,
+    /// Rules without a usable token, tried for every request
+    untokenized: Vec<usize>,
+    bloom: Vec<u64>,
+}
+
+impl RuleIndex {
+    /// File a rule under its rarest token so far, the longest on ties
+    fn add(&mut self, rule: NetworkRule) {
+        if self.bloom.is_empty() {
+            self.bloom = vec![0; BLOOM_BITS / 64];
+        }
+        let id = self.rules.len();
+        let token = rule
+            .index_tokens()
+            .into_iter()
+            .map(|token| hash_token(token.as_bytes()))
+            .enumerate()
+            .min_by_key(|&(position, hash)| (self.by_token.get(&hash).map_or(0, Vec::len), position))
+            .map(|(_, hash)| hash);
+        match token {
+            Some(hash) => {
+                for bit in bloom_bits(hash) {
+                    self.bloom[bit / 64] |= 1 << (bit % 64);
+                }
+                self.by_token.entry(hash).or_default().push(id);
+            }
+            None => self.untokenized.push(id),
+        }
+        self.rules.push(rule);
+    }
+
+    fn might_contain(&self, hash: u64) -> bool {
+        !self.bloom.is_empty() && bloom_bits(hash).iter().all(|&bit| self.bloom[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Find the first rule matching a request
+    fn find(&self, request: &ClassifiedRequest, token_hashes: &[u64]) -> Option<&NetworkRule> {
+        let tokenized = token_hashes
+            .iter()
+            .filter(|&&hash| self.might_contain(hash))
+            .filter_map(|hash| self.by_token.get(hash))
+            .flatten();
+        tokenized.chain(&self.untokenized).map(|&id| &self.rules[id]).find(|rule| rule.matches(request))
+    }
+}
+
+/// The two bloom filter bits of a token hash
+fn bloom_bits(hash: u64) -> [usize; 2] {
+    [(hash as usize) % BLOOM_BITS, ((hash >> 32) as usize) % BLOOM_BITS]
+}
+
+/// An element hiding rule
+#[derive(Debug, Clone)]
+struct CosmeticRule {
+    selector: String,
+    exception: bool,
+    include_domains: Vec<String>,
+    exclude_domains: Vec<String>,
+}
+
+impl CosmeticRule {
+    /// Parse a `domains##selector` or `domains#@#selector` rule
+    fn parse(line: &str) -> Option<Result<CosmeticRule, ()>> {
+        let (index, exception, separator_len) = match (line.find("##"), line.find("#@#")) {
+            (Some(index), _) => (index, false, 2),
+            (None, Some(index)) => (index, true, 3),
+            // Extended syntax such as `#?#` and `#$#` is not supported
+            _ if ["#?#", "#$#", "#%#"].iter().any(|separator| line.contains(separator)) => return Some(Err(())),
+            _ => return None,
+        };
+        let domains = &line[..index];
+        let selector = line[index + separator_len..].trim();
+        // Selectors must not be able to inject declarations
+        if selector.is_empty() || selector.contains(['{', '}']) || selector.starts_with('+') {
+            return Some(Err(()));
+        }
+        let mut rule = CosmeticRule {
+            selector: selector.to_string(),
+            exception,
+            include_domains: Vec::new(),
+            exclude_domains: Vec::new(),
+        };
+        for domain in domains.split(',').map(str::trim).filter(|domain| !domain.is_empty()) {
+            match domain.strip_prefix('~') {
+                Some(domain) => rule.exclude_domains.push(domain.to_ascii_lowercase()),
+                None => rule.include_domains.push(domain.to_ascii_lowercase()),
+            }
+        }
+        Some(Ok(rule))
+    }
+
+    fn applies_to(&self, host: &str) -> bool {
+        if self.exclude_domains.iter().any(|domain| is_domain_or_subdomain(host, domain)) {
+            return false;
+        }
+        self.include_domains.is_empty() || self.include_domains.iter().any(|domain| is_domain_or_subdomain(host, domain))
+    }
+}
+
+#[derive(Debug, Default)]
+struct BlockerState {
+    blocking: RuleIndex,
+    exceptions: RuleIndex,
+    cosmetic: Vec<CosmeticRule>,
+}
+
+/// A set of filter lists requests are classified against
+///
+/// Cloning shares the rules.
+#[derive(Debug, Clone, Default)]
+pub struct ContentBlocker {
+    state: Arc<Mutex<BlockerState>>,
+}
+
+impl ContentBlocker {
+    /// Create a blocker without rules, which allows everything
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open the filter lists of `dir`, the `.txt` files of its `filters`
+    /// directory, in name order
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let blocker = Self::new();
+        let mut lists: Vec<_> = match fs::read_dir(dir.as_ref().join(FILTERS_DIR)) {
+            Ok(entries) => entries.filter_map(Result::ok).map(|entry| entry.path()).collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        lists.retain(|path| path.extension().is_some_and(|extension| extension == "txt"));
+        lists.sort();
+        for path in lists {
+            blocker.add_filter_list(&fs::read_to_string(path)?);
+        }
+        Ok(blocker)
+    }
+
+    /// Add the rules of an EasyList-style filter list
+    pub fn add_filter_list(&self, list: &str) -> FilterListStats {
+        let mut stats = FilterListStats::default();
+        let mut state = self.lock();
+        for line in list.lines().map(str::trim) {
+            // Comments and the `[Adblock Plus 2.0]` header
+            if line.is_empty() || line.starts_with('!') || (line.starts_with('[') && line.ends_with(']')) {
+                continue;
+            }
+            if let Some(rule) = CosmeticRule::parse(line) {
+                match rule {
+                    Ok(rule) => {
+                        state.cosmetic.push(rule);
+                        stats.cosmetic += 1;
+                    }
+                    Err(()) => stats.skipped += 1,
+                }
+                continue;
+            }
+            match NetworkRule::parse(line) {
+                Some(rule) if rule.exception => {
+                    state.exceptions.add(rule);
+                    stats.network += 1;
+                }
+                Some(rule) => {
+                    state.blocking.add(rule);
+                    stats.network += 1;
+                }
+                None => stats.skipped += 1,
+            }
+        }
+        stats
+    }
+
+    /// Get the number of network and cosmetic rules
+    pub fn rule_count(&self) -> usize {
+        let state = self.lock();
+        state.blocking.rules.len() + state.exceptions.rules.len() + state.cosmetic.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rule_count() == 0
+    }
+
+    /// Classify a request for `url` made by a document of `source`
+    pub fn classify(&self, url: &Url, source: Option<&Origin>, destination: RequestDestination) -> BlockDecision {
+        let state = self.lock();
+        if state.blocking.rules.is_empty() {
+            return BlockDecision::Allowed;
+        }
+        let request = ClassifiedRequest::new(url, source, destination);
+        let token_hashes = request.token_hashes();
+        let Some(rule) = state.blocking.find(&request, &token_hashes) else {
+            return BlockDecision::Allowed;
+        };
+        match state.exceptions.find(&request, &token_hashes) {
+            Some(exception) => BlockDecision::Excepted { rule: rule.text.clone(), exception: exception.text.clone() },
+            None => BlockDecision::Blocked { rule: rule.text.clone() },
+        }
+    }
+
+    /// Generate the stylesheet hiding the elements the cosmetic rules
+    /// target on a host
+    pub fn cosmetic_css(&self, host: &str) -> String {
+        let host = host.to_ascii_lowercase();
+        let state = self.lock();
+        let excepted: Vec<&str> = state
+            .cosmetic
+            .iter()
+            .filter(|rule| rule.exception && rule.applies_to(&host))
+            .map(|rule| rule.selector.as_str())
+            .collect();
+        let mut css = String::new();
+        for rule in state.cosmetic.iter().filter(|rule| !rule.exception && rule.applies_to(&host)) {
+            if !excepted.contains(&rule.selector.as_str()) {
+                css.push_str(&format!("{} {{ display: none !important; }}\n", rule.selector));
+            }
+        }
+        css
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BlockerState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIST: &str = "[Adblock Plus 2.0]
+! Title: Test list
+||ads.example^
+/banner/*/ad.
+||tracker.test^$script,third-party
+@@||ads.example/allowed/
+&adid=
+|http://plain.example/pixel.gif|
+||cdn.example/social.js$domain=news.example|~sports.news.example
+/ad[0-9]+/
+##.ad-banner
+news.example,~sports.news.example##div[id^=\"sponsor\"]
+news.example#@#.ad-banner
+example.org#?#div:-abp-has(.ad)";
+
+    fn classify(blocker: &ContentBlocker, url: &str, source: &str, destination: RequestDestination) -> bool {
+        let source = Origin::from_url(source);
+        blocker.classify(&Url::parse(url).unwrap(), Some(&source), destination).is_blocked()
+    }
+
+    #[test]
+    fn test_requests_are_classified_against_filter_lists() {
+        let blocker = ContentBlocker::new();
+        let stats = blocker.add_filter_list(LIST);
+        assert_eq!(stats, FilterListStats { network: 7, cosmetic: 3, skipped: 2 });
+        let page = "https://news.example/story";
+        use RequestDestination::*;
+
+        // Domain anchors match the host and its subdomains only
+        assert!(classify(&blocker, "https://ads.example/a.js", page, Script));
+        assert!(classify(&blocker, "https://img.ads.example/x.png", page, Image));
+        assert!(!classify(&blocker, "https://notads.example/a.js", page, Script));
+        assert!(!classify(&blocker, "https://news.example/ads.example", page, Script));
+        // Exceptions win
+        assert!(!classify(&blocker, "https://ads.example/allowed/a.js", page, Script));
+        // Wildcards, separators and anchors
+        assert!(classify(&blocker, "https://news.example/banner/top/ad.png", page, Image));
+        assert!(classify(&blocker, "https://news.example/x?id=1&adid=7", page, Empty));
+        assert!(classify(&blocker, "http://plain.example/pixel.gif", page, Image));
+        assert!(!classify(&blocker, "http://plain.example/pixel.gif?x", page, Image));
+        // Type and party options
+        assert!(classify(&blocker, "https://tracker.test/t.js", page, Script));
+        assert!(!classify(&blocker, "https://tracker.test/t.png", page, Image));
+        assert!(!classify(&blocker, "https://tracker.test/t.js", "https://www.tracker.test/", Script));
+        // Domain options
+        assert!(classify(&blocker, "https://cdn.example/social.js", page, Script));
+        assert!(!classify(&blocker, "https://cdn.example/social.js", "https://sports.news.example/", Script));
+        assert!(!classify(&blocker, "https://cdn.example/social.js", "https://blog.example/", Script));
+        // Documents are only blocked by `$document` rules
+        assert!(!classify(&blocker, "https://ads.example/", page, Document));
+
+        assert_eq!(blocker.cosmetic_css("www.news.example"), "div[id^=\"sponsor\"] { display: none !important; }\n");
+        assert_eq!(blocker.cosmetic_css("sports.news.example"), "");
+        assert_eq!(blocker.clone().cosmetic_css("blog.example"), ".ad-banner { display: none !important; }\n");
+    }
+}
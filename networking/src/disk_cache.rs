@@ -0,0 +1,247 @@
+//! # Disk Cache
+//!
+//! This module stores whole HTTP responses on disk, keyed by URL. It is
+//! the storage layer under the Cache Storage API and the shell's offline
+//! copies of pinned pages.
+//!
+//! ## Design Principles
+//!
+//! 1. **One Entry, Two Files**: Each response is a JSON metadata file and
+//!    a body file, both named after the SHA-256 digest of its URL, so
+//!    bodies are stored byte for byte and file names are always safe.
+//! 2. **Index In Memory**: Entries are loaded when the cache is opened and
+//!    written through on every change, so lookups never touch the disk.
+//! 3. **Shared Handle**: A cache is shared by cloning, like the HSTS
+//!    store, so the shell and script engines see the same entries.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::HttpResponse;
+
+/// A stored response
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedResponse {
+    pub url: String,
+    pub status: u16,
+    pub status_text: String,
+    /// Header names and values, names in lower case
+    pub headers: Vec<(String, String)>,
+    #[serde(skip)]
+    pub body: Vec<u8>,
+    /// When the response was stored, in seconds since the Unix epoch
+    pub stored_at: u64,
+}
+
+impl CachedResponse {
+    /// Create a response to store
+    pub fn new(url: &str, status: u16, headers: Vec<(String, String)>, body: Vec<u8>) -> Self {
+        let status_text = reqwest::StatusCode::from_u16(status)
+            .ok()
+            .and_then(|status| status.canonical_reason())
+            .unwrap_or("")
+            .to_string();
+        let headers = headers.into_iter().map(|(name, value)| (name.to_ascii_lowercase(), value)).collect();
+        CachedResponse { url: url.to_string(), status, status_text, headers, body, stored_at: now() }
+    }
+
+    /// Get a header value by case-insensitive name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let name = name.to_ascii_lowercase();
+        self.headers.iter().find(|(header, _)| *header == name).map(|(_, value)| value.as_str())
+    }
+
+    /// Check whether the status is in the 200-299 range
+    pub fn is_ok(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+impl From<&HttpResponse> for CachedResponse {
+    fn from(response: &HttpResponse) -> Self {
+        let mut headers: Vec<(String, String)> = response.headers.iter().map(|(name, value)| (name.clone(), value.clone())).collect();
+        headers.sort();
+        CachedResponse::new(&response.url, response.status_code, headers, response.body.clone())
+    }
+}
+
+#[derive(Debug, Default)]
+struct DiskState {
+    entries: BTreeMap<String, CachedResponse>,
+    /// Directory the entries are saved in, if the cache is persistent
+    dir: Option<PathBuf>,
+}
+
+/// Responses stored by URL
+///
+/// Cloning shares the cache.
+#[derive(Debug, Clone, Default)]
+pub struct DiskCache {
+    state: Arc<Mutex<DiskState>>,
+}
+
+impl DiskCache {
+    /// Create an empty cache that is not saved anywhere
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open the cache saved in `dir`, creating the directory if needed
+    ///
+    /// Entries whose files cannot be read are skipped.
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let mut entries = BTreeMap::new();
+        for file in fs::read_dir(&dir)? {
+            let path = file?.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some("json") {
+                continue;
+            }
+            let metadata = fs::read_to_string(&path).ok().and_then(|contents| serde_json::from_str::<CachedResponse>(&contents).ok());
+            let (Some(mut response), Ok(body)) = (metadata, fs::read(path.with_extension("body"))) else {
+                println!("⚠️  Skipping unreadable cache entry {}", path.display());
+                continue;
+            };
+            response.body = body;
+            entries.insert(cache_key(&response.url), response);
+        }
+        Ok(DiskCache { state: Arc::new(Mutex::new(DiskState { entries, dir: Some(dir) })) })
+    }
+
+    /// Get the response stored for a URL, ignoring its fragment
+    pub fn get(&self, url: &str) -> Option<CachedResponse> {
+        self.lock().entries.get(&cache_key(url)).cloned()
+    }
+
+    /// Store a response, replacing any stored for the same URL
+    pub fn put(&self, response: CachedResponse) -> io::Result<()> {
+        let mut state = self.lock();
+        let key = cache_key(&response.url);
+        if let Some(dir) = &state.dir {
+            let base = dir.join(file_stem(&key));
+            fs::write(base.with_extension("body"), &response.body)?;
+            let metadata = serde_json::to_string_pretty(&response).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            let temporary = base.with_extension("json.tmp");
+            fs::write(&temporary, metadata)?;
+            fs::rename(&temporary, base.with_extension("json"))?;
+        }
+        state.entries.insert(key, response);
+        Ok(())
+    }
+
+    /// Remove the response stored for a URL
+    ///
+    /// Returns whether there was one.
+    pub fn remove(&self, url: &str) -> io::Result<bool> {
+        let mut state = self.lock();
+        let key = cache_key(url);
+        if state.entries.remove(&key).is_none() {
+            return Ok(false);
+        }
+        if let Some(dir) = &state.dir {
+            let base = dir.join(file_stem(&key));
+            remove_if_present(&base.with_extension("json"))?;
+            remove_if_present(&base.with_extension("body"))?;
+        }
+        Ok(true)
+    }
+
+    /// Get the URLs of the stored responses, sorted
+    pub fn urls(&self) -> Vec<String> {
+        self.lock().entries.values().map(|response| response.url.clone()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().entries.is_empty()
+    }
+
+    /// Remove every response, and the directory of a persistent cache
+    pub fn clear(&self) -> io::Result<()> {
+        let mut state = self.lock();
+        state.entries.clear();
+        if let Some(dir) = &state.dir {
+            match fs::remove_dir_all(dir) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, DiskState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Get the key a URL is stored under: the URL without its fragment
+pub fn cache_key(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut parsed) => {
+            parsed.set_fragment(None);
+            parsed.to_string()
+        }
+        Err(_) => url.to_string(),
+    }
+}
+
+fn file_stem(key: &str) -> String {
+    digest::digest(&digest::SHA256, key.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn remove_if_present(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disk_cache_round_trip() {
+        let dir = std::env::temp_dir().join(format!("dubby-disk-cache-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let cache = DiskCache::open(&dir).unwrap();
+        let headers = vec![("Content-Type".to_string(), "image/png".to_string())];
+        cache.put(CachedResponse::new("https://example.com/logo.png", 200, headers, vec![0, 159, 146, 150])).unwrap();
+        assert_eq!(cache.len(), 1);
+
+        // Bodies survive byte for byte, and fragments do not matter
+        let reopened = DiskCache::open(&dir).unwrap();
+        let response = reopened.get("https://example.com/logo.png#top").unwrap();
+        assert_eq!(response.body, vec![0, 159, 146, 150]);
+        assert_eq!(response.header("content-type"), Some("image/png"));
+        assert_eq!(response.status_text, "OK");
+
+        assert!(reopened.remove("https://example.com/logo.png").unwrap());
+        assert!(DiskCache::open(&dir).unwrap().is_empty());
+        reopened.clear().unwrap();
+        assert!(!dir.exists());
+    }
+}
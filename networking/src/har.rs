@@ -0,0 +1,276 @@
+//! # HTTP Archive Replay
+//!
+//! This module reads HTTP Archive (HAR 1.2) files and serves the responses
+//! they recorded in place of the network. With a `HarReplay` as the
+//! client's request interceptor, a page load, including scripts whose
+//! behavior depends on the order responses arrive in, is reproduced byte
+//! for byte in tests.
+//!
+//! ## Design Principles
+//!
+//! 1. **Nothing Leaks Out**: A request the archive has no response for
+//!    fails instead of reaching the network, so a replay never depends on
+//!    the live site.
+//! 2. **Recorded Order**: Repeated requests for the same method and URL get
+//!    the recorded responses in order; the last one is served again once
+//!    they run out.
+//! 3. **Timings Are Optional**: Responses are served at once unless the
+//!    replay honors the recorded timings, which reproduces the order of
+//!    concurrent loads as well as their results.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+use base64::Engine;
+use serde::Deserialize;
+use url::Url;
+use crate::{HttpMethod, InterceptedResponse, NetworkError, NetworkResult, RequestInterceptor};
+
+/// Most redirects followed within the archive for one request
+const MAX_REDIRECTS: usize = 10;
+
+#[derive(Debug, Deserialize)]
+struct HarFile {
+    log: HarLog,
+}
+
+#[derive(Debug, Deserialize)]
+struct HarLog {
+    #[serde(default)]
+    entries: Vec<HarEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HarEntry {
+    /// Total time of the request in milliseconds
+    #[serde(default)]
+    time: f64,
+    request: HarRequest,
+    response: HarResponse,
+}
+
+#[derive(Debug, Deserialize)]
+struct HarRequest {
+    method: String,
+    url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct HarResponse {
+    status: u16,
+    #[serde(default)]
+    headers: Vec<HarHeader>,
+    #[serde(default)]
+    content: HarContent,
+    #[serde(default, rename = "redirectURL")]
+    redirect_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct HarHeader {
+    name: String,
+    value: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct HarContent {
+    #[serde(default)]
+    text: Option<String>,
+    #[serde(default)]
+    encoding: Option<String>,
+}
+
+/// A response recorded in an archive
+#[derive(Debug, Clone)]
+struct RecordedResponse {
+    status_code: u16,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+    /// Where a redirect response points, resolved against its URL
+    location: Option<Url>,
+    time: Duration,
+}
+
+/// Get the key a request is matched by
+fn request_key(method: &str, url: &Url) -> (String, String) {
+    let mut url = url.clone();
+    url.set_fragment(None);
+    (method.to_ascii_uppercase(), url.to_string())
+}
+
+/// Serves the responses of an HTTP archive
+pub struct HarReplay {
+    responses: HashMap<(String, String), Vec<RecordedResponse>>,
+    /// How many responses of each request were served
+    served: Mutex<HashMap<(String, String), usize>>,
+    honor_timings: bool,
+    /// URL of the first recorded request, usually the page navigated to
+    first_url: Option<Url>,
+}
+
+impl HarReplay {
+    /// Read an archive from its JSON text
+    pub fn from_json(json: &str) -> io::Result<Self> {
+        let file: HarFile = serde_json::from_str(json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let mut responses: HashMap<(String, String), Vec<RecordedResponse>> = HashMap::new();
+        let mut first_url = None;
+        for (index, entry) in file.log.entries.into_iter().enumerate() {
+            let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, format!("HAR entry {}: {}", index, message));
+            let url = Url::parse(&entry.request.url).map_err(|e| invalid(format!("{}: {}", entry.request.url, e)))?;
+            first_url.get_or_insert_with(|| url.clone());
+            let body = match (entry.response.content.text, entry.response.content.encoding.as_deref()) {
+                (Some(text), Some("base64")) => base64::engine::general_purpose::STANDARD
+                    .decode(text.as_bytes())
+                    .map_err(|e| invalid(e.to_string()))?,
+                (Some(text), _) => text.into_bytes(),
+                (None, _) => Vec::new(),
+            };
+            // Bodies are recorded decoded
+            let headers: HashMap<String, String> = entry
+                .response
+                .headers
+                .into_iter()
+                .map(|header| (header.name.to_ascii_lowercase(), header.value))
+                .filter(|(name, _)| name != "content-encoding")
+                .collect();
+            let location = Some(entry.response.redirect_url)
+                .filter(|location| !location.is_empty())
+                .or_else(|| headers.get("location").cloned())
+                .filter(|_| (300..400).contains(&entry.response.status))
+                .and_then(|location| url.join(&location).ok());
+            responses.entry(request_key(&entry.request.method, &url)).or_default().push(RecordedResponse {
+                status_code: entry.response.status,
+                headers,
+                body,
+                location,
+                time: Duration::from_secs_f64(entry.time.max(0.0) / 1000.0),
+            });
+        }
+        Ok(HarReplay { responses, served: Mutex::new(HashMap::new()), honor_timings: false, first_url })
+    }
+
+    /// Read the archive at `path`
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    /// Choose whether each response is delayed by the time its request took
+    /// when it was recorded
+    pub fn honor_timings(mut self, honor: bool) -> Self {
+        self.honor_timings = honor;
+        self
+    }
+
+    /// Get the number of recorded responses
+    pub fn len(&self) -> usize {
+        self.responses.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.responses.is_empty()
+    }
+
+    /// Get the URL of the first recorded request, which is the page that
+    /// was loaded when the archive was recorded from a browser
+    pub fn first_url(&self) -> Option<&Url> {
+        self.first_url.as_ref()
+    }
+
+    /// Take the next recorded response to a request
+    fn next_response(&self, method: &str, url: &Url) -> Option<RecordedResponse> {
+        let key = request_key(method, url);
+        let recorded = self.responses.get(&key)?;
+        let mut served = self.served.lock().unwrap();
+        let count = served.entry(key).or_insert(0);
+        let response = recorded[(*count).min(recorded.len() - 1)].clone();
+        *count += 1;
+        Some(response)
+    }
+}
+
+impl RequestInterceptor for HarReplay {
+    /// Serve the recorded response, following recorded redirects like the
+    /// network client would
+    fn intercept(&self, method: &HttpMethod, url: &Url) -> Option<NetworkResult<InterceptedResponse>> {
+        let mut method = format!("{:?}", method);
+        let mut url = url.clone();
+        let mut delay = Duration::ZERO;
+        for _ in 0..=MAX_REDIRECTS {
+            let Some(response) = self.next_response(&method, &url) else {
+                return Some(Err(NetworkError::ConnectionFailed(format!("{} {} is not in the HAR archive", method, url))));
+            };
+            if self.honor_timings {
+                delay += response.time;
+            }
+            match response.location {
+                Some(location) => {
+                    // Like browsers, redirects other than 307 and 308 turn
+                    // POSTs into GETs
+                    if !matches!(response.status_code, 307 | 308) && method == "POST" {
+                        method = "GET".to_string();
+                    }
+                    url = location;
+                }
+                None => {
+                    return Some(Ok(InterceptedResponse {
+                        status_code: response.status_code,
+                        headers: response.headers,
+                        body: response.body,
+                        url: url.to_string(),
+                        delay,
+                    }))
+                }
+            }
+        }
+        Some(Err(NetworkError::ConnectionFailed(format!("Too many redirects in the HAR archive for {}", url))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HttpClient, HttpRequest};
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    const ARCHIVE: &str = r#"{"log": {"version": "1.2", "entries": [
+        {"time": 40, "request": {"method": "GET", "url": "http://example.com/"},
+         "response": {"status": 301, "headers": [{"name": "Location", "value": "/home"}], "content": {}, "redirectURL": ""}},
+        {"time": 10, "request": {"method": "GET", "url": "http://example.com/home"},
+         "response": {"status": 200, "headers": [{"name": "Content-Type", "value": "text/html"}, {"name": "Content-Encoding", "value": "gzip"}],
+                      "content": {"mimeType": "text/html", "text": "<p>First</p>"}}},
+        {"time": 10, "request": {"method": "GET", "url": "http://example.com/home"},
+         "response": {"status": 200, "headers": [], "content": {"text": "<p>Second</p>"}}},
+        {"request": {"method": "GET", "url": "http://example.com/logo.png"},
+         "response": {"status": 200, "headers": [], "content": {"text": "iVBORw==", "encoding": "base64"}}}
+    ]}}"#;
+
+    #[tokio::test]
+    async fn test_replay_serves_recorded_responses() {
+        let replay = HarReplay::from_json(ARCHIVE).unwrap().honor_timings(true);
+        assert_eq!(replay.len(), 4);
+        assert_eq!(replay.first_url().map(Url::as_str), Some("http://example.com/"));
+        let mut client = HttpClient::new();
+        client.set_request_interceptor(Arc::new(replay));
+
+        // The redirect is followed, and its time counts too
+        let start = Instant::now();
+        assert_eq!(client.fetch_html("http://example.com/#top").await.unwrap(), "<p>First</p>");
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        let response = client.send_request(HttpRequest::get("http://example.com/home".to_string())).await.unwrap();
+        assert_eq!(response.text().unwrap(), "<p>Second</p>");
+        assert_eq!(response.url, "http://example.com/home");
+        let again = client.send_request(HttpRequest::get("http://example.com/home".to_string())).await.unwrap();
+        assert_eq!(again.text().unwrap(), "<p>Second</p>");
+
+        let image = client.send_request(HttpRequest::get("http://example.com/logo.png".to_string())).await.unwrap();
+        assert_eq!(image.body, [0x89, b'P', b'N', b'G']);
+        assert!(client.fetch_html("http://example.com/missing").await.is_err());
+        assert!(HarReplay::from_json("{\"log\": {}}").unwrap().is_empty());
+        assert!(HarReplay::from_json("not json").is_err());
+    }
+}
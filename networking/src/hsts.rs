@@ -0,0 +1,298 @@
+//! # HTTP Strict Transport Security
+//!
+//! This module remembers the hosts that asked, through the
+//! `Strict-Transport-Security` header, to be reached over HTTPS only, and
+//! rewrites later `http://` URLs for them to `https://` before any request
+//! is sent.
+//!
+//! ## Design Principles
+//!
+//! 1. **Only Trust HTTPS**: Policies are only recorded from responses
+//!    received over HTTPS, so a network attacker cannot set or clear them.
+//! 2. **Upgrade Before Sending**: URLs are rewritten before the request
+//!    leaves the client, so no plain HTTP request reaches a protected host.
+//! 3. **Persistent and Shared**: A store is shared by cloning and, when
+//!    opened from a file, saved on every change, so policies survive
+//!    restarts and apply to every client of a profile.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use url::{Host, Url};
+
+/// Name of the HSTS file in a profile directory
+pub const HSTS_FILE: &str = "hsts.json";
+
+/// A parsed `Strict-Transport-Security` header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HstsDirective {
+    /// Seconds the policy stays in force; zero removes it
+    pub max_age: u64,
+    pub include_subdomains: bool,
+}
+
+impl HstsDirective {
+    /// Parse a header value such as `max-age=31536000; includeSubDomains`
+    ///
+    /// Returns `None` for headers without a valid `max-age` or with a
+    /// repeated directive, which must be ignored.
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut max_age = None;
+        let mut include_subdomains = false;
+        let mut seen = Vec::new();
+        for directive in value.split(';') {
+            let directive = directive.trim();
+            if directive.is_empty() {
+                continue;
+            }
+            let (name, argument) = match directive.split_once('=') {
+                Some((name, argument)) => (name.trim().to_ascii_lowercase(), Some(argument.trim().trim_matches('"'))),
+                None => (directive.to_ascii_lowercase(), None),
+            };
+            if seen.contains(&name) {
+                return None;
+            }
+            match name.as_str() {
+                "max-age" => max_age = Some(argument?.parse::<u64>().ok()?),
+                "includesubdomains" => include_subdomains = true,
+                _ => {}
+            }
+            seen.push(name);
+        }
+        Some(HstsDirective { max_age: max_age?, include_subdomains })
+    }
+}
+
+/// A host's HSTS policy
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HstsEntry {
+    pub host: String,
+    pub include_subdomains: bool,
+    /// When the policy expires, in seconds since the Unix epoch
+    pub expires_at: u64,
+}
+
+#[derive(Debug, Default)]
+struct HstsState {
+    entries: BTreeMap<String, HstsEntry>,
+    /// File the entries are saved to, if the store is persistent
+    path: Option<PathBuf>,
+}
+
+/// Known HSTS hosts
+///
+/// Cloning shares the store.
+#[derive(Debug, Clone, Default)]
+pub struct HstsStore {
+    state: Arc<Mutex<HstsState>>,
+}
+
+impl HstsStore {
+    /// Create an empty store that is not saved anywhere
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open the store saved in `dir`, creating an empty one if there is none
+    /// yet
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let path = dir.as_ref().join(HSTS_FILE);
+        let entries: Vec<HstsEntry> = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        let now = now();
+        let entries = entries
+            .into_iter()
+            .filter(|entry| entry.expires_at > now)
+            .map(|entry| (entry.host.clone(), entry))
+            .collect();
+        Ok(HstsStore { state: Arc::new(Mutex::new(HstsState { entries, path: Some(path) })) })
+    }
+
+    /// Record the `Strict-Transport-Security` header of a response
+    ///
+    /// Ignored unless the response came over HTTPS from a domain name.
+    /// Returns whether the store changed.
+    pub fn record(&self, url: &Url, header: &str) -> bool {
+        self.record_at(url, header, now())
+    }
+
+    fn record_at(&self, url: &Url, header: &str, now: u64) -> bool {
+        let (Some(host), Some(directive)) = (policy_host(url), HstsDirective::parse(header)) else {
+            return false;
+        };
+        if url.scheme() != "https" {
+            return false;
+        }
+        let mut state = self.lock();
+        let changed = if directive.max_age == 0 {
+            state.entries.remove(&host).is_some()
+        } else {
+            let entry = HstsEntry {
+                host: host.clone(),
+                include_subdomains: directive.include_subdomains,
+                expires_at: now.saturating_add(directive.max_age),
+            };
+            state.entries.insert(host, entry.clone()) != Some(entry)
+        };
+        if changed {
+            if let Err(e) = state.save() {
+                println!("❌ Failed to save HSTS policies: {}", e);
+            }
+        }
+        changed
+    }
+
+    /// Get the policy that applies to a host, if any
+    pub fn policy_for(&self, host: &str) -> Option<HstsEntry> {
+        self.policy_at(host, now())
+    }
+
+    fn policy_at(&self, host: &str, now: u64) -> Option<HstsEntry> {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        let state = self.lock();
+        let mut candidate = host.as_str();
+        let mut exact = true;
+        loop {
+            if let Some(entry) = state.entries.get(candidate) {
+                if entry.expires_at > now && (exact || entry.include_subdomains) {
+                    return Some(entry.clone());
+                }
+            }
+            candidate = candidate.split_once('.')?.1;
+            exact = false;
+        }
+    }
+
+    /// Rewrite an `http://` URL to `https://` if its host is protected
+    ///
+    /// Returns `None` if the URL needs no upgrade.
+    pub fn upgrade(&self, url: &Url) -> Option<Url> {
+        self.upgrade_at(url, now())
+    }
+
+    fn upgrade_at(&self, url: &Url, now: u64) -> Option<Url> {
+        if url.scheme() != "http" {
+            return None;
+        }
+        self.policy_at(&policy_host(url)?, now)?;
+        let mut upgraded = url.clone();
+        let port = url.port();
+        upgraded.set_scheme("https").ok()?;
+        if port == Some(80) {
+            upgraded.set_port(None).ok()?;
+        }
+        Some(upgraded)
+    }
+
+    /// Get the unexpired policies, sorted by host
+    pub fn entries(&self) -> Vec<HstsEntry> {
+        let now = now();
+        self.lock().entries.values().filter(|entry| entry.expires_at > now).cloned().collect()
+    }
+
+    /// Forget the policy of a host
+    ///
+    /// Returns whether the host had one.
+    pub fn remove(&self, host: &str) -> io::Result<bool> {
+        let mut state = self.lock();
+        let removed = state.entries.remove(&host.to_ascii_lowercase()).is_some();
+        if removed {
+            state.save()?;
+        }
+        Ok(removed)
+    }
+
+    /// Forget all policies
+    pub fn clear(&self) -> io::Result<()> {
+        let mut state = self.lock();
+        state.entries.clear();
+        state.save()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HstsState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl HstsState {
+    fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let entries: Vec<&HstsEntry> = self.entries.values().collect();
+        let contents = serde_json::to_string_pretty(&entries).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let temporary = path.with_extension("json.tmp");
+        fs::write(&temporary, contents)?;
+        fs::rename(&temporary, path)
+    }
+}
+
+/// Get the host a policy is kept under; IP addresses never have one
+fn policy_host(url: &Url) -> Option<String> {
+    match url.host()? {
+        Host::Domain(domain) => Some(domain.trim_end_matches('.').to_ascii_lowercase()),
+        Host::Ipv4(_) | Host::Ipv6(_) => None,
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(text: &str) -> Url {
+        Url::parse(text).unwrap()
+    }
+
+    #[test]
+    fn test_hsts_upgrades_and_persistence() {
+        assert_eq!(
+            HstsDirective::parse("max-age=\"600\"; includeSubDomains; preload"),
+            Some(HstsDirective { max_age: 600, include_subdomains: true })
+        );
+        assert_eq!(HstsDirective::parse("includeSubDomains"), None);
+        assert_eq!(HstsDirective::parse("max-age=1; max-age=2"), None);
+
+        let dir = std::env::temp_dir().join(format!("dubby-hsts-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let store = HstsStore::open(&dir).unwrap();
+        let now = now();
+
+        // Only HTTPS responses from domain names set policies
+        assert!(!store.record_at(&url("http://plain.example/"), "max-age=600", now));
+        assert!(!store.record_at(&url("https://127.0.0.1/"), "max-age=600", now));
+        assert!(store.record_at(&url("https://example.com/"), "max-age=600; includeSubDomains", now));
+        assert!(store.record_at(&url("https://only.test/"), "max-age=600", now));
+
+        assert_eq!(store.upgrade_at(&url("http://example.com:80/a?b"), now).unwrap().as_str(), "https://example.com/a?b");
+        assert_eq!(store.upgrade_at(&url("http://cdn.example.com:8080/"), now).unwrap().as_str(), "https://cdn.example.com:8080/");
+        assert!(store.upgrade_at(&url("http://sub.only.test/"), now).is_none());
+        assert!(store.upgrade_at(&url("http://example.com/"), now + 600).is_none());
+        assert!(store.upgrade_at(&url("https://example.com/"), now).is_none());
+
+        // Policies are saved and a zero max-age removes them
+        let reopened = HstsStore::open(&dir).unwrap();
+        assert_eq!(reopened.lock().entries.len(), 2);
+        assert!(reopened.record_at(&url("https://only.test/"), "max-age=0", now));
+        assert!(reopened.policy_at("only.test", now).is_none());
+        assert!(reopened.remove("example.com").unwrap());
+        assert!(HstsStore::open(&dir).unwrap().lock().entries.is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,164 @@
+//! # Subresource Integrity
+//!
+//! This module implements the `integrity` attribute of `<script>` and
+//! `<link>`. Pages list the digests a resource may have, and the fetched
+//! bytes must hash to one of them before the resource is used.
+//!
+//! ## Design Principles
+//!
+//! 1. **Strongest Algorithm Wins**: Only the digests of the strongest listed
+//!    algorithm are compared, so a page cannot be downgraded to a weaker
+//!    hash by adding one.
+//! 2. **Unknown Is Ignored**: Unknown algorithms and options are skipped, and
+//!    metadata without any usable digest does not block the resource, as
+//!    the specification requires.
+//! 3. **Bytes, Not Text**: Digests are computed over the response body as
+//!    received, before any decoding.
+
+use base64::Engine;
+use ring::digest;
+use crate::{NetworkError, NetworkResult};
+
+/// A hash algorithm usable in integrity metadata, weakest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IntegrityAlgorithm {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl IntegrityAlgorithm {
+    /// Parse an algorithm prefix such as `sha384`
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "sha256" => Some(IntegrityAlgorithm::Sha256),
+            "sha384" => Some(IntegrityAlgorithm::Sha384),
+            "sha512" => Some(IntegrityAlgorithm::Sha512),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IntegrityAlgorithm::Sha256 => "sha256",
+            IntegrityAlgorithm::Sha384 => "sha384",
+            IntegrityAlgorithm::Sha512 => "sha512",
+        }
+    }
+
+    /// Compute the base64 digest of `bytes`
+    pub fn digest(&self, bytes: &[u8]) -> String {
+        let algorithm = match self {
+            IntegrityAlgorithm::Sha256 => &digest::SHA256,
+            IntegrityAlgorithm::Sha384 => &digest::SHA384,
+            IntegrityAlgorithm::Sha512 => &digest::SHA512,
+        };
+        base64::engine::general_purpose::STANDARD.encode(digest::digest(algorithm, bytes))
+    }
+}
+
+/// One digest of integrity metadata, e.g. `sha384-oqVuAfXR...`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntegrityHash {
+    pub algorithm: IntegrityAlgorithm,
+    /// The expected digest in standard base64
+    pub digest: String,
+}
+
+/// The parsed value of an `integrity` attribute
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct IntegrityMetadata {
+    hashes: Vec<IntegrityHash>,
+}
+
+impl IntegrityMetadata {
+    /// Parse an `integrity` attribute value
+    pub fn parse(value: &str) -> Self {
+        let hashes = value
+            .split_whitespace()
+            .filter_map(|token| {
+                // Options after `?` are reserved and ignored
+                let token = token.split('?').next().unwrap_or_default();
+                let (algorithm, digest) = token.split_once('-')?;
+                let algorithm = IntegrityAlgorithm::parse(algorithm)?;
+                if digest.is_empty() {
+                    return None;
+                }
+                // Accept the URL-safe alphabet, as other browsers do
+                let digest = digest.replace('-', "+").replace('_', "/");
+                Some(IntegrityHash { algorithm, digest })
+            })
+            .collect();
+        IntegrityMetadata { hashes }
+    }
+
+    /// Get the usable digests
+    pub fn hashes(&self) -> &[IntegrityHash] {
+        &self.hashes
+    }
+
+    /// Whether the metadata has no usable digest and blocks nothing
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    /// The strongest algorithm listed
+    pub fn strongest_algorithm(&self) -> Option<IntegrityAlgorithm> {
+        self.hashes.iter().map(|hash| hash.algorithm).max()
+    }
+
+    /// Check fetched bytes against the metadata
+    ///
+    /// Fails with `NetworkError::IntegrityMismatch` if no digest of the
+    /// strongest algorithm matches.
+    pub fn verify(&self, bytes: &[u8]) -> NetworkResult<()> {
+        let Some(algorithm) = self.strongest_algorithm() else {
+            return Ok(());
+        };
+        let actual = algorithm.digest(bytes);
+        let padded = |digest: &str| digest.trim_end_matches('=').to_string();
+        let matches = self
+            .hashes
+            .iter()
+            .filter(|hash| hash.algorithm == algorithm)
+            .any(|hash| padded(&hash.digest) == padded(&actual));
+        if matches {
+            Ok(())
+        } else {
+            Err(NetworkError::IntegrityMismatch(format!(
+                "no {} digest matches the computed integrity '{}-{}'",
+                algorithm.as_str(),
+                algorithm.as_str(),
+                actual
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_integrity_verification() {
+        let body = b"alert('Hello, world.');";
+        let sha256 = IntegrityAlgorithm::Sha256.digest(body);
+        let sha384 = IntegrityAlgorithm::Sha384.digest(body);
+        assert_eq!(sha384, "H8BRh8j48O9oYatfu5AZzq6A9RINhZO5H16dQZngK7T62em8MUt1FLm52t+eX6xO");
+
+        assert!(IntegrityMetadata::parse(&format!("sha384-{}", sha384)).verify(body).is_ok());
+        assert!(IntegrityMetadata::parse(&format!("sha384-{}", sha384)).verify(b"alert('Hacked');").is_err());
+
+        // Only the strongest algorithm counts
+        let mixed = IntegrityMetadata::parse(&format!("sha256-{} sha384-AAAA", sha256));
+        assert_eq!(mixed.strongest_algorithm(), Some(IntegrityAlgorithm::Sha384));
+        assert!(matches!(mixed.verify(body), Err(NetworkError::IntegrityMismatch(_))));
+        let either = IntegrityMetadata::parse(&format!("sha384-AAAA sha384-{}?ct=text/javascript", sha384));
+        assert!(either.verify(body).is_ok());
+
+        // Unknown algorithms leave nothing to check
+        let unknown = IntegrityMetadata::parse("md5-deadbeef sha1-abc");
+        assert!(unknown.is_empty());
+        assert!(unknown.verify(body).is_ok());
+    }
+}
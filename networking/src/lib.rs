@@ -17,12 +17,44 @@
 //!    like caching, redirects, and custom headers in the future.
 
 use reqwest::Client;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::collections::HashMap;
+use std::sync::Arc;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use url::Url;
 
+// Subresource integrity verification
+pub mod integrity;
+
+// Strict-Transport-Security policies and HTTPS upgrades
+pub mod hsts;
+
+// Blocking and upgrading of insecure requests from secure documents
+pub mod mixed_content;
+
+// Responses stored on disk by URL
+pub mod disk_cache;
+
+// Named response caches for the Cache Storage API
+pub mod cache_storage;
+
+// Resolution of relative URLs against the document base URL
+pub mod base_url;
+
+// Replay of HTTP Archive (HAR) files instead of the network
+pub mod har;
+
+// Filter-list blocking of ads and trackers
+pub mod content_blocking;
+
+use content_blocking::{BlockDecision, ContentBlocker};
+use hsts::HstsStore;
+use mixed_content::{check_mixed_content, MixedContentDecision, RequestDestination};
+
+pub use dom::Origin;
+pub use base_url::{document_base_url, resolve_url, UrlResolver};
+
 /// Custom error types for networking operations
 #[derive(Error, Debug)]
 pub enum NetworkError {
@@ -56,8 +88,24 @@ pub enum NetworkError {
     #[error("SSL/TLS error: {0}")]
     SslError(String),
     
+    /// A document was requested and the response is not one, e.g. an image
+    #[error("Unsupported content type: {0}")]
+    UnsupportedContentType(String),
+    
     #[error("Parse error: {0}")]
     ParseError(String),
+    
+    #[error("Integrity check failed: {0}")]
+    IntegrityMismatch(String),
+    
+    #[error("Mixed Content: blocked insecure {destination} {url}")]
+    MixedContent { url: String, destination: &'static str },
+    
+    #[error("Cache storage failed: {0}")]
+    CacheError(String),
+    
+    #[error("Blocked by client: {destination} {url} matched {rule}")]
+    BlockedByClient { url: String, destination: &'static str, rule: String },
 }
 
 /// Result type for networking operations
@@ -172,12 +220,41 @@ impl HttpStatus {
     }
 }
 
+/// A response a request interceptor serves instead of the network
+#[derive(Debug, Clone)]
+pub struct InterceptedResponse {
+    pub status_code: u16,
+    /// Header names and values, names in lower case
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+    /// The final URL of the response, after redirects
+    pub url: String,
+    /// How long the client waits before delivering the response
+    pub delay: Duration,
+}
+
+/// Answers requests in place of the network, e.g. to replay a recording
+pub trait RequestInterceptor: Send + Sync {
+    /// Get the response to a request, or `None` to send it over the
+    /// network
+    fn intercept(&self, method: &HttpMethod, url: &Url) -> Option<NetworkResult<InterceptedResponse>>;
+}
+
 /// HTTP client for fetching web resources
 /// 
 /// This struct provides methods for fetching HTML, CSS, and other web resources
 /// with proper error handling and security measures.
 pub struct HttpClient {
     client: Client,
+    /// Hosts whose `http://` URLs are upgraded to `https://`
+    hsts: HstsStore,
+    /// Whether insecure images and media of secure documents are upgraded
+    /// to HTTPS rather than loaded as is
+    upgrade_passive_mixed_content: bool,
+    /// Answers requests before they reach the network
+    interceptor: Option<Arc<dyn RequestInterceptor>>,
+    /// Filter lists requests are checked against before they are sent
+    content_blocker: ContentBlocker,
 }
 
 impl HttpClient {
@@ -194,7 +271,77 @@ impl HttpClient {
             .build()
             .expect("Failed to create HTTP client");
         
-        HttpClient { client }
+        HttpClient {
+            client,
+            hsts: HstsStore::new(),
+            upgrade_passive_mixed_content: true,
+            interceptor: None,
+            content_blocker: ContentBlocker::new(),
+        }
+    }
+    
+    /// Choose whether insecure images and media of secure documents are
+    /// upgraded to HTTPS (the default) or loaded over HTTP
+    /// 
+    /// Active mixed content is blocked either way.
+    pub fn set_upgrade_passive_mixed_content(&mut self, upgrade: bool) {
+        self.upgrade_passive_mixed_content = upgrade;
+    }
+    
+    /// Use a (usually persistent) HSTS store shared with other clients
+    pub fn set_hsts_store(&mut self, store: HstsStore) {
+        self.hsts = store;
+    }
+    
+    /// Get the HSTS policies this client records and applies
+    pub fn hsts_store(&self) -> &HstsStore {
+        &self.hsts
+    }
+    
+    /// Block the requests a (usually shared) content blocker's filter lists
+    /// match
+    pub fn set_content_blocker(&mut self, blocker: ContentBlocker) {
+        self.content_blocker = blocker;
+    }
+    
+    /// Get the content blocker requests are checked against
+    pub fn content_blocker(&self) -> &ContentBlocker {
+        &self.content_blocker
+    }
+    
+    /// Let `interceptor` answer requests before they reach the network
+    pub fn set_request_interceptor(&mut self, interceptor: Arc<dyn RequestInterceptor>) {
+        self.interceptor = Some(interceptor);
+    }
+    
+    /// Ask the interceptor for the response to a request, waiting out its
+    /// delay
+    async fn intercept(&self, method: &HttpMethod, url: &Url) -> Option<NetworkResult<InterceptedResponse>> {
+        let result = self.interceptor.as_ref()?.intercept(method, url)?;
+        if let Ok(response) = &result {
+            if !response.delay.is_zero() {
+                tokio::time::sleep(response.delay).await;
+            }
+        }
+        Some(result)
+    }
+    
+    /// Upgrade a URL to HTTPS if an HSTS policy covers its host
+    fn apply_hsts(&self, url: Url) -> Url {
+        match self.hsts.upgrade(&url) {
+            Some(upgraded) => {
+                println!("🔒 Upgraded {} to HTTPS (HSTS)", url);
+                upgraded
+            }
+            None => url,
+        }
+    }
+    
+    /// Record the `Strict-Transport-Security` header of a response
+    fn record_hsts(&self, url: &str, header: Option<&str>) {
+        if let (Some(header), Ok(url)) = (header, Url::parse(url)) {
+            self.hsts.record(&url, header);
+        }
     }
     
     /// Fetch HTML content from a URL
@@ -224,12 +371,33 @@ impl HttpClient {
     /// }
     /// ```
     pub async fn fetch_html(&self, url: &str) -> NetworkResult<String> {
-        let parsed_url = self.validate_url(url)?;
+        let (content, content_type) = self.fetch_text_with_type(url).await?;
+        match content_type {
+            Some(content_type) if !is_document_type(&content_type) => Err(NetworkError::UnsupportedContentType(content_type)),
+            _ => Ok(content),
+        }
+    }
+    
+    /// Fetch text content from a URL with the `Content-Type` of the
+    /// response, if it had one
+    async fn fetch_text_with_type(&self, url: &str) -> NetworkResult<(String, Option<String>)> {
+        let parsed_url = self.apply_hsts(self.validate_url(url)?);
+        if let Some(result) = self.intercept(&HttpMethod::GET, &parsed_url).await {
+            let response = result?;
+            if !HttpStatus::from_code(response.status_code).is_success() {
+                return Err(NetworkError::HttpError { status: response.status_code, message: format!("{} failed", response.url) });
+            }
+            let content_type = response.headers.get("content-type").cloned();
+            return Ok((String::from_utf8_lossy(&response.body).into_owned(), content_type));
+        }
         
         let response = self.client
             .get(parsed_url.as_str())
             .send()
-            .await?;
+            .await
+            .map_err(classify_send_error)?;
+        let hsts_header = response.headers().get("strict-transport-security").and_then(|value| value.to_str().ok());
+        self.record_hsts(response.url().as_str(), hsts_header);
         
         if !response.status().is_success() {
             return Err(NetworkError::RequestFailed(
@@ -237,8 +405,9 @@ impl HttpClient {
             ));
         }
         
+        let content_type = response.headers().get(reqwest::header::CONTENT_TYPE).and_then(|value| value.to_str().ok()).map(String::from);
         let content = response.text().await?;
-        Ok(content)
+        Ok((content, content_type))
     }
     
     /// Fetch any text content from a URL (HTML, CSS, JS, etc.)
@@ -255,7 +424,7 @@ impl HttpClient {
     /// 
     /// A `NetworkResult<String>` containing the text content or an error
     pub async fn fetch_text(&self, url: &str) -> NetworkResult<String> {
-        self.fetch_html(url).await // Same implementation for now
+        Ok(self.fetch_text_with_type(url).await?.0)
     }
     
     /// Validate and parse a URL
@@ -280,10 +449,124 @@ impl HttpClient {
     }
 
     /// Make a real HTTP request and return the response
+    /// 
+    /// If the request carries an abort signal, aborting it drops the
+    /// in-flight reqwest future, which cancels the request and releases its
+    /// connection, and the call fails with `NetworkError::RequestAborted`.
     pub async fn send_request(&self, request: HttpRequest) -> Result<HttpResponse, NetworkError> {
+        match request.signal.clone() {
+            Some(signal) => {
+                if signal.is_aborted() {
+                    return Err(NetworkError::RequestAborted);
+                }
+                tokio::select! {
+                    result = self.perform_request(request) => result,
+                    _ = signal.aborted() => Err(NetworkError::RequestAborted),
+                }
+            }
+            None => self.perform_request(request).await,
+        }
+    }
+
+    /// Make a real HTTP request and stream the response body
+    /// 
+    /// Resolves once the response headers have arrived. The body is read
+    /// chunk by chunk with `StreamingResponse::next_chunk`, so large
+    /// payloads are never buffered in full. The request's abort signal is
+    /// observed both while waiting for headers and while reading chunks.
+    pub async fn send_request_streaming(&self, request: HttpRequest) -> Result<StreamingResponse, NetworkError> {
+        match request.signal.clone() {
+            Some(signal) => {
+                if signal.is_aborted() {
+                    return Err(NetworkError::RequestAborted);
+                }
+                tokio::select! {
+                    result = self.start_request(request) => result,
+                    _ = signal.aborted() => Err(NetworkError::RequestAborted),
+                }
+            }
+            None => self.start_request(request).await,
+        }
+    }
+
+    /// Perform a request without observing its abort signal
+    async fn perform_request(&self, request: HttpRequest) -> Result<HttpResponse, NetworkError> {
+        let fetch_start = Instant::now();
+        let response = self.start_request(request).await?;
+        let response_start = Instant::now();
+
+        // Get response body (this consumes the response)
+        let body = match response.body {
+            ResponseBody::Network(response) => response
+                .bytes()
+                .await
+                .map_err(|e| NetworkError::ConnectionFailed(e.to_string()))?
+                .to_vec(),
+            ResponseBody::Intercepted(body) => body.unwrap_or_default(),
+        };
+
+        Ok(HttpResponse {
+            status: response.status,
+            status_code: response.status_code,
+            headers: response.headers,
+            body,
+            url: response.url,
+            timing: Some(FetchTiming {
+                fetch_start,
+                response_start,
+                response_end: Instant::now(),
+            }),
+        })
+    }
+
+    /// Send a request and wait for the response headers
+    async fn start_request(&self, request: HttpRequest) -> Result<StreamingResponse, NetworkError> {
         // Parse the URL
         let url = Url::parse(&request.url)
             .map_err(|e| NetworkError::ParseError(e.to_string()))?;
+        let mut url = self.apply_hsts(url);
+        if let Some(origin) = &request.origin {
+            match check_mixed_content(origin, &url, request.destination, self.upgrade_passive_mixed_content) {
+                MixedContentDecision::Allowed => {}
+                MixedContentDecision::Upgraded(upgraded) => {
+                    println!("🔒 Upgraded insecure {} {} to HTTPS", request.destination.as_str(), url);
+                    url = upgraded;
+                }
+                MixedContentDecision::Blocked => {
+                    return Err(NetworkError::MixedContent {
+                        url: url.to_string(),
+                        destination: request.destination.as_str(),
+                    });
+                }
+            }
+        }
+        if let BlockDecision::Blocked { rule } = self.content_blocker.classify(&url, request.origin.as_ref(), request.destination) {
+            return Err(NetworkError::BlockedByClient { url: url.to_string(), destination: request.destination.as_str(), rule });
+        }
+
+        // Cross-origin requests carry an Origin header and are subject to CORS
+        let cors_origin = if request.mode == RequestMode::Cors && request.is_cross_origin() {
+            request.origin.clone()
+        } else {
+            None
+        };
+        let credentials = request.credentials;
+        let signal = request.signal.clone();
+
+        if let Some(result) = self.intercept(&request.method, &url).await {
+            let response = result?;
+            if let Some(origin) = &cors_origin {
+                check_cors_response(origin, credentials, &response.headers)?;
+            }
+            return Ok(StreamingResponse {
+                status: HttpStatus::from_code(response.status_code),
+                status_code: response.status_code,
+                headers: response.headers,
+                url: response.url,
+                body: ResponseBody::Intercepted(Some(response.body)),
+                signal,
+            });
+        }
 
         // Build the HTTP request
         let mut req_builder = match request.method {
@@ -300,6 +583,9 @@ impl HttpClient {
         for (key, value) in request.headers {
             req_builder = req_builder.header(&key, &value);
         }
+        if let Some(origin) = &cors_origin {
+            req_builder = req_builder.header("Origin", origin.serialize());
+        }
 
         // Add body for POST/PUT requests
         if let Some(body) = request.body {
@@ -328,19 +614,19 @@ impl HttpClient {
             );
         }
 
-        // Get response body (this consumes the response)
-        let body = response
-            .bytes()
-            .await
-            .map_err(|e| NetworkError::ConnectionFailed(e.to_string()))?
-            .to_vec();
+        self.record_hsts(&final_url, headers.get("strict-transport-security").map(String::as_str));
+        
+        if let Some(origin) = &cors_origin {
+            check_cors_response(origin, credentials, &headers)?;
+        }
 
-        Ok(HttpResponse {
+        Ok(StreamingResponse {
             status,
             status_code,
             headers,
-            body,
             url: final_url,
+            body: ResponseBody::Network(response),
+            signal,
         })
     }
 }
@@ -381,6 +667,39 @@ pub async fn fetch(url: &str) -> NetworkResult<String> {
     client.fetch_html(url).await
 }
 
+/// Check whether a `Content-Type` is one the browser shows as a document
+/// 
+/// HTML, XML and other text types are documents; images, archives and
+/// other binary types are not.
+pub fn is_document_type(content_type: &str) -> bool {
+    let essence = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    essence.is_empty()
+        || essence.starts_with("text/")
+        || essence == "application/xhtml+xml"
+        || essence == "application/xml"
+        || essence.ends_with("+xml")
+}
+
+/// Convert a failure to send a request, telling certificate and TLS
+/// failures apart from other connection failures
+fn classify_send_error(error: reqwest::Error) -> NetworkError {
+    let mut messages = Vec::new();
+    let mut source: Option<&dyn std::error::Error> = std::error::Error::source(&error);
+    while let Some(cause) = source {
+        messages.push(cause.to_string());
+        source = cause.source();
+    }
+    let is_tls = messages.iter().any(|message| {
+        let message = message.to_ascii_lowercase();
+        message.contains("certificate") || message.contains("tls") || message.contains("ssl") || message.contains("handshake")
+    });
+    if is_tls {
+        NetworkError::SslError(messages.join(": "))
+    } else {
+        NetworkError::RequestFailed(error)
+    }
+}
+
 /// Convenience function to fetch text content from a URL
 /// 
 /// This function creates a new HTTP client and fetches any text content
@@ -398,6 +717,66 @@ pub async fn fetch_text(url: &str) -> NetworkResult<String> {
     client.fetch_text(url).await
 }
 
+/// A response whose body is read incrementally
+pub struct StreamingResponse {
+    pub status: HttpStatus,
+    pub status_code: u16,
+    pub headers: HashMap<String, String>,
+    pub url: String,
+    body: ResponseBody,
+    signal: Option<AbortToken>,
+}
+
+/// Where the body of a streaming response comes from
+enum ResponseBody {
+    Network(reqwest::Response),
+    /// The body of an intercepted response, read in one chunk
+    Intercepted(Option<Vec<u8>>),
+}
+
+impl StreamingResponse {
+    /// Read the next chunk of the body, or `None` at the end of the body
+    pub async fn next_chunk(&mut self) -> NetworkResult<Option<Vec<u8>>> {
+        let response = match &mut self.body {
+            ResponseBody::Network(response) => response,
+            ResponseBody::Intercepted(body) => {
+                if self.signal.as_ref().is_some_and(AbortToken::is_aborted) {
+                    return Err(NetworkError::RequestAborted);
+                }
+                return Ok(body.take());
+            }
+        };
+        let chunk = match self.signal.clone() {
+            Some(signal) => {
+                if signal.is_aborted() {
+                    return Err(NetworkError::RequestAborted);
+                }
+                tokio::select! {
+                    chunk = response.chunk() => chunk,
+                    _ = signal.aborted() => return Err(NetworkError::RequestAborted),
+                }
+            }
+            None => response.chunk().await,
+        };
+        
+        chunk
+            .map(|chunk| chunk.map(|bytes| bytes.to_vec()))
+            .map_err(|e| NetworkError::ConnectionFailed(e.to_string()))
+    }
+    
+    pub fn get_header(&self, name: &str) -> Option<&String> {
+        self.headers.get(name)
+    }
+    
+    /// Declared body length, if the server sent one
+    pub fn content_length(&self) -> Option<u64> {
+        match &self.body {
+            ResponseBody::Network(response) => response.content_length(),
+            ResponseBody::Intercepted(body) => body.as_ref().map(|body| body.len() as u64),
+        }
+    }
+}
+
 /// XMLHttpRequest implementation
 pub struct XMLHttpRequest {
     request: HttpRequest,
@@ -411,7 +790,10 @@ pub struct XMLHttpRequest {
     onload: Option<Box<dyn Fn() + Send + Sync>>,
     onerror: Option<Box<dyn Fn() + Send + Sync>>,
     ontimeout: Option<Box<dyn Fn() + Send + Sync>>,
+    onabort: Option<Box<dyn Fn() + Send + Sync>>,
     is_aborted: bool,
+    /// Signal for the current request, replaced on every `open()`
+    abort_token: AbortToken,
 }
 
 /// HTTP request configuration
@@ -425,6 +807,64 @@ pub struct HttpRequest {
     pub follow_redirects: bool,
     pub max_redirects: u32,
     pub credentials: bool, // For CORS
+    /// Origin of the document that initiated the request, if any
+    pub origin: Option<Origin>,
+    /// Request mode, decides whether CORS checks apply
+    pub mode: RequestMode,
+    /// What the response is used for, decides how mixed content is handled
+    pub destination: RequestDestination,
+    /// Signal that cancels the request when aborted
+    pub signal: Option<AbortToken>,
+}
+
+/// Request mode, mirroring the Fetch specification
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RequestMode {
+    /// Top-level document navigation
+    Navigate,
+    /// Subresource loads like stylesheets and images; no CORS checks
+    NoCors,
+    /// Script-initiated requests (fetch, XHR); cross-origin responses need CORS headers
+    Cors,
+}
+
+/// Cancellation signal shared between an abort source and a request
+/// 
+/// This is the network-level counterpart of a DOM `AbortSignal`. Clones
+/// share state, so aborting any clone aborts them all.
+#[derive(Debug, Clone)]
+pub struct AbortToken {
+    sender: std::sync::Arc<tokio::sync::watch::Sender<bool>>,
+}
+
+impl AbortToken {
+    pub fn new() -> Self {
+        let (sender, _receiver) = tokio::sync::watch::channel(false);
+        Self {
+            sender: std::sync::Arc::new(sender),
+        }
+    }
+    
+    /// Abort every request observing this token
+    pub fn abort(&self) {
+        self.sender.send_replace(true);
+    }
+    
+    pub fn is_aborted(&self) -> bool {
+        *self.sender.borrow()
+    }
+    
+    /// Wait until the token is aborted
+    pub async fn aborted(&self) {
+        let mut receiver = self.sender.subscribe();
+        let _ = receiver.wait_for(|aborted| *aborted).await;
+    }
+}
+
+impl Default for AbortToken {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl HttpRequest {
@@ -438,6 +878,10 @@ impl HttpRequest {
             follow_redirects: true,
             max_redirects: 5,
             credentials: false,
+            origin: None,
+            mode: RequestMode::Cors,
+            destination: RequestDestination::Empty,
+            signal: None,
         }
     }
     
@@ -455,13 +899,44 @@ impl HttpRequest {
             follow_redirects: true,
             max_redirects: 5,
             credentials: false,
+            origin: None,
+            mode: RequestMode::Cors,
+            destination: RequestDestination::Empty,
+            signal: None,
         }
     }
     
+    /// Create a POST request carrying form-encoded entries
+    pub fn form_post(url: String, entries: &[(String, String)]) -> Self {
+        let mut request = Self::post(url, Some(encode_form_urlencoded(entries).into_bytes()));
+        request.set_header(
+            "Content-Type".to_string(),
+            "application/x-www-form-urlencoded".to_string(),
+        );
+        request
+    }
+    
+    /// Create a POST request carrying a `multipart/form-data` body
+    pub fn multipart_post(url: String, parts: &[MultipartPart]) -> Self {
+        let mut request = Self::post(url, None);
+        request.set_multipart_body(parts);
+        request
+    }
+    
     pub fn set_header(&mut self, name: String, value: String) {
         self.headers.insert(name, value);
     }
     
+    /// Replace the body with a `multipart/form-data` encoding of `parts`
+    pub fn set_multipart_body(&mut self, parts: &[MultipartPart]) {
+        let boundary = generate_multipart_boundary();
+        self.body = Some(encode_multipart_form_data(parts, &boundary));
+        self.set_header(
+            "Content-Type".to_string(),
+            format!("multipart/form-data; boundary={}", boundary),
+        );
+    }
+    
     pub fn set_timeout(&mut self, timeout: Duration) {
         self.timeout = Some(timeout);
     }
@@ -469,6 +944,161 @@ impl HttpRequest {
     pub fn set_credentials(&mut self, credentials: bool) {
         self.credentials = credentials;
     }
+    
+    pub fn set_origin(&mut self, origin: Origin) {
+        self.origin = Some(origin);
+    }
+    
+    pub fn set_signal(&mut self, signal: AbortToken) {
+        self.signal = Some(signal);
+    }
+    
+    pub fn set_destination(&mut self, destination: RequestDestination) {
+        self.destination = destination;
+    }
+    
+    /// Check whether this request goes to a different origin than the
+    /// document that initiated it
+    pub fn is_cross_origin(&self) -> bool {
+        match &self.origin {
+            Some(origin) => {
+                // The origin of the URL as the request resolves it for fetching
+                let target = UrlResolver::default().resolve(&self.url);
+                !origin.is_same_origin(&target.as_ref().map_or(Origin::Opaque, Origin::from))
+            }
+            None => false,
+        }
+    }
+}
+
+/// Encode name/value pairs as `application/x-www-form-urlencoded`
+/// 
+/// This is the encoding used for HTML form submissions, both for the query
+/// string of GET submissions and the body of POST submissions.
+pub fn encode_form_urlencoded(entries: &[(String, String)]) -> String {
+    let mut serializer = url::form_urlencoded::Serializer::new(String::new());
+    for (name, value) in entries {
+        serializer.append_pair(name, value);
+    }
+    serializer.finish()
+}
+
+/// Build the URL for a GET form submission
+/// 
+/// Any existing query string in `action` is replaced by the encoded entries.
+pub fn form_submission_url(action: &str, entries: &[(String, String)]) -> NetworkResult<String> {
+    let mut url = Url::parse(action)?;
+    url.set_query(Some(&encode_form_urlencoded(entries)));
+    Ok(url.to_string())
+}
+
+/// A single part of a `multipart/form-data` body
+#[derive(Debug, Clone, PartialEq)]
+pub enum MultipartPart {
+    /// A plain text field
+    Text { name: String, value: String },
+    /// A file field
+    File {
+        name: String,
+        filename: String,
+        content_type: String,
+        data: Vec<u8>,
+    },
+}
+
+/// Generate a boundary for a `multipart/form-data` body
+pub fn generate_multipart_boundary() -> String {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("----DubbyFormBoundary{:016x}{:04x}", nanos, count & 0xffff)
+}
+
+/// Escape a field name or filename for a Content-Disposition header
+fn escape_multipart_name(name: &str) -> String {
+    name.replace('"', "%22").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+/// Encode parts as a `multipart/form-data` body with the given boundary
+/// 
+/// Returns the body bytes; the matching Content-Type header value is
+/// `multipart/form-data; boundary=<boundary>`.
+pub fn encode_multipart_form_data(parts: &[MultipartPart], boundary: &str) -> Vec<u8> {
+    let mut body = Vec::new();
+    for part in parts {
+        body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+        match part {
+            MultipartPart::Text { name, value } => {
+                body.extend_from_slice(format!(
+                    "Content-Disposition: form-data; name=\"{}\"\r\n\r\n",
+                    escape_multipart_name(name)
+                ).as_bytes());
+                body.extend_from_slice(value.as_bytes());
+            }
+            MultipartPart::File { name, filename, content_type, data } => {
+                let content_type = if content_type.is_empty() {
+                    "application/octet-stream"
+                } else {
+                    content_type.as_str()
+                };
+                body.extend_from_slice(format!(
+                    "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n",
+                    escape_multipart_name(name),
+                    escape_multipart_name(filename),
+                    content_type
+                ).as_bytes());
+                body.extend_from_slice(data);
+            }
+        }
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+    body
+}
+
+/// Check a cross-origin response against the requesting origin
+/// 
+/// The response must carry an `Access-Control-Allow-Origin` header that is
+/// either `*` (only for requests without credentials) or matches the
+/// serialized requesting origin exactly.
+pub fn check_cors_response(
+    origin: &Origin,
+    credentials: bool,
+    headers: &HashMap<String, String>,
+) -> NetworkResult<()> {
+    let allow_origin = headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("access-control-allow-origin"))
+        .map(|(_, value)| value.trim());
+    
+    match allow_origin {
+        Some("*") if !credentials => Ok(()),
+        Some(value) if value == origin.serialize() => Ok(()),
+        Some(value) => Err(NetworkError::CorsError(format!(
+            "Access-Control-Allow-Origin '{}' does not match origin '{}'",
+            value, origin
+        ))),
+        None => Err(NetworkError::CorsError(format!(
+            "No Access-Control-Allow-Origin header present for origin '{}'",
+            origin
+        ))),
+    }
+}
+
+/// When the stages of a fetch happened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FetchTiming {
+    /// When the request was started
+    pub fetch_start: Instant,
+    /// When the response headers arrived
+    pub response_start: Instant,
+    /// When the last byte of the body arrived
+    pub response_end: Instant,
 }
 
 /// HTTP response
@@ -479,6 +1109,8 @@ pub struct HttpResponse {
     pub headers: HashMap<String, String>,
     pub body: Vec<u8>,
     pub url: String, // Final URL after redirects
+    /// Timing of the fetch, `None` for responses that were not fetched
+    pub timing: Option<FetchTiming>,
 }
 
 impl HttpResponse {
@@ -533,7 +1165,9 @@ impl XMLHttpRequest {
             onload: None,
             onerror: None,
             ontimeout: None,
+            onabort: None,
             is_aborted: false,
+            abort_token: AbortToken::new(),
         }
     }
     
@@ -543,12 +1177,23 @@ impl XMLHttpRequest {
         }
         
         let http_method = method.parse()?;
+        let origin = self.request.origin.take();
         self.request = HttpRequest::new(http_method, url.to_string());
+        self.request.origin = origin;
+        self.abort_token = AbortToken::new();
+        self.request.set_signal(self.abort_token.clone());
+        self.is_aborted = false;
         self.ready_state = ReadyState::Opened;
         self.trigger_readystatechange();
         Ok(())
     }
     
+    /// Set the origin of the document making the request, which subjects
+    /// it to CORS and mixed content checks
+    pub fn set_origin(&mut self, origin: Origin) {
+        self.request.set_origin(origin);
+    }
+    
     pub fn set_request_header(&mut self, name: &str, value: &str) -> Result<(), NetworkError> {
         if self.ready_state != ReadyState::Opened {
             return Err(NetworkError::ParseError("Request not opened".to_string()));
@@ -572,6 +1217,67 @@ impl XMLHttpRequest {
         Ok(())
     }
     
+    /// Send a `multipart/form-data` body, e.g. from a `FormData` object
+    pub fn send_multipart(&mut self, parts: &[MultipartPart]) -> Result<(), NetworkError> {
+        if self.ready_state != ReadyState::Opened {
+            return Err(NetworkError::ParseError("Request not opened".to_string()));
+        }
+        
+        self.request.set_multipart_body(parts);
+        let body = self.request.body.take();
+        self.send(body)
+    }
+    
+    /// Send the request over the network with the given client
+    /// 
+    /// The request can be cancelled mid-flight from another task through
+    /// the token returned by `abort_handle()`.
+    pub async fn send_with_client(&mut self, client: &HttpClient, body: Option<Vec<u8>>) -> Result<(), NetworkError> {
+        if self.ready_state != ReadyState::Opened {
+            return Err(NetworkError::ParseError("Request not opened".to_string()));
+        }
+        
+        self.request.body = body;
+        self.ready_state = ReadyState::HeadersReceived;
+        self.trigger_readystatechange();
+        
+        match client.send_request(self.request.clone()).await {
+            Ok(response) => {
+                self.ready_state = ReadyState::Loading;
+                self.trigger_readystatechange();
+                
+                self.status = response.status_code;
+                self.status_text = format!("{:?}", response.status);
+                self.response_text = String::from_utf8_lossy(&response.body).to_string();
+                self.response = Some(response);
+                
+                self.ready_state = ReadyState::Done;
+                self.trigger_readystatechange();
+                if let Some(callback) = &self.onload {
+                    callback();
+                }
+                Ok(())
+            }
+            Err(NetworkError::RequestAborted) => {
+                self.handle_abort();
+                Err(NetworkError::RequestAborted)
+            }
+            Err(e) => {
+                self.ready_state = ReadyState::Done;
+                self.trigger_readystatechange();
+                if let Some(callback) = &self.onerror {
+                    callback();
+                }
+                Err(e)
+            }
+        }
+    }
+    
+    /// Get a token that aborts the current request when triggered
+    pub fn abort_handle(&self) -> AbortToken {
+        self.abort_token.clone()
+    }
+    
     fn simulate_request(&mut self) {
         // Simulate network request
         self.ready_state = ReadyState::Loading;
@@ -584,6 +1290,7 @@ impl XMLHttpRequest {
             headers: HashMap::new(),
             body: b"Hello, World!".to_vec(),
             url: self.request.url.clone(),
+            timing: None,
         };
         
         response.headers.insert("content-type".to_string(), "text/plain".to_string());
@@ -603,7 +1310,35 @@ impl XMLHttpRequest {
     }
     
     pub fn abort(&mut self) {
+        self.abort_token.abort();
+        self.handle_abort();
+    }
+    
+    pub fn is_aborted(&self) -> bool {
+        self.is_aborted
+    }
+    
+    /// Run the abort steps: an in-flight request moves to DONE and fires
+    /// `abort`, then the request is reset to UNSENT
+    fn handle_abort(&mut self) {
         self.is_aborted = true;
+        let in_flight = matches!(
+            self.ready_state,
+            ReadyState::HeadersReceived | ReadyState::Loading
+        );
+        self.response = None;
+        self.status = 0;
+        self.status_text.clear();
+        self.response_text.clear();
+        
+        if in_flight {
+            self.ready_state = ReadyState::Done;
+            self.trigger_readystatechange();
+            if let Some(callback) = &self.onabort {
+                callback();
+            }
+        }
+        
         self.ready_state = ReadyState::Unsent;
         self.trigger_readystatechange();
     }
@@ -674,6 +1409,13 @@ impl XMLHttpRequest {
         self.ontimeout = Some(Box::new(callback));
     }
     
+    pub fn set_onabort<F>(&mut self, callback: F)
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.onabort = Some(Box::new(callback));
+    }
+    
     fn trigger_readystatechange(&self) {
         if let Some(callback) = &self.onreadystatechange {
             callback();
@@ -729,9 +1471,31 @@ impl FetchRequest {
         self
     }
     
+    pub fn origin(mut self, origin: Origin) -> Self {
+        self.request.set_origin(origin);
+        self
+    }
+    
+    pub fn signal(mut self, signal: AbortToken) -> Self {
+        self.request.set_signal(signal);
+        self
+    }
+    
     pub async fn send(self) -> Result<FetchResponse, NetworkError> {
         // Simulate async network request
-        tokio::time::sleep(Duration::from_millis(100)).await;
+        let delay = tokio::time::sleep(Duration::from_millis(100));
+        match &self.request.signal {
+            Some(signal) => {
+                if signal.is_aborted() {
+                    return Err(NetworkError::RequestAborted);
+                }
+                tokio::select! {
+                    _ = delay => {}
+                    _ = signal.aborted() => return Err(NetworkError::RequestAborted),
+                }
+            }
+            None => delay.await,
+        }
         
         // Simulate successful response
         let response = HttpResponse {
@@ -740,6 +1504,7 @@ impl FetchRequest {
             headers: HashMap::new(),
             body: b"{\"message\": \"Hello from fetch!\"}".to_vec(),
             url: self.request.url.clone(),
+            timing: None,
         };
         
         Ok(FetchResponse { response })
@@ -845,6 +1610,16 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_document_content_types() {
+        assert!(is_document_type("text/html; charset=utf-8"));
+        assert!(is_document_type("Application/XHTML+XML"));
+        assert!(is_document_type("image/svg+xml"));
+        assert!(is_document_type(""));
+        assert!(!is_document_type("image/png"));
+        assert!(!is_document_type("application/zip"));
+    }
+
     #[test]
     fn test_http_client_creation() {
         let client = HttpClient::new();
@@ -931,6 +1706,7 @@ mod tests {
             headers: HashMap::new(),
             body: b"Hello, World!".to_vec(),
             url: "https://example.com".to_string(),
+            timing: None,
         };
         
         response.headers.insert("content-type".to_string(), "text/plain".to_string());
@@ -940,4 +1716,148 @@ mod tests {
         assert_eq!(response.text().unwrap(), "Hello, World!");
         assert_eq!(response.get_header("content-type"), Some(&"text/plain".to_string()));
     }
+
+    #[test]
+    fn test_cross_origin_detection() {
+        let mut request = HttpRequest::get("https://api.example.com/data".to_string());
+        assert!(!request.is_cross_origin());
+        
+        request.set_origin(Origin::from_url("https://api.example.com/page"));
+        assert!(!request.is_cross_origin());
+        
+        request.set_origin(Origin::from_url("https://example.com/"));
+        assert!(request.is_cross_origin());
+
+        // A backslash ends the authority, so this request reaches
+        // bank.example, not the page's own evil.example
+        let mut request = HttpRequest::get("https://bank.example\\@evil.example/".to_string());
+        request.set_origin(Origin::from_url("https://evil.example/"));
+        assert!(request.is_cross_origin());
+        request.set_origin(Origin::from_url("https://bank.example/"));
+        assert!(!request.is_cross_origin());
+    }
+
+    #[test]
+    fn test_cors_response_check() {
+        let origin = Origin::from_url("https://example.com/");
+        let mut headers = HashMap::new();
+        
+        assert!(matches!(
+            check_cors_response(&origin, false, &headers),
+            Err(NetworkError::CorsError(_))
+        ));
+        
+        headers.insert("access-control-allow-origin".to_string(), "*".to_string());
+        assert!(check_cors_response(&origin, false, &headers).is_ok());
+        assert!(check_cors_response(&origin, true, &headers).is_err());
+        
+        headers.insert("access-control-allow-origin".to_string(), "https://example.com".to_string());
+        assert!(check_cors_response(&origin, true, &headers).is_ok());
+        
+        headers.insert("access-control-allow-origin".to_string(), "https://other.com".to_string());
+        assert!(check_cors_response(&origin, false, &headers).is_err());
+    }
+
+    #[test]
+    fn test_form_encoding() {
+        let entries = vec![
+            ("q".to_string(), "rust lang".to_string()),
+            ("sym".to_string(), "a&b=c".to_string()),
+        ];
+        assert_eq!(encode_form_urlencoded(&entries), "q=rust+lang&sym=a%26b%3Dc");
+        
+        let url = form_submission_url("https://example.com/search?old=1", &entries).unwrap();
+        assert_eq!(url, "https://example.com/search?q=rust+lang&sym=a%26b%3Dc");
+        
+        let request = HttpRequest::form_post("https://example.com/login".to_string(), &entries);
+        assert_eq!(request.method, HttpMethod::POST);
+        assert_eq!(request.body.as_deref(), Some("q=rust+lang&sym=a%26b%3Dc".as_bytes()));
+        assert_eq!(
+            request.headers.get("Content-Type").map(String::as_str),
+            Some("application/x-www-form-urlencoded")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_abort_token_cancels_requests() {
+        // Aborting mid-flight cancels the pending request
+        let token = AbortToken::new();
+        let trigger = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            trigger.abort();
+        });
+        let result = FetchRequest::new("https://example.com")
+            .signal(token.clone())
+            .send()
+            .await;
+        assert!(matches!(result, Err(NetworkError::RequestAborted)));
+        assert!(token.is_aborted());
+
+        // An already-aborted signal never reaches the network
+        let client = HttpClient::new();
+        let mut request = HttpRequest::get("https://example.com".to_string());
+        request.set_signal(token);
+        let result = client.send_request(request).await;
+        assert!(matches!(result, Err(NetworkError::RequestAborted)));
+    }
+
+    #[tokio::test]
+    async fn test_xmlhttprequest_abort() {
+        let aborted = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let flag = aborted.clone();
+
+        let mut xhr = XMLHttpRequest::new();
+        xhr.set_onabort(move || flag.store(true, std::sync::atomic::Ordering::SeqCst));
+        xhr.open("GET", "https://example.com").unwrap();
+
+        // Abort through the handle, as another task would while the send is pending
+        xhr.abort_handle().abort();
+
+        let result = xhr.send_with_client(&HttpClient::new(), None).await;
+        assert!(matches!(result, Err(NetworkError::RequestAborted)));
+        assert!(xhr.is_aborted());
+        assert!(aborted.load(std::sync::atomic::Ordering::SeqCst));
+        assert_eq!(xhr.get_ready_state(), ReadyState::Unsent);
+        assert_eq!(xhr.get_status(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_streaming_request_observes_abort() {
+        let client = HttpClient::new();
+        let token = AbortToken::new();
+        token.abort();
+
+        let mut request = HttpRequest::get("https://example.com".to_string());
+        request.set_signal(token);
+        let result = client.send_request_streaming(request).await;
+        assert!(matches!(result, Err(NetworkError::RequestAborted)));
+    }
+
+    #[test]
+    fn test_multipart_encoding() {
+        let parts = vec![
+            MultipartPart::Text { name: "title".to_string(), value: "Hello".to_string() },
+            MultipartPart::File {
+                name: "upload".to_string(),
+                filename: "a\"b.txt".to_string(),
+                content_type: String::new(),
+                data: b"file body".to_vec(),
+            },
+        ];
+        let body = String::from_utf8(encode_multipart_form_data(&parts, "XYZ")).unwrap();
+        assert_eq!(
+            body,
+            "--XYZ\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nHello\r\n\
+             --XYZ\r\nContent-Disposition: form-data; name=\"upload\"; filename=\"a%22b.txt\"\r\n\
+             Content-Type: application/octet-stream\r\n\r\nfile body\r\n--XYZ--\r\n"
+        );
+
+        let request = HttpRequest::multipart_post("https://example.com/upload".to_string(), &parts);
+        let content_type = request.headers.get("Content-Type").unwrap();
+        let boundary = content_type.strip_prefix("multipart/form-data; boundary=").unwrap();
+        let body = String::from_utf8(request.body.unwrap()).unwrap();
+        assert!(body.starts_with(&format!("--{}\r\n", boundary)));
+        assert!(body.ends_with(&format!("--{}--\r\n", boundary)));
+    }
 }
@@ -0,0 +1,142 @@
+//! # Mixed Content
+//!
+//! This module decides what happens to `http://` requests made by a
+//! document served over HTTPS. Such requests would let a network attacker
+//! read or change part of an otherwise secure page.
+//!
+//! ## Design Principles
+//!
+//! 1. **Block Active Content**: Scripts, stylesheets and script-initiated
+//!    requests can change the whole page, so insecure ones are blocked.
+//! 2. **Upgrade Passive Content**: Images and media can only change what
+//!    they display; they are upgraded to HTTPS by default, or loaded as is
+//!    when upgrading is turned off.
+//! 3. **Trust Local Hosts**: Loopback addresses and `localhost` never
+//!    cross the network, so requests to them are not mixed content.
+
+use std::net::IpAddr;
+use dom::Origin;
+use url::{Host, Url};
+
+/// What a request is for, mirroring the Fetch specification's destinations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RequestDestination {
+    /// A document being navigated to
+    Document,
+    Script,
+    Style,
+    Image,
+    /// Audio and video
+    Media,
+    /// `fetch()`, XHR and anything else without a destination
+    #[default]
+    Empty,
+}
+
+impl RequestDestination {
+    /// Whether insecure loads of this kind are passive mixed content
+    pub fn is_passive(&self) -> bool {
+        matches!(self, RequestDestination::Image | RequestDestination::Media)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RequestDestination::Document => "document",
+            RequestDestination::Script => "script",
+            RequestDestination::Style => "stylesheet",
+            RequestDestination::Image => "image",
+            RequestDestination::Media => "media",
+            RequestDestination::Empty => "resource",
+        }
+    }
+}
+
+/// The outcome of the mixed content check for a request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MixedContentDecision {
+    /// The request is not mixed content, or is passive and not upgraded
+    Allowed,
+    /// The request is passive mixed content and goes to this HTTPS URL
+    Upgraded(Url),
+    /// The request is active mixed content and must not be sent
+    Blocked,
+}
+
+/// Check a request made by a document of `origin`
+pub fn check_mixed_content(
+    origin: &Origin,
+    url: &Url,
+    destination: RequestDestination,
+    upgrade_passive: bool,
+) -> MixedContentDecision {
+    let secure_document = matches!(origin, Origin::Tuple { scheme, .. } if scheme == "https" || scheme == "wss");
+    if !secure_document || destination == RequestDestination::Document || !is_insecure(url) {
+        return MixedContentDecision::Allowed;
+    }
+    if !destination.is_passive() {
+        return MixedContentDecision::Blocked;
+    }
+    if !upgrade_passive {
+        return MixedContentDecision::Allowed;
+    }
+    let mut upgraded = url.clone();
+    let port = url.port();
+    let scheme = if url.scheme() == "ws" { "wss" } else { "https" };
+    if upgraded.set_scheme(scheme).is_err() {
+        return MixedContentDecision::Blocked;
+    }
+    if port == Some(80) {
+        let _ = upgraded.set_port(None);
+    }
+    MixedContentDecision::Upgraded(upgraded)
+}
+
+/// Check whether a URL would be fetched without transport security
+fn is_insecure(url: &Url) -> bool {
+    if !matches!(url.scheme(), "http" | "ws") {
+        return false;
+    }
+    match url.host() {
+        Some(Host::Domain(domain)) => {
+            let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+            domain != "localhost" && !domain.ends_with(".localhost")
+        }
+        Some(Host::Ipv4(address)) => !IpAddr::V4(address).is_loopback(),
+        Some(Host::Ipv6(address)) => !IpAddr::V6(address).is_loopback(),
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mixed_content_decisions() {
+        let secure = Origin::from_url("https://shop.example/");
+        let insecure = Origin::from_url("http://shop.example/");
+        let url = |text: &str| Url::parse(text).unwrap();
+
+        let script = url("http://cdn.example/app.js");
+        assert_eq!(check_mixed_content(&secure, &script, RequestDestination::Script, true), MixedContentDecision::Blocked);
+        assert_eq!(check_mixed_content(&secure, &script, RequestDestination::Empty, true), MixedContentDecision::Blocked);
+        assert_eq!(check_mixed_content(&insecure, &script, RequestDestination::Script, true), MixedContentDecision::Allowed);
+        assert_eq!(
+            check_mixed_content(&secure, &url("https://cdn.example/app.js"), RequestDestination::Style, true),
+            MixedContentDecision::Allowed
+        );
+
+        let image = url("http://img.example:80/logo.png");
+        assert_eq!(
+            check_mixed_content(&secure, &image, RequestDestination::Image, true),
+            MixedContentDecision::Upgraded(url("https://img.example/logo.png"))
+        );
+        assert_eq!(check_mixed_content(&secure, &image, RequestDestination::Image, false), MixedContentDecision::Allowed);
+
+        // Local development servers and navigations are never mixed content
+        for local in ["http://localhost:8080/api", "http://127.0.0.1/api", "http://[::1]/api"] {
+            assert_eq!(check_mixed_content(&secure, &url(local), RequestDestination::Empty, true), MixedContentDecision::Allowed);
+        }
+        assert_eq!(check_mixed_content(&secure, &script, RequestDestination::Document, true), MixedContentDecision::Allowed);
+    }
+}
@@ -0,0 +1,796 @@
+//! # Display Lists
+//!
+//! This module flattens a layout tree into the list of drawing commands
+//! needed to paint it. A display list holds no DOM or style references, so
+//! it can be sent to another process and painted there.
+//!
+//! ## Design Principles
+//!
+//! 1. **Paint Order**: Items are listed in the order they are painted, a
+//!    box's background before its children and text.
+//! 2. **Absolute Coordinates**: Every item is positioned in page
+//!    coordinates, so painting needs no knowledge of the tree.
+//! 3. **Plain Data**: Items only hold numbers and strings and serialize
+//!    with serde.
+//! 4. **Surfaces Are Content**: Iframes, canvases and video frames are
+//!    items that reference an external texture. They sit in paint order
+//!    and go through the same clipping and transforms as every other
+//!    item, so a backend only has to bind their texture.
+//! 5. **Marks Follow Their Text**: Spelling marks are painted right after
+//!    the run they underline, so they stack with it.
+
+use layout::{Dimensions, LayoutBox, Visibility};
+use layout::background::background_geometry;
+use layout::replaced::{fit_replaced_content, is_replaced_element, natural_size};
+use dom::spellcheck::{misspellings, Spellchecker};
+use dom::{media, Node, NodeType};
+use serde::{Deserialize, Serialize};
+
+/// A drawing command
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DisplayItem {
+    /// Fill a rectangle, e.g. a background
+    Rect {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        color: String,
+    },
+    /// Fill a rectangle with rounded corners, e.g. the background of a box
+    /// with `border-radius`
+    RoundedRect {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        radius: f32,
+        color: String,
+    },
+    /// Tile a background image over a rectangle with rounded corners
+    ///
+    /// Tiles start at the tile origin and repeat in every direction. The
+    /// origin of a fixed background is relative to the viewport, so
+    /// painters add the scroll offset to it; see `tile_origin`.
+    BackgroundImage {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        radius: f32,
+        src: String,
+        origin_x: f32,
+        origin_y: f32,
+        fixed: bool,
+    },
+    /// Draw a run of text
+    Text {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        text: String,
+        color: String,
+        font_size: f32,
+    },
+    /// Draw a wavy underline, such as the mark under a misspelled word
+    Squiggle {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        color: String,
+    },
+    /// Draw part of an image, or a video's poster, scaled onto a
+    /// rectangle of the page
+    ///
+    /// The source rectangle is in the content's natural pixels and has
+    /// already been cropped by `object-fit` and `object-position`.
+    Replaced {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        /// `img` or `video`
+        element: String,
+        /// The `src` attribute, if any; for a video, its `poster`, as
+        /// frames are a surface of their own
+        src: Option<String>,
+        source_x: f32,
+        source_y: f32,
+        source_width: f32,
+        source_height: f32,
+    },
+    /// Draw part of an external texture, scaled onto a rectangle of the
+    /// page
+    ///
+    /// The texture is produced outside the display list and found by
+    /// `surface_id`, the id of the element it belongs to. As with
+    /// `Replaced`, the source rectangle is in the surface's natural pixels.
+    Surface {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        kind: SurfaceKind,
+        surface_id: u64,
+        source_x: f32,
+        source_y: f32,
+        source_width: f32,
+        source_height: f32,
+    },
+}
+
+/// Where the texture of a surface comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SurfaceKind {
+    /// The compositor output of a nested document
+    Iframe,
+    /// A canvas's backing surface
+    Canvas,
+    /// The current frame of a video
+    Video,
+}
+
+impl SurfaceKind {
+    /// Get the kind of surface an element paints, if any
+    pub fn of(node: &Node) -> Option<Self> {
+        match node.tag_name()?.to_ascii_lowercase().as_str() {
+            "iframe" => Some(SurfaceKind::Iframe),
+            "canvas" => Some(SurfaceKind::Canvas),
+            "video" => Some(SurfaceKind::Video),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SurfaceKind::Iframe => "iframe",
+            SurfaceKind::Canvas => "canvas",
+            SurfaceKind::Video => "video",
+        }
+    }
+}
+
+/// Thickness of the wavy underline under misspelled words
+pub const SPELLING_MARK_HEIGHT: f32 = 3.0;
+
+/// Color of the wavy underline under misspelled words
+pub const SPELLING_MARK_COLOR: &str = "red";
+
+/// A scale followed by a translation, applied to item geometry
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub scale_x: f32,
+    pub scale_y: f32,
+    pub translate_x: f32,
+    pub translate_y: f32,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform { scale_x: 1.0, scale_y: 1.0, translate_x: 0.0, translate_y: 0.0 }
+    }
+}
+
+impl Transform {
+    pub fn translate(x: f32, y: f32) -> Self {
+        Transform { translate_x: x, translate_y: y, ..Default::default() }
+    }
+
+    pub fn scale(x: f32, y: f32) -> Self {
+        Transform { scale_x: x, scale_y: y, ..Default::default() }
+    }
+
+    /// Map a rectangle
+    pub fn apply(&self, rect: &Dimensions) -> Dimensions {
+        Dimensions::new(
+            rect.x * self.scale_x + self.translate_x,
+            rect.y * self.scale_y + self.translate_y,
+            rect.width * self.scale_x,
+            rect.height * self.scale_y,
+        )
+    }
+}
+
+impl DisplayItem {
+    /// Get the rectangle of the page the item paints
+    pub fn bounds(&self) -> Dimensions {
+        match self {
+            DisplayItem::Rect { x, y, width, height, .. }
+            | DisplayItem::RoundedRect { x, y, width, height, .. }
+            | DisplayItem::BackgroundImage { x, y, width, height, .. }
+            | DisplayItem::Text { x, y, width, height, .. }
+            | DisplayItem::Squiggle { x, y, width, height, .. }
+            | DisplayItem::Replaced { x, y, width, height, .. }
+            | DisplayItem::Surface { x, y, width, height, .. } => Dimensions::new(*x, *y, *width, *height),
+        }
+    }
+
+    fn set_bounds(&mut self, bounds: &Dimensions) {
+        match self {
+            DisplayItem::Rect { x, y, width, height, .. }
+            | DisplayItem::RoundedRect { x, y, width, height, .. }
+            | DisplayItem::BackgroundImage { x, y, width, height, .. }
+            | DisplayItem::Text { x, y, width, height, .. }
+            | DisplayItem::Squiggle { x, y, width, height, .. }
+            | DisplayItem::Replaced { x, y, width, height, .. }
+            | DisplayItem::Surface { x, y, width, height, .. } => {
+                (*x, *y, *width, *height) = (bounds.x, bounds.y, bounds.width, bounds.height);
+            }
+        }
+    }
+
+    /// Get the rectangle of the content drawn, for images and surfaces
+    pub fn source(&self) -> Option<Dimensions> {
+        match self {
+            DisplayItem::Replaced { source_x, source_y, source_width, source_height, .. }
+            | DisplayItem::Surface { source_x, source_y, source_width, source_height, .. } => {
+                Some(Dimensions::new(*source_x, *source_y, *source_width, *source_height))
+            }
+            DisplayItem::Rect { .. }
+            | DisplayItem::RoundedRect { .. }
+            | DisplayItem::BackgroundImage { .. }
+            | DisplayItem::Text { .. }
+            | DisplayItem::Squiggle { .. } => None,
+        }
+    }
+
+    fn set_source(&mut self, source: &Dimensions) {
+        match self {
+            DisplayItem::Replaced { source_x, source_y, source_width, source_height, .. }
+            | DisplayItem::Surface { source_x, source_y, source_width, source_height, .. } => {
+                (*source_x, *source_y, *source_width, *source_height) = (source.x, source.y, source.width, source.height);
+            }
+            DisplayItem::Rect { .. }
+            | DisplayItem::RoundedRect { .. }
+            | DisplayItem::BackgroundImage { .. }
+            | DisplayItem::Text { .. }
+            | DisplayItem::Squiggle { .. } => {}
+        }
+    }
+    
+    /// Get where the tiles of a background image start when the page is
+    /// scrolled by `scroll_offset`
+    pub fn tile_origin(&self, scroll_offset: (f32, f32)) -> Option<(f32, f32)> {
+        match self {
+            DisplayItem::BackgroundImage { origin_x, origin_y, fixed: true, .. } => {
+                Some((origin_x + scroll_offset.0, origin_y + scroll_offset.1))
+            }
+            DisplayItem::BackgroundImage { origin_x, origin_y, .. } => Some((*origin_x, *origin_y)),
+            _ => None,
+        }
+    }
+
+    /// Map the item through a transform
+    ///
+    /// Text keeps its run and scales its font with the vertical scale.
+    /// Corner radii scale with the horizontal scale, and tile origins move
+    /// with the item.
+    pub fn transformed(&self, transform: &Transform) -> DisplayItem {
+        let mut item = self.clone();
+        item.set_bounds(&transform.apply(&self.bounds()));
+        match &mut item {
+            DisplayItem::Text { font_size, .. } => *font_size *= transform.scale_y,
+            DisplayItem::RoundedRect { radius, .. } => *radius *= transform.scale_x,
+            DisplayItem::BackgroundImage { radius, origin_x, origin_y, .. } => {
+                *radius *= transform.scale_x;
+                *origin_x = *origin_x * transform.scale_x + transform.translate_x;
+                *origin_y = *origin_y * transform.scale_y + transform.translate_y;
+            }
+            _ => {}
+        }
+        item
+    }
+
+    /// Clip the item to a rectangle
+    ///
+    /// Images and surfaces crop their source rectangle along with their
+    /// bounds. Text runs cannot be split, so a run is kept whole if any
+    /// of it is visible. Returns `None` for an item entirely outside.
+    pub fn clipped(&self, clip: &Dimensions) -> Option<DisplayItem> {
+        let bounds = self.bounds();
+        let left = bounds.x.max(clip.x);
+        let top = bounds.y.max(clip.y);
+        let right = bounds.right().min(clip.right());
+        let bottom = bounds.bottom().min(clip.bottom());
+        if right <= left || bottom <= top {
+            return None;
+        }
+        let mut item = self.clone();
+        if matches!(item, DisplayItem::Text { .. }) {
+            return Some(item);
+        }
+        if let Some(source) = self.source() {
+            let scale_x = source.width / bounds.width;
+            let scale_y = source.height / bounds.height;
+            item.set_source(&Dimensions::new(
+                source.x + (left - bounds.x) * scale_x,
+                source.y + (top - bounds.y) * scale_y,
+                (right - left) * scale_x,
+                (bottom - top) * scale_y,
+            ));
+        }
+        item.set_bounds(&Dimensions::new(left, top, right - left, bottom - top));
+        Some(item)
+    }
+}
+
+/// What the items of a box depend on besides the box
+struct Painter<'a> {
+    /// Descendants left out, such as nested stacking contexts
+    is_boundary: &'a dyn Fn(&LayoutBox) -> bool,
+    /// Marks misspelled words of editable text, if set
+    spellchecker: Option<&'a dyn Spellchecker>,
+}
+
+/// The drawing commands of a page, in paint order
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct DisplayList {
+    pub items: Vec<DisplayItem>,
+}
+
+impl DisplayList {
+    /// Build the display list of a layout tree
+    pub fn from_layout(root: &LayoutBox) -> Self {
+        let mut list = DisplayList::default();
+        list.push_box(root, "black", 16.0, &Painter { is_boundary: &|_| false, spellchecker: None });
+        list
+    }
+
+    /// Build the display list of a layout tree, marking the misspelled
+    /// words of editable text
+    pub fn from_layout_with_spellchecker(root: &LayoutBox, spellchecker: &dyn Spellchecker) -> Self {
+        let mut list = DisplayList::default();
+        list.push_box(root, "black", 16.0, &Painter { is_boundary: &|_| false, spellchecker: Some(spellchecker) });
+        list
+    }
+
+    /// Build the display list of a subtree, leaving out the descendants
+    /// for which `is_boundary` holds, such as nested stacking contexts
+    /// that are painted on their own
+    pub fn from_subtree(root: &LayoutBox, is_boundary: &dyn Fn(&LayoutBox) -> bool) -> Self {
+        let mut list = DisplayList::default();
+        list.push_box(root, "black", 16.0, &Painter { is_boundary, spellchecker: None });
+        list
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Get the text of the page, one run per item
+    pub fn text_runs(&self) -> Vec<&str> {
+        self.items
+            .iter()
+            .filter_map(|item| match item {
+                DisplayItem::Text { text, .. } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Get the surfaces the list references, in paint order
+    pub fn surfaces(&self) -> Vec<(SurfaceKind, u64)> {
+        self.items
+            .iter()
+            .filter_map(|item| match item {
+                DisplayItem::Surface { kind, surface_id, .. } => Some((*kind, *surface_id)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Map every item through a transform
+    pub fn transformed(&self, transform: &Transform) -> DisplayList {
+        DisplayList { items: self.items.iter().map(|item| item.transformed(transform)).collect() }
+    }
+
+    /// Clip every item to a rectangle, dropping the ones outside it
+    pub fn clipped(&self, clip: &Dimensions) -> DisplayList {
+        DisplayList { items: self.items.iter().filter_map(|item| item.clipped(clip)).collect() }
+    }
+
+    /// Replace a surface with the display list that produces it
+    ///
+    /// `content` is in the surface's natural pixels, such as a nested
+    /// document's page coordinates. It is scaled and moved onto the
+    /// surface, clipped to it, and inserted where the surface was, so it
+    /// stacks exactly as the surface did. Returns whether the surface was
+    /// found.
+    pub fn embed_surface(&mut self, surface_id: u64, content: &DisplayList) -> bool {
+        let index = self.items.iter().position(|item| matches!(item, DisplayItem::Surface { surface_id: id, .. } if *id == surface_id));
+        let Some(index) = index else {
+            return false;
+        };
+        let bounds = self.items[index].bounds();
+        let Some(source) = self.items[index].source().filter(|source| source.width > 0.0 && source.height > 0.0) else {
+            self.items.remove(index);
+            return true;
+        };
+        let scale_x = bounds.width / source.width;
+        let scale_y = bounds.height / source.height;
+        let transform = Transform {
+            scale_x,
+            scale_y,
+            translate_x: bounds.x - source.x * scale_x,
+            translate_y: bounds.y - source.y * scale_y,
+        };
+        let embedded = content.transformed(&transform).clipped(&bounds);
+        self.items.splice(index..=index, embedded.items);
+        true
+    }
+
+    /// Underline the misspelled words of the text item just added
+    ///
+    /// Runs are not shaped here, so a word's position is interpolated from
+    /// its character offsets across the run.
+    fn push_spelling_marks(&mut self, spellchecker: &dyn Spellchecker) {
+        let Some(DisplayItem::Text { x, y, width, height, text, .. }) = self.items.last() else {
+            return;
+        };
+        let (x, y, width, height) = (*x, *y, *width, *height);
+        let length = text.chars().count().max(1) as f32;
+        let marks: Vec<DisplayItem> = misspellings(text, spellchecker)
+            .into_iter()
+            .map(|misspelling| DisplayItem::Squiggle {
+                x: x + width * misspelling.start as f32 / length,
+                y: y + height - SPELLING_MARK_HEIGHT,
+                width: width * (misspelling.end - misspelling.start) as f32 / length,
+                height: SPELLING_MARK_HEIGHT,
+                color: SPELLING_MARK_COLOR.to_string(),
+            })
+            .collect();
+        self.items.extend(marks);
+    }
+
+    /// Add the items of a box
+    ///
+    /// Text boxes take `color` and `font_size` from their parent element,
+    /// as layout does not style text nodes.
+    fn push_box(&mut self, layout_box: &LayoutBox, color: &str, font_size: f32, painter: &Painter) {
+        let styles = &layout_box.styles;
+        // Invisible boxes paint nothing, but their descendants can be visible
+        if styles.visibility.unwrap_or_default() != Visibility::Visible {
+            let color = styles.color.clone().unwrap_or_else(|| color.to_string());
+            for child in layout_box.children.iter().filter(|child| !(painter.is_boundary)(child)) {
+                self.push_box(child, &color, styles.font.size.unwrap_or(font_size), painter);
+            }
+            return;
+        }
+        let background = background_geometry(layout_box);
+        let area = &background.painting_area;
+        if let Some(color) = styles.background_color.as_ref().filter(|color| *color != "transparent") {
+            self.items.push(match background.radius {
+                radius if radius > 0.0 => DisplayItem::RoundedRect {
+                    x: area.x,
+                    y: area.y,
+                    width: area.width,
+                    height: area.height,
+                    radius,
+                    color: color.clone(),
+                },
+                _ => DisplayItem::Rect {
+                    x: area.x,
+                    y: area.y,
+                    width: area.width,
+                    height: area.height,
+                    color: color.clone(),
+                },
+            });
+        }
+        if let Some(src) = &styles.background_image {
+            self.items.push(DisplayItem::BackgroundImage {
+                x: area.x,
+                y: area.y,
+                width: area.width,
+                height: area.height,
+                radius: background.radius,
+                src: src.clone(),
+                origin_x: background.tile_origin.0,
+                origin_y: background.tile_origin.1,
+                fixed: background.fixed,
+            });
+        }
+
+        let content = &layout_box.content;
+        if is_replaced_element(&layout_box.node) {
+            let fitted = natural_size(&layout_box.node)
+                .and_then(|natural| fit_replaced_content(styles.object_fit, &styles.object_position, natural, content));
+            if let Some(rects) = fitted {
+                let surface = SurfaceKind::of(&layout_box.node);
+                // A video's poster shows until its first frame arrives
+                if matches!(surface, None | Some(SurfaceKind::Video)) {
+                    self.items.push(DisplayItem::Replaced {
+                        x: rects.dest.x,
+                        y: rects.dest.y,
+                        width: rects.dest.width,
+                        height: rects.dest.height,
+                        element: layout_box.node.tag_name().unwrap_or_default().to_ascii_lowercase(),
+                        src: match layout_box.node.media_kind() {
+                            Some(_) => media::poster(&layout_box.node),
+                            None => layout_box.node.get_attribute("src"),
+                        },
+                        source_x: rects.source.x,
+                        source_y: rects.source.y,
+                        source_width: rects.source.width,
+                        source_height: rects.source.height,
+                    });
+                }
+                if let Some(kind) = surface {
+                    self.items.push(DisplayItem::Surface {
+                        x: rects.dest.x,
+                        y: rects.dest.y,
+                        width: rects.dest.width,
+                        height: rects.dest.height,
+                        kind,
+                        surface_id: layout_box.node.id,
+                        source_x: rects.source.x,
+                        source_y: rects.source.y,
+                        source_width: rects.source.width,
+                        source_height: rects.source.height,
+                    });
+                }
+            }
+        }
+
+        let is_text = matches!(layout_box.node.node_type, NodeType::Text(_));
+        let (color, font_size) = if is_text {
+            (color.to_string(), font_size)
+        } else {
+            (styles.color.clone().unwrap_or_else(|| color.to_string()), styles.font.size.unwrap_or(font_size))
+        };
+        let spellchecker = painter.spellchecker.filter(|_| is_text && layout_box.node.spellcheck_enabled());
+        if !layout_box.fragments.is_empty() {
+            for fragment in &layout_box.fragments {
+                self.items.push(DisplayItem::Text {
+                    x: content.x + fragment.x,
+                    y: content.y + fragment.y,
+                    width: fragment.width,
+                    height: fragment.height,
+                    text: fragment.text.clone(),
+                    // Generated content is styled by its pseudo-element
+                    color: match fragment.pseudo {
+                        Some(_) => fragment.styles.color.clone().unwrap_or_else(|| color.clone()),
+                        None => color.clone(),
+                    },
+                    font_size,
+                });
+                if let (Some(spellchecker), None) = (spellchecker, fragment.pseudo) {
+                    self.push_spelling_marks(spellchecker);
+                }
+            }
+        } else if let Some(text) = layout_box.node.character_data() {
+            let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            if !text.is_empty() {
+                self.items.push(DisplayItem::Text {
+                    x: content.x,
+                    y: content.y,
+                    width: content.width,
+                    height: content.height,
+                    text,
+                    color: color.clone(),
+                    font_size,
+                });
+                if let Some(spellchecker) = spellchecker {
+                    self.push_spelling_marks(spellchecker);
+                }
+            }
+        }
+
+        for child in layout_box.children.iter().filter(|child| !(painter.is_boundary)(child)) {
+            self.push_box(child, &color, font_size, painter);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use css_parser::CSSParser;
+    use dom::Document;
+    use layout::LayoutEngine;
+
+    #[test]
+    fn test_display_list_from_layout() {
+        let doc = Document::new();
+        let body = doc.create_element("body");
+        let heading = doc.create_element("h1");
+        heading.append_child(&doc.create_text_node("Hello"));
+        body.append_child(&heading);
+        doc.root.append_child(&body);
+
+        let stylesheet = CSSParser::new("h1 { background-color: #eeeeee; color: navy }".to_string()).parse_stylesheet().unwrap();
+        let engine = LayoutEngine::new(stylesheet);
+        let layout = engine.layout_document(&doc);
+        let list = DisplayList::from_layout(&layout);
+
+        assert_eq!(list.text_runs(), vec!["Hello"]);
+        // The background is painted before the text on top of it
+        let rect = list.items.iter().position(|item| matches!(item, DisplayItem::Rect { color, .. } if color == "#eeeeee")).unwrap();
+        let text = list.items.iter().position(|item| matches!(item, DisplayItem::Text { color, .. } if color == "navy")).unwrap();
+        assert!(rect < text);
+
+        let json = serde_json::to_string(&list).unwrap();
+        assert_eq!(serde_json::from_str::<DisplayList>(&json).unwrap(), list);
+    }
+
+    #[test]
+    fn test_backgrounds_follow_clip_origin_and_attachment() {
+        let doc = Document::new();
+        let body = doc.create_element("body");
+        let card = doc.create_element("section");
+        card.append_child(&doc.create_text_node("Card"));
+        body.append_child(&card);
+        doc.root.append_child(&body);
+
+        let css = "section { border: 2px; padding: 5px; border-radius: 10px; background: url(tile.png) fixed content-box padding-box #eeeeee }";
+        let stylesheet = CSSParser::new(css.to_string()).parse_stylesheet().unwrap();
+        let engine = LayoutEngine::new(stylesheet);
+        let layout = engine.layout_document(&doc);
+        let list = DisplayList::from_layout(&layout);
+
+        // Clipped to the padding box, with the corners inset by the border
+        let (color_bounds, color_radius) = list
+            .items
+            .iter()
+            .find_map(|item| match item {
+                DisplayItem::RoundedRect { radius, color, .. } if color == "#eeeeee" => Some((item.bounds(), *radius)),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(color_radius, 8.0);
+        let image = list.items.iter().find(|item| matches!(item, DisplayItem::BackgroundImage { .. })).unwrap();
+        assert_eq!(image.bounds(), color_bounds);
+        match image {
+            DisplayItem::BackgroundImage { src, radius, fixed, .. } => {
+                assert_eq!(src, "tile.png");
+                assert_eq!(*radius, 8.0);
+                assert!(*fixed);
+            }
+            _ => unreachable!(),
+        }
+        // Fixed tiles stay with the viewport as the page scrolls
+        assert_eq!(image.tile_origin((0.0, 120.0)), Some((0.0, 120.0)));
+    }
+
+    #[test]
+    fn test_misspelled_editable_text_is_underlined() {
+        let doc = Document::new();
+        let editor = doc.create_element("div");
+        editor.set_attribute("contenteditable", "true");
+        editor.append_child(&doc.create_text_node("good wrod"));
+        let static_text = doc.create_element("p");
+        static_text.append_child(&doc.create_text_node("wrod"));
+        doc.root.append_child(&editor);
+        doc.root.append_child(&static_text);
+
+        let engine = LayoutEngine::new(CSSParser::new(String::new()).parse_stylesheet().unwrap());
+        let layout = engine.layout_document(&doc);
+        let checker = dom::spellcheck::DictionarySpellchecker::new();
+        let list = DisplayList::from_layout_with_spellchecker(&layout, &checker);
+
+        let marks: Vec<(usize, Dimensions)> = list
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| matches!(item, DisplayItem::Squiggle { .. }))
+            .map(|(index, item)| (index, item.bounds()))
+            .collect();
+        assert_eq!(marks.len(), 1);
+        // The mark follows its run and covers the second word
+        let (index, mark) = marks[0];
+        let run = list.items[index - 1].bounds();
+        assert!(matches!(&list.items[index - 1], DisplayItem::Text { text, .. } if text == "good wrod"));
+        assert!(mark.x > run.x && (mark.right() - run.right()).abs() < 0.01);
+        assert_eq!(mark.bottom(), run.bottom());
+
+        assert!(!DisplayList::from_layout(&layout).items.iter().any(|item| matches!(item, DisplayItem::Squiggle { .. })));
+    }
+
+    #[test]
+    fn test_replaced_content_is_fitted() {
+        let doc = Document::new();
+        let body = doc.create_element("body");
+        let img = doc.create_element("img");
+        img.set_attribute("src", "photo.png");
+        img.set_attribute("width", "100");
+        img.set_attribute("height", "100");
+        body.append_child(&img);
+        doc.root.append_child(&body);
+
+        let css = "img { width: 200px; height: 100px; object-fit: cover; object-position: center top }";
+        let stylesheet = CSSParser::new(css.to_string()).parse_stylesheet().unwrap();
+        let engine = LayoutEngine::new(stylesheet);
+        let layout = engine.layout_document(&doc);
+        let list = DisplayList::from_layout(&layout);
+
+        let image = list.items.iter().find_map(|item| match item {
+            DisplayItem::Replaced { width, height, src, source_y, source_height, .. } => Some((*width, *height, src.clone(), *source_y, *source_height)),
+            _ => None,
+        });
+        // Scaled to cover 200x100 and cropped to the top half
+        assert_eq!(image, Some((200.0, 100.0, Some("photo.png".to_string()), 0.0, 50.0)));
+    }
+
+    #[test]
+    fn test_video_poster_is_drawn_at_its_attribute_size() {
+        let doc = Document::new();
+        let body = doc.create_element("body");
+        let video = doc.create_element("video");
+        video.set_attribute("src", "clip.webm");
+        video.set_attribute("poster", "poster.jpg");
+        video.set_attribute("width", "320");
+        video.set_attribute("height", "180");
+        body.append_child(&video);
+        // Audio without controls is not rendered
+        body.append_child(&doc.create_element("audio"));
+        doc.root.append_child(&body);
+
+        let stylesheet = CSSParser::new(String::new()).parse_stylesheet().unwrap();
+        let engine = LayoutEngine::new(stylesheet);
+        let layout = engine.layout_document(&doc);
+        let list = DisplayList::from_layout(&layout);
+
+        let replaced: Vec<_> = list.items.iter().filter_map(|item| match item {
+            DisplayItem::Replaced { width, height, element, src, .. } => Some((*width, *height, element.clone(), src.clone())),
+            _ => None,
+        }).collect();
+        assert_eq!(replaced, vec![(320.0, 180.0, "video".to_string(), Some("poster.jpg".to_string()))]);
+    }
+
+    #[test]
+    fn test_surfaces_are_clipped_and_stacked_like_content() {
+        let doc = Document::new();
+        let body = doc.create_element("body");
+        let frame = doc.create_element("iframe");
+        frame.set_attribute("width", "200");
+        frame.set_attribute("height", "100");
+        body.append_child(&frame);
+        let canvas = doc.create_element("canvas");
+        body.append_child(&canvas);
+        let caption = doc.create_element("p");
+        caption.append_child(&doc.create_text_node("Caption"));
+        body.append_child(&caption);
+        doc.root.append_child(&body);
+
+        let css = "iframe, canvas { display: block } iframe { width: 200px; height: 100px } canvas { width: 300px; height: 150px }";
+        let stylesheet = CSSParser::new(css.to_string()).parse_stylesheet().unwrap();
+        let engine = LayoutEngine::new(stylesheet);
+        let layout = engine.layout_document(&doc);
+        let mut list = DisplayList::from_layout(&layout);
+
+        // Surfaces sit in paint order with the rest of the content
+        assert_eq!(list.surfaces(), vec![(SurfaceKind::Iframe, frame.id), (SurfaceKind::Canvas, canvas.id)]);
+        let canvas_index = list.items.iter().position(|item| matches!(item, DisplayItem::Surface { kind: SurfaceKind::Canvas, .. })).unwrap();
+        let caption_index = list.items.iter().position(|item| matches!(item, DisplayItem::Text { .. })).unwrap();
+        assert!(canvas_index < caption_index);
+
+        // Clipping crops a surface's source like an image's
+        let surface = list.items[canvas_index].clone();
+        let bounds = surface.bounds();
+        let clip = Dimensions::new(bounds.x, bounds.y, 150.0, 75.0);
+        let clipped = surface.clipped(&clip).unwrap();
+        assert_eq!(clipped.source(), Some(Dimensions::new(0.0, 0.0, 150.0, 75.0)));
+        let scaled = clipped.transformed(&Transform::scale(2.0, 2.0));
+        assert_eq!(scaled.bounds().width, 300.0);
+
+        // An iframe's own display list replaces its surface, clipped to it
+        let nested = DisplayList {
+            items: vec![
+                DisplayItem::Rect { x: 0.0, y: 0.0, width: 400.0, height: 50.0, color: "red".to_string() },
+                DisplayItem::Rect { x: 0.0, y: 500.0, width: 10.0, height: 10.0, color: "blue".to_string() },
+            ],
+        };
+        let frame_bounds = list.items.iter().find(|item| matches!(item, DisplayItem::Surface { kind: SurfaceKind::Iframe, .. })).unwrap().bounds();
+        assert!(list.embed_surface(frame.id, &nested));
+        assert_eq!(list.surfaces(), vec![(SurfaceKind::Canvas, canvas.id)]);
+        let red = list.items.iter().find(|item| matches!(item, DisplayItem::Rect { color, .. } if color == "red")).unwrap();
+        assert_eq!(red.bounds(), Dimensions::new(frame_bounds.x, frame_bounds.y, 200.0, 50.0));
+        assert!(!list.items.iter().any(|item| matches!(item, DisplayItem::Rect { color, .. } if color == "blue")));
+    }
+}
@@ -19,9 +19,13 @@
 //!    unnecessary allocations where possible.
 
 use dom::{Document, Node, NodeType};
+use dom::forms::FormControlType;
 use layout::{LayoutBox, ComputedStyles, DisplayType};
 use std::rc::Rc;
 
+// Drawing commands that can be painted in another process
+pub mod display_list;
+
 /// A trait for different rendering backends
 /// 
 /// This trait allows the renderer to work with different output formats
@@ -134,6 +138,11 @@ impl RenderBackend for TextRenderer {
             
             self.add_line(&format!("<{}{}>", tag_name, attr_string), depth);
             
+            // Draw the native widget for form controls
+            if let Some(widget) = render_form_control(node) {
+                self.add_line(&widget, depth + 1);
+            }
+            
             // Render children
             for child in node.children.borrow().iter() {
                 self.render_node_recursive(child, depth + 1);
@@ -144,7 +153,7 @@ impl RenderBackend for TextRenderer {
     }
     
     fn render_text(&mut self, node: &Rc<Node>, depth: usize) {
-        if let NodeType::Text(content) = &node.node_type {
+        if let Some(content) = node.character_data() {
             let trimmed = content.trim();
             if !trimmed.is_empty() {
                 self.add_line(&format!("\"{}\"", trimmed), depth);
@@ -220,7 +229,7 @@ impl RenderBackend for TextContentRenderer {
     }
     
     fn render_text(&mut self, node: &Rc<Node>, _depth: usize) {
-        if let NodeType::Text(content) = &node.node_type {
+        if let Some(content) = node.character_data() {
             let trimmed = content.trim();
             if !trimmed.is_empty() {
                 if !self.output.is_empty() {
@@ -306,6 +315,53 @@ impl<B: RenderBackend> Renderer<B> {
     }
 }
 
+/// Render a form control as a native-looking text widget
+/// 
+/// Returns `None` for nodes that are not form controls. The widget
+/// reflects the control's current state (value, checkedness, selection)
+/// rather than its content attributes.
+pub fn render_form_control(node: &Node) -> Option<String> {
+    let widget = match node.form_control_type()? {
+        FormControlType::Text => format!("[{:<20}]", node.value()),
+        FormControlType::Password => format!("[{:<20}]", "*".repeat(node.value().chars().count())),
+        FormControlType::TextArea => format!("[{:<20}]", node.value().replace('\n', " ")),
+        FormControlType::Checkbox => {
+            if node.checked() { "[x]".to_string() } else { "[ ]".to_string() }
+        }
+        FormControlType::Radio => {
+            if node.checked() { "(*)".to_string() } else { "( )".to_string() }
+        }
+        FormControlType::Submit | FormControlType::Reset | FormControlType::Button => {
+            let label = if node.tag_name() == Some("button") {
+                node.text_content().trim().to_string()
+            } else {
+                match node.get_attribute("value") {
+                    Some(value) => value,
+                    None => match node.form_control_type()? {
+                        FormControlType::Submit => "Submit".to_string(),
+                        FormControlType::Reset => "Reset".to_string(),
+                        _ => String::new(),
+                    },
+                }
+            };
+            format!("[ {} ]", label)
+        }
+        FormControlType::Select => {
+            let options = node.options();
+            let label = match node.selected_index() {
+                index if index >= 0 => options
+                    .get(index as usize)
+                    .map(|option| option.text_content().trim().to_string())
+                    .unwrap_or_default(),
+                _ => String::new(),
+            };
+            format!("[ {} v ]", label)
+        }
+        FormControlType::Hidden => return None,
+    };
+    Some(widget)
+}
+
 /// Convenience function to render a document as text
 /// 
 /// This function creates a text renderer and renders the document,
@@ -371,9 +427,17 @@ impl LayoutRenderer {
             return;
         }
         
+        // Generated content belongs to its element; print its text only
+        if let Some(pseudo) = layout_box.generated_by() {
+            let text: Vec<&str> = layout_box.fragments.iter().map(|fragment| fragment.text.as_str()).collect();
+            self.add_line(&format!("::{} \"{}\"", pseudo.name(), text.join(" ")), depth);
+            return;
+        }
+        
         match &layout_box.node.node_type {
-            NodeType::Element { tag_name, attributes } => {
-                // Create attribute string
+            NodeType::Element { tag_name, .. } => {
+                // Create attribute string from the current attributes
+                let attributes = layout_box.node.attributes();
                 let attr_string = if attributes.is_empty() {
                     String::new()
                 } else {
@@ -401,6 +465,9 @@ impl LayoutRenderer {
                 if !dimensions_info.is_empty() {
                     self.add_line(&format!("  Layout: {}", dimensions_info), depth + 1);
                 }
+                if let Some(widget) = render_form_control(&layout_box.node) {
+                    self.add_line(&widget, depth + 1);
+                }
                 
                 // Render children
                 for child in &layout_box.children {
@@ -409,7 +476,8 @@ impl LayoutRenderer {
                 
                 self.add_line(&format!("</{}>", tag_name), depth);
             }
-            NodeType::Text(content) => {
+            NodeType::Text(_) => {
+                let content = layout_box.node.text_content();
                 let trimmed = content.trim();
                 if !trimmed.is_empty() {
                     let style_info = self.format_text_style_info(&layout_box.styles);
@@ -418,6 +486,12 @@ impl LayoutRenderer {
                     } else {
                         self.add_line(&format!("\"{}\"", trimmed), depth);
                     }
+                    // First-line and first-letter runs are styled separately
+                    for fragment in layout_box.fragments.iter().filter(|fragment| fragment.pseudo.is_some()) {
+                        let name = fragment.pseudo.map(|pseudo| pseudo.name()).unwrap_or_default();
+                        let style_info = self.format_text_style_info(&fragment.styles);
+                        self.add_line(&format!("::{} \"{}\" ({})", name, fragment.text, style_info), depth + 1);
+                    }
                 }
             }
             NodeType::Document => {
@@ -439,13 +513,14 @@ impl LayoutRenderer {
             DisplayType::InlineBlock => info.push("display:inline-block".to_string()),
             DisplayType::Flex => info.push("display:flex".to_string()),
             DisplayType::Grid => info.push("display:grid".to_string()),
+            DisplayType::Contents => info.push("display:contents".to_string()),
             DisplayType::None => info.push("display:none".to_string()),
         }
         
-        if let Some(width) = styles.width {
+        if let Some(width) = styles.box_model.width {
             info.push(format!("width:{}px", width));
         }
-        if let Some(height) = styles.height {
+        if let Some(height) = styles.box_model.height {
             info.push(format!("height:{}px", height));
         }
         if let Some(ref color) = styles.color {
@@ -454,7 +529,7 @@ impl LayoutRenderer {
         if let Some(ref bg_color) = styles.background_color {
             info.push(format!("background:{}", bg_color));
         }
-        if let Some(font_size) = styles.font_size {
+        if let Some(font_size) = styles.font.size {
             info.push(format!("font-size:{}px", font_size));
         }
         
@@ -468,13 +543,13 @@ impl LayoutRenderer {
         if let Some(ref color) = styles.color {
             info.push(format!("color:{}", color));
         }
-        if let Some(font_size) = styles.font_size {
+        if let Some(font_size) = styles.font.size {
             info.push(format!("font-size:{}px", font_size));
         }
-        if let Some(ref font_family) = styles.font_family {
+        if let Some(ref font_family) = styles.font.family {
             info.push(format!("font-family:{}", font_family));
         }
-        if let Some(ref font_weight) = styles.font_weight {
+        if let Some(ref font_weight) = styles.font.weight {
             info.push(format!("font-weight:{}", font_weight));
         }
         
@@ -490,22 +565,22 @@ impl LayoutRenderer {
         info.push(format!("w:{}", layout_box.content.width));
         info.push(format!("h:{}", layout_box.content.height));
         
-        if layout_box.styles.margin.top > 0.0 || layout_box.styles.margin.right > 0.0 ||
-           layout_box.styles.margin.bottom > 0.0 || layout_box.styles.margin.left > 0.0 {
+        if layout_box.styles.box_model.margin.top > 0.0 || layout_box.styles.box_model.margin.right > 0.0 ||
+           layout_box.styles.box_model.margin.bottom > 0.0 || layout_box.styles.box_model.margin.left > 0.0 {
             info.push(format!("margin:{},{},{},{}", 
-                layout_box.styles.margin.top,
-                layout_box.styles.margin.right,
-                layout_box.styles.margin.bottom,
-                layout_box.styles.margin.left));
+                layout_box.styles.box_model.margin.top,
+                layout_box.styles.box_model.margin.right,
+                layout_box.styles.box_model.margin.bottom,
+                layout_box.styles.box_model.margin.left));
         }
         
-        if layout_box.styles.padding.top > 0.0 || layout_box.styles.padding.right > 0.0 ||
-           layout_box.styles.padding.bottom > 0.0 || layout_box.styles.padding.left > 0.0 {
+        if layout_box.styles.box_model.padding.top > 0.0 || layout_box.styles.box_model.padding.right > 0.0 ||
+           layout_box.styles.box_model.padding.bottom > 0.0 || layout_box.styles.box_model.padding.left > 0.0 {
             info.push(format!("padding:{},{},{},{}", 
-                layout_box.styles.padding.top,
-                layout_box.styles.padding.right,
-                layout_box.styles.padding.bottom,
-                layout_box.styles.padding.left));
+                layout_box.styles.box_model.padding.top,
+                layout_box.styles.box_model.padding.right,
+                layout_box.styles.box_model.padding.bottom,
+                layout_box.styles.box_model.padding.left));
         }
         
         info.join(", ")
@@ -599,4 +674,34 @@ mod tests {
         assert!(output.contains("class=\"container\""));
         assert!(output.contains("id=\"main\""));
     }
+
+    #[test]
+    fn test_form_control_widgets() {
+        let doc = Document::new();
+        let make = |tag: &str, attrs: &[(&str, &str)]| {
+            doc.create_node(NodeType::Element {
+                tag_name: tag.to_string(),
+                attributes: attrs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            })
+        };
+        
+        let text = make("input", &[("value", "hi")]);
+        let checkbox = make("input", &[("type", "checkbox"), ("checked", "")]);
+        let radio = make("input", &[("type", "radio")]);
+        let submit = make("input", &[("type", "submit")]);
+        let select = make("select", &[]);
+        let option = make("option", &[]);
+        option.append_child(&doc.create_text_node("First"));
+        select.append_child(&option);
+        
+        assert_eq!(render_form_control(&text).unwrap(), format!("[{:<20}]", "hi"));
+        assert_eq!(render_form_control(&checkbox).unwrap(), "[x]");
+        assert_eq!(render_form_control(&radio).unwrap(), "( )");
+        assert_eq!(render_form_control(&submit).unwrap(), "[ Submit ]");
+        assert_eq!(render_form_control(&select).unwrap(), "[ First v ]");
+        assert!(render_form_control(&option).is_none());
+        
+        checkbox.set_checked(false);
+        assert_eq!(render_form_control(&checkbox).unwrap(), "[ ]");
+    }
 }
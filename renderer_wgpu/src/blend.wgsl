@@ -0,0 +1,83 @@
+// Compositing pass for the blend modes fixed-function blending cannot
+// express. Both textures hold premultiplied colors; the output replaces
+// the backdrop.
+
+struct BlendParams {
+    mode: u32,
+    _padding0: u32,
+    _padding1: u32,
+    _padding2: u32,
+}
+
+@group(0) @binding(0) var backdrop_texture: texture_2d<f32>;
+@group(0) @binding(1) var source_texture: texture_2d<f32>;
+@group(0) @binding(2) var layer_sampler: sampler;
+@group(0) @binding(3) var<uniform> params: BlendParams;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// One triangle covering the target
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.uv = uv;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    return out;
+}
+
+// B(Cb, Cs) for one channel; the mode numbers match `BlendMode`
+fn blend_channel(mode: u32, cb: f32, cs: f32) -> f32 {
+    switch mode {
+        case 1u: {
+            return cb * cs;
+        }
+        case 2u: {
+            return cb + cs - cb * cs;
+        }
+        case 3u: {
+            if (cb <= 0.5) {
+                return 2.0 * cb * cs;
+            }
+            let doubled = 2.0 * cb - 1.0;
+            return cs + doubled - cs * doubled;
+        }
+        case 4u: {
+            return min(cb, cs);
+        }
+        case 5u: {
+            return max(cb, cs);
+        }
+        default: {
+            return cs;
+        }
+    }
+}
+
+fn unpremultiply(color: vec4<f32>) -> vec3<f32> {
+    if (color.a <= 0.0) {
+        return vec3<f32>(0.0);
+    }
+    return color.rgb / color.a;
+}
+
+@fragment
+fn fs_blend(in: VertexOutput) -> @location(0) vec4<f32> {
+    let backdrop = textureSample(backdrop_texture, layer_sampler, in.uv);
+    let source = textureSample(source_texture, layer_sampler, in.uv);
+    let cb = unpremultiply(backdrop);
+    let cs = unpremultiply(source);
+    let blended = vec3<f32>(
+        blend_channel(params.mode, cb.r, cs.r),
+        blend_channel(params.mode, cb.g, cs.g),
+        blend_channel(params.mode, cb.b, cs.b),
+    );
+    let color = source.rgb * (1.0 - backdrop.a)
+        + backdrop.rgb * (1.0 - source.a)
+        + source.a * backdrop.a * blended;
+    let alpha = source.a + backdrop.a * (1.0 - source.a);
+    return vec4<f32>(color, alpha);
+}
@@ -0,0 +1,296 @@
+//! # Blend Modes
+//!
+//! This module composites layers onto their backdrop with
+//! `mix-blend-mode`, and keeps `isolation: isolate` groups from blending
+//! with what is painted behind them.
+//!
+//! ## Design Principles
+//!
+//! 1. **Hardware When Exact**: `normal` and `screen` are plain
+//!    fixed-function blend states. The other modes need the backdrop's
+//!    color inside a product or a comparison, so they run through the
+//!    `blend.wgsl` shader with the backdrop bound as a texture.
+//! 2. **One Formula**: The CPU path and the shader share the separable
+//!    blend formula of the compositing spec, so both paint the same pixels.
+//! 3. **Groups Flatten Inward**: An isolated group is composited into a
+//!    transparent layer of its own first, then blended as one layer.
+
+use crate::filters::Layer;
+use bytemuck::{Pod, Zeroable};
+use layout::BlendMode;
+use std::borrow::Cow;
+
+/// Source of the blend shader
+pub const BLEND_SHADER: &str = include_str!("blend.wgsl");
+
+/// The number `blend.wgsl` uses for a blend mode
+pub fn shader_mode(mode: BlendMode) -> u32 {
+    match mode {
+        BlendMode::Normal => 0,
+        BlendMode::Multiply => 1,
+        BlendMode::Screen => 2,
+        BlendMode::Overlay => 3,
+        BlendMode::Darken => 4,
+        BlendMode::Lighten => 5,
+    }
+}
+
+/// Get the fixed-function blend state for a mode, for premultiplied
+/// colors
+///
+/// Returns `None` for the modes that need the blend shader.
+pub fn hardware_blend_state(mode: BlendMode) -> Option<wgpu::BlendState> {
+    match mode {
+        BlendMode::Normal => Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+        // cs + cb * (1 - cs)
+        BlendMode::Screen => Some(wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::OneMinusSrc,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent::OVER,
+        }),
+        BlendMode::Multiply | BlendMode::Overlay | BlendMode::Darken | BlendMode::Lighten => None,
+    }
+}
+
+/// Uniforms of the blend shader
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct BlendParams {
+    pub mode: u32,
+    _padding: [u32; 3],
+}
+
+impl BlendParams {
+    pub fn new(mode: BlendMode) -> Self {
+        BlendParams { mode: shader_mode(mode), _padding: [0; 3] }
+    }
+}
+
+/// Create the pipeline of the blend shader
+///
+/// Bind the backdrop texture, the source layer texture, a sampler and a
+/// `BlendParams` uniform buffer, in that order, and draw three vertices.
+pub fn create_blend_pipeline(device: &wgpu::Device, format: wgpu::TextureFormat) -> (wgpu::RenderPipeline, wgpu::BindGroupLayout) {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Blend Shader"),
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(BLEND_SHADER)),
+    });
+    let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    };
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Blend Bind Group Layout"),
+        entries: &[
+            texture_entry(0),
+            texture_entry(1),
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ],
+    });
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Blend Pipeline Layout"),
+        bind_group_layouts: &[&bind_group_layout],
+        push_constant_ranges: &[],
+    });
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Blend Pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_fullscreen",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_blend",
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                // The shader writes the finished composite
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    });
+    (pipeline, bind_group_layout)
+}
+
+/// B(Cb, Cs) for one straight color channel
+pub fn blend_channel(mode: BlendMode, backdrop: f32, source: f32) -> f32 {
+    match mode {
+        BlendMode::Normal => source,
+        BlendMode::Multiply => backdrop * source,
+        BlendMode::Screen => backdrop + source - backdrop * source,
+        BlendMode::Overlay => {
+            if backdrop <= 0.5 {
+                2.0 * backdrop * source
+            } else {
+                let doubled = 2.0 * backdrop - 1.0;
+                source + doubled - source * doubled
+            }
+        }
+        BlendMode::Darken => backdrop.min(source),
+        BlendMode::Lighten => backdrop.max(source),
+    }
+}
+
+/// Composite one premultiplied pixel onto another
+pub fn blend_pixel(mode: BlendMode, backdrop: [f32; 4], source: [f32; 4]) -> [f32; 4] {
+    let (backdrop_alpha, source_alpha) = (backdrop[3], source[3]);
+    let straight = |pixel: [f32; 4], channel: usize| if pixel[3] > 0.0 { pixel[channel] / pixel[3] } else { 0.0 };
+    let mut result = [0.0; 4];
+    for channel in 0..3 {
+        let blended = blend_channel(mode, straight(backdrop, channel), straight(source, channel));
+        result[channel] = source[channel] * (1.0 - backdrop_alpha)
+            + backdrop[channel] * (1.0 - source_alpha)
+            + source_alpha * backdrop_alpha * blended;
+    }
+    result[3] = source_alpha + backdrop_alpha * (1.0 - source_alpha);
+    result
+}
+
+/// Composite a layer onto a backdrop with its top-left corner at (x, y)
+pub fn composite_layer(backdrop: &mut Layer, source: &Layer, x: isize, y: isize, mode: BlendMode) {
+    for row in 0..source.height {
+        for column in 0..source.width {
+            let (target_x, target_y) = (x + column as isize, y + row as isize);
+            if target_x < 0 || target_y < 0 || target_x as usize >= backdrop.width || target_y as usize >= backdrop.height {
+                continue;
+            }
+            let index = target_y as usize * backdrop.width + target_x as usize;
+            let pixel = source.pixels[row * source.width + column];
+            backdrop.pixels[index] = blend_pixel(mode, backdrop.pixels[index], pixel);
+        }
+    }
+}
+
+/// A painted layer and the layers stacked on top of it
+#[derive(Debug, Clone)]
+pub struct CompositeNode {
+    pub layer: Layer,
+    /// Position relative to the parent node's layer
+    pub x: isize,
+    pub y: isize,
+    pub mode: BlendMode,
+    /// Whether the node's subtree is flattened on its own before it
+    /// blends, see `ComputedStyles::creates_isolated_group`
+    pub isolated: bool,
+    pub children: Vec<CompositeNode>,
+}
+
+/// Composite a layer tree onto a backdrop
+///
+/// `(x, y)` is where the parent of `node` sits on the backdrop.
+pub fn composite_tree(backdrop: &mut Layer, node: &CompositeNode, x: isize, y: isize) {
+    let (x, y) = (x + node.x, y + node.y);
+    if node.isolated {
+        let mut group = Layer::new(node.layer.width, node.layer.height);
+        composite_layer(&mut group, &node.layer, 0, 0, BlendMode::Normal);
+        for child in &node.children {
+            composite_tree(&mut group, child, 0, 0);
+        }
+        composite_layer(backdrop, &group, x, y, node.mode);
+    } else {
+        composite_layer(backdrop, &node.layer, x, y, node.mode);
+        for child in &node.children {
+            composite_tree(backdrop, child, x, y);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: usize, height: usize, color: [f32; 4]) -> Layer {
+        let mut layer = Layer::new(width, height);
+        layer.fill_rect(0, 0, width, height, color);
+        layer
+    }
+
+    #[test]
+    fn test_blend_modes() {
+        let close = |a: [f32; 4], b: [f32; 4]| a.iter().zip(&b).all(|(a, b)| (a - b).abs() < 1e-5);
+        let backdrop = [0.2, 0.6, 1.0, 1.0];
+        let source = [0.5, 0.5, 0.5, 1.0];
+        assert!(close(blend_pixel(BlendMode::Normal, backdrop, source), source));
+        assert!(close(blend_pixel(BlendMode::Multiply, backdrop, source), [0.1, 0.3, 0.5, 1.0]));
+        assert!(close(blend_pixel(BlendMode::Screen, backdrop, source), [0.6, 0.8, 1.0, 1.0]));
+        assert!(close(blend_pixel(BlendMode::Darken, backdrop, source), [0.2, 0.5, 0.5, 1.0]));
+        assert!(close(blend_pixel(BlendMode::Lighten, backdrop, source), [0.5, 0.6, 1.0, 1.0]));
+        assert!(close(blend_pixel(BlendMode::Overlay, backdrop, source), [0.2, 0.6, 1.0, 1.0]));
+
+        // Over a transparent backdrop every mode is plain source-over
+        assert!(close(blend_pixel(BlendMode::Multiply, [0.0; 4], source), source));
+
+        // Screen matches its fixed-function blend state exactly
+        assert!(hardware_blend_state(BlendMode::Screen).is_some());
+        assert!(hardware_blend_state(BlendMode::Overlay).is_none());
+    }
+
+    #[test]
+    fn test_isolation_hides_the_backdrop() {
+        let blue = [0.0, 0.0, 1.0, 1.0];
+        let yellow = [1.0, 1.0, 0.0, 1.0];
+        let child = CompositeNode {
+            layer: solid(1, 1, yellow),
+            x: 0,
+            y: 0,
+            mode: BlendMode::Multiply,
+            isolated: false,
+            children: Vec::new(),
+        };
+        let mut parent = CompositeNode {
+            layer: Layer::new(1, 1),
+            x: 0,
+            y: 0,
+            mode: BlendMode::Normal,
+            isolated: false,
+            children: vec![child],
+        };
+
+        // Without isolation, multiply reaches the page behind the parent
+        let mut page = solid(1, 1, blue);
+        composite_tree(&mut page, &parent, 0, 0);
+        assert_eq!(page.pixels[0], [0.0, 0.0, 0.0, 1.0]);
+
+        // An isolated parent only offers its own, transparent, contents
+        parent.isolated = true;
+        let mut page = solid(1, 1, blue);
+        composite_tree(&mut page, &parent, 0, 0);
+        assert_eq!(page.pixels[0], yellow);
+    }
+
+    #[test]
+    fn test_blend_shader_is_valid_wgsl() {
+        let module = naga::front::wgsl::parse_str(BLEND_SHADER).unwrap();
+        assert!(module.entry_points.iter().any(|entry| entry.name == "fs_blend"));
+    }
+}
@@ -2,15 +2,69 @@
 //! 
 //! This module provides real mouse and keyboard event capture that integrates
 //! with the event system and GPU rendering pipeline.
+//! 
+//! Pointer events are hit-tested against the current layout tree. Moving
+//! the pointer and pressing buttons update the hover and active chains on
+//! the DOM, restyle only the boxes whose `:hover`/`:active` rules changed,
+//! and request a repaint (or a relayout when geometry changed). Elements a
+//! modal dialog makes inert are never hit, so only the dialog and its
+//! backdrop receive the pointer, and Escape cancels the dialog. A pointer
+//! going down light dismisses the open `auto` popovers it is outside of,
+//! before any event of the press is fired. Escape exits fullscreen before
+//! anything else, and so does the window leaving fullscreen mode.
+//!
+//! Key presses are translated into DOM `KeyboardEvent`s (`key`, `code`,
+//! `location`, `repeat` and modifier state) and sent to the focused element,
+//! which a left click moves to the nearest focusable ancestor. IME input
+//! becomes composition events on the same target, and the copy, cut and
+//! paste shortcuts fire clipboard events there. With spatial navigation on,
+//! arrow keys the page does not cancel move focus to the nearest focusable
+//! element in their direction, except inside editable fields.
+//!
+//! Wheel input scrolls the viewport. When every `wheel` listener the event
+//! would reach is passive, the scroll is applied before the listeners run,
+//! since none of them can cancel it; otherwise the listeners run first and
+//! may prevent the scroll. Either way `scroll` events are queued and fired
+//! once per frame, and the time from input to scroll is recorded in the
+//! statistics.
+//!
+//! Touch contacts, from a touchscreen or from the mouse in touch emulation
+//! mode, fire pointer and touch events at the element each contact started
+//! on. Unless the page cancels them, a one-finger pan scrolls, a pinch
+//! changes the page scale, and a tap clicks as soon as the finger lifts.
 
 use std::collections::HashMap;
+use std::rc::Rc;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 use winit::{
-    event::{WindowEvent, MouseButton, ElementState},
-    keyboard::{KeyCode, PhysicalKey},
+    event::{WindowEvent, MouseButton, ElementState, Ime, KeyEvent, TouchPhase as WinitTouchPhase},
+    keyboard::{Key, KeyCode, KeyLocation as WinitKeyLocation, ModifiersState, NamedKey, PhysicalKey},
 };
+use css_parser::{Stylesheet, StyleInvalidation};
+use dom::Node;
+use dom::clipboard::Clipboard;
 use dom::dom_event_integration::DomEventManager;
+use dom::event_types::KeyLocation;
+use dom::events::{KeyboardEventInit, SyntheticEventFactory};
+use dom::gamepad::{Gamepad, GamepadBackend, GamepadChange, GamepadRegistry};
+use dom::pointer::MOUSE_POINTER_ID;
+use dom::element_state::{InteractionState, StateChange};
+use dom::scroll_events::{ScrollEventQueue, ScrollTarget};
+use dom::touch::{Gesture, GestureRecognizer, TouchPhase, TouchTracker};
+use layout::spatial_navigation::{self, NavigationDirection};
+use layout::{LayoutBox, ScrollCompositor, StyleMatcher};
+
+/// Environment variable turning spatial navigation on (`1`/`0`)
+pub const SPATIAL_NAVIGATION_ENV: &str = "DUBBY_SPATIAL_NAVIGATION";
+
+/// Touch contacts get the pointer ids after the mouse's
+const FIRST_TOUCH_POINTER_ID: i32 = MOUSE_POINTER_ID + 1;
+
+/// The range pinching can scale the page in
+const MIN_PAGE_SCALE: f64 = 1.0;
+const MAX_PAGE_SCALE: f64 = 5.0;
 
 /// Real-time input handler for mouse and keyboard events
 pub struct InputHandler {
@@ -22,12 +76,54 @@ pub struct InputHandler {
     mouse_buttons: HashMap<MouseButton, bool>,
     /// Keyboard key states
     keyboard_keys: HashMap<KeyCode, bool>,
+    /// Modifier state reported by the window system
+    modifiers_state: ModifiersState,
+    /// Text of the IME composition in progress
+    composition_text: Option<String>,
+    /// Clipboard used by the copy, cut and paste shortcuts
+    clipboard: Clipboard,
     /// Input event statistics
     input_stats: InputStats,
     /// Event callback registry
     event_callbacks: HashMap<String, Vec<Box<dyn Fn(&InputEvent) + Send + Sync>>>,
     /// Running state
     is_running: Arc<AtomicBool>,
+    /// Layout tree used for hit testing
+    layout_tree: Option<LayoutBox>,
+    /// Stylesheet used to restyle after interaction state changes
+    style_matcher: Option<(Stylesheet, StyleMatcher)>,
+    /// Hover, active and focus chains
+    interaction_state: InteractionState,
+    /// Pending work for the embedder
+    pending_update: InteractionUpdate,
+    /// Scroll events waiting for the next frame
+    scroll_events: ScrollEventQueue,
+    /// Set when `pointerdown` was cancelled, so the compatibility mouse
+    /// events of the press are not fired
+    suppress_mouse_events: bool,
+    /// Source of gamepad state, polled once per frame
+    gamepad_backend: Option<Box<dyn GamepadBackend>>,
+    /// Slots of the connected gamepads
+    gamepads: GamepadRegistry,
+    /// Contacts on the touch surface
+    touches: TouchTracker,
+    /// Pans, pinches and taps of the contacts
+    gestures: GestureRecognizer,
+    /// Whether the left mouse button acts as a finger
+    touch_emulation: bool,
+    /// Page scale set by pinching
+    page_scale: f64,
+    /// Whether arrow keys move focus between elements
+    spatial_navigation: bool,
+}
+
+/// Work requested by interaction state changes
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct InteractionUpdate {
+    /// The layout tree was restyled and must be repainted
+    pub repaint: bool,
+    /// A restyled property affects geometry, so layout must rerun first
+    pub relayout: bool,
 }
 
 /// Input event data structure
@@ -37,6 +133,8 @@ pub struct InputEvent {
     pub position: Option<(f64, f64)>,
     pub button: Option<MouseButton>,
     pub key: Option<KeyCode>,
+    /// DOM key event data for keyboard events
+    pub keyboard: Option<KeyboardEventInit>,
     pub modifiers: KeyModifiers,
     pub timestamp: std::time::Instant,
 }
@@ -75,6 +173,33 @@ pub struct InputStats {
     pub total_events: u64,
     pub last_event_type: Option<InputEventType>,
     pub last_timestamp: Option<std::time::Instant>,
+    pub wheel_events: u64,
+    pub touch_events: u64,
+    /// Wheel events scrolled without waiting for listeners
+    pub wheel_fast_path: u64,
+    /// Wheel events whose scroll waited for a blocking listener
+    pub wheel_blocked: u64,
+    /// Total time from wheel input until the scroll was applied
+    pub wheel_latency_total: Duration,
+    pub wheel_latency_max: Duration,
+}
+
+impl InputStats {
+    /// Get the mean time from wheel input until the scroll was applied
+    pub fn mean_wheel_latency(&self) -> Option<Duration> {
+        (self.wheel_events > 0).then(|| self.wheel_latency_total / self.wheel_events as u32)
+    }
+
+    fn record_wheel_latency(&mut self, latency: Duration, blocked: bool) {
+        self.wheel_events += 1;
+        if blocked {
+            self.wheel_blocked += 1;
+        } else {
+            self.wheel_fast_path += 1;
+        }
+        self.wheel_latency_total += latency;
+        self.wheel_latency_max = self.wheel_latency_max.max(latency);
+    }
 }
 
 impl InputHandler {
@@ -85,10 +210,82 @@ impl InputHandler {
             mouse_position: (0.0, 0.0),
             mouse_buttons: HashMap::new(),
             keyboard_keys: HashMap::new(),
+            modifiers_state: ModifiersState::empty(),
+            composition_text: None,
+            clipboard: Clipboard::in_memory(),
             input_stats: InputStats::default(),
             event_callbacks: HashMap::new(),
             is_running: Arc::new(AtomicBool::new(false)),
+            layout_tree: None,
+            style_matcher: None,
+            interaction_state: InteractionState::new(),
+            pending_update: InteractionUpdate::default(),
+            scroll_events: ScrollEventQueue::new(),
+            suppress_mouse_events: false,
+            gamepad_backend: None,
+            gamepads: GamepadRegistry::new(),
+            touches: TouchTracker::new(),
+            gestures: GestureRecognizer::new(),
+            touch_emulation: false,
+            page_scale: MIN_PAGE_SCALE,
+            spatial_navigation: false,
+        }
+    }
+
+    /// Set the layout tree used for hit testing
+    pub fn set_layout_tree(&mut self, layout_tree: LayoutBox) {
+        self.layout_tree = Some(layout_tree);
+    }
+
+    /// Get the layout tree, including any interaction restyles
+    pub fn layout_tree(&self) -> Option<&LayoutBox> {
+        self.layout_tree.as_ref()
+    }
+
+    /// Get the fullscreen element of the document being shown
+    pub fn fullscreen_element(&self) -> Option<Rc<Node>> {
+        self.layout_tree.as_ref().and_then(|layout_tree| dom::fullscreen::fullscreen_element(&layout_tree.node))
+    }
+
+    /// Take the document out of fullscreen because the window left
+    /// fullscreen mode, firing `fullscreenchange`
+    ///
+    /// Returns whether the document was in fullscreen.
+    pub fn exit_fullscreen(&mut self) -> bool {
+        let Some(root) = self.layout_tree.as_ref().map(|layout_tree| Rc::clone(&layout_tree.node)) else {
+            return false;
+        };
+        let exited = self.dom_event_manager.handle_user_input(|manager| manager.fully_exit_fullscreen(&root));
+        if exited {
+            self.pending_update.relayout = true;
         }
+        exited
+    }
+
+    /// Hide or show the document being shown, as the window is minimized
+    /// or restored, firing `visibilitychange`
+    ///
+    /// Returns whether the visibility changed.
+    pub fn set_visibility_state(&mut self, state: dom::page_visibility::VisibilityState) -> bool {
+        let Some(root) = self.layout_tree.as_ref().map(|layout_tree| Rc::clone(&layout_tree.node)) else {
+            return false;
+        };
+        self.dom_event_manager.set_visibility_state(&root, state)
+    }
+
+    /// Set the stylesheet used to restyle after hover and active changes
+    pub fn set_stylesheet(&mut self, stylesheet: Stylesheet) {
+        self.style_matcher = Some((stylesheet.clone(), StyleMatcher::new(stylesheet)));
+    }
+
+    /// Get the node the pointer is over
+    pub fn hovered_node(&self) -> Option<Rc<Node>> {
+        self.interaction_state.hovered()
+    }
+
+    /// Take the repaint and relayout requests accumulated since the last call
+    pub fn take_interaction_update(&mut self) -> InteractionUpdate {
+        std::mem::take(&mut self.pending_update)
     }
 
     /// Set the DOM event manager
@@ -124,75 +321,58 @@ impl InputHandler {
                     position: Some(self.mouse_position),
                     button: None,
                     key: None,
+                    keyboard: None,
                     modifiers: self.get_current_modifiers(),
                     timestamp: std::time::Instant::now(),
                 });
             }
             WindowEvent::MouseInput { state, button, .. } => {
                 let is_pressed = *state == ElementState::Pressed;
-                self.mouse_buttons.insert(*button, is_pressed);
+                let was_pressed = self.mouse_buttons.insert(*button, is_pressed).unwrap_or(false);
 
                 let event_type = if is_pressed {
                     InputEventType::MouseDown
                 } else {
                     InputEventType::MouseUp
                 };
-
-                input_event = Some(InputEvent {
-                    event_type: event_type.clone(),
+                let mouse_event = |event_type| InputEvent {
+                    event_type,
                     position: Some(self.mouse_position),
                     button: Some(*button),
                     key: None,
+                    keyboard: None,
                     modifiers: self.get_current_modifiers(),
                     timestamp: std::time::Instant::now(),
-                });
+                };
+                let press = mouse_event(event_type);
+                let click = mouse_event(InputEventType::MouseClick);
+                self.process_input_event(press);
 
                 // Handle click events (down + up sequence)
-                if !is_pressed && self.mouse_buttons.get(button).copied().unwrap_or(false) {
-                    let click_event = InputEvent {
-                        event_type: InputEventType::MouseClick,
-                        position: Some(self.mouse_position),
-                        button: Some(*button),
-                        key: None,
-                        modifiers: self.get_current_modifiers(),
-                        timestamp: std::time::Instant::now(),
-                    };
-                    self.process_input_event(click_event);
+                if !is_pressed && was_pressed {
+                    self.process_input_event(click);
                 }
+                return true;
             }
             WindowEvent::KeyboardInput { event, .. } => {
-                if let PhysicalKey::Code(keycode) = &event.physical_key {
-                    let is_pressed = event.state == ElementState::Pressed;
-                    self.keyboard_keys.insert(*keycode, is_pressed);
-
-                    let event_type = if is_pressed {
-                        InputEventType::KeyDown
-                    } else {
-                        InputEventType::KeyUp
-                    };
-
-                    input_event = Some(InputEvent {
-                        event_type: event_type.clone(),
-                        position: None,
-                        button: None,
-                        key: Some(*keycode),
-                        modifiers: self.get_current_modifiers(),
-                        timestamp: std::time::Instant::now(),
-                    });
-
-                    // Handle key press events (for printable characters)
-                    if is_pressed && self.is_printable_key(*keycode) {
-                        let press_event = InputEvent {
-                            event_type: InputEventType::KeyPress,
-                            position: None,
-                            button: None,
-                            key: Some(*keycode),
-                            modifiers: self.get_current_modifiers(),
-                            timestamp: std::time::Instant::now(),
-                        };
-                        self.process_input_event(press_event);
-                    }
+                let is_pressed = event.state == ElementState::Pressed;
+                let keycode = match event.physical_key {
+                    PhysicalKey::Code(keycode) => Some(keycode),
+                    PhysicalKey::Unidentified(_) => None,
+                };
+                if let Some(keycode) = keycode {
+                    self.keyboard_keys.insert(keycode, is_pressed);
                 }
+                let init = self.keyboard_event_init(event);
+                self.handle_key(keycode, init, is_pressed);
+                return true;
+            }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers_state = modifiers.state();
+            }
+            WindowEvent::Ime(ime) => {
+                self.handle_ime(ime);
+                return true;
             }
             WindowEvent::Focused(focused) => {
                 let event_type = if *focused {
@@ -206,16 +386,22 @@ impl InputHandler {
                     position: None,
                     button: None,
                     key: None,
+                    keyboard: None,
                     modifiers: self.get_current_modifiers(),
                     timestamp: std::time::Instant::now(),
                 });
             }
+            WindowEvent::CursorLeft { .. } => {
+                let changes = self.interaction_state.set_hovered(None);
+                self.invalidate_state_changes(changes);
+            }
             WindowEvent::Resized(_) => {
                 input_event = Some(InputEvent {
                     event_type: InputEventType::Resize,
                     position: None,
                     button: None,
                     key: None,
+                    keyboard: None,
                     modifiers: self.get_current_modifiers(),
                     timestamp: std::time::Instant::now(),
                 });
@@ -231,6 +417,412 @@ impl InputHandler {
         }
     }
 
+    /// Handle a key press or release described by a DOM init dictionary
+    ///
+    /// Emits `KeyDown` (followed by `KeyPress` for character-producing keys)
+    /// or `KeyUp`; the DOM receives the matching `KeyboardEvent`s at the
+    /// focused element.
+    pub fn handle_key(&mut self, key: Option<KeyCode>, init: KeyboardEventInit, pressed: bool) {
+        let produces_character = pressed
+            && SyntheticEventFactory::create_keyboard_event("keydown", &init).produces_character();
+        let key_event = |event_type| InputEvent {
+            event_type,
+            position: None,
+            button: None,
+            key,
+            keyboard: Some(init.clone()),
+            modifiers: self.get_current_modifiers(),
+            timestamp: std::time::Instant::now(),
+        };
+
+        if pressed {
+            let keydown = key_event(InputEventType::KeyDown);
+            let keypress = key_event(InputEventType::KeyPress);
+            self.process_input_event(keydown);
+            if produces_character {
+                self.process_input_event(keypress);
+            }
+        } else {
+            let keyup = key_event(InputEventType::KeyUp);
+            self.process_input_event(keyup);
+        }
+    }
+
+    /// Translate IME events into composition events at the focused element
+    pub fn handle_ime(&mut self, ime: &Ime) {
+        let target = self.keyboard_target();
+        let fire = |manager: &mut DomEventManager, event_type: &str, data: &str| {
+            if let Some(target) = &target {
+                let event = SyntheticEventFactory::create_composition_event(event_type, data);
+                manager.handle_user_input(|manager| manager.dispatch_composition_event(target, event));
+            }
+        };
+
+        match ime {
+            Ime::Preedit(text, _) if !text.is_empty() => {
+                if self.composition_text.is_none() {
+                    fire(&mut self.dom_event_manager, "compositionstart", "");
+                }
+                fire(&mut self.dom_event_manager, "compositionupdate", text);
+                self.composition_text = Some(text.clone());
+            }
+            Ime::Commit(text) => {
+                match self.composition_text.take() {
+                    None => {
+                        fire(&mut self.dom_event_manager, "compositionstart", "");
+                        fire(&mut self.dom_event_manager, "compositionupdate", text);
+                    }
+                    Some(current) if current != *text => {
+                        fire(&mut self.dom_event_manager, "compositionupdate", text);
+                    }
+                    Some(_) => {}
+                }
+                fire(&mut self.dom_event_manager, "compositionend", text);
+            }
+            Ime::Disabled => {
+                if self.composition_text.take().is_some() {
+                    fire(&mut self.dom_event_manager, "compositionend", "");
+                }
+            }
+            // An empty preedit precedes every commit, and Enabled carries
+            // no text
+            Ime::Preedit(..) | Ime::Enabled => {}
+        }
+    }
+
+    /// Build the DOM key event data for a winit key event
+    fn keyboard_event_init(&self, event: &KeyEvent) -> KeyboardEventInit {
+        let modifiers = self.get_current_modifiers();
+        KeyboardEventInit {
+            key: dom_key(&event.logical_key),
+            code: dom_code(&event.physical_key),
+            location: match event.location {
+                WinitKeyLocation::Standard => KeyLocation::Standard,
+                WinitKeyLocation::Left => KeyLocation::Left,
+                WinitKeyLocation::Right => KeyLocation::Right,
+                WinitKeyLocation::Numpad => KeyLocation::Numpad,
+            },
+            repeat: event.repeat,
+            ctrl_key: modifiers.ctrl,
+            shift_key: modifiers.shift,
+            alt_key: modifiers.alt,
+            meta_key: modifiers.meta,
+            is_composing: self.composition_text.is_some(),
+        }
+    }
+
+    /// Set the clipboard used by the copy, cut and paste shortcuts
+    pub fn set_clipboard(&mut self, clipboard: Clipboard) {
+        self.clipboard = clipboard;
+    }
+
+    /// Set the source of gamepad state, such as a gilrs reader
+    pub fn set_gamepad_backend(&mut self, backend: Box<dyn GamepadBackend>) {
+        self.gamepad_backend = Some(backend);
+    }
+
+    pub fn has_gamepad_backend(&self) -> bool {
+        self.gamepad_backend.is_some()
+    }
+
+    /// Poll the gamepad backend and fire `gamepadconnected` and
+    /// `gamepaddisconnected` at the document for devices that came and went
+    ///
+    /// Called once per frame. Returns the number of events fired.
+    pub fn update_gamepads(&mut self) -> usize {
+        let Some(backend) = self.gamepad_backend.as_mut() else {
+            return 0;
+        };
+        let changes = self.gamepads.update(backend.poll());
+        let Some(root) = self.dom_event_manager.document().map(|document| Rc::clone(&document.root)) else {
+            return 0;
+        };
+        for change in &changes {
+            let event_type = match change {
+                GamepadChange::Connected(_) => "gamepadconnected",
+                GamepadChange::Disconnected(_) => "gamepaddisconnected",
+            };
+            let mut event = dom::event_types::Event::new(event_type, false, false);
+            event.is_trusted = true;
+            self.dom_event_manager.dispatch_event(&root, event);
+        }
+        changes.len()
+    }
+
+    /// Get the gamepads as of the last poll, by slot
+    pub fn gamepads(&self) -> Vec<Option<Gamepad>> {
+        self.gamepads.gamepads()
+    }
+
+    /// Turn spatial navigation with the arrow keys on or off
+    pub fn set_spatial_navigation(&mut self, enabled: bool) {
+        self.spatial_navigation = enabled;
+    }
+
+    pub fn spatial_navigation(&self) -> bool {
+        self.spatial_navigation
+    }
+
+    /// Turn spatial navigation on if `DUBBY_SPATIAL_NAVIGATION` is set
+    pub fn apply_spatial_navigation_preference(&mut self) {
+        let enabled = std::env::var(SPATIAL_NAVIGATION_ENV)
+            .map(|value| matches!(value.trim().to_ascii_lowercase().as_str(), "1" | "true" | "on" | "yes"))
+            .unwrap_or(false);
+        self.set_spatial_navigation(enabled);
+    }
+
+    /// Move focus to the nearest focusable element in a direction, firing
+    /// `blur` and `focus`
+    ///
+    /// Returns whether focus moved.
+    pub fn navigate_focus(&mut self, direction: NavigationDirection) -> bool {
+        let focused = self.interaction_state.focused();
+        let Some(target) = self.layout_tree.as_ref().and_then(|tree| spatial_navigation::navigate(tree, focused.as_ref(), direction)) else {
+            return false;
+        };
+        let changes = self.update_focus(Some(&target));
+        self.invalidate_state_changes(changes);
+        true
+    }
+
+    /// Get the focused element
+    pub fn focused_node(&self) -> Option<Rc<Node>> {
+        self.interaction_state.focused()
+    }
+
+    /// The element key events are dispatched to: the focused element, or
+    /// the body when nothing has focus
+    fn keyboard_target(&self) -> Option<Rc<Node>> {
+        self.interaction_state
+            .focused()
+            .or_else(|| self.dom_event_manager.document().and_then(|document| document.body()))
+    }
+
+    /// Move focus to the nearest focusable ancestor of a clicked element,
+    /// firing `blur` and `focus`
+    fn update_focus(&mut self, target: Option<&Rc<Node>>) -> Vec<StateChange> {
+        let mut focus_target = target.cloned();
+        while let Some(node) = focus_target.clone() {
+            if node.is_focusable() {
+                break;
+            }
+            focus_target = node.parent.borrow().upgrade();
+        }
+
+        let previous = self.interaction_state.focused();
+        if previous.as_ref().map(|node| node.id) == focus_target.as_ref().map(|node| node.id) {
+            return Vec::new();
+        }
+        let changes = self.interaction_state.set_focused(focus_target.as_ref());
+        self.dom_event_manager.handle_user_input(|manager| {
+            if let Some(previous) = previous {
+                manager.dispatch_event(&previous, SyntheticEventFactory::create_blur_event().base);
+            }
+            if let Some(focused) = focus_target {
+                manager.dispatch_event(&focused, SyntheticEventFactory::create_focus_event().base);
+            }
+        });
+        changes
+    }
+
+    /// Scroll the viewport for a wheel delta at the pointer position
+    ///
+    /// With only passive `wheel` listeners on the path, the scroll happens
+    /// first and the listeners receive an uncancelable event afterwards.
+    /// Otherwise the listeners run first and a cancelled event prevents the
+    /// scroll. Returns whether the viewport moved, so a repaint is needed.
+    pub fn handle_wheel(&mut self, delta: (f32, f32), compositor: &mut ScrollCompositor) -> bool {
+        let timestamp = Instant::now();
+        let target = self.find_element_at_position(self.mouse_position);
+        let passive = target
+            .as_ref()
+            .is_none_or(|node| self.dom_event_manager.has_only_passive_listeners(node, "wheel"));
+
+        let scrolled = if passive {
+            let scrolled = compositor.scroll_by(delta.0, delta.1);
+            self.input_stats.record_wheel_latency(timestamp.elapsed(), false);
+            if let Some(node) = &target {
+                let wheel = dom::event_types::Event::new("wheel", true, false);
+                self.dom_event_manager.handle_user_input(|manager| manager.dispatch_event(node, wheel));
+            }
+            scrolled
+        } else {
+            let wheel = dom::event_types::Event::new("wheel", true, true);
+            let not_cancelled = target.as_ref().is_none_or(|node| {
+                self.dom_event_manager.handle_user_input(|manager| manager.dispatch_event(node, wheel))
+            });
+            let scrolled = not_cancelled && compositor.scroll_by(delta.0, delta.1);
+            self.input_stats.record_wheel_latency(timestamp.elapsed(), true);
+            scrolled
+        };
+
+        if scrolled {
+            self.scroll_events.schedule(ScrollTarget::Document);
+        }
+
+        let event = InputEvent {
+            event_type: InputEventType::MouseWheel,
+            position: Some(self.mouse_position),
+            button: None,
+            key: None,
+            keyboard: None,
+            modifiers: self.get_current_modifiers(),
+            timestamp,
+        };
+        self.input_stats.total_events += 1;
+        self.input_stats.last_event_type = Some(InputEventType::MouseWheel);
+        self.input_stats.last_timestamp = Some(timestamp);
+        self.call_event_callbacks(&event);
+        scrolled
+    }
+
+    /// Make the left mouse button act as a finger on a touchscreen, for
+    /// testing pages made for touch devices
+    pub fn set_touch_emulation(&mut self, enabled: bool) {
+        self.touch_emulation = enabled;
+    }
+
+    /// Get the page scale set by pinching, 1 when unzoomed
+    pub fn page_scale(&self) -> f64 {
+        self.page_scale
+    }
+
+    /// Handle a window event as touch input while touch emulation is on
+    ///
+    /// Returns `None` for events the emulation does not take, which go to
+    /// `handle_window_event`, and otherwise whether a repaint is needed.
+    /// Mouse moves with no button pressed are dropped, as a touchscreen
+    /// does not hover.
+    pub fn handle_emulated_touch(&mut self, event: &WindowEvent, compositor: &mut ScrollCompositor) -> Option<bool> {
+        if !self.touch_emulation {
+            return None;
+        }
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                self.mouse_position = (position.x, position.y);
+                let pressed = self.is_mouse_button_pressed(MouseButton::Left);
+                Some(pressed && self.handle_touch(0, WinitTouchPhase::Moved, self.mouse_position, compositor))
+            }
+            WindowEvent::MouseInput { state, button: MouseButton::Left, .. } => {
+                let pressed = *state == ElementState::Pressed;
+                let was_pressed = self.mouse_buttons.insert(MouseButton::Left, pressed).unwrap_or(false);
+                let phase = match (pressed, was_pressed) {
+                    (true, false) => WinitTouchPhase::Started,
+                    (false, true) => WinitTouchPhase::Ended,
+                    _ => return Some(false),
+                };
+                Some(self.handle_touch(0, phase, self.mouse_position, compositor))
+            }
+            WindowEvent::MouseInput { .. } => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Handle a touch contact going down, moving, lifting or being
+    /// cancelled
+    ///
+    /// Fires the pointer and touch events of the contact, then performs
+    /// its gesture unless the page cancelled a touch event of the
+    /// sequence. Returns whether the viewport moved or the page scale
+    /// changed, so a repaint is needed.
+    pub fn handle_touch(&mut self, identifier: u64, phase: WinitTouchPhase, position: (f64, f64), compositor: &mut ScrollCompositor) -> bool {
+        let phase = match phase {
+            WinitTouchPhase::Started => TouchPhase::Start,
+            WinitTouchPhase::Moved => TouchPhase::Move,
+            WinitTouchPhase::Ended => TouchPhase::End,
+            WinitTouchPhase::Cancelled => TouchPhase::Cancel,
+        };
+        let identifier = identifier as i32;
+        let (x, y) = position;
+        let timestamp = Instant::now();
+        let hit = if phase == TouchPhase::Start { self.find_element_at_position(position) } else { None };
+        if let Some(target) = &hit {
+            self.dom_event_manager.handle_user_input(|manager| manager.light_dismiss_popovers(target));
+        }
+        let Some(mut touch_event) = self.touches.update(phase, identifier, x, y, hit) else {
+            return false;
+        };
+        let modifiers = self.get_current_modifiers();
+        touch_event.ctrl_key = modifiers.ctrl;
+        touch_event.shift_key = modifiers.shift;
+        touch_event.alt_key = modifiers.alt;
+        touch_event.meta_key = modifiers.meta;
+
+        let not_cancelled = match touch_event.changed_touches[0].target.clone() {
+            Some(target) => {
+                let (pointer_type, buttons) = match phase {
+                    TouchPhase::Start => ("pointerdown", 1),
+                    TouchPhase::Move => ("pointermove", 1),
+                    TouchPhase::End => ("pointerup", 0),
+                    TouchPhase::Cancel => ("pointercancel", 0),
+                };
+                let mut pointer = SyntheticEventFactory::create_pointer_event(pointer_type, FIRST_TOUCH_POINTER_ID + identifier, "touch", x, y, buttons);
+                pointer.mouse.button = if matches!(phase, TouchPhase::Start | TouchPhase::End) { 0 } else { -1 };
+                pointer.is_primary = self.touches.is_primary(identifier);
+                self.dom_event_manager.handle_user_input(|manager| {
+                    manager.dispatch_pointer_event(&target, pointer);
+                    manager.dispatch_touch_event(touch_event)
+                })
+            }
+            None => true,
+        };
+        if !not_cancelled {
+            self.gestures.block();
+        }
+
+        let mut repaint = false;
+        match self.gestures.update(phase, identifier, x, y, timestamp) {
+            Some(Gesture::Pan { dx, dy }) => {
+                repaint = compositor.scroll_by((dx / self.page_scale) as f32, (dy / self.page_scale) as f32);
+                if repaint {
+                    self.scroll_events.schedule(ScrollTarget::Document);
+                }
+            }
+            Some(Gesture::Pinch { scale, .. }) => {
+                let page_scale = (self.page_scale * scale).clamp(MIN_PAGE_SCALE, MAX_PAGE_SCALE);
+                repaint = page_scale != self.page_scale;
+                self.page_scale = page_scale;
+            }
+            Some(Gesture::Tap { x, y }) => self.dispatch_tap((x, y)),
+            None => {}
+        }
+
+        self.input_stats.touch_events += 1;
+        self.input_stats.total_events += 1;
+        self.input_stats.last_timestamp = Some(timestamp);
+        repaint
+    }
+
+    /// Click where a tap ended, right away
+    ///
+    /// Fires the compatibility mouse events of the tap before `click`, and
+    /// moves focus as a mouse press would.
+    fn dispatch_tap(&mut self, position: (f64, f64)) {
+        let Some(target) = self.find_element_at_position(position) else {
+            return;
+        };
+        let changes = self.update_focus(Some(&target));
+        self.invalidate_state_changes(changes);
+        self.dom_event_manager.handle_user_input(|manager| {
+            for event_type in ["mousemove", "mousedown", "mouseup", "click"] {
+                manager.dispatch_event(&target, dom::event_types::Event::new(event_type, true, true));
+            }
+        });
+    }
+
+    /// Queue a `scroll` event for a scrolling box that moved
+    pub fn schedule_scroll_event(&mut self, target: ScrollTarget) {
+        self.scroll_events.schedule(target);
+    }
+
+    /// Fire the `scroll` events queued since the last frame
+    ///
+    /// Call once per frame, before painting. Returns the number of events
+    /// fired.
+    pub fn dispatch_frame_events(&mut self) -> usize {
+        self.scroll_events.dispatch_frame(&mut self.dom_event_manager)
+    }
+
     /// Process an input event
     fn process_input_event(&mut self, event: InputEvent) {
         // Update statistics
@@ -248,44 +840,175 @@ impl InputHandler {
             _ => {}
         }
 
+        // Hit test once and share the target
+        let target = event.position.and_then(|position| self.find_element_at_position(position));
+        if let (InputEventType::MouseDown, Some(target)) = (&event.event_type, &target) {
+            self.dom_event_manager.handle_user_input(|manager| manager.light_dismiss_popovers(target));
+        }
+        self.update_interaction_state(&event, target.as_ref());
+
         // Dispatch to DOM event manager
-        self.dispatch_to_dom(&event);
+        self.dispatch_to_dom(&event, target.as_ref());
 
         // Call registered callbacks
         self.call_event_callbacks(&event);
     }
 
+    /// Update the hover and active chains for a pointer event
+    fn update_interaction_state(&mut self, event: &InputEvent, target: Option<&Rc<Node>>) {
+        let changes = match event.event_type {
+            InputEventType::MouseMove => self.interaction_state.set_hovered(target),
+            InputEventType::MouseDown if event.button == Some(MouseButton::Left) => {
+                let mut changes = self.interaction_state.set_active(target);
+                changes.extend(self.update_focus(target));
+                changes
+            }
+            InputEventType::MouseUp if event.button == Some(MouseButton::Left) => {
+                self.interaction_state.set_active(None)
+            }
+            InputEventType::Blur => {
+                let mut changes = self.interaction_state.set_active(None);
+                changes.extend(self.interaction_state.set_hovered(None));
+                changes
+            }
+            _ => return,
+        };
+        self.invalidate_state_changes(changes);
+    }
+
+    /// Restyle the boxes affected by state changes and schedule a paint
+    fn invalidate_state_changes(&mut self, changes: Vec<StateChange>) {
+        let (Some((stylesheet, matcher)), Some(layout_tree)) = (&self.style_matcher, self.layout_tree.as_mut()) else {
+            return;
+        };
+
+        let mut invalidations: HashMap<u64, StyleInvalidation> = HashMap::new();
+        for change in &changes {
+            let invalidation = css_parser::invalidation_for_state_change(stylesheet, &change.node, change.changed);
+            if invalidation != StyleInvalidation::None {
+                let entry = invalidations.entry(change.node.id).or_insert(StyleInvalidation::None);
+                *entry = (*entry).max(invalidation);
+            }
+        }
+        if invalidations.is_empty() {
+            return;
+        }
+
+        let outcome = matcher.restyle_layout_tree(layout_tree, &invalidations);
+        if outcome.changed_boxes > 0 {
+            self.pending_update.repaint = true;
+            self.pending_update.relayout |= outcome.needs_layout;
+        }
+    }
+
     /// Dispatch input event to DOM event manager
-    fn dispatch_to_dom(&mut self, event: &InputEvent) {
+    fn dispatch_to_dom(&mut self, event: &InputEvent, target: Option<&Rc<Node>>) {
+        if matches!(event.event_type, InputEventType::KeyDown | InputEventType::KeyUp) {
+            let (Some(init), Some(target_node)) = (&event.keyboard, self.keyboard_target()) else {
+                return;
+            };
+            let event_type = if event.event_type == InputEventType::KeyDown { "keydown" } else { "keyup" };
+            let key_event = SyntheticEventFactory::create_keyboard_event(event_type, init);
+            let is_keydown = event.event_type == InputEventType::KeyDown;
+            let clipboard = &self.clipboard;
+            let not_cancelled = self.dom_event_manager.handle_user_input(|manager| {
+                let not_cancelled = manager.dispatch_keyboard_event(&target_node, key_event);
+                if not_cancelled && is_keydown {
+                    if let Some(clipboard_event) = clipboard_shortcut(init) {
+                        manager.dispatch_clipboard_event(&target_node, clipboard_event, clipboard);
+                    }
+                }
+                not_cancelled
+            });
+            // Arrow keys move the caret inside editable fields
+            let modified = init.ctrl_key || init.alt_key || init.meta_key || init.shift_key;
+            if let Some(direction) = NavigationDirection::from_key(&init.key)
+                .filter(|_| self.spatial_navigation && is_keydown && not_cancelled && !modified && !target_node.is_editable())
+            {
+                self.navigate_focus(direction);
+            }
+            return;
+        }
+
+        if let Some(target_node) = target {
+            if matches!(event.event_type, InputEventType::MouseMove | InputEventType::MouseDown | InputEventType::MouseUp)
+                && !self.dispatch_pointer_event(event, target_node)
+            {
+                return;
+            }
+        }
+
         let dom_event_type = match event.event_type {
             InputEventType::MouseClick => "click",
             InputEventType::MouseDown => "mousedown",
             InputEventType::MouseUp => "mouseup",
             InputEventType::MouseMove => "mousemove",
-            InputEventType::KeyDown => "keydown",
-            InputEventType::KeyUp => "keyup",
-            InputEventType::KeyPress => "keypress",
             InputEventType::Focus => "focus",
             InputEventType::Blur => "blur",
+            // keypress is dispatched by the DOM as part of keydown
             _ => return,
         };
 
-        // Find target element at mouse position (simplified)
-        if let Some(position) = event.position {
-            if let Some(target_id) = self.find_element_at_position(position) {
-                let dom_event = dom::event_types::Event::new(dom_event_type, true, true);
-                if let Some(target_node) = self.dom_event_manager.find_node_by_id(&target_id) {
-                    let _result = self.dom_event_manager.dispatch_event(&target_node, dom_event);
-                }
-            }
+        if let Some(target_node) = target {
+            let dom_event = dom::event_types::Event::new(dom_event_type, true, true);
+            let _result = self.dom_event_manager.handle_user_input(|manager| manager.dispatch_event(target_node, dom_event));
+        }
+    }
+
+    /// Fire the pointer event of a mouse move, press or release, returning
+    /// whether its compatibility mouse event should follow
+    ///
+    /// Only the first button pressed and the last one released give
+    /// `pointerdown` and `pointerup`; pressing or releasing another button
+    /// in between is a `pointermove`, as in Pointer Events. While a
+    /// cancelled `pointerdown` is held, mouse events other than `click`
+    /// are not fired.
+    fn dispatch_pointer_event(&mut self, event: &InputEvent, target: &Rc<Node>) -> bool {
+        let buttons = self.pressed_buttons();
+        let event_type = match event.event_type {
+            InputEventType::MouseDown if Some(buttons) == event.button.map(button_bit) => "pointerdown",
+            InputEventType::MouseUp if buttons == 0 => "pointerup",
+            _ => "pointermove",
+        };
+        let (x, y) = event.position.unwrap_or(self.mouse_position);
+        let mut pointer = SyntheticEventFactory::create_pointer_event(event_type, MOUSE_POINTER_ID, "mouse", x, y, buttons);
+        pointer.mouse.button = match (&event.event_type, event.button) {
+            (InputEventType::MouseDown | InputEventType::MouseUp, Some(button)) => dom_button(button),
+            _ => -1,
+        };
+        pointer.mouse.ctrl_key = event.modifiers.ctrl;
+        pointer.mouse.shift_key = event.modifiers.shift;
+        pointer.mouse.alt_key = event.modifiers.alt;
+        pointer.mouse.meta_key = event.modifiers.meta;
+
+        let not_cancelled = self.dom_event_manager.handle_user_input(|manager| manager.dispatch_pointer_event(target, pointer));
+        if event_type == "pointerdown" {
+            self.suppress_mouse_events = !not_cancelled;
+        }
+        let fire_mouse_event = !self.suppress_mouse_events;
+        if event_type == "pointerup" {
+            self.suppress_mouse_events = false;
         }
+        fire_mouse_event
+    }
+
+    /// Get the DOM `buttons` bitmask of the pressed mouse buttons
+    fn pressed_buttons(&self) -> u32 {
+        self.mouse_buttons
+            .iter()
+            .filter(|(_, pressed)| **pressed)
+            .map(|(button, _)| button_bit(*button))
+            .fold(0, |buttons, bit| buttons | bit)
     }
 
-    /// Find element at mouse position (simplified implementation)
-    fn find_element_at_position(&self, _position: (f64, f64)) -> Option<String> {
-        // This is a simplified implementation
-        // In a real browser, this would involve hit testing against the layout tree
-        Some("btn-red".to_string()) // Placeholder
+    /// Find the element at a window position by hit testing the layout tree
+    ///
+    /// Inert elements, such as those behind a modal dialog, are not hit.
+    fn find_element_at_position(&self, position: (f64, f64)) -> Option<Rc<Node>> {
+        self.layout_tree
+            .as_ref()?
+            .hit_test(position.0 as f32, position.1 as f32)
+            .filter(|node| !node.is_inert())
     }
 
     /// Call registered event callbacks
@@ -315,19 +1038,23 @@ impl InputHandler {
     /// Get current keyboard modifiers
     fn get_current_modifiers(&self) -> KeyModifiers {
         KeyModifiers {
-            ctrl: self.keyboard_keys.get(&KeyCode::ControlLeft).copied().unwrap_or(false) ||
+            ctrl: self.modifiers_state.control_key() ||
+                  self.keyboard_keys.get(&KeyCode::ControlLeft).copied().unwrap_or(false) ||
                   self.keyboard_keys.get(&KeyCode::ControlRight).copied().unwrap_or(false),
-            alt: self.keyboard_keys.get(&KeyCode::AltLeft).copied().unwrap_or(false) ||
+            alt: self.modifiers_state.alt_key() ||
+                 self.keyboard_keys.get(&KeyCode::AltLeft).copied().unwrap_or(false) ||
                  self.keyboard_keys.get(&KeyCode::AltRight).copied().unwrap_or(false),
-            shift: self.keyboard_keys.get(&KeyCode::ShiftLeft).copied().unwrap_or(false) ||
+            shift: self.modifiers_state.shift_key() ||
+                   self.keyboard_keys.get(&KeyCode::ShiftLeft).copied().unwrap_or(false) ||
                    self.keyboard_keys.get(&KeyCode::ShiftRight).copied().unwrap_or(false),
-            meta: self.keyboard_keys.get(&KeyCode::SuperLeft).copied().unwrap_or(false) ||
+            meta: self.modifiers_state.super_key() ||
+                  self.keyboard_keys.get(&KeyCode::SuperLeft).copied().unwrap_or(false) ||
                   self.keyboard_keys.get(&KeyCode::SuperRight).copied().unwrap_or(false),
         }
     }
 
     /// Check if a key is printable
-    fn is_printable_key(&self, key: KeyCode) -> bool {
+    pub fn is_printable_key(&self, key: KeyCode) -> bool {
         matches!(key,
             KeyCode::KeyA | KeyCode::KeyB | KeyCode::KeyC | KeyCode::KeyD |
             KeyCode::KeyE | KeyCode::KeyF | KeyCode::KeyG | KeyCode::KeyH |
@@ -379,6 +1106,70 @@ impl InputHandler {
     }
 }
 
+/// Get the DOM `button` number of a mouse button
+fn dom_button(button: MouseButton) -> i32 {
+    match button {
+        MouseButton::Left => 0,
+        MouseButton::Middle => 1,
+        MouseButton::Right => 2,
+        MouseButton::Back => 3,
+        MouseButton::Forward => 4,
+        MouseButton::Other(other) => i32::from(other),
+    }
+}
+
+/// Get the bit of a mouse button in the DOM `buttons` bitmask
+fn button_bit(button: MouseButton) -> u32 {
+    match button {
+        MouseButton::Left => 1,
+        MouseButton::Right => 2,
+        MouseButton::Middle => 4,
+        MouseButton::Back => 8,
+        MouseButton::Forward => 16,
+        MouseButton::Other(_) => 0,
+    }
+}
+
+/// The clipboard event triggered by a key combination, if any
+///
+/// Accepts both Control (Windows, Linux) and Meta (macOS) shortcuts.
+fn clipboard_shortcut(init: &KeyboardEventInit) -> Option<&'static str> {
+    if !(init.ctrl_key || init.meta_key) || init.alt_key {
+        return None;
+    }
+    match init.key.to_ascii_lowercase().as_str() {
+        "c" => Some("copy"),
+        "x" => Some("cut"),
+        "v" => Some("paste"),
+        _ => None,
+    }
+}
+
+/// The DOM `key` value of a logical key
+fn dom_key(key: &Key) -> String {
+    match key {
+        Key::Character(text) => text.to_string(),
+        Key::Named(NamedKey::Space) => " ".to_string(),
+        Key::Named(NamedKey::Super) => "Meta".to_string(),
+        Key::Named(named) => format!("{:?}", named),
+        Key::Dead(_) => "Dead".to_string(),
+        Key::Unidentified(_) => "Unidentified".to_string(),
+    }
+}
+
+/// The DOM `code` value of a physical key
+///
+/// winit's key codes follow the UI Events code names, except that it calls
+/// the OS keys "Super".
+fn dom_code(key: &PhysicalKey) -> String {
+    match key {
+        PhysicalKey::Code(KeyCode::SuperLeft) => "MetaLeft".to_string(),
+        PhysicalKey::Code(KeyCode::SuperRight) => "MetaRight".to_string(),
+        PhysicalKey::Code(code) => format!("{:?}", code),
+        PhysicalKey::Unidentified(_) => String::new(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -420,4 +1211,294 @@ mod tests {
         assert!(!handler.is_printable_key(KeyCode::Escape));
         assert!(!handler.is_printable_key(KeyCode::F1));
     }
+
+    fn pointer_event(event_type: InputEventType, position: (f64, f64)) -> InputEvent {
+        InputEvent {
+            event_type,
+            position: Some(position),
+            button: Some(MouseButton::Left),
+            key: None,
+            keyboard: None,
+            modifiers: KeyModifiers::default(),
+            timestamp: std::time::Instant::now(),
+        }
+    }
+
+    #[test]
+    fn test_hover_and_active_restyle_and_repaint() {
+        let document = dom::Document::new();
+        let button = document.create_element("button");
+        button.set_attribute("style", "width: 80px; height: 30px");
+        document.root.append_child(&button);
+
+        let stylesheet = css_parser::parse_css("button:hover {\n  background-color: #dddddd;\n}\nbutton:active {\n  padding: 4px;\n}");
+        let layout_tree = layout::LayoutEngine::new(stylesheet.clone()).layout_document(&document);
+
+        let mut handler = InputHandler::new();
+        handler.set_layout_tree(layout_tree);
+        handler.set_stylesheet(stylesheet);
+
+        handler.process_input_event(pointer_event(InputEventType::MouseMove, (10.0, 10.0)));
+        assert!(button.is_hovered());
+        assert_eq!(handler.hovered_node().map(|node| node.id), Some(button.id));
+        assert_eq!(handler.take_interaction_update(), InteractionUpdate { repaint: true, relayout: false });
+        let styles = &handler.layout_tree().unwrap().children[0].styles;
+        assert_eq!(styles.background_color.as_deref(), Some("#dddddd"));
+
+        // Moving within the same element needs no new work
+        handler.process_input_event(pointer_event(InputEventType::MouseMove, (20.0, 10.0)));
+        assert_eq!(handler.take_interaction_update(), InteractionUpdate::default());
+
+        // :active changes geometry, so layout must rerun
+        handler.process_input_event(pointer_event(InputEventType::MouseDown, (20.0, 10.0)));
+        assert!(button.is_active());
+        assert_eq!(handler.take_interaction_update(), InteractionUpdate { repaint: true, relayout: true });
+
+        handler.process_input_event(pointer_event(InputEventType::MouseUp, (20.0, 10.0)));
+        assert!(!button.is_active());
+    }
+
+    #[test]
+    fn test_arrow_keys_move_focus_with_spatial_navigation() {
+        let document = Rc::new(dom::Document::new());
+        let body = document.create_element("body");
+        document.root.append_child(&body);
+        let link = document.create_element("a");
+        link.set_attribute("href", "/next");
+        link.set_attribute("style", "width: 100px; height: 20px");
+        let field = document.create_element("input");
+        field.set_attribute("style", "width: 100px; height: 20px");
+        body.append_child(&link);
+        body.append_child(&field);
+
+        let layout_tree = layout::LayoutEngine::new(css_parser::parse_css("")).layout_document(&document);
+        let mut manager = DomEventManager::new();
+        manager.set_document(Rc::clone(&document));
+        let mut handler = InputHandler::new();
+        handler.set_dom_event_manager(manager);
+        handler.set_layout_tree(layout_tree);
+        let arrow = |handler: &mut InputHandler, key: &str| {
+            handler.handle_key(None, KeyboardEventInit::new(key, key), true);
+        };
+
+        // Off by default
+        arrow(&mut handler, "ArrowDown");
+        assert!(handler.focused_node().is_none());
+
+        handler.set_spatial_navigation(true);
+        arrow(&mut handler, "ArrowDown");
+        assert_eq!(handler.focused_node().map(|node| node.id), Some(link.id));
+        assert!(link.element_state().contains(dom::ElementState::FOCUS));
+        arrow(&mut handler, "ArrowDown");
+        assert_eq!(handler.focused_node().map(|node| node.id), Some(field.id));
+
+        // Inside a text field the arrows move the caret
+        arrow(&mut handler, "ArrowUp");
+        assert_eq!(handler.focused_node().map(|node| node.id), Some(field.id));
+        assert!(handler.navigate_focus(NavigationDirection::Up));
+        assert_eq!(handler.focused_node().map(|node| node.id), Some(link.id));
+    }
+
+    #[test]
+    fn test_key_events_reach_focused_field() {
+        let document = Rc::new(dom::Document::new());
+        let body = document.create_element("body");
+        let field = document.create_element("input");
+        field.set_attribute("style", "width: 100px; height: 20px");
+        document.root.append_child(&body);
+        body.append_child(&field);
+
+        let layout_tree = layout::LayoutEngine::new(css_parser::parse_css("")).layout_document(&document);
+        let mut manager = DomEventManager::new();
+        manager.set_document(Rc::clone(&document));
+        let mut handler = InputHandler::new();
+        handler.set_dom_event_manager(manager);
+        handler.set_layout_tree(layout_tree);
+
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        for event_type in ["keydown", "keypress", "keyup"] {
+            let seen = Arc::clone(&seen);
+            handler.add_event_callback(event_type, move |event| {
+                let key = event.keyboard.as_ref().map(|init| init.key.clone()).unwrap_or_default();
+                seen.lock().unwrap().push(format!("{:?} {}", event.event_type, key));
+            });
+        }
+
+        // Nothing is focused yet, so keys go to the body
+        handler.handle_key(Some(KeyCode::KeyX), KeyboardEventInit::new("x", "KeyX"), true);
+        assert_eq!(field.value(), "");
+
+        handler.process_input_event(pointer_event(InputEventType::MouseDown, (5.0, 5.0)));
+        assert_eq!(handler.focused_node().map(|node| node.id), Some(field.id));
+        seen.lock().unwrap().clear();
+
+        handler.handle_key(Some(KeyCode::KeyA), KeyboardEventInit::new("a", "KeyA"), true);
+        handler.handle_key(Some(KeyCode::KeyA), KeyboardEventInit::new("a", "KeyA"), false);
+        handler.handle_key(Some(KeyCode::ArrowLeft), KeyboardEventInit::new("ArrowLeft", "ArrowLeft"), true);
+        assert_eq!(field.value(), "a");
+        assert_eq!(*seen.lock().unwrap(), vec!["KeyDown a", "KeyPress a", "KeyUp a", "KeyDown ArrowLeft"]);
+
+        // IME preedit text is replaced on commit
+        handler.handle_window_event(&WindowEvent::Ime(Ime::Preedit("k".to_string(), None)));
+        handler.handle_window_event(&WindowEvent::Ime(Ime::Preedit("か".to_string(), None)));
+        assert_eq!(field.value(), "aか");
+        handler.handle_window_event(&WindowEvent::Ime(Ime::Preedit(String::new(), None)));
+        handler.handle_window_event(&WindowEvent::Ime(Ime::Commit("火".to_string())));
+        assert_eq!(field.value(), "a火");
+    }
+
+    #[test]
+    fn test_dom_key_and_code_mapping() {
+        assert_eq!(dom_key(&Key::Character("Q".into())), "Q");
+        assert_eq!(dom_key(&Key::Named(NamedKey::Space)), " ");
+        assert_eq!(dom_key(&Key::Named(NamedKey::ArrowUp)), "ArrowUp");
+        assert_eq!(dom_key(&Key::Named(NamedKey::Super)), "Meta");
+        assert_eq!(dom_code(&PhysicalKey::Code(KeyCode::KeyQ)), "KeyQ");
+        assert_eq!(dom_code(&PhysicalKey::Code(KeyCode::Numpad7)), "Numpad7");
+        assert_eq!(dom_code(&PhysicalKey::Code(KeyCode::SuperLeft)), "MetaLeft");
+    }
+
+    #[test]
+    fn test_clipboard_shortcuts() {
+        let document = Rc::new(dom::Document::new());
+        let body = document.create_element("body");
+        let field = document.create_element("input");
+        field.set_attribute("value", "copy me");
+        document.root.append_child(&body);
+        body.append_child(&field);
+
+        let mut manager = DomEventManager::new();
+        manager.set_document(Rc::clone(&document));
+        let mut handler = InputHandler::new();
+        handler.set_dom_event_manager(manager);
+        let clipboard = Clipboard::in_memory();
+        handler.set_clipboard(clipboard.clone());
+        handler.update_focus(Some(&field));
+
+        let mut copy = KeyboardEventInit::new("c", "KeyC");
+        copy.ctrl_key = true;
+        handler.handle_key(Some(KeyCode::KeyC), copy, true);
+        assert_eq!(clipboard.read_text().unwrap(), "copy me");
+
+        // Meta+V pastes on macOS
+        let mut paste = KeyboardEventInit::new("v", "KeyV");
+        paste.meta_key = true;
+        handler.handle_key(Some(KeyCode::KeyV), paste, true);
+        assert_eq!(field.value(), "copy mecopy me");
+    }
+
+    #[test]
+    fn test_wheel_fast_path_and_scroll_events() {
+        use dom::event_types::{EventListener, EventListenerOptions};
+
+        let document = Rc::new(dom::Document::new());
+        let panel = document.create_element("div");
+        panel.set_attribute("style", "width: 200px; height: 100px");
+        document.root.append_child(&panel);
+        let layout_tree = layout::LayoutEngine::new(css_parser::parse_css("")).layout_document(&document);
+
+        let mut manager = DomEventManager::new();
+        manager.set_document(Rc::clone(&document));
+        manager.enable_dispatch_log();
+        let passive = EventListenerOptions { passive: true, ..EventListenerOptions::default() };
+        manager.add_event_listener(&panel, "wheel", EventListener { callback: "f".to_string(), options: passive, id: 1 });
+
+        let mut handler = InputHandler::new();
+        handler.set_dom_event_manager(manager);
+        handler.set_layout_tree(layout_tree);
+        handler.mouse_position = (10.0, 10.0);
+        let viewport = layout::Dimensions::new(0.0, 0.0, 800.0, 600.0);
+        let mut compositor = ScrollCompositor::new(Vec::new(), viewport, (800.0, 2000.0));
+
+        // Two wheel ticks in one frame fire one scroll event
+        assert!(handler.handle_wheel((0.0, 40.0), &mut compositor));
+        assert!(handler.handle_wheel((0.0, 40.0), &mut compositor));
+        assert_eq!(compositor.scroll_offset(), (0.0, 80.0));
+        assert_eq!(handler.dispatch_frame_events(), 1);
+        assert_eq!(handler.get_stats().wheel_fast_path, 2);
+
+        // A blocking listener makes the scroll wait for dispatch
+        handler.dom_event_manager.add_event_listener(
+            &panel,
+            "wheel",
+            EventListener { callback: "g".to_string(), options: EventListenerOptions::default(), id: 2 },
+        );
+        assert!(handler.handle_wheel((0.0, 40.0), &mut compositor));
+        assert_eq!(handler.get_stats().wheel_blocked, 1);
+        assert!(handler.get_stats().mean_wheel_latency().is_some());
+        assert_eq!(handler.dispatch_frame_events(), 1);
+        assert_eq!(handler.dom_event_manager.take_dispatch_log(), vec!["wheel", "wheel", "scroll", "wheel", "scroll"]);
+    }
+
+    #[test]
+    fn test_touch_taps_click_and_pans_scroll() {
+        let document = Rc::new(dom::Document::new());
+        let button = document.create_element("button");
+        button.set_attribute("style", "width: 200px; height: 100px");
+        document.root.append_child(&button);
+        let layout_tree = layout::LayoutEngine::new(css_parser::parse_css("")).layout_document(&document);
+
+        let mut manager = DomEventManager::new();
+        manager.set_document(Rc::clone(&document));
+        manager.enable_dispatch_log();
+        let mut handler = InputHandler::new();
+        handler.set_dom_event_manager(manager);
+        handler.set_layout_tree(layout_tree);
+        let viewport = layout::Dimensions::new(0.0, 0.0, 800.0, 600.0);
+        let mut compositor = ScrollCompositor::new(Vec::new(), viewport, (800.0, 2000.0));
+
+        // A tap clicks when the finger lifts, without a delay
+        assert!(!handler.handle_touch(4, WinitTouchPhase::Started, (10.0, 10.0), &mut compositor));
+        assert!(!handler.handle_touch(4, WinitTouchPhase::Ended, (12.0, 10.0), &mut compositor));
+        assert_eq!(
+            handler.dom_event_manager.take_dispatch_log(),
+            vec!["pointerdown", "touchstart", "pointerup", "touchend", "focus", "mousemove", "mousedown", "mouseup", "click"]
+        );
+
+        // Dragging a finger up scrolls down and does not click
+        handler.handle_touch(5, WinitTouchPhase::Started, (10.0, 90.0), &mut compositor);
+        assert!(handler.handle_touch(5, WinitTouchPhase::Moved, (10.0, 40.0), &mut compositor));
+        handler.handle_touch(5, WinitTouchPhase::Ended, (10.0, 40.0), &mut compositor);
+        assert_eq!(compositor.scroll_offset(), (0.0, 50.0));
+        assert!(!handler.dom_event_manager.take_dispatch_log().contains(&"click".to_string()));
+        assert_eq!(handler.dispatch_frame_events(), 1);
+        assert_eq!(handler.get_stats().touch_events, 5);
+    }
+
+    #[test]
+    fn test_gamepads_are_polled_into_slots() {
+        use dom::gamepad::GamepadSnapshot;
+
+        /// Plays back one poll result per frame
+        struct ScriptedGamepads(Vec<Vec<GamepadSnapshot>>);
+
+        impl GamepadBackend for ScriptedGamepads {
+            fn poll(&mut self) -> Vec<GamepadSnapshot> {
+                if self.0.is_empty() { Vec::new() } else { self.0.remove(0) }
+            }
+        }
+
+        let pad = |pressed: bool| GamepadSnapshot {
+            device: 7,
+            name: "Pad".to_string(),
+            standard_mapping: true,
+            axes: vec![0.0; 4],
+            buttons: vec![dom::gamepad::GamepadButton { pressed, touched: pressed, value: f64::from(u8::from(pressed)) }],
+        };
+        let document = Rc::new(dom::Document::new());
+        let mut manager = DomEventManager::new();
+        manager.set_document(Rc::clone(&document));
+        manager.enable_dispatch_log();
+        let mut handler = InputHandler::new();
+        handler.set_dom_event_manager(manager);
+        assert_eq!(handler.update_gamepads(), 0);
+
+        handler.set_gamepad_backend(Box::new(ScriptedGamepads(vec![vec![pad(false)], vec![pad(true)], Vec::new()])));
+        assert_eq!(handler.update_gamepads(), 1);
+        assert_eq!(handler.update_gamepads(), 0);
+        assert!(handler.gamepads()[0].as_ref().is_some_and(|gamepad| gamepad.buttons[0].pressed));
+        assert_eq!(handler.update_gamepads(), 1);
+        assert!(handler.gamepads()[0].is_none());
+        assert_eq!(handler.dom_event_manager.take_dispatch_log(), vec!["gamepadconnected", "gamepaddisconnected"]);
+    }
 }
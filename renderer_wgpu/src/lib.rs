@@ -5,9 +5,10 @@
 //! and visual representation of the CSS box model.
 
 use winit::{
-    event::{Event, WindowEvent},
-    event_loop::EventLoop,
-    window::{Window, WindowBuilder},
+    event::{ElementState, Event, MouseScrollDelta, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    keyboard::{KeyCode, PhysicalKey},
+    window::{Fullscreen, Window, WindowBuilder},
 };
 use wgpu::{
     util::DeviceExt, Backends, Device, Instance, PresentMode, Queue, RenderPipeline,
@@ -19,6 +20,7 @@ use thiserror::Error;
 use wgpu_glyph::{GlyphBrush};
 use std::fs::File;
 use std::io::Write;
+use layout::{Dimensions, ScrollCompositor};
 
 // Event-driven rendering module
 pub mod event_driven_renderer;
@@ -29,6 +31,39 @@ pub mod input_handler;
 // Simple async event loop integration module
 pub mod async_event_loop_simple;
 
+// `filter` passes over composited layers
+pub mod filters;
+
+// `mix-blend-mode` and `isolation` in the compositing pass
+pub mod blending;
+
+// `clip-path` shapes and `mask-image` sources in the compositing pass
+pub mod masking;
+
+// Canvas textures WebGL commands are replayed on
+pub mod webgl;
+
+// Antialiasing, hinting and gamma settings, and the glyph atlas
+pub mod text_quality;
+
+// One memory budget shared by the renderer's caches
+pub mod memory;
+
+// Textures of unchanged stacking contexts reused across frames
+pub mod raster_cache;
+
+// Device pixel snapping of fractional page geometry at paint time
+pub mod snapping;
+
+// CPU painted page snapshots, encoded as PNG
+pub mod snapshot;
+
+// Debug overlay flashing repainted regions and relayout boundaries
+pub mod paint_flashing;
+
+// Layout reftests comparing pages with their references
+pub mod reftest;
+
 /// Custom error types for GPU rendering
 #[derive(Error, Debug)]
 pub enum RenderError {
@@ -57,6 +92,15 @@ pub enum RenderError {
     PipelineCreationFailed(String),
 }
 
+/// Pixels scrolled per mouse wheel line
+const SCROLL_LINE_HEIGHT: f32 = 40.0;
+
+/// How often gamepads are polled while nothing else wakes the event loop
+const GAMEPAD_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(16);
+
+/// Color the window is cleared to behind the page
+const PAGE_BACKGROUND: [f32; 3] = [0.95, 0.95, 0.95];
+
 /// Result type for rendering operations
 pub type RenderResult<T> = Result<T, RenderError>;
 
@@ -89,6 +133,27 @@ impl Vertex {
     }
 }
 
+/// Get the fragment entry point of `shader.wgsl` for a target format
+///
+/// Vertex colors are sRGB, as CSS specifies them. An sRGB target encodes
+/// what the shader writes, so `fs_srgb` decodes the colors to linear
+/// first; writing them unchanged would encode them twice and wash them out.
+pub fn fragment_entry_point(format: TextureFormat) -> &'static str {
+    if format.is_srgb() {
+        "fs_srgb"
+    } else {
+        "fs_main"
+    }
+}
+
+/// Convert an sRGB clear color to what a target format expects
+///
+/// Clear values bypass the shader, and sRGB targets take them linear.
+pub fn clear_color(rgb: [f32; 3], format: TextureFormat) -> wgpu::Color {
+    let [r, g, b] = if format.is_srgb() { rgb.map(css_parser::color::srgb_to_linear) } else { rgb }.map(f64::from);
+    wgpu::Color { r, g, b, a: 1.0 }
+}
+
 /// Simple GPU renderer for layout boxes
 pub struct GpuRenderer {
     device: Device,
@@ -172,7 +237,7 @@ impl GpuRenderer {
             },
             fragment: Some(wgpu::FragmentState {
                 module: &shader,
-                entry_point: "fs_main",
+                entry_point: fragment_entry_point(surface_format),
                 targets: &[Some(wgpu::ColorTargetState {
                     format: surface_format,
                     blend: Some(wgpu::BlendState::REPLACE),
@@ -213,10 +278,7 @@ impl GpuRenderer {
 
 /// Extract text content from a layout box
 fn extract_text_content(layout_box: &layout::LayoutBox) -> String {
-    match &layout_box.node.node_type {
-        dom::NodeType::Text(text) => text.clone(),
-        _ => String::new(),
-    }
+    layout_box.node.character_data().unwrap_or_default()
 }
 
 /// Render text using wgpu_glyph (placeholder for now)
@@ -236,6 +298,9 @@ fn render_text(
 }
 
 /// Add debug overlay vertices showing box model (margins, borders, content)
+/// 
+/// Sticky boxes, and everything inside them, are moved by the offset the
+/// compositor computed for the current scroll position.
 fn add_debug_overlay_vertices(
     layout_box: &layout::LayoutBox,
     vertices: &mut Vec<Vertex>,
@@ -243,10 +308,12 @@ fn add_debug_overlay_vertices(
     vertex_offset: &mut u16,
     parent_x: f32,
     parent_y: f32,
+    compositor: &ScrollCompositor,
 ) {
     // Use the border dimensions as the main box dimensions
-    let x = layout_box.border.x + parent_x;
-    let y = layout_box.border.y + parent_y;
+    let (sticky_x, sticky_y) = compositor.sticky_offset(layout_box.node.id);
+    let x = layout_box.border.x + parent_x + sticky_x;
+    let y = layout_box.border.y + parent_y + sticky_y;
     let width = layout_box.border.width;
     let height = layout_box.border.height;
 
@@ -324,7 +391,38 @@ fn add_debug_overlay_vertices(
 
     // Recursively add debug overlay for children
     for child in &layout_box.children {
-        add_debug_overlay_vertices(child, vertices, indices, vertex_offset, x, y);
+        add_debug_overlay_vertices(child, vertices, indices, vertex_offset, x, y, compositor);
+    }
+}
+
+/// Add an outline of a page rectangle, `thickness` CSS pixels wide, in
+/// the debug overlay's coordinates
+///
+/// Each side is two triangles of their own, since overlay vertices are
+/// drawn without an index buffer.
+fn add_outline_vertices(vertices: &mut Vec<Vertex>, bounds: &Dimensions, thickness: f32, color: [f32; 3], scroll: (f32, f32)) {
+    let scale_factor = 0.001;
+    let x = bounds.x - scroll.0;
+    let y = bounds.y - scroll.1;
+    let sides = [
+        (x, y, bounds.width, thickness),
+        (x, y + bounds.height - thickness, bounds.width, thickness),
+        (x, y, thickness, bounds.height),
+        (x + bounds.width - thickness, y, thickness, bounds.height),
+    ];
+    for (left, top, width, height) in sides {
+        let ndc_left = left * scale_factor - 0.5;
+        let ndc_top = 0.5 - top * scale_factor;
+        let ndc_right = ndc_left + width * scale_factor;
+        let ndc_bottom = ndc_top - height * scale_factor;
+        vertices.extend_from_slice(&[
+            Vertex { position: [ndc_left, ndc_top], color },
+            Vertex { position: [ndc_right, ndc_top], color },
+            Vertex { position: [ndc_left, ndc_bottom], color },
+            Vertex { position: [ndc_right, ndc_top], color },
+            Vertex { position: [ndc_right, ndc_bottom], color },
+            Vertex { position: [ndc_left, ndc_bottom], color },
+        ]);
     }
 }
 
@@ -566,10 +664,11 @@ pub async fn render_layout_tree_offscreen(
     let mut vertex_offset = 0;
 
     // Add layout box vertices
-    add_layout_box_vertices(layout_root, &mut vertices, &mut indices, &mut vertex_offset, 0.0, 0.0);
+    add_layout_box_vertices(layout_root, &mut vertices, &mut indices, &mut vertex_offset, 0.0, 0.0, &[]);
     
     // Add debug overlay vertices
-    add_debug_overlay_vertices(layout_root, &mut vertices, &mut indices, &mut vertex_offset, 0.0, 0.0);
+    let compositor = ScrollCompositor::from_layout(layout_root, Dimensions::new(0.0, 0.0, width as f32, height as f32));
+    add_debug_overlay_vertices(layout_root, &mut vertices, &mut indices, &mut vertex_offset, 0.0, 0.0, &compositor);
 
     if !vertices.is_empty() {
         let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -596,12 +695,7 @@ pub async fn render_layout_tree_offscreen(
                     view: &view,
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.1,
-                            b: 0.1,
-                            a: 1.0,
-                        }),
+                        load: wgpu::LoadOp::Clear(clear_color([0.1, 0.1, 0.1], surface_format)),
                         store: wgpu::StoreOp::Store,
                     },
                 })],
@@ -627,6 +721,17 @@ pub async fn render_layout_tree_offscreen(
 
 /// Render a layout tree to a GPU window
 pub async fn render_layout_tree(layout_root: &layout::LayoutBox) -> RenderResult<()> {
+    render_layout_tree_with_input(layout_root, input_handler::InputHandler::new()).await
+}
+
+/// Render a layout tree to a GPU window, handling its input with
+/// `input_handler`
+///
+/// The shell hands in an input handler set up with its clipboard.
+pub async fn render_layout_tree_with_input(
+    layout_root: &layout::LayoutBox,
+    mut input_handler: input_handler::InputHandler,
+) -> RenderResult<()> {
     let event_loop = EventLoop::new().unwrap();
     let window = WindowBuilder::new()
         .with_title("Browser Engine - Layout Renderer")
@@ -691,6 +796,17 @@ pub async fn render_layout_tree(layout_root: &layout::LayoutBox) -> RenderResult
 
     // Store window ID for comparison
     let window_id = window.id();
+    let window = &window;
+
+    // Scrolling only moves sticky boxes at paint time; layout is not rerun
+    let mut compositor = ScrollCompositor::from_layout(
+        layout_root,
+        Dimensions::new(0.0, 0.0, size.width as f32, size.height as f32),
+    );
+    input_handler.set_layout_tree(layout_root.clone());
+    input_handler.apply_spatial_navigation_preference();
+    let mut window_fullscreen = false;
+    let mut paint_flashing = paint_flashing::PaintFlashing::from_env();
 
     event_loop.run(move |event, elwt| {
         match event {
@@ -704,9 +820,65 @@ pub async fn render_layout_tree(layout_root: &layout::LayoutBox) -> RenderResult
                         config.width = physical_size.width;
                         config.height = physical_size.height;
                         surface.configure(&device, &config);
+                        compositor.set_viewport(Dimensions::new(
+                            0.0,
+                            0.0,
+                            physical_size.width as f32,
+                            physical_size.height as f32,
+                        ));
+                    }
+                }
+                WindowEvent::MouseWheel { delta, .. } => {
+                    let (dx, dy) = match delta {
+                        MouseScrollDelta::LineDelta(x, y) => (-x * SCROLL_LINE_HEIGHT, -y * SCROLL_LINE_HEIGHT),
+                        MouseScrollDelta::PixelDelta(position) => (-position.x as f32, -position.y as f32),
+                    };
+                    if input_handler.handle_wheel((dx, dy), &mut compositor) {
+                        window.request_redraw();
+                    }
+                }
+                WindowEvent::Touch(touch) => {
+                    let position = (touch.location.x, touch.location.y);
+                    if input_handler.handle_touch(touch.id, touch.phase, position, &mut compositor) {
+                        window.request_redraw();
+                    }
+                }
+                WindowEvent::CursorMoved { .. } | WindowEvent::MouseInput { .. } => {
+                    match input_handler.handle_emulated_touch(event, &mut compositor) {
+                        Some(true) => window.request_redraw(),
+                        Some(false) => {}
+                        None => {
+                            input_handler.handle_window_event(event);
+                        }
+                    }
+                }
+                WindowEvent::KeyboardInput { event: key_event, .. }
+                    if key_event.state == ElementState::Pressed && key_event.physical_key == PhysicalKey::Code(KeyCode::F9) =>
+                {
+                    let enabled = paint_flashing.toggle();
+                    println!("🎨 Paint flashing {}", if enabled { "on" } else { "off" });
+                    window.request_redraw();
+                }
+                WindowEvent::KeyboardInput { event: key_event, .. }
+                    if key_event.state == ElementState::Pressed && key_event.physical_key == PhysicalKey::Code(KeyCode::F7) =>
+                {
+                    let enabled = !input_handler.spatial_navigation();
+                    input_handler.set_spatial_navigation(enabled);
+                    println!("🧭 Spatial navigation {}", if enabled { "on" } else { "off" });
+                }
+                WindowEvent::KeyboardInput { .. } | WindowEvent::ModifiersChanged(_) => {
+                    input_handler.handle_window_event(event);
+                }
+                WindowEvent::Occluded(occluded) => {
+                    // A minimized or covered window hides the page
+                    let state = dom::page_visibility::VisibilityState::from_hidden(*occluded);
+                    if input_handler.set_visibility_state(state) && !occluded {
+                        window.request_redraw();
                     }
                 }
                 WindowEvent::RedrawRequested => {
+                    input_handler.dispatch_frame_events();
+
                     // Render layout boxes
                     let output = match surface.get_current_texture() {
                         Ok(output) => output,
@@ -725,11 +897,23 @@ pub async fn render_layout_tree(layout_root: &layout::LayoutBox) -> RenderResult
                     let mut vertex_offset = 0;
 
                     // Traverse layout tree and create vertices for each box
-                    add_layout_box_vertices(layout_root, &mut vertices, &mut indices, &mut vertex_offset, 0.0, 0.0);
+                    add_layout_box_vertices(layout_root, &mut vertices, &mut indices, &mut vertex_offset, 0.0, 0.0, &[]);
                     
-                    // Add debug overlay vertices
-                    add_debug_overlay_vertices(layout_root, &mut vertices, &mut indices, &mut vertex_offset, 0.0, 0.0);
-
+                    // Add debug overlay vertices, scrolled
+                    let (scroll_x, scroll_y) = compositor.scroll_offset();
+                    add_debug_overlay_vertices(layout_root, &mut vertices, &mut indices, &mut vertex_offset, -scroll_x, -scroll_y, &compositor);
+
+                    // Flash what this frame repainted, on top of everything
+                    if paint_flashing.is_enabled() {
+                        let painted = input_handler.layout_tree().unwrap_or(layout_root);
+                        paint_flashing.record_frame(painted);
+                        for boundary in paint_flashing::relayout_boundaries(painted) {
+                            add_outline_vertices(&mut vertices, &boundary, 1.0, paint_flashing::BOUNDARY_COLOR, (scroll_x, scroll_y));
+                        }
+                        for flash in paint_flashing.flashes() {
+                            add_outline_vertices(&mut vertices, &flash.bounds, 4.0, flash.color(PAGE_BACKGROUND), (scroll_x, scroll_y));
+                        }
+                    }
 
                     if !vertices.is_empty() {
                         let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -745,12 +929,7 @@ pub async fn render_layout_tree(layout_root: &layout::LayoutBox) -> RenderResult
                                     view: &view,
                                     resolve_target: None,
                                     ops: wgpu::Operations {
-                                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                                            r: 0.95,
-                                            g: 0.95,
-                                            b: 0.95,
-                                            a: 1.0,
-                                        }),
+                                        load: wgpu::LoadOp::Clear(clear_color(PAGE_BACKGROUND, surface_format)),
                                         store: wgpu::StoreOp::Store,
                                     },
                                 })],
@@ -767,12 +946,29 @@ pub async fn render_layout_tree(layout_root: &layout::LayoutBox) -> RenderResult
 
                     queue.submit(std::iter::once(encoder.finish()));
                     output.present();
+                    // Keep painting while flashes fade out
+                    if paint_flashing.is_animating() {
+                        window.request_redraw();
+                    }
                 }
                 _ => {}
             },
             Event::AboutToWait => {
                 // Note: In winit 0.29, we need to handle redraw requests differently
                 // For now, we'll rely on the initial redraw request
+                if sync_window_fullscreen(window, &mut input_handler, &mut window_fullscreen) {
+                    window.request_redraw();
+                }
+                // Hover and focus restyles show up in the next frame
+                if input_handler.take_interaction_update().repaint {
+                    window.request_redraw();
+                }
+                // Gamepads are polled, so keep waking up while a backend
+                // is installed
+                if input_handler.has_gamepad_backend() {
+                    input_handler.update_gamepads();
+                    elwt.set_control_flow(ControlFlow::WaitUntil(std::time::Instant::now() + GAMEPAD_POLL_INTERVAL));
+                }
             }
             _ => {}
         }
@@ -781,7 +977,32 @@ pub async fn render_layout_tree(layout_root: &layout::LayoutBox) -> RenderResult
     Ok(())
 }
 
+/// Put the window in or out of fullscreen mode to match the document
+///
+/// `window_fullscreen` is the mode last set. A window that left fullscreen
+/// by other means, such as the window manager, takes the document out of
+/// fullscreen instead. Returns whether the mode changed.
+fn sync_window_fullscreen(window: &Window, input_handler: &mut input_handler::InputHandler, window_fullscreen: &mut bool) -> bool {
+    if *window_fullscreen && window.fullscreen().is_none() {
+        *window_fullscreen = false;
+        input_handler.exit_fullscreen();
+        return true;
+    }
+    let wanted = input_handler.fullscreen_element().is_some();
+    if wanted == *window_fullscreen {
+        return false;
+    }
+    window.set_fullscreen(wanted.then_some(Fullscreen::Borderless(None)));
+    *window_fullscreen = wanted;
+    true
+}
+
 /// Add vertices for a layout box and its children
+///
+/// `filters` are the filters of the box's ancestors, innermost first. The
+/// box's own filters run before them, and the color passes of the chain
+/// are applied to its vertex colors; blur and drop-shadow need a layer,
+/// see the `filters` module.
 fn add_layout_box_vertices(
     layout_box: &layout::LayoutBox,
     vertices: &mut Vec<Vertex>,
@@ -789,6 +1010,7 @@ fn add_layout_box_vertices(
     vertex_offset: &mut u16,
     parent_x: f32,
     parent_y: f32,
+    filters: &[layout::FilterFunction],
 ) {
     // Get the box dimensions and position from the content area
     let x = parent_x + layout_box.content.x;
@@ -867,8 +1089,10 @@ fn add_layout_box_vertices(
         layout::DisplayType::InlineBlock => [0.8, 0.0, 0.8], // Purple for inline-block elements
         layout::DisplayType::Flex => [1.0, 0.5, 0.0], // Orange for flex elements
         layout::DisplayType::Grid => [1.0, 0.0, 1.0], // Magenta for grid elements
-        layout::DisplayType::None => [0.5, 0.5, 0.5], // Gray for hidden elements
+        layout::DisplayType::Contents | layout::DisplayType::None => [0.5, 0.5, 0.5], // Gray for boxless elements
     };
+    let filters: Vec<layout::FilterFunction> = layout_box.styles.filter.iter().chain(filters).cloned().collect();
+    let color = filters::filter_rgb(color, &filters);
     
 
     // Create vertices for this box (two triangles)
@@ -893,7 +1117,7 @@ fn add_layout_box_vertices(
 
     // Recursively add children
     for child in &layout_box.children {
-        add_layout_box_vertices(child, vertices, indices, vertex_offset, x, y);
+        add_layout_box_vertices(child, vertices, indices, vertex_offset, x, y, &filters);
     }
 }
 
@@ -1014,12 +1238,7 @@ pub async fn run_gpu_test() -> RenderResult<()> {
                                 view: &view,
                                 resolve_target: None,
                                 ops: wgpu::Operations {
-                                    load: wgpu::LoadOp::Clear(wgpu::Color {
-                                        r: 0.1,
-                                        g: 0.2,
-                                        b: 0.3,
-                                        a: 1.0,
-                                    }),
+                                    load: wgpu::LoadOp::Clear(clear_color([0.1, 0.2, 0.3], surface_format)),
                                     store: wgpu::StoreOp::Store,
                                 },
                             })],
@@ -1065,4 +1284,17 @@ mod tests {
         assert_eq!(vertex.position, [0.0, 0.0]);
         assert_eq!(vertex.color, [1.0, 0.0, 0.0]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_srgb_targets_decode_colors() {
+        let module = naga::front::wgsl::parse_str(include_str!("shader.wgsl")).unwrap();
+        assert!(module.entry_points.iter().any(|entry| entry.name == "fs_srgb"));
+        assert_eq!(fragment_entry_point(TextureFormat::Bgra8UnormSrgb), "fs_srgb");
+        assert_eq!(fragment_entry_point(TextureFormat::Bgra8Unorm), "fs_main");
+
+        // Mid gray is about a fifth of full intensity in linear light
+        let clear = clear_color([0.5, 0.5, 0.5], TextureFormat::Rgba8UnormSrgb);
+        assert!((clear.r - 0.214).abs() < 0.001);
+        assert_eq!(clear_color([0.5, 0.5, 0.5], TextureFormat::Rgba8Unorm).r, 0.5);
+    }
+}
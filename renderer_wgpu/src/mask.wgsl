@@ -0,0 +1,29 @@
+// Compositing pass for layers with a clip path or mask image. The layer
+// texture holds premultiplied colors; the mask texture holds the coverage
+// of each pixel in its red channel.
+
+@group(0) @binding(0) var source_texture: texture_2d<f32>;
+@group(0) @binding(1) var mask_texture: texture_2d<f32>;
+@group(0) @binding(2) var layer_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// One triangle covering the target
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.uv = uv;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_mask(in: VertexOutput) -> @location(0) vec4<f32> {
+    let source = textureSample(source_texture, layer_sampler, in.uv);
+    let coverage = textureSample(mask_texture, layer_sampler, in.uv).r;
+    return source * coverage;
+}
@@ -0,0 +1,578 @@
+//! # Clipping and Masking
+//!
+//! This module clips and masks composited layers with `clip-path` shapes
+//! and `mask-image` sources. Both become a coverage mask the size of the
+//! layer, which `mask.wgsl` samples in the fragment shader and the CPU
+//! path multiplies into the layer's pixels.
+//!
+//! ## Design Principles
+//!
+//! 1. **One Mask Texture**: A shape is tessellated into triangles and
+//!    filled into coverage, and a gradient or image is rendered into
+//!    coverage from its alpha or luminance, so the shader has a single way
+//!    to mask.
+//! 2. **Tessellated by lyon**: lyon flattens curves and arcs and applies
+//!    the fill rule, so the triangles it returns only cover the inside of
+//!    the shape and are filled without a rule of their own.
+//! 3. **Antialiased Edges**: Triangles are filled on sub-scanlines with
+//!    exact horizontal coverage, so diagonal and curved edges are smooth
+//!    rather than stair-stepped, and edges shared by two triangles leave
+//!    no seam.
+//! 4. **Decoded Once**: Mask images are decoded by the `image` crate when
+//!    their bytes arrive and tiled at their natural size from the border
+//!    box's corner, as `mask-size: auto` and `mask-repeat: repeat` do. An
+//!    image that fails to decode masks as transparent black, and one that
+//!    has not arrived leaves its layer unmasked.
+
+use crate::filters::{parse_color, Layer};
+use layout::masking::{ClipPath, FillRule, GradientStop, MaskImage, MaskMode, PathCommand};
+use layout::Dimensions;
+use lyon::math::{point, vector, Angle, Point};
+use lyon::path::builder::SvgPathBuilder;
+use lyon::path::{ArcFlags, Path};
+use lyon::tessellation::{BuffersBuilder, FillOptions, FillTessellator, FillVertex, VertexBuffers};
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// Source of the mask shader
+pub const MASK_SHADER: &str = include_str!("mask.wgsl");
+
+/// Sub-scanlines per row of pixels when filling shapes
+const SUBSAMPLES: usize = 4;
+
+/// Largest distance between a curve and its flattened segments, in
+/// layer pixels
+const TOLERANCE: f32 = 0.1;
+
+/// Coverage of each pixel of a layer, from 0 (hidden) to 1 (shown)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mask {
+    pub width: usize,
+    pub height: usize,
+    pub coverage: Vec<f32>,
+}
+
+impl Mask {
+    /// Create a mask hiding everything
+    pub fn new(width: usize, height: usize) -> Self {
+        Mask { width, height, coverage: vec![0.0; width * height] }
+    }
+
+    /// Get the coverage at (x, y), hidden outside the mask
+    pub fn coverage(&self, x: usize, y: usize) -> f32 {
+        if x >= self.width || y >= self.height {
+            return 0.0;
+        }
+        self.coverage[y * self.width + x]
+    }
+
+    /// Bytes of an `R8Unorm` texture of the mask, for `mask.wgsl`
+    pub fn to_texture_bytes(&self) -> Vec<u8> {
+        self.coverage.iter().map(|coverage| (coverage.clamp(0.0, 1.0) * 255.0).round() as u8).collect()
+    }
+}
+
+/// A decoded mask image, in straight RGBA
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaskSource {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<[u8; 4]>,
+}
+
+impl MaskSource {
+    /// Decode an image file; what cannot be decoded is an empty image,
+    /// which masks as transparent black
+    pub fn decode(bytes: &[u8]) -> Self {
+        match image::load_from_memory(bytes) {
+            Ok(image) => {
+                let image = image.to_rgba8();
+                let (width, height) = (image.width() as usize, image.height() as usize);
+                MaskSource { width, height, pixels: image.pixels().map(|pixel| pixel.0).collect() }
+            }
+            Err(error) => {
+                println!("⚠️ Mask image could not be decoded: {}", error);
+                MaskSource { width: 0, height: 0, pixels: Vec::new() }
+            }
+        }
+    }
+
+    /// Get the coverage of the pixel at (x, y) in a mode
+    fn coverage(&self, x: usize, y: usize, mode: MaskMode) -> f32 {
+        let [r, g, b, a] = self.pixels[y * self.width + x].map(|channel| channel as f32 / 255.0);
+        match mode {
+            MaskMode::Alpha => a,
+            MaskMode::Luminance => luminance(r, g, b) * a,
+        }
+    }
+}
+
+/// Where a layer is in page coordinates, and how many device pixels it
+/// has per page pixel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LayerSpace {
+    pub origin_x: f32,
+    pub origin_y: f32,
+    pub scale: f32,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl LayerSpace {
+    /// Map a page point to layer pixels
+    fn map(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        ((x - self.origin_x) * self.scale, (y - self.origin_y) * self.scale)
+    }
+}
+
+/// Build the mask of a layer's clip path and mask image, relative to the
+/// border box `reference`; `url()` images are looked up in `sources`
+///
+/// Returns `None` when nothing masks the layer.
+pub fn layer_mask(
+    clip_path: Option<&ClipPath>,
+    mask_image: Option<&MaskImage>,
+    mask_mode: MaskMode,
+    sources: &HashMap<String, MaskSource>,
+    reference: &Dimensions,
+    space: LayerSpace,
+) -> Option<Mask> {
+    let clip = clip_path.map(|clip_path| fill_triangles(&tessellate(clip_path, reference, space), space.width, space.height));
+    let image = match mask_image {
+        Some(MaskImage::LinearGradient { angle, stops }) => Some(gradient_mask(*angle, stops, mask_mode, reference, space)),
+        Some(MaskImage::Url(url)) => sources.get(url).map(|source| image_mask(source, mask_mode, reference, space)),
+        None => None,
+    };
+    match (clip, image) {
+        (Some(mut clip), Some(image)) => {
+            for (coverage, alpha) in clip.coverage.iter_mut().zip(&image.coverage) {
+                *coverage *= alpha;
+            }
+            Some(clip)
+        }
+        (clip, image) => clip.or(image),
+    }
+}
+
+/// Multiply a layer's premultiplied pixels by a mask's coverage
+pub fn apply_mask(layer: &mut Layer, mask: &Mask) {
+    for y in 0..layer.height {
+        for x in 0..layer.width {
+            let coverage = mask.coverage(x, y);
+            for channel in &mut layer.pixels[y * layer.width + x] {
+                *channel *= coverage;
+            }
+        }
+    }
+}
+
+/// Build the outline of a clip path in layer pixels
+pub fn outline(clip_path: &ClipPath, reference: &Dimensions, space: LayerSpace) -> Path {
+    let to_layer = |x: f32, y: f32| {
+        let (x, y) = space.map((reference.x + x, reference.y + y));
+        point(x, y)
+    };
+    let mut builder = Path::svg_builder();
+    match clip_path {
+        ClipPath::Polygon { points, .. } => {
+            for (index, (x, y)) in points.iter().enumerate() {
+                let point = to_layer(x.resolve(reference.width), y.resolve(reference.height));
+                if index == 0 {
+                    builder.move_to(point);
+                } else {
+                    builder.line_to(point);
+                }
+            }
+            builder.close();
+        }
+        ClipPath::Path { commands, .. } => {
+            for command in commands {
+                match *command {
+                    PathCommand::MoveTo(x, y) => {
+                        builder.move_to(to_layer(x, y));
+                    }
+                    PathCommand::LineTo(x, y) => {
+                        builder.line_to(to_layer(x, y));
+                    }
+                    PathCommand::QuadTo(cx, cy, x, y) => {
+                        builder.quadratic_bezier_to(to_layer(cx, cy), to_layer(x, y));
+                    }
+                    PathCommand::CubicTo(c1x, c1y, c2x, c2y, x, y) => {
+                        builder.cubic_bezier_to(to_layer(c1x, c1y), to_layer(c2x, c2y), to_layer(x, y));
+                    }
+                    PathCommand::ArcTo(rx, ry, rotation, large_arc, sweep, x, y) => {
+                        let radii = vector(rx * space.scale, ry * space.scale);
+                        builder.arc_to(radii, Angle::degrees(rotation), ArcFlags { large_arc, sweep }, to_layer(x, y));
+                    }
+                    PathCommand::Close => builder.close(),
+                }
+            }
+        }
+    }
+    builder.build()
+}
+
+/// Tessellate a clip path into triangles in layer pixels, covering the
+/// inside of the shape under its fill rule
+pub fn tessellate(clip_path: &ClipPath, reference: &Dimensions, space: LayerSpace) -> Vec<[Point; 3]> {
+    let fill_rule = match clip_path.fill_rule() {
+        FillRule::NonZero => lyon::tessellation::FillRule::NonZero,
+        FillRule::EvenOdd => lyon::tessellation::FillRule::EvenOdd,
+    };
+    let options = FillOptions::tolerance(TOLERANCE).with_fill_rule(fill_rule);
+    let mut geometry: VertexBuffers<Point, u32> = VertexBuffers::new();
+    let mut output = BuffersBuilder::new(&mut geometry, |vertex: FillVertex| vertex.position());
+    if let Err(error) = FillTessellator::new().tessellate_path(&outline(clip_path, reference, space), &options, &mut output) {
+        println!("⚠️ Clip path could not be tessellated: {:?}", error);
+        return Vec::new();
+    }
+    geometry.indices.chunks_exact(3).map(|triangle| [0, 1, 2].map(|corner| geometry.vertices[triangle[corner] as usize])).collect()
+}
+
+/// Fill triangles, in layer pixels, into a mask
+fn fill_triangles(triangles: &[[Point; 3]], width: usize, height: usize) -> Mask {
+    let mut mask = Mask::new(width, height);
+    let weight = 1.0 / SUBSAMPLES as f32;
+    let mut spans: Vec<(f32, f32)> = Vec::new();
+    for row in 0..height {
+        for sub in 0..SUBSAMPLES {
+            let y = row as f32 + (sub as f32 + 0.5) * weight;
+            spans.clear();
+            for triangle in triangles {
+                let mut crossings = [0.0; 2];
+                let mut count = 0;
+                for (a, b) in [(triangle[0], triangle[1]), (triangle[1], triangle[2]), (triangle[2], triangle[0])] {
+                    // Crossings are computed from the upper end, so an edge
+                    // two triangles share meets both at exactly the same x
+                    let (top, bottom) = if a.y < b.y { (a, b) } else { (b, a) };
+                    if top.y <= y && y < bottom.y && count < 2 {
+                        crossings[count] = top.x + (y - top.y) / (bottom.y - top.y) * (bottom.x - top.x);
+                        count += 1;
+                    }
+                }
+                if count == 2 {
+                    spans.push((crossings[0].min(crossings[1]), crossings[0].max(crossings[1])));
+                }
+            }
+            // Spans of neighbouring triangles are merged, leaving no seam
+            spans.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let mut merged: Option<(f32, f32)> = None;
+            for &(left, right) in &spans {
+                match merged.as_mut() {
+                    Some(span) if left <= span.1 => span.1 = span.1.max(right),
+                    _ => {
+                        if let Some((left, right)) = merged.replace((left, right)) {
+                            cover_span(&mut mask.coverage[row * width..(row + 1) * width], left, right, weight);
+                        }
+                    }
+                }
+            }
+            if let Some((left, right)) = merged {
+                cover_span(&mut mask.coverage[row * width..(row + 1) * width], left, right, weight);
+            }
+        }
+    }
+    mask
+}
+
+/// Add coverage to the pixels of a row a span overlaps, in proportion to
+/// the overlap
+fn cover_span(row: &mut [f32], left: f32, right: f32, weight: f32) {
+    let left = left.max(0.0);
+    let right = right.min(row.len() as f32);
+    if right <= left {
+        return;
+    }
+    for column in left.floor() as usize..(right.ceil() as usize).min(row.len()) {
+        let overlap = right.min(column as f32 + 1.0) - left.max(column as f32);
+        row[column] = (row[column] + overlap * weight).min(1.0);
+    }
+}
+
+/// Render a linear gradient's alpha or luminance over the border box
+/// into a mask; nothing outside the box is shown
+fn gradient_mask(angle: f32, stops: &[GradientStop], mode: MaskMode, reference: &Dimensions, space: LayerSpace) -> Mask {
+    let mut mask = Mask::new(space.width, space.height);
+    let stops = resolve_stops(stops, mode);
+    let (sin, cos) = angle.to_radians().sin_cos();
+    // The gradient line passes through the center, long enough for the
+    // box's corners to reach its ends
+    let length = (reference.width * sin).abs() + (reference.height * cos).abs();
+    let center = (reference.x + reference.width / 2.0, reference.y + reference.height / 2.0);
+    let (left, top) = space.map((reference.x, reference.y));
+    let (right, bottom) = space.map((reference.right(), reference.bottom()));
+    for y in 0..space.height {
+        for x in 0..space.width {
+            let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+            if px < left || px >= right || py < top || py >= bottom {
+                continue;
+            }
+            let page = (space.origin_x + px / space.scale, space.origin_y + py / space.scale);
+            let along = (page.0 - center.0) * sin - (page.1 - center.1) * cos;
+            let t = if length > 0.0 { along / length + 0.5 } else { 0.5 };
+            mask.coverage[y * space.width + x] = stop_alpha(&stops, t);
+        }
+    }
+    mask
+}
+
+/// Tile a mask image over the border box into a mask, one image pixel per
+/// page pixel from the box's top left corner; nothing outside the box is
+/// shown
+fn image_mask(source: &MaskSource, mode: MaskMode, reference: &Dimensions, space: LayerSpace) -> Mask {
+    let mut mask = Mask::new(space.width, space.height);
+    if source.width == 0 || source.height == 0 {
+        return mask;
+    }
+    let (left, top) = space.map((reference.x, reference.y));
+    let (right, bottom) = space.map((reference.right(), reference.bottom()));
+    for y in 0..space.height {
+        for x in 0..space.width {
+            let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+            if px < left || px >= right || py < top || py >= bottom {
+                continue;
+            }
+            let (image_x, image_y) = ((px - left) / space.scale, (py - top) / space.scale);
+            let (image_x, image_y) = (image_x as usize % source.width, image_y as usize % source.height);
+            mask.coverage[y * space.width + x] = source.coverage(image_x, image_y, mode);
+        }
+    }
+    mask
+}
+
+/// Give every stop a position and a coverage, its alpha or luminance
+///
+/// The first and last stops default to the ends, stops in between spread
+/// evenly, and no stop is placed before the one ahead of it.
+fn resolve_stops(stops: &[GradientStop], mode: MaskMode) -> Vec<(f32, f32)> {
+    let mut positions: Vec<Option<f32>> = stops.iter().map(|stop| stop.position).collect();
+    if let Some(first) = positions.first_mut() {
+        first.get_or_insert(0.0);
+    }
+    if let Some(last) = positions.last_mut() {
+        last.get_or_insert(1.0);
+    }
+    let mut index = 0;
+    while index < positions.len() {
+        if positions[index].is_some() {
+            index += 1;
+            continue;
+        }
+        let before = index - 1;
+        let after = (index..positions.len()).find(|&next| positions[next].is_some()).unwrap_or(positions.len() - 1);
+        let (start, end) = (positions[before].unwrap_or(0.0), positions[after].unwrap_or(1.0));
+        for (missing, position) in positions[index..after].iter_mut().enumerate() {
+            let fraction = (index + missing - before) as f32 / (after - before) as f32;
+            *position = Some(start + (end - start) * fraction);
+        }
+        index = after;
+    }
+    let mut previous = f32::MIN;
+    stops
+        .iter()
+        .zip(positions)
+        .map(|(stop, position)| {
+            previous = previous.max(position.unwrap_or(0.0));
+            let coverage = parse_color(&stop.color).map_or(0.0, |[r, g, b, a]| match mode {
+                MaskMode::Alpha => a,
+                MaskMode::Luminance => luminance(r, g, b) * a,
+            });
+            (previous, coverage)
+        })
+        .collect()
+}
+
+/// Luminance of a straight color, with the coefficients of CSS Masking
+fn luminance(r: f32, g: f32, b: f32) -> f32 {
+    0.2125 * r + 0.7154 * g + 0.0721 * b
+}
+
+/// Interpolate the alpha of resolved stops at a point on the gradient line
+fn stop_alpha(stops: &[(f32, f32)], t: f32) -> f32 {
+    let Some(&(first_position, first_alpha)) = stops.first() else {
+        return 0.0;
+    };
+    if t <= first_position {
+        return first_alpha;
+    }
+    for pair in stops.windows(2) {
+        let ((start, from), (end, to)) = (pair[0], pair[1]);
+        if t <= end {
+            if end <= start {
+                return to;
+            }
+            return from + (to - from) * (t - start) / (end - start);
+        }
+    }
+    stops.last().map_or(0.0, |stop| stop.1)
+}
+
+/// Create the pipeline of the mask shader
+///
+/// Bind the layer texture, an `R8Unorm` texture of the mask and a sampler,
+/// in that order, and draw three vertices.
+pub fn create_mask_pipeline(device: &wgpu::Device, format: wgpu::TextureFormat) -> (wgpu::RenderPipeline, wgpu::BindGroupLayout) {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Mask Shader"),
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(MASK_SHADER)),
+    });
+    let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    };
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Mask Bind Group Layout"),
+        entries: &[
+            texture_entry(0),
+            texture_entry(1),
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ],
+    });
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Mask Pipeline Layout"),
+        bind_group_layouts: &[&bind_group_layout],
+        push_constant_ranges: &[],
+    });
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Mask Pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_fullscreen",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_mask",
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                // Masked layers are premultiplied
+                blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    });
+    (pipeline, bind_group_layout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::encode_png;
+    use layout::masking::{parse_clip_path, parse_mask_image};
+
+    fn alpha_mask(clip_path: Option<&ClipPath>, mask_image: Option<&MaskImage>, reference: &Dimensions, space: LayerSpace) -> Option<Mask> {
+        layer_mask(clip_path, mask_image, MaskMode::Alpha, &HashMap::new(), reference, space)
+    }
+
+    fn space(width: usize, height: usize) -> LayerSpace {
+        LayerSpace { origin_x: 0.0, origin_y: 0.0, scale: 1.0, width, height }
+    }
+
+    #[test]
+    fn test_clip_paths_fill_with_fill_rules() {
+        let reference = Dimensions::new(0.0, 0.0, 20.0, 20.0);
+        let triangle = parse_clip_path("polygon(0 0, 100% 0, 0 100%)").unwrap().unwrap();
+        let mask = alpha_mask(Some(&triangle), None, &reference, space(20, 20)).unwrap();
+        assert_eq!(mask.coverage(2, 2), 1.0);
+        assert_eq!(mask.coverage(17, 17), 0.0);
+        // The diagonal edge is antialiased
+        assert!(mask.coverage(10, 9) > 0.0 && mask.coverage(10, 9) < 1.0);
+
+        // A square inside a square, both drawn the same way round
+        let data = "M0 0 H20 V20 H0 Z M5 5 H15 V15 H5 Z";
+        let nonzero = parse_clip_path(&format!("path('{data}')")).unwrap().unwrap();
+        let evenodd = parse_clip_path(&format!("path(evenodd, '{data}')")).unwrap().unwrap();
+        assert_eq!(alpha_mask(Some(&nonzero), None, &reference, space(20, 20)).unwrap().coverage(10, 10), 1.0);
+        let hole = alpha_mask(Some(&evenodd), None, &reference, space(20, 20)).unwrap();
+        assert_eq!((hole.coverage(10, 10), hole.coverage(2, 10)), (0.0, 1.0));
+
+        // Curves are tessellated, and shapes follow the reference box
+        let circle = parse_clip_path("path('M10 0 C15.5 0 20 4.5 20 10 S15.5 20 10 20 S0 15.5 0 10 S4.5 0 10 0')").unwrap().unwrap();
+        let moved = Dimensions::new(10.0, 10.0, 20.0, 20.0);
+        let mask = alpha_mask(Some(&circle), None, &moved, LayerSpace { origin_x: 10.0, origin_y: 10.0, scale: 2.0, width: 40, height: 40 }).unwrap();
+        assert_eq!((mask.coverage(20, 20), mask.coverage(1, 1)), (1.0, 0.0));
+    }
+
+    #[test]
+    fn test_gradient_masks_and_apply() {
+        let reference = Dimensions::new(0.0, 0.0, 10.0, 4.0);
+        let gradient = parse_mask_image("linear-gradient(to right, black, transparent)").unwrap().unwrap();
+        let mask = alpha_mask(None, Some(&gradient), &reference, space(12, 4)).unwrap();
+        assert!((mask.coverage(0, 0) - 0.95).abs() < 1e-4);
+        assert!((mask.coverage(9, 3) - 0.05).abs() < 1e-4);
+        // Nothing outside the border box shows
+        assert_eq!(mask.coverage(11, 0), 0.0);
+
+        // Stops without positions spread between their neighbours
+        let stops = parse_mask_image("linear-gradient(black, transparent 80%, black)").unwrap().unwrap();
+        let MaskImage::LinearGradient { stops, .. } = stops else { unreachable!() };
+        assert_eq!(resolve_stops(&stops, MaskMode::Alpha), vec![(0.0, 1.0), (0.8, 0.0), (1.0, 1.0)]);
+        let stops = parse_mask_image("linear-gradient(white, rgba(255, 255, 255, 0.5), black)").unwrap().unwrap();
+        let MaskImage::LinearGradient { stops, .. } = stops else { unreachable!() };
+        let luminance = resolve_stops(&stops, MaskMode::Luminance);
+        for (stop, expected) in luminance.iter().zip([(0.0, 1.0), (0.5, 0.5), (1.0, 0.0)]) {
+            assert!((stop.0 - expected.0).abs() < 1e-4 && (stop.1 - expected.1).abs() < 1e-4, "{luminance:?}");
+        }
+
+        let mut layer = Layer::new(12, 4);
+        layer.fill_rect(0, 0, 12, 4, [1.0, 0.0, 0.0, 1.0]);
+        apply_mask(&mut layer, &mask);
+        assert!((layer.pixel(0, 0)[0] - 0.95).abs() < 1e-4 && (layer.pixel(0, 0)[3] - 0.95).abs() < 1e-4);
+        assert_eq!(layer.pixel(11, 0), [0.0; 4]);
+        assert_eq!(mask.to_texture_bytes()[0], 242);
+    }
+
+    #[test]
+    fn test_arcs_and_image_masks() {
+        // A circle of two arcs covers its area, less what flattening cuts
+        // off, and nothing past its edge
+        let reference = Dimensions::new(0.0, 0.0, 20.0, 20.0);
+        let circle = parse_clip_path("path('M0 10 A10 10 0 1 1 20 10 A10 10 0 1 1 0 10 Z')").unwrap().unwrap();
+        let circle = alpha_mask(Some(&circle), None, &reference, space(20, 20)).unwrap();
+        assert_eq!((circle.coverage(10, 10), circle.coverage(0, 0), circle.coverage(19, 19)), (1.0, 0.0, 0.0));
+        let area: f32 = circle.coverage.iter().sum();
+        assert!((area - std::f32::consts::PI * 100.0).abs() < 4.0, "{area}");
+
+        // White, black and transparent pixels
+        let mut image = Layer::new(3, 1);
+        image.fill_rect(0, 0, 1, 1, [1.0, 1.0, 1.0, 1.0]);
+        image.fill_rect(1, 0, 1, 1, [0.0, 0.0, 0.0, 1.0]);
+        let url = MaskImage::Url("mask.png".to_string());
+        let sources = HashMap::from([("mask.png".to_string(), MaskSource::decode(&encode_png(&image)))]);
+        let reference = Dimensions::new(0.0, 0.0, 6.0, 1.0);
+        let alpha = layer_mask(None, Some(&url), MaskMode::Alpha, &sources, &reference, space(7, 1)).unwrap();
+        // Tiled from the box's corner, and hidden past it
+        assert_eq!(alpha.coverage, vec![1.0, 1.0, 0.0, 1.0, 1.0, 0.0, 0.0]);
+        let luminance = layer_mask(None, Some(&url), MaskMode::Luminance, &sources, &reference, space(7, 1)).unwrap();
+        assert!((luminance.coverage[0] - 1.0).abs() < 1e-4 && (luminance.coverage[3] - 1.0).abs() < 1e-4);
+        assert_eq!((luminance.coverage[1], luminance.coverage[2]), (0.0, 0.0));
+
+        // An image that has not arrived leaves the layer alone, and one that
+        // cannot be decoded hides it
+        assert_eq!(layer_mask(None, Some(&url), MaskMode::Alpha, &HashMap::new(), &reference, space(6, 1)), None);
+        let broken = HashMap::from([("mask.png".to_string(), MaskSource::decode(b"not an image"))]);
+        assert_eq!(layer_mask(None, Some(&url), MaskMode::Alpha, &broken, &reference, space(6, 1)).unwrap().coverage, vec![0.0; 6]);
+    }
+
+    #[test]
+    fn test_mask_shader_is_valid_wgsl() {
+        let module = naga::front::wgsl::parse_str(MASK_SHADER).unwrap();
+        assert!(module.entry_points.iter().any(|entry| entry.name == "fs_mask"));
+    }
+}
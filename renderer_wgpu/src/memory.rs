@@ -0,0 +1,93 @@
+//! # Memory Coordinator
+//!
+//! This module shares one memory budget between the renderer's caches.
+//! Caches reserve bytes before they keep something and release them when
+//! they drop it, so together they stay within the budget.
+//!
+//! ## Design Principles
+//!
+//! 1. **Reserve Before Keeping**: A cache that is refused a reservation
+//!    frees some of its own entries or goes without; nothing is kept that
+//!    the coordinator has not accounted for.
+//! 2. **Shared Handle**: Clones of a coordinator share its accounting, so
+//!    each cache holds its own handle instead of a reference.
+//! 3. **Shrinkable**: Lowering the budget under memory pressure does not
+//!    free anything itself. Caches see that they are over budget and trim
+//!    on their next frame.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Budget of a coordinator created with `Default`
+pub const DEFAULT_BUDGET_BYTES: usize = 256 * 1024 * 1024;
+
+#[derive(Debug, Default)]
+struct MemoryState {
+    budget: usize,
+    /// Bytes reserved by each client
+    usage: HashMap<String, usize>,
+}
+
+/// Accounts for the memory the renderer's caches keep
+#[derive(Debug, Clone)]
+pub struct MemoryCoordinator {
+    state: Rc<RefCell<MemoryState>>,
+}
+
+impl Default for MemoryCoordinator {
+    fn default() -> Self {
+        Self::new(DEFAULT_BUDGET_BYTES)
+    }
+}
+
+impl MemoryCoordinator {
+    pub fn new(budget: usize) -> Self {
+        MemoryCoordinator { state: Rc::new(RefCell::new(MemoryState { budget, usage: HashMap::new() })) }
+    }
+
+    pub fn budget(&self) -> usize {
+        self.state.borrow().budget
+    }
+
+    /// Change the budget; clients over it trim themselves
+    pub fn set_budget(&self, budget: usize) {
+        self.state.borrow_mut().budget = budget;
+    }
+
+    /// Reserve `bytes` for `client`
+    ///
+    /// Returns `false`, reserving nothing, if that would exceed the budget.
+    pub fn try_reserve(&self, client: &str, bytes: usize) -> bool {
+        let mut state = self.state.borrow_mut();
+        let total: usize = state.usage.values().sum();
+        if total + bytes > state.budget {
+            return false;
+        }
+        *state.usage.entry(client.to_string()).or_insert(0) += bytes;
+        true
+    }
+
+    /// Give back bytes `client` reserved
+    pub fn release(&self, client: &str, bytes: usize) {
+        let mut state = self.state.borrow_mut();
+        if let Some(usage) = state.usage.get_mut(client) {
+            *usage = usage.saturating_sub(bytes);
+        }
+    }
+
+    /// Get the bytes reserved by one client
+    pub fn usage(&self, client: &str) -> usize {
+        self.state.borrow().usage.get(client).copied().unwrap_or(0)
+    }
+
+    /// Get the bytes reserved by every client
+    pub fn total_usage(&self) -> usize {
+        self.state.borrow().usage.values().sum()
+    }
+
+    /// Check whether the clients together hold more than the budget
+    pub fn is_over_budget(&self) -> bool {
+        self.total_usage() > self.budget()
+    }
+}
@@ -0,0 +1,219 @@
+//! # Paint Flashing
+//!
+//! This module implements a debug overlay that flashes the regions
+//! repainted in each frame and outlines relayout boundaries, like paint
+//! flashing in other browsers' developer tools. It makes damage tracking
+//! and incremental layout visible: a hover that repaints the whole page
+//! instead of one button shows up at a glance.
+//!
+//! ## Design Principles
+//!
+//! 1. **Same Damage as the Cache**: A stacking context flashes when its
+//!    content hash changes, the same test `RasterCache` reuses textures by,
+//!    so the overlay shows what the cache has to repaint.
+//! 2. **Fading Flashes**: A flash fades out over `FLASH_FRAMES` frames, so
+//!    a region repainted every frame stays lit while a one-off repaint
+//!    blinks once.
+//! 3. **Off Unless Asked For**: Nothing is recorded until the overlay is
+//!    turned on with `DUBBY_PAINT_FLASHING`, `--paint-flashing` or F9 in
+//!    the window, so normal frames pay nothing for it.
+
+use crate::raster_cache::{context_hash, stacking_contexts, DamageStats};
+use layout::{ContentVisibility, Dimensions, LayoutBox};
+
+/// Environment variable turning paint flashing on (`1`/`0`)
+pub const PAINT_FLASHING_ENV: &str = "DUBBY_PAINT_FLASHING";
+
+/// Frames a flash takes to fade out
+pub const FLASH_FRAMES: u32 = 12;
+
+/// Color of a fresh flash
+pub const FLASH_COLOR: [f32; 3] = [0.2, 0.85, 0.3];
+
+/// Color of relayout boundary outlines
+pub const BOUNDARY_COLOR: [f32; 3] = [0.25, 0.45, 1.0];
+
+/// A repainted region
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Flash {
+    pub node_id: u64,
+    /// The repainted bounds, in page coordinates
+    pub bounds: Dimensions,
+    /// Frames since the region was repainted
+    pub age: u32,
+}
+
+impl Flash {
+    /// Get how strongly the flash shows, from 1 when fresh down to 0
+    pub fn intensity(&self) -> f32 {
+        1.0 - self.age.min(FLASH_FRAMES) as f32 / FLASH_FRAMES as f32
+    }
+
+    /// Get the flash color faded toward `background`
+    pub fn color(&self, background: [f32; 3]) -> [f32; 3] {
+        let t = self.intensity();
+        [0, 1, 2].map(|i| background[i] + (FLASH_COLOR[i] - background[i]) * t)
+    }
+}
+
+/// Check whether a box's size does not depend on its contents, so a
+/// relayout inside it stops at the box
+///
+/// These are boxes with a fixed width and height, and size-contained
+/// boxes skipping their contents.
+pub fn is_relayout_boundary(layout_box: &LayoutBox) -> bool {
+    let styles = &layout_box.styles;
+    (styles.box_model.width.is_some() && styles.box_model.height.is_some()) || styles.content_visibility != ContentVisibility::Visible
+}
+
+/// Get the border boxes of the relayout boundaries of a tree, in paint
+/// order
+///
+/// The root always bounds relayout, so it is left out.
+pub fn relayout_boundaries(root: &LayoutBox) -> Vec<Dimensions> {
+    fn collect(layout_box: &LayoutBox, found: &mut Vec<Dimensions>) {
+        for child in &layout_box.children {
+            if is_relayout_boundary(child) && child.border.width > 0.0 && child.border.height > 0.0 {
+                found.push(child.border);
+            }
+            collect(child, found);
+        }
+    }
+    let mut found = Vec::new();
+    collect(root, &mut found);
+    found
+}
+
+/// The paint flashing overlay of a window
+#[derive(Debug, Default)]
+pub struct PaintFlashing {
+    enabled: bool,
+    damage: DamageStats,
+    flashes: Vec<Flash>,
+}
+
+impl PaintFlashing {
+    pub fn new(enabled: bool) -> Self {
+        PaintFlashing { enabled, ..Default::default() }
+    }
+
+    /// Get an overlay that is on when `DUBBY_PAINT_FLASHING` is set
+    pub fn from_env() -> Self {
+        let enabled = std::env::var(PAINT_FLASHING_ENV)
+            .map(|value| matches!(value.trim().to_ascii_lowercase().as_str(), "1" | "true" | "on" | "yes"))
+            .unwrap_or(false);
+        Self::new(enabled)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Turn the overlay on or off
+    ///
+    /// Turning it off forgets what was painted, so turning it back on
+    /// flashes the whole page once.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.damage = DamageStats::default();
+            self.flashes.clear();
+        }
+    }
+
+    /// Turn the overlay on if it is off and off if it is on; returns
+    /// whether it is on now
+    pub fn toggle(&mut self) -> bool {
+        self.set_enabled(!self.enabled);
+        self.enabled
+    }
+
+    /// Record a frame painting `root`; returns how many regions were
+    /// repainted
+    ///
+    /// Earlier flashes age by a frame, and the ones that have faded out
+    /// are dropped.
+    pub fn record_frame(&mut self, root: &LayoutBox) -> usize {
+        if !self.enabled {
+            return 0;
+        }
+        for flash in &mut self.flashes {
+            flash.age += 1;
+        }
+        self.flashes.retain(|flash| flash.age < FLASH_FRAMES);
+
+        let mut repainted = 0;
+        for context in stacking_contexts(root) {
+            let damage = self.damage.record(context.node_id, context_hash(&context));
+            if damage.unchanged_paints > 0 {
+                continue;
+            }
+            repainted += 1;
+            self.flashes.retain(|flash| flash.node_id != context.node_id);
+            self.flashes.push(Flash { node_id: context.node_id, bounds: context.bounds, age: 0 });
+        }
+        repainted
+    }
+
+    /// Get the flashes still showing, oldest first
+    pub fn flashes(&self) -> &[Flash] {
+        &self.flashes
+    }
+
+    /// Check whether flashes are still fading, so more frames are needed
+    pub fn is_animating(&self) -> bool {
+        self.enabled && !self.flashes.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use css_parser::CSSParser;
+    use dom::Document;
+    use layout::LayoutEngine;
+
+    #[test]
+    fn test_repainted_contexts_flash_and_fade() {
+        let doc = Document::new();
+        let body = doc.create_element("body");
+        let card = doc.create_element("div");
+        card.set_attribute("class", "card");
+        card.append_child(&doc.create_text_node("Card"));
+        body.append_child(&card);
+        let spinner = doc.create_element("div");
+        spinner.set_attribute("class", "spinner");
+        spinner.append_child(&doc.create_text_node("Loading"));
+        body.append_child(&spinner);
+        doc.root.append_child(&body);
+
+        let css = ".card { width: 200px; height: 100px } .spinner { will-change: transform }";
+        let engine = LayoutEngine::new(CSSParser::new(css.to_string()).parse_stylesheet().unwrap());
+        let mut flashing = PaintFlashing::new(false);
+        assert_eq!(flashing.record_frame(&engine.layout_document(&doc)), 0);
+        assert!(flashing.toggle());
+
+        // Everything is new on the first frame, then nothing changes
+        assert_eq!(flashing.record_frame(&engine.layout_document(&doc)), 2);
+        assert_eq!(flashing.record_frame(&engine.layout_document(&doc)), 0);
+        assert!(flashing.flashes().iter().all(|flash| flash.age == 1));
+
+        // Only the spinner's context changed
+        spinner.set_text_content("Loaded", &doc);
+        assert_eq!(flashing.record_frame(&engine.layout_document(&doc)), 1);
+        let fresh: Vec<&Flash> = flashing.flashes().iter().filter(|flash| flash.age == 0).collect();
+        assert_eq!(fresh.len(), 1);
+        assert_eq!(fresh[0].node_id, spinner.id);
+        assert_eq!(fresh[0].color([0.0; 3]), FLASH_COLOR);
+
+        for _ in 0..FLASH_FRAMES {
+            flashing.record_frame(&engine.layout_document(&doc));
+        }
+        assert!(!flashing.is_animating());
+        assert!(!flashing.toggle());
+
+        let boundaries = relayout_boundaries(&engine.layout_document(&doc));
+        assert_eq!(boundaries.len(), 1);
+        assert_eq!((boundaries[0].width, boundaries[0].height), (200.0, 100.0));
+    }
+}
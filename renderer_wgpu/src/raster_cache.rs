@@ -0,0 +1,588 @@
+//! # Raster Cache
+//!
+//! This module keeps rasterized stacking contexts across frames. A
+//! context painted several frames in a row without changing, such as a
+//! page's static header while something below it animates, is recorded
+//! into a texture and reused until its content changes.
+//!
+//! ## Design Principles
+//!
+//! 1. **Earned, Not Assumed**: Damage stats count how many paints in a row
+//!    left a context unchanged. Only contexts past `CACHE_AFTER_FRAMES` are
+//!    recorded, so content that changes every frame never pays for a
+//!    texture it cannot reuse.
+//! 2. **Keyed on Scale**: Entries are keyed on the device scale factor as
+//!    well as the context, so a DPI change re-rasterizes at the new
+//!    resolution instead of stretching stale pixels.
+//! 3. **Budgeted**: Every texture is reserved with the `MemoryCoordinator`
+//!    first. When it refuses, the least recently used entries are evicted,
+//!    and if that is not enough the context is painted without caching.
+//! 4. **Live Surfaces Stay Live**: Contexts containing iframes, canvases
+//!    or video frames change without their display list changing, so they
+//!    are never cached.
+
+use crate::filters::{parse_color, Layer};
+use crate::masking::{apply_mask, layer_mask, LayerSpace, MaskSource};
+use crate::memory::MemoryCoordinator;
+use crate::snapping::{glyph_origin, DeviceRect};
+use crate::text_quality::{GlyphAtlas, TextRenderSettings};
+use layout::layers::promotion_reasons;
+use layout::{ClipPath, Dimensions, LayoutBox, MaskImage, MaskMode};
+use renderer::display_list::{DisplayItem, DisplayList, Transform};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+/// Client name the cache reserves memory under
+pub const RASTER_CACHE_CLIENT: &str = "raster-cache";
+
+/// Unchanged paints in a row after which a context is cached
+pub const CACHE_AFTER_FRAMES: u32 = 3;
+
+/// Longest texture side the cache records, in device pixels
+pub const MAX_TEXTURE_SIDE: usize = 4096;
+
+/// Color replaced content is painted with until images are decoded
+const PLACEHOLDER_COLOR: [f32; 4] = [0.87, 0.87, 0.87, 1.0];
+
+/// A stacking context and the items it paints itself
+#[derive(Debug, Clone)]
+pub struct StackingContext {
+    pub node_id: u64,
+    /// Union of the item bounds, in page coordinates
+    pub bounds: Dimensions,
+    /// Items of the context, without those of nested contexts
+    pub display_list: DisplayList,
+    /// Shape and image the context is clipped and masked with, relative to
+    /// the border box `reference_box`
+    pub clip_path: Option<ClipPath>,
+    pub mask_image: Option<MaskImage>,
+    pub mask_mode: MaskMode,
+    pub reference_box: Dimensions,
+}
+
+/// Check whether a box paints as a stacking context of its own
+pub fn is_stacking_context(layout_box: &LayoutBox) -> bool {
+    layout_box.styles.creates_isolated_group() || !promotion_reasons(layout_box).is_empty()
+}
+
+/// Split a layout tree into its stacking contexts, root first
+pub fn stacking_contexts(root: &LayoutBox) -> Vec<StackingContext> {
+    let mut contexts = Vec::new();
+    collect_contexts(root, &mut contexts);
+    contexts
+}
+
+fn collect_contexts(context: &LayoutBox, contexts: &mut Vec<StackingContext>) {
+    let display_list = DisplayList::from_subtree(context, &is_stacking_context);
+    if let Some(bounds) = list_bounds(&display_list) {
+        contexts.push(StackingContext {
+            node_id: context.node.id,
+            bounds,
+            display_list,
+            clip_path: context.styles.clip_path.clone(),
+            mask_image: context.styles.mask_image.clone(),
+            mask_mode: context.styles.mask_mode,
+            reference_box: context.border,
+        });
+    }
+    let mut pending: Vec<&LayoutBox> = context.children.iter().rev().collect();
+    while let Some(layout_box) = pending.pop() {
+        if is_stacking_context(layout_box) {
+            collect_contexts(layout_box, contexts);
+        } else {
+            pending.extend(layout_box.children.iter().rev());
+        }
+    }
+}
+
+/// Get the union of the bounds of a list's items
+fn list_bounds(list: &DisplayList) -> Option<Dimensions> {
+    let mut items = list.items.iter().map(DisplayItem::bounds).filter(|bounds| bounds.width > 0.0 && bounds.height > 0.0);
+    let first = items.next()?;
+    let (mut left, mut top, mut right, mut bottom) = (first.x, first.y, first.right(), first.bottom());
+    for bounds in items {
+        left = left.min(bounds.x);
+        top = top.min(bounds.y);
+        right = right.max(bounds.right());
+        bottom = bottom.max(bounds.bottom());
+    }
+    Some(Dimensions::new(left, top, right - left, bottom - top))
+}
+
+/// Hash the items of a list, to tell when a context's content changed
+pub(crate) fn content_hash(list: &DisplayList) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for item in &list.items {
+        format!("{:?}", item).hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Hash a context's items, clip and mask, to tell when its pixels changed
+pub(crate) fn context_hash(context: &StackingContext) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content_hash(&context.display_list).hash(&mut hasher);
+    if context.clip_path.is_some() || context.mask_image.is_some() {
+        format!("{:?} {:?} {:?} {:?}", context.clip_path, context.mask_image, context.mask_mode, context.reference_box).hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// How a stacking context has changed across paints
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ContextDamage {
+    pub content_hash: u64,
+    /// Frames the context was painted in
+    pub paints: u32,
+    /// Paints in a row that left the context unchanged
+    pub unchanged_paints: u32,
+}
+
+/// Per-context damage, recorded every frame
+#[derive(Debug, Default)]
+pub struct DamageStats {
+    contexts: HashMap<u64, ContextDamage>,
+}
+
+impl DamageStats {
+    /// Record a paint of a context with the given content
+    pub fn record(&mut self, node_id: u64, content_hash: u64) -> ContextDamage {
+        let damage = self.contexts.entry(node_id).or_insert(ContextDamage { content_hash, ..Default::default() });
+        if damage.paints > 0 && damage.content_hash == content_hash {
+            damage.unchanged_paints += 1;
+        } else {
+            damage.unchanged_paints = 0;
+        }
+        damage.content_hash = content_hash;
+        damage.paints += 1;
+        *damage
+    }
+
+    pub fn get(&self, node_id: u64) -> Option<&ContextDamage> {
+        self.contexts.get(&node_id)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct RasterKey {
+    node_id: u64,
+    scale_bits: u32,
+}
+
+#[derive(Debug)]
+struct CachedRaster {
+    content_hash: u64,
+    layer: Rc<Layer>,
+    bytes: usize,
+    last_used: u64,
+}
+
+/// A stacking context ready to composite
+#[derive(Debug, Clone)]
+pub struct PaintedContext {
+    pub node_id: u64,
+    pub bounds: Dimensions,
+    /// The context's pixels at the frame's scale
+    pub layer: Rc<Layer>,
+    /// Whether the pixels came from the cache
+    pub cached: bool,
+}
+
+/// What the cache did during one frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    /// Contexts composited from a cached texture
+    pub reused: usize,
+    /// Contexts painted from their display list
+    pub rasterized: usize,
+    /// Contexts newly recorded into the cache
+    pub recorded: usize,
+}
+
+/// Textures of unchanged stacking contexts, reused across frames
+#[derive(Debug)]
+pub struct RasterCache {
+    entries: HashMap<RasterKey, CachedRaster>,
+    damage: DamageStats,
+    memory: MemoryCoordinator,
+    text: TextRenderSettings,
+    atlas: GlyphAtlas,
+    frame: u64,
+    /// Decoded `url()` mask images, by URL
+    mask_sources: HashMap<String, MaskSource>,
+}
+
+impl RasterCache {
+    pub fn new(memory: MemoryCoordinator) -> Self {
+        RasterCache {
+            entries: HashMap::new(),
+            damage: DamageStats::default(),
+            memory,
+            text: TextRenderSettings::from_env(),
+            atlas: GlyphAtlas::new(),
+            frame: 0,
+            mask_sources: HashMap::new(),
+        }
+    }
+
+    /// Change the settings text is rasterized with, dropping every
+    /// texture painted under the old ones
+    pub fn set_text_rendering(&mut self, settings: TextRenderSettings) {
+        if settings != self.text {
+            self.text = settings;
+            self.atlas.retain_settings(&settings);
+            self.clear();
+        }
+    }
+
+    /// Decode the fetched bytes of a `url()` mask image, dropping every
+    /// texture, as those masked by it were painted without it
+    pub fn set_mask_image(&mut self, url: &str, bytes: &[u8]) {
+        self.mask_sources.insert(url.to_string(), MaskSource::decode(bytes));
+        self.clear();
+    }
+
+    /// Paint the stacking contexts of a frame at a device scale factor
+    pub fn paint_frame(&mut self, contexts: &[StackingContext], scale: f32) -> (Vec<PaintedContext>, FrameStats) {
+        self.frame += 1;
+        let mut stats = FrameStats::default();
+        let mut painted = Vec::with_capacity(contexts.len());
+        for context in contexts {
+            let hash = context_hash(context);
+            let damage = self.damage.record(context.node_id, hash);
+            let key = RasterKey { node_id: context.node_id, scale_bits: scale.to_bits() };
+
+            if let Some(entry) = self.entries.get_mut(&key).filter(|entry| entry.content_hash == hash) {
+                entry.last_used = self.frame;
+                stats.reused += 1;
+                painted.push(PaintedContext { node_id: context.node_id, bounds: context.bounds, layer: entry.layer.clone(), cached: true });
+                continue;
+            }
+            self.remove(&key);
+
+            let layer = Rc::new(self.rasterize(context, scale));
+            stats.rasterized += 1;
+            let cacheable = damage.unchanged_paints >= CACHE_AFTER_FRAMES
+                && layer.width <= MAX_TEXTURE_SIDE
+                && layer.height <= MAX_TEXTURE_SIDE
+                && !context.display_list.items.iter().any(|item| matches!(item, DisplayItem::Surface { .. }));
+            if cacheable && self.reserve(layer.width * layer.height * 4) {
+                let bytes = layer.width * layer.height * 4;
+                self.entries.insert(key, CachedRaster { content_hash: hash, layer: layer.clone(), bytes, last_used: self.frame });
+                stats.recorded += 1;
+            }
+            painted.push(PaintedContext { node_id: context.node_id, bounds: context.bounds, layer, cached: false });
+        }
+
+        // Contexts that were not painted are gone, or were at another scale
+        let stale: Vec<RasterKey> = self.entries.iter().filter(|(_, entry)| entry.last_used != self.frame).map(|(key, _)| *key).collect();
+        for key in stale {
+            self.remove(&key);
+        }
+        while self.memory.is_over_budget() && self.evict_least_recently_used() {}
+        (painted, stats)
+    }
+
+    /// Reserve texture memory, evicting older entries to make room
+    fn reserve(&mut self, bytes: usize) -> bool {
+        loop {
+            if self.memory.try_reserve(RASTER_CACHE_CLIENT, bytes) {
+                return true;
+            }
+            if !self.evict_least_recently_used() {
+                return false;
+            }
+        }
+    }
+
+    /// Evict the entry used longest ago; returns whether there was one
+    fn evict_least_recently_used(&mut self) -> bool {
+        let oldest = self.entries.iter().min_by_key(|(_, entry)| entry.last_used).map(|(key, _)| *key);
+        match oldest {
+            Some(key) => {
+                self.remove(&key);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn remove(&mut self, key: &RasterKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.memory.release(RASTER_CACHE_CLIENT, entry.bytes);
+        }
+    }
+
+    /// Paint a context's display list into a layer of its own
+    fn rasterize(&mut self, context: &StackingContext, scale: f32) -> Layer {
+        // Items are snapped on the page's pixel grid, then moved into the
+        // layer, see `snapping`
+        let area = DeviceRect::from_page(&context.bounds, scale);
+        let width = area.width.min(MAX_TEXTURE_SIDE * 2);
+        let height = area.height.min(MAX_TEXTURE_SIDE * 2);
+        let mut layer = Layer::new(width, height);
+        let transform = Transform { scale_x: scale, scale_y: scale, translate_x: 0.0, translate_y: 0.0 };
+        let pixels = |x: f32, y: f32, width: f32, height: f32| DeviceRect::snap(x, y, width, height).offset(-area.x, -area.y).clamped();
+        for item in context.display_list.transformed(&transform).items {
+            match &item {
+                DisplayItem::Rect { x, y, width, height, color } => {
+                    if let Some(color) = parse_color(color) {
+                        let (x, y, width, height) = pixels(*x, *y, *width, *height);
+                        layer.fill_rect(x, y, width, height, color);
+                    }
+                }
+                DisplayItem::Replaced { x, y, width, height, .. } => {
+                    let (x, y, width, height) = pixels(*x, *y, *width, *height);
+                    layer.fill_rect(x, y, width, height, PLACEHOLDER_COLOR);
+                }
+                DisplayItem::RoundedRect { x, y, width, height, radius, color } => {
+                    if let Some(color) = parse_color(color) {
+                        let (x, y, width, height) = pixels(*x, *y, *width, *height);
+                        fill_rounded_rect(&mut layer, x, y, width, height, *radius, color);
+                    }
+                }
+                // Images are not decoded here; tiles would start at the
+                // item's tile origin
+                DisplayItem::BackgroundImage { x, y, width, height, radius, .. } => {
+                    let (x, y, width, height) = pixels(*x, *y, *width, *height);
+                    fill_rounded_rect(&mut layer, x, y, width, height, *radius, PLACEHOLDER_COLOR);
+                }
+                DisplayItem::Text { x, y, text, color, font_size, .. } => {
+                    let color = parse_color(color).unwrap_or([0.0, 0.0, 0.0, 1.0]);
+                    let advance = font_size * 0.6;
+                    for (index, ch) in text.chars().enumerate() {
+                        let (glyph_x, glyph_y, subpixel) = glyph_origin(x + index as f32 * advance, *y);
+                        let glyph = self.atlas.glyph(ch, *font_size, subpixel, &self.text);
+                        draw_glyph(&mut layer, glyph_x - area.x, glyph_y - area.y, glyph, color);
+                    }
+                }
+                DisplayItem::Squiggle { x, y, width, height, color } => {
+                    if let Some(color) = parse_color(color) {
+                        let (x, y, width, height) = pixels(*x, *y, *width, *height);
+                        draw_squiggle(&mut layer, x, y, width, height.max(1), color);
+                    }
+                }
+                // Composited from their own texture
+                DisplayItem::Surface { .. } => {}
+            }
+        }
+        let space = LayerSpace { origin_x: area.x as f32 / scale, origin_y: area.y as f32 / scale, scale, width, height };
+        let mask = layer_mask(
+            context.clip_path.as_ref(),
+            context.mask_image.as_ref(),
+            context.mask_mode,
+            &self.mask_sources,
+            &context.reference_box,
+            space,
+        );
+        if let Some(mask) = mask {
+            apply_mask(&mut layer, &mask);
+        }
+        layer
+    }
+
+    /// Drop every cached texture
+    pub fn clear(&mut self) {
+        let keys: Vec<RasterKey> = self.entries.keys().copied().collect();
+        for key in keys {
+            self.remove(&key);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn damage(&self) -> &DamageStats {
+        &self.damage
+    }
+}
+
+/// Draw a glyph's coverage in a straight RGBA color, source over
+fn draw_glyph(layer: &mut Layer, x: isize, y: isize, glyph: &crate::text_quality::GlyphBitmap, color: [f32; 4]) {
+    for row in 0..glyph.height {
+        for column in 0..glyph.width {
+            let (px, py) = (x + column as isize, y + row as isize);
+            if px < 0 || py < 0 || px as usize >= layer.width || py as usize >= layer.height {
+                continue;
+            }
+            let coverage = (0..glyph.channels).map(|channel| glyph.coverage(column, row, channel) as f32).sum::<f32>()
+                / (glyph.channels as f32 * 255.0);
+            let alpha = color[3] * coverage;
+            let pixel = &mut layer.pixels[py as usize * layer.width + px as usize];
+            for channel in 0..3 {
+                pixel[channel] = color[channel] * alpha + pixel[channel] * (1.0 - alpha);
+            }
+            pixel[3] = alpha + pixel[3] * (1.0 - alpha);
+        }
+    }
+}
+
+/// Draw a one pixel wavy line that zigzags across `height` rows
+fn draw_squiggle(layer: &mut Layer, x: usize, y: usize, width: usize, height: usize, color: [f32; 4]) {
+    let period = 2 * (height - 1).max(1);
+    for column in x..x + width {
+        let phase = (column - x) % period;
+        let row = if phase < height { phase } else { period - phase };
+        layer.fill_rect(column, y + row, 1, 1, color);
+    }
+}
+
+/// Fill a rectangle with rounded corners, one span per row
+fn fill_rounded_rect(layer: &mut Layer, x: usize, y: usize, width: usize, height: usize, radius: f32, color: [f32; 4]) {
+    let radius = radius.min(width as f32 / 2.0).min(height as f32 / 2.0).max(0.0);
+    for row in 0..height {
+        // Distance of the row's centre from the nearest corner's centre
+        let centre = row as f32 + 0.5;
+        let dy = (radius - centre.min(height as f32 - centre)).max(0.0);
+        let inset = (radius - (radius * radius - dy * dy).max(0.0).sqrt()).round() as usize;
+        if width > 2 * inset {
+            layer.fill_rect(x + inset, y + row, width - 2 * inset, 1, color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapping;
+    use css_parser::CSSParser;
+    use dom::Document;
+    use layout::LayoutEngine;
+
+    fn context(node_id: u64, color: &str) -> StackingContext {
+        let display_list = DisplayList {
+            items: vec![DisplayItem::Rect { x: 0.0, y: 0.0, width: 100.0, height: 20.0, color: color.to_string() }],
+        };
+        let bounds = Dimensions::new(0.0, 0.0, 100.0, 20.0);
+        StackingContext { node_id, bounds, display_list, clip_path: None, mask_image: None, mask_mode: MaskMode::Alpha, reference_box: bounds }
+    }
+
+    #[test]
+    fn test_unchanged_contexts_are_cached_and_budgeted() {
+        let memory = MemoryCoordinator::new(1024 * 1024);
+        let mut cache = RasterCache::new(memory.clone());
+        let colors = ["red", "green", "blue", "white", "black", "gray"];
+
+        // The static header earns a texture; the animated box never does
+        let mut recorded_at = None;
+        for (frame, color) in colors.iter().enumerate() {
+            let (painted, stats) = cache.paint_frame(&[context(1, "navy"), context(2, color)], 1.0);
+            assert_eq!(painted.len(), 2);
+            if stats.recorded > 0 && recorded_at.is_none() {
+                recorded_at = Some(frame);
+            }
+        }
+        assert_eq!(recorded_at, Some(CACHE_AFTER_FRAMES as usize));
+        assert_eq!(cache.len(), 1);
+        assert_eq!(memory.usage(RASTER_CACHE_CLIENT), 100 * 20 * 4);
+        let (painted, stats) = cache.paint_frame(&[context(1, "navy"), context(2, "teal")], 1.0);
+        assert_eq!(stats, FrameStats { reused: 1, rasterized: 1, recorded: 0 });
+        assert!(painted[0].cached && !painted[1].cached);
+
+        // A DPI change paints at the new resolution and replaces the old
+        // texture
+        let (painted, stats) = cache.paint_frame(&[context(1, "navy")], 2.0);
+        assert_eq!(stats, FrameStats { reused: 0, rasterized: 1, recorded: 1 });
+        assert_eq!((painted[0].layer.width, painted[0].layer.height), (200, 40));
+        assert_eq!(cache.len(), 1);
+        assert_eq!(memory.usage(RASTER_CACHE_CLIENT), 200 * 40 * 4);
+
+        // Lowering the budget trims the cache on the next frame
+        memory.set_budget(1024);
+        cache.paint_frame(&[context(1, "navy")], 2.0);
+        assert!(cache.is_empty());
+        assert_eq!(memory.total_usage(), 0);
+    }
+
+    #[test]
+    fn test_rounded_rects_leave_corners_unpainted() {
+        let mut layer = Layer::new(20, 20);
+        let color = [1.0, 0.0, 0.0, 1.0];
+        fill_rounded_rect(&mut layer, 0, 0, 20, 20, 8.0, color);
+        assert_eq!(layer.pixel(0, 0), [0.0; 4]);
+        assert_eq!(layer.pixel(19, 19), [0.0; 4]);
+        assert_eq!(layer.pixel(10, 0), color);
+        assert_eq!(layer.pixel(0, 10), color);
+        assert_eq!(layer.pixel(10, 10), color);
+    }
+
+    #[test]
+    fn test_fractional_rects_snap_without_seams_or_double_lines() {
+        let rect = |x: f32, width: f32| DisplayItem::Rect { x, y: 0.5, width, height: 10.0, color: "red".to_string() };
+        // Three adjacent boxes 10.4px wide, then a 1px line painted as two
+        // halves at a fractional offset
+        let items = vec![rect(0.0, 10.4), rect(10.4, 10.4), rect(20.8, 10.4), rect(40.25, 0.5), rect(40.75, 0.5)];
+        let bounds = Dimensions::new(0.0, 0.0, 50.0, 12.0);
+        let page = StackingContext { node_id: 1, bounds, display_list: DisplayList { items }, clip_path: None, mask_image: None, mask_mode: MaskMode::Alpha, reference_box: bounds };
+        // A layer at a fractional origin lands on the page's pixel grid
+        let layer_bounds = Dimensions::new(10.4, 0.0, 10.4, 12.0);
+        let layered = StackingContext {
+            node_id: 2,
+            bounds: layer_bounds,
+            display_list: DisplayList { items: vec![rect(10.4, 10.4)] },
+            clip_path: None,
+            mask_image: None,
+            mask_mode: MaskMode::Alpha,
+            reference_box: layer_bounds,
+        };
+
+        let mut cache = RasterCache::new(MemoryCoordinator::new(1024 * 1024));
+        let covered = |layer: &Layer, row: isize| -> Vec<isize> { (0..layer.width as isize).filter(|&x| layer.pixel(x, row)[3] > 0.0).collect() };
+        for scale in [1.0, 2.0] {
+            let (painted, _) = cache.paint_frame(&[page.clone(), layered.clone()], scale);
+            let row = (5.0 * scale) as isize;
+            let (boxes, line) = (snapping::snap(31.2 * scale), snapping::snap(40.25 * scale));
+            let expected: Vec<isize> = (0..boxes).chain(line..line + scale as isize).collect();
+            assert_eq!(covered(&painted[0].layer, row), expected, "at {}x", scale);
+            // The top edge at 0.5px is on row 1 at both scales
+            assert_eq!(painted[0].layer.pixel(0, 0)[3], 0.0);
+            assert!(painted[0].layer.pixel(0, 1)[3] > 0.0);
+
+            let (left, right) = (snapping::snap(10.4 * scale), snapping::snap(20.8 * scale));
+            assert_eq!(covered(&painted[1].layer, row), (0..right - left).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_clip_paths_and_masks_change_the_raster() {
+        let mut cache = RasterCache::new(MemoryCoordinator::new(1024 * 1024));
+        let mut clipped = context(1, "red");
+        clipped.clip_path = layout::masking::parse_clip_path("polygon(0 0, 50% 0, 50% 100%, 0 100%)").unwrap();
+        let (painted, _) = cache.paint_frame(&[clipped.clone()], 1.0);
+        assert_eq!(painted[0].layer.pixel(10, 10), [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(painted[0].layer.pixel(60, 10), [0.0; 4]);
+        assert_ne!(context_hash(&clipped), context_hash(&context(1, "red")));
+    }
+
+    #[test]
+    fn test_layout_splits_into_stacking_contexts() {
+        let doc = Document::new();
+        let body = doc.create_element("body");
+        let header = doc.create_element("div");
+        header.append_child(&doc.create_text_node("Header"));
+        body.append_child(&header);
+        let spinner = doc.create_element("div");
+        spinner.set_attribute("class", "spinner");
+        spinner.append_child(&doc.create_text_node("Loading"));
+        body.append_child(&spinner);
+        doc.root.append_child(&body);
+
+        let css = ".spinner { will-change: transform; background-color: red }";
+        let stylesheet = CSSParser::new(css.to_string()).parse_stylesheet().unwrap();
+        let engine = LayoutEngine::new(stylesheet);
+        let layout = engine.layout_document(&doc);
+        let contexts = stacking_contexts(&layout);
+
+        assert_eq!(contexts.len(), 2);
+        assert_eq!(contexts[0].display_list.text_runs(), vec!["Header"]);
+        assert_eq!(contexts[1].node_id, spinner.id);
+        assert_eq!(contexts[1].display_list.text_runs(), vec!["Loading"]);
+    }
+}
@@ -0,0 +1,294 @@
+//! # Reftests
+//!
+//! This module runs layout reftests in the convention of the web platform
+//! tests: a test page names a reference page with `<link rel=match>` (or
+//! `<link rel=mismatch>`), both are painted offscreen, and the test passes
+//! when the pixels agree. The reference reaches the expected rendering by
+//! simpler means, so a layout change that breaks the test's feature shows
+//! up as a pixel difference.
+//!
+//! ## Design Principles
+//!
+//! 1. **Same Paint Path as Snapshots**: Pages are painted by
+//!    `snapshot::render_page` with deterministic text, so a reftest result
+//!    is the same on every machine. Suites can be run at any device scale
+//!    factor, so pixel snapping is tested at 1x and 2x alike.
+//! 2. **Explicit Fuzz**: Pixels must match exactly unless the test declares
+//!    a tolerance with `<meta name=fuzzy content="maxDifference=..;totalPixels=..">`.
+//! 3. **Self-Describing Suites**: A suite is a directory of pages; every
+//!    page with a `match` or `mismatch` link is a test, and every other
+//!    page is a reference.
+
+use crate::filters::Layer;
+use crate::snapshot::render_page;
+use css_parser::CSSParser;
+use dom::{Document, Node, NodeType};
+use layout::{Dimensions, LayoutEngine};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use thiserror::Error;
+
+/// Viewport tests are painted in, in CSS pixels
+pub const REFTEST_VIEWPORT: Dimensions = Dimensions { x: 0.0, y: 0.0, width: 800.0, height: 600.0 };
+
+/// Styles every page starts from: metadata elements are laid out like any
+/// other by the engine's default styles, so their text would be painted
+const REFTEST_STYLESHEET: &str = "head { display: none } title { display: none } style { display: none } \
+    link { display: none } meta { display: none } script { display: none }\n";
+
+/// Errors running a reftest
+#[derive(Error, Debug)]
+pub enum ReftestError {
+    #[error("Failed to read {path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error("Failed to parse {path}: {message}")]
+    Parse { path: PathBuf, message: String },
+
+    #[error("{0} has no <link rel=match> or <link rel=mismatch>")]
+    NoReference(PathBuf),
+}
+
+/// Whether a test must look like its reference or unlike it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReftestKind {
+    Match,
+    Mismatch,
+}
+
+/// How far a rendering may be from its reference and still match
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Tolerance {
+    /// Largest difference of any channel of a pixel, out of 255
+    pub max_difference: u8,
+    /// Most pixels that may differ
+    pub total_pixels: usize,
+}
+
+impl Tolerance {
+    /// Parse the content of a `fuzzy` meta element
+    ///
+    /// Both `maxDifference=2;totalPixels=10` and ranges such as
+    /// `maxDifference=0-2` are accepted; a range allows up to its upper
+    /// bound.
+    pub fn parse(content: &str) -> Option<Self> {
+        let mut tolerance = Tolerance::default();
+        for part in content.split(';').map(str::trim).filter(|part| !part.is_empty()) {
+            let (name, value) = part.split_once('=')?;
+            let upper = value.rsplit('-').next()?.trim();
+            match name.trim() {
+                "maxDifference" => tolerance.max_difference = upper.parse().ok()?,
+                "totalPixels" => tolerance.total_pixels = upper.parse().ok()?,
+                _ => return None,
+            }
+        }
+        Some(tolerance)
+    }
+}
+
+/// How two renderings differ
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImageDifference {
+    /// Pixels with any channel differing
+    pub differing_pixels: usize,
+    /// Largest difference of a channel, out of 255
+    pub max_difference: u8,
+}
+
+impl ImageDifference {
+    /// Check whether the difference is within a tolerance
+    pub fn is_within(&self, tolerance: &Tolerance) -> bool {
+        self.differing_pixels == 0
+            || (self.max_difference <= tolerance.max_difference && self.differing_pixels <= tolerance.total_pixels)
+    }
+}
+
+/// Compare two renderings pixel by pixel
+///
+/// Renderings of different sizes differ in every pixel.
+pub fn compare(a: &Layer, b: &Layer) -> ImageDifference {
+    if (a.width, a.height) != (b.width, b.height) {
+        return ImageDifference { differing_pixels: a.pixels.len().max(b.pixels.len()), max_difference: u8::MAX };
+    }
+    let mut difference = ImageDifference::default();
+    for (left, right) in a.pixels.iter().zip(&b.pixels) {
+        let channel = left
+            .iter()
+            .zip(right)
+            .map(|(l, r)| ((l - r).abs().min(1.0) * 255.0).round() as u8)
+            .max()
+            .unwrap_or(0);
+        if channel > 0 {
+            difference.differing_pixels += 1;
+            difference.max_difference = difference.max_difference.max(channel);
+        }
+    }
+    difference
+}
+
+/// Get the harness styles followed by the text of the `<style>` elements
+/// of a document, in order
+fn inline_styles(document: &Document) -> String {
+    fn collect(node: &Rc<Node>, css: &mut String) {
+        if let NodeType::Element { tag_name, .. } = &node.node_type {
+            if tag_name == "style" {
+                css.push_str(&node.text_content());
+                css.push('\n');
+                return;
+            }
+        }
+        for child in node.children.borrow().iter() {
+            collect(child, css);
+        }
+    }
+    let mut css = REFTEST_STYLESHEET.to_string();
+    collect(&document.root, &mut css);
+    css
+}
+
+/// Find the first element with a tag name
+fn find_element(node: &Rc<Node>, tag: &str, matches: &dyn Fn(&Rc<Node>) -> bool) -> Option<Rc<Node>> {
+    if node.tag_name() == Some(tag) && matches(node) {
+        return Some(Rc::clone(node));
+    }
+    node.children.borrow().iter().find_map(|child| find_element(child, tag, matches))
+}
+
+/// Paint a page offscreen, styled by its `<style>` elements
+pub fn render_html(html: &str, viewport: &Dimensions) -> Result<Layer, String> {
+    let (document, _) = html_parser::parse_html_string(html).map_err(|e| format!("{:?}", e))?;
+    render_document(&document, viewport, 1.0)
+}
+
+fn render_document(document: &Document, viewport: &Dimensions, scale: f32) -> Result<Layer, String> {
+    let stylesheet = CSSParser::new(inline_styles(document)).parse_stylesheet().map_err(|e| format!("{:?}", e))?;
+    let layout = LayoutEngine::new(stylesheet).layout_document(document);
+    Ok(render_page(&layout, viewport, scale))
+}
+
+/// The result of one reftest
+#[derive(Debug, Clone)]
+pub struct ReftestOutcome {
+    pub test: PathBuf,
+    pub reference: PathBuf,
+    pub kind: ReftestKind,
+    pub tolerance: Tolerance,
+    pub difference: ImageDifference,
+}
+
+impl ReftestOutcome {
+    pub fn passed(&self) -> bool {
+        match self.kind {
+            ReftestKind::Match => self.difference.is_within(&self.tolerance),
+            ReftestKind::Mismatch => !self.difference.is_within(&self.tolerance),
+        }
+    }
+}
+
+/// A parsed test or reference page
+struct Page {
+    document: Document,
+    /// The reference, if the page is a test
+    reference: Option<(ReftestKind, String)>,
+    tolerance: Tolerance,
+}
+
+fn read_page(path: &Path) -> Result<Page, ReftestError> {
+    let html = fs::read_to_string(path).map_err(|source| ReftestError::Io { path: path.to_path_buf(), source })?;
+    let parse_error = |message: String| ReftestError::Parse { path: path.to_path_buf(), message };
+    let (document, _) = html_parser::parse_html_string(&html).map_err(|e| parse_error(format!("{:?}", e)))?;
+
+    let rel = |node: &Rc<Node>| node.get_attribute("rel").unwrap_or_default().to_ascii_lowercase();
+    let link = find_element(&document.root, "link", &|node| matches!(rel(node).as_str(), "match" | "mismatch"));
+    let reference = link.and_then(|link| {
+        let kind = if rel(&link) == "match" { ReftestKind::Match } else { ReftestKind::Mismatch };
+        Some((kind, link.get_attribute("href")?))
+    });
+    let fuzzy = find_element(&document.root, "meta", &|node| node.get_attribute("name").as_deref() == Some("fuzzy"));
+    let tolerance = match fuzzy.and_then(|meta| meta.get_attribute("content")) {
+        Some(content) => Tolerance::parse(&content).ok_or_else(|| parse_error(format!("invalid fuzzy annotation '{}'", content)))?,
+        None => Tolerance::default(),
+    };
+    Ok(Page { document, reference, tolerance })
+}
+
+/// Run the test at `path` against the reference it links to
+pub fn run_reftest(path: &Path) -> Result<ReftestOutcome, ReftestError> {
+    run_reftest_at(path, 1.0)
+}
+
+/// Run the test at `path` with both pages painted at a device scale
+/// factor
+pub fn run_reftest_at(path: &Path, scale: f32) -> Result<ReftestOutcome, ReftestError> {
+    let test = read_page(path)?;
+    let (kind, href) = test.reference.clone().ok_or_else(|| ReftestError::NoReference(path.to_path_buf()))?;
+    let reference_path = path.parent().unwrap_or(Path::new(".")).join(href);
+    let reference = read_page(&reference_path)?;
+
+    let render = |page: &Page, path: &Path| {
+        render_document(&page.document, &REFTEST_VIEWPORT, scale).map_err(|message| ReftestError::Parse { path: path.to_path_buf(), message })
+    };
+    let difference = compare(&render(&test, path)?, &render(&reference, &reference_path)?);
+    Ok(ReftestOutcome { test: path.to_path_buf(), reference: reference_path, kind, tolerance: test.tolerance, difference })
+}
+
+/// Run every test in a directory, in file name order
+pub fn run_suite(directory: &Path) -> Result<Vec<ReftestOutcome>, ReftestError> {
+    run_suite_at(directory, 1.0)
+}
+
+/// Run every test in a directory at a device scale factor
+pub fn run_suite_at(directory: &Path, scale: f32) -> Result<Vec<ReftestOutcome>, ReftestError> {
+    let io_error = |source| ReftestError::Io { path: directory.to_path_buf(), source };
+    let mut paths: Vec<PathBuf> = fs::read_dir(directory)
+        .map_err(io_error)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "html"))
+        .collect();
+    paths.sort();
+
+    let mut outcomes = Vec::new();
+    for path in paths {
+        if read_page(&path)?.reference.is_some() {
+            outcomes.push(run_reftest_at(&path, scale)?);
+        }
+    }
+    Ok(outcomes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_reftest_suite() {
+        assert_eq!(Tolerance::parse("maxDifference=0-2;totalPixels=300"), Some(Tolerance { max_difference: 2, total_pixels: 300 }));
+        assert_eq!(Tolerance::parse("maxDiff=2"), None);
+        let red = render_html("<style>body { margin: 0 } div { height: 4px; background-color: #ff0000 }</style><div></div>", &Dimensions::new(0.0, 0.0, 4.0, 4.0)).unwrap();
+        let white = render_html("<p></p>", &Dimensions::new(0.0, 0.0, 4.0, 4.0)).unwrap();
+        assert_eq!(compare(&red, &red), ImageDifference::default());
+        assert_eq!(compare(&red, &white).differing_pixels, 16);
+
+        let suite = Path::new(env!("CARGO_MANIFEST_DIR")).join("reftests");
+        assert!(run_suite(&suite).unwrap().len() >= 8);
+        // Blank pages would match each other whatever the layout did, so
+        // every test and reference must paint something
+        for entry in fs::read_dir(&suite).unwrap() {
+            let path = entry.unwrap().path();
+            let rendering = render_document(&read_page(&path).unwrap().document, &REFTEST_VIEWPORT, 1.0).unwrap();
+            let background = rendering.pixels[0];
+            assert!(rendering.pixels.iter().any(|pixel| *pixel != background), "{} renders blank", path.display());
+        }
+        for scale in [1.0, 2.0] {
+            let failures: Vec<String> = run_suite_at(&suite, scale)
+                .unwrap()
+                .iter()
+                .filter(|outcome| !outcome.passed())
+                .map(|outcome| format!("{} at {}x ({:?})", outcome.test.display(), scale, outcome.difference))
+                .collect();
+            assert!(failures.is_empty(), "failing reftests: {:?}", failures);
+        }
+    }
+}
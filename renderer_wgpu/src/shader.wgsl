@@ -22,3 +22,18 @@ fn vs_main(model: VertexInput) -> VertexOutput {
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     return vec4<f32>(in.color, 1.0);
 }
+
+// Decode an sRGB channel to linear light
+fn srgb_to_linear(channel: f32) -> f32 {
+    if channel <= 0.04045 {
+        return channel / 12.92;
+    }
+    return pow((channel + 0.055) / 1.055, 2.4);
+}
+
+// Fragment shader for sRGB targets, which encode the output again
+@fragment
+fn fs_srgb(in: VertexOutput) -> @location(0) vec4<f32> {
+    let linear = vec3<f32>(srgb_to_linear(in.color.r), srgb_to_linear(in.color.g), srgb_to_linear(in.color.b));
+    return vec4<f32>(linear, 1.0);
+}
@@ -0,0 +1,105 @@
+//! # Pixel Snapping
+//!
+//! This module decides which device pixels a fractional page rectangle
+//! covers when it is painted. Layout stays in fractional CSS pixels;
+//! snapping happens only at paint time, once the device scale is known.
+//!
+//! ## Design Principles
+//!
+//! 1. **Snap Edges, Not Sizes**: Each edge is rounded to the nearest
+//!    device pixel on its own, and the size is what lies between them. Two
+//!    boxes sharing an edge share its pixel, so adjacent boxes never leave
+//!    a seam or overlap, and a 1px line is one device pixel at 1x and two
+//!    at 2x wherever it falls.
+//!
+//! 2. **One Grid for the Page**: Layer origins are snapped with the same
+//!    rule as the items inside them, and items are snapped in page
+//!    coordinates before the origin is subtracted. Content painted in a
+//!    layer of its own lands on the same pixels as it would in its
+//!    parent's.
+//!
+//! 3. **Glyphs Keep Their Subpixel Offset**: A glyph's baseline is snapped
+//!    like an edge, but its horizontal position keeps its fraction, which
+//!    the glyph atlas rasterizes as a subpixel offset, so text spacing
+//!    stays even.
+
+use layout::Dimensions;
+
+/// Snap a coordinate in device pixels to the nearest pixel edge
+///
+/// Halves round towards positive infinity, whatever the sign, so the
+/// result only depends on where the edge is on the page.
+pub fn snap(value: f32) -> isize {
+    (value + 0.5).floor() as isize
+}
+
+/// A rectangle in whole device pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceRect {
+    pub x: isize,
+    pub y: isize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl DeviceRect {
+    /// Snap a rectangle in device pixels by its edges
+    ///
+    /// A rectangle whose edges snap to the same pixel covers nothing.
+    pub fn snap(x: f32, y: f32, width: f32, height: f32) -> Self {
+        let (left, top) = (snap(x), snap(y));
+        let (right, bottom) = (snap(x + width.max(0.0)), snap(y + height.max(0.0)));
+        DeviceRect { x: left, y: top, width: (right - left) as usize, height: (bottom - top) as usize }
+    }
+
+    /// Get the device pixels a page rectangle covers at a scale
+    pub fn from_page(rect: &Dimensions, scale: f32) -> Self {
+        DeviceRect::snap(rect.x * scale, rect.y * scale, rect.width * scale, rect.height * scale)
+    }
+
+    /// Move the rectangle by whole pixels
+    pub fn offset(&self, dx: isize, dy: isize) -> Self {
+        DeviceRect { x: self.x + dx, y: self.y + dy, ..*self }
+    }
+
+    /// Get the part at non-negative coordinates, as unsigned pixels
+    pub fn clamped(&self) -> (usize, usize, usize, usize) {
+        let width = self.width.saturating_sub(self.x.min(0).unsigned_abs());
+        let height = self.height.saturating_sub(self.y.min(0).unsigned_abs());
+        (self.x.max(0) as usize, self.y.max(0) as usize, width, height)
+    }
+}
+
+/// Get the device pixel a glyph is drawn at and the subpixel fraction
+/// of its horizontal position, for a pen position in device pixels
+pub fn glyph_origin(x: f32, y: f32) -> (isize, isize, f32) {
+    (x.floor() as isize, snap(y), x - x.floor())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edges_snap_without_seams_or_double_lines() {
+        // Three boxes 10.4px tall share their edges
+        let boxes: Vec<DeviceRect> = (0..3).map(|index| DeviceRect::snap(0.0, index as f32 * 10.4, 5.0, 10.4)).collect();
+        assert_eq!(boxes.iter().map(|rect| (rect.y, rect.height)).collect::<Vec<_>>(), vec![(0, 10), (10, 11), (21, 10)]);
+        for pair in boxes.windows(2) {
+            assert_eq!(pair[0].y + pair[0].height as isize, pair[1].y);
+        }
+
+        // A 1px line covers one device pixel at 1x and two at 2x, wherever
+        // it falls
+        for top in [10.0, 10.25, 10.5, 10.75] {
+            let line = Dimensions::new(0.0, top, 100.0, 1.0);
+            assert_eq!(DeviceRect::from_page(&line, 1.0).height, 1);
+            assert_eq!(DeviceRect::from_page(&line, 2.0).height, 2);
+        }
+        assert_eq!(DeviceRect::from_page(&Dimensions::new(0.0, 10.5, 1.0, 0.5), 1.0).height, 0);
+
+        assert_eq!((snap(-0.5), snap(0.5), snap(1.49)), (0, 1, 1));
+        assert_eq!(DeviceRect::snap(-2.0, 3.0, 5.0, 1.0).clamped(), (0, 3, 3, 1));
+        assert_eq!(glyph_origin(3.25, 7.5), (3, 8, 0.25));
+    }
+}
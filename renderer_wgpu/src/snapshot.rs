@@ -0,0 +1,239 @@
+//! # Page Snapshots
+//!
+//! This module paints a laid-out page on the CPU and encodes the pixels
+//! as PNG, for screenshots taken without a window or a GPU, such as those
+//! requested over the remote debugging protocol. Besides the viewport, a
+//! snapshot can cover a region, one element's border box, or the whole
+//! page down to its full scroll height.
+//!
+//! ## Design Principles
+//!
+//! 1. **Same Paint Path**: Snapshots are painted through the raster cache's
+//!    stacking contexts and composited with the blending pass, so they
+//!    show what the compositor shows.
+//! 2. **Deterministic**: Text is rasterized grayscale and unhinted, so a
+//!    snapshot of a page is byte for byte the same on every machine.
+//! 3. **Full Pages in Strips**: A full-page capture is composited one
+//!    viewport-high strip at a time, as an offscreen target of bounded
+//!    size would be, and the strips are stitched together. The page is
+//!    painted once and shared by all strips.
+//! 4. **Dependency Free PNG**: Pixels are written in stored (uncompressed)
+//!    deflate blocks. Files are larger than they could be, but the encoder
+//!    is a page of code and needs no compression crate.
+
+use crate::blending::composite_layer;
+use crate::filters::Layer;
+use crate::memory::MemoryCoordinator;
+use crate::raster_cache::{stacking_contexts, PaintedContext, RasterCache};
+use crate::snapping::{snap, DeviceRect};
+use crate::text_quality::TextRenderSettings;
+use layout::{BlendMode, Dimensions, LayoutBox};
+
+/// Largest snapshot side, in device pixels
+pub const MAX_SNAPSHOT_SIDE: usize = 8192;
+
+/// Largest height of a full-page capture, in device pixels
+pub const MAX_FULL_PAGE_HEIGHT: usize = 4 * MAX_SNAPSHOT_SIDE;
+
+/// Largest payload of a stored deflate block
+const STORED_BLOCK_BYTES: usize = 65_535;
+
+/// Paint the part of a page inside `viewport` at a device scale factor,
+/// over a white canvas
+pub fn render_page(root: &LayoutBox, viewport: &Dimensions, scale: f32) -> Layer {
+    composite_region(&paint(root, scale), viewport, scale)
+}
+
+/// Paint the border box of the element with id `node_id`
+///
+/// Returns `None` if the element has no box in the layout, or an empty one.
+pub fn render_element(root: &LayoutBox, node_id: u64, scale: f32) -> Option<Layer> {
+    let border = layout::geometry::border_boxes(root).remove(&node_id).filter(|border| border.width > 0.0 && border.height > 0.0)?;
+    Some(render_page(root, &border, scale))
+}
+
+/// Paint the whole page, `width` wide and down to its full scroll height,
+/// by stitching strips of `strip_height`
+pub fn render_full_page(root: &LayoutBox, width: f32, strip_height: f32, scale: f32) -> Layer {
+    let painted = paint(root, scale);
+    let page_height = root.margin.height.max(1.0);
+    let height = ((page_height * scale).ceil() as usize).min(MAX_FULL_PAGE_HEIGHT);
+    let strip_height = strip_height.clamp(1.0, MAX_SNAPSHOT_SIDE as f32 / scale);
+
+    let first = composite_region(&painted, &Dimensions::new(0.0, 0.0, width, strip_height), scale);
+    let mut page = Layer::new(first.width, height.max(1));
+    let mut top = 0.0;
+    let mut strip = first;
+    loop {
+        composite_layer(&mut page, &strip, 0, snap(top * scale), BlendMode::Normal);
+        top += strip_height;
+        if snap(top * scale) as usize >= page.height {
+            break;
+        }
+        strip = composite_region(&painted, &Dimensions::new(0.0, top, width, strip_height), scale);
+    }
+    page
+}
+
+/// Paint every stacking context of a page once, for compositing regions
+fn paint(root: &LayoutBox, scale: f32) -> Vec<PaintedContext> {
+    let mut cache = RasterCache::new(MemoryCoordinator::default());
+    cache.set_text_rendering(TextRenderSettings::deterministic());
+    cache.paint_frame(&stacking_contexts(root), scale).0
+}
+
+/// Composite the painted contexts inside `viewport` over a white canvas
+fn composite_region(painted: &[PaintedContext], viewport: &Dimensions, scale: f32) -> Layer {
+    let width = ((viewport.width * scale).ceil().max(1.0) as usize).min(MAX_SNAPSHOT_SIDE);
+    let height = ((viewport.height * scale).ceil().max(1.0) as usize).min(MAX_SNAPSHOT_SIDE);
+    let mut page = Layer::new(width, height);
+    page.fill_rect(0, 0, width, height, [1.0, 1.0, 1.0, 1.0]);
+    // Layers start at their snapped origin, see `snapping`
+    let (left, top) = (snap(viewport.x * scale), snap(viewport.y * scale));
+    for context in painted {
+        let origin = DeviceRect::from_page(&context.bounds, scale);
+        composite_layer(&mut page, &context.layer, origin.x - left, origin.y - top, BlendMode::Normal);
+    }
+    page
+}
+
+/// Encode a layer as an 8-bit RGBA PNG
+pub fn encode_png(layer: &Layer) -> Vec<u8> {
+    // Scanlines, each behind filter type 0 (none)
+    let mut raw = Vec::with_capacity(layer.height * (layer.width * 4 + 1));
+    for row in layer.pixels.chunks(layer.width.max(1)).take(layer.height) {
+        raw.push(0);
+        for &[r, g, b, a] in row {
+            let straight = if a > 0.0 { [r / a, g / a, b / a, a] } else { [0.0; 4] };
+            raw.extend(straight.iter().map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8));
+        }
+    }
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    let mut header = Vec::with_capacity(13);
+    header.extend((layer.width as u32).to_be_bytes());
+    header.extend((layer.height as u32).to_be_bytes());
+    // 8 bits per channel, RGBA, deflate, adaptive filters, no interlace
+    header.extend([8, 6, 0, 0, 0]);
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend(kind);
+    png.extend(data);
+    let crc = crc32(&png[start..]);
+    png.extend(crc.to_be_bytes());
+}
+
+/// Wrap bytes in a zlib stream of stored deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(STORED_BLOCK_BYTES).peekable();
+    if blocks.peek().is_none() {
+        out.extend([1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        out.push(blocks.peek().is_none() as u8);
+        let length = block.len() as u16;
+        out.extend(length.to_le_bytes());
+        out.extend((!length).to_le_bytes());
+        out.extend(block);
+    }
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65_521;
+        b = (b + a) % 65_521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use css_parser::CSSParser;
+    use dom::Document;
+    use layout::LayoutEngine;
+
+    #[test]
+    fn test_snapshot_paints_and_encodes() {
+        let doc = Document::new();
+        let body = doc.create_element("body");
+        let banner = doc.create_element("div");
+        banner.append_child(&doc.create_text_node("Hi"));
+        body.append_child(&banner);
+        doc.root.append_child(&body);
+
+        let css = "body { margin: 0 } div { background-color: #ff0000 }";
+        let stylesheet = CSSParser::new(css.to_string()).parse_stylesheet().unwrap();
+        let engine = LayoutEngine::new(stylesheet);
+        let layout = engine.layout_document(&doc);
+        let viewport = Dimensions::new(0.0, 0.0, 40.0, 120.0);
+        let page = render_page(&layout, &viewport, 1.0);
+
+        assert_eq!((page.width, page.height), (40, 120));
+        // The banner's background, then the white canvas below it
+        assert_eq!(page.pixel(39, 5), [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(page.pixel(39, 110), [1.0, 1.0, 1.0, 1.0]);
+
+        let png = encode_png(&page);
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(u32::from_be_bytes([png[16], png[17], png[18], png[19]]), 40);
+        assert_eq!(&png[png.len() - 8..png.len() - 4], b"IEND");
+        // Deterministic: the same page encodes to the same bytes
+        assert_eq!(encode_png(&render_page(&layout, &viewport, 1.0)), png);
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+    }
+
+    #[test]
+    fn test_element_and_full_page_capture() {
+        let doc = Document::new();
+        let body = doc.create_element("body");
+        let header = doc.create_element("div");
+        let main = doc.create_element("main");
+        body.append_child(&header);
+        body.append_child(&main);
+        doc.root.append_child(&body);
+
+        let css = "body { margin: 0 } div { height: 50px; background-color: #ff0000 } main { height: 250px }";
+        let stylesheet = CSSParser::new(css.to_string()).parse_stylesheet().unwrap();
+        let engine = LayoutEngine::new(stylesheet);
+        let layout = engine.layout_document(&doc);
+
+        // The header's border box, resolved from the layout
+        let element = render_element(&layout, header.id, 1.0).unwrap();
+        assert_eq!((element.width, element.height), (layout.margin.width as usize, 50));
+        assert_eq!(element.pixel(0, 0), [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(element.pixel(0, 49), [1.0, 0.0, 0.0, 1.0]);
+        assert!(render_element(&layout, doc.create_element("span").id, 1.0).is_none());
+
+        // Strips of 100px stitched down to the full scroll height
+        let page = render_full_page(&layout, 40.0, 100.0, 1.0);
+        assert_eq!((page.width, page.height), (40, layout.margin.height.ceil() as usize));
+        assert_eq!(page.pixel(10, 10), [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(page.pixel(10, page.height as isize - 1), [1.0, 1.0, 1.0, 1.0]);
+        let whole = render_page(&layout, &Dimensions::new(0.0, 0.0, 40.0, page.height as f32), 1.0);
+        assert_eq!(page.pixels, whole.pixels);
+    }
+}
@@ -0,0 +1,369 @@
+//! # Text Rendering Quality
+//!
+//! This module holds the settings glyphs are rasterized with (grayscale
+//! or subpixel antialiasing, the hinting level and gamma), and the glyph
+//! atlas that caches rasterized glyphs under those settings.
+//!
+//! ## Design Principles
+//!
+//! 1. **Settings Are Part Of The Key**: Every atlas entry is keyed on the
+//!    settings it was rasterized with, so switching a preference never
+//!    shows a glyph rasterized under the old one.
+//! 2. **Deterministic Tests**: `DUBBY_DETERMINISTIC_TEXT` forces grayscale,
+//!    unhinted, linear text, so screenshots do not depend on the display
+//!    the tests run on.
+//! 3. **Coverage Then Gamma**: Glyphs are rasterized to linear coverage
+//!    first and gamma is applied last, the same order for both modes.
+
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Environment variable forcing deterministic text rendering (`1`/`0`)
+pub const DETERMINISTIC_TEXT_ENV: &str = "DUBBY_DETERMINISTIC_TEXT";
+
+/// Horizontal subpixel positions a glyph is rasterized at
+pub const SUBPIXEL_POSITIONS: u8 = 4;
+
+/// Order of the color stripes of an LCD panel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SubpixelOrder {
+    Rgb,
+    Bgr,
+}
+
+/// How glyph edges are antialiased
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Antialiasing {
+    /// One coverage value per pixel
+    Grayscale,
+    /// One coverage value per color stripe
+    Subpixel(SubpixelOrder),
+}
+
+impl Antialiasing {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "grayscale" | "gray" => Some(Antialiasing::Grayscale),
+            "subpixel" | "rgb" => Some(Antialiasing::Subpixel(SubpixelOrder::Rgb)),
+            "bgr" => Some(Antialiasing::Subpixel(SubpixelOrder::Bgr)),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Antialiasing::Grayscale => "grayscale",
+            Antialiasing::Subpixel(SubpixelOrder::Rgb) => "subpixel",
+            Antialiasing::Subpixel(SubpixelOrder::Bgr) => "bgr",
+        }
+    }
+
+    /// Bytes per pixel of a glyph bitmap
+    pub fn channels(&self) -> usize {
+        match self {
+            Antialiasing::Grayscale => 1,
+            Antialiasing::Subpixel(_) => 3,
+        }
+    }
+}
+
+/// How strongly glyph outlines are fitted to the pixel grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Hinting {
+    None,
+    Slight,
+    Medium,
+    Full,
+}
+
+impl Hinting {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "none" => Some(Hinting::None),
+            "slight" => Some(Hinting::Slight),
+            "medium" => Some(Hinting::Medium),
+            "full" => Some(Hinting::Full),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Hinting::None => "none",
+            Hinting::Slight => "slight",
+            Hinting::Medium => "medium",
+            Hinting::Full => "full",
+        }
+    }
+
+    /// How far an edge moves toward the nearest pixel boundary, 0 to 1
+    fn strength(&self) -> f32 {
+        match self {
+            Hinting::None => 0.0,
+            Hinting::Slight => 0.33,
+            Hinting::Medium => 0.66,
+            Hinting::Full => 1.0,
+        }
+    }
+}
+
+/// Errors from changing a text rendering setting
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum TextSettingError {
+    #[error("Unknown text rendering setting: {0}")]
+    UnknownSetting(String),
+
+    #[error("Invalid value for {setting}: {value}")]
+    InvalidValue { setting: String, value: String },
+}
+
+/// The settings glyphs are rasterized with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextRenderSettings {
+    pub antialiasing: Antialiasing,
+    pub hinting: Hinting,
+    /// Gamma coverage is corrected for, from 1.0 (linear) to 3.0
+    pub gamma: f32,
+}
+
+impl Default for TextRenderSettings {
+    fn default() -> Self {
+        TextRenderSettings {
+            antialiasing: Antialiasing::Subpixel(SubpixelOrder::Rgb),
+            hinting: Hinting::Slight,
+            gamma: 1.8,
+        }
+    }
+}
+
+impl TextRenderSettings {
+    /// Grayscale, unhinted, linear text that rasterizes the same everywhere
+    pub fn deterministic() -> Self {
+        TextRenderSettings {
+            antialiasing: Antialiasing::Grayscale,
+            hinting: Hinting::None,
+            gamma: 1.0,
+        }
+    }
+
+    /// Get the default settings, or the deterministic ones when
+    /// `DUBBY_DETERMINISTIC_TEXT` is set
+    pub fn from_env() -> Self {
+        let deterministic = std::env::var(DETERMINISTIC_TEXT_ENV)
+            .map(|value| matches!(value.trim().to_ascii_lowercase().as_str(), "1" | "true" | "on" | "yes"))
+            .unwrap_or(false);
+        if deterministic {
+            Self::deterministic()
+        } else {
+            Self::default()
+        }
+    }
+
+    /// Change one setting by name: `antialiasing`, `hinting` or `gamma`
+    pub fn set(&mut self, setting: &str, value: &str) -> Result<(), TextSettingError> {
+        let invalid = || TextSettingError::InvalidValue { setting: setting.to_string(), value: value.to_string() };
+        match setting {
+            "antialiasing" | "aa" => self.antialiasing = Antialiasing::parse(value).ok_or_else(invalid)?,
+            "hinting" => self.hinting = Hinting::parse(value).ok_or_else(invalid)?,
+            "gamma" => {
+                let gamma: f32 = value.trim().parse().map_err(|_| invalid())?;
+                if !(1.0..=3.0).contains(&gamma) {
+                    return Err(invalid());
+                }
+                self.gamma = gamma;
+            }
+            _ => return Err(TextSettingError::UnknownSetting(setting.to_string())),
+        }
+        Ok(())
+    }
+
+    /// Get the part of a glyph key these settings contribute
+    pub fn key(&self) -> RasterKey {
+        RasterKey {
+            antialiasing: self.antialiasing,
+            hinting: self.hinting,
+            gamma_bits: self.gamma.to_bits(),
+        }
+    }
+}
+
+/// Rasterization settings in hashable form
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RasterKey {
+    pub antialiasing: Antialiasing,
+    pub hinting: Hinting,
+    pub gamma_bits: u32,
+}
+
+/// Identifies one rasterized glyph in the atlas
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GlyphKey {
+    pub ch: char,
+    pub size_bits: u32,
+    /// Horizontal subpixel position, in `1 / SUBPIXEL_POSITIONS` pixels
+    pub subpixel_x: u8,
+    pub raster: RasterKey,
+}
+
+impl GlyphKey {
+    pub fn new(ch: char, size: f32, x: f32, settings: &TextRenderSettings) -> Self {
+        let subpixel_x = ((x.fract().abs() * SUBPIXEL_POSITIONS as f32).round() as u8) % SUBPIXEL_POSITIONS;
+        GlyphKey { ch, size_bits: size.to_bits(), subpixel_x, raster: settings.key() }
+    }
+}
+
+/// A rasterized glyph, `channels` coverage bytes per pixel
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlyphBitmap {
+    pub width: usize,
+    pub height: usize,
+    pub channels: usize,
+    pub data: Vec<u8>,
+}
+
+impl GlyphBitmap {
+    /// Get the coverage of one channel of a pixel
+    pub fn coverage(&self, x: usize, y: usize, channel: usize) -> u8 {
+        self.data[(y * self.width + x) * self.channels + channel]
+    }
+}
+
+/// Rasterize a glyph
+///
+/// There are no outlines to load, so every glyph is drawn as a stem half
+/// an em wide, which is enough to exercise hinting, antialiasing and gamma
+/// the way real outlines would.
+pub fn rasterize(ch: char, size: f32, subpixel_x: u8, settings: &TextRenderSettings) -> GlyphBitmap {
+    let width = (size * 0.6).ceil().max(1.0) as usize;
+    let height = size.ceil().max(1.0) as usize;
+    let channels = settings.antialiasing.channels();
+    if ch.is_whitespace() {
+        return GlyphBitmap { width, height, channels, data: vec![0; width * height * channels] };
+    }
+
+    // Stem edges in pixels, moved toward the pixel grid by hinting
+    let offset = subpixel_x as f32 / SUBPIXEL_POSITIONS as f32;
+    let hint = |edge: f32| edge + (edge.round() - edge) * settings.hinting.strength();
+    let left = hint(size * 0.05 + offset);
+    let right = hint(size * 0.55 + offset).max(left);
+    let top = hint(size * 0.2);
+    let bottom = hint(size * 0.9).max(top);
+
+    // Coverage of [from, to) by the stem's span [low, high)
+    let overlap = |from: f32, to: f32, low: f32, high: f32| ((to.min(high) - from.max(low)).max(0.0) / (to - from)).min(1.0);
+    let gamma = |coverage: f32| (coverage.powf(1.0 / settings.gamma) * 255.0).round() as u8;
+
+    let mut data = Vec::with_capacity(width * height * channels);
+    for y in 0..height {
+        let vertical = overlap(y as f32, y as f32 + 1.0, top, bottom);
+        for x in 0..width {
+            let x = x as f32;
+            match settings.antialiasing {
+                Antialiasing::Grayscale => data.push(gamma(overlap(x, x + 1.0, left, right) * vertical)),
+                Antialiasing::Subpixel(order) => {
+                    // Thirds of the pixel and one on each side, filtered
+                    // (1, 2, 1) to keep color fringes down
+                    let third = |index: i32| {
+                        let from = x + index as f32 / 3.0;
+                        overlap(from, from + 1.0 / 3.0, left, right)
+                    };
+                    let mut stripes = [0.0; 3];
+                    for (index, stripe) in stripes.iter_mut().enumerate() {
+                        let index = index as i32;
+                        *stripe = (third(index - 1) + 2.0 * third(index) + third(index + 1)) / 4.0;
+                    }
+                    if order == SubpixelOrder::Bgr {
+                        stripes.reverse();
+                    }
+                    data.extend(stripes.iter().map(|stripe| gamma(stripe * vertical)));
+                }
+            }
+        }
+    }
+    GlyphBitmap { width, height, channels, data }
+}
+
+/// Cache of rasterized glyphs
+#[derive(Debug, Default)]
+pub struct GlyphAtlas {
+    glyphs: HashMap<GlyphKey, GlyphBitmap>,
+    hits: usize,
+    misses: usize,
+}
+
+impl GlyphAtlas {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get a glyph, rasterizing it on first use under `settings`
+    pub fn glyph(&mut self, ch: char, size: f32, x: f32, settings: &TextRenderSettings) -> &GlyphBitmap {
+        let key = GlyphKey::new(ch, size, x, settings);
+        if self.glyphs.contains_key(&key) {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
+        self.glyphs.entry(key).or_insert_with(|| rasterize(ch, size, key.subpixel_x, settings))
+    }
+
+    /// Drop the glyphs not rasterized with `settings`
+    pub fn retain_settings(&mut self, settings: &TextRenderSettings) {
+        let raster = settings.key();
+        self.glyphs.retain(|key, _| key.raster == raster);
+    }
+
+    pub fn len(&self) -> usize {
+        self.glyphs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.glyphs.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.glyphs.clear();
+    }
+
+    /// Get the (hits, misses) of glyph lookups
+    pub fn stats(&self) -> (usize, usize) {
+        (self.hits, self.misses)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_atlas_is_keyed_on_settings() {
+        let mut atlas = GlyphAtlas::new();
+        let subpixel = TextRenderSettings::default();
+        let grayscale = TextRenderSettings::deterministic();
+
+        let colored = atlas.glyph('a', 16.0, 0.0, &subpixel).clone();
+        assert_eq!(colored.channels, 3);
+        let gray = atlas.glyph('a', 16.0, 0.0, &grayscale).clone();
+        assert_eq!(gray.channels, 1);
+        assert_eq!(atlas.len(), 2);
+
+        // Switching back reuses the first entry rather than the gray one
+        assert_eq!(atlas.glyph('a', 16.0, 0.0, &subpixel), &colored);
+        assert_eq!(atlas.stats(), (1, 2));
+        atlas.retain_settings(&grayscale);
+        assert_eq!(atlas.len(), 1);
+
+        // Deterministic text rasterizes identically every time
+        assert_eq!(rasterize('a', 16.0, 0, &grayscale), gray);
+
+        // Full hinting lands every stem edge on the pixel grid
+        let mut hinted = grayscale;
+        hinted.set("hinting", "full").unwrap();
+        let bitmap = rasterize('a', 13.0, 1, &hinted);
+        assert!(bitmap.data.iter().all(|&coverage| coverage == 0 || coverage == 255));
+        assert!(rasterize('a', 13.0, 1, &grayscale).data.iter().any(|&coverage| coverage != 0 && coverage != 255));
+
+        assert!(matches!(hinted.set("gamma", "5"), Err(TextSettingError::InvalidValue { .. })));
+        assert!(matches!(hinted.set("kerning", "on"), Err(TextSettingError::UnknownSetting(_))));
+    }
+}
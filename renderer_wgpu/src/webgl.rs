@@ -0,0 +1,217 @@
+//! # WebGL Surfaces
+//!
+//! This module gives each WebGL canvas a texture of its own and replays
+//! the commands its context recorded onto it. The compositor samples the
+//! texture like any other layer.
+//!
+//! ## Design Principles
+//!
+//! 1. **One Texture per Canvas**: A canvas draws into its own
+//!    `Rgba8Unorm` texture the size of its drawing buffer, never into the
+//!    page, so a page cannot paint outside its canvas.
+//! 2. **One Pipeline**: The context hands over triangle lists with a
+//!    color, so `webgl.wgsl` is a passthrough shader and every draw uses
+//!    the same pipeline.
+//! 3. **Replay in Order**: Clears and draws run as render passes in the
+//!    order the page issued them, within one submission per frame.
+
+use bytemuck::{Pod, Zeroable};
+use dom::webgl::WebGlCommand;
+use std::borrow::Cow;
+use wgpu::util::DeviceExt;
+
+/// Source of the WebGL replay shader
+pub const WEBGL_SHADER: &str = include_str!("webgl.wgsl");
+
+/// Format of canvas textures
+pub const CANVAS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+/// A vertex of a replayed draw
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Pod, Zeroable)]
+pub struct WebGlVertex {
+    pub position: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl WebGlVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4];
+
+    fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<WebGlVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// Get the vertices of a draw command
+pub fn draw_vertices(vertices: &[[f32; 2]], color: [f32; 4]) -> Vec<WebGlVertex> {
+    vertices.iter().map(|&position| WebGlVertex { position, color }).collect()
+}
+
+/// Convert a WebGL viewport, whose origin is the bottom left, to the
+/// top-left (x, y, width, height) wgpu expects, clipped to the canvas
+pub fn viewport_rect(x: i32, y: i32, width: u32, height: u32, canvas: (u32, u32)) -> Option<(f32, f32, f32, f32)> {
+    let (canvas_width, canvas_height) = (canvas.0 as i64, canvas.1 as i64);
+    let left = (x as i64).clamp(0, canvas_width);
+    let right = (x as i64 + width as i64).clamp(0, canvas_width);
+    let bottom = (y as i64).clamp(0, canvas_height);
+    let top = (y as i64 + height as i64).clamp(0, canvas_height);
+    (right > left && top > bottom).then(|| {
+        (left as f32, (canvas_height - top) as f32, (right - left) as f32, (top - bottom) as f32)
+    })
+}
+
+/// The texture of one WebGL canvas
+pub struct WebGlSurface {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    pipeline: wgpu::RenderPipeline,
+    size: (u32, u32),
+    viewport: Option<(f32, f32, f32, f32)>,
+}
+
+impl WebGlSurface {
+    /// Create the texture of a canvas with the given drawing buffer size
+    pub fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
+        let size = (width.max(1), height.max(1));
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("WebGL Canvas Texture"),
+            size: wgpu::Extent3d { width: size.0, height: size.1, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: CANVAS_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let pipeline = Self::create_pipeline(device);
+        WebGlSurface { texture, view, pipeline, size, viewport: None }
+    }
+
+    fn create_pipeline(device: &wgpu::Device) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("WebGL Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(WEBGL_SHADER)),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("WebGL Pipeline Layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("WebGL Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[WebGlVertex::layout()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: CANVAS_FORMAT,
+                    // WebGL leaves blending off by default
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// Replay recorded commands onto the texture
+    ///
+    /// Returns the number of render passes run.
+    pub fn replay(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, commands: Vec<WebGlCommand>) -> usize {
+        if commands.is_empty() {
+            return 0;
+        }
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("WebGL Replay Encoder") });
+        let mut passes = 0;
+        for command in commands {
+            match command {
+                WebGlCommand::Viewport { x, y, width, height } => {
+                    self.viewport = viewport_rect(x, y, width, height, self.size);
+                }
+                WebGlCommand::Clear { color } => {
+                    let [r, g, b, a] = color.map(f64::from);
+                    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("WebGL Clear Pass"),
+                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                            view: &self.view,
+                            resolve_target: None,
+                            ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color { r, g, b, a }), store: wgpu::StoreOp::Store },
+                        })],
+                        depth_stencil_attachment: None,
+                        timestamp_writes: None,
+                        occlusion_query_set: None,
+                    });
+                    passes += 1;
+                }
+                WebGlCommand::DrawTriangles { vertices, color } => {
+                    let vertices = draw_vertices(&vertices, color);
+                    let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some("WebGL Vertex Buffer"),
+                        contents: bytemuck::cast_slice(&vertices),
+                        usage: wgpu::BufferUsages::VERTEX,
+                    });
+                    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("WebGL Draw Pass"),
+                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                            view: &self.view,
+                            resolve_target: None,
+                            ops: wgpu::Operations { load: wgpu::LoadOp::Load, store: wgpu::StoreOp::Store },
+                        })],
+                        depth_stencil_attachment: None,
+                        timestamp_writes: None,
+                        occlusion_query_set: None,
+                    });
+                    if let Some((x, y, width, height)) = self.viewport {
+                        pass.set_viewport(x, y, width, height, 0.0, 1.0);
+                    }
+                    pass.set_pipeline(&self.pipeline);
+                    pass.set_vertex_buffer(0, buffer.slice(..));
+                    pass.draw(0..vertices.len() as u32, 0..1);
+                    passes += 1;
+                }
+            }
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+        passes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_webgl_shader_and_viewport() {
+        let module = naga::front::wgsl::parse_str(WEBGL_SHADER).unwrap();
+        assert!(module.entry_points.iter().any(|entry| entry.name == "vs_main"));
+        assert!(module.entry_points.iter().any(|entry| entry.name == "fs_main"));
+
+        // The bottom half of a 300x150 canvas starts 75 pixels down
+        assert_eq!(viewport_rect(0, 0, 300, 75, (300, 150)), Some((0.0, 75.0, 300.0, 75.0)));
+        assert_eq!(viewport_rect(-50, 100, 100, 100, (300, 150)), Some((0.0, 0.0, 50.0, 50.0)));
+        assert_eq!(viewport_rect(400, 0, 10, 10, (300, 150)), None);
+    }
+}